@@ -0,0 +1,33 @@
+package dev.truss.momentum
+
+import app.tauri.plugin.JSObject
+import com.google.firebase.messaging.FirebaseMessagingService
+import com.google.firebase.messaging.RemoteMessage
+
+// Receives FCM tokens and messages that arrive while the app is in the foreground;
+// MomentumPlugin forwards them to src/push.rs
+class MomentumMessagingService : FirebaseMessagingService() {
+    companion object {
+        @Volatile
+        var listener: ((JSObject) -> Unit)? = null
+    }
+
+    override fun onNewToken(token: String) {
+        listener?.invoke(JSObject().apply {
+            put("kind", "token")
+            put("token", token)
+        })
+    }
+
+    override fun onMessageReceived(message: RemoteMessage) {
+        val data = JSObject()
+        message.data.forEach { (key, value) -> data.put(key, value) }
+        listener?.invoke(JSObject().apply {
+            put("kind", "message")
+            put("title", message.notification?.title ?: message.data["title"])
+            put("body", message.notification?.body ?: message.data["body"])
+            put("data", data)
+            put("tapped", false)
+        })
+    }
+}
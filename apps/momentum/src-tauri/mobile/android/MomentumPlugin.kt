@@ -0,0 +1,341 @@
+package dev.truss.momentum
+
+import android.Manifest
+import android.app.Activity
+import android.content.Intent
+import android.content.pm.PackageManager
+import android.location.Geocoder
+import android.location.Location
+import android.location.LocationManager
+import android.net.Uri
+import android.os.Build
+import android.os.SystemClock
+import android.provider.MediaStore
+import android.provider.OpenableColumns
+import android.webkit.WebView
+import androidx.activity.result.ActivityResult
+import androidx.core.content.ContextCompat
+import androidx.core.content.FileProvider
+import androidx.core.location.LocationManagerCompat
+import app.tauri.annotation.ActivityCallback
+import app.tauri.annotation.Command
+import app.tauri.annotation.InvokeArg
+import app.tauri.annotation.Permission
+import app.tauri.annotation.TauriPlugin
+import app.tauri.plugin.Channel
+import app.tauri.plugin.Invoke
+import app.tauri.plugin.JSArray
+import app.tauri.plugin.JSObject
+import app.tauri.plugin.Plugin
+import com.google.firebase.messaging.FirebaseMessaging
+import java.io.File
+import java.util.Locale
+
+@InvokeArg
+class ShareFileArgs {
+    lateinit var path: String
+    lateinit var mime: String
+    var title: String? = null
+}
+
+@InvokeArg
+class ShareTargetArgs {
+    lateinit var inbox: String
+    lateinit var handler: Channel
+}
+
+@InvokeArg
+class LocationArgs {
+    var highAccuracy: Boolean = false
+    var maxAgeMs: Long = 0
+}
+
+@InvokeArg
+class PushListenArgs {
+    lateinit var handler: Channel
+}
+
+@InvokeArg
+class GeocodeArgs {
+    var latitude: Double = 0.0
+    var longitude: Double = 0.0
+}
+
+// Native side of src/mobile.rs
+@TauriPlugin(
+    permissions = [
+        Permission(
+            strings = [
+                Manifest.permission.ACCESS_FINE_LOCATION,
+                Manifest.permission.ACCESS_COARSE_LOCATION,
+            ],
+            alias = "location",
+        ),
+        Permission(strings = [Manifest.permission.POST_NOTIFICATIONS], alias = "notifications"),
+    ],
+)
+class MomentumPlugin(private val activity: Activity) : Plugin(activity) {
+    private var inbox: File? = null
+    private var shared: Channel? = null
+    private val pending = mutableListOf<Uri>()
+    private var photo: File? = null
+    private var push: Channel? = null
+    private val pendingPush = mutableListOf<JSObject>()
+
+    override fun load(webView: WebView) {
+        collect(activity.intent)
+        collectPush(activity.intent)
+        MomentumMessagingService.listener = { event -> activity.runOnUiThread { sendPush(event) } }
+    }
+
+    override fun onNewIntent(intent: Intent) {
+        collect(intent)
+        collectPush(intent)
+    }
+
+    @Command
+    fun shareFile(invoke: Invoke) {
+        val args = invoke.parseArgs(ShareFileArgs::class.java)
+        val send = Intent(Intent.ACTION_SEND).apply {
+            type = args.mime
+            putExtra(Intent.EXTRA_STREAM, fileUri(File(args.path)))
+            addFlags(Intent.FLAG_GRANT_READ_URI_PERMISSION)
+        }
+        activity.startActivity(Intent.createChooser(send, args.title))
+        invoke.resolve(JSObject())
+    }
+
+    @Command
+    fun takePhoto(invoke: Invoke) {
+        val file = File(activity.cacheDir, "camera/${System.currentTimeMillis()}.jpg")
+        file.parentFile?.mkdirs()
+        photo = file
+        val capture = Intent(MediaStore.ACTION_IMAGE_CAPTURE).apply {
+            putExtra(MediaStore.EXTRA_OUTPUT, fileUri(file))
+            addFlags(Intent.FLAG_GRANT_WRITE_URI_PERMISSION)
+        }
+        startActivityForResult(invoke, capture, "photoTaken")
+    }
+
+    @ActivityCallback
+    private fun photoTaken(invoke: Invoke, result: ActivityResult) {
+        val file = photo
+        photo = null
+        val taken = file?.takeIf { result.resultCode == Activity.RESULT_OK && it.length() > 0 }
+        if (taken == null) file?.delete()
+        invoke.resolve(JSObject().apply { put("path", taken?.absolutePath) })
+    }
+
+    @Command
+    fun getCurrentLocation(invoke: Invoke) {
+        val args = invoke.parseArgs(LocationArgs::class.java)
+        val granted = listOf(
+            Manifest.permission.ACCESS_FINE_LOCATION,
+            Manifest.permission.ACCESS_COARSE_LOCATION,
+        ).any {
+            ContextCompat.checkSelfPermission(activity, it) == PackageManager.PERMISSION_GRANTED
+        }
+        if (!granted) {
+            invoke.reject("location permission denied", "PERMISSION_DENIED:location")
+            return
+        }
+        val manager = activity.getSystemService(LocationManager::class.java)
+        if (!LocationManagerCompat.isLocationEnabled(manager)) {
+            invoke.reject("location services are turned off")
+            return
+        }
+        val gps = manager.isProviderEnabled(LocationManager.GPS_PROVIDER)
+        val network = manager.isProviderEnabled(LocationManager.NETWORK_PROVIDER)
+        val provider = when {
+            args.highAccuracy && gps -> LocationManager.GPS_PROVIDER
+            network -> LocationManager.NETWORK_PROVIDER
+            else -> LocationManager.GPS_PROVIDER
+        }
+        try {
+            val last = manager.getLastKnownLocation(provider)
+            if (last != null && args.maxAgeMs > 0 && age(last) <= args.maxAgeMs) {
+                invoke.resolve(location(last))
+                return
+            }
+            LocationManagerCompat.getCurrentLocation(
+                manager,
+                provider,
+                null,
+                ContextCompat.getMainExecutor(activity),
+            ) { fix ->
+                if (fix == null) {
+                    invoke.reject("no location fix available")
+                } else {
+                    invoke.resolve(location(fix))
+                }
+            }
+        } catch (e: SecurityException) {
+            invoke.reject("location permission denied", "PERMISSION_DENIED:location")
+        }
+    }
+
+    @Command
+    fun reverseGeocode(invoke: Invoke) {
+        val args = invoke.parseArgs(GeocodeArgs::class.java)
+        if (!Geocoder.isPresent()) {
+            invoke.resolve(JSObject().apply { put("address", null) })
+            return
+        }
+        // The synchronous lookup goes to the network, so it stays off the main thread
+        Thread {
+            try {
+                @Suppress("DEPRECATION")
+                val found = Geocoder(activity, Locale.getDefault())
+                    .getFromLocation(args.latitude, args.longitude, 1)
+                    ?.firstOrNull()
+                val address = found?.let {
+                    JSObject().apply {
+                        put("street", listOfNotNull(it.subThoroughfare, it.thoroughfare)
+                            .joinToString(" ")
+                            .ifEmpty { null })
+                        put("locality", it.locality)
+                        put("region", it.adminArea)
+                        put("postalCode", it.postalCode)
+                        put("country", it.countryName)
+                    }
+                }
+                invoke.resolve(JSObject().apply { put("address", address) })
+            } catch (e: Exception) {
+                invoke.reject(e.message ?: "reverse geocoding failed")
+            }
+        }.start()
+    }
+
+    @Command
+    fun listenForPush(invoke: Invoke) {
+        val args = invoke.parseArgs(PushListenArgs::class.java)
+        push = args.handler
+        pendingPush.forEach { args.handler.send(it) }
+        pendingPush.clear()
+        // A token the app was already given may have been rotated since the last launch
+        if (notificationsAllowed()) {
+            FirebaseMessaging.getInstance().token.addOnSuccessListener {
+                sendPush(JSObject().apply {
+                    put("kind", "token")
+                    put("token", it)
+                })
+            }
+        }
+        invoke.resolve()
+    }
+
+    @Command
+    fun registerForPush(invoke: Invoke) {
+        if (!notificationsAllowed()) {
+            invoke.reject("notification permission denied", "PERMISSION_DENIED:notifications")
+            return
+        }
+        FirebaseMessaging.getInstance().isAutoInitEnabled = true
+        FirebaseMessaging.getInstance().token
+            .addOnSuccessListener { invoke.resolve(JSObject().apply { put("token", it) }) }
+            .addOnFailureListener { invoke.reject(it.message ?: "FCM registration failed") }
+    }
+
+    @Command
+    fun unregisterForPush(invoke: Invoke) {
+        FirebaseMessaging.getInstance().isAutoInitEnabled = false
+        FirebaseMessaging.getInstance().deleteToken()
+            .addOnSuccessListener { invoke.resolve() }
+            .addOnFailureListener { invoke.reject(it.message ?: "FCM unregistration failed") }
+    }
+
+    @Command
+    fun registerShareTarget(invoke: Invoke) {
+        val args = invoke.parseArgs(ShareTargetArgs::class.java)
+        inbox = File(args.inbox).apply { mkdirs() }
+        shared = args.handler
+        flush()
+        invoke.resolve()
+    }
+
+    // Android 12 and earlier grant notifications at install
+    private fun notificationsAllowed(): Boolean =
+        Build.VERSION.SDK_INT < Build.VERSION_CODES.TIRAMISU ||
+            ContextCompat.checkSelfPermission(activity, Manifest.permission.POST_NOTIFICATIONS) ==
+            PackageManager.PERMISSION_GRANTED
+
+    private fun sendPush(event: JSObject) {
+        push?.send(event) ?: pendingPush.add(event)
+    }
+
+    // A tapped notification launches the activity with the message's data as extras
+    private fun collectPush(intent: Intent?) {
+        val extras = intent?.extras ?: return
+        if (!extras.containsKey("google.message_id")) return
+        val data = JSObject()
+        for (key in extras.keySet()) {
+            if (key.startsWith("google.") || key.startsWith("gcm.") || key == "from") continue
+            data.put(key, extras.getString(key))
+        }
+        intent.removeExtra("google.message_id")
+        sendPush(JSObject().apply {
+            put("kind", "message")
+            put("data", data)
+            put("tapped", true)
+        })
+    }
+
+    private fun age(fix: Location): Long =
+        (SystemClock.elapsedRealtimeNanos() - fix.elapsedRealtimeNanos) / 1_000_000
+
+    private fun location(fix: Location): JSObject = JSObject().apply {
+        put("latitude", fix.latitude)
+        put("longitude", fix.longitude)
+        put("accuracy", if (fix.hasAccuracy()) fix.accuracy.toDouble() else null)
+        put("altitude", if (fix.hasAltitude()) fix.altitude else null)
+        put("timestamp", fix.time)
+    }
+
+    private fun fileUri(file: File): Uri =
+        FileProvider.getUriForFile(activity, "${activity.packageName}.fileprovider", file)
+
+    private fun collect(intent: Intent?) {
+        val uris = when (intent?.action) {
+            Intent.ACTION_SEND ->
+                listOfNotNull(intent.getParcelableExtra<Uri>(Intent.EXTRA_STREAM))
+            Intent.ACTION_SEND_MULTIPLE ->
+                intent.getParcelableArrayListExtra<Uri>(Intent.EXTRA_STREAM).orEmpty()
+            else -> return
+        }
+        // Consumed, so recreating the activity does not deliver the same files twice
+        intent.action = null
+        pending.addAll(uris)
+        flush()
+    }
+
+    private fun flush() {
+        val inbox = inbox ?: return
+        val shared = shared ?: return
+        if (pending.isEmpty()) return
+        val paths = JSArray()
+        for (uri in pending) {
+            copy(uri, inbox)?.let { paths.put(it.absolutePath) }
+        }
+        pending.clear()
+        shared.send(JSObject().apply { put("paths", paths) })
+    }
+
+    // Content URIs are only readable while the grant lasts, so the bytes are copied out now
+    private fun copy(uri: Uri, inbox: File): File? {
+        val resolver = activity.contentResolver
+        val name = resolver.query(uri, arrayOf(OpenableColumns.DISPLAY_NAME), null, null, null)
+            ?.use { if (it.moveToFirst()) it.getString(0) else null }
+            ?.let { File(it).name }
+            ?: "shared"
+        val target = File(inbox, "${System.currentTimeMillis()}-$name")
+        return try {
+            resolver.openInputStream(uri)?.use { input ->
+                target.outputStream().use { input.copyTo(it) }
+            } ?: return null
+            target
+        } catch (e: Exception) {
+            target.delete()
+            null
+        }
+    }
+}
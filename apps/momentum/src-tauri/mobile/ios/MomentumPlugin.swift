@@ -0,0 +1,435 @@
+// Native side of src/mobile.rs; add to the app target in gen/apple. Push also needs the
+// target's Push Notifications capability.
+
+import CoreLocation
+import ImageIO
+import SwiftRs
+import Tauri
+import UIKit
+import UniformTypeIdentifiers
+import UserNotifications
+import WebKit
+
+class ShareFileArgs: Decodable {
+  let path: String
+  let mime: String
+  let title: String?
+}
+
+class LocationArgs: Decodable {
+  let highAccuracy: Bool?
+  let maxAgeMs: Double?
+}
+
+class PermissionArgs: Decodable {
+  let permissions: [String]?
+}
+
+class PushListenArgs: Decodable {
+  let handler: Channel
+}
+
+class GeocodeArgs: Decodable {
+  let latitude: Double
+  let longitude: Double
+}
+
+class MomentumPlugin: Plugin {
+  private var camera: CameraDelegate?
+  private let locator = Locator()
+
+  override func load(webview: WKWebView) {
+    PushRelay.shared.install()
+  }
+
+  @objc public override func checkPermissions(_ invoke: Invoke) {
+    UNUserNotificationCenter.current().getNotificationSettings { settings in
+      invoke.resolve([
+        "location": self.locator.state,
+        "notifications": PushRelay.state(settings.authorizationStatus),
+      ])
+    }
+  }
+
+  @objc public override func requestPermissions(_ invoke: Invoke) {
+    let wanted =
+      (try? invoke.parseArgs(PermissionArgs.self))?.permissions ?? ["location", "notifications"]
+    let group = DispatchGroup()
+    var states: [String: String] = [:]
+    if wanted.contains("location") {
+      group.enter()
+      locator.authorize { state in
+        states["location"] = state
+        group.leave()
+      }
+    }
+    if wanted.contains("notifications") {
+      group.enter()
+      UNUserNotificationCenter.current().requestAuthorization(options: [.alert, .badge, .sound]) {
+        granted, _ in
+        DispatchQueue.main.async {
+          states["notifications"] = granted ? "granted" : "denied"
+          group.leave()
+        }
+      }
+    }
+    group.notify(queue: .main) { invoke.resolve(states) }
+  }
+
+  @objc public func listenForPush(_ invoke: Invoke) throws {
+    let args = try invoke.parseArgs(PushListenArgs.self)
+    PushRelay.shared.listen(args.handler)
+    // Tokens can change between launches, so a device already allowed registers again
+    UNUserNotificationCenter.current().getNotificationSettings { settings in
+      if PushRelay.state(settings.authorizationStatus) == "granted" {
+        DispatchQueue.main.async { UIApplication.shared.registerForRemoteNotifications() }
+      }
+    }
+    invoke.resolve()
+  }
+
+  @objc public func registerForPush(_ invoke: Invoke) {
+    UNUserNotificationCenter.current().getNotificationSettings { settings in
+      guard PushRelay.state(settings.authorizationStatus) == "granted" else {
+        invoke.reject("notification permission denied", code: "PERMISSION_DENIED:notifications")
+        return
+      }
+      PushRelay.shared.register { result in
+        switch result {
+        case .success(let token): invoke.resolve(["token": token])
+        case .failure(let error): invoke.reject(error.localizedDescription)
+        }
+      }
+    }
+  }
+
+  @objc public func unregisterForPush(_ invoke: Invoke) {
+    DispatchQueue.main.async {
+      UIApplication.shared.unregisterForRemoteNotifications()
+      invoke.resolve()
+    }
+  }
+
+  @objc public func getCurrentLocation(_ invoke: Invoke) throws {
+    let args = try invoke.parseArgs(LocationArgs.self)
+    guard locator.state == "granted" else {
+      invoke.reject("location permission denied", code: "PERMISSION_DENIED:location")
+      return
+    }
+    locator.locate(
+      highAccuracy: args.highAccuracy ?? false, maxAge: (args.maxAgeMs ?? 0) / 1000
+    ) { result in
+      switch result {
+      case .success(let fix):
+        invoke.resolve([
+          "latitude": fix.coordinate.latitude,
+          "longitude": fix.coordinate.longitude,
+          "accuracy": fix.horizontalAccuracy >= 0 ? fix.horizontalAccuracy : nil as Double?,
+          "altitude": fix.verticalAccuracy >= 0 ? fix.altitude : nil as Double?,
+          "timestamp": Int64(fix.timestamp.timeIntervalSince1970 * 1000),
+        ])
+      case .failure(let error):
+        invoke.reject(error.localizedDescription)
+      }
+    }
+  }
+
+  @objc public func reverseGeocode(_ invoke: Invoke) throws {
+    let args = try invoke.parseArgs(GeocodeArgs.self)
+    let position = CLLocation(latitude: args.latitude, longitude: args.longitude)
+    CLGeocoder().reverseGeocodeLocation(position) { placemarks, error in
+      if let error = error, (error as NSError).code != CLError.geocodeFoundNoResult.rawValue {
+        invoke.reject(error.localizedDescription)
+        return
+      }
+      guard let place = placemarks?.first else {
+        invoke.resolve(["address": nil as [String: Any]?])
+        return
+      }
+      let street = [place.subThoroughfare, place.thoroughfare].compactMap { $0 }
+        .joined(separator: " ")
+      invoke.resolve([
+        "address": [
+          "street": street.isEmpty ? nil : street,
+          "locality": place.locality,
+          "region": place.administrativeArea,
+          "postalCode": place.postalCode,
+          "country": place.country,
+        ] as [String: Any?]
+      ])
+    }
+  }
+
+  @objc public func takePhoto(_ invoke: Invoke) {
+    DispatchQueue.main.async {
+      guard UIImagePickerController.isSourceTypeAvailable(.camera),
+        let controller = self.manager.viewController
+      else {
+        invoke.reject("this device has no camera")
+        return
+      }
+      let picker = UIImagePickerController()
+      picker.sourceType = .camera
+      let delegate = CameraDelegate { path in
+        self.camera = nil
+        invoke.resolve(["path": path as Any])
+      }
+      self.camera = delegate
+      picker.delegate = delegate
+      controller.present(picker, animated: true)
+    }
+  }
+
+  @objc public func shareFile(_ invoke: Invoke) throws {
+    let args = try invoke.parseArgs(ShareFileArgs.self)
+    let url = URL(fileURLWithPath: args.path)
+    DispatchQueue.main.async {
+      guard let controller = self.manager.viewController else {
+        invoke.reject("no view to present the share sheet from")
+        return
+      }
+      let sheet = UIActivityViewController(activityItems: [url], applicationActivities: nil)
+      // iPad shows the sheet as a popover, which needs an anchor
+      if let popover = sheet.popoverPresentationController {
+        popover.sourceView = controller.view
+        popover.sourceRect = CGRect(
+          x: controller.view.bounds.midX, y: controller.view.bounds.midY, width: 0, height: 0)
+        popover.permittedArrowDirections = []
+      }
+      sheet.completionWithItemsHandler = { activity, completed, _, error in
+        if let error = error {
+          invoke.reject(error.localizedDescription)
+        } else {
+          invoke.resolve(["completed": completed, "target": activity?.rawValue as Any])
+        }
+      }
+      controller.present(sheet, animated: true)
+    }
+  }
+}
+
+struct PushEvent: Encodable {
+  let kind: String
+  var token: String? = nil
+  var title: String? = nil
+  var body: String? = nil
+  var data: [String: String]? = nil
+  var tapped: Bool? = nil
+}
+
+// Tauri owns the app delegate, so the APNs token callbacks are added to its class at runtime
+class PushRelay: NSObject, UNUserNotificationCenterDelegate {
+  static let shared = PushRelay()
+
+  private var channel: Channel?
+  private var pending: [PushEvent] = []
+  private var registering: [(Result<String, Error>) -> Void] = []
+
+  static func state(_ status: UNAuthorizationStatus) -> String {
+    switch status {
+    case .authorized, .provisional, .ephemeral: return "granted"
+    case .denied: return "denied"
+    default: return "prompt"
+    }
+  }
+
+  func install() {
+    UNUserNotificationCenter.current().delegate = self
+    guard let delegate = UIApplication.shared.delegate else { return }
+    let target: AnyClass = type(of: delegate)
+    let registered: @convention(block) (AnyObject, UIApplication, Data) -> Void = { _, _, token in
+      PushRelay.shared.registered(.success(token.map { String(format: "%02x", $0) }.joined()))
+    }
+    let failed: @convention(block) (AnyObject, UIApplication, Error) -> Void = { _, _, error in
+      PushRelay.shared.registered(.failure(error))
+    }
+    class_addMethod(
+      target,
+      #selector(
+        UIApplicationDelegate.application(_:didRegisterForRemoteNotificationsWithDeviceToken:)),
+      imp_implementationWithBlock(registered), "v@:@@")
+    class_addMethod(
+      target,
+      #selector(
+        UIApplicationDelegate.application(_:didFailToRegisterForRemoteNotificationsWithError:)),
+      imp_implementationWithBlock(failed), "v@:@@")
+  }
+
+  func listen(_ channel: Channel) {
+    DispatchQueue.main.async {
+      self.channel = channel
+      self.pending.forEach { try? channel.send($0) }
+      self.pending = []
+    }
+  }
+
+  func register(_ done: @escaping (Result<String, Error>) -> Void) {
+    DispatchQueue.main.async {
+      self.registering.append(done)
+      UIApplication.shared.registerForRemoteNotifications()
+    }
+  }
+
+  private func registered(_ result: Result<String, Error>) {
+    let waiting = registering
+    registering = []
+    waiting.forEach { $0(result) }
+    if case .success(let token) = result {
+      send(PushEvent(kind: "token", token: token))
+    }
+  }
+
+  private func send(_ event: PushEvent) {
+    DispatchQueue.main.async {
+      if let channel = self.channel {
+        try? channel.send(event)
+      } else {
+        self.pending.append(event)
+      }
+    }
+  }
+
+  private func event(_ notification: UNNotification, tapped: Bool) -> PushEvent {
+    let content = notification.request.content
+    var data: [String: String] = [:]
+    for (key, value) in content.userInfo {
+      if let key = key as? String, key != "aps", let value = value as? String {
+        data[key] = value
+      }
+    }
+    return PushEvent(
+      kind: "message", title: content.title, body: content.body, data: data, tapped: tapped)
+  }
+
+  // The frontend decides how to show a notification that arrives while the app is open
+  func userNotificationCenter(
+    _ center: UNUserNotificationCenter, willPresent notification: UNNotification,
+    withCompletionHandler completionHandler: @escaping (UNNotificationPresentationOptions) -> Void
+  ) {
+    send(event(notification, tapped: false))
+    completionHandler([])
+  }
+
+  func userNotificationCenter(
+    _ center: UNUserNotificationCenter, didReceive response: UNNotificationResponse,
+    withCompletionHandler completionHandler: @escaping () -> Void
+  ) {
+    send(event(response.notification, tapped: true))
+    completionHandler()
+  }
+}
+
+// CLLocationManager reports back through its delegate, on the thread that created it
+class Locator: NSObject, CLLocationManagerDelegate {
+  private var manager: CLLocationManager?
+  private var authorized: [(String) -> Void] = []
+  private var located: [(Result<CLLocation, Error>) -> Void] = []
+
+  var state: String {
+    switch CLLocationManager().authorizationStatus {
+    case .authorizedAlways, .authorizedWhenInUse: return "granted"
+    case .denied, .restricted: return "denied"
+    default: return "prompt"
+    }
+  }
+
+  func authorize(_ done: @escaping (String) -> Void) {
+    DispatchQueue.main.async {
+      guard self.state == "prompt" else {
+        done(self.state)
+        return
+      }
+      self.authorized.append(done)
+      self.locationManager().requestWhenInUseAuthorization()
+    }
+  }
+
+  func locate(
+    highAccuracy: Bool, maxAge: TimeInterval,
+    _ done: @escaping (Result<CLLocation, Error>) -> Void
+  ) {
+    DispatchQueue.main.async {
+      let manager = self.locationManager()
+      if let last = manager.location, maxAge > 0, -last.timestamp.timeIntervalSinceNow <= maxAge {
+        done(.success(last))
+        return
+      }
+      manager.desiredAccuracy =
+        highAccuracy ? kCLLocationAccuracyBest : kCLLocationAccuracyHundredMeters
+      self.located.append(done)
+      manager.requestLocation()
+    }
+  }
+
+  private func locationManager() -> CLLocationManager {
+    if let manager = manager { return manager }
+    let created = CLLocationManager()
+    created.delegate = self
+    manager = created
+    return created
+  }
+
+  func locationManagerDidChangeAuthorization(_ manager: CLLocationManager) {
+    // Called once on creation too, before the user has answered
+    guard state != "prompt" else { return }
+    let waiting = authorized
+    authorized = []
+    waiting.forEach { $0(state) }
+  }
+
+  func locationManager(_ manager: CLLocationManager, didUpdateLocations locations: [CLLocation]) {
+    guard let fix = locations.last else { return }
+    let waiting = located
+    located = []
+    waiting.forEach { $0(.success(fix)) }
+  }
+
+  func locationManager(_ manager: CLLocationManager, didFailWithError error: Error) {
+    let waiting = located
+    located = []
+    waiting.forEach { $0(.failure(error)) }
+  }
+}
+
+class CameraDelegate: NSObject, UIImagePickerControllerDelegate, UINavigationControllerDelegate {
+  let done: (String?) -> Void
+
+  init(done: @escaping (String?) -> Void) {
+    self.done = done
+  }
+
+  func imagePickerController(
+    _ picker: UIImagePickerController,
+    didFinishPickingMediaWithInfo info: [UIImagePickerController.InfoKey: Any]
+  ) {
+    picker.dismiss(animated: true)
+    guard let image = info[.originalImage] as? UIImage, let pixels = image.cgImage else {
+      done(nil)
+      return
+    }
+    // Written with the camera's metadata, orientation included; Rust decides what to keep
+    var properties = info[.mediaMetadata] as? [String: Any] ?? [:]
+    properties[kCGImageDestinationLossyCompressionQuality as String] = 0.95
+    let url = FileManager.default.temporaryDirectory
+      .appendingPathComponent("\(UUID().uuidString).jpg")
+    guard
+      let destination = CGImageDestinationCreateWithURL(
+        url as CFURL, UTType.jpeg.identifier as CFString, 1, nil)
+    else {
+      done(nil)
+      return
+    }
+    CGImageDestinationAddImage(destination, pixels, properties as CFDictionary)
+    done(CGImageDestinationFinalize(destination) ? url.path : nil)
+  }
+
+  func imagePickerControllerDidCancel(_ picker: UIImagePickerController) {
+    picker.dismiss(animated: true)
+    done(nil)
+  }
+}
+
+@_cdecl("init_plugin_momentum")
+func initPlugin() -> Plugin {
+  return MomentumPlugin()
+}
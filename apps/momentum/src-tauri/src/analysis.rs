@@ -0,0 +1,730 @@
+//! Linear-elastic analysis of 2D pin-jointed trusses by the direct stiffness method.
+//!
+//! Meant for sanity-checking a design from the estimate, not for sealing drawings: members
+//! carry axial force only, joints are frictionless pins, and deflections are small. Inputs
+//! come in whatever units the engineer works in and are converted to SI for the solve;
+//! results go back out in the units asked for.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+use crate::tasks::TaskKind;
+
+// Dense solve: 2 unknowns a joint, so this is a 1000-square matrix at most
+const MAX_NODES: usize = 500;
+// Relative to the stiffest joint; a pivot below this means the truss can move freely
+const SINGULAR: f64 = 1e-10;
+// Member forces below this share of the largest one are reported as zero
+const ZERO_FORCE: f64 = 1e-9;
+
+/// Serialized as its symbol.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum LengthUnit {
+    #[serde(rename = "mm")]
+    Mm,
+    #[serde(rename = "cm")]
+    Cm,
+    #[serde(rename = "m")]
+    M,
+    #[serde(rename = "in")]
+    In,
+    #[serde(rename = "ft")]
+    Ft,
+}
+
+impl LengthUnit {
+    fn meters(self) -> f64 {
+        match self {
+            Self::Mm => 0.001,
+            Self::Cm => 0.01,
+            Self::M => 1.0,
+            Self::In => 0.0254,
+            Self::Ft => 0.3048,
+        }
+    }
+}
+
+/// Serialized as its symbol.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum ForceUnit {
+    #[serde(rename = "N")]
+    N,
+    #[serde(rename = "kN")]
+    KN,
+    #[serde(rename = "lbf")]
+    Lbf,
+    #[serde(rename = "kip")]
+    Kip,
+}
+
+impl ForceUnit {
+    fn newtons(self) -> f64 {
+        match self {
+            Self::N => 1.0,
+            Self::KN => 1000.0,
+            Self::Lbf => 4.448_221_615_260_5,
+            Self::Kip => 4_448.221_615_260_5,
+        }
+    }
+}
+
+/// Serialized as its symbol.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum StressUnit {
+    #[serde(rename = "Pa")]
+    Pa,
+    #[serde(rename = "kPa")]
+    KPa,
+    #[serde(rename = "MPa")]
+    MPa,
+    #[serde(rename = "GPa")]
+    GPa,
+    #[serde(rename = "psi")]
+    Psi,
+    #[serde(rename = "ksi")]
+    Ksi,
+}
+
+impl StressUnit {
+    fn pascals(self) -> f64 {
+        match self {
+            Self::Pa => 1.0,
+            Self::KPa => 1e3,
+            Self::MPa => 1e6,
+            Self::GPa => 1e9,
+            Self::Psi => 6_894.757_293_168,
+            Self::Ksi => 6_894_757.293_168,
+        }
+    }
+}
+
+/// Units of a model's inputs and results.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisUnits {
+    /// Joint coordinates.
+    pub length: LengthUnit,
+    pub force: ForceUnit,
+    /// Member areas are in this unit squared; `length` when not given.
+    pub section: Option<LengthUnit>,
+    /// Elastic modulus in, member stresses out.
+    pub stress: StressUnit,
+    /// Displacements out; `length` when not given.
+    pub deflection: Option<LengthUnit>,
+}
+
+impl Default for AnalysisUnits {
+    fn default() -> Self {
+        Self {
+            length: LengthUnit::M,
+            force: ForceUnit::KN,
+            section: Some(LengthUnit::Mm),
+            stress: StressUnit::MPa,
+            deflection: Some(LengthUnit::Mm),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Support {
+    /// Restrained horizontally.
+    pub x: bool,
+    /// Restrained vertically.
+    pub y: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrussNode {
+    pub id: String,
+    pub x: f64,
+    pub y: f64,
+    /// `None` for a free joint; both directions for a pin, one for a roller.
+    pub support: Option<Support>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrussMember {
+    pub id: String,
+    pub start: String,
+    pub end: String,
+    /// Cross-sectional area.
+    pub area: f64,
+    /// Elastic modulus.
+    pub modulus: f64,
+}
+
+/// A point load on a joint; positive `fy` is up.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeLoad {
+    pub node: String,
+    #[serde(default)]
+    pub fx: f64,
+    #[serde(default)]
+    pub fy: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrussModel {
+    pub nodes: Vec<TrussNode>,
+    pub members: Vec<TrussMember>,
+    #[serde(default)]
+    pub loads: Vec<NodeLoad>,
+    #[serde(default)]
+    pub units: AnalysisUnits,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MemberState {
+    Tension,
+    Compression,
+    Zero,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberResult {
+    pub id: String,
+    /// Axial force, positive in tension.
+    pub force: f64,
+    pub stress: f64,
+    pub state: MemberState,
+    /// Length in the model's length unit.
+    pub length: f64,
+    /// Change in length, in the deflection unit.
+    pub elongation: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reaction {
+    pub node: String,
+    pub fx: f64,
+    pub fy: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Displacement {
+    pub node: String,
+    pub dx: f64,
+    pub dy: f64,
+}
+
+/// The joint that moves furthest, and how far.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaxDisplacement {
+    pub node: String,
+    pub magnitude: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Determinacy {
+    Determinate,
+    /// More members and supports than the joints need; forces depend on stiffness.
+    Indeterminate,
+    /// Too few to hold every joint; the solve will fail.
+    Unstable,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrussResult {
+    pub members: Vec<MemberResult>,
+    pub reactions: Vec<Reaction>,
+    pub displacements: Vec<Displacement>,
+    pub max_displacement: Option<MaxDisplacement>,
+    /// Counted from members, reactions, and joints (`m + r - 2j`); a truss can count as
+    /// stable and still be a mechanism when members are badly arranged.
+    pub determinacy: Determinacy,
+    pub degree_of_indeterminacy: i64,
+    pub units: AnalysisUnits,
+}
+
+// Members in SI, with joint indices
+struct Element {
+    start: usize,
+    end: usize,
+    length: f64,
+    cos: f64,
+    sin: f64,
+    area: f64,
+    axial_stiffness: f64,
+}
+
+fn invalid(message: String) -> AppError {
+    AppError::InvalidInput(message)
+}
+
+fn elements(model: &TrussModel, index: &HashMap<&str, usize>) -> AppResult<Vec<Element>> {
+    let units = model.units;
+    let length = units.length.meters();
+    let section = units.section.unwrap_or(units.length).meters();
+    let joint = |member: &TrussMember, id: &str| {
+        index
+            .get(id)
+            .copied()
+            .ok_or_else(|| invalid(format!("member {} uses unknown joint {id}", member.id)))
+    };
+    model
+        .members
+        .iter()
+        .map(|member| {
+            let (start, end) = (joint(member, &member.start)?, joint(member, &member.end)?);
+            let (a, b) = (&model.nodes[start], &model.nodes[end]);
+            let (dx, dy) = ((b.x - a.x) * length, (b.y - a.y) * length);
+            let span = dx.hypot(dy);
+            if !span.is_finite() || span == 0.0 {
+                return Err(invalid(format!("member {} has no length", member.id)));
+            }
+            if !(member.area > 0.0 && member.modulus > 0.0) {
+                return Err(invalid(format!(
+                    "member {} needs a positive area and modulus",
+                    member.id
+                )));
+            }
+            let area = member.area * section * section;
+            let modulus = member.modulus * units.stress.pascals();
+            Ok(Element {
+                start,
+                end,
+                length: span,
+                cos: dx / span,
+                sin: dy / span,
+                area,
+                axial_stiffness: area * modulus / span,
+            })
+        })
+        .collect()
+}
+
+// Gaussian elimination with partial pivoting; on a mechanism, the unknown that could not
+// be pinned down
+fn solve(mut k: Vec<Vec<f64>>, mut f: Vec<f64>) -> Result<Vec<f64>, usize> {
+    let n = f.len();
+    let scale = (0..n).map(|i| k[i][i].abs()).fold(0.0, f64::max);
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|a, b| k[*a][col].abs().total_cmp(&k[*b][col].abs()))
+            .unwrap_or(col);
+        if k[pivot][col].abs() <= scale * SINGULAR {
+            return Err(col);
+        }
+        k.swap(col, pivot);
+        f.swap(col, pivot);
+        let (above, below) = k.split_at_mut(col + 1);
+        let pivot_row = &above[col];
+        for (offset, row) in below.iter_mut().enumerate() {
+            let factor = row[col] / pivot_row[col];
+            if factor == 0.0 {
+                continue;
+            }
+            for (cell, pivot_cell) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                *cell -= factor * pivot_cell;
+            }
+            f[col + 1 + offset] -= factor * f[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|c| k[row][c] * x[c]).sum();
+        x[row] = (f[row] - sum) / k[row][row];
+    }
+    Ok(x)
+}
+
+pub fn analyze(model: &TrussModel) -> AppResult<TrussResult> {
+    let nodes = &model.nodes;
+    if nodes.is_empty() || model.members.is_empty() {
+        return Err(invalid("a truss needs joints and members".into()));
+    }
+    if nodes.len() > MAX_NODES {
+        return Err(invalid(format!(
+            "trusses are limited to {MAX_NODES} joints"
+        )));
+    }
+    let mut index = HashMap::new();
+    for (i, node) in nodes.iter().enumerate() {
+        if !(node.x.is_finite() && node.y.is_finite()) {
+            return Err(invalid(format!("joint {} has no position", node.id)));
+        }
+        if index.insert(node.id.as_str(), i).is_some() {
+            return Err(invalid(format!("joint {} is defined twice", node.id)));
+        }
+    }
+    let elements = elements(model, &index)?;
+    let units = model.units;
+    let force_unit = units.force.newtons();
+    let mut loads = vec![0.0; nodes.len() * 2];
+    for load in &model.loads {
+        let node = *index
+            .get(load.node.as_str())
+            .ok_or_else(|| invalid(format!("a load is on unknown joint {}", load.node)))?;
+        loads[2 * node] += load.fx * force_unit;
+        loads[2 * node + 1] += load.fy * force_unit;
+    }
+
+    let size = nodes.len() * 2;
+    let mut stiffness = vec![vec![0.0; size]; size];
+    for element in &elements {
+        let (c, s) = (element.cos, element.sin);
+        let local = [[c * c, c * s], [c * s, s * s]];
+        let dofs = [2 * element.start, 2 * element.end];
+        for (a, row_base) in dofs.iter().enumerate() {
+            for (b, col_base) in dofs.iter().enumerate() {
+                let sign = if a == b { 1.0 } else { -1.0 };
+                for i in 0..2 {
+                    for j in 0..2 {
+                        stiffness[row_base + i][col_base + j] +=
+                            sign * element.axial_stiffness * local[i][j];
+                    }
+                }
+            }
+        }
+    }
+
+    let restrained = |dof: usize| {
+        let support = nodes[dof / 2].support.unwrap_or_default();
+        if dof.is_multiple_of(2) {
+            support.x
+        } else {
+            support.y
+        }
+    };
+    let free: Vec<usize> = (0..size).filter(|dof| !restrained(*dof)).collect();
+    let reduced = free
+        .iter()
+        .map(|row| free.iter().map(|col| stiffness[*row][*col]).collect())
+        .collect();
+    let solved = solve(reduced, free.iter().map(|dof| loads[*dof]).collect()).map_err(|at| {
+        let dof = free[at];
+        invalid(format!(
+            "the truss is unstable: joint {} is free to move {}",
+            nodes[dof / 2].id,
+            if dof.is_multiple_of(2) {
+                "horizontally"
+            } else {
+                "vertically"
+            }
+        ))
+    })?;
+    let mut displacement = vec![0.0; size];
+    for (dof, value) in free.iter().zip(solved) {
+        displacement[*dof] = value;
+    }
+
+    let deflection_unit = units.deflection.unwrap_or(units.length).meters();
+    let length_unit = units.length.meters();
+    let forces: Vec<f64> = elements
+        .iter()
+        .map(|e| {
+            let du = displacement[2 * e.end] - displacement[2 * e.start];
+            let dv = displacement[2 * e.end + 1] - displacement[2 * e.start + 1];
+            e.axial_stiffness * (e.cos * du + e.sin * dv)
+        })
+        .collect();
+    let largest = forces.iter().map(|f| f.abs()).fold(0.0, f64::max);
+    let members = model
+        .members
+        .iter()
+        .zip(&elements)
+        .zip(&forces)
+        .map(|((member, element), force)| {
+            let state = if force.abs() <= largest * ZERO_FORCE {
+                MemberState::Zero
+            } else if *force > 0.0 {
+                MemberState::Tension
+            } else {
+                MemberState::Compression
+            };
+            MemberResult {
+                id: member.id.clone(),
+                force: force / force_unit,
+                stress: force / element.area / units.stress.pascals(),
+                state,
+                length: element.length / length_unit,
+                elongation: force / element.axial_stiffness / deflection_unit,
+            }
+        })
+        .collect();
+
+    let reactions = nodes
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| restrained(2 * i) || restrained(2 * i + 1))
+        .map(|(i, node)| {
+            // What the supports push back with: the stiffness force less the applied load
+            let reaction = |dof: usize| {
+                if !restrained(dof) {
+                    return 0.0;
+                }
+                let internal: f64 = (0..size)
+                    .map(|col| stiffness[dof][col] * displacement[col])
+                    .sum();
+                (internal - loads[dof]) / force_unit
+            };
+            Reaction {
+                node: node.id.clone(),
+                fx: reaction(2 * i),
+                fy: reaction(2 * i + 1),
+            }
+        })
+        .collect();
+    let displacements: Vec<Displacement> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| Displacement {
+            node: node.id.clone(),
+            dx: displacement[2 * i] / deflection_unit,
+            dy: displacement[2 * i + 1] / deflection_unit,
+        })
+        .collect();
+    let max_displacement = displacements
+        .iter()
+        .map(|d| MaxDisplacement {
+            node: d.node.clone(),
+            magnitude: d.dx.hypot(d.dy),
+        })
+        .max_by(|a, b| a.magnitude.total_cmp(&b.magnitude));
+
+    let supports = (0..size).filter(|dof| restrained(*dof)).count() as i64;
+    let degree = model.members.len() as i64 + supports - size as i64;
+    let determinacy = match degree {
+        0 => Determinacy::Determinate,
+        d if d > 0 => Determinacy::Indeterminate,
+        _ => Determinacy::Unstable,
+    };
+    Ok(TrussResult {
+        members,
+        reactions,
+        displacements,
+        max_displacement,
+        determinacy,
+        degree_of_indeterminacy: degree,
+        units,
+    })
+}
+
+/// Member forces, support reactions, and joint deflections of a 2D truss under its loads.
+///
+/// A cancelled analysis returns `CANCELLED` at once and its result is thrown away.
+#[tauri::command]
+pub async fn analyze_truss(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    model: TrussModel,
+    task_id: Option<String>,
+) -> AppResult<TrussResult> {
+    state
+        .tasks()
+        .run(&app, TaskKind::Analysis, task_id, |task| async move {
+            let work = tauri::async_runtime::spawn_blocking(move || analyze(&model));
+            task.abortable(async { work.await? }).await
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Steel-ish members in the default units: m, kN, mm², MPa
+    const AREA: f64 = 1000.0;
+    const MODULUS: f64 = 200_000.0;
+
+    fn node(id: &str, x: f64, y: f64, support: Option<(bool, bool)>) -> TrussNode {
+        TrussNode {
+            id: id.into(),
+            x,
+            y,
+            support: support.map(|(x, y)| Support { x, y }),
+        }
+    }
+
+    fn member(start: &str, end: &str) -> TrussMember {
+        TrussMember {
+            id: format!("{start}-{end}"),
+            start: start.into(),
+            end: end.into(),
+            area: AREA,
+            modulus: MODULUS,
+        }
+    }
+
+    fn down(node: &str, fy: f64) -> NodeLoad {
+        NodeLoad {
+            node: node.into(),
+            fx: 0.0,
+            fy: -fy,
+        }
+    }
+
+    fn model(nodes: Vec<TrussNode>, members: &[(&str, &str)], loads: Vec<NodeLoad>) -> TrussModel {
+        TrussModel {
+            nodes,
+            members: members.iter().map(|(a, b)| member(a, b)).collect(),
+            loads,
+            units: AnalysisUnits::default(),
+        }
+    }
+
+    fn force(result: &TrussResult, id: &str) -> f64 {
+        result
+            .members
+            .iter()
+            .find(|m| m.id == id)
+            .unwrap_or_else(|| panic!("no member {id}"))
+            .force
+    }
+
+    fn close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6 * expected.abs().max(1.0),
+            "{actual} is not {expected}"
+        );
+    }
+
+    // Four 3 m panels, 4 m deep, 10 kN at each inner bottom joint; solved by the method of
+    // joints: reactions 15 kN, end posts -18.75, first diagonals 6.25, top chord -15
+    fn pratt() -> TrussModel {
+        let nodes = vec![
+            node("L0", 0.0, 0.0, Some((true, true))),
+            node("L1", 3.0, 0.0, None),
+            node("L2", 6.0, 0.0, None),
+            node("L3", 9.0, 0.0, None),
+            node("L4", 12.0, 0.0, Some((false, true))),
+            node("U1", 3.0, 4.0, None),
+            node("U2", 6.0, 4.0, None),
+            node("U3", 9.0, 4.0, None),
+        ];
+        let members = [
+            ("L0", "L1"),
+            ("L1", "L2"),
+            ("L2", "L3"),
+            ("L3", "L4"),
+            ("U1", "U2"),
+            ("U2", "U3"),
+            ("L0", "U1"),
+            ("U3", "L4"),
+            ("L1", "U1"),
+            ("L2", "U2"),
+            ("L3", "U3"),
+            ("U1", "L2"),
+            ("U3", "L2"),
+        ];
+        let loads = vec![down("L1", 10.0), down("L2", 10.0), down("L3", 10.0)];
+        model(nodes, &members, loads)
+    }
+
+    #[test]
+    fn a_pratt_truss_matches_the_method_of_joints() {
+        let result = analyze(&pratt()).unwrap();
+        assert!(matches!(result.determinacy, Determinacy::Determinate));
+        assert_eq!(result.degree_of_indeterminacy, 0);
+
+        for (id, expected) in [
+            ("L0-L1", 11.25),
+            ("L1-L2", 11.25),
+            ("L2-L3", 11.25),
+            ("L3-L4", 11.25),
+            ("U1-U2", -15.0),
+            ("U2-U3", -15.0),
+            ("L0-U1", -18.75),
+            ("U3-L4", -18.75),
+            ("L1-U1", 10.0),
+            ("L3-U3", 10.0),
+            ("U1-L2", 6.25),
+            ("U3-L2", 6.25),
+        ] {
+            close(force(&result, id), expected);
+        }
+        let middle = result.members.iter().find(|m| m.id == "L2-U2").unwrap();
+        assert_eq!(middle.state, MemberState::Zero);
+        let post = result.members.iter().find(|m| m.id == "L0-U1").unwrap();
+        assert_eq!(post.state, MemberState::Compression);
+        // σ = 18.75 kN / 1000 mm²
+        close(post.stress, -18.75);
+
+        let total: f64 = result.reactions.iter().map(|r| r.fy).sum();
+        close(total, 30.0);
+        for reaction in &result.reactions {
+            close(reaction.fy, 15.0);
+            close(reaction.fx, 0.0);
+        }
+        // δ = FL / AE = 11.25 kN × 3 m / (1000 mm² × 200 GPa), in mm
+        let chord = result.members.iter().find(|m| m.id == "L0-L1").unwrap();
+        close(chord.elongation, 0.168_75);
+        close(chord.length, 3.0);
+    }
+
+    #[test]
+    fn too_few_members_is_unstable() {
+        let mut truss = pratt();
+        truss.members.retain(|m| m.id != "U1-L2");
+        let err = analyze(&truss).unwrap_err().to_string();
+        assert!(err.contains("unstable"), "{err}");
+    }
+
+    // A square frame on two pins has m + r = 2j, yet nothing stops it swaying sideways
+    #[test]
+    fn a_mechanism_that_counts_as_determinate_is_refused() {
+        let nodes = vec![
+            node("A", 0.0, 0.0, Some((true, true))),
+            node("B", 4.0, 0.0, Some((true, true))),
+            node("C", 4.0, 4.0, None),
+            node("D", 0.0, 4.0, None),
+        ];
+        let members = [("A", "B"), ("B", "C"), ("C", "D"), ("D", "A")];
+        let sway = NodeLoad {
+            node: "D".into(),
+            fx: 5.0,
+            fy: 0.0,
+        };
+        let err = analyze(&model(nodes, &members, vec![sway]))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unstable"), "{err}");
+    }
+
+    // Three equal bars holding one joint, the usual first indeterminate example: with θ the
+    // angle of the outer bars from vertical, the middle one carries P / (1 + 2cos³θ) and the
+    // outer ones P cos²θ / (1 + 2cos³θ)
+    #[test]
+    fn three_bars_share_a_load_by_stiffness() {
+        let nodes = vec![
+            node("A", -3.0, 4.0, Some((true, true))),
+            node("B", 0.0, 4.0, Some((true, true))),
+            node("C", 3.0, 4.0, Some((true, true))),
+            node("D", 0.0, 0.0, None),
+        ];
+        let members = [("A", "D"), ("B", "D"), ("C", "D")];
+        let result = analyze(&model(nodes, &members, vec![down("D", 100.0)])).unwrap();
+        assert!(matches!(result.determinacy, Determinacy::Indeterminate));
+        assert_eq!(result.degree_of_indeterminacy, 1);
+
+        let cos: f64 = 0.8;
+        let share = 1.0 + 2.0 * cos.powi(3);
+        close(force(&result, "B-D"), 100.0 / share);
+        close(force(&result, "A-D"), 100.0 * cos * cos / share);
+        close(force(&result, "C-D"), 100.0 * cos * cos / share);
+
+        // The joint drops by the middle bar's stretch, FL / AE worked in N and m, then in mm
+        let drop = result.displacements.iter().find(|d| d.node == "D").unwrap();
+        close(drop.dx, 0.0);
+        let stretch = (100e3 / share) * 4.0 / (AREA * 1e-6 * MODULUS * 1e6);
+        close(drop.dy, -stretch * 1000.0);
+        let lifted: f64 = result.reactions.iter().map(|r| r.fy).sum();
+        close(lifted, 100.0);
+    }
+}
@@ -0,0 +1,465 @@
+//! Portable project bundles for moving work between machines without the server.
+//!
+//! An archive is a zip holding `manifest.json`, the project and item rows as JSON, and the
+//! project's folder under `files/`. The manifest carries a format version and a SHA-256 per
+//! file; archives from a newer version are refused rather than half-imported.
+//!
+//! A password seals every entry with AES-256, which also authenticates the manifest and so
+//! its optional expiry. An expiring archive is written as format 2, which older apps refuse,
+//! but without a password the expiry is only a stamp anyone can edit.
+
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use rusqlite::{params, OptionalExtension};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, State};
+use zip::read::ZipFile;
+use zip::result::ZipError;
+use zip::write::{FileOptions, SimpleFileOptions};
+use zip::{AesMode, ZipArchive, ZipWriter};
+
+use crate::audit::{self, Change};
+use crate::db::projects::{Item, Project};
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::reports::file_stem;
+use crate::state::AppState;
+use crate::wake_lock;
+
+const FORMAT: &str = "momentum-project";
+const FORMAT_VERSION: u32 = 2;
+// Archives without an expiry stay readable by apps that predate it
+const UNSTAMPED_FORMAT_VERSION: u32 = 1;
+const MANIFEST_ENTRY: &str = "manifest.json";
+const PROJECT_ENTRY: &str = "project.json";
+const ITEMS_ENTRY: &str = "items.json";
+const FILES_PREFIX: &str = "files/";
+const ARCHIVES_DIR_NAME: &str = "archives";
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    format: String,
+    version: u32,
+    app_version: String,
+    exported_at: i64,
+    project_id: String,
+    project_name: String,
+    item_count: usize,
+    files: Vec<ManifestFile>,
+    /// Unix milliseconds after which the importer refuses the archive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ManifestFile {
+    /// Relative to the project folder, with `/` separators.
+    path: String,
+    size: u64,
+    sha256: String,
+}
+
+/// Result of `export_project_archive`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveExport {
+    pub path: PathBuf,
+    pub item_count: usize,
+    pub file_count: usize,
+    pub protected: bool,
+}
+
+/// Result of `import_project_archive`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveImport {
+    pub project: Project,
+    pub item_count: usize,
+    pub file_count: usize,
+    /// The archive's project already existed here, so it was imported as a copy with new ids.
+    pub copied: bool,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// Every file under `dir`, as archive-relative paths with `/` separators
+pub(crate) fn project_files(
+    dir: &Path,
+    relative: &str,
+    files: &mut Vec<(String, PathBuf)>,
+) -> AppResult<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = format!("{relative}{name}");
+        if entry.file_type()?.is_dir() {
+            project_files(&entry.path(), &format!("{path}/"), files)?;
+        } else {
+            files.push((path, entry.path()));
+        }
+    }
+    Ok(())
+}
+
+// Copies while hashing so each file is read once
+pub(crate) fn copy_hashed(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+) -> AppResult<(u64, String)> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+        writer.write_all(&buf[..read])?;
+        size += read as u64;
+    }
+    Ok((size, hex(&hasher.finalize())))
+}
+
+fn write_archive(
+    target: &Path,
+    manifest: &mut Manifest,
+    project: &Project,
+    items: &[Item],
+    files: &[(String, PathBuf)],
+    password: Option<&str>,
+) -> AppResult<()> {
+    let tmp = target.with_extension("zip.tmp");
+    let mut zip = ZipWriter::new(File::create(&tmp)?);
+    let options: FileOptions<'_, '_, ()> = match password {
+        Some(password) => {
+            SimpleFileOptions::default().with_aes_encryption(AesMode::Aes256, password)
+        }
+        None => SimpleFileOptions::default(),
+    };
+    zip.start_file(PROJECT_ENTRY, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(project)?)?;
+    zip.start_file(ITEMS_ENTRY, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(items)?)?;
+    for (path, source) in files {
+        let mut file = File::open(source)?;
+        let large = file.metadata()?.len() >= u32::MAX as u64;
+        zip.start_file(format!("{FILES_PREFIX}{path}"), options.large_file(large))?;
+        let (size, sha256) = copy_hashed(&mut file, &mut zip)?;
+        manifest.files.push(ManifestFile {
+            path: path.clone(),
+            size,
+            sha256,
+        });
+    }
+    // Written last since it lists every file's hash
+    zip.start_file(MANIFEST_ENTRY, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(manifest)?)?;
+    zip.finish()?;
+    fs::rename(&tmp, target)?;
+    Ok(())
+}
+
+fn entry<'a>(
+    zip: &'a mut ZipArchive<BufReader<File>>,
+    name: &str,
+    password: Option<&str>,
+) -> AppResult<ZipFile<'a, BufReader<File>>> {
+    let entry = match password {
+        Some(password) => zip.by_name_decrypt(name, password.as_bytes()),
+        None => zip.by_name(name),
+    };
+    entry.map_err(|err| match err {
+        ZipError::FileNotFound => {
+            AppError::InvalidInput(format!("not a project archive: {name} is missing"))
+        }
+        ZipError::UnsupportedArchive(reason) if reason == ZipError::PASSWORD_REQUIRED => {
+            AppError::PasswordRequired
+        }
+        ZipError::InvalidPassword => AppError::WrongPassword,
+        err => err.into(),
+    })
+}
+
+fn read_json<T: DeserializeOwned>(
+    zip: &mut ZipArchive<BufReader<File>>,
+    name: &str,
+    password: Option<&str>,
+) -> AppResult<T> {
+    Ok(serde_json::from_reader(entry(zip, name, password)?)?)
+}
+
+// Archive paths come from outside, so only plain relative components are accepted
+fn safe_relative(path: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+    path.components()
+        .all(|c| matches!(c, Component::Normal(_)))
+        .then(|| path.to_path_buf())
+}
+
+fn extract_files(
+    zip: &mut ZipArchive<BufReader<File>>,
+    files: &[ManifestFile],
+    staging: &Path,
+    password: Option<&str>,
+) -> AppResult<()> {
+    for file in files {
+        let relative = safe_relative(&file.path).ok_or_else(|| {
+            AppError::InvalidInput(format!("archive file {} has an unsafe path", file.path))
+        })?;
+        let target = staging.join(relative);
+        if let Some(dir) = target.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut entry = entry(zip, &format!("{FILES_PREFIX}{}", file.path), password)?;
+        let (size, sha256) = copy_hashed(&mut entry, &mut File::create(&target)?)?;
+        if size != file.size || sha256 != file.sha256 {
+            return Err(AppError::InvalidInput(format!(
+                "archive file {} is corrupt",
+                file.path
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Bundle a project's rows and files into a zip at `path`, or in the app's archives folder.
+///
+/// A `password` encrypts the archive; `expires_at`, in Unix milliseconds, stamps a date
+/// after which it can no longer be imported.
+#[tauri::command]
+pub async fn export_project_archive(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    db: State<'_, Db>,
+    project_id: String,
+    path: Option<PathBuf>,
+    password: Option<String>,
+    expires_at: Option<i64>,
+) -> AppResult<ArchiveExport> {
+    if password.as_deref().is_some_and(str::is_empty) {
+        return Err(AppError::InvalidInput("the password is empty".into()));
+    }
+    if expires_at.is_some_and(|at| at <= now_ms()) {
+        return Err(AppError::InvalidInput("the expiry is in the past".into()));
+    }
+    let project_dir = state.project_dir(&project_id);
+    let archives_dir = state.data_dir().join(ARCHIVES_DIR_NAME);
+    let app_version = app.package_info().version.to_string();
+    let _awake = wake_lock::hold(&app, "Exporting a project archive");
+
+    db.run(move |conn| {
+        let project = conn
+            .query_row(
+                "SELECT * FROM projects WHERE id = ?1",
+                [&project_id],
+                Project::from_row,
+            )
+            .optional()?
+            .ok_or_else(|| AppError::not_found("project", project_id.clone()))?;
+        let items = conn
+            .prepare("SELECT * FROM items WHERE project_id = ?1 ORDER BY sort_order, name")?
+            .query_map([&project_id], Item::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let mut files = Vec::new();
+        project_files(&project_dir, "", &mut files)?;
+
+        let target = match path {
+            Some(path) => path,
+            None => archives_dir.join(format!("{}-{}.zip", file_stem(&project.name), now_ms())),
+        };
+        if let Some(dir) = target.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut manifest = Manifest {
+            format: FORMAT.into(),
+            version: if expires_at.is_some() {
+                FORMAT_VERSION
+            } else {
+                UNSTAMPED_FORMAT_VERSION
+            },
+            app_version,
+            exported_at: now_ms(),
+            project_id: project.id.clone(),
+            project_name: project.name.clone(),
+            item_count: items.len(),
+            files: Vec::with_capacity(files.len()),
+            expires_at,
+        };
+        write_archive(
+            &target,
+            &mut manifest,
+            &project,
+            &items,
+            &files,
+            password.as_deref(),
+        )?;
+        tracing::info!(
+            %project_id,
+            items = items.len(),
+            files = files.len(),
+            protected = password.is_some(),
+            expires_at,
+            "project archive exported"
+        );
+        Ok(ArchiveExport {
+            path: target,
+            item_count: items.len(),
+            file_count: files.len(),
+            protected: password.is_some(),
+        })
+    })
+    .await
+}
+
+/// Restore a project from an archive made by `export_project_archive`.
+///
+/// A project that already exists here is never overwritten; the archive comes in as a copy.
+/// Fails with `PASSWORD_REQUIRED` when the archive is encrypted and no `password` is given,
+/// and with `EXPIRED` once its expiry has passed.
+#[tauri::command]
+pub async fn import_project_archive(
+    state: State<'_, AppState>,
+    db: State<'_, Db>,
+    path: PathBuf,
+    password: Option<String>,
+) -> AppResult<ArchiveImport> {
+    let projects_dir = state.projects_dir();
+    let actor = audit::actor(&state);
+
+    db.run(move |conn| {
+        let password = password.as_deref();
+        let mut zip = ZipArchive::new(BufReader::new(File::open(&path)?))?;
+        let (manifest, sealed) = {
+            let manifest = entry(&mut zip, MANIFEST_ENTRY, password)?;
+            let sealed = manifest.encrypted();
+            (serde_json::from_reader::<_, Manifest>(manifest)?, sealed)
+        };
+        if manifest.format != FORMAT {
+            return Err(AppError::InvalidInput("not a project archive".into()));
+        }
+        if manifest.version > FORMAT_VERSION {
+            return Err(AppError::InvalidInput(format!(
+                "the archive was made by a newer version of the app (format {}); update to import it",
+                manifest.version
+            )));
+        }
+        if let Some(expired_at) = manifest.expires_at.filter(|&at| at <= now_ms()) {
+            return Err(AppError::Expired { expired_at });
+        }
+        let project_entry_sealed = zip
+            .index_for_name(PROJECT_ENTRY)
+            .and_then(|index| zip.by_index_raw(index).ok().map(|entry| entry.encrypted()))
+            .unwrap_or(false);
+        // A swapped-in plaintext manifest would carry an expiry nobody authenticated
+        if project_entry_sealed && !sealed {
+            return Err(AppError::InvalidInput(
+                "the archive has been tampered with".into(),
+            ));
+        }
+        let mut project: Project = read_json(&mut zip, PROJECT_ENTRY, password)?;
+        let mut items: Vec<Item> = read_json(&mut zip, ITEMS_ENTRY, password)?;
+
+        let exists = conn
+            .query_row("SELECT 1 FROM projects WHERE id = ?1", [&project.id], |_| Ok(()))
+            .optional()?
+            .is_some();
+        if exists {
+            project.id = uuid::Uuid::new_v4().to_string();
+            for item in &mut items {
+                item.id = uuid::Uuid::new_v4().to_string();
+            }
+        }
+        for item in &mut items {
+            item.project_id = project.id.clone();
+        }
+        let now = now_ms();
+        project.updated_at = now;
+
+        // Files land in a staging folder first so a corrupt archive leaves nothing behind
+        let target = projects_dir.join(&project.id);
+        let staging = projects_dir.join(format!(".{}.partial", project.id));
+        let _ = fs::remove_dir_all(&staging);
+        fs::create_dir_all(&staging)?;
+        let result = extract_files(&mut zip, &manifest.files, &staging, password).and_then(|()| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO projects (id, name, description, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    project.id,
+                    project.name,
+                    project.description,
+                    project.created_at,
+                    project.updated_at
+                ],
+            )?;
+            audit::record(
+                &tx,
+                &actor,
+                Change::new("import_project_archive", "project", &project.id).after(&project)?,
+            )?;
+            {
+                let mut insert = tx.prepare(
+                    "INSERT INTO items (id, project_id, name, description, quantity, unit,
+                                        unit_cost, sort_order, created_at, updated_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                )?;
+                for item in &items {
+                    insert.execute(params![
+                        item.id,
+                        project.id,
+                        item.name,
+                        item.description,
+                        item.quantity,
+                        item.unit,
+                        item.unit_cost,
+                        item.sort_order,
+                        item.created_at,
+                        item.updated_at
+                    ])?;
+                    audit::record(
+                        &tx,
+                        &actor,
+                        Change::new("import_project_archive", "item", &item.id).after(item)?,
+                    )?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        });
+        if let Err(err) = result {
+            let _ = fs::remove_dir_all(&staging);
+            return Err(err);
+        }
+        let _ = fs::remove_dir_all(&target);
+        fs::rename(&staging, &target)?;
+
+        tracing::info!(
+            project_id = %project.id,
+            items = items.len(),
+            files = manifest.files.len(),
+            copied = exists,
+            "project archive imported"
+        );
+        Ok(ArchiveImport {
+            project,
+            item_count: items.len(),
+            file_count: manifest.files.len(),
+            copied: exists,
+        })
+    })
+    .await
+}
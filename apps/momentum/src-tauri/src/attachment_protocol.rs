@@ -0,0 +1,244 @@
+//! The `attachment:` URI scheme, which serves attachments to the webview by id.
+//!
+//! `<img>` and `<video>` tags point at `attachment://localhost/<id>`, or
+//! `http://attachment.localhost/<id>` on Windows and Android, instead of a file path from
+//! `convertFileSrc`, so no path into the data folder ever reaches the page. Requests are
+//! only answered for the app's own pages and while the session is unlocked.
+//!
+//! Single byte ranges are honoured so media can seek; an open-ended range is answered with
+//! at most 4 MiB, which players follow up with the next range. The blob hash is the `ETag`.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+use tauri::http::header::{
+    ACCEPT_RANGES, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_NONE_MATCH,
+    RANGE, X_CONTENT_TYPE_OPTIONS,
+};
+use tauri::http::{Method, Request, Response, StatusCode, Uri};
+use tauri::{AppHandle, Manager, UriSchemeContext, UriSchemeResponder, Url, Wry};
+
+use crate::attachments::{self, AttachmentStore};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::idle_lock;
+
+/// The scheme name registered with the webview.
+pub const SCHEME: &str = "attachment";
+
+const MAX_RANGE_BYTES: u64 = 4 * 1024 * 1024;
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Which bytes of a file a request asked for.
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    Full,
+    /// Inclusive start and end.
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+// Multiple ranges and malformed headers are answered with the whole file, as RFC 9110 allows
+fn byte_range(header: Option<&str>, size: u64) -> ByteRange {
+    let Some(spec) = header.and_then(|h| h.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Full;
+    };
+    let Some((start, end)) = spec.split_once('-').filter(|_| !spec.contains(',')) else {
+        return ByteRange::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+    let (start, end) = if start.is_empty() {
+        // A suffix: the last `end` bytes
+        match end.parse::<u64>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(len) => (size.saturating_sub(len), size.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        }
+    } else {
+        let Ok(start) = start.parse::<u64>() else {
+            return ByteRange::Full;
+        };
+        // Checked first, so `size - 1` below cannot wrap
+        if start >= size {
+            return ByteRange::Unsatisfiable;
+        }
+        let end = match end {
+            "" => (size - 1).min(start.saturating_add(MAX_RANGE_BYTES - 1)),
+            end => match end.parse::<u64>() {
+                Ok(end) if end >= start => end.min(size - 1),
+                _ => return ByteRange::Full,
+            },
+        };
+        (start, end)
+    };
+    if size == 0 || start >= size {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial(start, end)
+}
+
+// Built pages load from `tauri://localhost` or `http(s)://tauri.localhost`; debug builds
+// also from the dev server
+pub(crate) fn is_app_origin(app: &AppHandle, url: &Url) -> bool {
+    match url.scheme() {
+        "tauri" => true,
+        "http" | "https" if url.host_str() == Some("tauri.localhost") => true,
+        _ => {
+            cfg!(debug_assertions)
+                && app
+                    .config()
+                    .build
+                    .dev_url
+                    .as_ref()
+                    .is_some_and(|dev| dev.origin() == url.origin())
+        }
+    }
+}
+
+// `attachment://localhost/<id>` and `attachment://<id>` both name the attachment
+fn attachment_id(uri: &Uri) -> Option<String> {
+    let from_path = uri.path().trim_matches('/');
+    let id = match from_path {
+        "" => uri.host().filter(|host| *host != "localhost")?,
+        path => path,
+    };
+    let id = percent_encoding::percent_decode_str(id)
+        .decode_utf8()
+        .ok()?;
+    (!id.contains('/')).then(|| id.into_owned())
+}
+
+fn status(code: StatusCode) -> Response<Vec<u8>> {
+    let mut response = Response::new(Vec::new());
+    *response.status_mut() = code;
+    response
+}
+
+fn error_response(err: &AppError) -> Response<Vec<u8>> {
+    match err {
+        AppError::NotFound { .. } => status(StatusCode::NOT_FOUND),
+        AppError::InvalidInput(_) => status(StatusCode::BAD_REQUEST),
+        AppError::SessionLocked | AppError::DatabaseLocked => status(StatusCode::LOCKED),
+        AppError::Io(io) if io.kind() == std::io::ErrorKind::NotFound => {
+            status(StatusCode::NOT_FOUND)
+        }
+        _ => status(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+fn invalid_response(err: tauri::http::Error) -> AppError {
+    AppError::Internal(format!("cannot build the response: {err}"))
+}
+
+fn read_range(file: &mut File, start: u64, len: u64) -> AppResult<Vec<u8>> {
+    file.seek(SeekFrom::Start(start))?;
+    let mut bytes = Vec::with_capacity(len as usize);
+    file.take(len).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+async fn serve(
+    app: &AppHandle,
+    webview_label: &str,
+    request: &Request<Vec<u8>>,
+) -> AppResult<Response<Vec<u8>>> {
+    let trusted = app
+        .get_webview_window(webview_label)
+        .and_then(|webview| webview.url().ok())
+        .is_some_and(|url| is_app_origin(app, &url));
+    if !trusted {
+        tracing::warn!(webview_label, uri = %request.uri(), "attachment request refused");
+        return Ok(status(StatusCode::FORBIDDEN));
+    }
+    idle_lock::ensure_unlocked(app)?;
+    let head = match *request.method() {
+        Method::GET => false,
+        Method::HEAD => true,
+        _ => return Ok(status(StatusCode::METHOD_NOT_ALLOWED)),
+    };
+    let id = attachment_id(request.uri())
+        .ok_or_else(|| AppError::InvalidInput(format!("no attachment in {}", request.uri())))?;
+    let attachment = attachments::get(&app.state::<Db>(), id).await?;
+
+    let etag = format!("\"{}\"", attachment.hash);
+    let header = |name| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let content_type = attachment
+        .content_type
+        .clone()
+        .or_else(|| attachments::content_type_for(&attachment.file_name).map(str::to_string))
+        .unwrap_or_else(|| DEFAULT_CONTENT_TYPE.into());
+    let builder = Response::builder()
+        .header(ETAG, &etag)
+        // Revalidated every time so a locked session cannot be read from the cache
+        .header(CACHE_CONTROL, "private, no-cache")
+        .header(ACCEPT_RANGES, "bytes")
+        .header(X_CONTENT_TYPE_OPTIONS, "nosniff");
+    if header(IF_NONE_MATCH).is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == etag)) {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Vec::new())
+            .map_err(invalid_response);
+    }
+
+    let range_header = header(RANGE);
+    let handle = app.clone();
+    let (size, range, body) = tauri::async_runtime::spawn_blocking(move || {
+        let path = handle
+            .state::<AttachmentStore>()
+            .plaintext_path(&attachment.hash)?;
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len();
+        let range = byte_range(range_header.as_deref(), size);
+        let body = match (&range, head) {
+            (_, true) | (ByteRange::Unsatisfiable, _) => Vec::new(),
+            (ByteRange::Full, false) => read_range(&mut file, 0, size)?,
+            (ByteRange::Partial(start, end), false) => {
+                read_range(&mut file, *start, end - start + 1)?
+            }
+        };
+        Ok::<_, AppError>((size, range, body))
+    })
+    .await??;
+
+    let response = match range {
+        ByteRange::Full => builder
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, content_type)
+            .header(CONTENT_LENGTH, size),
+        ByteRange::Partial(start, end) => builder
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(CONTENT_TYPE, content_type)
+            .header(CONTENT_RANGE, format!("bytes {start}-{end}/{size}"))
+            .header(CONTENT_LENGTH, end - start + 1),
+        ByteRange::Unsatisfiable => builder
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(CONTENT_RANGE, format!("bytes */{size}")),
+    };
+    response.body(body).map_err(invalid_response)
+}
+
+/// Answer one request for the scheme; registered with the builder in `run`.
+pub fn handle(
+    ctx: UriSchemeContext<'_, Wry>,
+    request: Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    let app = ctx.app_handle().clone();
+    let webview_label = ctx.webview_label().to_string();
+    tauri::async_runtime::spawn(async move {
+        let response = match serve(&app, &webview_label, &request).await {
+            Ok(response) => response,
+            Err(err) => {
+                tracing::debug!(%err, uri = %request.uri(), "attachment request failed");
+                error_response(&err)
+            }
+        };
+        responder.respond(response);
+    });
+}
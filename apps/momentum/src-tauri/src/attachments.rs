@@ -0,0 +1,781 @@
+//! Content-addressed storage for files attached to projects.
+//!
+//! Each distinct file is stored once under `data_dir/attachments/<ab>/<sha256>` and shared by
+//! every attachment row with the same hash. Triggers keep a reference count per blob, and
+//! the `attachments.gc` job deletes blobs nothing references any more.
+//!
+//! Blob files are written, and deleted by the collector, only inside an immediate write
+//! transaction, so an add can never reference a blob the collector is removing.
+//!
+//! With encryption on, blobs are stored encrypted, still named by the hash of their plaintext,
+//! and anything handed to another app or the webview is a decrypted copy under `open/`.
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rusqlite::{params, OptionalExtension, Row, Transaction, TransactionBehavior};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_opener::OpenerExt;
+
+use crate::audit::{self, Change};
+use crate::db::{now_ms, Db};
+use crate::encryption::{self, DataKey, KeyHandle};
+use crate::error::{AppError, AppResult};
+use crate::jobs::Scheduler;
+use crate::state::AppState;
+
+/// Job kind that removes unreferenced blobs.
+pub const GC_JOB: &str = "attachments.gc";
+const GC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+// Staged files younger than this may belong to an add still in progress
+const STAGING_GRACE_MS: i64 = 60 * 60 * 1000;
+
+const STORE_DIR_NAME: &str = "attachments";
+const STAGING_DIR_NAME: &str = "staging";
+const OPEN_DIR_NAME: &str = "open";
+
+/// A file attached to a project.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub id: String,
+    pub project_id: String,
+    /// SHA-256 of the contents.
+    pub hash: String,
+    pub file_name: String,
+    pub content_type: Option<String>,
+    pub size: i64,
+    pub created_at: i64,
+    /// The attachment this is a later version of.
+    pub previous_id: Option<String>,
+    /// 1 for an original, counting up with each later version.
+    pub version: i64,
+}
+
+impl Attachment {
+    pub(crate) fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            project_id: row.get("project_id")?,
+            hash: row.get("hash")?,
+            file_name: row.get("file_name")?,
+            content_type: row.get("content_type")?,
+            size: row.get("size")?,
+            created_at: row.get("created_at")?,
+            previous_id: row.get("previous_id")?,
+            version: row.get("version")?,
+        })
+    }
+}
+
+const SELECT_ATTACHMENT: &str = "SELECT a.*, b.size FROM attachments a
+     JOIN attachment_blobs b ON b.hash = a.hash";
+
+/// What re-hashing a stored blob found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BlobStatus {
+    Intact,
+    Missing,
+    /// The contents no longer match the hash, or an encrypted blob fails to decrypt.
+    Corrupted,
+}
+
+/// Contents copied into the store's staging folder and hashed, not yet referenced.
+///
+/// Inserting a clone of an already inserted value just adds another reference.
+#[derive(Clone)]
+pub(crate) struct Staged {
+    path: PathBuf,
+    pub(crate) hash: String,
+    size: i64,
+}
+
+/// Metadata for a new attachment row.
+pub(crate) struct NewAttachment {
+    pub(crate) id: Option<String>,
+    pub(crate) project_id: String,
+    pub(crate) file_name: String,
+    pub(crate) content_type: Option<String>,
+    pub(crate) created_at: i64,
+    /// Set when this is a later version of an existing attachment.
+    pub(crate) previous_id: Option<String>,
+}
+
+/// Blob folder handle registered in app state.
+pub struct AttachmentStore {
+    dir: PathBuf,
+    // Set while encryption is on and unlocked
+    key: KeyHandle,
+}
+
+/// The store's folder under `data_dir`.
+pub(crate) fn store_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join(STORE_DIR_NAME)
+}
+
+/// Where the blob with `hash` lives in the store at `dir`; two-character fan-out keeps
+/// folders small.
+pub(crate) fn blob_path_in(dir: &Path, hash: &str) -> PathBuf {
+    dir.join(&hash[..2]).join(hash)
+}
+
+/// Open the store under `data_dir/attachments`.
+pub(crate) fn init(data_dir: &Path, key: KeyHandle) -> AppResult<AttachmentStore> {
+    let dir = store_dir(data_dir);
+    fs::create_dir_all(dir.join(STAGING_DIR_NAME))?;
+    Ok(AttachmentStore { dir, key })
+}
+
+// Hashes and counts the plaintext on its way into the encryptor
+struct Hashing<'a, R> {
+    inner: &'a mut R,
+    hasher: Sha256,
+    size: i64,
+}
+
+impl<R: Read> Read for Hashing<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        self.size += read as i64;
+        Ok(read)
+    }
+}
+
+fn set_writable(path: &Path) -> std::io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    fs::set_permissions(path, permissions)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Best-guess MIME type from a file name.
+pub(crate) fn content_type_for(file_name: &str) -> Option<&'static str> {
+    let extension = Path::new(file_name)
+        .extension()?
+        .to_str()?
+        .to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "pdf" => "application/pdf",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "heic" => "image/heic",
+        "tif" | "tiff" => "image/tiff",
+        "txt" => "text/plain",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "zip" => "application/zip",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "xls" => "application/vnd.ms-excel",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "dxf" => "image/vnd.dxf",
+        "dwg" => "image/vnd.dwg",
+        _ => return None,
+    })
+}
+
+impl AttachmentStore {
+    /// Where the blob with `hash` lives.
+    pub(crate) fn blob_path(&self, hash: &str) -> PathBuf {
+        blob_path_in(&self.dir, hash)
+    }
+
+    /// Copy `reader` into staging while hashing it, encrypted when a key is set.
+    pub(crate) fn stage(&self, reader: &mut impl Read) -> AppResult<Staged> {
+        let path = self
+            .dir
+            .join(STAGING_DIR_NAME)
+            .join(uuid::Uuid::new_v4().to_string());
+        let mut file = File::create(&path)?;
+        let mut reader = Hashing {
+            inner: reader,
+            hasher: Sha256::new(),
+            size: 0,
+        };
+        let result = (|| -> AppResult<()> {
+            match self.key.get() {
+                Some(key) => encryption::encrypt_stream(&key, &mut reader, &mut file)?,
+                None => {
+                    std::io::copy(&mut reader, &mut file)?;
+                }
+            }
+            file.sync_all()?;
+            Ok(())
+        })();
+        if let Err(err) = result {
+            let _ = fs::remove_file(&path);
+            return Err(err);
+        }
+        Ok(Staged {
+            path,
+            hash: hex(&reader.hasher.finalize()),
+            size: reader.size,
+        })
+    }
+
+    /// Write the plaintext of the blob with `hash` to a new writable file at `target`.
+    pub(crate) fn copy_out(&self, hash: &str, target: &Path) -> AppResult<()> {
+        if let Some(dir) = target.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let blob = self.blob_path(hash);
+        if encryption::is_encrypted_file(&blob) {
+            let key = self.key.get().ok_or(AppError::DatabaseLocked)?;
+            let mut out = File::create(target)?;
+            let result = encryption::decrypt_stream(&key, &mut File::open(&blob)?, &mut out);
+            if result.is_err() {
+                let _ = fs::remove_file(target);
+            }
+            return result;
+        }
+        fs::copy(&blob, target)?;
+        // fs::copy carries the blob's read-only flag over
+        set_writable(target)?;
+        Ok(())
+    }
+
+    /// A file holding the plaintext of the blob with `hash`, for reading at any offset.
+    ///
+    /// That is the blob itself unless it is encrypted; otherwise a decrypted copy under
+    /// `open/`, named by hash so it is never a copy the user opened and changed.
+    pub(crate) fn plaintext_path(&self, hash: &str) -> AppResult<PathBuf> {
+        let blob = self.blob_path(hash);
+        if !encryption::is_encrypted_file(&blob) {
+            return Ok(blob);
+        }
+        let copy = self.dir.join(OPEN_DIR_NAME).join(hash);
+        if copy.is_file() {
+            return Ok(copy);
+        }
+        let tmp = self
+            .dir
+            .join(OPEN_DIR_NAME)
+            .join(format!("{hash}.{}.tmp", uuid::Uuid::new_v4()));
+        self.copy_out(hash, &tmp)?;
+        if let Err(err) = fs::rename(&tmp, &copy) {
+            let _ = fs::remove_file(&tmp);
+            return Err(err.into());
+        }
+        Ok(copy)
+    }
+
+    /// Write the plaintext of the blob with `hash` into `writer`.
+    pub(crate) fn read_into(&self, hash: &str, writer: &mut impl Write) -> AppResult<()> {
+        let blob = self.blob_path(hash);
+        let mut file = File::open(&blob)?;
+        if encryption::is_encrypted_file(&blob) {
+            let key = self.key.get().ok_or(AppError::DatabaseLocked)?;
+            return encryption::decrypt_stream(&key, &mut file, writer);
+        }
+        std::io::copy(&mut file, writer)?;
+        Ok(())
+    }
+
+    /// Store `reader` at `target` the way blobs are kept: encrypted when a key is set.
+    pub(crate) fn seal_into(&self, reader: &mut impl Read, target: &Path) -> AppResult<()> {
+        if let Some(dir) = target.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = File::create(target)?;
+        match self.key.get() {
+            Some(key) => encryption::encrypt_stream(&key, reader, &mut file)?,
+            None => {
+                std::io::copy(reader, &mut file)?;
+            }
+        }
+        file.sync_all()?;
+        let mut permissions = file.metadata()?.permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(target, permissions)?;
+        Ok(())
+    }
+
+    /// Encrypt every blob still stored in plaintext; returns how many were.
+    pub(crate) fn encrypt_existing(&self, key: &DataKey) -> AppResult<usize> {
+        let mut encrypted = 0;
+        for fan_out in fs::read_dir(&self.dir)? {
+            let fan_out = fan_out?;
+            let name = fan_out.file_name();
+            if name == STAGING_DIR_NAME || name == OPEN_DIR_NAME || !fan_out.path().is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(fan_out.path())? {
+                let blob = entry?.path();
+                if encryption::is_encrypted_file(&blob) {
+                    continue;
+                }
+                let tmp = self
+                    .dir
+                    .join(STAGING_DIR_NAME)
+                    .join(uuid::Uuid::new_v4().to_string());
+                let result = (|| -> AppResult<()> {
+                    let mut out = File::create(&tmp)?;
+                    encryption::encrypt_stream(key, &mut File::open(&blob)?, &mut out)?;
+                    out.sync_all()?;
+                    drop(out);
+                    // Windows refuses to replace a read-only file
+                    set_writable(&blob)?;
+                    fs::rename(&tmp, &blob)?;
+                    let mut permissions = fs::metadata(&blob)?.permissions();
+                    permissions.set_readonly(true);
+                    fs::set_permissions(&blob, permissions)?;
+                    Ok(())
+                })();
+                if let Err(err) = result {
+                    let _ = fs::remove_file(&tmp);
+                    return Err(err);
+                }
+                encrypted += 1;
+            }
+        }
+        Ok(encrypted)
+    }
+
+    /// Move staged contents into place, if not already stored, and add a row referencing them.
+    ///
+    /// `tx` must already hold the write lock; see the module docs.
+    pub(crate) fn insert(
+        &self,
+        tx: &Transaction<'_>,
+        staged: Staged,
+        new: NewAttachment,
+    ) -> AppResult<Attachment> {
+        let blob = self.blob_path(&staged.hash);
+        if blob.exists() {
+            let _ = fs::remove_file(&staged.path);
+        } else {
+            if let Some(dir) = blob.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::rename(&staged.path, &blob)?;
+            // Opened files are copies, so nothing should ever write to a blob
+            let mut permissions = fs::metadata(&blob)?.permissions();
+            permissions.set_readonly(true);
+            fs::set_permissions(&blob, permissions)?;
+        }
+        tx.execute(
+            "INSERT INTO attachment_blobs (hash, size, created_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(hash) DO NOTHING",
+            params![staged.hash, staged.size, now_ms()],
+        )?;
+        let id = new.id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let version = match &new.previous_id {
+            Some(previous) => {
+                tx.query_row(
+                    "SELECT version FROM attachments WHERE id = ?1",
+                    [previous],
+                    |row| row.get::<_, i64>(0),
+                )
+                .optional()?
+                .ok_or_else(|| AppError::not_found("attachment", previous))?
+                    + 1
+            }
+            None => 1,
+        };
+        tx.execute(
+            "INSERT INTO attachments
+                 (id, project_id, hash, file_name, content_type, created_at, previous_id, version)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                id,
+                new.project_id,
+                staged.hash,
+                new.file_name,
+                new.content_type,
+                new.created_at,
+                new.previous_id,
+                version
+            ],
+        )?;
+        Ok(Attachment {
+            id,
+            project_id: new.project_id,
+            hash: staged.hash,
+            file_name: new.file_name,
+            content_type: new.content_type,
+            size: staged.size,
+            created_at: new.created_at,
+            previous_id: new.previous_id,
+            version,
+        })
+    }
+
+    /// Re-hash the plaintext of the blob with `hash`.
+    ///
+    /// Fails with [`AppError::DatabaseLocked`] for an encrypted blob while locked.
+    pub(crate) fn check(&self, hash: &str) -> AppResult<BlobStatus> {
+        let mut hasher = Sha256::new();
+        match self.read_into(hash, &mut hasher) {
+            Ok(()) => {}
+            Err(AppError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(BlobStatus::Missing)
+            }
+            Err(AppError::Encryption(_)) => return Ok(BlobStatus::Corrupted),
+            Err(err) => return Err(err),
+        }
+        Ok(if hex(&hasher.finalize()) == hash {
+            BlobStatus::Intact
+        } else {
+            BlobStatus::Corrupted
+        })
+    }
+
+    /// Put staged contents in place of the missing or damaged blob with the same hash.
+    pub(crate) fn replace(&self, conn: &mut rusqlite::Connection, staged: Staged) -> AppResult<()> {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let referenced = tx
+            .query_row(
+                "SELECT ref_count > 0 FROM attachment_blobs WHERE hash = ?1",
+                [&staged.hash],
+                |row| row.get::<_, bool>(0),
+            )
+            .optional()?
+            .unwrap_or(false);
+        if !referenced {
+            let hash = staged.hash.clone();
+            self.discard(staged);
+            return Err(AppError::not_found("attachment file", hash));
+        }
+        let blob = self.blob_path(&staged.hash);
+        if blob.exists() {
+            // Windows refuses to replace a read-only file
+            set_writable(&blob)?;
+        } else if let Some(dir) = blob.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::rename(&staged.path, &blob)?;
+        let mut permissions = fs::metadata(&blob)?.permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&blob, permissions)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Discard staged contents that will not be inserted.
+    pub(crate) fn discard(&self, staged: Staged) {
+        let _ = fs::remove_file(staged.path);
+    }
+
+    fn collect(&self, conn: &mut rusqlite::Connection) -> AppResult<usize> {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let hashes = tx
+            .prepare("SELECT hash FROM attachment_blobs WHERE ref_count <= 0")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for hash in &hashes {
+            let blob = self.blob_path(hash);
+            match fs::remove_file(&blob) {
+                Ok(()) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                // Read-only files cannot be deleted on Windows
+                Err(_) => {
+                    set_writable(&blob)?;
+                    fs::remove_file(&blob)?;
+                }
+            }
+            tx.execute("DELETE FROM attachment_blobs WHERE hash = ?1", [hash])?;
+        }
+        tx.commit()?;
+
+        // Leftovers from adds that crashed before inserting, and copies made for opening
+        let cutoff = now_ms() - STAGING_GRACE_MS;
+        for dir in [STAGING_DIR_NAME, OPEN_DIR_NAME] {
+            let Ok(entries) = fs::read_dir(self.dir.join(dir)) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let stale = entry
+                    .metadata()
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .is_some_and(|t| (t.as_millis() as i64) < cutoff);
+                if stale {
+                    let path = entry.path();
+                    let _ = if path.is_dir() {
+                        fs::remove_dir_all(path)
+                    } else {
+                        fs::remove_file(path)
+                    };
+                }
+            }
+        }
+        Ok(hashes.len())
+    }
+}
+
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(GC_JOB, Some(GC_INTERVAL), |app| async move {
+        let db = app.state::<Db>().inner().clone();
+        let app = app.clone();
+        let removed = db
+            .run(move |conn| app.state::<AttachmentStore>().collect(conn))
+            .await?;
+        if removed > 0 {
+            tracing::info!(removed, "unreferenced attachment blobs removed");
+        }
+        Ok(())
+    });
+}
+
+/// A project's attachments, oldest first.
+pub(crate) fn project_attachments(
+    conn: &rusqlite::Connection,
+    project_id: &str,
+) -> rusqlite::Result<Vec<Attachment>> {
+    let mut stmt = conn.prepare(&format!(
+        "{SELECT_ATTACHMENT} WHERE a.project_id = ?1 ORDER BY a.created_at"
+    ))?;
+    let rows = stmt.query_map([project_id], Attachment::from_row)?;
+    rows.collect()
+}
+
+/// Every attachment sharing the blob with `hash`, oldest first.
+pub(crate) fn attachments_with_hash(
+    conn: &rusqlite::Connection,
+    hash: &str,
+) -> rusqlite::Result<Vec<Attachment>> {
+    let mut stmt = conn.prepare(&format!(
+        "{SELECT_ATTACHMENT} WHERE a.hash = ?1 ORDER BY a.created_at"
+    ))?;
+    let rows = stmt.query_map([hash], Attachment::from_row)?;
+    rows.collect()
+}
+
+pub(crate) async fn get(db: &Db, id: String) -> AppResult<Attachment> {
+    db.run(move |conn| {
+        conn.query_row(
+            &format!("{SELECT_ATTACHMENT} WHERE a.id = ?1"),
+            [&id],
+            Attachment::from_row,
+        )
+        .optional()?
+        .ok_or_else(|| AppError::not_found("attachment", id))
+    })
+    .await
+}
+
+/// Attach a copy of the file at `path` to a project.
+pub(crate) fn add_file(
+    conn: &mut rusqlite::Connection,
+    store: &AttachmentStore,
+    project_id: String,
+    path: &Path,
+) -> AppResult<Attachment> {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .ok_or_else(|| AppError::InvalidInput(format!("{} is not a file", path.display())))?;
+    // Hashing a large file happens before the write lock is taken
+    let staged = store.stage(&mut File::open(path)?)?;
+    add_staged(conn, store, project_id, file_name, None, staged)
+}
+
+/// Attach `bytes` to a project as a file named `file_name`, such as a pasted image.
+pub(crate) fn add_bytes(
+    conn: &mut rusqlite::Connection,
+    store: &AttachmentStore,
+    project_id: String,
+    file_name: String,
+    bytes: &[u8],
+) -> AppResult<Attachment> {
+    let staged = store.stage(&mut std::io::Cursor::new(bytes))?;
+    add_staged(conn, store, project_id, file_name, None, staged)
+}
+
+/// Attach the file at `path` as the next version of `previous`, keeping its name.
+///
+/// Returns `None`, storing nothing, when the contents are unchanged.
+pub(crate) fn add_version(
+    conn: &mut rusqlite::Connection,
+    store: &AttachmentStore,
+    previous: &Attachment,
+    path: &Path,
+) -> AppResult<Option<Attachment>> {
+    let staged = store.stage(&mut File::open(path)?)?;
+    if staged.hash == previous.hash {
+        store.discard(staged);
+        return Ok(None);
+    }
+    add_staged(
+        conn,
+        store,
+        previous.project_id.clone(),
+        previous.file_name.clone(),
+        Some(previous.id.clone()),
+        staged,
+    )
+    .map(Some)
+}
+
+fn add_staged(
+    conn: &mut rusqlite::Connection,
+    store: &AttachmentStore,
+    project_id: String,
+    file_name: String,
+    previous_id: Option<String>,
+    staged: Staged,
+) -> AppResult<Attachment> {
+    let tx = match conn.transaction_with_behavior(TransactionBehavior::Immediate) {
+        Ok(tx) => tx,
+        Err(err) => {
+            store.discard(staged);
+            return Err(err.into());
+        }
+    };
+    let exists = tx
+        .query_row(
+            "SELECT 1 FROM projects WHERE id = ?1",
+            [&project_id],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    if !exists {
+        store.discard(staged);
+        return Err(AppError::not_found("project", project_id));
+    }
+    let attachment = store.insert(
+        &tx,
+        staged,
+        NewAttachment {
+            id: None,
+            content_type: content_type_for(&file_name).map(str::to_string),
+            project_id,
+            file_name,
+            created_at: now_ms(),
+            previous_id,
+        },
+    )?;
+    tx.commit()?;
+    Ok(attachment)
+}
+
+/// Attach a copy of the file at `path` to a project.
+#[tauri::command]
+pub async fn attachment_add(
+    app: AppHandle,
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    project_id: String,
+    path: PathBuf,
+) -> AppResult<Attachment> {
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let attachment = add_file(conn, &app.state::<AttachmentStore>(), project_id, &path)?;
+        audit::record_now(
+            conn,
+            &actor,
+            Change::new("attachment_add", "attachment", &attachment.id).after(&attachment)?,
+        )?;
+        Ok(attachment)
+    })
+    .await
+}
+
+/// List a project's attachments.
+#[tauri::command]
+pub async fn list_attachments(db: State<'_, Db>, project_id: String) -> AppResult<Vec<Attachment>> {
+    db.run(move |conn| Ok(project_attachments(conn, &project_id)?))
+        .await
+}
+
+/// Detach a file; its contents are deleted later if nothing else references them.
+#[tauri::command]
+pub async fn attachment_remove(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    id: String,
+) -> AppResult<()> {
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let before = tx
+            .query_row(
+                "SELECT * FROM attachments WHERE id = ?1",
+                [&id],
+                Attachment::from_row,
+            )
+            .optional()?
+            .ok_or_else(|| AppError::not_found("attachment", &id))?;
+        tx.execute("DELETE FROM attachments WHERE id = ?1", [&id])?;
+        audit::record(
+            &tx,
+            &actor,
+            Change::new("attachment_remove", "attachment", &id).before(&before)?,
+        )?;
+        tx.commit()?;
+        Ok(())
+    })
+    .await
+}
+
+/// Path of the stored contents, for reading in place such as through `convertFileSrc`.
+///
+/// The file is shared by every identical attachment and must not be modified. With
+/// encryption on it is a decrypted copy instead, removed by the next collection after an hour.
+#[tauri::command]
+pub async fn attachment_path(
+    app: AppHandle,
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    id: String,
+) -> AppResult<PathBuf> {
+    let attachment = get(&db, id).await?;
+    let blob = app.state::<AttachmentStore>().blob_path(&attachment.hash);
+    let copy = open_copy_path(&state, &attachment);
+    tauri::async_runtime::spawn_blocking(move || {
+        if !encryption::is_encrypted_file(&blob) {
+            return Ok(blob);
+        }
+        app.state::<AttachmentStore>()
+            .copy_out(&attachment.hash, &copy)?;
+        Ok(copy)
+    })
+    .await?
+}
+
+fn open_copy_path(state: &AppState, attachment: &Attachment) -> PathBuf {
+    state
+        .data_dir()
+        .join(STORE_DIR_NAME)
+        .join(OPEN_DIR_NAME)
+        .join(&attachment.id)
+        .join(&attachment.file_name)
+}
+
+/// Open an attachment in its default app.
+///
+/// The app gets a copy under the original file name, so edits never reach the shared blob.
+#[tauri::command]
+pub async fn attachment_open(
+    app: AppHandle,
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    id: String,
+) -> AppResult<PathBuf> {
+    let attachment = get(&db, id).await?;
+    let copy = open_copy_path(&state, &attachment);
+    let (handle, target) = (app.clone(), copy.clone());
+    tauri::async_runtime::spawn_blocking(move || {
+        handle
+            .state::<AttachmentStore>()
+            .copy_out(&attachment.hash, &target)
+    })
+    .await??;
+    app.opener()
+        .open_path(copy.to_string_lossy(), None::<&str>)
+        .map_err(|e| AppError::Internal(format!("failed to open attachment: {e}")))?;
+    Ok(copy)
+}
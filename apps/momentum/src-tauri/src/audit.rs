@@ -0,0 +1,670 @@
+//! Append-only, hash-chained log of changes to project data.
+//!
+//! Every entry's hash covers its fields and the previous entry's hash, so rewriting or
+//! removing a row breaks the chain from there on, and triggers refuse UPDATE and DELETE on
+//! the table outright. Every pooled connection also carries an authorizer that refuses
+//! statements taking those triggers or the table away, so raw SQL from the frontend cannot
+//! either. A change is recorded in the transaction that makes it when there is one, so the
+//! log never claims a change that was rolled back.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use rusqlite::hooks::{AuthAction, AuthContext, Authorization};
+use rusqlite::{params, Connection, OptionalExtension, Row, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+
+const TABLE: &str = "audit_log";
+// Stands in for the previous hash of the very first entry
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+const DEFAULT_PAGE_SIZE: u32 = 100;
+const MAX_PAGE_SIZE: u32 = 1000;
+
+/// One recorded change.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub seq: i64,
+    pub at: i64,
+    /// Email of the signed-in user, or `local:<os user>` on a machine never signed in.
+    pub actor: String,
+    pub command: String,
+    pub entity: String,
+    pub entity_id: String,
+    /// The entity before the change; absent for creations.
+    pub before: Option<Value>,
+    /// The entity after the change; absent for deletions.
+    pub after: Option<Value>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+struct StoredEntry {
+    entry: AuditEntry,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+impl StoredEntry {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let before: Option<String> = row.get("before")?;
+        let after: Option<String> = row.get("after")?;
+        let parse = |raw: &Option<String>| {
+            raw.as_deref()
+                .map(|raw| serde_json::from_str(raw).unwrap_or(Value::String(raw.into())))
+        };
+        Ok(Self {
+            entry: AuditEntry {
+                seq: row.get("seq")?,
+                at: row.get("at")?,
+                actor: row.get("actor")?,
+                command: row.get("command")?,
+                entity: row.get("entity")?,
+                entity_id: row.get("entity_id")?,
+                before: parse(&before),
+                after: parse(&after),
+                prev_hash: row.get("prev_hash")?,
+                hash: row.get("hash")?,
+            },
+            before,
+            after,
+        })
+    }
+
+    // Recomputed from the stored text, which is exactly what was hashed
+    fn expected_hash(&self) -> String {
+        let entry = &self.entry;
+        entry_hash(
+            &entry.prev_hash,
+            entry.seq,
+            entry.at,
+            &entry.actor,
+            &entry.command,
+            &entry.entity,
+            &entry.entity_id,
+            self.before.as_deref(),
+            self.after.as_deref(),
+        )
+    }
+}
+
+/// A change about to be recorded.
+pub(crate) struct Change {
+    command: &'static str,
+    entity: &'static str,
+    entity_id: String,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+impl Change {
+    pub fn new(command: &'static str, entity: &'static str, entity_id: impl Into<String>) -> Self {
+        Self {
+            command,
+            entity,
+            entity_id: entity_id.into(),
+            before: None,
+            after: None,
+        }
+    }
+
+    pub fn before(mut self, value: &impl Serialize) -> AppResult<Self> {
+        self.before = Some(serde_json::to_string(value)?);
+        Ok(self)
+    }
+
+    pub fn after(mut self, value: &impl Serialize) -> AppResult<Self> {
+        self.after = Some(serde_json::to_string(value)?);
+        Ok(self)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// Length-prefixed so no two different entries can produce the same input
+#[allow(clippy::too_many_arguments)]
+fn entry_hash(
+    prev_hash: &str,
+    seq: i64,
+    at: i64,
+    actor: &str,
+    command: &str,
+    entity: &str,
+    entity_id: &str,
+    before: Option<&str>,
+    after: Option<&str>,
+) -> String {
+    let mut hasher = Sha256::new();
+    let mut field = |value: Option<&[u8]>| match value {
+        Some(bytes) => {
+            hasher.update((bytes.len() as u64).to_be_bytes());
+            hasher.update(bytes);
+        }
+        None => hasher.update(u64::MAX.to_be_bytes()),
+    };
+    field(Some(prev_hash.as_bytes()));
+    field(Some(&seq.to_be_bytes()[..]));
+    field(Some(&at.to_be_bytes()[..]));
+    field(Some(actor.as_bytes()));
+    field(Some(command.as_bytes()));
+    field(Some(entity.as_bytes()));
+    field(Some(entity_id.as_bytes()));
+    field(before.map(str::as_bytes));
+    field(after.map(str::as_bytes));
+    hex(&hasher.finalize())
+}
+
+/// Who changes are attributed to.
+pub(crate) fn actor(state: &AppState) -> String {
+    state.user().unwrap_or_else(|| {
+        let os_user = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_else(|_| "unknown".into());
+        format!("local:{os_user}")
+    })
+}
+
+// Appending to the log is the only change to it that gets through. Databases attached under
+// other names, e.g. for an encrypted copy, are not guarded
+fn authorize(ctx: AuthContext<'_>) -> Authorization {
+    let guarded = matches!(ctx.database_name, None | Some("main" | "temp"));
+    let refused = match ctx.action {
+        AuthAction::Update { table_name, .. }
+        | AuthAction::Delete { table_name }
+        | AuthAction::DropTable { table_name }
+        | AuthAction::AlterTable { table_name, .. }
+        | AuthAction::CreateTrigger { table_name, .. }
+        | AuthAction::CreateTempTrigger { table_name, .. }
+        | AuthAction::DropTrigger { table_name, .. }
+        | AuthAction::DropTempTrigger { table_name, .. } => {
+            guarded && table_name.eq_ignore_ascii_case(TABLE)
+        }
+        // Would allow editing the schema, triggers included, as plain rows
+        AuthAction::Pragma {
+            pragma_name,
+            pragma_value: Some(_),
+        } => pragma_name.eq_ignore_ascii_case("writable_schema"),
+        _ => false,
+    };
+    if refused {
+        Authorization::Deny
+    } else {
+        Authorization::Allow
+    }
+}
+
+/// Refuse, on `conn`, any statement that would rewrite the log or remove its triggers.
+pub(crate) fn guard(conn: &Connection) -> rusqlite::Result<()> {
+    conn.authorizer(Some(authorize))
+}
+
+/// Lift [`guard`], for migrations, which are what create the table and its triggers.
+pub(crate) fn unguard(conn: &Connection) -> rusqlite::Result<()> {
+    conn.authorizer(None::<fn(AuthContext<'_>) -> Authorization>)
+}
+
+/// Append `change` as part of the caller's transaction.
+///
+/// The caller must already hold the write lock, e.g. by having written the change itself, so
+/// two entries can never claim the same place in the chain.
+pub(crate) fn record(conn: &Connection, actor: &str, change: Change) -> AppResult<()> {
+    let last: Option<(i64, String)> = conn
+        .query_row(
+            "SELECT seq, hash FROM audit_log ORDER BY seq DESC LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let (seq, prev_hash) = match last {
+        Some((seq, hash)) => (seq + 1, hash),
+        None => (1, GENESIS_HASH.to_string()),
+    };
+    let at = now_ms();
+    let hash = entry_hash(
+        &prev_hash,
+        seq,
+        at,
+        actor,
+        change.command,
+        change.entity,
+        &change.entity_id,
+        change.before.as_deref(),
+        change.after.as_deref(),
+    );
+    conn.execute(
+        "INSERT INTO audit_log (seq, at, actor, command, entity, entity_id, before, after,
+                                prev_hash, hash)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            seq,
+            at,
+            actor,
+            change.command,
+            change.entity,
+            change.entity_id,
+            change.before,
+            change.after,
+            prev_hash,
+            hash
+        ],
+    )?;
+    Ok(())
+}
+
+/// Append `change` in a transaction of its own, for changes that already committed.
+pub(crate) fn record_now(conn: &mut Connection, actor: &str, change: Change) -> AppResult<()> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    record(&tx, actor, change)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Narrows [`query_audit_log`]; every field is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AuditFilter {
+    pub entity: Option<String>,
+    pub entity_id: Option<String>,
+    pub actor: Option<String>,
+    /// Unix milliseconds, inclusive.
+    pub since: Option<i64>,
+    /// Unix milliseconds, exclusive.
+    pub until: Option<i64>,
+    /// Return entries older than this sequence number, to page backwards.
+    pub before_seq: Option<i64>,
+    pub limit: Option<u32>,
+}
+
+/// Entries matching `filter`, newest first.
+#[tauri::command]
+pub async fn query_audit_log(db: State<'_, Db>, filter: AuditFilter) -> AppResult<Vec<AuditEntry>> {
+    let limit = filter
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    db.run(move |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT * FROM audit_log
+             WHERE (?1 IS NULL OR entity = ?1)
+               AND (?2 IS NULL OR entity_id = ?2)
+               AND (?3 IS NULL OR actor = ?3)
+               AND (?4 IS NULL OR at >= ?4)
+               AND (?5 IS NULL OR at < ?5)
+               AND (?6 IS NULL OR seq < ?6)
+             ORDER BY seq DESC
+             LIMIT ?7",
+        )?;
+        let rows = stmt.query_map(
+            params![
+                filter.entity,
+                filter.entity_id,
+                filter.actor,
+                filter.since,
+                filter.until,
+                filter.before_seq,
+                limit
+            ],
+            StoredEntry::from_row,
+        )?;
+        Ok(rows
+            .map(|row| row.map(|stored| stored.entry))
+            .collect::<rusqlite::Result<_>>()?)
+    })
+    .await
+}
+
+/// Result of [`export_audit_log`].
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditExport {
+    pub path: PathBuf,
+    pub entries: u64,
+    /// Hash of the newest entry, to compare against a later export.
+    pub head_hash: Option<String>,
+    /// Whether every entry's hash and link to its predecessor checked out.
+    pub intact: bool,
+    /// First entry whose hash or link does not match, when not intact.
+    pub first_broken_seq: Option<i64>,
+}
+
+// The parsed values may not re-serialize byte for byte, so the hashed text comes along
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportLine<'a> {
+    #[serde(flatten)]
+    entry: &'a AuditEntry,
+    before_text: Option<&'a str>,
+    after_text: Option<&'a str>,
+}
+
+fn write_export(conn: &Connection, path: &Path) -> AppResult<AuditExport> {
+    let partial = path.with_extension("partial");
+    let mut out = BufWriter::new(File::create(&partial)?);
+    let mut stmt = conn.prepare("SELECT * FROM audit_log ORDER BY seq")?;
+    let mut rows = stmt.query([])?;
+    let mut summary = AuditExport {
+        path: path.to_path_buf(),
+        entries: 0,
+        head_hash: None,
+        intact: true,
+        first_broken_seq: None,
+    };
+    let mut prev_hash = GENESIS_HASH.to_string();
+    while let Some(row) = rows.next()? {
+        let stored = StoredEntry::from_row(row)?;
+        let entry = &stored.entry;
+        if summary.intact && (entry.prev_hash != prev_hash || entry.hash != stored.expected_hash())
+        {
+            summary.intact = false;
+            summary.first_broken_seq = Some(entry.seq);
+        }
+        let line = ExportLine {
+            entry,
+            before_text: stored.before.as_deref(),
+            after_text: stored.after.as_deref(),
+        };
+        serde_json::to_writer(&mut out, &line)?;
+        out.write_all(b"\n")?;
+        prev_hash = entry.hash.clone();
+        summary.entries += 1;
+    }
+    summary.head_hash = (summary.entries > 0).then_some(prev_hash);
+    out.into_inner()
+        .map_err(|e| AppError::Io(e.into_error()))?
+        .sync_all()?;
+    fs::rename(&partial, path)?;
+    Ok(summary)
+}
+
+/// Write the whole log as JSON Lines, verifying the chain on the way.
+///
+/// Each line is an [`AuditEntry`] plus `beforeText` and `afterText`, the exact JSON that was
+/// hashed. A hash is SHA-256 over the previous hash, `seq`, `at`, `actor`, `command`,
+/// `entity`, `entityId`, `beforeText`, and `afterText`, each preceded by its length as a
+/// big-endian u64 (`u64::MAX` for an absent value); integers are 8 big-endian bytes. Auditors
+/// can check the file without the app.
+#[tauri::command]
+pub async fn export_audit_log(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    path: PathBuf,
+) -> AppResult<AuditExport> {
+    let actor = actor(&state);
+    let export = db
+        .run(move |conn| {
+            let export = write_export(conn, &path)?;
+            record_now(
+                conn,
+                &actor,
+                Change::new("export_audit_log", "audit_log", "").after(&serde_json::json!({
+                    "entries": export.entries,
+                    "headHash": export.head_hash,
+                    "intact": export.intact,
+                }))?,
+            )?;
+            Ok(export)
+        })
+        .await?;
+    if !export.intact {
+        tracing::warn!(
+            seq = export.first_broken_seq,
+            "audit log chain is broken; it was modified outside the app"
+        );
+    }
+    Ok(export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(include_str!("db/migrations/0008_audit.sql"))
+            .unwrap();
+        for (i, name) in ["Pier A", "Pier B", "Deck"].into_iter().enumerate() {
+            let change = Change::new("update_member", "member", format!("m{i}"))
+                .before(&serde_json::json!({ "name": name, "depth": 12 }))
+                .unwrap()
+                .after(&serde_json::json!({ "name": name, "depth": 14 }))
+                .unwrap();
+            record_now(&mut conn, "pat@example.com", change).unwrap();
+        }
+        record_now(
+            &mut conn,
+            "pat@example.com",
+            Change::new("delete_member", "member", "m2"),
+        )
+        .unwrap();
+        conn
+    }
+
+    fn export(conn: &Connection) -> AuditExport {
+        let path = std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4()));
+        let export = write_export(conn, &path).unwrap();
+        let lines = fs::read_to_string(&path).unwrap().lines().count();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(lines as u64, export.entries);
+        export
+    }
+
+    // What someone editing the database file directly could do
+    fn tamper(conn: &Connection, sql: &str) {
+        conn.execute_batch(&format!(
+            "DROP TRIGGER audit_log_no_update; DROP TRIGGER audit_log_no_delete; {sql}"
+        ))
+        .unwrap();
+    }
+
+    #[test]
+    fn an_untouched_log_verifies() {
+        let conn = log();
+        let export = export(&conn);
+        assert_eq!(export.entries, 4);
+        assert!(export.intact);
+        assert_eq!(export.first_broken_seq, None);
+        let head: String = conn
+            .query_row("SELECT hash FROM audit_log WHERE seq = 4", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(export.head_hash, Some(head));
+    }
+
+    #[test]
+    fn each_entry_links_to_the_one_before() {
+        let conn = log();
+        let mut stmt = conn
+            .prepare("SELECT * FROM audit_log ORDER BY seq")
+            .unwrap();
+        let entries: Vec<StoredEntry> = stmt
+            .query_map([], StoredEntry::from_row)
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(entries[0].entry.prev_hash, GENESIS_HASH);
+        for pair in entries.windows(2) {
+            assert_eq!(pair[1].entry.prev_hash, pair[0].entry.hash);
+        }
+        assert_eq!(entries[3].entry.before, None);
+        assert_eq!(entries[3].entry.after, None);
+    }
+
+    #[test]
+    fn updates_and_deletes_are_refused() {
+        let conn = log();
+        assert!(conn
+            .execute("UPDATE audit_log SET actor = 'someone' WHERE seq = 2", [])
+            .is_err());
+        assert!(conn
+            .execute("DELETE FROM audit_log WHERE seq = 4", [])
+            .is_err());
+        assert!(export(&conn).intact);
+    }
+
+    #[test]
+    fn a_rewritten_field_breaks_the_chain_at_that_entry() {
+        for sql in [
+            "UPDATE audit_log SET after = '{\"name\":\"Pier B\",\"depth\":10}' WHERE seq = 2",
+            "UPDATE audit_log SET actor = 'someone@example.com' WHERE seq = 2",
+            "UPDATE audit_log SET at = at - 86400000 WHERE seq = 2",
+            "UPDATE audit_log SET before = NULL WHERE seq = 2",
+        ] {
+            let conn = log();
+            tamper(&conn, sql);
+            let export = export(&conn);
+            assert!(!export.intact, "{sql}");
+            assert_eq!(export.first_broken_seq, Some(2), "{sql}");
+        }
+    }
+
+    #[test]
+    fn a_removed_entry_breaks_the_chain_after_it() {
+        let conn = log();
+        tamper(&conn, "DELETE FROM audit_log WHERE seq = 2");
+        let export = export(&conn);
+        assert_eq!(export.entries, 3);
+        assert_eq!(export.first_broken_seq, Some(3));
+    }
+
+    #[test]
+    fn a_rehashed_entry_breaks_the_link_from_the_next() {
+        let conn = log();
+        let mut stored = conn
+            .query_row(
+                "SELECT * FROM audit_log WHERE seq = 2",
+                [],
+                StoredEntry::from_row,
+            )
+            .unwrap();
+        stored.entry.actor = "someone@example.com".into();
+        let hash = stored.expected_hash();
+        tamper(
+            &conn,
+            &format!(
+                "UPDATE audit_log SET actor = 'someone@example.com', hash = '{hash}' WHERE seq = 2"
+            ),
+        );
+        assert_eq!(export(&conn).first_broken_seq, Some(3));
+    }
+
+    #[test]
+    fn a_rebuilt_chain_still_changes_the_head_hash() {
+        let conn = log();
+        let original = export(&conn).head_hash;
+        // Rewrite the first entry and rehash everything after it
+        tamper(
+            &conn,
+            "UPDATE audit_log SET actor = 'someone@example.com' WHERE seq = 1",
+        );
+        let mut prev_hash = GENESIS_HASH.to_string();
+        for seq in 1..=4 {
+            let mut stored = conn
+                .query_row(
+                    "SELECT * FROM audit_log WHERE seq = ?1",
+                    [seq],
+                    StoredEntry::from_row,
+                )
+                .unwrap();
+            stored.entry.prev_hash = prev_hash;
+            prev_hash = stored.expected_hash();
+            conn.execute(
+                "UPDATE audit_log SET prev_hash = ?1, hash = ?2 WHERE seq = ?3",
+                params![stored.entry.prev_hash, prev_hash, seq],
+            )
+            .unwrap();
+        }
+        let forged = export(&conn);
+        assert!(forged.intact);
+        assert_ne!(forged.head_hash, original);
+    }
+
+    #[test]
+    fn a_guarded_connection_refuses_tampering() {
+        let mut conn = log();
+        guard(&conn).unwrap();
+        for sql in [
+            "UPDATE audit_log SET actor = 'someone' WHERE seq = 2",
+            "DELETE FROM audit_log",
+            "DROP TRIGGER audit_log_no_update",
+            "DROP TRIGGER main.audit_log_no_delete",
+            "DROP TABLE audit_log",
+            "ALTER TABLE audit_log RENAME TO old_log",
+            "ALTER TABLE AUDIT_LOG ADD COLUMN note TEXT",
+            "CREATE TRIGGER quiet BEFORE INSERT ON audit_log BEGIN SELECT RAISE(IGNORE); END",
+            "CREATE TEMP TRIGGER quiet BEFORE INSERT ON main.audit_log \
+             BEGIN SELECT RAISE(IGNORE); END",
+            "PRAGMA writable_schema = ON",
+        ] {
+            assert!(conn.execute_batch(sql).is_err(), "{sql}");
+        }
+        record_now(
+            &mut conn,
+            "pat@example.com",
+            Change::new("create_member", "member", "m3"),
+        )
+        .unwrap();
+        let export = export(&conn);
+        assert_eq!(export.entries, 5);
+        assert!(export.intact);
+    }
+
+    #[test]
+    fn a_guarded_connection_allows_everything_else() {
+        let conn = log();
+        guard(&conn).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE audit_log_notes (seq INTEGER, note TEXT);
+             INSERT INTO audit_log_notes SELECT seq, command FROM audit_log;
+             UPDATE audit_log_notes SET note = 'checked';
+             CREATE TRIGGER notes_stay BEFORE DELETE ON audit_log_notes BEGIN SELECT 1; END;
+             DROP TRIGGER notes_stay;
+             DELETE FROM audit_log_notes;
+             DROP TABLE audit_log_notes;
+             PRAGMA writable_schema;",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn unguarding_lifts_the_authorizer() {
+        let conn = log();
+        guard(&conn).unwrap();
+        unguard(&conn).unwrap();
+        conn.execute_batch("DROP TRIGGER audit_log_no_update")
+            .unwrap();
+    }
+
+    #[test]
+    fn entries_with_swapped_fields_hash_differently() {
+        let hash = |entity: &str, entity_id: &str, before: Option<&str>, after: Option<&str>| {
+            entry_hash(
+                GENESIS_HASH,
+                1,
+                0,
+                "pat",
+                "update",
+                entity,
+                entity_id,
+                before,
+                after,
+            )
+        };
+        assert_ne!(hash("ab", "c", None, None), hash("a", "bc", None, None));
+        assert_ne!(
+            hash("a", "b", Some("{}"), None),
+            hash("a", "b", None, Some("{}"))
+        );
+        assert_ne!(hash("a", "b", Some(""), None), hash("a", "b", None, None));
+    }
+}
@@ -0,0 +1,409 @@
+//! OAuth 2.0 authorization-code flow with PKCE, completed via the deep-link callback.
+//!
+//! Tokens never leave Rust. A background task refreshes the access token shortly before it
+//! expires, and [`access_token`] refreshes on demand when that task has fallen behind, such
+//! as after the machine slept. The frontend only hears `auth:session-expired`, once the
+//! server has refused the refresh token.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_http::reqwest::StatusCode;
+use tauri_plugin_opener::OpenerExt;
+use url::Url;
+
+use crate::biometric;
+use crate::config;
+use crate::db::now_ms;
+use crate::error::{AppError, AppResult};
+use crate::http_client::{HttpClient, RetryPolicy};
+use crate::idle_lock;
+use crate::push;
+use crate::rbac;
+use crate::secrets;
+
+const CLIENT_ID: &str = "momentum-desktop";
+const REDIRECT_URI: &str = "truss://auth/callback";
+const SCOPES: &str = "openid profile email offline_access";
+const AUTHORIZE_PATH: &str = "/oauth2/authorize";
+const TOKEN_PATH: &str = "/oauth2/token";
+const SESSION_SECRET_KEY: &str = "auth.session";
+const LOGIN_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+// Refresh this long before expiry so requests in flight never carry a stale token
+const REFRESH_MARGIN_MS: i64 = 2 * 60 * 1000;
+const REFRESH_RETRY: Duration = Duration::from_secs(30);
+// Sleeps are capped so a suspended machine re-checks soon after it wakes
+const REFRESH_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Emitted once tokens are exchanged and stored.
+pub const SIGNED_IN_EVENT: &str = "auth:signed-in";
+/// Emitted when the callback is rejected or the token exchange fails.
+pub const SIGN_IN_FAILED_EVENT: &str = "auth:sign-in-failed";
+/// Emitted when the refresh token is refused and the user has to sign in again.
+pub const SESSION_EXPIRED_EVENT: &str = "auth:session-expired";
+
+struct PendingLogin {
+    verifier: String,
+    state: String,
+    started: Instant,
+}
+
+/// In-flight login bookkeeping registered in app state.
+#[derive(Default)]
+pub struct AuthState {
+    pending: Mutex<Option<PendingLogin>>,
+    // Held across a refresh so concurrent callers share one round trip
+    refreshing: tokio::sync::Mutex<()>,
+    // Wakes the refresh task when a new session is stored
+    session_changed: tokio::sync::Notify,
+}
+
+/// Tokens persisted in the keychain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    /// Unix milliseconds; `None` when the server omits `expires_in`.
+    pub expires_at: Option<i64>,
+}
+
+impl Session {
+    fn from_tokens(tokens: TokenResponse, previous_refresh: Option<String>) -> Self {
+        Self {
+            access_token: tokens.access_token,
+            // Servers that do not rotate refresh tokens omit them from refresh responses
+            refresh_token: tokens.refresh_token.or(previous_refresh),
+            expires_at: tokens.expires_in.map(|s| now_ms() + s * 1000),
+        }
+    }
+
+    fn needs_refresh(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| now_ms() >= expires_at - REFRESH_MARGIN_MS)
+    }
+}
+
+/// Payload for `auth:signed-in`; tokens stay in Rust.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedIn {
+    pub expires_at: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<i64>,
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn code_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// Whether a deep link is the OAuth redirect rather than an in-app route.
+pub fn is_callback(url: &Url) -> bool {
+    url.as_str().starts_with(REDIRECT_URI)
+}
+
+/// Load the stored session, if signed in.
+///
+/// Fails with `SESSION_LOCKED` on mobile until the device owner has unlocked this launch.
+pub fn session() -> AppResult<Option<Session>> {
+    biometric::ensure_unlocked()?;
+    match secrets::get(SESSION_SECRET_KEY)? {
+        Some(raw) => Ok(Some(serde_json::from_str(&raw)?)),
+        None => Ok(None),
+    }
+}
+
+fn store_session(session: &Session) -> AppResult<()> {
+    secrets::set(SESSION_SECRET_KEY, &serde_json::to_string(session)?)
+}
+
+/// Delete the stored session.
+pub fn forget_session() -> AppResult<()> {
+    secrets::delete(SESSION_SECRET_KEY)
+}
+
+/// Complete a login from the redirect URL delivered by the deep-link plugin.
+///
+/// Errors are reported as events because no command invocation is waiting on this path.
+pub fn handle_callback(app: &AppHandle, url: Url) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        match complete_login(&app, url).await {
+            Ok(signed_in) => {
+                tracing::info!("oauth sign-in completed");
+                idle_lock::on_signed_in(&app);
+                push::on_signed_in(&app);
+                let _ = app.emit(SIGNED_IN_EVENT, signed_in);
+            }
+            Err(err) => {
+                tracing::warn!(%err, "oauth sign-in failed");
+                let _ = app.emit(SIGN_IN_FAILED_EVENT, &err);
+            }
+        }
+    });
+}
+
+async fn complete_login(app: &AppHandle, url: Url) -> AppResult<SignedIn> {
+    let query = |key: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.into_owned())
+    };
+    if let Some(error) = query("error") {
+        return Err(AppError::Auth(format!("authorization denied: {error}")));
+    }
+
+    let pending = app
+        .state::<AuthState>()
+        .pending
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+        .ok_or_else(|| AppError::Auth("no sign-in in progress".into()))?;
+    if pending.started.elapsed() > LOGIN_TIMEOUT {
+        return Err(AppError::Auth("sign-in attempt expired".into()));
+    }
+    if query("state").as_deref() != Some(pending.state.as_str()) {
+        return Err(AppError::Auth("state mismatch in OAuth callback".into()));
+    }
+    let code = query("code").ok_or_else(|| AppError::Auth("callback missing code".into()))?;
+
+    let http = app.state::<HttpClient>();
+    let request = http
+        .client()
+        .post(config::api_url(app, TOKEN_PATH)?)
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code.as_str()),
+            ("redirect_uri", REDIRECT_URI),
+            ("client_id", CLIENT_ID),
+            ("code_verifier", pending.verifier.as_str()),
+        ]);
+    let response = http
+        .send(request, RetryPolicy::default())
+        .await?
+        .error_for_status()?;
+    let tokens: TokenResponse = serde_json::from_slice(&response.bytes().await?)?;
+
+    let session = Session::from_tokens(tokens, None);
+    let expires_at = session.expires_at;
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        store_session(&session)?;
+        rbac::update_identity(&handle, &session.access_token)
+    })
+    .await??;
+    app.state::<AuthState>().session_changed.notify_one();
+
+    Ok(SignedIn { expires_at })
+}
+
+// Refusals of the refresh token itself, as opposed to the server or network failing
+fn is_definitive(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+    )
+}
+
+fn expire(app: &AppHandle, reason: &str) -> AppResult<()> {
+    forget_session()?;
+    tracing::warn!(reason, "session expired");
+    let _ = app.emit(SESSION_EXPIRED_EVENT, ());
+    Ok(())
+}
+
+/// Exchange the refresh token for a new access token and store it.
+///
+/// `stale` is the access token the caller saw; if another caller already replaced it, the
+/// stored session is returned without another round trip. A refusal forgets the session
+/// and emits `auth:session-expired`; network and server errors leave it in place.
+async fn refresh(app: &AppHandle, stale: &str) -> AppResult<Session> {
+    let auth = app.state::<AuthState>();
+    let _refreshing = auth.refreshing.lock().await;
+    let session = tauri::async_runtime::spawn_blocking(session)
+        .await??
+        .ok_or_else(|| AppError::Auth("not signed in".into()))?;
+    if session.access_token != stale {
+        return Ok(session);
+    }
+    let Some(refresh_token) = session.refresh_token.clone() else {
+        // Without a refresh token the access token is used until it actually runs out
+        if session
+            .expires_at
+            .is_none_or(|expires_at| now_ms() < expires_at)
+        {
+            return Ok(session);
+        }
+        tauri::async_runtime::spawn_blocking({
+            let app = app.clone();
+            move || expire(&app, "no refresh token")
+        })
+        .await??;
+        return Err(AppError::Auth("session expired; sign in again".into()));
+    };
+
+    let http = app.state::<HttpClient>();
+    let request = http
+        .client()
+        .post(config::api_url(app, TOKEN_PATH)?)
+        .form(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token.as_str()),
+            ("client_id", CLIENT_ID),
+        ]);
+    let response = http.send(request, RetryPolicy::default()).await?;
+    if is_definitive(response.status()) {
+        tauri::async_runtime::spawn_blocking({
+            let app = app.clone();
+            move || expire(&app, "refresh token refused")
+        })
+        .await??;
+        return Err(AppError::Auth("session expired; sign in again".into()));
+    }
+    let tokens: TokenResponse =
+        serde_json::from_slice(&response.error_for_status()?.bytes().await?)?;
+    let session = Session::from_tokens(tokens, Some(refresh_token));
+    let (stored, handle) = (session.clone(), app.clone());
+    // A refreshed token carries the role as it is now, so demotions apply without signing out
+    tauri::async_runtime::spawn_blocking(move || {
+        store_session(&stored)?;
+        rbac::update_identity(&handle, &stored.access_token)
+    })
+    .await??;
+    tracing::debug!("access token refreshed");
+    Ok(session)
+}
+
+/// A current access token, refreshed first if it is about to expire; `None` when signed out.
+pub async fn access_token(app: &AppHandle) -> AppResult<Option<String>> {
+    let Some(session) = tauri::async_runtime::spawn_blocking(session).await?? else {
+        return Ok(None);
+    };
+    if !session.needs_refresh() {
+        return Ok(Some(session.access_token));
+    }
+    refresh(app, &session.access_token)
+        .await
+        .map(|session| Some(session.access_token))
+}
+
+/// Refresh after the server rejected `rejected` with a 401, e.g. because it was revoked early.
+pub async fn refresh_rejected(app: &AppHandle, rejected: &str) -> AppResult<String> {
+    refresh(app, rejected)
+        .await
+        .map(|session| session.access_token)
+}
+
+/// Keep the access token fresh in the background while signed in.
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let auth = app.state::<AuthState>();
+        loop {
+            let session = match tauri::async_runtime::spawn_blocking(session).await {
+                Ok(Ok(session)) => session,
+                // Locked or the keychain is unavailable; look again later
+                _ => {
+                    tokio::time::sleep(REFRESH_RETRY).await;
+                    continue;
+                }
+            };
+            let Some(session) = session else {
+                auth.session_changed.notified().await;
+                continue;
+            };
+            let Some(expires_at) = session.expires_at else {
+                auth.session_changed.notified().await;
+                continue;
+            };
+            let margin = if session.refresh_token.is_some() {
+                REFRESH_MARGIN_MS
+            } else {
+                0
+            };
+            let due = expires_at - margin - now_ms();
+            if due > 0 {
+                let wait = Duration::from_millis(due as u64).min(REFRESH_CHECK_INTERVAL);
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {}
+                    _ = auth.session_changed.notified() => {}
+                }
+                continue;
+            }
+            if let Err(err) = refresh(&app, &session.access_token).await {
+                tracing::warn!(%err, "background token refresh failed");
+                tokio::time::sleep(REFRESH_RETRY).await;
+            }
+        }
+    });
+}
+
+/// Begin a browser-based login; completion arrives as `auth:signed-in`.
+#[tauri::command]
+pub async fn start_oauth_login(app: AppHandle, auth: State<'_, AuthState>) -> AppResult<()> {
+    let verifier = random_token();
+    let state = random_token();
+
+    let mut url = config::api_url(&app, AUTHORIZE_PATH)?;
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", CLIENT_ID)
+        .append_pair("redirect_uri", REDIRECT_URI)
+        .append_pair("scope", SCOPES)
+        .append_pair("state", &state)
+        .append_pair("code_challenge", &code_challenge(&verifier))
+        .append_pair("code_challenge_method", "S256");
+
+    // A new attempt supersedes any abandoned one
+    *auth.pending.lock().unwrap_or_else(|e| e.into_inner()) = Some(PendingLogin {
+        verifier,
+        state,
+        started: Instant::now(),
+    });
+
+    app.opener()
+        .open_url(url.as_str(), None::<&str>)
+        .map_err(|e| AppError::Internal(format!("failed to open browser: {e}")))
+}
+
+/// Signed-in state as seen by the frontend; tokens never leave Rust.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthStatus {
+    pub signed_in: bool,
+    pub expires_at: Option<i64>,
+}
+
+#[tauri::command]
+pub async fn get_auth_status() -> AppResult<AuthStatus> {
+    let session = tauri::async_runtime::spawn_blocking(session).await??;
+    Ok(AuthStatus {
+        signed_in: session.is_some(),
+        expires_at: session.and_then(|s| s.expires_at),
+    })
+}
+
+#[tauri::command]
+pub async fn sign_out(auth: State<'_, AuthState>) -> AppResult<()> {
+    tauri::async_runtime::spawn_blocking(forget_session).await??;
+    auth.session_changed.notify_one();
+    Ok(())
+}
@@ -0,0 +1,426 @@
+//! Crash-safe autosave of documents the frontend is editing.
+//!
+//! The frontend pushes whole-document snapshots as they change; they are held in memory and
+//! written to `data_dir/autosave` once edits pause, at least every [`MAX_DIRTY`], and right
+//! away when the owning window loses focus or closes. A snapshot is deleted once the document
+//! is saved for real, so anything left on disk at launch is unsaved work.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, Runtime, State, Window, WindowEvent};
+
+use crate::db::now_ms;
+use crate::error::{AppError, AppResult};
+
+const AUTOSAVE_DIR_NAME: &str = "autosave";
+// Quiet time after the last edit before a snapshot is written
+const DEBOUNCE: Duration = Duration::from_secs(2);
+// Continuous typing still reaches disk this often
+const MAX_DIRTY: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A snapshot as persisted, and as returned by [`recover_unsaved_work`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnsavedWork {
+    pub document_id: String,
+    /// Label of the window that was editing it.
+    pub window: String,
+    /// Increases with every snapshot pushed for the document.
+    pub revision: u64,
+    pub saved_at: i64,
+    pub content: Value,
+}
+
+struct Document {
+    window: String,
+    revision: u64,
+    content: Value,
+    dirty_since: Option<Instant>,
+    changed_at: Instant,
+}
+
+/// Autosave state registered in app state.
+pub struct Autosave {
+    dir: PathBuf,
+    documents: Mutex<HashMap<String, Document>>,
+    // Files this session wrote, which are live documents rather than recoverable leftovers,
+    // with the revision each holds
+    written: Mutex<HashMap<String, u64>>,
+}
+
+/// Open the autosave folder under `data_dir`.
+pub fn init(data_dir: &Path) -> AppResult<Autosave> {
+    let dir = data_dir.join(AUTOSAVE_DIR_NAME);
+    fs::create_dir_all(&dir)?;
+    // Half-written snapshots from a crash; the file each was replacing is still whole
+    for entry in fs::read_dir(&dir)?.flatten() {
+        if entry.file_name().to_string_lossy().ends_with(".tmp") {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+    Ok(Autosave {
+        dir,
+        documents: Mutex::new(HashMap::new()),
+        written: Mutex::new(HashMap::new()),
+    })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// Document ids come from the frontend, so they are hashed rather than used as file names
+fn file_name(document_id: &str) -> String {
+    format!("{}.json", hex(&Sha256::digest(document_id.as_bytes())))
+}
+
+impl Autosave {
+    // Replace the document's file with `snapshot` unless a newer revision is already there;
+    // flushes run concurrently, so each writes its own temporary file and the check and
+    // rename happen together under the lock
+    fn write(&self, name: &str, snapshot: &UnsavedWork) -> AppResult<bool> {
+        let bytes = serde_json::to_vec(snapshot)?;
+        let tmp = self
+            .dir
+            .join(format!("{name}.{}.tmp", uuid::Uuid::new_v4()));
+        let result = (|| {
+            let mut file = File::create(&tmp)?;
+            file.write_all(&bytes)?;
+            // Without this a crash right after the rename can leave an empty file behind
+            file.sync_all()?;
+            let mut written = self.written.lock().unwrap_or_else(|e| e.into_inner());
+            if written
+                .get(name)
+                .is_some_and(|&revision| revision >= snapshot.revision)
+            {
+                return Ok(false);
+            }
+            fs::rename(&tmp, self.dir.join(name))?;
+            written.insert(name.to_string(), snapshot.revision);
+            Ok(true)
+        })();
+        if !matches!(result, Ok(true)) {
+            let _ = fs::remove_file(&tmp);
+        }
+        result
+    }
+
+    fn push(&self, window: &str, document_id: String, content: Value) -> u64 {
+        let mut documents = self.documents.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let document = documents.entry(document_id).or_insert_with(|| Document {
+            window: window.to_string(),
+            revision: 0,
+            content: Value::Null,
+            dirty_since: None,
+            changed_at: now,
+        });
+        document.window = window.to_string();
+        document.revision += 1;
+        document.content = content;
+        document.changed_at = now;
+        document.dirty_since.get_or_insert(now);
+        document.revision
+    }
+
+    // Write every dirty document `select` picks; returns how many were written
+    fn flush(&self, select: impl Fn(&str, &Document) -> bool) -> usize {
+        let pending: Vec<UnsavedWork> = {
+            let mut documents = self.documents.lock().unwrap_or_else(|e| e.into_inner());
+            documents
+                .iter_mut()
+                .filter(|(id, doc)| doc.dirty_since.is_some() && select(id, doc))
+                .map(|(id, doc)| {
+                    doc.dirty_since = None;
+                    UnsavedWork {
+                        document_id: id.clone(),
+                        window: doc.window.clone(),
+                        revision: doc.revision,
+                        saved_at: now_ms(),
+                        content: doc.content.clone(),
+                    }
+                })
+                .collect()
+        };
+
+        let mut written = 0;
+        for snapshot in pending {
+            let name = file_name(&snapshot.document_id);
+            match self.write(&name, &snapshot) {
+                // A later snapshot was written first
+                Ok(false) => {}
+                Ok(true) => {
+                    written += 1;
+                    let documents = self.documents.lock().unwrap_or_else(|e| e.into_inner());
+                    // Discarded while being written; the saved document must not come back
+                    if !documents.contains_key(&snapshot.document_id) {
+                        self.written
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .remove(&name);
+                        let _ = fs::remove_file(self.dir.join(&name));
+                    }
+                }
+                Err(err) => {
+                    tracing::warn!(%err, document = %snapshot.document_id, "autosave failed");
+                    // Stay dirty so the next pass retries
+                    let mut documents = self.documents.lock().unwrap_or_else(|e| e.into_inner());
+                    if let Some(doc) = documents.get_mut(&snapshot.document_id) {
+                        doc.dirty_since.get_or_insert_with(Instant::now);
+                    }
+                }
+            }
+        }
+        written
+    }
+
+    /// Write every dirty document now, e.g. on exit.
+    pub fn flush_all(&self) -> usize {
+        self.flush(|_, _| true)
+    }
+
+    fn flush_window(&self, label: &str) -> usize {
+        self.flush(|_, doc| doc.window == label)
+    }
+
+    fn flush_due(&self) -> usize {
+        self.flush(|_, doc| {
+            doc.changed_at.elapsed() >= DEBOUNCE
+                || doc.dirty_since.is_some_and(|t| t.elapsed() >= MAX_DIRTY)
+        })
+    }
+
+    fn discard(&self, document_id: &str) -> AppResult<()> {
+        self.documents
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(document_id);
+        let name = file_name(document_id);
+        self.written
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&name);
+        match fs::remove_file(self.dir.join(name)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    // Snapshots on disk that this session didn't write, newest first
+    fn recover(&self) -> AppResult<Vec<UnsavedWork>> {
+        let written = self
+            .written
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let mut recovered = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.ends_with(".json") || written.contains_key(&name) {
+                continue;
+            }
+            match fs::read(entry.path())
+                .map_err(AppError::from)
+                .and_then(|bytes| Ok(serde_json::from_slice::<UnsavedWork>(&bytes)?))
+            {
+                Ok(work) => recovered.push(work),
+                Err(err) => tracing::warn!(%err, file = %name, "skipping unreadable autosave"),
+            }
+        }
+        recovered.sort_by_key(|work| std::cmp::Reverse(work.saved_at));
+        Ok(recovered)
+    }
+}
+
+/// Write a window's documents as soon as it loses focus or closes.
+pub fn on_window_event<R: Runtime>(window: &Window<R>, event: &WindowEvent) {
+    if !matches!(
+        event,
+        WindowEvent::Focused(false) | WindowEvent::CloseRequested { .. } | WindowEvent::Destroyed
+    ) {
+        return;
+    }
+    if let Some(autosave) = window.try_state::<Autosave>() {
+        let written = autosave.flush_window(window.label());
+        if written > 0 {
+            tracing::debug!(
+                window = window.label(),
+                written,
+                "autosaved on window event"
+            );
+        }
+    }
+}
+
+/// Write debounced snapshots in the background.
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let handle = app.clone();
+            let _ = tauri::async_runtime::spawn_blocking(move || {
+                handle.state::<Autosave>().flush_due()
+            })
+            .await;
+        }
+    });
+}
+
+/// Record the latest snapshot of a document; returns its revision.
+#[tauri::command]
+pub async fn autosave_snapshot(
+    window: Window,
+    autosave: State<'_, Autosave>,
+    document_id: String,
+    content: Value,
+) -> AppResult<u64> {
+    Ok(autosave.push(window.label(), document_id, content))
+}
+
+/// Write pending snapshots now: one document's, or every document's.
+#[tauri::command]
+pub async fn autosave_flush(app: AppHandle, document_id: Option<String>) -> AppResult<usize> {
+    Ok(tauri::async_runtime::spawn_blocking(move || {
+        let autosave = app.state::<Autosave>();
+        match document_id {
+            Some(target) => autosave.flush(|id, _| id == target),
+            None => autosave.flush_all(),
+        }
+    })
+    .await?)
+}
+
+/// Forget a document's snapshot once it has been saved or its changes abandoned.
+#[tauri::command]
+pub async fn autosave_discard(autosave: State<'_, Autosave>, document_id: String) -> AppResult<()> {
+    autosave.discard(&document_id)
+}
+
+/// Snapshots left by an earlier session that crashed or closed with unsaved changes.
+///
+/// They stay on disk until discarded, so a recovery the user dismisses is offered again.
+#[tauri::command]
+pub async fn recover_unsaved_work(app: AppHandle) -> AppResult<Vec<UnsavedWork>> {
+    tauri::async_runtime::spawn_blocking(move || app.state::<Autosave>().recover()).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("autosave-{}", uuid::Uuid::new_v4()));
+            fs::create_dir(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // Write `content` as the document's snapshot and return the session that wrote it
+    fn session_with(data_dir: &Path, document_id: &str, content: Value) -> Autosave {
+        let autosave = init(data_dir).unwrap();
+        autosave.push("main", document_id.into(), content);
+        assert_eq!(autosave.flush_all(), 1);
+        autosave
+    }
+
+    #[test]
+    fn an_interrupted_write_leaves_the_last_snapshot_readable() {
+        let data = Scratch::new();
+        let session = session_with(&data.0, "estimate", json!({ "total": 100 }));
+        // The crash: a newer snapshot got partway into its temporary file
+        let name = file_name("estimate");
+        let tmp = session
+            .dir
+            .join(format!("{name}.{}.tmp", uuid::Uuid::new_v4()));
+        fs::write(&tmp, br#"{"documentId":"estimate","revision":2,"con"#).unwrap();
+        drop(session);
+
+        let next = init(&data.0).unwrap();
+        assert!(!tmp.exists());
+        let recovered = next.recover().unwrap();
+        assert_eq!(recovered.len(), 1);
+        assert_eq!(recovered[0].revision, 1);
+        assert_eq!(recovered[0].content, json!({ "total": 100 }));
+    }
+
+    #[test]
+    fn recovery_returns_the_newest_snapshot() {
+        let data = Scratch::new();
+        let session = session_with(&data.0, "estimate", json!({ "total": 100 }));
+        session.push("main", "estimate".into(), json!({ "total": 250 }));
+        assert_eq!(session.flush_all(), 1);
+        // A slow flush of an older revision must not replace it
+        let stale = UnsavedWork {
+            document_id: "estimate".into(),
+            window: "main".into(),
+            revision: 1,
+            saved_at: now_ms(),
+            content: json!({ "total": 100 }),
+        };
+        assert!(!session.write(&file_name("estimate"), &stale).unwrap());
+        drop(session);
+
+        let recovered = init(&data.0).unwrap().recover().unwrap();
+        assert_eq!(recovered.len(), 1);
+        assert_eq!(recovered[0].revision, 2);
+        assert_eq!(recovered[0].content, json!({ "total": 250 }));
+    }
+
+    #[test]
+    fn documents_recover_newest_first() {
+        let data = Scratch::new();
+        drop(session_with(&data.0, "older", json!(1)));
+        std::thread::sleep(Duration::from_millis(5));
+        drop(session_with(&data.0, "newer", json!(2)));
+
+        let recovered = init(&data.0).unwrap().recover().unwrap();
+        let ids: Vec<_> = recovered.iter().map(|w| w.document_id.as_str()).collect();
+        assert_eq!(ids, ["newer", "older"]);
+    }
+
+    #[test]
+    fn truncated_and_corrupt_snapshots_are_skipped() {
+        let data = Scratch::new();
+        drop(session_with(&data.0, "estimate", json!({ "total": 100 })));
+        let dir = data.0.join(AUTOSAVE_DIR_NAME);
+        let whole = fs::read(dir.join(file_name("estimate"))).unwrap();
+        fs::write(dir.join(file_name("truncated")), &whole[..whole.len() / 2]).unwrap();
+        fs::write(dir.join(file_name("corrupt")), [0xff, 0x00, 0x13]).unwrap();
+        fs::write(dir.join(file_name("empty")), b"").unwrap();
+
+        let recovered = init(&data.0).unwrap().recover().unwrap();
+        assert_eq!(recovered.len(), 1);
+        assert_eq!(recovered[0].document_id, "estimate");
+    }
+
+    #[test]
+    fn this_sessions_snapshots_are_not_offered_back() {
+        let data = Scratch::new();
+        let session = session_with(&data.0, "estimate", json!({}));
+        assert!(session.recover().unwrap().is_empty());
+        session.discard("estimate").unwrap();
+        drop(session);
+        assert!(init(&data.0).unwrap().recover().unwrap().is_empty());
+    }
+}
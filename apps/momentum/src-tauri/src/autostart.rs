@@ -0,0 +1,295 @@
+//! Launch at login through each platform's own mechanism: a LaunchAgent on macOS, the
+//! per-user `Run` key on Windows, and an XDG autostart entry on Linux.
+//!
+//! The OS entry is the source of truth for whether the app launches at login, so it is
+//! never mirrored into the config. Entries pass [`AUTOSTART_ARG`], which is how a launch
+//! at login is told apart from the user opening the app; with `startMinimized` set such a
+//! launch stays in the tray, where sync and reminders keep running.
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::config::{self, Config};
+use crate::error::AppResult;
+use crate::state::AppState;
+
+/// Passed by the login entry.
+pub const AUTOSTART_ARG: &str = "--autostart";
+
+/// Whether the app launches at login, and how.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LaunchAtLogin {
+    pub enabled: bool,
+    pub start_minimized: bool,
+}
+
+/// Whether this process was started by the login entry.
+pub fn launched_at_login() -> bool {
+    std::env::args().skip(1).any(|arg| arg == AUTOSTART_ARG)
+}
+
+/// Whether to keep the main window hidden for this launch.
+pub fn start_hidden(config: &Config) -> bool {
+    config.start_minimized && launched_at_login()
+}
+
+/// Rewrite an existing login entry so it points at the current executable, which moves
+/// when the app is updated or reinstalled elsewhere.
+pub fn refresh(app: &AppHandle) {
+    let result = platform::is_enabled(app).and_then(|enabled| match enabled {
+        true => platform::enable(app),
+        false => Ok(()),
+    });
+    if let Err(err) = result {
+        tracing::warn!(%err, "cannot refresh launch-at-login entry");
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use quick_xml::escape::escape;
+    use tauri::{AppHandle, Manager};
+
+    use super::AUTOSTART_ARG;
+    use crate::error::AppResult;
+
+    fn agent_path(app: &AppHandle) -> AppResult<PathBuf> {
+        Ok(app
+            .path()
+            .home_dir()?
+            .join("Library/LaunchAgents")
+            .join(format!("{}.plist", app.config().identifier)))
+    }
+
+    pub fn is_enabled(app: &AppHandle) -> AppResult<bool> {
+        Ok(agent_path(app)?.is_file())
+    }
+
+    pub fn enable(app: &AppHandle) -> AppResult<()> {
+        let path = agent_path(app)?;
+        let exe = std::env::current_exe()?;
+        let plist = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN"
+    "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{exe}</string>
+        <string>{AUTOSTART_ARG}</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>ProcessType</key>
+    <string>Interactive</string>
+</dict>
+</plist>
+"#,
+            label = escape(&app.config().identifier),
+            exe = escape(&exe.to_string_lossy()),
+        );
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, plist)?;
+        Ok(())
+    }
+
+    pub fn disable(app: &AppHandle) -> AppResult<()> {
+        match fs::remove_file(agent_path(app)?) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use tauri::AppHandle;
+    use windows::core::{w, HSTRING, PCWSTR};
+    use windows::Win32::Foundation::ERROR_FILE_NOT_FOUND;
+    use windows::Win32::System::Registry::{
+        RegDeleteKeyValueW, RegGetValueW, RegSetKeyValueW, HKEY_CURRENT_USER, REG_SZ, RRF_RT_REG_SZ,
+    };
+
+    use super::AUTOSTART_ARG;
+    use crate::error::{AppError, AppResult};
+
+    const RUN_KEY: PCWSTR = w!(r"Software\Microsoft\Windows\CurrentVersion\Run");
+
+    fn value_name(app: &AppHandle) -> HSTRING {
+        HSTRING::from(app.package_info().name.as_str())
+    }
+
+    pub fn is_enabled(app: &AppHandle) -> AppResult<bool> {
+        // SAFETY: only the value's presence is queried, so no buffer is passed
+        let status = unsafe {
+            RegGetValueW(
+                HKEY_CURRENT_USER,
+                RUN_KEY,
+                &value_name(app),
+                RRF_RT_REG_SZ,
+                None,
+                None,
+                None,
+            )
+        };
+        match status {
+            ERROR_FILE_NOT_FOUND => Ok(false),
+            status => status
+                .ok()
+                .map(|()| true)
+                .map_err(|e| AppError::Internal(format!("login item: {e}"))),
+        }
+    }
+
+    pub fn enable(app: &AppHandle) -> AppResult<()> {
+        let exe = std::env::current_exe()?;
+        let command: Vec<u16> = format!("\"{}\" {AUTOSTART_ARG}", exe.display())
+            .encode_utf16()
+            .chain([0])
+            .collect();
+        // SAFETY: `command` is a NUL-terminated UTF-16 string of the given byte length
+        unsafe {
+            RegSetKeyValueW(
+                HKEY_CURRENT_USER,
+                RUN_KEY,
+                &value_name(app),
+                REG_SZ.0,
+                Some(command.as_ptr().cast()),
+                std::mem::size_of_val(command.as_slice()) as u32,
+            )
+        }
+        .ok()
+        .map_err(|e| AppError::Internal(format!("login item: {e}")))
+    }
+
+    pub fn disable(app: &AppHandle) -> AppResult<()> {
+        // SAFETY: both arguments are valid NUL-terminated strings
+        match unsafe { RegDeleteKeyValueW(HKEY_CURRENT_USER, RUN_KEY, &value_name(app)) } {
+            ERROR_FILE_NOT_FOUND => Ok(()),
+            status => status
+                .ok()
+                .map_err(|e| AppError::Internal(format!("login item: {e}"))),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use tauri::{AppHandle, Manager};
+
+    use super::AUTOSTART_ARG;
+    use crate::error::AppResult;
+
+    fn entry_path(app: &AppHandle) -> AppResult<PathBuf> {
+        Ok(app
+            .path()
+            .config_dir()?
+            .join("autostart")
+            .join(format!("{}.desktop", app.config().identifier)))
+    }
+
+    // Quoting rules for an `Exec` argument in the desktop entry spec
+    fn quote(arg: &str) -> String {
+        let mut quoted = String::with_capacity(arg.len() + 2);
+        quoted.push('"');
+        for c in arg.chars() {
+            if matches!(c, '"' | '`' | '$' | '\\') {
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+        quoted.push('"');
+        quoted
+    }
+
+    pub fn is_enabled(app: &AppHandle) -> AppResult<bool> {
+        Ok(entry_path(app)?.is_file())
+    }
+
+    pub fn enable(app: &AppHandle) -> AppResult<()> {
+        let path = entry_path(app)?;
+        // An AppImage runs from a temporary mount, so the entry must launch the image itself
+        let exe = match std::env::var_os("APPIMAGE") {
+            Some(image) => PathBuf::from(image),
+            None => std::env::current_exe()?,
+        };
+        let entry = format!(
+            "[Desktop Entry]\n\
+             Type=Application\n\
+             Name={name}\n\
+             Exec={exec} {AUTOSTART_ARG}\n\
+             Terminal=false\n\
+             X-GNOME-Autostart-enabled=true\n",
+            name = app.package_info().name,
+            exec = quote(&exe.to_string_lossy()),
+        );
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, entry)?;
+        Ok(())
+    }
+
+    pub fn disable(app: &AppHandle) -> AppResult<()> {
+        match fs::remove_file(entry_path(app)?) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Whether the app launches at login, and whether such launches start in the tray.
+#[tauri::command]
+pub async fn get_launch_at_login(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> AppResult<LaunchAtLogin> {
+    let enabled =
+        tauri::async_runtime::spawn_blocking(move || platform::is_enabled(&app)).await??;
+    Ok(LaunchAtLogin {
+        enabled,
+        start_minimized: state.config().start_minimized,
+    })
+}
+
+/// Register or remove the login entry; `startMinimized`, when given, is saved to the config.
+#[tauri::command]
+pub async fn set_launch_at_login(
+    app: AppHandle,
+    enabled: bool,
+    start_minimized: Option<bool>,
+) -> AppResult<LaunchAtLogin> {
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || match enabled {
+        true => platform::enable(&handle),
+        false => platform::disable(&handle),
+    })
+    .await??;
+    let config = match start_minimized {
+        Some(start_minimized) => config::update(&app, |config| {
+            config.start_minimized = start_minimized;
+        })?,
+        None => app.state::<AppState>().config(),
+    };
+    tracing::info!(
+        enabled,
+        start_minimized = config.start_minimized,
+        "launch at login"
+    );
+    Ok(LaunchAtLogin {
+        enabled,
+        start_minimized: config.start_minimized,
+    })
+}
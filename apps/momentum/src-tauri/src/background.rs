@@ -0,0 +1,259 @@
+//! Fitting background work around the user and the power supply.
+//!
+//! Once a minute the machine's idle time and power source settle a [`BackgroundMode`]. While
+//! the user is at the keyboard on battery, background sync runs only every
+//! `throttledSyncMinutes` and the daily search index rebuild waits, for up to a few days.
+//! Once the user has been away for `idleMinutes` on mains power, the catalog is downloaded
+//! afresh and the search index rebuilt, at most twice a day and never while the session is
+//! locked, since the database is closed then. Otherwise jobs keep their own schedules. Only
+//! scheduled runs are affected; `sync_now` and the other manual commands always run, and a
+//! low battery still pauses sync whatever the policy says.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::catalog::Catalog;
+use crate::config;
+use crate::db::now_ms;
+use crate::error::{AppError, AppResult};
+use crate::idle_lock;
+use crate::network::NetworkMonitor;
+use crate::power::{PowerMonitor, PowerSource, PowerStatus};
+use crate::search;
+use crate::state::AppState;
+
+/// Emitted with a [`BackgroundStatus`] when the mode changes.
+pub const MODE_CHANGED_EVENT: &str = "background:mode-changed";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+const MAINTENANCE_INTERVAL_MS: i64 = 12 * 60 * 60 * 1000;
+// A rebuild held back longer than this goes ahead on battery anyway
+const MAX_INDEX_DEFER_MS: i64 = 3 * 24 * 60 * 60 * 1000;
+
+/// When background work slows down or catches up.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BackgroundPolicy {
+    /// Minutes without keyboard or mouse input anywhere on the machine before the user
+    /// counts as away.
+    pub idle_minutes: u32,
+    /// Slow background sync and indexing while the user is active on battery.
+    pub throttle_on_battery: bool,
+    /// Minutes between background syncs while throttled; five otherwise.
+    pub throttled_sync_minutes: u32,
+    /// Refresh the catalog and rebuild the search index while away on mains power.
+    pub maintain_when_idle: bool,
+}
+
+impl Default for BackgroundPolicy {
+    fn default() -> Self {
+        Self {
+            idle_minutes: 10,
+            throttle_on_battery: true,
+            throttled_sync_minutes: 30,
+            maintain_when_idle: true,
+        }
+    }
+}
+
+/// What the policy currently makes of background work.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BackgroundMode {
+    /// Jobs run on their own schedules.
+    #[default]
+    Normal,
+    /// Active on battery: sync and indexing are spaced out.
+    Throttled,
+    /// Away on mains power: full refreshes are due.
+    Maintenance,
+}
+
+/// Background work a scheduled job asks [`defer`] about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Work {
+    Sync,
+    Indexing,
+}
+
+/// Payload for `background:mode-changed`, and what `get_background_status` returns.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackgroundStatus {
+    pub mode: BackgroundMode,
+    /// `None` where the OS does not report idle time; the user then always counts as active.
+    pub idle_ms: Option<u64>,
+    pub power_source: PowerSource,
+    pub last_maintenance_at: Option<i64>,
+    pub checked_at: i64,
+}
+
+#[derive(Default)]
+struct Inner {
+    status: BackgroundStatus,
+    // When each kind of work last went ahead
+    last_runs: HashMap<Work, i64>,
+}
+
+/// The current mode, registered in app state.
+#[derive(Default)]
+pub struct Background {
+    inner: Mutex<Inner>,
+}
+
+impl Background {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn status(&self) -> BackgroundStatus {
+        self.lock().status.clone()
+    }
+
+    fn ran(&self, work: Work) {
+        self.lock().last_runs.insert(work, now_ms());
+    }
+}
+
+fn mode_for(
+    policy: &BackgroundPolicy,
+    idle: Option<Duration>,
+    power: &PowerStatus,
+) -> BackgroundMode {
+    let away =
+        idle.is_some_and(|idle| idle >= Duration::from_secs(policy.idle_minutes as u64 * 60));
+    if away && power.source == PowerSource::Ac && policy.maintain_when_idle {
+        BackgroundMode::Maintenance
+    } else if !away && power.on_battery() && policy.throttle_on_battery {
+        BackgroundMode::Throttled
+    } else {
+        BackgroundMode::Normal
+    }
+}
+
+/// Whether a scheduled run of `work` should be skipped under the current mode; a run that
+/// goes ahead is recorded, so the next one is spaced from it.
+pub fn defer(app: &AppHandle, work: Work) -> bool {
+    let Some(background) = app.try_state::<Background>() else {
+        return false;
+    };
+    let mut inner = background.lock();
+    let now = now_ms();
+    if inner.status.mode == BackgroundMode::Throttled {
+        let spacing = match work {
+            Work::Sync => {
+                let minutes = app
+                    .state::<AppState>()
+                    .config()
+                    .background
+                    .throttled_sync_minutes;
+                minutes as i64 * 60 * 1000
+            }
+            Work::Indexing => MAX_INDEX_DEFER_MS,
+        };
+        if inner
+            .last_runs
+            .get(&work)
+            .is_some_and(|&last| now - last < spacing)
+        {
+            tracing::debug!(?work, "background work throttled");
+            return true;
+        }
+    }
+    inner.last_runs.insert(work, now);
+    false
+}
+
+async fn maintain(app: &AppHandle) {
+    let background = app.state::<Background>();
+    // Stamped first, so a failed run waits for the next window instead of repeating
+    background.lock().status.last_maintenance_at = Some(now_ms());
+    tracing::info!("idle on mains power; refreshing the catalog and search index");
+    if app.state::<NetworkMonitor>().is_online() {
+        match app.state::<Catalog>().refresh(app).await {
+            Ok(_) | Err(AppError::Auth(_)) => {}
+            Err(err) => tracing::warn!(%err, "idle catalog refresh failed"),
+        }
+    }
+    match search::rebuild(app).await {
+        Ok(_) => background.ran(Work::Indexing),
+        Err(err) => tracing::warn!(%err, "idle search index rebuild failed"),
+    }
+}
+
+fn maintenance_due(status: &BackgroundStatus) -> bool {
+    status.mode == BackgroundMode::Maintenance
+        && status
+            .last_maintenance_at
+            .is_none_or(|at| now_ms() - at >= MAINTENANCE_INTERVAL_MS)
+}
+
+/// Re-evaluate the mode every minute, and run maintenance when it comes due.
+pub fn start(app: &AppHandle) {
+    app.manage(Background::default());
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let policy = app.state::<AppState>().config().background;
+            let idle = tauri::async_runtime::spawn_blocking(idle_lock::idle_time)
+                .await
+                .ok()
+                .flatten();
+            let power = app
+                .try_state::<PowerMonitor>()
+                .map(|monitor| monitor.snapshot())
+                .unwrap_or_default();
+            let mode = mode_for(&policy, idle, &power);
+            let background = app.state::<Background>();
+            let (status, changed) = {
+                let mut inner = background.lock();
+                let changed = inner.status.mode != mode;
+                inner.status.mode = mode;
+                inner.status.idle_ms = idle.map(|idle| idle.as_millis() as u64);
+                inner.status.power_source = power.source;
+                inner.status.checked_at = now_ms();
+                (inner.status.clone(), changed)
+            };
+            if changed {
+                tracing::info!(?mode, "background mode changed");
+                let _ = app.emit(MODE_CHANGED_EVENT, &status);
+            }
+            if maintenance_due(&status) && idle_lock::ensure_unlocked(&app).is_ok() {
+                maintain(&app).await;
+            }
+        }
+    });
+}
+
+/// Save how background work follows activity and power; takes effect within a minute.
+#[tauri::command]
+pub async fn set_background_policy(
+    app: AppHandle,
+    background: State<'_, Background>,
+    policy: BackgroundPolicy,
+) -> AppResult<BackgroundStatus> {
+    if policy.idle_minutes == 0 {
+        return Err(AppError::InvalidInput(
+            "the idle time must be at least a minute".into(),
+        ));
+    }
+    if policy.throttled_sync_minutes == 0 {
+        return Err(AppError::InvalidInput(
+            "the throttled sync interval must be at least a minute".into(),
+        ));
+    }
+    config::update(&app, |c| c.background = policy)?;
+    Ok(background.status())
+}
+
+#[tauri::command]
+pub async fn get_background_status(
+    background: State<'_, Background>,
+) -> AppResult<BackgroundStatus> {
+    Ok(background.status())
+}
@@ -0,0 +1,202 @@
+//! Rolling copies of the database kept on this machine, independent of cloud backups.
+//!
+//! A copy is made daily and before every migration under `backups/local` in the data dir.
+//! `VACUUM INTO` writes a consistent copy, encrypted under the same key when encryption at
+//! rest is on, which must pass `PRAGMA integrity_check` before it is renamed into place. The
+//! daily job then prunes by the configured retention.
+
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use jiff::civil::DateTime;
+use jiff::tz::TimeZone;
+use jiff::Timestamp;
+use rusqlite::Connection;
+use serde::Serialize;
+use tauri::{Manager, State};
+
+use super::{Retention, DAY_MS, KEY_TIME_FORMAT};
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::jobs::Scheduler;
+use crate::state::AppState;
+
+const LOCAL_JOB: &str = "backup.local";
+const LOCAL_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+const LOCAL_DIR_NAME: &str = "backups/local";
+const EXTENSION: &str = "db";
+
+/// Why a copy was made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LocalBackupReason {
+    Daily,
+    /// Before migrating away from `schema_version`.
+    PreMigration,
+}
+
+/// A database copy in the local backups folder.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalBackup {
+    pub path: PathBuf,
+    pub reason: LocalBackupReason,
+    /// The schema the copy was taken at, for pre-migration copies.
+    pub schema_version: Option<i64>,
+    /// Unix ms, from the file name.
+    pub created_at: i64,
+    pub size: u64,
+}
+
+/// The local backups folder under `data_dir`.
+pub(crate) fn dir(data_dir: &Path) -> PathBuf {
+    data_dir.join(LOCAL_DIR_NAME)
+}
+
+// `<timestamp>-daily.db` or `<timestamp>-v<schema>.db`
+fn file_name(reason: LocalBackupReason, schema_version: i64) -> String {
+    let stamp = Timestamp::now().strftime(KEY_TIME_FORMAT);
+    match reason {
+        LocalBackupReason::Daily => format!("{stamp}-daily.{EXTENSION}"),
+        LocalBackupReason::PreMigration => format!("{stamp}-v{schema_version}.{EXTENSION}"),
+    }
+}
+
+pub(super) fn parse(path: PathBuf) -> Option<LocalBackup> {
+    let name = path.file_name()?.to_str()?;
+    let (stamp, kind) = name
+        .strip_suffix(&format!(".{EXTENSION}"))?
+        .split_once('-')?;
+    let (reason, schema_version) = match kind {
+        "daily" => (LocalBackupReason::Daily, None),
+        kind => (
+            LocalBackupReason::PreMigration,
+            Some(kind.strip_prefix('v')?.parse().ok()?),
+        ),
+    };
+    let created_at = DateTime::strptime(KEY_TIME_FORMAT, stamp)
+        .ok()?
+        .to_zoned(TimeZone::UTC)
+        .ok()?
+        .timestamp()
+        .as_millisecond();
+    let size = fs::metadata(&path).ok()?.len();
+    Some(LocalBackup {
+        path,
+        reason,
+        schema_version,
+        created_at,
+        size,
+    })
+}
+
+/// Run `PRAGMA integrity_check` on the attached database `schema`.
+pub(super) fn check_integrity(conn: &Connection, schema: &str) -> AppResult<()> {
+    let problems = conn
+        .prepare(&format!("PRAGMA {schema}.integrity_check"))?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    if problems != ["ok"] {
+        return Err(AppError::Backup(format!(
+            "the database failed its integrity check: {}",
+            problems.join("; ")
+        )));
+    }
+    Ok(())
+}
+
+/// Copy the database to `dir`, check the copy, and move it into place; returns its path.
+///
+/// `schema_version` names pre-migration copies. Must run outside a transaction.
+pub(crate) fn snapshot(
+    conn: &Connection,
+    dir: &Path,
+    reason: LocalBackupReason,
+    schema_version: i64,
+) -> AppResult<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let target = dir.join(file_name(reason, schema_version));
+    let tmp = target.with_extension(format!("{EXTENSION}.tmp"));
+    let _ = fs::remove_file(&tmp);
+    let result = (|| -> AppResult<()> {
+        conn.execute("VACUUM INTO ?1", [tmp.to_string_lossy()])?;
+        // Attached without a key, the copy is read with the main database's
+        conn.execute(
+            "ATTACH DATABASE ?1 AS verify",
+            [tmp.to_string_lossy().into_owned()],
+        )?;
+        let checked = check_integrity(conn, "verify");
+        conn.execute_batch("DETACH DATABASE verify;")?;
+        checked?;
+        fs::File::open(&tmp)?.sync_all()?;
+        fs::rename(&tmp, &target)?;
+        Ok(())
+    })();
+    if let Err(err) = result {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+    Ok(target)
+}
+
+/// Every copy in `dir`, newest first.
+pub(crate) fn list(dir: &Path) -> AppResult<Vec<LocalBackup>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let mut backups: Vec<LocalBackup> = entries
+        .filter_map(|entry| parse(entry.ok()?.path()))
+        .collect();
+    backups.sort_by_key(|backup| Reverse(backup.created_at));
+    Ok(backups)
+}
+
+// Same rules as the cloud: the newest always stays
+fn prune(dir: &Path, retention: &Retention) -> AppResult<usize> {
+    let cutoff = retention
+        .keep_days
+        .map(|days| now_ms() - i64::from(days) * DAY_MS);
+    let mut removed = 0;
+    for (index, backup) in list(dir)?.iter().enumerate().skip(1) {
+        let surplus = index >= retention.keep_last.max(1) as usize;
+        let expired = cutoff.is_some_and(|cutoff| backup.created_at < cutoff);
+        if surplus || expired {
+            fs::remove_file(&backup.path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(LOCAL_JOB, Some(LOCAL_INTERVAL), |app| async move {
+        let state = app.state::<AppState>();
+        let dir = dir(state.data_dir());
+        let retention = state.config().backup.local;
+        let path = app
+            .state::<Db>()
+            .run(move |conn| {
+                let path = snapshot(conn, &dir, LocalBackupReason::Daily, 0)?;
+                match prune(&dir, &retention) {
+                    Ok(0) => {}
+                    Ok(removed) => tracing::info!(removed, "old local backups removed"),
+                    Err(err) => tracing::warn!(%err, "could not prune old local backups"),
+                }
+                Ok(path)
+            })
+            .await?;
+        tracing::info!(path = %path.display(), "local backup written");
+        Ok(())
+    });
+}
+
+/// Database copies on this machine, newest first.
+#[tauri::command]
+pub async fn list_local_backups(state: State<'_, AppState>) -> AppResult<Vec<LocalBackup>> {
+    let dir = dir(state.data_dir());
+    tauri::async_runtime::spawn_blocking(move || list(&dir)).await?
+}
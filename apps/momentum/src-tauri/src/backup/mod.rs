@@ -0,0 +1,490 @@
+//! Scheduled, encrypted backups of the whole local data set to S3-compatible storage.
+//!
+//! A backup is one [`snapshot`] file: the database, attachment blobs, and project folders,
+//! sealed under a passphrase kept in the OS keychain. It is built in a staging folder, then
+//! uploaded to `<prefix><device>/<timestamp>.mbak` (in parts once it is large) and removed
+//! locally. The scheduler checks hourly and backs up once the newest backup from this device
+//! is older than the configured interval; retention then prunes this device's older backups.
+//! Other devices' backups in the same bucket are listed but never pruned from here. Plain
+//! database copies kept on this machine are in [`local`], and restoring either is in
+//! [`restore`].
+
+pub mod local;
+pub mod restore;
+mod s3;
+mod snapshot;
+
+use std::cmp::Reverse;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use jiff::civil::DateTime;
+use jiff::tz::TimeZone;
+use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+pub use s3::S3Settings;
+
+use self::s3::Bucket;
+use crate::attachments::AttachmentStore;
+use crate::config;
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::http_client::HttpClient;
+use crate::jobs::Scheduler;
+use crate::network::NetworkMonitor;
+use crate::power;
+use crate::progress;
+use crate::secrets;
+use crate::state::AppState;
+use crate::wake_lock;
+
+const CLOUD_JOB: &str = "backup.cloud";
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Emitted with a [`BackupProgress`] while a backup is built and uploaded.
+pub const PROGRESS_EVENT: &str = "backup:progress";
+
+const PASSPHRASE_SECRET_KEY: &str = "backup.passphrase";
+const S3_SECRET_KEY: &str = "backup.s3.secretAccessKey";
+const STAGING_DIR_NAME: &str = "backups/staging";
+// S3 wants parts of at least 5 MiB and at most 10,000 of them
+const PART_SIZE: u64 = 16 * 1024 * 1024;
+pub(crate) const KEY_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+pub(crate) const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// How many backups of this device to keep.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Retention {
+    /// Newest backups kept regardless of age.
+    pub keep_last: u32,
+    /// Backups older than this many days are removed, except the newest; `None` keeps them.
+    pub keep_days: Option<u32>,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            keep_last: 7,
+            keep_days: None,
+        }
+    }
+}
+
+/// Persisted backup settings; the passphrase and secret access key live in the keychain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BackupSettings {
+    /// Where to upload; `None` turns scheduled backups off.
+    pub s3: Option<S3Settings>,
+    /// Hours between scheduled backups.
+    pub interval_hours: u32,
+    pub retention: Retention,
+    /// Retention of the daily and pre-migration copies kept on this machine.
+    pub local: Retention,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            s3: None,
+            interval_hours: 24,
+            retention: Retention::default(),
+            local: Retention {
+                keep_last: 7,
+                keep_days: Some(30),
+            },
+        }
+    }
+}
+
+/// Saved settings plus whether the secrets are stored, which are never returned themselves.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupConfig {
+    pub settings: BackupSettings,
+    pub has_passphrase: bool,
+    pub has_secret_access_key: bool,
+}
+
+/// A backup in the bucket.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudBackup {
+    pub key: String,
+    pub device: String,
+    /// Unix ms, from the object key.
+    pub created_at: i64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupProgress {
+    /// `snapshot` while the file is written, `upload` while it is sent.
+    pub phase: &'static str,
+    pub done: u64,
+    pub total: Option<u64>,
+}
+
+/// Guards against two backups running at once, registered in app state.
+#[derive(Default)]
+pub struct Backups {
+    running: AtomicBool,
+}
+
+struct Running<'a>(&'a AtomicBool);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+impl Backups {
+    fn start(&self) -> AppResult<Running<'_>> {
+        if self.running.swap(true, Ordering::AcqRel) {
+            return Err(AppError::Backup("a backup is already running".into()));
+        }
+        Ok(Running(&self.running))
+    }
+}
+
+// The S3 settings and secret access key, or an error naming what is missing
+async fn storage(app: &AppHandle) -> AppResult<(S3Settings, String)> {
+    let settings = app
+        .state::<AppState>()
+        .config()
+        .backup
+        .s3
+        .ok_or_else(|| AppError::InvalidInput("cloud backups are not configured".into()))?;
+    let secret = tauri::async_runtime::spawn_blocking(|| secrets::get(S3_SECRET_KEY))
+        .await??
+        .ok_or_else(|| AppError::InvalidInput("set the S3 secret access key first".into()))?;
+    Ok((settings, secret))
+}
+
+async fn passphrase() -> AppResult<String> {
+    tauri::async_runtime::spawn_blocking(|| secrets::get(PASSPHRASE_SECRET_KEY))
+        .await??
+        .ok_or_else(|| AppError::InvalidInput("set a backup passphrase first".into()))
+}
+
+// Object keys carry the device, so it must be safe in a path segment
+fn device_name() -> String {
+    let name: String = tauri_plugin_os::hostname()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    match name.trim_matches('.') {
+        "" => "device".into(),
+        name => name.to_string(),
+    }
+}
+
+// `<prefix><device>/<timestamp>.mbak`, or `None` for anything else in the bucket
+fn parse_key(prefix: &str, object: &s3::Object) -> Option<CloudBackup> {
+    let rest = object.key.strip_prefix(prefix)?;
+    let (device, file) = rest.split_once('/')?;
+    let stamp = file.strip_suffix(&format!(".{}", snapshot::EXTENSION))?;
+    let created_at = DateTime::strptime(KEY_TIME_FORMAT, stamp)
+        .ok()?
+        .to_zoned(TimeZone::UTC)
+        .ok()?
+        .timestamp()
+        .as_millisecond();
+    Some(CloudBackup {
+        key: object.key.clone(),
+        device: device.to_string(),
+        created_at,
+        size: object.size,
+    })
+}
+
+// Newest first
+async fn list(bucket: &Bucket<'_>, device: Option<&str>) -> AppResult<Vec<CloudBackup>> {
+    let prefix = match device {
+        Some(device) => format!("{}{device}/", bucket.prefix()),
+        None => bucket.prefix().to_string(),
+    };
+    let mut backups: Vec<CloudBackup> = bucket
+        .list(&prefix)
+        .await?
+        .iter()
+        .filter_map(|object| parse_key(bucket.prefix(), object))
+        .collect();
+    backups.sort_by_key(|backup| Reverse(backup.created_at));
+    Ok(backups)
+}
+
+fn emit_progress(app: &AppHandle, phase: &'static str, done: u64, total: Option<u64>) {
+    let _ = app.emit(PROGRESS_EVENT, BackupProgress { phase, done, total });
+}
+
+/// Write a backup of everything to `target`.
+pub(crate) async fn create_snapshot(
+    app: &AppHandle,
+    target: &Path,
+    passphrase: String,
+) -> AppResult<snapshot::Manifest> {
+    let state = app.state::<AppState>();
+    let database = target.with_extension("db.tmp");
+    let export_to = database.clone();
+    if let Some(dir) = target.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let (database_key, schema_version, blobs) = app
+        .state::<Db>()
+        .run(move |conn| snapshot::export_database(conn, &export_to))
+        .await?;
+    let contents = snapshot::Contents {
+        database: database.clone(),
+        database_key,
+        schema_version,
+        blobs,
+        projects_dir: state.projects_dir(),
+        app_version: app.package_info().version.to_string(),
+        device: tauri_plugin_os::hostname(),
+    };
+    let handle = app.clone();
+    let target = target.to_path_buf();
+    let written = tauri::async_runtime::spawn_blocking(move || {
+        let store = handle.state::<AttachmentStore>();
+        snapshot::write(&target, contents, &store, &passphrase, |done| {
+            emit_progress(&handle, "snapshot", done, None)
+        })
+    })
+    .await?;
+    let _ = fs::remove_file(&database);
+    written
+}
+
+fn read_part(path: &Path, offset: u64, len: u64) -> AppResult<Vec<u8>> {
+    use std::io::{Seek, SeekFrom};
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut part = Vec::with_capacity(len as usize);
+    file.take(len).read_to_end(&mut part)?;
+    Ok(part)
+}
+
+// One PUT when small, a multipart upload otherwise, aborted if any part fails
+async fn upload(app: &AppHandle, bucket: &Bucket<'_>, key: &str, path: &Path) -> AppResult<u64> {
+    let size = fs::metadata(path)?.len();
+    let taskbar = progress::track(app, "backup".into());
+    if size <= PART_SIZE {
+        let source = path.to_path_buf();
+        let body =
+            tauri::async_runtime::spawn_blocking(move || read_part(&source, 0, size)).await??;
+        bucket.put(key, body).await?;
+        emit_progress(app, "upload", size, Some(size));
+        return Ok(size);
+    }
+
+    let upload_id = bucket.start_upload(key).await?;
+    let result = async {
+        let mut etags = Vec::new();
+        let mut offset = 0;
+        while offset < size {
+            let len = PART_SIZE.min(size - offset);
+            let source = path.to_path_buf();
+            let part =
+                tauri::async_runtime::spawn_blocking(move || read_part(&source, offset, len))
+                    .await??;
+            let number = etags.len() as u32 + 1;
+            etags.push(bucket.upload_part(key, &upload_id, number, part).await?);
+            offset += len;
+            taskbar.set(offset, Some(size));
+            emit_progress(app, "upload", offset, Some(size));
+        }
+        bucket.complete_upload(key, &upload_id, &etags).await
+    }
+    .await;
+    if let Err(err) = result {
+        if let Err(abort) = bucket.abort_upload(key, &upload_id).await {
+            tracing::warn!(%abort, key, "could not abort a failed backup upload");
+        }
+        return Err(err);
+    }
+    Ok(size)
+}
+
+// Keeps the newest `keep_last`, drops anything past `keep_days`, and never the newest
+async fn prune(bucket: &Bucket<'_>, device: &str, retention: &Retention) -> AppResult<usize> {
+    let backups = list(bucket, Some(device)).await?;
+    let cutoff = retention
+        .keep_days
+        .map(|days| now_ms() - i64::from(days) * DAY_MS);
+    let mut removed = 0;
+    for (index, backup) in backups.iter().enumerate().skip(1) {
+        let surplus = index >= retention.keep_last.max(1) as usize;
+        let expired = cutoff.is_some_and(|cutoff| backup.created_at < cutoff);
+        if surplus || expired {
+            bucket.delete(&backup.key).await?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+async fn run_backup(app: &AppHandle) -> AppResult<CloudBackup> {
+    let backups = app.state::<Backups>();
+    let _running = backups.start()?;
+    if !app.state::<NetworkMonitor>().is_online() {
+        return Err(AppError::Backup("the network is offline".into()));
+    }
+    let (settings, secret) = storage(app).await?;
+    let passphrase = passphrase().await?;
+    let retention = app.state::<AppState>().config().backup.retention;
+    let http = app.state::<HttpClient>();
+    let bucket = Bucket::new(&http, &settings, &secret)?;
+    let _awake = wake_lock::hold(app, "Backing up to the cloud");
+
+    let device = device_name();
+    let now = Timestamp::now();
+    let created_at = now.as_millisecond();
+    let stamp = now.strftime(KEY_TIME_FORMAT);
+    let key = format!(
+        "{}{device}/{stamp}.{}",
+        bucket.prefix(),
+        snapshot::EXTENSION
+    );
+    let staging = app
+        .state::<AppState>()
+        .data_dir()
+        .join(STAGING_DIR_NAME)
+        .join(format!("{stamp}.{}", snapshot::EXTENSION));
+
+    let result = async {
+        create_snapshot(app, &staging, passphrase).await?;
+        upload(app, &bucket, &key, &staging).await
+    }
+    .await;
+    let _ = fs::remove_file(&staging);
+    let size = result?;
+    tracing::info!(key, size, "cloud backup uploaded");
+
+    match prune(&bucket, &device, &retention).await {
+        Ok(0) => {}
+        Ok(removed) => tracing::info!(removed, "old cloud backups removed"),
+        // The backup itself landed; pruning is retried after the next one
+        Err(err) => tracing::warn!(%err, "could not prune old cloud backups"),
+    }
+    Ok(CloudBackup {
+        key,
+        device,
+        created_at,
+        size,
+    })
+}
+
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(CLOUD_JOB, Some(CHECK_INTERVAL), |app| async move {
+        let backup = app.state::<AppState>().config().backup;
+        if backup.s3.is_none() || !app.state::<NetworkMonitor>().is_online() {
+            return Ok(());
+        }
+        // Checked again hourly, so a backup due on battery runs soon after plugging in
+        if power::defer_heavy_work(&app) {
+            return Ok(());
+        }
+        let (settings, secret) = storage(&app).await?;
+        let latest = {
+            let http = app.state::<HttpClient>();
+            let bucket = Bucket::new(&http, &settings, &secret)?;
+            list(&bucket, Some(&device_name()))
+                .await?
+                .first()
+                .map(|b| b.created_at)
+        };
+        let interval = i64::from(backup.interval_hours.max(1)) * 60 * 60 * 1000;
+        if latest.is_some_and(|at| now_ms() - at < interval) {
+            return Ok(());
+        }
+        run_backup(&app).await?;
+        Ok(())
+    });
+}
+
+#[tauri::command]
+pub async fn get_backup_settings(state: State<'_, AppState>) -> AppResult<BackupConfig> {
+    let settings = state.config().backup;
+    let (has_passphrase, has_secret_access_key) = tauri::async_runtime::spawn_blocking(|| {
+        Ok::<_, AppError>((
+            secrets::get(PASSPHRASE_SECRET_KEY)?.is_some(),
+            secrets::get(S3_SECRET_KEY)?.is_some(),
+        ))
+    })
+    .await??;
+    Ok(BackupConfig {
+        settings,
+        has_passphrase,
+        has_secret_access_key,
+    })
+}
+
+/// Save backup settings.
+///
+/// `passphrase` and `secret_access_key` replace the stored ones when given; an empty string
+/// removes them. Backups made under an old passphrase still need it to restore.
+#[tauri::command]
+pub async fn set_backup_settings(
+    app: AppHandle,
+    http: State<'_, HttpClient>,
+    settings: BackupSettings,
+    passphrase: Option<String>,
+    secret_access_key: Option<String>,
+) -> AppResult<()> {
+    if let Some(s3) = &settings.s3 {
+        // Validates the endpoint and bucket before anything persists
+        Bucket::new(&http, s3, "")?;
+    }
+    if settings.interval_hours == 0 {
+        return Err(AppError::InvalidInput(
+            "the interval must be at least an hour".into(),
+        ));
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        for (key, value) in [
+            (PASSPHRASE_SECRET_KEY, passphrase),
+            (S3_SECRET_KEY, secret_access_key),
+        ] {
+            match value.as_deref() {
+                Some("") => secrets::delete(key)?,
+                Some(value) => secrets::set(key, value)?,
+                None => {}
+            }
+        }
+        Ok::<_, AppError>(())
+    })
+    .await??;
+    config::update(&app, |c| c.backup = settings)?;
+    Ok(())
+}
+
+/// Back up now, whatever the schedule says.
+#[tauri::command]
+pub async fn backup_now(app: AppHandle) -> AppResult<CloudBackup> {
+    run_backup(&app).await
+}
+
+/// Every backup under the configured prefix, from all devices, newest first.
+#[tauri::command]
+pub async fn list_cloud_backups(
+    app: AppHandle,
+    http: State<'_, HttpClient>,
+) -> AppResult<Vec<CloudBackup>> {
+    let (settings, secret) = storage(&app).await?;
+    let bucket = Bucket::new(&http, &settings, &secret)?;
+    list(&bucket, None).await
+}
@@ -0,0 +1,511 @@
+//! Restoring from a backup file: a full `.mbak` snapshot or a local database copy.
+//!
+//! Everything is first unpacked under `backups/restore` and checked: every snapshot entry
+//! against the SHA-256 in its manifest, the database with `PRAGMA integrity_check`, and its
+//! schema against what this build can migrate. The database is then re-exported under this
+//! install's key, so a backup made with encryption at rest off restores into an encrypted
+//! install and the other way round. A dry run stops there and reports what would change. A
+//! real restore swaps the database, and for snapshots the attachment store and project
+//! folders, into place and moves what was there to `backups/pre-restore/<timestamp>`.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+
+use jiff::Timestamp;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, Manager};
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use super::snapshot::{
+    self, Manifest, ATTACHMENTS_PREFIX, DATABASE_ENTRY, MANIFEST_ENTRY, PROJECTS_PREFIX,
+};
+use super::{local, Backups, KEY_TIME_FORMAT};
+use crate::archive;
+use crate::attachments::{self, AttachmentStore};
+use crate::db::{migrations, Db};
+use crate::encryption::Encryption;
+use crate::error::{AppError, AppResult};
+use crate::search;
+use crate::state::AppState;
+use crate::wake_lock;
+
+/// Emitted with the [`RestoreReport`] once a restore has been swapped in.
+pub const RESTORED_EVENT: &str = "backup:restored";
+
+const RESTORE_DIR_NAME: &str = "backups/restore";
+const SAFETY_DIR_NAME: &str = "backups/pre-restore";
+const DB_FILE_NAME: &str = "momentum.db";
+// Row counts compared in the report; tables an older schema lacks count as empty
+const COMPARED_TABLES: &[&str] = &[
+    "projects",
+    "items",
+    "attachments",
+    "reminders",
+    "timers",
+    "document_templates",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RestoreMode {
+    /// Verify and report without touching any data.
+    DryRun,
+    Restore,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BackupKind {
+    /// A `.mbak` file with the database, attachments, and project folders.
+    Snapshot,
+    /// A database copy from the local backups folder.
+    Database,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableCount {
+    pub table: &'static str,
+    pub current: i64,
+    pub backup: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProjectChange {
+    /// Only in the backup; restoring brings it back.
+    Added,
+    /// Only here; restoring removes it.
+    Removed,
+    /// In both, last updated at a different time.
+    Changed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectDiff {
+    pub id: String,
+    pub name: String,
+    pub change: ProjectChange,
+}
+
+/// What a restore found and, for a dry run, what it would change.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreReport {
+    pub kind: BackupKind,
+    pub created_at: Option<i64>,
+    pub device: Option<String>,
+    pub schema_version: i64,
+    pub current_schema_version: i64,
+    pub tables: Vec<TableCount>,
+    pub projects: Vec<ProjectDiff>,
+    /// Attachment blobs and project files restored alongside, for snapshots.
+    pub attachments: usize,
+    pub project_files: usize,
+    pub restored: bool,
+    /// Where the replaced data was moved.
+    pub safety_copy: Option<PathBuf>,
+}
+
+// A verified restore waiting in its staging folder
+struct Staged {
+    kind: BackupKind,
+    database: PathBuf,
+    attachments: Option<PathBuf>,
+    projects: Option<PathBuf>,
+    created_at: Option<i64>,
+    device: Option<String>,
+    schema_version: i64,
+    attachment_count: usize,
+    project_file_count: usize,
+}
+
+#[derive(Debug, Default)]
+struct Summary {
+    counts: BTreeMap<&'static str, i64>,
+    // id to (name, updated_at)
+    projects: BTreeMap<String, (String, i64)>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn mismatch(name: &str) -> AppError {
+    AppError::Backup(format!(
+        "{name} does not match its checksum; the backup is damaged"
+    ))
+}
+
+// Hashes the plaintext on its way out of the zip
+struct Hashing<R> {
+    inner: R,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<R: Read> Read for Hashing<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        self.size += read as u64;
+        Ok(read)
+    }
+}
+
+fn entry<'a>(
+    zip: &'a mut ZipArchive<BufReader<File>>,
+    name: &str,
+    passphrase: &str,
+) -> AppResult<zip::read::ZipFile<'a, BufReader<File>>> {
+    zip.by_name_decrypt(name, passphrase.as_bytes())
+        .map_err(|err| match err {
+            ZipError::FileNotFound => {
+                AppError::Backup(format!("not a complete backup: {name} is missing"))
+            }
+            ZipError::InvalidPassword => AppError::WrongPassword,
+            err => err.into(),
+        })
+}
+
+// Only plain relative paths, so nothing lands outside the staging folder
+fn safe_relative(path: &str) -> AppResult<&Path> {
+    let relative = Path::new(path);
+    if path.is_empty()
+        || !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return Err(AppError::Backup(format!("unsafe path in backup: {path}")));
+    }
+    Ok(relative)
+}
+
+/// Check `source`, opened with `source_key` (hex), and export it to `target` under
+/// `target_key`; returns its schema version.
+fn adopt(
+    source: &Path,
+    source_key: Option<&str>,
+    target: &Path,
+    target_key: Option<&[u8; 32]>,
+) -> AppResult<i64> {
+    let conn = Connection::open(source)?;
+    if let Some(key) = source_key {
+        conn.execute_batch(&format!("PRAGMA key = \"x'{key}'\";"))?;
+    }
+    local::check_integrity(&conn, "main").map_err(|err| match err {
+        AppError::Backup(_) => err,
+        _ => AppError::Backup("the backup's database cannot be read".into()),
+    })?;
+    let schema = migrations::schema_version(&conn)?;
+    if schema.current > schema.latest {
+        return Err(AppError::Backup(format!(
+            "the backup was made by a newer version of the app (schema {}, this build knows {})",
+            schema.current, schema.latest
+        )));
+    }
+    let _ = fs::remove_file(target);
+    conn.execute(
+        "ATTACH DATABASE ?1 AS restored KEY ?2",
+        [
+            target.to_string_lossy().into_owned(),
+            target_key
+                .map(|key| format!("x'{}'", hex(key)))
+                .unwrap_or_default(),
+        ],
+    )?;
+    let exported = conn.query_row("SELECT sqlcipher_export('restored')", [], |_| Ok(()));
+    conn.execute_batch("DETACH DATABASE restored;")?;
+    exported?;
+    Ok(schema.current)
+}
+
+fn stage_snapshot(
+    path: &Path,
+    passphrase: &str,
+    staging: &Path,
+    store: &AttachmentStore,
+    key: Option<&[u8; 32]>,
+) -> AppResult<Staged> {
+    let mut zip = ZipArchive::new(BufReader::new(File::open(path)?))?;
+    let manifest: Manifest = serde_json::from_reader(entry(&mut zip, MANIFEST_ENTRY, passphrase)?)
+        .map_err(|_| AppError::Backup("the backup manifest is unreadable".into()))?;
+    if manifest.format != snapshot::FORMAT || manifest.version > snapshot::FORMAT_VERSION {
+        return Err(AppError::Backup(format!(
+            "unsupported backup format {} version {}",
+            manifest.format, manifest.version
+        )));
+    }
+
+    let extracted = staging.join(DATABASE_ENTRY);
+    let (size, sha256) = archive::copy_hashed(
+        &mut entry(&mut zip, DATABASE_ENTRY, passphrase)?,
+        &mut File::create(&extracted)?,
+    )?;
+    if size != manifest.database.size || sha256 != manifest.database.sha256 {
+        return Err(mismatch(DATABASE_ENTRY));
+    }
+    let database = staging.join(DB_FILE_NAME);
+    let schema_version = adopt(&extracted, Some(&manifest.database_key), &database, key)?;
+    fs::remove_file(&extracted)?;
+
+    let attachments = staging.join("attachments");
+    fs::create_dir_all(attachments.join("staging"))?;
+    for file in &manifest.attachments {
+        let name = format!("{ATTACHMENTS_PREFIX}{}", file.path);
+        safe_relative(&file.path)?;
+        let mut reader = Hashing {
+            inner: entry(&mut zip, &name, passphrase)?,
+            hasher: Sha256::new(),
+            size: 0,
+        };
+        store.seal_into(
+            &mut reader,
+            &attachments::blob_path_in(&attachments, &file.path),
+        )?;
+        let sha256 = hex(&reader.hasher.finalize());
+        // A blob's name is the hash of its content
+        if reader.size != file.size || sha256 != file.sha256 || sha256 != file.path {
+            return Err(mismatch(&name));
+        }
+    }
+
+    let projects = staging.join("projects");
+    fs::create_dir_all(&projects)?;
+    for file in &manifest.project_files {
+        let name = format!("{PROJECTS_PREFIX}{}", file.path);
+        let target = projects.join(safe_relative(&file.path)?);
+        if let Some(dir) = target.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let (size, sha256) = archive::copy_hashed(
+            &mut entry(&mut zip, &name, passphrase)?,
+            &mut File::create(&target)?,
+        )?;
+        if size != file.size || sha256 != file.sha256 {
+            return Err(mismatch(&name));
+        }
+    }
+
+    Ok(Staged {
+        kind: BackupKind::Snapshot,
+        database,
+        attachments: Some(attachments),
+        projects: Some(projects),
+        created_at: Some(manifest.created_at),
+        device: Some(manifest.device),
+        schema_version,
+        attachment_count: manifest.attachments.len(),
+        project_file_count: manifest.project_files.len(),
+    })
+}
+
+// Local copies carry the key the install had when they were made, which may not be today's
+fn stage_database(path: &Path, staging: &Path, key: Option<&[u8; 32]>) -> AppResult<Staged> {
+    let copy = staging.join("source.db");
+    fs::copy(path, &copy)?;
+    let database = staging.join(DB_FILE_NAME);
+    let current_key = key.map(|key| hex(key));
+    let schema_version = match adopt(&copy, current_key.as_deref(), &database, key) {
+        Err(_) if current_key.is_some() => adopt(&copy, None, &database, key)?,
+        result => result?,
+    };
+    fs::remove_file(&copy)?;
+    Ok(Staged {
+        kind: BackupKind::Database,
+        database,
+        attachments: None,
+        projects: None,
+        created_at: local::parse(path.to_path_buf()).map(|backup| backup.created_at),
+        device: None,
+        schema_version,
+        attachment_count: 0,
+        project_file_count: 0,
+    })
+}
+
+fn summarize(conn: &Connection) -> AppResult<Summary> {
+    let mut summary = Summary::default();
+    for &table in COMPARED_TABLES {
+        let count = conn
+            .query_row(&format!("SELECT count(*) FROM {table}"), [], |row| {
+                row.get(0)
+            })
+            .unwrap_or(0);
+        summary.counts.insert(table, count);
+    }
+    summary.projects = conn
+        .prepare("SELECT id, name, updated_at FROM projects")?
+        .query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(summary)
+}
+
+fn summarize_file(path: &Path, key: Option<&[u8; 32]>) -> AppResult<Summary> {
+    let conn = Connection::open(path)?;
+    if let Some(key) = key {
+        conn.execute_batch(&format!("PRAGMA key = \"x'{}'\";", hex(key)))?;
+    }
+    summarize(&conn)
+}
+
+fn diff(current: &Summary, backup: &Summary) -> (Vec<TableCount>, Vec<ProjectDiff>) {
+    let tables = COMPARED_TABLES
+        .iter()
+        .map(|&table| TableCount {
+            table,
+            current: current.counts.get(table).copied().unwrap_or_default(),
+            backup: backup.counts.get(table).copied().unwrap_or_default(),
+        })
+        .collect();
+    let mut projects = Vec::new();
+    for (id, (name, updated_at)) in &backup.projects {
+        let change = match current.projects.get(id) {
+            None => ProjectChange::Added,
+            Some((_, current_updated_at)) if current_updated_at != updated_at => {
+                ProjectChange::Changed
+            }
+            Some(_) => continue,
+        };
+        projects.push(ProjectDiff {
+            id: id.clone(),
+            name: name.clone(),
+            change,
+        });
+    }
+    for (id, (name, _)) in &current.projects {
+        if !backup.projects.contains_key(id) {
+            projects.push(ProjectDiff {
+                id: id.clone(),
+                name: name.clone(),
+                change: ProjectChange::Removed,
+            });
+        }
+    }
+    (tables, projects)
+}
+
+// Each folder moves in one rename; the database has already been swapped by then
+fn swap_folders(staged: &Staged, state: &AppState, safety: &Path) -> AppResult<()> {
+    let folders = [
+        (
+            staged.attachments.as_deref(),
+            attachments::store_dir(state.data_dir()),
+        ),
+        (staged.projects.as_deref(), state.projects_dir()),
+    ];
+    for (source, live) in folders {
+        let Some(source) = source else { continue };
+        if live.exists() {
+            fs::rename(&live, safety.join(live.file_name().unwrap_or_default()))?;
+        }
+        fs::rename(source, &live)?;
+    }
+    Ok(())
+}
+
+/// Verify a backup and, unless `mode` is a dry run, restore it over the current data.
+///
+/// `path` is a `.mbak` snapshot or a copy from the local backups folder. Snapshots open
+/// with `passphrase`, or the stored backup passphrase when it is omitted.
+#[tauri::command]
+pub async fn restore_backup(
+    app: AppHandle,
+    path: PathBuf,
+    mode: RestoreMode,
+    passphrase: Option<String>,
+) -> AppResult<RestoreReport> {
+    let backups = app.state::<Backups>();
+    let _running = backups.start()?;
+    let encryption = app.state::<Encryption>();
+    let key = encryption.key_handle().get();
+    if encryption.is_enabled() && key.is_none() {
+        return Err(AppError::DatabaseLocked);
+    }
+    let is_snapshot = path
+        .extension()
+        .is_some_and(|extension| extension == snapshot::EXTENSION);
+    let passphrase = match (is_snapshot, passphrase) {
+        (false, _) => String::new(),
+        (true, Some(passphrase)) => passphrase,
+        (true, None) => super::passphrase().await?,
+    };
+
+    let data_dir = app.state::<AppState>().data_dir().to_path_buf();
+    let stamp = Timestamp::now().strftime(KEY_TIME_FORMAT).to_string();
+    let staging = data_dir.join(RESTORE_DIR_NAME).join(&stamp);
+    let result = async {
+        fs::create_dir_all(&staging)?;
+        let handle = app.clone();
+        let (directory, source, stage_key) = (staging.clone(), path.clone(), key.clone());
+        let (staged, backup) = tauri::async_runtime::spawn_blocking(move || {
+            let key = stage_key.as_deref();
+            let staged = if is_snapshot {
+                let store = handle.state::<AttachmentStore>();
+                stage_snapshot(&source, &passphrase, &directory, &store, key)?
+            } else {
+                stage_database(&source, &directory, key)?
+            };
+            let backup = summarize_file(&staged.database, key)?;
+            Ok::<_, AppError>((staged, backup))
+        })
+        .await??;
+
+        let db = app.state::<Db>().inner().clone();
+        let (current, current_schema_version) = db
+            .run(|conn| Ok((summarize(conn)?, migrations::schema_version(conn)?.current)))
+            .await?;
+        let (tables, projects) = diff(&current, &backup);
+        let mut report = RestoreReport {
+            kind: staged.kind,
+            created_at: staged.created_at,
+            device: staged.device.clone(),
+            schema_version: staged.schema_version,
+            current_schema_version,
+            tables,
+            projects,
+            attachments: staged.attachment_count,
+            project_files: staged.project_file_count,
+            restored: false,
+            safety_copy: None,
+        };
+        if mode == RestoreMode::DryRun {
+            return Ok(report);
+        }
+
+        let _awake = wake_lock::hold(&app, "Restoring a backup");
+        let safety = data_dir.join(SAFETY_DIR_NAME).join(&stamp);
+        let (kept, handle) = (safety.clone(), app.clone());
+        tauri::async_runtime::spawn_blocking(move || {
+            fs::create_dir_all(&kept)?;
+            db.replace(&staged.database, &kept.join(DB_FILE_NAME), key.as_deref())?;
+            swap_folders(&staged, &handle.state::<AppState>(), &kept)
+        })
+        .await?
+        .map_err(|err| {
+            tracing::error!(%err, safety = %safety.display(), "restore did not finish");
+            err
+        })?;
+        tracing::info!(source = %path.display(), safety = %safety.display(), "backup restored");
+        report.restored = true;
+        report.safety_copy = Some(safety);
+        Ok::<_, AppError>(report)
+    }
+    .await;
+    let _ = fs::remove_dir_all(&staging);
+    let report = result?;
+
+    if report.restored {
+        if let Err(err) = search::rebuild(&app).await {
+            tracing::warn!(%err, "search index not rebuilt after restore");
+        }
+        let _ = app.emit(RESTORED_EVENT, &report);
+    }
+    Ok(report)
+}
@@ -0,0 +1,378 @@
+//! The handful of S3 calls backups need, presigned with AWS Signature Version 4 by `rusty-s3`.
+//!
+//! Works against AWS and S3-compatible services (MinIO, R2, B2, Wasabi). `rusty-s3` only
+//! builds the signed URLs and reads the answers; requests go through the shared HTTP client,
+//! so proxy settings apply. Uploads go up in parts small enough to hold in memory.
+
+use std::time::Duration;
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use rusty_s3::actions::{CreateMultipartUpload, ListObjectsV2};
+use rusty_s3::{Credentials, S3Action, UrlStyle};
+use serde::{Deserialize, Serialize};
+use tauri_plugin_http::reqwest::header::ETAG;
+use tauri_plugin_http::reqwest::{Method, Response};
+use url::Url;
+
+use crate::error::{AppError, AppResult};
+use crate::http_client::{HttpClient, RetryPolicy};
+
+/// Where backups are stored; the secret access key lives in the keychain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct S3Settings {
+    /// Service URL, e.g. `https://s3.eu-west-1.amazonaws.com` or a MinIO server.
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: String,
+    /// Prepended to every object key, e.g. `momentum/`.
+    pub prefix: String,
+    pub access_key_id: String,
+    /// Address the bucket in the path rather than the host name; most self-hosted services
+    /// need this.
+    pub path_style: bool,
+}
+
+impl Default for S3Settings {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            region: "us-east-1".into(),
+            bucket: String::new(),
+            prefix: "momentum/".into(),
+            access_key_id: String::new(),
+            path_style: true,
+        }
+    }
+}
+
+/// An object in a listing.
+#[derive(Debug, Clone)]
+pub(crate) struct Object {
+    pub key: String,
+    pub size: u64,
+}
+
+// Long enough to cover the client's retries of a slow part upload
+const SIGNED_FOR: Duration = Duration::from_secs(60 * 60);
+
+fn bucket(settings: &S3Settings) -> AppResult<rusty_s3::Bucket> {
+    let invalid = || AppError::InvalidInput(format!("invalid S3 endpoint {}", settings.endpoint));
+    let mut endpoint = Url::parse(&settings.endpoint).map_err(|_| invalid())?;
+    // The bucket is joined onto the endpoint, which would drop a last segment without `/`
+    if !endpoint.path().ends_with('/') {
+        endpoint.set_path(&format!("{}/", endpoint.path()));
+    }
+    if settings.bucket.is_empty() || settings.access_key_id.is_empty() {
+        return Err(AppError::InvalidInput(
+            "S3 backups need a bucket and an access key".into(),
+        ));
+    }
+    let style = if settings.path_style {
+        UrlStyle::Path
+    } else {
+        UrlStyle::VirtualHost
+    };
+    rusty_s3::Bucket::new(
+        endpoint,
+        style,
+        settings.bucket.clone(),
+        settings.region.clone(),
+    )
+    .map_err(|_| invalid())
+}
+
+/// A signed-request client for one bucket.
+pub(crate) struct Bucket<'a> {
+    http: &'a HttpClient,
+    settings: &'a S3Settings,
+    bucket: rusty_s3::Bucket,
+    credentials: Credentials,
+}
+
+impl<'a> Bucket<'a> {
+    pub(crate) fn new(
+        http: &'a HttpClient,
+        settings: &'a S3Settings,
+        secret_access_key: &'a str,
+    ) -> AppResult<Self> {
+        Ok(Self {
+            http,
+            settings,
+            bucket: bucket(settings)?,
+            credentials: Credentials::new(&settings.access_key_id, secret_access_key),
+        })
+    }
+
+    /// The configured key prefix.
+    pub(crate) fn prefix(&self) -> &str {
+        &self.settings.prefix
+    }
+
+    // Sends to a presigned URL; anything but a 2xx becomes an error carrying S3's own code
+    async fn send(&self, method: Method, url: Url, body: Vec<u8>) -> AppResult<Response> {
+        let request = self.http.client().request(method, url).body(body);
+        let response = self.http.send(request, RetryPolicy::default()).await?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        let field = |name: &str| xml_values(&body, name).into_iter().next();
+        let reason = match (field("Code"), field("Message")) {
+            (Some(code), Some(message)) => format!("{code}: {message}"),
+            (Some(code), None) => code,
+            _ => status.to_string(),
+        };
+        Err(AppError::Backup(format!(
+            "storage refused the request ({reason})"
+        )))
+    }
+
+    pub(crate) async fn put(&self, key: &str, body: Vec<u8>) -> AppResult<()> {
+        let url = self
+            .bucket
+            .put_object(Some(&self.credentials), key)
+            .sign(SIGNED_FOR);
+        self.send(Method::PUT, url, body).await?;
+        Ok(())
+    }
+
+    pub(crate) async fn delete(&self, key: &str) -> AppResult<()> {
+        let url = self
+            .bucket
+            .delete_object(Some(&self.credentials), key)
+            .sign(SIGNED_FOR);
+        self.send(Method::DELETE, url, Vec::new()).await?;
+        Ok(())
+    }
+
+    /// Every object under `prefix`, following continuation tokens.
+    pub(crate) async fn list(&self, prefix: &str) -> AppResult<Vec<Object>> {
+        let mut objects = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut action = self.bucket.list_objects_v2(Some(&self.credentials));
+            action.with_prefix(prefix);
+            if let Some(token) = &token {
+                action.with_continuation_token(token.as_str());
+            }
+            let url = action.sign(SIGNED_FOR);
+            let body = self
+                .send(Method::GET, url, Vec::new())
+                .await?
+                .text()
+                .await?;
+            let page = ListObjectsV2::parse_response(&body)
+                .map_err(|e| AppError::Backup(format!("unreadable object listing: {e}")))?;
+            objects.extend(page.contents.into_iter().map(|object| Object {
+                key: object.key,
+                size: object.size,
+            }));
+            token = page.next_continuation_token;
+            if token.is_none() {
+                return Ok(objects);
+            }
+        }
+    }
+
+    /// Start a multipart upload; returns its id.
+    pub(crate) async fn start_upload(&self, key: &str) -> AppResult<String> {
+        let url = self
+            .bucket
+            .create_multipart_upload(Some(&self.credentials), key)
+            .sign(SIGNED_FOR);
+        let body = self
+            .send(Method::POST, url, Vec::new())
+            .await?
+            .text()
+            .await?;
+        let started = CreateMultipartUpload::parse_response(&body)
+            .map_err(|_| AppError::Backup("storage did not start the upload".into()))?;
+        Ok(started.upload_id().to_string())
+    }
+
+    /// Upload part `number` (from 1); returns the ETag completing the upload needs.
+    pub(crate) async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        number: u32,
+        body: Vec<u8>,
+    ) -> AppResult<String> {
+        let number = u16::try_from(number)
+            .map_err(|_| AppError::Backup("the backup has too many parts".into()))?;
+        let url = self
+            .bucket
+            .upload_part(Some(&self.credentials), key, number, upload_id)
+            .sign(SIGNED_FOR);
+        let response = self.send(Method::PUT, url, body).await?;
+        response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(str::to_string)
+            .ok_or_else(|| AppError::Backup("storage returned no ETag for a part".into()))
+    }
+
+    pub(crate) async fn complete_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        etags: &[String],
+    ) -> AppResult<()> {
+        let action = self.bucket.complete_multipart_upload(
+            Some(&self.credentials),
+            key,
+            upload_id,
+            etags.iter().map(String::as_str),
+        );
+        let url = action.sign(SIGNED_FOR);
+        let answer = self
+            .send(Method::POST, url, action.body().into_bytes())
+            .await?
+            .text()
+            .await?;
+        // S3 can fail a completion after already answering 200
+        if let Some(code) = xml_values(&answer, "Code").into_iter().next() {
+            return Err(AppError::Backup(format!(
+                "storage could not complete the upload ({code})"
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn abort_upload(&self, key: &str, upload_id: &str) -> AppResult<()> {
+        let url = self
+            .bucket
+            .abort_multipart_upload(Some(&self.credentials), key, upload_id)
+            .sign(SIGNED_FOR);
+        self.send(Method::DELETE, url, Vec::new()).await?;
+        Ok(())
+    }
+}
+
+// Text of every element named `name`, in document order; S3's answers are flat enough
+fn xml_values(xml: &str, name: &str) -> Vec<String> {
+    let mut reader = Reader::from_str(xml);
+    let mut values = Vec::new();
+    let mut inside = false;
+    let mut text = String::new();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => {
+                inside = start.local_name().as_ref() == name.as_bytes();
+                text.clear();
+            }
+            Ok(Event::Text(chunk)) if inside => {
+                if let Ok(chunk) = chunk.decode() {
+                    text.push_str(&chunk);
+                }
+            }
+            Ok(Event::GeneralRef(reference)) if inside => {
+                let entity = match &*reference {
+                    b"amp" => "&",
+                    b"lt" => "<",
+                    b"gt" => ">",
+                    b"quot" => "\"",
+                    b"apos" => "'",
+                    _ => "",
+                };
+                text.push_str(entity);
+            }
+            Ok(Event::End(end)) => {
+                if inside && end.local_name().as_ref() == name.as_bytes() {
+                    values.push(std::mem::take(&mut text));
+                }
+                inside = false;
+            }
+            Ok(Event::Eof) | Err(_) => return values,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(endpoint: &str, path_style: bool) -> S3Settings {
+        S3Settings {
+            endpoint: endpoint.into(),
+            region: "eu-west-1".into(),
+            bucket: "site-backups".into(),
+            access_key_id: "AKIDEXAMPLE".into(),
+            path_style,
+            ..S3Settings::default()
+        }
+    }
+
+    fn signed(settings: &S3Settings, key: &str) -> Url {
+        let credentials = Credentials::new("AKIDEXAMPLE", "secret");
+        bucket(settings)
+            .unwrap()
+            .put_object(Some(&credentials), key)
+            .sign(SIGNED_FOR)
+    }
+
+    #[test]
+    fn path_style_keeps_the_endpoint_path() {
+        let url = signed(
+            &settings("https://storage.example.com/s3", true),
+            "momentum/2026 backup.mbk",
+        );
+        assert_eq!(url.host_str(), Some("storage.example.com"));
+        assert_eq!(url.path(), "/s3/site-backups/momentum/2026%20backup.mbk");
+    }
+
+    #[test]
+    fn virtual_host_style_puts_the_bucket_in_the_host() {
+        let url = signed(
+            &settings("https://s3.eu-west-1.amazonaws.com", false),
+            "momentum/a.mbk",
+        );
+        assert_eq!(
+            url.host_str(),
+            Some("site-backups.s3.eu-west-1.amazonaws.com")
+        );
+        assert_eq!(url.path(), "/momentum/a.mbk");
+    }
+
+    #[test]
+    fn requests_are_signed_for_the_region_and_key() {
+        let url = signed(&settings("http://127.0.0.1:9000", true), "momentum/a.mbk");
+        let query: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        let value = |name: &str| {
+            query
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(value("X-Amz-Algorithm"), Some("AWS4-HMAC-SHA256"));
+        let credential = value("X-Amz-Credential").unwrap();
+        assert!(credential.starts_with("AKIDEXAMPLE/"));
+        assert!(credential.ends_with("/eu-west-1/s3/aws4_request"));
+        assert_eq!(value("X-Amz-Signature").map(str::len), Some(64));
+    }
+
+    #[test]
+    fn incomplete_settings_are_refused() {
+        assert!(bucket(&settings("ftp://storage.example.com", true)).is_err());
+        assert!(bucket(&settings("not a url", true)).is_err());
+        let mut no_bucket = settings("https://storage.example.com", true);
+        no_bucket.bucket.clear();
+        assert!(bucket(&no_bucket).is_err());
+    }
+
+    #[test]
+    fn error_codes_are_read_from_the_answer() {
+        let answer = "<?xml version=\"1.0\"?><Error><Code>NoSuchBucket</Code>\
+                      <Message>The bucket &amp; key do not exist</Message></Error>";
+        assert_eq!(xml_values(answer, "Code"), ["NoSuchBucket"]);
+        assert_eq!(
+            xml_values(answer, "Message"),
+            ["The bucket & key do not exist"]
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! The backup file: one zip holding everything needed to rebuild this install's data.
+//!
+//! `database.db` is the database exported through SQLCipher under a key made for that
+//! backup alone, so no plaintext copy ever touches the disk even when encryption at rest is
+//! off. Attachment blobs follow as plaintext under `attachments/<sha256>`, project folders
+//! under `projects/`, and `manifest.json` last, carrying the database key and a SHA-256 per
+//! file. Every entry is sealed with AES-256 under the backup passphrase.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zip::write::SimpleFileOptions;
+use zip::{AesMode, ZipWriter};
+
+use crate::archive;
+use crate::attachments::AttachmentStore;
+use crate::db::{migrations, now_ms};
+use crate::error::{AppError, AppResult};
+
+pub(crate) const FORMAT: &str = "momentum-backup";
+pub(crate) const FORMAT_VERSION: u32 = 1;
+/// File extension of a backup.
+pub(crate) const EXTENSION: &str = "mbak";
+pub(crate) const MANIFEST_ENTRY: &str = "manifest.json";
+pub(crate) const DATABASE_ENTRY: &str = "database.db";
+pub(crate) const ATTACHMENTS_PREFIX: &str = "attachments/";
+pub(crate) const PROJECTS_PREFIX: &str = "projects/";
+
+/// What a backup holds; the last entry written.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Manifest {
+    pub format: String,
+    pub version: u32,
+    pub app_version: String,
+    pub created_at: i64,
+    pub device: String,
+    pub schema_version: i64,
+    /// SQLCipher key of `database.db`, as hex.
+    pub database_key: String,
+    pub database: ManifestFile,
+    pub attachments: Vec<ManifestFile>,
+    pub project_files: Vec<ManifestFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ManifestFile {
+    /// Blob hash for attachments, folder-relative path for project files.
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Export the database to `target` under a fresh key; returns the key, the schema version,
+/// and the blob hashes the copy references.
+///
+/// Runs in one read transaction so the rows and the blob list agree.
+pub(crate) fn export_database(
+    conn: &mut Connection,
+    target: &Path,
+) -> AppResult<(String, i64, Vec<String>)> {
+    let key = hex(&rand::random::<[u8; 32]>());
+    let _ = fs::remove_file(target);
+    conn.execute(
+        "ATTACH DATABASE ?1 AS snapshot KEY ?2",
+        [target.to_string_lossy().into_owned(), format!("x'{key}'")],
+    )?;
+    let result = (|| -> AppResult<(i64, Vec<String>)> {
+        let tx = conn.transaction()?;
+        tx.query_row("SELECT sqlcipher_export('snapshot')", [], |_| Ok(()))?;
+        let schema_version = migrations::schema_version(&tx)?.current;
+        let hashes = tx
+            .prepare("SELECT hash FROM attachment_blobs WHERE ref_count > 0 ORDER BY hash")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        tx.commit()?;
+        Ok((schema_version, hashes))
+    })();
+    conn.execute_batch("DETACH DATABASE snapshot;")?;
+    let (schema_version, hashes) = result.inspect_err(|_| {
+        let _ = fs::remove_file(target);
+    })?;
+    Ok((key, schema_version, hashes))
+}
+
+/// Inputs gathered before the zip is written.
+pub(crate) struct Contents {
+    pub database: PathBuf,
+    pub database_key: String,
+    pub schema_version: i64,
+    pub blobs: Vec<String>,
+    pub projects_dir: PathBuf,
+    pub app_version: String,
+    pub device: String,
+}
+
+/// Write the backup to `target`, reporting bytes written so far to `progress`.
+///
+/// Blobs collected since the database was exported are skipped with a warning; nothing in
+/// the copy could still reference them.
+pub(crate) fn write(
+    target: &Path,
+    contents: Contents,
+    store: &AttachmentStore,
+    passphrase: &str,
+    mut progress: impl FnMut(u64),
+) -> AppResult<Manifest> {
+    if let Some(dir) = target.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = target.with_extension(format!("{EXTENSION}.tmp"));
+    let result = (|| -> AppResult<Manifest> {
+        let mut zip = ZipWriter::new(File::create(&tmp)?);
+        let options = SimpleFileOptions::default()
+            .with_aes_encryption(AesMode::Aes256, passphrase)
+            .large_file(true);
+        let mut written = 0u64;
+
+        zip.start_file(DATABASE_ENTRY, options)?;
+        let (size, sha256) = archive::copy_hashed(&mut File::open(&contents.database)?, &mut zip)?;
+        written += size;
+        progress(written);
+        let database = ManifestFile {
+            path: DATABASE_ENTRY.into(),
+            size,
+            sha256,
+        };
+
+        let mut attachments = Vec::with_capacity(contents.blobs.len());
+        for hash in contents.blobs {
+            zip.start_file(format!("{ATTACHMENTS_PREFIX}{hash}"), options)?;
+            let mut hashing = HashingWriter::new(&mut zip);
+            match store.read_into(&hash, &mut hashing) {
+                Ok(()) => {}
+                Err(AppError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                    tracing::warn!(%hash, "attachment blob vanished during backup; skipped");
+                    zip.abort_file()?;
+                    continue;
+                }
+                Err(err) => return Err(err),
+            }
+            let (size, sha256) = hashing.finish();
+            written += size;
+            progress(written);
+            attachments.push(ManifestFile {
+                path: hash,
+                size,
+                sha256,
+            });
+        }
+
+        let mut files = Vec::new();
+        archive::project_files(&contents.projects_dir, "", &mut files)?;
+        let mut project_files = Vec::with_capacity(files.len());
+        for (path, source) in files {
+            zip.start_file(format!("{PROJECTS_PREFIX}{path}"), options)?;
+            let (size, sha256) = archive::copy_hashed(&mut File::open(&source)?, &mut zip)?;
+            written += size;
+            progress(written);
+            project_files.push(ManifestFile { path, size, sha256 });
+        }
+
+        let manifest = Manifest {
+            format: FORMAT.into(),
+            version: FORMAT_VERSION,
+            app_version: contents.app_version,
+            created_at: now_ms(),
+            device: contents.device,
+            schema_version: contents.schema_version,
+            database_key: contents.database_key,
+            database,
+            attachments,
+            project_files,
+        };
+        zip.start_file(MANIFEST_ENTRY, options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+        zip.finish()?.sync_all()?;
+        Ok(manifest)
+    })();
+    match result {
+        Ok(manifest) => {
+            fs::rename(&tmp, target)?;
+            Ok(manifest)
+        }
+        Err(err) => {
+            let _ = fs::remove_file(&tmp);
+            Err(err)
+        }
+    }
+}
+
+// Hashes and counts what passes through, for sources that write rather than read
+struct HashingWriter<'a, W> {
+    inner: &'a mut W,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<'a, W: Write> HashingWriter<'a, W> {
+    fn new(inner: &'a mut W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    fn finish(self) -> (u64, String) {
+        (self.size, hex(&self.hasher.finalize()))
+    }
+}
+
+impl<W: Write> Write for HashingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,232 @@
+//! A count of things waiting on the user, on the macOS dock icon and Windows taskbar button.
+//!
+//! The badge is the sum of several sources: local edits not yet synced, outbox operations the
+//! server rejected, and whatever the frontend reports through `set_badge_count`, such as
+//! overdue tasks. Rust sources are recounted after each sync and outbox rejection and on a
+//! timer, since edits can land from anywhere. macOS and Linux (on launchers that support it)
+//! draw a native badge; Windows has none, so a numbered overlay icon stands in.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::error::AppResult;
+use crate::jobs::Scheduler;
+use crate::outbox::Outbox;
+use crate::sync::SyncEngine;
+
+/// Job kind that recounts the Rust badge sources.
+pub const REFRESH_JOB: &str = "badge.refresh";
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+const SOURCE_FRONTEND: &str = "frontend";
+const SOURCE_UNSYNCED: &str = "unsynced";
+const SOURCE_OUTBOX_FAILED: &str = "outboxFailed";
+
+/// What [`get_badge`] reports.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BadgeCounts {
+    /// The number on the badge.
+    pub total: u64,
+    /// Contribution of each source, keyed `frontend`, `unsynced`, or `outboxFailed`.
+    pub sources: BTreeMap<&'static str, u64>,
+}
+
+/// Badge sources registered in app state.
+#[derive(Default)]
+pub struct Badge {
+    sources: Mutex<BTreeMap<&'static str, u64>>,
+    shown: Mutex<Option<u64>>,
+}
+
+impl Badge {
+    fn counts(&self) -> BadgeCounts {
+        let sources = self
+            .sources
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        BadgeCounts {
+            total: sources.values().sum(),
+            sources,
+        }
+    }
+
+    fn set(&self, app: &AppHandle, source: &'static str, count: u64) -> BadgeCounts {
+        self.sources
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(source, count);
+        let counts = self.counts();
+        let mut shown = self.shown.lock().unwrap_or_else(|e| e.into_inner());
+        if *shown != Some(counts.total) {
+            os::show(app, counts.total);
+            *shown = Some(counts.total);
+        }
+        counts
+    }
+}
+
+/// Recount unsynced changes and rejected outbox operations.
+pub async fn refresh(app: &AppHandle) -> AppResult<()> {
+    let status = app.state::<SyncEngine>().status().await?;
+    // Someone who never synced works local-only, and every edit would count forever
+    let unsynced = match status.last_synced_at {
+        Some(_) => u64::try_from(status.pending).unwrap_or(0),
+        None => 0,
+    };
+    let failed = u64::try_from(app.state::<Outbox>().failed_count().await?).unwrap_or(0);
+    let badge = app.state::<Badge>();
+    badge.set(app, SOURCE_UNSYNCED, unsynced);
+    badge.set(app, SOURCE_OUTBOX_FAILED, failed);
+    Ok(())
+}
+
+/// Recount in the background, after something that changes a count.
+pub fn spawn_refresh(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(err) = refresh(&app).await {
+            tracing::debug!(%err, "badge refresh failed");
+        }
+    });
+}
+
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(REFRESH_JOB, Some(REFRESH_INTERVAL), |app| async move {
+        refresh(&app).await
+    });
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+mod os {
+    use tauri::{AppHandle, Manager};
+
+    use crate::windows::MAIN_WINDOW;
+
+    // The badge belongs to the app here, so any window can set it
+    pub fn show(app: &AppHandle, total: u64) {
+        let Some(window) = app.get_webview_window(MAIN_WINDOW) else {
+            return;
+        };
+        let count = (total > 0).then(|| i64::try_from(total).unwrap_or(i64::MAX));
+        if let Err(err) = window.set_badge_count(count) {
+            tracing::debug!(%err, "cannot set the badge count");
+        }
+    }
+}
+
+#[cfg(windows)]
+mod os {
+    use tauri::image::Image;
+    use tauri::{AppHandle, Manager};
+
+    use crate::windows::MAIN_WINDOW;
+
+    const SIZE: usize = 32;
+    const BADGE_COLOR: [u8; 4] = [0xd9, 0x2d, 0x20, 0xff];
+    const TEXT_COLOR: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+
+    // 3x5 glyphs, one row per entry with the high bit on the left
+    const DIGITS: [[u8; 5]; 10] = [
+        [0b111, 0b101, 0b101, 0b101, 0b111],
+        [0b010, 0b110, 0b010, 0b010, 0b111],
+        [0b111, 0b001, 0b111, 0b100, 0b111],
+        [0b111, 0b001, 0b111, 0b001, 0b111],
+        [0b101, 0b101, 0b111, 0b001, 0b001],
+        [0b111, 0b100, 0b111, 0b001, 0b111],
+        [0b111, 0b100, 0b111, 0b101, 0b111],
+        [0b111, 0b001, 0b010, 0b010, 0b010],
+        [0b111, 0b101, 0b111, 0b101, 0b111],
+        [0b111, 0b101, 0b111, 0b001, 0b111],
+    ];
+    const PLUS: [u8; 5] = [0b000, 0b010, 0b111, 0b010, 0b000];
+
+    fn draw_glyph(pixels: &mut [u8], glyph: &[u8; 5], left: usize, top: usize, scale: usize) {
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let x = left + column * scale + dx;
+                        let y = top + row * scale + dy;
+                        let at = (y * SIZE + x) * 4;
+                        pixels[at..at + 4].copy_from_slice(&TEXT_COLOR);
+                    }
+                }
+            }
+        }
+    }
+
+    // A filled circle with the count, or 9+ past what fits legibly at taskbar size
+    fn render(total: u64) -> Vec<u8> {
+        let mut pixels = vec![0u8; SIZE * SIZE * 4];
+        let radius = SIZE as f32 / 2.0;
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let (dx, dy) = (x as f32 + 0.5 - radius, y as f32 + 0.5 - radius);
+                if dx * dx + dy * dy <= radius * radius {
+                    let at = (y * SIZE + x) * 4;
+                    pixels[at..at + 4].copy_from_slice(&BADGE_COLOR);
+                }
+            }
+        }
+        if total <= 9 {
+            let scale = 4;
+            let (width, height) = (3 * scale, 5 * scale);
+            draw_glyph(
+                &mut pixels,
+                &DIGITS[total as usize],
+                (SIZE - width) / 2,
+                (SIZE - height) / 2,
+                scale,
+            );
+        } else {
+            let scale = 3;
+            let (width, height) = (3 * scale * 2 + scale, 5 * scale);
+            let (left, top) = ((SIZE - width) / 2, (SIZE - height) / 2);
+            draw_glyph(&mut pixels, &DIGITS[9], left, top, scale);
+            draw_glyph(&mut pixels, &PLUS, left + 4 * scale, top, scale);
+        }
+        pixels
+    }
+
+    pub fn show(app: &AppHandle, total: u64) {
+        let Some(window) = app.get_webview_window(MAIN_WINDOW) else {
+            return;
+        };
+        let icon = (total > 0).then(|| Image::new_owned(render(total), SIZE as u32, SIZE as u32));
+        if let Err(err) = window.set_overlay_icon(icon) {
+            tracing::debug!(%err, "cannot set the taskbar overlay");
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", windows)))]
+mod os {
+    use tauri::AppHandle;
+
+    // Mobile badges come with push notifications, which set their own counts
+    pub fn show(_app: &AppHandle, _total: u64) {}
+}
+
+/// Set the frontend's share of the badge, such as overdue tasks; 0 clears it.
+#[tauri::command]
+pub async fn set_badge_count(
+    app: AppHandle,
+    badge: State<'_, Badge>,
+    count: u64,
+) -> AppResult<BadgeCounts> {
+    Ok(badge.set(&app, SOURCE_FRONTEND, count))
+}
+
+#[tauri::command]
+pub async fn get_badge(badge: State<'_, Badge>) -> AppResult<BadgeCounts> {
+    Ok(badge.counts())
+}
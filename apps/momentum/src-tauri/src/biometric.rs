@@ -0,0 +1,203 @@
+//! Face ID / fingerprint gate in front of the keychain-stored session on iOS and Android.
+//!
+//! Mobile builds start locked: `auth::session` refuses to hand out tokens until
+//! `biometric_unlock` succeeds or the user enters their PIN. Too many wrong PINs sign the
+//! device out, so a lost phone cannot be brute-forced into the account. Desktop builds are
+//! never gated here; they use `idle_lock` instead.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use rand::Rng;
+use serde::Serialize;
+use tauri::AppHandle;
+use zeroize::Zeroizing;
+
+use crate::error::{AppError, AppResult};
+use crate::{auth, idle_lock, secrets};
+
+const PIN_SECRET_KEY: &str = "session.pin";
+const MAX_PIN_ATTEMPTS: u32 = 5;
+const PIN_LEN: std::ops::RangeInclusive<usize> = 4..=12;
+
+// Process-wide because `auth::session` is called from places without an app handle
+static LOCKED: AtomicBool = AtomicBool::new(cfg!(mobile));
+static FAILED_PIN_ATTEMPTS: AtomicU32 = AtomicU32::new(0);
+
+/// What the unlock screen can offer.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BiometricStatus {
+    pub locked: bool,
+    pub available: bool,
+    /// `faceId`, `touchId` (any fingerprint reader), or `none`.
+    pub kind: &'static str,
+    pub pin_set: bool,
+    /// Why biometrics cannot be used right now, such as nothing being enrolled.
+    pub unavailable_reason: Option<String>,
+}
+
+/// Fail with `SESSION_LOCKED` until the user has unlocked this launch.
+pub fn ensure_unlocked() -> AppResult<()> {
+    if LOCKED.load(Ordering::SeqCst) {
+        return Err(AppError::SessionLocked);
+    }
+    Ok(())
+}
+
+fn unlock(app: &AppHandle) {
+    FAILED_PIN_ATTEMPTS.store(0, Ordering::SeqCst);
+    if LOCKED.swap(false, Ordering::SeqCst) {
+        tracing::info!("session unlocked on device");
+    }
+    idle_lock::release(app);
+}
+
+#[cfg(mobile)]
+mod platform {
+    use tauri::AppHandle;
+    use tauri_plugin_biometric::{AuthOptions, BiometricExt, BiometryType};
+
+    use crate::error::{AppError, AppResult};
+
+    pub fn status(app: &AppHandle) -> (bool, &'static str, Option<String>) {
+        match app.biometric().status() {
+            Ok(status) => {
+                let kind = match status.biometry_type {
+                    BiometryType::FaceID => "faceId",
+                    BiometryType::TouchID => "touchId",
+                    BiometryType::None => "none",
+                };
+                (status.is_available, kind, status.error)
+            }
+            Err(err) => (false, "none", Some(err.to_string())),
+        }
+    }
+
+    // The PIN is ours, so the device passcode is not offered as a fallback
+    pub fn authenticate(app: &AppHandle, reason: String) -> AppResult<()> {
+        app.biometric()
+            .authenticate(
+                reason,
+                AuthOptions {
+                    allow_device_credential: false,
+                    fallback_title: Some("Use PIN".into()),
+                    title: Some("Unlock Momentum".into()),
+                    ..Default::default()
+                },
+            )
+            .map_err(|e| AppError::Auth(format!("biometric check failed: {e}")))
+    }
+}
+
+#[cfg(desktop)]
+mod platform {
+    use tauri::AppHandle;
+
+    use crate::error::{AppError, AppResult};
+
+    pub fn status(_app: &AppHandle) -> (bool, &'static str, Option<String>) {
+        (
+            false,
+            "none",
+            Some("biometric unlock is only on mobile".into()),
+        )
+    }
+
+    pub fn authenticate(_app: &AppHandle, _reason: String) -> AppResult<()> {
+        Err(AppError::InvalidInput(
+            "biometric unlock is only available on mobile".into(),
+        ))
+    }
+}
+
+/// Whether the session is gated, and which ways of unlocking it are available.
+#[tauri::command]
+pub async fn get_biometric_status(app: AppHandle) -> AppResult<BiometricStatus> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let (available, kind, unavailable_reason) = platform::status(&app);
+        Ok(BiometricStatus {
+            locked: LOCKED.load(Ordering::SeqCst),
+            available,
+            kind,
+            pin_set: secrets::get(PIN_SECRET_KEY)?.is_some(),
+            unavailable_reason,
+        })
+    })
+    .await?
+}
+
+/// Prompt for Face ID or a fingerprint and unlock the session on success.
+#[tauri::command]
+pub async fn biometric_unlock(app: AppHandle, reason: Option<String>) -> AppResult<()> {
+    let reason = reason.unwrap_or_else(|| "Unlock your estimates".into());
+    tauri::async_runtime::spawn_blocking(move || {
+        platform::authenticate(&app, reason)?;
+        unlock(&app);
+        Ok(())
+    })
+    .await?
+}
+
+/// Set the fallback PIN, or remove it with `None`; only possible while unlocked.
+#[tauri::command]
+pub async fn set_unlock_pin(pin: Option<String>) -> AppResult<()> {
+    ensure_unlocked()?;
+    let pin = pin.map(Zeroizing::new);
+    tauri::async_runtime::spawn_blocking(move || {
+        let Some(pin) = pin else {
+            return secrets::delete(PIN_SECRET_KEY);
+        };
+        if !PIN_LEN.contains(&pin.len()) || !pin.chars().all(|c| c.is_ascii_digit()) {
+            return Err(AppError::InvalidInput(format!(
+                "a PIN is {} to {} digits",
+                PIN_LEN.start(),
+                PIN_LEN.end()
+            )));
+        }
+        let mut salt = [0u8; 16];
+        rand::rng().fill(&mut salt[..]);
+        let salt = SaltString::encode_b64(&salt)
+            .map_err(|e| AppError::Internal(format!("cannot salt the PIN: {e}")))?;
+        let hash = Argon2::default()
+            .hash_password(pin.as_bytes(), &salt)
+            .map_err(|e| AppError::Internal(format!("cannot hash the PIN: {e}")))?;
+        secrets::set(PIN_SECRET_KEY, &hash.to_string())
+    })
+    .await?
+}
+
+/// Unlock with the fallback PIN.
+///
+/// After too many wrong PINs the stored session and PIN are deleted, and the user has to
+/// sign in again.
+#[tauri::command]
+pub async fn unlock_with_pin(app: AppHandle, pin: String) -> AppResult<()> {
+    let pin = Zeroizing::new(pin);
+    tauri::async_runtime::spawn_blocking(move || {
+        let stored = secrets::get(PIN_SECRET_KEY)?
+            .ok_or_else(|| AppError::InvalidInput("no PIN is set".into()))?;
+        let hash = PasswordHash::new(&stored)
+            .map_err(|e| AppError::Internal(format!("stored PIN is unreadable: {e}")))?;
+        if Argon2::default()
+            .verify_password(pin.as_bytes(), &hash)
+            .is_ok()
+        {
+            unlock(&app);
+            return Ok(());
+        }
+        let failed = FAILED_PIN_ATTEMPTS.fetch_add(1, Ordering::SeqCst) + 1;
+        if failed >= MAX_PIN_ATTEMPTS {
+            tracing::warn!("too many wrong PINs; signing out");
+            auth::forget_session()?;
+            secrets::delete(PIN_SECRET_KEY)?;
+            FAILED_PIN_ATTEMPTS.store(0, Ordering::SeqCst);
+            // Nothing is left to protect, and signing in again needs a usable app
+            LOCKED.store(false, Ordering::SeqCst);
+            return Err(AppError::Auth("too many wrong PINs; sign in again".into()));
+        }
+        Err(AppError::WrongPassword)
+    })
+    .await?
+}
@@ -0,0 +1,726 @@
+//! Cost rollups: every item's cost summed up its assembly tree by cost type, then markups
+//! and taxes applied on top, computed in parallel across subtrees.
+//!
+//! Items hang under other items through `cost_structure`, and each carries its
+//! `quantity * unit_cost` as labor, material, equipment, subcontract, or other cost. The
+//! totals of the last run are kept in `cost_rollups`; the next run recomputes only items
+//! edited, moved, or removed since, along with their ancestors, reuses every other subtree
+//! as stored, and returns what changed.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::OnceLock;
+
+use rayon::prelude::*;
+use rusqlite::{params, Connection, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::audit::{self, Change};
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+use crate::undo::{Journal, Table};
+use crate::units::{self, Dimension, Quantity, Unit};
+
+// Node id of the estimate as a whole in `cost_rollups`
+const ESTIMATE_NODE: &str = "";
+// Parallel sums add in no fixed order, so totals within this are the same
+const EPSILON: f64 = 1e-6;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CostType {
+    Labor,
+    #[default]
+    Material,
+    Equipment,
+    Subcontract,
+    Other,
+}
+
+impl CostType {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Labor => "labor",
+            Self::Material => "material",
+            Self::Equipment => "equipment",
+            Self::Subcontract => "subcontract",
+            Self::Other => "other",
+        }
+    }
+
+    pub(crate) fn parse(value: &str) -> Self {
+        match value {
+            "labor" => Self::Labor,
+            "equipment" => Self::Equipment,
+            "subcontract" => Self::Subcontract,
+            "other" => Self::Other,
+            _ => Self::Material,
+        }
+    }
+}
+
+/// Cost split by type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CostBreakdown {
+    pub labor: f64,
+    pub material: f64,
+    pub equipment: f64,
+    pub subcontract: f64,
+    pub other: f64,
+}
+
+impl CostBreakdown {
+    pub(crate) fn of(cost_type: CostType, amount: f64) -> Self {
+        let mut breakdown = Self::default();
+        *breakdown.get_mut(cost_type) = amount;
+        breakdown
+    }
+
+    fn get_mut(&mut self, cost_type: CostType) -> &mut f64 {
+        match cost_type {
+            CostType::Labor => &mut self.labor,
+            CostType::Material => &mut self.material,
+            CostType::Equipment => &mut self.equipment,
+            CostType::Subcontract => &mut self.subcontract,
+            CostType::Other => &mut self.other,
+        }
+    }
+
+    fn get(mut self, cost_type: CostType) -> f64 {
+        *self.get_mut(cost_type)
+    }
+
+    pub fn total(&self) -> f64 {
+        self.labor + self.material + self.equipment + self.subcontract + self.other
+    }
+
+    pub(crate) fn sum(self, other: Self) -> Self {
+        Self {
+            labor: self.labor + other.labor,
+            material: self.material + other.material,
+            equipment: self.equipment + other.equipment,
+            subcontract: self.subcontract + other.subcontract,
+            other: self.other + other.other,
+        }
+    }
+
+    fn differs(&self, other: &Self) -> bool {
+        [
+            self.labor - other.labor,
+            self.material - other.material,
+            self.equipment - other.equipment,
+            self.subcontract - other.subcontract,
+            self.other - other.other,
+        ]
+        .iter()
+        .any(|delta| delta.abs() > EPSILON)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AdjustmentKind {
+    Markup,
+    Tax,
+}
+
+/// A markup or tax applied on top of the subtotal, in `sortOrder`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CostAdjustment {
+    pub id: Option<String>,
+    pub name: String,
+    pub kind: AdjustmentKind,
+    /// A fraction, e.g. `0.08` for 8%.
+    pub rate: f64,
+    /// Cost type the rate applies to; `None` applies it to the running total, so it
+    /// compounds on the adjustments before it.
+    pub applies_to: Option<CostType>,
+    #[serde(default)]
+    pub sort_order: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdjustmentAmount {
+    pub id: String,
+    pub name: String,
+    pub amount: f64,
+}
+
+/// An estimate's bottom line.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EstimateTotals {
+    pub subtotal: CostBreakdown,
+    pub adjustments: Vec<AdjustmentAmount>,
+    pub total: f64,
+    /// Item quantities added up by dimension, each in the unit most of its items use;
+    /// items in units that are not recognized are left out.
+    #[serde(default)]
+    pub quantities: Vec<Quantity>,
+}
+
+/// An item whose rolled-up cost changed; `before` is `None` for a first calculation and
+/// `after` for an item since deleted.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotalChange {
+    pub item_id: String,
+    pub before: Option<CostBreakdown>,
+    pub after: Option<CostBreakdown>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RollupDiff {
+    pub totals: EstimateTotals,
+    /// `None` for the estimate's first calculation.
+    pub previous_totals: Option<EstimateTotals>,
+    pub changes: Vec<TotalChange>,
+    /// Items recomputed; the rest were reused from the last calculation.
+    pub recalculated: usize,
+    pub reused: usize,
+}
+
+/// Where one item sits in the rollup.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CostStructureInput {
+    pub item_id: String,
+    /// Assembly or section item this one rolls up into; `None` puts it at the top.
+    pub parent_id: Option<String>,
+    #[serde(default)]
+    pub cost_type: CostType,
+}
+
+struct Node {
+    id: String,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    direct: CostBreakdown,
+    // Latest edit to the item or its place in the tree
+    changed_at: i64,
+}
+
+struct Stored {
+    parent_id: Option<String>,
+    totals: String,
+    calculated_at: i64,
+}
+
+struct Tree {
+    nodes: Vec<Node>,
+    dirty: Vec<bool>,
+    stored: Vec<Option<CostBreakdown>>,
+    results: Vec<OnceLock<CostBreakdown>>,
+}
+
+impl Tree {
+    // Clean subtrees come back as stored; dirty ones are summed, children in parallel
+    fn total(&self, index: usize) -> CostBreakdown {
+        if !self.dirty[index] {
+            if let Some(stored) = self.stored[index] {
+                return stored;
+            }
+        }
+        let node = &self.nodes[index];
+        let children = node
+            .children
+            .par_iter()
+            .map(|child| self.total(*child))
+            .reduce(CostBreakdown::default, CostBreakdown::sum);
+        *self.results[index].get_or_init(|| node.direct.sum(children))
+    }
+}
+
+fn load_nodes(conn: &Connection, project_id: &str) -> AppResult<Vec<Node>> {
+    let rows = conn
+        .prepare(
+            "SELECT i.id, i.quantity, i.unit_cost, i.updated_at,
+                    s.parent_id, s.cost_type, s.updated_at
+             FROM items i LEFT JOIN cost_structure s ON s.item_id = i.id
+             WHERE i.project_id = ?1",
+        )?
+        .query_map([project_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, f64>(1)? * row.get::<_, f64>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, Option<String>>(4)?,
+                row.get::<_, Option<String>>(5)?,
+                row.get::<_, Option<i64>>(6)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let index: HashMap<&str, usize> = rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| (row.0.as_str(), i))
+        .collect();
+    let mut parents: Vec<Option<usize>> = rows
+        .iter()
+        .map(|row| row.3.as_deref().and_then(|id| index.get(id).copied()))
+        .collect();
+    // A cycle left by concurrent edits would recurse forever; its members go to the top
+    for start in 0..parents.len() {
+        let (mut at, mut steps) = (parents[start], 0);
+        while let Some(parent) = at {
+            steps += 1;
+            if parent == start || steps > parents.len() {
+                parents[start] = None;
+                break;
+            }
+            at = parents[parent];
+        }
+    }
+    let mut nodes: Vec<Node> = rows
+        .into_iter()
+        .zip(&parents)
+        .map(
+            |((id, cost, item_at, _, cost_type, placed_at), parent)| Node {
+                id,
+                parent: *parent,
+                children: Vec::new(),
+                direct: CostBreakdown::of(
+                    CostType::parse(cost_type.as_deref().unwrap_or("")),
+                    cost,
+                ),
+                changed_at: item_at.max(placed_at.unwrap_or(0)),
+            },
+        )
+        .collect();
+    for (child, parent) in parents.iter().enumerate() {
+        if let Some(parent) = parent {
+            nodes[*parent].children.push(child);
+        }
+    }
+    Ok(nodes)
+}
+
+pub(crate) fn load_adjustments(
+    conn: &Connection,
+    project_id: &str,
+) -> AppResult<Vec<CostAdjustment>> {
+    let adjustments = conn
+        .prepare(
+            "SELECT id, name, kind, rate, applies_to, sort_order FROM cost_adjustments
+             WHERE project_id = ?1 ORDER BY sort_order, name",
+        )?
+        .query_map([project_id], |row| {
+            Ok(CostAdjustment {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                kind: match row.get::<_, String>(2)?.as_str() {
+                    "tax" => AdjustmentKind::Tax,
+                    _ => AdjustmentKind::Markup,
+                },
+                rate: row.get(3)?,
+                applies_to: row
+                    .get::<_, Option<String>>(4)?
+                    .as_deref()
+                    .map(CostType::parse),
+                sort_order: row.get(5)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(adjustments)
+}
+
+pub(crate) fn apply_adjustments(
+    subtotal: CostBreakdown,
+    adjustments: &[CostAdjustment],
+) -> EstimateTotals {
+    let mut running = subtotal.total();
+    let amounts = adjustments
+        .iter()
+        .map(|adjustment| {
+            let base = match adjustment.applies_to {
+                Some(cost_type) => subtotal.get(cost_type),
+                None => running,
+            };
+            let amount = base * adjustment.rate;
+            running += amount;
+            AdjustmentAmount {
+                id: adjustment.id.clone().unwrap_or_default(),
+                name: adjustment.name.clone(),
+                amount,
+            }
+        })
+        .collect();
+    EstimateTotals {
+        subtotal,
+        adjustments: amounts,
+        total: running,
+        quantities: Vec::new(),
+    }
+}
+
+fn quantity_totals(conn: &Connection, project_id: &str) -> AppResult<Vec<Quantity>> {
+    let mut by_dimension: BTreeMap<Dimension, Vec<(f64, &'static Unit)>> = BTreeMap::new();
+    let mut stmt = conn.prepare_cached(
+        "SELECT quantity, unit FROM items WHERE project_id = ?1 AND unit IS NOT NULL",
+    )?;
+    let mut rows = stmt.query([project_id])?;
+    while let Some(row) = rows.next()? {
+        if let Some(unit) = units::find(&row.get::<_, String>(1)?) {
+            by_dimension
+                .entry(unit.dimension)
+                .or_default()
+                .push((row.get(0)?, unit));
+        }
+    }
+    let mut quantities = Vec::new();
+    for (_, entries) in by_dimension {
+        let mut counts: Vec<(&'static Unit, usize)> = Vec::new();
+        for (_, unit) in &entries {
+            match counts
+                .iter_mut()
+                .find(|(known, _)| std::ptr::eq(*known, *unit))
+            {
+                Some((_, count)) => *count += 1,
+                None => counts.push((unit, 1)),
+            }
+        }
+        let Some((common, _)) = counts.into_iter().max_by_key(|(_, count)| *count) else {
+            continue;
+        };
+        let mut value = 0.0;
+        for (amount, unit) in entries {
+            value += units::convert(amount, unit, common)?;
+        }
+        quantities.push(Quantity {
+            value,
+            unit: common.symbol.into(),
+        });
+    }
+    Ok(quantities)
+}
+
+fn recalculate(conn: &mut Connection, project_id: &str, full: bool) -> AppResult<RollupDiff> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let exists: bool = tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM projects WHERE id = ?1)",
+        [project_id],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(AppError::not_found("estimate", project_id));
+    }
+    let nodes = load_nodes(&tx, project_id)?;
+    let adjustments = load_adjustments(&tx, project_id)?;
+    let mut stored: HashMap<String, Stored> = tx
+        .prepare(
+            "SELECT node_id, parent_id, totals, calculated_at FROM cost_rollups
+             WHERE project_id = ?1",
+        )?
+        .query_map([project_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                Stored {
+                    parent_id: row.get(1)?,
+                    totals: row.get(2)?,
+                    calculated_at: row.get(3)?,
+                },
+            ))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    let previous_totals = stored
+        .remove(ESTIMATE_NODE)
+        .and_then(|row| serde_json::from_str::<EstimateTotals>(&row.totals).ok());
+
+    let index: HashMap<&str, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node.id.as_str(), i))
+        .collect();
+    let mut dirty = vec![full; nodes.len()];
+    let mut before = vec![None; nodes.len()];
+    for (i, node) in nodes.iter().enumerate() {
+        let current_parent = node.parent.map(|parent| nodes[parent].id.as_str());
+        match stored.get(&node.id) {
+            Some(row) => {
+                before[i] = serde_json::from_str::<CostBreakdown>(&row.totals).ok();
+                let moved = row.parent_id.as_deref() != current_parent;
+                if before[i].is_none() || moved || node.changed_at >= row.calculated_at {
+                    dirty[i] = true;
+                }
+                // The subtree it left needs its total taken down
+                if moved {
+                    if let Some(&old) = row.parent_id.as_deref().and_then(|id| index.get(id)) {
+                        dirty[old] = true;
+                    }
+                }
+            }
+            None => dirty[i] = true,
+        }
+    }
+    let removed: Vec<(String, Stored)> = stored
+        .into_iter()
+        .filter(|(id, _)| !index.contains_key(id.as_str()))
+        .collect();
+    for (_, row) in &removed {
+        if let Some(&parent) = row.parent_id.as_deref().and_then(|id| index.get(id)) {
+            dirty[parent] = true;
+        }
+    }
+    // Every ancestor of a changed item changes with it
+    let seeds: Vec<usize> = (0..nodes.len()).filter(|i| dirty[*i]).collect();
+    for seed in seeds {
+        let mut at = nodes[seed].parent;
+        while let Some(parent) = at {
+            if dirty[parent] {
+                break;
+            }
+            dirty[parent] = true;
+            at = nodes[parent].parent;
+        }
+    }
+
+    let recalculated = dirty.iter().filter(|dirty| **dirty).count();
+    let tree = Tree {
+        results: (0..nodes.len()).map(|_| OnceLock::new()).collect(),
+        stored: before.clone(),
+        nodes,
+        dirty,
+    };
+    let roots: Vec<usize> = (0..tree.nodes.len())
+        .filter(|i| tree.nodes[*i].parent.is_none())
+        .collect();
+    let subtotal = roots
+        .par_iter()
+        .map(|root| tree.total(*root))
+        .reduce(CostBreakdown::default, CostBreakdown::sum);
+    let mut totals = apply_adjustments(subtotal, &adjustments);
+    totals.quantities = quantity_totals(&tx, project_id)?;
+
+    let now = now_ms();
+    let mut changes = Vec::new();
+    {
+        let mut upsert = tx.prepare_cached(
+            "INSERT INTO cost_rollups (project_id, node_id, parent_id, totals, calculated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(project_id, node_id) DO UPDATE SET
+                parent_id = excluded.parent_id,
+                totals = excluded.totals,
+                calculated_at = excluded.calculated_at",
+        )?;
+        for (i, node) in tree.nodes.iter().enumerate() {
+            let Some(after) = tree.results[i].get() else {
+                continue;
+            };
+            let parent_id = node.parent.map(|parent| tree.nodes[parent].id.as_str());
+            upsert.execute(params![
+                project_id,
+                node.id,
+                parent_id,
+                serde_json::to_string(after)?,
+                now
+            ])?;
+            if before[i].is_none_or(|before| before.differs(after)) {
+                changes.push(TotalChange {
+                    item_id: node.id.clone(),
+                    before: before[i],
+                    after: Some(*after),
+                });
+            }
+        }
+        let mut delete =
+            tx.prepare_cached("DELETE FROM cost_rollups WHERE project_id = ?1 AND node_id = ?2")?;
+        for (id, row) in removed {
+            delete.execute(params![project_id, id])?;
+            changes.push(TotalChange {
+                item_id: id,
+                before: serde_json::from_str(&row.totals).ok(),
+                after: None,
+            });
+        }
+        upsert.execute(params![
+            project_id,
+            ESTIMATE_NODE,
+            None::<String>,
+            serde_json::to_string(&totals)?,
+            now
+        ])?;
+    }
+    tx.commit()?;
+    Ok(RollupDiff {
+        totals,
+        previous_totals,
+        changes,
+        recalculated,
+        reused: tree.nodes.len() - recalculated,
+    })
+}
+
+/// Roll an estimate's costs up and report which totals changed since the last run.
+///
+/// Only items changed since then, and their ancestors, are recomputed unless `full` is set.
+#[tauri::command]
+pub async fn recalculate_estimate(
+    db: State<'_, Db>,
+    estimate_id: String,
+    full: Option<bool>,
+) -> AppResult<RollupDiff> {
+    db.run(move |conn| {
+        let started = std::time::Instant::now();
+        let diff = recalculate(conn, &estimate_id, full.unwrap_or(false))?;
+        tracing::debug!(
+            estimate = %estimate_id,
+            recalculated = diff.recalculated,
+            reused = diff.reused,
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "estimate recalculated"
+        );
+        Ok(diff)
+    })
+    .await
+}
+
+/// Place items in the rollup tree and set their cost types.
+#[tauri::command]
+pub async fn set_cost_structure(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    project_id: String,
+    entries: Vec<CostStructureInput>,
+) -> AppResult<()> {
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let items: HashSet<String> = tx
+            .prepare("SELECT id FROM items WHERE project_id = ?1")?
+            .query_map([&project_id], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        let mut parents: HashMap<String, Option<String>> = tx
+            .prepare(
+                "SELECT s.item_id, s.parent_id FROM cost_structure s
+                 JOIN items i ON i.id = s.item_id WHERE i.project_id = ?1",
+            )?
+            .query_map([&project_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        for entry in &entries {
+            for id in std::iter::once(&entry.item_id).chain(&entry.parent_id) {
+                if !items.contains(id) {
+                    return Err(AppError::not_found("item", id.clone()));
+                }
+            }
+            parents.insert(entry.item_id.clone(), entry.parent_id.clone());
+        }
+        for entry in &entries {
+            let mut at = entry.parent_id.as_ref();
+            let mut steps = 0;
+            while let Some(parent) = at {
+                steps += 1;
+                if *parent == entry.item_id || steps > parents.len() {
+                    return Err(AppError::InvalidInput(format!(
+                        "item {} cannot roll up into itself",
+                        entry.item_id
+                    )));
+                }
+                at = parents.get(parent).and_then(Option::as_ref);
+            }
+        }
+        let mut journal = Journal::new(&project_id, "set_cost_structure", "Change cost structure");
+        for entry in &entries {
+            journal.watch(&tx, Table::CostStructure, &entry.item_id)?;
+        }
+        let now = now_ms();
+        for entry in &entries {
+            tx.execute(
+                "INSERT INTO cost_structure (item_id, parent_id, cost_type, updated_at)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(item_id) DO UPDATE SET
+                    parent_id = excluded.parent_id,
+                    cost_type = excluded.cost_type,
+                    updated_at = excluded.updated_at",
+                params![
+                    entry.item_id,
+                    entry.parent_id,
+                    entry.cost_type.as_str(),
+                    now
+                ],
+            )?;
+        }
+        audit::record(
+            &tx,
+            &actor,
+            Change::new("set_cost_structure", "project", &project_id).after(
+                &serde_json::json!({ "items": entries.iter().map(|e| &e.item_id).collect::<Vec<_>>() }),
+            )?,
+        )?;
+        journal.record(&tx)?;
+        tx.commit()?;
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_cost_adjustments(
+    db: State<'_, Db>,
+    project_id: String,
+) -> AppResult<Vec<CostAdjustment>> {
+    db.run(move |conn| load_adjustments(conn, &project_id))
+        .await
+}
+
+/// Replace an estimate's markups and taxes.
+#[tauri::command]
+pub async fn set_cost_adjustments(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    project_id: String,
+    adjustments: Vec<CostAdjustment>,
+) -> AppResult<Vec<CostAdjustment>> {
+    if let Some(bad) = adjustments.iter().find(|a| !a.rate.is_finite()) {
+        return Err(AppError::InvalidInput(format!(
+            "the rate for {} is not a number",
+            bad.name
+        )));
+    }
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let before = load_adjustments(&tx, &project_id)?;
+        tx.execute(
+            "DELETE FROM cost_adjustments WHERE project_id = ?1",
+            [&project_id],
+        )?;
+        for adjustment in &adjustments {
+            tx.execute(
+                "INSERT INTO cost_adjustments
+                    (id, project_id, name, kind, rate, applies_to, sort_order)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    adjustment
+                        .id
+                        .clone()
+                        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+                    project_id,
+                    adjustment.name,
+                    match adjustment.kind {
+                        AdjustmentKind::Markup => "markup",
+                        AdjustmentKind::Tax => "tax",
+                    },
+                    adjustment.rate,
+                    adjustment.applies_to.map(CostType::as_str),
+                    adjustment.sort_order
+                ],
+            )?;
+        }
+        let saved = load_adjustments(&tx, &project_id)?;
+        audit::record(
+            &tx,
+            &actor,
+            Change::new("set_cost_adjustments", "project", &project_id)
+                .before(&before)?
+                .after(&saved)?,
+        )?;
+        tx.commit()?;
+        Ok(saved)
+    })
+    .await
+}
@@ -0,0 +1,409 @@
+//! Offline copy of the Truss material and pricing catalog.
+//!
+//! The first sync pages the whole catalog down; later ones ask only for SKUs changed or
+//! withdrawn since the stored cursor, so the mirror stays current over a slow connection.
+//! A refresh pages the whole catalog down again and drops any SKU it did not include, for
+//! when the machine has time to spare.
+//! Searches run against the local copy, matching SKU prefixes and word prefixes anywhere in
+//! the text, and work the same with no network at all. How long ago the last sync finished
+//! is reported so estimators know when prices may be out of date.
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::auth;
+use crate::config;
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::http_client::{HttpClient, RetryPolicy};
+use crate::jobs::Scheduler;
+use crate::network::NetworkMonitor;
+
+const CHANGES_PATH: &str = "/v1/catalog/changes";
+const PAGE_SIZE: u32 = 2000;
+const DEFAULT_SEARCH_LIMIT: usize = 25;
+const MAX_SEARCH_LIMIT: usize = 200;
+
+const CURSOR_KEY: &str = "cursor";
+const LAST_SYNCED_KEY: &str = "last_synced_at";
+
+/// Prices older than this are flagged as stale.
+const STALE_AFTER: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Job kind for the periodic catalog refresh.
+pub const CATALOG_JOB: &str = "catalog.sync";
+const CATALOG_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Emitted with a [`CatalogSyncSummary`] of the work so far after each page of changes.
+pub const PROGRESS_EVENT: &str = "catalog:progress";
+
+/// One SKU and its current price.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogItem {
+    pub sku: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub unit: Option<String>,
+    pub unit_cost: f64,
+    pub currency: Option<String>,
+    pub category: Option<String>,
+    pub supplier: Option<String>,
+    pub updated_at: i64,
+}
+
+impl CatalogItem {
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            sku: row.get("sku")?,
+            name: row.get("name")?,
+            description: row.get("description")?,
+            unit: row.get("unit")?,
+            unit_cost: row.get("unit_cost")?,
+            currency: row.get("currency")?,
+            category: row.get("category")?,
+            supplier: row.get("supplier")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ChangesPage {
+    #[serde(default)]
+    items: Vec<CatalogItem>,
+    /// SKUs withdrawn from the catalog.
+    #[serde(default)]
+    deleted: Vec<String>,
+    cursor: String,
+    #[serde(default)]
+    has_more: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogSyncSummary {
+    pub upserted: u64,
+    pub deleted: u64,
+    /// Whether this was a full download, first or refreshed, rather than a delta.
+    pub full: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogStatus {
+    pub item_count: u64,
+    /// `None` before the first sync.
+    pub last_synced_at: Option<i64>,
+    pub stale: bool,
+    pub syncing: bool,
+}
+
+/// Catalog mirror registered in app state.
+pub struct Catalog {
+    db: Db,
+    running: tokio::sync::Mutex<()>,
+}
+
+fn get_state(conn: &Connection, key: &str) -> AppResult<Option<String>> {
+    Ok(conn
+        .query_row(
+            "SELECT value FROM catalog_state WHERE key = ?1",
+            [key],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+fn set_state(conn: &Connection, key: &str, value: &str) -> AppResult<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO catalog_state (key, value) VALUES (?1, ?2)",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+fn apply(conn: &mut Connection, page: &ChangesPage) -> AppResult<()> {
+    let tx = conn.transaction()?;
+    {
+        let mut upsert = tx.prepare_cached(
+            "INSERT INTO catalog_items
+                (sku, name, description, unit, unit_cost, currency, category, supplier, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT(sku) DO UPDATE SET
+                name = excluded.name,
+                description = excluded.description,
+                unit = excluded.unit,
+                unit_cost = excluded.unit_cost,
+                currency = excluded.currency,
+                category = excluded.category,
+                supplier = excluded.supplier,
+                updated_at = excluded.updated_at",
+        )?;
+        for item in &page.items {
+            upsert.execute(params![
+                item.sku,
+                item.name,
+                item.description,
+                item.unit,
+                item.unit_cost,
+                item.currency,
+                item.category,
+                item.supplier,
+                item.updated_at
+            ])?;
+        }
+        let mut delete = tx.prepare_cached("DELETE FROM catalog_items WHERE sku = ?1")?;
+        for sku in &page.deleted {
+            delete.execute([sku])?;
+        }
+    }
+    // The cursor moves with the rows it covers, so an interrupted sync resumes where it stopped
+    set_state(&tx, CURSOR_KEY, &page.cursor)?;
+    tx.commit()?;
+    Ok(())
+}
+
+// Drop the SKUs a full download did not include; returns how many
+fn prune(conn: &mut Connection, seen: &HashSet<String>) -> AppResult<u64> {
+    let tx = conn.transaction()?;
+    let skus: Vec<String> = tx
+        .prepare("SELECT sku FROM catalog_items")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    let mut pruned = 0;
+    {
+        let mut delete = tx.prepare_cached("DELETE FROM catalog_items WHERE sku = ?1")?;
+        for sku in skus.iter().filter(|sku| !seen.contains(*sku)) {
+            delete.execute([sku])?;
+            pruned += 1;
+        }
+    }
+    tx.commit()?;
+    Ok(pruned)
+}
+
+// Each word becomes a quoted prefix term, so search text is never read as FTS syntax
+fn match_expression(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{word}\"*"))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+fn search(
+    conn: &Connection,
+    query: &str,
+    category: Option<&str>,
+    limit: usize,
+) -> AppResult<Vec<CatalogItem>> {
+    let query = query.trim();
+    let prefix = format!(
+        "{}%",
+        query
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+    // An exact or partial SKU ranks first, as it is what a scanned or typed code will be
+    let mut results = conn
+        .prepare_cached(
+            "SELECT * FROM catalog_items
+             WHERE sku LIKE ?1 ESCAPE '\\' AND (?2 IS NULL OR category = ?2)
+             ORDER BY sku LIMIT ?3",
+        )?
+        .query_map(
+            params![prefix, category, limit as i64],
+            CatalogItem::from_row,
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    if let Some(expression) = match_expression(query) {
+        let matches = conn
+            .prepare_cached(
+                "SELECT c.* FROM catalog_search s JOIN catalog_items c ON c.rowid = s.rowid
+                 WHERE catalog_search MATCH ?1 AND (?2 IS NULL OR c.category = ?2)
+                 ORDER BY s.rank LIMIT ?3",
+            )?
+            .query_map(
+                params![expression, category, limit as i64],
+                CatalogItem::from_row,
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for item in matches {
+            if results.len() >= limit {
+                break;
+            }
+            if !results.iter().any(|known| known.sku == item.sku) {
+                results.push(item);
+            }
+        }
+    }
+    Ok(results)
+}
+
+impl Catalog {
+    pub fn new(db: Db) -> Self {
+        Self {
+            db,
+            running: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Pull every change since the last sync, or the whole catalog the first time.
+    pub async fn sync(&self, app: &AppHandle) -> AppResult<CatalogSyncSummary> {
+        self.pull(app, false).await
+    }
+
+    /// Download the whole catalog again, dropping SKUs no longer in it.
+    pub async fn refresh(&self, app: &AppHandle) -> AppResult<CatalogSyncSummary> {
+        self.pull(app, true).await
+    }
+
+    async fn pull(&self, app: &AppHandle, full: bool) -> AppResult<CatalogSyncSummary> {
+        let _running = self.running.lock().await;
+        let token = auth::access_token(app)
+            .await?
+            .ok_or_else(|| AppError::Auth("sign in to update the catalog".into()))?;
+        let http = app.state::<HttpClient>();
+        let mut summary = CatalogSyncSummary::default();
+        let mut cursor = if full {
+            None
+        } else {
+            self.db.run(|conn| get_state(conn, CURSOR_KEY)).await?
+        };
+        summary.full = cursor.is_none();
+        let mut seen = HashSet::new();
+        loop {
+            let mut url = config::api_url(app, CHANGES_PATH)?;
+            url.query_pairs_mut()
+                .append_pair("limit", &PAGE_SIZE.to_string());
+            if let Some(cursor) = &cursor {
+                url.query_pairs_mut().append_pair("since", cursor);
+            }
+            let response = http
+                .send(
+                    http.client().get(url).bearer_auth(&token),
+                    RetryPolicy::default(),
+                )
+                .await?
+                .error_for_status()?;
+            let page: ChangesPage = serde_json::from_slice(&response.bytes().await?)?;
+            let page = self
+                .db
+                .run(move |conn| {
+                    apply(conn, &page)?;
+                    Ok(page)
+                })
+                .await?;
+            summary.upserted += page.items.len() as u64;
+            summary.deleted += page.deleted.len() as u64;
+            if summary.full {
+                seen.extend(page.items.iter().map(|item| item.sku.clone()));
+            }
+            let _ = app.emit(PROGRESS_EVENT, summary.clone());
+            if !page.has_more {
+                break;
+            }
+            cursor = Some(page.cursor);
+        }
+        if summary.full {
+            summary.deleted += self.db.run(move |conn| prune(conn, &seen)).await?;
+        }
+        let now = now_ms().to_string();
+        self.db
+            .run(move |conn| set_state(conn, LAST_SYNCED_KEY, &now))
+            .await?;
+        tracing::info!(
+            upserted = summary.upserted,
+            deleted = summary.deleted,
+            full = summary.full,
+            "catalog synced"
+        );
+        Ok(summary)
+    }
+
+    pub async fn status(&self) -> AppResult<CatalogStatus> {
+        let syncing = self.running.try_lock().is_err();
+        let (item_count, last_synced_at) = self
+            .db
+            .run(|conn| {
+                let count: i64 =
+                    conn.query_row("SELECT count(*) FROM catalog_items", [], |row| row.get(0))?;
+                let last = get_state(conn, LAST_SYNCED_KEY)?.and_then(|v| v.parse::<i64>().ok());
+                Ok((count as u64, last))
+            })
+            .await?;
+        let stale = last_synced_at
+            .is_none_or(|at| now_ms().saturating_sub(at) > STALE_AFTER.as_millis() as i64);
+        Ok(CatalogStatus {
+            item_count,
+            last_synced_at,
+            stale,
+            syncing,
+        })
+    }
+}
+
+/// Signed-out users keep whatever catalog they last had, which is not a job failure.
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(CATALOG_JOB, Some(CATALOG_INTERVAL), |app| async move {
+        if !app.state::<NetworkMonitor>().is_online() {
+            return Ok(());
+        }
+        match app.state::<Catalog>().sync(&app).await {
+            Ok(_) | Err(AppError::Auth(_)) => Ok(()),
+            Err(err) => Err(err),
+        }
+    });
+}
+
+/// Update the catalog now instead of waiting for the next scheduled run.
+#[tauri::command]
+pub async fn sync_catalog(
+    app: AppHandle,
+    catalog: State<'_, Catalog>,
+) -> AppResult<CatalogSyncSummary> {
+    catalog.sync(&app).await
+}
+
+#[tauri::command]
+pub async fn get_catalog_status(catalog: State<'_, Catalog>) -> AppResult<CatalogStatus> {
+    catalog.status().await
+}
+
+/// Catalog entries whose SKU starts with `query` or whose text has words starting with each
+/// of its words, best matches first.
+#[tauri::command]
+pub async fn search_catalog(
+    db: State<'_, Db>,
+    query: String,
+    category: Option<String>,
+    limit: Option<usize>,
+) -> AppResult<Vec<CatalogItem>> {
+    let limit = limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT);
+    db.run(move |conn| search(conn, &query, category.as_deref(), limit))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_catalog_item(db: State<'_, Db>, sku: String) -> AppResult<CatalogItem> {
+    db.run(move |conn| {
+        conn.query_row(
+            "SELECT * FROM catalog_items WHERE sku = ?1",
+            [&sku],
+            CatalogItem::from_row,
+        )
+        .optional()?
+        .ok_or_else(|| AppError::not_found("catalog item", sku))
+    })
+    .await
+}
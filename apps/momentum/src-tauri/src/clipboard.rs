@@ -0,0 +1,757 @@
+//! Pasting screenshots, copied files, and rich text from the system clipboard.
+//!
+//! Webviews only see text on paste, so a screenshot of a drawing detail went nowhere.
+//! `paste_from_clipboard` reads the clipboard natively and takes the richest thing on it: an
+//! image, then a list of copied files, then HTML, then plain text. Images are stored as PNG
+//! attachments and files are attached as copies, so what comes back can be inserted straight
+//! away. On Linux the clipboard is read through `wl-paste` under Wayland, falling back to X11
+//! (which XWayland also serves).
+
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use image::{ImageFormat, ImageReader};
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::attachments::{self, Attachment, AttachmentStore};
+use crate::audit::{self, Change};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+
+// More than this at once is almost certainly a mistaken copy of a whole folder
+const MAX_FILES: usize = 50;
+// Decoded size cap for images, so a pasted panorama cannot exhaust memory
+const MAX_IMAGE_PIXELS: u64 = 100_000_000;
+
+/// What the clipboard held, the richest form first.
+enum Raw {
+    /// Encoded in any format the image crate reads: PNG, TIFF, BMP, and so on.
+    Image(Vec<u8>),
+    Files(Vec<PathBuf>),
+    Html {
+        html: String,
+        text: Option<String>,
+    },
+    Text(String),
+    Empty,
+}
+
+/// What `paste_from_clipboard` found, ready to insert.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum PastedContent {
+    /// An image saved as a PNG attachment.
+    Image {
+        attachment: Attachment,
+        width: u32,
+        height: u32,
+    },
+    /// Copied files attached to the project; folders and unreadable paths are skipped.
+    Files {
+        attachments: Vec<Attachment>,
+        skipped: Vec<PathBuf>,
+    },
+    /// Rich text, with the plain-text version when the source offered one.
+    Html {
+        html: String,
+        text: Option<String>,
+    },
+    Text {
+        text: String,
+    },
+    Empty,
+}
+
+// PNGs are kept byte for byte; everything else is re-encoded so attachments stay uniform
+fn to_png(bytes: Vec<u8>) -> AppResult<(Vec<u8>, u32, u32)> {
+    let unreadable = |e: image::ImageError| {
+        AppError::InvalidInput(format!("the clipboard image cannot be read: {e}"))
+    };
+    let reader = ImageReader::new(Cursor::new(&bytes)).with_guessed_format()?;
+    let format = reader.format();
+    let (width, height) = reader.into_dimensions().map_err(unreadable)?;
+    if u64::from(width) * u64::from(height) > MAX_IMAGE_PIXELS {
+        return Err(AppError::InvalidInput(format!(
+            "the clipboard image is too large ({width} x {height})"
+        )));
+    }
+    if format == Some(ImageFormat::Png) {
+        return Ok((bytes, width, height));
+    }
+    let image = ImageReader::new(Cursor::new(&bytes))
+        .with_guessed_format()?
+        .decode()
+        .map_err(unreadable)?;
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(unreadable)?;
+    Ok((png, width, height))
+}
+
+fn pasted_image_name() -> String {
+    let now = jiff::Zoned::now();
+    format!("Pasted image {}.png", now.strftime("%Y-%m-%d at %H.%M.%S"))
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use std::path::PathBuf;
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{
+        Atom, AtomEnum, ConnectionExt, CreateWindowAux, EventMask, Property, WindowClass,
+    };
+    use x11rb::protocol::Event;
+    use x11rb::rust_connection::RustConnection;
+    use x11rb::{COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE};
+
+    use super::Raw;
+    use crate::error::{AppError, AppResult};
+
+    // Owners answer in milliseconds; a hung one must not hang the paste
+    const TIMEOUT: Duration = Duration::from_secs(2);
+    const POLL: Duration = Duration::from_millis(5);
+
+    /// A clipboard that lists MIME targets and hands over one on request.
+    trait Source {
+        fn targets(&mut self) -> AppResult<Vec<String>>;
+        fn read(&mut self, target: &str) -> AppResult<Option<Vec<u8>>>;
+    }
+
+    // Skips comments and anything that is not a local path
+    fn parse_uri_list(list: &str) -> Vec<PathBuf> {
+        list.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| url::Url::parse(line).ok()?.to_file_path().ok())
+            .collect()
+    }
+
+    fn read_text(source: &mut impl Source, targets: &[String]) -> AppResult<Option<String>> {
+        for target in [
+            "text/plain;charset=utf-8",
+            "UTF8_STRING",
+            "text/plain",
+            "STRING",
+        ] {
+            if !targets.iter().any(|t| t == target) {
+                continue;
+            }
+            if let Some(bytes) = source.read(target)? {
+                return Ok(Some(String::from_utf8_lossy(&bytes).into_owned()));
+            }
+        }
+        Ok(None)
+    }
+
+    fn read_from(source: &mut impl Source) -> AppResult<Raw> {
+        let targets = source.targets()?;
+        let has = |name: &str| targets.iter().any(|t| t == name);
+        let image = if has("image/png") {
+            Some("image/png".to_string())
+        } else {
+            targets.iter().find(|t| t.starts_with("image/")).cloned()
+        };
+        if let Some(image) = image {
+            if let Some(bytes) = source.read(&image)? {
+                return Ok(Raw::Image(bytes));
+            }
+        }
+        if has("text/uri-list") {
+            if let Some(bytes) = source.read("text/uri-list")? {
+                let files = parse_uri_list(&String::from_utf8_lossy(&bytes));
+                if !files.is_empty() {
+                    return Ok(Raw::Files(files));
+                }
+            }
+        }
+        if has("text/html") {
+            if let Some(bytes) = source.read("text/html")? {
+                let html = String::from_utf8_lossy(&bytes).into_owned();
+                let text = read_text(source, &targets)?;
+                return Ok(Raw::Html { html, text });
+            }
+        }
+        Ok(match read_text(source, &targets)? {
+            Some(text) => Raw::Text(text),
+            None => Raw::Empty,
+        })
+    }
+
+    struct WlPaste;
+
+    impl WlPaste {
+        fn run(args: &[&str]) -> AppResult<Option<Vec<u8>>> {
+            let output = Command::new("wl-paste").args(args).output().map_err(|e| {
+                AppError::Internal(format!("wl-paste is needed to paste under Wayland: {e}"))
+            })?;
+            // wl-paste exits non-zero when the clipboard is empty
+            Ok(output.status.success().then_some(output.stdout))
+        }
+    }
+
+    impl Source for WlPaste {
+        fn targets(&mut self) -> AppResult<Vec<String>> {
+            let list = Self::run(&["--list-types"])?.unwrap_or_default();
+            Ok(String::from_utf8_lossy(&list)
+                .lines()
+                .map(str::to_string)
+                .collect())
+        }
+
+        fn read(&mut self, target: &str) -> AppResult<Option<Vec<u8>>> {
+            Self::run(&["--no-newline", "--type", target])
+        }
+    }
+
+    fn x11_error(err: impl std::fmt::Display) -> AppError {
+        AppError::Internal(format!("X11 clipboard: {err}"))
+    }
+
+    struct X11 {
+        conn: RustConnection,
+        window: u32,
+        clipboard: Atom,
+        property: Atom,
+        incr: Atom,
+    }
+
+    impl X11 {
+        fn connect() -> AppResult<Self> {
+            let (conn, screen) = x11rb::connect(None).map_err(x11_error)?;
+            let root = conn.setup().roots[screen].root;
+            let window = conn.generate_id().map_err(x11_error)?;
+            conn.create_window(
+                COPY_DEPTH_FROM_PARENT,
+                window,
+                root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                COPY_FROM_PARENT,
+                &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+            )
+            .map_err(x11_error)?;
+            let mut x11 = Self {
+                conn,
+                window,
+                clipboard: 0,
+                property: 0,
+                incr: 0,
+            };
+            x11.clipboard = x11.atom("CLIPBOARD")?;
+            x11.property = x11.atom("MOMENTUM_CLIPBOARD")?;
+            x11.incr = x11.atom("INCR")?;
+            Ok(x11)
+        }
+
+        fn atom(&self, name: &str) -> AppResult<Atom> {
+            Ok(self
+                .conn
+                .intern_atom(false, name.as_bytes())
+                .map_err(x11_error)?
+                .reply()
+                .map_err(x11_error)?
+                .atom)
+        }
+
+        fn wait_for<T>(&self, mut matches: impl FnMut(&Event) -> Option<T>) -> AppResult<T> {
+            let started = Instant::now();
+            loop {
+                match self.conn.poll_for_event().map_err(x11_error)? {
+                    Some(event) => {
+                        if let Some(found) = matches(&event) {
+                            return Ok(found);
+                        }
+                    }
+                    None if started.elapsed() > TIMEOUT => {
+                        return Err(AppError::Internal(
+                            "the clipboard owner did not answer".into(),
+                        ))
+                    }
+                    None => std::thread::sleep(POLL),
+                }
+            }
+        }
+
+        fn take_property(&self) -> AppResult<(Atom, Vec<u8>)> {
+            let reply = self
+                .conn
+                .get_property(true, self.window, self.property, AtomEnum::ANY, 0, u32::MAX)
+                .map_err(x11_error)?
+                .reply()
+                .map_err(x11_error)?;
+            Ok((reply.type_, reply.value))
+        }
+
+        fn convert(&self, target: Atom) -> AppResult<Option<Vec<u8>>> {
+            self.conn
+                .convert_selection(
+                    self.window,
+                    self.clipboard,
+                    target,
+                    self.property,
+                    CURRENT_TIME,
+                )
+                .map_err(x11_error)?;
+            self.conn.flush().map_err(x11_error)?;
+            let property = self.wait_for(|event| match event {
+                Event::SelectionNotify(notify) if notify.requestor == self.window => {
+                    Some(notify.property)
+                }
+                _ => None,
+            })?;
+            if property == NONE {
+                return Ok(None);
+            }
+            let (kind, value) = self.take_property()?;
+            if kind != self.incr {
+                return Ok(Some(value));
+            }
+            // Large contents arrive in chunks, each announced by a new property value; deleting
+            // the INCR marker above asked for the first one, and an empty chunk ends it
+            let mut data = Vec::new();
+            loop {
+                self.wait_for(|event| match event {
+                    Event::PropertyNotify(notify)
+                        if notify.window == self.window
+                            && notify.atom == self.property
+                            && notify.state == Property::NEW_VALUE =>
+                    {
+                        Some(())
+                    }
+                    _ => None,
+                })?;
+                let (_, chunk) = self.take_property()?;
+                if chunk.is_empty() {
+                    return Ok(Some(data));
+                }
+                data.extend_from_slice(&chunk);
+            }
+        }
+    }
+
+    impl Source for X11 {
+        fn targets(&mut self) -> AppResult<Vec<String>> {
+            let targets = self.atom("TARGETS")?;
+            let Some(list) = self.convert(targets)? else {
+                return Ok(Vec::new());
+            };
+            let atoms: Vec<Atom> = list
+                .chunks_exact(4)
+                .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            // Sent together so the names cost one round trip
+            let cookies = atoms
+                .iter()
+                .map(|&atom| self.conn.get_atom_name(atom))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(x11_error)?;
+            Ok(cookies
+                .into_iter()
+                .filter_map(|cookie| cookie.reply().ok())
+                .map(|reply| String::from_utf8_lossy(&reply.name).into_owned())
+                .collect())
+        }
+
+        fn read(&mut self, target: &str) -> AppResult<Option<Vec<u8>>> {
+            let target = self.atom(target)?;
+            self.convert(target)
+        }
+    }
+
+    pub fn read() -> AppResult<Raw> {
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            match read_from(&mut WlPaste) {
+                Ok(raw) => return Ok(raw),
+                Err(err) => tracing::debug!(%err, "wl-paste failed; trying X11"),
+            }
+        }
+        read_from(&mut X11::connect()?)
+    }
+
+    pub fn text() -> AppResult<Option<String>> {
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            let mut source = WlPaste;
+            if let Ok(targets) = source.targets() {
+                return read_text(&mut source, &targets);
+            }
+        }
+        let mut source = X11::connect()?;
+        let targets = source.targets()?;
+        read_text(&mut source, &targets)
+    }
+
+    // Neither X11 nor Wayland has a cheap counter without extensions; callers compare text
+    pub fn change_count() -> Option<u64> {
+        None
+    }
+}
+
+#[cfg(windows)]
+mod os {
+    use std::path::PathBuf;
+
+    use windows::core::w;
+    use windows::Win32::Foundation::HGLOBAL;
+    use windows::Win32::System::DataExchange::{
+        CloseClipboard, GetClipboardData, GetClipboardSequenceNumber, IsClipboardFormatAvailable,
+        OpenClipboard, RegisterClipboardFormatW,
+    };
+    use windows::Win32::System::Memory::{GlobalLock, GlobalSize, GlobalUnlock};
+    use windows::Win32::System::Ole::{CF_DIB, CF_HDROP, CF_UNICODETEXT};
+    use windows::Win32::UI::Shell::{DragQueryFileW, HDROP};
+
+    use super::Raw;
+    use crate::error::{AppError, AppResult};
+
+    const BI_BITFIELDS: u32 = 3;
+    const FILE_HEADER_LEN: u32 = 14;
+
+    // Closes the clipboard on every return path; only one process can hold it open
+    struct Open;
+
+    impl Open {
+        fn new() -> AppResult<Self> {
+            unsafe { OpenClipboard(None) }
+                .map_err(|e| AppError::Internal(format!("the clipboard is busy: {e}")))?;
+            Ok(Self)
+        }
+    }
+
+    impl Drop for Open {
+        fn drop(&mut self) {
+            let _ = unsafe { CloseClipboard() };
+        }
+    }
+
+    fn available(format: u32) -> bool {
+        unsafe { IsClipboardFormatAvailable(format) }.is_ok()
+    }
+
+    // Copies out a global-memory clipboard format while it is locked
+    fn bytes(format: u32) -> Option<Vec<u8>> {
+        let handle = unsafe { GetClipboardData(format) }.ok()?;
+        let global = HGLOBAL(handle.0);
+        let size = unsafe { GlobalSize(global) };
+        let data = unsafe { GlobalLock(global) };
+        if data.is_null() {
+            return None;
+        }
+        let copy = unsafe { std::slice::from_raw_parts(data.cast::<u8>(), size) }.to_vec();
+        let _ = unsafe { GlobalUnlock(global) };
+        Some(copy)
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        bytes
+            .get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .unwrap_or(0)
+    }
+
+    // CF_DIB is a BMP file without its 14-byte header; put one back so it decodes
+    fn dib_to_bmp(dib: Vec<u8>) -> Vec<u8> {
+        let header_len = u32_at(&dib, 0);
+        let bit_count = dib
+            .get(14..16)
+            .map_or(0, |b| u16::from_le_bytes([b[0], b[1]]));
+        let compression = u32_at(&dib, 16);
+        let colors_used = u32_at(&dib, 32);
+        let palette = match bit_count {
+            1..=8 if colors_used == 0 => (1u32 << bit_count) * 4,
+            1..=8 => colors_used * 4,
+            _ => 0,
+        };
+        let masks = if compression == BI_BITFIELDS && header_len == 40 {
+            12
+        } else {
+            0
+        };
+        let offset = FILE_HEADER_LEN + header_len + palette + masks;
+        let total = FILE_HEADER_LEN + dib.len() as u32;
+        let mut bmp = Vec::with_capacity(total as usize);
+        bmp.extend_from_slice(b"BM");
+        bmp.extend_from_slice(&total.to_le_bytes());
+        bmp.extend_from_slice(&0u32.to_le_bytes());
+        bmp.extend_from_slice(&offset.to_le_bytes());
+        bmp.extend(dib);
+        bmp
+    }
+
+    fn files() -> Vec<PathBuf> {
+        let Ok(handle) = (unsafe { GetClipboardData(u32::from(CF_HDROP.0)) }) else {
+            return Vec::new();
+        };
+        let drop = HDROP(handle.0);
+        let count = unsafe { DragQueryFileW(drop, u32::MAX, None) };
+        (0..count)
+            .filter_map(|index| {
+                let len = unsafe { DragQueryFileW(drop, index, None) } as usize;
+                let mut buffer = vec![0u16; len + 1];
+                let written = unsafe { DragQueryFileW(drop, index, Some(&mut buffer)) } as usize;
+                (written > 0).then(|| PathBuf::from(String::from_utf16_lossy(&buffer[..written])))
+            })
+            .collect()
+    }
+
+    fn unicode_text() -> Option<String> {
+        let bytes = bytes(u32::from(CF_UNICODETEXT.0))?;
+        let wide: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .take_while(|&c| c != 0)
+            .collect();
+        Some(String::from_utf16_lossy(&wide))
+    }
+
+    // "HTML Format" wraps the markup in a header of byte offsets; keep just the fragment
+    fn html_fragment(raw: &[u8]) -> String {
+        let text = String::from_utf8_lossy(raw);
+        let offset = |key: &str| {
+            text.lines()
+                .find_map(|line| line.strip_prefix(key))
+                .and_then(|value| value.trim().parse::<usize>().ok())
+        };
+        match (offset("StartFragment:"), offset("EndFragment:")) {
+            (Some(start), Some(end)) if start <= end && end <= raw.len() => {
+                String::from_utf8_lossy(&raw[start..end]).into_owned()
+            }
+            _ => text.trim_end_matches('\0').to_string(),
+        }
+    }
+
+    pub fn read() -> AppResult<Raw> {
+        let _open = Open::new()?;
+        // Browsers and Office put a PNG alongside the DIB; it keeps transparency
+        let png = unsafe { RegisterClipboardFormatW(w!("PNG")) };
+        if available(png) {
+            if let Some(bytes) = bytes(png) {
+                return Ok(Raw::Image(bytes));
+            }
+        }
+        if available(u32::from(CF_DIB.0)) {
+            if let Some(dib) = bytes(u32::from(CF_DIB.0)) {
+                return Ok(Raw::Image(dib_to_bmp(dib)));
+            }
+        }
+        if available(u32::from(CF_HDROP.0)) {
+            let files = files();
+            if !files.is_empty() {
+                return Ok(Raw::Files(files));
+            }
+        }
+        let html = unsafe { RegisterClipboardFormatW(w!("HTML Format")) };
+        if available(html) {
+            if let Some(raw) = bytes(html) {
+                return Ok(Raw::Html {
+                    html: html_fragment(&raw),
+                    text: unicode_text(),
+                });
+            }
+        }
+        Ok(match unicode_text() {
+            Some(text) => Raw::Text(text),
+            None => Raw::Empty,
+        })
+    }
+
+    pub fn text() -> AppResult<Option<String>> {
+        let _open = Open::new()?;
+        Ok(unicode_text())
+    }
+
+    pub fn change_count() -> Option<u64> {
+        Some(u64::from(unsafe { GetClipboardSequenceNumber() }))
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use objc2_app_kit::{
+        NSPasteboard, NSPasteboardTypeFileURL, NSPasteboardTypeHTML, NSPasteboardTypePNG,
+        NSPasteboardTypeString, NSPasteboardTypeTIFF,
+    };
+
+    use super::Raw;
+    use crate::error::AppResult;
+
+    pub fn read() -> AppResult<Raw> {
+        let pasteboard = NSPasteboard::generalPasteboard();
+        let (png, tiff, file_url, html, string) = unsafe {
+            (
+                NSPasteboardTypePNG,
+                NSPasteboardTypeTIFF,
+                NSPasteboardTypeFileURL,
+                NSPasteboardTypeHTML,
+                NSPasteboardTypeString,
+            )
+        };
+        // Screenshots arrive as PNG; most apps offer TIFF, which the image crate also reads
+        for kind in [png, tiff] {
+            if let Some(data) = pasteboard.dataForType(kind) {
+                return Ok(Raw::Image(data.to_vec()));
+            }
+        }
+        // Each copied file is its own item carrying a file:// URL
+        let files: Vec<_> = pasteboard
+            .pasteboardItems()
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|item| item.stringForType(file_url))
+                    .filter_map(|url| url::Url::parse(&url.to_string()).ok()?.to_file_path().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        if !files.is_empty() {
+            return Ok(Raw::Files(files));
+        }
+        let text = pasteboard.stringForType(string).map(|s| s.to_string());
+        if let Some(html) = pasteboard.stringForType(html) {
+            return Ok(Raw::Html {
+                html: html.to_string(),
+                text,
+            });
+        }
+        Ok(match text {
+            Some(text) => Raw::Text(text),
+            None => Raw::Empty,
+        })
+    }
+
+    pub fn text() -> AppResult<Option<String>> {
+        let string = unsafe { NSPasteboardTypeString };
+        Ok(NSPasteboard::generalPasteboard()
+            .stringForType(string)
+            .map(|s| s.to_string()))
+    }
+
+    pub fn change_count() -> Option<u64> {
+        u64::try_from(NSPasteboard::generalPasteboard().changeCount()).ok()
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows, target_os = "macos")))]
+mod os {
+    use super::Raw;
+    use crate::error::{AppError, AppResult};
+
+    pub fn read() -> AppResult<Raw> {
+        Err(AppError::InvalidInput(
+            "pasting images and files is not available on this platform".into(),
+        ))
+    }
+
+    pub fn text() -> AppResult<Option<String>> {
+        Ok(None)
+    }
+
+    pub fn change_count() -> Option<u64> {
+        None
+    }
+}
+
+/// Plain text on the clipboard, without touching richer formats.
+pub(crate) fn read_text() -> AppResult<Option<String>> {
+    os::text()
+}
+
+/// A counter that moves whenever the clipboard changes, where the platform keeps one.
+pub(crate) fn change_count() -> Option<u64> {
+    os::change_count()
+}
+
+/// Read the clipboard; images and files are attached to `project_id` and returned as attachments.
+#[tauri::command]
+pub async fn paste_from_clipboard(
+    app: AppHandle,
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    project_id: String,
+) -> AppResult<PastedContent> {
+    let raw = tauri::async_runtime::spawn_blocking(os::read).await??;
+    let actor = audit::actor(&state);
+    match raw {
+        Raw::Image(bytes) => {
+            let (png, width, height) =
+                tauri::async_runtime::spawn_blocking(move || to_png(bytes)).await??;
+            let attachment = db
+                .run(move |conn| {
+                    let store = app.state::<AttachmentStore>();
+                    let attachment = attachments::add_bytes(
+                        conn,
+                        &store,
+                        project_id,
+                        pasted_image_name(),
+                        &png,
+                    )?;
+                    audit::record_now(
+                        conn,
+                        &actor,
+                        Change::new("paste_from_clipboard", "attachment", &attachment.id)
+                            .after(&attachment)?,
+                    )?;
+                    Ok(attachment)
+                })
+                .await?;
+            tracing::info!(id = %attachment.id, width, height, "clipboard image attached");
+            Ok(PastedContent::Image {
+                attachment,
+                width,
+                height,
+            })
+        }
+        Raw::Files(paths) => {
+            if paths.len() > MAX_FILES {
+                return Err(AppError::InvalidInput(format!(
+                    "{} files were copied; paste at most {MAX_FILES} at once",
+                    paths.len()
+                )));
+            }
+            db.run(move |conn| {
+                let store = app.state::<AttachmentStore>();
+                let (mut added, mut skipped) = (Vec::new(), Vec::new());
+                for path in paths {
+                    if !path.is_file() {
+                        skipped.push(path);
+                        continue;
+                    }
+                    match attachments::add_file(conn, &store, project_id.clone(), &path) {
+                        Ok(attachment) => {
+                            audit::record_now(
+                                conn,
+                                &actor,
+                                Change::new("paste_from_clipboard", "attachment", &attachment.id)
+                                    .after(&attachment)?,
+                            )?;
+                            added.push(attachment);
+                        }
+                        // A missing project fails every file the same way
+                        Err(err @ AppError::NotFound { .. }) => return Err(err),
+                        Err(err) => {
+                            tracing::warn!(path = %path.display(), %err, "pasted file not attached");
+                            skipped.push(path);
+                        }
+                    }
+                }
+                Ok(PastedContent::Files {
+                    attachments: added,
+                    skipped,
+                })
+            })
+            .await
+        }
+        Raw::Html { html, text } => Ok(PastedContent::Html { html, text }),
+        Raw::Text(text) => Ok(PastedContent::Text { text }),
+        Raw::Empty => Ok(PastedContent::Empty),
+    }
+}
@@ -0,0 +1,339 @@
+//! Opt-in watching of the clipboard for quantities copied out of other takeoff tools.
+//!
+//! While the user has it switched on, copied text is checked for measurements such as
+//! `125.5 LF`, `1,240 sq ft`, `12'-6"`, or `18 EA`, and `clipboard:quantities` carries what
+//! was found so the frontend can offer to add them to the open estimate. Watching is off at
+//! every launch and never persisted, copies made inside Momentum are ignored, and clipboard
+//! contents are never logged. Where the platform keeps a change counter only changed
+//! clipboards are read; elsewhere the text is read on each poll and compared.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::clipboard;
+use crate::error::AppResult;
+
+/// Emitted with [`CopiedQuantities`] when copied text holds at least one quantity.
+pub const QUANTITIES_EVENT: &str = "clipboard:quantities";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// Past this it is a document, not a list of measurements
+const MAX_TEXT_LEN: usize = 256 * 1024;
+const MAX_QUANTITIES: usize = 1000;
+
+// Aliases are matched case-insensitively, longest first so "sq ft" wins over "ft"
+const UNITS: &[(&str, &str)] = &[
+    ("square feet", "SF"),
+    ("square yards", "SY"),
+    ("cubic yards", "CY"),
+    ("cubic feet", "CF"),
+    ("linear feet", "LF"),
+    ("lin. ft.", "LF"),
+    ("sq. ft.", "SF"),
+    ("sq. yd.", "SY"),
+    ("cu. yd.", "CY"),
+    ("cu. ft.", "CF"),
+    ("lin ft", "LF"),
+    ("sq ft", "SF"),
+    ("sq yd", "SY"),
+    ("cu yd", "CY"),
+    ("cu ft", "CF"),
+    ("sq m", "M2"),
+    ("each", "EA"),
+    ("feet", "LF"),
+    ("tons", "TON"),
+    ("sqft", "SF"),
+    ("ton", "TON"),
+    ("lbs", "LB"),
+    ("gal", "GAL"),
+    ("hrs", "HR"),
+    ("pcs", "EA"),
+    ("ft²", "SF"),
+    ("ft³", "CF"),
+    ("yd²", "SY"),
+    ("yd³", "CY"),
+    ("lf", "LF"),
+    ("sf", "SF"),
+    ("sy", "SY"),
+    ("cy", "CY"),
+    ("cf", "CF"),
+    ("ea", "EA"),
+    ("ls", "LS"),
+    ("lb", "LB"),
+    ("hr", "HR"),
+    ("ft", "LF"),
+    ("m²", "M2"),
+    ("m2", "M2"),
+    ("m³", "M3"),
+    ("m3", "M3"),
+    ("mm", "MM"),
+    ("m", "M"),
+];
+
+/// One measurement found in copied text.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Quantity {
+    pub value: f64,
+    /// Normalized unit code such as `LF`, `SF`, `CY`, or `EA`.
+    pub unit: String,
+    /// The rest of the line, when there is any, e.g. a markup subject.
+    pub label: Option<String>,
+    /// Zero-based line of the copied text it came from.
+    pub line: usize,
+}
+
+/// Payload for `clipboard:quantities`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CopiedQuantities {
+    pub quantities: Vec<Quantity>,
+    /// Lines in the copied text, so the frontend can tell how much was skipped.
+    pub lines: usize,
+}
+
+/// Watcher state registered in app state.
+#[derive(Default)]
+pub struct ClipboardWatch {
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+// A number at `at`, with thousands separators; returns the value and where it ends
+fn number(chars: &[char], at: usize) -> Option<(f64, usize)> {
+    let mut end = at;
+    let mut digits = String::new();
+    while end < chars.len() {
+        let c = chars[end];
+        if c.is_ascii_digit() {
+            digits.push(c);
+        } else if c == ','
+            && chars
+                .get(end + 1..end + 4)
+                .is_some_and(|next| next.iter().all(char::is_ascii_digit))
+            && !chars.get(end + 4).is_some_and(char::is_ascii_digit)
+        {
+            // A thousands separator, not a list separator
+        } else if c == '.'
+            && chars.get(end + 1).is_some_and(char::is_ascii_digit)
+            && !digits.contains('.')
+        {
+            digits.push(c);
+        } else {
+            break;
+        }
+        end += 1;
+    }
+    let value = digits.parse().ok()?;
+    Some((value, end))
+}
+
+fn skip_spaces(chars: &[char], mut at: usize) -> usize {
+    while chars.get(at).is_some_and(|c| *c == ' ' || *c == '\u{a0}') {
+        at += 1;
+    }
+    at
+}
+
+fn is_feet_mark(c: char) -> bool {
+    matches!(c, '\'' | '’' | '′')
+}
+
+fn is_inch_mark(c: char) -> bool {
+    matches!(c, '"' | '”' | '″')
+}
+
+// 12'-6", 12' 6", 12'6", or 12'
+fn feet_and_inches(chars: &[char], feet: f64, at: usize) -> Option<(f64, usize)> {
+    if !chars.get(at).copied().is_some_and(is_feet_mark) {
+        return None;
+    }
+    let after_feet = at + 1;
+    let mut inches_at = skip_spaces(chars, after_feet);
+    if chars.get(inches_at) == Some(&'-') {
+        inches_at = skip_spaces(chars, inches_at + 1);
+    }
+    if let Some((inches, end)) = number(chars, inches_at) {
+        let end = skip_spaces(chars, end);
+        if chars.get(end).copied().is_some_and(is_inch_mark) {
+            return Some((feet + inches / 12.0, end + 1));
+        }
+    }
+    Some((feet, after_feet))
+}
+
+// The unit right after a number, ending at a word boundary
+fn unit(chars: &[char], at: usize) -> Option<(&'static str, usize)> {
+    let rest: String = chars[at..]
+        .iter()
+        .take(16)
+        .collect::<String>()
+        .to_lowercase();
+    UNITS.iter().find_map(|(alias, code)| {
+        let tail = rest.strip_prefix(alias)?;
+        if tail.chars().next().is_some_and(char::is_alphanumeric) {
+            return None;
+        }
+        Some((*code, at + alias.chars().count()))
+    })
+}
+
+fn label(chars: &[char], spans: &[(usize, usize)]) -> Option<String> {
+    let mut text = String::new();
+    let mut last = 0;
+    for &(start, end) in spans {
+        text.extend(&chars[last..start]);
+        text.push(' ');
+        last = end;
+    }
+    text.extend(&chars[last..]);
+    let label = text
+        .split(|c: char| c.is_whitespace() || matches!(c, '|' | ';'))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let label = label.trim_matches(|c: char| matches!(c, ':' | '-' | ',' | '=' | ' '));
+    (!label.is_empty()).then(|| label.to_string())
+}
+
+fn parse_line(line: &str, index: usize) -> Vec<Quantity> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut found = Vec::new();
+    let mut spans = Vec::new();
+    let mut at = 0;
+    while at < chars.len() {
+        let starts_number = chars[at].is_ascii_digit()
+            && (at == 0 || !(chars[at - 1].is_alphanumeric() || chars[at - 1] == '.'));
+        if !starts_number {
+            at += 1;
+            continue;
+        }
+        let Some((value, end)) = number(&chars, at) else {
+            at += 1;
+            continue;
+        };
+        let measured = feet_and_inches(&chars, value, end)
+            .map(|(feet, end)| ("LF", feet, end))
+            .or_else(|| {
+                let (code, end) = unit(&chars, skip_spaces(&chars, end))?;
+                Some((code, value, end))
+            });
+        match measured {
+            Some((code, value, unit_end)) => {
+                found.push((value, code));
+                spans.push((at, unit_end));
+                at = unit_end;
+            }
+            None => at = end,
+        }
+    }
+    let label = label(&chars, &spans);
+    found
+        .into_iter()
+        .map(|(value, unit)| Quantity {
+            value,
+            unit: unit.to_string(),
+            label: label.clone(),
+            line: index,
+        })
+        .collect()
+}
+
+/// Every quantity in `text`, line by line.
+pub fn parse_quantities(text: &str) -> Vec<Quantity> {
+    text.lines()
+        .enumerate()
+        .flat_map(|(index, line)| parse_line(line, index))
+        .take(MAX_QUANTITIES)
+        .collect()
+}
+
+fn fingerprint(text: &str) -> [u8; 32] {
+    Sha256::digest(text.as_bytes()).into()
+}
+
+// Copies made in our own windows are the user working in Momentum, not importing
+fn app_focused(app: &AppHandle) -> bool {
+    app.webview_windows()
+        .values()
+        .any(|window| window.is_focused().unwrap_or(false))
+}
+
+async fn watch(app: AppHandle) {
+    // What was on the clipboard when watching started is not a new copy
+    let mut last_count = clipboard::change_count();
+    let mut last_text = tauri::async_runtime::spawn_blocking(clipboard::read_text)
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .flatten()
+        .map(|text| fingerprint(&text));
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let count = clipboard::change_count();
+        if count.is_some() && count == last_count {
+            continue;
+        }
+        last_count = count;
+        let text = match tauri::async_runtime::spawn_blocking(clipboard::read_text).await {
+            Ok(Ok(Some(text))) => text,
+            Ok(Ok(None)) => continue,
+            Ok(Err(err)) => {
+                tracing::debug!(%err, "cannot read the clipboard");
+                continue;
+            }
+            Err(_) => continue,
+        };
+        let print = fingerprint(&text);
+        if last_text == Some(print) {
+            continue;
+        }
+        last_text = Some(print);
+        if text.len() > MAX_TEXT_LEN || app_focused(&app) {
+            continue;
+        }
+        let quantities = parse_quantities(&text);
+        if quantities.is_empty() {
+            continue;
+        }
+        tracing::debug!(count = quantities.len(), "quantities copied");
+        let payload = CopiedQuantities {
+            quantities,
+            lines: text.lines().count(),
+        };
+        let _ = app.emit(QUANTITIES_EVENT, payload);
+    }
+}
+
+/// Turn clipboard watching on or off for this session.
+#[tauri::command]
+pub async fn set_clipboard_watch(
+    app: AppHandle,
+    watcher: State<'_, ClipboardWatch>,
+    enabled: bool,
+) -> AppResult<bool> {
+    let mut task = watcher.task.lock().unwrap_or_else(|e| e.into_inner());
+    if enabled && task.is_none() {
+        *task = Some(tauri::async_runtime::spawn(watch(app.clone())));
+        tracing::info!("clipboard watching on");
+    } else if !enabled {
+        if let Some(task) = task.take() {
+            task.abort();
+            tracing::info!("clipboard watching off");
+        }
+    }
+    Ok(enabled)
+}
+
+#[tauri::command]
+pub async fn get_clipboard_watch(watcher: State<'_, ClipboardWatch>) -> AppResult<bool> {
+    Ok(watcher
+        .task
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .is_some())
+}
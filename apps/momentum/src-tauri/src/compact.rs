@@ -0,0 +1,158 @@
+//! Compact mode: a small always-on-top window, such as the job timer, that stays visible
+//! over other apps.
+//!
+//! Entering compact mode holds the window's saved geometry, so closing or quitting while
+//! compact still reopens at the normal size, and leaving it restores that geometry along
+//! with the window's previous resizability and stacking.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{
+    Emitter, LogicalSize, Manager, PhysicalPosition, Runtime, WebviewWindow, Window, WindowEvent,
+};
+
+use crate::error::AppResult;
+use crate::window_state::WindowStateStore;
+
+/// Emitted to a window with [`CompactChanged`] when it enters or leaves compact mode.
+pub const COMPACT_CHANGED_EVENT: &str = "window:compact-changed";
+
+const COMPACT_SIZE: LogicalSize<f64> = LogicalSize::new(360.0, 200.0);
+// Gap from the screen's top-right corner, in logical pixels
+const MARGIN: f64 = 16.0;
+
+/// Payload for `window:compact-changed`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompactChanged {
+    pub compact: bool,
+}
+
+// What compact mode overrides, to put back on the way out
+#[derive(Debug, Clone, Copy)]
+struct Previous {
+    always_on_top: bool,
+    resizable: bool,
+}
+
+/// Windows currently in compact mode, registered in app state.
+#[derive(Default)]
+pub struct CompactMode {
+    windows: Mutex<BTreeMap<String, Previous>>,
+}
+
+impl CompactMode {
+    fn is_compact(&self, label: &str) -> bool {
+        self.windows
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key(label)
+    }
+}
+
+fn enter(window: &WebviewWindow, compact: &CompactMode) -> AppResult<()> {
+    let previous = Previous {
+        always_on_top: window.is_always_on_top()?,
+        resizable: window.is_resizable()?,
+    };
+    if window.is_fullscreen()? {
+        window.set_fullscreen(false)?;
+    }
+    // Held before un-maximizing so the window reopens maximized if it was
+    let store = window.state::<WindowStateStore>();
+    store.hold(&window.as_ref().window());
+    compact
+        .windows
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(window.label().to_string(), previous);
+    if window.is_maximized()? {
+        window.unmaximize()?;
+    }
+    window.unminimize()?;
+
+    window.set_min_size(None::<LogicalSize<f64>>)?;
+    window.set_resizable(false)?;
+    window.set_size(COMPACT_SIZE)?;
+    if let Some(monitor) = window.current_monitor()? {
+        let area = monitor.work_area();
+        let scale = monitor.scale_factor();
+        let width = (COMPACT_SIZE.width * scale) as i32;
+        let margin = (MARGIN * scale) as i32;
+        window.set_position(PhysicalPosition::new(
+            area.position.x + area.size.width as i32 - width - margin,
+            area.position.y + margin,
+        ))?;
+    }
+    window.set_always_on_top(true)?;
+    window.set_visible_on_all_workspaces(true)?;
+    window.set_focus()?;
+    Ok(())
+}
+
+fn leave(window: &WebviewWindow, compact: &CompactMode) -> AppResult<()> {
+    let Some(previous) = compact
+        .windows
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(window.label())
+    else {
+        return Ok(());
+    };
+    window.set_visible_on_all_workspaces(false)?;
+    window.set_always_on_top(previous.always_on_top)?;
+    window.set_resizable(previous.resizable)?;
+    window.set_min_size(crate::windows::min_size(
+        window.app_handle(),
+        window.label(),
+    ))?;
+    let store = window.state::<WindowStateStore>();
+    store.release(window.label());
+    store.restore(window);
+    Ok(())
+}
+
+/// Forget windows that close while compact.
+pub fn on_window_event<R: Runtime>(window: &Window<R>, event: &WindowEvent) {
+    if let WindowEvent::Destroyed = event {
+        if let Some(compact) = window.try_state::<CompactMode>() {
+            compact
+                .windows
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(window.label());
+        }
+        if let Some(store) = window.try_state::<WindowStateStore>() {
+            store.release(window.label());
+        }
+    }
+}
+
+/// Shrink the calling window to a small always-on-top layout on every space and virtual
+/// desktop, or restore its previous geometry; returns whether it is now compact.
+#[tauri::command]
+pub async fn set_compact_mode(window: WebviewWindow, enabled: bool) -> AppResult<bool> {
+    let compact = window.state::<CompactMode>();
+    if enabled == compact.is_compact(window.label()) {
+        return Ok(enabled);
+    }
+    if enabled {
+        enter(&window, &compact)?;
+    } else {
+        leave(&window, &compact)?;
+    }
+    let _ = window.emit_to(
+        window.label(),
+        COMPACT_CHANGED_EVENT,
+        CompactChanged { compact: enabled },
+    );
+    Ok(enabled)
+}
+
+/// Whether the calling window is in compact mode.
+#[tauri::command]
+pub async fn get_compact_mode(window: WebviewWindow) -> AppResult<bool> {
+    Ok(window.state::<CompactMode>().is_compact(window.label()))
+}
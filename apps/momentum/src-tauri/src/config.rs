@@ -0,0 +1,223 @@
+//! User-editable app configuration persisted as JSON and hot-reloaded on external edits.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use url::Url;
+
+use crate::background::BackgroundPolicy;
+use crate::backup::BackupSettings;
+use crate::email::EmailSettings;
+use crate::env;
+use crate::error::{AppError, AppResult};
+use crate::licensing::LicenseSettings;
+use crate::photos::PhotoSettings;
+use crate::portable;
+use crate::power::PowerSettings;
+use crate::proxy::ProxySettings;
+use crate::resources::ResourceSettings;
+use crate::state::AppState;
+use crate::sync::ConflictStrategy;
+use crate::watched_folders::{self, WatchedFolder};
+
+const CONFIG_FILE_NAME: &str = "config.json";
+
+/// Emitted with the new [`Config`] whenever settings change.
+pub const CONFIG_CHANGED_EVENT: &str = "config-changed";
+
+/// Persisted application settings.
+///
+/// Every field has a default so config files written by older builds keep loading.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Config {
+    /// Overrides the build environment's API base URL, e.g. for a self-hosted server.
+    pub api_base_url: Option<String>,
+    pub features: BTreeMap<String, bool>,
+    /// Overrides the platform app data dir of the default workspace; takes effect on next
+    /// launch.
+    pub data_dir: Option<PathBuf>,
+    /// Hide the main window on close so timers and sync keep running from the tray.
+    pub close_to_tray: bool,
+    /// When launched at login, stay in the tray instead of opening the main window.
+    pub start_minimized: bool,
+    /// Global shortcut accelerators keyed by action name.
+    pub shortcuts: BTreeMap<String, String>,
+    /// How sync settles rows edited both locally and on the server.
+    pub sync_conflict_strategy: ConflictStrategy,
+    /// Per-host `sha256/<base64>` key pins replacing the bundled set, e.g. for staging.
+    /// An empty list disables pinning for that host. Takes effect on next launch.
+    pub tls_pins: BTreeMap<String, Vec<String>>,
+    /// Outbound proxy for API traffic and update checks.
+    pub proxy: ProxySettings,
+    /// Folders whose new files are reported, and optionally imported, as they arrive.
+    pub watched_folders: Vec<WatchedFolder>,
+    /// Minutes without keyboard or mouse input anywhere on the machine before the session
+    /// locks; `None` never locks.
+    pub idle_lock_minutes: Option<u32>,
+    /// Stop collecting and sending anonymous usage events.
+    pub telemetry_opt_out: bool,
+    /// Scheduled cloud backups.
+    pub backup: BackupSettings,
+    /// Size, quality, and metadata of photos taken in the app.
+    pub photos: PhotoSettings,
+    /// Memory levels at which the frontend is told to release caches.
+    pub resources: ResourceSettings,
+    /// Deferring background work on battery, and the low-battery warning level.
+    pub power: PowerSettings,
+    /// When background sync and indexing slow down or catch up, by activity and power.
+    pub background: BackgroundPolicy,
+    /// The SMTP server reports are sent through directly.
+    pub email: EmailSettings,
+    /// How long the license keeps working without reaching the license server.
+    pub license: LicenseSettings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            api_base_url: None,
+            features: BTreeMap::new(),
+            data_dir: None,
+            close_to_tray: false,
+            start_minimized: false,
+            shortcuts: BTreeMap::from([(
+                "quickCapture".to_string(),
+                "CommandOrControl+Shift+Space".to_string(),
+            )]),
+            sync_conflict_strategy: ConflictStrategy::default(),
+            tls_pins: BTreeMap::new(),
+            proxy: ProxySettings::default(),
+            watched_folders: Vec::new(),
+            idle_lock_minutes: None,
+            telemetry_opt_out: false,
+            backup: BackupSettings::default(),
+            photos: PhotoSettings::default(),
+            resources: ResourceSettings::default(),
+            power: PowerSettings::default(),
+            background: BackgroundPolicy::default(),
+            email: EmailSettings::default(),
+            license: LicenseSettings::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Resolve the config file path under the platform config dir.
+    pub fn path(app: &AppHandle) -> AppResult<PathBuf> {
+        Ok(portable::app_config_dir(app)?.join(CONFIG_FILE_NAME))
+    }
+
+    /// Load from disk, falling back to defaults when the file is missing.
+    pub fn load(path: &Path) -> AppResult<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Write atomically so a crash mid-save never leaves a truncated file.
+    pub fn save(&self, path: &Path) -> AppResult<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Resolve `path` against the configured API base URL.
+///
+/// Only absolute paths are accepted; a full or protocol-relative URL from the frontend would
+/// otherwise send the session token to another host.
+pub fn api_url(app: &AppHandle, path: &str) -> AppResult<Url> {
+    if !path.starts_with('/') || path.starts_with("//") {
+        return Err(AppError::InvalidInput(format!(
+            "expected an API path, got {path}"
+        )));
+    }
+    let base = app
+        .state::<AppState>()
+        .config()
+        .api_base_url
+        .unwrap_or_else(|| env::current().api_base_url().into());
+    Url::parse(&base)
+        .and_then(|base| base.join(path))
+        .map_err(|e| AppError::InvalidInput(format!("invalid API base URL {base}: {e}")))
+}
+
+/// Keeps the config file watcher alive for the app's lifetime.
+pub struct ConfigWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+/// Watch the config file and apply edits made outside the app.
+///
+/// The parent dir is watched because editors commonly save by renaming a temp file.
+pub fn watch(app: &AppHandle) -> AppResult<ConfigWatcher> {
+    let path = app.state::<AppState>().config_path().to_path_buf();
+    let dir = path
+        .parent()
+        .ok_or_else(|| AppError::Internal("config path has no parent".into()))?
+        .to_path_buf();
+    fs::create_dir_all(&dir)?;
+
+    let handle = app.clone();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(event) = res else { return };
+        if !event.paths.iter().any(|p| p == &path) {
+            return;
+        }
+        match Config::load(&path) {
+            Ok(config) => apply(&handle, config),
+            // Half-written files fail to parse; the follow-up event will succeed
+            Err(err) => tracing::debug!(%err, "ignoring unreadable config change"),
+        }
+    })?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+
+    Ok(ConfigWatcher { _watcher: watcher })
+}
+
+// Our own saves also trigger the watcher, so unchanged configs are not re-broadcast.
+fn apply(app: &AppHandle, config: Config) {
+    let state = app.state::<AppState>();
+    if state.replace_config(config.clone()) {
+        tracing::info!("config updated");
+        let _ = app.emit(CONFIG_CHANGED_EVENT, &config);
+        watched_folders::reconfigure(app);
+    }
+}
+
+/// Apply an in-app edit to the current config, persist it, and broadcast the change.
+pub fn update(app: &AppHandle, edit: impl FnOnce(&mut Config)) -> AppResult<Config> {
+    let state = app.state::<AppState>();
+    let mut config = state.config();
+    edit(&mut config);
+    config.save(state.config_path())?;
+    apply(app, config.clone());
+    Ok(config)
+}
+
+#[tauri::command]
+pub async fn get_config(state: State<'_, AppState>) -> AppResult<Config> {
+    Ok(state.config())
+}
+
+#[tauri::command]
+pub async fn set_config(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    config: Config,
+) -> AppResult<Config> {
+    config.save(state.config_path())?;
+    apply(&app, config.clone());
+    Ok(config)
+}
@@ -0,0 +1,367 @@
+//! Crash capture: panic reports and native minidumps, offered for upload on the next launch.
+//!
+//! Native crashes are written by a monitor child process because a crashed process cannot
+//! reliably dump itself; the app re-launches its own executable with `--crash-monitor`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+use uuid::Uuid;
+
+use crate::config;
+use crate::db::now_ms;
+use crate::error::{AppError, AppResult};
+use crate::http_client::{HttpClient, RetryPolicy};
+
+const CRASH_DIR_NAME: &str = "crashes";
+const MINIDUMP_EXTENSION: &str = "dmp";
+const PANIC_EXTENSION: &str = "json";
+const UPLOAD_PATH: &str = "/v1/crash-reports";
+
+#[cfg(desktop)]
+const MONITOR_ARG: &str = "--crash-monitor";
+#[cfg(desktop)]
+const MONITOR_CONNECT_ATTEMPTS: u32 = 20;
+
+/// What produced a crash report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CrashKind {
+    Minidump,
+    Panic,
+}
+
+/// A crash report waiting for the user to upload or dismiss it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashReport {
+    pub id: String,
+    pub kind: CrashKind,
+    pub created_at: i64,
+    pub size: u64,
+    /// Panic message; minidumps need symbolication server-side.
+    pub message: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PanicReport<'a> {
+    message: &'a str,
+    location: &'a str,
+    backtrace: String,
+    version: &'static str,
+    os: &'static str,
+    created_at: i64,
+}
+
+/// Crash capture handle registered in app state.
+pub struct CrashReporter {
+    dir: PathBuf,
+    #[cfg(desktop)]
+    _native: Option<native::Guard>,
+}
+
+/// Start crash capture, writing reports under `data_dir/crashes`.
+///
+/// Native capture is best-effort: if the monitor can't start, panics are still recorded.
+pub fn init(data_dir: &Path) -> AppResult<CrashReporter> {
+    let dir = data_dir.join(CRASH_DIR_NAME);
+    fs::create_dir_all(&dir)?;
+    install_panic_hook(dir.clone());
+
+    #[cfg(desktop)]
+    let native = match native::attach(&dir) {
+        Ok(guard) => Some(guard),
+        Err(err) => {
+            tracing::warn!(%err, "native crash capture unavailable");
+            None
+        }
+    };
+
+    Ok(CrashReporter {
+        dir,
+        #[cfg(desktop)]
+        _native: native,
+    })
+}
+
+/// Run as the crash monitor when launched with `--crash-monitor`; returns whether it did.
+///
+/// Must be called before the Tauri builder so the monitor never creates windows.
+#[cfg(desktop)]
+pub fn run_monitor_if_requested() -> bool {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() != Some(MONITOR_ARG) {
+        return false;
+    }
+    let (Some(socket), Some(dir)) = (args.next(), args.next()) else {
+        return true;
+    };
+    if let Err(err) = native::serve(Path::new(&socket), PathBuf::from(dir)) {
+        eprintln!("crash monitor failed: {err}");
+    }
+    true
+}
+
+// Chains onto the logging hook so panics are still logged and printed.
+fn install_panic_hook(dir: PathBuf) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}", l.file(), l.line()))
+            .unwrap_or_default();
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic payload".into());
+        let report = PanicReport {
+            message: &message,
+            location: &location,
+            backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+            version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+            created_at: now_ms(),
+        };
+        let path = dir.join(format!("{}.{PANIC_EXTENSION}", Uuid::new_v4()));
+        if let Ok(bytes) = serde_json::to_vec_pretty(&report) {
+            let _ = fs::write(path, bytes);
+        }
+        previous(info);
+    }));
+}
+
+impl CrashReporter {
+    fn reports(&self) -> AppResult<Vec<CrashReport>> {
+        let mut reports = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let Some((id, kind)) = report_identity(&path) else {
+                continue;
+            };
+            let metadata = fs::metadata(&path)?;
+            let created_at = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_millis() as i64);
+            let message = match kind {
+                CrashKind::Panic => fs::read(&path)
+                    .ok()
+                    .and_then(|b| serde_json::from_slice::<serde_json::Value>(&b).ok())
+                    .and_then(|v| v.get("message")?.as_str().map(str::to_string)),
+                CrashKind::Minidump => None,
+            };
+            reports.push(CrashReport {
+                id,
+                kind,
+                created_at,
+                size: metadata.len(),
+                message,
+            });
+        }
+        reports.sort_by_key(|r| r.created_at);
+        Ok(reports)
+    }
+
+    fn path_for(&self, id: &str) -> AppResult<(PathBuf, CrashKind)> {
+        // Ids come from the frontend; only bare UUIDs may be turned into paths
+        Uuid::parse_str(id)
+            .map_err(|_| AppError::InvalidInput(format!("invalid report id {id}")))?;
+        [
+            (MINIDUMP_EXTENSION, CrashKind::Minidump),
+            (PANIC_EXTENSION, CrashKind::Panic),
+        ]
+        .into_iter()
+        .map(|(ext, kind)| (self.dir.join(format!("{id}.{ext}")), kind))
+        .find(|(path, _)| path.is_file())
+        .ok_or_else(|| AppError::not_found("crash report", id))
+    }
+}
+
+fn report_identity(path: &Path) -> Option<(String, CrashKind)> {
+    let kind = match path.extension()?.to_str()? {
+        MINIDUMP_EXTENSION => CrashKind::Minidump,
+        PANIC_EXTENSION => CrashKind::Panic,
+        _ => return None,
+    };
+    let id = path.file_stem()?.to_str()?;
+    Uuid::parse_str(id).ok()?;
+    Some((id.to_string(), kind))
+}
+
+/// Reports left by previous sessions, oldest first.
+#[tauri::command]
+pub async fn get_pending_crash_reports(
+    reporter: State<'_, CrashReporter>,
+) -> AppResult<Vec<CrashReport>> {
+    reporter.reports()
+}
+
+/// Upload the reports the user agreed to send, deleting each once accepted.
+///
+/// Nothing is ever uploaded without this explicit call. Returns the ids that were sent.
+#[tauri::command]
+pub async fn upload_crash_reports(
+    app: AppHandle,
+    reporter: State<'_, CrashReporter>,
+    ids: Vec<String>,
+) -> AppResult<Vec<String>> {
+    let url = config::api_url(&app, UPLOAD_PATH)?;
+    let http = app.state::<HttpClient>();
+    let mut uploaded = Vec::with_capacity(ids.len());
+    for id in ids {
+        let (path, kind) = reporter.path_for(&id)?;
+        let (content_type, kind_header) = match kind {
+            CrashKind::Minidump => ("application/x-dmp", "minidump"),
+            CrashKind::Panic => ("application/json", "panic"),
+        };
+        // The report id makes a repeated upload safe to retry
+        let request = http
+            .client()
+            .post(url.clone())
+            .header("Content-Type", content_type)
+            .header("Idempotency-Key", &id)
+            .header("X-Crash-Kind", kind_header)
+            .header("X-Crash-Id", &id)
+            .header("X-App-Version", env!("CARGO_PKG_VERSION"))
+            .header("X-App-Os", std::env::consts::OS)
+            .body(fs::read(&path)?);
+        http.send(request, RetryPolicy::default())
+            .await?
+            .error_for_status()?;
+        fs::remove_file(&path)?;
+        tracing::info!(%id, "crash report uploaded");
+        uploaded.push(id);
+    }
+    Ok(uploaded)
+}
+
+/// Discard reports the user declined to send.
+#[tauri::command]
+pub async fn dismiss_crash_reports(
+    reporter: State<'_, CrashReporter>,
+    ids: Vec<String>,
+) -> AppResult<()> {
+    for id in ids {
+        let (path, _) = reporter.path_for(&id)?;
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(desktop)]
+mod native {
+    use std::fs::File;
+    use std::path::{Path, PathBuf};
+    use std::process::{Child, Command};
+    use std::sync::atomic::AtomicBool;
+    use std::time::Duration;
+
+    use crash_handler::{CrashContext, CrashEventResult, CrashHandler};
+    use minidumper::{Client, LoopAction, MinidumpBinary, Server, ServerHandler, SocketName};
+    use uuid::Uuid;
+
+    use super::{MINIDUMP_EXTENSION, MONITOR_ARG, MONITOR_CONNECT_ATTEMPTS};
+    use crate::error::{AppError, AppResult};
+
+    /// Keeps the crash handler attached and the monitor process owned.
+    pub struct Guard {
+        _handler: CrashHandler,
+        _monitor: Child,
+    }
+
+    fn internal(err: impl std::fmt::Display) -> AppError {
+        AppError::Internal(format!("crash monitor: {err}"))
+    }
+
+    /// Spawn the monitor and route native crashes to it.
+    pub fn attach(dir: &Path) -> AppResult<Guard> {
+        // Unix socket paths are length-limited, so use the short temp dir rather than app data
+        let socket =
+            std::env::temp_dir().join(format!("momentum-crash-{}.sock", std::process::id()));
+        let monitor = Command::new(std::env::current_exe()?)
+            .arg(MONITOR_ARG)
+            .arg(&socket)
+            .arg(dir)
+            .spawn()?;
+
+        let mut attempts = 0;
+        let client = loop {
+            match Client::with_name(SocketName::Path(&socket)) {
+                Ok(client) => break client,
+                Err(_) if attempts < MONITOR_CONNECT_ATTEMPTS => {
+                    attempts += 1;
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                Err(err) => return Err(internal(err)),
+            }
+        };
+
+        // SAFETY: the closure only performs the IPC request minidumper documents as crash-safe
+        let handler = CrashHandler::attach(unsafe {
+            crash_handler::make_crash_event(move |context: &CrashContext| {
+                CrashEventResult::Handled(client.request_dump(context).is_ok())
+            })
+        })
+        .map_err(internal)?;
+
+        // Yama ptrace scoping would otherwise block the monitor from reading our memory
+        #[cfg(target_os = "linux")]
+        handler.set_ptracer(Some(monitor.id()));
+
+        Ok(Guard {
+            _handler: handler,
+            _monitor: monitor,
+        })
+    }
+
+    struct Handler {
+        dir: PathBuf,
+    }
+
+    impl ServerHandler for Handler {
+        fn create_minidump_file(&self) -> Result<(File, PathBuf), std::io::Error> {
+            let path = self
+                .dir
+                .join(format!("{}.{MINIDUMP_EXTENSION}", Uuid::new_v4()));
+            Ok((File::create(&path)?, path))
+        }
+
+        fn on_minidump_created(
+            &self,
+            result: Result<MinidumpBinary, minidumper::Error>,
+        ) -> LoopAction {
+            if let Err(err) = result {
+                eprintln!("failed to write minidump: {err}");
+            }
+            LoopAction::Exit
+        }
+
+        fn on_message(&self, _kind: u32, _buffer: Vec<u8>) {}
+
+        // The app exiting normally disconnects its only client
+        fn on_client_disconnected(&self, num_clients: usize) -> LoopAction {
+            if num_clients == 0 {
+                LoopAction::Exit
+            } else {
+                LoopAction::Continue
+            }
+        }
+    }
+
+    /// Monitor main loop, run in the child process.
+    pub fn serve(socket: &Path, dir: PathBuf) -> AppResult<()> {
+        let mut server = Server::with_name(SocketName::Path(socket)).map_err(internal)?;
+        let shutdown = AtomicBool::new(false);
+        let result = server
+            .run(Box::new(Handler { dir }), &shutdown, None)
+            .map_err(internal);
+        let _ = std::fs::remove_file(socket);
+        result
+    }
+}
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS jobs (
+    id               TEXT PRIMARY KEY,
+    kind             TEXT NOT NULL,
+    interval_ms      INTEGER,
+    next_run_at      INTEGER,
+    paused           INTEGER NOT NULL DEFAULT 0,
+    last_run_at      INTEGER,
+    last_duration_ms INTEGER,
+    last_status      TEXT,
+    last_error       TEXT,
+    created_at       INTEGER NOT NULL,
+    updated_at       INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_jobs_due ON jobs(paused, next_run_at);
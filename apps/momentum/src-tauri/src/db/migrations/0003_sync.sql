@@ -0,0 +1,67 @@
+-- Last server version each row was synced at; local edits leave it unchanged
+ALTER TABLE projects ADD COLUMN version INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE items ADD COLUMN version INTEGER NOT NULL DEFAULT 0;
+
+-- One pending change per row; later edits collapse into the same entry
+CREATE TABLE IF NOT EXISTS sync_changes (
+    entity       TEXT NOT NULL,
+    entity_id    TEXT NOT NULL,
+    base_version INTEGER NOT NULL,
+    deleted      INTEGER NOT NULL DEFAULT 0,
+    changed_at   INTEGER NOT NULL,
+    PRIMARY KEY (entity, entity_id)
+);
+
+CREATE TABLE IF NOT EXISTS sync_state (
+    key   TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+
+-- Existing data has never been pushed
+INSERT OR IGNORE INTO sync_changes (entity, entity_id, base_version, changed_at)
+    SELECT 'project', id, 0, updated_at FROM projects;
+INSERT OR IGNORE INTO sync_changes (entity, entity_id, base_version, changed_at)
+    SELECT 'item', id, 0, updated_at FROM items;
+
+-- The sync engine sets the 'applying' key while writing pulled rows so they are not echoed back
+CREATE TRIGGER IF NOT EXISTS sync_projects_insert AFTER INSERT ON projects
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying')
+BEGIN
+    INSERT OR REPLACE INTO sync_changes (entity, entity_id, base_version, deleted, changed_at)
+    VALUES ('project', NEW.id, NEW.version, 0, NEW.updated_at);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_projects_update AFTER UPDATE ON projects
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying')
+BEGIN
+    INSERT OR REPLACE INTO sync_changes (entity, entity_id, base_version, deleted, changed_at)
+    VALUES ('project', NEW.id, NEW.version, 0, NEW.updated_at);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_projects_delete AFTER DELETE ON projects
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying')
+BEGIN
+    INSERT OR REPLACE INTO sync_changes (entity, entity_id, base_version, deleted, changed_at)
+    VALUES ('project', OLD.id, OLD.version, 1, CAST(unixepoch('subsec') * 1000 AS INTEGER));
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_items_insert AFTER INSERT ON items
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying')
+BEGIN
+    INSERT OR REPLACE INTO sync_changes (entity, entity_id, base_version, deleted, changed_at)
+    VALUES ('item', NEW.id, NEW.version, 0, NEW.updated_at);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_items_update AFTER UPDATE ON items
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying')
+BEGIN
+    INSERT OR REPLACE INTO sync_changes (entity, entity_id, base_version, deleted, changed_at)
+    VALUES ('item', NEW.id, NEW.version, 0, NEW.updated_at);
+END;
+
+CREATE TRIGGER IF NOT EXISTS sync_items_delete AFTER DELETE ON items
+WHEN NOT EXISTS (SELECT 1 FROM sync_state WHERE key = 'applying')
+BEGIN
+    INSERT OR REPLACE INTO sync_changes (entity, entity_id, base_version, deleted, changed_at)
+    VALUES ('item', OLD.id, OLD.version, 1, CAST(unixepoch('subsec') * 1000 AS INTEGER));
+END;
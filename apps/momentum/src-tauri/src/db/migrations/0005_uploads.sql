@@ -0,0 +1,20 @@
+-- uploaded only advances once the server acknowledges a chunk, so it is always safe to resume from
+CREATE TABLE IF NOT EXISTS uploads (
+    id           TEXT PRIMARY KEY,
+    path         TEXT NOT NULL,
+    file_name    TEXT NOT NULL,
+    size         INTEGER NOT NULL,
+    modified_at  INTEGER NOT NULL,
+    content_type TEXT NOT NULL,
+    sha256       TEXT NOT NULL,
+    remote_id    TEXT,
+    chunk_size   INTEGER NOT NULL,
+    uploaded     INTEGER NOT NULL DEFAULT 0,
+    status       TEXT NOT NULL DEFAULT 'uploading',
+    last_error   TEXT,
+    result       TEXT,
+    created_at   INTEGER NOT NULL,
+    updated_at   INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_uploads_status ON uploads(status);
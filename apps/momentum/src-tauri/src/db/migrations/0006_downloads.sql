@@ -0,0 +1,19 @@
+-- The .part file on disk is the source of truth for progress; downloaded is for display
+CREATE TABLE IF NOT EXISTS downloads (
+    id          TEXT PRIMARY KEY,
+    path        TEXT NOT NULL,
+    destination TEXT NOT NULL,
+    status      TEXT NOT NULL DEFAULT 'queued',
+    total       INTEGER,
+    downloaded  INTEGER NOT NULL DEFAULT 0,
+    sha256      TEXT,
+    etag        TEXT,
+    verified    INTEGER NOT NULL DEFAULT 0,
+    -- Set when the caller asked to replace a file already at the destination
+    overwrite   INTEGER NOT NULL DEFAULT 0,
+    last_error  TEXT,
+    created_at  INTEGER NOT NULL,
+    updated_at  INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_downloads_status ON downloads(status, created_at);
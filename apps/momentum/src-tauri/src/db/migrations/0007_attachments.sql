@@ -0,0 +1,35 @@
+-- File contents, stored once under attachments/ by SHA-256 however many times they are attached
+CREATE TABLE IF NOT EXISTS attachment_blobs (
+    hash       TEXT PRIMARY KEY,
+    size       INTEGER NOT NULL,
+    ref_count  INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS attachments (
+    id           TEXT PRIMARY KEY,
+    project_id   TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    hash         TEXT NOT NULL REFERENCES attachment_blobs(hash),
+    file_name    TEXT NOT NULL,
+    content_type TEXT,
+    created_at   INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_attachments_project ON attachments(project_id, created_at);
+
+-- Blobs whose count reaches zero are removed by the attachments.gc job
+CREATE TRIGGER IF NOT EXISTS attachments_ref_insert AFTER INSERT ON attachments
+BEGIN
+    UPDATE attachment_blobs SET ref_count = ref_count + 1 WHERE hash = NEW.hash;
+END;
+
+CREATE TRIGGER IF NOT EXISTS attachments_ref_delete AFTER DELETE ON attachments
+BEGIN
+    UPDATE attachment_blobs SET ref_count = ref_count - 1 WHERE hash = OLD.hash;
+END;
+
+CREATE TRIGGER IF NOT EXISTS attachments_ref_update AFTER UPDATE OF hash ON attachments
+BEGIN
+    UPDATE attachment_blobs SET ref_count = ref_count - 1 WHERE hash = OLD.hash;
+    UPDATE attachment_blobs SET ref_count = ref_count + 1 WHERE hash = NEW.hash;
+END;
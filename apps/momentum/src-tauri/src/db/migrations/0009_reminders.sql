@@ -0,0 +1,20 @@
+-- The user's wall-clock time is the source of truth; fire_at is derived from it and the zone,
+-- and recomputed when zone rules or the device zone change
+CREATE TABLE IF NOT EXISTS reminders (
+    id            TEXT PRIMARY KEY,
+    project_id    TEXT REFERENCES projects(id) ON DELETE CASCADE,
+    kind          TEXT NOT NULL,
+    title         TEXT NOT NULL,
+    body          TEXT NOT NULL DEFAULT '',
+    local_time    TEXT NOT NULL,
+    -- NULL follows the device's zone
+    time_zone     TEXT,
+    fire_at       INTEGER NOT NULL,
+    snoozed_until INTEGER,
+    fired_at      INTEGER,
+    created_at    INTEGER NOT NULL,
+    updated_at    INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_reminders_pending ON reminders(fire_at) WHERE fired_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_reminders_project ON reminders(project_id, fire_at);
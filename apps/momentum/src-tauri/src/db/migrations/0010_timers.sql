@@ -0,0 +1,22 @@
+-- Elapsed time is accumulated_ms plus, while running, now minus resumed_at. heartbeat_at is
+-- the last moment the app was known to be open with the timer running, so a restart can tell
+-- how long it was closed
+CREATE TABLE IF NOT EXISTS timers (
+    id             TEXT PRIMARY KEY,
+    project_id     TEXT REFERENCES projects(id) ON DELETE CASCADE,
+    note           TEXT NOT NULL DEFAULT '',
+    -- running, paused, or stopped
+    state          TEXT NOT NULL,
+    accumulated_ms INTEGER NOT NULL DEFAULT 0,
+    resumed_at     INTEGER,
+    heartbeat_at   INTEGER,
+    -- Running time that passed while the app was not open
+    offline_ms     INTEGER NOT NULL DEFAULT 0,
+    started_at     INTEGER NOT NULL,
+    stopped_at     INTEGER,
+    created_at     INTEGER NOT NULL,
+    updated_at     INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_timers_active ON timers(state) WHERE state != 'stopped';
+CREATE INDEX IF NOT EXISTS idx_timers_project ON timers(project_id, started_at);
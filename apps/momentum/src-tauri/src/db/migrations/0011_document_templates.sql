@@ -0,0 +1,56 @@
+-- User-editable proposal and cover-letter templates, rendered against a project's data
+CREATE TABLE IF NOT EXISTS document_templates (
+    id         TEXT PRIMARY KEY,
+    name       TEXT NOT NULL,
+    kind       TEXT NOT NULL,
+    body       TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_document_templates_kind ON document_templates(kind, name);
+
+-- Starting points the user can edit or delete
+INSERT OR IGNORE INTO document_templates (id, name, kind, body, created_at, updated_at)
+VALUES
+    ('builtin-proposal', 'Proposal', 'proposal',
+     '# Proposal for {{project.name}}
+
+{{date today}}
+
+{{#if data.client}}
+Prepared for {{data.client}}
+{{/if}}
+{{#if project.description}}
+{{project.description}}
+{{/if}}
+
+## Scope and pricing
+
+{{#each items}}
+{{name}}: {{unit quantity unit}} at {{currency unitCost}}, {{currency total}}
+{{else}}
+No line items yet.
+{{/each}}
+
+Total: {{currency subtotal}}
+
+This proposal is valid for 30 days from the date above.
+',
+     CAST(strftime('%s', 'now') AS INTEGER) * 1000,
+     CAST(strftime('%s', 'now') AS INTEGER) * 1000),
+    ('builtin-cover-letter', 'Cover letter', 'coverLetter',
+     '{{date today}}
+
+{{default data.client "To whom it may concern"}},
+
+Thank you for the opportunity to bid on {{project.name}}. Our estimate covers {{count items}} line items for a total of {{currency subtotal}}; the attached proposal has the details.
+
+Please let us know if you have any questions.
+
+Sincerely,
+
+{{data.sender}}
+',
+     CAST(strftime('%s', 'now') AS INTEGER) * 1000,
+     CAST(strftime('%s', 'now') AS INTEGER) * 1000);
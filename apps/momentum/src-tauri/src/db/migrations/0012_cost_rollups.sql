@@ -0,0 +1,35 @@
+-- How each item's cost rolls up: the assembly or section it sits under and what kind of cost
+-- it is. Items without a row are top-level material
+CREATE TABLE IF NOT EXISTS cost_structure (
+    item_id    TEXT PRIMARY KEY REFERENCES items(id) ON DELETE CASCADE,
+    parent_id  TEXT REFERENCES items(id) ON DELETE SET NULL,
+    cost_type  TEXT NOT NULL DEFAULT 'material',
+    updated_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_cost_structure_parent ON cost_structure(parent_id);
+
+-- Markups and taxes applied in order on top of an estimate's subtotal
+CREATE TABLE IF NOT EXISTS cost_adjustments (
+    id         TEXT PRIMARY KEY,
+    project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    name       TEXT NOT NULL,
+    kind       TEXT NOT NULL,
+    rate       REAL NOT NULL,
+    -- A cost type the rate applies to; NULL applies it to the running total
+    applies_to TEXT,
+    sort_order INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_cost_adjustments_project ON cost_adjustments(project_id, sort_order);
+
+-- Totals from the last recalculation, kept to diff against and to skip unchanged subtrees.
+-- node_id is an item id, or '' for the estimate as a whole
+CREATE TABLE IF NOT EXISTS cost_rollups (
+    project_id    TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    node_id       TEXT NOT NULL,
+    parent_id     TEXT,
+    totals        TEXT NOT NULL,
+    calculated_at INTEGER NOT NULL,
+    PRIMARY KEY (project_id, node_id)
+);
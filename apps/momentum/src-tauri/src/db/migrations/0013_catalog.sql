@@ -0,0 +1,47 @@
+-- Local mirror of the server's material and pricing catalog, kept current by deltas
+CREATE TABLE IF NOT EXISTS catalog_items (
+    sku         TEXT PRIMARY KEY COLLATE NOCASE,
+    name        TEXT NOT NULL,
+    description TEXT,
+    unit        TEXT,
+    unit_cost   REAL NOT NULL,
+    currency    TEXT,
+    category    TEXT,
+    supplier    TEXT,
+    -- When the server last changed the entry
+    updated_at  INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_catalog_items_category ON catalog_items(category);
+
+-- Word-prefix search over the text columns; two- and three-letter prefixes are indexed so
+-- the first keystrokes of a search stay fast
+CREATE VIRTUAL TABLE IF NOT EXISTS catalog_search USING fts5(
+    sku, name, description, category, supplier,
+    content = 'catalog_items',
+    content_rowid = 'rowid',
+    prefix = '2 3'
+);
+
+CREATE TRIGGER IF NOT EXISTS catalog_items_search_insert AFTER INSERT ON catalog_items BEGIN
+    INSERT INTO catalog_search (rowid, sku, name, description, category, supplier)
+    VALUES (new.rowid, new.sku, new.name, new.description, new.category, new.supplier);
+END;
+
+CREATE TRIGGER IF NOT EXISTS catalog_items_search_delete AFTER DELETE ON catalog_items BEGIN
+    INSERT INTO catalog_search (catalog_search, rowid, sku, name, description, category, supplier)
+    VALUES ('delete', old.rowid, old.sku, old.name, old.description, old.category, old.supplier);
+END;
+
+CREATE TRIGGER IF NOT EXISTS catalog_items_search_update AFTER UPDATE ON catalog_items BEGIN
+    INSERT INTO catalog_search (catalog_search, rowid, sku, name, description, category, supplier)
+    VALUES ('delete', old.rowid, old.sku, old.name, old.description, old.category, old.supplier);
+    INSERT INTO catalog_search (rowid, sku, name, description, category, supplier)
+    VALUES (new.rowid, new.sku, new.name, new.description, new.category, new.supplier);
+END;
+
+-- Delta cursor and last sync time
+CREATE TABLE IF NOT EXISTS catalog_state (
+    key   TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
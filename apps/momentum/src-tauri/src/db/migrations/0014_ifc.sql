@@ -0,0 +1,32 @@
+-- Quantities taken off IFC building models, grouped by element type and level
+CREATE TABLE IF NOT EXISTS ifc_imports (
+    id            TEXT PRIMARY KEY,
+    project_id    TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    file_name     TEXT NOT NULL,
+    -- IFC2X3, IFC4, ...
+    ifc_schema    TEXT,
+    element_count INTEGER NOT NULL,
+    imported_at   INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_ifc_imports_project ON ifc_imports(project_id, imported_at);
+
+-- level is '' for elements not on a storey
+CREATE TABLE IF NOT EXISTS ifc_element_groups (
+    import_id     TEXT NOT NULL REFERENCES ifc_imports(id) ON DELETE CASCADE,
+    element_type  TEXT NOT NULL,
+    level         TEXT NOT NULL,
+    element_count INTEGER NOT NULL,
+    PRIMARY KEY (import_id, element_type, level)
+);
+
+-- Values are in SI units: meters, square meters, cubic meters, kilograms
+CREATE TABLE IF NOT EXISTS ifc_quantities (
+    import_id    TEXT NOT NULL REFERENCES ifc_imports(id) ON DELETE CASCADE,
+    element_type TEXT NOT NULL,
+    level        TEXT NOT NULL,
+    name         TEXT NOT NULL,
+    kind         TEXT NOT NULL,
+    value        REAL NOT NULL,
+    PRIMARY KEY (import_id, element_type, level, name, kind)
+);
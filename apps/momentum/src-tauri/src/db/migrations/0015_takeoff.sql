@@ -0,0 +1,27 @@
+-- Plan scale per page: how many pixels of the page image make one unit
+CREATE TABLE IF NOT EXISTS takeoff_calibrations (
+    attachment_id   TEXT NOT NULL REFERENCES attachments(id) ON DELETE CASCADE,
+    page            INTEGER NOT NULL,
+    pixels_per_unit REAL NOT NULL,
+    unit            TEXT NOT NULL,
+    calibrated_at   INTEGER NOT NULL,
+    PRIMARY KEY (attachment_id, page)
+);
+
+-- Points are kept in page pixels, so recalibrating a page corrects everything measured on it
+CREATE TABLE IF NOT EXISTS takeoff_measurements (
+    id            TEXT PRIMARY KEY,
+    attachment_id TEXT NOT NULL REFERENCES attachments(id) ON DELETE CASCADE,
+    page          INTEGER NOT NULL,
+    -- length, area, or count
+    kind          TEXT NOT NULL,
+    label         TEXT,
+    -- JSON array of [x, y]
+    points        TEXT NOT NULL,
+    item_id       TEXT REFERENCES items(id) ON DELETE SET NULL,
+    created_at    INTEGER NOT NULL,
+    updated_at    INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_takeoff_measurements_page
+    ON takeoff_measurements(attachment_id, page, created_at);
@@ -0,0 +1,23 @@
+-- Point-in-time copies of an estimate, kept as deflated JSON
+CREATE TABLE IF NOT EXISTS estimate_versions (
+    id           TEXT PRIMARY KEY,
+    project_id   TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    label        TEXT,
+    -- manual, or the operation it was taken ahead of, such as import
+    reason       TEXT NOT NULL,
+    item_count   INTEGER NOT NULL,
+    total        REAL NOT NULL,
+    -- SHA-256 of the uncompressed snapshot, so an unchanged estimate is not stored twice
+    content_hash BLOB NOT NULL,
+    snapshot     BLOB NOT NULL,
+    created_by   TEXT,
+    created_at   INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_estimate_versions_project
+    ON estimate_versions(project_id, created_at);
+
+CREATE TRIGGER IF NOT EXISTS estimate_versions_immutable BEFORE UPDATE ON estimate_versions
+BEGIN
+    SELECT RAISE(ABORT, 'estimate versions cannot be changed');
+END;
@@ -0,0 +1,9 @@
+-- Captured photos waiting to reach the server; a row goes once the server has the file
+CREATE TABLE IF NOT EXISTS photo_uploads (
+    attachment_id TEXT PRIMARY KEY REFERENCES attachments(id) ON DELETE CASCADE,
+    project_id    TEXT NOT NULL,
+    -- Plaintext copy the upload reads, since the stored blob may be encrypted
+    path          TEXT NOT NULL,
+    upload_id     TEXT,
+    created_at    INTEGER NOT NULL
+);
@@ -0,0 +1,31 @@
+-- Where a field entry was made. entity is attachment, timer, or dailyLog; a daily log's id
+-- is its project id and date joined by a slash
+CREATE TABLE IF NOT EXISTS location_tags (
+    entity      TEXT NOT NULL,
+    entity_id   TEXT NOT NULL,
+    latitude    REAL NOT NULL,
+    longitude   REAL NOT NULL,
+    -- Radius in metres the fix is good to
+    accuracy    REAL,
+    address     TEXT,
+    captured_at INTEGER NOT NULL,
+    PRIMARY KEY (entity, entity_id)
+);
+
+CREATE TRIGGER IF NOT EXISTS location_tags_attachment_delete AFTER DELETE ON attachments
+BEGIN
+    DELETE FROM location_tags WHERE entity = 'attachment' AND entity_id = OLD.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS location_tags_timer_delete AFTER DELETE ON timers
+BEGIN
+    DELETE FROM location_tags WHERE entity = 'timer' AND entity_id = OLD.id;
+END;
+
+-- Reverse-geocoded addresses keyed by coordinates rounded to about 10 m, so places already
+-- looked up have an address offline
+CREATE TABLE IF NOT EXISTS geocode_cache (
+    cell       TEXT PRIMARY KEY,
+    address    TEXT NOT NULL,
+    fetched_at INTEGER NOT NULL
+);
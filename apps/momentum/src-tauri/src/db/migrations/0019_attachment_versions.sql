@@ -0,0 +1,6 @@
+-- A later version of an attachment, such as a copy saved back from an external editor,
+-- points at the attachment it replaced; originals are version 1
+ALTER TABLE attachments ADD COLUMN previous_id TEXT REFERENCES attachments(id) ON DELETE SET NULL;
+ALTER TABLE attachments ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+CREATE INDEX IF NOT EXISTS idx_attachments_previous ON attachments(previous_id);
@@ -0,0 +1,14 @@
+-- Undo history per project. changes is a JSON array of rows as they were before and after
+CREATE TABLE IF NOT EXISTS undo_journal (
+    seq        INTEGER PRIMARY KEY,
+    project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    command    TEXT NOT NULL,
+    label      TEXT NOT NULL,
+    changes    TEXT NOT NULL,
+    row_count  INTEGER NOT NULL,
+    -- 1 once undone, until redone or cleared by a new change
+    undone     INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_undo_journal_project ON undo_journal(project_id, undone, seq);
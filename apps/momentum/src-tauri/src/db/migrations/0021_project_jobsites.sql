@@ -0,0 +1,5 @@
+-- A project's jobsite is its location tag with entity 'project'
+CREATE TRIGGER IF NOT EXISTS location_tags_project_delete AFTER DELETE ON projects
+BEGIN
+    DELETE FROM location_tags WHERE entity = 'project' AND entity_id = OLD.id;
+END;
@@ -0,0 +1,11 @@
+-- The weather at a project's jobsite, one row per day in the machine's time zone.
+-- conditions is JSON; complete is set when the day was already over at fetched_at, so the
+-- row is final
+CREATE TABLE IF NOT EXISTS weather_days (
+    project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    date       TEXT NOT NULL,
+    conditions TEXT NOT NULL,
+    complete   INTEGER NOT NULL DEFAULT 0,
+    fetched_at INTEGER NOT NULL,
+    PRIMARY KEY (project_id, date)
+);
@@ -0,0 +1,232 @@
+//! Ordered, versioned schema migrations applied at startup.
+
+use std::path::Path;
+
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use tauri::State;
+
+use super::{now_ms, Db};
+use crate::audit;
+use crate::backup::local::{self, LocalBackupReason};
+use crate::error::{AppError, AppResult};
+
+struct Migration {
+    version: i64,
+    name: &'static str,
+    sql: &'static str,
+}
+
+// Append only: applied versions are recorded, so editing an existing entry never reruns it.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        sql: include_str!("0001_initial.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "jobs",
+        sql: include_str!("0002_jobs.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "sync",
+        sql: include_str!("0003_sync.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "outbox",
+        sql: include_str!("0004_outbox.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "uploads",
+        sql: include_str!("0005_uploads.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "downloads",
+        sql: include_str!("0006_downloads.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "attachments",
+        sql: include_str!("0007_attachments.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "audit",
+        sql: include_str!("0008_audit.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "reminders",
+        sql: include_str!("0009_reminders.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "timers",
+        sql: include_str!("0010_timers.sql"),
+    },
+    Migration {
+        version: 11,
+        name: "document_templates",
+        sql: include_str!("0011_document_templates.sql"),
+    },
+    Migration {
+        version: 12,
+        name: "cost_rollups",
+        sql: include_str!("0012_cost_rollups.sql"),
+    },
+    Migration {
+        version: 13,
+        name: "catalog",
+        sql: include_str!("0013_catalog.sql"),
+    },
+    Migration {
+        version: 14,
+        name: "ifc",
+        sql: include_str!("0014_ifc.sql"),
+    },
+    Migration {
+        version: 15,
+        name: "takeoff",
+        sql: include_str!("0015_takeoff.sql"),
+    },
+    Migration {
+        version: 16,
+        name: "versions",
+        sql: include_str!("0016_versions.sql"),
+    },
+    Migration {
+        version: 17,
+        name: "photo_uploads",
+        sql: include_str!("0017_photo_uploads.sql"),
+    },
+    Migration {
+        version: 18,
+        name: "locations",
+        sql: include_str!("0018_locations.sql"),
+    },
+    Migration {
+        version: 19,
+        name: "attachment_versions",
+        sql: include_str!("0019_attachment_versions.sql"),
+    },
+    Migration {
+        version: 20,
+        name: "undo_journal",
+        sql: include_str!("0020_undo_journal.sql"),
+    },
+    Migration {
+        version: 21,
+        name: "project_jobsites",
+        sql: include_str!("0021_project_jobsites.sql"),
+    },
+    Migration {
+        version: 22,
+        name: "weather",
+        sql: include_str!("0022_weather.sql"),
+    },
+];
+
+/// Schema version reported to the frontend.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaVersion {
+    pub current: i64,
+    pub latest: i64,
+}
+
+/// Apply every pending migration, snapshotting the database first so a failed
+/// upgrade can be recovered by hand.
+pub fn run(conn: &mut Connection, db_path: &Path) -> AppResult<()> {
+    // Pooled connections guard the audit log, which migrations create and may alter
+    audit::unguard(conn)?;
+    let applied = apply(conn, db_path);
+    audit::guard(conn)?;
+    applied
+}
+
+fn apply(conn: &mut Connection, db_path: &Path) -> AppResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version    INTEGER PRIMARY KEY,
+            name       TEXT NOT NULL,
+            applied_at INTEGER NOT NULL
+        );",
+    )?;
+
+    let current = current_version(conn)?;
+    let pending: Vec<&Migration> = MIGRATIONS.iter().filter(|m| m.version > current).collect();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let backup = if current > 0 {
+        let dir = local::dir(db_path.parent().unwrap_or(Path::new(".")));
+        Some(local::snapshot(
+            conn,
+            &dir,
+            LocalBackupReason::PreMigration,
+            current,
+        )?)
+    } else {
+        None
+    };
+
+    for migration in pending {
+        tracing::info!(
+            version = migration.version,
+            name = migration.name,
+            "applying migration"
+        );
+        let tx = conn.transaction()?;
+        let applied = tx.execute_batch(migration.sql).and_then(|_| {
+            tx.execute(
+                "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, ?2, ?3)",
+                params![migration.version, migration.name, now_ms()],
+            )
+        });
+        if let Err(err) = applied {
+            let hint = backup
+                .as_ref()
+                .map(|p| format!("; pre-migration backup kept at {}", p.display()))
+                .unwrap_or_default();
+            return Err(AppError::Migration {
+                version: migration.version,
+                name: migration.name,
+                message: format!("{err}{hint}"),
+            });
+        }
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+fn current_version(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+        [],
+        |row| row.get(0),
+    )
+}
+
+fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or_default()
+}
+
+/// Applied and latest known schema versions.
+pub fn schema_version(conn: &Connection) -> AppResult<SchemaVersion> {
+    Ok(SchemaVersion {
+        current: current_version(conn)?,
+        latest: latest_version(),
+    })
+}
+
+#[tauri::command]
+pub async fn get_schema_version(db: State<'_, Db>) -> AppResult<SchemaVersion> {
+    db.run(|conn| schema_version(conn)).await
+}
@@ -0,0 +1,548 @@
+//! SQLite persistence layer shared by every command that touches relational project data.
+
+pub mod migrations;
+pub mod paging;
+pub mod projects;
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{Connection, Row};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tauri::State;
+use tokio::sync::watch;
+
+use crate::audit::{self, Change};
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+
+const DB_FILE_NAME: &str = "momentum.db";
+// How long re-encryption, restores, and moves wait for in-flight work to hand its connection back
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pooled SQLite handle registered in app state.
+///
+/// Cloning is cheap; all clones share the same underlying pool. An encrypted database
+/// starts locked, with no pool, and every `run` fails with `DATABASE_LOCKED` until
+/// [`Db::unlock`] supplies the key.
+#[derive(Clone)]
+pub struct Db {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    path: PathBuf,
+    pool: RwLock<Option<Pool<SqliteConnectionManager>>>,
+    unlocked: watch::Sender<bool>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// SQLCipher takes a raw key as a blob literal, which skips its own passphrase KDF
+fn key_pragma(key: &[u8; 32]) -> String {
+    format!("PRAGMA key = \"x'{}'\";", hex(key))
+}
+
+fn build_pool(
+    path: &Path,
+    key: Option<&[u8; 32]>,
+) -> Result<Pool<SqliteConnectionManager>, r2d2::Error> {
+    // The key must be the first statement on every connection
+    let key = key.map(key_pragma).unwrap_or_default();
+    let manager = SqliteConnectionManager::file(path).with_init(move |conn| {
+        conn.execute_batch(&format!(
+            "{key}
+             PRAGMA journal_mode = WAL;
+             PRAGMA foreign_keys = ON;
+             PRAGMA busy_timeout = 5000;"
+        ))?;
+        audit::guard(conn)
+    });
+    Pool::builder().max_size(8).build(manager)
+}
+
+// A wrong key only shows up on first read; checked on a lone connection because a pool
+// would retry failing connections until its timeout
+fn readable(path: &Path, key: Option<&[u8; 32]>) -> bool {
+    Connection::open(path)
+        .and_then(|conn| {
+            if let Some(key) = key {
+                conn.execute_batch(&key_pragma(key))?;
+            }
+            conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(()))
+        })
+        .is_ok()
+}
+
+// Wait for work that grabbed the pool before the write lock to hand its connections back,
+// then checkpoint through the one left
+fn drain(
+    pool: &Pool<SqliteConnectionManager>,
+) -> AppResult<PooledConnection<SqliteConnectionManager>> {
+    let conn = pool.get()?;
+    let started = Instant::now();
+    while pool.state().idle_connections + 1 < pool.state().connections {
+        if started.elapsed() > DRAIN_TIMEOUT {
+            return Err(AppError::Internal("the database stayed busy".into()));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);")?;
+    Ok(conn)
+}
+
+impl Db {
+    fn new(path: &Path, pool: Option<Pool<SqliteConnectionManager>>) -> Self {
+        let (unlocked, _) = watch::channel(pool.is_some());
+        Self {
+            inner: Arc::new(Inner {
+                path: path.to_path_buf(),
+                pool: RwLock::new(pool),
+                unlocked,
+            }),
+        }
+    }
+
+    /// Open (or create) the plaintext database at `path` and apply connection pragmas.
+    pub fn open(path: &Path) -> Result<Self, r2d2::Error> {
+        Ok(Self::new(path, Some(build_pool(path, None)?)))
+    }
+
+    /// Handle for an encrypted database that stays unusable until unlocked.
+    pub fn locked(path: &Path) -> Self {
+        Self::new(path, None)
+    }
+
+    /// Resolve once the database is usable; immediately for plaintext databases.
+    pub async fn wait_unlocked(&self) {
+        let mut unlocked = self.inner.unlocked.subscribe();
+        let _ = unlocked.wait_for(|unlocked| *unlocked).await;
+    }
+
+    fn pool(&self) -> AppResult<Pool<SqliteConnectionManager>> {
+        self.inner
+            .pool
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+            .ok_or(AppError::DatabaseLocked)
+    }
+
+    /// Open an encrypted database with `key` and bring its schema up to date. Blocking.
+    ///
+    /// A database still in plaintext, left by an encryption that was interrupted, is
+    /// encrypted first.
+    pub fn unlock(&self, key: &[u8; 32]) -> AppResult<()> {
+        let path = &self.inner.path;
+        if !readable(path, Some(key)) {
+            if !readable(path, None) {
+                return Err(AppError::Encryption(
+                    "the database cannot be decrypted with this key".into(),
+                ));
+            }
+            tracing::warn!("finishing an interrupted database encryption");
+            *self.inner.pool.write().unwrap_or_else(|e| e.into_inner()) =
+                Some(build_pool(path, None)?);
+            self.encrypt(key)?;
+        }
+        let pool = build_pool(path, Some(key))?;
+        migrations::run(&mut *pool.get()?, path)?;
+        *self.inner.pool.write().unwrap_or_else(|e| e.into_inner()) = Some(pool);
+        self.inner.unlocked.send_replace(true);
+        Ok(())
+    }
+
+    /// Close an encrypted database again so its key leaves memory; `run` fails until unlocked.
+    pub fn lock(&self) {
+        self.inner.unlocked.send_replace(false);
+        // In-flight work keeps its pool clone, and so its connections, until it finishes
+        drop(
+            self.inner
+                .pool
+                .write()
+                .unwrap_or_else(|e| e.into_inner())
+                .take(),
+        );
+    }
+
+    /// Rewrite the open plaintext database encrypted with `key`, then reopen it. Blocking.
+    ///
+    /// Commands wait rather than fail while the copy is made.
+    pub(crate) fn encrypt(&self, key: &[u8; 32]) -> AppResult<()> {
+        let path = &self.inner.path;
+        let mut slot = self.inner.pool.write().unwrap_or_else(|e| e.into_inner());
+        let pool = slot.take().ok_or(AppError::DatabaseLocked)?;
+        let tmp = path.with_extension("db.encrypting");
+        let result = (|| -> AppResult<()> {
+            let conn = drain(&pool)?;
+            let _ = std::fs::remove_file(&tmp);
+            conn.execute(
+                "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+                [
+                    tmp.to_string_lossy().into_owned(),
+                    format!("x'{}'", hex(key)),
+                ],
+            )?;
+            conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))?;
+            conn.execute_batch("DETACH DATABASE encrypted;")?;
+            Ok(())
+        })();
+        if let Err(err) = result {
+            let _ = std::fs::remove_file(&tmp);
+            *slot = Some(pool);
+            return Err(err);
+        }
+        drop(pool);
+        std::fs::rename(&tmp, path)?;
+        for suffix in ["-wal", "-shm"] {
+            let mut sidecar = path.as_os_str().to_os_string();
+            sidecar.push(suffix);
+            let _ = std::fs::remove_file(sidecar);
+        }
+        *slot = Some(build_pool(path, Some(key))?);
+        Ok(())
+    }
+
+    /// Swap the database file for `staged`, keeping the current one at `keep_as`, then
+    /// reopen and migrate it. Blocking.
+    ///
+    /// `staged` must already be encrypted under `key`, or plaintext when there is none. When
+    /// the new file cannot be opened the previous one is put back.
+    pub(crate) fn replace(
+        &self,
+        staged: &Path,
+        keep_as: &Path,
+        key: Option<&[u8; 32]>,
+    ) -> AppResult<()> {
+        let path = &self.inner.path;
+        let mut slot = self.inner.pool.write().unwrap_or_else(|e| e.into_inner());
+        let pool = slot.take().ok_or(AppError::DatabaseLocked)?;
+        if let Err(err) = drain(&pool) {
+            *slot = Some(pool);
+            return Err(err);
+        }
+        drop(pool);
+        if let Some(dir) = keep_as.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::rename(path, keep_as)?;
+        for suffix in ["-wal", "-shm"] {
+            let mut sidecar = path.as_os_str().to_os_string();
+            sidecar.push(suffix);
+            let _ = std::fs::remove_file(sidecar);
+        }
+        let opened = std::fs::rename(staged, path)
+            .map_err(AppError::from)
+            .and_then(|()| {
+                let pool = build_pool(path, key)?;
+                migrations::run(&mut *pool.get()?, path)?;
+                Ok(pool)
+            });
+        match opened {
+            Ok(pool) => {
+                *slot = Some(pool);
+                Ok(())
+            }
+            Err(err) => {
+                tracing::error!(%err, "restored database would not open; putting the old one back");
+                let _ = std::fs::remove_file(path);
+                std::fs::rename(keep_as, path)?;
+                *slot = Some(build_pool(path, key)?);
+                Err(err)
+            }
+        }
+    }
+
+    /// Close the database with its log checkpointed and run `f` on the quiet file. Blocking.
+    ///
+    /// Commands wait while `f` runs. When it fails the database reopens with `key`; when it
+    /// succeeds the database stays closed for the rest of the process, as for a move the
+    /// app relaunches after, and `run` fails with `DATABASE_LOCKED`.
+    pub(crate) fn close_with<T>(
+        &self,
+        key: Option<&[u8; 32]>,
+        f: impl FnOnce(&Path) -> AppResult<T>,
+    ) -> AppResult<T> {
+        let path = &self.inner.path;
+        let mut slot = self.inner.pool.write().unwrap_or_else(|e| e.into_inner());
+        let pool = slot.take().ok_or(AppError::DatabaseLocked)?;
+        if let Err(err) = drain(&pool) {
+            *slot = Some(pool);
+            return Err(err);
+        }
+        drop(pool);
+        match f(path) {
+            Ok(value) => {
+                self.inner.unlocked.send_replace(false);
+                Ok(value)
+            }
+            Err(err) => {
+                *slot = Some(build_pool(path, key)?);
+                Err(err)
+            }
+        }
+    }
+
+    /// Run blocking SQLite work off the async runtime so commands never stall IPC.
+    pub async fn run<T, F>(&self, f: F) -> AppResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> AppResult<T> + Send + 'static,
+    {
+        let db = self.clone();
+        tauri::async_runtime::spawn_blocking(move || f(&mut *db.pool()?.get()?)).await?
+    }
+
+    /// Move everything in the write-ahead log into the database file, e.g. before exit.
+    ///
+    /// A locked database has nothing to write and succeeds.
+    pub async fn checkpoint(&self) -> AppResult<()> {
+        match self
+            .run(|conn| Ok(conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);")?))
+            .await
+        {
+            Err(AppError::DatabaseLocked) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// File the database lives in.
+    pub fn path(&self) -> &Path {
+        &self.inner.path
+    }
+}
+
+/// Open the app database under `data_dir` and bring its schema up to date.
+///
+/// An `encrypted` database is left locked; migrations run when it is unlocked.
+pub fn init(data_dir: &Path, encrypted: bool) -> AppResult<Db> {
+    let path = data_dir.join(DB_FILE_NAME);
+    if encrypted {
+        return Ok(Db::locked(&path));
+    }
+    let db = Db::open(&path)?;
+    migrations::run(&mut *db.pool()?.get()?, &path)?;
+    Ok(db)
+}
+
+/// Current time as Unix milliseconds, the timestamp format used by every table.
+pub fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// Outcome of a statement executed through `db_execute`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecuteResult {
+    pub rows_affected: usize,
+    pub last_insert_rowid: i64,
+}
+
+/// Run a read query and return each row as a column-name keyed object.
+///
+/// Statements that write are refused; they go through `db_execute`, which audits them.
+#[tauri::command]
+pub async fn db_query(
+    db: State<'_, Db>,
+    sql: String,
+    params: Option<Vec<Value>>,
+) -> AppResult<Vec<Map<String, Value>>> {
+    let sql = non_empty_sql(sql)?;
+    let params = to_sql_params(params.unwrap_or_default());
+    db.run(move |conn| read_rows(conn, &sql, params)).await
+}
+
+// `readonly` covers what the statement itself does; `query_only` covers the rest
+fn read_rows(
+    conn: &Connection,
+    sql: &str,
+    params: Vec<SqlValue>,
+) -> AppResult<Vec<Map<String, Value>>> {
+    let mut stmt = conn.prepare(sql)?;
+    if !stmt.readonly() {
+        return Err(AppError::InvalidInput(
+            "db_query only runs statements that read data".into(),
+        ));
+    }
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+    conn.pragma_update(None, "query_only", true)?;
+    let rows = stmt
+        .query_map(rusqlite::params_from_iter(params), |row| {
+            row_to_json(row, &columns)
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>());
+    drop(stmt);
+    conn.pragma_update(None, "query_only", false)?;
+    Ok(rows?)
+}
+
+/// Run a single mutating statement.
+///
+/// Raw SQL has no entity to diff, so the audit log records the statement and its effect.
+#[tauri::command]
+pub async fn db_execute(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    sql: String,
+    params: Option<Vec<Value>>,
+) -> AppResult<ExecuteResult> {
+    // The connection's authorizer keeps the statement away from the audit log
+    let sql = non_empty_sql(sql)?;
+    let actor = audit::actor(&state);
+    let logged = params.clone().unwrap_or_default();
+    let params = to_sql_params(params.unwrap_or_default());
+    db.run(move |conn| {
+        let rows_affected = conn.execute(&sql, rusqlite::params_from_iter(params))?;
+        let result = ExecuteResult {
+            rows_affected,
+            last_insert_rowid: conn.last_insert_rowid(),
+        };
+        audit::record_now(
+            conn,
+            &actor,
+            Change::new("db_execute", "sql", "").after(&serde_json::json!({
+                "sql": sql,
+                "params": logged,
+                "rowsAffected": rows_affected,
+            }))?,
+        )?;
+        Ok(result)
+    })
+    .await
+}
+
+fn non_empty_sql(sql: String) -> AppResult<String> {
+    if sql.trim().is_empty() {
+        return Err(AppError::InvalidInput("SQL statement is empty".into()));
+    }
+    Ok(sql)
+}
+
+/// Bind JSON values as SQLite parameters the way `db_query` does.
+pub(crate) fn to_sql_params(params: Vec<Value>) -> Vec<SqlValue> {
+    params.into_iter().map(json_to_sql).collect()
+}
+
+fn json_to_sql(value: Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => SqlValue::Text(s),
+        // Nested structures are stored as JSON text so SQLite's json_* functions can read them
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+/// A result row as a column-name keyed object, blobs as byte arrays.
+pub(crate) fn row_to_json(
+    row: &Row<'_>,
+    columns: &[String],
+) -> rusqlite::Result<Map<String, Value>> {
+    let mut object = Map::with_capacity(columns.len());
+    for (index, name) in columns.iter().enumerate() {
+        let value = match row.get_ref(index)? {
+            ValueRef::Null => Value::Null,
+            ValueRef::Integer(i) => Value::from(i),
+            ValueRef::Real(f) => Value::from(f),
+            ValueRef::Text(t) => Value::from(String::from_utf8_lossy(t).into_owned()),
+            ValueRef::Blob(b) => Value::from(b.to_vec()),
+        };
+        object.insert(name.clone(), value);
+    }
+    Ok(object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn materials() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE materials (id INTEGER PRIMARY KEY, name TEXT NOT NULL);
+             INSERT INTO materials (name) VALUES ('rebar'), ('formwork');",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT count(*) FROM materials", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn db_query_reads_rows_by_column_name() {
+        let conn = materials();
+        let rows = read_rows(
+            &conn,
+            "SELECT name FROM materials WHERE id = ?1",
+            to_sql_params(vec![Value::from(2)]),
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["name"], "formwork");
+    }
+
+    #[test]
+    fn db_query_refuses_statements_that_write() {
+        let conn = materials();
+        for sql in [
+            "DELETE FROM materials",
+            "UPDATE materials SET name = 'steel'",
+            "INSERT INTO materials (name) VALUES ('steel')",
+            "DROP TABLE materials",
+            "WITH gone AS (SELECT 1) DELETE FROM materials",
+            "PRAGMA user_version = 7",
+        ] {
+            let refused = read_rows(&conn, sql, Vec::new());
+            assert!(
+                matches!(refused, Err(AppError::InvalidInput(_))),
+                "{sql}: {refused:?}"
+            );
+        }
+        assert_eq!(count(&conn), 2);
+        // The connection goes back to the pool writable
+        conn.execute("DELETE FROM materials WHERE id = 1", [])
+            .unwrap();
+        assert_eq!(count(&conn), 1);
+    }
+
+    #[test]
+    fn db_query_cannot_tamper_with_the_audit_log() {
+        let conn = materials();
+        conn.execute_batch(include_str!("migrations/0008_audit.sql"))
+            .unwrap();
+        audit::guard(&conn).unwrap();
+        for sql in [
+            "DROP TRIGGER audit_log_no_update",
+            "DELETE FROM audit_log",
+            "ALTER TABLE audit_log RENAME TO old_log",
+        ] {
+            assert!(read_rows(&conn, sql, Vec::new()).is_err(), "{sql}");
+        }
+        let triggers: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM sqlite_master
+                 WHERE type = 'trigger' AND tbl_name = 'audit_log'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(triggers, 2);
+    }
+}
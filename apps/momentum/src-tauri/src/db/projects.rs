@@ -0,0 +1,379 @@
+//! Typed project and line-item commands backed by the SQLite layer.
+
+use std::collections::HashMap;
+
+use rusqlite::{params, OptionalExtension, Row, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use super::{now_ms, Db};
+use crate::audit::{self, Change};
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+use crate::undo::{Journal, Table};
+use crate::versions::{self, Reason};
+
+/// A tracked project.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Project {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Project {
+    pub(crate) fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            description: row.get("description")?,
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+}
+
+/// A line item belonging to a project.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Item {
+    pub id: String,
+    pub project_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub quantity: f64,
+    pub unit: Option<String>,
+    pub unit_cost: f64,
+    pub sort_order: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Item {
+    pub(crate) fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            project_id: row.get("project_id")?,
+            name: row.get("name")?,
+            description: row.get("description")?,
+            quantity: row.get("quantity")?,
+            unit: row.get("unit")?,
+            unit_cost: row.get("unit_cost")?,
+            sort_order: row.get("sort_order")?,
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+}
+
+/// Fields accepted when creating or updating a project.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectInput {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/// Fields accepted when creating or updating a line item.
+///
+/// A missing `id` creates a new item; a present one updates it in place.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemInput {
+    pub id: Option<String>,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub quantity: f64,
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub unit_cost: f64,
+    #[serde(default)]
+    pub sort_order: i64,
+}
+
+#[tauri::command]
+pub async fn list_projects(db: State<'_, Db>) -> AppResult<Vec<Project>> {
+    db.run(|conn| {
+        let mut stmt = conn.prepare("SELECT * FROM projects ORDER BY updated_at DESC")?;
+        let rows = stmt.query_map([], Project::from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_project(db: State<'_, Db>, id: String) -> AppResult<Option<Project>> {
+    db.run(move |conn| {
+        Ok(conn
+            .query_row(
+                "SELECT * FROM projects WHERE id = ?1",
+                [&id],
+                Project::from_row,
+            )
+            .optional()?)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn create_project(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    input: ProjectInput,
+) -> AppResult<Project> {
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let now = now_ms();
+        let project = Project {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: input.name,
+            description: input.description,
+            created_at: now,
+            updated_at: now,
+        };
+        tx.execute(
+            "INSERT INTO projects (id, name, description, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                project.id,
+                project.name,
+                project.description,
+                project.created_at,
+                project.updated_at
+            ],
+        )?;
+        audit::record(
+            &tx,
+            &actor,
+            Change::new("create_project", "project", &project.id).after(&project)?,
+        )?;
+        tx.commit()?;
+        Ok(project)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn update_project(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    id: String,
+    input: ProjectInput,
+) -> AppResult<Project> {
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let before = tx
+            .query_row(
+                "SELECT * FROM projects WHERE id = ?1",
+                [&id],
+                Project::from_row,
+            )
+            .optional()?
+            .ok_or_else(|| AppError::not_found("project", &id))?;
+        let mut journal = Journal::new(&id, "update_project", "Edit project");
+        journal.watch(&tx, Table::Projects, &id)?;
+        tx.execute(
+            "UPDATE projects SET name = ?2, description = ?3, updated_at = ?4 WHERE id = ?1",
+            params![id, input.name, input.description, now_ms()],
+        )?;
+        let project = tx.query_row(
+            "SELECT * FROM projects WHERE id = ?1",
+            [&id],
+            Project::from_row,
+        )?;
+        audit::record(
+            &tx,
+            &actor,
+            Change::new("update_project", "project", &id)
+                .before(&before)?
+                .after(&project)?,
+        )?;
+        journal.record(&tx)?;
+        tx.commit()?;
+        Ok(project)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn delete_project(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    id: String,
+) -> AppResult<()> {
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let before = tx
+            .query_row(
+                "SELECT * FROM projects WHERE id = ?1",
+                [&id],
+                Project::from_row,
+            )
+            .optional()?;
+        if let Some(before) = before {
+            let items: i64 = tx.query_row(
+                "SELECT COUNT(*) FROM items WHERE project_id = ?1",
+                [&id],
+                |row| row.get(0),
+            )?;
+            tx.execute("DELETE FROM projects WHERE id = ?1", [&id])?;
+            // Items go with the project by cascade, so the count summarises them
+            audit::record(
+                &tx,
+                &actor,
+                Change::new("delete_project", "project", &id).before(&serde_json::json!({
+                    "project": before,
+                    "itemCount": items,
+                }))?,
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn list_items(db: State<'_, Db>, project_id: String) -> AppResult<Vec<Item>> {
+    db.run(move |conn| {
+        let mut stmt =
+            conn.prepare("SELECT * FROM items WHERE project_id = ?1 ORDER BY sort_order, name")?;
+        let rows = stmt.query_map([&project_id], Item::from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    })
+    .await
+}
+
+/// Insert or update a batch of items in one transaction so bulk edits stay atomic.
+#[tauri::command]
+pub async fn save_items(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    project_id: String,
+    items: Vec<ItemInput>,
+) -> AppResult<Vec<Item>> {
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let now = now_ms();
+        let mut saved = Vec::with_capacity(items.len());
+        let mut journal = Journal::new(&project_id, "save_items", "Edit items");
+        for input in items {
+            let id = input.id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+            journal.watch(&tx, Table::Items, &id)?;
+            let before = tx
+                .query_row("SELECT * FROM items WHERE id = ?1", [&id], Item::from_row)
+                .optional()?;
+            tx.execute(
+                "INSERT INTO items (id, project_id, name, description, quantity, unit, unit_cost,
+                                    sort_order, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9)
+                 ON CONFLICT(id) DO UPDATE SET
+                    name = excluded.name,
+                    description = excluded.description,
+                    quantity = excluded.quantity,
+                    unit = excluded.unit,
+                    unit_cost = excluded.unit_cost,
+                    sort_order = excluded.sort_order,
+                    updated_at = excluded.updated_at",
+                params![
+                    id,
+                    project_id,
+                    input.name,
+                    input.description,
+                    input.quantity,
+                    input.unit,
+                    input.unit_cost,
+                    input.sort_order,
+                    now
+                ],
+            )?;
+            let item = tx.query_row("SELECT * FROM items WHERE id = ?1", [&id], Item::from_row)?;
+            let change = match &before {
+                Some(before) => Change::new("save_items", "item", &id).before(before)?,
+                None => Change::new("save_items", "item", &id),
+            };
+            audit::record(&tx, &actor, change.after(&item)?)?;
+            saved.push(item);
+        }
+        tx.execute(
+            "UPDATE projects SET updated_at = ?2 WHERE id = ?1",
+            params![project_id, now],
+        )?;
+        journal.record(&tx)?;
+        tx.commit()?;
+        Ok(saved)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn delete_items(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    ids: Vec<String>,
+) -> AppResult<usize> {
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let mut projects: Vec<String> = Vec::new();
+        for id in &ids {
+            let project = tx
+                .query_row("SELECT project_id FROM items WHERE id = ?1", [id], |row| {
+                    row.get(0)
+                })
+                .optional()?;
+            if let Some(project) = project.filter(|p| !projects.contains(p)) {
+                projects.push(project);
+            }
+        }
+        let mut journals: HashMap<String, Journal> = HashMap::new();
+        for project in &projects {
+            versions::capture(&tx, project, Reason::DeleteItems, None, Some(&actor))?;
+            journals.insert(
+                project.clone(),
+                Journal::new(project, "delete_items", "Delete items"),
+            );
+        }
+        let mut deleted = 0;
+        for id in &ids {
+            let before = tx
+                .query_row("SELECT * FROM items WHERE id = ?1", [id], Item::from_row)
+                .optional()?;
+            let Some(before) = before else {
+                continue;
+            };
+            if let Some(journal) = journals.get_mut(&before.project_id) {
+                // Its place in the rollup goes by cascade, and children lose their parent
+                let children: Vec<String> = tx
+                    .prepare("SELECT item_id FROM cost_structure WHERE parent_id = ?1")?
+                    .query_map([id], |row| row.get(0))?
+                    .collect::<rusqlite::Result<_>>()?;
+                journal.watch(&tx, Table::Items, id)?;
+                journal.watch(&tx, Table::CostStructure, id)?;
+                for child in &children {
+                    journal.watch(&tx, Table::CostStructure, child)?;
+                }
+            }
+            deleted += tx.execute("DELETE FROM items WHERE id = ?1", [id])?;
+            audit::record(
+                &tx,
+                &actor,
+                Change::new("delete_items", "item", id).before(&before)?,
+            )?;
+        }
+        for journal in journals.into_values() {
+            journal.record(&tx)?;
+        }
+        tx.commit()?;
+        Ok(deleted)
+    })
+    .await
+}
@@ -0,0 +1,137 @@
+//! Developer console for debug builds: read-only SQL and a dump of process state.
+//!
+//! Compiled only with `debug_assertions`, so none of this ships in a release. The frontend
+//! opens its hidden console from Help › Developer Console.
+
+use std::time::Instant;
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{row_to_json, to_sql_params, Db};
+use crate::encryption::Encryption;
+use crate::error::{AppError, AppResult};
+use crate::idle_lock;
+use crate::jobs::Scheduler;
+use crate::network::NetworkMonitor;
+use crate::power::PowerMonitor;
+use crate::state::AppState;
+
+const MAX_ROWS: usize = 1000;
+
+/// Rows returned by [`debug_sql`].
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DebugQueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Map<String, Value>>,
+    /// More rows matched than the 1000 returned.
+    pub truncated: bool,
+    pub elapsed_ms: u64,
+}
+
+/// Run one read-only statement, such as a `SELECT` or `PRAGMA table_info`.
+#[tauri::command]
+pub async fn debug_sql(
+    db: State<'_, Db>,
+    query: String,
+    params: Option<Vec<Value>>,
+) -> AppResult<DebugQueryResult> {
+    let sql = query.trim().trim_end_matches(';').to_string();
+    if sql.is_empty() {
+        return Err(AppError::InvalidInput("SQL statement is empty".into()));
+    }
+    let params = to_sql_params(params.unwrap_or_default());
+    let started = Instant::now();
+    let result = db
+        .run(move |conn| {
+            let mut stmt = conn.prepare(&sql)?;
+            // `readonly` covers what the statement itself does; `query_only` covers the rest
+            if !stmt.readonly() {
+                return Err(AppError::InvalidInput(
+                    "the console only runs statements that read data".into(),
+                ));
+            }
+            let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+            conn.pragma_update(None, "query_only", true)?;
+            let rows = (|| {
+                let mut rows = stmt.query(rusqlite::params_from_iter(params))?;
+                let mut out = Vec::new();
+                while let Some(row) = rows.next()? {
+                    if out.len() == MAX_ROWS {
+                        return Ok((out, true));
+                    }
+                    out.push(row_to_json(row, &columns)?);
+                }
+                Ok::<_, AppError>((out, false))
+            })();
+            drop(stmt);
+            conn.pragma_update(None, "query_only", false)?;
+            let (rows, truncated) = rows?;
+            Ok(DebugQueryResult {
+                columns,
+                rows,
+                truncated,
+                elapsed_ms: 0,
+            })
+        })
+        .await?;
+    Ok(DebugQueryResult {
+        elapsed_ms: started.elapsed().as_millis() as u64,
+        ..result
+    })
+}
+
+/// Everything worth knowing about the running process, as one JSON object.
+///
+/// The config holds no secrets; those all live in the keychain.
+#[tauri::command]
+pub async fn debug_dump_state(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    db: State<'_, Db>,
+) -> AppResult<Value> {
+    let schema_version = match db
+        .run(|conn| {
+            Ok(
+                conn.query_row("SELECT max(version) FROM schema_migrations", [], |row| {
+                    row.get::<_, Option<i64>>(0)
+                })?,
+            )
+        })
+        .await
+    {
+        Ok(version) => json!(version),
+        Err(AppError::DatabaseLocked) => json!("locked"),
+        Err(err) => json!(err.to_string()),
+    };
+    let encryption = app.state::<Encryption>();
+    let jobs = crate::jobs::list_jobs(app.state::<Scheduler>())
+        .await
+        .map(|jobs| json!(jobs))
+        .unwrap_or_else(|err| json!(err.to_string()));
+    Ok(json!({
+        "appVersion": env!("CARGO_PKG_VERSION"),
+        "os": tauri_plugin_os::platform(),
+        "arch": tauri_plugin_os::arch(),
+        "dataDir": state.data_dir(),
+        "configPath": state.config_path(),
+        "config": state.config(),
+        "role": state.role(),
+        "user": state.user(),
+        "sessionLocked": idle_lock::ensure_unlocked(&app).is_err(),
+        "encryption": {
+            "enabled": encryption.is_enabled(),
+            "unlocked": encryption.key_handle().get().is_some(),
+        },
+        "database": {
+            "path": db.path(),
+            "schemaVersion": schema_version,
+        },
+        "network": app.try_state::<NetworkMonitor>().map(|n| n.snapshot()),
+        "power": app.try_state::<PowerMonitor>().map(|p| p.snapshot()),
+        "windows": app.webview_windows().into_keys().collect::<Vec<_>>(),
+        "jobs": jobs,
+    }))
+}
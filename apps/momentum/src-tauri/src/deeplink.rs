@@ -0,0 +1,126 @@
+//! Deep-link parsing into typed routes and delivery to the frontend router.
+
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+use url::Url;
+
+use crate::auth;
+use crate::error::AppResult;
+
+const SCHEMES: &[&str] = &["momentum", "truss"];
+const MAX_ID_LEN: usize = 64;
+
+/// Emitted with a [`Route`] once the frontend router is ready.
+pub const NAVIGATE_EVENT: &str = "navigate";
+
+/// In-app destinations reachable from a deep link.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Route {
+    Projects,
+    #[serde(rename_all = "camelCase")]
+    Project {
+        project_id: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    Estimate {
+        project_id: String,
+        estimate_id: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    ProjectSettings {
+        project_id: String,
+    },
+    Settings,
+}
+
+impl Route {
+    /// Parse `momentum://project/123/estimate/456` style URLs.
+    ///
+    /// Returns `None` for foreign schemes, unknown paths, and malformed ids.
+    pub fn parse(url: &Url) -> Option<Self> {
+        if !SCHEMES.contains(&url.scheme()) {
+            return None;
+        }
+        // Custom schemes put the first segment in the host position
+        let segments: Vec<&str> = url
+            .host_str()
+            .into_iter()
+            .chain(url.path_segments().into_iter().flatten())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        let route = match segments.as_slice() {
+            ["projects"] => Self::Projects,
+            ["settings"] => Self::Settings,
+            ["project", id] => Self::Project {
+                project_id: valid_id(id)?,
+            },
+            ["project", id, "settings"] => Self::ProjectSettings {
+                project_id: valid_id(id)?,
+            },
+            ["project", id, "estimate", estimate] => Self::Estimate {
+                project_id: valid_id(id)?,
+                estimate_id: valid_id(estimate)?,
+            },
+            _ => return None,
+        };
+        Some(route)
+    }
+}
+
+fn valid_id(raw: &str) -> Option<String> {
+    let ok = !raw.is_empty()
+        && raw.len() <= MAX_ID_LEN
+        && raw
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    ok.then(|| raw.to_string())
+}
+
+/// Routes received before the frontend router mounted.
+#[derive(Default)]
+pub struct DeepLinkState {
+    inner: Mutex<Pending>,
+}
+
+#[derive(Default)]
+struct Pending {
+    ready: bool,
+    queue: Vec<Route>,
+}
+
+/// Dispatch incoming URLs: OAuth redirects go to the auth flow, everything else navigates.
+pub fn handle_urls(app: &AppHandle, urls: Vec<Url>) {
+    for url in urls {
+        if auth::is_callback(&url) {
+            auth::handle_callback(app, url);
+            continue;
+        }
+        match Route::parse(&url) {
+            Some(route) => navigate(app, route),
+            None => tracing::warn!(%url, "ignoring unrecognized deep link"),
+        }
+    }
+}
+
+/// Deliver a route now, or queue it until the frontend calls `deeplink_ready`.
+pub fn navigate(app: &AppHandle, route: Route) {
+    let state = app.state::<DeepLinkState>();
+    let mut pending = state.inner.lock().unwrap_or_else(|e| e.into_inner());
+    if pending.ready {
+        let _ = app.emit(NAVIGATE_EVENT, &route);
+    } else {
+        pending.queue.push(route);
+    }
+}
+
+/// Mark the router ready and hand back everything queued during startup.
+#[tauri::command]
+pub async fn deeplink_ready(state: State<'_, DeepLinkState>) -> AppResult<Vec<Route>> {
+    let mut pending = state.inner.lock().unwrap_or_else(|e| e.into_inner());
+    pending.ready = true;
+    Ok(std::mem::take(&mut pending.queue))
+}
@@ -0,0 +1,155 @@
+//! Support bundle export: logs, versions, redacted config, and DB health in one zip.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Manager, State};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::db::{self, Db};
+use crate::error::AppResult;
+use crate::logging::Logging;
+use crate::state::AppState;
+use crate::system_info;
+use crate::window_state::WindowStateStore;
+
+const REDACTED: &str = "[redacted]";
+// Config keys containing any of these are replaced before export
+const SENSITIVE_KEY_PARTS: &[&str] = &["token", "secret", "password", "key"];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DbHealth {
+    integrity_check: Vec<String>,
+    foreign_key_violations: usize,
+    schema: db::migrations::SchemaVersion,
+}
+
+/// Replace values under sensitive-looking keys and strip credentials from URLs.
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let key = key.to_ascii_lowercase();
+                if SENSITIVE_KEY_PARTS.iter().any(|part| key.contains(part)) {
+                    *value = Value::String(REDACTED.into());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        Value::String(s) => {
+            if let Ok(mut url) = url::Url::parse(s) {
+                if url.password().is_some() || !url.username().is_empty() || url.query().is_some() {
+                    let _ = url.set_username("");
+                    let _ = url.set_password(None);
+                    url.set_query(None);
+                    *s = url.to_string();
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+async fn db_health(db: &Db) -> AppResult<DbHealth> {
+    db.run(|conn| {
+        let integrity_check = conn
+            .prepare("PRAGMA integrity_check")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        let foreign_key_violations = conn
+            .prepare("PRAGMA foreign_key_check")?
+            .query_map([], |_| Ok(()))?
+            .count();
+        Ok(DbHealth {
+            integrity_check,
+            foreign_key_violations,
+            schema: db::migrations::schema_version(conn)?,
+        })
+    })
+    .await
+}
+
+fn write_bundle(
+    target: &Path,
+    entries: Vec<(String, Vec<u8>)>,
+    log_files: Vec<PathBuf>,
+) -> AppResult<()> {
+    let tmp = target.with_extension("zip.tmp");
+    let mut zip = ZipWriter::new(File::create(&tmp)?);
+    let options = SimpleFileOptions::default();
+    for (name, bytes) in entries {
+        zip.start_file(name, options)?;
+        zip.write_all(&bytes)?;
+    }
+    for path in log_files {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        zip.start_file(format!("logs/{name}"), options)?;
+        zip.write_all(&fs::read(&path)?)?;
+    }
+    zip.finish()?;
+    fs::rename(&tmp, target)?;
+    Ok(())
+}
+
+/// Write a diagnostics zip to `destination`, or the downloads folder when omitted.
+///
+/// Returns the path written so the frontend can reveal it.
+#[tauri::command]
+pub async fn export_diagnostics(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    logging: State<'_, Logging>,
+    db: State<'_, Db>,
+    window_state: State<'_, WindowStateStore>,
+    destination: Option<PathBuf>,
+) -> AppResult<PathBuf> {
+    let target = match destination {
+        Some(path) => path,
+        None => app
+            .path()
+            .download_dir()?
+            .join(format!("momentum-diagnostics-{}.zip", db::now_ms())),
+    };
+
+    let mut config = serde_json::to_value(state.config())?;
+    redact(&mut config);
+    // A failed check is itself useful diagnostics, so it never aborts the export
+    let health = match db_health(&db).await {
+        Ok(health) => serde_json::to_vec_pretty(&health)?,
+        Err(err) => serde_json::to_vec_pretty(&serde_json::json!({ "error": err }))?,
+    };
+
+    let handle = app.clone();
+    let system =
+        tauri::async_runtime::spawn_blocking(move || system_info::collect(&handle)).await?;
+
+    let mut entries = vec![
+        (
+            "system.json".to_string(),
+            serde_json::to_vec_pretty(&system)?,
+        ),
+        (
+            "config.json".to_string(),
+            serde_json::to_vec_pretty(&config)?,
+        ),
+        ("db-health.json".to_string(), health),
+    ];
+    if let Ok(bytes) = fs::read(window_state.path()) {
+        entries.push(("window-state.json".to_string(), bytes));
+    }
+    let log_files = logging.log_files()?;
+
+    let path = target.clone();
+    tauri::async_runtime::spawn_blocking(move || write_bundle(&path, entries, log_files)).await??;
+    tracing::info!(path = %target.display(), "diagnostics exported");
+    Ok(target)
+}
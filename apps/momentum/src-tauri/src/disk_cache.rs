@@ -0,0 +1,76 @@
+//! Helpers for derived-file caches such as rendered PDF pages and thumbnails.
+//!
+//! Entries are keyed by the source file's path, size, and modification time, so an edited
+//! source never serves stale output. Hits refresh an entry's modification time, which
+//! eviction then uses as its least-recently-used order.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::AppResult;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Cache key for `source` as it is on disk now, combined with the caller's `variant`.
+pub(crate) fn source_key(source: &Path, variant: &str) -> AppResult<String> {
+    let path = fs::canonicalize(source)?;
+    let meta = fs::metadata(&path)?;
+    let modified = meta
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let mut hasher = Sha256::new();
+    hasher.update(path.to_string_lossy().as_bytes());
+    hasher.update(format!("\0{}\0{modified}\0{variant}", meta.len()).as_bytes());
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Mark an entry as recently used; failure only makes it an earlier eviction.
+pub(crate) fn touch(entry: &Path) {
+    let _ = File::options()
+        .write(true)
+        .open(entry)
+        .and_then(|f| f.set_modified(SystemTime::now()));
+}
+
+/// Delete the least recently used files in `dir` until it fits in `budget` bytes.
+///
+/// `keep` is never removed. Best effort; a failed eviction only lets the cache run over
+/// budget until the next call.
+pub(crate) fn evict(dir: &Path, budget: u64, keep: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let meta = entry.metadata().ok()?;
+            meta.is_file().then(|| {
+                (
+                    meta.modified().unwrap_or(UNIX_EPOCH),
+                    meta.len(),
+                    entry.path(),
+                )
+            })
+        })
+        .collect();
+    let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+    files.sort_by_key(|(modified, _, _)| *modified);
+    for (_, size, path) in files {
+        if total <= budget {
+            break;
+        }
+        if path == keep {
+            continue;
+        }
+        if fs::remove_file(&path).is_ok() {
+            total -= size;
+        }
+    }
+}
@@ -0,0 +1,381 @@
+//! Connected displays and moving windows between them.
+//!
+//! Displays are polled, since no platform event reaches the app when one is plugged in or
+//! out. Every window's place is remembered relative to the work area of the display it is
+//! on. When a display goes away, its windows are noted as displaced and any the OS left
+//! off-screen are put back in view; when it returns, as when a laptop is docked again, they
+//! go back to where they were on it. `displays:changed` reports every change.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, PhysicalPosition, PhysicalSize, WebviewWindow};
+
+use crate::error::{AppError, AppResult};
+use crate::windows::{self, MAIN_WINDOW};
+
+/// Emitted with a [`DisplayChange`] when displays are added, removed, or rearranged.
+pub const DISPLAYS_CHANGED_EVENT: &str = "displays:changed";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+// Keep at least this much of a window on a display for it to count as visible
+const MIN_VISIBLE_PX: i32 = 100;
+
+/// A rectangle in physical pixels on the virtual desktop.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bounds {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Bounds {
+    fn right(&self) -> i32 {
+        self.x + self.width as i32
+    }
+
+    fn bottom(&self) -> i32 {
+        self.y + self.height as i32
+    }
+
+    fn overlaps(&self, other: &Bounds, margin: i32) -> bool {
+        self.x + margin <= other.right()
+            && self.right() - margin >= other.x
+            && self.y + margin <= other.bottom()
+            && self.bottom() - margin >= other.y
+    }
+}
+
+/// One connected display.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitorInfo {
+    /// Stable while the display stays connected; pass it to `move_window_to_monitor`.
+    pub id: String,
+    pub name: Option<String>,
+    pub bounds: Bounds,
+    /// The bounds less the taskbar, dock, or menu bar.
+    pub work_area: Bounds,
+    pub scale_factor: f64,
+    pub primary: bool,
+}
+
+/// What changed, as sent with `displays:changed`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DisplayChange {
+    pub monitors: Vec<MonitorInfo>,
+    /// Ids of displays connected since the last check.
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Labels of windows that were moved back into view or onto a returning display.
+    pub moved: Vec<String>,
+}
+
+// Where a window sits, as fractions of its display's work area
+#[derive(Debug, Clone)]
+struct Placement {
+    monitor: String,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+#[derive(Default)]
+struct Tracked {
+    monitors: Vec<MonitorInfo>,
+    placements: HashMap<String, Placement>,
+    // Windows whose display went away, by label, with where they were on it
+    displaced: HashMap<String, Placement>,
+}
+
+/// Display tracking registered in app state.
+#[derive(Default)]
+pub struct Displays {
+    tracked: Mutex<Tracked>,
+}
+
+fn bounds(position: &PhysicalPosition<i32>, size: &PhysicalSize<u32>) -> Bounds {
+    Bounds {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    }
+}
+
+// Two identical models share a name, so repeats are told apart by order
+pub(crate) fn read_monitors(app: &AppHandle) -> AppResult<Vec<MonitorInfo>> {
+    let primary = app
+        .primary_monitor()?
+        .map(|m| bounds(m.position(), m.size()));
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut monitors = Vec::new();
+    for monitor in app.available_monitors()? {
+        let rect = bounds(monitor.position(), monitor.size());
+        let base = monitor
+            .name()
+            .cloned()
+            .unwrap_or_else(|| format!("display@{},{}", rect.x, rect.y));
+        let count = seen.entry(base.clone()).or_default();
+        *count += 1;
+        let id = match *count {
+            1 => base,
+            n => format!("{base} ({n})"),
+        };
+        let work_area = monitor.work_area();
+        monitors.push(MonitorInfo {
+            id,
+            name: monitor.name().cloned(),
+            bounds: rect,
+            work_area: bounds(&work_area.position, &work_area.size),
+            scale_factor: monitor.scale_factor(),
+            primary: primary == Some(rect),
+        });
+    }
+    Ok(monitors)
+}
+
+fn window_bounds(window: &WebviewWindow) -> AppResult<Bounds> {
+    Ok(bounds(&window.outer_position()?, &window.outer_size()?))
+}
+
+// The display holding the middle of the window
+fn monitor_of<'a>(monitors: &'a [MonitorInfo], window: &Bounds) -> Option<&'a MonitorInfo> {
+    let (cx, cy) = (
+        window.x + window.width as i32 / 2,
+        window.y + window.height as i32 / 2,
+    );
+    monitors.iter().find(|m| {
+        let b = &m.bounds;
+        (b.x..b.right()).contains(&cx) && (b.y..b.bottom()).contains(&cy)
+    })
+}
+
+fn placement(monitor: &MonitorInfo, window: &Bounds) -> Placement {
+    let area = &monitor.work_area;
+    let (w, h) = (area.width.max(1) as f64, area.height.max(1) as f64);
+    Placement {
+        monitor: monitor.id.clone(),
+        x: (window.x - area.x) as f64 / w,
+        y: (window.y - area.y) as f64 / h,
+        width: window.width as f64 / w,
+        height: window.height as f64 / h,
+    }
+}
+
+// Maximized and full-screen windows are restored first, moved, and then put back
+fn place(window: &WebviewWindow, monitor: &MonitorInfo, placement: &Placement) -> AppResult<()> {
+    let fullscreen = window.is_fullscreen().unwrap_or(false);
+    let maximized = window.is_maximized().unwrap_or(false);
+    if fullscreen {
+        window.set_fullscreen(false)?;
+    }
+    if maximized {
+        window.unmaximize()?;
+    }
+    let area = &monitor.work_area;
+    let outer = window.outer_size()?;
+    let inner = window.inner_size()?;
+    let frame_w = outer.width.saturating_sub(inner.width);
+    let frame_h = outer.height.saturating_sub(inner.height);
+    let width = ((placement.width * area.width as f64) as u32).clamp(1, area.width);
+    let height = ((placement.height * area.height as f64) as u32).clamp(1, area.height);
+    let x = area.x + (placement.x * area.width as f64) as i32;
+    let y = area.y + (placement.y * area.height as f64) as i32;
+    // Kept wholly inside the work area, so a window from a larger display still fits
+    let x = x.clamp(area.x, area.right() - width as i32);
+    let y = y.clamp(area.y, area.bottom() - height as i32);
+    window.set_size(PhysicalSize::new(
+        width.saturating_sub(frame_w).max(1),
+        height.saturating_sub(frame_h).max(1),
+    ))?;
+    window.set_position(PhysicalPosition::new(x, y))?;
+    if maximized {
+        window.maximize()?;
+    }
+    if fullscreen {
+        window.set_fullscreen(true)?;
+    }
+    Ok(())
+}
+
+fn caller_label(window: &WebviewWindow) -> String {
+    let label = window.label();
+    label
+        .strip_prefix(windows::SECONDARY_PREFIX)
+        .unwrap_or(label)
+        .to_string()
+}
+
+fn resolve_window(app: &AppHandle, label: &str) -> AppResult<WebviewWindow> {
+    let full_label = match label {
+        MAIN_WINDOW => MAIN_WINDOW.to_string(),
+        label => windows::window_label(label)?,
+    };
+    app.get_webview_window(&full_label)
+        .ok_or_else(|| AppError::not_found("window", label))
+}
+
+impl Displays {
+    fn check(&self, app: &AppHandle) -> AppResult<Option<DisplayChange>> {
+        let monitors = read_monitors(app)?;
+        let windows = app.webview_windows();
+        let mut tracked = self.tracked.lock().unwrap_or_else(|e| e.into_inner());
+        tracked
+            .placements
+            .retain(|label, _| windows.contains_key(label));
+        tracked
+            .displaced
+            .retain(|label, _| windows.contains_key(label));
+
+        if monitors == tracked.monitors {
+            for (label, window) in &windows {
+                if window.is_minimized().unwrap_or(false) || !window.is_visible().unwrap_or(false) {
+                    continue;
+                }
+                let Ok(bounds) = window_bounds(window) else {
+                    continue;
+                };
+                if let Some(monitor) = monitor_of(&monitors, &bounds) {
+                    tracked
+                        .placements
+                        .insert(label.clone(), placement(monitor, &bounds));
+                }
+            }
+            return Ok(None);
+        }
+
+        let first_check = tracked.monitors.is_empty();
+        let old: BTreeSet<&str> = tracked.monitors.iter().map(|m| m.id.as_str()).collect();
+        let new: BTreeSet<&str> = monitors.iter().map(|m| m.id.as_str()).collect();
+        let added: Vec<String> = new.difference(&old).map(|id| id.to_string()).collect();
+        let removed: Vec<String> = old.difference(&new).map(|id| id.to_string()).collect();
+        let fallback = monitors
+            .iter()
+            .find(|m| m.primary)
+            .or_else(|| monitors.first());
+
+        let mut moved = Vec::new();
+        for (label, window) in &windows {
+            let returning = tracked
+                .displaced
+                .get(label)
+                .filter(|p| added.contains(&p.monitor))
+                .cloned();
+            if let Some(placement) = returning {
+                let Some(monitor) = monitors.iter().find(|m| m.id == placement.monitor) else {
+                    continue;
+                };
+                tracked.displaced.remove(label);
+                match place(window, monitor, &placement) {
+                    Ok(()) => moved.push(caller_label(window)),
+                    Err(err) => tracing::warn!(%err, label, "cannot move window back"),
+                }
+                continue;
+            }
+
+            let last = tracked.placements.get(label).cloned();
+            if let Some(last) = last.filter(|p| removed.contains(&p.monitor)) {
+                tracked.displaced.insert(label.clone(), last);
+            }
+            // Whatever the OS did with it, a window must stay where it can be grabbed
+            let Ok(bounds) = window_bounds(window) else {
+                continue;
+            };
+            let visible = monitors
+                .iter()
+                .any(|m| bounds.overlaps(&m.work_area, MIN_VISIBLE_PX));
+            if let (false, Some(monitor)) = (visible, fallback) {
+                let placement = tracked
+                    .placements
+                    .get(label)
+                    .cloned()
+                    .unwrap_or_else(|| placement(monitor, &bounds));
+                match place(window, monitor, &placement) {
+                    Ok(()) => moved.push(caller_label(window)),
+                    Err(err) => tracing::warn!(%err, label, "cannot move window into view"),
+                }
+            }
+        }
+        tracked.monitors = monitors.clone();
+        if first_check {
+            return Ok(None);
+        }
+        tracing::info!(?added, ?removed, ?moved, "displays changed");
+        Ok(Some(DisplayChange {
+            monitors,
+            added,
+            removed,
+            moved,
+        }))
+    }
+}
+
+/// Start watching for display changes in the background.
+pub fn start(app: &AppHandle) {
+    app.manage(Displays::default());
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match app.state::<Displays>().check(&app) {
+                Ok(Some(change)) => {
+                    let _ = app.emit(DISPLAYS_CHANGED_EVENT, &change);
+                }
+                Ok(None) => {}
+                Err(err) => tracing::warn!(%err, "display check failed"),
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn list_monitors(app: AppHandle) -> AppResult<Vec<MonitorInfo>> {
+    read_monitors(&app)
+}
+
+/// Move a window to another display, keeping its place relative to the work area.
+///
+/// `label` is `main` or a label passed to `open_secondary_window`; `monitor` is an id from
+/// `list_monitors`.
+#[tauri::command]
+pub async fn move_window_to_monitor(
+    app: AppHandle,
+    label: String,
+    monitor: String,
+) -> AppResult<()> {
+    let window = resolve_window(&app, &label)?;
+    let monitors = read_monitors(&app)?;
+    let target = monitors
+        .iter()
+        .find(|m| m.id == monitor)
+        .ok_or_else(|| AppError::not_found("monitor", &monitor))?;
+    let bounds = window_bounds(&window)?;
+    let mut placement = match monitor_of(&monitors, &bounds) {
+        Some(current) => placement(current, &bounds),
+        // Off every display: centered at its current size
+        None => {
+            let mut centered = placement(target, &bounds);
+            centered.x = (1.0 - centered.width) / 2.0;
+            centered.y = (1.0 - centered.height) / 2.0;
+            centered
+        }
+    };
+    placement.monitor = target.id.clone();
+    place(&window, target, &placement)?;
+    // A deliberate move means the window no longer belongs to a display that went away
+    if let Some(displays) = app.try_state::<Displays>() {
+        let mut tracked = displays.tracked.lock().unwrap_or_else(|e| e.into_inner());
+        tracked.displaced.remove(window.label());
+        tracked
+            .placements
+            .insert(window.label().to_string(), placement);
+    }
+    Ok(())
+}
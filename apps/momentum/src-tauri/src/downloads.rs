@@ -0,0 +1,816 @@
+//! Download manager for attachments and plan sets.
+//!
+//! Downloads queue in SQLite and run a few at a time. Bytes stream into a `.part` file next to
+//! the destination; pausing keeps it, and resuming asks for the rest with a `Range` request.
+//! The finished file is checked against the server's SHA-256 before it is moved into place.
+//!
+//! Files land in the downloads folder or at a path the user picked in a save dialog, and
+//! never replace an existing file unless the caller asked for that.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use percent_encoding::percent_decode_str;
+use rusqlite::{params, OptionalExtension, Row};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_dialog::DialogExt;
+use tauri_plugin_http::reqwest::header::{CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_RANGE, RANGE};
+use tauri_plugin_http::reqwest::StatusCode;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Notify;
+
+use crate::config;
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::http_client::{HttpClient, RetryPolicy};
+use crate::progress;
+use crate::wake_lock;
+
+const MAX_CONCURRENT: usize = 3;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+const CONTENT_SHA256_HEADER: &str = "X-Content-SHA256";
+const PART_EXTENSION: &str = "part";
+
+const STATUS_QUEUED: &str = "queued";
+const STATUS_DOWNLOADING: &str = "downloading";
+const STATUS_PAUSED: &str = "paused";
+const STATUS_COMPLETED: &str = "completed";
+const STATUS_FAILED: &str = "failed";
+
+/// Emitted with a [`DownloadProgress`] while bytes arrive, throttled.
+pub const PROGRESS_EVENT: &str = "download:progress";
+/// Emitted with the [`Download`] once the file is verified and in place.
+pub const COMPLETED_EVENT: &str = "download:completed";
+/// Emitted with the [`Download`] when it stops on an error; it can be resumed.
+pub const FAILED_EVENT: &str = "download:failed";
+
+/// A download as shown to the frontend.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Download {
+    pub id: String,
+    /// API path the file is fetched from.
+    pub path: String,
+    pub destination: PathBuf,
+    /// `queued`, `downloading`, `paused`, `completed`, or `failed`.
+    pub status: String,
+    /// `None` until the server reports a length.
+    pub total: Option<i64>,
+    pub downloaded: i64,
+    /// Expected hex SHA-256, from the caller or the server's `X-Content-SHA256`.
+    pub sha256: Option<String>,
+    /// Whether the finished file matched `sha256`; false when no hash was available.
+    pub verified: bool,
+    /// Whether the finished file may replace one already at `destination`.
+    pub overwrite: bool,
+    pub last_error: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Download {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let destination: String = row.get("destination")?;
+        Ok(Self {
+            id: row.get("id")?,
+            path: row.get("path")?,
+            destination: PathBuf::from(destination),
+            status: row.get("status")?,
+            total: row.get("total")?,
+            downloaded: row.get("downloaded")?,
+            sha256: row.get("sha256")?,
+            verified: row.get("verified")?,
+            overwrite: row.get("overwrite")?,
+            last_error: row.get("last_error")?,
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+
+    fn part_path(&self) -> PathBuf {
+        let mut name = self
+            .destination
+            .file_name()
+            .unwrap_or_default()
+            .to_os_string();
+        name.push(format!(".{PART_EXTENSION}"));
+        self.destination.with_file_name(name)
+    }
+}
+
+/// Payload for `download:progress`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadProgress {
+    pub id: String,
+    pub downloaded: i64,
+    pub total: Option<i64>,
+}
+
+/// Download queue held in app state.
+pub struct Downloads {
+    db: Db,
+    // Notified to stop a running download at the next await point
+    active: Mutex<HashMap<String, Arc<Notify>>>,
+    // Paths the user chose in a save dialog, each good for one download
+    picked: Mutex<HashSet<PathBuf>>,
+}
+
+impl Downloads {
+    pub fn new(db: Db) -> Self {
+        Self {
+            db,
+            active: Mutex::new(HashMap::new()),
+            picked: Mutex::new(HashSet::new()),
+        }
+    }
+
+    async fn get(&self, id: &str) -> AppResult<Download> {
+        let lookup = id.to_string();
+        self.db
+            .run(move |conn| {
+                Ok(conn
+                    .query_row(
+                        "SELECT * FROM downloads WHERE id = ?1",
+                        [lookup],
+                        Download::from_row,
+                    )
+                    .optional()?)
+            })
+            .await?
+            .ok_or_else(|| AppError::not_found("download", id))
+    }
+
+    async fn set_status(
+        &self,
+        id: &str,
+        status: &'static str,
+        error: Option<String>,
+    ) -> AppResult<()> {
+        let id = id.to_string();
+        self.db
+            .run(move |conn| {
+                conn.execute(
+                    "UPDATE downloads SET status = ?2, last_error = ?3, updated_at = ?4
+                     WHERE id = ?1",
+                    params![id, status, error, now_ms()],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    fn stop(&self, id: &str) {
+        if let Some(stop) = self
+            .active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(id)
+        {
+            stop.notify_one();
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// Hashing what is already on disk lets a resumed download verify the whole file
+fn hash_part(path: &Path) -> AppResult<(Sha256, i64)> {
+    let mut hasher = Sha256::new();
+    match std::fs::File::open(path) {
+        Ok(mut file) => {
+            let len = std::io::copy(&mut file, &mut hasher)?;
+            Ok((hasher, len as i64))
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok((hasher, 0)),
+        Err(err) => Err(err.into()),
+    }
+}
+
+// "report (1).pdf" when "report.pdf" is taken, so a default destination never overwrites
+pub(crate) fn unique_destination(dir: &Path, file_name: &str) -> PathBuf {
+    let candidate = dir.join(file_name);
+    if !candidate.exists() {
+        return candidate;
+    }
+    let path = Path::new(file_name);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()));
+    (1..)
+        .map(|n| {
+            dir.join(format!(
+                "{stem} ({n}){}",
+                extension.as_deref().unwrap_or("")
+            ))
+        })
+        .find(|p| !p.exists())
+        .unwrap_or(candidate)
+}
+
+// A destination the user didn't pick must be a file directly in, or below, the downloads
+// folder once `..` and symlinks in its parent are resolved
+fn check_destination(destination: &Path, downloads_dir: &Path) -> AppResult<PathBuf> {
+    let refused = || {
+        AppError::InvalidInput(format!(
+            "{} is outside the downloads folder",
+            destination.display()
+        ))
+    };
+    let (Some(parent), Some(name)) = (destination.parent(), destination.file_name()) else {
+        return Err(refused());
+    };
+    if !destination.is_absolute() {
+        return Err(refused());
+    }
+    let parent = std::fs::canonicalize(parent).map_err(|_| refused())?;
+    if !parent.starts_with(std::fs::canonicalize(downloads_dir)?) {
+        return Err(refused());
+    }
+    Ok(parent.join(name))
+}
+
+// Anything at the path counts, including a dangling symlink
+fn taken(path: &Path) -> bool {
+    std::fs::symlink_metadata(path).is_ok()
+}
+
+// The first byte and, when known, the full length from `bytes <first>-<last>/<length>`
+fn content_range(value: &str) -> Option<(i64, Option<i64>)> {
+    let (range, length) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let (first, last) = range.split_once('-')?;
+    let first: i64 = first.trim().parse().ok()?;
+    let last: i64 = last.trim().parse().ok()?;
+    if first < 0 || last < first {
+        return None;
+    }
+    let length = match length.trim() {
+        "*" => None,
+        length => Some(length.parse().ok().filter(|l| *l > last)?),
+    };
+    Some((first, length))
+}
+
+async fn run(app: &AppHandle, id: &str) -> AppResult<()> {
+    let _awake = wake_lock::hold(app, "Downloading a file");
+    let taskbar = progress::track(app, format!("download:{id}"));
+    let downloads = app.state::<Downloads>();
+    let http = app.state::<HttpClient>();
+    let download = downloads.get(id).await?;
+    let part = download.part_path();
+
+    let lookup = part.clone();
+    let (mut hasher, mut downloaded) =
+        tauri::async_runtime::spawn_blocking(move || hash_part(&lookup)).await??;
+
+    let url = config::api_url(app, &download.path)?;
+    let mut request = http.client().get(url);
+    if downloaded > 0 {
+        request = request.header(RANGE, format!("bytes={downloaded}-"));
+        // If the file changed since the part was written, the server sends all of it instead
+        if let Some(etag) = &download_etag(&downloads, id).await? {
+            request = request.header(IF_RANGE, etag);
+        }
+    }
+    let mut response = http
+        .send_authorized(app, request, RetryPolicy::default())
+        .await?;
+
+    match response.status() {
+        StatusCode::PARTIAL_CONTENT => {
+            let first = response
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(content_range)
+                .map(|(first, _)| first);
+            if first != Some(downloaded) {
+                // Appending any other range would splice the wrong bytes into the file
+                let _ = tokio::fs::remove_file(&part).await;
+                return Err(AppError::Internal(match first {
+                    Some(first) => {
+                        format!("server resumed at byte {first} instead of {downloaded}")
+                    }
+                    None => "server sent a partial response without a valid Content-Range".into(),
+                }));
+            }
+        }
+        // The part file already holds everything
+        StatusCode::RANGE_NOT_SATISFIABLE if downloaded > 0 => {
+            return finish(app, &download, hasher, downloaded).await;
+        }
+        _ => {
+            // The server ignored the range or the file changed; start over
+            response = response.error_for_status()?;
+            hasher = Sha256::new();
+            downloaded = 0;
+        }
+    }
+
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let remaining: Option<i64> = header(CONTENT_LENGTH.as_str()).and_then(|v| v.parse().ok());
+    let total = remaining.map(|r| r + downloaded).or(download.total);
+    let sha256 = download
+        .sha256
+        .clone()
+        .or_else(|| header(CONTENT_SHA256_HEADER).map(|h| h.to_ascii_lowercase()));
+    let etag = header(ETAG.as_str());
+    let row = id.to_string();
+    let expected = sha256.clone();
+    downloads
+        .db
+        .run(move |conn| {
+            conn.execute(
+                "UPDATE downloads SET total = ?2, sha256 = ?3, etag = COALESCE(?4, etag),
+                     downloaded = ?5, updated_at = ?6
+                 WHERE id = ?1",
+                params![row, total, expected, etag, downloaded, now_ms()],
+            )?;
+            Ok(())
+        })
+        .await?;
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(downloaded > 0)
+        .truncate(downloaded == 0)
+        .open(&part)
+        .await?;
+    let mut last_emit = Instant::now() - PROGRESS_INTERVAL;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+        hasher.update(&chunk);
+        downloaded += chunk.len() as i64;
+        // Chunks arrive every few KB; throttle so the IPC channel isn't flooded
+        if last_emit.elapsed() >= PROGRESS_INTERVAL {
+            last_emit = Instant::now();
+            taskbar.set(
+                u64::try_from(downloaded).unwrap_or(0),
+                total.and_then(|t| u64::try_from(t).ok()),
+            );
+            let _ = app.emit(
+                PROGRESS_EVENT,
+                DownloadProgress {
+                    id: id.to_string(),
+                    downloaded,
+                    total,
+                },
+            );
+        }
+    }
+    file.flush().await?;
+    drop(file);
+
+    let download = Download {
+        sha256,
+        total,
+        ..download
+    };
+    finish(app, &download, hasher, downloaded).await
+}
+
+async fn download_etag(downloads: &Downloads, id: &str) -> AppResult<Option<String>> {
+    let id = id.to_string();
+    downloads
+        .db
+        .run(move |conn| {
+            Ok(
+                conn.query_row("SELECT etag FROM downloads WHERE id = ?1", [id], |row| {
+                    row.get(0)
+                })?,
+            )
+        })
+        .await
+}
+
+async fn finish(
+    app: &AppHandle,
+    download: &Download,
+    hasher: Sha256,
+    downloaded: i64,
+) -> AppResult<()> {
+    let downloads = app.state::<Downloads>();
+    let part = download.part_path();
+    let actual = hex(&hasher.finalize());
+    let verified = match &download.sha256 {
+        Some(expected) if *expected != actual => {
+            // A corrupt part would fail every resume, so the next attempt starts clean
+            let _ = tokio::fs::remove_file(&part).await;
+            return Err(AppError::Internal(format!(
+                "checksum mismatch: expected {expected}, got {actual}"
+            )));
+        }
+        Some(_) => true,
+        None => {
+            tracing::warn!(id = %download.id, "server sent no checksum; download unverified");
+            false
+        }
+    };
+    // The destination was free when queued, but something may have been saved there since
+    if !download.overwrite && taken(&download.destination) {
+        return Err(AppError::InvalidInput(format!(
+            "{} already exists; the download was not moved into place",
+            download.destination.display()
+        )));
+    }
+    tokio::fs::rename(&part, &download.destination).await?;
+
+    let id = download.id.clone();
+    downloads
+        .db
+        .run(move |conn| {
+            conn.execute(
+                "UPDATE downloads SET status = ?2, downloaded = ?3, verified = ?4,
+                     last_error = NULL, updated_at = ?5
+                 WHERE id = ?1",
+                params![id, STATUS_COMPLETED, downloaded, verified, now_ms()],
+            )?;
+            Ok(())
+        })
+        .await?;
+    tracing::info!(id = %download.id, downloaded, verified, "download completed");
+    let _ = app.emit(COMPLETED_EVENT, downloads.get(&download.id).await?);
+    Ok(())
+}
+
+/// Start queued downloads, oldest first, until the concurrency limit is reached.
+fn pump(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let downloads = app.state::<Downloads>();
+        let running = downloads
+            .active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len();
+        let Some(slots) = MAX_CONCURRENT.checked_sub(running).filter(|s| *s > 0) else {
+            return;
+        };
+        let queued = downloads
+            .db
+            .run(move |conn| {
+                Ok(conn
+                    .prepare(
+                        "SELECT id FROM downloads WHERE status = ?1 ORDER BY created_at LIMIT ?2",
+                    )?
+                    .query_map(params![STATUS_QUEUED, slots as i64], |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<String>>>()?)
+            })
+            .await;
+        match queued {
+            Ok(ids) => {
+                for id in ids {
+                    spawn(&app, id).await;
+                }
+            }
+            Err(err) => tracing::warn!(%err, "failed to load queued downloads"),
+        }
+    });
+}
+
+async fn spawn(app: &AppHandle, id: String) {
+    let downloads = app.state::<Downloads>();
+    let stop = Arc::new(Notify::new());
+    {
+        let mut active = downloads.active.lock().unwrap_or_else(|e| e.into_inner());
+        if active.contains_key(&id) || active.len() >= MAX_CONCURRENT {
+            return;
+        }
+        active.insert(id.clone(), stop.clone());
+    }
+    if let Err(err) = downloads.set_status(&id, STATUS_DOWNLOADING, None).await {
+        tracing::warn!(%id, %err, "failed to start download");
+        downloads.stop(&id);
+        return;
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        // Pausing drops the future mid-stream; the part file keeps what was written
+        let result = tokio::select! {
+            result = run(&app, &id) => Some(result),
+            _ = stop.notified() => None,
+        };
+        let downloads = app.state::<Downloads>();
+        downloads
+            .active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
+        if let Some(Err(err)) = result {
+            tracing::warn!(%id, %err, "download failed");
+            if let Err(err) = downloads
+                .set_status(&id, STATUS_FAILED, Some(err.to_string()))
+                .await
+            {
+                tracing::warn!(%id, %err, "failed to record download failure");
+            }
+            if let Ok(download) = downloads.get(&id).await {
+                let _ = app.emit(FAILED_EVENT, download);
+            }
+        }
+        pump(&app);
+    });
+}
+
+/// Requeue downloads that were running when the app last quit, then start the queue.
+pub fn resume_interrupted(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        // An encrypted database stays closed until the user unlocks it
+        app.state::<Db>().wait_unlocked().await;
+        let requeued = app
+            .state::<Downloads>()
+            .db
+            .run(|conn| {
+                Ok(conn.execute(
+                    "UPDATE downloads SET status = ?1 WHERE status = ?2",
+                    params![STATUS_QUEUED, STATUS_DOWNLOADING],
+                )?)
+            })
+            .await;
+        if let Err(err) = requeued {
+            tracing::warn!(%err, "failed to requeue interrupted downloads");
+        }
+        pump(&app);
+    });
+}
+
+/// Ask the user where to save `file_name`, starting in the downloads folder.
+///
+/// The chosen path can be passed once to [`download_enqueue`] as its destination. `None`
+/// when the dialog is dismissed.
+#[tauri::command]
+pub async fn download_choose_destination(
+    app: AppHandle,
+    downloads: State<'_, Downloads>,
+    file_name: String,
+) -> AppResult<Option<PathBuf>> {
+    let dir = app.path().download_dir()?;
+    let dialog = app.dialog().clone();
+    let chosen = tauri::async_runtime::spawn_blocking(move || {
+        dialog
+            .file()
+            .set_directory(dir)
+            .set_file_name(file_name)
+            .blocking_save_file()
+    })
+    .await?;
+    let Some(path) = chosen.and_then(|p| p.into_path().ok()) else {
+        return Ok(None);
+    };
+    downloads
+        .picked
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(path.clone());
+    Ok(Some(path))
+}
+
+/// Queue an API file for download.
+///
+/// Without `destination` the file goes to the downloads dir under the last path segment.
+/// A `destination` must be in the downloads dir or come from
+/// [`download_choose_destination`]. An existing file there is only replaced when
+/// `overwrite` is set. `sha256` overrides the checksum the server sends.
+#[tauri::command]
+pub async fn download_enqueue(
+    app: AppHandle,
+    downloads: State<'_, Downloads>,
+    path: String,
+    destination: Option<PathBuf>,
+    overwrite: Option<bool>,
+    sha256: Option<String>,
+) -> AppResult<Download> {
+    // Rejects paths that would resolve to another host before they are queued
+    let url = config::api_url(&app, &path)?;
+    let overwrite = overwrite.unwrap_or(false);
+    let destination = match destination {
+        Some(destination) => {
+            let picked = downloads
+                .picked
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&destination);
+            let destination = if picked {
+                destination
+            } else {
+                check_destination(&destination, &app.path().download_dir()?)?
+            };
+            if !overwrite && taken(&destination) {
+                return Err(AppError::InvalidInput(format!(
+                    "{} already exists; pass overwrite to replace it",
+                    destination.display()
+                )));
+            }
+            destination
+        }
+        None => {
+            let file_name = url
+                .path_segments()
+                .and_then(|mut s| s.next_back())
+                .filter(|s| !s.is_empty())
+                .ok_or_else(|| AppError::InvalidInput(format!("{path} names no file")))?;
+            // Path segments arrive percent-encoded; file names should not
+            let file_name = percent_decode_str(file_name).decode_utf8_lossy();
+            unique_destination(&app.path().download_dir()?, &file_name)
+        }
+    };
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let row = (id.clone(), destination.to_string_lossy().into_owned());
+    let sha256 = sha256.map(|h| h.to_ascii_lowercase());
+    downloads
+        .db
+        .run(move |conn| {
+            let now = now_ms();
+            conn.execute(
+                "INSERT INTO downloads
+                     (id, path, destination, sha256, overwrite, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+                params![row.0, path, row.1, sha256, overwrite, now],
+            )?;
+            Ok(())
+        })
+        .await?;
+    pump(&app);
+    downloads.get(&id).await
+}
+
+/// Stop a queued or running download, keeping what has been written.
+#[tauri::command]
+pub async fn download_pause(
+    app: AppHandle,
+    downloads: State<'_, Downloads>,
+    id: String,
+) -> AppResult<Download> {
+    let download = downloads.get(&id).await?;
+    if download.status != STATUS_QUEUED && download.status != STATUS_DOWNLOADING {
+        return Err(AppError::InvalidInput(format!(
+            "download is {}, not active",
+            download.status
+        )));
+    }
+    downloads.set_status(&id, STATUS_PAUSED, None).await?;
+    downloads.stop(&id);
+    pump(&app);
+    downloads.get(&id).await
+}
+
+/// Requeue a paused or failed download; it continues from the bytes already on disk.
+#[tauri::command]
+pub async fn download_resume(
+    app: AppHandle,
+    downloads: State<'_, Downloads>,
+    id: String,
+) -> AppResult<Download> {
+    let download = downloads.get(&id).await?;
+    if download.status != STATUS_PAUSED && download.status != STATUS_FAILED {
+        return Err(AppError::InvalidInput(format!(
+            "download is {}, not paused",
+            download.status
+        )));
+    }
+    downloads.set_status(&id, STATUS_QUEUED, None).await?;
+    pump(&app);
+    downloads.get(&id).await
+}
+
+/// Stop a download and delete its partial file; completed files are left in place.
+#[tauri::command]
+pub async fn download_cancel(
+    app: AppHandle,
+    downloads: State<'_, Downloads>,
+    id: String,
+) -> AppResult<()> {
+    let download = downloads.get(&id).await?;
+    downloads.stop(&id);
+    let lookup = id.clone();
+    downloads
+        .db
+        .run(move |conn| {
+            conn.execute("DELETE FROM downloads WHERE id = ?1", [lookup])?;
+            Ok(())
+        })
+        .await?;
+    let _ = tokio::fs::remove_file(download.part_path()).await;
+    pump(&app);
+    Ok(())
+}
+
+/// Every known download, newest first.
+#[tauri::command]
+pub async fn list_downloads(downloads: State<'_, Downloads>) -> AppResult<Vec<Download>> {
+    downloads
+        .db
+        .run(|conn| {
+            Ok(conn
+                .prepare("SELECT * FROM downloads ORDER BY created_at DESC")?
+                .query_map([], Download::from_row)?
+                .collect::<rusqlite::Result<_>>()?)
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory under the temp dir; canonical, since macOS links /var to /private/var
+    fn scratch() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("downloads-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::canonicalize(dir).unwrap()
+    }
+
+    #[test]
+    fn content_range_reads_the_first_byte_and_length() {
+        assert_eq!(content_range("bytes 100-199/200"), Some((100, Some(200))));
+        assert_eq!(content_range("bytes 0-0/*"), Some((0, None)));
+        assert_eq!(content_range(" bytes 5-9/10 "), Some((5, Some(10))));
+    }
+
+    #[test]
+    fn content_range_rejects_malformed_values() {
+        for value in [
+            "",
+            "bytes */200",
+            "bytes 100-/200",
+            "bytes 200-100/300",
+            "bytes 100-199/150",
+            "bytes -5-10/20",
+            "items 0-9/10",
+            "bytes 0-9",
+        ] {
+            assert_eq!(content_range(value), None, "{value}");
+        }
+    }
+
+    #[test]
+    fn destinations_stay_in_the_downloads_folder() {
+        let root = scratch();
+        let downloads = root.join("Downloads");
+        std::fs::create_dir_all(downloads.join("plans")).unwrap();
+
+        let inside = check_destination(&downloads.join("a.pdf"), &downloads).unwrap();
+        assert_eq!(inside.file_name().unwrap(), "a.pdf");
+        check_destination(&downloads.join("plans/b.pdf"), &downloads).unwrap();
+
+        for outside in [
+            root.join("a.pdf"),
+            downloads.join("../a.pdf"),
+            downloads.join("plans/../../a.pdf"),
+            downloads.join("missing/a.pdf"),
+            downloads.join(".."),
+            PathBuf::from("a.pdf"),
+        ] {
+            assert!(
+                check_destination(&outside, &downloads).is_err(),
+                "{}",
+                outside.display()
+            );
+        }
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_symlinked_folder_cannot_lead_out_of_downloads() {
+        let root = scratch();
+        let downloads = root.join("Downloads");
+        std::fs::create_dir(&downloads).unwrap();
+        std::os::unix::fs::symlink(&root, downloads.join("out")).unwrap();
+        assert!(check_destination(&downloads.join("out/a.pdf"), &downloads).is_err());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn an_existing_file_or_link_counts_as_taken() {
+        let dir = scratch();
+        let file = dir.join("a.pdf");
+        assert!(!taken(&file));
+        std::fs::write(&file, b"x").unwrap();
+        assert!(taken(&file));
+        #[cfg(unix)]
+        {
+            let link = dir.join("gone.pdf");
+            std::os::unix::fs::symlink(dir.join("missing.pdf"), &link).unwrap();
+            assert!(taken(&link));
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,139 @@
+//! Dragging files out of the app into Explorer, Finder, or an email draft.
+//!
+//! The webview cannot start an OS drag carrying a real file, so the frontend calls
+//! `start_native_drag` from its `mousedown`/`dragstart` handler and the drag is started
+//! natively while the button is still held.
+
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use drag::{DragItem, DragResult, Image, Options};
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use pdfium_render::prelude::PdfRenderConfig;
+use serde::Serialize;
+use tauri::{Emitter, Manager, WebviewWindow};
+
+use crate::error::{AppError, AppResult};
+use crate::pdf::PdfEngine;
+use crate::thumbnails;
+
+/// Emitted to the dragging window with a [`DragEnded`] when the drag finishes.
+pub const DRAG_ENDED_EVENT: &str = "native-drag:ended";
+
+// Longest side of the image that follows the cursor
+const PREVIEW_SIZE: u32 = 128;
+
+/// Payload for `native-drag:ended`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DragEnded {
+    pub path: PathBuf,
+    /// False when the user let go somewhere that did not accept the file.
+    pub dropped: bool,
+}
+
+fn encode_png(image: &DynamicImage) -> AppResult<Vec<u8>> {
+    let mut bytes = Cursor::new(Vec::new());
+    image
+        .write_to(&mut bytes, ImageFormat::Png)
+        .map_err(|e| AppError::Internal(format!("cannot encode drag image: {e}")))?;
+    Ok(bytes.into_inner())
+}
+
+// The first page for PDFs, the picture itself for images, and otherwise the app icon
+fn preview(window: &WebviewWindow, path: &Path) -> AppResult<Vec<u8>> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let image = if extension == "pdf" {
+        window
+            .try_state::<PdfEngine>()
+            .and_then(|engine| engine.pdfium().ok())
+            .and_then(|pdfium| {
+                let document = pdfium.load_pdf_from_file(path, None).ok()?;
+                let page = document.pages().first().ok()?;
+                let config = PdfRenderConfig::new()
+                    .set_target_width(PREVIEW_SIZE as i32)
+                    .set_maximum_height(PREVIEW_SIZE as i32);
+                let bitmap = page.render_with_config(&config).ok()?;
+                Some(bitmap.as_image())
+            })
+    } else {
+        thumbnails::load_upright(path)
+            .ok()
+            .map(|image| image.thumbnail(PREVIEW_SIZE, PREVIEW_SIZE))
+    };
+    let image = match image {
+        Some(image) => image,
+        None => {
+            let icon = window
+                .app_handle()
+                .default_window_icon()
+                .ok_or_else(|| AppError::Internal("the app has no icon".into()))?;
+            let rgba = RgbaImage::from_raw(icon.width(), icon.height(), icon.rgba().to_vec())
+                .ok_or_else(|| AppError::Internal("the app icon is malformed".into()))?;
+            DynamicImage::ImageRgba8(rgba).thumbnail(PREVIEW_SIZE, PREVIEW_SIZE)
+        }
+    };
+    encode_png(&image)
+}
+
+/// Start an OS file drag of `path` from the calling window.
+///
+/// Call while the mouse button is down; the drag follows the cursor until it is released
+/// and `native-drag:ended` reports where it went.
+#[tauri::command]
+pub async fn start_native_drag(window: WebviewWindow, path: PathBuf) -> AppResult<()> {
+    let path = std::fs::canonicalize(&path)?;
+    if !path.is_file() {
+        return Err(AppError::InvalidInput(format!(
+            "{} is not a file",
+            path.display()
+        )));
+    }
+    let preview = {
+        let (window, path) = (window.clone(), path.clone());
+        tauri::async_runtime::spawn_blocking(move || preview(&window, &path)).await??
+    };
+
+    // Drag sessions belong to the UI thread on every platform
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let target = window.clone();
+    window.run_on_main_thread(move || {
+        let label = target.label().to_string();
+        let app = target.app_handle().clone();
+        let dragged = path.clone();
+        let on_drop = move |result: DragResult, _| {
+            let dropped = matches!(result, DragResult::Dropped);
+            tracing::debug!(path = %dragged.display(), dropped, "native drag ended");
+            let _ = app.emit_to(
+                label.as_str(),
+                DRAG_ENDED_EVENT,
+                DragEnded {
+                    path: dragged.clone(),
+                    dropped,
+                },
+            );
+        };
+        let item = DragItem::Files(vec![path]);
+        let image = Image::Raw(preview);
+
+        #[cfg(target_os = "linux")]
+        let result = target.gtk_window().map_err(AppError::from).and_then(|gtk| {
+            Ok(drag::start_drag(
+                &gtk,
+                item,
+                image,
+                on_drop,
+                Options::default(),
+            )?)
+        });
+        #[cfg(not(target_os = "linux"))]
+        let result = drag::start_drag(&target, item, image, on_drop, Options::default())
+            .map_err(AppError::from);
+        let _ = tx.send(result);
+    })?;
+    rx.await
+        .map_err(|_| AppError::Internal("the drag was never started".into()))?
+}
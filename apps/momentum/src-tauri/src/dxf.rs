@@ -0,0 +1,579 @@
+//! DXF drawings read into plain geometry for takeoff.
+//!
+//! A DXF file is a flat list of group code and value pairs. The reader keeps the layer
+//! table and the model-space entities that carry takeoff geometry: lines, polylines, arcs,
+//! and circles become polylines with their length and, when closed, area; dimensions keep
+//! their measured value; text keeps its position for labels; block inserts are listed by
+//! block name for counting. Curves are flattened to short chords, but lengths of arcs are
+//! exact. Paper-space layouts and block definitions are skipped, and coordinates stay in
+//! drawing units as the header declares them.
+
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::error::{AppError, AppResult};
+use crate::takeoff::signed_area;
+
+// Largest angle one chord of a flattened curve spans
+const MAX_CHORD_ANGLE: f64 = TAU / 72.0;
+const BINARY_SENTINEL: &[u8] = b"AutoCAD Binary DXF";
+
+/// A layer from the drawing's layer table.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Layer {
+    pub name: String,
+    /// AutoCAD color index.
+    pub color: i32,
+    /// Off or frozen layers are usually construction lines and notes.
+    pub visible: bool,
+    pub entity_count: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Source {
+    Line,
+    Polyline,
+    Arc,
+    Circle,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Polyline {
+    pub layer: String,
+    pub source: Source,
+    pub points: Vec<[f64; 2]>,
+    pub closed: bool,
+    pub length: f64,
+    /// Enclosed area, for closed shapes.
+    pub area: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dimension {
+    pub layer: String,
+    /// Value the dimension measures, as the drawing computed it.
+    pub measurement: Option<f64>,
+    /// Override text, when the drafter typed one over the measured value.
+    pub text: Option<String>,
+    /// The two points measured between, for linear and aligned dimensions.
+    pub points: Option<[[f64; 2]; 2]>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Label {
+    pub layer: String,
+    pub text: String,
+    pub position: [f64; 2],
+    pub height: f64,
+}
+
+/// One placement of a block, such as a door or fixture symbol.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockInsert {
+    pub layer: String,
+    pub block: String,
+    pub position: [f64; 2],
+    /// Degrees counterclockwise.
+    pub rotation: f64,
+    pub scale: [f64; 2],
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bounds {
+    pub min: [f64; 2],
+    pub max: [f64; 2],
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Drawing {
+    /// Unit symbol from the header, such as `ft` or `mm`; `None` when unitless.
+    pub units: Option<&'static str>,
+    pub layers: Vec<Layer>,
+    pub polylines: Vec<Polyline>,
+    pub dimensions: Vec<Dimension>,
+    pub labels: Vec<Label>,
+    pub inserts: Vec<BlockInsert>,
+    pub bounds: Option<Bounds>,
+    /// Entity types present but not read, with how many of each.
+    pub skipped: HashMap<String, usize>,
+}
+
+type Pair = (i32, String);
+
+fn pairs(bytes: &[u8]) -> AppResult<Vec<Pair>> {
+    if bytes.starts_with(BINARY_SENTINEL) {
+        return Err(AppError::InvalidInput(
+            "binary DXF is not supported; save the drawing as ASCII DXF".into(),
+        ));
+    }
+    // Files from older versions are in the Windows code page, which only text values use
+    let text = String::from_utf8_lossy(bytes);
+    let mut lines = text.lines();
+    let mut pairs = Vec::new();
+    while let Some(code) = lines.next() {
+        let code = code.trim();
+        if code.is_empty() {
+            continue;
+        }
+        let code: i32 = code.parse().map_err(|_| {
+            AppError::InvalidInput(format!(
+                "not a DXF file: expected a group code, found {code}"
+            ))
+        })?;
+        let value = lines.next().unwrap_or_default().trim_end().to_string();
+        pairs.push((code, value));
+    }
+    Ok(pairs)
+}
+
+// One entity or table entry: its type and the pairs up to the next
+struct Record<'a> {
+    kind: &'a str,
+    pairs: &'a [Pair],
+}
+
+impl Record<'_> {
+    fn text(&self, code: i32) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(c, _)| *c == code)
+            .map(|(_, v)| v.trim_start())
+    }
+
+    fn number(&self, code: i32) -> Option<f64> {
+        self.text(code)
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|n: &f64| n.is_finite())
+    }
+
+    fn int(&self, code: i32) -> Option<i32> {
+        self.text(code).and_then(|v| v.trim().parse().ok())
+    }
+
+    fn point(&self, x: i32, y: i32) -> Option<[f64; 2]> {
+        Some([self.number(x)?, self.number(y)?])
+    }
+
+    fn layer(&self) -> String {
+        self.text(8).unwrap_or("0").to_string()
+    }
+
+    // Entities drawn with an extrusion of -Z, as mirrored arcs are, have x flipped
+    fn mirrored(&self) -> bool {
+        self.number(230).is_some_and(|z| z < 0.0)
+    }
+}
+
+// Split a section's pairs into records at each code 0
+fn records(pairs: &[Pair]) -> Vec<Record<'_>> {
+    let mut records = Vec::new();
+    let mut start: Option<usize> = None;
+    for (index, (code, _)) in pairs.iter().enumerate() {
+        if *code == 0 {
+            if let Some(begin) = start {
+                records.push(Record {
+                    kind: &pairs[begin].1,
+                    pairs: &pairs[begin + 1..index],
+                });
+            }
+            start = Some(index);
+        }
+    }
+    if let Some(begin) = start {
+        records.push(Record {
+            kind: &pairs[begin].1,
+            pairs: &pairs[begin + 1..],
+        });
+    }
+    records
+}
+
+// Sections by name, each the pairs between SECTION and ENDSEC
+fn sections(pairs: &[Pair]) -> HashMap<&str, &[Pair]> {
+    let mut sections = HashMap::new();
+    let mut index = 0;
+    while index < pairs.len() {
+        if pairs[index].0 == 0 && pairs[index].1 == "SECTION" {
+            let name = pairs.get(index + 1).map(|(_, v)| v.as_str()).unwrap_or("");
+            let end = pairs[index..]
+                .iter()
+                .position(|pair| pair.0 == 0 && pair.1 == "ENDSEC")
+                .map_or(pairs.len(), |offset| index + offset);
+            sections.insert(name, &pairs[(index + 2).min(end)..end]);
+            index = end;
+        }
+        index += 1;
+    }
+    sections
+}
+
+fn units(header: &[Pair]) -> Option<&'static str> {
+    let at = header
+        .iter()
+        .position(|(c, v)| *c == 9 && v == "$INSUNITS")?;
+    let code: i32 = header.get(at + 1)?.1.trim().parse().ok()?;
+    Some(match code {
+        1 => "in",
+        2 => "ft",
+        3 => "mi",
+        4 => "mm",
+        5 => "cm",
+        6 => "m",
+        7 => "km",
+        10 => "yd",
+        _ => return None,
+    })
+}
+
+// Points along an arc from `start` to `end` radians, counterclockwise
+fn arc_points(center: [f64; 2], radius: f64, start: f64, mut end: f64) -> Vec<[f64; 2]> {
+    while end <= start {
+        end += TAU;
+    }
+    let steps = ((end - start) / MAX_CHORD_ANGLE).ceil().max(1.0) as usize;
+    (0..=steps)
+        .map(|step| {
+            let angle = start + (end - start) * step as f64 / steps as f64;
+            [
+                center[0] + radius * angle.cos(),
+                center[1] + radius * angle.sin(),
+            ]
+        })
+        .collect()
+}
+
+// A polyline segment that bulges into an arc: the points after `from`, its length, and the
+// area between arc and chord, positive when it bulges to the right
+fn bulge_segment(from: [f64; 2], to: [f64; 2], bulge: f64) -> (Vec<[f64; 2]>, f64, f64) {
+    let chord = (to[0] - from[0]).hypot(to[1] - from[1]);
+    if bulge.abs() < 1e-12 || chord == 0.0 {
+        return (vec![to], chord, 0.0);
+    }
+    // The bulge is the tangent of a quarter of the included angle
+    let angle = 4.0 * bulge.atan();
+    let radius = chord / (2.0 * (angle / 2.0).sin().abs());
+    let mid = [(from[0] + to[0]) / 2.0, (from[1] + to[1]) / 2.0];
+    let offset = radius * (angle / 2.0).cos();
+    let normal = [-(to[1] - from[1]) / chord, (to[0] - from[0]) / chord];
+    let sign = if bulge > 0.0 { 1.0 } else { -1.0 };
+    let center = [
+        mid[0] + sign * normal[0] * offset,
+        mid[1] + sign * normal[1] * offset,
+    ];
+    let start = (from[1] - center[1]).atan2(from[0] - center[0]);
+    let steps = (angle.abs() / MAX_CHORD_ANGLE).ceil().max(1.0) as usize;
+    let points = (1..=steps)
+        .map(|step| {
+            let a = start + angle * step as f64 / steps as f64;
+            [center[0] + radius * a.cos(), center[1] + radius * a.sin()]
+        })
+        .collect();
+    let swept = angle.abs();
+    let segment = radius * radius / 2.0 * (swept - swept.sin());
+    (points, radius * swept, segment * sign)
+}
+
+// Vertices with the bulge leading to the next, flattened into a polyline
+fn polyline(layer: String, vertices: &[([f64; 2], f64)], closed: bool) -> Option<Polyline> {
+    let first = vertices.first()?;
+    let mut points = vec![first.0];
+    let mut length = 0.0;
+    let mut bulges = 0.0;
+    let segments = if closed {
+        vertices.len()
+    } else {
+        vertices.len() - 1
+    };
+    for index in 0..segments {
+        let (from, bulge) = vertices[index];
+        let to = vertices[(index + 1) % vertices.len()].0;
+        let (more, segment, area) = bulge_segment(from, to, bulge);
+        points.extend(more);
+        length += segment;
+        bulges += area;
+    }
+    if closed {
+        points.pop();
+    }
+    Some(Polyline {
+        layer,
+        source: Source::Polyline,
+        // Exact, from the vertices and the arcs between them rather than the flattened points
+        area: closed.then(|| {
+            (signed_area(&vertices.iter().map(|v| v.0).collect::<Vec<_>>()) + bulges).abs()
+        }),
+        points,
+        closed,
+        length,
+    })
+}
+
+fn mirror(point: [f64; 2], mirrored: bool) -> [f64; 2] {
+    if mirrored {
+        [-point[0], point[1]]
+    } else {
+        point
+    }
+}
+
+fn lwpolyline(record: &Record) -> Option<Polyline> {
+    let mirrored = record.mirrored();
+    let mut vertices: Vec<([f64; 2], f64)> = Vec::new();
+    let mut x = None;
+    for (code, value) in record.pairs {
+        let value = value.trim().parse::<f64>().ok();
+        match code {
+            10 => x = value,
+            20 => {
+                if let (Some(x), Some(y)) = (x.take(), value) {
+                    vertices.push((mirror([x, y], mirrored), 0.0));
+                }
+            }
+            42 => {
+                if let (Some(last), Some(bulge)) = (vertices.last_mut(), value) {
+                    last.1 = if mirrored { -bulge } else { bulge };
+                }
+            }
+            _ => {}
+        }
+    }
+    let closed = record.int(70).unwrap_or(0) & 1 == 1;
+    polyline(record.layer(), &vertices, closed)
+}
+
+fn arc(record: &Record, full: bool) -> Option<Polyline> {
+    let mirrored = record.mirrored();
+    let center = mirror(record.point(10, 20)?, mirrored);
+    let radius = record.number(40).filter(|r| *r > 0.0)?;
+    let layer = record.layer();
+    if full {
+        let mut points = arc_points(center, radius, 0.0, TAU);
+        points.pop();
+        return Some(Polyline {
+            layer,
+            source: Source::Circle,
+            area: Some(std::f64::consts::PI * radius * radius),
+            points,
+            closed: true,
+            length: TAU * radius,
+        });
+    }
+    let (mut start, mut end) = (
+        record.number(50)?.to_radians(),
+        record.number(51)?.to_radians(),
+    );
+    if mirrored {
+        (start, end) = (std::f64::consts::PI - end, std::f64::consts::PI - start);
+    }
+    let points = arc_points(center, radius, start, end);
+    let mut sweep = end - start;
+    while sweep <= 0.0 {
+        sweep += TAU;
+    }
+    Some(Polyline {
+        layer,
+        source: Source::Arc,
+        points,
+        closed: false,
+        length: radius * sweep,
+        area: None,
+    })
+}
+
+fn parse(bytes: &[u8]) -> AppResult<Drawing> {
+    let pairs = pairs(bytes)?;
+    let sections = sections(&pairs);
+    let Some(entities) = sections.get("ENTITIES") else {
+        return Err(AppError::InvalidInput(
+            "not a DXF drawing: it has no ENTITIES section".into(),
+        ));
+    };
+
+    let mut layers: Vec<Layer> = sections
+        .get("TABLES")
+        .map(|tables| records(tables))
+        .unwrap_or_default()
+        .iter()
+        .filter(|record| record.kind == "LAYER")
+        .filter_map(|record| {
+            let color = record.int(62).unwrap_or(7);
+            Some(Layer {
+                name: record.text(2)?.to_string(),
+                visible: color >= 0 && record.int(70).unwrap_or(0) & 1 == 0,
+                color: color.abs(),
+                entity_count: 0,
+            })
+        })
+        .collect();
+
+    let mut drawing = Drawing {
+        units: sections.get("HEADER").and_then(|header| units(header)),
+        layers: Vec::new(),
+        polylines: Vec::new(),
+        dimensions: Vec::new(),
+        labels: Vec::new(),
+        inserts: Vec::new(),
+        bounds: None,
+        skipped: HashMap::new(),
+    };
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let records = records(entities);
+    let mut index = 0;
+    while index < records.len() {
+        let record = &records[index];
+        index += 1;
+        if record.int(67) == Some(1) {
+            continue;
+        }
+        *counts.entry(record.layer()).or_default() += 1;
+        match record.kind {
+            "LINE" => {
+                let (Some(from), Some(to)) = (record.point(10, 20), record.point(11, 21)) else {
+                    continue;
+                };
+                drawing.polylines.push(Polyline {
+                    layer: record.layer(),
+                    source: Source::Line,
+                    length: (to[0] - from[0]).hypot(to[1] - from[1]),
+                    points: vec![from, to],
+                    closed: false,
+                    area: None,
+                });
+            }
+            "LWPOLYLINE" => drawing.polylines.extend(lwpolyline(record)),
+            // The old form: vertices follow as their own entities until SEQEND
+            "POLYLINE" => {
+                let mirrored = record.mirrored();
+                let mut vertices = Vec::new();
+                while let Some(vertex) = records.get(index).filter(|r| r.kind == "VERTEX") {
+                    index += 1;
+                    if let Some(point) = vertex.point(10, 20) {
+                        let bulge = vertex.number(42).unwrap_or(0.0);
+                        vertices.push((
+                            mirror(point, mirrored),
+                            if mirrored { -bulge } else { bulge },
+                        ));
+                    }
+                }
+                if records.get(index).is_some_and(|r| r.kind == "SEQEND") {
+                    index += 1;
+                }
+                let closed = record.int(70).unwrap_or(0) & 1 == 1;
+                drawing
+                    .polylines
+                    .extend(polyline(record.layer(), &vertices, closed));
+            }
+            "ARC" => drawing.polylines.extend(arc(record, false)),
+            "CIRCLE" => drawing.polylines.extend(arc(record, true)),
+            "DIMENSION" => {
+                // `<>` in override text stands for the measured value
+                let text = record
+                    .text(1)
+                    .filter(|text| !text.is_empty() && *text != "<>")
+                    .map(str::to_string);
+                drawing.dimensions.push(Dimension {
+                    layer: record.layer(),
+                    measurement: record.number(42),
+                    text,
+                    points: record
+                        .point(13, 23)
+                        .zip(record.point(14, 24))
+                        .map(|(a, b)| [a, b]),
+                });
+            }
+            "TEXT" | "MTEXT" => {
+                let (Some(text), Some(position)) = (record.text(1), record.point(10, 20)) else {
+                    continue;
+                };
+                drawing.labels.push(Label {
+                    layer: record.layer(),
+                    text: text.to_string(),
+                    position,
+                    height: record.number(40).unwrap_or(0.0),
+                });
+            }
+            "INSERT" => {
+                let (Some(block), Some(position)) = (record.text(2), record.point(10, 20)) else {
+                    continue;
+                };
+                drawing.inserts.push(BlockInsert {
+                    layer: record.layer(),
+                    block: block.to_string(),
+                    position,
+                    rotation: record.number(50).unwrap_or(0.0),
+                    scale: [
+                        record.number(41).unwrap_or(1.0),
+                        record.number(42).unwrap_or(1.0),
+                    ],
+                });
+            }
+            other => *drawing.skipped.entry(other.to_string()).or_default() += 1,
+        }
+    }
+
+    // Entities may sit on layers the table never declared
+    for (name, count) in counts {
+        match layers.iter_mut().find(|layer| layer.name == name) {
+            Some(layer) => layer.entity_count = count,
+            None => layers.push(Layer {
+                name,
+                color: 7,
+                visible: true,
+                entity_count: count,
+            }),
+        }
+    }
+    layers.sort_by(|a, b| a.name.cmp(&b.name));
+    drawing.layers = layers;
+
+    let points = drawing
+        .polylines
+        .iter()
+        .flat_map(|polyline| polyline.points.iter())
+        .chain(drawing.inserts.iter().map(|insert| &insert.position))
+        .chain(drawing.labels.iter().map(|label| &label.position));
+    drawing.bounds = points.fold(None, |bounds: Option<Bounds>, point| {
+        Some(match bounds {
+            None => Bounds {
+                min: *point,
+                max: *point,
+            },
+            Some(Bounds { min, max }) => Bounds {
+                min: [min[0].min(point[0]), min[1].min(point[1])],
+                max: [max[0].max(point[0]), max[1].max(point[1])],
+            },
+        })
+    });
+    Ok(drawing)
+}
+
+pub fn read(path: &Path) -> AppResult<Drawing> {
+    parse(&std::fs::read(path)?)
+}
+
+/// Read a DXF drawing's layers, polylines, dimensions, labels, and block inserts.
+#[tauri::command]
+pub async fn dxf_import(path: PathBuf) -> AppResult<Drawing> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let drawing = read(&path)?;
+        tracing::debug!(
+            path = %path.display(),
+            polylines = drawing.polylines.len(),
+            dimensions = drawing.dimensions.len(),
+            "DXF read"
+        );
+        Ok(drawing)
+    })
+    .await?
+}
@@ -0,0 +1,218 @@
+//! Pre-filled drafts in the user's own mail app: Simple MAPI on Windows, Apple Mail through
+//! AppleScript on macOS, and `xdg-email` on Linux.
+
+use super::{DeliveryStatus, EmailReport};
+use crate::error::AppResult;
+
+/// Open a draft of `report` for the user to review and send.
+///
+/// Returns [`DeliveryStatus::Sent`] or [`DeliveryStatus::Cancelled`] where the mail app
+/// reports back, which only Simple MAPI does, and [`DeliveryStatus::DraftOpened`] otherwise.
+pub(crate) fn open(report: &EmailReport) -> AppResult<DeliveryStatus> {
+    platform::open(report)
+}
+
+#[cfg(windows)]
+mod platform {
+    use windows::core::{s, w, PWSTR};
+    use windows::Win32::Foundation::FreeLibrary;
+    use windows::Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryW};
+    use windows::Win32::System::Mapi::{
+        MapiFileDescW, MapiMessageW, MapiRecipDescW, LPMAPISENDMAILW, MAPI_BCC, MAPI_CC,
+        MAPI_DIALOG, MAPI_E_USER_ABORT, MAPI_LOGON_UI, MAPI_TO, SUCCESS_SUCCESS,
+    };
+
+    use super::super::{DeliveryStatus, EmailReport};
+    use crate::error::{AppError, AppResult};
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain([0]).collect()
+    }
+
+    pub fn open(report: &EmailReport) -> AppResult<DeliveryStatus> {
+        // Every string the message points at lives until the call returns
+        let mut subject = wide(&report.subject);
+        let mut body = wide(&report.body);
+        let mut addresses: Vec<(u32, Vec<u16>, Vec<u16>)> = [
+            (MAPI_TO, &report.to),
+            (MAPI_CC, &report.cc),
+            (MAPI_BCC, &report.bcc),
+        ]
+        .into_iter()
+        .flat_map(|(class, list)| {
+            list.iter()
+                .map(move |address| (class, wide(address), wide(&format!("SMTP:{address}"))))
+        })
+        .collect();
+        let mut recipients: Vec<MapiRecipDescW> = addresses
+            .iter_mut()
+            .map(|(class, name, address)| MapiRecipDescW {
+                ulRecipClass: *class,
+                lpszName: PWSTR(name.as_mut_ptr()),
+                lpszAddress: PWSTR(address.as_mut_ptr()),
+                ..Default::default()
+            })
+            .collect();
+        let mut paths: Vec<Vec<u16>> = report
+            .attachments
+            .iter()
+            .map(|path| wide(&path.to_string_lossy()))
+            .collect();
+        let mut files: Vec<MapiFileDescW> = paths
+            .iter_mut()
+            .map(|path| MapiFileDescW {
+                // Not placed in the body text
+                nPosition: u32::MAX,
+                lpszPathName: PWSTR(path.as_mut_ptr()),
+                ..Default::default()
+            })
+            .collect();
+        let message = MapiMessageW {
+            lpszSubject: PWSTR(subject.as_mut_ptr()),
+            lpszNoteText: PWSTR(body.as_mut_ptr()),
+            nRecipCount: recipients.len() as u32,
+            lpRecips: recipients.as_mut_ptr(),
+            nFileCount: files.len() as u32,
+            lpFiles: files.as_mut_ptr(),
+            ..Default::default()
+        };
+
+        // SAFETY: mapi32.dll is the system MAPI stub, which forwards to the default mail app
+        let library = unsafe { LoadLibraryW(w!("mapi32.dll")) }
+            .map_err(|e| AppError::Email(format!("no mail app is set up: {e}")))?;
+        // SAFETY: MAPISendMailW has the LPMAPISENDMAILW signature
+        let send: LPMAPISENDMAILW =
+            unsafe { std::mem::transmute(GetProcAddress(library, s!("MAPISendMailW"))) };
+        let status = match send {
+            // SAFETY: `message` and everything it points to outlive the call, which blocks
+            // until the user sends or closes the draft
+            Some(send) => unsafe { send(0, 0, &message, MAPI_DIALOG | MAPI_LOGON_UI, 0) },
+            None => u32::MAX,
+        };
+        // SAFETY: `library` was loaded above and nothing from it is used after this
+        let _ = unsafe { FreeLibrary(library) };
+        match status {
+            SUCCESS_SUCCESS => Ok(DeliveryStatus::Sent),
+            MAPI_E_USER_ABORT => Ok(DeliveryStatus::Cancelled),
+            u32::MAX => Err(AppError::Email(
+                "the default mail app does not support Simple MAPI".into(),
+            )),
+            code => Err(AppError::Email(format!(
+                "the mail app failed with MAPI error {code}"
+            ))),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::process::Command;
+
+    use super::super::{DeliveryStatus, EmailReport};
+    use crate::error::{AppError, AppResult};
+
+    // Values are passed as arguments so they never need AppleScript quoting; each after
+    // the subject and body is tagged with what it is
+    const SCRIPT: &[&str] = &[
+        "on run argv",
+        "tell application \"Mail\"",
+        "set msg to make new outgoing message with properties \
+         {subject:(item 1 of argv), content:(item 2 of argv) & return, visible:true}",
+        "tell msg",
+        "repeat with i from 3 to count of argv",
+        "set arg to item i of argv",
+        "if arg starts with \"to:\" then",
+        "make new to recipient at end of to recipients \
+         with properties {address:(text 4 thru -1 of arg)}",
+        "else if arg starts with \"cc:\" then",
+        "make new cc recipient at end of cc recipients \
+         with properties {address:(text 4 thru -1 of arg)}",
+        "else if arg starts with \"bcc:\" then",
+        "make new bcc recipient at end of bcc recipients \
+         with properties {address:(text 5 thru -1 of arg)}",
+        "else if arg starts with \"file:\" then",
+        "tell content to make new attachment \
+         with properties {file name:(POSIX file (text 6 thru -1 of arg))} \
+         at after last paragraph",
+        "end if",
+        "end repeat",
+        "end tell",
+        "activate",
+        "end tell",
+        "end run",
+    ];
+
+    pub fn open(report: &EmailReport) -> AppResult<DeliveryStatus> {
+        let mut command = Command::new("osascript");
+        for line in SCRIPT {
+            command.args(["-e", line]);
+        }
+        command.arg(&report.subject).arg(&report.body);
+        for (tag, list) in [("to", &report.to), ("cc", &report.cc), ("bcc", &report.bcc)] {
+            command.args(list.iter().map(|address| format!("{tag}:{address}")));
+        }
+        command.args(
+            report
+                .attachments
+                .iter()
+                .map(|path| format!("file:{}", path.display())),
+        );
+        let output = command.output()?;
+        if !output.status.success() {
+            return Err(AppError::Email(format!(
+                "Mail could not open the draft: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(DeliveryStatus::DraftOpened)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::process::Command;
+
+    use super::super::{DeliveryStatus, EmailReport};
+    use crate::error::{AppError, AppResult};
+
+    // Whether attachments arrive depends on the mail app; Thunderbird and Evolution take them
+    pub fn open(report: &EmailReport) -> AppResult<DeliveryStatus> {
+        let mut command = Command::new("xdg-email");
+        command
+            .arg("--utf8")
+            .args(["--subject", &report.subject])
+            .args(["--body", &report.body]);
+        for address in &report.cc {
+            command.args(["--cc", address]);
+        }
+        for address in &report.bcc {
+            command.args(["--bcc", address]);
+        }
+        for path in &report.attachments {
+            command.arg("--attach").arg(path);
+        }
+        command.args(&report.to);
+        let output = command
+            .output()
+            .map_err(|e| AppError::Email(format!("cannot run xdg-email: {e}")))?;
+        if !output.status.success() {
+            return Err(AppError::Email(format!(
+                "xdg-email failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(DeliveryStatus::DraftOpened)
+    }
+}
+
+#[cfg(mobile)]
+mod platform {
+    use super::super::{DeliveryStatus, EmailReport};
+    use crate::error::{AppError, AppResult};
+
+    pub fn open(_report: &EmailReport) -> AppResult<DeliveryStatus> {
+        Err(AppError::InvalidInput(
+            "drafts are opened through the share sheet on mobile".into(),
+        ))
+    }
+}
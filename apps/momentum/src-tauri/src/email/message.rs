@@ -0,0 +1,247 @@
+//! MIME rendering of an outgoing message (RFC 5322, 2045, and 2231).
+//!
+//! The output is plain 7-bit ASCII with CRLF line endings: the body and attachments are
+//! base64 and non-ASCII header text is encoded-word, so any server accepts it without
+//! `8BITMIME` or `SMTPUTF8`.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+// attr-char from RFC 2231, which leaves these unescaped
+const ATTR_CHAR: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
+const LINE_WIDTH: usize = 76;
+
+/// A file carried by the message.
+pub(crate) struct Part {
+    pub file_name: String,
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Everything that goes into the rendered message; Bcc recipients are only on the envelope.
+pub(crate) struct Message<'a> {
+    pub from_address: &'a str,
+    pub from_name: Option<&'a str>,
+    pub to: &'a [String],
+    pub cc: &'a [String],
+    pub subject: &'a str,
+    pub body: &'a str,
+    pub attachments: Vec<Part>,
+}
+
+// Text that can go into a header as it is: printable ASCII, so no line break can end the
+// header early and start another
+fn is_plain(text: &str) -> bool {
+    text.chars().all(|c| c.is_ascii() && !c.is_ascii_control())
+}
+
+fn encode_word(text: &str) -> String {
+    if is_plain(text) {
+        return text.to_string();
+    }
+    format!("=?UTF-8?B?{}?=", STANDARD.encode(text))
+}
+
+fn mailbox(address: &str, name: Option<&str>) -> String {
+    match name.filter(|name| !name.trim().is_empty()) {
+        Some(name) if is_plain(name) => {
+            let escaped = name.replace('\\', "\\\\").replace('"', "\\\"");
+            format!("\"{escaped}\" <{address}>")
+        }
+        Some(name) => format!("{} <{address}>", encode_word(name)),
+        None => address.to_string(),
+    }
+}
+
+fn push_base64(out: &mut String, bytes: &[u8]) {
+    let encoded = STANDARD.encode(bytes);
+    for line in encoded.as_bytes().chunks(LINE_WIDTH) {
+        // Base64 output is ASCII, so every chunk is valid UTF-8
+        out.push_str(std::str::from_utf8(line).unwrap_or_default());
+        out.push_str("\r\n");
+    }
+}
+
+// RFC 2231 extended parameter for names that are not plain ASCII, quoted otherwise
+fn file_name_param(name: &str) -> String {
+    if is_plain(name) {
+        let escaped = name.replace('\\', "\\\\").replace('"', "\\\"");
+        format!("filename=\"{escaped}\"")
+    } else {
+        format!("filename*=UTF-8''{}", utf8_percent_encode(name, ATTR_CHAR))
+    }
+}
+
+/// The domain of `address`, for the `Message-ID`.
+fn domain(address: &str) -> &str {
+    address
+        .rsplit_once('@')
+        .map(|(_, domain)| domain)
+        .filter(|domain| !domain.is_empty())
+        .unwrap_or("localhost")
+}
+
+/// Render `message`; returns its `Message-ID` and the bytes to send.
+pub(crate) fn render(message: &Message<'_>) -> (String, Vec<u8>) {
+    let message_id = format!(
+        "<{}@{}>",
+        uuid::Uuid::new_v4(),
+        domain(message.from_address)
+    );
+    let date = jiff::Zoned::now().strftime("%a, %d %b %Y %H:%M:%S %z");
+    let mut out = String::new();
+    out.push_str(&format!("Date: {date}\r\n"));
+    out.push_str(&format!(
+        "From: {}\r\n",
+        mailbox(message.from_address, message.from_name)
+    ));
+    if !message.to.is_empty() {
+        out.push_str(&format!("To: {}\r\n", message.to.join(",\r\n ")));
+    }
+    if !message.cc.is_empty() {
+        out.push_str(&format!("Cc: {}\r\n", message.cc.join(",\r\n ")));
+    }
+    out.push_str(&format!("Subject: {}\r\n", encode_word(message.subject)));
+    out.push_str(&format!("Message-ID: {message_id}\r\n"));
+    out.push_str(&format!(
+        "X-Mailer: Momentum/{}\r\nMIME-Version: 1.0\r\n",
+        env!("CARGO_PKG_VERSION")
+    ));
+
+    let body = message.body.replace("\r\n", "\n").replace('\n', "\r\n");
+    let text_headers = "Content-Type: text/plain; charset=utf-8\r\n\
+                        Content-Transfer-Encoding: base64\r\n";
+    if message.attachments.is_empty() {
+        out.push_str(text_headers);
+        out.push_str("\r\n");
+        push_base64(&mut out, body.as_bytes());
+        return (message_id, out.into_bytes());
+    }
+
+    // Base64 never contains `=_`, so the boundary cannot occur in any part
+    let boundary = format!("=_{}", uuid::Uuid::new_v4().simple());
+    out.push_str(&format!(
+        "Content-Type: multipart/mixed; boundary=\"{boundary}\"\r\n\r\n"
+    ));
+    out.push_str(&format!("--{boundary}\r\n{text_headers}\r\n"));
+    push_base64(&mut out, body.as_bytes());
+    for part in &message.attachments {
+        let name = encode_word(&part.file_name).replace('"', "");
+        out.push_str(&format!(
+            "--{boundary}\r\n\
+             Content-Type: {}; name=\"{name}\"\r\n\
+             Content-Transfer-Encoding: base64\r\n\
+             Content-Disposition: attachment; {}\r\n\r\n",
+            part.content_type,
+            file_name_param(&part.file_name)
+        ));
+        push_base64(&mut out, &part.bytes);
+    }
+    out.push_str(&format!("--{boundary}--\r\n"));
+    (message_id, out.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendered(message: &Message<'_>) -> String {
+        String::from_utf8(render(message).1).unwrap()
+    }
+
+    fn headers(text: &str) -> Vec<&str> {
+        let (head, _) = text.split_once("\r\n\r\n").unwrap();
+        head.split("\r\n").collect()
+    }
+
+    fn message<'a>(from_name: Option<&'a str>, subject: &'a str) -> Message<'a> {
+        Message {
+            from_address: "pm@example.com",
+            from_name,
+            to: &[],
+            cc: &[],
+            subject,
+            body: "Hello",
+            attachments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn plain_names_are_quoted() {
+        assert_eq!(
+            mailbox("pm@example.com", Some("Site \"North\"")),
+            "\"Site \\\"North\\\"\" <pm@example.com>"
+        );
+        assert_eq!(mailbox("pm@example.com", Some("  ")), "pm@example.com");
+    }
+
+    #[test]
+    fn line_breaks_in_the_sender_name_do_not_add_headers() {
+        let text = rendered(&message(Some("Ops\r\nBcc: victim@example.com"), "Report"));
+        let headers = headers(&text);
+        assert!(!headers.iter().any(|line| line.starts_with("Bcc:")));
+        let from = headers
+            .iter()
+            .find(|line| line.starts_with("From: "))
+            .unwrap();
+        assert!(from.starts_with("From: =?UTF-8?B?"));
+        assert!(from.ends_with(" <pm@example.com>"));
+    }
+
+    #[test]
+    fn control_characters_in_the_subject_are_encoded() {
+        let text = rendered(&message(None, "Report\nX-Injected: yes"));
+        let headers = headers(&text);
+        assert!(!headers.iter().any(|line| line.starts_with("X-Injected")));
+        let subject = headers
+            .iter()
+            .find(|line| line.starts_with("Subject: "))
+            .unwrap();
+        let encoded = subject
+            .strip_prefix("Subject: =?UTF-8?B?")
+            .and_then(|rest| rest.strip_suffix("?="))
+            .unwrap();
+        assert_eq!(
+            STANDARD.decode(encoded).unwrap(),
+            b"Report\nX-Injected: yes"
+        );
+    }
+
+    #[test]
+    fn line_breaks_in_file_names_stay_in_their_parameter() {
+        let mut message = message(None, "Drawings");
+        message.attachments.push(Part {
+            file_name: "plan.pdf\r\nX-Injected: yes".into(),
+            content_type: "application/pdf".into(),
+            bytes: b"%PDF".to_vec(),
+        });
+        let text = rendered(&message);
+        assert!(!text
+            .split("\r\n")
+            .any(|line| line.starts_with("X-Injected")));
+        assert!(text.contains("filename*=UTF-8''plan.pdf%0D%0AX-Injected%3A%20yes"));
+    }
+
+    #[test]
+    fn non_ascii_text_is_encoded() {
+        assert_eq!(
+            encode_word("Büro"),
+            format!("=?UTF-8?B?{}?=", STANDARD.encode("Büro"))
+        );
+        assert_eq!(encode_word("Weekly report"), "Weekly report");
+    }
+}
@@ -0,0 +1,367 @@
+//! Emailing generated reports, either as a draft in the user's own mail app or sent
+//! straight from the app through an SMTP server they configure.
+//!
+//! The SMTP password lives in the keychain and is never returned. Each delivery is given an
+//! id, and its progress is announced with `email:delivery` as well as returned at the end.
+
+mod draft;
+mod message;
+mod smtp;
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, State};
+
+use crate::attachments;
+use crate::config;
+use crate::db::now_ms;
+use crate::error::{AppError, AppResult};
+use crate::secrets;
+use crate::state::AppState;
+use message::{Message, Part};
+
+/// Emitted with an [`EmailDelivery`] each time a delivery moves on a stage.
+pub const DELIVERY_EVENT: &str = "email:delivery";
+
+const SMTP_PASSWORD_SECRET_KEY: &str = "email.smtp.password";
+// Most providers refuse messages much over this once base64 has grown them by a third
+const MAX_ATTACHMENT_BYTES: u64 = 20 * 1024 * 1024;
+const SEND_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// How the connection to the SMTP server is secured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SmtpSecurity {
+    /// TLS from the start, usually on port 465.
+    Tls,
+    /// Upgraded with STARTTLS, usually on port 587.
+    #[default]
+    StartTls,
+    /// Unencrypted, for a relay on the local network.
+    None,
+}
+
+/// The user's outgoing mail server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    /// Sign-in name; `None` sends without signing in.
+    pub username: Option<String>,
+    pub from_address: String,
+    pub from_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EmailSettings {
+    /// `None` until the user sets up direct sending.
+    pub smtp: Option<SmtpSettings>,
+}
+
+/// Saved settings plus whether a password is stored, which is never returned itself.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailConfig {
+    pub settings: EmailSettings,
+    pub has_password: bool,
+}
+
+/// How to deliver a report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EmailMethod {
+    /// Open a pre-filled draft in the user's mail app.
+    Draft,
+    /// Send through the configured SMTP server.
+    Smtp,
+}
+
+/// A report to email.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailReport {
+    pub method: EmailMethod,
+    pub to: Vec<String>,
+    #[serde(default)]
+    pub cc: Vec<String>,
+    #[serde(default)]
+    pub bcc: Vec<String>,
+    pub subject: String,
+    #[serde(default)]
+    pub body: String,
+    /// Files to attach, such as a rendered report PDF.
+    #[serde(default)]
+    pub attachments: Vec<PathBuf>,
+}
+
+/// Where a delivery has got to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DeliveryStatus {
+    Connecting,
+    Sending,
+    /// Accepted by the server, or sent from the mail app where it says so.
+    Sent,
+    /// Handed to the mail app, which does not report whether it was sent.
+    DraftOpened,
+    /// The user closed the draft without sending.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    Cancelled,
+    Failed,
+}
+
+/// Progress or outcome of one delivery.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailDelivery {
+    pub id: String,
+    pub method: EmailMethod,
+    pub status: DeliveryStatus,
+    /// The server's final reply, or why the delivery failed.
+    pub detail: Option<String>,
+    /// Set once sent over SMTP.
+    pub message_id: Option<String>,
+    /// Recipients the server refused, with its reason, when others were accepted.
+    pub rejected: Vec<RejectedRecipient>,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RejectedRecipient {
+    pub address: String,
+    pub reason: String,
+}
+
+impl EmailDelivery {
+    fn update(&mut self, app: &AppHandle, status: DeliveryStatus) {
+        self.status = status;
+        self.updated_at = now_ms();
+        let _ = app.emit(DELIVERY_EVENT, &*self);
+    }
+}
+
+// Only plain ASCII addresses without anything a header or command line could misread
+fn validate_address(address: &str) -> AppResult<()> {
+    let valid = address
+        .split_once('@')
+        .is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.'))
+        && !address.starts_with('-')
+        && address.chars().all(|c| {
+            c.is_ascii_graphic() && !matches!(c, '<' | '>' | '(' | ')' | ',' | ';' | '"' | '\\')
+        });
+    if !valid {
+        return Err(AppError::InvalidInput(format!(
+            "{address:?} is not a valid email address"
+        )));
+    }
+    Ok(())
+}
+
+fn validate(report: &mut EmailReport) -> AppResult<()> {
+    for list in [&mut report.to, &mut report.cc, &mut report.bcc] {
+        for address in list.iter_mut() {
+            *address = address.trim().to_string();
+            validate_address(address)?;
+        }
+    }
+    if report.method == EmailMethod::Smtp
+        && report.to.is_empty()
+        && report.cc.is_empty()
+        && report.bcc.is_empty()
+    {
+        return Err(AppError::InvalidInput("add at least one recipient".into()));
+    }
+    if report.subject.contains(['\r', '\n']) {
+        return Err(AppError::InvalidInput(
+            "the subject must be a single line".into(),
+        ));
+    }
+    let mut total = 0;
+    for path in &report.attachments {
+        let metadata = std::fs::metadata(path)
+            .map_err(|_| AppError::not_found("file", path.display().to_string()))?;
+        if !metadata.is_file() {
+            return Err(AppError::InvalidInput(format!(
+                "{} is not a file",
+                path.display()
+            )));
+        }
+        total += metadata.len();
+    }
+    if total > MAX_ATTACHMENT_BYTES {
+        return Err(AppError::InvalidInput(format!(
+            "attachments total {} MB; the limit is {} MB",
+            total / (1024 * 1024),
+            MAX_ATTACHMENT_BYTES / (1024 * 1024)
+        )));
+    }
+    Ok(())
+}
+
+async fn send_smtp(
+    app: &AppHandle,
+    settings: SmtpSettings,
+    report: EmailReport,
+    delivery: &mut EmailDelivery,
+) -> AppResult<()> {
+    validate_address(&settings.from_address)?;
+    let password = match settings.username {
+        Some(_) => Some(
+            tauri::async_runtime::spawn_blocking(|| secrets::get(SMTP_PASSWORD_SECRET_KEY))
+                .await??
+                .ok_or_else(|| AppError::InvalidInput("set the SMTP password first".into()))?,
+        ),
+        None => None,
+    };
+    let recipients: Vec<String> = [&report.to[..], &report.cc, &report.bcc].concat();
+    let (from_address, from_name) = (settings.from_address.clone(), settings.from_name.clone());
+    let (message_id, data) = tauri::async_runtime::spawn_blocking(move || {
+        let attachments = report
+            .attachments
+            .iter()
+            .map(|path| {
+                let file_name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "attachment".into());
+                Ok(Part {
+                    content_type: attachments::content_type_for(&file_name)
+                        .unwrap_or("application/octet-stream")
+                        .to_string(),
+                    file_name,
+                    bytes: std::fs::read(path)?,
+                })
+            })
+            .collect::<AppResult<Vec<_>>>()?;
+        Ok::<_, AppError>(message::render(&Message {
+            from_address: &from_address,
+            from_name: from_name.as_deref(),
+            to: &report.to,
+            cc: &report.cc,
+            subject: &report.subject,
+            body: &report.body,
+            attachments,
+        }))
+    })
+    .await??;
+
+    delivery.update(app, DeliveryStatus::Connecting);
+    let envelope = smtp::Envelope {
+        from: &settings.from_address,
+        recipients: &recipients,
+    };
+    let send = smtp::send(&settings, password.as_deref(), envelope, &data, || {
+        delivery.update(app, DeliveryStatus::Sending)
+    });
+    let accepted = tokio::time::timeout(SEND_TIMEOUT, send)
+        .await
+        .map_err(|_| AppError::Email("timed out sending the message".into()))??;
+    delivery.message_id = Some(message_id);
+    delivery.detail = Some(accepted.reply);
+    delivery.rejected = accepted
+        .rejected
+        .into_iter()
+        .map(|(address, reason)| RejectedRecipient { address, reason })
+        .collect();
+    Ok(())
+}
+
+/// Email a report as a draft in the user's mail app or directly over SMTP.
+///
+/// The delivery is returned once it has finished; a failed delivery is announced with
+/// `email:delivery` before the error is returned.
+#[tauri::command]
+pub async fn email_report(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    mut report: EmailReport,
+) -> AppResult<EmailDelivery> {
+    validate(&mut report)?;
+    let mut delivery = EmailDelivery {
+        id: uuid::Uuid::new_v4().to_string(),
+        method: report.method,
+        status: DeliveryStatus::Connecting,
+        detail: None,
+        message_id: None,
+        rejected: Vec::new(),
+        updated_at: now_ms(),
+    };
+    let result = match report.method {
+        EmailMethod::Draft => {
+            // Simple MAPI blocks until the user closes the draft
+            tauri::async_runtime::spawn_blocking(move || draft::open(&report))
+                .await
+                .map_err(AppError::from)
+                .and_then(|status| status)
+        }
+        EmailMethod::Smtp => {
+            let smtp = state
+                .config()
+                .email
+                .smtp
+                .ok_or_else(|| AppError::InvalidInput("set up an SMTP server first".into()))?;
+            send_smtp(&app, smtp, report, &mut delivery)
+                .await
+                .map(|()| DeliveryStatus::Sent)
+        }
+    };
+    match result {
+        Ok(status) => {
+            tracing::info!(id = %delivery.id, method = ?delivery.method, ?status, "report emailed");
+            delivery.update(&app, status);
+            Ok(delivery)
+        }
+        Err(err) => {
+            tracing::warn!(%err, id = %delivery.id, method = ?delivery.method, "email failed");
+            delivery.detail = Some(err.to_string());
+            delivery.update(&app, DeliveryStatus::Failed);
+            Err(err)
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn get_email_settings(state: State<'_, AppState>) -> AppResult<EmailConfig> {
+    let settings = state.config().email;
+    let has_password =
+        tauri::async_runtime::spawn_blocking(|| secrets::get(SMTP_PASSWORD_SECRET_KEY))
+            .await??
+            .is_some();
+    Ok(EmailConfig {
+        settings,
+        has_password,
+    })
+}
+
+/// Save email settings.
+///
+/// `password` replaces the stored SMTP password when given; an empty string removes it.
+#[tauri::command]
+pub async fn set_email_settings(
+    app: AppHandle,
+    settings: EmailSettings,
+    password: Option<String>,
+) -> AppResult<()> {
+    if let Some(smtp) = &settings.smtp {
+        if smtp.host.trim().is_empty() || smtp.port == 0 {
+            return Err(AppError::InvalidInput(
+                "the SMTP server needs a host and port".into(),
+            ));
+        }
+        validate_address(&smtp.from_address)?;
+    }
+    tauri::async_runtime::spawn_blocking(move || match password.as_deref() {
+        Some("") => secrets::delete(SMTP_PASSWORD_SECRET_KEY),
+        Some(password) => secrets::set(SMTP_PASSWORD_SECRET_KEY, password),
+        None => Ok(()),
+    })
+    .await??;
+    config::update(&app, |c| c.email = settings)?;
+    Ok(())
+}
@@ -0,0 +1,304 @@
+//! SMTP submission (RFC 5321) of one already rendered message, over lettre's connection.
+//!
+//! Implicit TLS or STARTTLS with the web PKI roots, `AUTH PLAIN` or `AUTH LOGIN`, and one
+//! transaction per connection. The transaction is driven command by command rather than
+//! through lettre's `send`, so recipients the server refuses are reported back instead of
+//! failing the send, as long as at least one is accepted.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use lettre::transport::smtp::authentication::{Credentials, Mechanism};
+use lettre::transport::smtp::client::{
+    AsyncSmtpConnection, CertificateStore, TlsParameters, TlsParametersBuilder,
+};
+use lettre::transport::smtp::commands::{Data, Mail, Rcpt};
+use lettre::transport::smtp::extension::ClientId;
+use lettre::transport::smtp::response::Response;
+use lettre::Address;
+
+use super::{SmtpSecurity, SmtpSettings};
+use crate::error::{AppError, AppResult};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Who the server should deliver to, apart from what the headers say.
+pub(crate) struct Envelope<'a> {
+    pub from: &'a str,
+    pub recipients: &'a [String],
+}
+
+/// What the server made of the message.
+pub(crate) struct Accepted {
+    /// Recipients refused at `RCPT`, with the server's reason.
+    pub rejected: Vec<(String, String)>,
+    /// The final reply to the message, often carrying the server's queue id.
+    pub reply: String,
+}
+
+fn failed(stage: &str) -> impl FnOnce(lettre::transport::smtp::Error) -> AppError + '_ {
+    move |e| AppError::Email(format!("{stage} refused: {e}"))
+}
+
+fn text(response: &Response) -> String {
+    response.message().collect::<Vec<_>>().join(" ")
+}
+
+fn address(address: &str) -> AppResult<Address> {
+    Address::from_str(address)
+        .map_err(|e| AppError::InvalidInput(format!("invalid address {address}: {e}")))
+}
+
+// The EHLO argument must be a domain; the hostname is close enough when it is a valid one
+fn client_name() -> ClientId {
+    let host = tauri_plugin_os::hostname();
+    let valid = !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.'));
+    ClientId::Domain(if valid { host } else { "localhost".into() })
+}
+
+fn tls(host: &str) -> AppResult<TlsParameters> {
+    TlsParametersBuilder::new(host.to_string())
+        .certificate_store(CertificateStore::WebpkiRoots)
+        .build_rustls()
+        .map_err(|e| AppError::Email(format!("TLS setup failed: {e}")))
+}
+
+/// Submit `data`, an already rendered message, to the server in `settings`.
+///
+/// `on_sending` is called once the server has accepted the envelope and the message itself
+/// starts going out.
+pub(crate) async fn send(
+    settings: &SmtpSettings,
+    password: Option<&str>,
+    envelope: Envelope<'_>,
+    data: &[u8],
+    on_sending: impl FnOnce(),
+) -> AppResult<Accepted> {
+    let host = settings.host.trim_matches(['[', ']']);
+    let from = address(envelope.from)?;
+    let hello = client_name();
+    let implicit = match settings.security {
+        SmtpSecurity::Tls => Some(tls(host)?),
+        SmtpSecurity::StartTls | SmtpSecurity::None => None,
+    };
+    let mut conn = AsyncSmtpConnection::connect_tokio1(
+        (host, settings.port),
+        Some(CONNECT_TIMEOUT),
+        &hello,
+        implicit,
+        None,
+    )
+    .await
+    .map_err(|e| AppError::Email(format!("could not connect to {host}: {e}")))?;
+
+    if settings.security == SmtpSecurity::StartTls {
+        if !conn.can_starttls() {
+            conn.abort().await;
+            return Err(AppError::Email("the server does not offer STARTTLS".into()));
+        }
+        conn.starttls(tls(host)?, &hello)
+            .await
+            .map_err(failed("STARTTLS"))?;
+    }
+
+    if let (Some(username), Some(password)) = (settings.username.as_deref(), password) {
+        let credentials = Credentials::new(username.to_string(), password.to_string());
+        conn.auth(&[Mechanism::Plain, Mechanism::Login], &credentials)
+            .await
+            .map_err(failed("sign-in"))?;
+    }
+
+    conn.command(Mail::new(Some(from), vec![]))
+        .await
+        .map_err(failed("the sender"))?;
+    let mut rejected = Vec::new();
+    for recipient in envelope.recipients {
+        let refused = match address(recipient) {
+            Ok(to) => conn.command(Rcpt::new(to, vec![])).await.err(),
+            Err(e) => {
+                rejected.push((recipient.clone(), e.to_string()));
+                continue;
+            }
+        };
+        if let Some(e) = refused {
+            // A dropped connection is not a refusal, so it fails the whole send
+            if !e.is_permanent() && !e.is_transient() {
+                return Err(AppError::Email(format!("the recipient {recipient}: {e}")));
+            }
+            rejected.push((recipient.clone(), e.to_string()));
+        }
+    }
+    if rejected.len() == envelope.recipients.len() {
+        conn.abort().await;
+        return Err(AppError::Email(match rejected.first() {
+            Some((_, reason)) => format!("every recipient was refused: {reason}"),
+            None => "no recipients".into(),
+        }));
+    }
+    conn.command(Data).await.map_err(failed("DATA"))?;
+    on_sending();
+    let reply = conn.message(data).await.map_err(failed("the message"))?;
+    let _ = conn.quit().await;
+    Ok(Accepted {
+        rejected,
+        reply: text(&reply),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    // A server that refuses `bad@` recipients and records every line the client sends
+    async fn server(
+        extensions: &'static [&'static str],
+    ) -> (u16, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let task = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read, mut write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+            let mut seen = Vec::new();
+            write.write_all(b"220 test ESMTP\r\n").await.unwrap();
+            let mut data = false;
+            while let Ok(Some(line)) = lines.next_line().await {
+                seen.push(line.clone());
+                let reply = if data {
+                    if line != "." {
+                        continue;
+                    }
+                    data = false;
+                    "250 2.0.0 queued as 1A2B".to_string()
+                } else if line.starts_with("EHLO") {
+                    let mut reply = "250-test".to_string();
+                    for extension in extensions {
+                        reply.push_str(&format!("\r\n250-{extension}"));
+                    }
+                    reply + "\r\n250 SIZE 1000000"
+                } else if line.starts_with("RCPT TO:<bad@") {
+                    "550 5.1.1 no such user".into()
+                } else if line == "DATA" {
+                    data = true;
+                    "354 go ahead".into()
+                } else if line == "QUIT" {
+                    write.write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    "250 ok".into()
+                };
+                write
+                    .write_all(format!("{reply}\r\n").as_bytes())
+                    .await
+                    .unwrap();
+            }
+            seen
+        });
+        (port, task)
+    }
+
+    fn settings(port: u16, security: SmtpSecurity, username: Option<&str>) -> SmtpSettings {
+        SmtpSettings {
+            host: "127.0.0.1".into(),
+            port,
+            security,
+            username: username.map(str::to_string),
+            from_address: "pm@example.com".into(),
+            from_name: None,
+        }
+    }
+
+    #[test]
+    fn refused_recipients_are_reported_and_the_rest_delivered() {
+        tauri::async_runtime::block_on(async {
+            let (port, server) = server(&["AUTH PLAIN LOGIN"]).await;
+            let recipients = [
+                "site@example.com".to_string(),
+                "bad@example.com".to_string(),
+            ];
+            let mut sending = false;
+            let accepted = send(
+                &settings(port, SmtpSecurity::None, Some("pm")),
+                Some("secret"),
+                Envelope {
+                    from: "pm@example.com",
+                    recipients: &recipients,
+                },
+                b"Subject: Report\r\n\r\n.hidden\r\nbody\r\n",
+                || sending = true,
+            )
+            .await
+            .unwrap();
+            assert!(sending);
+            assert_eq!(accepted.reply, "2.0.0 queued as 1A2B");
+            assert_eq!(accepted.rejected.len(), 1);
+            assert_eq!(accepted.rejected[0].0, "bad@example.com");
+            assert!(accepted.rejected[0].1.contains("no such user"));
+
+            let seen = server.await.unwrap();
+            let token = STANDARD.encode("\0pm\0secret");
+            assert!(seen.contains(&format!("AUTH PLAIN {token}")));
+            assert!(seen.contains(&"MAIL FROM:<pm@example.com>".to_string()));
+            // A leading dot in the message is doubled so it cannot end the data
+            assert!(seen.contains(&"..hidden".to_string()));
+            assert_eq!(seen.last().map(String::as_str), Some("QUIT"));
+        });
+    }
+
+    #[test]
+    fn a_send_with_every_recipient_refused_fails() {
+        tauri::async_runtime::block_on(async {
+            let (port, server) = server(&[]).await;
+            let recipients = ["bad@example.com".to_string()];
+            let err = send(
+                &settings(port, SmtpSecurity::None, None),
+                None,
+                Envelope {
+                    from: "pm@example.com",
+                    recipients: &recipients,
+                },
+                b"Subject: Report\r\n\r\nbody\r\n",
+                || panic!("nothing should be sent"),
+            )
+            .await
+            .err()
+            .unwrap();
+            assert!(err.to_string().contains("every recipient was refused"));
+            assert!(!server.await.unwrap().contains(&"DATA".to_string()));
+        });
+    }
+
+    #[test]
+    fn starttls_is_required_when_configured() {
+        tauri::async_runtime::block_on(async {
+            let (port, server) = server(&[]).await;
+            let recipients = ["site@example.com".to_string()];
+            let err = send(
+                &settings(port, SmtpSecurity::StartTls, Some("pm")),
+                Some("secret"),
+                Envelope {
+                    from: "pm@example.com",
+                    recipients: &recipients,
+                },
+                b"Subject: Report\r\n\r\nbody\r\n",
+                || panic!("nothing should be sent"),
+            )
+            .await
+            .err()
+            .unwrap();
+            assert!(err.to_string().contains("STARTTLS"));
+            let seen = server.await.unwrap();
+            // Nothing, least of all the password, goes over the unencrypted connection
+            assert!(!seen.iter().any(|line| line.starts_with("AUTH")));
+            assert!(!seen.iter().any(|line| line.starts_with("MAIL")));
+        });
+    }
+}
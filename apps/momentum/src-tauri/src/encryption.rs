@@ -0,0 +1,528 @@
+//! Encryption at rest for the database and the attachment store.
+//!
+//! A random data key encrypts everything; it is never stored in the clear but wrapped twice
+//! in `encryption.json`, once with a key derived from the user's passphrase and once with one
+//! derived from a recovery key shown a single time when encryption is turned on. Both are
+//! stretched with Argon2id, so changing the passphrase only rewraps the data key.
+//!
+//! The database is handed to SQLCipher and attachment blobs are sealed in 64 KiB
+//! ChaCha20-Poly1305 chunks. The search index, logs, caches, autosave snapshots, and copies
+//! made for opening attachments stay plaintext.
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::stream::{DecryptorBE32, EncryptorBE32};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::ChaCha20Poly1305;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+use zeroize::Zeroizing;
+
+use crate::attachments::AttachmentStore;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::idle_lock;
+
+const KEY_FILE_NAME: &str = "encryption.json";
+const KEY_FILE_VERSION: u32 = 1;
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+// Identifies what a wrapped key is for, so it cannot be swapped into another slot
+const WRAP_AAD: &[u8] = b"momentum-data-key-v1";
+const MIN_PASSPHRASE_LEN: usize = 8;
+// Recovery keys are 20 random bytes, shown as 32 base32 characters
+const RECOVERY_KEY_BYTES: usize = 20;
+
+/// Header of an encrypted blob: magic, format version, and then the stream nonce prefix.
+const BLOB_MAGIC: &[u8; 8] = b"MOMENC\x00\x01";
+// STREAM takes the nonce minus its 4-byte counter and 1-byte last-chunk flag
+const STREAM_NONCE_LEN: usize = NONCE_LEN - 5;
+const CHUNK_LEN: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+
+/// Key that encrypts the database and blobs.
+pub(crate) type DataKey = Zeroizing<[u8; KEY_LEN]>;
+
+/// The data key while unlocked, shared with the attachment store.
+#[derive(Clone, Default)]
+pub(crate) struct KeyHandle(Arc<RwLock<Option<DataKey>>>);
+
+impl KeyHandle {
+    pub(crate) fn get(&self) -> Option<DataKey> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn set(&self, key: DataKey) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = Some(key);
+    }
+
+    fn clear(&self) {
+        // Dropping the key zeroes it
+        self.0.write().unwrap_or_else(|e| e.into_inner()).take();
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Kdf {
+    /// Memory in KiB.
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+impl Default for Kdf {
+    // Around a third of a second on a mid-range laptop
+    fn default() -> Self {
+        Self {
+            m_cost: 64 * 1024,
+            t_cost: 3,
+            p_cost: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WrappedKey {
+    salt: String,
+    nonce: String,
+    key: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeyFile {
+    version: u32,
+    kdf: Kdf,
+    passphrase: WrappedKey,
+    recovery: WrappedKey,
+}
+
+/// Whether the data at rest is encrypted and currently readable.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptionStatus {
+    pub enabled: bool,
+    pub unlocked: bool,
+}
+
+/// Encryption state registered in app state.
+pub struct Encryption {
+    key_file: PathBuf,
+    key: KeyHandle,
+}
+
+/// Find the key file under `data_dir`; the app starts locked when one exists.
+pub fn init(data_dir: &Path) -> Encryption {
+    Encryption {
+        key_file: data_dir.join(KEY_FILE_NAME),
+        key: KeyHandle::default(),
+    }
+}
+
+impl Encryption {
+    pub fn is_enabled(&self) -> bool {
+        self.key_file.exists()
+    }
+
+    pub(crate) fn key_handle(&self) -> KeyHandle {
+        self.key.clone()
+    }
+
+    fn status(&self) -> EncryptionStatus {
+        let enabled = self.is_enabled();
+        EncryptionStatus {
+            enabled,
+            unlocked: !enabled || self.key.get().is_some(),
+        }
+    }
+
+    fn read_key_file(&self) -> AppResult<KeyFile> {
+        let key_file: KeyFile = serde_json::from_slice(&fs::read(&self.key_file)?)?;
+        if key_file.version > KEY_FILE_VERSION {
+            return Err(AppError::Encryption(format!(
+                "key file version {} is newer than this app supports",
+                key_file.version
+            )));
+        }
+        Ok(key_file)
+    }
+
+    fn write_key_file(&self, key_file: &KeyFile) -> AppResult<()> {
+        let tmp = self.key_file.with_extension("json.tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec_pretty(key_file)?)?;
+        // Losing this file loses the data, so it must be on disk before anything is encrypted
+        file.sync_all()?;
+        fs::rename(&tmp, &self.key_file)?;
+        Ok(())
+    }
+}
+
+fn random<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    rand::rng().fill(&mut bytes[..]);
+    bytes
+}
+
+fn decode(field: &str) -> AppResult<Vec<u8>> {
+    STANDARD
+        .decode(field)
+        .map_err(|_| AppError::Encryption("the key file is corrupt".into()))
+}
+
+fn derive(secret: &[u8], salt: &[u8], kdf: Kdf) -> AppResult<Zeroizing<[u8; KEY_LEN]>> {
+    let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(KEY_LEN))
+        .map_err(|e| AppError::Encryption(format!("invalid key derivation settings: {e}")))?;
+    let mut out = Zeroizing::new([0u8; KEY_LEN]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(secret, salt, &mut out[..])
+        .map_err(|e| AppError::Encryption(format!("key derivation failed: {e}")))?;
+    Ok(out)
+}
+
+fn wrap(secret: &[u8], kdf: Kdf, key: &DataKey) -> AppResult<WrappedKey> {
+    let salt = random::<SALT_LEN>();
+    let nonce = random::<NONCE_LEN>();
+    let kek = derive(secret, &salt, kdf)?;
+    let sealed = ChaCha20Poly1305::new((&*kek).into())
+        .encrypt(
+            (&nonce).into(),
+            Payload {
+                msg: &key[..],
+                aad: WRAP_AAD,
+            },
+        )
+        .map_err(|_| AppError::Encryption("cannot wrap the data key".into()))?;
+    Ok(WrappedKey {
+        salt: STANDARD.encode(salt),
+        nonce: STANDARD.encode(nonce),
+        key: STANDARD.encode(sealed),
+    })
+}
+
+fn unwrap(secret: &[u8], kdf: Kdf, wrapped: &WrappedKey) -> AppResult<DataKey> {
+    let nonce = decode(&wrapped.nonce)?;
+    if nonce.len() != NONCE_LEN {
+        return Err(AppError::Encryption("the key file is corrupt".into()));
+    }
+    let kek = derive(secret, &decode(&wrapped.salt)?, kdf)?;
+    let opened = Zeroizing::new(
+        ChaCha20Poly1305::new((&*kek).into())
+            .decrypt(
+                nonce.as_slice().into(),
+                Payload {
+                    msg: &decode(&wrapped.key)?,
+                    aad: WRAP_AAD,
+                },
+            )
+            .map_err(|_| AppError::WrongPassphrase)?,
+    );
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    if opened.len() != KEY_LEN {
+        return Err(AppError::Encryption("the key file is corrupt".into()));
+    }
+    key.copy_from_slice(&opened);
+    Ok(key)
+}
+
+// RFC 4648 base32 without padding, in dash-separated groups of four
+fn format_recovery_key(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut chars = Vec::with_capacity(bytes.len() * 8 / 5 + 1);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            chars.push(ALPHABET[(buffer >> bits) as usize & 31] as char);
+        }
+    }
+    if bits > 0 {
+        chars.push(ALPHABET[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+    chars
+        .chunks(4)
+        .map(|group| group.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// Recovery keys get typed back in by hand, so case, dashes, and spaces are forgiven
+fn normalize_recovery_key(input: &str) -> Zeroizing<String> {
+    Zeroizing::new(
+        input
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_uppercase())
+            .collect(),
+    )
+}
+
+fn check_passphrase(passphrase: &str) -> AppResult<()> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(AppError::InvalidInput(format!(
+            "the passphrase must be at least {MIN_PASSPHRASE_LEN} characters"
+        )));
+    }
+    Ok(())
+}
+
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+    Ok(filled)
+}
+
+fn sealing_failed<T>(_: T) -> AppError {
+    AppError::Encryption("encryption failed".into())
+}
+
+/// Encrypt `reader` into `writer` in authenticated chunks.
+pub(crate) fn encrypt_stream(
+    key: &DataKey,
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+) -> AppResult<()> {
+    let nonce = random::<STREAM_NONCE_LEN>();
+    writer.write_all(BLOB_MAGIC)?;
+    writer.write_all(&nonce)?;
+    let mut encryptor =
+        EncryptorBE32::from_aead(ChaCha20Poly1305::new((&**key).into()), (&nonce).into());
+    let mut current = vec![0; CHUNK_LEN];
+    let mut next = vec![0; CHUNK_LEN];
+    let mut len = read_full(reader, &mut current)?;
+    // The last chunk is sealed differently, so one chunk of lookahead is needed to find it
+    loop {
+        let next_len = if len == CHUNK_LEN {
+            read_full(reader, &mut next)?
+        } else {
+            0
+        };
+        if next_len == 0 {
+            writer.write_all(
+                &encryptor
+                    .encrypt_last(&current[..len])
+                    .map_err(sealing_failed)?,
+            )?;
+            return Ok(());
+        }
+        writer.write_all(
+            &encryptor
+                .encrypt_next(&current[..len])
+                .map_err(sealing_failed)?,
+        )?;
+        std::mem::swap(&mut current, &mut next);
+        len = next_len;
+    }
+}
+
+/// Decrypt what [`encrypt_stream`] wrote; fails on any tampering or truncation.
+pub(crate) fn decrypt_stream(
+    key: &DataKey,
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+) -> AppResult<()> {
+    let corrupt =
+        |_| AppError::Encryption("the file is corrupt or was encrypted with another key".into());
+    let mut header = [0u8; BLOB_MAGIC.len() + STREAM_NONCE_LEN];
+    if read_full(reader, &mut header)? != header.len() || &header[..BLOB_MAGIC.len()] != BLOB_MAGIC
+    {
+        return Err(AppError::Encryption("the file is not encrypted".into()));
+    }
+    let mut decryptor = DecryptorBE32::from_aead(
+        ChaCha20Poly1305::new((&**key).into()),
+        header[BLOB_MAGIC.len()..].into(),
+    );
+    let mut current = vec![0; CHUNK_LEN + TAG_LEN];
+    let mut next = vec![0; CHUNK_LEN + TAG_LEN];
+    let mut len = read_full(reader, &mut current)?;
+    loop {
+        let next_len = if len == current.len() {
+            read_full(reader, &mut next)?
+        } else {
+            0
+        };
+        if next_len == 0 {
+            writer.write_all(&decryptor.decrypt_last(&current[..len]).map_err(corrupt)?)?;
+            return Ok(());
+        }
+        writer.write_all(&decryptor.decrypt_next(&current[..len]).map_err(corrupt)?)?;
+        std::mem::swap(&mut current, &mut next);
+        len = next_len;
+    }
+}
+
+/// Whether the file at `path` was written by [`encrypt_stream`].
+pub(crate) fn is_encrypted_file(path: &Path) -> bool {
+    let mut magic = [0u8; BLOB_MAGIC.len()];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok()
+        && &magic == BLOB_MAGIC
+}
+
+// Pre-migration backups are whole plaintext copies of the database
+fn remove_plaintext_backups(db_path: &Path) {
+    let (Some(dir), Some(name)) = (db_path.parent(), db_path.file_name()) else {
+        return;
+    };
+    let prefix = format!("{}.v", name.to_string_lossy());
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if file_name.starts_with(&prefix) && file_name.ends_with(".bak") {
+            if let Err(err) = fs::remove_file(entry.path()) {
+                tracing::warn!(%err, file = %file_name, "cannot remove plaintext backup");
+            }
+        }
+    }
+}
+
+// Bring everything to rest under `key`; safe to repeat after an interruption
+fn encrypt_everything(app: &AppHandle, key: &DataKey) -> AppResult<()> {
+    let db = app.state::<Db>();
+    db.encrypt(key)?;
+    remove_plaintext_backups(db.path());
+    let encrypted = app.state::<AttachmentStore>().encrypt_existing(key)?;
+    tracing::info!(blobs = encrypted, "data encrypted at rest");
+    Ok(())
+}
+
+fn unlock_with(app: &AppHandle, key: DataKey) -> AppResult<()> {
+    let db = app.state::<Db>();
+    db.unlock(&key)?;
+    app.state::<Encryption>().key.set(key.clone());
+    // Blobs left plaintext when encryption was switched on mid-way
+    let encrypted = app.state::<AttachmentStore>().encrypt_existing(&key)?;
+    if encrypted > 0 {
+        tracing::info!(blobs = encrypted, "finished encrypting attachments");
+    }
+    Ok(())
+}
+
+/// Forget the data key and close the database until `unlock_database` runs again.
+pub(crate) fn lock(app: &AppHandle) {
+    let encryption = app.state::<Encryption>();
+    if encryption.is_enabled() && encryption.key.get().is_some() {
+        encryption.key.clear();
+        app.state::<Db>().lock();
+    }
+}
+
+/// Whether encryption is on and unlocked.
+#[tauri::command]
+pub async fn get_encryption_status(
+    encryption: State<'_, Encryption>,
+) -> AppResult<EncryptionStatus> {
+    Ok(encryption.status())
+}
+
+/// Turn encryption on, or change the passphrase when it already is.
+///
+/// Turning it on encrypts the database and every attachment in place and returns the
+/// recovery key, which is never shown again. Changing it requires `current_passphrase`.
+#[tauri::command]
+pub async fn set_encryption_passphrase(
+    app: AppHandle,
+    passphrase: String,
+    current_passphrase: Option<String>,
+) -> AppResult<Option<String>> {
+    let passphrase = Zeroizing::new(passphrase);
+    let current_passphrase = current_passphrase.map(Zeroizing::new);
+    check_passphrase(&passphrase)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let encryption = app.state::<Encryption>();
+        if encryption.is_enabled() {
+            let current = current_passphrase.ok_or_else(|| {
+                AppError::InvalidInput("the current passphrase is required".into())
+            })?;
+            let mut key_file = encryption.read_key_file()?;
+            let key = unwrap(current.as_bytes(), key_file.kdf, &key_file.passphrase)?;
+            key_file.passphrase = wrap(passphrase.as_bytes(), key_file.kdf, &key)?;
+            encryption.write_key_file(&key_file)?;
+            tracing::info!("encryption passphrase changed");
+            return Ok(None);
+        }
+
+        let key = Zeroizing::new(random::<KEY_LEN>());
+        let recovery_key = Zeroizing::new(format_recovery_key(&random::<RECOVERY_KEY_BYTES>()));
+        let kdf = Kdf::default();
+        encryption.write_key_file(&KeyFile {
+            version: KEY_FILE_VERSION,
+            kdf,
+            passphrase: wrap(passphrase.as_bytes(), kdf, &key)?,
+            recovery: wrap(normalize_recovery_key(&recovery_key).as_bytes(), kdf, &key)?,
+        })?;
+        encryption.key.set(key.clone());
+        encrypt_everything(&app, &key)?;
+        Ok(Some(recovery_key.to_string()))
+    })
+    .await?
+}
+
+/// Unlock an encrypted database with the passphrase.
+#[tauri::command]
+pub async fn unlock_database(app: AppHandle, passphrase: String) -> AppResult<EncryptionStatus> {
+    let passphrase = Zeroizing::new(passphrase);
+    tauri::async_runtime::spawn_blocking(move || {
+        let encryption = app.state::<Encryption>();
+        if !encryption.is_enabled() || encryption.key.get().is_some() {
+            return Ok(encryption.status());
+        }
+        let key_file = encryption.read_key_file()?;
+        let key = unwrap(passphrase.as_bytes(), key_file.kdf, &key_file.passphrase)?;
+        unlock_with(&app, key)?;
+        idle_lock::release(&app);
+        tracing::info!("database unlocked");
+        Ok(encryption.status())
+    })
+    .await?
+}
+
+/// Unlock with the recovery key after the passphrase was forgotten, and set a new one.
+///
+/// The recovery key stays valid.
+#[tauri::command]
+pub async fn unlock_with_recovery_key(
+    app: AppHandle,
+    recovery_key: String,
+    new_passphrase: String,
+) -> AppResult<EncryptionStatus> {
+    let recovery_key = normalize_recovery_key(&recovery_key);
+    let new_passphrase = Zeroizing::new(new_passphrase);
+    check_passphrase(&new_passphrase)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let encryption = app.state::<Encryption>();
+        if !encryption.is_enabled() {
+            return Err(AppError::InvalidInput("encryption is not enabled".into()));
+        }
+        let mut key_file = encryption.read_key_file()?;
+        let key = unwrap(recovery_key.as_bytes(), key_file.kdf, &key_file.recovery)?;
+        key_file.passphrase = wrap(new_passphrase.as_bytes(), key_file.kdf, &key)?;
+        encryption.write_key_file(&key_file)?;
+        if encryption.key.get().is_none() {
+            unlock_with(&app, key)?;
+        }
+        idle_lock::release(&app);
+        tracing::info!("passphrase reset with the recovery key");
+        Ok(encryption.status())
+    })
+    .await?
+}
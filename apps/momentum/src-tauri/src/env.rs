@@ -0,0 +1,140 @@
+//! Which backend this build talks to: development, staging, or production.
+//!
+//! The environment is chosen at compile time with the `env-dev` or `env-staging` cargo
+//! feature, production otherwise. QA can point any build elsewhere by launching it with
+//! `MOMENTUM_ENV=dev|staging|prod`. Every subsystem resolves URLs through here, via
+//! `config::api_url` for the API, so one switch moves all of them together.
+
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use tauri::State;
+use url::Url;
+
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+
+const OVERRIDE_VAR: &str = "MOMENTUM_ENV";
+
+/// A backend deployment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Environment {
+    Development,
+    Staging,
+    Production,
+}
+
+impl Environment {
+    const COMPILED: Self = if cfg!(feature = "env-dev") {
+        Self::Development
+    } else if cfg!(feature = "env-staging") {
+        Self::Staging
+    } else {
+        Self::Production
+    };
+
+    fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "dev" | "development" => Some(Self::Development),
+            "staging" => Some(Self::Staging),
+            "prod" | "production" => Some(Self::Production),
+            _ => None,
+        }
+    }
+
+    pub fn api_base_url(self) -> &'static str {
+        match self {
+            Self::Development => "http://localhost:8787",
+            Self::Staging => "https://api.staging.truss.dev",
+            Self::Production => "https://api.truss.dev",
+        }
+    }
+
+    // Development builds follow the staging feed so the updater can be exercised locally
+    fn update_feed_base(self) -> &'static str {
+        match self {
+            Self::Development | Self::Staging => {
+                "https://collinwillis.github.io/truss/updates/momentum-staging/"
+            }
+            Self::Production => "https://collinwillis.github.io/truss/updates/momentum/",
+        }
+    }
+
+    /// Base of the update manifests for this environment.
+    pub fn update_feed_url(self) -> AppResult<Url> {
+        Url::parse(self.update_feed_base())
+            .map_err(|e| AppError::Internal(format!("invalid update feed URL: {e}")))
+    }
+}
+
+struct Selected {
+    environment: Environment,
+    overridden: bool,
+}
+
+static SELECTED: OnceLock<Selected> = OnceLock::new();
+
+fn selected() -> &'static Selected {
+    SELECTED.get_or_init(|| {
+        let Ok(name) = std::env::var(OVERRIDE_VAR) else {
+            return Selected {
+                environment: Environment::COMPILED,
+                overridden: false,
+            };
+        };
+        match Environment::parse(&name) {
+            Some(environment) => {
+                if environment != Environment::COMPILED {
+                    tracing::warn!(?environment, "backend environment overridden at launch");
+                }
+                Selected {
+                    environment,
+                    overridden: environment != Environment::COMPILED,
+                }
+            }
+            None => {
+                tracing::warn!(%name, "ignoring unknown {OVERRIDE_VAR}");
+                Selected {
+                    environment: Environment::COMPILED,
+                    overridden: false,
+                }
+            }
+        }
+    })
+}
+
+/// The environment this process runs against; fixed for its lifetime.
+pub fn current() -> Environment {
+    selected().environment
+}
+
+/// What [`get_environment`] reports.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvironmentInfo {
+    pub environment: Environment,
+    /// The environment the build was compiled for.
+    pub compiled: Environment,
+    /// Whether `MOMENTUM_ENV` moved this launch off the compiled environment.
+    pub overridden: bool,
+    /// API base URL in effect, including an `apiBaseUrl` setting if one is configured.
+    pub api_base_url: String,
+    pub update_feed_url: String,
+}
+
+/// The backend in effect, so the frontend uses the same URLs as Rust.
+#[tauri::command]
+pub async fn get_environment(state: State<'_, AppState>) -> AppResult<EnvironmentInfo> {
+    let selected = selected();
+    Ok(EnvironmentInfo {
+        environment: selected.environment,
+        compiled: Environment::COMPILED,
+        overridden: selected.overridden,
+        api_base_url: state
+            .config()
+            .api_base_url
+            .unwrap_or_else(|| selected.environment.api_base_url().into()),
+        update_feed_url: selected.environment.update_feed_url()?.to_string(),
+    })
+}
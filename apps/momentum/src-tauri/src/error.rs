@@ -0,0 +1,305 @@
+//! Error type shared by every IPC command.
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+/// Result alias used throughout the command layer.
+pub type AppResult<T> = Result<T, AppError>;
+
+/// Every failure a command can surface to the frontend.
+///
+/// Serializes as `{ code, message, context? }` where `code` is a stable identifier
+/// the frontend can match on; `message` is for logs and developer-facing display.
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("database error: {0}")]
+    Database(#[from] rusqlite::Error),
+
+    #[error("database pool error: {0}")]
+    Pool(#[from] r2d2::Error),
+
+    #[error("migration {version} ({name}) failed: {message}")]
+    Migration {
+        version: i64,
+        name: &'static str,
+        message: String,
+    },
+
+    #[error("the database is encrypted and locked")]
+    DatabaseLocked,
+
+    #[error("the session is locked")]
+    SessionLocked,
+
+    #[error("the passphrase or recovery key is incorrect")]
+    WrongPassphrase,
+
+    #[error("encryption error: {0}")]
+    Encryption(String),
+
+    #[error("a {role:?} may not {permission:?}")]
+    PermissionDenied {
+        permission: crate::rbac::Permission,
+        role: crate::rbac::Role,
+    },
+
+    #[error("the presentation window is read-only and may not run {command}")]
+    ReadOnlyWindow { command: String },
+
+    #[error("{permission} access is turned off for Momentum in the device settings")]
+    #[cfg_attr(desktop, allow(dead_code))]
+    DevicePermissionDenied { permission: String },
+
+    #[error("search index error: {0}")]
+    Search(#[from] tantivy::TantivyError),
+
+    #[error("http error: {0}")]
+    Http(tauri_plugin_http::reqwest::Error),
+
+    #[error("certificate for {host} does not match any pinned key")]
+    CertificatePinMismatch { host: String },
+
+    #[error("authentication failed: {0}")]
+    Auth(String),
+
+    #[error("keychain error: {0}")]
+    Keychain(#[from] keyring::Error),
+
+    #[error("file watch error: {0}")]
+    Watch(#[from] notify::Error),
+
+    #[cfg(desktop)]
+    #[error("global shortcut error: {0}")]
+    Shortcut(#[from] tauri_plugin_global_shortcut::Error),
+
+    #[cfg(desktop)]
+    #[error("{accelerator} is already assigned to {action}")]
+    ShortcutConflict { accelerator: String, action: String },
+
+    #[cfg(desktop)]
+    #[error("update error: {0}")]
+    Update(#[from] tauri_plugin_updater::Error),
+
+    #[cfg(desktop)]
+    #[error("switching to the {channel} channel from {current_version} may downgrade; confirmation required")]
+    DowngradeConfirmationRequired {
+        current_version: String,
+        channel: &'static str,
+    },
+
+    #[cfg(desktop)]
+    #[error("drag error: {0}")]
+    Drag(#[from] drag::Error),
+
+    #[error("csv error: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("spreadsheet error: {0}")]
+    Xlsx(#[from] rust_xlsxwriter::XlsxError),
+
+    #[error("spreadsheet read error: {0}")]
+    SpreadsheetRead(#[from] calamine::Error),
+
+    #[error("pdf error: {0}")]
+    Pdf(#[from] printpdf::Error),
+
+    #[error("pdf render error: {0}")]
+    PdfRender(#[from] pdfium_render::prelude::PdfiumError),
+
+    #[error("ocr error: {0}")]
+    Ocr(String),
+
+    #[error("extension {extension} failed: {message}")]
+    Extension { extension: String, message: String },
+
+    #[error("lan transfer failed: {0}")]
+    LanSync(String),
+
+    #[error("the pairing code did not match")]
+    PairingFailed,
+
+    #[error("realtime connection error: {0}")]
+    Realtime(String),
+
+    #[error("backup failed: {0}")]
+    Backup(String),
+
+    #[error("email delivery failed: {0}")]
+    Email(String),
+
+    #[error("archive error: {0}")]
+    Archive(#[from] zip::result::ZipError),
+
+    #[error("a password is required to open this file")]
+    PasswordRequired,
+
+    #[error("the password is incorrect")]
+    WrongPassword,
+
+    #[error("this file has expired")]
+    Expired {
+        /// Unix milliseconds.
+        expired_at: i64,
+    },
+
+    #[error("{path} is open elsewhere")]
+    FileLocked {
+        path: String,
+        /// Who holds the lock, when it could be read.
+        owner: Option<String>,
+    },
+
+    #[error("the operation was cancelled")]
+    Cancelled,
+
+    #[error("i/o error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("{0}")]
+    Tauri(#[from] tauri::Error),
+
+    #[error("{entity} {id} not found")]
+    NotFound { entity: &'static str, id: String },
+
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl AppError {
+    /// Stable machine-readable code; never rename an existing value.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Database(_) => "DATABASE",
+            Self::Pool(_) => "DATABASE_UNAVAILABLE",
+            Self::Migration { .. } => "MIGRATION_FAILED",
+            Self::DatabaseLocked => "DATABASE_LOCKED",
+            Self::SessionLocked => "SESSION_LOCKED",
+            Self::WrongPassphrase => "WRONG_PASSPHRASE",
+            Self::Encryption(_) => "ENCRYPTION",
+            Self::PermissionDenied { .. } => "PERMISSION_DENIED",
+            Self::ReadOnlyWindow { .. } => "READ_ONLY_WINDOW",
+            Self::DevicePermissionDenied { .. } => "DEVICE_PERMISSION_DENIED",
+            Self::Search(_) => "SEARCH",
+            Self::Http(_) => "HTTP",
+            Self::CertificatePinMismatch { .. } => "CERTIFICATE_PIN_MISMATCH",
+            Self::Auth(_) => "AUTH",
+            Self::Keychain(_) => "KEYCHAIN",
+            Self::Watch(_) => "FILE_WATCH",
+            #[cfg(desktop)]
+            Self::Shortcut(_) => "SHORTCUT_UNAVAILABLE",
+            #[cfg(desktop)]
+            Self::ShortcutConflict { .. } => "SHORTCUT_CONFLICT",
+            #[cfg(desktop)]
+            Self::Update(_) => "UPDATE",
+            #[cfg(desktop)]
+            Self::DowngradeConfirmationRequired { .. } => "DOWNGRADE_CONFIRMATION_REQUIRED",
+            #[cfg(desktop)]
+            Self::Drag(_) => "DRAG",
+            Self::Csv(_) => "CSV",
+            Self::Xlsx(_) => "XLSX",
+            Self::SpreadsheetRead(_) => "SPREADSHEET_READ",
+            Self::Pdf(_) => "PDF",
+            Self::PdfRender(_) => "PDF_RENDER",
+            Self::Ocr(_) => "OCR",
+            Self::Extension { .. } => "EXTENSION",
+            Self::LanSync(_) => "LAN_SYNC",
+            Self::PairingFailed => "PAIRING_FAILED",
+            Self::Realtime(_) => "REALTIME",
+            Self::Backup(_) => "BACKUP",
+            Self::Email(_) => "EMAIL",
+            Self::Archive(_) => "ARCHIVE",
+            Self::PasswordRequired => "PASSWORD_REQUIRED",
+            Self::WrongPassword => "WRONG_PASSWORD",
+            Self::Expired { .. } => "EXPIRED",
+            Self::FileLocked { .. } => "FILE_LOCKED",
+            Self::Cancelled => "CANCELLED",
+            Self::Io(_) => "IO",
+            Self::Serialization(_) => "SERIALIZATION",
+            Self::Tauri(_) => "RUNTIME",
+            Self::NotFound { .. } => "NOT_FOUND",
+            Self::InvalidInput(_) => "INVALID_INPUT",
+            Self::Internal(_) => "INTERNAL",
+        }
+    }
+
+    /// Structured details the frontend can use without parsing `message`.
+    pub fn context(&self) -> Option<serde_json::Value> {
+        match self {
+            Self::Migration { version, name, .. } => {
+                Some(serde_json::json!({ "version": version, "name": name }))
+            }
+            #[cfg(desktop)]
+            Self::ShortcutConflict {
+                accelerator,
+                action,
+            } => Some(serde_json::json!({ "accelerator": accelerator, "action": action })),
+            #[cfg(desktop)]
+            Self::DowngradeConfirmationRequired {
+                current_version,
+                channel,
+            } => Some(serde_json::json!({
+                "currentVersion": current_version,
+                "channel": channel,
+            })),
+            Self::PermissionDenied { permission, role } => {
+                Some(serde_json::json!({ "permission": permission, "role": role }))
+            }
+            Self::ReadOnlyWindow { command } => Some(serde_json::json!({ "command": command })),
+            Self::DevicePermissionDenied { permission } => {
+                Some(serde_json::json!({ "permission": permission }))
+            }
+            Self::CertificatePinMismatch { host } => Some(serde_json::json!({ "host": host })),
+            Self::Extension { extension, .. } => {
+                Some(serde_json::json!({ "extension": extension }))
+            }
+            Self::Expired { expired_at } => Some(serde_json::json!({ "expiredAt": expired_at })),
+            Self::FileLocked { path, owner } => {
+                Some(serde_json::json!({ "path": path, "owner": owner }))
+            }
+            Self::NotFound { entity, id } => {
+                Some(serde_json::json!({ "entity": entity, "id": id }))
+            }
+            _ => None,
+        }
+    }
+
+    /// Shorthand for a missing entity.
+    pub fn not_found(entity: &'static str, id: impl Into<String>) -> Self {
+        Self::NotFound {
+            entity,
+            id: id.into(),
+        }
+    }
+}
+
+// Pin failures arrive buried in a connect error; lift them out so the frontend can tell
+// a possible interception apart from an ordinary network failure.
+impl From<tauri_plugin_http::reqwest::Error> for AppError {
+    fn from(err: tauri_plugin_http::reqwest::Error) -> Self {
+        match crate::pinning::mismatch_host(&err) {
+            Some(host) => Self::CertificatePinMismatch { host },
+            None => Self::Http(err),
+        }
+    }
+}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let context = self.context();
+        let mut state = serializer.serialize_struct("AppError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        if let Some(context) = context {
+            state.serialize_field("context", &context)?;
+        } else {
+            state.skip_field("context")?;
+        }
+        state.end()
+    }
+}
@@ -0,0 +1,302 @@
+//! Query results streamed straight from SQLite to a CSV or JSON Lines file.
+//!
+//! Rows go to disk one at a time as the statement steps, so an export of hundreds of
+//! thousands of items never passes through the webview or sits in memory. The file is
+//! written beside its destination and renamed into place when complete; a failed or
+//! cancelled export leaves nothing behind.
+//!
+//! `stream_dataset` sends the same CSV or JSON Lines to the webview as a binary stream
+//! instead, for grids that page through more rows than a JSON response could carry.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rusqlite::types::Value as SqlValue;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tauri::{AppHandle, Emitter, State};
+
+use super::{ExportProgress, ExportSummary, PROGRESS_EVENT};
+use crate::db::{row_to_json, to_sql_params, Db};
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+use crate::streams::{BinaryStream, StreamSummary};
+use crate::tasks::{Task, TaskKind};
+use crate::{progress, wake_lock};
+
+const PROGRESS_EVERY: u64 = 5000;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DatasetFormat {
+    /// A header row of column names, then one line per row.
+    Csv,
+    /// One JSON object per line, keyed by column name.
+    Jsonl,
+}
+
+/// Outcome of [`stream_dataset`].
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamedDataset {
+    pub rows_written: u64,
+    pub stream: StreamSummary,
+}
+
+/// A read-only statement and the values for its `?` placeholders.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatasetQuery {
+    pub sql: String,
+    #[serde(default)]
+    pub params: Vec<Value>,
+}
+
+fn csv_cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+enum Sink<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    Jsonl(W),
+}
+
+impl<W: Write> Sink<W> {
+    fn row(&mut self, row: &serde_json::Map<String, Value>) -> AppResult<()> {
+        match self {
+            Self::Csv(writer) => writer.write_record(row.values().map(csv_cell))?,
+            Self::Jsonl(writer) => {
+                serde_json::to_writer(&mut *writer, row)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn into_inner(self) -> AppResult<W> {
+        match self {
+            Self::Csv(writer) => writer
+                .into_inner()
+                .map_err(|err| AppError::Io(err.into_error())),
+            Self::Jsonl(writer) => Ok(writer),
+        }
+    }
+}
+
+/// Run the read-only statement `sql` and write its rows to `writer`.
+///
+/// `on_progress` gets the rows written so far and the total, every few thousand rows and
+/// once at the end. Returns the writer and how many rows went into it.
+fn write_rows<W: Write>(
+    conn: &rusqlite::Connection,
+    sql: &str,
+    params: Vec<SqlValue>,
+    format: DatasetFormat,
+    writer: W,
+    cancelled: &AtomicBool,
+    mut on_progress: impl FnMut(u64, u64),
+) -> AppResult<(W, u64)> {
+    let mut stmt = conn.prepare(sql)?;
+    if !stmt.readonly() {
+        return Err(AppError::InvalidInput(
+            "only queries that read data can be exported".into(),
+        ));
+    }
+    // Counted first so progress has a total; cheap next to writing every row out
+    let total_rows = conn.query_row(
+        &format!("SELECT count(*) FROM ({sql})"),
+        rusqlite::params_from_iter(params.iter()),
+        |row| row.get::<_, i64>(0),
+    )? as u64;
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+    let mut sink = match format {
+        DatasetFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            writer.write_record(&columns)?;
+            Sink::Csv(Box::new(writer))
+        }
+        DatasetFormat::Jsonl => Sink::Jsonl(writer),
+    };
+
+    let mut rows = stmt.query(rusqlite::params_from_iter(params))?;
+    let mut rows_written = 0u64;
+    while let Some(row) = rows.next()? {
+        sink.row(&row_to_json(row, &columns)?)?;
+        rows_written += 1;
+        if rows_written.is_multiple_of(PROGRESS_EVERY) {
+            if cancelled.load(Ordering::Relaxed) {
+                return Err(AppError::Cancelled);
+            }
+            on_progress(rows_written, total_rows);
+        }
+    }
+    let writer = sink.into_inner()?;
+    on_progress(rows_written, total_rows);
+    Ok((writer, rows_written))
+}
+
+fn emit_progress(app: &AppHandle, export_id: &str, rows_written: u64, total_rows: u64) {
+    let _ = app.emit(
+        PROGRESS_EVENT,
+        ExportProgress {
+            export_id: export_id.to_string(),
+            rows_written,
+            total_rows,
+        },
+    );
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_os_string();
+    partial.push(".part");
+    PathBuf::from(partial)
+}
+
+/// Stream the rows of `query` to `path` as CSV or JSON Lines.
+///
+/// `export_id` tags `export:progress` events and is the task id to cancel it by.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn export_dataset(
+    app: AppHandle,
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    query: DatasetQuery,
+    format: DatasetFormat,
+    path: PathBuf,
+    export_id: Option<String>,
+) -> AppResult<ExportSummary> {
+    let sql = query.sql.trim().trim_end_matches(';').to_string();
+    if sql.is_empty() {
+        return Err(AppError::InvalidInput("SQL statement is empty".into()));
+    }
+    let _awake = wake_lock::hold(&app, "Exporting data");
+    let params = to_sql_params(query.params);
+    state
+        .tasks()
+        .run(&app, TaskKind::Export, export_id, |task| {
+            write_export(&app, &db, task, (sql, params), format, path)
+        })
+        .await
+}
+
+async fn write_export(
+    app: &AppHandle,
+    db: &Db,
+    task: Task,
+    (sql, params): (String, Vec<SqlValue>),
+    format: DatasetFormat,
+    path: PathBuf,
+) -> AppResult<ExportSummary> {
+    let export_id = task.id().to_string();
+    let partial = partial_path(&path);
+    let (id, target, handle) = (export_id.clone(), partial.clone(), app.clone());
+    let result = db
+        .run(move |conn| {
+            let taskbar = progress::track(&handle, format!("export:{id}"));
+            let file = BufWriter::new(File::create(&target)?);
+            let (file, rows_written) = write_rows(
+                conn,
+                &sql,
+                params,
+                format,
+                file,
+                &task.cancelled(),
+                |rows, total| {
+                    taskbar.set(rows, Some(total));
+                    task.progress(rows, Some(total));
+                    emit_progress(&handle, &id, rows, total);
+                },
+            )?;
+            file.into_inner()
+                .map_err(|err| err.into_error())?
+                .sync_all()?;
+            Ok(rows_written)
+        })
+        .await;
+
+    let finished = result.and_then(|rows| {
+        fs::rename(&partial, &path)?;
+        Ok(rows)
+    });
+    let rows_written = match finished {
+        Ok(rows) => rows,
+        Err(err) => {
+            let _ = fs::remove_file(&partial);
+            return Err(err);
+        }
+    };
+    tracing::info!(%export_id, rows_written, path = %path.display(), "dataset export written");
+    Ok(ExportSummary {
+        export_id,
+        path,
+        rows_written,
+    })
+}
+
+/// Stop a running dataset export; it fails with `CANCELLED` and removes its partial file.
+///
+/// The same as `cancel_task` with the export id.
+#[tauri::command]
+pub async fn cancel_dataset_export(state: State<'_, AppState>, export_id: String) -> AppResult<()> {
+    state.tasks().cancel(&export_id)
+}
+
+/// Stream the rows of `query` as CSV or JSON Lines down `channel`, for views that read a
+/// large dataset without a file in between.
+///
+/// `stream_id` also tags `export:progress` events; stop it with `stream_cancel`.
+#[tauri::command]
+pub async fn stream_dataset(
+    app: AppHandle,
+    db: State<'_, Db>,
+    query: DatasetQuery,
+    format: DatasetFormat,
+    stream_id: String,
+    channel: Channel<InvokeResponseBody>,
+) -> AppResult<StreamedDataset> {
+    let sql = query.sql.trim().trim_end_matches(';').to_string();
+    if sql.is_empty() {
+        return Err(AppError::InvalidInput("SQL statement is empty".into()));
+    }
+    let params = to_sql_params(query.params);
+    let stream = BinaryStream::open(&app, stream_id.clone(), channel)?;
+    let (id, handle) = (stream_id.clone(), app.clone());
+    let (stream, rows_written) = db
+        .run(move |conn| {
+            let cancelled = stream.cancelled();
+            let (stream, rows_written) = write_rows(
+                conn,
+                &sql,
+                params,
+                format,
+                stream,
+                &cancelled,
+                |rows, total| {
+                    emit_progress(&handle, &id, rows, total);
+                },
+            )
+            .map_err(|err| {
+                // A write refused mid-row surfaces as an IO error
+                if cancelled.load(Ordering::Relaxed) {
+                    AppError::Cancelled
+                } else {
+                    err
+                }
+            })?;
+            Ok((stream.finish(None)?, rows_written))
+        })
+        .await?;
+    tracing::info!(%stream_id, rows_written, bytes = stream.bytes, "dataset streamed");
+    Ok(StreamedDataset {
+        rows_written,
+        stream,
+    })
+}
@@ -0,0 +1,197 @@
+//! iCalendar (.ics) export of reminders, so bid due dates and milestones show up in Outlook,
+//! Apple Calendar, and Google Calendar.
+//!
+//! Each reminder becomes an event with a stable UID and a SEQUENCE that grows with every
+//! edit, so importing a newer file updates the events already in a calendar instead of
+//! duplicating them. Reminders pinned to a zone are written in UTC; ones that follow the
+//! device are written as floating local times, which calendars show at the same wall-clock
+//! time wherever the user is.
+
+use std::path::PathBuf;
+
+use jiff::civil::DateTime;
+use jiff::Timestamp;
+use rusqlite::OptionalExtension;
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_opener::OpenerExt;
+
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::reminders::{Reminder, Reminders};
+use crate::state::AppState;
+
+const PRODUCT_ID: &str = "-//Truss//Momentum//EN";
+const UID_DOMAIN: &str = "momentum.truss.dev";
+// Deadlines are points in time; calendars need some length to draw them
+const EVENT_DURATION: &str = "PT30M";
+const MAX_LINE_OCTETS: usize = 75;
+const CALENDAR_DIR_NAME: &str = "calendar";
+
+/// Outcome of a calendar export.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalendarExport {
+    pub path: PathBuf,
+    pub events: usize,
+}
+
+// TEXT values escape backslashes, separators, and newlines (RFC 5545 3.3.11)
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Lines longer than 75 octets continue on the next line after a space, never inside a
+// UTF-8 sequence
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn utc(ms: i64) -> AppResult<String> {
+    let timestamp = Timestamp::from_millisecond(ms)
+        .map_err(|e| AppError::Internal(format!("timestamp out of range: {e}")))?;
+    Ok(timestamp.strftime("%Y%m%dT%H%M%SZ").to_string())
+}
+
+fn start(reminder: &Reminder) -> AppResult<String> {
+    match reminder.time_zone {
+        Some(_) => Ok(format!("DTSTART:{}", utc(reminder.fire_at)?)),
+        None => {
+            let local: DateTime = reminder
+                .local_time
+                .parse()
+                .map_err(|e| AppError::Internal(format!("stored reminder time: {e}")))?;
+            Ok(format!("DTSTART:{}", local.strftime("%Y%m%dT%H%M%S")))
+        }
+    }
+}
+
+/// Render `reminders` as a VCALENDAR named `name`.
+pub fn render(name: &str, reminders: &[Reminder]) -> AppResult<String> {
+    let stamp = utc(now_ms())?;
+    let mut out = String::new();
+    for line in [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        &format!("PRODID:{PRODUCT_ID}"),
+        "CALSCALE:GREGORIAN",
+        "METHOD:PUBLISH",
+        &format!("X-WR-CALNAME:{}", escape(name)),
+    ] {
+        push_line(&mut out, line);
+    }
+    for reminder in reminders {
+        push_line(&mut out, "BEGIN:VEVENT");
+        push_line(&mut out, &format!("UID:{}@{UID_DOMAIN}", reminder.id));
+        push_line(&mut out, &format!("DTSTAMP:{stamp}"));
+        push_line(&mut out, &start(reminder)?);
+        push_line(&mut out, &format!("DURATION:{EVENT_DURATION}"));
+        // Whole seconds of edits since creation only ever grow, which is all SEQUENCE needs
+        let sequence = (reminder.updated_at - reminder.created_at).max(0) / 1000;
+        push_line(&mut out, &format!("SEQUENCE:{sequence}"));
+        push_line(
+            &mut out,
+            &format!("LAST-MODIFIED:{}", utc(reminder.updated_at)?),
+        );
+        push_line(&mut out, &format!("SUMMARY:{}", escape(&reminder.title)));
+        if !reminder.body.is_empty() {
+            push_line(&mut out, &format!("DESCRIPTION:{}", escape(&reminder.body)));
+        }
+        push_line(&mut out, &format!("CATEGORIES:{}", escape(&reminder.kind)));
+        if let Some(project_id) = &reminder.project_id {
+            push_line(&mut out, &format!("URL:momentum://project/{project_id}"));
+        }
+        push_line(&mut out, "TRANSP:TRANSPARENT");
+        push_line(&mut out, "END:VEVENT");
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    Ok(out)
+}
+
+async fn calendar(
+    db: &Db,
+    reminders: &Reminders,
+    project_id: Option<String>,
+) -> AppResult<(String, usize)> {
+    let name = match &project_id {
+        Some(id) => {
+            let lookup = id.clone();
+            let name: Option<String> = db
+                .run(move |conn| {
+                    Ok(conn
+                        .query_row("SELECT name FROM projects WHERE id = ?1", [lookup], |row| {
+                            row.get(0)
+                        })
+                        .optional()?)
+                })
+                .await?;
+            let name = name.ok_or_else(|| AppError::not_found("project", id.clone()))?;
+            format!("{name} deadlines")
+        }
+        None => "Momentum deadlines".to_string(),
+    };
+    // Past deadlines stay on the calendar as a record
+    let reminders = reminders.list(project_id, true).await?;
+    let ics = render(&name, &reminders)?;
+    Ok((ics, reminders.len()))
+}
+
+/// Write one project's reminders, or every reminder, to an .ics file at `path`.
+#[tauri::command]
+pub async fn export_ics(
+    db: State<'_, Db>,
+    reminders: State<'_, Reminders>,
+    project_id: Option<String>,
+    path: PathBuf,
+) -> AppResult<CalendarExport> {
+    let (ics, events) = calendar(&db, &reminders, project_id).await?;
+    tokio::fs::write(&path, ics).await?;
+    tracing::info!(events, path = %path.display(), "calendar exported");
+    Ok(CalendarExport { path, events })
+}
+
+/// Hand the reminders to the system calendar app, which adds them or updates earlier copies.
+///
+/// The file is kept under the data directory so the calendar app can still read it after
+/// the import dialog closes.
+#[tauri::command]
+pub async fn add_to_calendar(
+    app: AppHandle,
+    db: State<'_, Db>,
+    reminders: State<'_, Reminders>,
+    project_id: Option<String>,
+) -> AppResult<CalendarExport> {
+    let file_name = match &project_id {
+        Some(id) => format!("{id}.ics"),
+        None => "all.ics".to_string(),
+    };
+    let (ics, events) = calendar(&db, &reminders, project_id).await?;
+    let dir = app.state::<AppState>().data_dir().join(CALENDAR_DIR_NAME);
+    tokio::fs::create_dir_all(&dir).await?;
+    let path = dir.join(file_name);
+    tokio::fs::write(&path, ics).await?;
+    app.opener()
+        .open_path(path.to_string_lossy(), None::<&str>)
+        .map_err(|e| AppError::Internal(format!("no calendar app could open the file: {e}")))?;
+    Ok(CalendarExport { path, events })
+}
@@ -0,0 +1,29 @@
+//! File exports of estimates and reports.
+
+pub mod dataset;
+pub mod ics;
+pub mod view;
+pub mod xlsx;
+
+use serde::Serialize;
+
+/// Emitted with an [`ExportProgress`] while a large export is written.
+pub const PROGRESS_EVENT: &str = "export:progress";
+
+/// Payload for `export:progress`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportProgress {
+    pub export_id: String,
+    pub rows_written: u64,
+    pub total_rows: u64,
+}
+
+/// Outcome of an export.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportSummary {
+    pub export_id: String,
+    pub path: std::path::PathBuf,
+    pub rows_written: u64,
+}
@@ -0,0 +1,353 @@
+//! PNG captures of a window's webview at 2x or more, for exporting takeoff markups and
+//! Gantt views as images.
+//!
+//! Windows renders the capture through the DevTools protocol and macOS through a WebKit
+//! snapshot, both at the requested scale, so lines and text stay sharp. WebKitGTK only
+//! snapshots at the display's own resolution, so on Linux the image is resampled up to the
+//! requested size.
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use image::imageops::FilterType;
+use image::{ImageFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, WebviewWindow};
+use tokio::sync::oneshot;
+
+use crate::error::{AppError, AppResult};
+use crate::window_state::WindowStateStore;
+
+const DEFAULT_SCALE: f64 = 2.0;
+const MAX_SCALE: f64 = 4.0;
+// Engines fail or run out of texture memory past this many pixels on a side
+const MAX_SIDE_PX: f64 = 16384.0;
+
+/// Part of the page to capture, in CSS pixels from the top-left of the viewport.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureRegion {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// A capture written to disk.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewCapture {
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+}
+
+// What the platform snapshot needs; Linux resamples afterwards instead of using `scale`
+#[cfg_attr(not(any(windows, target_os = "macos")), allow(dead_code))]
+#[derive(Debug, Clone, Copy)]
+struct Request {
+    region: CaptureRegion,
+    scale: f64,
+    /// Page zoom, to turn CSS pixels into view points.
+    zoom: f64,
+}
+
+type Reply = oneshot::Sender<AppResult<RgbaImage>>;
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use base64::Engine;
+    use serde::Deserialize;
+    use serde_json::json;
+    use tauri::WebviewWindow;
+    use webview2_com::CallDevToolsProtocolMethodCompletedHandler;
+    use windows::core::{w, HSTRING};
+
+    use super::{Reply, Request};
+    use crate::error::{AppError, AppResult};
+
+    #[derive(Deserialize)]
+    struct Screenshot {
+        data: String,
+    }
+
+    fn decode(json: &str) -> AppResult<image::RgbaImage> {
+        let screenshot: Screenshot = serde_json::from_str(json)?;
+        let png = base64::engine::general_purpose::STANDARD
+            .decode(screenshot.data)
+            .map_err(|e| AppError::Internal(format!("view capture: {e}")))?;
+        Ok(image::load_from_memory(&png)
+            .map_err(|e| AppError::Internal(format!("view capture: {e}")))?
+            .to_rgba8())
+    }
+
+    pub fn capture(window: &WebviewWindow, request: Request, reply: Reply) -> AppResult<()> {
+        let region = request.region;
+        let params = json!({
+            "format": "png",
+            "captureBeyondViewport": true,
+            "clip": {
+                "x": region.x,
+                "y": region.y,
+                "width": region.width,
+                "height": region.height,
+                "scale": request.scale,
+            },
+        })
+        .to_string();
+        window.with_webview(move |webview| {
+            let handler = CallDevToolsProtocolMethodCompletedHandler::create(Box::new(
+                move |result, json| {
+                    let image = result
+                        .map_err(|e| AppError::Internal(format!("view capture: {e}")))
+                        .and_then(|()| decode(&json));
+                    let _ = reply.send(image);
+                    Ok(())
+                },
+            ));
+            // A failed call drops the handler, which the caller sees as an aborted capture
+            let started = unsafe {
+                webview.controller().CoreWebView2().and_then(|core| {
+                    core.CallDevToolsProtocolMethod(
+                        w!("Page.captureScreenshot"),
+                        &HSTRING::from(params),
+                        &handler,
+                    )
+                })
+            };
+            if let Err(err) = started {
+                tracing::warn!(%err, "cannot start view capture");
+            }
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::sync::Mutex;
+
+    use block2::RcBlock;
+    use objc2::MainThreadMarker;
+    use objc2_app_kit::NSImage;
+    use objc2_foundation::{NSError, NSNumber, NSPoint, NSRect, NSSize};
+    use objc2_web_kit::{WKSnapshotConfiguration, WKWebView};
+    use tauri::WebviewWindow;
+
+    use super::{Reply, Request};
+    use crate::error::{AppError, AppResult};
+
+    fn decode(image: &NSImage) -> AppResult<image::RgbaImage> {
+        let tiff = image
+            .TIFFRepresentation()
+            .ok_or_else(|| AppError::Internal("view capture has no bitmap".into()))?;
+        Ok(image::load_from_memory(&tiff.to_vec())
+            .map_err(|e| AppError::Internal(format!("view capture: {e}")))?
+            .to_rgba8())
+    }
+
+    pub fn capture(window: &WebviewWindow, request: Request, reply: Reply) -> AppResult<()> {
+        let backing = window.scale_factor()?;
+        window.with_webview(move |webview| unsafe {
+            let view: &WKWebView = &*webview.inner().cast();
+            let mtm = MainThreadMarker::new_unchecked();
+            let region = request.region;
+            let zoom = request.zoom;
+            let config = WKSnapshotConfiguration::new(mtm);
+            config.setRect(NSRect::new(
+                NSPoint::new(region.x * zoom, region.y * zoom),
+                NSSize::new(region.width * zoom, region.height * zoom),
+            ));
+            // WebKit re-renders at this width in points, times the display's backing scale
+            config.setSnapshotWidth(Some(&NSNumber::new_f64(
+                region.width * request.scale / backing,
+            )));
+            let reply = Mutex::new(Some(reply));
+            let completion = RcBlock::new(move |image: *mut NSImage, error: *mut NSError| {
+                let Some(reply) = reply.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+                    return;
+                };
+                let result = match image.as_ref() {
+                    Some(image) => decode(image),
+                    None => Err(AppError::Internal(format!(
+                        "view capture: {}",
+                        error
+                            .as_ref()
+                            .map(|e| e.localizedDescription().to_string())
+                            .unwrap_or_default()
+                    ))),
+                };
+                let _ = reply.send(result);
+            });
+            view.takeSnapshotWithConfiguration_completionHandler(Some(&config), &completion);
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use image::{Rgba, RgbaImage};
+    use tauri::WebviewWindow;
+    use webkit2gtk::{SnapshotOptions, SnapshotRegion, WebViewExt};
+
+    use super::{Reply, Request};
+    use crate::error::{AppError, AppResult};
+
+    // Cairo's ARGB32 is premultiplied and stored as BGRA on little-endian machines
+    fn to_image(surface: cairo::Surface, request: &Request) -> AppResult<RgbaImage> {
+        let (device_scale, _) = surface.device_scale();
+        let mut surface = cairo::ImageSurface::try_from(surface)
+            .map_err(|_| AppError::Internal("view capture is not a bitmap".into()))?;
+        let (width, height) = (surface.width() as u32, surface.height() as u32);
+        let stride = surface.stride() as usize;
+        let data = surface
+            .data()
+            .map_err(|e| AppError::Internal(format!("view capture: {e}")))?;
+        let mut image = RgbaImage::new(width, height);
+        for (y, row) in data.chunks(stride).take(height as usize).enumerate() {
+            for x in 0..width as usize {
+                let [b, g, r, a] = [row[x * 4], row[x * 4 + 1], row[x * 4 + 2], row[x * 4 + 3]];
+                let unpremultiply = |c: u8| match a {
+                    0 => 0,
+                    _ => (c as u32 * 255 / a as u32).min(255) as u8,
+                };
+                image.put_pixel(
+                    x as u32,
+                    y as u32,
+                    Rgba([unpremultiply(r), unpremultiply(g), unpremultiply(b), a]),
+                );
+            }
+        }
+        let px = request.zoom * device_scale;
+        let region = request.region;
+        Ok(image::imageops::crop_imm(
+            &image,
+            (region.x * px) as u32,
+            (region.y * px) as u32,
+            (region.width * px).round() as u32,
+            (region.height * px).round() as u32,
+        )
+        .to_image())
+    }
+
+    pub fn capture(window: &WebviewWindow, request: Request, reply: Reply) -> AppResult<()> {
+        window.with_webview(move |webview| {
+            let view = webview.inner();
+            webkit2gtk::glib::MainContext::default().spawn_local(async move {
+                let result = match view
+                    .snapshot_future(SnapshotRegion::Visible, SnapshotOptions::NONE)
+                    .await
+                {
+                    Ok(surface) => to_image(surface, &request),
+                    Err(err) => Err(AppError::Internal(format!("view capture: {err}"))),
+                };
+                let _ = reply.send(result);
+            });
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+mod platform {
+    use tauri::WebviewWindow;
+
+    use super::{Reply, Request};
+    use crate::error::{AppError, AppResult};
+
+    pub fn capture(_window: &WebviewWindow, _request: Request, _reply: Reply) -> AppResult<()> {
+        Err(AppError::InvalidInput(
+            "view capture is only available on desktop".into(),
+        ))
+    }
+}
+
+/// Capture the calling window's page, or `region` of it, to a PNG at `path`.
+///
+/// `scale` is the output's pixels per CSS pixel, from 1 to 4; it defaults to 2. Without a
+/// region the visible viewport is captured.
+#[tauri::command]
+pub async fn capture_view(
+    window: WebviewWindow,
+    path: PathBuf,
+    region: Option<CaptureRegion>,
+    scale: Option<f64>,
+) -> AppResult<ViewCapture> {
+    let scale = scale.unwrap_or(DEFAULT_SCALE);
+    if !(1.0..=MAX_SCALE).contains(&scale) {
+        return Err(AppError::InvalidInput(format!(
+            "scale must be between 1 and {MAX_SCALE}"
+        )));
+    }
+    let zoom = window
+        .state::<WindowStateStore>()
+        .zoom(window.label())
+        .unwrap_or(1.0);
+    let region = match region {
+        Some(region) => region,
+        None => {
+            let size = window
+                .inner_size()?
+                .to_logical::<f64>(window.scale_factor()?);
+            CaptureRegion {
+                x: 0.0,
+                y: 0.0,
+                width: size.width / zoom,
+                height: size.height / zoom,
+            }
+        }
+    };
+    let valid = [region.x, region.y, region.width, region.height]
+        .iter()
+        .all(|v| v.is_finite() && *v >= 0.0);
+    if !valid || region.width < 1.0 || region.height < 1.0 {
+        return Err(AppError::InvalidInput(format!(
+            "invalid capture region {region:?}"
+        )));
+    }
+    let width = (region.width * scale).round();
+    let height = (region.height * scale).round();
+    if width > MAX_SIDE_PX || height > MAX_SIDE_PX {
+        return Err(AppError::InvalidInput(format!(
+            "a {width}x{height} capture is larger than {MAX_SIDE_PX} pixels on a side"
+        )));
+    }
+
+    let (reply, captured) = oneshot::channel();
+    platform::capture(
+        &window,
+        Request {
+            region,
+            scale,
+            zoom,
+        },
+        reply,
+    )?;
+    let image = captured
+        .await
+        .map_err(|_| AppError::Internal("view capture was aborted".into()))??;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let (width, height) = (width as u32, height as u32);
+        // Snapshots that come back at the display's resolution are brought up to the request
+        let image = if image.dimensions() == (width, height) {
+            image
+        } else {
+            image::imageops::resize(&image, width, height, FilterType::Lanczos3)
+        };
+        let tmp = path.with_extension("png.tmp");
+        image
+            .write_to(&mut BufWriter::new(File::create(&tmp)?), ImageFormat::Png)
+            .map_err(|e| AppError::Internal(format!("cannot encode view capture: {e}")))?;
+        fs::rename(&tmp, &path)?;
+        tracing::debug!(width, height, path = %path.display(), "view captured");
+        Ok(ViewCapture {
+            path,
+            width,
+            height,
+        })
+    })
+    .await?
+}
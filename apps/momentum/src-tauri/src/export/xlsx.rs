@@ -0,0 +1,228 @@
+//! Native Excel export driven by a report description from the frontend.
+//!
+//! Worksheets are written in constant-memory mode, so row data streams to disk as it is
+//! written and a large estimate never holds a whole workbook in memory.
+
+use std::path::PathBuf;
+
+use rust_xlsxwriter::{Format, Formula, Workbook};
+use serde::Deserialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, State};
+
+use super::{ExportProgress, ExportSummary, PROGRESS_EVENT};
+use crate::error::{AppError, AppResult};
+use crate::progress;
+use crate::state::AppState;
+use crate::tasks::TaskKind;
+use crate::wake_lock;
+
+const PROGRESS_EVERY: u64 = 1000;
+// Excel's own sheet limit; rows past it would fail deep inside the writer
+const MAX_ROWS: usize = 1_048_575;
+
+/// A workbook to write.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XlsxReport {
+    pub sheets: Vec<SheetSpec>,
+}
+
+/// One worksheet: a header row followed by data rows.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SheetSpec {
+    pub name: String,
+    pub columns: Vec<ColumnSpec>,
+    /// Cells by column; numbers, strings, booleans, `null`, or `{ "formula": "=A2*B2" }`.
+    #[serde(default)]
+    pub rows: Vec<Vec<Value>>,
+    /// Keep the header row visible while scrolling.
+    #[serde(default = "default_true")]
+    pub freeze_header: bool,
+    #[serde(default)]
+    pub autofilter: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Header and formatting for a column.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnSpec {
+    pub header: String,
+    /// Width in Excel character units.
+    pub width: Option<f64>,
+    /// Excel number format such as `$#,##0.00` or `0.0%`.
+    pub num_format: Option<String>,
+    /// Formula written on every row instead of the row's value, with `{row}` replaced by the
+    /// 1-based row number, e.g. `=B{row}*C{row}`.
+    pub formula: Option<String>,
+}
+
+fn write_sheet(
+    workbook: &mut Workbook,
+    sheet: &SheetSpec,
+    header: &Format,
+    mut progress: impl FnMut(u64) -> AppResult<()>,
+) -> AppResult<()> {
+    if sheet.rows.len() > MAX_ROWS {
+        return Err(AppError::InvalidInput(format!(
+            "sheet {} has {} rows; Excel allows {MAX_ROWS}",
+            sheet.name,
+            sheet.rows.len()
+        )));
+    }
+    let worksheet = workbook.add_worksheet_with_constant_memory();
+    worksheet.set_name(&sheet.name)?;
+
+    let formats: Vec<Format> = sheet
+        .columns
+        .iter()
+        .map(|c| match &c.num_format {
+            Some(num_format) => Format::new().set_num_format(num_format),
+            None => Format::new(),
+        })
+        .collect();
+    for (col, column) in sheet.columns.iter().enumerate() {
+        let col = col as u16;
+        if let Some(width) = column.width {
+            worksheet.set_column_width(col, width)?;
+        }
+        worksheet.write_string_with_format(0, col, &column.header, header)?;
+    }
+    if sheet.freeze_header {
+        worksheet.set_freeze_panes(1, 0)?;
+    }
+
+    for (index, cells) in sheet.rows.iter().enumerate() {
+        let row = index as u32 + 1;
+        // Excel numbers rows from 1 and the header takes the first
+        let excel_row = row + 1;
+        for (col, column) in sheet.columns.iter().enumerate() {
+            let format = &formats[col];
+            let col = col as u16;
+            if let Some(template) = &column.formula {
+                let formula = template.replace("{row}", &excel_row.to_string());
+                worksheet.write_formula_with_format(row, col, Formula::new(formula), format)?;
+                continue;
+            }
+            // Strings are never treated as formulas, so cell text from users cannot inject one
+            match cells.get(col as usize).unwrap_or(&Value::Null) {
+                Value::Null => {
+                    worksheet.write_blank(row, col, format)?;
+                }
+                Value::Bool(value) => {
+                    worksheet.write_boolean_with_format(row, col, *value, format)?;
+                }
+                Value::Number(value) => {
+                    let value = value.as_f64().unwrap_or_default();
+                    worksheet.write_number_with_format(row, col, value, format)?;
+                }
+                Value::String(value) => {
+                    worksheet.write_string_with_format(row, col, value, format)?;
+                }
+                Value::Object(object) => match object.get("formula").and_then(Value::as_str) {
+                    Some(formula) => {
+                        worksheet.write_formula_with_format(
+                            row,
+                            col,
+                            Formula::new(formula),
+                            format,
+                        )?;
+                    }
+                    None => {
+                        return Err(AppError::InvalidInput(format!(
+                            "sheet {} row {excel_row} column {col}: objects must carry a formula",
+                            sheet.name
+                        )))
+                    }
+                },
+                Value::Array(_) => {
+                    return Err(AppError::InvalidInput(format!(
+                        "sheet {} row {excel_row} column {col}: arrays are not cell values",
+                        sheet.name
+                    )))
+                }
+            }
+        }
+        progress(1)?;
+    }
+
+    if sheet.autofilter && !sheet.columns.is_empty() {
+        let last_col = sheet.columns.len() as u16 - 1;
+        worksheet.autofilter(0, 0, sheet.rows.len() as u32, last_col)?;
+    }
+    Ok(())
+}
+
+/// Write `report` to `path` as an .xlsx workbook.
+///
+/// `export_id` tags progress events so the caller can tell concurrent exports apart, and is
+/// the task id to cancel it by.
+#[tauri::command]
+pub async fn export_xlsx(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    path: PathBuf,
+    report: XlsxReport,
+    export_id: Option<String>,
+) -> AppResult<ExportSummary> {
+    if report.sheets.is_empty() {
+        return Err(AppError::InvalidInput(
+            "a workbook needs at least one sheet".into(),
+        ));
+    }
+    let _awake = wake_lock::hold(&app, "Exporting a workbook");
+
+    let handle = app.clone();
+    state
+        .tasks()
+        .run(&handle, TaskKind::Export, export_id, |task| async move {
+            let export_id = task.id().to_string();
+            tauri::async_runtime::spawn_blocking(move || {
+                let total_rows: u64 = report.sheets.iter().map(|s| s.rows.len() as u64).sum();
+                let taskbar = progress::track(&app, format!("export:{export_id}"));
+                let emit = |rows_written| {
+                    taskbar.set(rows_written, Some(total_rows));
+                    task.progress(rows_written, Some(total_rows));
+                    let _ = app.emit(
+                        PROGRESS_EVENT,
+                        ExportProgress {
+                            export_id: export_id.clone(),
+                            rows_written,
+                            total_rows,
+                        },
+                    );
+                };
+
+                let mut workbook = Workbook::new();
+                let header = Format::new().set_bold();
+                let mut rows_written = 0u64;
+                for sheet in &report.sheets {
+                    write_sheet(&mut workbook, sheet, &header, |rows| {
+                        task.check()?;
+                        rows_written += rows;
+                        if rows_written.is_multiple_of(PROGRESS_EVERY) {
+                            emit(rows_written);
+                        }
+                        Ok(())
+                    })?;
+                }
+                // Saving flushes every sheet to the zip container and can take a while on its own
+                workbook.save(&path)?;
+                emit(rows_written);
+                let shown = path.display();
+                tracing::info!(%export_id, rows_written, path = %shown, "xlsx export written");
+                Ok(ExportSummary {
+                    export_id,
+                    path,
+                    rows_written,
+                })
+            })
+            .await?
+        })
+        .await
+}
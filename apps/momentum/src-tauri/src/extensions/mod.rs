@@ -0,0 +1,991 @@
+//! Third-party extensions, run as sandboxed WebAssembly modules.
+//!
+//! An extension is a zip holding `manifest.json` and `extension.wasm`, installed into its own
+//! folder under `data_dir/extensions`. The manifest lists the capabilities it needs, and
+//! install refuses a module that imports anything beyond the host functions those
+//! capabilities unlock, so an extension has no way to reach files, the network, or data it
+//! was not granted. New installs start disabled so the capabilities can be reviewed first.
+//!
+//! Enabling an extension runs its `activate` export, where it adds menu items and import
+//! formats. Menu items appear in the Extensions menu and reach the frontend as
+//! `menu:extension`, which answers with `run_extension_menu_item`; import formats are used
+//! through `import_with_extension`. Every call gets a fresh instance under the limits in
+//! [`wasm`].
+//!
+//! Exports an extension may provide, all optional: `activate()`, `on_menu(ptr, len)` with
+//! `{"itemId", "projectId"}`, and `import_file(ptr, len) -> i32` with the file's bytes,
+//! returning 0 on success. Passing input needs `alloc(len) -> ptr`. Text and JSON go both
+//! ways as a pointer and length into the module's memory; host results are fetched by
+//! length, then `read_result(ptr)`.
+
+pub mod wasm;
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Manager, State};
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use crate::db::now_ms;
+use crate::db::projects::{Item, Project};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::import::{ImportSchema, ImportSummary, Importer};
+use crate::state::AppState;
+use crate::tasks::TaskKind;
+use wasm::ValType::I32;
+use wasm::{Host, Import, Instance, Module, ValType, WasmError};
+
+const EXTENSIONS_DIR_NAME: &str = "extensions";
+const REGISTRY_FILE: &str = "extensions.json";
+const MANIFEST_FILE: &str = "manifest.json";
+const MODULE_FILE: &str = "extension.wasm";
+const HOST_MODULE: &str = "truss";
+const MAX_MANIFEST_BYTES: u64 = 64 * 1024;
+const MAX_ID_LEN: usize = 100;
+const MAX_LABEL_LEN: usize = 100;
+// Per extension, across everything `activate` registers
+const MAX_CONTRIBUTIONS: usize = 50;
+const MAX_HOST_TEXT: usize = 1024 * 1024;
+const MAX_LOG_LINES: usize = 200;
+// The file has to fit in the module's memory alongside whatever it parses it into
+const MAX_IMPORT_BYTES: u64 = 8 * 1024 * 1024;
+const MAX_IMPORT_ROWS: usize = 500_000;
+
+/// Something an extension may do, granted by installing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Capability {
+    /// Read a project and its items.
+    #[serde(rename = "projects:read")]
+    ProjectsRead,
+    /// Add items to the Extensions menu.
+    #[serde(rename = "menu")]
+    Menu,
+    /// Register import formats and turn files into item rows.
+    #[serde(rename = "import")]
+    Import,
+}
+
+impl Capability {
+    fn name(self) -> &'static str {
+        match self {
+            Self::ProjectsRead => "projects:read",
+            Self::Menu => "menu",
+            Self::Import => "import",
+        }
+    }
+}
+
+/// `manifest.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtensionManifest {
+    /// Reverse-DNS style, e.g. `com.example.rsmeans`.
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+}
+
+/// A menu item an extension added.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtensionMenuItem {
+    pub id: String,
+    pub label: String,
+}
+
+/// A file format an extension can import.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtensionImportFormat {
+    pub id: String,
+    pub name: String,
+    /// File extensions it reads, without the dot.
+    #[serde(default)]
+    pub extensions: Vec<String>,
+}
+
+/// What an enabled extension registered when it activated.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Contributions {
+    pub menu_items: Vec<ExtensionMenuItem>,
+    pub import_formats: Vec<ExtensionImportFormat>,
+}
+
+/// An installed extension as `list_extensions` reports it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstalledExtension {
+    #[serde(flatten)]
+    pub manifest: ExtensionManifest,
+    pub enabled: bool,
+    pub installed_at: i64,
+    pub contributions: Contributions,
+    /// Why the extension failed to activate, when it did.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RegistryEntry {
+    enabled: bool,
+    installed_at: i64,
+}
+
+#[derive(Clone)]
+struct Loaded {
+    module: Arc<Module>,
+    contributions: Contributions,
+}
+
+/// Installed extensions and the ones running, registered in app state.
+pub struct Extensions {
+    dir: PathBuf,
+    registry: Mutex<HashMap<String, RegistryEntry>>,
+    loaded: Mutex<HashMap<String, Loaded>>,
+    errors: Mutex<HashMap<String, String>>,
+}
+
+/// Open the extensions folder under `data_dir/extensions`.
+pub fn init(data_dir: &Path) -> AppResult<Extensions> {
+    let dir = data_dir.join(EXTENSIONS_DIR_NAME);
+    fs::create_dir_all(&dir)?;
+    let registry = fs::read(dir.join(REGISTRY_FILE))
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default();
+    Ok(Extensions {
+        dir,
+        registry: Mutex::new(registry),
+        loaded: Mutex::new(HashMap::new()),
+        errors: Mutex::new(HashMap::new()),
+    })
+}
+
+struct HostFunction {
+    name: &'static str,
+    params: &'static [ValType],
+    results: &'static [ValType],
+    capability: Option<Capability>,
+}
+
+const fn host_fn(
+    name: &'static str,
+    params: &'static [ValType],
+    results: &'static [ValType],
+    capability: Option<Capability>,
+) -> HostFunction {
+    HostFunction {
+        name,
+        params,
+        results,
+        capability,
+    }
+}
+
+/// Everything an extension can import from `truss`.
+const HOST_FUNCTIONS: &[HostFunction] = &[
+    // log(ptr, len): a line in the app log
+    host_fn("log", &[I32, I32], &[], None),
+    // read_result(ptr): copy the last result a host function produced
+    host_fn("read_result", &[I32], &[], None),
+    // reply(ptr, len): text the command that called the extension returns
+    host_fn("reply", &[I32, I32], &[], None),
+    // project_get(id_ptr, id_len) -> len: `{project, items}` as JSON, or -1 if there is none
+    host_fn(
+        "project_get",
+        &[I32, I32],
+        &[I32],
+        Some(Capability::ProjectsRead),
+    ),
+    // menu_add(ptr, len): `{id, label}`, during activate
+    host_fn("menu_add", &[I32, I32], &[], Some(Capability::Menu)),
+    // import_register(ptr, len): `{id, name, extensions}`, during activate
+    host_fn(
+        "import_register",
+        &[I32, I32],
+        &[],
+        Some(Capability::Import),
+    ),
+    // import_headers(ptr, len) and import_row(ptr, len): JSON arrays of cell text
+    host_fn("import_headers", &[I32, I32], &[], Some(Capability::Import)),
+    host_fn("import_row", &[I32, I32], &[], Some(Capability::Import)),
+];
+
+// Exports the host calls, with the signatures it calls them with
+const EXPORTS: &[(&str, &[ValType], &[ValType])] = &[
+    ("activate", &[], &[]),
+    ("alloc", &[I32], &[I32]),
+    ("on_menu", &[I32, I32], &[]),
+    ("import_file", &[I32, I32], &[I32]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Activate,
+    Menu,
+    Import,
+}
+
+// Ids become folder and menu item names
+fn validate_id(what: &str, id: &str) -> AppResult<()> {
+    let valid = !id.is_empty()
+        && id.len() <= MAX_ID_LEN
+        && !id.starts_with('.')
+        && id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '-' | '_'));
+    if !valid {
+        return Err(AppError::InvalidInput(format!(
+            "invalid {what} id {id}; use lowercase letters, digits, dots, dashes, and underscores"
+        )));
+    }
+    Ok(())
+}
+
+fn validate_label(what: &str, label: &str) -> AppResult<()> {
+    let len = label.trim().chars().count();
+    if len == 0 || len > MAX_LABEL_LEN {
+        return Err(AppError::InvalidInput(format!(
+            "{what} must be 1 to {MAX_LABEL_LEN} characters"
+        )));
+    }
+    Ok(())
+}
+
+fn validate_manifest(manifest: &ExtensionManifest) -> AppResult<()> {
+    validate_id("extension", &manifest.id)?;
+    validate_label("extension name", &manifest.name)?;
+    validate_label("extension version", &manifest.version)?;
+    Ok(())
+}
+
+// Only host functions the granted capabilities unlock, with the signatures the host calls
+fn check_module(module: &Module, manifest: &ExtensionManifest) -> AppResult<()> {
+    let invalid = |message: String| AppError::Extension {
+        extension: manifest.id.clone(),
+        message,
+    };
+    for import in module.imports() {
+        let function = HOST_FUNCTIONS
+            .iter()
+            .find(|f| import.module == HOST_MODULE && f.name == import.name)
+            .ok_or_else(|| {
+                invalid(format!(
+                    "it imports {}.{}, which is not part of the extension API",
+                    import.module, import.name
+                ))
+            })?;
+        if import.ty.params != function.params || import.ty.results != function.results {
+            return Err(invalid(format!(
+                "it imports {} with the wrong signature",
+                import.name
+            )));
+        }
+        if let Some(capability) = function.capability {
+            if !manifest.capabilities.contains(&capability) {
+                return Err(invalid(format!(
+                    "it uses {} without asking for the {} capability",
+                    import.name,
+                    capability.name()
+                )));
+            }
+        }
+    }
+    for (name, params, results) in EXPORTS {
+        if let Some(ty) = module.export_type(name) {
+            if ty.params != *params || ty.results != *results {
+                return Err(invalid(format!(
+                    "it exports {name} with the wrong signature"
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn package_entry(
+    zip: &mut ZipArchive<BufReader<File>>,
+    name: &str,
+    limit: u64,
+) -> AppResult<Vec<u8>> {
+    let entry = zip.by_name(name).map_err(|err| match err {
+        ZipError::FileNotFound => {
+            AppError::InvalidInput(format!("not an extension package: {name} is missing"))
+        }
+        err => err.into(),
+    })?;
+    let mut bytes = Vec::new();
+    entry.take(limit + 1).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > limit {
+        return Err(AppError::InvalidInput(format!("{name} is too large")));
+    }
+    Ok(bytes)
+}
+
+// Read and check a package without installing anything
+fn read_package(path: &Path) -> AppResult<(ExtensionManifest, Vec<u8>)> {
+    let mut zip = ZipArchive::new(BufReader::new(File::open(path)?))?;
+    let manifest: ExtensionManifest =
+        serde_json::from_slice(&package_entry(&mut zip, MANIFEST_FILE, MAX_MANIFEST_BYTES)?)?;
+    validate_manifest(&manifest)?;
+    let bytes = package_entry(&mut zip, MODULE_FILE, wasm::MAX_MODULE_BYTES as u64)?;
+    Ok((manifest, bytes))
+}
+
+fn decode(manifest: &ExtensionManifest, bytes: &[u8]) -> AppResult<Arc<Module>> {
+    let module = Module::decode(bytes).map_err(|err| AppError::Extension {
+        extension: manifest.id.clone(),
+        message: err.to_string(),
+    })?;
+    check_module(&module, manifest)?;
+    Ok(Arc::new(module))
+}
+
+fn trap<T>(message: impl Into<String>) -> Result<T, WasmError> {
+    Err(WasmError(message.into()))
+}
+
+fn text(memory: &[u8], ptr: u64, len: u64) -> Result<String, WasmError> {
+    let (ptr, len) = (ptr as u32 as usize, len as u32 as usize);
+    if len > MAX_HOST_TEXT {
+        return trap("text passed to the host is too long");
+    }
+    let Some(bytes) = memory.get(ptr..ptr + len) else {
+        return trap("text passed to the host is outside the module's memory");
+    };
+    String::from_utf8(bytes.to_vec()).or_else(|_| trap("text passed to the host is not UTF-8"))
+}
+
+fn parse<T: serde::de::DeserializeOwned>(memory: &[u8], args: &[u64]) -> Result<T, WasmError> {
+    let json = text(memory, args[0], args[1])?;
+    serde_json::from_str(&json)
+        .or_else(|err| trap(format!("invalid JSON from the extension: {err}")))
+}
+
+fn project_json(conn: &Connection, project_id: &str) -> AppResult<Option<Vec<u8>>> {
+    let Some(project) = conn
+        .query_row(
+            "SELECT * FROM projects WHERE id = ?1",
+            [project_id],
+            Project::from_row,
+        )
+        .optional()?
+    else {
+        return Ok(None);
+    };
+    let mut stmt =
+        conn.prepare("SELECT * FROM items WHERE project_id = ?1 ORDER BY sort_order, name")?;
+    let items = stmt
+        .query_map([project_id], Item::from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(Some(serde_json::to_vec(&json!({
+        "project": project,
+        "items": items,
+    }))?))
+}
+
+/// The host side of one extension call.
+struct ExtensionHost<'a> {
+    extension: &'a str,
+    phase: Phase,
+    conn: Option<&'a Connection>,
+    result: Vec<u8>,
+    reply: Option<String>,
+    contributions: Contributions,
+    headers: Option<Vec<String>>,
+    rows: Vec<Vec<String>>,
+    logged: usize,
+}
+
+impl<'a> ExtensionHost<'a> {
+    fn new(extension: &'a str, phase: Phase, conn: Option<&'a Connection>) -> Self {
+        Self {
+            extension,
+            phase,
+            conn,
+            result: Vec::new(),
+            reply: None,
+            contributions: Contributions::default(),
+            headers: None,
+            rows: Vec::new(),
+            logged: 0,
+        }
+    }
+
+    fn during(&self, phase: Phase, name: &str) -> Result<(), WasmError> {
+        if self.phase != phase {
+            return trap(format!("{name} cannot be called here"));
+        }
+        Ok(())
+    }
+
+    fn contribute(&mut self) -> Result<(), WasmError> {
+        let count = self.contributions.menu_items.len() + self.contributions.import_formats.len();
+        if count >= MAX_CONTRIBUTIONS {
+            return trap("the extension registered too many menu items and formats");
+        }
+        Ok(())
+    }
+}
+
+impl Host for ExtensionHost<'_> {
+    fn call(
+        &mut self,
+        import: &Import,
+        args: &[u64],
+        memory: &mut [u8],
+    ) -> Result<Vec<u64>, WasmError> {
+        if args.len() != import.ty.params.len() {
+            return trap("wrong number of arguments to the host");
+        }
+        match import.name.as_str() {
+            "log" => {
+                let line = text(memory, args[0], args[1])?;
+                if self.logged < MAX_LOG_LINES {
+                    self.logged += 1;
+                    tracing::info!(extension = self.extension, "{line}");
+                }
+            }
+            "read_result" => {
+                let ptr = args[0] as u32 as usize;
+                let Some(target) = memory.get_mut(ptr..ptr + self.result.len()) else {
+                    return trap("read_result target is outside the module's memory");
+                };
+                target.copy_from_slice(&self.result);
+            }
+            "reply" => self.reply = Some(text(memory, args[0], args[1])?),
+            "project_get" => {
+                let project_id = text(memory, args[0], args[1])?;
+                let found = match self.conn {
+                    Some(conn) => project_json(conn, &project_id)
+                        .or_else(|err| trap(format!("cannot read the project: {err}")))?,
+                    None => None,
+                };
+                let Some(bytes) = found else {
+                    return Ok(vec![u64::from(u32::MAX)]);
+                };
+                if bytes.len() > i32::MAX as usize {
+                    return trap("the project is too large to pass to an extension");
+                }
+                let len = bytes.len() as u64;
+                self.result = bytes;
+                return Ok(vec![len]);
+            }
+            "menu_add" => {
+                self.during(Phase::Activate, "menu_add")?;
+                self.contribute()?;
+                let item: ExtensionMenuItem = parse(memory, args)?;
+                validate_id("menu item", &item.id)
+                    .and_then(|()| validate_label("menu item label", &item.label))
+                    .or_else(|err| trap(err.to_string()))?;
+                self.contributions
+                    .menu_items
+                    .retain(|existing| existing.id != item.id);
+                self.contributions.menu_items.push(item);
+            }
+            "import_register" => {
+                self.during(Phase::Activate, "import_register")?;
+                self.contribute()?;
+                let format: ExtensionImportFormat = parse(memory, args)?;
+                validate_id("import format", &format.id)
+                    .and_then(|()| validate_label("import format name", &format.name))
+                    .or_else(|err| trap(err.to_string()))?;
+                self.contributions
+                    .import_formats
+                    .retain(|existing| existing.id != format.id);
+                self.contributions.import_formats.push(format);
+            }
+            "import_headers" => {
+                self.during(Phase::Import, "import_headers")?;
+                self.headers = Some(parse(memory, args)?);
+            }
+            "import_row" => {
+                self.during(Phase::Import, "import_row")?;
+                if self.headers.is_none() {
+                    return trap("import_row called before import_headers");
+                }
+                if self.rows.len() >= MAX_IMPORT_ROWS {
+                    return trap("the file has too many rows");
+                }
+                let row = parse(memory, args)?;
+                self.rows.push(row);
+            }
+            name => return trap(format!("unknown host function {name}")),
+        }
+        Ok(Vec::new())
+    }
+}
+
+// Make an instance, pass `input` through `alloc` if there is any, and call `export`
+fn run<'a>(
+    id: &str,
+    module: &Arc<Module>,
+    host: ExtensionHost<'a>,
+    export: &str,
+    input: Option<&[u8]>,
+) -> AppResult<(ExtensionHost<'a>, Vec<u64>)> {
+    let failed = |err: WasmError| AppError::Extension {
+        extension: id.to_string(),
+        message: err.to_string(),
+    };
+    let mut instance = Instance::new(Arc::clone(module), host).map_err(failed)?;
+    let args = match input {
+        None => Vec::new(),
+        Some(bytes) => {
+            let len = u32::try_from(bytes.len())
+                .map_err(|_| failed(WasmError("the input is too large".into())))?;
+            let ptr = instance
+                .invoke("alloc", &[u64::from(len)])
+                .map_err(failed)?
+                .first()
+                .map_or(0, |ptr| *ptr as u32 as usize);
+            let Some(target) = instance.memory_mut().get_mut(ptr..ptr + bytes.len()) else {
+                return Err(failed(WasmError(
+                    "alloc returned memory outside the module".into(),
+                )));
+            };
+            target.copy_from_slice(bytes);
+            vec![ptr as u64, u64::from(len)]
+        }
+    };
+    let results = instance.invoke(export, &args).map_err(failed)?;
+    Ok((instance.into_host(), results))
+}
+
+impl Extensions {
+    fn extension_dir(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+
+    fn save_registry(&self, registry: &HashMap<String, RegistryEntry>) -> AppResult<()> {
+        let path = self.dir.join(REGISTRY_FILE);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(registry)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    fn manifest(&self, id: &str) -> AppResult<ExtensionManifest> {
+        let bytes =
+            fs::read(self.extension_dir(id).join(MANIFEST_FILE)).map_err(|err| {
+                match err.kind() {
+                    std::io::ErrorKind::NotFound => AppError::not_found("extension", id),
+                    _ => err.into(),
+                }
+            })?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    fn loaded(&self, id: &str) -> AppResult<Loaded> {
+        self.loaded
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+            .cloned()
+            .ok_or_else(|| AppError::InvalidInput(format!("extension {id} is not enabled")))
+    }
+
+    /// Load an installed extension and run its `activate` export.
+    fn activate(&self, id: &str) -> AppResult<()> {
+        let manifest = self.manifest(id)?;
+        let bytes = fs::read(self.extension_dir(id).join(MODULE_FILE))?;
+        let module = decode(&manifest, &bytes)?;
+        let contributions = if module.export_type("activate").is_some() {
+            let host = ExtensionHost::new(id, Phase::Activate, None);
+            let (host, _) = run(id, &module, host, "activate", None)?;
+            host.contributions
+        } else {
+            Contributions::default()
+        };
+        tracing::info!(
+            extension = id,
+            menu_items = contributions.menu_items.len(),
+            import_formats = contributions.import_formats.len(),
+            "extension activated"
+        );
+        self.loaded
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(
+                id.to_string(),
+                Loaded {
+                    module,
+                    contributions,
+                },
+            );
+        Ok(())
+    }
+
+    // Activate, remembering a failure for `list_extensions` instead of returning it
+    fn try_activate(&self, id: &str) {
+        let result = self.activate(id);
+        let mut errors = self.errors.lock().unwrap_or_else(|e| e.into_inner());
+        match result {
+            Ok(()) => {
+                errors.remove(id);
+            }
+            Err(err) => {
+                tracing::warn!(extension = id, %err, "extension failed to activate");
+                errors.insert(id.to_string(), err.to_string());
+            }
+        }
+    }
+
+    fn deactivate(&self, id: &str) {
+        self.loaded
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(id);
+        self.errors
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(id);
+    }
+
+    fn describe(&self, id: &str, entry: &RegistryEntry) -> AppResult<InstalledExtension> {
+        let contributions = self
+            .loaded
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+            .map(|loaded| loaded.contributions.clone())
+            .unwrap_or_default();
+        Ok(InstalledExtension {
+            manifest: self.manifest(id)?,
+            enabled: entry.enabled,
+            installed_at: entry.installed_at,
+            contributions,
+            error: self
+                .errors
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get(id)
+                .cloned(),
+        })
+    }
+
+    fn entry(&self, id: &str) -> AppResult<RegistryEntry> {
+        self.registry
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+            .cloned()
+            .ok_or_else(|| AppError::not_found("extension", id))
+    }
+}
+
+// Rebuild the Extensions menu from every running extension
+fn refresh_menu(app: &AppHandle) {
+    #[cfg(desktop)]
+    {
+        let extensions = app.state::<Extensions>();
+        let mut items: Vec<(String, String, String)> = extensions
+            .loaded
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .flat_map(|(id, loaded)| {
+                loaded
+                    .contributions
+                    .menu_items
+                    .iter()
+                    .map(|item| (id.clone(), item.id.clone(), item.label.clone()))
+            })
+            .collect();
+        items.sort_by_key(|(_, _, label)| label.to_lowercase());
+        if let Err(err) = crate::menu::set_extension_items(app, &items) {
+            tracing::warn!(%err, "cannot update the extensions menu");
+        }
+    }
+    #[cfg(not(desktop))]
+    let _ = app;
+}
+
+/// Activate every enabled extension in the background.
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let extensions = app.state::<Extensions>();
+        let enabled: Vec<String> = extensions
+            .registry
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|(_, entry)| entry.enabled)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &enabled {
+            extensions.try_activate(id);
+        }
+        if !enabled.is_empty() {
+            refresh_menu(&app);
+        }
+    });
+}
+
+/// Read an extension package without installing it, so its capabilities can be reviewed.
+#[tauri::command]
+pub async fn inspect_extension(path: PathBuf) -> AppResult<ExtensionManifest> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let (manifest, bytes) = read_package(&path)?;
+        decode(&manifest, &bytes)?;
+        Ok(manifest)
+    })
+    .await?
+}
+
+/// Install an extension package, or upgrade one already installed.
+///
+/// New installs start disabled. An upgrade stays enabled unless it asks for capabilities
+/// the installed version did not have.
+#[tauri::command]
+pub async fn install_extension(app: AppHandle, path: PathBuf) -> AppResult<InstalledExtension> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let extensions = app.state::<Extensions>();
+        let (manifest, bytes) = read_package(&path)?;
+        decode(&manifest, &bytes)?;
+        let id = manifest.id.clone();
+        let previous = extensions.entry(&id).ok();
+        let keep_enabled = previous.is_some_and(|entry| entry.enabled)
+            && extensions.manifest(&id).is_ok_and(|installed| {
+                manifest
+                    .capabilities
+                    .iter()
+                    .all(|capability| installed.capabilities.contains(capability))
+            });
+
+        // Staged beside the final folder so a failed install never leaves half an extension
+        let staging = extensions.dir.join(format!(".{id}.partial"));
+        let _ = fs::remove_dir_all(&staging);
+        fs::create_dir_all(&staging)?;
+        fs::write(
+            staging.join(MANIFEST_FILE),
+            serde_json::to_vec_pretty(&manifest)?,
+        )?;
+        fs::write(staging.join(MODULE_FILE), &bytes)?;
+        extensions.deactivate(&id);
+        let target = extensions.extension_dir(&id);
+        let _ = fs::remove_dir_all(&target);
+        fs::rename(&staging, &target)?;
+
+        let entry = RegistryEntry {
+            enabled: keep_enabled,
+            installed_at: now_ms(),
+        };
+        {
+            let mut registry = extensions
+                .registry
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            registry.insert(id.clone(), entry.clone());
+            extensions.save_registry(&registry)?;
+        }
+        tracing::info!(extension = %id, version = %manifest.version, "extension installed");
+        if keep_enabled {
+            extensions.try_activate(&id);
+        }
+        refresh_menu(&app);
+        extensions.describe(&id, &entry)
+    })
+    .await?
+}
+
+#[tauri::command]
+pub async fn list_extensions(
+    extensions: State<'_, Extensions>,
+) -> AppResult<Vec<InstalledExtension>> {
+    let registry = extensions
+        .registry
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    let mut installed = Vec::with_capacity(registry.len());
+    for (id, entry) in &registry {
+        match extensions.describe(id, entry) {
+            Ok(extension) => installed.push(extension),
+            Err(err) => tracing::warn!(extension = %id, %err, "skipping unreadable extension"),
+        }
+    }
+    installed.sort_by(|a, b| {
+        a.manifest
+            .name
+            .to_lowercase()
+            .cmp(&b.manifest.name.to_lowercase())
+    });
+    Ok(installed)
+}
+
+/// Turn an extension on, running its `activate`, or off.
+#[tauri::command]
+pub async fn set_extension_enabled(
+    app: AppHandle,
+    id: String,
+    enabled: bool,
+) -> AppResult<InstalledExtension> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let extensions = app.state::<Extensions>();
+        let mut entry = extensions.entry(&id)?;
+        if enabled {
+            // Refuse to enable one that cannot start, rather than leaving it on and broken
+            extensions.activate(&id)?;
+        } else {
+            extensions.deactivate(&id);
+        }
+        entry.enabled = enabled;
+        {
+            let mut registry = extensions
+                .registry
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            registry.insert(id.clone(), entry.clone());
+            extensions.save_registry(&registry)?;
+        }
+        refresh_menu(&app);
+        extensions.describe(&id, &entry)
+    })
+    .await?
+}
+
+#[tauri::command]
+pub async fn remove_extension(app: AppHandle, id: String) -> AppResult<()> {
+    validate_id("extension", &id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let extensions = app.state::<Extensions>();
+        extensions.entry(&id)?;
+        extensions.deactivate(&id);
+        {
+            let mut registry = extensions
+                .registry
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            registry.remove(&id);
+            extensions.save_registry(&registry)?;
+        }
+        match fs::remove_dir_all(extensions.extension_dir(&id)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+        tracing::info!(extension = %id, "extension removed");
+        refresh_menu(&app);
+        Ok(())
+    })
+    .await?
+}
+
+/// Run an extension's `on_menu` for one of its menu items; returns what it replied, if anything.
+#[tauri::command]
+pub async fn run_extension_menu_item(
+    db: State<'_, Db>,
+    extensions: State<'_, Extensions>,
+    extension_id: String,
+    item_id: String,
+    project_id: Option<String>,
+) -> AppResult<Option<String>> {
+    let loaded = extensions.loaded(&extension_id)?;
+    if !loaded
+        .contributions
+        .menu_items
+        .iter()
+        .any(|item| item.id == item_id)
+    {
+        return Err(AppError::not_found("extension menu item", item_id));
+    }
+    let input = serde_json::to_vec(&json!({ "itemId": item_id, "projectId": project_id }))?;
+    db.run(move |conn| {
+        let host = ExtensionHost::new(&extension_id, Phase::Menu, Some(conn));
+        let (host, _) = run(&extension_id, &loaded.module, host, "on_menu", Some(&input))?;
+        Ok(host.reply)
+    })
+    .await
+}
+
+/// Import a file into a project through an extension's import format.
+///
+/// The extension turns the file into rows, which then go through the same column mapping,
+/// validation, progress events, and cancellation by `import_id` as a CSV import.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn import_with_extension(
+    app: AppHandle,
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    extensions: State<'_, Extensions>,
+    extension_id: String,
+    format_id: String,
+    project_id: String,
+    path: PathBuf,
+    schema: ImportSchema,
+    import_id: Option<String>,
+) -> AppResult<ImportSummary> {
+    let loaded = extensions.loaded(&extension_id)?;
+    if !loaded
+        .contributions
+        .import_formats
+        .iter()
+        .any(|format| format.id == format_id)
+    {
+        return Err(AppError::not_found("import format", format_id));
+    }
+    if tokio::fs::metadata(&path).await?.len() > MAX_IMPORT_BYTES {
+        return Err(AppError::InvalidInput(format!(
+            "extension imports are limited to {} MB",
+            MAX_IMPORT_BYTES / (1024 * 1024)
+        )));
+    }
+    let bytes = tokio::fs::read(&path).await?;
+    let handle = app.clone();
+    state
+        .tasks()
+        .run(&handle, TaskKind::Import, import_id, |task| {
+            db.run(move |conn| {
+                let host = ExtensionHost::new(&extension_id, Phase::Import, Some(conn));
+                let (host, status) = run(
+                    &extension_id,
+                    &loaded.module,
+                    host,
+                    "import_file",
+                    Some(&bytes),
+                )?;
+                let ExtensionHost {
+                    headers,
+                    rows,
+                    reply,
+                    ..
+                } = host;
+                if status.first() != Some(&0) {
+                    return Err(AppError::Extension {
+                        extension: extension_id,
+                        message: reply.unwrap_or_else(|| format!("cannot read {}", path.display())),
+                    });
+                }
+                let headers = headers.ok_or_else(|| AppError::Extension {
+                    extension: extension_id.clone(),
+                    message: "the extension found no column headers".into(),
+                })?;
+                let mut importer = Importer::new(conn, app, task, project_id, schema, &headers)?;
+                // Numbered as a spreadsheet would, after the header row
+                for (index, row) in rows.iter().enumerate() {
+                    importer.push_row(index as u64 + 2, |column| {
+                        row.get(column).map(String::as_str)
+                    })?;
+                }
+                importer.finish("import_with_extension")
+            })
+        })
+        .await
+}
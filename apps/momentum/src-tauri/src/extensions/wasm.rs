@@ -0,0 +1,334 @@
+//! Running extension modules on the wasmi interpreter.
+//!
+//! Modules get whichever WebAssembly proposals wasmi enables by default, but may only import
+//! functions, which the [`Host`] implements; they get no tables, memories, or globals from
+//! outside. Every run is metered: fuel is handed out in slices and the clock is checked
+//! between them, so a module that runs too many instructions, too long, too deep, or grows
+//! its memory or table past the [`StoreLimits`] traps instead.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use wasmi::{
+    Caller, Config, Engine, Extern, ExternType, Linker, ResumableCall, Store, StoreLimits,
+    StoreLimitsBuilder, TrapCode, Val, F32, F64,
+};
+
+pub const MAX_MODULE_BYTES: usize = 16 * 1024 * 1024;
+// 32 MiB of linear memory
+const MAX_MEMORY_BYTES: usize = 32 * 1024 * 1024;
+const MAX_TABLE_LEN: usize = 100_000;
+const MAX_CALL_DEPTH: usize = 300;
+/// Fuel one call may burn, about one unit per instruction.
+pub const DEFAULT_FUEL: u64 = 200_000_000;
+/// Wall-clock time one call may run.
+pub const DEFAULT_TIME_LIMIT: Duration = Duration::from_secs(5);
+// The clock is read each time this much fuel runs out
+const FUEL_SLICE: u64 = 1 << 20;
+
+/// Why a module could not be loaded or stopped running.
+#[derive(Debug, Clone)]
+pub struct WasmError(pub String);
+
+impl fmt::Display for WasmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+type Result<T> = std::result::Result<T, WasmError>;
+
+fn fail<T>(message: impl Into<String>) -> Result<T> {
+    Err(WasmError(message.into()))
+}
+
+// Traps the limits raise get plain words; anything else keeps wasmi's message
+fn trapped(err: wasmi::Error) -> WasmError {
+    let message = match err.as_trap_code() {
+        Some(TrapCode::OutOfFuel) => "the extension ran too many instructions".to_string(),
+        Some(TrapCode::StackOverflow) => "call stack exhausted".to_string(),
+        Some(TrapCode::GrowthOperationLimited) => "the extension asked for too much memory".into(),
+        _ => err.to_string(),
+    };
+    WasmError(message)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl ValType {
+    fn from_wasmi(ty: wasmi::ValType) -> Result<Self> {
+        match ty {
+            wasmi::ValType::I32 => Ok(Self::I32),
+            wasmi::ValType::I64 => Ok(Self::I64),
+            wasmi::ValType::F32 => Ok(Self::F32),
+            wasmi::ValType::F64 => Ok(Self::F64),
+            other => fail(format!("{other:?} values are not supported")),
+        }
+    }
+
+    // Values cross the host boundary as raw bits, as the `Host` sees them
+    fn val(self, bits: u64) -> Val {
+        match self {
+            Self::I32 => Val::I32(bits as u32 as i32),
+            Self::I64 => Val::I64(bits as i64),
+            Self::F32 => Val::F32(F32::from_bits(bits as u32)),
+            Self::F64 => Val::F64(F64::from_bits(bits)),
+        }
+    }
+}
+
+fn bits(value: &Val) -> u64 {
+    match value {
+        Val::I32(v) => u64::from(*v as u32),
+        Val::I64(v) => *v as u64,
+        Val::F32(v) => u64::from(v.to_bits()),
+        Val::F64(v) => v.to_bits(),
+        // Signatures with other types are refused before anything is called
+        _ => 0,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuncType {
+    pub params: Vec<ValType>,
+    pub results: Vec<ValType>,
+}
+
+impl FuncType {
+    fn from_wasmi(ty: &wasmi::FuncType) -> Result<Self> {
+        let types = |types: &[wasmi::ValType]| {
+            types
+                .iter()
+                .map(|ty| ValType::from_wasmi(*ty))
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            params: types(ty.params())?,
+            results: types(ty.results())?,
+        })
+    }
+}
+
+/// A function the module expects the host to provide.
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub module: String,
+    pub name: String,
+    pub ty: FuncType,
+}
+
+/// A validated module, ready to instantiate.
+pub struct Module {
+    module: wasmi::Module,
+    imports: Vec<Import>,
+    // wasmi's own types for the imports, for linking them
+    import_types: Vec<wasmi::FuncType>,
+    // Exported functions whose signatures use only the types above
+    exports: HashMap<String, FuncType>,
+}
+
+impl Module {
+    /// Validate a binary module and check that it imports nothing but functions.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() > MAX_MODULE_BYTES {
+            return fail("module is too large");
+        }
+        let mut config = Config::default();
+        config
+            .consume_fuel(true)
+            .set_max_recursion_depth(MAX_CALL_DEPTH);
+        let engine = Engine::new(&config);
+        let module = wasmi::Module::new(&engine, bytes).map_err(trapped)?;
+        let (imports, import_types) = module
+            .imports()
+            .map(|import| {
+                let ExternType::Func(ty) = import.ty() else {
+                    return fail(format!(
+                        "modules may only import functions, not {}.{}",
+                        import.module(),
+                        import.name()
+                    ));
+                };
+                let converted = FuncType::from_wasmi(ty).map_err(|err| {
+                    WasmError(format!("{}.{}: {err}", import.module(), import.name()))
+                })?;
+                let import = Import {
+                    module: import.module().to_string(),
+                    name: import.name().to_string(),
+                    ty: converted,
+                };
+                Ok((import, ty.clone()))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+        let exports = module
+            .exports()
+            .filter_map(|export| {
+                let ty = FuncType::from_wasmi(export.ty().func()?).ok()?;
+                Some((export.name().to_string(), ty))
+            })
+            .collect();
+        Ok(Self {
+            module,
+            imports,
+            import_types,
+            exports,
+        })
+    }
+
+    pub fn imports(&self) -> &[Import] {
+        &self.imports
+    }
+
+    /// The signature of an exported function.
+    pub fn export_type(&self, name: &str) -> Option<&FuncType> {
+        self.exports.get(name)
+    }
+}
+
+/// What a module's imports call into.
+pub trait Host {
+    /// Run `import` with its arguments as raw bits; `memory` is the module's exported memory.
+    fn call(&mut self, import: &Import, args: &[u64], memory: &mut [u8]) -> Result<Vec<u64>>;
+}
+
+struct Data<H> {
+    host: H,
+    limits: StoreLimits,
+}
+
+/// A running module with its own store, under the limits above.
+pub struct Instance<H> {
+    store: Store<Data<H>>,
+    instance: wasmi::Instance,
+}
+
+// Forward an import to the host with the module's memory, converting values both ways
+fn host_call<H: Host>(
+    import: &Import,
+    mut caller: Caller<'_, Data<H>>,
+    params: &[Val],
+    results: &mut [Val],
+) -> std::result::Result<(), wasmi::Error> {
+    let args: Vec<u64> = params.iter().map(bits).collect();
+    let memory = caller.get_export("memory").and_then(Extern::into_memory);
+    let (memory, data) = match memory {
+        Some(memory) => memory.data_and_store_mut(&mut caller),
+        None => (&mut [][..], caller.data_mut()),
+    };
+    let values = data
+        .host
+        .call(import, &args, memory)
+        .map_err(|err| wasmi::Error::new(err.0))?;
+    if values.len() != results.len() {
+        return Err(wasmi::Error::new(format!(
+            "host function {} returned the wrong values",
+            import.name
+        )));
+    }
+    for ((slot, ty), value) in results.iter_mut().zip(&import.ty.results).zip(values) {
+        *slot = ty.val(value);
+    }
+    Ok(())
+}
+
+impl<H: Host> Instance<H> {
+    /// Link the imports to `host` and instantiate, running any start function.
+    pub fn new(module: Arc<Module>, host: H) -> Result<Self> {
+        let engine = module.module.engine();
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(MAX_MEMORY_BYTES)
+            .table_elements(MAX_TABLE_LEN)
+            .instances(1)
+            .memories(1)
+            .tables(1)
+            .build();
+        let mut store = Store::new(engine, Data { host, limits });
+        store.limiter(|data| &mut data.limits);
+        // A start function gets one call's fuel in a single slice
+        store.set_fuel(DEFAULT_FUEL).map_err(trapped)?;
+        let mut linker = Linker::new(engine);
+        for (import, ty) in module.imports.iter().zip(&module.import_types) {
+            let forwarded = import.clone();
+            linker
+                .func_new(
+                    &import.module,
+                    &import.name,
+                    ty.clone(),
+                    move |caller, params, results| host_call(&forwarded, caller, params, results),
+                )
+                .map_err(|err| WasmError(err.to_string()))?;
+        }
+        let instance = linker
+            .instantiate_and_start(&mut store, &module.module)
+            .map_err(trapped)?;
+        Ok(Self { store, instance })
+    }
+
+    /// Call an exported function, with a fresh fuel and time budget.
+    pub fn invoke(&mut self, name: &str, args: &[u64]) -> Result<Vec<u64>> {
+        let Some(func) = self.instance.get_func(&self.store, name) else {
+            return fail(format!("the extension does not export {name}"));
+        };
+        let ty = FuncType::from_wasmi(&func.ty(&self.store))
+            .map_err(|err| WasmError(format!("{name}: {err}")))?;
+        if args.len() != ty.params.len() {
+            return fail(format!("{name} takes {} arguments", ty.params.len()));
+        }
+        let inputs: Vec<Val> = ty
+            .params
+            .iter()
+            .zip(args)
+            .map(|(ty, v)| ty.val(*v))
+            .collect();
+        let mut outputs: Vec<Val> = ty.results.iter().map(|ty| ty.val(0)).collect();
+        let deadline = Instant::now() + DEFAULT_TIME_LIMIT;
+        let mut remaining = DEFAULT_FUEL - FUEL_SLICE;
+        self.store.set_fuel(FUEL_SLICE).map_err(trapped)?;
+        let mut call = func
+            .call_resumable(&mut self.store, &inputs, &mut outputs)
+            .map_err(trapped)?;
+        loop {
+            match call {
+                ResumableCall::Finished => break,
+                ResumableCall::HostTrap(trap) => return Err(trapped(trap.into_host_error())),
+                ResumableCall::OutOfFuel(paused) => {
+                    let slice = FUEL_SLICE.max(paused.required_fuel());
+                    if slice > remaining {
+                        return fail("the extension ran too many instructions");
+                    }
+                    if Instant::now() >= deadline {
+                        return fail("the extension ran too long");
+                    }
+                    remaining -= slice;
+                    self.store.set_fuel(slice).map_err(trapped)?;
+                    call = paused
+                        .resume(&mut self.store, &mut outputs)
+                        .map_err(trapped)?;
+                }
+            }
+        }
+        Ok(outputs.iter().map(bits).collect())
+    }
+
+    /// The module's exported memory; empty when it exports none.
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        match self.instance.get_memory(&self.store, "memory") {
+            Some(memory) => memory.data_mut(&mut self.store),
+            None => &mut [],
+        }
+    }
+
+    pub fn into_host(self) -> H {
+        self.store.into_data().host
+    }
+}
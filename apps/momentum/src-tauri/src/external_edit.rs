@@ -0,0 +1,342 @@
+//! Round trips through another app: `edit_externally` copies an attachment to a temporary
+//! folder, opens the copy in its default app, and watches it.
+//!
+//! Each save is handled once the file has stopped changing for [`SETTLE_DELAY`], since
+//! editors often write in several steps or replace the file by renaming. Changed contents
+//! are attached as the next version of the attachment and announced with
+//! `attachment:edited-externally`; later saves chain on from that version. A session lasts
+//! until `stop_editing_externally` or the app exits, and its copy is deleted when it ends.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use notify::event::{EventKind, ModifyKind};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_opener::OpenerExt;
+
+use crate::attachments::{self, Attachment, AttachmentStore};
+use crate::audit::{self, Change};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+
+/// Emitted with an [`ExternallyEdited`] when a save has been attached as a new version.
+pub const EDITED_EVENT: &str = "attachment:edited-externally";
+
+const SETTLE_DELAY: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const TEMP_DIR_NAME: &str = "momentum-edits";
+
+/// An attachment open in another app.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalEdit {
+    pub session_id: String,
+    /// The version saves are currently chained onto.
+    pub attachment_id: String,
+    /// The copy the other app is editing.
+    pub path: PathBuf,
+}
+
+/// A save from another app, attached as a new version.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternallyEdited {
+    pub session_id: String,
+    /// The version this one replaces.
+    pub previous_id: String,
+    pub attachment: Attachment,
+}
+
+struct Session {
+    current: Attachment,
+    path: PathBuf,
+    actor: String,
+    watcher: RecommendedWatcher,
+    last_event: Option<Instant>,
+    snapshot: Option<(u64, SystemTime)>,
+}
+
+impl Session {
+    fn info(&self, session_id: &str) -> ExternalEdit {
+        ExternalEdit {
+            session_id: session_id.to_string(),
+            attachment_id: self.current.id.clone(),
+            path: self.path.clone(),
+        }
+    }
+}
+
+/// Open sessions, registered in app state.
+#[derive(Default)]
+pub struct ExternalEdits {
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+fn snapshot(path: &Path) -> Option<(u64, SystemTime)> {
+    let meta = fs::metadata(path).ok()?;
+    meta.is_file()
+        .then_some((meta.len(), meta.modified().ok()?))
+}
+
+// The folder holds only the copy, so removing it also catches editor lock and backup files
+fn remove_copy(path: &Path) {
+    if let Some(dir) = path.parent() {
+        let _ = fs::remove_dir_all(dir);
+    }
+}
+
+impl ExternalEdits {
+    fn record(&self, session_id: &str, event: notify::Event) {
+        match event.kind {
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_)) => {}
+            EventKind::Modify(ModifyKind::Metadata(_)) => return,
+            EventKind::Modify(_) => {}
+            _ => return,
+        }
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(session) = sessions.get_mut(session_id) {
+            if event.paths.iter().any(|path| path == &session.path) {
+                session.last_event = Some(Instant::now());
+            }
+        }
+    }
+
+    // Sessions quiet for the settle delay whose copy held still since the last poll
+    fn take_settled(&self) -> Vec<(String, Attachment, PathBuf, String)> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let mut settled = Vec::new();
+        for (id, session) in sessions.iter_mut() {
+            if session
+                .last_event
+                .is_none_or(|at| at.elapsed() < SETTLE_DELAY)
+            {
+                continue;
+            }
+            // Mid-replace; the rename back into place raises another event
+            let Some(current) = snapshot(&session.path) else {
+                session.last_event = None;
+                continue;
+            };
+            if session.snapshot != Some(current) {
+                session.snapshot = Some(current);
+                continue;
+            }
+            session.last_event = None;
+            settled.push((
+                id.clone(),
+                session.current.clone(),
+                session.path.clone(),
+                session.actor.clone(),
+            ));
+        }
+        settled
+    }
+
+    fn advance(&self, session_id: &str, attachment: &Attachment) {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(session) = sessions.get_mut(session_id) {
+            session.current = attachment.clone();
+        }
+    }
+
+    fn remove(&self, session_id: &str) -> Option<Session> {
+        self.sessions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(session_id)
+    }
+
+    /// End every session and delete the copies; call on exit.
+    pub fn stop_all(&self) {
+        let sessions: Vec<Session> = self
+            .sessions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .drain()
+            .map(|(_, session)| session)
+            .collect();
+        for session in sessions {
+            let path = session.path.clone();
+            // Dropping a watcher joins its thread, which may be waiting on the lock
+            drop(session);
+            remove_copy(&path);
+        }
+    }
+}
+
+async fn ingest(
+    app: &AppHandle,
+    session_id: String,
+    current: Attachment,
+    path: PathBuf,
+    actor: String,
+) {
+    let handle = app.clone();
+    let result = app
+        .state::<Db>()
+        .run(move |conn| {
+            let store = handle.state::<AttachmentStore>();
+            let Some(attachment) = attachments::add_version(conn, &store, &current, &path)? else {
+                return Ok(None);
+            };
+            audit::record_now(
+                conn,
+                &actor,
+                Change::new("edit_externally", "attachment", &attachment.id)
+                    .before(&current)?
+                    .after(&attachment)?,
+            )?;
+            Ok(Some((current.id, attachment)))
+        })
+        .await;
+    match result {
+        Ok(Some((previous_id, attachment))) => {
+            app.state::<ExternalEdits>()
+                .advance(&session_id, &attachment);
+            tracing::info!(
+                attachment = %attachment.id,
+                version = attachment.version,
+                "external edit saved"
+            );
+            let _ = app.emit(
+                EDITED_EVENT,
+                &ExternallyEdited {
+                    session_id,
+                    previous_id,
+                    attachment,
+                },
+            );
+        }
+        // Saved without changes, as when only touched
+        Ok(None) => {}
+        Err(err) => tracing::warn!(%err, session = %session_id, "external edit import failed"),
+    }
+}
+
+/// Start attaching saves from open sessions as they settle.
+pub fn start(app: &AppHandle) {
+    app.manage(ExternalEdits::default());
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let settled = app.state::<ExternalEdits>().take_settled();
+            for (session_id, current, path, actor) in settled {
+                ingest(&app, session_id, current, path, actor).await;
+            }
+        }
+    });
+}
+
+/// Open an attachment in its default app and attach each save as a new version.
+///
+/// The app edits a copy under the original file name in a temporary folder; watch for
+/// `attachment:edited-externally` to pick up new versions.
+#[tauri::command]
+pub async fn edit_externally(
+    app: AppHandle,
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    edits: State<'_, ExternalEdits>,
+    attachment_id: String,
+) -> AppResult<ExternalEdit> {
+    let attachment = attachments::get(&db, attachment_id).await?;
+    let session_id = uuid::Uuid::new_v4().to_string();
+    let dir = std::env::temp_dir().join(TEMP_DIR_NAME).join(&session_id);
+    let (handle, hash, name) = (
+        app.clone(),
+        attachment.hash.clone(),
+        attachment.file_name.clone(),
+    );
+    // Canonical so watcher paths, which resolve links such as macOS's /var, compare equal
+    let path = tauri::async_runtime::spawn_blocking(move || -> AppResult<PathBuf> {
+        fs::create_dir_all(&dir)?;
+        let path = dir.canonicalize()?.join(name);
+        handle.state::<AttachmentStore>().copy_out(&hash, &path)?;
+        Ok(path)
+    })
+    .await??;
+
+    let handle = app.clone();
+    let id = session_id.clone();
+    let watcher =
+        notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+            Ok(event) => handle.state::<ExternalEdits>().record(&id, event),
+            Err(err) => tracing::warn!(%err, "external edit watch error"),
+        });
+    // Watching the folder rather than the file survives editors that save by renaming
+    let watched = watcher.and_then(|mut watcher| {
+        let dir = path.parent().unwrap_or(&path);
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(watcher)
+    });
+    let watcher = match watched {
+        Ok(watcher) => watcher,
+        Err(err) => {
+            remove_copy(&path);
+            return Err(AppError::Internal(format!("cannot watch for saves: {err}")));
+        }
+    };
+    if let Err(err) = app.opener().open_path(path.to_string_lossy(), None::<&str>) {
+        remove_copy(&path);
+        return Err(AppError::Internal(format!(
+            "failed to open attachment: {err}"
+        )));
+    }
+
+    let session = Session {
+        snapshot: snapshot(&path),
+        current: attachment,
+        path,
+        actor: audit::actor(&state),
+        watcher,
+        last_event: None,
+    };
+    let info = session.info(&session_id);
+    tracing::info!(session = %session_id, attachment = %info.attachment_id, "editing externally");
+    edits
+        .sessions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(session_id, session);
+    Ok(info)
+}
+
+/// Sessions still open.
+#[tauri::command]
+pub async fn list_external_edits(edits: State<'_, ExternalEdits>) -> AppResult<Vec<ExternalEdit>> {
+    let sessions = edits.sessions.lock().unwrap_or_else(|e| e.into_inner());
+    Ok(sessions
+        .iter()
+        .map(|(id, session)| session.info(id))
+        .collect())
+}
+
+/// Stop watching a session and delete its copy, first attaching a save still settling.
+#[tauri::command]
+pub async fn stop_editing_externally(
+    app: AppHandle,
+    edits: State<'_, ExternalEdits>,
+    session_id: String,
+) -> AppResult<()> {
+    let session = edits
+        .remove(&session_id)
+        .ok_or_else(|| AppError::not_found("external edit", &session_id))?;
+    let Session {
+        current,
+        path,
+        actor,
+        watcher,
+        ..
+    } = session;
+    drop(watcher);
+    ingest(&app, session_id, current, path.clone(), actor).await;
+    tauri::async_runtime::spawn_blocking(move || remove_copy(&path)).await?;
+    Ok(())
+}
@@ -0,0 +1,474 @@
+//! Launch a fixed set of OS tools on files, in place of shell access from the frontend.
+//!
+//! Each [`ToolId`] maps to one launcher per platform (Explorer, Finder's `open`, or
+//! `xdg-open`) and takes a single absolute path, which must exist and is canonicalized
+//! before use. Files open only when they are a type the app exports or attaches, and
+//! programs, scripts, and app bundles are refused, since every launcher would run them
+//! rather than open them.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, AppResult};
+
+// Launchers hand off and exit; one still running after this is left alone and reported
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const MAX_STDERR_BYTES: u64 = 16 * 1024;
+
+/// Opened by each platform's CAD tooling; checked by extension.
+const CAD_EXTENSIONS: &[&str] = &[
+    "dwg", "dxf", "dwf", "dwfx", "dgn", "ifc", "rvt", "rfa", "nwd", "nwc", "skp", "step", "stp",
+    "iges", "igs", "3dm",
+];
+
+/// Documents, images, and archives the app exports or attaches; with [`CAD_EXTENSIONS`],
+/// the only files `OpenFile` opens.
+const DOCUMENT_EXTENSIONS: &[&str] = &[
+    "pdf", "txt", "csv", "json", "html", "ics", "xlsx", "xls", "ods", "docx", "doc", "odt", "rtf",
+    "png", "jpg", "jpeg", "gif", "webp", "tif", "tiff", "heic", "zip", "momentum",
+];
+
+// Things every launcher would execute instead of displaying; files are allow-listed anyway,
+// so this mostly guards folders, which is how app bundles arrive
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "exe",
+    "com",
+    "pif",
+    "bat",
+    "cmd",
+    "ps1",
+    "psm1",
+    "vbs",
+    "vbe",
+    "js",
+    "jse",
+    "wsf",
+    "wsh",
+    "msi",
+    "msp",
+    "msc",
+    "scr",
+    "cpl",
+    "inf",
+    "scf",
+    "lnk",
+    "url",
+    "reg",
+    "hta",
+    "gadget",
+    "application",
+    "appref-ms",
+    "settingcontent-ms",
+    "jar",
+    "app",
+    "command",
+    "sh",
+    "desktop",
+    "appimage",
+];
+
+/// A tool the frontend may launch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ToolId {
+    /// Show a file selected in its folder.
+    RevealInFolder,
+    /// Open a folder in the file manager.
+    OpenFolder,
+    /// Open a document in its default app.
+    OpenFile,
+    /// Open a drawing or model in the default CAD viewer.
+    OpenCadViewer,
+}
+
+impl ToolId {
+    const ALL: [Self; 4] = [
+        Self::RevealInFolder,
+        Self::OpenFolder,
+        Self::OpenFile,
+        Self::OpenCadViewer,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::RevealInFolder if cfg!(target_os = "macos") => "Reveal in Finder",
+            Self::RevealInFolder if cfg!(windows) => "Show in Explorer",
+            Self::RevealInFolder => "Show in Folder",
+            Self::OpenFolder => "Open Folder",
+            Self::OpenFile => "Open",
+            Self::OpenCadViewer => "Open in CAD Viewer",
+        }
+    }
+}
+
+/// An allow-listed tool and whether it can run here.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalTool {
+    pub id: ToolId,
+    pub label: &'static str,
+    pub available: bool,
+}
+
+/// How a launch went.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolRun {
+    pub tool_id: ToolId,
+    /// `None` when the launcher was still running at the timeout or was killed by a signal.
+    pub exit_code: Option<i32>,
+    pub success: bool,
+    /// Still running after the timeout; it was left to finish on its own.
+    pub detached: bool,
+    /// The launcher's error output, truncated.
+    pub stderr: String,
+    pub duration_ms: u64,
+}
+
+fn has_extension(path: &Path, list: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| list.iter().any(|known| ext.eq_ignore_ascii_case(known)))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path, metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    (metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        || has_extension(path, EXECUTABLE_EXTENSIONS)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path, _metadata: &std::fs::Metadata) -> bool {
+    has_extension(path, EXECUTABLE_EXTENSIONS)
+}
+
+fn invalid(message: impl Into<String>) -> AppError {
+    AppError::InvalidInput(message.into())
+}
+
+/// Check the arguments against what `tool` takes and return its canonical path.
+fn validate(tool: ToolId, args: &[String]) -> AppResult<PathBuf> {
+    let [arg] = args else {
+        return Err(invalid(format!(
+            "{tool:?} takes exactly one path, got {} arguments",
+            args.len()
+        )));
+    };
+    if arg.is_empty() || arg.contains('\0') || arg.chars().any(char::is_control) {
+        return Err(invalid("the path contains invalid characters"));
+    }
+    let path = Path::new(arg);
+    // Absolute paths never begin with `-`, so no launcher can read one as an option
+    if !path.is_absolute() {
+        return Err(invalid(format!("{arg} is not an absolute path")));
+    }
+    let path = dunce(
+        path.canonicalize()
+            .map_err(|_| AppError::not_found("path", arg))?,
+    );
+    let metadata = path.metadata()?;
+    match tool {
+        ToolId::OpenFolder if !metadata.is_dir() => {
+            return Err(invalid(format!("{} is not a folder", path.display())));
+        }
+        ToolId::OpenFile | ToolId::OpenCadViewer if !metadata.is_file() => {
+            return Err(invalid(format!("{} is not a file", path.display())));
+        }
+        ToolId::OpenFile
+            if !has_extension(&path, DOCUMENT_EXTENSIONS)
+                && !has_extension(&path, CAD_EXTENSIONS) =>
+        {
+            return Err(invalid(format!(
+                "{} is not a document or drawing",
+                path.display()
+            )));
+        }
+        ToolId::OpenCadViewer if !has_extension(&path, CAD_EXTENSIONS) => {
+            return Err(invalid(format!(
+                "{} is not a drawing or model",
+                path.display()
+            )));
+        }
+        // Launchers run an `.app` bundle handed to them as a folder
+        ToolId::OpenFolder | ToolId::OpenFile | ToolId::OpenCadViewer
+            if is_executable(&path, &metadata) =>
+        {
+            return Err(invalid(format!(
+                "{} is a program and will not be opened",
+                path.display()
+            )));
+        }
+        _ => {}
+    }
+    Ok(path)
+}
+
+// `canonicalize` gives `\\?\C:\...` on Windows, which Explorer does not accept
+fn dunce(path: PathBuf) -> PathBuf {
+    #[cfg(windows)]
+    if let Some(rest) = path.to_str().and_then(|p| p.strip_prefix(r"\\?\")) {
+        if !rest.starts_with("UNC\\") {
+            return PathBuf::from(rest);
+        }
+    }
+    path
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::path::Path;
+    use std::process::Command;
+
+    use super::ToolId;
+
+    pub fn available(_tool: ToolId) -> bool {
+        true
+    }
+
+    pub fn command(tool: ToolId, path: &Path) -> Command {
+        let mut command = Command::new("/usr/bin/open");
+        if tool == ToolId::RevealInFolder {
+            command.arg("-R");
+        }
+        command.arg(path);
+        command
+    }
+
+    pub fn trust_exit_code(_tool: ToolId) -> bool {
+        true
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::os::windows::process::CommandExt;
+    use std::path::Path;
+    use std::process::Command;
+
+    use super::ToolId;
+
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    pub fn available(_tool: ToolId) -> bool {
+        true
+    }
+
+    pub fn command(tool: ToolId, path: &Path) -> Command {
+        let mut command = Command::new("explorer.exe");
+        command.creation_flags(CREATE_NO_WINDOW);
+        // Explorer parses its own command line; Windows paths cannot contain quotes
+        match tool {
+            ToolId::RevealInFolder => command.raw_arg(format!("/select,\"{}\"", path.display())),
+            _ => command.raw_arg(format!("\"{}\"", path.display())),
+        };
+        command
+    }
+
+    // Explorer exits with 1 even when it opened the path
+    pub fn trust_exit_code(_tool: ToolId) -> bool {
+        false
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+mod platform {
+    use std::path::Path;
+    use std::process::Command;
+
+    use super::ToolId;
+
+    fn xdg_open() -> Option<std::path::PathBuf> {
+        let path = std::env::var_os("PATH")?;
+        std::env::split_paths(&path)
+            .map(|dir| dir.join("xdg-open"))
+            .find(|candidate| candidate.is_file())
+    }
+
+    pub fn available(_tool: ToolId) -> bool {
+        cfg!(target_os = "linux") && xdg_open().is_some()
+    }
+
+    // xdg-open cannot select a file, so revealing opens the containing folder
+    pub fn command(tool: ToolId, path: &Path) -> Command {
+        let target = match tool {
+            ToolId::RevealInFolder => path.parent().unwrap_or(path),
+            _ => path,
+        };
+        let mut command = Command::new("xdg-open");
+        command.arg(target);
+        command
+    }
+
+    pub fn trust_exit_code(_tool: ToolId) -> bool {
+        true
+    }
+}
+
+fn launch(tool: ToolId, path: &Path) -> AppResult<ToolRun> {
+    let started = Instant::now();
+    let mut child = platform::command(tool, path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| AppError::Internal(format!("cannot start {tool:?}: {e}")))?;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if started.elapsed() >= LAUNCH_TIMEOUT {
+            break None;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+    let mut stderr = String::new();
+    if status.is_some() {
+        if let Some(pipe) = child.stderr.take() {
+            let _ = pipe.take(MAX_STDERR_BYTES).read_to_string(&mut stderr);
+        }
+    }
+    let exit_code = status.and_then(|status| status.code());
+    let success = match status {
+        Some(status) => status.success() || !platform::trust_exit_code(tool),
+        // Still running means it started, which is all a launcher has to do
+        None => true,
+    };
+    Ok(ToolRun {
+        tool_id: tool,
+        exit_code,
+        success,
+        detached: status.is_none(),
+        stderr: stderr.trim().to_string(),
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// The allow-listed tools and which of them this system can run.
+#[tauri::command]
+pub async fn list_external_tools() -> AppResult<Vec<ExternalTool>> {
+    Ok(ToolId::ALL
+        .into_iter()
+        .map(|id| ExternalTool {
+            id,
+            label: id.label(),
+            available: platform::available(id),
+        })
+        .collect())
+}
+
+/// Run an allow-listed tool on one path.
+///
+/// Fails with `INVALID_INPUT` when `args` is not a single absolute path of the kind the
+/// tool takes, and `NOT_FOUND` when the path does not exist. A launcher that exits with an
+/// error is reported in the result rather than as an error.
+#[tauri::command]
+pub async fn run_external_tool(tool_id: ToolId, args: Vec<String>) -> AppResult<ToolRun> {
+    if !platform::available(tool_id) {
+        return Err(invalid(format!(
+            "{tool_id:?} is not available on this system"
+        )));
+    }
+    let run = tauri::async_runtime::spawn_blocking(move || {
+        let path = validate(tool_id, &args)?;
+        tracing::info!(tool = ?tool_id, path = %path.display(), "running external tool");
+        launch(tool_id, &path)
+    })
+    .await??;
+    if !run.success {
+        tracing::warn!(
+            tool = ?tool_id,
+            code = ?run.exit_code,
+            stderr = %run.stderr,
+            "external tool failed"
+        );
+    }
+    Ok(run)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("tools-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn file(&self, name: &str) -> String {
+            let path = self.0.join(name);
+            std::fs::write(&path, b"x").unwrap();
+            path.to_string_lossy().into_owned()
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn documents_and_drawings_open() {
+        let dir = Scratch::new();
+        for name in ["estimate.pdf", "Takeoff.XLSX", "site.jpg", "plan.dwg"] {
+            let path = validate(ToolId::OpenFile, &[dir.file(name)]).unwrap();
+            assert!(path.is_absolute(), "{name}");
+        }
+    }
+
+    #[test]
+    fn anything_else_is_refused_as_a_file() {
+        let dir = Scratch::new();
+        for name in [
+            "setup.exe",
+            "old.pif",
+            "snap.msc",
+            "explorer.scf",
+            "driver.inf",
+            "launch.appref-ms",
+            "panel.settingcontent-ms",
+            "deploy.application",
+            "clock.gadget",
+            "notes.unknown",
+            "README",
+        ] {
+            assert!(
+                matches!(
+                    validate(ToolId::OpenFile, &[dir.file(name)]),
+                    Err(AppError::InvalidInput(_))
+                ),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn an_app_bundle_is_not_opened_as_a_folder() {
+        let dir = Scratch::new();
+        let bundle = dir.0.join("Tool.app");
+        std::fs::create_dir(&bundle).unwrap();
+        let bundle = bundle.to_string_lossy().into_owned();
+        assert!(validate(ToolId::OpenFolder, &[bundle]).is_err());
+        let folder = dir.0.to_string_lossy().into_owned();
+        validate(ToolId::OpenFolder, &[folder]).unwrap();
+    }
+
+    #[test]
+    fn only_one_absolute_path_is_taken() {
+        let dir = Scratch::new();
+        let file = dir.file("a.pdf");
+        assert!(validate(ToolId::OpenFile, &[]).is_err());
+        assert!(validate(ToolId::OpenFile, &[file.clone(), file]).is_err());
+        assert!(validate(ToolId::OpenFile, &["a.pdf".into()]).is_err());
+    }
+}
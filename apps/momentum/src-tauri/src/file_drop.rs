@@ -0,0 +1,235 @@
+//! Files dropped onto a window, classified and stored before the frontend sees them.
+//!
+//! Plans, price sheets, and images are copied into the attachment store of the project the
+//! window has open; project archives and files are only classified, since importing one is
+//! something the user confirms. Either way the window gets one typed `files-dropped` event.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, DragDropEvent, Emitter, Manager, State, Window, WindowEvent};
+use zip::ZipArchive;
+
+use crate::attachments::{self, Attachment, AttachmentStore};
+use crate::db::Db;
+use crate::error::AppResult;
+use crate::file_open::PROJECT_EXTENSION;
+
+/// Emitted to the target window with a [`FilesDropped`] once every dropped file is handled.
+pub const FILES_DROPPED_EVENT: &str = "files-dropped";
+
+/// What a dropped file looks like it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DroppedKind {
+    /// A PDF drawing set.
+    Plan,
+    /// A CSV or spreadsheet to import prices or items from.
+    Pricing,
+    Image,
+    /// A zip made by `export_project_archive`.
+    ProjectArchive,
+    /// A native `.momentum` file.
+    ProjectFile,
+    Folder,
+    Other,
+}
+
+impl DroppedKind {
+    fn attachable(self) -> bool {
+        matches!(self, Self::Plan | Self::Pricing | Self::Image | Self::Other)
+    }
+}
+
+/// One dropped path.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DroppedFile {
+    pub path: PathBuf,
+    pub name: String,
+    pub kind: DroppedKind,
+    pub size: u64,
+    /// Set when the file was copied into the window's project.
+    pub attachment: Option<Attachment>,
+    pub error: Option<String>,
+}
+
+/// Payload for `files-dropped`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilesDropped {
+    pub files: Vec<DroppedFile>,
+    /// Drop point in logical pixels relative to the window, for drop-zone hit testing.
+    pub x: f64,
+    pub y: f64,
+    /// Project the attachments went to, if the window had one set.
+    pub project_id: Option<String>,
+}
+
+/// Which project each window shows, keyed by window label.
+#[derive(Default)]
+pub struct DropTargets {
+    projects: Mutex<HashMap<String, String>>,
+}
+
+impl DropTargets {
+    fn project(&self, label: &str) -> Option<String> {
+        self.projects
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(label)
+            .cloned()
+    }
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+fn is_project_archive(path: &Path) -> bool {
+    File::open(path)
+        .ok()
+        .and_then(|file| ZipArchive::new(BufReader::new(file)).ok())
+        // Looked up by name only, since a password-protected manifest cannot be opened here
+        .is_some_and(|zip| zip.index_for_name("manifest.json").is_some())
+}
+
+// Extensions decide, except that a PDF is recognized by its header even when misnamed
+fn classify(path: &Path) -> DroppedKind {
+    if path.is_dir() {
+        return DroppedKind::Folder;
+    }
+    match extension(path).as_str() {
+        "pdf" => return DroppedKind::Plan,
+        "csv" | "tsv" | "xlsx" | "xls" | "xlsm" | "ods" => return DroppedKind::Pricing,
+        "png" | "jpg" | "jpeg" | "gif" | "webp" | "bmp" | "tif" | "tiff" | "heic" => {
+            return DroppedKind::Image
+        }
+        "zip" if is_project_archive(path) => return DroppedKind::ProjectArchive,
+        ext if ext == PROJECT_EXTENSION => return DroppedKind::ProjectFile,
+        _ => {}
+    }
+    let mut header = [0u8; 5];
+    let is_pdf = File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok()
+        && &header == b"%PDF-";
+    if is_pdf {
+        DroppedKind::Plan
+    } else {
+        DroppedKind::Other
+    }
+}
+
+async fn handle_drop(app: AppHandle, label: String, paths: Vec<PathBuf>, x: f64, y: f64) {
+    let project_id = app.state::<DropTargets>().project(&label);
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
+        let classified = {
+            let path = path.clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                (classify(&path), std::fs::metadata(&path).map(|m| m.len()))
+            })
+            .await
+        };
+        let (kind, size) = match classified {
+            Ok((kind, size)) => (kind, size.unwrap_or(0)),
+            Err(_) => (DroppedKind::Other, 0),
+        };
+        let mut file = DroppedFile {
+            name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            path,
+            kind,
+            size,
+            attachment: None,
+            error: None,
+        };
+        if let (true, Some(project_id)) = (kind.attachable(), project_id.clone()) {
+            let handle = app.clone();
+            let path = file.path.clone();
+            let result = app
+                .state::<Db>()
+                .run(move |conn| {
+                    attachments::add_file(
+                        conn,
+                        &handle.state::<AttachmentStore>(),
+                        project_id,
+                        &path,
+                    )
+                })
+                .await;
+            match result {
+                Ok(attachment) => file.attachment = Some(attachment),
+                Err(err) => {
+                    tracing::warn!(%err, path = %file.path.display(), "dropped file not attached");
+                    file.error = Some(err.to_string());
+                }
+            }
+        }
+        files.push(file);
+    }
+    tracing::debug!(window = %label, files = files.len(), "files dropped");
+    let _ = app.emit_to(
+        label.as_str(),
+        FILES_DROPPED_EVENT,
+        FilesDropped {
+            files,
+            x,
+            y,
+            project_id,
+        },
+    );
+}
+
+/// Route file drops on any window through [`handle_drop`] and forget closed windows' targets.
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    if let WindowEvent::Destroyed = event {
+        if let Some(targets) = window.try_state::<DropTargets>() {
+            targets
+                .projects
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(window.label());
+        }
+        return;
+    }
+    let WindowEvent::DragDrop(DragDropEvent::Drop { paths, position }) = event else {
+        return;
+    };
+    if paths.is_empty() {
+        return;
+    }
+    let scale = window.scale_factor().unwrap_or(1.0);
+    let position = position.to_logical::<f64>(scale);
+    tauri::async_runtime::spawn(handle_drop(
+        window.app_handle().clone(),
+        window.label().to_string(),
+        paths.clone(),
+        position.x,
+        position.y,
+    ));
+}
+
+/// Set the project drops on the calling window are attached to, or `None` to only classify.
+#[tauri::command]
+pub async fn set_drop_target_project(
+    window: Window,
+    targets: State<'_, DropTargets>,
+    project_id: Option<String>,
+) -> AppResult<()> {
+    let mut projects = targets.projects.lock().unwrap_or_else(|e| e.into_inner());
+    match project_id {
+        Some(id) => projects.insert(window.label().to_string(), id),
+        None => projects.remove(window.label()),
+    };
+    Ok(())
+}
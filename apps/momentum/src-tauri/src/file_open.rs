@@ -0,0 +1,87 @@
+//! Opening `.momentum` project files handed to the app by the OS.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::error::AppResult;
+
+/// Extension registered for project files in `tauri.conf.json`.
+pub const PROJECT_EXTENSION: &str = "momentum";
+
+/// Emitted with an [`OpenProject`] for each project file the OS asks us to open.
+pub const OPEN_PROJECT_EVENT: &str = "open-project";
+
+/// Payload for `open-project`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenProject {
+    pub path: PathBuf,
+}
+
+/// Files received before the frontend subscribed to `open-project`.
+#[derive(Default)]
+pub struct FileOpenState {
+    inner: Mutex<Pending>,
+}
+
+#[derive(Default)]
+struct Pending {
+    ready: bool,
+    queue: Vec<PathBuf>,
+}
+
+/// Extract existing project files from launch arguments, resolving relative paths against `cwd`.
+pub fn project_paths_from_args<I>(args: I, cwd: &Path) -> Vec<PathBuf>
+where
+    I: IntoIterator<Item = String>,
+{
+    args.into_iter()
+        // The first argument is the executable itself
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .map(|arg| cwd.join(arg))
+        .filter(|path| is_project_file(path))
+        .collect()
+}
+
+fn is_project_file(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case(PROJECT_EXTENSION))
+}
+
+/// Surface the app and deliver each project file, queueing until the frontend is ready.
+pub fn open_paths(app: &AppHandle, paths: Vec<PathBuf>) {
+    if paths.is_empty() {
+        return;
+    }
+    crate::windows::show_main_window(app);
+
+    let state = app.state::<FileOpenState>();
+    let mut pending = state.inner.lock().unwrap_or_else(|e| e.into_inner());
+    for path in paths.into_iter().filter(|p| is_project_file(p)) {
+        tracing::info!(path = %path.display(), "opening project file");
+        if pending.ready {
+            let _ = app.emit(OPEN_PROJECT_EVENT, OpenProject { path });
+        } else {
+            pending.queue.push(path);
+        }
+    }
+}
+
+/// Mark the frontend ready and return project files received during startup.
+#[tauri::command]
+pub async fn take_pending_project_files(
+    state: State<'_, FileOpenState>,
+) -> AppResult<Vec<OpenProject>> {
+    let mut pending = state.inner.lock().unwrap_or_else(|e| e.into_inner());
+    pending.ready = true;
+    Ok(std::mem::take(&mut pending.queue)
+        .into_iter()
+        .map(|path| OpenProject { path })
+        .collect())
+}
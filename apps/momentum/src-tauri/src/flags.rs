@@ -0,0 +1,198 @@
+//! Feature flags: bundled defaults, refreshed from the API, with local developer overrides.
+//!
+//! Later layers win: the defaults shipped in `flags.json`, then the last set the API returned,
+//! cached so flags survive offline launches, then `flags.override.json` next to the config
+//! file. Overrides are re-read on every refresh, so edits apply without a restart.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_http::reqwest::header::{ETAG, IF_NONE_MATCH};
+use tauri_plugin_http::reqwest::StatusCode;
+
+use crate::config;
+use crate::db::now_ms;
+use crate::error::AppResult;
+use crate::http_client::{HttpClient, RetryPolicy};
+use crate::jobs::Scheduler;
+use crate::network::NetworkMonitor;
+
+/// Emitted with the new [`FlagSet`] whenever an effective flag value changes.
+pub const FLAGS_UPDATED_EVENT: &str = "flags-updated";
+/// Job kind that refreshes flags from the API.
+pub const REFRESH_JOB: &str = "flags.refresh";
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+const BUNDLED_FLAGS: &str = include_str!("../flags.json");
+const CACHE_FILE_NAME: &str = "flags-cache.json";
+const OVERRIDE_FILE_NAME: &str = "flags.override.json";
+const FLAGS_PATH: &str = "/v1/flags";
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteFlags {
+    flags: BTreeMap<String, bool>,
+    etag: Option<String>,
+    fetched_at: i64,
+}
+
+/// Effective flags and where they came from.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlagSet {
+    pub flags: BTreeMap<String, bool>,
+    /// Unix milliseconds of the last successful fetch, `None` if the API was never reached.
+    pub fetched_at: Option<i64>,
+    /// Flags whose value comes from the local override file.
+    pub overridden: Vec<String>,
+}
+
+/// Flag state registered in app state.
+pub struct Flags {
+    bundled: BTreeMap<String, bool>,
+    cache_path: PathBuf,
+    override_path: PathBuf,
+    remote: RwLock<RemoteFlags>,
+    overrides: RwLock<BTreeMap<String, bool>>,
+}
+
+// Non-boolean values are skipped so one bad entry cannot hide the rest
+fn parse_flags(json: &str) -> AppResult<BTreeMap<String, bool>> {
+    let raw: BTreeMap<String, Value> = serde_json::from_str(json)?;
+    Ok(raw
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value.as_bool()?)))
+        .collect())
+}
+
+fn load_overrides(path: &Path) -> BTreeMap<String, bool> {
+    match fs::read_to_string(path) {
+        Ok(json) => parse_flags(&json).unwrap_or_else(|err| {
+            tracing::warn!(%err, "ignoring unreadable flag overrides");
+            BTreeMap::new()
+        }),
+        Err(_) => BTreeMap::new(),
+    }
+}
+
+/// Load the bundled defaults, the cached remote set, and local overrides.
+pub fn init(data_dir: &Path, config_path: &Path) -> AppResult<Flags> {
+    let cache_path = data_dir.join(CACHE_FILE_NAME);
+    let override_path = config_path.with_file_name(OVERRIDE_FILE_NAME);
+    let remote = fs::read(&cache_path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default();
+    Ok(Flags {
+        bundled: parse_flags(BUNDLED_FLAGS)?,
+        cache_path,
+        overrides: RwLock::new(load_overrides(&override_path)),
+        override_path,
+        remote: RwLock::new(remote),
+    })
+}
+
+impl Flags {
+    pub fn snapshot(&self) -> FlagSet {
+        let remote = self.remote.read().unwrap_or_else(|e| e.into_inner());
+        let overrides = self.overrides.read().unwrap_or_else(|e| e.into_inner());
+        let mut flags = self.bundled.clone();
+        flags.extend(remote.flags.clone());
+        flags.extend(overrides.clone());
+        FlagSet {
+            flags,
+            fetched_at: (remote.fetched_at > 0).then_some(remote.fetched_at),
+            overridden: overrides.keys().cloned().collect(),
+        }
+    }
+
+    fn store_remote(&self, remote: RemoteFlags) -> AppResult<()> {
+        let tmp = self.cache_path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(&remote)?)?;
+        fs::rename(&tmp, &self.cache_path)?;
+        *self.remote.write().unwrap_or_else(|e| e.into_inner()) = remote;
+        Ok(())
+    }
+
+    async fn fetch(&self, app: &AppHandle) -> AppResult<()> {
+        let url = config::api_url(app, FLAGS_PATH)?;
+        let http = app.state::<HttpClient>();
+        let etag = self
+            .remote
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .etag
+            .clone();
+        let mut request = http.client().get(url);
+        if let Some(etag) = &etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        // Flags can be targeted per organization, so the session goes along when there is one
+        let response = http
+            .send_authorized(app, request, RetryPolicy::default())
+            .await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            let mut remote = self
+                .remote
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone();
+            remote.fetched_at = now_ms();
+            return self.store_remote(remote);
+        }
+        let response = response.error_for_status()?;
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let flags = parse_flags(&response.text().await?)?;
+        self.store_remote(RemoteFlags {
+            flags,
+            etag,
+            fetched_at: now_ms(),
+        })
+    }
+
+    /// Re-read overrides and, when online, fetch the remote set; emits `flags-updated` on change.
+    pub async fn refresh(&self, app: &AppHandle) -> AppResult<FlagSet> {
+        let before = self.snapshot();
+        *self.overrides.write().unwrap_or_else(|e| e.into_inner()) =
+            load_overrides(&self.override_path);
+        // Offline keeps the cached set; the API is asked again on the next refresh
+        let fetched = if app.state::<NetworkMonitor>().is_online() {
+            self.fetch(app).await
+        } else {
+            Ok(())
+        };
+        let after = self.snapshot();
+        if after.flags != before.flags || after.overridden != before.overridden {
+            tracing::info!("feature flags updated");
+            let _ = app.emit(FLAGS_UPDATED_EVENT, &after);
+        }
+        fetched.map(|()| after)
+    }
+}
+
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(REFRESH_JOB, Some(REFRESH_INTERVAL), |app| async move {
+        app.state::<Flags>().refresh(&app).await.map(|_| ())
+    });
+}
+
+#[tauri::command]
+pub async fn get_flags(flags: State<'_, Flags>) -> AppResult<FlagSet> {
+    Ok(flags.snapshot())
+}
+
+/// Refresh now instead of waiting for the next scheduled refresh.
+#[tauri::command]
+pub async fn refresh_flags(app: AppHandle, flags: State<'_, Flags>) -> AppResult<FlagSet> {
+    flags.refresh(&app).await
+}
@@ -0,0 +1,339 @@
+//! Installed font families for the report designer's font picker, and the check the PDF
+//! renderer runs before embedding one.
+//!
+//! The OS font directories are scanned and each file's name table read directly, so no font
+//! service is needed. The scan is cached for the session and redone on request, after the
+//! user installs something. printpdf can only embed a standalone font with TrueType outlines
+//! whose license allows embedding, so collections, CFF-flavoured OpenType, and restricted
+//! fonts are listed but marked as not embeddable.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use ttf_parser::{name_id, Face, Language, Permissions};
+
+use crate::error::{AppError, AppResult};
+
+const FONT_EXTENSIONS: &[&str] = &["ttf", "otf", "ttc", "otc"];
+// Deep enough for distro layouts like /usr/share/fonts/truetype/dejavu
+const MAX_DEPTH: usize = 6;
+// Big CJK families run to tens of megabytes; anything past this is not a text face
+const MAX_FONT_BYTES: u64 = 64 * 1024 * 1024;
+const REGULAR_WEIGHT: u16 = 400;
+const BOLD_WEIGHT: u16 = 700;
+// Faces from here up count as bold when a family has no 700
+const MIN_BOLD_WEIGHT: u16 = 600;
+
+/// One style of an installed family.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FontStyle {
+    /// The font's own style name, e.g. `Regular` or `Semibold Italic`.
+    pub name: String,
+    /// CSS-style weight, 100 to 900.
+    pub weight: u16,
+    pub italic: bool,
+    /// Whether reports can embed this face.
+    pub embeddable: bool,
+}
+
+/// An installed family and its styles, lightest first.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FontFamily {
+    pub family: String,
+    pub styles: Vec<FontStyle>,
+    /// Whether the family can be chosen for a report, meaning it has an embeddable upright face.
+    pub embeddable: bool,
+}
+
+/// What `validate_font` found for a family.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FontCheck {
+    pub family: String,
+    /// Style used for body text.
+    pub regular: String,
+    /// Style used for bold text; regular is used when there is none.
+    pub bold: Option<String>,
+}
+
+/// Font files a report embeds.
+pub(crate) struct EmbeddedFamily {
+    pub(crate) regular: Vec<u8>,
+    pub(crate) bold: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone)]
+struct InstalledFace {
+    family: String,
+    style: FontStyle,
+    path: PathBuf,
+}
+
+static CACHE: Mutex<Option<Arc<Vec<InstalledFace>>>> = Mutex::new(None);
+
+// Prefer the US English name, which is what the font picker sorts and matches on
+fn name(face: &Face, ids: &[u16]) -> Option<String> {
+    ids.iter().find_map(|id| {
+        let names: Vec<_> = face
+            .names()
+            .into_iter()
+            .filter(|name| name.name_id == *id && name.is_unicode())
+            .collect();
+        names
+            .iter()
+            .find(|name| name.language() == Language::English_UnitedStates)
+            .or_else(|| names.first())
+            .and_then(|name| name.to_string())
+            .filter(|name| !name.trim().is_empty())
+    })
+}
+
+fn read_faces(path: &Path, faces: &mut Vec<InstalledFace>) {
+    let Ok(data) = std::fs::read(path) else {
+        return;
+    };
+    let collection = ttf_parser::fonts_in_collection(&data);
+    for index in 0..collection.unwrap_or(1) {
+        let Ok(face) = Face::parse(&data, index) else {
+            continue;
+        };
+        let Some(family) = name(&face, &[name_id::TYPOGRAPHIC_FAMILY, name_id::FAMILY]) else {
+            continue;
+        };
+        let style = name(&face, &[name_id::TYPOGRAPHIC_SUBFAMILY, name_id::SUBFAMILY])
+            .unwrap_or_else(|| "Regular".into());
+        let embeddable = collection.is_none()
+            && face.tables().glyf.is_some()
+            && face.permissions() != Some(Permissions::Restricted);
+        faces.push(InstalledFace {
+            family: family.trim().to_string(),
+            style: FontStyle {
+                name: style,
+                weight: face.weight().to_number(),
+                italic: face.is_italic(),
+                embeddable,
+            },
+            path: path.to_path_buf(),
+        });
+    }
+}
+
+fn scan_dir(dir: &Path, depth: usize, faces: &mut Vec<InstalledFace>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(meta) = std::fs::metadata(&path) else {
+            continue;
+        };
+        if meta.is_dir() {
+            if depth < MAX_DEPTH {
+                scan_dir(&path, depth + 1, faces);
+            }
+            continue;
+        }
+        let is_font = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| FONT_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+        if is_font && meta.len() <= MAX_FONT_BYTES {
+            read_faces(&path, faces);
+        }
+    }
+}
+
+fn scan() -> Vec<InstalledFace> {
+    let mut faces = Vec::new();
+    for dir in os::font_dirs() {
+        scan_dir(&dir, 0, &mut faces);
+    }
+    // The same face is often installed both system-wide and for the user
+    faces.sort_by(|a, b| {
+        (
+            a.family.to_lowercase(),
+            a.style.weight,
+            a.style.italic,
+            &a.style.name,
+        )
+            .cmp(&(
+                b.family.to_lowercase(),
+                b.style.weight,
+                b.style.italic,
+                &b.style.name,
+            ))
+    });
+    faces.dedup_by(|dup, kept| {
+        let same =
+            dup.family.eq_ignore_ascii_case(&kept.family) && dup.style.name == kept.style.name;
+        // Keep whichever copy can be embedded
+        if same && dup.style.embeddable && !kept.style.embeddable {
+            std::mem::swap(dup, kept);
+        }
+        same
+    });
+    tracing::debug!(faces = faces.len(), "system fonts scanned");
+    faces
+}
+
+fn installed(refresh: bool) -> Arc<Vec<InstalledFace>> {
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    match cache.as_ref() {
+        Some(faces) if !refresh => faces.clone(),
+        _ => cache.insert(Arc::new(scan())).clone(),
+    }
+}
+
+// The embeddable upright face nearest the weight, within the range allowed
+fn pick(faces: &[&InstalledFace], target: u16, min: u16) -> Option<InstalledFace> {
+    faces
+        .iter()
+        .filter(|face| face.style.embeddable && !face.style.italic && face.style.weight >= min)
+        .min_by_key(|face| face.style.weight.abs_diff(target))
+        .map(|face| (*face).clone())
+}
+
+fn resolve(family: &str) -> AppResult<(InstalledFace, Option<InstalledFace>)> {
+    let family = family.trim();
+    if family.is_empty() {
+        return Err(AppError::InvalidInput("font family is empty".into()));
+    }
+    let faces = installed(false);
+    let matching: Vec<&InstalledFace> = faces
+        .iter()
+        .filter(|face| face.family.eq_ignore_ascii_case(family))
+        .collect();
+    if matching.is_empty() {
+        return Err(AppError::not_found("font", family));
+    }
+    let regular = pick(&matching, REGULAR_WEIGHT, 0).ok_or_else(|| {
+        AppError::InvalidInput(format!(
+            "{family} cannot be embedded in a PDF; choose a TrueType font that allows embedding"
+        ))
+    })?;
+    let bold = pick(
+        &matching,
+        BOLD_WEIGHT,
+        MIN_BOLD_WEIGHT.max(regular.style.weight + 1),
+    );
+    Ok((regular, bold))
+}
+
+/// Check that reports can embed `family`, as the PDF renderer does before using it.
+pub(crate) fn validate(family: &str) -> AppResult<FontCheck> {
+    let (regular, bold) = resolve(family)?;
+    Ok(FontCheck {
+        family: regular.family,
+        regular: regular.style.name,
+        bold: bold.map(|face| face.style.name),
+    })
+}
+
+/// Validate `family` and read the files a PDF embeds for it.
+pub(crate) fn load(family: &str) -> AppResult<EmbeddedFamily> {
+    let (regular, bold) = resolve(family)?;
+    Ok(EmbeddedFamily {
+        regular: std::fs::read(&regular.path)?,
+        bold: bold.map(|face| std::fs::read(face.path)).transpose()?,
+    })
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use std::path::PathBuf;
+
+    pub fn font_dirs() -> Vec<PathBuf> {
+        let mut dirs = vec![
+            PathBuf::from("/usr/share/fonts"),
+            PathBuf::from("/usr/local/share/fonts"),
+        ];
+        let data_home = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+            });
+        if let Some(data_home) = data_home {
+            dirs.push(data_home.join("fonts"));
+        }
+        if let Some(home) = std::env::var_os("HOME") {
+            dirs.push(PathBuf::from(home).join(".fonts"));
+        }
+        dirs
+    }
+}
+
+#[cfg(windows)]
+mod os {
+    use std::path::PathBuf;
+
+    pub fn font_dirs() -> Vec<PathBuf> {
+        let windir = std::env::var_os("WINDIR").unwrap_or_else(|| r"C:\Windows".into());
+        let mut dirs = vec![PathBuf::from(windir).join("Fonts")];
+        // Fonts installed without admin rights since Windows 10 1809
+        if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+            dirs.push(PathBuf::from(local).join(r"Microsoft\Windows\Fonts"));
+        }
+        dirs
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use std::path::PathBuf;
+
+    pub fn font_dirs() -> Vec<PathBuf> {
+        let mut dirs = vec![
+            PathBuf::from("/System/Library/Fonts"),
+            PathBuf::from("/Library/Fonts"),
+        ];
+        if let Some(home) = std::env::var_os("HOME") {
+            dirs.push(PathBuf::from(home).join("Library/Fonts"));
+        }
+        dirs
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows, target_os = "macos")))]
+mod os {
+    use std::path::PathBuf;
+
+    pub fn font_dirs() -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+/// Installed font families by name; `refresh` rescans after fonts are installed.
+#[tauri::command]
+pub async fn list_system_fonts(refresh: Option<bool>) -> AppResult<Vec<FontFamily>> {
+    let faces =
+        tauri::async_runtime::spawn_blocking(move || installed(refresh.unwrap_or(false))).await?;
+    let mut families: Vec<FontFamily> = Vec::new();
+    for face in faces.iter() {
+        match families.last_mut() {
+            Some(family) if family.family.eq_ignore_ascii_case(&face.family) => {
+                family.styles.push(face.style.clone());
+            }
+            _ => families.push(FontFamily {
+                family: face.family.clone(),
+                styles: vec![face.style.clone()],
+                embeddable: false,
+            }),
+        }
+    }
+    for family in &mut families {
+        family.embeddable = family
+            .styles
+            .iter()
+            .any(|style| style.embeddable && !style.italic);
+    }
+    Ok(families)
+}
+
+/// Check that a report can be set in `family`, and which styles it would use.
+#[tauri::command]
+pub async fn validate_font(family: String) -> AppResult<FontCheck> {
+    tauri::async_runtime::spawn_blocking(move || validate(&family)).await?
+}
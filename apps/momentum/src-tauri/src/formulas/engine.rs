@@ -0,0 +1,371 @@
+//! The formula language: Rhai expressions, sandboxed for pricing rules.
+//!
+//! A formula is a few `let name = expr;` statements ending in the expression whose value is
+//! the result, e.g. `let waste = 0.1; item.quantity * (1 + waste) * laborRate`. It gets
+//! Rhai's numbers, text, booleans, arrays, and object maps, its operators (`**` is a power,
+//! and `/` never truncates), `if` expressions, field access, and the functions `abs`, `sqrt`,
+//! `round`, `ceil`, `floor`, `clamp`, `min`, `max`, `sum`, `avg`, `count`, `lookup`, and
+//! `text`. The engine has no packages, loops, user functions, modules, or printing,
+//! and the estimate data is read-only. Every evaluation runs under an operation budget and a
+//! deadline, so a formula cannot hang recalculation.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use rhai::{
+    Array, Dynamic, Engine, EvalAltResult, Map, OptimizationLevel, ParseErrorType, Position, Scope,
+    AST, FLOAT, INT,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+const MAX_SOURCE_LEN: usize = 16 * 1024;
+const MAX_DEPTH: usize = 64;
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_INPUTS: usize = 256;
+const TIME_LIMIT: Duration = Duration::from_millis(100);
+// The clock is read every this many operations rather than on each one
+const CLOCK_EVERY: u64 = 1024;
+const MAX_TEXT_LEN: usize = 64 * 1024;
+const MAX_COLLECTION_LEN: usize = 100_000;
+
+/// A problem in a formula, at the place it was found.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormulaError {
+    pub message: String,
+    /// One-based line.
+    pub line: usize,
+    /// One-based column, in characters.
+    pub column: usize,
+}
+
+impl fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl FormulaError {
+    fn at(position: Position, message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            line: position.line().unwrap_or(1),
+            column: position.position().unwrap_or(1),
+        }
+    }
+
+    fn from_eval(mut err: EvalAltResult) -> Self {
+        let position = err.take_position();
+        let message = match err {
+            EvalAltResult::ErrorTooManyOperations(_) => "the formula took too many steps".into(),
+            EvalAltResult::ErrorTerminated(..) => "the formula took too long".into(),
+            EvalAltResult::ErrorDataTooLarge(..) => "the value is too large".into(),
+            EvalAltResult::ErrorRuntime(ref value, _) => value.to_string(),
+            ref other => other.to_string(),
+        };
+        Self::at(position, message)
+    }
+}
+
+thread_local! {
+    // When the evaluation running on this thread has to stop
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+type Fallible<T> = Result<T, Box<EvalAltResult>>;
+
+fn runtime(message: impl Into<String>) -> Box<EvalAltResult> {
+    EvalAltResult::ErrorRuntime(message.into().into(), Position::NONE).into()
+}
+
+// A number from a value: text is parsed and missing estimate fields count as zero, as they
+// do in the grid
+fn number(value: &Dynamic, what: &str) -> Fallible<f64> {
+    if let Some(n) = value
+        .as_float()
+        .ok()
+        .or_else(|| value.as_int().ok().map(|n| n as f64))
+    {
+        return Ok(n);
+    }
+    if value.is_unit() {
+        return Ok(0.0);
+    }
+    if let Ok(text) = value.clone().into_immutable_string() {
+        return text
+            .trim()
+            .parse()
+            .map_err(|_| runtime(format!("{what} needs a number, got text \"{text}\"")));
+    }
+    Err(runtime(format!(
+        "{what} needs a number, got {}",
+        kind(value)
+    )))
+}
+
+fn finite(value: f64) -> Fallible<FLOAT> {
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(runtime("the result is not a finite number"))
+    }
+}
+
+fn round_to(value: f64, digits: f64, f: fn(f64) -> f64) -> f64 {
+    let scale = 10f64.powi(digits.clamp(-12.0, 12.0) as i32);
+    f(value * scale) / scale
+}
+
+// Each element of a list as a number, or its `field` when given
+fn numbers(list: &Array, field: Option<&str>, what: &str) -> Fallible<Vec<f64>> {
+    list.iter()
+        .map(|item| match field {
+            None => number(item, what),
+            Some(field) => match item.read_lock::<Map>() {
+                Some(map) => number(map.get(field).unwrap_or(&Dynamic::UNIT), what),
+                None => Err(runtime(format!(
+                    "{what} by field needs records, got {}",
+                    kind(item)
+                ))),
+            },
+        })
+        .collect()
+}
+
+fn extreme(values: Vec<f64>, pick: fn(f64, f64) -> f64) -> Fallible<Dynamic> {
+    match values.into_iter().reduce(pick) {
+        Some(value) => Ok(finite(value)?.into()),
+        None => Ok(Dynamic::UNIT),
+    }
+}
+
+fn average(values: Vec<f64>) -> Fallible<Dynamic> {
+    if values.is_empty() {
+        return Ok(Dynamic::UNIT);
+    }
+    Ok(finite(values.iter().sum::<f64>() / values.len() as f64)?.into())
+}
+
+// The only functions a formula can reach
+fn register_functions(engine: &mut Engine) {
+    engine
+        .register_fn("abs", |x: Dynamic| finite(number(&x, "`abs`")?.abs()))
+        .register_fn("sqrt", |x: Dynamic| {
+            let value = number(&x, "`sqrt`")?;
+            if value < 0.0 {
+                return Err(runtime("`sqrt` of a negative number"));
+            }
+            finite(value.sqrt())
+        });
+    for (name, f) in [
+        ("round", f64::round as fn(f64) -> f64),
+        ("ceil", f64::ceil),
+        ("floor", f64::floor),
+    ] {
+        let what = format!("`{name}`");
+        let with_digits = what.clone();
+        engine
+            .register_fn(name, move |x: Dynamic| {
+                finite(round_to(number(&x, &what)?, 0.0, f))
+            })
+            .register_fn(name, move |x: Dynamic, digits: Dynamic| {
+                let digits = number(&digits, &with_digits)?;
+                finite(round_to(number(&x, &with_digits)?, digits, f))
+            });
+    }
+    engine.register_fn("clamp", |x: Dynamic, low: Dynamic, high: Dynamic| {
+        let what = "`clamp`";
+        let (x, low, high) = (number(&x, what)?, number(&low, what)?, number(&high, what)?);
+        if low > high {
+            return Err(runtime("`clamp` needs the low bound first"));
+        }
+        finite(x.clamp(low, high))
+    });
+    for (name, pick) in [("min", f64::min as fn(f64, f64) -> f64), ("max", f64::max)] {
+        let what = format!("`{name}`");
+        let pair = what.clone();
+        engine
+            .register_fn(name, move |list: Array| {
+                extreme(numbers(&list, None, &what)?, pick)
+            })
+            .register_fn(name, move |a: Dynamic, b: Dynamic| {
+                extreme(vec![number(&a, &pair)?, number(&b, &pair)?], pick)
+            });
+    }
+    engine
+        .register_fn("sum", |list: Array| {
+            finite(numbers(&list, None, "`sum`")?.iter().sum())
+        })
+        .register_fn("sum", |list: Array, field: &str| {
+            finite(numbers(&list, Some(field), "`sum`")?.iter().sum())
+        })
+        .register_fn("avg", |list: Array| average(numbers(&list, None, "`avg`")?))
+        .register_fn("avg", |list: Array, field: &str| {
+            average(numbers(&list, Some(field), "`avg`")?)
+        })
+        .register_fn("count", |list: Array| list.len() as INT)
+        // The first record whose field equals the value, e.g. a rate from a price list
+        .register_fn("lookup", |list: Array, field: &str, value: Dynamic| {
+            let wanted = value.to_string();
+            for item in &list {
+                if let Some(map) = item.read_lock::<Map>() {
+                    let found = map.get(field).unwrap_or(&Dynamic::UNIT);
+                    let equal = match (number(found, ""), number(&value, "")) {
+                        (Ok(a), Ok(b)) if !found.is_string() && !value.is_string() => a == b,
+                        _ => found.type_name() == value.type_name() && found.to_string() == wanted,
+                    };
+                    if equal {
+                        return item.clone();
+                    }
+                }
+            }
+            Dynamic::UNIT
+        })
+        .register_fn("text", |value: Dynamic| {
+            if value.is_unit() {
+                String::new()
+            } else {
+                value.to_string()
+            }
+        });
+    // Whole numbers divide exactly, not with Rhai's integer division
+    engine.register_fn("/", |a: INT, b: INT| -> Fallible<FLOAT> {
+        if b == 0 {
+            return Err(runtime("division by zero"));
+        }
+        finite(a as f64 / b as f64)
+    });
+}
+
+static ENGINE: LazyLock<Engine> = LazyLock::new(|| {
+    let mut engine = Engine::new_raw();
+    engine
+        .set_optimization_level(OptimizationLevel::None)
+        .set_strict_variables(true)
+        .set_fast_operators(false)
+        .set_allow_looping(false)
+        .set_allow_anonymous_fn(false)
+        .set_allow_statement_expression(false)
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_expr_depths(MAX_DEPTH, MAX_DEPTH)
+        .set_max_string_size(MAX_TEXT_LEN)
+        .set_max_array_size(MAX_COLLECTION_LEN)
+        .set_max_map_size(MAX_COLLECTION_LEN)
+        .set_max_variables(MAX_INPUTS)
+        .on_print(|_| {})
+        .on_debug(|_, _, _| {})
+        .on_progress(|operations| {
+            if !operations.is_multiple_of(CLOCK_EVERY) {
+                return None;
+            }
+            let expired = DEADLINE
+                .get()
+                .is_some_and(|deadline| Instant::now() > deadline);
+            expired.then_some(Dynamic::UNIT)
+        });
+    for symbol in ["fn", "eval", "print", "debug"] {
+        engine.disable_symbol(symbol);
+    }
+    register_functions(&mut engine);
+    engine
+});
+
+/// Estimate data as a formula value.
+pub fn value(json: &Value) -> Dynamic {
+    rhai::serde::to_dynamic(json).unwrap_or(Dynamic::UNIT)
+}
+
+/// A result back as JSON for the frontend.
+pub fn to_json(value: &Dynamic) -> Value {
+    rhai::serde::from_dynamic(&value.flatten_clone()).unwrap_or(Value::Null)
+}
+
+/// The number a result holds, if it is one.
+pub fn as_number(value: &Dynamic) -> Option<f64> {
+    value
+        .as_float()
+        .ok()
+        .or_else(|| value.as_int().ok().map(|n| n as f64))
+}
+
+pub(crate) fn kind(value: &Dynamic) -> &'static str {
+    if value.is_unit() {
+        "nothing"
+    } else if value.is_bool() {
+        "true/false"
+    } else if value.is_float() || value.is_int() {
+        "a number"
+    } else if value.is_string() || value.is_char() {
+        "text"
+    } else if value.is_array() {
+        "a list"
+    } else if value.is_map() {
+        "a record"
+    } else {
+        "something else"
+    }
+}
+
+/// A parsed formula, ready to evaluate against any data.
+pub struct Formula {
+    ast: AST,
+    /// Names the formula reads that it does not define itself.
+    pub inputs: Vec<String>,
+}
+
+/// Parse `source`, reporting the first problem with where it is.
+pub fn parse(source: &str) -> Result<Formula, FormulaError> {
+    if source.len() > MAX_SOURCE_LEN {
+        return Err(FormulaError::at(Position::NONE, "the formula is too long"));
+    }
+    // Strict variables make each undeclared name a parse error; those are the inputs
+    let mut scope = Scope::new();
+    let mut inputs = Vec::new();
+    loop {
+        match ENGINE.compile_with_scope(&scope, source) {
+            Ok(ast) => return Ok(Formula { ast, inputs }),
+            Err(err) => match *err.0 {
+                ParseErrorType::VariableUndefined(name) if inputs.len() < MAX_INPUTS => {
+                    scope.push_constant_dynamic(name.clone(), Dynamic::UNIT);
+                    inputs.push(name);
+                }
+                other => return Err(FormulaError::at(err.1, other.to_string())),
+            },
+        }
+    }
+}
+
+impl Formula {
+    /// Evaluate against `globals`, the names the formula can read; they cannot be changed.
+    pub fn evaluate(&self, globals: &HashMap<String, Dynamic>) -> Result<Dynamic, FormulaError> {
+        let mut scope = Scope::new();
+        for name in &self.inputs {
+            let Some(value) = globals.get(name) else {
+                return Err(FormulaError::at(
+                    Position::NONE,
+                    format!("`{name}` is not defined"),
+                ));
+            };
+            scope.push_constant_dynamic(name.clone(), value.clone());
+        }
+        DEADLINE.set(Some(Instant::now() + TIME_LIMIT));
+        let result = ENGINE.eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast);
+        DEADLINE.set(None);
+        let value = result.map_err(|err| FormulaError::from_eval(*err))?;
+        if value.as_float().is_ok_and(|n| !n.is_finite()) {
+            return Err(FormulaError::at(
+                Position::NONE,
+                "the result is not a finite number",
+            ));
+        }
+        Ok(value)
+    }
+}
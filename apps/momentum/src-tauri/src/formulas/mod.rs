@@ -0,0 +1,188 @@
+//! Custom pricing formulas that power users write for estimate columns, such as a unit price
+//! with waste and markup applied.
+//!
+//! Formulas are sandboxed Rhai expressions (see [`engine`]) that can only read the estimate
+//! data passed in and call a fixed set of functions, under operation and time limits. A
+//! formula sees `project`, `items` (each with its `total`), `subtotal`, the caller's
+//! `variables` such as `laborRate`, and during recalculation the row's own `item`.
+
+pub mod engine;
+
+use std::collections::HashMap;
+
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::db::projects::{Item, Project};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use rhai::Dynamic;
+
+use engine::{Formula, FormulaError};
+
+// Names the data provides, which user variables may not shadow
+const RESERVED: &[&str] = &["project", "items", "subtotal", "item"];
+
+/// What `validate_formula` found.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormulaCheck {
+    /// The first problem, or `None` when the formula is valid.
+    pub error: Option<FormulaError>,
+    /// Names the formula reads from its data, e.g. `item` or `laborRate`.
+    pub inputs: Vec<String>,
+}
+
+/// One row's result from `evaluate_item_formula`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemFormulaResult {
+    pub item_id: String,
+    pub value: Option<f64>,
+    /// Why this row has no value; other rows are still evaluated.
+    pub error: Option<FormulaError>,
+}
+
+fn item_value(item: &Item) -> AppResult<Value> {
+    let mut value = serde_json::to_value(item)?;
+    value["total"] = json!(item.quantity * item.unit_cost);
+    Ok(value)
+}
+
+// The names every formula can read
+fn globals(
+    conn: &Connection,
+    project_id: &str,
+    variables: Option<HashMap<String, Value>>,
+) -> AppResult<(HashMap<String, Dynamic>, Vec<Item>)> {
+    let project = conn
+        .query_row(
+            "SELECT * FROM projects WHERE id = ?1",
+            [project_id],
+            Project::from_row,
+        )
+        .optional()?
+        .ok_or_else(|| AppError::not_found("project", project_id))?;
+    let mut stmt =
+        conn.prepare("SELECT * FROM items WHERE project_id = ?1 ORDER BY sort_order, name")?;
+    let items = stmt
+        .query_map([project_id], Item::from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut globals = HashMap::new();
+    for (name, value) in variables.unwrap_or_default() {
+        if RESERVED.contains(&name.as_str()) {
+            return Err(AppError::InvalidInput(format!(
+                "variable {name} would hide the estimate's own {name}"
+            )));
+        }
+        globals.insert(name, engine::value(&value));
+    }
+    let subtotal: f64 = items
+        .iter()
+        .map(|item| item.quantity * item.unit_cost)
+        .sum();
+    let values = items
+        .iter()
+        .map(item_value)
+        .collect::<AppResult<Vec<_>>>()?;
+    globals.insert(
+        "project".into(),
+        engine::value(&serde_json::to_value(&project)?),
+    );
+    globals.insert("items".into(), engine::value(&Value::Array(values)));
+    globals.insert("subtotal".into(), subtotal.into());
+    Ok((globals, items))
+}
+
+fn parse(source: &str) -> AppResult<Formula> {
+    engine::parse(source).map_err(|err| AppError::InvalidInput(err.to_string()))
+}
+
+/// Check a formula without running it.
+#[tauri::command]
+pub async fn validate_formula(source: String) -> AppResult<FormulaCheck> {
+    Ok(match engine::parse(&source) {
+        Ok(formula) => FormulaCheck {
+            error: None,
+            inputs: formula.inputs,
+        },
+        Err(err) => FormulaCheck {
+            error: Some(err),
+            inputs: Vec::new(),
+        },
+    })
+}
+
+/// Evaluate a formula once against a project, optionally as seen from one of its items.
+#[tauri::command]
+pub async fn evaluate_formula(
+    db: State<'_, Db>,
+    source: String,
+    project_id: String,
+    item_id: Option<String>,
+    variables: Option<HashMap<String, Value>>,
+) -> AppResult<Value> {
+    let formula = parse(&source)?;
+    db.run(move |conn| {
+        let (mut globals, items) = globals(conn, &project_id, variables)?;
+        if let Some(item_id) = item_id {
+            let item = items
+                .iter()
+                .find(|item| item.id == item_id)
+                .ok_or_else(|| AppError::not_found("item", &item_id))?;
+            globals.insert("item".into(), engine::value(&item_value(item)?));
+        }
+        let value = formula
+            .evaluate(&globals)
+            .map_err(|err| AppError::InvalidInput(err.to_string()))?;
+        Ok(engine::to_json(&value))
+    })
+    .await
+}
+
+/// Evaluate a column formula for every item in a project, as the grid does on recalculation.
+///
+/// The formula must produce a number for each row; rows that fail carry their own error.
+#[tauri::command]
+pub async fn evaluate_item_formula(
+    db: State<'_, Db>,
+    source: String,
+    project_id: String,
+    variables: Option<HashMap<String, Value>>,
+) -> AppResult<Vec<ItemFormulaResult>> {
+    let formula = parse(&source)?;
+    db.run(move |conn| {
+        let (mut globals, items) = globals(conn, &project_id, variables)?;
+        let mut results = Vec::with_capacity(items.len());
+        for item in &items {
+            globals.insert("item".into(), engine::value(&item_value(item)?));
+            let (value, error) = match formula.evaluate(&globals) {
+                Ok(value) if value.is_unit() => (None, None),
+                Ok(value) => match engine::as_number(&value) {
+                    Some(number) => (Some(number), None),
+                    None => {
+                        let err = FormulaError {
+                            message: format!(
+                                "the formula must produce a number, not {}",
+                                engine::kind(&value)
+                            ),
+                            line: 1,
+                            column: 1,
+                        };
+                        (None, Some(err))
+                    }
+                },
+                Err(err) => (None, Some(err)),
+            };
+            results.push(ItemFormulaResult {
+                item_id: item.id.clone(),
+                value,
+                error,
+            });
+        }
+        Ok(results)
+    })
+    .await
+}
@@ -0,0 +1,280 @@
+//! Disk-backed HTTP cache for read-heavy API lookups such as catalogs and pricing.
+//!
+//! Entries revalidate with `If-None-Match`/`If-Modified-Since` and are served stale when the
+//! API is unreachable, so lookups keep working offline.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_http::reqwest::header::{
+    CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use tauri_plugin_http::reqwest::StatusCode;
+
+use crate::config;
+use crate::db::now_ms;
+use crate::error::AppResult;
+use crate::http_client::{HttpClient, RetryPolicy};
+use crate::network::NetworkMonitor;
+
+const CACHE_DIR_NAME: &str = "http-cache";
+const META_EXTENSION: &str = "json";
+const BODY_EXTENSION: &str = "body";
+
+/// Metadata stored alongside each cached body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheEntry {
+    pub url: String,
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub fetched_at: i64,
+    /// Freshness lifetime from `Cache-Control: max-age`; `None` means always revalidate.
+    pub max_age_ms: Option<i64>,
+    pub size: u64,
+}
+
+impl CacheEntry {
+    fn is_fresh(&self, max_age_ms: Option<i64>) -> bool {
+        max_age_ms
+            .or(self.max_age_ms)
+            .is_some_and(|age| now_ms() - self.fetched_at < age)
+    }
+}
+
+/// Result of `cached_fetch`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: String,
+    pub from_cache: bool,
+    /// Served from cache without revalidation because the API was unreachable.
+    pub stale: bool,
+    pub fetched_at: i64,
+}
+
+impl CachedResponse {
+    fn from_entry(entry: &CacheEntry, body: Vec<u8>, from_cache: bool, stale: bool) -> Self {
+        Self {
+            status: entry.status,
+            content_type: entry.content_type.clone(),
+            body: String::from_utf8_lossy(&body).into_owned(),
+            from_cache,
+            stale,
+            fetched_at: entry.fetched_at,
+        }
+    }
+}
+
+/// Cache directory handle registered in app state.
+pub struct HttpCache {
+    dir: PathBuf,
+}
+
+/// Open the cache under `data_dir/http-cache`.
+pub fn init(data_dir: &Path) -> AppResult<HttpCache> {
+    let dir = data_dir.join(CACHE_DIR_NAME);
+    fs::create_dir_all(&dir)?;
+    Ok(HttpCache { dir })
+}
+
+impl HttpCache {
+    // URLs are hashed so any query string maps to a safe file name
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let key = hex(&Sha256::digest(url.as_bytes()));
+        (
+            self.dir.join(format!("{key}.{META_EXTENSION}")),
+            self.dir.join(format!("{key}.{BODY_EXTENSION}")),
+        )
+    }
+
+    fn load(&self, url: &str) -> Option<(CacheEntry, Vec<u8>)> {
+        let (meta, body) = self.paths(url);
+        let entry: CacheEntry = serde_json::from_slice(&fs::read(meta).ok()?).ok()?;
+        Some((entry, fs::read(body).ok()?))
+    }
+
+    // Body first, then metadata, so a reader never sees metadata for a missing body
+    fn store(&self, entry: &CacheEntry, body: &[u8]) -> AppResult<()> {
+        let (meta, body_path) = self.paths(&entry.url);
+        write_atomic(&body_path, body)?;
+        write_atomic(&meta, &serde_json::to_vec_pretty(entry)?)
+    }
+
+    fn remove(&self, url: &str) {
+        let (meta, body) = self.paths(url);
+        let _ = fs::remove_file(meta);
+        let _ = fs::remove_file(body);
+    }
+
+    fn entries(&self) -> AppResult<Vec<CacheEntry>> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(META_EXTENSION) {
+                continue;
+            }
+            if let Some(entry) = fs::read(&path)
+                .ok()
+                .and_then(|b| serde_json::from_slice::<CacheEntry>(&b).ok())
+            {
+                entries.push(entry);
+            }
+        }
+        entries.sort_by(|a, b| a.url.cmp(&b.url));
+        Ok(entries)
+    }
+}
+
+fn write_atomic(path: &Path, bytes: &[u8]) -> AppResult<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// Returns None for `no-store`; `no-cache` keeps the entry but forces revalidation
+fn cache_lifetime(cache_control: Option<&str>) -> Option<Option<i64>> {
+    let mut max_age = None;
+    for directive in cache_control.unwrap_or_default().split(',') {
+        let directive = directive.trim().to_ascii_lowercase();
+        if directive == "no-store" {
+            return None;
+        }
+        if directive == "no-cache" {
+            return Some(None);
+        }
+        if let Some(seconds) = directive.strip_prefix("max-age=") {
+            max_age = seconds.parse::<i64>().ok().map(|s| s * 1000);
+        }
+    }
+    Some(max_age)
+}
+
+/// GET an API path through the cache.
+///
+/// `max_age_ms` overrides the server's freshness lifetime, e.g. to accept day-old pricing.
+#[tauri::command]
+pub async fn cached_fetch(
+    app: AppHandle,
+    cache: State<'_, HttpCache>,
+    path: String,
+    max_age_ms: Option<i64>,
+) -> AppResult<CachedResponse> {
+    let url = config::api_url(&app, &path)?.to_string();
+    let cached = cache.load(&url);
+    if let Some((entry, body)) = &cached {
+        if entry.is_fresh(max_age_ms) {
+            return Ok(CachedResponse::from_entry(entry, body.clone(), true, false));
+        }
+        if !app.state::<NetworkMonitor>().is_online() {
+            return Ok(CachedResponse::from_entry(entry, body.clone(), true, true));
+        }
+    }
+
+    let http = app.state::<HttpClient>();
+    let mut request = http.client().get(&url);
+    if let Some((entry, _)) = &cached {
+        if let Some(etag) = &entry.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &entry.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let response = match http
+        .send_authorized(&app, request, RetryPolicy::default())
+        .await
+    {
+        Ok(response) => response,
+        // The monitor may not have noticed the outage yet
+        Err(err) => match cached {
+            Some((entry, body)) => {
+                tracing::debug!(%err, %url, "serving stale cache entry");
+                return Ok(CachedResponse::from_entry(&entry, body, true, true));
+            }
+            None => return Err(err),
+        },
+    };
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let lifetime = cache_lifetime(header(CACHE_CONTROL).as_deref());
+
+    if response.status() == StatusCode::NOT_MODIFIED {
+        if let Some((mut entry, body)) = cached {
+            entry.fetched_at = now_ms();
+            if let Some(max_age) = lifetime {
+                entry.max_age_ms = max_age;
+            }
+            let (meta, _) = cache.paths(&url);
+            write_atomic(&meta, &serde_json::to_vec_pretty(&entry)?)?;
+            return Ok(CachedResponse::from_entry(&entry, body, true, false));
+        }
+    }
+
+    let (content_type, etag, last_modified) =
+        (header(CONTENT_TYPE), header(ETAG), header(LAST_MODIFIED));
+    let response = response.error_for_status()?;
+    let status = response.status().as_u16();
+    let body = response.bytes().await?.to_vec();
+    let entry = CacheEntry {
+        url: url.clone(),
+        status,
+        content_type,
+        etag,
+        last_modified,
+        fetched_at: now_ms(),
+        max_age_ms: lifetime.flatten(),
+        size: body.len() as u64,
+    };
+    match lifetime {
+        Some(_) => cache.store(&entry, &body)?,
+        None => cache.remove(&url),
+    }
+    Ok(CachedResponse::from_entry(&entry, body, false, false))
+}
+
+/// Cached entries, for a settings or debug view.
+#[tauri::command]
+pub async fn list_http_cache(cache: State<'_, HttpCache>) -> AppResult<Vec<CacheEntry>> {
+    cache.entries()
+}
+
+/// Remove entries whose URL starts with `url_prefix`, or everything when omitted.
+///
+/// Returns the number of entries removed.
+#[tauri::command]
+pub async fn purge_http_cache(
+    cache: State<'_, HttpCache>,
+    url_prefix: Option<String>,
+) -> AppResult<usize> {
+    let mut removed = 0;
+    for entry in cache.entries()? {
+        if url_prefix
+            .as_deref()
+            .is_none_or(|prefix| entry.url.starts_with(prefix))
+        {
+            cache.remove(&entry.url);
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,256 @@
+//! Shared outbound HTTP client with timeouts and retries with jittered exponential backoff.
+
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, State};
+use tauri_plugin_http::reqwest::header::{AUTHORIZATION, RETRY_AFTER};
+use tauri_plugin_http::reqwest::{Client, Method, Request, RequestBuilder, Response, StatusCode};
+
+use crate::auth;
+use crate::config::{self, Config};
+use crate::error::{AppError, AppResult};
+use crate::pinning::{self, PinSet};
+use crate::proxy::ResolvedProxy;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// How many times to attempt a request and how long to wait between attempts.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total attempts including the first; 1 disables retries.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Full jitter keeps many clients recovering from the same outage from retrying in lockstep
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.max_delay);
+        Duration::from_millis(rand::rng().random_range(0..=ceiling.as_millis() as u64))
+    }
+}
+
+/// Shared client registered in app state; reuses connections across every feature.
+pub struct HttpClient {
+    client: RwLock<Client>,
+}
+
+fn build(config: &Config, proxy: &ResolvedProxy) -> AppResult<Client> {
+    let tls = pinning::tls_config(PinSet::load(&config.tls_pins)?)?;
+    let builder = Client::builder()
+        .use_preconfigured_tls(tls)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(concat!("Momentum/", env!("CARGO_PKG_VERSION")));
+    Ok(proxy.apply(builder)?.build()?)
+}
+
+impl HttpClient {
+    /// Build the client with the pin set derived from `config` and the given proxy.
+    pub fn new(config: &Config, proxy: &ResolvedProxy) -> AppResult<Self> {
+        Ok(Self {
+            client: RwLock::new(build(config, proxy)?),
+        })
+    }
+
+    /// Swap in a client for new proxy settings; requests in flight finish on the old one.
+    pub fn rebuild(&self, config: &Config, proxy: &ResolvedProxy) -> AppResult<()> {
+        let client = build(config, proxy)?;
+        *self.client.write().unwrap_or_else(|e| e.into_inner()) = client;
+        Ok(())
+    }
+
+    /// The underlying client, for callers that manage their own retries.
+    ///
+    /// Clones share one connection pool, so this is cheap.
+    pub fn client(&self) -> Client {
+        self.client
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Send with retries.
+    ///
+    /// Connect failures are always retried because the request never left. Timeouts, 5xx, and
+    /// 429 are retried only for idempotent requests, so a POST is never applied twice.
+    pub async fn send(&self, request: RequestBuilder, policy: RetryPolicy) -> AppResult<Response> {
+        let request = request.build()?;
+        let client = self.client();
+        let idempotent = is_idempotent(&request);
+        let mut attempt = 1;
+        loop {
+            // Streaming bodies cannot be replayed
+            let Some(this) = request.try_clone() else {
+                return Ok(client.execute(request).await?);
+            };
+            let last = attempt >= policy.max_attempts;
+            let delay = match client.execute(this).await {
+                Ok(response) if !last && idempotent && is_retryable(response.status()) => {
+                    retry_after(&response)
+                        .map(|d| d.min(policy.max_delay))
+                        .unwrap_or_else(|| policy.delay(attempt))
+                }
+                Ok(response) => return Ok(response),
+                // A pin mismatch also looks like a connect failure but will not go away
+                Err(err)
+                    if !last
+                        && (err.is_connect() || (idempotent && err.is_timeout()))
+                        && pinning::mismatch_host(&err).is_none() =>
+                {
+                    policy.delay(attempt)
+                }
+                Err(err) => return Err(err.into()),
+            };
+            tracing::debug!(url = %request.url(), attempt, ?delay, "retrying request");
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Send with the session's access token attached, when signed in.
+    ///
+    /// A 401 means the token was revoked or expired early, so it is refreshed and the request
+    /// sent once more; streaming bodies cannot be replayed and return the 401 as is.
+    pub async fn send_authorized(
+        &self,
+        app: &AppHandle,
+        request: RequestBuilder,
+        policy: RetryPolicy,
+    ) -> AppResult<Response> {
+        let Some(token) = auth::access_token(app).await? else {
+            return self.send(request, policy).await;
+        };
+        let retry = request.try_clone();
+        let response = self.send(request.bearer_auth(&token), policy).await?;
+        match retry {
+            Some(retry) if response.status() == StatusCode::UNAUTHORIZED => {
+                let token = auth::refresh_rejected(app, &token).await?;
+                self.send(retry.bearer_auth(token), policy).await
+            }
+            _ => Ok(response),
+        }
+    }
+}
+
+fn is_idempotent(request: &Request) -> bool {
+    matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE
+    ) || request.headers().contains_key(IDEMPOTENCY_KEY_HEADER)
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    seconds.trim().parse().ok().map(Duration::from_secs)
+}
+
+fn default_method() -> String {
+    "GET".into()
+}
+
+/// A request to our API from the frontend.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiRequest {
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// API path, resolved against the configured base URL.
+    pub path: String,
+    #[serde(default)]
+    pub query: BTreeMap<String, String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Sent as JSON.
+    pub body: Option<Value>,
+    pub timeout_ms: Option<u64>,
+    pub max_attempts: Option<u32>,
+}
+
+/// Response to an [`ApiRequest`]; non-2xx statuses are returned, not raised.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiResponse {
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+}
+
+/// Call the API with the session token attached and the shared retry policy applied.
+#[tauri::command]
+pub async fn api_request(
+    app: AppHandle,
+    http: State<'_, HttpClient>,
+    request: ApiRequest,
+) -> AppResult<ApiResponse> {
+    let method = Method::from_bytes(request.method.to_ascii_uppercase().as_bytes())
+        .map_err(|_| AppError::InvalidInput(format!("invalid method {}", request.method)))?;
+    let mut url = config::api_url(&app, &request.path)?;
+    if !request.query.is_empty() {
+        url.query_pairs_mut().extend_pairs(&request.query);
+    }
+
+    let mut builder = http.client().request(method, url);
+    for (name, value) in &request.headers {
+        builder = builder.header(name, value);
+    }
+    let has_auth = request
+        .headers
+        .keys()
+        .any(|name| name.eq_ignore_ascii_case(AUTHORIZATION.as_str()));
+    if let Some(body) = &request.body {
+        builder = builder
+            .header("Content-Type", "application/json")
+            .body(body.to_string());
+    }
+    if let Some(ms) = request.timeout_ms {
+        builder = builder.timeout(Duration::from_millis(ms));
+    }
+
+    let mut policy = RetryPolicy::default();
+    if let Some(max_attempts) = request.max_attempts {
+        policy.max_attempts = max_attempts.max(1);
+    }
+    let response = if has_auth {
+        http.send(builder, policy).await?
+    } else {
+        http.send_authorized(&app, builder, policy).await?
+    };
+    let status = response.status().as_u16();
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let body = String::from_utf8_lossy(&response.bytes().await?).into_owned();
+    Ok(ApiResponse {
+        status,
+        headers,
+        body,
+    })
+}
@@ -0,0 +1,267 @@
+//! Locking the session after the machine sits idle, for shared estimating workstations.
+//!
+//! Idle time is the OS's, so typing in another app keeps the session open. Locking drops
+//! the encryption key and closes the database; unlocking takes the passphrase through
+//! `unlock_database`, or a fresh OAuth sign-in when encryption is off. Without either there
+//! is nothing to re-authenticate against, so the session never locks. While it is locked the
+//! invoke handler refuses every command but those the lock screen needs.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::ipc::Invoke;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::auth;
+use crate::encryption::{self, Encryption};
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+
+/// Emitted with a [`SessionLocked`] when the session locks.
+pub const SESSION_LOCK_EVENT: &str = "session:lock";
+/// Emitted once the user has re-authenticated.
+pub const SESSION_UNLOCK_EVENT: &str = "session:unlock";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Commands that still run while locked: unlocking, and what the lock screen shows.
+pub(crate) const WHILE_LOCKED: &[&str] = &[
+    "unlock_database",
+    "unlock_with_recovery_key",
+    "start_oauth_login",
+    "biometric_unlock",
+    "unlock_with_pin",
+    "lock_session",
+    "get_session_locked",
+    "get_encryption_status",
+    "get_auth_status",
+    "get_biometric_status",
+    "frontend_ready",
+    "deeplink_ready",
+];
+
+/// Why the session locked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LockReason {
+    Idle,
+    Manual,
+}
+
+/// Payload for `session:lock`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionLocked {
+    pub reason: LockReason,
+    /// Unlocking takes the encryption passphrase; otherwise it takes an OAuth sign-in.
+    pub passphrase: bool,
+}
+
+/// Lock state registered in app state.
+#[derive(Default)]
+pub struct SessionLock {
+    locked: AtomicBool,
+}
+
+/// Fail with `SESSION_LOCKED` while the session is locked.
+pub fn ensure_unlocked(app: &AppHandle) -> AppResult<()> {
+    match app.try_state::<SessionLock>() {
+        Some(lock) if lock.locked.load(Ordering::SeqCst) => Err(AppError::SessionLocked),
+        _ => Ok(()),
+    }
+}
+
+/// Refuse an invoke with `SESSION_LOCKED` while locked, unless it is in [`WHILE_LOCKED`];
+/// a refused invoke is already answered.
+pub fn guard(invoke: Invoke) -> Option<Invoke> {
+    let command = invoke.message.command();
+    if WHILE_LOCKED.contains(&command)
+        || ensure_unlocked(invoke.message.webview().app_handle()).is_ok()
+    {
+        return Some(invoke);
+    }
+    tracing::debug!(command, "command refused while the session is locked");
+    invoke.resolver.reject(AppError::SessionLocked);
+    None
+}
+
+fn lock(app: &AppHandle, reason: LockReason) -> AppResult<bool> {
+    let passphrase = app.state::<Encryption>().is_enabled();
+    if !passphrase && auth::session()?.is_none() {
+        return Ok(false);
+    }
+    if app
+        .state::<SessionLock>()
+        .locked
+        .swap(true, Ordering::SeqCst)
+    {
+        return Ok(false);
+    }
+    encryption::lock(app);
+    tracing::info!(?reason, "session locked");
+    let _ = app.emit(SESSION_LOCK_EVENT, SessionLocked { reason, passphrase });
+    Ok(true)
+}
+
+/// Clear the lock after the user re-authenticated.
+pub fn release(app: &AppHandle) {
+    let Some(lock) = app.try_state::<SessionLock>() else {
+        return;
+    };
+    if lock.locked.swap(false, Ordering::SeqCst) {
+        tracing::info!("session unlocked");
+        let _ = app.emit(SESSION_UNLOCK_EVENT, ());
+    }
+}
+
+/// Signing in again re-authenticates unless the database also needs its passphrase.
+pub fn on_signed_in(app: &AppHandle) {
+    if !app.state::<Encryption>().is_enabled() {
+        release(app);
+    }
+}
+
+#[cfg(windows)]
+mod os {
+    use std::time::Duration;
+
+    use windows::Win32::System::SystemInformation::GetTickCount;
+    use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+
+    pub fn idle_time() -> Option<Duration> {
+        let mut info = LASTINPUTINFO {
+            cbSize: size_of::<LASTINPUTINFO>() as u32,
+            dwTime: 0,
+        };
+        unsafe { GetLastInputInfo(&mut info) }.ok()?;
+        // Both are tick counts that wrap after 49 days
+        let ticks = unsafe { GetTickCount() }.wrapping_sub(info.dwTime);
+        Some(Duration::from_millis(ticks as u64))
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use std::time::Duration;
+
+    const COMBINED_SESSION_STATE: i32 = 0;
+    const ANY_INPUT_EVENT: u32 = u32::MAX;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGEventSourceSecondsSinceLastEventType(state: i32, event_type: u32) -> f64;
+    }
+
+    pub fn idle_time() -> Option<Duration> {
+        let seconds = unsafe {
+            CGEventSourceSecondsSinceLastEventType(COMBINED_SESSION_STATE, ANY_INPUT_EVENT)
+        };
+        Duration::try_from_secs_f64(seconds).ok()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use std::time::Duration;
+
+    use x11rb::connection::Connection;
+    use x11rb::protocol::screensaver::ConnectionExt;
+
+    // GNOME on Wayland; XWayland only sees input aimed at X clients, so this goes first
+    fn mutter() -> Option<Duration> {
+        let connection = zbus::blocking::Connection::session().ok()?;
+        let reply = connection
+            .call_method(
+                Some("org.gnome.Mutter.IdleMonitor"),
+                "/org/gnome/Mutter/IdleMonitor/Core",
+                Some("org.gnome.Mutter.IdleMonitor"),
+                "GetIdletime",
+                &(),
+            )
+            .ok()?;
+        reply
+            .body()
+            .deserialize::<u64>()
+            .ok()
+            .map(Duration::from_millis)
+    }
+
+    fn x11() -> Option<Duration> {
+        let (connection, screen) = x11rb::connect(None).ok()?;
+        let root = connection.setup().roots.get(screen)?.root;
+        let info = connection.screensaver_query_info(root).ok()?.reply().ok()?;
+        Some(Duration::from_millis(info.ms_since_user_input as u64))
+    }
+
+    pub fn idle_time() -> Option<Duration> {
+        mutter().or_else(x11)
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+mod os {
+    use std::time::Duration;
+
+    pub fn idle_time() -> Option<Duration> {
+        None
+    }
+}
+
+/// How long since the last keyboard or mouse input anywhere on the machine, where the OS
+/// says; this blocks on some desktops, so call it off the async runtime.
+pub(crate) fn idle_time() -> Option<Duration> {
+    os::idle_time()
+}
+
+/// Poll OS idle time and lock once it passes the configured timeout.
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut warned = false;
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let minutes = app.state::<AppState>().config().idle_lock_minutes;
+            let Some(minutes) = minutes.filter(|&minutes| minutes > 0) else {
+                continue;
+            };
+            if app.state::<SessionLock>().locked.load(Ordering::SeqCst) {
+                continue;
+            }
+            let idle = tauri::async_runtime::spawn_blocking(os::idle_time)
+                .await
+                .ok()
+                .flatten();
+            let Some(idle) = idle else {
+                if !warned {
+                    tracing::warn!("system idle time is unavailable; idle lock is inactive");
+                    warned = true;
+                }
+                continue;
+            };
+            if idle >= Duration::from_secs(minutes as u64 * 60) {
+                let handle = app.clone();
+                let result =
+                    tauri::async_runtime::spawn_blocking(move || lock(&handle, LockReason::Idle))
+                        .await;
+                if let Ok(Err(err)) = result {
+                    tracing::warn!(%err, "idle lock failed");
+                }
+            }
+        }
+    });
+}
+
+/// Lock right away, e.g. from a "lock" button before walking away.
+///
+/// Returns false when already locked or when there is nothing to re-authenticate against.
+#[tauri::command]
+pub async fn lock_session(app: AppHandle) -> AppResult<bool> {
+    tauri::async_runtime::spawn_blocking(move || lock(&app, LockReason::Manual)).await?
+}
+
+/// Whether the session is locked.
+#[tauri::command]
+pub async fn get_session_locked(lock: State<'_, SessionLock>) -> AppResult<bool> {
+    Ok(lock.locked.load(Ordering::SeqCst))
+}
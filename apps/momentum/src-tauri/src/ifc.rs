@@ -0,0 +1,863 @@
+//! Quantity takeoff from IFC building models.
+//!
+//! An IFC file is a STEP physical file: one `#id=TYPE(args);` statement per entity. Most of
+//! a model is geometry, so the reader notes every entity's type but parses arguments only
+//! for the handful that matter here: the spatial tree of storeys, the relationships that
+//! place elements on them, the element quantity sets authoring tools export, and the
+//! project's units. Each element's quantities are converted to SI, added up by element type
+//! and level, and stored against the project so takeoffs can be priced without re-reading
+//! the model.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::audit::{self, Change};
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::progress;
+use crate::state::AppState;
+use crate::units;
+
+/// Emitted with an [`IfcProgress`] while a model is read and saved.
+pub const PROGRESS_EVENT: &str = "ifc:progress";
+const PROGRESS_EVERY_BYTES: usize = 4 << 20;
+
+// Entities whose arguments are read; everything else is only typed
+const PARSED: &[&str] = &[
+    "IFCPROJECT",
+    "IFCUNITASSIGNMENT",
+    "IFCSIUNIT",
+    "IFCCONVERSIONBASEDUNIT",
+    "IFCMEASUREWITHUNIT",
+    "IFCBUILDINGSTOREY",
+    "IFCRELCONTAINEDINSPATIALSTRUCTURE",
+    "IFCRELAGGREGATES",
+    "IFCRELDEFINESBYPROPERTIES",
+    "IFCELEMENTQUANTITY",
+    "IFCQUANTITYLENGTH",
+    "IFCQUANTITYAREA",
+    "IFCQUANTITYVOLUME",
+    "IFCQUANTITYCOUNT",
+    "IFCQUANTITYWEIGHT",
+];
+// Containers rather than elements to count
+const SPATIAL: &[&str] = &["IFCPROJECT", "IFCSITE", "IFCBUILDING", "IFCBUILDINGSTOREY"];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IfcProgress {
+    pub import_id: String,
+    /// `read` while the file is parsed, then `save`.
+    pub phase: &'static str,
+    pub done: u64,
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum QuantityKind {
+    Length,
+    Area,
+    Volume,
+    Count,
+    Weight,
+}
+
+impl QuantityKind {
+    fn from_entity(kind: &str) -> Option<Self> {
+        Some(match kind {
+            "IFCQUANTITYLENGTH" => Self::Length,
+            "IFCQUANTITYAREA" => Self::Area,
+            "IFCQUANTITYVOLUME" => Self::Volume,
+            "IFCQUANTITYCOUNT" => Self::Count,
+            "IFCQUANTITYWEIGHT" => Self::Weight,
+            _ => return None,
+        })
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Length => "length",
+            Self::Area => "area",
+            Self::Volume => "volume",
+            Self::Count => "count",
+            Self::Weight => "weight",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "length" => Self::Length,
+            "area" => Self::Area,
+            "volume" => Self::Volume,
+            "weight" => Self::Weight,
+            _ => Self::Count,
+        }
+    }
+
+    /// SI symbol the values are stored in.
+    fn unit(self) -> Option<&'static str> {
+        match self {
+            Self::Length => Some("m"),
+            Self::Area => Some("m²"),
+            Self::Volume => Some("m³"),
+            Self::Weight => Some("kg"),
+            Self::Count => None,
+        }
+    }
+
+    fn unit_type(self) -> &'static str {
+        match self {
+            Self::Length => "LENGTHUNIT",
+            Self::Area => "AREAUNIT",
+            Self::Volume => "VOLUMEUNIT",
+            Self::Weight => "MASSUNIT",
+            Self::Count => "",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IfcQuantity {
+    /// Name from the quantity set, such as `NetSideArea` or `Length`.
+    pub name: String,
+    pub kind: QuantityKind,
+    pub value: f64,
+    pub unit: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IfcElementGroup {
+    /// IFC entity name as written in the file, such as `IFCWALLSTANDARDCASE`.
+    pub element_type: String,
+    /// Storey name; `None` when the elements are not on a storey.
+    pub level: Option<String>,
+    pub element_count: u64,
+    pub quantities: Vec<IfcQuantity>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IfcImportInfo {
+    pub id: String,
+    pub project_id: String,
+    pub file_name: String,
+    pub ifc_schema: Option<String>,
+    pub element_count: u64,
+    pub imported_at: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IfcImport {
+    #[serde(flatten)]
+    pub info: IfcImportInfo,
+    pub groups: Vec<IfcElementGroup>,
+}
+
+#[derive(Debug, Clone)]
+enum Arg {
+    Null,
+    Ref(u64),
+    Number(f64),
+    Text(String),
+    Enum(String),
+    List(Vec<Arg>),
+    /// A value wrapped in its type, as `IFCLENGTHMEASURE(2.5)`.
+    Typed(Box<Arg>),
+}
+
+impl Arg {
+    fn reference(&self) -> Option<u64> {
+        match self {
+            Self::Ref(id) => Some(*id),
+            _ => None,
+        }
+    }
+
+    fn number(&self) -> Option<f64> {
+        match self {
+            Self::Number(n) => Some(*n),
+            Self::Typed(inner) => inner.number(),
+            _ => None,
+        }
+    }
+
+    fn text(&self) -> Option<&str> {
+        match self {
+            Self::Text(text) | Self::Enum(text) => Some(text),
+            Self::Typed(inner) => inner.text(),
+            _ => None,
+        }
+    }
+
+    fn references(&self) -> Vec<u64> {
+        match self {
+            Self::List(items) => items.iter().filter_map(Arg::reference).collect(),
+            Self::Ref(id) => vec![*id],
+            _ => Vec::new(),
+        }
+    }
+}
+
+// Text escapes: '' for a quote, \X2\hhhh...\X0\ for UTF-16, \X\hh for Latin-1
+fn decode(raw: &str) -> String {
+    let raw = raw.replace("''", "'");
+    let mut out = String::with_capacity(raw.len());
+    let mut rest = raw.as_str();
+    while let Some(at) = rest.find('\\') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+        if let Some(body) = rest.strip_prefix("\\X2\\") {
+            let end = body.find("\\X0\\").unwrap_or(body.len());
+            let units: Vec<u16> = body.as_bytes()[..end]
+                .chunks(4)
+                .filter_map(|c| u16::from_str_radix(std::str::from_utf8(c).ok()?, 16).ok())
+                .collect();
+            out.push_str(&String::from_utf16_lossy(&units));
+            rest = body.get(end + 4..).unwrap_or_default();
+        } else if let Some(hex) = rest.strip_prefix("\\X\\").and_then(|b| b.get(..2)) {
+            out.extend(u8::from_str_radix(hex, 16).ok().map(char::from));
+            rest = &rest[5..];
+        } else {
+            out.push('\\');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+struct Args<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Args<'_> {
+    fn skip_space(&mut self) {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(u8::is_ascii_whitespace)
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn take_while(&mut self, keep: impl Fn(u8) -> bool) -> &str {
+        let start = self.pos;
+        while self.bytes.get(self.pos).is_some_and(|b| keep(*b)) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos]).unwrap_or_default()
+    }
+
+    // A parenthesized, comma-separated list starting at the current `(`
+    fn list(&mut self) -> Option<Vec<Arg>> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        loop {
+            self.skip_space();
+            match self.bytes.get(self.pos)? {
+                b')' => {
+                    self.pos += 1;
+                    return Some(items);
+                }
+                b',' => self.pos += 1,
+                _ => items.push(self.value()?),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Option<Arg> {
+        self.skip_space();
+        let byte = *self.bytes.get(self.pos)?;
+        Some(match byte {
+            b'$' | b'*' => {
+                self.pos += 1;
+                Arg::Null
+            }
+            b'#' => {
+                self.pos += 1;
+                Arg::Ref(self.take_while(|b| b.is_ascii_digit()).parse().ok()?)
+            }
+            b'\'' => {
+                let start = self.pos + 1;
+                let mut at = start;
+                loop {
+                    match self.bytes.get(at)? {
+                        b'\'' if self.bytes.get(at + 1) == Some(&b'\'') => at += 2,
+                        b'\'' => break,
+                        _ => at += 1,
+                    }
+                }
+                self.pos = at + 1;
+                Arg::Text(decode(&String::from_utf8_lossy(&self.bytes[start..at])))
+            }
+            b'.' => {
+                self.pos += 1;
+                let name = self.take_while(|b| b != b'.').to_string();
+                self.pos += 1;
+                Arg::Enum(name)
+            }
+            b'(' => Arg::List(self.list()?),
+            b if b.is_ascii_alphabetic() => {
+                self.take_while(|b| b.is_ascii_alphanumeric() || b == b'_');
+                self.skip_space();
+                let mut inner = self.list()?;
+                Arg::Typed(Box::new(if inner.len() == 1 {
+                    inner.remove(0)
+                } else {
+                    Arg::List(inner)
+                }))
+            }
+            _ => Arg::Number(
+                self.take_while(|b| {
+                    b.is_ascii_digit() || matches!(b, b'-' | b'+' | b'.' | b'E' | b'e')
+                })
+                .parse()
+                .ok()?,
+            ),
+        })
+    }
+}
+
+struct Model<'a> {
+    schema: Option<String>,
+    types: HashMap<u64, &'a str>,
+    args: HashMap<u64, Vec<Arg>>,
+}
+
+impl Model<'_> {
+    fn arg(&self, id: u64, index: usize) -> Option<&Arg> {
+        self.args.get(&id)?.get(index)
+    }
+
+    // Entities of one type in file order, so the first of several quantity sets wins
+    fn of_type(&self, kind: &str) -> Vec<(u64, &[Arg])> {
+        let mut found: Vec<(u64, &[Arg])> = self
+            .types
+            .iter()
+            .filter(|(_, t)| **t == kind)
+            .filter_map(|(id, _)| Some((*id, self.args.get(id)?.as_slice())))
+            .collect();
+        found.sort_by_key(|(id, _)| *id);
+        found
+    }
+}
+
+fn read_model<'a>(text: &'a str, mut on_progress: impl FnMut(u64)) -> AppResult<Model<'a>> {
+    let schema = text.find("FILE_SCHEMA").and_then(|at| {
+        let rest = &text[at..];
+        let open = rest.find('\'')? + 1;
+        let close = rest[open..].find('\'')? + open;
+        Some(rest[open..close].to_string())
+    });
+    let Some(data) = text.find("DATA;") else {
+        return Err(AppError::InvalidInput(
+            "not an IFC file: it has no DATA section".into(),
+        ));
+    };
+    let bytes = text.as_bytes();
+    let mut model = Model {
+        schema,
+        types: HashMap::new(),
+        args: HashMap::new(),
+    };
+    let mut pos = data + 5;
+    let mut reported = pos;
+    while let Some(offset) = bytes[pos..].iter().position(|b| *b == b'#' || *b == b'E') {
+        pos += offset;
+        if bytes[pos..].starts_with(b"ENDSEC;") {
+            break;
+        }
+        if bytes[pos] == b'E' {
+            pos += 1;
+            continue;
+        }
+        let mut args = Args {
+            bytes,
+            pos: pos + 1,
+        };
+        let id: Option<u64> = args.take_while(|b| b.is_ascii_digit()).parse().ok();
+        args.skip_space();
+        if args.bytes.get(args.pos) != Some(&b'=') {
+            pos = args.pos.max(pos + 1);
+            continue;
+        }
+        args.pos += 1;
+        args.skip_space();
+        let start = args.pos;
+        args.take_while(|b| b.is_ascii_alphanumeric() || b == b'_');
+        let kind = &text[start..args.pos];
+        args.skip_space();
+        if let (Some(id), true) = (id, args.bytes.get(args.pos) == Some(&b'(')) {
+            model.types.insert(id, kind);
+            if PARSED.contains(&kind) {
+                if let Some(values) = args.list() {
+                    model.args.insert(id, values);
+                }
+            }
+        }
+        // On to the end of the statement, stepping over quoted text that may hold a `;`
+        let mut quoted = false;
+        let mut at = args.pos;
+        while let Some(byte) = bytes.get(at) {
+            match byte {
+                b'\'' => quoted = !quoted,
+                b';' if !quoted => break,
+                _ => {}
+            }
+            at += 1;
+        }
+        pos = at + 1;
+        if pos - reported >= PROGRESS_EVERY_BYTES {
+            reported = pos;
+            on_progress(pos as u64);
+        }
+        if pos >= bytes.len() {
+            break;
+        }
+    }
+    Ok(model)
+}
+
+// Size of a unit entity in SI for its kind, such as 0.001 for millimeters
+fn unit_factor(model: &Model, id: u64, depth: usize) -> Option<f64> {
+    let kind = *model.types.get(&id)?;
+    let unit_type = model.arg(id, 1)?.text()?;
+    let power = match unit_type {
+        "AREAUNIT" => 2,
+        "VOLUMEUNIT" => 3,
+        _ => 1,
+    };
+    match kind {
+        "IFCSIUNIT" => {
+            let prefix = match model.arg(id, 2).and_then(Arg::text) {
+                Some("KILO") => 1e3,
+                Some("HECTO") => 1e2,
+                Some("DECA") => 1e1,
+                Some("DECI") => 1e-1,
+                Some("CENTI") => 1e-2,
+                Some("MILLI") => 1e-3,
+                Some("MICRO") => 1e-6,
+                _ => 1.0,
+            };
+            // Mass is counted in kilograms, not grams
+            let base = if model.arg(id, 3).and_then(Arg::text) == Some("GRAM") {
+                1e-3
+            } else {
+                1.0
+            };
+            Some(base * f64::powi(prefix, power))
+        }
+        "IFCCONVERSIONBASEDUNIT" => {
+            let named = model
+                .arg(id, 2)
+                .and_then(Arg::text)
+                .and_then(units::find)
+                .and_then(|unit| {
+                    let si = units::UNITS.iter().find(|si| {
+                        si.dimension == unit.dimension
+                            && matches!(si.symbol, "m" | "m²" | "m³" | "kg")
+                    })?;
+                    units::convert(1.0, unit, si).ok()
+                });
+            named.or_else(|| {
+                // Otherwise the factor it declares, in terms of some other unit
+                let measure = model.arg(id, 3)?.reference()?;
+                let value = model.arg(measure, 0)?.number()?;
+                let of = model.arg(measure, 1)?.reference()?;
+                (depth < 4)
+                    .then(|| unit_factor(model, of, depth + 1))?
+                    .map(|f| value * f)
+            })
+        }
+        _ => None,
+    }
+}
+
+// Conversion factors the project declares, by unit type
+fn project_units(model: &Model) -> HashMap<String, f64> {
+    let mut factors = HashMap::new();
+    let Some((project, _)) = model.of_type("IFCPROJECT").first().copied() else {
+        return factors;
+    };
+    let Some(assignment) = model.arg(project, 8).and_then(Arg::reference) else {
+        return factors;
+    };
+    for unit in model
+        .arg(assignment, 0)
+        .map(Arg::references)
+        .unwrap_or_default()
+    {
+        if let (Some(kind), Some(factor)) = (
+            model.arg(unit, 1).and_then(Arg::text),
+            unit_factor(model, unit, 0),
+        ) {
+            factors.insert(kind.to_string(), factor);
+        }
+    }
+    factors
+}
+
+// Each element's level: the storey it is placed on, or what it is a part of is on
+fn levels(model: &Model) -> HashMap<u64, Option<String>> {
+    let storey_name = |id: u64| {
+        (model.types.get(&id) == Some(&"IFCBUILDINGSTOREY")).then(|| {
+            model
+                .arg(id, 2)
+                .and_then(Arg::text)
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("#{id}"))
+        })
+    };
+    let is_spatial = |id: &u64| model.types.get(id).is_some_and(|t| SPATIAL.contains(t));
+    let mut levels: HashMap<u64, Option<String>> = HashMap::new();
+    for (_, args) in model.of_type("IFCRELCONTAINEDINSPATIALSTRUCTURE") {
+        let Some(structure) = args.get(5).and_then(Arg::reference) else {
+            continue;
+        };
+        for element in args.get(4).map(Arg::references).unwrap_or_default() {
+            levels
+                .entry(element)
+                .or_insert_with(|| storey_name(structure));
+        }
+    }
+    let mut parts: HashMap<u64, Vec<u64>> = HashMap::new();
+    for (_, args) in model.of_type("IFCRELAGGREGATES") {
+        if let Some(whole) = args.get(4).and_then(Arg::reference) {
+            parts
+                .entry(whole)
+                .or_default()
+                .extend(args.get(5).map(Arg::references).unwrap_or_default());
+        }
+    }
+    // Spaces and the like hang off storeys by aggregation rather than containment
+    let mut queue: Vec<(u64, Option<String>)> = levels
+        .iter()
+        .map(|(id, level)| (*id, level.clone()))
+        .chain(
+            model
+                .types
+                .iter()
+                .filter(|(_, t)| **t == "IFCBUILDINGSTOREY")
+                .map(|(id, _)| (*id, storey_name(*id))),
+        )
+        .collect();
+    while let Some((whole, level)) = queue.pop() {
+        for part in parts.get(&whole).into_iter().flatten() {
+            if !is_spatial(part) && !levels.contains_key(part) {
+                levels.insert(*part, level.clone());
+                queue.push((*part, level.clone()));
+            }
+        }
+    }
+    levels
+}
+
+// Quantity totals by name and kind
+type Sums = BTreeMap<(String, QuantityKind), f64>;
+
+fn take_off(model: &Model) -> (u64, Vec<IfcElementGroup>) {
+    let factors = project_units(model);
+    let levels = levels(model);
+
+    // Several quantity sets may repeat a value; the first one read for an element counts
+    let mut quantities: HashMap<u64, Sums> = HashMap::new();
+    for (_, args) in model.of_type("IFCRELDEFINESBYPROPERTIES") {
+        let Some(set) = args.get(5).and_then(Arg::reference) else {
+            continue;
+        };
+        if model.types.get(&set) != Some(&"IFCELEMENTQUANTITY") {
+            continue;
+        }
+        let elements: Vec<u64> = args
+            .get(4)
+            .map(Arg::references)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|id| levels.contains_key(id))
+            .collect();
+        for quantity in model.arg(set, 5).map(Arg::references).unwrap_or_default() {
+            let Some(kind) = model
+                .types
+                .get(&quantity)
+                .and_then(|t| QuantityKind::from_entity(t))
+            else {
+                continue;
+            };
+            let (Some(name), Some(value)) = (
+                model.arg(quantity, 0).and_then(Arg::text),
+                model.arg(quantity, 3).and_then(Arg::number),
+            ) else {
+                continue;
+            };
+            let factor = model
+                .arg(quantity, 2)
+                .and_then(Arg::reference)
+                .and_then(|unit| unit_factor(model, unit, 0))
+                .or_else(|| factors.get(kind.unit_type()).copied())
+                .unwrap_or(1.0);
+            for element in &elements {
+                quantities
+                    .entry(*element)
+                    .or_default()
+                    .entry((name.to_string(), kind))
+                    .or_insert(value * factor);
+            }
+        }
+    }
+
+    let mut groups: BTreeMap<(Option<String>, String), (u64, Sums)> = BTreeMap::new();
+    for (element, level) in &levels {
+        let Some(kind) = model.types.get(element) else {
+            continue;
+        };
+        let group = groups.entry((level.clone(), kind.to_string())).or_default();
+        group.0 += 1;
+        for (key, value) in quantities.get(element).into_iter().flatten() {
+            *group.1.entry(key.clone()).or_default() += value;
+        }
+    }
+    let groups = groups
+        .into_iter()
+        .map(
+            |((level, element_type), (element_count, sums))| IfcElementGroup {
+                element_type,
+                level,
+                element_count,
+                quantities: sums
+                    .into_iter()
+                    .map(|((name, kind), value)| IfcQuantity {
+                        name,
+                        kind,
+                        value,
+                        unit: kind.unit(),
+                    })
+                    .collect(),
+            },
+        )
+        .collect();
+    (levels.len() as u64, groups)
+}
+
+fn save(conn: &mut Connection, actor: &str, import: &IfcImport) -> AppResult<()> {
+    let tx = conn.transaction()?;
+    let info = &import.info;
+    tx.execute(
+        "INSERT INTO ifc_imports
+            (id, project_id, file_name, ifc_schema, element_count, imported_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            info.id,
+            info.project_id,
+            info.file_name,
+            info.ifc_schema,
+            info.element_count as i64,
+            info.imported_at
+        ],
+    )?;
+    {
+        let mut group = tx.prepare_cached(
+            "INSERT INTO ifc_element_groups (import_id, element_type, level, element_count)
+             VALUES (?1, ?2, ?3, ?4)",
+        )?;
+        let mut quantity = tx.prepare_cached(
+            "INSERT INTO ifc_quantities (import_id, element_type, level, name, kind, value)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        for g in &import.groups {
+            let level = g.level.as_deref().unwrap_or_default();
+            group.execute(params![
+                info.id,
+                g.element_type,
+                level,
+                g.element_count as i64
+            ])?;
+            for q in &g.quantities {
+                quantity.execute(params![
+                    info.id,
+                    g.element_type,
+                    level,
+                    q.name,
+                    q.kind.as_str(),
+                    q.value
+                ])?;
+            }
+        }
+    }
+    audit::record(
+        &tx,
+        actor,
+        Change::new("import_ifc", "project", &info.project_id).after(&serde_json::json!({
+            "importId": info.id,
+            "fileName": info.file_name,
+            "elementCount": info.element_count,
+        }))?,
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn info_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<IfcImportInfo> {
+    Ok(IfcImportInfo {
+        id: row.get("id")?,
+        project_id: row.get("project_id")?,
+        file_name: row.get("file_name")?,
+        ifc_schema: row.get("ifc_schema")?,
+        element_count: row.get::<_, i64>("element_count")? as u64,
+        imported_at: row.get("imported_at")?,
+    })
+}
+
+fn load(conn: &Connection, import_id: &str) -> AppResult<IfcImport> {
+    let info = conn
+        .query_row(
+            "SELECT * FROM ifc_imports WHERE id = ?1",
+            [import_id],
+            info_from_row,
+        )
+        .map_err(|err| match err {
+            rusqlite::Error::QueryReturnedNoRows => AppError::not_found("IFC import", import_id),
+            err => err.into(),
+        })?;
+    let mut groups: Vec<IfcElementGroup> = conn
+        .prepare(
+            "SELECT element_type, level, element_count FROM ifc_element_groups
+             WHERE import_id = ?1 ORDER BY level, element_type",
+        )?
+        .query_map([import_id], |row| {
+            Ok(IfcElementGroup {
+                element_type: row.get(0)?,
+                level: Some(row.get::<_, String>(1)?).filter(|level| !level.is_empty()),
+                element_count: row.get::<_, i64>(2)? as u64,
+                quantities: Vec::new(),
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    let mut stmt = conn.prepare(
+        "SELECT element_type, level, name, kind, value FROM ifc_quantities
+         WHERE import_id = ?1 ORDER BY name, kind",
+    )?;
+    let mut rows = stmt.query([import_id])?;
+    while let Some(row) = rows.next()? {
+        let (element_type, level): (String, String) = (row.get(0)?, row.get(1)?);
+        let kind = QuantityKind::parse(&row.get::<_, String>(3)?);
+        if let Some(group) = groups.iter_mut().find(|g| {
+            g.element_type == element_type && g.level.as_deref().unwrap_or_default() == level
+        }) {
+            group.quantities.push(IfcQuantity {
+                name: row.get(2)?,
+                kind,
+                value: row.get(4)?,
+                unit: kind.unit(),
+            });
+        }
+    }
+    Ok(IfcImport { info, groups })
+}
+
+/// Take quantities off an IFC model and store them against a project.
+///
+/// `import_id` tags `ifc:progress` events.
+#[tauri::command]
+pub async fn import_ifc(
+    app: AppHandle,
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    project_id: String,
+    path: PathBuf,
+    import_id: Option<String>,
+) -> AppResult<IfcImport> {
+    let import_id = import_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let actor = audit::actor(&state);
+    let (id, handle) = (import_id.clone(), app.clone());
+    let import = tauri::async_runtime::spawn_blocking(move || {
+        let bytes = std::fs::read(&path)?;
+        let text = String::from_utf8_lossy(&bytes);
+        let total = bytes.len() as u64;
+        let taskbar = progress::track(&handle, format!("ifc:{id}"));
+        let emit = |phase, done| {
+            taskbar.set(done, Some(total));
+            let _ = handle.emit(
+                PROGRESS_EVENT,
+                IfcProgress {
+                    import_id: id.clone(),
+                    phase,
+                    done,
+                    total,
+                },
+            );
+        };
+        let model = read_model(&text, |done| emit("read", done))?;
+        emit("save", total);
+        let (element_count, groups) = take_off(&model);
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        AppResult::Ok(IfcImport {
+            info: IfcImportInfo {
+                id,
+                project_id,
+                file_name,
+                ifc_schema: model.schema,
+                element_count,
+                imported_at: now_ms(),
+            },
+            groups,
+        })
+    })
+    .await??;
+
+    db.run(move |conn| {
+        save(conn, &actor, &import)?;
+        tracing::info!(
+            import_id = %import.info.id,
+            elements = import.info.element_count,
+            groups = import.groups.len(),
+            "IFC quantities imported"
+        );
+        Ok(import)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn list_ifc_imports(
+    db: State<'_, Db>,
+    project_id: String,
+) -> AppResult<Vec<IfcImportInfo>> {
+    db.run(move |conn| {
+        let imports = conn
+            .prepare("SELECT * FROM ifc_imports WHERE project_id = ?1 ORDER BY imported_at DESC")?
+            .query_map([&project_id], info_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(imports)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_ifc_import(db: State<'_, Db>, import_id: String) -> AppResult<IfcImport> {
+    db.run(move |conn| load(conn, &import_id)).await
+}
+
+#[tauri::command]
+pub async fn delete_ifc_import(db: State<'_, Db>, import_id: String) -> AppResult<()> {
+    db.run(move |conn| {
+        let deleted = conn.execute("DELETE FROM ifc_imports WHERE id = ?1", [&import_id])?;
+        if deleted == 0 {
+            return Err(AppError::not_found("IFC import", import_id));
+        }
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,76 @@
+//! CSV line-item import, streamed so large cost sheets never load into memory at once.
+
+use std::path::PathBuf;
+
+use csv::{ErrorKind, ReaderBuilder, StringRecord};
+use tauri::{AppHandle, State};
+
+use super::{ImportSchema, ImportSummary, Importer};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+use crate::tasks::TaskKind;
+
+/// Import the rows of a CSV file as items in a project.
+///
+/// `import_id` tags progress events so the caller can tell concurrent imports apart, and
+/// is the task id to cancel it by.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn import_csv(
+    app: AppHandle,
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    project_id: String,
+    path: PathBuf,
+    schema: ImportSchema,
+    delimiter: Option<char>,
+    import_id: Option<String>,
+) -> AppResult<ImportSummary> {
+    let delimiter = match delimiter.unwrap_or(',') {
+        c if c.is_ascii() => c as u8,
+        c => {
+            return Err(AppError::InvalidInput(format!(
+                "delimiter {c} is not an ASCII character"
+            )))
+        }
+    };
+    let handle = app.clone();
+    state
+        .tasks()
+        .run(&handle, TaskKind::Import, import_id, |task| {
+            db.run(move |conn| {
+                let mut reader = ReaderBuilder::new()
+                    .delimiter(delimiter)
+                    .flexible(true)
+                    .from_path(&path)?;
+                // Excel writes a byte-order mark that would otherwise stick to the first header
+                let headers: Vec<String> = reader
+                    .headers()?
+                    .iter()
+                    .map(|h| h.trim_start_matches('\u{feff}').to_string())
+                    .collect();
+                let mut importer = Importer::new(conn, app, task, project_id, schema, &headers)?;
+
+                let mut record = StringRecord::new();
+                loop {
+                    match reader.read_record(&mut record) {
+                        Ok(true) => {
+                            let row = record.position().map(|p| p.line()).unwrap_or_default();
+                            importer.push_row(row, |index| record.get(index))?;
+                        }
+                        Ok(false) => break,
+                        // A bad row is reported and skipped; anything else means the file is
+                        // unreadable
+                        Err(err) if matches!(err.kind(), ErrorKind::Utf8 { .. }) => {
+                            let row = err.position().map(|p| p.line()).unwrap_or_default();
+                            importer.reject_row(row, err.to_string())?;
+                        }
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+                importer.finish("import_csv")
+            })
+        })
+        .await
+}
@@ -0,0 +1,1299 @@
+//! Excel formula evaluation, so workbook cells import what their formulas work out to from
+//! the data in the file rather than whatever result was cached when it was last saved.
+//!
+//! A practical subset is understood: arithmetic, comparison, percent, and `&`; cell, range,
+//! and whole-column references on any sheet; defined names; and the functions estimates lean
+//! on (see [`Book::call`]). A formula outside that subset, or one caught in a circular
+//! reference, keeps the value Excel saved and is reported so the import can say so.
+
+use std::collections::{HashMap, HashSet};
+
+use calamine::{CellErrorType, Data, Range};
+
+// Dependency chains deeper than this fall back to cached values rather than the stack
+const MAX_DEPTH: usize = 200;
+
+/// A zero-based (row, column) position, as calamine uses.
+pub(crate) type Pos = (u32, u32);
+
+/// A position as Excel writes it, such as `C12`.
+pub(crate) fn cell_name((row, col): Pos) -> String {
+    let mut letters = Vec::new();
+    let mut col = col + 1;
+    while col > 0 {
+        letters.push(b'A' + ((col - 1) % 26) as u8);
+        col = (col - 1) / 26;
+    }
+    letters.reverse();
+    format!("{}{}", String::from_utf8_lossy(&letters), row + 1)
+}
+
+/// Loads a sheet's cell values and formulas by name, or `None` when there is no such sheet.
+pub(crate) type Loader<'l> = Box<dyn FnMut(&str) -> Option<(Range<Data>, Range<String>)> + 'l>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Val {
+    Empty,
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Error(CellErrorType),
+}
+
+impl Val {
+    fn from_data(data: &Data) -> Self {
+        match data {
+            Data::Empty => Self::Empty,
+            Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => Self::Text(s.clone()),
+            Data::Int(n) => Self::Number(*n as f64),
+            Data::Float(n) => Self::Number(*n),
+            Data::Bool(b) => Self::Bool(*b),
+            Data::DateTime(dt) => Self::Number(dt.as_f64()),
+            Data::Error(err) => Self::Error(err.clone()),
+        }
+    }
+
+    fn into_data(self) -> Data {
+        match self {
+            // A formula pointing at a blank cell shows 0
+            Self::Empty => Data::Float(0.0),
+            Self::Number(n) => Data::Float(n),
+            Self::Text(s) => Data::String(s),
+            Self::Bool(b) => Data::Bool(b),
+            Self::Error(err) => Data::Error(err),
+        }
+    }
+
+    fn number(&self) -> Result<f64, CellErrorType> {
+        match self {
+            Self::Empty => Ok(0.0),
+            Self::Number(n) => Ok(*n),
+            Self::Bool(b) => Ok(f64::from(u8::from(*b))),
+            Self::Text(s) => s.trim().parse().map_err(|_| CellErrorType::Value),
+            Self::Error(err) => Err(err.clone()),
+        }
+    }
+
+    fn text(&self) -> Result<String, CellErrorType> {
+        match self {
+            Self::Empty => Ok(String::new()),
+            Self::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => Ok(format!("{n:.0}")),
+            Self::Number(n) => Ok(n.to_string()),
+            Self::Text(s) => Ok(s.clone()),
+            Self::Bool(b) => Ok(if *b { "TRUE" } else { "FALSE" }.into()),
+            Self::Error(err) => Err(err.clone()),
+        }
+    }
+
+    fn truth(&self) -> Result<bool, CellErrorType> {
+        match self {
+            Self::Empty => Ok(false),
+            Self::Bool(b) => Ok(*b),
+            Self::Number(n) => Ok(*n != 0.0),
+            Self::Text(s) if s.eq_ignore_ascii_case("TRUE") => Ok(true),
+            Self::Text(s) if s.eq_ignore_ascii_case("FALSE") => Ok(false),
+            Self::Text(_) => Err(CellErrorType::Value),
+            Self::Error(err) => Err(err.clone()),
+        }
+    }
+
+    // Excel orders numbers before text before booleans, and text without regard to case
+    fn compare(&self, other: &Self) -> std::cmp::Ordering {
+        fn rank(v: &Val) -> u8 {
+            match v {
+                Val::Number(_) | Val::Empty => 0,
+                Val::Text(_) => 1,
+                Val::Bool(_) => 2,
+                Val::Error(_) => 3,
+            }
+        }
+        match (self, other) {
+            (Self::Text(a), Self::Empty) | (Self::Empty, Self::Text(a)) if a.is_empty() => {
+                std::cmp::Ordering::Equal
+            }
+            (Self::Text(a), Self::Text(b)) => a.to_lowercase().cmp(&b.to_lowercase()),
+            (Self::Bool(a), Self::Bool(b)) => a.cmp(b),
+            (a, b) if rank(a) == 0 && rank(b) == 0 => {
+                let (x, y) = (a.number().unwrap_or(0.0), b.number().unwrap_or(0.0));
+                x.partial_cmp(&y).unwrap_or(std::cmp::Ordering::Equal)
+            }
+            (a, b) => rank(a).cmp(&rank(b)),
+        }
+    }
+}
+
+/// An argument as a function sees it: one value, or the cells of a range row by row.
+enum Arg {
+    Value(Val),
+    Range(Vec<Vec<Val>>),
+}
+
+impl Arg {
+    fn cells(&self) -> Box<dyn Iterator<Item = &Val> + '_> {
+        match self {
+            Self::Value(v) => Box::new(std::iter::once(v)),
+            Self::Range(rows) => Box::new(rows.iter().flatten()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(Val),
+    Cell(Option<String>, Pos),
+    /// Inclusive corners; a whole column runs to `u32::MAX`.
+    Range(Option<String>, Pos, Pos),
+    Name(String),
+    Negate(Box<Expr>),
+    Percent(Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Word(String),
+    Sheet(String),
+    Error(CellErrorType),
+    Op(&'static str),
+    Open,
+    Close,
+    Comma,
+    Colon,
+}
+
+const OPERATORS: &[&str] = &[
+    "<>", "<=", ">=", "+", "-", "*", "/", "^", "&", "=", "<", ">", "%",
+];
+
+fn tokenize(formula: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = formula.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let rest: String = chars[i..].iter().take(2).collect();
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' | ')' | ',' | ':' => {
+                tokens.push(match c {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    ',' => Token::Comma,
+                    _ => Token::Colon,
+                });
+                i += 1;
+            }
+            '"' | '\'' => {
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err("unterminated text".into()),
+                        Some(q) if *q == c && chars.get(i + 1) == Some(&c) => {
+                            text.push(c);
+                            i += 2;
+                        }
+                        Some(q) if *q == c => break,
+                        Some(other) => {
+                            text.push(*other);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                if c == '"' {
+                    tokens.push(Token::Text(text));
+                } else if chars.get(i) == Some(&'!') {
+                    tokens.push(Token::Sheet(text));
+                    i += 1;
+                } else {
+                    return Err("a quoted sheet name without a reference".into());
+                }
+            }
+            '#' => {
+                let word: String = chars[i..]
+                    .iter()
+                    .take_while(|c| c.is_alphanumeric() || matches!(c, '#' | '/' | '!' | '?'))
+                    .collect();
+                i += word.chars().count();
+                let error = match word.to_uppercase().as_str() {
+                    "#DIV/0!" => CellErrorType::Div0,
+                    "#N/A" => CellErrorType::NA,
+                    "#NAME?" => CellErrorType::Name,
+                    "#NULL!" => CellErrorType::Null,
+                    "#NUM!" => CellErrorType::Num,
+                    "#REF!" => CellErrorType::Ref,
+                    "#VALUE!" => CellErrorType::Value,
+                    _ => return Err(format!("{word} is not understood")),
+                };
+                tokens.push(Token::Error(error));
+            }
+            c if c.is_ascii_digit()
+                || (c == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit)) =>
+            {
+                let start = i;
+                while chars
+                    .get(i)
+                    .is_some_and(|c| c.is_ascii_digit() || *c == '.')
+                {
+                    i += 1;
+                }
+                if matches!(chars.get(i), Some('e' | 'E'))
+                    && chars
+                        .get(i + 1)
+                        .is_some_and(|c| c.is_ascii_digit() || matches!(c, '+' | '-'))
+                {
+                    i += 2;
+                    while chars.get(i).is_some_and(char::is_ascii_digit) {
+                        i += 1;
+                    }
+                }
+                let text: String = chars[start..i].iter().collect();
+                tokens.push(Token::Number(
+                    text.parse()
+                        .map_err(|_| format!("{text} is not a number"))?,
+                ));
+            }
+            c if c.is_alphabetic() || matches!(c, '$' | '_' | '\\') => {
+                let word: String = chars[i..]
+                    .iter()
+                    .take_while(|c| c.is_alphanumeric() || matches!(c, '$' | '_' | '.' | '\\'))
+                    .collect();
+                i += word.chars().count();
+                if chars.get(i) == Some(&'!') {
+                    tokens.push(Token::Sheet(word));
+                    i += 1;
+                } else {
+                    tokens.push(Token::Word(word));
+                }
+            }
+            _ => match OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+                Some(op) => {
+                    tokens.push(Token::Op(op));
+                    i += op.len();
+                }
+                None => return Err(format!("{c} is not understood")),
+            },
+        }
+    }
+    Ok(tokens)
+}
+
+fn column_index(letters: &str) -> Option<u32> {
+    if letters.is_empty() || letters.len() > 3 || !letters.chars().all(|c| c.is_ascii_alphabetic())
+    {
+        return None;
+    }
+    let mut col = 0u32;
+    for c in letters.chars() {
+        col = col * 26 + (c.to_ascii_uppercase() as u32 - 'A' as u32 + 1);
+    }
+    Some(col - 1)
+}
+
+// A1, $B$12, and the like
+fn cell_position(word: &str) -> Option<Pos> {
+    let word = word.replace('$', "");
+    let split = word.find(|c: char| c.is_ascii_digit())?;
+    let (letters, digits) = word.split_at(split);
+    let row: u32 = digits.parse().ok()?;
+    Some((row.checked_sub(1)?, column_index(letters)?))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    at: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.at)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.at).cloned();
+        self.at += 1;
+        token
+    }
+
+    fn eat_op(&mut self, ops: &[&str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                let op = *op;
+                self.at += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        const LEVELS: &[&[&str]] = &[
+            &["=", "<>", "<", ">", "<=", ">="],
+            &["&"],
+            &["+", "-"],
+            &["*", "/"],
+            &["^"],
+        ];
+        let Some(ops) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1)?;
+        while let Some(op) = self.eat_op(ops) {
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    // Negation binds tighter than `^` in Excel, so -2^2 is 4
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.eat_op(&["-", "+"]) {
+            Some("-") => Ok(Expr::Negate(Box::new(self.unary()?))),
+            Some(_) => self.unary(),
+            None => {
+                let mut expr = self.primary()?;
+                while self.eat_op(&["%"]).is_some() {
+                    expr = Expr::Percent(Box::new(expr));
+                }
+                Ok(expr)
+            }
+        }
+    }
+
+    fn reference(&mut self, sheet: Option<String>, word: &str) -> Result<Expr, String> {
+        let column = column_index(&word.replace('$', ""));
+        if self.peek() == Some(&Token::Colon) {
+            self.at += 1;
+            let Some(Token::Word(end)) = self.next() else {
+                return Err("a range without an end".into());
+            };
+            return match (cell_position(word), cell_position(&end), column) {
+                (Some(a), Some(b), _) => Ok(Expr::Range(sheet, a, b)),
+                (None, None, Some(a)) => {
+                    let b = column_index(&end.replace('$', "")).ok_or("a bad column range")?;
+                    Ok(Expr::Range(sheet, (0, a), (u32::MAX, b)))
+                }
+                _ => Err(format!("{word}:{end} is not a range")),
+            };
+        }
+        match cell_position(word) {
+            Some(pos) => Ok(Expr::Cell(sheet, pos)),
+            None if sheet.is_none() => Ok(Expr::Name(word.to_lowercase())),
+            None => Err(format!("{word} is not a cell")),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next().ok_or("the formula ends early")? {
+            Token::Number(n) => Ok(Expr::Literal(Val::Number(n))),
+            Token::Text(s) => Ok(Expr::Literal(Val::Text(s))),
+            Token::Error(err) => Ok(Expr::Literal(Val::Error(err))),
+            Token::Open => {
+                let expr = self.binary(0)?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err("a parenthesis is not closed".into()),
+                }
+            }
+            Token::Sheet(sheet) => match self.next() {
+                Some(Token::Word(word)) => self.reference(Some(sheet.to_lowercase()), &word),
+                _ => Err(format!("{sheet}! is not followed by a reference")),
+            },
+            Token::Word(word) if self.peek() == Some(&Token::Open) => {
+                self.at += 1;
+                let mut args = Vec::new();
+                if self.peek() == Some(&Token::Close) {
+                    self.at += 1;
+                } else {
+                    loop {
+                        // An argument left empty, as in IF(A1,,1), is blank
+                        if matches!(self.peek(), Some(Token::Comma | Token::Close)) {
+                            args.push(Expr::Literal(Val::Empty));
+                        } else {
+                            args.push(self.binary(0)?);
+                        }
+                        match self.next() {
+                            Some(Token::Comma) => {}
+                            Some(Token::Close) => break,
+                            _ => return Err(format!("the arguments to {word} are not closed")),
+                        }
+                    }
+                }
+                let name = word.to_uppercase();
+                let name = name
+                    .strip_prefix("_XLFN.")
+                    .or_else(|| name.strip_prefix("_XLWS."))
+                    .unwrap_or(&name)
+                    .to_string();
+                Ok(Expr::Call(name, args))
+            }
+            Token::Word(word) if word.eq_ignore_ascii_case("TRUE") => {
+                Ok(Expr::Literal(Val::Bool(true)))
+            }
+            Token::Word(word) if word.eq_ignore_ascii_case("FALSE") => {
+                Ok(Expr::Literal(Val::Bool(false)))
+            }
+            Token::Word(word) => self.reference(None, &word),
+            token => Err(format!("{token:?} is out of place")),
+        }
+    }
+}
+
+fn parse(formula: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        tokens: tokenize(formula.trim_start_matches('='))?,
+        at: 0,
+    };
+    let expr = parser.binary(0)?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) => Err(format!("{token:?} is out of place")),
+    }
+}
+
+struct Sheet {
+    values: Range<Data>,
+    formulas: HashMap<Pos, String>,
+    // Last used row and column, for whole-column references
+    end: Pos,
+}
+
+/// A workbook's sheets, loaded as formulas reach them, and every formula result worked out.
+pub(crate) struct Book<'l> {
+    sheets: HashMap<String, Option<Sheet>>,
+    names: HashMap<String, String>,
+    load: Loader<'l>,
+    results: HashMap<(String, Pos), Val>,
+    visiting: HashSet<(String, Pos)>,
+    /// Formula cells that kept their cached value, and why.
+    pub issues: Vec<(String, Pos, String)>,
+}
+
+type Eval = Result<Val, String>;
+
+impl<'l> Book<'l> {
+    /// `names` are the workbook's defined names and what they refer to.
+    pub(crate) fn new(names: &[(String, String)], load: Loader<'l>) -> Self {
+        Self {
+            sheets: HashMap::new(),
+            names: names
+                .iter()
+                .map(|(name, formula)| (name.to_lowercase(), formula.clone()))
+                .collect(),
+            load,
+            results: HashMap::new(),
+            visiting: HashSet::new(),
+            issues: Vec::new(),
+        }
+    }
+
+    fn sheet(&mut self, name: &str) -> Option<&Sheet> {
+        if !self.sheets.contains_key(name) {
+            let loaded = (self.load)(name).map(|(values, formulas)| {
+                let formulas: HashMap<Pos, String> = formulas
+                    .cells()
+                    .filter(|(_, _, f)| !f.is_empty())
+                    .map(|(r, c, f)| {
+                        let (r0, c0) = formulas.start().unwrap_or_default();
+                        ((r0 + r as u32, c0 + c as u32), f.clone())
+                    })
+                    .collect();
+                let end = formulas
+                    .keys()
+                    .fold(values.end().unwrap_or_default(), |end, pos| {
+                        (end.0.max(pos.0), end.1.max(pos.1))
+                    });
+                Sheet {
+                    values,
+                    formulas,
+                    end,
+                }
+            });
+            self.sheets.insert(name.to_string(), loaded);
+        }
+        self.sheets.get(name)?.as_ref()
+    }
+
+    /// Hand back a sheet's cell values, as loaded.
+    pub(crate) fn into_values(mut self, sheet: &str) -> Option<Range<Data>> {
+        Some(self.sheets.remove(&sheet.to_lowercase())??.values)
+    }
+
+    /// Work out every formula on `sheet`, in row order, returning each cell's new value.
+    pub(crate) fn evaluate_sheet(&mut self, sheet: &str) -> Vec<(Pos, Data)> {
+        let sheet = sheet.to_lowercase();
+        let mut cells: Vec<Pos> = match self.sheet(&sheet) {
+            Some(loaded) => loaded.formulas.keys().copied().collect(),
+            None => return Vec::new(),
+        };
+        cells.sort_unstable();
+        cells
+            .into_iter()
+            .map(|pos| (pos, self.cell(&sheet, pos, 0).into_data()))
+            .collect()
+    }
+
+    // A cell's value, working out its formula if it has one
+    fn cell(&mut self, sheet: &str, pos: Pos, depth: usize) -> Val {
+        let key = (sheet.to_string(), pos);
+        if let Some(value) = self.results.get(&key) {
+            return value.clone();
+        }
+        let Some(loaded) = self.sheet(sheet) else {
+            return Val::Error(CellErrorType::Ref);
+        };
+        let cached = loaded
+            .values
+            .get_value(pos)
+            .map(Val::from_data)
+            .unwrap_or(Val::Empty);
+        let Some(formula) = loaded.formulas.get(&pos).cloned() else {
+            return cached;
+        };
+        if depth > MAX_DEPTH {
+            return cached;
+        }
+        if !self.visiting.insert(key.clone()) {
+            self.issues.push((
+                sheet.to_string(),
+                pos,
+                "it is part of a circular reference".into(),
+            ));
+            return cached;
+        }
+        let result = parse(&formula).and_then(|expr| self.eval(&expr, sheet, pos, depth + 1));
+        self.visiting.remove(&key);
+        let value = match result {
+            Ok(value) => value,
+            Err(reason) => {
+                self.issues.push((sheet.to_string(), pos, reason));
+                cached
+            }
+        };
+        self.results.insert(key, value.clone());
+        value
+    }
+
+    fn range(
+        &mut self,
+        sheet: &str,
+        from: Pos,
+        to: Pos,
+        depth: usize,
+    ) -> Result<Vec<Vec<Val>>, String> {
+        let end = self
+            .sheet(sheet)
+            .map(|s| s.end)
+            .ok_or("a sheet that does not exist")?;
+        let (top, bottom) = (from.0.min(to.0), from.0.max(to.0).min(end.0));
+        let (left, right) = (from.1.min(to.1), from.1.max(to.1));
+        if (u64::from(bottom.saturating_sub(top)) + 1) * (u64::from(right - left) + 1) > 1_000_000 {
+            return Err("a range over a million cells".into());
+        }
+        Ok((top..=bottom)
+            .map(|row| {
+                (left..=right)
+                    .map(|col| self.cell(sheet, (row, col), depth))
+                    .collect()
+            })
+            .collect())
+    }
+
+    fn arg(&mut self, expr: &Expr, sheet: &str, at: Pos, depth: usize) -> Result<Arg, String> {
+        match expr {
+            // A referenced cell is read the way a range is, so SUM(A1) passes over text in A1
+            Expr::Cell(..) => Ok(Arg::Range(vec![vec![self.eval(expr, sheet, at, depth)?]])),
+            Expr::Range(on, from, to) => {
+                let on = on.as_deref().unwrap_or(sheet).to_string();
+                Ok(Arg::Range(self.range(&on, *from, *to, depth)?))
+            }
+            Expr::Name(name) => {
+                let formula = self
+                    .names
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| format!("{name} is not a defined name"))?;
+                self.arg(&parse(&formula)?, sheet, at, depth + 1)
+            }
+            expr => Ok(Arg::Value(self.eval(expr, sheet, at, depth)?)),
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr, sheet: &str, at: Pos, depth: usize) -> Eval {
+        if depth > MAX_DEPTH {
+            return Err("references nested too deeply".into());
+        }
+        Ok(match expr {
+            Expr::Literal(value) => value.clone(),
+            Expr::Cell(on, pos) => {
+                let on = on.as_deref().unwrap_or(sheet).to_string();
+                self.cell(&on, *pos, depth)
+            }
+            Expr::Range(..) | Expr::Name(_) => match self.arg(expr, sheet, at, depth)? {
+                Arg::Value(value) => value,
+                // A range where one value belongs takes the cell in the formula's own row or
+                // column, as Excel's implicit intersection does
+                Arg::Range(rows) => {
+                    let (top, left) = match expr {
+                        Expr::Range(_, from, to) => (from.0.min(to.0), from.1.min(to.1)),
+                        _ => (at.0, at.1),
+                    };
+                    match (rows.len(), rows.first().map_or(0, Vec::len)) {
+                        (1, 1) => rows[0][0].clone(),
+                        (_, 1) => {
+                            at.0.checked_sub(top)
+                                .and_then(|r| rows.get(r as usize))
+                                .map(|r| r[0].clone())
+                                .unwrap_or(Val::Error(CellErrorType::Value))
+                        }
+                        (1, _) => {
+                            at.1.checked_sub(left)
+                                .and_then(|c| rows[0].get(c as usize))
+                                .cloned()
+                                .unwrap_or(Val::Error(CellErrorType::Value))
+                        }
+                        _ => Val::Error(CellErrorType::Value),
+                    }
+                }
+            },
+            Expr::Negate(inner) => match self.eval(inner, sheet, at, depth)?.number() {
+                Ok(n) => Val::Number(-n),
+                Err(err) => Val::Error(err),
+            },
+            Expr::Percent(inner) => match self.eval(inner, sheet, at, depth)?.number() {
+                Ok(n) => Val::Number(n / 100.0),
+                Err(err) => Val::Error(err),
+            },
+            Expr::Binary(op, left, right) => {
+                let a = self.eval(left, sheet, at, depth)?;
+                let b = self.eval(right, sheet, at, depth)?;
+                binary(op, &a, &b)
+            }
+            Expr::Call(name, args) => self.call(name, args, sheet, at, depth)?,
+        })
+    }
+
+    fn args(
+        &mut self,
+        args: &[Expr],
+        sheet: &str,
+        at: Pos,
+        depth: usize,
+    ) -> Result<Vec<Arg>, String> {
+        args.iter()
+            .map(|arg| self.arg(arg, sheet, at, depth))
+            .collect()
+    }
+
+    fn value(&mut self, args: &[Expr], index: usize, sheet: &str, at: Pos, depth: usize) -> Eval {
+        match args.get(index) {
+            Some(expr) => self.eval(expr, sheet, at, depth),
+            None => Ok(Val::Empty),
+        }
+    }
+
+    /// Functions understood: SUM, AVERAGE, MIN, MAX, COUNT, COUNTA, PRODUCT, SUMPRODUCT,
+    /// SUMIF, COUNTIF, IF, IFERROR, AND, OR, NOT, ROUND, ROUNDUP, ROUNDDOWN, INT, ABS, MOD,
+    /// CEILING, FLOOR, VLOOKUP, HLOOKUP, INDEX, MATCH, CONCATENATE, and CONCAT.
+    fn call(&mut self, name: &str, args: &[Expr], sheet: &str, at: Pos, depth: usize) -> Eval {
+        let arity = |min: usize, max: usize| {
+            if args.len() < min || args.len() > max {
+                Err(format!("{name} takes {min} to {max} arguments"))
+            } else {
+                Ok(())
+            }
+        };
+        macro_rules! num {
+            ($index:expr) => {
+                match self.value(args, $index, sheet, at, depth)?.number() {
+                    Ok(n) => n,
+                    Err(err) => return Ok(Val::Error(err)),
+                }
+            };
+        }
+        Ok(match name {
+            "IF" => {
+                arity(2, 3)?;
+                match self.value(args, 0, sheet, at, depth)?.truth() {
+                    Ok(true) => self.value(args, 1, sheet, at, depth)?,
+                    Ok(false) if args.len() == 3 => self.value(args, 2, sheet, at, depth)?,
+                    Ok(false) => Val::Bool(false),
+                    Err(err) => Val::Error(err),
+                }
+            }
+            "IFERROR" => {
+                arity(2, 2)?;
+                match self.value(args, 0, sheet, at, depth)? {
+                    Val::Error(_) => self.value(args, 1, sheet, at, depth)?,
+                    value => value,
+                }
+            }
+            "SUM" | "AVERAGE" | "MIN" | "MAX" | "COUNT" | "PRODUCT" => {
+                let values = self.args(args, sheet, at, depth)?;
+                let mut numbers = Vec::new();
+                for arg in &values {
+                    for cell in arg.cells() {
+                        match (arg, cell) {
+                            (_, Val::Error(err)) if name != "COUNT" => {
+                                return Ok(Val::Error(err.clone()))
+                            }
+                            (_, Val::Number(n)) => numbers.push(*n),
+                            // Typed values count where they convert; cells only as numbers
+                            (Arg::Value(v), _) if !matches!(v, Val::Empty) => match v.number() {
+                                Ok(n) => numbers.push(n),
+                                Err(err) if name != "COUNT" => return Ok(Val::Error(err)),
+                                Err(_) => {}
+                            },
+                            _ => {}
+                        }
+                    }
+                }
+                match name {
+                    "SUM" => Val::Number(numbers.iter().sum()),
+                    "PRODUCT" => Val::Number(numbers.iter().product()),
+                    "COUNT" => Val::Number(numbers.len() as f64),
+                    "AVERAGE" if numbers.is_empty() => Val::Error(CellErrorType::Div0),
+                    "AVERAGE" => Val::Number(numbers.iter().sum::<f64>() / numbers.len() as f64),
+                    "MIN" => Val::Number(numbers.iter().copied().reduce(f64::min).unwrap_or(0.0)),
+                    _ => Val::Number(numbers.iter().copied().reduce(f64::max).unwrap_or(0.0)),
+                }
+            }
+            "COUNTA" => {
+                let values = self.args(args, sheet, at, depth)?;
+                Val::Number(
+                    values
+                        .iter()
+                        .flat_map(Arg::cells)
+                        .filter(|v| !matches!(v, Val::Empty))
+                        .count() as f64,
+                )
+            }
+            "AND" | "OR" => {
+                let values = self.args(args, sheet, at, depth)?;
+                let mut seen = Vec::new();
+                for cell in values.iter().flat_map(Arg::cells) {
+                    match cell {
+                        Val::Empty | Val::Text(_) => {}
+                        value => match value.truth() {
+                            Ok(b) => seen.push(b),
+                            Err(err) => return Ok(Val::Error(err)),
+                        },
+                    }
+                }
+                if seen.is_empty() {
+                    Val::Error(CellErrorType::Value)
+                } else if name == "AND" {
+                    Val::Bool(seen.iter().all(|b| *b))
+                } else {
+                    Val::Bool(seen.iter().any(|b| *b))
+                }
+            }
+            "NOT" => {
+                arity(1, 1)?;
+                match self.value(args, 0, sheet, at, depth)?.truth() {
+                    Ok(b) => Val::Bool(!b),
+                    Err(err) => Val::Error(err),
+                }
+            }
+            "ROUND" | "ROUNDUP" | "ROUNDDOWN" => {
+                arity(2, 2)?;
+                let (value, digits) = (num!(0), num!(1).trunc());
+                let scale = 10f64.powf(digits);
+                let scaled = value * scale;
+                // Scaling leaves float noise, as 2.675 * 100 = 267.49999..., that Excel discounts
+                let scaled = (scaled * 1e9).round() / 1e9;
+                let rounded = match name {
+                    "ROUND" => scaled.round(),
+                    "ROUNDUP" => scaled.abs().ceil() * scaled.signum(),
+                    _ => scaled.trunc(),
+                };
+                Val::Number(rounded / scale)
+            }
+            "INT" => {
+                arity(1, 1)?;
+                Val::Number(num!(0).floor())
+            }
+            "ABS" => {
+                arity(1, 1)?;
+                Val::Number(num!(0).abs())
+            }
+            "MOD" => {
+                arity(2, 2)?;
+                let (x, y) = (num!(0), num!(1));
+                if y == 0.0 {
+                    Val::Error(CellErrorType::Div0)
+                } else {
+                    Val::Number(x - y * (x / y).floor())
+                }
+            }
+            "CEILING" | "FLOOR" => {
+                arity(1, 2)?;
+                let value = num!(0);
+                let step = if args.len() == 2 { num!(1) } else { 1.0 };
+                if step == 0.0 {
+                    Val::Number(0.0)
+                } else if name == "CEILING" {
+                    Val::Number((value / step).ceil() * step)
+                } else {
+                    Val::Number((value / step).floor() * step)
+                }
+            }
+            "CONCATENATE" | "CONCAT" => {
+                let values = self.args(args, sheet, at, depth)?;
+                let mut text = String::new();
+                for cell in values.iter().flat_map(Arg::cells) {
+                    match cell.text() {
+                        Ok(part) => text.push_str(&part),
+                        Err(err) => return Ok(Val::Error(err)),
+                    }
+                }
+                Val::Text(text)
+            }
+            "VLOOKUP" | "HLOOKUP" => {
+                arity(3, 4)?;
+                let key = self.value(args, 0, sheet, at, depth)?;
+                let Arg::Range(mut table) = self.arg(&args[1], sheet, at, depth)? else {
+                    return Ok(Val::Error(CellErrorType::Value));
+                };
+                if name == "HLOOKUP" {
+                    table = transpose(table);
+                }
+                let index = num!(2).trunc();
+                let approximate = match args.get(3) {
+                    Some(_) => match self.value(args, 3, sheet, at, depth)?.truth() {
+                        Ok(b) => b,
+                        Err(err) => return Ok(Val::Error(err)),
+                    },
+                    None => true,
+                };
+                if index < 1.0 {
+                    return Ok(Val::Error(CellErrorType::Value));
+                }
+                let column = index as usize - 1;
+                let keys: Vec<Val> = table
+                    .iter()
+                    .map(|row| row.first().cloned().unwrap_or(Val::Empty))
+                    .collect();
+                match lookup(&key, &keys, if approximate { 1 } else { 0 }) {
+                    Some(row) => match table[row].get(column) {
+                        Some(value) => value.clone(),
+                        None => Val::Error(CellErrorType::Ref),
+                    },
+                    None => Val::Error(CellErrorType::NA),
+                }
+            }
+            "MATCH" => {
+                arity(2, 3)?;
+                let key = self.value(args, 0, sheet, at, depth)?;
+                let Arg::Range(table) = self.arg(&args[1], sheet, at, depth)? else {
+                    return Ok(Val::Error(CellErrorType::NA));
+                };
+                let kind = if args.len() == 3 { num!(2) } else { 1.0 };
+                let keys: Vec<Val> = match table.len() {
+                    1 => table[0].clone(),
+                    _ => table
+                        .iter()
+                        .map(|row| row.first().cloned().unwrap_or(Val::Empty))
+                        .collect(),
+                };
+                let kind = match kind {
+                    k if k > 0.0 => 1,
+                    k if k < 0.0 => -1,
+                    _ => 0,
+                };
+                match lookup(&key, &keys, kind) {
+                    Some(index) => Val::Number(index as f64 + 1.0),
+                    None => Val::Error(CellErrorType::NA),
+                }
+            }
+            "INDEX" => {
+                arity(2, 3)?;
+                let Arg::Range(table) = self.arg(&args[0], sheet, at, depth)? else {
+                    return Ok(Val::Error(CellErrorType::Value));
+                };
+                let first = num!(1).trunc() as usize;
+                let second = if args.len() == 3 {
+                    Some(num!(2).trunc() as usize)
+                } else {
+                    None
+                };
+                // With one index into a single row, it picks the column
+                let (row, col) = match second {
+                    Some(col) => (first, col),
+                    None if table.len() == 1 => (1, first),
+                    None => (first, 1),
+                };
+                match row
+                    .checked_sub(1)
+                    .zip(col.checked_sub(1))
+                    .and_then(|(r, c)| table.get(r)?.get(c))
+                {
+                    Some(value) => value.clone(),
+                    None => Val::Error(CellErrorType::Ref),
+                }
+            }
+            "SUMIF" | "COUNTIF" => {
+                arity(2, if name == "SUMIF" { 3 } else { 2 })?;
+                let Arg::Range(range) = self.arg(&args[0], sheet, at, depth)? else {
+                    return Ok(Val::Error(CellErrorType::Value));
+                };
+                let criterion = Criterion::parse(&self.value(args, 1, sheet, at, depth)?);
+                let sums = match args.get(2) {
+                    Some(expr) => match self.arg(expr, sheet, at, depth)? {
+                        Arg::Range(rows) => Some(rows),
+                        Arg::Value(_) => return Ok(Val::Error(CellErrorType::Value)),
+                    },
+                    None => None,
+                };
+                let mut total = 0.0;
+                let mut count = 0;
+                for (r, row) in range.iter().enumerate() {
+                    for (c, cell) in row.iter().enumerate() {
+                        if !criterion.matches(cell) {
+                            continue;
+                        }
+                        count += 1;
+                        let summed = match &sums {
+                            Some(rows) => rows.get(r).and_then(|row| row.get(c)),
+                            None => Some(cell),
+                        };
+                        if let Some(Val::Number(n)) = summed {
+                            total += n;
+                        }
+                    }
+                }
+                Val::Number(if name == "SUMIF" { total } else { count as f64 })
+            }
+            "SUMPRODUCT" => {
+                let values = self.args(args, sheet, at, depth)?;
+                let arrays: Vec<Vec<Val>> = values
+                    .iter()
+                    .map(|arg| arg.cells().cloned().collect())
+                    .collect();
+                let Some(len) = arrays.first().map(Vec::len) else {
+                    return Err("SUMPRODUCT needs an argument".into());
+                };
+                if arrays.iter().any(|a| a.len() != len) {
+                    return Ok(Val::Error(CellErrorType::Value));
+                }
+                let mut total = 0.0;
+                for i in 0..len {
+                    let mut product = 1.0;
+                    for array in &arrays {
+                        match &array[i] {
+                            Val::Number(n) => product *= n,
+                            Val::Error(err) => return Ok(Val::Error(err.clone())),
+                            _ => product = 0.0,
+                        }
+                    }
+                    total += product;
+                }
+                Val::Number(total)
+            }
+            _ => return Err(format!("{name} is not a supported function")),
+        })
+    }
+}
+
+fn binary(op: &str, a: &Val, b: &Val) -> Val {
+    use std::cmp::Ordering;
+    match op {
+        "&" => match (a.text(), b.text()) {
+            (Ok(x), Ok(y)) => Val::Text(x + y.as_str()),
+            (Err(err), _) | (_, Err(err)) => Val::Error(err),
+        },
+        "=" | "<>" | "<" | ">" | "<=" | ">=" => {
+            if let Val::Error(err) = a {
+                return Val::Error(err.clone());
+            }
+            if let Val::Error(err) = b {
+                return Val::Error(err.clone());
+            }
+            let order = a.compare(b);
+            Val::Bool(match op {
+                "=" => order == Ordering::Equal,
+                "<>" => order != Ordering::Equal,
+                "<" => order == Ordering::Less,
+                ">" => order == Ordering::Greater,
+                "<=" => order != Ordering::Greater,
+                _ => order != Ordering::Less,
+            })
+        }
+        _ => {
+            let (x, y) = match (a.number(), b.number()) {
+                (Ok(x), Ok(y)) => (x, y),
+                (Err(err), _) | (_, Err(err)) => return Val::Error(err),
+            };
+            let result = match op {
+                "+" => x + y,
+                "-" => x - y,
+                "*" => x * y,
+                "/" if y == 0.0 => return Val::Error(CellErrorType::Div0),
+                "/" => x / y,
+                _ => x.powf(y),
+            };
+            if result.is_finite() {
+                Val::Number(result)
+            } else {
+                Val::Error(CellErrorType::Num)
+            }
+        }
+    }
+}
+
+fn transpose(rows: Vec<Vec<Val>>) -> Vec<Vec<Val>> {
+    let width = rows.first().map_or(0, Vec::len);
+    (0..width)
+        .map(|col| {
+            rows.iter()
+                .map(|row| row.get(col).cloned().unwrap_or(Val::Empty))
+                .collect()
+        })
+        .collect()
+}
+
+// Position of `key` in `keys`: exactly for 0, the last at or below it in ascending keys for
+// 1, and the last at or above it in descending keys for -1
+fn lookup(key: &Val, keys: &[Val], kind: i8) -> Option<usize> {
+    use std::cmp::Ordering;
+    let comparable = |v: &Val| {
+        matches!(
+            (key, v),
+            (Val::Number(_), Val::Number(_))
+                | (Val::Text(_), Val::Text(_))
+                | (Val::Bool(_), Val::Bool(_))
+        )
+    };
+    if kind == 0 {
+        return keys
+            .iter()
+            .position(|k| comparable(k) && k.compare(key) == Ordering::Equal);
+    }
+    let mut found = None;
+    for (index, k) in keys.iter().enumerate() {
+        if !comparable(k) {
+            continue;
+        }
+        match (k.compare(key), kind) {
+            (Ordering::Equal, _) | (Ordering::Less, 1) | (Ordering::Greater, -1) => {
+                found = Some(index)
+            }
+            _ => break,
+        }
+    }
+    found
+}
+
+// A SUMIF or COUNTIF condition such as 10, "Labor", or ">=100"
+struct Criterion {
+    op: &'static str,
+    value: Val,
+}
+
+impl Criterion {
+    fn parse(value: &Val) -> Self {
+        let Val::Text(text) = value else {
+            return Self {
+                op: "=",
+                value: value.clone(),
+            };
+        };
+        let op = ["<>", "<=", ">=", "=", "<", ">"]
+            .into_iter()
+            .find(|op| text.starts_with(op))
+            .unwrap_or("");
+        let rest = &text[op.len()..];
+        Self {
+            op: if op.is_empty() { "=" } else { op },
+            value: rest
+                .trim()
+                .parse()
+                .map(Val::Number)
+                .unwrap_or_else(|_| Val::Text(rest.to_string())),
+        }
+    }
+
+    fn matches(&self, cell: &Val) -> bool {
+        // Numbers compare only with numbers, so ">5" passes over text cells
+        let kinds_match = matches!(
+            (&self.value, cell),
+            (Val::Number(_), Val::Number(_))
+                | (Val::Text(_), Val::Text(_) | Val::Empty)
+                | (Val::Bool(_), Val::Bool(_))
+        );
+        if !kinds_match {
+            return self.op == "<>";
+        }
+        binary(self.op, cell, &self.value) == Val::Bool(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What a formula cell held when the workbook was last saved
+    const STALE: f64 = -1.0;
+
+    type Cells = (Range<Data>, Range<String>);
+
+    // A sheet with a cell per string: `=...` is a formula, a number is a number, else text
+    fn sheet(rows: &[&[&str]]) -> Cells {
+        let width = rows.iter().map(|row| row.len()).max().unwrap_or(1) as u32;
+        let end = (rows.len() as u32 - 1, width - 1);
+        let mut values = Range::new((0, 0), end);
+        let mut formulas = Range::new((0, 0), end);
+        for (r, row) in rows.iter().enumerate() {
+            for (c, cell) in row.iter().enumerate() {
+                let pos = (r as u32, c as u32);
+                if let Some(formula) = cell.strip_prefix('=') {
+                    formulas.set_value(pos, formula.to_string());
+                    values.set_value(pos, Data::Float(STALE));
+                } else if let Ok(n) = cell.parse() {
+                    values.set_value(pos, Data::Float(n));
+                } else if !cell.is_empty() {
+                    values.set_value(pos, Data::String(cell.to_string()));
+                }
+            }
+        }
+        (values, formulas)
+    }
+
+    fn book(sheets: Vec<(&str, Cells)>, names: &[(&str, &str)]) -> Book<'static> {
+        let mut sheets: HashMap<String, _> = sheets
+            .into_iter()
+            .map(|(name, sheet)| (name.to_lowercase(), sheet))
+            .collect();
+        let names: Vec<(String, String)> = names
+            .iter()
+            .map(|(name, formula)| (name.to_string(), formula.to_string()))
+            .collect();
+        Book::new(&names, Box::new(move |name| sheets.remove(name)))
+    }
+
+    fn at(results: &[(Pos, Data)], cell: &str) -> Data {
+        let pos = cell_position(cell).unwrap();
+        results
+            .iter()
+            .find(|(p, _)| *p == pos)
+            .map(|(_, data)| data.clone())
+            .unwrap_or_else(|| panic!("{cell} has no formula"))
+    }
+
+    // Each formula alone in A1 of a one-cell sheet
+    fn eval(formula: &str) -> Data {
+        let mut book = book(vec![("Sheet1", sheet(&[&[formula]]))], &[]);
+        let results = book.evaluate_sheet("Sheet1");
+        assert!(book.issues.is_empty(), "{formula}: {:?}", book.issues);
+        at(&results, "A1")
+    }
+
+    #[test]
+    fn operators_follow_excel_precedence() {
+        for (formula, expected) in [
+            ("=1+2*3", Data::Float(7.0)),
+            ("=(1+2)*3", Data::Float(9.0)),
+            ("=10-2-3", Data::Float(5.0)),
+            ("=12/3/2", Data::Float(2.0)),
+            ("=2^3^2", Data::Float(64.0)),
+            ("=2*3^2", Data::Float(18.0)),
+            ("=-2^2", Data::Float(4.0)),
+            ("=200*15%", Data::Float(30.0)),
+            ("=1+2&\"x\"", Data::String("3x".into())),
+            ("=1+1=2", Data::Bool(true)),
+            ("=3>2*2", Data::Bool(false)),
+            ("=1/0", Data::Error(CellErrorType::Div0)),
+        ] {
+            assert_eq!(eval(formula), expected, "{formula}");
+        }
+    }
+
+    #[test]
+    fn ranges_read_cells_sheets_and_whole_columns() {
+        let items = sheet(&[
+            &["Qty", "Cost", "=SUM(A:A)"],
+            &["2", "10"],
+            &["3", "=A3*4"],
+            &["note", "5"],
+            &["=SUM(A2:A4)", "=SUM(B2:B4)"],
+            &["=AVERAGE(A2:A4)", "=SUMPRODUCT(A2:A3,B2:B3)"],
+            &["=Rates!B1*2", "=COUNT(A1:B4)"],
+            &["=Markup*100", "=SUM(Rates!A1:B1)"],
+        ]);
+        let rates = sheet(&[&["7", "1.5"]]);
+        let mut book = book(
+            vec![("Items", items), ("Rates", rates)],
+            &[("Markup", "Rates!$B$1")],
+        );
+        let results = book.evaluate_sheet("Items");
+        assert!(book.issues.is_empty(), "{:?}", book.issues);
+        for (cell, expected) in [
+            ("B3", 12.0),
+            // Text in a range is passed over
+            ("A5", 5.0),
+            ("B5", 27.0),
+            ("A6", 2.5),
+            ("B6", 56.0),
+            ("A7", 3.0),
+            ("B7", 5.0),
+            ("A8", 150.0),
+            ("B8", 8.5),
+            // Every number in the column, formulas included
+            ("C1", 165.5),
+        ] {
+            assert_eq!(at(&results, cell), Data::Float(expected), "{cell}");
+        }
+    }
+
+    #[test]
+    fn vlookup_finds_exact_and_sorted_matches() {
+        let prices = sheet(&[
+            &["stud", "3.5", "0"],
+            &["joist", "12", "100"],
+            &["beam", "40", "500"],
+            &[
+                "=VLOOKUP(\"joist\",A1:B3,2,FALSE)",
+                "=VLOOKUP(\"BEAM\",A1:B3,2,0)",
+            ],
+            &[
+                "=VLOOKUP(250,C1:C3,1,TRUE)",
+                "=VLOOKUP(\"rafter\",A1:B3,2,FALSE)",
+            ],
+            &["=VLOOKUP(\"stud\",A1:B3,3,FALSE)", "=IFERROR(B5,\"none\")"],
+        ]);
+        let mut book = book(vec![("Prices", prices)], &[]);
+        let results = book.evaluate_sheet("Prices");
+        assert_eq!(at(&results, "A4"), Data::Float(12.0));
+        // Matching ignores case, as Excel's does
+        assert_eq!(at(&results, "B4"), Data::Float(40.0));
+        // The largest key not above the one sought
+        assert_eq!(at(&results, "A5"), Data::Float(100.0));
+        assert_eq!(at(&results, "B5"), Data::Error(CellErrorType::NA));
+        assert_eq!(at(&results, "A6"), Data::Error(CellErrorType::Ref));
+        assert_eq!(at(&results, "B6"), Data::String("none".into()));
+    }
+
+    #[test]
+    fn circular_references_keep_the_saved_value() {
+        let cycle = sheet(&[&["=B1+1", "=A1+1", "=A1*2"], &["=A2", "4", "=B2*2"]]);
+        let mut book = book(vec![("Sheet1", cycle)], &[]);
+        let results = book.evaluate_sheet("Sheet1");
+        // Cells outside the cycle still work out, from what the cycle kept
+        assert_eq!(at(&results, "C2"), Data::Float(8.0));
+        for cell in ["A1", "B1", "A2"] {
+            assert_ne!(at(&results, cell), Data::Float(8.0), "{cell}");
+        }
+        let circular: Vec<String> = book
+            .issues
+            .iter()
+            .filter(|(_, _, reason)| reason.contains("circular"))
+            .map(|(_, pos, _)| cell_name(*pos))
+            .collect();
+        assert!(circular.contains(&"A1".to_string()), "{:?}", book.issues);
+        assert!(circular.contains(&"A2".to_string()), "{:?}", book.issues);
+    }
+}
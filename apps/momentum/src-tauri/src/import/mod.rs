@@ -0,0 +1,474 @@
+//! Bulk line-item import shared by every file format.
+//!
+//! A format reader feeds rows to an [`Importer`], which validates them against the
+//! caller's [`ImportSchema`], inserts valid rows in batches, and reports progress.
+//! Invalid rows are skipped and reported rather than failing the whole import.
+
+pub mod csv;
+mod formula;
+pub mod xlsx;
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::audit::{self, Change};
+use crate::db::now_ms;
+use crate::error::{AppError, AppResult};
+use crate::progress::{self, ProgressGuard};
+use crate::state::AppState;
+use crate::tasks::Task;
+use crate::units::{self, Unit};
+use crate::versions::{self, Reason};
+
+// Each batch commits on its own so sync and autosave writes are not held up for a whole file
+const BATCH_SIZE: usize = 1000;
+const MAX_REPORTED_ERRORS: usize = 1000;
+
+/// Emitted with an [`ImportProgress`] every `progressEvery` rows and once at the end.
+pub const PROGRESS_EVENT: &str = "import:progress";
+
+/// Line-item fields a source column can map to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ItemField {
+    Name,
+    Description,
+    Quantity,
+    Unit,
+    UnitCost,
+}
+
+/// How one source column maps to an item field and what values it accepts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnRule {
+    pub field: ItemField,
+    /// Source column header, matched case-insensitively.
+    pub header: String,
+    /// Rejects rows with an empty value; the item name is always required.
+    #[serde(default)]
+    pub required: bool,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub max_length: Option<usize>,
+}
+
+impl ColumnRule {
+    fn apply(&self, value: &str, item: &mut PendingItem) -> Result<(), String> {
+        if value.is_empty() {
+            return if self.required || self.field == ItemField::Name {
+                Err("a value is required".into())
+            } else {
+                Ok(())
+            };
+        }
+        if let Some(max) = self.max_length {
+            if value.chars().count() > max {
+                return Err(format!("longer than {max} characters"));
+            }
+        }
+        match self.field {
+            ItemField::Name => item.name = value.to_string(),
+            ItemField::Description => item.description = Some(value.to_string()),
+            ItemField::Unit => item.unit = Some(value.to_string()),
+            ItemField::Quantity => item.quantity = self.number(value)?,
+            ItemField::UnitCost => item.unit_cost = self.number(value)?,
+        }
+        Ok(())
+    }
+
+    // Cost sheets commonly carry "$1,250.00"; thousands separators and a dollar sign are dropped
+    fn number(&self, value: &str) -> Result<f64, String> {
+        let cleaned: String = value
+            .trim_start_matches('$')
+            .chars()
+            .filter(|c| *c != ',')
+            .collect();
+        let number = cleaned
+            .parse::<f64>()
+            .ok()
+            .filter(|n| n.is_finite())
+            .ok_or_else(|| format!("{value} is not a number"))?;
+        if let Some(min) = self.min.filter(|min| number < *min) {
+            return Err(format!("{number} is below the minimum of {min}"));
+        }
+        if let Some(max) = self.max.filter(|max| number > *max) {
+            return Err(format!("{number} is above the maximum of {max}"));
+        }
+        Ok(number)
+    }
+}
+
+/// Column mapping and validation rules supplied with an import.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSchema {
+    pub columns: Vec<ColumnRule>,
+    /// Rows between progress events.
+    #[serde(default = "default_progress_every")]
+    pub progress_every: u64,
+    /// Units quantities are converted into, at most one per dimension, such as
+    /// `["ft", "ft²", "yd³"]`. Unit costs convert with them; items in other or unrecognized
+    /// units keep what the file says.
+    #[serde(default)]
+    pub convert_to: Vec<String>,
+}
+
+fn default_progress_every() -> u64 {
+    1000
+}
+
+/// A rejected row or value.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RowError {
+    /// Line or row number in the source file, counting the header.
+    pub row: u64,
+    pub column: Option<String>,
+    pub message: String,
+}
+
+/// Payload for `import:progress`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportProgress {
+    pub import_id: String,
+    pub rows_read: u64,
+    pub rows_imported: u64,
+    pub error_count: u64,
+}
+
+/// Outcome of an import.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+    pub import_id: String,
+    pub rows_read: u64,
+    pub rows_imported: u64,
+    /// Total rejected values; `errors` holds at most the first thousand.
+    pub error_count: u64,
+    pub errors: Vec<RowError>,
+}
+
+/// An item as a valid row would create it.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingItem {
+    pub name: String,
+    pub description: Option<String>,
+    pub quantity: f64,
+    pub unit: Option<String>,
+    pub unit_cost: f64,
+}
+
+/// An [`ImportSchema`]'s rules resolved against a source's headers.
+pub(crate) struct RowMapper {
+    // Source column index for each rule
+    columns: Vec<(usize, ColumnRule)>,
+    targets: Vec<&'static Unit>,
+}
+
+impl RowMapper {
+    /// Fails if the schema maps no name or a required column is absent.
+    pub(crate) fn new(rules: Vec<ColumnRule>, headers: &[String]) -> AppResult<Self> {
+        if !rules.iter().any(|c| c.field == ItemField::Name) {
+            return Err(AppError::InvalidInput(
+                "the schema must map a column to the item name".into(),
+            ));
+        }
+        let mut columns = Vec::new();
+        let mut missing = Vec::new();
+        for rule in rules {
+            let header = rule.header.trim();
+            match headers
+                .iter()
+                .position(|h| h.trim().eq_ignore_ascii_case(header))
+            {
+                Some(index) => columns.push((index, rule)),
+                None if rule.required || rule.field == ItemField::Name => missing.push(rule.header),
+                None => {}
+            }
+        }
+        if !missing.is_empty() {
+            return Err(AppError::InvalidInput(format!(
+                "missing required columns: {}",
+                missing.join(", ")
+            )));
+        }
+        Ok(Self {
+            columns,
+            targets: Vec::new(),
+        })
+    }
+
+    /// Convert quantities into `units`; fails on an unknown unit or two of one dimension.
+    pub(crate) fn converting(mut self, units: &[String]) -> AppResult<Self> {
+        for name in units {
+            let unit = units::find(name)
+                .ok_or_else(|| AppError::InvalidInput(format!("{name} is not a known unit")))?;
+            if let Some(other) = self.targets.iter().find(|t| t.dimension == unit.dimension) {
+                return Err(AppError::InvalidInput(format!(
+                    "{} and {} measure the same thing; convert to one of them",
+                    other.symbol, unit.symbol
+                )));
+            }
+            self.targets.push(unit);
+        }
+        Ok(self)
+    }
+
+    fn convert(&self, item: &mut PendingItem) {
+        let Some(from) = item.unit.as_deref().and_then(units::find) else {
+            return;
+        };
+        let Some(to) = self.targets.iter().find(|t| t.dimension == from.dimension) else {
+            return;
+        };
+        if let Ok(factor) = units::convert(1.0, from, to) {
+            item.quantity *= factor;
+            item.unit_cost /= factor;
+            item.unit = Some(to.symbol.into());
+        }
+    }
+
+    /// The item one row makes, or every value it rejects; `value` returns the cell at a
+    /// source column index.
+    pub(crate) fn map<'v>(
+        &self,
+        row: u64,
+        value: impl Fn(usize) -> Option<&'v str>,
+    ) -> Result<PendingItem, Vec<RowError>> {
+        let mut item = PendingItem::default();
+        let mut errors = Vec::new();
+        for (index, rule) in &self.columns {
+            let cell = value(*index).map(str::trim).unwrap_or_default();
+            if let Err(message) = rule.apply(cell, &mut item) {
+                errors.push(RowError {
+                    row,
+                    column: Some(rule.header.clone()),
+                    message,
+                });
+            }
+        }
+        if errors.is_empty() {
+            self.convert(&mut item);
+            Ok(item)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Validates rows and writes them to a project in batches.
+pub(crate) struct Importer<'a> {
+    conn: &'a mut Connection,
+    app: AppHandle,
+    task: Task,
+    import_id: String,
+    project_id: String,
+    mapper: RowMapper,
+    progress_every: u64,
+    pending: Vec<PendingItem>,
+    next_sort_order: i64,
+    rows_read: u64,
+    rows_imported: u64,
+    error_count: u64,
+    errors: Vec<RowError>,
+    // Row counts are all a reader knows, so the taskbar shows the import as indeterminate
+    _taskbar: ProgressGuard,
+}
+
+impl<'a> Importer<'a> {
+    /// Resolve `schema` against the source `headers`; fails if a required column is absent.
+    ///
+    /// Batches commit as savepoints, so an import run inside a transaction the caller
+    /// opened stays all or nothing. `task`'s id is the import id.
+    pub(crate) fn new(
+        conn: &'a mut Connection,
+        app: AppHandle,
+        task: Task,
+        project_id: String,
+        schema: ImportSchema,
+        headers: &[String],
+    ) -> AppResult<Self> {
+        let mapper = RowMapper::new(schema.columns, headers)?.converting(&schema.convert_to)?;
+        let next_sort_order = conn
+            .query_row(
+                "SELECT (SELECT COALESCE(MAX(sort_order) + 1, 0) FROM items WHERE project_id = ?1)
+                 FROM projects WHERE id = ?1",
+                [&project_id],
+                |row| row.get(0),
+            )
+            .map_err(|err| match err {
+                rusqlite::Error::QueryReturnedNoRows => {
+                    AppError::not_found("project", project_id.clone())
+                }
+                err => err.into(),
+            })?;
+        // Taken first, so the import can be reviewed against the estimate it was added to
+        versions::capture(conn, &project_id, Reason::Import, None, None)?;
+
+        let import_id = task.id().to_string();
+        Ok(Self {
+            _taskbar: progress::track(&app, format!("import:{import_id}")),
+            conn,
+            app,
+            task,
+            import_id,
+            project_id,
+            mapper,
+            progress_every: schema.progress_every.max(1),
+            pending: Vec::with_capacity(BATCH_SIZE),
+            next_sort_order,
+            rows_read: 0,
+            rows_imported: 0,
+            error_count: 0,
+            errors: Vec::new(),
+        })
+    }
+
+    /// Validate one row; `value` returns the cell at a source column index.
+    pub(crate) fn push_row<'v>(
+        &mut self,
+        row: u64,
+        value: impl Fn(usize) -> Option<&'v str>,
+    ) -> AppResult<()> {
+        match self.mapper.map(row, value) {
+            Ok(item) => self.pending.push(item),
+            Err(errors) => {
+                self.error_count += errors.len() as u64;
+                let room = MAX_REPORTED_ERRORS.saturating_sub(self.errors.len());
+                self.errors.extend(errors.into_iter().take(room));
+            }
+        }
+        self.advance()
+    }
+
+    /// Record a row the reader could not parse at all.
+    pub(crate) fn reject_row(&mut self, row: u64, message: String) -> AppResult<()> {
+        self.error_count += 1;
+        if self.errors.len() < MAX_REPORTED_ERRORS {
+            self.errors.push(RowError {
+                row,
+                column: None,
+                message,
+            });
+        }
+        self.advance()
+    }
+
+    // A cancel keeps the batches already written, as a failure partway through would
+    fn advance(&mut self) -> AppResult<()> {
+        self.task.check()?;
+        self.rows_read += 1;
+        if self.pending.len() >= BATCH_SIZE {
+            self.flush()?;
+        }
+        if self.rows_read.is_multiple_of(self.progress_every) {
+            self.emit_progress();
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> AppResult<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let now = now_ms();
+        let tx = self.conn.savepoint()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT INTO items (id, project_id, name, description, quantity, unit, unit_cost,
+                                    sort_order, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9)",
+            )?;
+            for item in &self.pending {
+                insert.execute(params![
+                    uuid::Uuid::new_v4().to_string(),
+                    self.project_id,
+                    item.name,
+                    item.description,
+                    item.quantity,
+                    item.unit,
+                    item.unit_cost,
+                    self.next_sort_order,
+                    now
+                ])?;
+                self.next_sort_order += 1;
+            }
+            tx.execute(
+                "UPDATE projects SET updated_at = ?2 WHERE id = ?1",
+                params![self.project_id, now],
+            )?;
+        }
+        tx.commit()?;
+        self.rows_imported += self.pending.len() as u64;
+        self.pending.clear();
+        Ok(())
+    }
+
+    fn emit_progress(&self) {
+        self.task.progress(self.rows_read, None);
+        let _ = self.app.emit(
+            PROGRESS_EVENT,
+            ImportProgress {
+                import_id: self.import_id.clone(),
+                rows_read: self.rows_read,
+                rows_imported: self.rows_imported,
+                error_count: self.error_count,
+            },
+        );
+    }
+
+    /// Rejected values so far.
+    pub(crate) fn error_count(&self) -> u64 {
+        self.error_count
+    }
+
+    /// Stop without writing what is left, reporting nothing as imported; for a caller
+    /// about to roll back its transaction.
+    pub(crate) fn abandon(mut self) -> ImportSummary {
+        self.rows_imported = 0;
+        self.emit_progress();
+        ImportSummary {
+            import_id: self.import_id,
+            rows_read: self.rows_read,
+            rows_imported: 0,
+            error_count: self.error_count,
+            errors: self.errors,
+        }
+    }
+
+    /// Write what is left and record the import, as `command`, in the audit log.
+    pub(crate) fn finish(mut self, command: &'static str) -> AppResult<ImportSummary> {
+        self.flush()?;
+        self.emit_progress();
+        // Batches commit as they go, so the import is recorded once as a whole
+        let actor = audit::actor(&self.app.state::<AppState>());
+        audit::record_now(
+            self.conn,
+            &actor,
+            Change::new(command, "project", &self.project_id).after(&serde_json::json!({
+                "importId": self.import_id,
+                "rowsRead": self.rows_read,
+                "rowsImported": self.rows_imported,
+                "errorCount": self.error_count,
+            }))?,
+        )?;
+        tracing::info!(
+            import_id = %self.import_id,
+            rows_read = self.rows_read,
+            rows_imported = self.rows_imported,
+            error_count = self.error_count,
+            "import finished"
+        );
+        Ok(ImportSummary {
+            import_id: self.import_id,
+            rows_read: self.rows_read,
+            rows_imported: self.rows_imported,
+            error_count: self.error_count,
+            errors: self.errors,
+        })
+    }
+}
@@ -0,0 +1,296 @@
+//! Spreadsheet line-item import for .xlsx, .xls, and .ods workbooks.
+//!
+//! Formula cells are worked out again from the workbook's own data, so they import current
+//! results even from files saved without recalculating; a formula that cannot be evaluated
+//! keeps the result Excel last saved. Merged cells take the top-left value across the whole
+//! region, matching what users see.
+
+use std::path::{Path, PathBuf};
+
+use calamine::{open_workbook_auto, Data, Dimensions, Reader, Sheets};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use super::formula::{self, Book};
+use super::{ImportSchema, ImportSummary, Importer};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+use crate::tasks::TaskKind;
+
+const DEFAULT_PREVIEW_ROWS: usize = 20;
+const MAX_FORMULA_ISSUES: usize = 50;
+
+/// Sheet names and the first rows of one sheet, for building a column mapping.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkbookPreview {
+    pub sheets: Vec<String>,
+    pub sheet: String,
+    /// Cells as they would be imported, including the header row.
+    pub rows: Vec<Vec<String>>,
+    pub row_count: usize,
+    /// Excel row number of `rows[0]`.
+    pub first_row: u32,
+    pub formula_cells: usize,
+    /// Formulas that kept their saved result, as `C12: reason`.
+    pub formula_issues: Vec<String>,
+}
+
+/// The sheet to import and where its headers are.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SheetSelection {
+    pub name: String,
+    /// Excel row number holding the column headers; by default the first row with content.
+    pub header_row: Option<u32>,
+}
+
+/// A sheet materialized as text with merged regions filled in.
+pub(crate) struct Grid {
+    pub rows: Vec<Vec<String>>,
+    /// Zero-based sheet row of `rows[0]`.
+    pub offset: u32,
+    pub formula_cells: usize,
+    pub formula_issues: Vec<String>,
+}
+
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::Empty => String::new(),
+        Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => s.clone(),
+        Data::Int(n) => n.to_string(),
+        // Whole numbers stored as floats would otherwise import as "12.0"
+        Data::Float(n) if n.fract() == 0.0 && n.abs() < 1e15 => format!("{n:.0}"),
+        Data::Float(n) => n.to_string(),
+        Data::Bool(b) => if *b { "TRUE" } else { "FALSE" }.into(),
+        Data::DateTime(dt) => match dt.as_datetime() {
+            Some(value) if dt.is_datetime() => {
+                if value.time() == chrono::NaiveTime::MIN {
+                    value.format("%Y-%m-%d").to_string()
+                } else {
+                    value.format("%Y-%m-%dT%H:%M:%S").to_string()
+                }
+            }
+            _ => dt.as_f64().to_string(),
+        },
+        Data::Error(err) => err.to_string(),
+    }
+}
+
+fn merged_regions(
+    workbook: &mut Sheets<std::io::BufReader<std::fs::File>>,
+    sheet: &str,
+) -> AppResult<Vec<Dimensions>> {
+    Ok(match workbook {
+        Sheets::Xlsx(xlsx) => xlsx
+            .worksheet_merge_cells(sheet)
+            .transpose()
+            .map_err(calamine::Error::Xlsx)?
+            .unwrap_or_default(),
+        Sheets::Xls(xls) => xls.worksheet_merge_cells(sheet).unwrap_or_default(),
+        // Other formats do not expose merge information
+        _ => Vec::new(),
+    })
+}
+
+/// Sheet names, the sheet read, and its cells; `sheet` defaults to the first.
+pub(crate) fn read_sheet(
+    path: &Path,
+    sheet: Option<&str>,
+) -> AppResult<(Vec<String>, String, Grid)> {
+    let mut workbook = open_workbook_auto(path)?;
+    let sheets = workbook.sheet_names();
+    let sheet = match sheet {
+        Some(name) if sheets.iter().any(|s| s == name) => name.to_string(),
+        Some(name) => return Err(AppError::not_found("sheet", name)),
+        None => sheets
+            .first()
+            .cloned()
+            .ok_or_else(|| AppError::InvalidInput("the workbook has no sheets".into()))?,
+    };
+    let mut range = workbook.worksheet_range(&sheet)?;
+    let merged = merged_regions(&mut workbook, &sheet)?;
+    // Formats without formula access read as having none, keeping their saved values
+    let formulas = workbook.worksheet_formula(&sheet).unwrap_or_default();
+    let formula_cells = formulas.cells().filter(|(_, _, f)| !f.is_empty()).count();
+    let mut formula_issues = Vec::new();
+    if formula_cells > 0 {
+        let names = workbook.defined_names().to_vec();
+        let mut own = Some((std::mem::take(&mut range), formulas));
+        let target = sheet.to_lowercase();
+        let mut book = Book::new(
+            &names,
+            Box::new(|name: &str| {
+                if name == target {
+                    if let Some(own) = own.take() {
+                        return Some(own);
+                    }
+                }
+                let found = sheets.iter().find(|s| s.to_lowercase() == name)?;
+                Some((
+                    workbook.worksheet_range(found).ok()?,
+                    workbook.worksheet_formula(found).unwrap_or_default(),
+                ))
+            }),
+        );
+        let results = book.evaluate_sheet(&sheet);
+        formula_issues = book
+            .issues
+            .iter()
+            .map(|(on, pos, reason)| {
+                let cell = formula::cell_name(*pos);
+                if *on == target {
+                    format!("{cell}: {reason}")
+                } else {
+                    format!("{on}!{cell}: {reason}")
+                }
+            })
+            .collect();
+        formula_issues.dedup();
+        formula_issues.truncate(MAX_FORMULA_ISSUES);
+        range = book.into_values(&sheet).unwrap_or_default();
+        for (pos, value) in results {
+            range.set_value(pos, value);
+        }
+        tracing::debug!(
+            sheet = %sheet,
+            formula_cells,
+            kept = formula_issues.len(),
+            "workbook formulas evaluated"
+        );
+    }
+
+    let (offset_row, offset_col) = range.start().unwrap_or_default();
+    let mut rows: Vec<Vec<String>> = range
+        .rows()
+        .map(|row| row.iter().map(cell_text).collect())
+        .collect();
+    for region in merged {
+        let value = range
+            .get_value(region.start)
+            .map(cell_text)
+            .unwrap_or_default();
+        for row in region.start.0..=region.end.0 {
+            for col in region.start.1..=region.end.1 {
+                let (Some(r), Some(c)) = (row.checked_sub(offset_row), col.checked_sub(offset_col))
+                else {
+                    continue;
+                };
+                if let Some(cell) = rows
+                    .get_mut(r as usize)
+                    .and_then(|cells| cells.get_mut(c as usize))
+                {
+                    cell.clone_from(&value);
+                }
+            }
+        }
+    }
+    // Column offsets shift every index, so pad them back in to keep columns where Excel has them
+    if offset_col > 0 {
+        for cells in &mut rows {
+            cells.splice(
+                0..0,
+                std::iter::repeat_n(String::new(), offset_col as usize),
+            );
+        }
+    }
+    Ok((
+        sheets,
+        sheet,
+        Grid {
+            rows,
+            offset: offset_row,
+            formula_cells,
+            formula_issues,
+        },
+    ))
+}
+
+/// List a workbook's sheets and preview the first rows of `sheet`, or the first sheet.
+#[tauri::command]
+pub async fn preview_xlsx(
+    path: PathBuf,
+    sheet: Option<String>,
+    rows: Option<usize>,
+) -> AppResult<WorkbookPreview> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let (sheets, sheet, grid) = read_sheet(&path, sheet.as_deref())?;
+        let row_count = grid.rows.len();
+        Ok(WorkbookPreview {
+            sheets,
+            sheet,
+            rows: grid
+                .rows
+                .into_iter()
+                .take(rows.unwrap_or(DEFAULT_PREVIEW_ROWS))
+                .collect(),
+            row_count,
+            first_row: grid.offset + 1,
+            formula_cells: grid.formula_cells,
+            formula_issues: grid.formula_issues,
+        })
+    })
+    .await?
+}
+
+/// Import the rows of one sheet as items in a project.
+///
+/// `import_id` tags progress events so the caller can tell concurrent imports apart, and
+/// is the task id to cancel it by.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn import_xlsx(
+    app: AppHandle,
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    project_id: String,
+    path: PathBuf,
+    sheet: SheetSelection,
+    schema: ImportSchema,
+    import_id: Option<String>,
+) -> AppResult<ImportSummary> {
+    let handle = app.clone();
+    state
+        .tasks()
+        .run(&handle, TaskKind::Import, import_id, |task| {
+            db.run(move |conn| {
+                let (_, _, grid) = read_sheet(&path, Some(&sheet.name))?;
+                let header_index = match sheet.header_row {
+                    Some(row) => row
+                        .checked_sub(grid.offset + 1)
+                        .map(|i| i as usize)
+                        .filter(|i| *i < grid.rows.len())
+                        .ok_or_else(|| {
+                            AppError::InvalidInput(format!("row {row} is outside the sheet"))
+                        })?,
+                    None => grid
+                        .rows
+                        .iter()
+                        .position(|cells| cells.iter().any(|c| !c.trim().is_empty()))
+                        .ok_or_else(|| {
+                            AppError::InvalidInput(format!("sheet {} is empty", sheet.name))
+                        })?,
+                };
+
+                let mut importer = Importer::new(
+                    conn,
+                    app,
+                    task,
+                    project_id,
+                    schema,
+                    &grid.rows[header_index],
+                )?;
+                for (index, cells) in grid.rows.iter().enumerate().skip(header_index + 1) {
+                    // Blank spacer rows are common in hand-made sheets and are not errors
+                    if cells.iter().all(|c| c.trim().is_empty()) {
+                        continue;
+                    }
+                    let row = grid.offset as u64 + index as u64 + 1;
+                    importer.push_row(row, |col| cells.get(col).map(String::as_str))?;
+                }
+                importer.finish("import_xlsx")
+            })
+        })
+        .await
+}
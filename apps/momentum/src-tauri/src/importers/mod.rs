@@ -0,0 +1,287 @@
+//! Estimates made in other estimating tools, brought in as line items.
+//!
+//! Each external layout is an [`EstimateFormat`]: it says how sure it is that a file is
+//! in its layout, reads the line-item rows out from around title blocks, section headings,
+//! and totals, and suggests an [`ImportSchema`] for its columns. The frontend detects,
+//! previews with the suggested or an edited mapping, then imports. An import runs in one
+//! transaction, so a file with rejected rows lands whole or not at all unless partial
+//! imports are allowed.
+
+pub mod sectioned;
+
+use std::path::{Path, PathBuf};
+
+use rusqlite::Connection;
+use serde::Serialize;
+use tauri::{AppHandle, State};
+
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::import::{
+    ColumnRule, ImportSchema, ImportSummary, Importer, PendingItem, RowError, RowMapper,
+};
+use crate::state::AppState;
+use crate::tasks::{Task, TaskKind};
+
+const DEFAULT_PREVIEW_ROWS: usize = 50;
+
+/// Every layout an estimate can be imported from.
+const FORMATS: &[&dyn EstimateFormat] = &[&sectioned::SectionedEstimate];
+
+/// How sure a format is that a file is in its layout.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Detection {
+    /// From 0 to 1.
+    pub confidence: f32,
+    /// What gave the layout away, for showing alongside the guess.
+    pub reason: String,
+}
+
+/// Line-item rows read out of a file, with everything else taken out.
+pub struct SourceTable {
+    pub headers: Vec<String>,
+    /// Source row number and cells, aligned with `headers`.
+    pub rows: Vec<(u64, Vec<String>)>,
+    /// Rows that read fine but look wrong, such as a total that does not add up.
+    pub warnings: Vec<RowError>,
+}
+
+/// An external estimate layout.
+pub trait EstimateFormat: Sync {
+    /// Stable id the frontend passes back.
+    fn id(&self) -> &'static str;
+    fn name(&self) -> &'static str;
+    /// `None` when the file is plainly not in this layout. Blocking.
+    fn detect(&self, path: &Path) -> AppResult<Option<Detection>>;
+    /// Blocking.
+    fn read(&self, path: &Path) -> AppResult<SourceTable>;
+    /// Column rules for the headers this layout's [`read`](Self::read) returns.
+    fn suggest(&self, headers: &[String]) -> Vec<ColumnRule>;
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DetectedFormat {
+    pub id: &'static str,
+    pub name: &'static str,
+    #[serde(flatten)]
+    pub detection: Detection,
+}
+
+/// One source row as it would import.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewRow {
+    pub row: u64,
+    /// `None` when the row is rejected.
+    pub item: Option<PendingItem>,
+    pub errors: Vec<RowError>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EstimatePreview {
+    pub format_id: &'static str,
+    pub headers: Vec<String>,
+    /// The mapping previewed: the format's suggestion unless one was passed in.
+    pub schema: ImportSchema,
+    pub rows: Vec<PreviewRow>,
+    pub row_count: usize,
+    /// Rejected rows across the whole file, not only those previewed.
+    pub rejected_rows: usize,
+    pub warnings: Vec<RowError>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EstimateImportReport {
+    #[serde(flatten)]
+    pub summary: ImportSummary,
+    /// `false` when rejected rows rolled the whole import back.
+    pub committed: bool,
+    pub warnings: Vec<RowError>,
+}
+
+fn format(id: &str) -> AppResult<&'static dyn EstimateFormat> {
+    FORMATS
+        .iter()
+        .copied()
+        .find(|format| format.id() == id)
+        .ok_or_else(|| AppError::not_found("import format", id))
+}
+
+fn detect_all(path: &Path) -> AppResult<Vec<DetectedFormat>> {
+    let mut detected = Vec::new();
+    for format in FORMATS {
+        if let Some(detection) = format.detect(path)? {
+            detected.push(DetectedFormat {
+                id: format.id(),
+                name: format.name(),
+                detection,
+            });
+        }
+    }
+    detected.sort_by(|a, b| b.detection.confidence.total_cmp(&a.detection.confidence));
+    Ok(detected)
+}
+
+// The named format, or the likeliest one
+fn resolve(path: &Path, id: Option<&str>) -> AppResult<&'static dyn EstimateFormat> {
+    match id {
+        Some(id) => format(id),
+        None => detect_all(path)?
+            .first()
+            .map(|detected| format(detected.id))
+            .transpose()?
+            .ok_or_else(|| {
+                AppError::InvalidInput(format!(
+                    "{} is not in a known estimate layout",
+                    path.display()
+                ))
+            }),
+    }
+}
+
+/// Layouts `path` could be in, likeliest first.
+#[tauri::command]
+pub async fn detect_estimate_format(path: PathBuf) -> AppResult<Vec<DetectedFormat>> {
+    tauri::async_runtime::spawn_blocking(move || detect_all(&path)).await?
+}
+
+/// Show how the first `rows` of an estimate would import with `schema`, or the suggested
+/// mapping when none is given.
+#[tauri::command]
+pub async fn preview_estimate_import(
+    path: PathBuf,
+    format_id: Option<String>,
+    schema: Option<ImportSchema>,
+    rows: Option<usize>,
+) -> AppResult<EstimatePreview> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let format = resolve(&path, format_id.as_deref())?;
+        let table = format.read(&path)?;
+        let schema = schema.unwrap_or_else(|| ImportSchema {
+            columns: format.suggest(&table.headers),
+            progress_every: 1000,
+            convert_to: Vec::new(),
+        });
+        let mapper = RowMapper::new(schema.columns.clone(), &table.headers)?
+            .converting(&schema.convert_to)?;
+        let mut previewed = Vec::new();
+        let mut rejected_rows = 0;
+        for (row, cells) in &table.rows {
+            let mapped = mapper.map(*row, |column| cells.get(column).map(String::as_str));
+            rejected_rows += usize::from(mapped.is_err());
+            if previewed.len() < rows.unwrap_or(DEFAULT_PREVIEW_ROWS) {
+                let (item, errors) = match mapped {
+                    Ok(item) => (Some(item), Vec::new()),
+                    Err(errors) => (None, errors),
+                };
+                previewed.push(PreviewRow {
+                    row: *row,
+                    item,
+                    errors,
+                });
+            }
+        }
+        Ok(EstimatePreview {
+            format_id: format.id(),
+            row_count: table.rows.len(),
+            headers: table.headers,
+            schema,
+            rows: previewed,
+            rejected_rows,
+            warnings: table.warnings,
+        })
+    })
+    .await?
+}
+
+// Returns the summary and whether it should be committed
+fn import_rows(
+    conn: &mut Connection,
+    app: AppHandle,
+    (task, project_id): (Task, String),
+    schema: ImportSchema,
+    table: &SourceTable,
+    allow_partial: bool,
+) -> AppResult<(ImportSummary, bool)> {
+    let mut importer = Importer::new(conn, app, task, project_id, schema, &table.headers)?;
+    for (row, cells) in &table.rows {
+        importer.push_row(*row, |column| cells.get(column).map(String::as_str))?;
+    }
+    if importer.error_count() > 0 && !allow_partial {
+        return Ok((importer.abandon(), false));
+    }
+    Ok((importer.finish("import_estimate")?, true))
+}
+
+/// Import an estimate's line items into a project in one transaction.
+///
+/// Any rejected row rolls back the whole file unless `allow_partial` is set; the report
+/// lists every rejected value either way. `import_id` tags `import:progress` events and is
+/// the task id to cancel it by; a cancelled import is rolled back.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn import_estimate(
+    app: AppHandle,
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    project_id: String,
+    path: PathBuf,
+    format_id: String,
+    schema: ImportSchema,
+    allow_partial: Option<bool>,
+    import_id: Option<String>,
+) -> AppResult<EstimateImportReport> {
+    let format = format(&format_id)?;
+    let handle = app.clone();
+    state
+        .tasks()
+        .run(&handle, TaskKind::Import, import_id, |task| async move {
+            let table = tauri::async_runtime::spawn_blocking(move || format.read(&path)).await??;
+            task.check()?;
+
+            db.run(move |conn| {
+                conn.execute_batch("BEGIN IMMEDIATE;")?;
+                let result = import_rows(
+                    conn,
+                    app,
+                    (task, project_id),
+                    schema,
+                    &table,
+                    allow_partial.unwrap_or(false),
+                );
+                match result {
+                    Ok((summary, true)) => {
+                        conn.execute_batch("COMMIT;")?;
+                        Ok(EstimateImportReport {
+                            summary,
+                            committed: true,
+                            warnings: table.warnings,
+                        })
+                    }
+                    Ok((summary, false)) => {
+                        conn.execute_batch("ROLLBACK;")?;
+                        tracing::info!(
+                            import_id = %summary.import_id,
+                            error_count = summary.error_count,
+                            "estimate import rolled back over rejected rows"
+                        );
+                        Ok(EstimateImportReport {
+                            summary,
+                            committed: false,
+                            warnings: table.warnings,
+                        })
+                    }
+                    Err(err) => {
+                        let _ = conn.execute_batch("ROLLBACK;");
+                        Err(err)
+                    }
+                }
+            })
+            .await
+        })
+        .await
+}
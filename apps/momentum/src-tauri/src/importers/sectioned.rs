@@ -0,0 +1,319 @@
+//! The sectioned estimate workbook most estimating packages export to Excel.
+//!
+//! A title block with the project and estimate number sits above a header row along the
+//! lines of `Item | Description | Qty | UOM | Unit Price | Amount`. Below it, rows with a
+//! code and title but no quantity or price open a section (`03 30 00  Cast-in-Place
+//! Concrete`), line items follow, and `Subtotal`, `Total`, or `Grand Total` rows close them.
+//! The reader drops titles and totals and adds a `Section` column carrying the heading
+//! each item sits under.
+
+use std::path::Path;
+
+use calamine::{open_workbook_auto, Reader};
+
+use super::{Detection, EstimateFormat, SourceTable};
+use crate::error::{AppError, AppResult};
+use crate::import::xlsx::{read_sheet, Grid};
+use crate::import::{ColumnRule, ItemField, RowError};
+
+/// Header of the column the reader adds.
+pub const SECTION_HEADER: &str = "Section";
+
+// How far down a sheet the header row is looked for, past the title block
+const HEADER_SCAN_ROWS: usize = 40;
+// Extended totals are rounded to the cent in these exports
+const TOTAL_TOLERANCE: f64 = 0.01;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Column {
+    Code,
+    Description,
+    Quantity,
+    Unit,
+    UnitCost,
+    Total,
+}
+
+impl Column {
+    const ALL: [Self; 6] = [
+        Self::Code,
+        Self::Description,
+        Self::Quantity,
+        Self::Unit,
+        Self::UnitCost,
+        Self::Total,
+    ];
+
+    fn aliases(self) -> &'static [&'static str] {
+        match self {
+            Self::Code => &[
+                "item",
+                "item no",
+                "item #",
+                "code",
+                "cost code",
+                "line",
+                "ref",
+            ],
+            Self::Description => &["description", "item description", "work item", "scope"],
+            Self::Quantity => &["qty", "quantity", "quant"],
+            Self::Unit => &["uom", "unit", "units", "u/m", "um"],
+            Self::UnitCost => &["unit price", "unit cost", "rate", "unit rate", "price"],
+            Self::Total => &[
+                "amount",
+                "total",
+                "extended",
+                "ext price",
+                "extended cost",
+                "extended price",
+                "line total",
+            ],
+        }
+    }
+}
+
+// `Unit Price ($):` and `unit price` are the same header
+fn normalize(cell: &str) -> String {
+    cell.split('(')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .trim_end_matches([':', '.'])
+        .replace('.', "")
+        .to_ascii_lowercase()
+}
+
+// Where each known column sits in a candidate header row
+#[derive(Default)]
+struct Layout {
+    columns: Vec<(Column, usize)>,
+}
+
+impl Layout {
+    fn from_row(cells: &[String]) -> Self {
+        let mut columns: Vec<(Column, usize)> = Vec::new();
+        for (index, cell) in cells.iter().enumerate() {
+            let cell = normalize(cell);
+            if let Some(column) = Column::ALL
+                .into_iter()
+                .find(|column| column.aliases().contains(&cell.as_str()))
+            {
+                if !columns.iter().any(|(known, _)| *known == column) {
+                    columns.push((column, index));
+                }
+            }
+        }
+        Self { columns }
+    }
+
+    fn get(&self, column: Column) -> Option<usize> {
+        self.columns
+            .iter()
+            .find(|(known, _)| *known == column)
+            .map(|(_, index)| *index)
+    }
+
+    // A description, a quantity, and some price are what make it an estimate
+    fn is_estimate(&self) -> bool {
+        self.get(Column::Description).is_some()
+            && self.get(Column::Quantity).is_some()
+            && (self.get(Column::UnitCost).is_some() || self.get(Column::Total).is_some())
+    }
+}
+
+struct Found {
+    sheet: String,
+    grid: Grid,
+    header_index: usize,
+    layout: Layout,
+    titled: bool,
+}
+
+fn cell(cells: &[String], index: Option<usize>) -> &str {
+    index
+        .and_then(|index| cells.get(index))
+        .map(|cell| cell.trim())
+        .unwrap_or_default()
+}
+
+fn number(value: &str) -> Option<f64> {
+    let cleaned: String = value
+        .trim_start_matches('$')
+        .chars()
+        .filter(|c| *c != ',')
+        .collect();
+    cleaned.parse().ok().filter(|n: &f64| n.is_finite())
+}
+
+fn find(path: &Path) -> AppResult<Option<Found>> {
+    let sheets = open_workbook_auto(path)?.sheet_names();
+    let mut best: Option<Found> = None;
+    for name in sheets {
+        let (_, sheet, grid) = read_sheet(path, Some(&name))?;
+        let Some((header_index, layout)) = grid
+            .rows
+            .iter()
+            .take(HEADER_SCAN_ROWS)
+            .enumerate()
+            .map(|(index, cells)| (index, Layout::from_row(cells)))
+            .filter(|(_, layout)| layout.is_estimate())
+            .max_by_key(|(_, layout)| layout.columns.len())
+        else {
+            continue;
+        };
+        let titled = grid.rows[..header_index].iter().flatten().any(|cell| {
+            let cell = cell.to_ascii_lowercase();
+            cell.contains("estimate") || cell.contains("bid") || cell.contains("proposal")
+        });
+        let better = best.as_ref().is_none_or(|best| {
+            (layout.columns.len(), titled) > (best.layout.columns.len(), best.titled)
+        });
+        if better {
+            best = Some(Found {
+                sheet,
+                grid,
+                header_index,
+                layout,
+                titled,
+            });
+        }
+    }
+    Ok(best)
+}
+
+fn is_total(text: &str) -> bool {
+    let text = text.to_ascii_lowercase();
+    ["subtotal", "sub-total", "sub total", "total", "grand total"]
+        .iter()
+        .any(|word| text.starts_with(word))
+}
+
+pub struct SectionedEstimate;
+
+impl EstimateFormat for SectionedEstimate {
+    fn id(&self) -> &'static str {
+        "sectioned-estimate"
+    }
+
+    fn name(&self) -> &'static str {
+        "Sectioned estimate workbook"
+    }
+
+    fn detect(&self, path: &Path) -> AppResult<Option<Detection>> {
+        // Anything the spreadsheet reader cannot open is simply some other format
+        let Ok(Some(found)) = find(path) else {
+            return Ok(None);
+        };
+        let matched = found.layout.columns.len() as f32 / Column::ALL.len() as f32;
+        let confidence = (0.3 + 0.6 * matched + if found.titled { 0.1 } else { 0.0 }).min(1.0);
+        Ok(Some(Detection {
+            confidence,
+            reason: format!(
+                "estimate columns on row {} of sheet {}",
+                found.grid.offset as usize + found.header_index + 1,
+                found.sheet
+            ),
+        }))
+    }
+
+    fn read(&self, path: &Path) -> AppResult<SourceTable> {
+        let found = find(path)?.ok_or_else(|| {
+            AppError::InvalidInput(format!(
+                "{} has no sheet with estimate columns",
+                path.display()
+            ))
+        })?;
+        let Found {
+            grid,
+            header_index,
+            layout,
+            ..
+        } = found;
+        let mut headers = grid.rows[header_index].clone();
+        headers.push(SECTION_HEADER.into());
+        let (code, description) = (layout.get(Column::Code), layout.get(Column::Description));
+        let (quantity, unit_cost, total) = (
+            layout.get(Column::Quantity),
+            layout.get(Column::UnitCost),
+            layout.get(Column::Total),
+        );
+
+        let mut rows = Vec::new();
+        let mut warnings = Vec::new();
+        let mut section = String::new();
+        for (index, cells) in grid.rows.iter().enumerate().skip(header_index + 1) {
+            let row = grid.offset as u64 + index as u64 + 1;
+            if cells.iter().all(|cell| cell.trim().is_empty()) {
+                continue;
+            }
+            let label = match cell(cells, description) {
+                "" => cell(cells, code),
+                text => text,
+            };
+            // A quantity means a line item, even one called "Total station survey"
+            if (is_total(cell(cells, code)) || is_total(label)) && cell(cells, quantity).is_empty()
+            {
+                continue;
+            }
+            let priced = [quantity, unit_cost, total]
+                .iter()
+                .any(|column| !cell(cells, *column).is_empty());
+            if !priced {
+                section = [cell(cells, code), cell(cells, description)]
+                    .into_iter()
+                    .filter(|part| !part.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                continue;
+            }
+            if let (Some(qty), Some(rate), Some(extended)) = (
+                number(cell(cells, quantity)),
+                number(cell(cells, unit_cost)),
+                number(cell(cells, total)),
+            ) {
+                if (qty * rate - extended).abs() > TOTAL_TOLERANCE.max(extended.abs() * 1e-6) {
+                    warnings.push(RowError {
+                        row,
+                        column: total.map(|index| headers[index].clone()),
+                        message: format!("{qty} × {rate} is not the {extended} shown"),
+                    });
+                }
+            }
+            let mut cells = cells.clone();
+            cells.resize(headers.len() - 1, String::new());
+            cells.push(section.clone());
+            rows.push((row, cells));
+        }
+        Ok(SourceTable {
+            headers,
+            rows,
+            warnings,
+        })
+    }
+
+    fn suggest(&self, headers: &[String]) -> Vec<ColumnRule> {
+        let layout = Layout::from_row(headers);
+        let rule = |field, index: Option<usize>| {
+            index.map(|index| ColumnRule {
+                field,
+                header: headers[index].clone(),
+                required: false,
+                min: matches!(field, ItemField::Quantity | ItemField::UnitCost).then_some(0.0),
+                max: None,
+                max_length: None,
+            })
+        };
+        // Imported items keep their section, as there is nowhere else to put it
+        let section = headers.iter().position(|header| header == SECTION_HEADER);
+        [
+            rule(ItemField::Name, layout.get(Column::Description)),
+            rule(ItemField::Description, section),
+            rule(ItemField::Quantity, layout.get(Column::Quantity)),
+            rule(ItemField::Unit, layout.get(Column::Unit)),
+            rule(ItemField::UnitCost, layout.get(Column::UnitCost)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
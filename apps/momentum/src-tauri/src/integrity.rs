@@ -0,0 +1,308 @@
+//! File hashing and integrity checks for the attachment store.
+//!
+//! `hash_file` tells the frontend whether a file is already attached somewhere before it is
+//! added again. The `attachments.integrity` job re-hashes every stored blob once a day and
+//! announces any that are missing or damaged with `attachment:integrity`; those can be
+//! fetched again from the Truss API by hash while signed in.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_http::reqwest::StatusCode;
+use tokio::sync::mpsc;
+
+use crate::attachments::{self, Attachment, AttachmentStore, BlobStatus};
+use crate::auth;
+use crate::config;
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::http_client::{HttpClient, RetryPolicy};
+use crate::jobs::Scheduler;
+use crate::power;
+
+/// Job kind that re-hashes every stored blob.
+pub const SCAN_JOB: &str = "attachments.integrity";
+const SCAN_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Emitted with an [`IntegrityReport`] after each scan.
+pub const INTEGRITY_EVENT: &str = "attachment:integrity";
+
+const BLOBS_PATH: &str = "/v1/blobs";
+// Chunks in flight between the download and the store writer
+const CHANNEL_DEPTH: usize = 16;
+
+/// A file's hash, and the attachments that already hold the same contents.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileHash {
+    pub path: PathBuf,
+    /// Hex SHA-256 of the contents.
+    pub sha256: String,
+    pub size: u64,
+    pub duplicates: Vec<Attachment>,
+}
+
+/// What verifying one attachment found.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentCheck {
+    pub attachment_id: String,
+    pub status: BlobStatus,
+    /// Whether the file can be fetched again from the server.
+    pub can_redownload: bool,
+}
+
+/// A stored blob that is missing or damaged, and every attachment it backs.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityIssue {
+    pub hash: String,
+    pub status: BlobStatus,
+    pub attachments: Vec<Attachment>,
+}
+
+/// Outcome of scanning the whole store.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityReport {
+    /// Blobs re-hashed.
+    pub checked: usize,
+    pub issues: Vec<IntegrityIssue>,
+    /// Whether the affected files can be fetched again from the server.
+    pub can_redownload: bool,
+    pub finished_at: i64,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+async fn signed_in() -> AppResult<bool> {
+    Ok(tauri::async_runtime::spawn_blocking(auth::session)
+        .await??
+        .is_some())
+}
+
+/// Re-hash every referenced blob; `None` while the store is encrypted and locked.
+async fn scan(app: &AppHandle) -> AppResult<Option<IntegrityReport>> {
+    let db = app.state::<Db>().inner().clone();
+    let hashes = db
+        .run(|conn| {
+            Ok(conn
+                .prepare("SELECT hash FROM attachment_blobs WHERE ref_count > 0 ORDER BY hash")?
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+        .await?;
+    let checked = hashes.len();
+    let store_app = app.clone();
+    let damaged = tauri::async_runtime::spawn_blocking(move || {
+        let store = store_app.state::<AttachmentStore>();
+        let mut damaged = Vec::new();
+        for hash in hashes {
+            match store.check(&hash) {
+                Ok(BlobStatus::Intact) => {}
+                Ok(status) => damaged.push((hash, status)),
+                Err(AppError::DatabaseLocked) => return Ok(None),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(Some(damaged))
+    })
+    .await??;
+    let Some(damaged) = damaged else {
+        return Ok(None);
+    };
+
+    let issues = db
+        .run(move |conn| {
+            damaged
+                .into_iter()
+                .map(|(hash, status)| {
+                    Ok(IntegrityIssue {
+                        attachments: attachments::attachments_with_hash(conn, &hash)?,
+                        hash,
+                        status,
+                    })
+                })
+                .collect::<AppResult<Vec<_>>>()
+        })
+        .await?;
+    for issue in &issues {
+        tracing::warn!(
+            hash = %issue.hash,
+            status = ?issue.status,
+            attachments = issue.attachments.len(),
+            "attachment file failed its integrity check"
+        );
+    }
+    let report = IntegrityReport {
+        checked,
+        issues,
+        can_redownload: signed_in().await?,
+        finished_at: now_ms(),
+    };
+    let _ = app.emit(INTEGRITY_EVENT, &report);
+    Ok(Some(report))
+}
+
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(SCAN_JOB, Some(SCAN_INTERVAL), |app| async move {
+        if power::defer_heavy_work(&app) {
+            return Ok(());
+        }
+        if scan(&app).await?.is_none() {
+            tracing::info!("attachment integrity scan skipped while locked");
+        }
+        Ok(())
+    });
+}
+
+// Hands chunks arriving on the async side to the blocking store writer
+struct ChunkReader {
+    chunks: mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.chunks.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.chunk.len() - self.pos);
+        buf[..len].copy_from_slice(&self.chunk[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+/// Hash the file at `path` and find attachments that already hold the same contents.
+#[tauri::command]
+pub async fn hash_file(db: State<'_, Db>, path: PathBuf) -> AppResult<FileHash> {
+    let lookup = path.clone();
+    let (sha256, size) = tauri::async_runtime::spawn_blocking(move || {
+        let mut hasher = Sha256::new();
+        let size = std::io::copy(&mut File::open(&lookup)?, &mut hasher)?;
+        Ok::<_, AppError>((hex(&hasher.finalize()), size))
+    })
+    .await??;
+    let hash = sha256.clone();
+    let duplicates = db
+        .run(move |conn| Ok(attachments::attachments_with_hash(conn, &hash)?))
+        .await?;
+    Ok(FileHash {
+        path,
+        sha256,
+        size,
+        duplicates,
+    })
+}
+
+/// Check that an attachment's stored file still matches the hash it was added with.
+#[tauri::command]
+pub async fn verify_attachment(
+    app: AppHandle,
+    db: State<'_, Db>,
+    attachment_id: String,
+) -> AppResult<AttachmentCheck> {
+    let attachment = attachments::get(&db, attachment_id).await?;
+    let hash = attachment.hash.clone();
+    let status =
+        tauri::async_runtime::spawn_blocking(move || app.state::<AttachmentStore>().check(&hash))
+            .await??;
+    if status != BlobStatus::Intact {
+        tracing::warn!(id = %attachment.id, ?status, "attachment failed its integrity check");
+    }
+    Ok(AttachmentCheck {
+        attachment_id: attachment.id,
+        status,
+        can_redownload: status != BlobStatus::Intact && signed_in().await?,
+    })
+}
+
+/// Scan the whole store now rather than waiting for the daily job.
+#[tauri::command]
+pub async fn scan_attachment_integrity(app: AppHandle) -> AppResult<IntegrityReport> {
+    scan(&app).await?.ok_or(AppError::DatabaseLocked)
+}
+
+/// Fetch a missing or damaged attachment file again from the server.
+///
+/// The server's copy is only stored if it hashes to what the attachment was added with.
+#[tauri::command]
+pub async fn redownload_attachment(
+    app: AppHandle,
+    db: State<'_, Db>,
+    http: State<'_, HttpClient>,
+    attachment_id: String,
+) -> AppResult<AttachmentCheck> {
+    let attachment = attachments::get(&db, attachment_id).await?;
+    if !signed_in().await? {
+        return Err(AppError::InvalidInput(
+            "sign in to fetch files from the server".into(),
+        ));
+    }
+    let hash = attachment.hash.clone();
+    let url = config::api_url(&app, &format!("{BLOBS_PATH}/{hash}"))?;
+    let mut response = http
+        .send_authorized(&app, http.client().get(url), RetryPolicy::default())
+        .await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Err(AppError::not_found("attachment file on the server", hash));
+    }
+    response = response.error_for_status()?;
+
+    let (tx, rx) = mpsc::channel(CHANNEL_DEPTH);
+    let store_app = app.clone();
+    let staging = tauri::async_runtime::spawn_blocking(move || {
+        let mut reader = ChunkReader {
+            chunks: rx,
+            chunk: Vec::new(),
+            pos: 0,
+        };
+        store_app.state::<AttachmentStore>().stage(&mut reader)
+    });
+    let received = async {
+        while let Some(chunk) = response.chunk().await? {
+            if tx.send(chunk.to_vec()).await.is_err() {
+                // The writer stopped early; its error is reported below
+                break;
+            }
+        }
+        Ok::<_, AppError>(())
+    }
+    .await;
+    drop(tx);
+    let staged = staging.await??;
+    let store = app.state::<AttachmentStore>();
+    if let Err(err) = received {
+        store.discard(staged);
+        return Err(err);
+    }
+    if staged.hash != hash {
+        store.discard(staged);
+        return Err(AppError::Internal(
+            "the server's copy does not match the attachment".into(),
+        ));
+    }
+    let replace_app = app.clone();
+    db.run(move |conn| replace_app.state::<AttachmentStore>().replace(conn, staged))
+        .await?;
+    tracing::info!(id = %attachment.id, %hash, "attachment file restored from the server");
+    Ok(AttachmentCheck {
+        attachment_id: attachment.id,
+        status: BlobStatus::Intact,
+        can_redownload: true,
+    })
+}
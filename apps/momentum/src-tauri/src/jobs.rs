@@ -0,0 +1,441 @@
+//! Background job scheduler: recurring and one-shot tasks with persisted run history.
+//!
+//! Handlers are registered in code by kind; the `jobs` table holds schedules and last-run
+//! results so intervals survive restarts and overdue jobs catch up on the next launch.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rusqlite::{params, OptionalExtension, Row};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Notify;
+
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+
+// Upper bound on one sleep so clock changes and sleep/wake are picked up
+const MAX_IDLE: Duration = Duration::from_secs(60);
+
+/// Emitted with a [`JobLifecycle`] when a job starts.
+pub const JOB_STARTED_EVENT: &str = "job:started";
+/// Emitted with a [`JobLifecycle`] when a job completes successfully.
+pub const JOB_FINISHED_EVENT: &str = "job:finished";
+/// Emitted with a [`JobLifecycle`] carrying `error` when a job fails.
+pub const JOB_FAILED_EVENT: &str = "job:failed";
+
+type JobFuture = Pin<Box<dyn Future<Output = AppResult<()>> + Send>>;
+type Handler = Arc<dyn Fn(AppHandle) -> JobFuture + Send + Sync>;
+
+/// A scheduled job as shown to the frontend.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    pub id: String,
+    pub kind: String,
+    /// `None` for one-shot jobs.
+    pub interval_ms: Option<i64>,
+    pub next_run_at: Option<i64>,
+    pub paused: bool,
+    pub running: bool,
+    pub last_run_at: Option<i64>,
+    pub last_duration_ms: Option<i64>,
+    pub last_status: Option<String>,
+    pub last_error: Option<String>,
+}
+
+impl Job {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            kind: row.get("kind")?,
+            interval_ms: row.get("interval_ms")?,
+            next_run_at: row.get("next_run_at")?,
+            paused: row.get("paused")?,
+            running: false,
+            last_run_at: row.get("last_run_at")?,
+            last_duration_ms: row.get("last_duration_ms")?,
+            last_status: row.get("last_status")?,
+            last_error: row.get("last_error")?,
+        })
+    }
+}
+
+/// Payload for the `job:*` lifecycle events.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobLifecycle {
+    pub id: String,
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Serialized [`AppError`], so the frontend can branch on `code`.
+    pub error: Option<serde_json::Value>,
+}
+
+struct Registration {
+    handler: Handler,
+    every: Option<Duration>,
+}
+
+/// Job registry and run bookkeeping, registered in app state.
+pub struct Scheduler {
+    db: Db,
+    registrations: Mutex<HashMap<&'static str, Registration>>,
+    running: Mutex<HashSet<String>>,
+    wake: Notify,
+    stopping: AtomicBool,
+}
+
+impl Scheduler {
+    pub fn new(db: Db) -> Self {
+        Self {
+            db,
+            registrations: Mutex::new(HashMap::new()),
+            running: Mutex::new(HashSet::new()),
+            wake: Notify::new(),
+            stopping: AtomicBool::new(false),
+        }
+    }
+
+    /// Register a handler for `kind`; with `every`, a recurring job with id `kind` is kept scheduled.
+    pub fn register<F, Fut>(&self, kind: &'static str, every: Option<Duration>, handler: F)
+    where
+        F: Fn(AppHandle) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = AppResult<()>> + Send + 'static,
+    {
+        let handler: Handler = Arc::new(move |app| Box::pin(handler(app)));
+        self.registrations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(kind, Registration { handler, every });
+    }
+
+    /// Queue a one-shot run of `kind` at `run_at` (Unix ms); returns the new job id.
+    pub async fn schedule_once(&self, kind: &str, run_at: i64) -> AppResult<String> {
+        if self.handler(kind).is_none() {
+            return Err(AppError::InvalidInput(format!("unknown job kind {kind}")));
+        }
+        let kind = kind.to_string();
+        let id = uuid::Uuid::new_v4().to_string();
+        let job_id = id.clone();
+        self.db
+            .run(move |conn| {
+                let now = now_ms();
+                conn.execute(
+                    "INSERT INTO jobs (id, kind, next_run_at, created_at, updated_at)
+                     VALUES (?1, ?2, ?3, ?4, ?4)",
+                    params![job_id, kind, run_at, now],
+                )?;
+                Ok(())
+            })
+            .await?;
+        self.wake.notify_one();
+        Ok(id)
+    }
+
+    /// Start no more runs and wait up to `timeout` for running ones to finish.
+    ///
+    /// Returns how many were still running, e.g. a long sync that is abandoned at exit; their
+    /// jobs simply run again on the next launch.
+    pub async fn stop(&self, timeout: Duration) -> usize {
+        self.stopping.store(true, Ordering::SeqCst);
+        self.wake.notify_one();
+        let started = Instant::now();
+        loop {
+            let running = self.running.lock().unwrap_or_else(|e| e.into_inner()).len();
+            if running == 0 || started.elapsed() >= timeout {
+                return running;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    fn handler(&self, kind: &str) -> Option<Handler> {
+        self.registrations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(kind)
+            .map(|r| r.handler.clone())
+    }
+
+    fn is_running(&self, id: &str) -> bool {
+        self.running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains(id)
+    }
+
+    // Insert missing recurring jobs and pick up interval changes from code
+    async fn sync_definitions(&self) -> AppResult<()> {
+        let definitions: Vec<(&'static str, i64)> = self
+            .registrations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter_map(|(kind, r)| Some((*kind, r.every?.as_millis() as i64)))
+            .collect();
+        self.db
+            .run(move |conn| {
+                let now = now_ms();
+                for (kind, interval) in definitions {
+                    conn.execute(
+                        "INSERT INTO jobs (id, kind, interval_ms, next_run_at, created_at, updated_at)
+                         VALUES (?1, ?1, ?2, ?3, ?4, ?4)
+                         ON CONFLICT(id) DO UPDATE SET interval_ms = excluded.interval_ms,
+                             updated_at = excluded.updated_at
+                         WHERE interval_ms IS NOT excluded.interval_ms",
+                        params![kind, interval, now + interval, now],
+                    )?;
+                }
+                Ok(())
+            })
+            .await
+    }
+
+    // Claim due jobs by moving their next run forward before they start
+    async fn claim_due(&self) -> AppResult<Vec<Job>> {
+        self.db
+            .run(|conn| {
+                let now = now_ms();
+                let tx = conn.transaction()?;
+                let due = tx
+                    .prepare(
+                        "SELECT * FROM jobs WHERE paused = 0 AND next_run_at IS NOT NULL
+                         AND next_run_at <= ?1",
+                    )?
+                    .query_map([now], Job::from_row)?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                for job in &due {
+                    tx.execute(
+                        "UPDATE jobs SET next_run_at = ?2, updated_at = ?3 WHERE id = ?1",
+                        params![job.id, job.interval_ms.map(|i| now + i), now],
+                    )?;
+                }
+                tx.commit()?;
+                Ok(due)
+            })
+            .await
+    }
+
+    async fn next_wakeup(&self) -> AppResult<Duration> {
+        let next: Option<i64> = self
+            .db
+            .run(|conn| {
+                Ok(conn.query_row(
+                    "SELECT MIN(next_run_at) FROM jobs WHERE paused = 0",
+                    [],
+                    |row| row.get(0),
+                )?)
+            })
+            .await?;
+        Ok(next
+            .map(|at| Duration::from_millis((at - now_ms()).max(0) as u64))
+            .unwrap_or(MAX_IDLE)
+            .min(MAX_IDLE))
+    }
+
+    async fn load(&self, id: String) -> AppResult<Job> {
+        let lookup = id.clone();
+        self.db
+            .run(move |conn| {
+                Ok(conn
+                    .query_row("SELECT * FROM jobs WHERE id = ?1", [lookup], Job::from_row)
+                    .optional()?)
+            })
+            .await?
+            .ok_or_else(|| AppError::not_found("job", id))
+    }
+}
+
+/// Start the scheduler loop; call after every subsystem has registered its jobs.
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        // An encrypted database stays closed until the user unlocks it
+        app.state::<Db>().wait_unlocked().await;
+        let scheduler = app.state::<Scheduler>();
+        if let Err(err) = scheduler.sync_definitions().await {
+            tracing::error!(%err, "failed to sync job definitions");
+        }
+        while !scheduler.stopping.load(Ordering::SeqCst) {
+            // Locked again after the session idled out
+            scheduler.db.wait_unlocked().await;
+            match scheduler.claim_due().await {
+                Ok(due) => {
+                    for job in due {
+                        spawn_run(&app, job);
+                    }
+                }
+                Err(err) => tracing::error!(%err, "failed to load due jobs"),
+            }
+            let wait = scheduler.next_wakeup().await.unwrap_or(MAX_IDLE);
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = scheduler.wake.notified() => {}
+            }
+        }
+    });
+}
+
+fn spawn_run(app: &AppHandle, job: Job) {
+    let scheduler = app.state::<Scheduler>();
+    if scheduler.stopping.load(Ordering::SeqCst) {
+        return;
+    }
+    if !scheduler
+        .running
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(job.id.clone())
+    {
+        tracing::debug!(id = %job.id, "skipping job that is still running");
+        return;
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let scheduler = app.state::<Scheduler>();
+        let lifecycle = |duration_ms, error| JobLifecycle {
+            id: job.id.clone(),
+            kind: job.kind.clone(),
+            duration_ms,
+            error,
+        };
+        let _ = app.emit(JOB_STARTED_EVENT, lifecycle(None, None));
+
+        let started = Instant::now();
+        let result = match scheduler.handler(&job.kind) {
+            Some(handler) => handler(app.clone()).await,
+            None => Err(AppError::Internal(format!(
+                "no handler registered for job kind {}",
+                job.kind
+            ))),
+        };
+        let duration_ms = started.elapsed().as_millis() as i64;
+
+        let (status, error) = match &result {
+            Ok(()) => ("succeeded", None),
+            Err(err) => ("failed", Some(err.to_string())),
+        };
+        let id = job.id.clone();
+        let recorded = scheduler
+            .db
+            .run(move |conn| {
+                let now = now_ms();
+                conn.execute(
+                    "UPDATE jobs SET last_run_at = ?2, last_duration_ms = ?3, last_status = ?4,
+                         last_error = ?5, updated_at = ?2
+                     WHERE id = ?1",
+                    params![id, now, duration_ms, status, error],
+                )?;
+                Ok(())
+            })
+            .await;
+        if let Err(err) = recorded {
+            tracing::warn!(%err, id = %job.id, "failed to record job run");
+        }
+        scheduler
+            .running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&job.id);
+
+        match result {
+            Ok(()) => {
+                tracing::info!(id = %job.id, kind = %job.kind, duration_ms, "job finished");
+                let _ = app.emit(JOB_FINISHED_EVENT, lifecycle(Some(duration_ms), None));
+            }
+            Err(err) => {
+                tracing::warn!(id = %job.id, kind = %job.kind, %err, "job failed");
+                let _ = app.emit(
+                    JOB_FAILED_EVENT,
+                    lifecycle(Some(duration_ms), serde_json::to_value(&err).ok()),
+                );
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn list_jobs(scheduler: State<'_, Scheduler>) -> AppResult<Vec<Job>> {
+    let mut jobs = scheduler
+        .db
+        .run(|conn| {
+            Ok(conn
+                .prepare("SELECT * FROM jobs ORDER BY next_run_at IS NULL, next_run_at")?
+                .query_map([], Job::from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+        .await?;
+    for job in &mut jobs {
+        job.running = scheduler.is_running(&job.id);
+    }
+    Ok(jobs)
+}
+
+/// Start a job now, even if paused; its regular schedule is unaffected.
+///
+/// Recurring jobs use their kind as id, so subsystems can trigger them by kind.
+pub async fn run_now(app: &AppHandle, id: &str) -> AppResult<()> {
+    let scheduler = app.state::<Scheduler>();
+    let job = scheduler.load(id.to_string()).await?;
+    if scheduler.is_running(&job.id) {
+        return Err(AppError::InvalidInput(format!(
+            "job {} is already running",
+            job.id
+        )));
+    }
+    spawn_run(app, job);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn run_job_now(app: AppHandle, id: String) -> AppResult<()> {
+    run_now(&app, &id).await
+}
+
+/// Schedule a one-shot run of a registered job kind at `run_at` (Unix ms).
+#[tauri::command]
+pub async fn schedule_job(
+    scheduler: State<'_, Scheduler>,
+    kind: String,
+    run_at: i64,
+) -> AppResult<String> {
+    scheduler.schedule_once(&kind, run_at).await
+}
+
+async fn set_paused(scheduler: &Scheduler, id: String, paused: bool) -> AppResult<Job> {
+    let lookup = id.clone();
+    let changed = scheduler
+        .db
+        .run(move |conn| {
+            Ok(conn.execute(
+                "UPDATE jobs SET paused = ?2, updated_at = ?3 WHERE id = ?1",
+                params![lookup, paused, now_ms()],
+            )?)
+        })
+        .await?;
+    if changed == 0 {
+        return Err(AppError::not_found("job", id));
+    }
+    scheduler.wake.notify_one();
+    scheduler.load(id).await
+}
+
+/// Stop scheduling a job; a run already in progress finishes normally.
+#[tauri::command]
+pub async fn pause_job(scheduler: State<'_, Scheduler>, id: String) -> AppResult<Job> {
+    set_paused(&scheduler, id, true).await
+}
+
+/// Resume a paused job; overdue recurring jobs run on the next tick.
+#[tauri::command]
+pub async fn resume_job(scheduler: State<'_, Scheduler>, id: String) -> AppResult<Job> {
+    set_paused(&scheduler, id, false).await
+}
@@ -0,0 +1,212 @@
+//! The authenticated, encrypted connection a project travels over.
+//!
+//! The receiver shows a short pairing code and the sender types it in; the code itself is
+//! never sent. Both sides run SPAKE2 over Ed25519 with the `spake2` crate, so an eavesdropper
+//! learns nothing about the code and an attacker in the middle gets exactly one guess per
+//! connection, which the receiver limits. Each side then proves it derived the same key
+//! before anything else is sent. After the handshake every frame is sealed with
+//! ChaCha20-Poly1305 under a key per direction and a counter nonce, so frames cannot be
+//! read, altered, replayed, or reordered.
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use spake2::{Ed25519Group, Identity, Password, Spake2};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::error::{AppError, AppResult};
+
+const MAGIC: &[u8; 4] = b"MLS2";
+// A side byte and a compressed Edwards point
+const MESSAGE_BYTES: usize = 33;
+const CONFIRM_BYTES: usize = 32;
+const TAG_BYTES: usize = 16;
+/// Largest plaintext a sealed frame may carry.
+pub(crate) const MAX_FRAME: usize = 256 * 1024;
+
+const SENDER_ID: &[u8] = b"momentum-lan-sync sender";
+const RECEIVER_ID: &[u8] = b"momentum-lan-sync receiver";
+
+fn password(code: &str) -> Password {
+    Password::new(format!("momentum-lan-sync pairing:{}", code.trim()))
+}
+
+struct Keys {
+    sender: [u8; 32],
+    receiver: [u8; 32],
+    sender_confirm: [u8; 32],
+    receiver_confirm: [u8; 32],
+}
+
+// The SPAKE2 key already binds the code and both messages; these are split from it
+fn derive(t: &[u8], s: &[u8], shared: &[u8]) -> Keys {
+    let mut transcript = Sha256::new();
+    transcript.update(MAGIC);
+    transcript.update(t);
+    transcript.update(s);
+    let salt = transcript.finalize();
+    let hkdf = Hkdf::<Sha256>::new(Some(&salt), shared);
+    let key = |info: &[u8]| {
+        let mut out = [0u8; 32];
+        // 32 bytes is always a valid HKDF-SHA256 length
+        let _ = hkdf.expand(info, &mut out);
+        out
+    };
+    Keys {
+        sender: key(b"sender key"),
+        receiver: key(b"receiver key"),
+        sender_confirm: key(b"sender confirm"),
+        receiver_confirm: key(b"receiver confirm"),
+    }
+}
+
+fn finish(state: Spake2<Ed25519Group>, theirs: &[u8]) -> AppResult<Vec<u8>> {
+    state
+        .finish(theirs)
+        .map_err(|_| AppError::LanSync("the peer sent an invalid key".into()))
+}
+
+fn confirmation(key: &[u8; 32], label: &[u8]) -> Vec<u8> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC takes any key length");
+    mac.update(label);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn confirms(key: &[u8; 32], label: &[u8], given: &[u8]) -> bool {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC takes any key length");
+    mac.update(label);
+    mac.verify_slice(given).is_ok()
+}
+
+async fn write_frame(stream: &mut TcpStream, bytes: &[u8]) -> AppResult<()> {
+    stream
+        .write_all(&(bytes.len() as u32).to_be_bytes())
+        .await?;
+    stream.write_all(bytes).await?;
+    Ok(())
+}
+
+async fn read_frame(stream: &mut TcpStream, max: usize) -> AppResult<Vec<u8>> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).await?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > max {
+        return Err(AppError::LanSync("the peer sent an oversized frame".into()));
+    }
+    let mut bytes = vec![0u8; len];
+    stream.read_exact(&mut bytes).await?;
+    Ok(bytes)
+}
+
+/// A connection that has completed the handshake.
+pub(crate) struct Channel {
+    stream: TcpStream,
+    seal: ChaCha20Poly1305,
+    open: ChaCha20Poly1305,
+    sent: u64,
+    received: u64,
+}
+
+impl Channel {
+    fn new(stream: TcpStream, seal: &[u8; 32], open: &[u8; 32]) -> Self {
+        Self {
+            stream,
+            seal: ChaCha20Poly1305::new(Key::from_slice(seal)),
+            open: ChaCha20Poly1305::new(Key::from_slice(open)),
+            sent: 0,
+            received: 0,
+        }
+    }
+
+    /// Run the sender's side of the handshake with the code the receiver shows.
+    pub(crate) async fn connect(mut stream: TcpStream, code: &str) -> AppResult<Self> {
+        let (state, t) = Spake2::<Ed25519Group>::start_a(
+            &password(code),
+            &Identity::new(SENDER_ID),
+            &Identity::new(RECEIVER_ID),
+        );
+        let mut hello = MAGIC.to_vec();
+        hello.extend_from_slice(&t);
+        write_frame(&mut stream, &hello).await?;
+
+        let reply = read_frame(&mut stream, MESSAGE_BYTES + CONFIRM_BYTES).await?;
+        if reply.len() != MESSAGE_BYTES + CONFIRM_BYTES {
+            return Err(AppError::LanSync("the peer refused the connection".into()));
+        }
+        let (s, proof) = reply.split_at(MESSAGE_BYTES);
+        let keys = derive(&t, s, &finish(state, s)?);
+        if !confirms(&keys.receiver_confirm, b"receiver", proof) {
+            return Err(AppError::PairingFailed);
+        }
+        write_frame(&mut stream, &confirmation(&keys.sender_confirm, b"sender")).await?;
+        Ok(Self::new(stream, &keys.sender, &keys.receiver))
+    }
+
+    /// Run the receiver's side of the handshake against the code it is showing.
+    pub(crate) async fn accept(mut stream: TcpStream, code: &str) -> AppResult<Self> {
+        let hello = read_frame(&mut stream, MAGIC.len() + MESSAGE_BYTES).await?;
+        if hello.len() != MAGIC.len() + MESSAGE_BYTES || !hello.starts_with(MAGIC) {
+            return Err(AppError::LanSync("not a transfer from Momentum".into()));
+        }
+        let t = &hello[MAGIC.len()..];
+        let (state, s) = Spake2::<Ed25519Group>::start_b(
+            &password(code),
+            &Identity::new(SENDER_ID),
+            &Identity::new(RECEIVER_ID),
+        );
+        let keys = derive(t, &s, &finish(state, t)?);
+        let mut reply = s;
+        reply.extend(confirmation(&keys.receiver_confirm, b"receiver"));
+        write_frame(&mut stream, &reply).await?;
+
+        // A sender with the wrong code hangs up instead of answering
+        let proof = read_frame(&mut stream, CONFIRM_BYTES)
+            .await
+            .map_err(|_| AppError::PairingFailed)?;
+        if !confirms(&keys.sender_confirm, b"sender", &proof) {
+            return Err(AppError::PairingFailed);
+        }
+        Ok(Self::new(stream, &keys.receiver, &keys.sender))
+    }
+
+    fn nonce(counter: u64) -> [u8; 12] {
+        let mut nonce = [0u8; 12];
+        nonce[4..].copy_from_slice(&counter.to_be_bytes());
+        nonce
+    }
+
+    pub(crate) async fn send(&mut self, plaintext: &[u8]) -> AppResult<()> {
+        if plaintext.len() > MAX_FRAME {
+            return Err(AppError::Internal("frame too large".into()));
+        }
+        let nonce = Self::nonce(self.sent);
+        self.sent += 1;
+        let sealed = self
+            .seal
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| AppError::Encryption("cannot seal a transfer frame".into()))?;
+        write_frame(&mut self.stream, &sealed).await
+    }
+
+    pub(crate) async fn receive(&mut self) -> AppResult<Vec<u8>> {
+        let sealed = read_frame(&mut self.stream, MAX_FRAME + TAG_BYTES).await?;
+        let nonce = Self::nonce(self.received);
+        self.received += 1;
+        self.open
+            .decrypt(Nonce::from_slice(&nonce), sealed.as_slice())
+            .map_err(|_| AppError::LanSync("a frame failed authentication".into()))
+    }
+
+    pub(crate) async fn send_json<T: Serialize>(&mut self, value: &T) -> AppResult<()> {
+        self.send(&serde_json::to_vec(value)?).await
+    }
+
+    pub(crate) async fn receive_json<T: DeserializeOwned>(&mut self) -> AppResult<T> {
+        Ok(serde_json::from_slice(&self.receive().await?)?)
+    }
+}
@@ -0,0 +1,213 @@
+//! Advertising and finding other instances over multicast DNS (RFC 6762) and DNS-SD
+//! (RFC 6763), through the `mdns-sd` responder.
+//!
+//! Each instance registers `<instance>._momentum-sync._tcp.local.` with its transfer port,
+//! a TXT record carrying its display name and protocol version, and its LAN addresses.
+//! The responder answers queries, re-announces, and says goodbye on its own; this module
+//! only turns what it reports about that one service type into [`Event`]s.
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+use mdns_sd::{IfKind, Receiver, ServiceDaemon, ServiceEvent, ServiceInfo};
+
+use crate::error::{AppError, AppResult};
+
+pub(crate) const SERVICE: &str = "_momentum-sync._tcp.local.";
+/// Advertised in TXT so incompatible peers can be told apart.
+pub(crate) const PROTOCOL_VERSION: &str = "2";
+
+// A TXT string holds at most 255 bytes including the key
+const MAX_TXT_VALUE: usize = 240;
+const GOODBYE_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn failed(err: mdns_sd::Error) -> AppError {
+    AppError::LanSync(format!("cannot advertise to nearby devices: {err}"))
+}
+
+/// What this instance advertises.
+#[derive(Debug, Clone)]
+pub(crate) struct Advert {
+    /// Random per session, so the name says nothing about the machine.
+    pub instance: String,
+    pub name: String,
+    pub port: u16,
+    pub addresses: Vec<Ipv4Addr>,
+}
+
+impl Advert {
+    fn service(&self) -> AppResult<ServiceInfo> {
+        let mut name = self.name.as_str();
+        if name.len() > MAX_TXT_VALUE {
+            let mut end = MAX_TXT_VALUE;
+            while !name.is_char_boundary(end) {
+                end -= 1;
+            }
+            name = &name[..end];
+        }
+        let addresses: Vec<IpAddr> = self.addresses.iter().copied().map(IpAddr::V4).collect();
+        ServiceInfo::new(
+            SERVICE,
+            &self.instance,
+            &format!("momentum-{}.local.", self.instance),
+            addresses.as_slice(),
+            self.port,
+            &[("v", PROTOCOL_VERSION), ("name", name)][..],
+        )
+        .map_err(failed)
+    }
+}
+
+/// An instance seen on the network.
+#[derive(Debug, Clone)]
+pub(crate) struct Sighting {
+    pub instance: String,
+    pub name: String,
+    pub version: String,
+    pub port: u16,
+    pub addresses: Vec<Ipv4Addr>,
+}
+
+/// What the responder reported, as far as this module cares.
+#[derive(Debug)]
+pub(crate) enum Event {
+    Seen(Sighting),
+    /// The instance said goodbye, or its records expired.
+    Gone(String),
+}
+
+fn instance(fullname: &str) -> Option<String> {
+    let instance = fullname.strip_suffix(SERVICE)?.strip_suffix('.')?;
+    (!instance.is_empty()).then(|| instance.to_string())
+}
+
+/// The [`Event`] for a responder event; `None` for progress reports and other services.
+pub(crate) fn event(event: ServiceEvent) -> Option<Event> {
+    match event {
+        ServiceEvent::ServiceResolved(service) if service.ty_domain == SERVICE => {
+            let instance = instance(&service.fullname)?;
+            let text = |key: &str| service.get_property_val_str(key).map(str::to_string);
+            let mut addresses: Vec<Ipv4Addr> = service.get_addresses_v4().into_iter().collect();
+            addresses.sort();
+            Some(Event::Seen(Sighting {
+                name: text("name").unwrap_or_else(|| instance.clone()),
+                version: text("v").unwrap_or_default(),
+                port: service.port,
+                addresses,
+                instance,
+            }))
+        }
+        ServiceEvent::ServiceRemoved(ty, fullname) if ty == SERVICE => {
+            instance(&fullname).map(Event::Gone)
+        }
+        _ => None,
+    }
+}
+
+/// The running responder, advertising one [`Advert`] until stopped.
+pub(crate) struct Responder {
+    daemon: ServiceDaemon,
+    fullname: String,
+}
+
+impl Responder {
+    /// Advertise `advert` on the interfaces holding its addresses and browse for others.
+    pub(crate) fn start(advert: &Advert) -> AppResult<(Self, Receiver<ServiceEvent>)> {
+        let daemon = ServiceDaemon::new().map_err(failed)?;
+        // Only the LAN interfaces; VPNs and container bridges have no peers worth finding
+        daemon.disable_interface(IfKind::All).map_err(failed)?;
+        let interfaces: Vec<IfKind> = advert
+            .addresses
+            .iter()
+            .map(|address| IfKind::Addr(IpAddr::V4(*address)))
+            .collect();
+        daemon.enable_interface(interfaces).map_err(failed)?;
+        let service = advert.service()?;
+        let fullname = service.get_fullname().to_string();
+        daemon.register(service).map_err(failed)?;
+        let events = daemon.browse(SERVICE).map_err(failed)?;
+        Ok((Self { daemon, fullname }, events))
+    }
+
+    /// Say goodbye so peers drop this instance now, then shut the responder down.
+    pub(crate) async fn stop(self) {
+        if let Ok(done) = self.daemon.unregister(&self.fullname) {
+            let _ = tokio::time::timeout(GOODBYE_TIMEOUT, done.recv_async()).await;
+        }
+        let _ = self.daemon.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn advert(name: &str) -> Advert {
+        Advert {
+            instance: "0a1b2c3d4e5f6071".into(),
+            name: name.into(),
+            port: 40123,
+            addresses: vec![Ipv4Addr::new(192, 168, 1, 20), Ipv4Addr::new(10, 0, 0, 5)],
+        }
+    }
+
+    fn resolved(advert: &Advert) -> ServiceEvent {
+        let service = advert.service().unwrap().as_resolved_service();
+        ServiceEvent::ServiceResolved(Box::new(service))
+    }
+
+    #[test]
+    fn an_advert_is_read_back_as_a_sighting() {
+        let Some(Event::Seen(sighting)) = event(resolved(&advert("Site trailer"))) else {
+            panic!("expected a sighting");
+        };
+        assert_eq!(sighting.instance, "0a1b2c3d4e5f6071");
+        assert_eq!(sighting.name, "Site trailer");
+        assert_eq!(sighting.version, PROTOCOL_VERSION);
+        assert_eq!(sighting.port, 40123);
+        assert_eq!(
+            sighting.addresses,
+            [Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(192, 168, 1, 20)]
+        );
+    }
+
+    #[test]
+    fn long_names_are_cut_to_fit_the_txt_record() {
+        let name = "é".repeat(200);
+        let Some(Event::Seen(sighting)) = event(resolved(&advert(&name))) else {
+            panic!("expected a sighting");
+        };
+        assert!(sighting.name.len() <= MAX_TXT_VALUE);
+        assert!(name.starts_with(&sighting.name));
+    }
+
+    #[test]
+    fn goodbyes_name_the_instance() {
+        let gone = event(ServiceEvent::ServiceRemoved(
+            SERVICE.into(),
+            format!("0a1b2c3d4e5f6071.{SERVICE}"),
+        ));
+        assert!(matches!(gone, Some(Event::Gone(instance)) if instance == "0a1b2c3d4e5f6071"));
+    }
+
+    #[test]
+    fn other_services_are_ignored() {
+        let other = ServiceInfo::new(
+            "_http._tcp.local.",
+            "printer",
+            "printer.local.",
+            "192.168.1.30",
+            80,
+            None,
+        )
+        .unwrap();
+        let resolved = ServiceEvent::ServiceResolved(Box::new(other.as_resolved_service()));
+        assert!(event(resolved).is_none());
+        let removed = ServiceEvent::ServiceRemoved(
+            "_http._tcp.local.".into(),
+            "printer._http._tcp.local.".into(),
+        );
+        assert!(event(removed).is_none());
+        assert!(event(ServiceEvent::SearchStarted(SERVICE.into())).is_none());
+    }
+}
@@ -0,0 +1,693 @@
+//! Finding other Momentum instances on the local network and pushing a project straight to
+//! one, for job sites with no internet.
+//!
+//! Nothing is advertised until `start_lan_sync` is called. While it is on, this instance
+//! announces itself over mDNS (see [`mdns`]), keeps `lan-sync:peers` up to date with the
+//! others it hears, and accepts transfers on a random TCP port. The receiving side shows a
+//! pairing code which the sender types in; [`channel`] turns it into an authenticated,
+//! encrypted connection without ever sending it. Three wrong codes in a row replace the
+//! code. A received project is imported like an archive, as a copy when it already exists
+//! here, and both sides report `lan-sync:progress` while it moves.
+
+mod channel;
+mod mdns;
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rand::{Rng, RngCore};
+use rusqlite::OptionalExtension;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::archive;
+use crate::db::projects::Project;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::network;
+use crate::state::AppState;
+use crate::wake_lock;
+use channel::{Channel, MAX_FRAME};
+use mdns_sd::{Receiver, ServiceEvent};
+
+/// Emitted with the current `Vec<LanPeer>` whenever an instance appears, changes, or leaves.
+pub const PEERS_EVENT: &str = "lan-sync:peers";
+/// Emitted with [`LanSyncStatus`] when the pairing code is replaced after failed attempts.
+pub const STATUS_EVENT: &str = "lan-sync:status";
+/// Emitted with [`LanTransferProgress`] as an archive is sent or received.
+pub const PROGRESS_EVENT: &str = "lan-sync:progress";
+/// Emitted with [`LanTransferReceived`] once a pushed project has been imported.
+pub const RECEIVED_EVENT: &str = "lan-sync:received";
+
+const DIR_NAME: &str = "lan-sync";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+// How long either side waits for the other mid-transfer, including the receiver's import
+const IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+const MAX_FAILED_PAIRINGS: u32 = 3;
+const MAX_ARCHIVE_BYTES: u64 = 8 * 1024 * 1024 * 1024;
+
+/// Another instance advertising on the local network.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanPeer {
+    pub id: String,
+    pub name: String,
+    pub addresses: Vec<String>,
+    pub port: u16,
+    /// False when the peer speaks a different transfer protocol and cannot receive from here.
+    pub compatible: bool,
+}
+
+/// Whether this instance is visible, and what the user needs to receive.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanSyncStatus {
+    pub active: bool,
+    /// The name other devices see.
+    pub device_name: Option<String>,
+    /// Shown to the user so senders can type it in.
+    pub pairing_code: Option<String>,
+    pub peers: Vec<LanPeer>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TransferDirection {
+    Send,
+    Receive,
+}
+
+/// Payload of [`PROGRESS_EVENT`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanTransferProgress {
+    pub transfer_id: String,
+    pub direction: TransferDirection,
+    /// The other device's name.
+    pub peer: String,
+    pub project_name: String,
+    pub bytes: u64,
+    pub total: u64,
+}
+
+/// Payload of [`RECEIVED_EVENT`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanTransferReceived {
+    pub transfer_id: String,
+    pub from: String,
+    pub project: Project,
+    /// The project already existed here, so it came in as a copy.
+    pub copied: bool,
+}
+
+/// Result of `send_project_to_peer`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanTransferResult {
+    pub transfer_id: String,
+    pub bytes: u64,
+    /// The project's id on the receiving device, which differs when it arrived as a copy.
+    pub remote_project_id: String,
+}
+
+// What the sender says first, inside the encrypted channel
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Offer {
+    transfer_id: String,
+    sender_name: String,
+    project_name: String,
+    size: u64,
+    sha256: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+enum Reply {
+    Accepted,
+    #[serde(rename_all = "camelCase")]
+    Imported {
+        project_id: String,
+    },
+    Refused {
+        reason: String,
+    },
+}
+
+struct Session {
+    advert: mdns::Advert,
+    code: String,
+    failures: u32,
+    responder: mdns::Responder,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+/// LAN sync registered in app state; idle until started.
+#[derive(Default)]
+pub struct LanSync {
+    session: Mutex<Option<Session>>,
+    peers: Mutex<HashMap<String, LanPeer>>,
+}
+
+impl LanSync {
+    fn status(&self) -> LanSyncStatus {
+        let session = self.session.lock().unwrap_or_else(|e| e.into_inner());
+        LanSyncStatus {
+            active: session.is_some(),
+            device_name: session.as_ref().map(|s| s.advert.name.clone()),
+            pairing_code: session.as_ref().map(|s| s.code.clone()),
+            peers: self.peers(),
+        }
+    }
+
+    fn peers(&self) -> Vec<LanPeer> {
+        let mut peers: Vec<LanPeer> = self
+            .peers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .cloned()
+            .collect();
+        peers.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+        peers
+    }
+
+    fn code(&self) -> Option<String> {
+        let session = self.session.lock().unwrap_or_else(|e| e.into_inner());
+        session.as_ref().map(|s| s.code.clone())
+    }
+
+    // Returns true when the code was replaced
+    fn record_pairing(&self, succeeded: bool) -> bool {
+        let mut session = self.session.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(session) = session.as_mut() else {
+            return false;
+        };
+        if succeeded {
+            session.failures = 0;
+            return false;
+        }
+        session.failures += 1;
+        if session.failures < MAX_FAILED_PAIRINGS {
+            return false;
+        }
+        session.failures = 0;
+        session.code = pairing_code();
+        true
+    }
+
+    fn update_peer(&self, sighting: mdns::Sighting) -> bool {
+        let peer = LanPeer {
+            id: sighting.instance,
+            name: sighting.name,
+            addresses: sighting.addresses.iter().map(Ipv4Addr::to_string).collect(),
+            port: sighting.port,
+            compatible: sighting.version == mdns::PROTOCOL_VERSION,
+        };
+        let mut peers = self.peers.lock().unwrap_or_else(|e| e.into_inner());
+        let changed = peers.get(&peer.id) != Some(&peer);
+        peers.insert(peer.id.clone(), peer);
+        changed
+    }
+
+    fn remove_peer(&self, id: &str) -> bool {
+        let mut peers = self.peers.lock().unwrap_or_else(|e| e.into_inner());
+        peers.remove(id).is_some()
+    }
+}
+
+fn pairing_code() -> String {
+    format!("{:06}", rand::rng().random_range(0..1_000_000))
+}
+
+fn instance_id() -> String {
+    let mut bytes = [0u8; 8];
+    rand::rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn transfer_dir(app: &AppHandle) -> PathBuf {
+    app.state::<AppState>().data_dir().join(DIR_NAME)
+}
+
+fn emit_peers(app: &AppHandle) {
+    let _ = app.emit(PEERS_EVENT, app.state::<LanSync>().peers());
+}
+
+// Throttles progress events so a fast LAN does not flood the frontend
+struct Progress {
+    app: AppHandle,
+    event: LanTransferProgress,
+    emitted: Option<Instant>,
+}
+
+impl Progress {
+    fn update(&mut self, bytes: u64) {
+        self.event.bytes = bytes;
+        let done = bytes >= self.event.total;
+        if done
+            || self
+                .emitted
+                .is_none_or(|at| at.elapsed() >= PROGRESS_INTERVAL)
+        {
+            self.emitted = Some(Instant::now());
+            let _ = self.app.emit(PROGRESS_EVENT, &self.event);
+        }
+    }
+}
+
+async fn timed<T>(
+    limit: Duration,
+    what: &str,
+    future: impl std::future::Future<Output = AppResult<T>>,
+) -> AppResult<T> {
+    tokio::time::timeout(limit, future)
+        .await
+        .map_err(|_| AppError::LanSync(format!("timed out {what}")))?
+}
+
+// Keeps the peer list in step with what the responder hears, until it shuts down
+async fn discover(app: AppHandle, events: Receiver<ServiceEvent>, own: String) {
+    let lan = app.state::<LanSync>();
+    while let Ok(event) = events.recv_async().await {
+        let changed = match mdns::event(event) {
+            Some(mdns::Event::Seen(sighting)) if sighting.instance != own => {
+                lan.update_peer(sighting)
+            }
+            Some(mdns::Event::Gone(instance)) => lan.remove_peer(&instance),
+            _ => false,
+        };
+        if changed {
+            emit_peers(&app);
+        }
+    }
+}
+
+async fn listen(app: AppHandle, listener: TcpListener) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, address)) => {
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(err) = receive(&app, stream).await {
+                        tracing::warn!(%address, %err, "incoming lan transfer failed");
+                    }
+                });
+            }
+            Err(err) => {
+                tracing::warn!(%err, "cannot accept lan transfers");
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+async fn receive(app: &AppHandle, stream: TcpStream) -> AppResult<()> {
+    let lan = app.state::<LanSync>();
+    let Some(code) = lan.code() else {
+        return Ok(());
+    };
+    let channel = timed(HANDSHAKE_TIMEOUT, "pairing", Channel::accept(stream, &code)).await;
+    let mut channel = match channel {
+        Ok(channel) => {
+            lan.record_pairing(true);
+            channel
+        }
+        // Only a wrong code counts; stray connections from scanners are not guesses
+        Err(err @ AppError::PairingFailed) => {
+            if lan.record_pairing(false) {
+                tracing::warn!("replacing the pairing code after repeated failures");
+                let _ = app.emit(STATUS_EVENT, lan.status());
+            }
+            return Err(err);
+        }
+        Err(err) => return Err(err),
+    };
+
+    let offer: Offer = timed(
+        IDLE_TIMEOUT,
+        "waiting for the offer",
+        channel.receive_json(),
+    )
+    .await?;
+    if offer.size > MAX_ARCHIVE_BYTES {
+        let reason = "the project is too large to send this way".to_string();
+        channel
+            .send_json(&Reply::Refused {
+                reason: reason.clone(),
+            })
+            .await?;
+        return Err(AppError::LanSync(reason));
+    }
+    channel.send_json(&Reply::Accepted).await?;
+
+    let _awake = wake_lock::hold(app, "Receiving a project");
+    let dir = transfer_dir(app);
+    tokio::fs::create_dir_all(&dir).await?;
+    let path = dir.join(format!("{}.zip", uuid::Uuid::new_v4()));
+    let result = receive_archive(app, &mut channel, &offer, &path).await;
+    let outcome = match result {
+        Ok(()) => {
+            archive::import_project_archive(app.state(), app.state(), path.clone(), None).await
+        }
+        Err(err) => Err(err),
+    };
+    let _ = tokio::fs::remove_file(&path).await;
+
+    match outcome {
+        Ok(imported) => {
+            channel
+                .send_json(&Reply::Imported {
+                    project_id: imported.project.id.clone(),
+                })
+                .await?;
+            tracing::info!(
+                transfer_id = %offer.transfer_id,
+                project_id = %imported.project.id,
+                bytes = offer.size,
+                "project received over the lan"
+            );
+            let _ = app.emit(
+                RECEIVED_EVENT,
+                LanTransferReceived {
+                    transfer_id: offer.transfer_id,
+                    from: offer.sender_name,
+                    project: imported.project,
+                    copied: imported.copied,
+                },
+            );
+            Ok(())
+        }
+        Err(err) => {
+            let _ = channel
+                .send_json(&Reply::Refused {
+                    reason: err.to_string(),
+                })
+                .await;
+            Err(err)
+        }
+    }
+}
+
+// Writes chunks until the empty end frame, then checks the size and digest
+async fn receive_archive(
+    app: &AppHandle,
+    channel: &mut Channel,
+    offer: &Offer,
+    path: &Path,
+) -> AppResult<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut file = tokio::fs::File::create(path).await?;
+    let mut hasher = Sha256::new();
+    let mut received = 0u64;
+    let mut progress = Progress {
+        app: app.clone(),
+        event: LanTransferProgress {
+            transfer_id: offer.transfer_id.clone(),
+            direction: TransferDirection::Receive,
+            peer: offer.sender_name.clone(),
+            project_name: offer.project_name.clone(),
+            bytes: 0,
+            total: offer.size,
+        },
+        emitted: None,
+    };
+    loop {
+        let chunk = timed(IDLE_TIMEOUT, "receiving the project", channel.receive()).await?;
+        if chunk.is_empty() {
+            break;
+        }
+        received += chunk.len() as u64;
+        if received > offer.size {
+            return Err(AppError::LanSync(
+                "the sender sent more than it offered".into(),
+            ));
+        }
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+        progress.update(received);
+    }
+    file.flush().await?;
+    let digest: String = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    if received != offer.size || digest != offer.sha256 {
+        return Err(AppError::LanSync("the project arrived incomplete".into()));
+    }
+    Ok(())
+}
+
+async fn connect(peer: &LanPeer) -> AppResult<TcpStream> {
+    let mut last = None;
+    for address in &peer.addresses {
+        let Ok(ip) = address.parse::<Ipv4Addr>() else {
+            continue;
+        };
+        let connecting = TcpStream::connect(SocketAddr::from((ip, peer.port)));
+        match tokio::time::timeout(CONNECT_TIMEOUT, connecting).await {
+            Ok(Ok(stream)) => return Ok(stream),
+            Ok(Err(err)) => last = Some(err.to_string()),
+            Err(_) => last = Some("timed out".into()),
+        }
+    }
+    Err(AppError::LanSync(format!(
+        "cannot reach {}: {}",
+        peer.name,
+        last.unwrap_or_else(|| "no usable address".into())
+    )))
+}
+
+async fn file_digest(path: PathBuf) -> AppResult<(u64, String)> {
+    tauri::async_runtime::spawn_blocking(move || -> AppResult<(u64, String)> {
+        let mut file = std::fs::File::open(&path)?;
+        let mut hasher = Sha256::new();
+        let size = std::io::copy(&mut file, &mut hasher)?;
+        let digest = hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        Ok((size, digest))
+    })
+    .await?
+}
+
+async fn send_archive(
+    app: &AppHandle,
+    peer: &LanPeer,
+    offer: Offer,
+    path: &Path,
+    code: &str,
+) -> AppResult<LanTransferResult> {
+    let stream = connect(peer).await?;
+    let mut channel = timed(HANDSHAKE_TIMEOUT, "pairing", Channel::connect(stream, code)).await?;
+    channel.send_json(&offer).await?;
+    match timed(IDLE_TIMEOUT, "waiting for the peer", channel.receive_json()).await? {
+        Reply::Accepted => {}
+        Reply::Refused { reason } => return Err(AppError::LanSync(reason)),
+        Reply::Imported { .. } => {
+            return Err(AppError::LanSync("the peer answered out of turn".into()))
+        }
+    }
+
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut progress = Progress {
+        app: app.clone(),
+        event: LanTransferProgress {
+            transfer_id: offer.transfer_id.clone(),
+            direction: TransferDirection::Send,
+            peer: peer.name.clone(),
+            project_name: offer.project_name.clone(),
+            bytes: 0,
+            total: offer.size,
+        },
+        emitted: None,
+    };
+    let mut buf = vec![0u8; MAX_FRAME];
+    let mut sent = 0u64;
+    loop {
+        let read = file.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        timed(
+            IDLE_TIMEOUT,
+            "sending the project",
+            channel.send(&buf[..read]),
+        )
+        .await?;
+        sent += read as u64;
+        progress.update(sent);
+    }
+    channel.send(&[]).await?;
+
+    match timed(
+        IDLE_TIMEOUT,
+        "waiting for the import",
+        channel.receive_json(),
+    )
+    .await?
+    {
+        Reply::Imported { project_id } => Ok(LanTransferResult {
+            transfer_id: offer.transfer_id,
+            bytes: sent,
+            remote_project_id: project_id,
+        }),
+        Reply::Refused { reason } => Err(AppError::LanSync(reason)),
+        Reply::Accepted => Err(AppError::LanSync("the peer answered out of turn".into())),
+    }
+}
+
+/// Become visible to other instances on the network, find them, and accept transfers.
+#[tauri::command]
+pub async fn start_lan_sync(app: AppHandle, lan: State<'_, LanSync>) -> AppResult<LanSyncStatus> {
+    if lan
+        .session
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .is_some()
+    {
+        return Ok(lan.status());
+    }
+    let addresses = network::lan_addresses()?;
+    let listener = TcpListener::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))).await?;
+    let advert = mdns::Advert {
+        instance: instance_id(),
+        name: tauri_plugin_os::hostname(),
+        port: listener.local_addr()?.port(),
+        addresses,
+    };
+    let (responder, events) = mdns::Responder::start(&advert)?;
+
+    let tasks = vec![
+        tauri::async_runtime::spawn(discover(app.clone(), events, advert.instance.clone())),
+        tauri::async_runtime::spawn(listen(app.clone(), listener)),
+    ];
+    tracing::info!(port = advert.port, "lan sync started");
+    let lost = {
+        let mut session = lan.session.lock().unwrap_or_else(|e| e.into_inner());
+        // Another call may have won the race while this one was binding
+        if session.is_some() {
+            Some((tasks, responder))
+        } else {
+            *session = Some(Session {
+                advert,
+                code: pairing_code(),
+                failures: 0,
+                responder,
+                tasks,
+            });
+            None
+        }
+    };
+    if let Some((tasks, responder)) = lost {
+        for task in tasks {
+            task.abort();
+        }
+        responder.stop().await;
+    }
+    Ok(lan.status())
+}
+
+/// Stop advertising and accepting transfers; peers are told this instance has gone.
+#[tauri::command]
+pub async fn stop_lan_sync(app: AppHandle, lan: State<'_, LanSync>) -> AppResult<()> {
+    let session = lan.session.lock().unwrap_or_else(|e| e.into_inner()).take();
+    let Some(session) = session else {
+        return Ok(());
+    };
+    for task in &session.tasks {
+        task.abort();
+    }
+    session.responder.stop().await;
+    lan.peers.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    emit_peers(&app);
+    tracing::info!("lan sync stopped");
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_lan_sync_status(lan: State<'_, LanSync>) -> AppResult<LanSyncStatus> {
+    Ok(lan.status())
+}
+
+/// Push a project to a peer, using the pairing code shown on that device.
+///
+/// Resolves once the peer has imported it; progress arrives as `lan-sync:progress`.
+#[tauri::command]
+pub async fn send_project_to_peer(
+    app: AppHandle,
+    lan: State<'_, LanSync>,
+    db: State<'_, Db>,
+    peer_id: String,
+    project_id: String,
+    pairing_code: String,
+) -> AppResult<LanTransferResult> {
+    let peer = lan
+        .peers
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&peer_id)
+        .cloned()
+        .ok_or_else(|| AppError::not_found("peer", &peer_id))?;
+    if !peer.compatible {
+        return Err(AppError::InvalidInput(format!(
+            "{} runs an incompatible version; update both devices",
+            peer.name
+        )));
+    }
+    let id = project_id.clone();
+    let project_name: String = db
+        .run(move |conn| {
+            conn.query_row("SELECT name FROM projects WHERE id = ?1", [&id], |row| {
+                row.get(0)
+            })
+            .optional()?
+            .ok_or_else(|| AppError::not_found("project", &id))
+        })
+        .await?;
+    let _awake = wake_lock::hold(&app, "Sending a project");
+    let transfer_id = uuid::Uuid::new_v4().to_string();
+    let path = transfer_dir(&app).join(format!("{transfer_id}.zip"));
+    let export = archive::export_project_archive(
+        app.clone(),
+        app.state(),
+        app.state(),
+        project_id,
+        Some(path.clone()),
+        None,
+        None,
+    )
+    .await?;
+
+    let result = async {
+        let (size, sha256) = file_digest(export.path.clone()).await?;
+        let offer = Offer {
+            transfer_id: transfer_id.clone(),
+            sender_name: tauri_plugin_os::hostname(),
+            project_name,
+            size,
+            sha256,
+        };
+        send_archive(&app, &peer, offer, &export.path, &pairing_code).await
+    }
+    .await;
+    let _ = tokio::fs::remove_file(&export.path).await;
+    if let Ok(sent) = &result {
+        tracing::info!(%transfer_id, peer = %peer.name, bytes = sent.bytes, "project sent over the lan");
+    }
+    result
+}
@@ -1,9 +1,150 @@
+mod analysis;
+mod archive;
+mod attachment_protocol;
+mod attachments;
+mod audit;
+mod auth;
+mod autosave;
+#[cfg(desktop)]
+mod autostart;
+mod background;
+mod backup;
+mod badge;
+mod biometric;
+mod calc;
+mod catalog;
+mod clipboard;
+mod clipboard_watch;
+#[cfg(desktop)]
+mod compact;
+mod config;
+mod crash;
+mod db;
+#[cfg(debug_assertions)]
+mod debug_console;
+mod deeplink;
+mod diagnostics;
+mod disk_cache;
+#[cfg(desktop)]
+mod displays;
+mod downloads;
+#[cfg(desktop)]
+mod drag_out;
+mod dxf;
+mod email;
+mod encryption;
+mod env;
+mod error;
+mod export;
+mod extensions;
+#[cfg(desktop)]
+mod external_edit;
+#[cfg(desktop)]
+mod external_tools;
+mod file_drop;
+mod file_open;
+mod flags;
+mod fonts;
+mod formulas;
+mod http_cache;
+mod http_client;
+mod idle_lock;
+mod ifc;
+mod import;
+mod importers;
+mod integrity;
+mod jobs;
+mod lan_sync;
+mod licensing;
+mod locale;
+mod location;
+mod log_stream;
+mod logging;
+mod maps;
+#[cfg(desktop)]
+mod menu;
+mod metrics;
+#[cfg(mobile)]
+mod mobile;
+mod network;
+mod notifications;
+mod ocr;
+mod outbox;
+mod pdf;
+mod photos;
+mod pinning;
+mod portable;
+mod power;
+#[cfg(desktop)]
+mod presentation;
+mod print;
+mod progress;
+mod project_file;
+mod proxy;
+mod push;
+mod qr;
+#[cfg(desktop)]
+mod quick_capture;
+mod rbac;
+mod realtime;
+mod recent_projects;
+mod relocate;
+mod reminders;
+mod reports;
+mod resources;
+mod scan;
+mod search;
+mod secrets;
+mod share;
+mod share_sheet;
+#[cfg(desktop)]
+mod shortcuts;
+mod shutdown;
+mod signing;
+#[cfg(desktop)]
+mod single_instance;
+mod spellcheck;
+mod splash;
+mod state;
+mod store_migrations;
+mod streams;
+mod sync;
+mod system_info;
+mod takeoff;
+mod tasks;
+mod telemetry;
+mod templates;
+mod theme;
+mod thumbnails;
+mod timer;
+#[cfg(desktop)]
+mod titlebar;
+#[cfg(desktop)]
+mod tray;
+mod undo;
+mod units;
+#[cfg(desktop)]
+mod updater;
+mod uploads;
+mod versions;
+mod wake_lock;
+mod watched_folders;
+mod weather;
+mod window_effects;
+mod window_state;
+mod windows;
+mod workspaces;
+mod zoom;
+
 use tauri::Manager;
+use tauri_plugin_deep_link::DeepLinkExt;
+
+use crate::error::AppResult;
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
-fn greet(name: &str) -> String {
-    format!("Hello, {}! You've been greeted from Rust!", name)
+fn greet(name: &str) -> AppResult<String> {
+    Ok(format!("Hello, {}! You've been greeted from Rust!", name))
 }
 
 /// Build the prevent-default plugin.
@@ -27,12 +168,386 @@ fn prevent_default() -> tauri::plugin::TauriPlugin<tauri::Wry> {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    #[cfg(debug_assertions)]
-    let builder = tauri::Builder::default().plugin(tauri_plugin_devtools::init());
+    #[cfg(desktop)]
+    if crash::run_monitor_if_requested() {
+        return;
+    }
 
-    #[cfg(not(debug_assertions))]
     let builder = tauri::Builder::default();
 
+    // Must be the first plugin so a second launch exits before anything else initializes
+    #[cfg(desktop)]
+    let builder = builder.plugin(single_instance::plugin());
+
+    #[cfg(desktop)]
+    let builder = builder.plugin(shortcuts::plugin());
+
+    #[cfg(mobile)]
+    let builder = builder.plugin(tauri_plugin_biometric::init());
+    #[cfg(mobile)]
+    let builder = builder.plugin(mobile::init());
+
+    #[cfg(debug_assertions)]
+    let builder = builder.plugin(tauri_plugin_devtools::init());
+
+    let builder = builder.register_asynchronous_uri_scheme_protocol(
+        attachment_protocol::SCHEME,
+        attachment_protocol::handle,
+    );
+    let builder = builder.register_asynchronous_uri_scheme_protocol(maps::SCHEME, maps::handle);
+
+    let handler: fn(tauri::ipc::Invoke) -> bool = tauri::generate_handler![
+        greet,
+        analysis::analyze_truss,
+        archive::export_project_archive,
+        archive::import_project_archive,
+        attachments::attachment_add,
+        attachments::list_attachments,
+        attachments::attachment_remove,
+        attachments::attachment_path,
+        attachments::attachment_open,
+        audit::query_audit_log,
+        audit::export_audit_log,
+        autosave::autosave_snapshot,
+        autosave::autosave_flush,
+        autosave::autosave_discard,
+        autosave::recover_unsaved_work,
+        backup::get_backup_settings,
+        backup::set_backup_settings,
+        backup::backup_now,
+        backup::list_cloud_backups,
+        backup::local::list_local_backups,
+        backup::restore::restore_backup,
+        badge::set_badge_count,
+        badge::get_badge,
+        auth::start_oauth_login,
+        auth::get_auth_status,
+        auth::sign_out,
+        biometric::get_biometric_status,
+        biometric::biometric_unlock,
+        biometric::set_unlock_pin,
+        biometric::unlock_with_pin,
+        calc::recalculate_estimate,
+        calc::set_cost_structure,
+        calc::get_cost_adjustments,
+        calc::set_cost_adjustments,
+        catalog::sync_catalog,
+        catalog::get_catalog_status,
+        catalog::search_catalog,
+        catalog::get_catalog_item,
+        clipboard::paste_from_clipboard,
+        clipboard_watch::set_clipboard_watch,
+        clipboard_watch::get_clipboard_watch,
+        config::get_config,
+        config::set_config,
+        crash::get_pending_crash_reports,
+        crash::upload_crash_reports,
+        crash::dismiss_crash_reports,
+        db::db_query,
+        db::db_execute,
+        #[cfg(debug_assertions)]
+        debug_console::debug_sql,
+        #[cfg(debug_assertions)]
+        debug_console::debug_dump_state,
+        db::migrations::get_schema_version,
+        db::paging::query_page,
+        db::projects::list_projects,
+        db::projects::get_project,
+        db::projects::create_project,
+        db::projects::update_project,
+        db::projects::delete_project,
+        db::projects::list_items,
+        db::projects::save_items,
+        db::projects::delete_items,
+        deeplink::deeplink_ready,
+        splash::frontend_ready,
+        streams::stream_ack,
+        streams::stream_cancel,
+        diagnostics::export_diagnostics,
+        system_info::get_system_info,
+        downloads::download_choose_destination,
+        downloads::download_enqueue,
+        downloads::download_pause,
+        downloads::download_resume,
+        downloads::download_cancel,
+        downloads::list_downloads,
+        #[cfg(desktop)]
+        drag_out::start_native_drag,
+        dxf::dxf_import,
+        email::email_report,
+        email::get_email_settings,
+        email::set_email_settings,
+        encryption::get_encryption_status,
+        encryption::set_encryption_passphrase,
+        encryption::unlock_database,
+        encryption::unlock_with_recovery_key,
+        env::get_environment,
+        export::dataset::export_dataset,
+        export::dataset::cancel_dataset_export,
+        export::dataset::stream_dataset,
+        export::ics::export_ics,
+        export::ics::add_to_calendar,
+        export::xlsx::export_xlsx,
+        export::view::capture_view,
+        file_drop::set_drop_target_project,
+        file_open::take_pending_project_files,
+        flags::get_flags,
+        flags::refresh_flags,
+        extensions::inspect_extension,
+        extensions::install_extension,
+        extensions::list_extensions,
+        extensions::set_extension_enabled,
+        extensions::remove_extension,
+        extensions::run_extension_menu_item,
+        extensions::import_with_extension,
+        #[cfg(desktop)]
+        external_edit::edit_externally,
+        #[cfg(desktop)]
+        external_edit::list_external_edits,
+        #[cfg(desktop)]
+        external_edit::stop_editing_externally,
+        #[cfg(desktop)]
+        external_tools::list_external_tools,
+        #[cfg(desktop)]
+        external_tools::run_external_tool,
+        fonts::list_system_fonts,
+        fonts::validate_font,
+        formulas::validate_formula,
+        formulas::evaluate_formula,
+        formulas::evaluate_item_formula,
+        http_cache::cached_fetch,
+        http_cache::list_http_cache,
+        http_cache::purge_http_cache,
+        http_client::api_request,
+        ifc::import_ifc,
+        ifc::list_ifc_imports,
+        ifc::get_ifc_import,
+        ifc::delete_ifc_import,
+        idle_lock::lock_session,
+        idle_lock::get_session_locked,
+        integrity::hash_file,
+        integrity::verify_attachment,
+        integrity::scan_attachment_integrity,
+        integrity::redownload_attachment,
+        import::csv::import_csv,
+        import::xlsx::preview_xlsx,
+        import::xlsx::import_xlsx,
+        importers::detect_estimate_format,
+        importers::preview_estimate_import,
+        importers::import_estimate,
+        jobs::list_jobs,
+        jobs::run_job_now,
+        jobs::schedule_job,
+        jobs::pause_job,
+        jobs::resume_job,
+        lan_sync::start_lan_sync,
+        lan_sync::stop_lan_sync,
+        lan_sync::get_lan_sync_status,
+        lan_sync::send_project_to_peer,
+        licensing::get_license_status,
+        licensing::activate_license,
+        licensing::deactivate_license,
+        locale::get_locale_info,
+        location::get_current_location,
+        location::tag_location,
+        location::get_location_tags,
+        location::remove_location_tag,
+        maps::prefetch_project_map,
+        weather::get_weather,
+        log_stream::subscribe_logs,
+        log_stream::unsubscribe_logs,
+        logging::get_recent_logs,
+        metrics::get_command_metrics,
+        metrics::set_metrics_overlay,
+        resources::get_resource_usage,
+        #[cfg(desktop)]
+        menu::set_menu_item_enabled,
+        network::get_connectivity,
+        notifications::send_notification,
+        ocr::ocr_list_packs,
+        ocr::ocr_install_pack,
+        ocr::ocr_remove_pack,
+        ocr::ocr_extract_text,
+        outbox::outbox_enqueue,
+        outbox::list_outbox,
+        outbox::cancel_outbox_operation,
+        outbox::retry_outbox_operation,
+        pdf::pdf_page_count,
+        pdf::pdf_metadata,
+        pdf::render::pdf_render_page,
+        pdf::render::pdf_stream_page,
+        pdf::pages::pdf_merge,
+        pdf::pages::pdf_extract_pages,
+        pdf::pages::pdf_split,
+        photos::capture_photo,
+        portable::get_app_paths,
+        power::get_power_status,
+        background::set_background_policy,
+        background::get_background_status,
+        print::list_printers,
+        print::print,
+        print::print_to_pdf,
+        progress::set_progress,
+        project_file::project_open,
+        project_file::project_save,
+        project_file::project_save_as,
+        project_file::project_close,
+        proxy::get_proxy_settings,
+        proxy::detect_system_proxy,
+        proxy::set_proxy_settings,
+        push::enable_push_notifications,
+        push::disable_push_notifications,
+        push::get_push_status,
+        qr::generate_qr,
+        #[cfg(desktop)]
+        quick_capture::quick_capture_done,
+        #[cfg(desktop)]
+        quick_capture::quick_capture_dismiss,
+        rbac::get_permissions,
+        realtime::realtime_connect,
+        realtime::realtime_disconnect,
+        realtime::realtime_send,
+        realtime::topics::realtime_subscribe,
+        realtime::topics::realtime_unsubscribe,
+        realtime::get_realtime_status,
+        recent_projects::get_recent_projects,
+        recent_projects::clear_recent_projects,
+        relocate::relocate_data_dir,
+        reminders::reminder_create,
+        reminders::reminder_update,
+        reminders::reminder_delete,
+        reminders::list_reminders,
+        reports::generate_report_pdf,
+        reports::daily_log::generate_daily_log_pdf,
+        scan::scan_code,
+        search::search_index_document,
+        search::search_query,
+        search::search_rebuild,
+        secrets::secret_set,
+        secrets::secret_get,
+        secrets::secret_delete,
+        shutdown::respond_close_request,
+        shutdown::close_anyway,
+        shutdown::quit_app,
+        share::start_sharing,
+        share::stop_sharing,
+        share::get_share_status,
+        share_sheet::share_file,
+        share_sheet::take_shared_files,
+        share_sheet::import_shared_files,
+        share_sheet::discard_shared_files,
+        signing::sign_document,
+        signing::verify_document,
+        signing::get_signing_identity,
+        spellcheck::get_dictionary,
+        spellcheck::add_dictionary_words,
+        spellcheck::remove_dictionary_words,
+        #[cfg(desktop)]
+        shortcuts::set_global_shortcut,
+        #[cfg(desktop)]
+        shortcuts::clear_global_shortcut,
+        takeoff::calibrate_plan_page,
+        takeoff::get_plan_calibration,
+        takeoff::measure_plan,
+        takeoff::save_takeoff_measurement,
+        takeoff::list_takeoff_measurements,
+        takeoff::delete_takeoff_measurement,
+        tasks::list_tasks,
+        tasks::cancel_task,
+        telemetry::track_event,
+        telemetry::get_telemetry_status,
+        telemetry::set_telemetry_enabled,
+        templates::list_document_templates,
+        templates::validate_document_template,
+        templates::save_document_template,
+        templates::delete_document_template,
+        templates::render_document_template,
+        templates::generate_document_pdf,
+        theme::get_system_theme,
+        thumbnails::get_thumbnail,
+        timer::timer_start,
+        timer::timer_pause,
+        timer::timer_resume,
+        timer::timer_stop,
+        timer::list_timers,
+        timer::take_timer_gaps,
+        #[cfg(desktop)]
+        titlebar::get_titlebar_info,
+        #[cfg(desktop)]
+        titlebar::window_minimize,
+        #[cfg(desktop)]
+        titlebar::window_maximize,
+        #[cfg(desktop)]
+        titlebar::window_unmaximize,
+        #[cfg(desktop)]
+        titlebar::window_toggle_maximize,
+        #[cfg(desktop)]
+        titlebar::window_close,
+        #[cfg(desktop)]
+        autostart::get_launch_at_login,
+        #[cfg(desktop)]
+        autostart::set_launch_at_login,
+        #[cfg(desktop)]
+        compact::set_compact_mode,
+        #[cfg(desktop)]
+        compact::get_compact_mode,
+        units::parse_quantity,
+        units::convert_quantity,
+        units::list_units,
+        units::board_feet,
+        #[cfg(desktop)]
+        updater::get_update_channel,
+        #[cfg(desktop)]
+        updater::set_update_channel,
+        #[cfg(desktop)]
+        updater::check_for_updates,
+        #[cfg(desktop)]
+        updater::install_update,
+        #[cfg(desktop)]
+        updater::rollback_update,
+        uploads::upload_start,
+        uploads::upload_pause,
+        uploads::upload_resume,
+        uploads::upload_cancel,
+        uploads::list_uploads,
+        sync::sync_now,
+        sync::get_sync_status,
+        versions::create_estimate_version,
+        versions::list_estimate_versions,
+        versions::get_estimate_version,
+        versions::diff_estimate_versions,
+        versions::delete_estimate_version,
+        undo::undo,
+        undo::redo,
+        undo::get_undo_stack,
+        wake_lock::acquire_wake_lock,
+        wake_lock::release_wake_lock,
+        wake_lock::list_wake_locks,
+        watched_folders::get_watched_folders,
+        watched_folders::set_watched_folders,
+        watched_folders::set_watch_target_project,
+        windows::open_secondary_window,
+        windows::close_window,
+        #[cfg(desktop)]
+        displays::list_monitors,
+        #[cfg(desktop)]
+        displays::move_window_to_monitor,
+        #[cfg(desktop)]
+        presentation::enter_presentation_mode,
+        #[cfg(desktop)]
+        presentation::exit_presentation_mode,
+        #[cfg(desktop)]
+        presentation::get_presentation,
+        window_effects::get_window_effects_support,
+        window_effects::set_window_effect,
+        window_effects::set_window_corners,
+        workspaces::list_workspaces,
+        workspaces::create_workspace,
+        workspaces::switch_workspace,
+        zoom::set_zoom,
+        zoom::get_zoom,
+    ];
+
     builder
         .plugin(prevent_default())
         .plugin(tauri_plugin_opener::init())
@@ -41,11 +556,178 @@ pub fn run() {
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_os::init())
-        .invoke_handler(tauri::generate_handler![greet])
+        .plugin(tauri_plugin_dialog::init())
+        // Every command passes the lock and role checks before it runs; a denied invoke is
+        // already answered
+        .invoke_handler(
+            move |invoke| match idle_lock::guard(invoke).and_then(rbac::authorize) {
+                Some(invoke) => handler(invoke),
+                None => true,
+            },
+        )
+        .on_window_event(|window, event| {
+            window_state::on_window_event(window, event);
+            autosave::on_window_event(window, event);
+            file_drop::on_window_event(window, event);
+            log_stream::on_window_event(window, event);
+            realtime::topics::on_window_event(window, event);
+            progress::on_window_event(window, event);
+            theme::on_window_event(window, event);
+            locale::on_window_event(window, event);
+            #[cfg(desktop)]
+            presentation::on_window_event(window, event);
+            shutdown::on_window_event(window, event);
+            wake_lock::on_window_event(window, event);
+            #[cfg(desktop)]
+            tray::on_window_event(window, event);
+            #[cfg(desktop)]
+            titlebar::on_window_event(window, event);
+            #[cfg(desktop)]
+            compact::on_window_event(window, event);
+        })
         .setup(|app| {
+            // Picks the data dir and keychain scope, so it comes before anything reads either
+            let workspaces = workspaces::init(app.handle())?;
+            let state = state::AppState::load(app.handle(), &workspaces)?;
+            app.manage(logging::init(state.data_dir())?);
+            tracing::info!(environment = ?env::current(), "backend environment");
+            #[cfg(desktop)]
+            let start_hidden = autostart::start_hidden(&state.config());
+            #[cfg(mobile)]
+            let start_hidden = false;
+            // First window on screen, before migrations and state loading delay the main one
+            #[cfg(desktop)]
+            if !start_hidden {
+                splash::show(app.handle());
+            }
+            let data_dir = state.data_dir().to_path_buf();
+            tauri::async_runtime::spawn_blocking(move || relocate::finish(&data_dir));
+            app.manage(crash::init(state.data_dir())?);
+            // Before the main window exists, so the frontend only ever reads current shapes
+            store_migrations::run(app.handle())?;
+            // A broken proxy setting must not keep the app from starting
+            let proxy = proxy::resolve(&state.config().proxy).unwrap_or_else(|err| {
+                tracing::warn!(%err, "ignoring proxy settings");
+                proxy::ResolvedProxy::default()
+            });
+            app.manage(http_client::HttpClient::new(&state.config(), &proxy)?);
+            app.manage(proxy::ProxyState::new(proxy));
+            let encryption = encryption::init(state.data_dir());
+            let db = db::init(state.data_dir(), encryption.is_enabled())?;
+            let scheduler = jobs::Scheduler::new(db.clone());
+            app.manage(search::init(state.data_dir())?);
+            app.manage(http_cache::init(state.data_dir())?);
+            app.manage(attachments::init(
+                state.data_dir(),
+                encryption.key_handle(),
+            )?);
+            app.manage(encryption);
+            attachments::register_jobs(&scheduler);
+            integrity::register_jobs(&scheduler);
+            backup::register_jobs(&scheduler);
+            backup::local::register_jobs(&scheduler);
+            app.manage(autosave::init(state.data_dir())?);
+            app.manage(pdf::init(app.handle(), state.data_dir())?);
+            app.manage(thumbnails::init(state.data_dir())?);
+            app.manage(maps::init(state.data_dir())?);
+            maps::register_jobs(&scheduler);
+            weather::register_jobs(&scheduler);
+            app.manage(ocr::init(state.data_dir())?);
+            app.manage(extensions::init(state.data_dir())?);
+            search::register_jobs(&scheduler);
+            app.manage(sync::SyncEngine::new(db.clone()));
+            sync::register_jobs(&scheduler);
+            app.manage(catalog::Catalog::new(db.clone()));
+            catalog::register_jobs(&scheduler);
+            app.manage(network::NetworkMonitor::default());
+            app.manage(outbox::Outbox::new(db.clone()));
+            outbox::register_jobs(&scheduler);
+            photos::register_jobs(&scheduler);
+            reminders::register_jobs(&scheduler);
+            badge::register_jobs(&scheduler);
+            app.manage(telemetry::init(state.data_dir())?);
+            telemetry::register_jobs(&scheduler);
+            app.manage(flags::init(state.data_dir(), state.config_path())?);
+            flags::register_jobs(&scheduler);
+            app.manage(spellcheck::init(state.data_dir(), state.config_path())?);
+            app.manage(licensing::init(state.config_path())?);
+            licensing::register_jobs(&scheduler);
+            app.manage(downloads::Downloads::new(db.clone()));
+            app.manage(uploads::Uploads::new(db.clone()));
+            app.manage(streams::Streams::default());
+            app.manage(reminders::Reminders::new(db.clone()));
+            app.manage(timer::Timers::new(db.clone()));
+            app.manage(db);
+            app.manage(scheduler);
+            #[cfg(desktop)]
+            app.manage(updater::UpdaterState::load(state.data_dir()));
+
+            // The main window starts hidden so restored geometry never flashes at the default size
+            let window_state = window_state::WindowStateStore::load(state.data_dir());
+            let window = windows::create_main_window(app.handle())?;
+            window_state.restore(&window);
+            // Desktop keeps it behind the splash until the frontend reports ready
+            #[cfg(mobile)]
+            window.show()?;
+            splash::await_frontend(app.handle(), !start_hidden);
+            app.manage(window_state);
+            #[cfg(desktop)]
+            app.manage(titlebar::TitlebarState::default());
+            #[cfg(desktop)]
+            app.manage(compact::CompactMode::default());
+            #[cfg(desktop)]
+            app.manage(presentation::Presentation::default());
+            app.manage(recent_projects::RecentProjects::load(state.data_dir()));
+            app.manage(state);
+            app.manage(workspaces);
+            app.manage(config::watch(app.handle())?);
+            app.manage(auth::AuthState::default());
+            app.manage(idle_lock::SessionLock::default());
+            app.manage(deeplink::DeepLinkState::default());
+            app.manage(file_open::FileOpenState::default());
+            app.manage(file_drop::DropTargets::default());
+            app.manage(project_file::ProjectFiles::default());
+            app.manage(windows::WindowRegistry::default());
+            app.manage(watched_folders::WatchedFolders::default());
+            app.manage(shutdown::Shutdown::default());
+            app.manage(wake_lock::WakeLocks::default());
+            app.manage(progress::TaskbarProgress::default());
+            app.manage(badge::Badge::default());
+            app.manage(clipboard_watch::ClipboardWatch::default());
+            app.manage(share::Shares::default());
+            app.manage(share_sheet::init(app.handle()));
+            push::init(app.handle());
+            app.manage(lan_sync::LanSync::default());
+            app.manage(realtime::Realtime::default());
+            app.manage(backup::Backups::default());
+            app.manage(notifications::init(app.handle()));
+            app.manage(theme::init(app.handle()));
+            app.manage(locale::init());
+            #[cfg(desktop)]
+            app.manage(menu::init(app.handle())?);
+            #[cfg(desktop)]
+            tray::init(app.handle())?;
+            #[cfg(desktop)]
+            app.manage(shortcuts::init(app.handle()));
+
+            // Windows and Linux pass double-clicked files as launch arguments
+            let cwd = std::env::current_dir().unwrap_or_default();
+            file_open::open_paths(
+                app.handle(),
+                file_open::project_paths_from_args(std::env::args(), &cwd),
+            );
+
+            // Links that launched the app are queued like any other until the router mounts
+            if let Ok(Some(urls)) = app.deep_link().get_current() {
+                deeplink::handle_urls(app.handle(), urls);
+            }
+            let handle = app.handle().clone();
+            app.deep_link()
+                .on_open_url(move |event| deeplink::handle_urls(&handle, event.urls()));
+
             #[cfg(debug_assertions)]
             {
-                if let Some(window) = app.get_webview_window("main") {
+                if let Some(window) = app.get_webview_window(windows::MAIN_WINDOW) {
                     window.open_devtools();
                 }
             }
@@ -55,8 +737,61 @@ pub fn run() {
             app.handle()
                 .plugin(tauri_plugin_updater::Builder::new().build())?;
 
+            #[cfg(desktop)]
+            autostart::refresh(app.handle());
+            jobs::start(app.handle());
+            metrics::start(app.handle());
+            resources::start(app.handle());
+            power::start(app.handle());
+            network::start(app.handle());
+            spellcheck::start(app.handle());
+            licensing::start(app.handle());
+            #[cfg(desktop)]
+            displays::start(app.handle());
+            downloads::resume_interrupted(app.handle());
+            uploads::resume_interrupted(app.handle());
+            watched_folders::start(app.handle());
+            #[cfg(desktop)]
+            external_edit::start(app.handle());
+            auth::start(app.handle());
+            autosave::start(app.handle());
+            idle_lock::start(app.handle());
+            background::start(app.handle());
+            timer::start(app.handle());
+            extensions::start(app.handle());
+
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| match event {
+            tauri::RunEvent::ExitRequested { api, code, .. } => {
+                shutdown::on_exit_requested(app, &api, code);
+            }
+            tauri::RunEvent::Exit => {
+                if let Some(autosave) = app.try_state::<autosave::Autosave>() {
+                    autosave.flush_all();
+                }
+                window_state::save_all(app);
+                #[cfg(desktop)]
+                if let Some(edits) = app.try_state::<external_edit::ExternalEdits>() {
+                    edits.stop_all();
+                }
+                telemetry::flush_on_exit(app);
+                #[cfg(desktop)]
+                updater::install_staged(app);
+            }
+            // Clicking the dock icon brings back a window hidden to the tray
+            #[cfg(target_os = "macos")]
+            tauri::RunEvent::Reopen { .. } => windows::show_main_window(app),
+            // macOS delivers double-clicked files as an event instead of argv
+            #[cfg(any(target_os = "macos", target_os = "ios"))]
+            tauri::RunEvent::Opened { urls } => {
+                let paths: Vec<_> = urls.iter().filter_map(|u| u.to_file_path().ok()).collect();
+                #[cfg(target_os = "ios")]
+                share_sheet::receive_opened(app, &paths);
+                file_open::open_paths(app, paths);
+            }
+            _ => {}
+        });
 }
@@ -0,0 +1,461 @@
+//! License activation with signed offline tokens.
+//!
+//! Activating a key trades it with the license server for a token: the license claims plus
+//! an Ed25519 signature by the server. The claims name this machine's fingerprint, so copying
+//! `license.json` elsewhere does not carry the license along. The token is verified locally
+//! on every launch and refreshed in the background; while the server cannot be reached the
+//! license keeps working for the configured grace period after the last successful check,
+//! dated by the `issuedAt` the server signs into each token it issues.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_http::reqwest::{Response, StatusCode};
+
+use crate::config;
+use crate::db::now_ms;
+use crate::error::{AppError, AppResult};
+use crate::http_client::{HttpClient, RetryPolicy};
+use crate::jobs::Scheduler;
+use crate::network::NetworkMonitor;
+use crate::state::AppState;
+
+/// Emitted with the new [`LicenseStatus`] whenever the license is activated, refreshed,
+/// revoked, or deactivated.
+pub const LICENSE_CHANGED_EVENT: &str = "license:changed";
+/// Job kind that re-validates the token with the license server.
+pub const REFRESH_JOB: &str = "license.refresh";
+const REFRESH_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+
+const LICENSE_FILE_NAME: &str = "license.json";
+const ACTIVATE_PATH: &str = "/v1/licenses/activate";
+const REFRESH_PATH: &str = "/v1/licenses/refresh";
+const DEACTIVATE_PATH: &str = "/v1/licenses/deactivate";
+// Base64 of the server's raw Ed25519 public key, injected by the release pipeline
+const SERVER_PUBLIC_KEY: Option<&str> = option_env!("MOMENTUM_LICENSE_PUBLIC_KEY");
+// Bound into every signature so no other server-signed payload passes as a license
+const TOKEN_CONTEXT: &[u8] = b"momentum-license-v1\0";
+const MACHINE_CONTEXT: &[u8] = b"momentum-machine-v1\0";
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+const MAX_GRACE_DAYS: u32 = 30;
+
+/// How long a license keeps working without reaching the license server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LicenseSettings {
+    /// Days after the last successful check, capped at 30.
+    pub offline_grace_days: u32,
+}
+
+impl Default for LicenseSettings {
+    fn default() -> Self {
+        Self {
+            offline_grace_days: 14,
+        }
+    }
+}
+
+/// What the license server signed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LicenseClaims {
+    pub license_id: String,
+    pub customer: String,
+    pub plan: String,
+    /// Fingerprint of the machine the token was issued to.
+    pub machine: String,
+    /// When the server signed the token; every refresh issues a new one.
+    pub issued_at: i64,
+    /// `None` for a perpetual license.
+    pub expires_at: Option<i64>,
+}
+
+/// Where the license stands on this machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LicenseState {
+    /// No license has been activated.
+    Unlicensed,
+    /// Checked with the server within the last day.
+    Active,
+    /// Not checked recently, but still within the offline grace period.
+    Grace,
+    /// The grace period ran out before the server could be reached.
+    Lapsed,
+    /// The license itself has expired.
+    Expired,
+    /// The stored token fails verification or belongs to another machine.
+    Invalid,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LicenseStatus {
+    pub state: LicenseState,
+    pub license: Option<LicenseClaims>,
+    /// Unix milliseconds of the last successful check with the server.
+    pub validated_at: Option<i64>,
+    pub grace_ends_at: Option<i64>,
+    /// This machine's fingerprint, for support to match against activations.
+    pub machine: String,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Stored {
+    token: Option<String>,
+    /// Random id standing in for the machine id where the OS does not provide one.
+    install_id: String,
+    /// Latest time seen on this machine, so winding the clock back does not extend grace.
+    last_seen_at: i64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    token: String,
+}
+
+/// License state registered in app state.
+pub struct Licensing {
+    path: PathBuf,
+    machine: String,
+    stored: Mutex<Stored>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn fingerprint(install_id: &str) -> String {
+    let id = os::machine_id().unwrap_or_else(|| install_id.to_string());
+    let mut hasher = Sha256::new();
+    hasher.update(MACHINE_CONTEXT);
+    hasher.update(id.trim().as_bytes());
+    hex(&hasher.finalize())
+}
+
+/// Check the signature and machine binding of `token` and return its claims.
+fn verify(token: &str, machine: &str) -> Result<LicenseClaims, String> {
+    let key = SERVER_PUBLIC_KEY
+        .and_then(|key| STANDARD.decode(key).ok())
+        .ok_or("this build cannot verify licenses")?;
+    let (claims, signature) = token
+        .split_once('.')
+        .ok_or("the license token is malformed")?;
+    let claims = URL_SAFE_NO_PAD
+        .decode(claims)
+        .map_err(|_| "the license token is malformed")?;
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| "the license token is malformed")?;
+    let message = [TOKEN_CONTEXT, claims.as_slice()].concat();
+    UnparsedPublicKey::new(&ED25519, &key)
+        .verify(&message, &signature)
+        .map_err(|_| "the license signature does not verify")?;
+    let claims: LicenseClaims =
+        serde_json::from_slice(&claims).map_err(|_| "the license token is malformed")?;
+    if claims.machine != machine {
+        return Err("the license was activated on a different machine".into());
+    }
+    Ok(claims)
+}
+
+/// Load the stored token, creating the install id on first launch.
+pub fn init(config_path: &Path) -> AppResult<Licensing> {
+    let path = config_path.with_file_name(LICENSE_FILE_NAME);
+    let mut stored: Stored = fs::read(&path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default();
+    if stored.install_id.is_empty() {
+        stored.install_id = uuid::Uuid::new_v4().to_string();
+    }
+    stored.last_seen_at = stored.last_seen_at.max(now_ms());
+    let licensing = Licensing {
+        machine: fingerprint(&stored.install_id),
+        path,
+        stored: Mutex::new(stored.clone()),
+    };
+    licensing.save(&stored)?;
+    Ok(licensing)
+}
+
+impl Licensing {
+    fn save(&self, stored: &Stored) -> AppResult<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(stored)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn update(&self, change: impl FnOnce(&mut Stored)) -> AppResult<()> {
+        let mut stored = self.stored.lock().unwrap_or_else(|e| e.into_inner());
+        change(&mut stored);
+        stored.last_seen_at = stored.last_seen_at.max(now_ms());
+        self.save(&stored)
+    }
+
+    fn token(&self) -> Option<String> {
+        self.stored
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .token
+            .clone()
+    }
+
+    pub fn status(&self, settings: &LicenseSettings) -> LicenseStatus {
+        let stored = self
+            .stored
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let mut status = LicenseStatus {
+            state: LicenseState::Unlicensed,
+            license: None,
+            validated_at: None,
+            grace_ends_at: None,
+            machine: self.machine.clone(),
+            message: None,
+        };
+        let Some(token) = stored.token else {
+            return status;
+        };
+        let claims = match verify(&token, &self.machine) {
+            Ok(claims) => claims,
+            Err(message) => {
+                status.state = LicenseState::Invalid;
+                status.message = Some(message);
+                return status;
+            }
+        };
+        let now = now_ms().max(stored.last_seen_at);
+        // Only the signed time counts, and never one ahead of the clock
+        let validated_at = claims.issued_at.min(now);
+        let grace_days = settings.offline_grace_days.min(MAX_GRACE_DAYS);
+        let grace_ends_at = validated_at + i64::from(grace_days) * DAY_MS;
+        status.state = if claims.expires_at.is_some_and(|at| now >= at) {
+            LicenseState::Expired
+        } else if now < validated_at + DAY_MS {
+            LicenseState::Active
+        } else if now < grace_ends_at {
+            LicenseState::Grace
+        } else {
+            LicenseState::Lapsed
+        };
+        status.validated_at = Some(validated_at);
+        status.grace_ends_at = Some(grace_ends_at);
+        status.license = Some(claims);
+        status
+    }
+
+    fn notify(&self, app: &AppHandle) -> LicenseStatus {
+        let status = self.status(&app.state::<AppState>().config().license);
+        let _ = app.emit(LICENSE_CHANGED_EVENT, &status);
+        status
+    }
+
+    /// Trade the stored token for a fresh one; a revoked license is forgotten.
+    pub async fn refresh(&self, app: &AppHandle) -> AppResult<()> {
+        let Some(token) = self.token() else {
+            return Ok(());
+        };
+        // Offline is what the grace period is for
+        if !app.state::<NetworkMonitor>().is_online() {
+            return self.update(|_| {});
+        }
+        let request = app
+            .state::<HttpClient>()
+            .client()
+            .post(config::api_url(app, REFRESH_PATH)?)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_vec(&serde_json::json!({
+                "token": token,
+                "machine": self.machine,
+            }))?);
+        let response = app
+            .state::<HttpClient>()
+            .send(request, RetryPolicy::default())
+            .await?;
+        if matches!(
+            response.status(),
+            StatusCode::FORBIDDEN | StatusCode::NOT_FOUND | StatusCode::GONE
+        ) {
+            tracing::warn!(status = %response.status(), "license revoked by the server");
+            self.update(|stored| stored.token = None)?;
+            self.notify(app);
+            return Ok(());
+        }
+        let fresh: TokenResponse =
+            serde_json::from_slice(&response.error_for_status()?.bytes().await?)?;
+        verify(&fresh.token, &self.machine).map_err(AppError::InvalidInput)?;
+        self.update(|stored| stored.token = Some(fresh.token))?;
+        self.notify(app);
+        Ok(())
+    }
+}
+
+// Turns a refusal into the server's own explanation, which names the actual problem
+async fn refusal(response: Response) -> AppResult<Response> {
+    let status = response.status();
+    if !status.is_client_error() {
+        return Ok(response.error_for_status()?);
+    }
+    let body = response.bytes().await.unwrap_or_default();
+    let message = serde_json::from_slice::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|v| v.get("message")?.as_str().map(str::to_string))
+        .unwrap_or_else(|| format!("the license server refused the request ({status})"));
+    Err(AppError::InvalidInput(message))
+}
+
+pub fn start(app: &AppHandle) {
+    let status = app
+        .state::<Licensing>()
+        .status(&app.state::<AppState>().config().license);
+    tracing::info!(state = ?status.state, "license checked");
+}
+
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(REFRESH_JOB, Some(REFRESH_INTERVAL), |app| async move {
+        app.state::<Licensing>().refresh(&app).await
+    });
+}
+
+#[tauri::command]
+pub async fn get_license_status(
+    state: State<'_, AppState>,
+    licensing: State<'_, Licensing>,
+) -> AppResult<LicenseStatus> {
+    Ok(licensing.status(&state.config().license))
+}
+
+/// Activate `license_key` on this machine, replacing any license already activated.
+#[tauri::command]
+pub async fn activate_license(
+    app: AppHandle,
+    licensing: State<'_, Licensing>,
+    license_key: String,
+) -> AppResult<LicenseStatus> {
+    let license_key = license_key.trim();
+    if license_key.is_empty() {
+        return Err(AppError::InvalidInput("license key is empty".into()));
+    }
+    let http = app.state::<HttpClient>();
+    let request = http
+        .client()
+        .post(config::api_url(&app, ACTIVATE_PATH)?)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&serde_json::json!({
+            "licenseKey": license_key,
+            "machine": licensing.machine,
+            "hostname": tauri_plugin_os::hostname(),
+            "platform": tauri_plugin_os::platform(),
+            "appVersion": env!("CARGO_PKG_VERSION"),
+        }))?);
+    let response = refusal(http.send(request, RetryPolicy::default()).await?).await?;
+    let issued: TokenResponse = serde_json::from_slice(&response.bytes().await?)?;
+    let claims = verify(&issued.token, &licensing.machine).map_err(AppError::InvalidInput)?;
+    licensing.update(|stored| stored.token = Some(issued.token))?;
+    tracing::info!(license = %claims.license_id, "license activated");
+    Ok(licensing.notify(&app))
+}
+
+/// Release this machine's activation so the seat can be used elsewhere.
+#[tauri::command]
+pub async fn deactivate_license(
+    app: AppHandle,
+    licensing: State<'_, Licensing>,
+) -> AppResult<LicenseStatus> {
+    let Some(token) = licensing.token() else {
+        return Ok(licensing.notify(&app));
+    };
+    let http = app.state::<HttpClient>();
+    let request = http
+        .client()
+        .post(config::api_url(&app, DEACTIVATE_PATH)?)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&serde_json::json!({ "token": token }))?);
+    let response = http.send(request, RetryPolicy::default()).await?;
+    // An activation the server no longer knows about is already released
+    if !matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::GONE) {
+        refusal(response).await?;
+    }
+    licensing.update(|stored| stored.token = None)?;
+    tracing::info!("license deactivated");
+    Ok(licensing.notify(&app))
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    pub fn machine_id() -> Option<String> {
+        ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+            .iter()
+            .find_map(|path| std::fs::read_to_string(path).ok())
+            .filter(|id| !id.trim().is_empty())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use std::process::Command;
+
+    pub fn machine_id() -> Option<String> {
+        let output = Command::new("/usr/sbin/ioreg")
+            .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find(|line| line.contains("\"IOPlatformUUID\""))
+            .and_then(|line| line.rsplit('"').nth(1))
+            .map(str::to_string)
+    }
+}
+
+#[cfg(windows)]
+mod os {
+    use windows::core::w;
+    use windows::Win32::System::Registry::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_SZ};
+
+    pub fn machine_id() -> Option<String> {
+        let mut buf = [0u16; 64];
+        let mut len = std::mem::size_of_val(&buf) as u32;
+        // SAFETY: `buf` is writable for `len` bytes
+        let status = unsafe {
+            RegGetValueW(
+                HKEY_LOCAL_MACHINE,
+                w!(r"SOFTWARE\Microsoft\Cryptography"),
+                w!("MachineGuid"),
+                RRF_RT_REG_SZ,
+                None,
+                Some(buf.as_mut_ptr().cast()),
+                Some(&mut len),
+            )
+        };
+        if status.is_err() {
+            return None;
+        }
+        let end = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+        Some(String::from_utf16_lossy(&buf[..end]))
+    }
+}
+
+#[cfg(mobile)]
+mod os {
+    // Mobile platforms expose no stable machine id, so the install id stands in
+    pub fn machine_id() -> Option<String> {
+        None
+    }
+}
@@ -0,0 +1,312 @@
+//! The user's locale and the conventions that follow from it: first day of the week, number
+//! separators, and imperial or metric units.
+//!
+//! Defaults come from the locale's language and region using CLDR's conventions, then
+//! whatever the user customized in the OS wins: regional format settings on Windows and
+//! macOS, and the `LC_NUMERIC` and `LC_MEASUREMENT` categories on Linux, which may name a
+//! different locale than `LANG`. Like the theme, settings are rechecked when a window
+//! regains focus and `locale:changed` is emitted if anything moved.
+
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State, Window, WindowEvent};
+
+use crate::error::AppResult;
+
+/// Emitted with the new [`LocaleInfo`] when any of it changes.
+pub const LOCALE_CHANGED_EVENT: &str = "locale:changed";
+
+const FALLBACK_LOCALE: &str = "en-US";
+
+// CLDR weekData: regions whose week starts on Sunday or Saturday; everywhere else it is Monday
+const SUNDAY_FIRST: &[&str] = &[
+    "AG", "AS", "BD", "BR", "BS", "BT", "BW", "BZ", "CA", "CN", "CO", "DM", "DO", "ET", "GT", "GU",
+    "HK", "HN", "ID", "IL", "IN", "JM", "JP", "KE", "KH", "KR", "LA", "MH", "MM", "MO", "MT", "MX",
+    "MZ", "NI", "NP", "PA", "PE", "PH", "PK", "PR", "PT", "PY", "SA", "SG", "SV", "TH", "TT", "TW",
+    "UM", "US", "VE", "VI", "WS", "YE", "ZA", "ZW",
+];
+const SATURDAY_FIRST: &[&str] = &[
+    "AE", "AF", "BH", "DJ", "DZ", "EG", "IQ", "IR", "JO", "KW", "LY", "OM", "QA", "SD", "SY",
+];
+// The only regions still measuring in feet and pounds day to day
+const IMPERIAL: &[&str] = &["US", "LR", "MM"];
+// Languages writing 1,234.5; most others write 1.234,5 or 1 234,5
+const PERIOD_DECIMAL_LANGUAGES: &[&str] = &[
+    "en", "ja", "zh", "ko", "he", "th", "hi", "ms", "fil", "ga", "sw", "ta", "te", "bn", "ur", "ar",
+];
+// Spanish-speaking regions that follow the US convention
+const PERIOD_DECIMAL_REGIONS: &[&str] = &["MX", "GT", "HN", "NI", "PA", "SV", "DO", "PR"];
+// Languages grouping thousands with a space
+const SPACE_GROUPING_LANGUAGES: &[&str] = &[
+    "fr", "ru", "pl", "cs", "sk", "sv", "fi", "nb", "no", "uk", "hu", "bg", "lt", "lv", "et",
+];
+
+/// Day a week starts on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Weekday {
+    Monday,
+    Saturday,
+    Sunday,
+}
+
+/// Units estimates should default to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MeasurementSystem {
+    Imperial,
+    Metric,
+}
+
+/// What `get_locale_info` reports.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocaleInfo {
+    /// BCP 47 tag, e.g. `en-US` or `fr-CA`.
+    pub locale: String,
+    pub language: String,
+    pub region: Option<String>,
+    pub first_day_of_week: Weekday,
+    pub decimal_separator: String,
+    pub grouping_separator: String,
+    pub measurement_system: MeasurementSystem,
+}
+
+/// Conventions the user set in the OS, each overriding the locale's default.
+#[derive(Debug, Default)]
+struct Overrides {
+    first_day_of_week: Option<Weekday>,
+    decimal_separator: Option<String>,
+    grouping_separator: Option<String>,
+    measurement_system: Option<MeasurementSystem>,
+}
+
+/// Last locale reported, registered in app state.
+pub struct LocaleWatcher {
+    last: Mutex<Option<LocaleInfo>>,
+}
+
+// POSIX names like en_US.UTF-8@euro become en-US
+fn normalize(raw: &str) -> Option<String> {
+    let tag = raw.split(['.', '@']).next()?.replace('_', "-");
+    if tag.is_empty() || tag == "C" || tag == "POSIX" {
+        return None;
+    }
+    Some(tag)
+}
+
+// Language and region of a tag, skipping any script subtag as in zh-Hant-TW
+fn split(tag: &str) -> (String, Option<String>) {
+    let mut parts = tag.split('-');
+    let language = parts.next().unwrap_or("en").to_ascii_lowercase();
+    let region = parts
+        .find(|part| {
+            (part.len() == 2 && part.chars().all(|c| c.is_ascii_alphabetic()))
+                || (part.len() == 3 && part.chars().all(|c| c.is_ascii_digit()))
+        })
+        .map(str::to_ascii_uppercase);
+    (language, region)
+}
+
+fn first_day_for(region: Option<&str>) -> Weekday {
+    match region {
+        Some(region) if SUNDAY_FIRST.contains(&region) => Weekday::Sunday,
+        Some(region) if SATURDAY_FIRST.contains(&region) => Weekday::Saturday,
+        _ => Weekday::Monday,
+    }
+}
+
+fn measurement_for(region: Option<&str>) -> MeasurementSystem {
+    match region {
+        Some(region) if IMPERIAL.contains(&region) => MeasurementSystem::Imperial,
+        _ => MeasurementSystem::Metric,
+    }
+}
+
+fn separators_for(language: &str, region: Option<&str>) -> (&'static str, &'static str) {
+    let period = match region {
+        Some("ZA") => false,
+        Some(region) if PERIOD_DECIMAL_REGIONS.contains(&region) => true,
+        Some("CH" | "LI") if language == "de" || language == "it" => true,
+        _ => PERIOD_DECIMAL_LANGUAGES.contains(&language),
+    };
+    match (period, region) {
+        (true, Some("CH" | "LI")) => (".", "’"),
+        (true, _) => (".", ","),
+        (false, _) if SPACE_GROUPING_LANGUAGES.contains(&language) || region == Some("ZA") => {
+            (",", "\u{a0}")
+        }
+        (false, _) => (",", "."),
+    }
+}
+
+pub(crate) fn current() -> LocaleInfo {
+    let locale = tauri_plugin_os::locale()
+        .as_deref()
+        .and_then(normalize)
+        .unwrap_or_else(|| FALLBACK_LOCALE.to_string());
+    let (language, region) = split(&locale);
+    let (decimal, grouping) = separators_for(&language, region.as_deref());
+    let overrides = os::overrides();
+    LocaleInfo {
+        first_day_of_week: overrides
+            .first_day_of_week
+            .unwrap_or_else(|| first_day_for(region.as_deref())),
+        decimal_separator: overrides
+            .decimal_separator
+            .unwrap_or_else(|| decimal.to_string()),
+        grouping_separator: overrides
+            .grouping_separator
+            .unwrap_or_else(|| grouping.to_string()),
+        measurement_system: overrides
+            .measurement_system
+            .unwrap_or_else(|| measurement_for(region.as_deref())),
+        locale,
+        language,
+        region,
+    }
+}
+
+/// Reread the locale and tell the frontend if it changed.
+pub fn refresh(app: &AppHandle) {
+    let Some(watcher) = app.try_state::<LocaleWatcher>() else {
+        return;
+    };
+    let info = current();
+    let mut last = watcher.last.lock().unwrap_or_else(|e| e.into_inner());
+    if last.as_ref() == Some(&info) {
+        return;
+    }
+    tracing::info!(locale = %info.locale, "system locale changed");
+    *last = Some(info.clone());
+    drop(last);
+    let _ = app.emit(LOCALE_CHANGED_EVENT, info);
+}
+
+/// Read the locale once at startup as the baseline for change events.
+pub fn init() -> LocaleWatcher {
+    LocaleWatcher {
+        last: Mutex::new(Some(current())),
+    }
+}
+
+/// Recheck when a window regains focus, as after a visit to the OS settings.
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    if let WindowEvent::Focused(true) = event {
+        let app = window.app_handle().clone();
+        tauri::async_runtime::spawn_blocking(move || refresh(&app));
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use super::{measurement_for, normalize, separators_for, split, Overrides};
+
+    // The locale glibc would use for a category: LC_ALL, then the category, then LANG
+    fn category(name: &str) -> Option<String> {
+        ["LC_ALL", name, "LANG"]
+            .into_iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| normalize(&value))
+    }
+
+    pub(super) fn overrides() -> Overrides {
+        let measurement = category("LC_MEASUREMENT").map(|tag| {
+            let (_, region) = split(&tag);
+            measurement_for(region.as_deref())
+        });
+        let separators = category("LC_NUMERIC").map(|tag| {
+            let (language, region) = split(&tag);
+            separators_for(&language, region.as_deref())
+        });
+        Overrides {
+            first_day_of_week: None,
+            decimal_separator: separators.map(|(decimal, _)| decimal.to_string()),
+            grouping_separator: separators.map(|(_, grouping)| grouping.to_string()),
+            measurement_system: measurement,
+        }
+    }
+}
+
+#[cfg(windows)]
+mod os {
+    use windows::core::PCWSTR;
+    use windows::Win32::Globalization::{
+        GetLocaleInfoEx, LOCALE_IFIRSTDAYOFWEEK, LOCALE_IMEASURE, LOCALE_SDECIMAL, LOCALE_STHOUSAND,
+    };
+
+    use super::{MeasurementSystem, Overrides, Weekday};
+
+    // The user's own regional format settings, not just their locale's defaults
+    fn info(kind: u32) -> Option<String> {
+        let mut buffer = [0u16; 16];
+        let len = unsafe { GetLocaleInfoEx(PCWSTR::null(), kind, Some(&mut buffer)) };
+        let len = usize::try_from(len).ok().filter(|len| *len > 0)?;
+        Some(String::from_utf16_lossy(&buffer[..len - 1]))
+    }
+
+    pub(super) fn overrides() -> Overrides {
+        Overrides {
+            // 0 is Monday through 6 for Sunday
+            first_day_of_week: match info(LOCALE_IFIRSTDAYOFWEEK).as_deref() {
+                Some("0") => Some(Weekday::Monday),
+                Some("5") => Some(Weekday::Saturday),
+                Some("6") => Some(Weekday::Sunday),
+                _ => None,
+            },
+            decimal_separator: info(LOCALE_SDECIMAL),
+            grouping_separator: info(LOCALE_STHOUSAND),
+            measurement_system: match info(LOCALE_IMEASURE).as_deref() {
+                Some("0") => Some(MeasurementSystem::Metric),
+                Some("1") => Some(MeasurementSystem::Imperial),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use objc2_foundation::{NSCalendar, NSLocale};
+
+    use super::{MeasurementSystem, Overrides, Weekday};
+
+    // The current locale carries the overrides from Language & Region settings
+    pub(super) fn overrides() -> Overrides {
+        let locale = NSLocale::currentLocale();
+        Overrides {
+            // 1 is Sunday through 7 for Saturday
+            first_day_of_week: match NSCalendar::currentCalendar().firstWeekday() {
+                1 => Some(Weekday::Sunday),
+                2 => Some(Weekday::Monday),
+                7 => Some(Weekday::Saturday),
+                _ => None,
+            },
+            decimal_separator: Some(locale.decimalSeparator().to_string()),
+            grouping_separator: Some(locale.groupingSeparator().to_string()),
+            measurement_system: Some(if locale.usesMetricSystem() {
+                MeasurementSystem::Metric
+            } else {
+                MeasurementSystem::Imperial
+            }),
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows, target_os = "macos")))]
+mod os {
+    use super::Overrides;
+
+    pub(super) fn overrides() -> Overrides {
+        Overrides::default()
+    }
+}
+
+#[tauri::command]
+pub async fn get_locale_info(watcher: State<'_, LocaleWatcher>) -> AppResult<LocaleInfo> {
+    let info = tauri::async_runtime::spawn_blocking(current).await?;
+    *watcher.last.lock().unwrap_or_else(|e| e.into_inner()) = Some(info.clone());
+    Ok(info)
+}
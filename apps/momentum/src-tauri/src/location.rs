@@ -0,0 +1,397 @@
+//! The device's position, for tagging site photos, daily logs, time entries, and jobsites.
+//!
+//! `get_current_location` asks for location permission the first time, takes a fix from the
+//! OS, and adds the street address when it can. Addresses come from the platform geocoder
+//! and are cached by coordinates in `geocode_cache`, so a site visited before still gets its
+//! address with no signal. Tags are rows in `location_tags`, one per entry, removed along
+//! with the photo, timer, or project they belong to.
+
+use rusqlite::{params, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::audit::{self, Change};
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::network::NetworkMonitor;
+use crate::state::AppState;
+
+// Cached addresses older than this are looked up again when online
+const GEOCODE_MAX_AGE_MS: i64 = 90 * 24 * 60 * 60 * 1000;
+// Four decimal places of a degree is about 11 m of latitude
+const CELL_PRECISION: usize = 4;
+
+/// Record types a location can be attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TaggedEntity {
+    Attachment,
+    Timer,
+    /// Identified by its project id and `YYYY-MM-DD` date joined by a slash.
+    DailyLog,
+    /// A project's jobsite.
+    Project,
+}
+
+impl TaggedEntity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Attachment => "attachment",
+            Self::Timer => "timer",
+            Self::DailyLog => "dailyLog",
+            Self::Project => "project",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "attachment" => Some(Self::Attachment),
+            "timer" => Some(Self::Timer),
+            "dailyLog" => Some(Self::DailyLog),
+            "project" => Some(Self::Project),
+            _ => None,
+        }
+    }
+}
+
+/// A postal address near a position.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Address {
+    pub street: Option<String>,
+    pub locality: Option<String>,
+    pub region: Option<String>,
+    pub postal_code: Option<String>,
+    pub country: Option<String>,
+}
+
+/// A position fix.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Radius in metres the fix is good to.
+    pub accuracy: Option<f64>,
+    /// Metres above sea level, when the fix has it.
+    pub altitude: Option<f64>,
+    /// When the fix was taken, in milliseconds since the epoch.
+    pub timestamp: i64,
+    #[serde(default)]
+    pub address: Option<Address>,
+}
+
+/// How fresh and precise a fix has to be.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationOptions {
+    /// Use GPS instead of the quicker network position.
+    #[serde(default)]
+    pub high_accuracy: bool,
+    /// Accept a fix the OS already has if it is at most this old.
+    #[serde(default)]
+    pub max_age_ms: u64,
+}
+
+/// A location attached to a record.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationTag {
+    pub entity: TaggedEntity,
+    pub entity_id: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub accuracy: Option<f64>,
+    pub address: Option<Address>,
+    pub captured_at: i64,
+}
+
+impl LocationTag {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let entity: String = row.get("entity")?;
+        let address: Option<String> = row.get("address")?;
+        Ok(Self {
+            entity: TaggedEntity::parse(&entity).unwrap_or(TaggedEntity::Attachment),
+            entity_id: row.get("entity_id")?,
+            latitude: row.get("latitude")?,
+            longitude: row.get("longitude")?,
+            accuracy: row.get("accuracy")?,
+            address: address.and_then(|a| serde_json::from_str(&a).ok()),
+            captured_at: row.get("captured_at")?,
+        })
+    }
+}
+
+fn cell(latitude: f64, longitude: f64) -> String {
+    format!("{latitude:.CELL_PRECISION$},{longitude:.CELL_PRECISION$}")
+}
+
+#[cfg(mobile)]
+mod platform {
+    use serde::{Deserialize, Serialize};
+    use tauri::AppHandle;
+
+    use super::{Address, Location, LocationOptions};
+    use crate::error::AppResult;
+    use crate::mobile;
+
+    #[derive(Serialize)]
+    struct GeocodeArgs {
+        latitude: f64,
+        longitude: f64,
+    }
+
+    #[derive(Deserialize)]
+    struct Geocoded {
+        address: Option<Address>,
+    }
+
+    pub fn locate(app: &AppHandle, options: &LocationOptions) -> AppResult<Location> {
+        mobile::ensure_permission(app, "location")?;
+        mobile::run(app, "getCurrentLocation", options)
+    }
+
+    pub fn reverse_geocode(
+        app: &AppHandle,
+        latitude: f64,
+        longitude: f64,
+    ) -> AppResult<Option<Address>> {
+        let geocoded: Geocoded = mobile::run(
+            app,
+            "reverseGeocode",
+            GeocodeArgs {
+                latitude,
+                longitude,
+            },
+        )?;
+        Ok(geocoded.address)
+    }
+}
+
+#[cfg(desktop)]
+mod platform {
+    use tauri::AppHandle;
+
+    use super::{Address, Location, LocationOptions};
+    use crate::error::{AppError, AppResult};
+
+    pub fn locate(_app: &AppHandle, _options: &LocationOptions) -> AppResult<Location> {
+        Err(AppError::InvalidInput(
+            "location is only available on mobile".into(),
+        ))
+    }
+
+    pub fn reverse_geocode(
+        _app: &AppHandle,
+        _latitude: f64,
+        _longitude: f64,
+    ) -> AppResult<Option<Address>> {
+        Ok(None)
+    }
+}
+
+/// The address near a position: cached if recent, otherwise from the geocoder when online,
+/// falling back to a stale cached one.
+async fn address(app: &AppHandle, latitude: f64, longitude: f64) -> AppResult<Option<Address>> {
+    let db = app.state::<Db>();
+    let key = cell(latitude, longitude);
+    let lookup = key.clone();
+    let cached: Option<(String, i64)> = db
+        .run(move |conn| {
+            Ok(conn
+                .query_row(
+                    "SELECT address, fetched_at FROM geocode_cache WHERE cell = ?1",
+                    [lookup],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?)
+        })
+        .await?;
+    let cached = cached.and_then(|(address, fetched_at)| {
+        Some((serde_json::from_str::<Address>(&address).ok()?, fetched_at))
+    });
+    if let Some((address, fetched_at)) = &cached {
+        if now_ms() - fetched_at < GEOCODE_MAX_AGE_MS {
+            return Ok(Some(address.clone()));
+        }
+    }
+    let stale = cached.map(|(address, _)| address);
+    if !app.state::<NetworkMonitor>().is_online() {
+        return Ok(stale);
+    }
+
+    let handle = app.clone();
+    let fetched = tauri::async_runtime::spawn_blocking(move || {
+        platform::reverse_geocode(&handle, latitude, longitude)
+    })
+    .await?;
+    match fetched {
+        Ok(Some(address)) => {
+            let json = serde_json::to_string(&address)?;
+            db.run(move |conn| {
+                conn.execute(
+                    "INSERT INTO geocode_cache (cell, address, fetched_at) VALUES (?1, ?2, ?3)
+                     ON CONFLICT(cell) DO UPDATE SET
+                        address = excluded.address,
+                        fetched_at = excluded.fetched_at",
+                    params![key, json, now_ms()],
+                )?;
+                Ok(())
+            })
+            .await?;
+            Ok(Some(address))
+        }
+        Ok(None) => Ok(stale),
+        Err(err) => {
+            tracing::debug!(%err, "reverse geocoding failed");
+            Ok(stale)
+        }
+    }
+}
+
+/// Take a fix and add its address.
+pub(crate) async fn current(app: &AppHandle, options: LocationOptions) -> AppResult<Location> {
+    let handle = app.clone();
+    let mut location =
+        tauri::async_runtime::spawn_blocking(move || platform::locate(&handle, &options)).await??;
+    location.address = address(app, location.latitude, location.longitude).await?;
+    Ok(location)
+}
+
+/// Attach `location` to a record, replacing any earlier tag.
+pub(crate) fn tag(
+    conn: &rusqlite::Connection,
+    entity: TaggedEntity,
+    entity_id: &str,
+    location: &Location,
+) -> AppResult<LocationTag> {
+    let address = location
+        .address
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+    conn.execute(
+        "INSERT INTO location_tags (entity, entity_id, latitude, longitude, accuracy, address,
+                                    captured_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+         ON CONFLICT(entity, entity_id) DO UPDATE SET
+            latitude = excluded.latitude,
+            longitude = excluded.longitude,
+            accuracy = excluded.accuracy,
+            address = excluded.address,
+            captured_at = excluded.captured_at",
+        params![
+            entity.as_str(),
+            entity_id,
+            location.latitude,
+            location.longitude,
+            location.accuracy,
+            address,
+            location.timestamp
+        ],
+    )?;
+    Ok(conn.query_row(
+        "SELECT * FROM location_tags WHERE entity = ?1 AND entity_id = ?2",
+        params![entity.as_str(), entity_id],
+        LocationTag::from_row,
+    )?)
+}
+
+/// Where the device is now, with the nearest address when one is known.
+///
+/// Fails with `DEVICE_PERMISSION_DENIED` when the user refuses location access.
+#[tauri::command]
+pub async fn get_current_location(
+    app: AppHandle,
+    options: Option<LocationOptions>,
+) -> AppResult<Location> {
+    current(&app, options.unwrap_or_default()).await
+}
+
+/// Attach a location to a photo, timer, daily log, or project; the current one when none is
+/// given.
+#[tauri::command]
+pub async fn tag_location(
+    app: AppHandle,
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    entity: TaggedEntity,
+    entity_id: String,
+    location: Option<Location>,
+) -> AppResult<LocationTag> {
+    if entity_id.is_empty() {
+        return Err(AppError::InvalidInput("entity id is empty".into()));
+    }
+    let location = match location {
+        Some(location) => location,
+        None => current(&app, LocationOptions::default()).await?,
+    };
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let tag = tag(conn, entity, &entity_id, &location)?;
+        audit::record_now(
+            conn,
+            &actor,
+            Change::new("tag_location", entity.as_str(), &entity_id).after(&tag)?,
+        )?;
+        Ok(tag)
+    })
+    .await
+}
+
+/// Tags for the given records of one type; records without one are left out.
+#[tauri::command]
+pub async fn get_location_tags(
+    db: State<'_, Db>,
+    entity: TaggedEntity,
+    entity_ids: Vec<String>,
+) -> AppResult<Vec<LocationTag>> {
+    db.run(move |conn| {
+        let mut stmt =
+            conn.prepare("SELECT * FROM location_tags WHERE entity = ?1 AND entity_id = ?2")?;
+        let mut tags = Vec::new();
+        for id in &entity_ids {
+            if let Some(tag) = stmt
+                .query_row(params![entity.as_str(), id], LocationTag::from_row)
+                .optional()?
+            {
+                tags.push(tag);
+            }
+        }
+        Ok(tags)
+    })
+    .await
+}
+
+/// Remove a record's location.
+#[tauri::command]
+pub async fn remove_location_tag(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    entity: TaggedEntity,
+    entity_id: String,
+) -> AppResult<()> {
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let before = conn
+            .query_row(
+                "SELECT * FROM location_tags WHERE entity = ?1 AND entity_id = ?2",
+                params![entity.as_str(), entity_id],
+                LocationTag::from_row,
+            )
+            .optional()?;
+        if let Some(before) = before {
+            conn.execute(
+                "DELETE FROM location_tags WHERE entity = ?1 AND entity_id = ?2",
+                params![entity.as_str(), entity_id],
+            )?;
+            audit::record_now(
+                conn,
+                &actor,
+                Change::new("remove_location_tag", entity.as_str(), &entity_id).before(&before)?,
+            )?;
+        }
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,277 @@
+//! Live tail of backend log records for the in-app developer console.
+//!
+//! Each subscription has its own filter and a bounded queue. When the console cannot keep
+//! up, new records are dropped and counted rather than letting the queue, or the logging
+//! call sites, wait on the webview; the next batch reports how many were lost.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Window, WindowEvent};
+use tokio::sync::mpsc;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Metadata, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::{Context, Filter, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::db::now_ms;
+use crate::error::{AppError, AppResult};
+
+/// Emitted to the subscribing window with a [`LogBatch`].
+pub const LOG_RECORDS_EVENT: &str = "logs:records";
+
+const QUEUE_CAPACITY: usize = 2000;
+const MAX_BATCH: usize = 200;
+const BATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// One log record as the console shows it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogRecord {
+    /// Unix milliseconds.
+    pub at: i64,
+    pub level: &'static str,
+    pub target: String,
+    pub message: String,
+    pub fields: BTreeMap<String, Value>,
+}
+
+/// Payload for `logs:records`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogBatch {
+    pub subscription_id: u64,
+    pub records: Vec<LogRecord>,
+    /// Records lost since the previous batch because the console fell behind.
+    pub dropped: u64,
+}
+
+struct Subscription {
+    id: u64,
+    window: String,
+    filter: Targets,
+    queue: mpsc::Sender<LogRecord>,
+    dropped: Arc<AtomicU64>,
+}
+
+#[derive(Default)]
+struct Subscriptions {
+    // Checked on every log call, so the common no-console case costs one load
+    count: AtomicUsize,
+    next_id: AtomicU64,
+    list: Mutex<Vec<Subscription>>,
+}
+
+static SUBSCRIPTIONS: LazyLock<Subscriptions> = LazyLock::new(Subscriptions::default);
+
+impl Subscriptions {
+    fn wants(&self, meta: &Metadata<'_>) -> bool {
+        self.count.load(Ordering::Relaxed) > 0
+            && self
+                .list
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .iter()
+                .any(|s| s.filter.would_enable(meta.target(), meta.level()))
+    }
+
+    fn remove(&self, keep: impl Fn(&Subscription) -> bool) -> usize {
+        let mut list = self.list.lock().unwrap_or_else(|e| e.into_inner());
+        let before = list.len();
+        list.retain(keep);
+        self.count.store(list.len(), Ordering::Relaxed);
+        before - list.len()
+    }
+}
+
+#[derive(Default)]
+struct Fields {
+    message: String,
+    fields: BTreeMap<String, Value>,
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_string(),
+            name => {
+                self.fields.insert(name.to_string(), value.into());
+            }
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => self.message = format!("{value:?}"),
+            name => {
+                self.fields
+                    .insert(name.to_string(), format!("{value:?}").into());
+            }
+        }
+    }
+}
+
+/// Subscriber layer feeding every live subscription.
+pub struct LogStreamLayer;
+
+/// Passes events some subscription's filter wants, independent of the file log level.
+pub struct Subscribed;
+
+impl<S> Filter<S> for Subscribed {
+    fn enabled(&self, meta: &Metadata<'_>, _: &Context<'_, S>) -> bool {
+        meta.is_event() && SUBSCRIPTIONS.wants(meta)
+    }
+
+    // Subscriptions come and go, so no callsite can be cached as never wanted
+    fn callsite_enabled(&self, meta: &'static Metadata<'static>) -> tracing::subscriber::Interest {
+        if meta.is_event() {
+            tracing::subscriber::Interest::sometimes()
+        } else {
+            tracing::subscriber::Interest::never()
+        }
+    }
+}
+
+/// The layer with its filter, for the global subscriber.
+pub fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    LogStreamLayer.with_filter(Subscribed)
+}
+
+impl<S: Subscriber> Layer<S> for LogStreamLayer {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        let meta = event.metadata();
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let record = LogRecord {
+            at: now_ms(),
+            level: meta.level().as_str(),
+            target: meta.target().to_string(),
+            message: fields.message,
+            fields: fields.fields,
+        };
+        let list = SUBSCRIPTIONS.list.lock().unwrap_or_else(|e| e.into_inner());
+        for subscription in list.iter() {
+            if !subscription
+                .filter
+                .would_enable(meta.target(), meta.level())
+            {
+                continue;
+            }
+            if subscription.queue.try_send(record.clone()).is_err() {
+                subscription.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+fn deliver(
+    app: AppHandle,
+    window: String,
+    id: u64,
+    mut rx: mpsc::Receiver<LogRecord>,
+    dropped: Arc<AtomicU64>,
+) {
+    tauri::async_runtime::spawn(async move {
+        let mut records = Vec::with_capacity(MAX_BATCH);
+        // Ends once the subscription is removed and its sender dropped
+        while rx.recv_many(&mut records, MAX_BATCH).await > 0 {
+            // Let a burst fill the batch instead of sending one event per record
+            tokio::time::sleep(BATCH_INTERVAL).await;
+            while records.len() < MAX_BATCH {
+                let Ok(record) = rx.try_recv() else {
+                    break;
+                };
+                records.push(record);
+            }
+            let batch = LogBatch {
+                subscription_id: id,
+                records: std::mem::take(&mut records),
+                dropped: dropped.swap(0, Ordering::Relaxed),
+            };
+            if app
+                .emit_to(window.as_str(), LOG_RECORDS_EVENT, batch)
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+}
+
+/// Drop a window's subscriptions when it closes.
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    if matches!(event, WindowEvent::Destroyed) {
+        SUBSCRIPTIONS.remove(|s| s.window != window.label());
+    }
+}
+
+/// Stream log records to the calling window as `logs:records` batches.
+///
+/// `level_filter` takes `RUST_LOG`-style targets such as `debug` or
+/// `info,momentum_lib::sync=trace`, and defaults to `info`. Returns the subscription id.
+#[tauri::command]
+pub async fn subscribe_logs(
+    app: AppHandle,
+    window: Window,
+    level_filter: Option<String>,
+) -> AppResult<u64> {
+    let filter: Targets = level_filter
+        .as_deref()
+        .unwrap_or("info")
+        .parse()
+        .map_err(|e| AppError::InvalidInput(format!("invalid log filter: {e}")))?;
+    let (queue, rx) = mpsc::channel(QUEUE_CAPACITY);
+    let dropped = Arc::new(AtomicU64::new(0));
+    let id = SUBSCRIPTIONS.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+    let label = window.label().to_string();
+    deliver(app, label.clone(), id, rx, dropped.clone());
+    let mut list = SUBSCRIPTIONS.list.lock().unwrap_or_else(|e| e.into_inner());
+    list.push(Subscription {
+        id,
+        window: label,
+        filter,
+        queue,
+        dropped,
+    });
+    SUBSCRIPTIONS.count.store(list.len(), Ordering::Relaxed);
+    drop(list);
+    // Interest may have been cached while nobody was listening
+    tracing::callsite::rebuild_interest_cache();
+    Ok(id)
+}
+
+/// Stop a subscription; records already queued are still delivered.
+#[tauri::command]
+pub async fn unsubscribe_logs(subscription_id: u64) -> AppResult<()> {
+    if SUBSCRIPTIONS.remove(|s| s.id != subscription_id) == 0 {
+        return Err(AppError::not_found(
+            "log subscription",
+            subscription_id.to_string(),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,139 @@
+//! Structured, rotated file logging with panic capture.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tauri::State;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::{Layer, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter};
+
+use crate::error::{AppError, AppResult};
+use crate::{log_stream, metrics};
+
+const LOG_DIR_NAME: &str = "logs";
+const LOG_FILE_PREFIX: &str = "momentum";
+const LOG_FILE_SUFFIX: &str = "log";
+const MAX_LOG_FILES: usize = 7;
+const DEFAULT_FILTER: &str = "info,momentum_lib=debug";
+const DEFAULT_RECENT_LINES: usize = 200;
+
+/// Logging handle registered in app state.
+///
+/// Holds the appender guard so buffered records are flushed when the app exits.
+pub struct Logging {
+    dir: PathBuf,
+    _guard: WorkerGuard,
+}
+
+/// Install the global subscriber and panic hook.
+///
+/// `RUST_LOG` overrides the default filter for local debugging.
+pub fn init(data_dir: &Path) -> AppResult<Logging> {
+    let dir = data_dir.join(LOG_DIR_NAME);
+    fs::create_dir_all(&dir)?;
+
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix(LOG_FILE_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(&dir)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+
+    let filter =
+        || EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let file_layer = fmt::layer()
+        .json()
+        .with_writer(writer)
+        .with_current_span(false)
+        .with_filter(filter());
+
+    // The devtools plugin claims the global subscriber in debug builds; records
+    // then flow to its inspector instead of the file, which is fine for development.
+    // Filters are per layer so command metrics and the live console see records below the
+    // log level.
+    if let Err(err) = tracing_subscriber::registry()
+        .with(file_layer)
+        .with(cfg!(debug_assertions).then(|| fmt::layer().with_filter(filter())))
+        .with(metrics::layer())
+        .with(log_stream::layer())
+        .try_init()
+    {
+        eprintln!("file logging disabled: {err}");
+    }
+
+    install_panic_hook();
+    tracing::info!(version = env!("CARGO_PKG_VERSION"), "logging initialized");
+
+    Ok(Logging { dir, _guard: guard })
+}
+
+// Chains onto the default hook so panics still reach stderr in development.
+fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}", l.file(), l.line()))
+            .unwrap_or_default();
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic payload".into());
+        let backtrace = std::backtrace::Backtrace::force_capture();
+        tracing::error!(%location, %message, %backtrace, "panic");
+        previous(info);
+    }));
+}
+
+impl Logging {
+    /// Rotated log files, oldest first; file names embed the date so lexical order is chronological.
+    pub fn log_files(&self) -> std::io::Result<Vec<PathBuf>> {
+        let mut files: Vec<PathBuf> = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with(LOG_FILE_PREFIX))
+            })
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
+    /// Read the last `count` lines across the rotated files, newest last.
+    pub fn recent_lines(&self, count: usize) -> std::io::Result<Vec<String>> {
+        let mut lines = Vec::with_capacity(count);
+        for file in self.log_files()?.iter().rev() {
+            let content = fs::read_to_string(file)?;
+            let needed = count - lines.len();
+            let mut chunk: Vec<String> = content
+                .lines()
+                .rev()
+                .take(needed)
+                .map(str::to_string)
+                .collect();
+            lines.append(&mut chunk);
+            if lines.len() >= count {
+                break;
+            }
+        }
+        lines.reverse();
+        Ok(lines)
+    }
+}
+
+/// Return the most recent log lines (JSON records) for the support screen.
+#[tauri::command]
+pub async fn get_recent_logs(
+    logging: State<'_, Logging>,
+    lines: Option<usize>,
+) -> AppResult<Vec<String>> {
+    Ok(logging.recent_lines(lines.unwrap_or(DEFAULT_RECENT_LINES))?)
+}
@@ -0,0 +1,342 @@
+//! Map tiles for the project overview map, cached on disk so it works without signal.
+//!
+//! The map loads tiles from `maptile://localhost/<z>/<x>/<y>`, or
+//! `http://maptile.localhost/<z>/<x>/<y>` on Windows and Android. A cached tile is always
+//! served, however old, and only a tile never seen before needs the network; it is fetched
+//! through the API's tile proxy and kept for next time. Tiles around every project's jobsite,
+//! its `project` location tag, are downloaded ahead of time by a daily job so a site's map is
+//! ready before anyone drives out to it. Once downloaded a tile is kept until the cache, trimmed
+//! least recently used first, runs over its budget.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rusqlite::OptionalExtension;
+use serde::Serialize;
+use tauri::http::header::{CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, X_CONTENT_TYPE_OPTIONS};
+use tauri::http::{Method, Request, Response, StatusCode, Uri};
+use tauri::{AppHandle, Manager, State, UriSchemeContext, UriSchemeResponder, Wry};
+
+use crate::attachment_protocol::is_app_origin;
+use crate::config;
+use crate::db::Db;
+use crate::disk_cache;
+use crate::error::{AppError, AppResult};
+use crate::http_client::{HttpClient, RetryPolicy};
+use crate::idle_lock;
+use crate::jobs::Scheduler;
+use crate::network::NetworkMonitor;
+
+/// The scheme name registered with the webview.
+pub const SCHEME: &str = "maptile";
+/// Job kind that downloads tiles around every jobsite.
+pub const PREFETCH_JOB: &str = "maps.prefetch";
+const PREFETCH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+const CACHE_DIR_NAME: &str = "map-tiles";
+const CACHE_BUDGET_BYTES: u64 = 512 * 1024 * 1024;
+const MAX_ZOOM: u8 = 19;
+// From neighbourhood to individual buildings; about 100 tiles per site
+const PREFETCH_ZOOMS: std::ops::RangeInclusive<u8> = 12..=18;
+const PREFETCH_RADIUS_M: f64 = 500.0;
+const METRES_PER_DEGREE: f64 = 111_320.0;
+
+/// One tile of the Web Mercator grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Tile {
+    z: u8,
+    x: u32,
+    y: u32,
+}
+
+impl Tile {
+    fn new(z: u8, x: u32, y: u32) -> Option<Self> {
+        let side = 1u32 << z.min(MAX_ZOOM);
+        (z <= MAX_ZOOM && x < side && y < side).then_some(Self { z, x, y })
+    }
+
+    /// The tile containing a position.
+    fn at(latitude: f64, longitude: f64, z: u8) -> Self {
+        let side = f64::from(1u32 << z);
+        let latitude = latitude.clamp(-85.0511, 85.0511).to_radians();
+        let x = ((longitude + 180.0) / 360.0 * side).floor();
+        let y = ((1.0 - latitude.tan().asinh() / std::f64::consts::PI) / 2.0 * side).floor();
+        let max = side - 1.0;
+        Self {
+            z,
+            x: x.clamp(0.0, max) as u32,
+            y: y.clamp(0.0, max) as u32,
+        }
+    }
+
+    fn file_name(self) -> String {
+        format!("{}-{}-{}.png", self.z, self.x, self.y)
+    }
+}
+
+/// Tiles covering `radius_m` around a position at each prefetch zoom.
+fn tiles_around(latitude: f64, longitude: f64, radius_m: f64) -> Vec<Tile> {
+    let dlat = radius_m / METRES_PER_DEGREE;
+    let dlon = radius_m / (METRES_PER_DEGREE * latitude.to_radians().cos().max(0.01));
+    let mut tiles = Vec::new();
+    for z in PREFETCH_ZOOMS {
+        // y grows southwards
+        let north_west = Tile::at(latitude + dlat, longitude - dlon, z);
+        let south_east = Tile::at(latitude - dlat, longitude + dlon, z);
+        for x in north_west.x..=south_east.x {
+            for y in north_west.y..=south_east.y {
+                tiles.push(Tile { z, x, y });
+            }
+        }
+    }
+    tiles
+}
+
+// `maptile://localhost/12/654/1583` and `maptile://12/654/1583`, with or without `.png`
+fn tile_from_uri(uri: &Uri) -> Option<Tile> {
+    let path = uri.path().trim_matches('/');
+    let path = match uri.host().filter(|host| *host != "localhost") {
+        Some(z) => format!("{z}/{path}"),
+        None => path.to_string(),
+    };
+    let mut parts = path.trim_end_matches(".png").split('/');
+    let z = parts.next()?.parse().ok()?;
+    let x = parts.next()?.parse().ok()?;
+    let y = parts.next()?.parse().ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Tile::new(z, x, y)
+}
+
+/// Cache directory handle registered in app state.
+pub struct MapTiles {
+    dir: PathBuf,
+}
+
+/// Open the cache under `data_dir/map-tiles`.
+pub fn init(data_dir: &Path) -> AppResult<MapTiles> {
+    let dir = data_dir.join(CACHE_DIR_NAME);
+    fs::create_dir_all(&dir)?;
+    Ok(MapTiles { dir })
+}
+
+impl MapTiles {
+    fn path(&self, tile: Tile) -> PathBuf {
+        self.dir.join(tile.file_name())
+    }
+
+    fn is_cached(&self, tile: Tile) -> bool {
+        self.path(tile).is_file()
+    }
+
+    fn read(&self, tile: Tile) -> Option<Vec<u8>> {
+        let path = self.path(tile);
+        let bytes = fs::read(&path).ok()?;
+        disk_cache::touch(&path);
+        Some(bytes)
+    }
+
+    fn store(&self, tile: Tile, bytes: &[u8]) -> AppResult<()> {
+        let path = self.path(tile);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, &path)?;
+        disk_cache::evict(&self.dir, CACHE_BUDGET_BYTES, &path);
+        Ok(())
+    }
+
+    /// Download a tile from the API's tile proxy and cache it.
+    async fn fetch(&self, app: &AppHandle, tile: Tile) -> AppResult<Vec<u8>> {
+        let url = config::api_url(
+            app,
+            &format!("/v1/tiles/{}/{}/{}.png", tile.z, tile.x, tile.y),
+        )?;
+        let http = app.state::<HttpClient>();
+        let response = http
+            .send_authorized(app, http.client().get(url), RetryPolicy::default())
+            .await?;
+        if response.status() == tauri_plugin_http::reqwest::StatusCode::NOT_FOUND {
+            return Err(AppError::NotFound {
+                entity: "map tile",
+                id: tile.file_name(),
+            });
+        }
+        let bytes = response.error_for_status()?.bytes().await?.to_vec();
+        self.store(tile, &bytes)?;
+        Ok(bytes)
+    }
+
+    async fn get(&self, app: &AppHandle, tile: Tile) -> AppResult<Vec<u8>> {
+        if let Some(bytes) = self.read(tile) {
+            return Ok(bytes);
+        }
+        if !app.state::<NetworkMonitor>().is_online() {
+            return Err(AppError::NotFound {
+                entity: "map tile",
+                id: tile.file_name(),
+            });
+        }
+        self.fetch(app, tile).await
+    }
+
+    /// Download the tiles around one position that are not cached yet.
+    async fn prefetch(&self, app: &AppHandle, latitude: f64, longitude: f64) -> PrefetchSummary {
+        let mut summary = PrefetchSummary::default();
+        for tile in tiles_around(latitude, longitude, PREFETCH_RADIUS_M) {
+            summary.tiles += 1;
+            if self.is_cached(tile) {
+                continue;
+            }
+            match self.fetch(app, tile).await {
+                Ok(_) => summary.downloaded += 1,
+                Err(err) => {
+                    tracing::debug!(%err, tile = tile.file_name(), "map tile download failed");
+                    summary.failed += 1;
+                }
+            }
+        }
+        summary
+    }
+}
+
+/// What a prefetch downloaded.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrefetchSummary {
+    /// Tiles covering the jobsite.
+    pub tiles: usize,
+    pub downloaded: usize,
+    /// Tiles that could not be downloaded; the map shows gaps there offline.
+    pub failed: usize,
+}
+
+/// A project's jobsite as latitude and longitude, if it has been tagged.
+pub(crate) async fn jobsite(db: &Db, project_id: String) -> AppResult<Option<(f64, f64)>> {
+    db.run(move |conn| {
+        Ok(conn
+            .query_row(
+                "SELECT latitude, longitude FROM location_tags
+                 WHERE entity = 'project' AND entity_id = ?1",
+                [project_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?)
+    })
+    .await
+}
+
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(PREFETCH_JOB, Some(PREFETCH_INTERVAL), |app| async move {
+        if !app.state::<NetworkMonitor>().is_online() {
+            return Ok(());
+        }
+        let sites: Vec<(f64, f64)> = app
+            .state::<Db>()
+            .run(|conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT latitude, longitude FROM location_tags WHERE entity = 'project'",
+                )?;
+                let sites = stmt
+                    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<rusqlite::Result<_>>()?;
+                Ok(sites)
+            })
+            .await?;
+        let tiles = app.state::<MapTiles>();
+        for (latitude, longitude) in sites {
+            let summary = tiles.prefetch(&app, latitude, longitude).await;
+            if summary.failed > 0 {
+                tracing::warn!(
+                    failed = summary.failed,
+                    "some jobsite map tiles did not download"
+                );
+            }
+        }
+        Ok(())
+    });
+}
+
+/// Download the map around a project's jobsite now instead of waiting for the daily job.
+#[tauri::command]
+pub async fn prefetch_project_map(
+    app: AppHandle,
+    db: State<'_, Db>,
+    tiles: State<'_, MapTiles>,
+    project_id: String,
+) -> AppResult<PrefetchSummary> {
+    let (latitude, longitude) =
+        jobsite(&db, project_id.clone())
+            .await?
+            .ok_or_else(|| AppError::NotFound {
+                entity: "jobsite",
+                id: project_id,
+            })?;
+    if !app.state::<NetworkMonitor>().is_online() {
+        return Err(AppError::InvalidInput(
+            "map tiles can only be downloaded while online".into(),
+        ));
+    }
+    Ok(tiles.prefetch(&app, latitude, longitude).await)
+}
+
+fn status(code: StatusCode) -> Response<Vec<u8>> {
+    let mut response = Response::new(Vec::new());
+    *response.status_mut() = code;
+    response
+}
+
+async fn serve(
+    app: &AppHandle,
+    webview_label: &str,
+    request: &Request<Vec<u8>>,
+) -> AppResult<Response<Vec<u8>>> {
+    let trusted = app
+        .get_webview_window(webview_label)
+        .and_then(|webview| webview.url().ok())
+        .is_some_and(|url| is_app_origin(app, &url));
+    if !trusted {
+        tracing::warn!(webview_label, uri = %request.uri(), "map tile request refused");
+        return Ok(status(StatusCode::FORBIDDEN));
+    }
+    // Jobsite maps show where the work is, so they stay hidden while locked
+    idle_lock::ensure_unlocked(app)?;
+    if request.method() != Method::GET {
+        return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+    }
+    let tile = tile_from_uri(request.uri())
+        .ok_or_else(|| AppError::InvalidInput(format!("no map tile in {}", request.uri())))?;
+    let bytes = app.state::<MapTiles>().get(app, tile).await?;
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "image/png")
+        .header(CONTENT_LENGTH, bytes.len())
+        .header(CACHE_CONTROL, "private, no-cache")
+        .header(X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .body(bytes)
+        .map_err(|err| AppError::Internal(format!("cannot build the response: {err}")))
+}
+
+/// Answer one request for the scheme; registered with the builder in `run`.
+pub fn handle(
+    ctx: UriSchemeContext<'_, Wry>,
+    request: Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    let app = ctx.app_handle().clone();
+    let webview_label = ctx.webview_label().to_string();
+    tauri::async_runtime::spawn(async move {
+        let response = match serve(&app, &webview_label, &request).await {
+            Ok(response) => response,
+            Err(AppError::NotFound { .. }) => status(StatusCode::NOT_FOUND),
+            Err(AppError::InvalidInput(_)) => status(StatusCode::BAD_REQUEST),
+            Err(AppError::SessionLocked) => status(StatusCode::LOCKED),
+            Err(err) => {
+                tracing::debug!(%err, uri = %request.uri(), "map tile request failed");
+                status(StatusCode::BAD_GATEWAY)
+            }
+        };
+        responder.respond(response);
+    });
+}
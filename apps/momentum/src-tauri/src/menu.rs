@@ -0,0 +1,359 @@
+//! Native application menu bar with typed actions forwarded to the frontend.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu};
+use tauri::{AppHandle, Emitter, Manager, State, Wry};
+
+use crate::error::{AppError, AppResult};
+use crate::windows::MAIN_WINDOW;
+use crate::zoom::{self, ZoomStep};
+
+/// Emitted with a [`MenuAction`] to the focused window when a custom menu item is chosen.
+pub const MENU_ACTION_EVENT: &str = "menu:action";
+
+/// Emitted with an [`ExtensionMenuAction`] to the focused window when an extension's item is chosen.
+pub const EXTENSION_MENU_EVENT: &str = "menu:extension";
+
+const ID_PREFIX: &str = "menu:";
+const EXTENSION_ID_PREFIX: &str = "extension:";
+
+/// Menu items handled by the frontend; native items (copy, paste, quit) are handled by the OS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MenuAction {
+    NewProject,
+    OpenProject,
+    Save,
+    Preferences,
+    ToggleSidebar,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    Documentation,
+    ReportIssue,
+    CheckForUpdates,
+    About,
+    /// Opens the hidden SQL and state console; debug builds only.
+    #[cfg(debug_assertions)]
+    DeveloperConsole,
+}
+
+impl MenuAction {
+    const ALL: &'static [Self] = &[
+        Self::NewProject,
+        Self::OpenProject,
+        Self::Save,
+        Self::Preferences,
+        Self::ToggleSidebar,
+        Self::ZoomIn,
+        Self::ZoomOut,
+        Self::ZoomReset,
+        Self::Documentation,
+        Self::ReportIssue,
+        Self::CheckForUpdates,
+        Self::About,
+        #[cfg(debug_assertions)]
+        Self::DeveloperConsole,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::NewProject => "newProject",
+            Self::OpenProject => "openProject",
+            Self::Save => "save",
+            Self::Preferences => "preferences",
+            Self::ToggleSidebar => "toggleSidebar",
+            Self::ZoomIn => "zoomIn",
+            Self::ZoomOut => "zoomOut",
+            Self::ZoomReset => "zoomReset",
+            Self::Documentation => "documentation",
+            Self::ReportIssue => "reportIssue",
+            Self::CheckForUpdates => "checkForUpdates",
+            Self::About => "about",
+            #[cfg(debug_assertions)]
+            Self::DeveloperConsole => "developerConsole",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::NewProject => "New Project",
+            Self::OpenProject => "Open Project…",
+            Self::Save => "Save",
+            Self::Preferences => "Settings…",
+            Self::ToggleSidebar => "Toggle Sidebar",
+            Self::ZoomIn => "Zoom In",
+            Self::ZoomOut => "Zoom Out",
+            Self::ZoomReset => "Actual Size",
+            Self::Documentation => "Documentation",
+            Self::ReportIssue => "Report an Issue…",
+            Self::CheckForUpdates => "Check for Updates…",
+            Self::About => "About Momentum",
+            #[cfg(debug_assertions)]
+            Self::DeveloperConsole => "Developer Console",
+        }
+    }
+
+    fn accelerator(self) -> Option<&'static str> {
+        match self {
+            Self::NewProject => Some("CmdOrCtrl+N"),
+            Self::OpenProject => Some("CmdOrCtrl+O"),
+            Self::Save => Some("CmdOrCtrl+S"),
+            Self::Preferences => Some("CmdOrCtrl+,"),
+            Self::ToggleSidebar => Some("CmdOrCtrl+B"),
+            Self::ZoomIn => Some("CmdOrCtrl+="),
+            Self::ZoomOut => Some("CmdOrCtrl+-"),
+            Self::ZoomReset => Some("CmdOrCtrl+0"),
+            #[cfg(debug_assertions)]
+            Self::DeveloperConsole => Some("CmdOrCtrl+Alt+Shift+D"),
+            _ => None,
+        }
+    }
+
+    // Items that need app state (e.g. a dirty project) start disabled
+    fn enabled_by_default(self) -> bool {
+        !matches!(self, Self::Save)
+    }
+
+    fn id(self) -> String {
+        format!("{ID_PREFIX}{}", self.name())
+    }
+
+    fn from_id(id: &str) -> Option<Self> {
+        let name = id.strip_prefix(ID_PREFIX)?;
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|action| action.name() == name)
+    }
+}
+
+/// An item an extension added to the Extensions menu.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtensionMenuAction {
+    pub extension_id: String,
+    pub item_id: String,
+}
+
+/// Handles to custom menu items so commands can toggle them, registered in app state.
+pub struct MenuState {
+    items: HashMap<MenuAction, MenuItem<Wry>>,
+    extensions: Submenu<Wry>,
+}
+
+/// Build the menu bar, install it app-wide, and start forwarding its events.
+pub fn init(app: &AppHandle) -> AppResult<MenuState> {
+    let items = MenuAction::ALL
+        .iter()
+        .copied()
+        .map(|action| {
+            let item = MenuItem::with_id(
+                app,
+                action.id(),
+                action.label(),
+                action.enabled_by_default(),
+                action.accelerator(),
+            )?;
+            Ok((action, item))
+        })
+        .collect::<AppResult<HashMap<_, _>>>()?;
+    let item = |action: MenuAction| &items[&action];
+
+    let file = Submenu::with_items(
+        app,
+        "File",
+        true,
+        &[
+            item(MenuAction::NewProject),
+            item(MenuAction::OpenProject),
+            &PredefinedMenuItem::separator(app)?,
+            item(MenuAction::Save),
+            #[cfg(not(target_os = "macos"))]
+            &PredefinedMenuItem::separator(app)?,
+            #[cfg(not(target_os = "macos"))]
+            item(MenuAction::Preferences),
+            &PredefinedMenuItem::separator(app)?,
+            &PredefinedMenuItem::close_window(app, None)?,
+            #[cfg(not(target_os = "macos"))]
+            &PredefinedMenuItem::quit(app, None)?,
+        ],
+    )?;
+    let edit = Submenu::with_items(
+        app,
+        "Edit",
+        true,
+        &[
+            &PredefinedMenuItem::undo(app, None)?,
+            &PredefinedMenuItem::redo(app, None)?,
+            &PredefinedMenuItem::separator(app)?,
+            &PredefinedMenuItem::cut(app, None)?,
+            &PredefinedMenuItem::copy(app, None)?,
+            &PredefinedMenuItem::paste(app, None)?,
+            &PredefinedMenuItem::select_all(app, None)?,
+        ],
+    )?;
+    let view = Submenu::with_items(
+        app,
+        "View",
+        true,
+        &[
+            item(MenuAction::ToggleSidebar),
+            &PredefinedMenuItem::separator(app)?,
+            item(MenuAction::ZoomIn),
+            item(MenuAction::ZoomOut),
+            item(MenuAction::ZoomReset),
+            &PredefinedMenuItem::separator(app)?,
+            &PredefinedMenuItem::fullscreen(app, None)?,
+        ],
+    )?;
+    // Filled in by `set_extension_items` as extensions are enabled
+    let extensions = Submenu::with_items(app, "Extensions", true, &[&no_extensions(app)?])?;
+    let help = Submenu::with_items(
+        app,
+        "Help",
+        true,
+        &[
+            item(MenuAction::Documentation),
+            item(MenuAction::ReportIssue),
+            &PredefinedMenuItem::separator(app)?,
+            item(MenuAction::CheckForUpdates),
+            #[cfg(not(target_os = "macos"))]
+            item(MenuAction::About),
+            #[cfg(debug_assertions)]
+            &PredefinedMenuItem::separator(app)?,
+            #[cfg(debug_assertions)]
+            item(MenuAction::DeveloperConsole),
+        ],
+    )?;
+
+    // macOS expects the first submenu to be the application menu
+    #[cfg(target_os = "macos")]
+    let menu = {
+        let app_menu = Submenu::with_items(
+            app,
+            "Momentum",
+            true,
+            &[
+                item(MenuAction::About),
+                &PredefinedMenuItem::separator(app)?,
+                item(MenuAction::Preferences),
+                &PredefinedMenuItem::separator(app)?,
+                &PredefinedMenuItem::services(app, None)?,
+                &PredefinedMenuItem::separator(app)?,
+                &PredefinedMenuItem::hide(app, None)?,
+                &PredefinedMenuItem::hide_others(app, None)?,
+                &PredefinedMenuItem::show_all(app, None)?,
+                &PredefinedMenuItem::separator(app)?,
+                &PredefinedMenuItem::quit(app, None)?,
+            ],
+        )?;
+        let window = Submenu::with_items(
+            app,
+            "Window",
+            true,
+            &[
+                &PredefinedMenuItem::minimize(app, None)?,
+                &PredefinedMenuItem::maximize(app, None)?,
+                &PredefinedMenuItem::separator(app)?,
+                &PredefinedMenuItem::bring_all_to_front(app, None)?,
+            ],
+        )?;
+        Menu::with_items(
+            app,
+            &[&app_menu, &file, &edit, &view, &extensions, &window, &help],
+        )?
+    };
+    #[cfg(not(target_os = "macos"))]
+    let menu = Menu::with_items(app, &[&file, &edit, &view, &extensions, &help])?;
+
+    app.set_menu(menu)?;
+    app.on_menu_event(on_menu_event);
+    Ok(MenuState { items, extensions })
+}
+
+fn no_extensions(app: &AppHandle) -> AppResult<MenuItem<Wry>> {
+    Ok(MenuItem::with_id(
+        app,
+        "extensions:none",
+        "No Extensions",
+        false,
+        None::<&str>,
+    )?)
+}
+
+/// Replace the Extensions menu with `items`, as `(extension id, item id, label)`.
+pub fn set_extension_items(app: &AppHandle, items: &[(String, String, String)]) -> AppResult<()> {
+    let Some(state) = app.try_state::<MenuState>() else {
+        return Ok(());
+    };
+    let submenu = &state.extensions;
+    for item in submenu.items()? {
+        submenu.remove(&item)?;
+    }
+    if items.is_empty() {
+        return Ok(submenu.append(&no_extensions(app)?)?);
+    }
+    for (extension_id, item_id, label) in items {
+        let id = format!("{EXTENSION_ID_PREFIX}{extension_id}:{item_id}");
+        submenu.append(&MenuItem::with_id(app, id, label, true, None::<&str>)?)?;
+    }
+    Ok(())
+}
+
+// Tray and per-window menus share this channel; their ids never carry our prefix
+fn on_menu_event(app: &AppHandle, event: MenuEvent) {
+    let id = event.id().as_ref();
+    if let Some((extension_id, item_id)) = id
+        .strip_prefix(EXTENSION_ID_PREFIX)
+        .and_then(|rest| rest.split_once(':'))
+    {
+        let action = ExtensionMenuAction {
+            extension_id: extension_id.to_string(),
+            item_id: item_id.to_string(),
+        };
+        let _ = app.emit_to(focused_window(app).as_str(), EXTENSION_MENU_EVENT, action);
+        return;
+    }
+    let Some(action) = MenuAction::from_id(id) else {
+        return;
+    };
+    let label = focused_window(app);
+    let zoom = match action {
+        MenuAction::ZoomIn => Some(ZoomStep::In),
+        MenuAction::ZoomOut => Some(ZoomStep::Out),
+        MenuAction::ZoomReset => Some(ZoomStep::Reset),
+        _ => None,
+    };
+    if let (Some(step), Some(window)) = (zoom, app.get_webview_window(&label)) {
+        if let Err(err) = zoom::step(&window, step) {
+            tracing::warn!(%err, "cannot zoom window");
+        }
+    }
+    let _ = app.emit_to(label.as_str(), MENU_ACTION_EVENT, action);
+}
+
+fn focused_window(app: &AppHandle) -> String {
+    app.webview_windows()
+        .into_values()
+        .find(|w| w.is_focused().unwrap_or(false))
+        .map(|w| w.label().to_string())
+        .unwrap_or_else(|| MAIN_WINDOW.to_string())
+}
+
+/// Enable or disable a custom menu item, e.g. `save` only while a project is dirty.
+#[tauri::command]
+pub async fn set_menu_item_enabled(
+    state: State<'_, MenuState>,
+    action: MenuAction,
+    enabled: bool,
+) -> AppResult<()> {
+    let item = state
+        .items
+        .get(&action)
+        .ok_or_else(|| AppError::Internal(format!("menu item {} missing", action.name())))?;
+    item.set_enabled(enabled)?;
+    Ok(())
+}
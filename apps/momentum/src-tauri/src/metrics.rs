@@ -0,0 +1,366 @@
+//! Per-command IPC timings, payload sizes, and failures, kept in memory for finding slow paths.
+//!
+//! The invoke handler cannot see when an async command finishes, since the response is sent
+//! from a task it never gets back. Tauri's `tracing` feature wraps every request in spans
+//! that do, so a subscriber layer turns those into samples: a request span opens when the
+//! webview's message arrives and closes once the response has been handed back.
+//!
+//! Peak memory and CPU from the resource monitor are kept here too, and reset with the
+//! command samples.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Filter, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::db::now_ms;
+use crate::error::AppResult;
+
+/// Emitted with a batch of [`CommandSample`]s while the overlay stream is on.
+pub const METRICS_SAMPLES_EVENT: &str = "metrics:samples";
+
+const RING_CAPACITY: usize = 4096;
+const OVERLAY_INTERVAL: Duration = Duration::from_secs(1);
+// Kept if the overlay falls behind, e.g. while its window is hidden
+const MAX_OVERLAY_BACKLOG: usize = 1024;
+
+// Span names from tauri's IPC protocol handler
+const REQUEST_SPAN: &str = "ipc::request";
+const HANDLE_SPAN: &str = "ipc::request::handle";
+const RESPONSE_SPAN: &str = "ipc::request::response";
+
+/// One finished command.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandSample {
+    pub command: String,
+    /// Unix milliseconds when the request arrived.
+    pub at: i64,
+    pub duration_us: u64,
+    pub request_bytes: usize,
+    pub response_bytes: usize,
+    pub ok: bool,
+}
+
+/// Highest resource use the resource monitor has seen since the last reset.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourcePeaks {
+    /// App and webview processes together.
+    pub memory_bytes: u64,
+    pub memory_at: Option<i64>,
+    /// Percent of one core, so above 100 when several are busy.
+    pub cpu_percent: f64,
+    pub cpu_at: Option<i64>,
+}
+
+#[derive(Default)]
+struct Recorder {
+    samples: Mutex<VecDeque<CommandSample>>,
+    overlay: AtomicBool,
+    overlay_backlog: Mutex<Vec<CommandSample>>,
+    peaks: Mutex<ResourcePeaks>,
+}
+
+static RECORDER: LazyLock<Recorder> = LazyLock::new(Recorder::default);
+
+impl Recorder {
+    fn push(&self, sample: CommandSample) {
+        if self.overlay.load(Ordering::Relaxed) {
+            let mut backlog = self
+                .overlay_backlog
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            if backlog.len() < MAX_OVERLAY_BACKLOG {
+                backlog.push(sample.clone());
+            }
+        }
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        if samples.len() == RING_CAPACITY {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+}
+
+/// Note a resource sample, keeping it if it is a new peak.
+pub fn record_resources(at: i64, memory_bytes: u64, cpu_percent: f64) {
+    let mut peaks = RECORDER.peaks.lock().unwrap_or_else(|e| e.into_inner());
+    if memory_bytes > peaks.memory_bytes {
+        peaks.memory_bytes = memory_bytes;
+        peaks.memory_at = Some(at);
+    }
+    if cpu_percent > peaks.cpu_percent {
+        peaks.cpu_percent = cpu_percent;
+        peaks.cpu_at = Some(at);
+    }
+}
+
+// Request body size, noted on the outer span until the handle span picks it up
+struct RequestBytes(usize);
+
+struct Call {
+    command: String,
+    at: i64,
+    started: Instant,
+    request_bytes: usize,
+    response_bytes: usize,
+    ok: bool,
+}
+
+#[derive(Default)]
+struct Fields {
+    cmd: Option<String>,
+    request: Option<usize>,
+    response: Option<usize>,
+    error: bool,
+}
+
+// Only lengths are kept; bodies may hold anything the user typed
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "cmd" => self.cmd = Some(value.to_string()),
+            "request" => self.request = Some(value.len()),
+            "response" => self.response = Some(value.len()),
+            "error" => self.error = true,
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "cmd" => self.cmd = Some(format!("{value:?}").trim_matches('"').to_string()),
+            "error" => self.error = true,
+            _ => {}
+        }
+    }
+}
+
+/// Subscriber layer that records a [`CommandSample`] per IPC request.
+pub struct MetricsLayer;
+
+/// Filter enabling just the IPC spans the layer reads, whatever the log level.
+pub struct IpcSpans;
+
+impl<S> Filter<S> for IpcSpans {
+    fn enabled(&self, meta: &tracing::Metadata<'_>, _: &Context<'_, S>) -> bool {
+        meta.is_span() && matches!(meta.name(), REQUEST_SPAN | HANDLE_SPAN | RESPONSE_SPAN)
+    }
+}
+
+/// The layer with its filter, for the global subscriber.
+pub fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    MetricsLayer.with_filter(IpcSpans)
+}
+
+impl<S> Layer<S> for MetricsLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        match attrs.metadata().name() {
+            REQUEST_SPAN => {
+                if let Some(bytes) = fields.request {
+                    span.extensions_mut().insert(RequestBytes(bytes));
+                }
+            }
+            HANDLE_SPAN => {
+                let request_bytes = span
+                    .parent()
+                    .and_then(|parent| parent.extensions().get::<RequestBytes>().map(|b| b.0))
+                    .unwrap_or(0);
+                span.extensions_mut().insert(Call {
+                    command: fields.cmd.unwrap_or_default(),
+                    at: now_ms(),
+                    started: Instant::now(),
+                    request_bytes,
+                    response_bytes: 0,
+                    ok: true,
+                });
+            }
+            RESPONSE_SPAN => {
+                let handle = span.scope().skip(1).find(|s| s.name() == HANDLE_SPAN);
+                if let Some(handle) = handle {
+                    if let Some(call) = handle.extensions_mut().get_mut::<Call>() {
+                        call.response_bytes = fields.response.unwrap_or(0);
+                        call.ok = !fields.error;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if span.name() != REQUEST_SPAN {
+            return;
+        }
+        let mut fields = Fields::default();
+        values.record(&mut fields);
+        if let Some(bytes) = fields.request {
+            span.extensions_mut().insert(RequestBytes(bytes));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        if span.name() != HANDLE_SPAN {
+            return;
+        }
+        let Some(call) = span.extensions_mut().remove::<Call>() else {
+            return;
+        };
+        RECORDER.push(CommandSample {
+            command: call.command,
+            at: call.at,
+            duration_us: call.started.elapsed().as_micros() as u64,
+            request_bytes: call.request_bytes,
+            response_bytes: call.response_bytes,
+            ok: call.ok,
+        });
+    }
+}
+
+/// Emit overlay batches while the stream is on.
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(OVERLAY_INTERVAL).await;
+            if !RECORDER.overlay.load(Ordering::Relaxed) {
+                continue;
+            }
+            let batch = std::mem::take(
+                &mut *RECORDER
+                    .overlay_backlog
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner()),
+            );
+            if !batch.is_empty() {
+                let _ = app.emit(METRICS_SAMPLES_EVENT, batch);
+            }
+        }
+    });
+}
+
+/// Aggregates for one command over the samples still in the buffer.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandStats {
+    pub command: String,
+    pub calls: usize,
+    pub errors: usize,
+    pub error_rate: f64,
+    pub total_us: u64,
+    pub mean_us: u64,
+    pub p50_us: u64,
+    pub p95_us: u64,
+    pub max_us: u64,
+    pub mean_request_bytes: usize,
+    pub mean_response_bytes: usize,
+    pub max_response_bytes: usize,
+}
+
+/// Result of [`get_command_metrics`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandMetrics {
+    /// Unix milliseconds of the oldest sample still held.
+    pub since: Option<i64>,
+    pub samples: usize,
+    /// Slowest in total first, which is usually where time goes.
+    pub commands: Vec<CommandStats>,
+    pub peaks: ResourcePeaks,
+}
+
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    let index = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[index]
+}
+
+fn summarize(samples: &VecDeque<CommandSample>) -> CommandMetrics {
+    let mut by_command: BTreeMap<&str, Vec<&CommandSample>> = BTreeMap::new();
+    for sample in samples {
+        by_command.entry(&sample.command).or_default().push(sample);
+    }
+    let mut commands: Vec<CommandStats> = by_command
+        .into_iter()
+        .map(|(command, samples)| {
+            let calls = samples.len();
+            let errors = samples.iter().filter(|s| !s.ok).count();
+            let mut durations: Vec<u64> = samples.iter().map(|s| s.duration_us).collect();
+            durations.sort_unstable();
+            let total_us = durations.iter().sum();
+            CommandStats {
+                command: command.to_string(),
+                calls,
+                errors,
+                error_rate: errors as f64 / calls as f64,
+                total_us,
+                mean_us: total_us / calls as u64,
+                p50_us: percentile(&durations, 0.5),
+                p95_us: percentile(&durations, 0.95),
+                max_us: durations[calls - 1],
+                mean_request_bytes: samples.iter().map(|s| s.request_bytes).sum::<usize>() / calls,
+                mean_response_bytes: samples.iter().map(|s| s.response_bytes).sum::<usize>()
+                    / calls,
+                max_response_bytes: samples.iter().map(|s| s.response_bytes).max().unwrap_or(0),
+            }
+        })
+        .collect();
+    commands.sort_by_key(|stats| std::cmp::Reverse(stats.total_us));
+    CommandMetrics {
+        since: samples.front().map(|s| s.at),
+        samples: samples.len(),
+        commands,
+        peaks: *RECORDER.peaks.lock().unwrap_or_else(|e| e.into_inner()),
+    }
+}
+
+/// Per-command statistics over the most recent requests, with resource peaks.
+#[tauri::command]
+pub async fn get_command_metrics(reset: Option<bool>) -> AppResult<CommandMetrics> {
+    let mut samples = RECORDER.samples.lock().unwrap_or_else(|e| e.into_inner());
+    let metrics = summarize(&samples);
+    if reset.unwrap_or(false) {
+        samples.clear();
+        *RECORDER.peaks.lock().unwrap_or_else(|e| e.into_inner()) = ResourcePeaks::default();
+    }
+    Ok(metrics)
+}
+
+/// Turn the `metrics:samples` stream for a debug overlay on or off.
+#[tauri::command]
+pub async fn set_metrics_overlay(enabled: bool) -> AppResult<()> {
+    RECORDER.overlay.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        RECORDER
+            .overlay_backlog
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+    Ok(())
+}
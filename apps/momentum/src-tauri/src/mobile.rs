@@ -0,0 +1,96 @@
+//! The app's own Kotlin and Swift code, for device features no published plugin covers.
+//!
+//! The sources live in `mobile/android` and `mobile/ios` beside this crate and are added to
+//! the projects `tauri android init` and `tauri ios init` generate under `gen/`. Both are
+//! registered as one plugin, and commands reach them through [`run`].
+//!
+//! Runtime permissions go through the plugin's `checkPermissions` and `requestPermissions`
+//! under an alias such as `location`, and a denial surfaces as `DEVICE_PERMISSION_DENIED`.
+
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tauri::plugin::mobile::{ErrorResponse, PluginInvokeError};
+use tauri::plugin::{Builder, PluginHandle, TauriPlugin};
+use tauri::{AppHandle, Manager, Wry};
+
+use crate::error::{AppError, AppResult};
+
+#[cfg(target_os = "android")]
+const ANDROID_PACKAGE: &str = "dev.truss.momentum";
+
+#[cfg(target_os = "ios")]
+tauri::ios_plugin_binding!(init_plugin_momentum);
+
+// Rejection code the native side uses for a missing permission, followed by its alias
+const PERMISSION_DENIED_CODE: &str = "PERMISSION_DENIED:";
+
+struct Native(PluginHandle<Wry>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum PermissionState {
+    Granted,
+    Denied,
+    Prompt,
+    PromptWithRationale,
+}
+
+/// Register the native plugin; must be added before anything calls [`run`].
+pub fn init() -> TauriPlugin<Wry> {
+    Builder::new("momentum-native")
+        .setup(|app, api| {
+            #[cfg(target_os = "android")]
+            let handle = api.register_android_plugin(ANDROID_PACKAGE, "MomentumPlugin")?;
+            #[cfg(target_os = "ios")]
+            let handle = api.register_ios_plugin(init_plugin_momentum)?;
+            app.manage(Native(handle));
+            Ok(())
+        })
+        .build()
+}
+
+/// Call `command` on the native plugin and deserialize what it resolves with.
+pub(crate) fn run<T: DeserializeOwned>(
+    app: &AppHandle,
+    command: &str,
+    payload: impl Serialize,
+) -> AppResult<T> {
+    app.state::<Native>()
+        .0
+        .run_mobile_plugin(command, payload)
+        .map_err(|e| match e {
+            PluginInvokeError::InvokeRejected(ErrorResponse {
+                code: Some(code), ..
+            }) if code.starts_with(PERMISSION_DENIED_CODE) => AppError::DevicePermissionDenied {
+                permission: code[PERMISSION_DENIED_CODE.len()..].to_string(),
+            },
+            e => AppError::Internal(format!("{command} failed on the device: {e}")),
+        })
+}
+
+/// Ask for the permission `alias` unless it is granted already.
+///
+/// Once the user has refused for good only the device settings can change it, so this fails
+/// without showing a prompt that the OS would suppress anyway.
+pub(crate) fn ensure_permission(app: &AppHandle, alias: &str) -> AppResult<()> {
+    let denied = || AppError::DevicePermissionDenied {
+        permission: alias.to_string(),
+    };
+    let states: HashMap<String, PermissionState> = run(app, "checkPermissions", ())?;
+    match states.get(alias) {
+        Some(PermissionState::Granted) => return Ok(()),
+        Some(PermissionState::Denied) => return Err(denied()),
+        _ => {}
+    }
+    let states: HashMap<String, PermissionState> = run(
+        app,
+        "requestPermissions",
+        serde_json::json!({ "permissions": [alias] }),
+    )?;
+    match states.get(alias) {
+        Some(PermissionState::Granted) => Ok(()),
+        _ => Err(denied()),
+    }
+}
@@ -0,0 +1,182 @@
+//! Connectivity monitor that probes the API itself rather than trusting OS network state.
+//!
+//! A captive portal or a down API looks "connected" to the OS; what sync and the outbox care
+//! about is whether our server answers.
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::config;
+use crate::db::now_ms;
+use crate::error::{AppError, AppResult};
+use crate::http_client::HttpClient;
+use crate::realtime::Realtime;
+use crate::{jobs, outbox, sync};
+
+const PROBE_PATH: &str = "/v1/health";
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+const STEADY_INTERVAL: Duration = Duration::from_secs(30);
+// Used while offline and while confirming a suspected change
+const FAST_INTERVAL: Duration = Duration::from_secs(5);
+// Consecutive contrary probes needed before the state flips, so one dropped request never flaps
+const FLIP_THRESHOLD: u32 = 2;
+
+/// Emitted with a [`Connectivity`] snapshot when the API becomes reachable.
+pub const ONLINE_EVENT: &str = "network:online";
+/// Emitted with a [`Connectivity`] snapshot when the API stops answering.
+pub const OFFLINE_EVENT: &str = "network:offline";
+
+/// Current reachability of the API.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Connectivity {
+    pub online: bool,
+    /// When the current state began, Unix ms.
+    pub since: i64,
+    pub last_checked_at: Option<i64>,
+}
+
+struct Inner {
+    status: Connectivity,
+    contrary: u32,
+}
+
+/// Connectivity state registered in app state.
+pub struct NetworkMonitor {
+    inner: Mutex<Inner>,
+}
+
+impl Default for NetworkMonitor {
+    // Optimistic until probed so startup work is not held back by a slow first probe
+    fn default() -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                status: Connectivity {
+                    online: true,
+                    since: now_ms(),
+                    last_checked_at: None,
+                },
+                contrary: 0,
+            }),
+        }
+    }
+}
+
+impl NetworkMonitor {
+    pub fn is_online(&self) -> bool {
+        self.snapshot().online
+    }
+
+    pub fn snapshot(&self) -> Connectivity {
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .status
+            .clone()
+    }
+
+    // Returns the new status if the state flipped, and whether a flip is awaiting confirmation
+    fn record(&self, reachable: bool) -> (Option<Connectivity>, bool) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let now = now_ms();
+        inner.status.last_checked_at = Some(now);
+        if reachable == inner.status.online {
+            inner.contrary = 0;
+            return (None, false);
+        }
+        inner.contrary += 1;
+        if inner.contrary < FLIP_THRESHOLD {
+            return (None, true);
+        }
+        inner.contrary = 0;
+        inner.status.online = reachable;
+        inner.status.since = now;
+        (Some(inner.status.clone()), false)
+    }
+}
+
+// Single attempt with a short timeout; the confirmation probe is the retry
+async fn probe(app: &AppHandle) -> bool {
+    let Ok(url) = config::api_url(app, PROBE_PATH) else {
+        return false;
+    };
+    let http = app.state::<HttpClient>();
+    match http.client().get(url).timeout(PROBE_TIMEOUT).send().await {
+        // Any answer short of a server error means the API is reachable
+        Ok(response) => !response.status().is_server_error(),
+        Err(_) => false,
+    }
+}
+
+/// Start probing in the background; call once the scheduler is running.
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let reachable = probe(&app).await;
+            let monitor = app.state::<NetworkMonitor>();
+            let (flipped, confirming) = monitor.record(reachable);
+            if let Some(status) = flipped {
+                tracing::info!(online = status.online, "connectivity changed");
+                if status.online {
+                    let _ = app.emit(ONLINE_EVENT, &status);
+                    catch_up(&app);
+                    app.state::<Realtime>().wake();
+                } else {
+                    let _ = app.emit(OFFLINE_EVENT, &status);
+                }
+            }
+            let wait = if confirming || !monitor.is_online() {
+                FAST_INTERVAL
+            } else {
+                STEADY_INTERVAL
+            };
+            tokio::time::sleep(wait).await;
+        }
+    });
+}
+
+// Replay queued writes and sync now rather than at the next interval
+fn catch_up(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        for job in [outbox::FLUSH_JOB, sync::SYNC_JOB] {
+            if let Err(err) = jobs::run_now(&app, job).await {
+                tracing::debug!(%err, job, "could not start job after reconnecting");
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_connectivity(monitor: State<'_, NetworkMonitor>) -> AppResult<Connectivity> {
+    Ok(monitor.snapshot())
+}
+
+/// This machine's IPv4 addresses on the local network, the primary one first.
+pub(crate) fn lan_addresses() -> AppResult<Vec<Ipv4Addr>> {
+    let primary = match local_ip_address::local_ip() {
+        Ok(IpAddr::V4(ip)) => Some(ip),
+        _ => None,
+    };
+    let mut addresses: Vec<Ipv4Addr> = primary.into_iter().collect();
+    if let Ok(interfaces) = local_ip_address::list_afinet_netifas() {
+        for (_, ip) in interfaces {
+            if let IpAddr::V4(ip) = ip {
+                if !ip.is_loopback() && !ip.is_link_local() && !addresses.contains(&ip) {
+                    addresses.push(ip);
+                }
+            }
+        }
+    }
+    if addresses.is_empty() {
+        return Err(AppError::InvalidInput(
+            "this device is not connected to a network".into(),
+        ));
+    }
+    Ok(addresses)
+}
@@ -0,0 +1,648 @@
+//! Native OS notifications with action buttons, and routing of the user's response.
+//!
+//! A notification can carry a deep-link target and up to three actions. Clicking it, or an
+//! action with a target, brings the main window back from the tray and navigates through
+//! the deep-link router, which queues the route if the frontend is not listening yet. The
+//! `snooze` action is handled here and shows the notification again later. Every response is
+//! also emitted as `notifications:activated` for the frontend to act on. Responses only reach
+//! a running app; clicking one after quitting just launches it.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use url::Url;
+
+use crate::deeplink::{self, Route};
+use crate::error::{AppError, AppResult};
+use crate::windows;
+
+/// Emitted with a [`NotificationActivation`] when the user clicks a notification or an action.
+pub const ACTIVATED_EVENT: &str = "notifications:activated";
+/// Action id that re-shows the notification after its snooze delay.
+pub const SNOOZE_ACTION: &str = "snooze";
+
+const DEFAULT_SNOOZE: Duration = Duration::from_secs(10 * 60);
+const MAX_ACTIONS: usize = 3;
+const MAX_ACTION_ID_LEN: usize = 32;
+// Responses to anything older are ignored
+const MAX_REMEMBERED: usize = 200;
+
+/// A notification to show.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationRequest {
+    pub title: String,
+    #[serde(default)]
+    pub body: String,
+    /// Deep link opened when the notification itself is clicked, e.g. `momentum://project/42`.
+    pub target: Option<String>,
+    #[serde(default)]
+    pub actions: Vec<NotificationAction>,
+    /// Delay for the `snooze` action; 10 minutes when omitted.
+    pub snooze_minutes: Option<u64>,
+}
+
+/// A button on a notification.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationAction {
+    /// Reported back in [`NotificationActivation`]; `snooze` is handled by the app.
+    pub id: String,
+    pub title: String,
+    /// Deep link opened when this action is chosen, e.g. "Open project".
+    pub target: Option<String>,
+}
+
+/// Payload for `notifications:activated`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationActivation {
+    pub notification_id: u64,
+    /// `None` when the notification body was clicked.
+    pub action_id: Option<String>,
+    /// Where the app navigated, if anywhere.
+    pub route: Option<Route>,
+}
+
+/// Takes over `snooze` for a notification, given the delay it asked for.
+pub type SnoozeHandler = Arc<dyn Fn(&AppHandle, Duration) + Send + Sync>;
+
+/// A validated request, kept until the user responds or it ages out.
+#[derive(Clone)]
+struct Delivered {
+    request: NotificationRequest,
+    target: Option<Route>,
+    action_targets: Vec<(String, Option<Route>)>,
+    on_snooze: Option<SnoozeHandler>,
+}
+
+fn parse_target(target: Option<&str>) -> AppResult<Option<Route>> {
+    let Some(target) = target else {
+        return Ok(None);
+    };
+    Url::parse(target)
+        .ok()
+        .and_then(|url| Route::parse(&url))
+        .map(Some)
+        .ok_or_else(|| AppError::InvalidInput(format!("{target} is not an in-app link")))
+}
+
+fn validate(request: NotificationRequest) -> AppResult<Delivered> {
+    if request.title.trim().is_empty() {
+        return Err(AppError::InvalidInput(
+            "a notification needs a title".into(),
+        ));
+    }
+    if request.actions.len() > MAX_ACTIONS {
+        return Err(AppError::InvalidInput(format!(
+            "a notification can have at most {MAX_ACTIONS} actions"
+        )));
+    }
+    let mut action_targets = Vec::with_capacity(request.actions.len());
+    for action in &request.actions {
+        // Ids travel through OS activation strings, so they are kept plain
+        let plain = !action.id.is_empty()
+            && action.id.len() <= MAX_ACTION_ID_LEN
+            && action
+                .id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !plain || action.id == "default" {
+            return Err(AppError::InvalidInput(format!(
+                "invalid action id {:?}",
+                action.id
+            )));
+        }
+        action_targets.push((action.id.clone(), parse_target(action.target.as_deref())?));
+    }
+    Ok(Delivered {
+        target: parse_target(request.target.as_deref())?,
+        action_targets,
+        request,
+        on_snooze: None,
+    })
+}
+
+/// Notification delivery registered in app state.
+pub struct Notifications {
+    // `None` when the platform service is unavailable, such as a Linux session without one
+    backend: Option<os::Backend>,
+    next_id: AtomicU64,
+    delivered: Mutex<BTreeMap<u64, Delivered>>,
+}
+
+/// Connect to the platform notification service.
+pub fn init(app: &AppHandle) -> Notifications {
+    let backend = os::Backend::new(app)
+        .inspect_err(|err| tracing::warn!(%err, "native notifications unavailable"))
+        .ok();
+    Notifications {
+        backend,
+        next_id: AtomicU64::new(1),
+        delivered: Mutex::new(BTreeMap::new()),
+    }
+}
+
+impl Notifications {
+    fn show(&self, delivered: Delivered) -> AppResult<u64> {
+        let backend = self
+            .backend
+            .as_ref()
+            .ok_or_else(|| AppError::Internal("native notifications are unavailable".into()))?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        backend.show(id, &delivered.request)?;
+        let mut remembered = self.delivered.lock().unwrap_or_else(|e| e.into_inner());
+        remembered.insert(id, delivered);
+        while remembered.len() > MAX_REMEMBERED {
+            remembered.pop_first();
+        }
+        Ok(id)
+    }
+}
+
+/// Show a notification; returns its id.
+pub fn send(app: &AppHandle, request: NotificationRequest) -> AppResult<u64> {
+    let delivered = validate(request)?;
+    app.state::<Notifications>().show(delivered)
+}
+
+/// Show a notification whose snooze is handled by `on_snooze` instead of in memory.
+pub fn send_snoozable(
+    app: &AppHandle,
+    request: NotificationRequest,
+    on_snooze: SnoozeHandler,
+) -> AppResult<u64> {
+    let delivered = Delivered {
+        on_snooze: Some(on_snooze),
+        ..validate(request)?
+    };
+    app.state::<Notifications>().show(delivered)
+}
+
+// Called by the platform backends, from whatever thread the OS reports on
+fn activated(app: &AppHandle, id: u64, action_id: Option<String>) {
+    let Some(delivered) = app
+        .state::<Notifications>()
+        .delivered
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&id)
+    else {
+        return;
+    };
+    tracing::debug!(id, action = ?action_id, "notification activated");
+
+    let route = match &action_id {
+        None => delivered.target.clone(),
+        Some(action) if action == SNOOZE_ACTION => {
+            snooze(app, delivered.clone());
+            None
+        }
+        Some(action) => delivered
+            .action_targets
+            .iter()
+            .find(|(id, _)| id == action)
+            .and_then(|(_, target)| target.clone()),
+    };
+    if action_id.as_deref() != Some(SNOOZE_ACTION) {
+        windows::show_main_window(app);
+    }
+    if let Some(route) = &route {
+        deeplink::navigate(app, route.clone());
+    }
+    let _ = app.emit(
+        ACTIVATED_EVENT,
+        NotificationActivation {
+            notification_id: id,
+            action_id,
+            route,
+        },
+    );
+}
+
+// In memory unless the sender took it over; otherwise it does not survive quitting
+fn snooze(app: &AppHandle, delivered: Delivered) {
+    let delay = delivered
+        .request
+        .snooze_minutes
+        .map(|minutes| Duration::from_secs(minutes.max(1) * 60))
+        .unwrap_or(DEFAULT_SNOOZE);
+    if let Some(on_snooze) = &delivered.on_snooze {
+        on_snooze(app, delay);
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(delay).await;
+        if let Err(err) = app.state::<Notifications>().show(delivered) {
+            tracing::warn!(%err, "failed to show a snoozed notification");
+        }
+    });
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use std::collections::{BTreeMap, HashMap};
+    use std::sync::{Arc, Mutex};
+
+    use tauri::AppHandle;
+    use zbus::blocking::{Connection, Proxy};
+    use zbus::zvariant::Value;
+
+    use super::NotificationRequest;
+    use crate::error::{AppError, AppResult};
+
+    const DESTINATION: &str = "org.freedesktop.Notifications";
+    const PATH: &str = "/org/freedesktop/Notifications";
+    const INTERFACE: &str = "org.freedesktop.Notifications";
+    // The action key servers report for a click on the notification body
+    const DEFAULT_ACTION: &str = "default";
+    const MAX_TRACKED: usize = 200;
+
+    fn failed(err: zbus::Error) -> AppError {
+        AppError::Internal(format!("notification service: {err}"))
+    }
+
+    pub struct Backend {
+        connection: Connection,
+        // Server-assigned ids, which only ever grow, mapped to ours
+        server_ids: Arc<Mutex<BTreeMap<u32, u64>>>,
+    }
+
+    impl Backend {
+        pub fn new(app: &AppHandle) -> AppResult<Self> {
+            let connection = Connection::session().map_err(failed)?;
+            let proxy = Proxy::new_owned(connection.clone(), DESTINATION, PATH, INTERFACE)
+                .map_err(failed)?;
+            let signals = proxy.receive_signal("ActionInvoked").map_err(failed)?;
+            let server_ids = Arc::new(Mutex::new(BTreeMap::<u32, u64>::new()));
+            let ids = server_ids.clone();
+            let app = app.clone();
+            std::thread::Builder::new()
+                .name("notification-actions".into())
+                .spawn(move || {
+                    let _proxy = proxy;
+                    for message in signals {
+                        let Ok((server_id, key)) = message.body().deserialize::<(u32, String)>()
+                        else {
+                            continue;
+                        };
+                        // Other apps' notifications signal on the same bus
+                        let id = ids
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .remove(&server_id);
+                        if let Some(id) = id {
+                            super::activated(&app, id, (key != DEFAULT_ACTION).then_some(key));
+                        }
+                    }
+                })?;
+            Ok(Self {
+                connection,
+                server_ids,
+            })
+        }
+
+        pub fn show(&self, id: u64, request: &NotificationRequest) -> AppResult<()> {
+            let mut actions = vec![DEFAULT_ACTION, ""];
+            for action in &request.actions {
+                actions.extend([action.id.as_str(), action.title.as_str()]);
+            }
+            let hints = HashMap::from([("desktop-entry", Value::from("momentum"))]);
+            let reply = self
+                .connection
+                .call_method(
+                    Some(DESTINATION),
+                    PATH,
+                    Some(INTERFACE),
+                    "Notify",
+                    &(
+                        "Momentum",
+                        0u32,
+                        "",
+                        request.title.as_str(),
+                        request.body.as_str(),
+                        actions,
+                        hints,
+                        -1i32,
+                    ),
+                )
+                .map_err(failed)?;
+            let server_id: u32 = reply.body().deserialize().map_err(failed)?;
+            let mut ids = self.server_ids.lock().unwrap_or_else(|e| e.into_inner());
+            ids.insert(server_id, id);
+            while ids.len() > MAX_TRACKED {
+                ids.pop_first();
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(windows)]
+mod os {
+    use tauri::AppHandle;
+    use windows::core::{IInspectable, Interface, Ref, HSTRING};
+    use windows::Data::Xml::Dom::XmlDocument;
+    use windows::Foundation::TypedEventHandler;
+    use windows::UI::Notifications::{
+        ToastActivatedEventArgs, ToastNotification, ToastNotificationManager,
+    };
+
+    use super::NotificationRequest;
+    use crate::error::{AppError, AppResult};
+
+    fn failed(err: windows::core::Error) -> AppError {
+        AppError::Internal(format!("toast notification: {err}"))
+    }
+
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    // Activation hands back the toast's argument string, so it carries the ids
+    fn arguments(id: u64, action: Option<&str>) -> String {
+        match action {
+            Some(action) => format!("{id}/{action}"),
+            None => id.to_string(),
+        }
+    }
+
+    fn parse_arguments(arguments: &str) -> Option<(u64, Option<String>)> {
+        match arguments.split_once('/') {
+            Some((id, action)) => Some((id.parse().ok()?, Some(action.to_string()))),
+            None => Some((arguments.parse().ok()?, None)),
+        }
+    }
+
+    pub struct Backend {
+        app: AppHandle,
+        // The installer registers the bundle identifier as the app user model id
+        app_id: HSTRING,
+    }
+
+    impl Backend {
+        pub fn new(app: &AppHandle) -> AppResult<Self> {
+            Ok(Self {
+                app: app.clone(),
+                app_id: HSTRING::from(app.config().identifier.as_str()),
+            })
+        }
+
+        pub fn show(&self, id: u64, request: &NotificationRequest) -> AppResult<()> {
+            let actions: String = request
+                .actions
+                .iter()
+                .map(|action| {
+                    format!(
+                        r#"<action content="{}" arguments="{}" activationType="foreground"/>"#,
+                        escape(&action.title),
+                        escape(&arguments(id, Some(&action.id)))
+                    )
+                })
+                .collect();
+            let xml = format!(
+                r#"<toast launch="{}"><visual><binding template="ToastGeneric"><text>{}</text><text>{}</text></binding></visual><actions>{actions}</actions></toast>"#,
+                escape(&arguments(id, None)),
+                escape(&request.title),
+                escape(&request.body)
+            );
+            let document = XmlDocument::new().map_err(failed)?;
+            document.LoadXml(&HSTRING::from(xml)).map_err(failed)?;
+            let toast = ToastNotification::CreateToastNotification(&document).map_err(failed)?;
+            let app = self.app.clone();
+            let handler = TypedEventHandler::new(
+                move |_: Ref<ToastNotification>, args: Ref<IInspectable>| {
+                    if let Some(args) = args.as_ref() {
+                        let arguments = args.cast::<ToastActivatedEventArgs>()?.Arguments()?;
+                        if let Some((id, action)) = parse_arguments(&arguments.to_string_lossy()) {
+                            super::activated(&app, id, action);
+                        }
+                    }
+                    Ok(())
+                },
+            );
+            toast.Activated(&handler).map_err(failed)?;
+            ToastNotificationManager::CreateToastNotifierWithId(&self.app_id)
+                .and_then(|notifier| notifier.Show(&toast))
+                .map_err(failed)
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use std::collections::BTreeMap;
+    use std::sync::Mutex;
+
+    use block2::RcBlock;
+    use objc2::rc::Retained;
+    use objc2::runtime::{Bool, ProtocolObject};
+    use objc2::{define_class, msg_send, AllocAnyThread, DefinedClass};
+    use objc2_foundation::{
+        NSArray, NSBundle, NSError, NSObject, NSObjectProtocol, NSSet, NSString,
+    };
+    use objc2_user_notifications::{
+        UNAuthorizationOptions, UNMutableNotificationContent, UNNotification, UNNotificationAction,
+        UNNotificationActionOptions, UNNotificationCategory, UNNotificationCategoryOptions,
+        UNNotificationDefaultActionIdentifier, UNNotificationPresentationOptions,
+        UNNotificationRequest, UNNotificationResponse, UNUserNotificationCenter,
+        UNUserNotificationCenterDelegate,
+    };
+    use tauri::AppHandle;
+
+    use super::{NotificationRequest, SNOOZE_ACTION};
+    use crate::error::{AppError, AppResult};
+
+    struct Ivars {
+        app: AppHandle,
+    }
+
+    define_class!(
+        #[unsafe(super(NSObject))]
+        #[name = "MomentumNotificationDelegate"]
+        #[ivars = Ivars]
+        struct Delegate;
+
+        unsafe impl NSObjectProtocol for Delegate {}
+
+        unsafe impl UNUserNotificationCenterDelegate for Delegate {
+            #[unsafe(method(userNotificationCenter:didReceiveNotificationResponse:withCompletionHandler:))]
+            fn did_receive_response(
+                &self,
+                _center: &UNUserNotificationCenter,
+                response: &UNNotificationResponse,
+                completion_handler: &block2::DynBlock<dyn Fn()>,
+            ) {
+                let identifier = response.notification().request().identifier().to_string();
+                let action = response.actionIdentifier().to_string();
+                let clicked = unsafe { UNNotificationDefaultActionIdentifier }.to_string();
+                if let Ok(id) = identifier.parse() {
+                    super::activated(&self.ivars().app, id, (action != clicked).then_some(action));
+                }
+                completion_handler.call(());
+            }
+
+            // Banners are hidden while the app is frontmost unless asked for
+            #[unsafe(method(userNotificationCenter:willPresentNotification:withCompletionHandler:))]
+            fn will_present(
+                &self,
+                _center: &UNUserNotificationCenter,
+                _notification: &UNNotification,
+                completion_handler: &block2::DynBlock<dyn Fn(UNNotificationPresentationOptions)>,
+            ) {
+                completion_handler.call((UNNotificationPresentationOptions::Banner
+                    | UNNotificationPresentationOptions::List,));
+            }
+        }
+    );
+
+    impl Delegate {
+        fn new(app: &AppHandle) -> Retained<Self> {
+            let this = Self::alloc().set_ivars(Ivars { app: app.clone() });
+            unsafe { msg_send![super(this), init] }
+        }
+    }
+
+    type ActionSet = Vec<(String, String)>;
+
+    pub struct Backend {
+        // Each distinct set of buttons is a category; the center takes them all at once
+        categories: Mutex<BTreeMap<String, ActionSet>>,
+    }
+
+    impl Backend {
+        pub fn new(app: &AppHandle) -> AppResult<Self> {
+            // The notification center raises for processes without a bundle, like `tauri dev`
+            if NSBundle::mainBundle().bundleIdentifier().is_none() {
+                return Err(AppError::Internal(
+                    "notifications need the bundled app".into(),
+                ));
+            }
+            let center = UNUserNotificationCenter::currentNotificationCenter();
+            let delegate = Delegate::new(app);
+            center.setDelegate(Some(ProtocolObject::from_ref(&*delegate)));
+            // The center holds its delegate weakly, so it lives as long as the process
+            std::mem::forget(delegate);
+            let answered = RcBlock::new(|granted: Bool, _error: *mut NSError| {
+                if !granted.as_bool() {
+                    tracing::info!("notifications are not permitted");
+                }
+            });
+            center.requestAuthorizationWithOptions_completionHandler(
+                UNAuthorizationOptions::Alert | UNAuthorizationOptions::Sound,
+                &answered,
+            );
+            Ok(Self {
+                categories: Mutex::new(BTreeMap::new()),
+            })
+        }
+
+        fn category(
+            &self,
+            center: &UNUserNotificationCenter,
+            request: &NotificationRequest,
+        ) -> String {
+            let actions: ActionSet = request
+                .actions
+                .iter()
+                .map(|action| (action.id.clone(), action.title.clone()))
+                .collect();
+            let key = actions
+                .iter()
+                .map(|(id, title)| format!("{id}:{title}"))
+                .collect::<Vec<_>>()
+                .join("|");
+            let mut categories = self.categories.lock().unwrap_or_else(|e| e.into_inner());
+            if categories.contains_key(&key) {
+                return key;
+            }
+            categories.insert(key.clone(), actions);
+            let registered: Vec<Retained<UNNotificationCategory>> = categories
+                .iter()
+                .map(|(key, actions)| {
+                    let actions: Vec<Retained<UNNotificationAction>> = actions
+                        .iter()
+                        .map(|(id, title)| {
+                            // Snoozing happens in the background without raising the app
+                            let options = if id == SNOOZE_ACTION {
+                                UNNotificationActionOptions::empty()
+                            } else {
+                                UNNotificationActionOptions::Foreground
+                            };
+                            UNNotificationAction::actionWithIdentifier_title_options(
+                                &NSString::from_str(id),
+                                &NSString::from_str(title),
+                                options,
+                            )
+                        })
+                        .collect();
+                    UNNotificationCategory::categoryWithIdentifier_actions_intentIdentifiers_options(
+                        &NSString::from_str(key),
+                        &NSArray::from_retained_slice(&actions),
+                        &NSArray::new(),
+                        UNNotificationCategoryOptions::empty(),
+                    )
+                })
+                .collect();
+            center.setNotificationCategories(&NSSet::from_retained_slice(&registered));
+            key
+        }
+
+        pub fn show(&self, id: u64, request: &NotificationRequest) -> AppResult<()> {
+            let center = UNUserNotificationCenter::currentNotificationCenter();
+            let content = UNMutableNotificationContent::new();
+            content.setTitle(&NSString::from_str(&request.title));
+            content.setBody(&NSString::from_str(&request.body));
+            content.setCategoryIdentifier(&NSString::from_str(&self.category(&center, request)));
+            let request = UNNotificationRequest::requestWithIdentifier_content_trigger(
+                &NSString::from_str(&id.to_string()),
+                &content,
+                None,
+            );
+            let added = RcBlock::new(|error: *mut NSError| {
+                if let Some(error) = unsafe { error.as_ref() } {
+                    tracing::warn!(error = %error.localizedDescription(), "notification not shown");
+                }
+            });
+            center.addNotificationRequest_withCompletionHandler(&request, Some(&added));
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows, target_os = "macos")))]
+mod os {
+    use tauri::AppHandle;
+
+    use super::NotificationRequest;
+    use crate::error::{AppError, AppResult};
+
+    // Mobile notifications need the platform plugins, which this build does not include
+    pub struct Backend;
+
+    impl Backend {
+        pub fn new(_app: &AppHandle) -> AppResult<Self> {
+            Err(AppError::Internal(
+                "notifications are not supported on this platform".into(),
+            ))
+        }
+
+        pub fn show(&self, _id: u64, _request: &NotificationRequest) -> AppResult<()> {
+            Ok(())
+        }
+    }
+}
+
+/// Show a notification; returns its id, as reported in `notifications:activated`.
+#[tauri::command]
+pub async fn send_notification(app: AppHandle, request: NotificationRequest) -> AppResult<u64> {
+    send(&app, request)
+}
@@ -0,0 +1,432 @@
+//! Text recognition for scanned PDFs and photos, using ocrs models.
+//!
+//! Models come in language packs, each a folder under `data_dir/ocr` holding a detection
+//! model, a recognition model, and a `pack.json` manifest. Packs in [`CATALOG`] install
+//! with one command; others can be dropped in by hand with their own manifest. Loaded
+//! engines are kept for the life of the process since loading a pack takes a moment.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use image::{DynamicImage, GenericImageView};
+use ocrs::{ImageSource, OcrEngine, OcrEngineParams, TextItem};
+use pdfium_render::prelude::PdfRenderConfig;
+use rten::Model;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::error::{AppError, AppResult};
+use crate::http_client::{HttpClient, RetryPolicy};
+use crate::pdf::PdfEngine;
+
+const PACKS_DIR_NAME: &str = "ocr";
+const MANIFEST_FILE: &str = "pack.json";
+const DETECTION_FILE: &str = "detection.rten";
+const RECOGNITION_FILE: &str = "recognition.rten";
+const DEFAULT_PACK: &str = "latin";
+// Scans are usually 200-300 DPI; rendering PDFs at the same keeps small print legible
+const PDF_RENDER_DPI: f32 = 300.0;
+
+/// A pack that can be installed by id.
+struct CatalogPack {
+    id: &'static str,
+    name: &'static str,
+    detection_url: &'static str,
+    recognition_url: &'static str,
+}
+
+const CATALOG: &[CatalogPack] = &[CatalogPack {
+    id: DEFAULT_PACK,
+    name: "Latin alphabet (English and Western European)",
+    detection_url: "https://ocrs-models.s3-accelerate.amazonaws.com/text-detection.rten",
+    recognition_url: "https://ocrs-models.s3-accelerate.amazonaws.com/text-recognition.rten",
+}];
+
+/// `pack.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PackManifest {
+    id: String,
+    name: String,
+    /// Characters the recognition model emits, in model order; ocrs' default when omitted.
+    #[serde(default)]
+    alphabet: Option<String>,
+}
+
+/// A language pack, installed or available to install.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OcrPack {
+    pub id: String,
+    pub name: String,
+    pub installed: bool,
+    /// Bytes on disk when installed.
+    pub size: Option<u64>,
+}
+
+/// Part of a page to read, as fractions of its width and height from the top left.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OcrRegion {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// A recognized line with its box as fractions of the whole page.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OcrLine {
+    pub text: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OcrPage {
+    /// 1-based; always 1 for images.
+    pub page: u16,
+    pub text: String,
+    pub lines: Vec<OcrLine>,
+}
+
+/// Language packs and loaded engines, registered in app state.
+pub struct Ocr {
+    dir: PathBuf,
+    engines: Mutex<HashMap<String, Arc<OcrEngine>>>,
+}
+
+/// Open the pack folder under `data_dir/ocr`.
+pub fn init(data_dir: &Path) -> AppResult<Ocr> {
+    let dir = data_dir.join(PACKS_DIR_NAME);
+    fs::create_dir_all(&dir)?;
+    Ok(Ocr {
+        dir,
+        engines: Mutex::new(HashMap::new()),
+    })
+}
+
+fn ocr_error(err: impl std::fmt::Display) -> AppError {
+    AppError::Ocr(err.to_string())
+}
+
+// Pack ids become folder names
+fn validate_id(id: &str) -> AppResult<()> {
+    if id.is_empty()
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(AppError::InvalidInput(format!(
+            "invalid language pack id {id}"
+        )));
+    }
+    Ok(())
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|e| e.metadata().ok())
+                .map(|m| m.len())
+                .sum()
+        })
+        .unwrap_or_default()
+}
+
+impl Ocr {
+    fn pack_dir(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+
+    fn manifest(&self, id: &str) -> Option<PackManifest> {
+        let bytes = fs::read(self.pack_dir(id).join(MANIFEST_FILE)).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    fn packs(&self) -> AppResult<Vec<OcrPack>> {
+        let mut packs: Vec<OcrPack> = CATALOG
+            .iter()
+            .map(|pack| OcrPack {
+                id: pack.id.into(),
+                name: pack.name.into(),
+                installed: false,
+                size: None,
+            })
+            .collect();
+        for entry in fs::read_dir(&self.dir)?.flatten() {
+            let id = entry.file_name().to_string_lossy().into_owned();
+            let Some(manifest) = self.manifest(&id) else {
+                continue;
+            };
+            let size = Some(dir_size(&entry.path()));
+            match packs.iter_mut().find(|p| p.id == id) {
+                Some(pack) => {
+                    pack.installed = true;
+                    pack.size = size;
+                }
+                None => packs.push(OcrPack {
+                    id,
+                    name: manifest.name,
+                    installed: true,
+                    size,
+                }),
+            }
+        }
+        Ok(packs)
+    }
+
+    fn engine(&self, id: &str) -> AppResult<Arc<OcrEngine>> {
+        if let Some(engine) = self
+            .engines
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+        {
+            return Ok(engine.clone());
+        }
+        let manifest = self
+            .manifest(id)
+            .ok_or_else(|| AppError::not_found("ocr language pack", id))?;
+        let dir = self.pack_dir(id);
+        let engine = OcrEngine::new(OcrEngineParams {
+            detection_model: Some(Model::load_file(dir.join(DETECTION_FILE)).map_err(ocr_error)?),
+            recognition_model: Some(
+                Model::load_file(dir.join(RECOGNITION_FILE)).map_err(ocr_error)?,
+            ),
+            alphabet: manifest.alphabet,
+            ..Default::default()
+        })
+        .map_err(ocr_error)?;
+        let engine = Arc::new(engine);
+        self.engines
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id.to_string(), engine.clone());
+        Ok(engine)
+    }
+}
+
+fn recognize(
+    engine: &OcrEngine,
+    image: &DynamicImage,
+    region: Option<OcrRegion>,
+) -> AppResult<(String, Vec<OcrLine>)> {
+    let (page_w, page_h) = image.dimensions();
+    let (left, top, crop) = match region {
+        Some(r) => {
+            let clamp = |v: f32| v.clamp(0.0, 1.0);
+            let left = (clamp(r.x) * page_w as f32) as u32;
+            let top = (clamp(r.y) * page_h as f32) as u32;
+            let width = ((clamp(r.width) * page_w as f32) as u32).min(page_w - left);
+            let height = ((clamp(r.height) * page_h as f32) as u32).min(page_h - top);
+            if width == 0 || height == 0 {
+                return Err(AppError::InvalidInput("the region is empty".into()));
+            }
+            (left, top, image.crop_imm(left, top, width, height))
+        }
+        None => (0, 0, image.clone()),
+    };
+
+    let rgb = crop.into_rgb8();
+    let source = ImageSource::from_bytes(rgb.as_raw(), rgb.dimensions()).map_err(ocr_error)?;
+    let input = engine.prepare_input(source).map_err(ocr_error)?;
+    let words = engine.detect_words(&input).map_err(ocr_error)?;
+    let line_rects = engine.find_text_lines(&input, &words);
+    let recognized = engine
+        .recognize_text(&input, &line_rects)
+        .map_err(ocr_error)?;
+
+    let lines: Vec<OcrLine> = recognized
+        .into_iter()
+        .flatten()
+        .filter_map(|line| {
+            let text = line.to_string().trim().to_string();
+            // Single characters are nearly always specks or ruling lines
+            if text.chars().count() < 2 {
+                return None;
+            }
+            let rect = line.bounding_rect();
+            Some(OcrLine {
+                text,
+                x: (left as f32 + rect.left() as f32) / page_w as f32,
+                y: (top as f32 + rect.top() as f32) / page_h as f32,
+                width: rect.width() as f32 / page_w as f32,
+                height: rect.height() as f32 / page_h as f32,
+            })
+        })
+        .collect();
+    let text = lines
+        .iter()
+        .map(|l| l.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok((text, lines))
+}
+
+fn is_pdf(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("pdf"))
+}
+
+/// Language packs in the catalog and on disk.
+#[tauri::command]
+pub async fn ocr_list_packs(ocr: State<'_, Ocr>) -> AppResult<Vec<OcrPack>> {
+    ocr.packs()
+}
+
+/// Download and install a pack from the catalog.
+#[tauri::command]
+pub async fn ocr_install_pack(
+    app: AppHandle,
+    ocr: State<'_, Ocr>,
+    id: String,
+) -> AppResult<OcrPack> {
+    let pack = CATALOG
+        .iter()
+        .find(|pack| pack.id == id)
+        .ok_or_else(|| AppError::not_found("ocr language pack", id.clone()))?;
+    let http = app.state::<HttpClient>();
+    // Staged beside the final folder so a failed download never leaves a half-installed pack
+    let staging = ocr.dir.join(format!(".{id}.partial"));
+    let _ = tokio::fs::remove_dir_all(&staging).await;
+    tokio::fs::create_dir_all(&staging).await?;
+    for (url, file) in [
+        (pack.detection_url, DETECTION_FILE),
+        (pack.recognition_url, RECOGNITION_FILE),
+    ] {
+        let response = http
+            .send(http.client().get(url), RetryPolicy::default())
+            .await?
+            .error_for_status()?;
+        tokio::fs::write(staging.join(file), response.bytes().await?).await?;
+    }
+    let manifest = PackManifest {
+        id: id.clone(),
+        name: pack.name.into(),
+        alphabet: None,
+    };
+    tokio::fs::write(
+        staging.join(MANIFEST_FILE),
+        serde_json::to_vec_pretty(&manifest)?,
+    )
+    .await?;
+
+    let target = ocr.pack_dir(&id);
+    let check = staging.clone();
+    let verify = tauri::async_runtime::spawn_blocking(move || -> AppResult<()> {
+        // A truncated or HTML error body would otherwise only fail at first use
+        Model::load_file(check.join(DETECTION_FILE)).map_err(ocr_error)?;
+        Model::load_file(check.join(RECOGNITION_FILE)).map_err(ocr_error)?;
+        Ok(())
+    })
+    .await?;
+    if let Err(err) = verify {
+        let _ = tokio::fs::remove_dir_all(&staging).await;
+        return Err(err);
+    }
+    let _ = tokio::fs::remove_dir_all(&target).await;
+    tokio::fs::rename(&staging, &target).await?;
+    ocr.engines
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&id);
+    tracing::info!(%id, "ocr language pack installed");
+    Ok(OcrPack {
+        id,
+        name: manifest.name,
+        installed: true,
+        size: Some(dir_size(&target)),
+    })
+}
+
+/// Delete an installed pack.
+#[tauri::command]
+pub async fn ocr_remove_pack(ocr: State<'_, Ocr>, id: String) -> AppResult<()> {
+    validate_id(&id)?;
+    ocr.engines
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&id);
+    match tokio::fs::remove_dir_all(ocr.pack_dir(&id)).await {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            Err(AppError::not_found("ocr language pack", id))
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Recognize text in an image or PDF, optionally only inside `region` of each page.
+///
+/// PDFs are read page by page, or only `page` (1-based) when given. `language` picks the
+/// pack and defaults to the Latin pack.
+#[tauri::command]
+pub async fn ocr_extract_text(
+    ocr: State<'_, Ocr>,
+    pdf: State<'_, PdfEngine>,
+    path: PathBuf,
+    region: Option<OcrRegion>,
+    page: Option<u16>,
+    language: Option<String>,
+) -> AppResult<Vec<OcrPage>> {
+    let language = language.unwrap_or_else(|| DEFAULT_PACK.into());
+    validate_id(&language)?;
+    let engine = ocr.engine(&language)?;
+    let pdfium = if is_pdf(&path) {
+        Some(pdf.pdfium()?)
+    } else {
+        None
+    };
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let Some(pdfium) = pdfium else {
+            let image = image::open(&path).map_err(|e| {
+                AppError::InvalidInput(format!("cannot read image {}: {e}", path.display()))
+            })?;
+            let (text, lines) = recognize(&engine, &image, region)?;
+            return Ok(vec![OcrPage {
+                page: 1,
+                text,
+                lines,
+            }]);
+        };
+
+        let document = pdfium.load_pdf_from_file(&path, None)?;
+        let count = document.pages().len();
+        let range = match page {
+            Some(page) if page == 0 || page > count => {
+                return Err(AppError::InvalidInput(format!(
+                    "page {page} is outside a {count}-page document"
+                )))
+            }
+            Some(page) => page..=page,
+            None => 1..=count,
+        };
+        let config = PdfRenderConfig::new().scale_page_by_factor(PDF_RENDER_DPI / 72.0);
+        let mut pages = Vec::new();
+        for number in range {
+            let image = document
+                .pages()
+                .get(number - 1)?
+                .render_with_config(&config)?
+                .as_image();
+            let (text, lines) = recognize(&engine, &image, region)?;
+            pages.push(OcrPage {
+                page: number,
+                text,
+                lines,
+            });
+        }
+        tracing::debug!(pages = pages.len(), path = %path.display(), "ocr finished");
+        Ok(pages)
+    })
+    .await?
+}
@@ -0,0 +1,395 @@
+//! Durable outbox for API writes made while offline.
+//!
+//! Operations are stored in SQLite and replayed in enqueue order. A retryable failure stops the
+//! replay so later writes never overtake earlier ones; a rejected write is parked as `failed`.
+
+use std::time::Duration;
+
+use rusqlite::{params, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_http::reqwest::{Client, Method, StatusCode};
+
+use crate::auth;
+use crate::badge;
+use crate::config;
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::http_client::HttpClient;
+use crate::jobs::Scheduler;
+use crate::network::NetworkMonitor;
+
+/// Job kind that retries queued operations.
+pub const FLUSH_JOB: &str = "outbox.flush";
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+const MAX_BACKOFF_MS: i64 = 5 * 60 * 1000;
+
+const STATUS_PENDING: &str = "pending";
+const STATUS_FAILED: &str = "failed";
+
+/// Emitted with an [`OutboxDelivery`] when the server accepts a queued operation.
+pub const DELIVERED_EVENT: &str = "outbox:delivered";
+/// Emitted with an [`OutboxOperation`] when the server rejects one permanently.
+pub const FAILED_EVENT: &str = "outbox:failed";
+
+/// A write the frontend wants delivered eventually.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboxRequest {
+    pub method: String,
+    /// API path, resolved against the configured base URL.
+    pub path: String,
+    pub body: Option<Value>,
+    /// Re-enqueueing the same key returns the existing operation; generated when omitted.
+    pub idempotency_key: Option<String>,
+}
+
+/// A queued operation as shown to the frontend.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboxOperation {
+    pub id: String,
+    pub idempotency_key: String,
+    pub method: String,
+    pub path: String,
+    pub body: Option<Value>,
+    /// `pending` or `failed`.
+    pub status: String,
+    pub attempts: i64,
+    pub next_attempt_at: i64,
+    pub last_error: Option<String>,
+    pub created_at: i64,
+}
+
+impl OutboxOperation {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let body: Option<String> = row.get("body")?;
+        Ok(Self {
+            id: row.get("id")?,
+            idempotency_key: row.get("idempotency_key")?,
+            method: row.get("method")?,
+            path: row.get("path")?,
+            body: body.and_then(|b| serde_json::from_str(&b).ok()),
+            status: row.get("status")?,
+            attempts: row.get("attempts")?,
+            next_attempt_at: row.get("next_attempt_at")?,
+            last_error: row.get("last_error")?,
+            created_at: row.get("created_at")?,
+        })
+    }
+}
+
+/// Payload for `outbox:delivered`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboxDelivery {
+    pub id: String,
+    pub idempotency_key: String,
+    pub status: u16,
+}
+
+enum DeliveryError {
+    Retry(AppError),
+    Rejected(AppError),
+}
+
+/// Outbox handle registered in app state.
+pub struct Outbox {
+    db: Db,
+    // Only one replay at a time, or two flushes could send the same head twice
+    flushing: tokio::sync::Mutex<()>,
+}
+
+impl Outbox {
+    pub fn new(db: Db) -> Self {
+        Self {
+            db,
+            flushing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Operations the server rejected, waiting for the user to retry or cancel them.
+    pub async fn failed_count(&self) -> AppResult<i64> {
+        self.db
+            .run(|conn| {
+                Ok(conn.query_row(
+                    "SELECT COUNT(*) FROM outbox WHERE status = ?1",
+                    [STATUS_FAILED],
+                    |row| row.get(0),
+                )?)
+            })
+            .await
+    }
+
+    /// Replay due operations in order until the queue drains or one must be retried later.
+    pub async fn flush(&self, app: &AppHandle) -> AppResult<()> {
+        let Ok(_flushing) = self.flushing.try_lock() else {
+            return Ok(());
+        };
+        // The connectivity monitor triggers a flush once the API is reachable again
+        if !app.state::<NetworkMonitor>().is_online() {
+            return Ok(());
+        }
+        let http = app.state::<HttpClient>();
+        loop {
+            let head = self
+                .db
+                .run(|conn| {
+                    Ok(conn
+                        .query_row(
+                            "SELECT * FROM outbox WHERE status = ?1 ORDER BY seq LIMIT 1",
+                            [STATUS_PENDING],
+                            OutboxOperation::from_row,
+                        )
+                        .optional()?)
+                })
+                .await?;
+            let Some(op) = head else {
+                return Ok(());
+            };
+            if op.next_attempt_at > now_ms() {
+                return Ok(());
+            }
+
+            match deliver(app, &http.client(), &op).await {
+                Ok(status) => {
+                    let id = op.id.clone();
+                    self.db
+                        .run(move |conn| {
+                            conn.execute("DELETE FROM outbox WHERE id = ?1", [id])?;
+                            Ok(())
+                        })
+                        .await?;
+                    tracing::info!(id = %op.id, %status, "outbox operation delivered");
+                    let _ = app.emit(
+                        DELIVERED_EVENT,
+                        OutboxDelivery {
+                            id: op.id,
+                            idempotency_key: op.idempotency_key,
+                            status,
+                        },
+                    );
+                }
+                Err(DeliveryError::Retry(err)) => {
+                    let attempts = op.attempts + 1;
+                    let backoff = 1000i64
+                        .saturating_mul(1 << attempts.min(20))
+                        .min(MAX_BACKOFF_MS);
+                    let (id, error) = (op.id.clone(), err.to_string());
+                    self.db
+                        .run(move |conn| {
+                            conn.execute(
+                                "UPDATE outbox SET attempts = ?2, next_attempt_at = ?3,
+                                     last_error = ?4
+                                 WHERE id = ?1",
+                                params![id, attempts, now_ms() + backoff, error],
+                            )?;
+                            Ok(())
+                        })
+                        .await?;
+                    tracing::debug!(id = %op.id, %err, attempts, "outbox delivery will retry");
+                    return Ok(());
+                }
+                Err(DeliveryError::Rejected(err)) => {
+                    let (id, error) = (op.id.clone(), err.to_string());
+                    let failed = self
+                        .db
+                        .run(move |conn| {
+                            conn.execute(
+                                "UPDATE outbox SET status = ?2, attempts = attempts + 1,
+                                     last_error = ?3
+                                 WHERE id = ?1",
+                                params![id, STATUS_FAILED, error],
+                            )?;
+                            Ok(conn
+                                .query_row(
+                                    "SELECT * FROM outbox WHERE id = ?1",
+                                    [id],
+                                    OutboxOperation::from_row,
+                                )
+                                .optional()?)
+                        })
+                        .await?;
+                    tracing::warn!(id = %op.id, %err, "outbox operation rejected");
+                    if let Some(failed) = failed {
+                        let _ = app.emit(FAILED_EVENT, failed);
+                    }
+                    badge::spawn_refresh(app);
+                }
+            }
+        }
+    }
+}
+
+async fn deliver(
+    app: &AppHandle,
+    client: &Client,
+    op: &OutboxOperation,
+) -> Result<u16, DeliveryError> {
+    // Config and auth problems can clear up without the operation changing
+    let url = config::api_url(app, &op.path).map_err(DeliveryError::Retry)?;
+    let token = auth::access_token(app)
+        .await
+        .map_err(DeliveryError::Retry)?;
+    let method = Method::from_bytes(op.method.as_bytes())
+        .map_err(|e| DeliveryError::Rejected(AppError::InvalidInput(e.to_string())))?;
+
+    let mut request = client
+        .request(method, url)
+        .header("Idempotency-Key", &op.idempotency_key);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    if let Some(body) = &op.body {
+        request = request
+            .header("Content-Type", "application/json")
+            .body(body.to_string());
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| DeliveryError::Retry(e.into()))?;
+    let status = response.status();
+    match response.error_for_status() {
+        Ok(_) => Ok(status.as_u16()),
+        // Expired sessions and throttling resolve on their own; other 4xx never will
+        Err(err)
+            if status.is_server_error()
+                || matches!(
+                    status,
+                    StatusCode::UNAUTHORIZED
+                        | StatusCode::REQUEST_TIMEOUT
+                        | StatusCode::TOO_MANY_REQUESTS
+                ) =>
+        {
+            Err(DeliveryError::Retry(err.into()))
+        }
+        Err(err) => Err(DeliveryError::Rejected(err.into())),
+    }
+}
+
+fn spawn_flush(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(err) = app.state::<Outbox>().flush(&app).await {
+            tracing::warn!(%err, "outbox flush failed");
+        }
+    });
+}
+
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(FLUSH_JOB, Some(FLUSH_INTERVAL), |app| async move {
+        app.state::<Outbox>().flush(&app).await
+    });
+}
+
+/// Queue a write and attempt delivery right away.
+pub(crate) async fn enqueue(app: &AppHandle, request: OutboxRequest) -> AppResult<OutboxOperation> {
+    let method = request.method.to_ascii_uppercase();
+    if !matches!(method.as_str(), "POST" | "PUT" | "PATCH" | "DELETE") {
+        return Err(AppError::InvalidInput(format!(
+            "{method} is not a write method"
+        )));
+    }
+    // Rejects paths that would resolve to another host before they are queued
+    config::api_url(app, &request.path)?;
+
+    let key = request
+        .idempotency_key
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let body = request.body.map(|b| b.to_string());
+    let op = app
+        .state::<Outbox>()
+        .db
+        .run(move |conn| {
+            let now = now_ms();
+            conn.execute(
+                "INSERT INTO outbox (id, idempotency_key, method, path, body, next_attempt_at,
+                                     created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)
+                 ON CONFLICT(idempotency_key) DO NOTHING",
+                params![
+                    uuid::Uuid::new_v4().to_string(),
+                    key,
+                    method,
+                    request.path,
+                    body,
+                    now
+                ],
+            )?;
+            Ok(conn.query_row(
+                "SELECT * FROM outbox WHERE idempotency_key = ?1",
+                [key],
+                OutboxOperation::from_row,
+            )?)
+        })
+        .await?;
+    spawn_flush(app);
+    Ok(op)
+}
+
+/// Queue a write and attempt delivery right away.
+#[tauri::command]
+pub async fn outbox_enqueue(app: AppHandle, request: OutboxRequest) -> AppResult<OutboxOperation> {
+    enqueue(&app, request).await
+}
+
+/// Queued and failed operations in replay order.
+#[tauri::command]
+pub async fn list_outbox(outbox: State<'_, Outbox>) -> AppResult<Vec<OutboxOperation>> {
+    outbox
+        .db
+        .run(|conn| {
+            Ok(conn
+                .prepare("SELECT * FROM outbox ORDER BY seq")?
+                .query_map([], OutboxOperation::from_row)?
+                .collect::<rusqlite::Result<_>>()?)
+        })
+        .await
+}
+
+/// Drop an operation; one already in flight may still reach the server.
+#[tauri::command]
+pub async fn cancel_outbox_operation(
+    app: AppHandle,
+    outbox: State<'_, Outbox>,
+    id: String,
+) -> AppResult<()> {
+    let lookup = id.clone();
+    let deleted = outbox
+        .db
+        .run(move |conn| Ok(conn.execute("DELETE FROM outbox WHERE id = ?1", [lookup])?))
+        .await?;
+    if deleted == 0 {
+        return Err(AppError::not_found("outbox operation", id));
+    }
+    badge::spawn_refresh(&app);
+    Ok(())
+}
+
+/// Return a failed operation to the queue in its original position.
+#[tauri::command]
+pub async fn retry_outbox_operation(
+    app: AppHandle,
+    outbox: State<'_, Outbox>,
+    id: String,
+) -> AppResult<()> {
+    let lookup = id.clone();
+    let updated = outbox
+        .db
+        .run(move |conn| {
+            Ok(conn.execute(
+                "UPDATE outbox SET status = ?2, next_attempt_at = ?3 WHERE id = ?1",
+                params![lookup, STATUS_PENDING, now_ms()],
+            )?)
+        })
+        .await?;
+    if updated == 0 {
+        return Err(AppError::not_found("outbox operation", id));
+    }
+    spawn_flush(&app);
+    badge::spawn_refresh(&app);
+    Ok(())
+}
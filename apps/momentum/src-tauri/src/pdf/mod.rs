@@ -0,0 +1,175 @@
+//! PDF reading, rasterizing, and page editing through pdfium.
+//!
+//! pdfium is loaded at runtime from the app's resources, next to the executable, or from
+//! the system, so builds without it still run and only these commands fail. The first
+//! command to need it binds the library; a failed bind is retried on the next call.
+
+pub mod pages;
+pub mod render;
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use pdfium_render::prelude::{PdfDocumentMetadataTagType, PdfDocumentVersion, Pdfium};
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::error::AppResult;
+
+const CACHE_DIR_NAME: &str = "pdf-pages";
+
+/// pdfium bindings and the rendered-page cache, registered in app state.
+pub struct PdfEngine {
+    library_dirs: Vec<PathBuf>,
+    pdfium: Mutex<Option<Arc<Pdfium>>>,
+    cache_dir: PathBuf,
+}
+
+/// Open the page cache under `data_dir/pdf-pages` and note where pdfium may live.
+pub fn init(app: &AppHandle, data_dir: &Path) -> AppResult<PdfEngine> {
+    let cache_dir = data_dir.join(CACHE_DIR_NAME);
+    std::fs::create_dir_all(&cache_dir)?;
+    let mut library_dirs = Vec::new();
+    if let Ok(resources) = app.path().resource_dir() {
+        library_dirs.push(resources.join("pdfium"));
+        library_dirs.push(resources);
+    }
+    if let Some(exe_dir) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        library_dirs.push(exe_dir);
+    }
+    Ok(PdfEngine {
+        library_dirs,
+        pdfium: Mutex::new(None),
+        cache_dir,
+    })
+}
+
+impl PdfEngine {
+    pub(crate) fn pdfium(&self) -> AppResult<Arc<Pdfium>> {
+        let mut pdfium = self.pdfium.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(pdfium) = pdfium.as_ref() {
+            return Ok(pdfium.clone());
+        }
+        let bindings = self
+            .library_dirs
+            .iter()
+            .map(Pdfium::pdfium_platform_library_name_at_path)
+            .filter(|path| path.is_file())
+            .find_map(|path| match Pdfium::bind_to_library(&path) {
+                Ok(bindings) => Some(bindings),
+                Err(err) => {
+                    tracing::warn!(%err, path = %path.display(), "cannot load pdfium");
+                    None
+                }
+            });
+        let bindings = match bindings {
+            Some(bindings) => bindings,
+            None => Pdfium::bind_to_system_library()?,
+        };
+        let bound = Arc::new(Pdfium::new(bindings));
+        *pdfium = Some(bound.clone());
+        Ok(bound)
+    }
+
+    pub(crate) fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+}
+
+/// Page size in PDF points (1/72 inch).
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfPageSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Document information for the viewer.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfMetadata {
+    pub page_count: u16,
+    /// PDF version such as "1.7", when the file declares one.
+    pub version: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    pub creator: Option<String>,
+    pub producer: Option<String>,
+    /// Raw PDF date strings, e.g. `D:20240131120000Z`.
+    pub creation_date: Option<String>,
+    pub modification_date: Option<String>,
+    /// Sizes of every page, so the viewer can lay out a plan set before rendering any of it.
+    pub pages: Vec<PdfPageSize>,
+}
+
+fn version_text(version: PdfDocumentVersion) -> Option<String> {
+    Some(
+        match version {
+            PdfDocumentVersion::Pdf1_0 => "1.0",
+            PdfDocumentVersion::Pdf1_1 => "1.1",
+            PdfDocumentVersion::Pdf1_2 => "1.2",
+            PdfDocumentVersion::Pdf1_3 => "1.3",
+            PdfDocumentVersion::Pdf1_4 => "1.4",
+            PdfDocumentVersion::Pdf1_5 => "1.5",
+            PdfDocumentVersion::Pdf1_6 => "1.6",
+            PdfDocumentVersion::Pdf1_7 => "1.7",
+            PdfDocumentVersion::Pdf2_0 => "2.0",
+            PdfDocumentVersion::Other(n) => return Some(format!("{}.{}", n / 10, n % 10)),
+            PdfDocumentVersion::Unset => return None,
+        }
+        .into(),
+    )
+}
+
+/// Number of pages in the PDF at `path`.
+#[tauri::command]
+pub async fn pdf_page_count(engine: State<'_, PdfEngine>, path: PathBuf) -> AppResult<u16> {
+    let pdfium = engine.pdfium()?;
+    tauri::async_runtime::spawn_blocking(move || {
+        Ok(pdfium.load_pdf_from_file(&path, None)?.pages().len())
+    })
+    .await?
+}
+
+/// Document properties and page sizes of the PDF at `path`.
+#[tauri::command]
+pub async fn pdf_metadata(engine: State<'_, PdfEngine>, path: PathBuf) -> AppResult<PdfMetadata> {
+    let pdfium = engine.pdfium()?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let document = pdfium.load_pdf_from_file(&path, None)?;
+        let tag = |tag| {
+            document
+                .metadata()
+                .get(tag)
+                .map(|t| t.value().trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let pages = document
+            .pages()
+            .iter()
+            .map(|page| PdfPageSize {
+                width: page.width().value,
+                height: page.height().value,
+            })
+            .collect();
+        Ok(PdfMetadata {
+            page_count: document.pages().len(),
+            version: version_text(document.version()),
+            title: tag(PdfDocumentMetadataTagType::Title),
+            author: tag(PdfDocumentMetadataTagType::Author),
+            subject: tag(PdfDocumentMetadataTagType::Subject),
+            keywords: tag(PdfDocumentMetadataTagType::Keywords),
+            creator: tag(PdfDocumentMetadataTagType::Creator),
+            producer: tag(PdfDocumentMetadataTagType::Producer),
+            creation_date: tag(PdfDocumentMetadataTagType::CreationDate),
+            modification_date: tag(PdfDocumentMetadataTagType::ModificationDate),
+            pages,
+        })
+    })
+    .await?
+}
@@ -0,0 +1,151 @@
+//! Merging, splitting, and page extraction, done in pdfium without re-rendering pages.
+//!
+//! Outputs default to the source's folder under a name that never overwrites an existing
+//! file; an explicit output path is written as given.
+
+use std::path::{Path, PathBuf};
+
+use pdfium_render::prelude::{PdfDocument, Pdfium};
+use serde::Deserialize;
+use tauri::State;
+
+use super::PdfEngine;
+use crate::downloads::unique_destination;
+use crate::error::{AppError, AppResult};
+
+/// Pages `start` through `end`, 1-based and inclusive; `end` defaults to `start`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageRange {
+    pub start: u16,
+    pub end: Option<u16>,
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "document".into())
+}
+
+fn sibling(path: &Path, file_name: &str) -> PathBuf {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    unique_destination(dir, file_name)
+}
+
+fn open<'a>(pdfium: &'a Pdfium, path: &Path) -> AppResult<PdfDocument<'a>> {
+    pdfium
+        .load_pdf_from_file(path, None)
+        .map_err(|err| AppError::InvalidInput(format!("cannot open {}: {err}", path.display())))
+}
+
+fn save(document: &PdfDocument, path: &Path) -> AppResult<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    document.save_to_file(path)?;
+    Ok(())
+}
+
+/// Combine `paths` in order into one PDF and return where it was written.
+#[tauri::command]
+pub async fn pdf_merge(
+    engine: State<'_, PdfEngine>,
+    paths: Vec<PathBuf>,
+    output: Option<PathBuf>,
+) -> AppResult<PathBuf> {
+    let Some(first) = paths.first() else {
+        return Err(AppError::InvalidInput("nothing to merge".into()));
+    };
+    let output = output.unwrap_or_else(|| sibling(first, &format!("{}-merged.pdf", stem(first))));
+    let pdfium = engine.pdfium()?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut merged = pdfium.create_new_pdf()?;
+        for path in &paths {
+            let source = open(&pdfium, path)?;
+            merged.pages_mut().append(&source)?;
+        }
+        save(&merged, &output)?;
+        tracing::info!(
+            files = paths.len(),
+            pages = merged.pages().len(),
+            path = %output.display(),
+            "pdfs merged"
+        );
+        Ok(output)
+    })
+    .await?
+}
+
+/// Copy the pages in `ranges`, in the order given, into a new PDF.
+#[tauri::command]
+pub async fn pdf_extract_pages(
+    engine: State<'_, PdfEngine>,
+    path: PathBuf,
+    ranges: Vec<PageRange>,
+    output: Option<PathBuf>,
+) -> AppResult<PathBuf> {
+    if ranges.is_empty() {
+        return Err(AppError::InvalidInput("no pages selected".into()));
+    }
+    let output = output.unwrap_or_else(|| sibling(&path, &format!("{}-pages.pdf", stem(&path))));
+    let pdfium = engine.pdfium()?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let source = open(&pdfium, &path)?;
+        let count = source.pages().len();
+        let mut extracted = pdfium.create_new_pdf()?;
+        for range in ranges {
+            let end = range.end.unwrap_or(range.start);
+            if range.start == 0 || end < range.start || end > count {
+                return Err(AppError::InvalidInput(format!(
+                    "pages {}-{end} are outside a {count}-page document",
+                    range.start
+                )));
+            }
+            let at = extracted.pages().len();
+            extracted.pages_mut().copy_page_range_from_document(
+                &source,
+                range.start - 1..=end - 1,
+                at,
+            )?;
+        }
+        save(&extracted, &output)?;
+        Ok(output)
+    })
+    .await?
+}
+
+/// Write every page to its own PDF in `directory`, by default a folder beside the source.
+///
+/// Returns the new files in page order.
+#[tauri::command]
+pub async fn pdf_split(
+    engine: State<'_, PdfEngine>,
+    path: PathBuf,
+    directory: Option<PathBuf>,
+) -> AppResult<Vec<PathBuf>> {
+    let name = stem(&path);
+    let directory = directory.unwrap_or_else(|| sibling(&path, &format!("{name}-pages")));
+    let pdfium = engine.pdfium()?;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let source = open(&pdfium, &path)?;
+        let count = source.pages().len();
+        std::fs::create_dir_all(&directory)?;
+        // Zero-padded so the files sort in page order
+        let width = count.to_string().len().max(3);
+        let mut outputs = Vec::with_capacity(count as usize);
+        for index in 0..count {
+            let mut page = pdfium.create_new_pdf()?;
+            page.pages_mut()
+                .copy_page_from_document(&source, index, 0)?;
+            let output = directory.join(format!("{name}-p{:0width$}.pdf", index + 1));
+            save(&page, &output)?;
+            outputs.push(output);
+        }
+        tracing::info!(pages = count, dir = %directory.display(), "pdf split");
+        Ok(outputs)
+    })
+    .await?
+}
@@ -0,0 +1,147 @@
+//! Page rasterizing with a size-bounded disk cache.
+//!
+//! Renders are keyed by page and DPI on top of the source file's identity, and the least
+//! recently used pages are evicted once the cache passes its budget.
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use image::ImageFormat;
+use pdfium_render::prelude::{PdfRenderConfig, Pdfium};
+use serde::Serialize;
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tauri::{AppHandle, State};
+
+use super::PdfEngine;
+use crate::disk_cache;
+use crate::error::{AppError, AppResult};
+use crate::streams::{BinaryStream, StreamSummary};
+
+const CACHE_BUDGET_BYTES: u64 = 512 * 1024 * 1024;
+const MAX_DPI: u16 = 600;
+// A 36x48 inch sheet at 300 DPI would be over half a gigabyte of pixels
+const MAX_SIDE_PX: i32 = 8192;
+
+/// A rendered page on disk.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedPage {
+    /// PNG file inside the page cache; load it with `convertFileSrc`.
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    pub cached: bool,
+}
+
+/// A rendered page streamed to the webview.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamedPage {
+    pub width: u32,
+    pub height: u32,
+    pub cached: bool,
+    pub stream: StreamSummary,
+}
+
+fn render(
+    pdfium: &Pdfium,
+    cache_dir: &Path,
+    path: &Path,
+    page: u16,
+    dpi: u16,
+) -> AppResult<RenderedPage> {
+    let output = cache_dir.join(format!(
+        "{}.png",
+        disk_cache::source_key(path, &format!("page={page};dpi={dpi}"))?
+    ));
+    if let Ok((width, height)) = image::image_dimensions(&output) {
+        disk_cache::touch(&output);
+        return Ok(RenderedPage {
+            path: output,
+            width,
+            height,
+            cached: true,
+        });
+    }
+
+    let document = pdfium.load_pdf_from_file(path, None)?;
+    let pages = document.pages();
+    if page > pages.len() {
+        return Err(AppError::InvalidInput(format!(
+            "page {page} is past the end of a {}-page document",
+            pages.len()
+        )));
+    }
+    let config = PdfRenderConfig::new()
+        .scale_page_by_factor(dpi as f32 / 72.0)
+        .set_maximum_width(MAX_SIDE_PX)
+        .set_maximum_height(MAX_SIDE_PX);
+    let image = pages.get(page - 1)?.render_with_config(&config)?.as_image();
+
+    let tmp = output.with_extension("tmp");
+    image
+        .write_to(&mut BufWriter::new(File::create(&tmp)?), ImageFormat::Png)
+        .map_err(|e| AppError::Internal(format!("cannot encode page {page}: {e}")))?;
+    fs::rename(&tmp, &output)?;
+    disk_cache::evict(cache_dir, CACHE_BUDGET_BYTES, &output);
+    tracing::debug!(page, dpi, path = %path.display(), "pdf page rendered");
+    Ok(RenderedPage {
+        path: output,
+        width: image.width(),
+        height: image.height(),
+        cached: false,
+    })
+}
+
+/// Rasterize one page to PNG, serving it from the page cache when already rendered.
+///
+/// `page` is 1-based. `dpi` is capped at 600 and the longer side at 8192 pixels, whichever
+/// is smaller.
+#[tauri::command]
+pub async fn pdf_render_page(
+    engine: State<'_, PdfEngine>,
+    path: PathBuf,
+    page: u16,
+    dpi: u16,
+) -> AppResult<RenderedPage> {
+    if page == 0 {
+        return Err(AppError::InvalidInput("pages are numbered from 1".into()));
+    }
+    let dpi = dpi.clamp(1, MAX_DPI);
+    let pdfium = engine.pdfium()?;
+    let cache_dir = engine.cache_dir().to_path_buf();
+    tauri::async_runtime::spawn_blocking(move || render(&pdfium, &cache_dir, &path, page, dpi))
+        .await?
+}
+
+/// Like [`pdf_render_page`], but the PNG bytes are streamed down `channel`, for views that
+/// cannot load files from the page cache.
+#[tauri::command]
+pub async fn pdf_stream_page(
+    app: AppHandle,
+    engine: State<'_, PdfEngine>,
+    path: PathBuf,
+    page: u16,
+    dpi: u16,
+    stream_id: String,
+    channel: Channel<InvokeResponseBody>,
+) -> AppResult<StreamedPage> {
+    if page == 0 {
+        return Err(AppError::InvalidInput("pages are numbered from 1".into()));
+    }
+    let dpi = dpi.clamp(1, MAX_DPI);
+    let pdfium = engine.pdfium()?;
+    let cache_dir = engine.cache_dir().to_path_buf();
+    let stream = BinaryStream::open(&app, stream_id, channel)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let rendered = render(&pdfium, &cache_dir, &path, page, dpi)?;
+        Ok(StreamedPage {
+            width: rendered.width,
+            height: rendered.height,
+            cached: rendered.cached,
+            stream: stream.send_file(&rendered.path)?,
+        })
+    })
+    .await?
+}
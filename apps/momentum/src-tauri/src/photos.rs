@@ -0,0 +1,410 @@
+//! Site photos taken with the device camera.
+//!
+//! `capture_photo` opens the native camera, then downscales and re-encodes the shot as JPEG
+//! per [`PhotoSettings`] before it enters the attachment store. Camera metadata such as the
+//! GPS position is dropped unless `keepMetadata` is on; either way the orientation is applied
+//! to the pixels so every viewer shows the photo upright.
+//!
+//! Each photo is then queued in `photo_uploads` and the `photos.sync` job uploads it through
+//! `uploads` and tells the server which project it belongs to through the outbox.
+
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageEncoder, ImageReader};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Manager, State};
+
+use crate::attachments::{self, Attachment, AttachmentStore};
+use crate::audit::{self, Change};
+use crate::auth;
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::jobs::Scheduler;
+use crate::location::{self, Location, LocationOptions, TaggedEntity};
+use crate::network::NetworkMonitor;
+use crate::outbox::{self, OutboxRequest};
+use crate::state::AppState;
+use crate::uploads::{self, Uploads};
+
+/// Job kind that uploads queued photos.
+pub const SYNC_JOB: &str = "photos.sync";
+const SYNC_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+const OUTGOING_DIR_NAME: &str = "photo-uploads";
+const CONTENT_TYPE: &str = "image/jpeg";
+const QUALITY: std::ops::RangeInclusive<u8> = 1..=100;
+const MIN_DIMENSION: u32 = 320;
+// A copy this new may belong to a capture whose queue row is not written yet
+const ORPHAN_GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// How captured photos are stored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PhotoSettings {
+    /// Longest side in pixels; larger shots are scaled down.
+    pub max_dimension: u32,
+    /// JPEG quality from 1 to 100.
+    pub quality: u8,
+    /// Keep camera metadata such as capture time, device, and GPS position.
+    pub keep_metadata: bool,
+    /// Tag each photo with the device's location and the nearest address.
+    pub tag_location: bool,
+}
+
+impl Default for PhotoSettings {
+    fn default() -> Self {
+        Self {
+            max_dimension: 2560,
+            quality: 82,
+            keep_metadata: false,
+            tag_location: false,
+        }
+    }
+}
+
+/// Overrides of [`PhotoSettings`] for one capture.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureOptions {
+    pub max_dimension: Option<u32>,
+    pub quality: Option<u8>,
+    pub keep_metadata: Option<bool>,
+    pub tag_location: Option<bool>,
+}
+
+impl CaptureOptions {
+    fn apply(self, settings: PhotoSettings) -> PhotoSettings {
+        PhotoSettings {
+            max_dimension: self
+                .max_dimension
+                .unwrap_or(settings.max_dimension)
+                .max(MIN_DIMENSION),
+            quality: self
+                .quality
+                .unwrap_or(settings.quality)
+                .clamp(*QUALITY.start(), *QUALITY.end()),
+            keep_metadata: self.keep_metadata.unwrap_or(settings.keep_metadata),
+            tag_location: self.tag_location.unwrap_or(settings.tag_location),
+        }
+    }
+}
+
+/// A stored photo.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapturedPhoto {
+    pub attachment: Attachment,
+    pub width: u32,
+    pub height: u32,
+    /// Where it was taken, when location tagging is on and a fix was available.
+    pub location: Option<Location>,
+}
+
+/// Downscale and re-encode a photo as JPEG.
+pub(crate) fn process(bytes: &[u8], settings: &PhotoSettings) -> AppResult<(Vec<u8>, u32, u32)> {
+    let unreadable =
+        |e: image::ImageError| AppError::InvalidInput(format!("unreadable photo: {e}"));
+    let mut decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()?
+        .into_decoder()
+        .map_err(unreadable)?;
+    let mut exif = decoder.exif_metadata().map_err(unreadable)?;
+    let orientation = exif
+        .as_deref_mut()
+        .and_then(Orientation::remove_from_exif_chunk)
+        .unwrap_or(Orientation::NoTransforms);
+    let mut image = DynamicImage::from_decoder(decoder).map_err(unreadable)?;
+    image.apply_orientation(orientation);
+    let max = settings.max_dimension;
+    if image.width() > max || image.height() > max {
+        image = image.resize(max, max, FilterType::Lanczos3);
+    }
+
+    let rgb = image.to_rgb8();
+    let mut out = Vec::new();
+    let mut encoder = JpegEncoder::new_with_quality(&mut out, settings.quality);
+    if let Some(exif) = exif.filter(|_| settings.keep_metadata) {
+        // Infallible for JPEG
+        let _ = encoder.set_exif_metadata(exif);
+    }
+    encoder
+        .write_image(
+            rgb.as_raw(),
+            rgb.width(),
+            rgb.height(),
+            image::ExtendedColorType::Rgb8,
+        )
+        .map_err(|e| AppError::Internal(format!("cannot encode photo: {e}")))?;
+    Ok((out, rgb.width(), rgb.height()))
+}
+
+fn outgoing_dir(state: &AppState) -> PathBuf {
+    state.data_dir().join(OUTGOING_DIR_NAME)
+}
+
+#[cfg(mobile)]
+mod platform {
+    use std::path::PathBuf;
+
+    use serde::Deserialize;
+    use tauri::AppHandle;
+
+    use crate::error::AppResult;
+    use crate::mobile;
+
+    #[derive(Deserialize)]
+    struct Shot {
+        path: Option<PathBuf>,
+    }
+
+    // None when the user closed the camera without taking a photo
+    pub fn take(app: &AppHandle) -> AppResult<Option<PathBuf>> {
+        Ok(mobile::run::<Shot>(app, "takePhoto", ())?.path)
+    }
+}
+
+#[cfg(desktop)]
+mod platform {
+    use std::path::PathBuf;
+
+    use tauri::AppHandle;
+
+    use crate::error::{AppError, AppResult};
+
+    pub fn take(_app: &AppHandle) -> AppResult<Option<PathBuf>> {
+        Err(AppError::InvalidInput(
+            "the camera is only available on mobile".into(),
+        ))
+    }
+}
+
+/// Open the device camera and return the encoded shot; blocks until the user is done.
+///
+/// Fails with `CANCELLED` when the user closes the camera without a shot.
+pub(crate) fn take_picture(app: &AppHandle) -> AppResult<Vec<u8>> {
+    let shot = platform::take(app)?.ok_or(AppError::Cancelled)?;
+    let bytes = fs::read(&shot);
+    let _ = fs::remove_file(&shot);
+    Ok(bytes?)
+}
+
+/// Take a photo with the device camera and attach it to a project.
+///
+/// Fails with `CANCELLED` when the user closes the camera without a shot.
+#[tauri::command]
+pub async fn capture_photo(
+    app: AppHandle,
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    project_id: String,
+    options: Option<CaptureOptions>,
+) -> AppResult<CapturedPhoto> {
+    let settings = options.unwrap_or_default().apply(state.config().photos);
+    let tag_location = settings.tag_location;
+    let handle = app.clone();
+    let (jpeg, width, height) =
+        tauri::async_runtime::spawn_blocking(move || process(&take_picture(&handle)?, &settings))
+            .await??;
+    // A photo is still worth keeping without its position
+    let location = if tag_location {
+        location::current(&app, LocationOptions::default())
+            .await
+            .inspect_err(|err| tracing::warn!(%err, "could not locate photo"))
+            .ok()
+    } else {
+        None
+    };
+
+    let actor = audit::actor(&state);
+    let outgoing = outgoing_dir(&state);
+    let name = jiff::Zoned::now()
+        .strftime("Photo %Y-%m-%d %H.%M.%S.jpg")
+        .to_string();
+    let stored = app.clone();
+    let tag = location.clone();
+    let attachment = db
+        .run(move |conn| {
+            let store = stored.state::<AttachmentStore>();
+            let attachment = attachments::add_bytes(conn, &store, project_id, name, &jpeg)?;
+            audit::record_now(
+                conn,
+                &actor,
+                Change::new("capture_photo", "attachment", &attachment.id).after(&attachment)?,
+            )?;
+            if let Some(tag) = &tag {
+                location::tag(conn, TaggedEntity::Attachment, &attachment.id, tag)?;
+            }
+            fs::create_dir_all(&outgoing)?;
+            let copy = outgoing.join(format!("{}.jpg", attachment.id));
+            fs::write(&copy, &jpeg)?;
+            conn.execute(
+                "INSERT INTO photo_uploads (attachment_id, project_id, path, created_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    attachment.id,
+                    attachment.project_id,
+                    copy.to_string_lossy(),
+                    now_ms()
+                ],
+            )?;
+            Ok(attachment)
+        })
+        .await?;
+    spawn_sync(&app);
+    Ok(CapturedPhoto {
+        attachment,
+        width,
+        height,
+        location,
+    })
+}
+
+struct Queued {
+    attachment_id: String,
+    project_id: String,
+    path: PathBuf,
+    upload_id: Option<String>,
+}
+
+/// Move each queued photo one step further: start its upload, restart a failed one, or
+/// once the file is on the server, link it to its project and drop it from the queue.
+///
+/// A photo whose upload the user cancelled leaves the queue without being sent, and
+/// signed-out users keep their photos queued.
+pub async fn sync_pending(app: &AppHandle) -> AppResult<()> {
+    if !app.state::<NetworkMonitor>().is_online() {
+        return Ok(());
+    }
+    match auth::access_token(app).await {
+        Ok(Some(_)) => {}
+        Ok(None) | Err(AppError::Auth(_)) => return Ok(()),
+        Err(err) => return Err(err),
+    }
+    let db = app.state::<Db>();
+    let queued = db
+        .run(|conn| {
+            let mut stmt = conn.prepare("SELECT * FROM photo_uploads ORDER BY created_at")?;
+            let rows = stmt.query_map([], |row| {
+                let path: String = row.get("path")?;
+                Ok(Queued {
+                    attachment_id: row.get("attachment_id")?,
+                    project_id: row.get("project_id")?,
+                    path: PathBuf::from(path),
+                    upload_id: row.get("upload_id")?,
+                })
+            })?;
+            Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+        })
+        .await?;
+
+    for photo in queued {
+        let Some(upload_id) = photo.upload_id else {
+            let upload = uploads::start(app, photo.path, Some(CONTENT_TYPE.into())).await?;
+            db.run(move |conn| {
+                conn.execute(
+                    "UPDATE photo_uploads SET upload_id = ?2 WHERE attachment_id = ?1",
+                    params![photo.attachment_id, upload.id],
+                )?;
+                Ok(())
+            })
+            .await?;
+            continue;
+        };
+        let upload = match app.state::<Uploads>().get(&upload_id).await {
+            Ok(upload) => upload,
+            Err(AppError::NotFound { .. }) => {
+                dequeue(&db, photo.attachment_id, &photo.path).await?;
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        match upload.status.as_str() {
+            uploads::STATUS_COMPLETED => {
+                outbox::enqueue(
+                    app,
+                    OutboxRequest {
+                        method: "POST".into(),
+                        path: format!("/v1/projects/{}/photos", photo.project_id),
+                        body: Some(json!({
+                            "attachmentId": photo.attachment_id,
+                            "fileName": upload.file_name,
+                            "upload": upload.result,
+                        })),
+                        idempotency_key: Some(format!("photo:{}", photo.attachment_id)),
+                    },
+                )
+                .await?;
+                dequeue(&db, photo.attachment_id, &photo.path).await?;
+            }
+            uploads::STATUS_FAILED => {
+                uploads::resume(app, &upload_id).await?;
+            }
+            // Still running, or paused by the user
+            _ => {}
+        }
+    }
+    remove_orphans(app).await
+}
+
+async fn dequeue(db: &Db, attachment_id: String, path: &Path) -> AppResult<()> {
+    db.run(move |conn| {
+        conn.execute(
+            "DELETE FROM photo_uploads WHERE attachment_id = ?1",
+            [attachment_id],
+        )?;
+        Ok(())
+    })
+    .await?;
+    let _ = fs::remove_file(path);
+    Ok(())
+}
+
+// Deleting an attachment drops its queue row by cascade but leaves the copy behind
+async fn remove_orphans(app: &AppHandle) -> AppResult<()> {
+    let dir = outgoing_dir(&app.state::<AppState>());
+    let queued: Vec<String> = app
+        .state::<Db>()
+        .run(|conn| {
+            let mut stmt = conn.prepare("SELECT path FROM photo_uploads")?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            Ok(rows.collect::<rusqlite::Result<_>>()?)
+        })
+        .await?;
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Ok(());
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let recent = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| modified.elapsed().unwrap_or_default() < ORPHAN_GRACE);
+        if !recent && !queued.iter().any(|q| Path::new(q) == path) {
+            let _ = fs::remove_file(path);
+        }
+    }
+    Ok(())
+}
+
+fn spawn_sync(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(err) = sync_pending(&app).await {
+            tracing::warn!(%err, "photo sync failed");
+        }
+    });
+}
+
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(SYNC_JOB, Some(SYNC_INTERVAL), |app| async move {
+        sync_pending(&app).await
+    });
+}
@@ -0,0 +1,307 @@
+//! TLS public-key pinning for API traffic.
+//!
+//! Pins are SHA-256 hashes of a certificate's SubjectPublicKeyInfo in `sha256/<base64>` form,
+//! the format `openssl` and HPKP tooling produce. Normal chain validation still runs first;
+//! pinning only narrows which valid certificates are accepted.
+//!
+//! The API is served by Let's Encrypt certificates, so the bundled pins are its two roots,
+//! ISRG Root X1 and X2; leaf and intermediate keys rotate too often to pin from a release.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::WebPkiSupportedAlgorithms;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{CertificateError, ClientConfig, DigitallySignedStruct, OtherError, RootCertStore};
+use sha2::{Digest, Sha256};
+
+use crate::error::{AppError, AppResult};
+
+// Host to pins; shipped with each release so rotating a key means shipping a backup pin first
+const BUNDLED_PINS: &str = include_str!("../pins.json");
+const PIN_PREFIX: &str = "sha256/";
+
+/// Raised inside the TLS handshake; [`mismatch_host`] recovers it from a request error.
+#[derive(Debug, thiserror::Error)]
+#[error("certificate for {host} does not match any pinned key")]
+pub struct PinMismatch {
+    pub host: String,
+}
+
+/// Pins per host; a host without pins is not pinned.
+#[derive(Debug, Default)]
+pub struct PinSet {
+    hosts: HashMap<String, Vec<[u8; 32]>>,
+}
+
+impl PinSet {
+    /// The bundled pins with per-host `overrides` from settings replacing them.
+    ///
+    /// An override with no pins disables pinning for that host, e.g. a staging server.
+    pub fn load(overrides: &BTreeMap<String, Vec<String>>) -> AppResult<Self> {
+        let mut raw: BTreeMap<String, Vec<String>> = serde_json::from_str(BUNDLED_PINS)?;
+        raw.extend(overrides.clone());
+        let hosts = raw
+            .into_iter()
+            .map(|(host, pins)| {
+                let pins = pins
+                    .iter()
+                    .map(|pin| parse_pin(pin))
+                    .collect::<AppResult<Vec<_>>>()?;
+                Ok((host.to_ascii_lowercase(), pins))
+            })
+            .collect::<AppResult<_>>()?;
+        Ok(Self { hosts })
+    }
+
+    fn pins_for(&self, host: &str) -> Option<&[[u8; 32]]> {
+        self.hosts
+            .get(&host.to_ascii_lowercase())
+            .map(Vec::as_slice)
+            .filter(|pins| !pins.is_empty())
+    }
+}
+
+fn parse_pin(pin: &str) -> AppResult<[u8; 32]> {
+    let invalid = || AppError::InvalidInput(format!("invalid certificate pin {pin}"));
+    let encoded = pin.strip_prefix(PIN_PREFIX).ok_or_else(invalid)?;
+    STANDARD
+        .decode(encoded)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(invalid)
+}
+
+// Trust anchors keep the SubjectPublicKeyInfo without its outer SEQUENCE; pins hash all of it
+fn spki_der(contents: &[u8]) -> Vec<u8> {
+    let len = contents.len();
+    let mut der = vec![0x30];
+    if len < 0x80 {
+        der.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|b| *b == 0)
+            .collect();
+        der.push(0x80 | bytes.len() as u8);
+        der.extend(bytes);
+    }
+    der.extend_from_slice(contents);
+    der
+}
+
+fn pin_of(spki: &[u8]) -> [u8; 32] {
+    Sha256::digest(spki).into()
+}
+
+#[derive(Debug)]
+struct PinningVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    algorithms: WebPkiSupportedAlgorithms,
+    pins: PinSet,
+}
+
+impl PinningVerifier {
+    // Rebuilds the chain to one of the roots, so a pinned root matches even though servers
+    // don't send it; path building tries every chain, including cross-signed ones
+    fn chain_is_pinned(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+        pins: &[[u8; 32]],
+    ) -> bool {
+        let Ok(cert) = webpki::EndEntityCert::try_from(end_entity) else {
+            return false;
+        };
+        let check = |path: &webpki::VerifiedPath<'_>| {
+            let matched = std::iter::once(path.end_entity().subject_public_key_info())
+                .chain(
+                    path.intermediate_certificates()
+                        .map(|cert| cert.subject_public_key_info()),
+                )
+                .any(|spki| pins.contains(&pin_of(spki.as_ref())))
+                || pins.contains(&pin_of(&spki_der(
+                    path.anchor().subject_public_key_info.as_ref(),
+                )));
+            if matched {
+                Ok(())
+            } else {
+                Err(webpki::Error::UnknownIssuer)
+            }
+        };
+        cert.verify_for_usage(
+            self.algorithms.all,
+            webpki_roots::TLS_SERVER_ROOTS,
+            intermediates,
+            now,
+            webpki::KeyUsage::server_auth(),
+            None,
+            Some(&check),
+        )
+        .is_ok()
+    }
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        let ServerName::DnsName(host) = server_name else {
+            return Ok(verified);
+        };
+        let Some(pins) = self.pins.pins_for(host.as_ref()) else {
+            return Ok(verified);
+        };
+
+        // Any key in the chain may be pinned, so pinning a root survives leaf renewals
+        if self.chain_is_pinned(end_entity, intermediates, now, pins) {
+            return Ok(verified);
+        }
+        tracing::error!(host = host.as_ref(), "certificate pin mismatch");
+        Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+            OtherError(Arc::new(PinMismatch {
+                host: host.as_ref().to_string(),
+            })),
+        )))
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// TLS config enforcing `pins` on top of the same web PKI roots reqwest uses by default.
+pub fn tls_config(pins: PinSet) -> AppResult<ClientConfig> {
+    let tls_error = |e: &dyn std::fmt::Display| AppError::Internal(format!("tls setup: {e}"));
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let inner = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()
+        .map_err(|e| tls_error(&e))?;
+    let algorithms = provider.signature_verification_algorithms;
+    Ok(ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| tls_error(&e))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinningVerifier {
+            inner,
+            algorithms,
+            pins,
+        }))
+        .with_no_client_auth())
+}
+
+/// The host whose pin check failed, if that is what caused `err`.
+pub fn mismatch_host(err: &(dyn std::error::Error + 'static)) -> Option<String> {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(rustls::Error::InvalidCertificate(CertificateError::Other(other))) =
+            err.downcast_ref::<rustls::Error>()
+        {
+            let other: &(dyn std::error::Error + 'static) = other.0.as_ref();
+            return other.downcast_ref::<PinMismatch>().map(|m| m.host.clone());
+        }
+        source = err.source();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Published by Let's Encrypt; a second source for what the bundled file should hold
+    const ISRG_ROOT_X1: &str = "sha256/C5+lpZ7tcVwmwQIMcRtPbsQtWLABXhQzejna0wHFr8M=";
+
+    fn root_pins() -> Vec<[u8; 32]> {
+        webpki_roots::TLS_SERVER_ROOTS
+            .iter()
+            .map(|root| pin_of(&spki_der(root.subject_public_key_info.as_ref())))
+            .collect()
+    }
+
+    #[test]
+    fn the_api_ships_with_a_primary_and_a_backup_pin() {
+        let pins = PinSet::load(&BTreeMap::new()).unwrap();
+        let api = pins.pins_for("api.truss.dev").unwrap();
+        assert!(api.len() >= 2, "{} pins", api.len());
+        assert!(api.contains(&parse_pin(ISRG_ROOT_X1).unwrap()));
+    }
+
+    #[test]
+    fn bundled_pins_name_roots_in_the_trust_store() {
+        let roots = root_pins();
+        let pins = PinSet::load(&BTreeMap::new()).unwrap();
+        for (host, pins) in &pins.hosts {
+            for pin in pins {
+                assert!(roots.contains(pin), "{host} pins a key no trusted root has");
+            }
+        }
+    }
+
+    #[test]
+    fn spki_der_restores_the_outer_sequence() {
+        assert_eq!(spki_der(&[1, 2, 3]), [0x30, 3, 1, 2, 3]);
+        let long = spki_der(&[0; 0x80]);
+        assert_eq!(long[..3], [0x30, 0x81, 0x80]);
+        let longer = spki_der(&[0; 0x1234]);
+        assert_eq!(longer[..4], [0x30, 0x82, 0x12, 0x34]);
+        assert_eq!(longer.len(), 4 + 0x1234);
+    }
+
+    #[test]
+    fn an_empty_override_unpins_a_host() {
+        let overrides = BTreeMap::from([("api.truss.dev".to_string(), Vec::new())]);
+        let pins = PinSet::load(&overrides).unwrap();
+        assert!(pins.pins_for("api.truss.dev").is_none());
+    }
+
+    #[test]
+    fn malformed_pins_are_rejected() {
+        for pin in [
+            "C5+lpZ7tcVwmwQIMcRtPbsQtWLABXhQzejna0wHFr8M=",
+            "sha256/c2hvcnQ=",
+            "sha256/!",
+        ] {
+            assert!(parse_pin(pin).is_err(), "{pin}");
+        }
+    }
+}
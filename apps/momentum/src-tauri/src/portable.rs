@@ -0,0 +1,86 @@
+//! Portable mode: every byte of app data in a folder beside the executable.
+//!
+//! Turned on by a `momentum.portable` file next to the executable or by launching with
+//! `--portable`, so the app can run from a USB stick without installing. Data, config,
+//! and webview storage then live under `MomentumData/` beside the executable instead of the
+//! platform folders, and the updater stays off because it installs system-wide. The store
+//! plugin resolves relative names against the platform folder regardless, so the frontend
+//! opens stores by the absolute `storeDir` from `get_app_paths`. Credentials still go to
+//! the machine's keychain, so signing in is needed on each machine.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, Runtime, State, WebviewWindowBuilder};
+
+use crate::error::AppResult;
+use crate::state::AppState;
+
+const MARKER_FILE_NAME: &str = "momentum.portable";
+const PORTABLE_ARG: &str = "--portable";
+const ROOT_DIR_NAME: &str = "MomentumData";
+
+static ROOT: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Folder holding everything in portable mode; `None` for a normal install.
+pub fn root() -> Option<&'static Path> {
+    ROOT.get_or_init(|| {
+        let exe_dir = std::env::current_exe().ok()?.parent()?.to_path_buf();
+        let requested = std::env::args().skip(1).any(|arg| arg == PORTABLE_ARG)
+            || exe_dir.join(MARKER_FILE_NAME).is_file();
+        requested.then(|| exe_dir.join(ROOT_DIR_NAME))
+    })
+    .as_deref()
+}
+
+pub fn is_portable() -> bool {
+    root().is_some()
+}
+
+/// Stand-in for `app.path().app_data_dir()` that honors portable mode.
+pub fn app_data_dir(app: &AppHandle) -> AppResult<PathBuf> {
+    match root() {
+        Some(root) => Ok(root.join("data")),
+        None => Ok(app.path().app_data_dir()?),
+    }
+}
+
+/// Stand-in for `app.path().app_config_dir()` that honors portable mode.
+pub fn app_config_dir(app: &AppHandle) -> AppResult<PathBuf> {
+    match root() {
+        Some(root) => Ok(root.join("config")),
+        None => Ok(app.path().app_config_dir()?),
+    }
+}
+
+/// Keep a window's webview storage in the portable folder; a no-op otherwise.
+pub fn webview<'a, R: Runtime, M: Manager<R>>(
+    builder: WebviewWindowBuilder<'a, R, M>,
+) -> WebviewWindowBuilder<'a, R, M> {
+    match root() {
+        Some(root) => builder.data_directory(root.join("webview")),
+        None => builder,
+    }
+}
+
+/// Where the app keeps its files.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppPaths {
+    pub portable: bool,
+    pub data_dir: PathBuf,
+    pub config_dir: PathBuf,
+    /// Folder to open `tauri-plugin-store` files in, by absolute path.
+    pub store_dir: PathBuf,
+}
+
+#[tauri::command]
+pub async fn get_app_paths(app: AppHandle, state: State<'_, AppState>) -> AppResult<AppPaths> {
+    Ok(AppPaths {
+        portable: is_portable(),
+        data_dir: state.data_dir().to_path_buf(),
+        config_dir: app_config_dir(&app)?,
+        store_dir: app_data_dir(&app)?,
+    })
+}
@@ -0,0 +1,323 @@
+//! Power source and battery monitor.
+//!
+//! A background task polls the OS and emits `power:source-changed` when the machine moves
+//! between mains and battery, and `power:low-battery` once each time the charge falls to
+//! the configured level while discharging. Cloud backups wait for mains power, and sync
+//! holds off while the battery is low, unless `deferOnBattery` is turned off.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::now_ms;
+use crate::error::AppResult;
+use crate::state::AppState;
+
+/// Emitted with a [`PowerStatus`] when the machine switches between mains and battery.
+pub const SOURCE_CHANGED_EVENT: &str = "power:source-changed";
+/// Emitted with a [`PowerStatus`] when the battery runs low while discharging.
+pub const LOW_BATTERY_EVENT: &str = "power:low-battery";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+// Low battery is armed again only once the charge is this far above the level
+const REARM_MARGIN: u8 = 5;
+
+/// How power state shapes background work.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PowerSettings {
+    /// Hold cloud backups until mains power, and sync while the battery is low.
+    pub defer_on_battery: bool,
+    /// Charge in percent at or below which `power:low-battery` fires.
+    pub low_battery_percent: u8,
+}
+
+impl Default for PowerSettings {
+    fn default() -> Self {
+        Self {
+            defer_on_battery: true,
+            low_battery_percent: 20,
+        }
+    }
+}
+
+/// Where the machine is drawing power from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PowerSource {
+    Ac,
+    Battery,
+    /// Not reported, as on mobile.
+    #[default]
+    Unknown,
+}
+
+/// Power source and battery charge at the last poll.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PowerStatus {
+    pub source: PowerSource,
+    /// Charge in percent; `None` without a battery.
+    pub battery_percent: Option<u8>,
+    pub charging: bool,
+    /// At or below the low-battery level while on battery.
+    pub low_battery: bool,
+    pub checked_at: i64,
+}
+
+impl PowerStatus {
+    pub fn on_battery(&self) -> bool {
+        self.source == PowerSource::Battery
+    }
+}
+
+// What each platform reads
+#[cfg_attr(mobile, allow(dead_code))]
+#[derive(Debug, Default)]
+struct Reading {
+    source: PowerSource,
+    battery_percent: Option<u8>,
+    charging: bool,
+}
+
+#[derive(Default)]
+struct Inner {
+    status: PowerStatus,
+    // Set once low battery fires, so it is not repeated every poll
+    low_battery_sent: bool,
+}
+
+/// Latest power status, registered in app state.
+#[derive(Default)]
+pub struct PowerMonitor {
+    inner: Mutex<Inner>,
+}
+
+impl PowerMonitor {
+    pub fn snapshot(&self) -> PowerStatus {
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .status
+            .clone()
+    }
+
+    // Returns the new status, whether the source changed, and whether the battery just ran low
+    fn record(&self, reading: Reading, settings: &PowerSettings) -> (PowerStatus, bool, bool) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let level = settings.low_battery_percent;
+        let low_battery = reading.source == PowerSource::Battery
+            && reading
+                .battery_percent
+                .is_some_and(|percent| percent <= level);
+        let status = PowerStatus {
+            source: reading.source,
+            battery_percent: reading.battery_percent,
+            charging: reading.charging,
+            low_battery,
+            checked_at: now_ms(),
+        };
+        // The first poll only establishes the source
+        let source_changed = inner.status.checked_at != 0 && inner.status.source != status.source;
+        let ran_low = low_battery && !inner.low_battery_sent;
+        if ran_low {
+            inner.low_battery_sent = true;
+        } else if reading.source != PowerSource::Battery
+            || reading
+                .battery_percent
+                .is_some_and(|percent| percent > level.saturating_add(REARM_MARGIN))
+        {
+            inner.low_battery_sent = false;
+        }
+        inner.status = status.clone();
+        (status, source_changed, ran_low)
+    }
+}
+
+/// Whether heavy background work such as cloud backups should wait for mains power.
+pub fn defer_heavy_work(app: &AppHandle) -> bool {
+    app.state::<AppState>().config().power.defer_on_battery
+        && app
+            .try_state::<PowerMonitor>()
+            .is_some_and(|monitor| monitor.snapshot().on_battery())
+}
+
+/// Whether routine work such as sync should pause to save a low battery.
+pub fn battery_critical(app: &AppHandle) -> bool {
+    app.state::<AppState>().config().power.defer_on_battery
+        && app
+            .try_state::<PowerMonitor>()
+            .is_some_and(|monitor| monitor.snapshot().low_battery)
+}
+
+/// Start polling power state in the background.
+pub fn start(app: &AppHandle) {
+    app.manage(PowerMonitor::default());
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match tauri::async_runtime::spawn_blocking(os::read).await {
+                Ok(reading) => {
+                    let settings = app.state::<AppState>().config().power;
+                    let monitor = app.state::<PowerMonitor>();
+                    let (status, source_changed, ran_low) = monitor.record(reading, &settings);
+                    if source_changed {
+                        tracing::info!(source = ?status.source, "power source changed");
+                        let _ = app.emit(SOURCE_CHANGED_EVENT, &status);
+                    }
+                    if ran_low {
+                        tracing::info!(percent = ?status.battery_percent, "battery low");
+                        let _ = app.emit(LOW_BATTERY_EVENT, &status);
+                    }
+                }
+                Err(err) => tracing::warn!(%err, "power poll failed"),
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use std::fs;
+    use std::path::Path;
+
+    use super::{PowerSource, Reading};
+
+    const SUPPLY_DIR: &str = "/sys/class/power_supply";
+
+    fn attr(supply: &Path, name: &str) -> Option<String> {
+        fs::read_to_string(supply.join(name))
+            .ok()
+            .map(|value| value.trim().to_string())
+    }
+
+    // Desktops have no battery and often no mains entry either, and count as on mains
+    pub fn read() -> Reading {
+        let mut mains_online = None;
+        let mut batteries = Vec::new();
+        for entry in fs::read_dir(SUPPLY_DIR).into_iter().flatten().flatten() {
+            let supply = entry.path();
+            match attr(&supply, "type").as_deref() {
+                Some("Mains") => {
+                    let online = attr(&supply, "online").as_deref() == Some("1");
+                    mains_online = Some(mains_online.unwrap_or(false) || online);
+                }
+                // Peripheral batteries, such as a mouse's, report `scope` as `Device`
+                Some("Battery") if attr(&supply, "scope").as_deref() != Some("Device") => {
+                    let percent = attr(&supply, "capacity").and_then(|c| c.parse::<u8>().ok());
+                    let status = attr(&supply, "status").unwrap_or_default();
+                    batteries.push((percent, status));
+                }
+                _ => {}
+            }
+        }
+        if batteries.is_empty() {
+            return Reading {
+                source: PowerSource::Ac,
+                ..Reading::default()
+            };
+        }
+        let discharging = batteries.iter().any(|(_, status)| status == "Discharging");
+        let on_battery = match mains_online {
+            Some(online) => !online,
+            None => discharging,
+        };
+        let percents: Vec<u8> = batteries.iter().filter_map(|(p, _)| *p).collect();
+        Reading {
+            source: if on_battery {
+                PowerSource::Battery
+            } else {
+                PowerSource::Ac
+            },
+            battery_percent: (!percents.is_empty()).then(|| {
+                (percents.iter().map(|&p| p as u32).sum::<u32>() / percents.len() as u32) as u8
+            }),
+            charging: batteries.iter().any(|(_, status)| status == "Charging"),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod os {
+    use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    use super::{PowerSource, Reading};
+
+    const NO_BATTERY: u8 = 128;
+    const CHARGING: u8 = 8;
+    const UNKNOWN: u8 = 255;
+
+    pub fn read() -> Reading {
+        let mut status = SYSTEM_POWER_STATUS::default();
+        // SAFETY: `status` is a valid SYSTEM_POWER_STATUS to write into
+        if unsafe { GetSystemPowerStatus(&mut status) }.is_err() {
+            return Reading::default();
+        }
+        let has_battery = status.BatteryFlag != UNKNOWN && status.BatteryFlag & NO_BATTERY == 0;
+        Reading {
+            source: match status.ACLineStatus {
+                0 => PowerSource::Battery,
+                1 => PowerSource::Ac,
+                _ => PowerSource::Unknown,
+            },
+            battery_percent: (has_battery && status.BatteryLifePercent <= 100)
+                .then_some(status.BatteryLifePercent),
+            charging: has_battery && status.BatteryFlag & CHARGING != 0,
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use std::process::Command;
+
+    use super::{PowerSource, Reading};
+
+    // `pmset -g batt` prints "Now drawing from 'AC Power'" and, on laptops, a line like
+    // "-InternalBattery-0 (id=1234)	85%; charging; 1:02 remaining present: true"
+    pub fn read() -> Reading {
+        let Ok(output) = Command::new("pmset").args(["-g", "batt"]).output() else {
+            return Reading::default();
+        };
+        let text = String::from_utf8_lossy(&output.stdout);
+        let source = if text.contains("'Battery Power'") {
+            PowerSource::Battery
+        } else if text.contains("'AC Power'") {
+            PowerSource::Ac
+        } else {
+            PowerSource::Unknown
+        };
+        let battery = text.lines().find(|line| line.contains("InternalBattery"));
+        let battery_percent = battery.and_then(|line| {
+            let (before, _) = line.split_once('%')?;
+            before
+                .rsplit(|c: char| !c.is_ascii_digit())
+                .next()?
+                .parse()
+                .ok()
+        });
+        Reading {
+            source,
+            battery_percent,
+            charging: battery.is_some_and(|line| line.contains("; charging;")),
+        }
+    }
+}
+
+#[cfg(mobile)]
+mod os {
+    use super::Reading;
+
+    pub fn read() -> Reading {
+        Reading::default()
+    }
+}
+
+/// Power source and battery charge as of the last poll.
+#[tauri::command]
+pub async fn get_power_status(monitor: State<'_, PowerMonitor>) -> AppResult<PowerStatus> {
+    Ok(monitor.snapshot())
+}
@@ -0,0 +1,199 @@
+//! Presentation mode: one project shown read-only, fullscreen on a conference-room screen.
+//!
+//! The presentation window has no chrome and its own capability, and [`crate::rbac`] lets it
+//! run only a short list of reads, whatever the signed-in role, so a client clicking around
+//! the view cannot change the bid. Closing it
+//! by keyboard shortcut or from the taskbar is ignored; only `exit_presentation_mode`, from
+//! either window, ends the presentation. The machine is kept awake while it runs.
+
+use std::sync::Mutex;
+
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use rusqlite::OptionalExtension;
+use serde::Serialize;
+use tauri::{
+    AppHandle, Emitter, Manager, PhysicalPosition, State, WebviewUrl, WebviewWindowBuilder, Window,
+    WindowEvent,
+};
+
+use crate::db::Db;
+use crate::displays;
+use crate::error::{AppError, AppResult};
+use crate::wake_lock::{self, WakeLockGuard};
+use crate::windows::PRESENTATION_WINDOW;
+
+/// Emitted with a [`PresentationState`] when presenting starts or ends.
+pub const PRESENTATION_EVENT: &str = "presentation:changed";
+/// Emitted to the presentation window when a close was ignored, so it can say how to exit.
+pub const CLOSE_BLOCKED_EVENT: &str = "presentation:close-blocked";
+
+/// Payload for `presentation:changed`, and what `get_presentation` returns.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresentationState {
+    /// The project on screen; `None` when nothing is being presented.
+    pub project_id: Option<String>,
+}
+
+struct Active {
+    project_id: String,
+    // Set by `exit_presentation_mode` so the next close request goes through
+    exiting: bool,
+    _awake: WakeLockGuard,
+}
+
+/// The running presentation, registered in app state.
+#[derive(Default)]
+pub struct Presentation {
+    active: Mutex<Option<Active>>,
+}
+
+impl Presentation {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<Active>> {
+        self.active.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn state(&self) -> PresentationState {
+        PresentationState {
+            project_id: self.lock().as_ref().map(|active| active.project_id.clone()),
+        }
+    }
+}
+
+/// Hold the presentation window open until it is exited on purpose, and clear the state
+/// once it is gone.
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    if window.label() != PRESENTATION_WINDOW {
+        return;
+    }
+    let Some(presentation) = window.try_state::<Presentation>() else {
+        return;
+    };
+    match event {
+        WindowEvent::CloseRequested { api, .. } => {
+            let exiting = presentation.lock().as_ref().is_none_or(|a| a.exiting);
+            if !exiting {
+                api.prevent_close();
+                let _ = window.emit_to(PRESENTATION_WINDOW, CLOSE_BLOCKED_EVENT, ());
+            }
+        }
+        WindowEvent::Destroyed if presentation.lock().take().is_some() => {
+            let _ = window.emit(PRESENTATION_EVENT, presentation.state());
+            crate::windows::show_main_window(window.app_handle());
+        }
+        _ => {}
+    }
+}
+
+// The display to present on: the one asked for, else the first that is not the laptop's
+// own, since that is usually the projector or wall screen
+fn target_monitor(
+    app: &AppHandle,
+    monitor: Option<&str>,
+) -> AppResult<Option<PhysicalPosition<i32>>> {
+    let monitors = displays::read_monitors(app)?;
+    let target = match monitor {
+        Some(id) => Some(
+            monitors
+                .iter()
+                .find(|m| m.id == id)
+                .ok_or_else(|| AppError::not_found("monitor", id))?,
+        ),
+        None => monitors
+            .iter()
+            .find(|m| !m.primary)
+            .or_else(|| monitors.first()),
+    };
+    Ok(target.map(|m| PhysicalPosition::new(m.bounds.x, m.bounds.y)))
+}
+
+/// Open `project_id` in a fullscreen, read-only presentation window.
+///
+/// `monitor` is an id from `list_monitors`; by default the presentation goes to a secondary
+/// display when one is connected. Fails if another project is already being presented.
+#[tauri::command]
+pub async fn enter_presentation_mode(
+    app: AppHandle,
+    db: State<'_, Db>,
+    presentation: State<'_, Presentation>,
+    project_id: String,
+    monitor: Option<String>,
+) -> AppResult<()> {
+    if let Some(current) = presentation.state().project_id {
+        if current != project_id {
+            return Err(AppError::InvalidInput(format!(
+                "project {current} is already being presented"
+            )));
+        }
+        if let Some(window) = app.get_webview_window(PRESENTATION_WINDOW) {
+            window.set_focus()?;
+        }
+        return Ok(());
+    }
+    let id = project_id.clone();
+    db.run(move |conn| {
+        conn.query_row("SELECT 1 FROM projects WHERE id = ?1", [&id], |_| Ok(()))
+            .optional()?
+            .ok_or_else(|| AppError::not_found("project", id))
+    })
+    .await?;
+    let position = target_monitor(&app, monitor.as_deref())?;
+
+    let route = format!(
+        "/present/{}",
+        utf8_percent_encode(&project_id, NON_ALPHANUMERIC)
+    );
+    let builder =
+        WebviewWindowBuilder::new(&app, PRESENTATION_WINDOW, WebviewUrl::App(route.into()))
+            .title("Momentum presentation")
+            .decorations(false)
+            .resizable(false)
+            .always_on_top(true)
+            .skip_taskbar(true)
+            .visible(false);
+    let window = crate::portable::webview(builder).build()?;
+    *presentation.lock() = Some(Active {
+        project_id: project_id.clone(),
+        exiting: false,
+        _awake: wake_lock::hold(&app, "Presenting a project"),
+    });
+    // Moved while windowed, since fullscreen sticks to whichever display the window is on
+    if let Some(position) = position {
+        window.set_position(position)?;
+    }
+    window.set_fullscreen(true)?;
+    window.show()?;
+    window.set_focus()?;
+    tracing::info!(project_id, "presentation started");
+    let _ = app.emit(PRESENTATION_EVENT, presentation.state());
+    Ok(())
+}
+
+/// End the presentation and close its window; does nothing when none is running.
+#[tauri::command]
+pub async fn exit_presentation_mode(
+    app: AppHandle,
+    presentation: State<'_, Presentation>,
+) -> AppResult<()> {
+    match presentation.lock().as_mut() {
+        Some(active) => active.exiting = true,
+        None => return Ok(()),
+    }
+    match app.get_webview_window(PRESENTATION_WINDOW) {
+        Some(window) => Ok(window.close()?),
+        // Already gone; drop the stale state
+        None => {
+            presentation.lock().take();
+            let _ = app.emit(PRESENTATION_EVENT, presentation.state());
+            Ok(())
+        }
+    }
+}
+
+/// The project being presented, for a view opened after presenting started.
+#[tauri::command]
+pub async fn get_presentation(
+    presentation: State<'_, Presentation>,
+) -> AppResult<PresentationState> {
+    Ok(presentation.state())
+}
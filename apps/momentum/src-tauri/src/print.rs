@@ -0,0 +1,427 @@
+//! Printing PDFs and generated reports through the operating system.
+//!
+//! Sources are first laid onto the requested paper with pdfium, scaled to fit, so paper
+//! size and orientation apply no matter which program ends up printing. macOS prints through
+//! Preview's system dialog, Windows hands the file to the default PDF app's print verb, and
+//! Linux spools straight to CUPS since there is no system dialog outside GTK apps.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use pdfium_render::prelude::{PdfPagePaperSize, Pdfium};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::db::now_ms;
+use crate::error::{AppError, AppResult};
+use crate::pdf::PdfEngine;
+use crate::reports::{self, ReportData, ReportTemplate};
+use crate::state::AppState;
+
+const WORK_DIR_NAME: &str = "print";
+// Print spoolers and viewers read the file after the command returns, so it cannot be
+// deleted right away; leftovers are swept on the next print instead
+const WORK_FILE_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// What to print.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum PrintSource {
+    /// An existing PDF, such as the open document exported to disk.
+    File { path: PathBuf },
+    /// A report rendered the same way as `generate_report_pdf`.
+    Report(Box<ReportSource>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportSource {
+    pub data: ReportData,
+    #[serde(default)]
+    pub template: Option<ReportTemplate>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PaperSize {
+    Letter,
+    Legal,
+    Tabloid,
+    A4,
+    A3,
+}
+
+impl PaperSize {
+    // Millimetres, portrait
+    fn dimensions(self) -> (f32, f32) {
+        match self {
+            Self::Letter => (215.9, 279.4),
+            Self::Legal => (215.9, 355.6),
+            Self::Tabloid => (279.4, 431.8),
+            Self::A4 => (210.0, 297.0),
+            Self::A3 => (297.0, 420.0),
+        }
+    }
+
+    #[cfg_attr(windows, allow(dead_code))]
+    fn cups_media(self) -> &'static str {
+        match self {
+            Self::Letter => "Letter",
+            Self::Legal => "Legal",
+            Self::Tabloid => "Tabloid",
+            Self::A4 => "A4",
+            Self::A3 => "A3",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Orientation {
+    #[default]
+    Portrait,
+    Landscape,
+}
+
+/// Paper and destination options; every field is optional.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PrintOptions {
+    /// Printer name from `list_printers`; the system default when omitted.
+    pub printer: Option<String>,
+    pub copies: u16,
+    /// Lay every page onto this paper; pages keep their own size when omitted.
+    pub paper_size: Option<PaperSize>,
+    /// Applies together with `paper_size`.
+    pub orientation: Orientation,
+    /// Show the system print dialog where the platform has one.
+    pub dialog: bool,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            printer: None,
+            copies: 1,
+            paper_size: None,
+            orientation: Orientation::Portrait,
+            dialog: true,
+        }
+    }
+}
+
+impl PrintOptions {
+    fn paper_mm(&self) -> Option<(f32, f32)> {
+        let (w, h) = self.paper_size?.dimensions();
+        Some(match self.orientation {
+            Orientation::Portrait => (w, h),
+            Orientation::Landscape => (h, w),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+pub enum PrintMethod {
+    /// The user confirmed the job in the system print dialog.
+    Dialog,
+    /// Queued with the system spooler.
+    Spooled,
+    /// Handed to the default PDF app, which may ask for confirmation itself.
+    DefaultApp,
+}
+
+/// How a print request was carried out.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrintJob {
+    pub method: PrintMethod,
+    pub printer: Option<String>,
+    /// Spooler job id, when the spooler reports one.
+    pub job_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Printer {
+    pub name: String,
+    pub is_default: bool,
+}
+
+fn run(command: &mut Command) -> AppResult<String> {
+    // Keep a console window from flashing up when launched from the GUI process
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(AppError::Internal(format!(
+            "{:?} failed: {}",
+            command.get_program(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn sweep(work_dir: &Path) {
+    let Ok(entries) = fs::read_dir(work_dir) else {
+        return;
+    };
+    let now = SystemTime::now();
+    for entry in entries.flatten() {
+        let stale = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age > WORK_FILE_MAX_AGE);
+        if stale {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+// Produces the PDF that is actually printed: the source as is, or laid onto the paper
+fn prepare(
+    source: PrintSource,
+    paper: Option<(f32, f32)>,
+    pdfium: Option<&Pdfium>,
+    work_dir: &Path,
+    output: Option<PathBuf>,
+) -> AppResult<PathBuf> {
+    fs::create_dir_all(work_dir)?;
+    let stamp = now_ms();
+    let source = match source {
+        PrintSource::File { path } => path,
+        PrintSource::Report(report) => {
+            let path = work_dir.join(format!("report-{stamp}.pdf"));
+            let template = report.template.unwrap_or_default();
+            reports::pdf::render(&report.data, &template, None, &path)?;
+            path
+        }
+    };
+    let output = output.unwrap_or_else(|| work_dir.join(format!("print-{stamp}.pdf")));
+    if let Some(dir) = output.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Written beside the output first, since the output may be the source itself
+    let tmp = output.with_extension("pdf.tmp");
+    match (paper, pdfium) {
+        (Some((width, height)), Some(pdfium)) => {
+            let document = pdfium.load_pdf_from_file(&source, None)?;
+            let laid_out = document.pages().tile_into_new_document(
+                1,
+                1,
+                PdfPagePaperSize::from_mm(width, height),
+            )?;
+            laid_out.save_to_file(&tmp)?;
+        }
+        _ if source == output => return Ok(output),
+        _ => {
+            fs::copy(&source, &tmp)?;
+        }
+    }
+    fs::rename(&tmp, &output)?;
+    Ok(output)
+}
+
+#[cfg(target_os = "macos")]
+fn print_file(path: &Path, options: &PrintOptions) -> AppResult<PrintJob> {
+    if !options.dialog {
+        return spool(path, options);
+    }
+    // The path is passed as an argument so it never needs AppleScript quoting
+    run(Command::new("osascript")
+        .args([
+            "-e",
+            "on run argv",
+            "-e",
+            "with timeout of 3600 seconds",
+            "-e",
+            "tell application \"Preview\" to print POSIX file (item 1 of argv) with print dialog",
+            "-e",
+            "end timeout",
+            "-e",
+            "end run",
+        ])
+        .arg(path))?;
+    Ok(PrintJob {
+        method: PrintMethod::Dialog,
+        printer: None,
+        job_id: None,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn print_file(path: &Path, options: &PrintOptions) -> AppResult<PrintJob> {
+    spool(path, options)
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn spool(path: &Path, options: &PrintOptions) -> AppResult<PrintJob> {
+    let mut command = Command::new("lp");
+    if let Some(printer) = &options.printer {
+        command.args(["-d", printer]);
+    }
+    command.args(["-n", &options.copies.max(1).to_string()]);
+    if let Some(paper) = options.paper_size {
+        command.args(["-o", &format!("media={}", paper.cups_media())]);
+    }
+    command.args(["-o", "fit-to-page"]).arg("--").arg(path);
+    // "request id is Office-42 (1 file(s))"
+    let output = run(&mut command)?;
+    let job_id = output
+        .split_whitespace()
+        .skip_while(|word| *word != "is")
+        .nth(1)
+        .map(str::to_string);
+    Ok(PrintJob {
+        method: PrintMethod::Spooled,
+        printer: options.printer.clone(),
+        job_id,
+    })
+}
+
+#[cfg(windows)]
+fn print_file(path: &Path, options: &PrintOptions) -> AppResult<PrintJob> {
+    // Values travel in environment variables so PowerShell never parses them as code
+    let script = if options.printer.is_some() {
+        "Start-Process -FilePath $env:MOMENTUM_PRINT_FILE -Verb PrintTo \
+         -ArgumentList ('\"' + $env:MOMENTUM_PRINT_PRINTER + '\"')"
+    } else {
+        "Start-Process -FilePath $env:MOMENTUM_PRINT_FILE -Verb Print"
+    };
+    for _ in 0..options.copies.max(1) {
+        run(Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", script])
+            .env("MOMENTUM_PRINT_FILE", path)
+            .env(
+                "MOMENTUM_PRINT_PRINTER",
+                options.printer.as_deref().unwrap_or_default(),
+            ))?;
+    }
+    Ok(PrintJob {
+        method: PrintMethod::DefaultApp,
+        printer: options.printer.clone(),
+        job_id: None,
+    })
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", windows)))]
+fn print_file(_path: &Path, _options: &PrintOptions) -> AppResult<PrintJob> {
+    Err(AppError::InvalidInput(
+        "printing is not supported on this platform".into(),
+    ))
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn printers() -> AppResult<Vec<Printer>> {
+    // No default prints an error and exits non-zero, which is not a failure here
+    let default = run(Command::new("lpstat").arg("-d"))
+        .ok()
+        .and_then(|out| out.split_once(':').map(|(_, name)| name.trim().to_string()));
+    Ok(run(Command::new("lpstat").arg("-e"))?
+        .lines()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| Printer {
+            is_default: default.as_deref() == Some(name),
+            name: name.to_string(),
+        })
+        .collect())
+}
+
+#[cfg(windows)]
+fn printers() -> AppResult<Vec<Printer>> {
+    let output = run(Command::new("powershell").args([
+        "-NoProfile",
+        "-NonInteractive",
+        "-Command",
+        "Get-CimInstance Win32_Printer | ForEach-Object { \"$($_.Default)`t$($_.Name)\" }",
+    ]))?;
+    Ok(output
+        .lines()
+        .filter_map(|line| line.trim().split_once('\t'))
+        .map(|(default, name)| Printer {
+            name: name.to_string(),
+            is_default: default.eq_ignore_ascii_case("true"),
+        })
+        .collect())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", windows)))]
+fn printers() -> AppResult<Vec<Printer>> {
+    Ok(Vec::new())
+}
+
+fn pdfium_for(engine: &PdfEngine, options: &PrintOptions) -> AppResult<Option<Arc<Pdfium>>> {
+    options.paper_size.map(|_| engine.pdfium()).transpose()
+}
+
+/// Installed printers, for a printer picker.
+#[tauri::command]
+pub async fn list_printers() -> AppResult<Vec<Printer>> {
+    tauri::async_runtime::spawn_blocking(printers).await?
+}
+
+/// Print a PDF or a report, through the system dialog unless `options.dialog` is false.
+#[tauri::command]
+pub async fn print(
+    state: State<'_, AppState>,
+    engine: State<'_, PdfEngine>,
+    source: PrintSource,
+    options: Option<PrintOptions>,
+) -> AppResult<PrintJob> {
+    let options = options.unwrap_or_default();
+    let pdfium = pdfium_for(&engine, &options)?;
+    let work_dir = state.data_dir().join(WORK_DIR_NAME);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        sweep(&work_dir);
+        let path = prepare(
+            source,
+            options.paper_mm(),
+            pdfium.as_deref(),
+            &work_dir,
+            None,
+        )?;
+        let job = print_file(&path, &options)?;
+        tracing::info!(method = ?job.method, printer = ?job.printer, "print job submitted");
+        Ok(job)
+    })
+    .await?
+}
+
+/// Write what `print` would send to the printer to `path` instead, without any dialog.
+#[tauri::command]
+pub async fn print_to_pdf(
+    state: State<'_, AppState>,
+    engine: State<'_, PdfEngine>,
+    source: PrintSource,
+    path: PathBuf,
+    options: Option<PrintOptions>,
+) -> AppResult<PathBuf> {
+    let options = options.unwrap_or_default();
+    let pdfium = pdfium_for(&engine, &options)?;
+    let work_dir = state.data_dir().join(WORK_DIR_NAME);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        sweep(&work_dir);
+        prepare(
+            source,
+            options.paper_mm(),
+            pdfium.as_deref(),
+            &work_dir,
+            Some(path),
+        )
+    })
+    .await?
+}
@@ -0,0 +1,213 @@
+//! Progress of long operations on the Windows taskbar button and the macOS dock icon.
+//!
+//! Exports, imports, downloads, and update downloads each report under their own key, and
+//! the OS indicator shows them combined, so a user can minimize the app and still see a big
+//! job move. The frontend reports through `set_progress` for work only it knows about. An
+//! error or pause on any operation shows over the others. Where the OS has no indicator
+//! (mobile, and Linux desktops without libunity) reports are kept but nothing is drawn.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use tauri::{AppHandle, Manager, Window, WindowEvent};
+
+use crate::error::{AppError, AppResult};
+
+/// How an operation is going.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProgressState {
+    /// Finished or abandoned; removes the operation.
+    None,
+    Normal,
+    /// Running with no known end.
+    Indeterminate,
+    Paused,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Operation {
+    state: ProgressState,
+    fraction: Option<f64>,
+}
+
+/// What the OS is currently showing, as a state and a whole percentage.
+type Shown = (ProgressState, Option<u64>);
+
+/// Operations reporting progress, registered in app state.
+#[derive(Default)]
+pub struct TaskbarProgress {
+    operations: Mutex<HashMap<String, Operation>>,
+    shown: Mutex<Option<Shown>>,
+}
+
+impl TaskbarProgress {
+    fn update(&self, app: &AppHandle, key: &str, state: ProgressState, fraction: Option<f64>) {
+        let combined = {
+            let mut operations = self.operations.lock().unwrap_or_else(|e| e.into_inner());
+            if state == ProgressState::None {
+                operations.remove(key);
+            } else {
+                let fraction = fraction
+                    .filter(|f| f.is_finite())
+                    .map(|f| f.clamp(0.0, 1.0));
+                operations.insert(key.to_string(), Operation { state, fraction });
+            }
+            combine(operations.values())
+        };
+        let mut shown = self.shown.lock().unwrap_or_else(|e| e.into_inner());
+        // Byte counts move on every chunk; the indicator only needs whole percents
+        if shown.as_ref() == Some(&combined) {
+            return;
+        }
+        show(app, combined);
+        *shown = Some(combined);
+    }
+
+    fn remove_window(&self, app: &AppHandle, label: &str) {
+        let key = window_key(label);
+        let known = self
+            .operations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key(&key);
+        if known {
+            self.update(app, &key, ProgressState::None, None);
+        }
+    }
+}
+
+// Errors and pauses outrank running work so a stalled job is not hidden by a busy one;
+// fractions of the operations that know theirs are averaged
+fn combine<'a>(operations: impl Iterator<Item = &'a Operation>) -> Shown {
+    let mut state = ProgressState::None;
+    let (mut sum, mut known) = (0.0, 0u32);
+    for operation in operations {
+        state = match (state, operation.state) {
+            (ProgressState::Error, _) | (_, ProgressState::Error) => ProgressState::Error,
+            (ProgressState::Paused, _) | (_, ProgressState::Paused) => ProgressState::Paused,
+            (ProgressState::Normal, _) | (_, ProgressState::Normal) => ProgressState::Normal,
+            _ => ProgressState::Indeterminate,
+        };
+        if let Some(fraction) = operation.fraction {
+            sum += fraction;
+            known += 1;
+        }
+    }
+    if state == ProgressState::None {
+        return (state, None);
+    }
+    if known == 0 {
+        // Nothing to draw a bar from
+        let state = match state {
+            ProgressState::Normal => ProgressState::Indeterminate,
+            state => state,
+        };
+        return (state, None);
+    }
+    let percent = (sum / f64::from(known) * 100.0).round() as u64;
+    (state, Some(percent))
+}
+
+#[cfg(desktop)]
+fn show(app: &AppHandle, (state, percent): Shown) {
+    use tauri::window::{ProgressBarState, ProgressBarStatus};
+
+    let Some(window) = app.get_webview_window(crate::windows::MAIN_WINDOW) else {
+        return;
+    };
+    let status = match state {
+        ProgressState::None => ProgressBarStatus::None,
+        ProgressState::Normal => ProgressBarStatus::Normal,
+        ProgressState::Indeterminate => ProgressBarStatus::Indeterminate,
+        ProgressState::Paused => ProgressBarStatus::Paused,
+        ProgressState::Error => ProgressBarStatus::Error,
+    };
+    let shown = window.set_progress_bar(ProgressBarState {
+        status: Some(status),
+        progress: percent,
+    });
+    if let Err(err) = shown {
+        tracing::debug!(%err, "cannot show taskbar progress");
+    }
+}
+
+#[cfg(not(desktop))]
+fn show(_app: &AppHandle, _shown: Shown) {}
+
+fn window_key(label: &str) -> String {
+    format!("window:{label}")
+}
+
+/// Clears its operation's progress when dropped.
+pub struct ProgressGuard {
+    app: AppHandle,
+    key: String,
+}
+
+impl ProgressGuard {
+    /// Report `done` out of `total`; an unknown total shows as indeterminate.
+    pub fn set(&self, done: u64, total: Option<u64>) {
+        match total {
+            Some(total) if total > 0 => {
+                self.report(ProgressState::Normal, Some(done as f64 / total as f64))
+            }
+            _ => self.report(ProgressState::Indeterminate, None),
+        }
+    }
+
+    pub fn report(&self, state: ProgressState, fraction: Option<f64>) {
+        if let Some(progress) = self.app.try_state::<TaskbarProgress>() {
+            progress.update(&self.app, &self.key, state, fraction);
+        }
+    }
+}
+
+impl Drop for ProgressGuard {
+    fn drop(&mut self) {
+        self.report(ProgressState::None, None);
+    }
+}
+
+/// Show an operation under `key` as running until the returned guard is dropped.
+pub fn track(app: &AppHandle, key: String) -> ProgressGuard {
+    let guard = ProgressGuard {
+        app: app.clone(),
+        key,
+    };
+    guard.report(ProgressState::Indeterminate, None);
+    guard
+}
+
+/// Drop the progress a window reported when it closes.
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    if !matches!(event, WindowEvent::Destroyed) {
+        return;
+    }
+    if let Some(progress) = window.try_state::<TaskbarProgress>() {
+        progress.remove_window(window.app_handle(), window.label());
+    }
+}
+
+/// Report the calling window's operation; `fraction` runs from 0 to 1.
+///
+/// The state `none` clears it. Each window has one slot, combined with the app's own
+/// operations on the indicator.
+#[tauri::command]
+pub async fn set_progress(
+    app: AppHandle,
+    window: Window,
+    progress: tauri::State<'_, TaskbarProgress>,
+    state: ProgressState,
+    fraction: Option<f64>,
+) -> AppResult<()> {
+    if state == ProgressState::Normal && fraction.is_none() {
+        return Err(AppError::InvalidInput(
+            "normal progress needs a fraction; use indeterminate otherwise".into(),
+        ));
+    }
+    progress.update(&app, &window_key(window.label()), state, fraction);
+    Ok(())
+}
@@ -0,0 +1,518 @@
+//! The native `.momentum` project file: a single-file SQLite database.
+//!
+//! Files are identified by SQLite's `application_id` and versioned in `user_version`. A
+//! `meta` row records the oldest format version able to read the file, so later versions
+//! can add tables and columns that older builds still open, read-only, while a truly
+//! incompatible change is refused outright.
+//!
+//! Opening a file copies it into the local database and locks it with a sidecar lock file,
+//! so the same project cannot be edited from two places at once. Saving writes a temp file
+//! and renames it over the original.
+
+use std::collections::HashMap;
+use std::fs::{self, File, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::db::projects::{Item, Project};
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::recent_projects;
+
+// "MOMT"
+const APPLICATION_ID: i32 = 0x4D4F_4D54;
+/// Version written by this build.
+const FORMAT_VERSION: i64 = 1;
+/// Oldest version able to read what this build writes.
+const MIN_READER_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE meta (
+    key   TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE project (
+    id          TEXT PRIMARY KEY,
+    name        TEXT NOT NULL,
+    description TEXT,
+    created_at  INTEGER NOT NULL,
+    updated_at  INTEGER NOT NULL
+);
+CREATE TABLE items (
+    id          TEXT PRIMARY KEY,
+    name        TEXT NOT NULL,
+    description TEXT,
+    quantity    REAL NOT NULL DEFAULT 0,
+    unit        TEXT,
+    unit_cost   REAL NOT NULL DEFAULT 0,
+    sort_order  INTEGER NOT NULL DEFAULT 0,
+    created_at  INTEGER NOT NULL,
+    updated_at  INTEGER NOT NULL
+);
+";
+
+/// A project file open in this app.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectDocument {
+    pub path: PathBuf,
+    pub project: Project,
+    pub item_count: usize,
+    /// Format version of the file on disk.
+    pub version: i64,
+    /// Written by a newer version; saving in place would drop what this build cannot read,
+    /// so only Save As is allowed.
+    pub read_only: bool,
+}
+
+/// Who holds a project file's lock, as written into the lock file.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LockOwner {
+    user: String,
+    host: String,
+    pid: u32,
+    since: i64,
+}
+
+struct OpenFile {
+    path: PathBuf,
+    _lock: FileLock,
+    version: i64,
+    read_only: bool,
+}
+
+// The advisory lock is held for as long as the handle lives, and the OS drops it if the
+// app dies, so a lock file left behind by a crash never blocks anyone
+struct FileLock {
+    path: PathBuf,
+    _file: File,
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".~{name}.lock"))
+}
+
+fn acquire_lock(path: &Path) -> AppResult<FileLock> {
+    let lock_path = lock_path(path);
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_path)?;
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let owner = fs::read(&lock_path)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice::<LockOwner>(&bytes).ok())
+                .map(|owner| format!("{} on {}", owner.user, owner.host));
+            return Err(AppError::FileLocked {
+                path: path.display().to_string(),
+                owner,
+            });
+        }
+        Err(TryLockError::Error(err)) => return Err(err.into()),
+    }
+    let owner = LockOwner {
+        user: std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_default(),
+        host: tauri_plugin_os::hostname(),
+        pid: std::process::id(),
+        since: now_ms(),
+    };
+    file.set_len(0)?;
+    file.write_all(&serde_json::to_vec(&owner)?)?;
+    Ok(FileLock {
+        path: lock_path,
+        _file: file,
+    })
+}
+
+/// Project files open in this app, keyed by project id.
+#[derive(Default)]
+pub struct ProjectFiles {
+    open: Mutex<HashMap<String, OpenFile>>,
+}
+
+impl ProjectFiles {
+    fn document(&self, project: Project, item_count: usize) -> Option<ProjectDocument> {
+        let open = self.open.lock().unwrap_or_else(|e| e.into_inner());
+        open.get(&project.id).map(|file| ProjectDocument {
+            path: file.path.clone(),
+            version: file.version,
+            read_only: file.read_only,
+            project,
+            item_count,
+        })
+    }
+
+    fn project_at(&self, path: &Path) -> Option<String> {
+        let open = self.open.lock().unwrap_or_else(|e| e.into_inner());
+        open.iter()
+            .find(|(_, file)| file.path == path)
+            .map(|(id, _)| id.clone())
+    }
+}
+
+struct Contents {
+    version: i64,
+    project: Project,
+    items: Vec<Item>,
+}
+
+fn read_file(path: &Path) -> AppResult<Contents> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let not_a_project =
+        || AppError::InvalidInput(format!("{} is not a project file", path.display()));
+    let application_id: i32 = conn
+        .query_row("PRAGMA application_id", [], |row| row.get(0))
+        .map_err(|_| not_a_project())?;
+    if application_id != APPLICATION_ID {
+        return Err(not_a_project());
+    }
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    let min_reader: i64 = conn
+        .query_row(
+            "SELECT value FROM meta WHERE key = 'min_reader_version'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(version);
+    if min_reader > FORMAT_VERSION {
+        return Err(AppError::InvalidInput(format!(
+            "{} needs a newer version of the app (format {version}); update to open it",
+            path.display()
+        )));
+    }
+
+    // Columns are named so ones added by newer versions are ignored
+    let project = conn.query_row(
+        "SELECT id, name, description, created_at, updated_at FROM project",
+        [],
+        Project::from_row,
+    )?;
+    let items = conn
+        .prepare(
+            "SELECT id, ?1 AS project_id, name, description, quantity, unit, unit_cost,
+                    sort_order, created_at, updated_at
+             FROM items ORDER BY sort_order, name",
+        )?
+        .query_map([&project.id], Item::from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(Contents {
+        version,
+        project,
+        items,
+    })
+}
+
+fn write_file(path: &Path, app_version: &str, project: &Project, items: &[Item]) -> AppResult<()> {
+    let tmp = path.with_extension("momentum.tmp");
+    let _ = fs::remove_file(&tmp);
+    let result = (|| {
+        let mut conn = Connection::open(&tmp)?;
+        conn.pragma_update(None, "application_id", APPLICATION_ID)?;
+        conn.pragma_update(None, "user_version", FORMAT_VERSION)?;
+        let tx = conn.transaction()?;
+        tx.execute_batch(SCHEMA)?;
+        for (key, value) in [
+            ("format_version", FORMAT_VERSION.to_string()),
+            ("min_reader_version", MIN_READER_VERSION.to_string()),
+            ("app_version", app_version.to_string()),
+            ("saved_at", now_ms().to_string()),
+        ] {
+            tx.execute(
+                "INSERT INTO meta (key, value) VALUES (?1, ?2)",
+                params![key, value],
+            )?;
+        }
+        tx.execute(
+            "INSERT INTO project (id, name, description, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                project.id,
+                project.name,
+                project.description,
+                project.created_at,
+                project.updated_at
+            ],
+        )?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO items (id, name, description, quantity, unit, unit_cost,
+                                    sort_order, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?;
+            for item in items {
+                insert.execute(params![
+                    item.id,
+                    item.name,
+                    item.description,
+                    item.quantity,
+                    item.unit,
+                    item.unit_cost,
+                    item.sort_order,
+                    item.created_at,
+                    item.updated_at
+                ])?;
+            }
+        }
+        tx.commit()?;
+        conn.close().map_err(|(_, err)| err)?;
+        Ok::<_, AppError>(())
+    })();
+    if let Err(err) = result {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+// The file is the source of truth, so its rows replace the local copy wholesale
+fn load_into(conn: &mut Connection, contents: &Contents) -> AppResult<()> {
+    let project = &contents.project;
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO projects (id, name, description, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(id) DO UPDATE SET
+            name = excluded.name,
+            description = excluded.description,
+            updated_at = excluded.updated_at",
+        params![
+            project.id,
+            project.name,
+            project.description,
+            project.created_at,
+            project.updated_at
+        ],
+    )?;
+    tx.execute("DELETE FROM items WHERE project_id = ?1", [&project.id])?;
+    {
+        let mut insert = tx.prepare(
+            "INSERT INTO items (id, project_id, name, description, quantity, unit,
+                                unit_cost, sort_order, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        )?;
+        for item in &contents.items {
+            insert.execute(params![
+                item.id,
+                project.id,
+                item.name,
+                item.description,
+                item.quantity,
+                item.unit,
+                item.unit_cost,
+                item.sort_order,
+                item.created_at,
+                item.updated_at
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn load_project(conn: &Connection, project_id: &str) -> AppResult<(Project, Vec<Item>)> {
+    let project = conn
+        .query_row(
+            "SELECT * FROM projects WHERE id = ?1",
+            [project_id],
+            Project::from_row,
+        )
+        .optional()?
+        .ok_or_else(|| AppError::not_found("project", project_id))?;
+    let items = conn
+        .prepare("SELECT * FROM items WHERE project_id = ?1 ORDER BY sort_order, name")?
+        .query_map([project_id], Item::from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok((project, items))
+}
+
+/// Open a project file for editing, copying it into the local database.
+///
+/// Fails with `FILE_LOCKED` while another window or machine has it open.
+#[tauri::command]
+pub async fn project_open(
+    app: AppHandle,
+    files: State<'_, ProjectFiles>,
+    db: State<'_, Db>,
+    path: PathBuf,
+) -> AppResult<ProjectDocument> {
+    let path = fs::canonicalize(&path)?;
+    // Already open here; the local copy may have edits newer than the file
+    if let Some(project_id) = files.project_at(&path) {
+        let (project, count) = db
+            .run(move |conn| {
+                let (project, items) = load_project(conn, &project_id)?;
+                Ok((project, items.len()))
+            })
+            .await?;
+        recent_projects::record(&app, &path, &project);
+        return files
+            .document(project, count)
+            .ok_or_else(|| AppError::Internal("project file closed while opening".into()));
+    }
+    let lock = acquire_lock(&path)?;
+    let contents = {
+        let path = path.clone();
+        tauri::async_runtime::spawn_blocking(move || read_file(&path)).await??
+    };
+    let contents = db
+        .run(move |conn| {
+            load_into(conn, &contents)?;
+            Ok(contents)
+        })
+        .await?;
+
+    let project_id = contents.project.id.clone();
+    files.open.lock().unwrap_or_else(|e| e.into_inner()).insert(
+        project_id.clone(),
+        OpenFile {
+            path: path.clone(),
+            _lock: lock,
+            version: contents.version,
+            read_only: contents.version > FORMAT_VERSION,
+        },
+    );
+    tracing::info!(%project_id, path = %path.display(), version = contents.version, "project file opened");
+    recent_projects::record(&app, &path, &contents.project);
+    files
+        .document(contents.project, contents.items.len())
+        .ok_or_else(|| AppError::Internal("project file closed while opening".into()))
+}
+
+/// Write a project back to the file it was opened from.
+#[tauri::command]
+pub async fn project_save(
+    app: AppHandle,
+    files: State<'_, ProjectFiles>,
+    db: State<'_, Db>,
+    project_id: String,
+) -> AppResult<ProjectDocument> {
+    let path = {
+        let open = files.open.lock().unwrap_or_else(|e| e.into_inner());
+        let file = open
+            .get(&project_id)
+            .ok_or_else(|| AppError::not_found("open project file", project_id.clone()))?;
+        if file.read_only {
+            return Err(AppError::InvalidInput(format!(
+                "{} was saved by a newer version of the app; use Save As to keep a copy",
+                file.path.display()
+            )));
+        }
+        file.path.clone()
+    };
+    let app_version = app.package_info().version.to_string();
+    let (project, count) = db
+        .run(move |conn| {
+            let (project, items) = load_project(conn, &project_id)?;
+            write_file(&path, &app_version, &project, &items)?;
+            Ok((project, items.len()))
+        })
+        .await?;
+    tracing::info!(project_id = %project.id, "project file saved");
+    files
+        .document(project, count)
+        .ok_or_else(|| AppError::Internal("project file closed while saving".into()))
+}
+
+/// Write a project to a new file, which becomes the one it is saved to from now on.
+///
+/// Also works for projects that were never opened from a file.
+#[tauri::command]
+pub async fn project_save_as(
+    app: AppHandle,
+    files: State<'_, ProjectFiles>,
+    db: State<'_, Db>,
+    project_id: String,
+    path: PathBuf,
+) -> AppResult<ProjectDocument> {
+    let path = match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) if !dir.as_os_str().is_empty() => fs::canonicalize(dir)?.join(name),
+        _ => path,
+    };
+    let same_file = files
+        .open
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&project_id)
+        .is_some_and(|file| file.path == path);
+    let lock = if same_file {
+        None
+    } else {
+        Some(acquire_lock(&path)?)
+    };
+
+    let app_version = app.package_info().version.to_string();
+    let (project, count) = {
+        let path = path.clone();
+        let project_id = project_id.clone();
+        db.run(move |conn| {
+            let (project, items) = load_project(conn, &project_id)?;
+            write_file(&path, &app_version, &project, &items)?;
+            Ok((project, items.len()))
+        })
+        .await?
+    };
+
+    {
+        let mut open = files.open.lock().unwrap_or_else(|e| e.into_inner());
+        match (open.get_mut(&project_id), lock) {
+            (Some(file), None) => {
+                file.version = FORMAT_VERSION;
+                file.read_only = false;
+            }
+            // Replacing the entry drops the old file's lock
+            (_, Some(lock)) => {
+                open.insert(
+                    project_id.clone(),
+                    OpenFile {
+                        path: path.clone(),
+                        _lock: lock,
+                        version: FORMAT_VERSION,
+                        read_only: false,
+                    },
+                );
+            }
+            (None, None) => {}
+        }
+    }
+    tracing::info!(%project_id, path = %path.display(), "project file saved as");
+    recent_projects::record(&app, &path, &project);
+    files
+        .document(project, count)
+        .ok_or_else(|| AppError::Internal("project file closed while saving".into()))
+}
+
+/// Release a project file so it can be opened elsewhere. Unsaved changes stay local.
+#[tauri::command]
+pub async fn project_close(files: State<'_, ProjectFiles>, project_id: String) -> AppResult<()> {
+    files
+        .open
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&project_id);
+    Ok(())
+}
@@ -0,0 +1,434 @@
+//! Outbound proxy support: system detection and manual settings applied to every HTTP client.
+//!
+//! PAC scripts need a JavaScript engine to evaluate, so a PAC-only system configuration is
+//! reported by detection but requests go direct; users in that setup configure a manual proxy.
+
+use std::process::Command;
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use tauri_plugin_http::reqwest::{ClientBuilder, NoProxy, Proxy};
+use url::Url;
+
+use crate::config;
+use crate::error::{AppError, AppResult};
+use crate::http_client::HttpClient;
+use crate::secrets;
+use crate::state::AppState;
+
+const PASSWORD_SECRET_KEY: &str = "proxy.password";
+
+/// Where proxy settings come from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProxyMode {
+    /// OS proxy settings, falling back to `HTTPS_PROXY`-style environment variables.
+    #[default]
+    System,
+    Manual,
+    /// Never use a proxy, even if the OS or environment sets one.
+    Direct,
+}
+
+/// Persisted proxy settings; the password lives in the keychain.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProxySettings {
+    pub mode: ProxyMode,
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    /// Comma-separated hosts or domains that bypass the proxy.
+    pub no_proxy: Option<String>,
+}
+
+/// What the OS or environment is configured to use.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemProxy {
+    pub http: Option<String>,
+    pub https: Option<String>,
+    pub no_proxy: Option<String>,
+    /// Detected but not evaluated.
+    pub pac_url: Option<String>,
+    /// `system` or `environment`; `None` when nothing is configured.
+    pub source: Option<&'static str>,
+}
+
+/// Proxy URLs actually applied to outbound requests, credentials included.
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedProxy {
+    http: Option<Url>,
+    https: Option<Url>,
+    no_proxy: Option<String>,
+}
+
+impl ResolvedProxy {
+    /// Configure `builder` to use exactly this proxy.
+    pub fn apply(&self, builder: ClientBuilder) -> AppResult<ClientBuilder> {
+        // Start from none so reqwest's own environment lookup never overrides the settings
+        let mut builder = builder.no_proxy();
+        let bypass = || self.no_proxy.as_deref().and_then(NoProxy::from_string);
+        if let Some(url) = &self.http {
+            builder = builder.proxy(Proxy::http(url.as_str())?.no_proxy(bypass()));
+        }
+        if let Some(url) = &self.https {
+            builder = builder.proxy(Proxy::https(url.as_str())?.no_proxy(bypass()));
+        }
+        Ok(builder)
+    }
+
+    /// The updater accepts a single proxy, and its endpoints are HTTPS.
+    pub fn updater_proxy(&self) -> Option<&Url> {
+        self.https.as_ref().or(self.http.as_ref())
+    }
+
+    /// The HTTP proxy to open a CONNECT tunnel to `host` through, for clients outside reqwest.
+    ///
+    /// SOCKS and HTTPS proxies are not tunnelled through; those connections go direct.
+    pub fn tunnel(&self, host: &str, secure: bool) -> Option<&Url> {
+        if self
+            .no_proxy
+            .as_deref()
+            .is_some_and(|list| bypasses(list, host))
+        {
+            return None;
+        }
+        let proxy = if secure { &self.https } else { &self.http };
+        proxy.as_ref().filter(|url| url.scheme() == "http")
+    }
+}
+
+// Same forms reqwest accepts: `*`, exact hosts, and domains with or without a leading dot
+fn bypasses(list: &str, host: &str) -> bool {
+    let host = host.trim_matches(['[', ']']).to_ascii_lowercase();
+    list.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| {
+            if entry == "*" {
+                return true;
+            }
+            let domain = entry
+                .trim_start_matches("*.")
+                .trim_start_matches('.')
+                .to_ascii_lowercase();
+            host == domain || host.ends_with(&format!(".{domain}"))
+        })
+}
+
+/// The proxy currently in effect, registered in app state.
+pub struct ProxyState {
+    resolved: RwLock<ResolvedProxy>,
+}
+
+impl ProxyState {
+    pub fn new(resolved: ResolvedProxy) -> Self {
+        Self {
+            resolved: RwLock::new(resolved),
+        }
+    }
+
+    pub fn current(&self) -> ResolvedProxy {
+        self.resolved
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+/// Turn settings into proxy URLs; blocks on the keychain and OS queries.
+pub fn resolve(settings: &ProxySettings) -> AppResult<ResolvedProxy> {
+    match settings.mode {
+        ProxyMode::Direct => Ok(ResolvedProxy::default()),
+        ProxyMode::Manual => {
+            if settings.host.is_empty() || settings.port == 0 {
+                return Err(AppError::InvalidInput(
+                    "manual proxy needs a host and port".into(),
+                ));
+            }
+            let mut url = Url::parse(&format!("http://{}:{}", settings.host, settings.port))
+                .map_err(|e| AppError::InvalidInput(format!("invalid proxy address: {e}")))?;
+            if let Some(username) = settings.username.as_deref().filter(|u| !u.is_empty()) {
+                let password = secrets::get(PASSWORD_SECRET_KEY)?;
+                // reqwest reads proxy credentials from the URL's userinfo
+                let _ = url.set_username(username);
+                let _ = url.set_password(password.as_deref());
+            }
+            Ok(ResolvedProxy {
+                http: Some(url.clone()),
+                https: Some(url),
+                no_proxy: settings.no_proxy.clone(),
+            })
+        }
+        ProxyMode::System => {
+            let detected = detect();
+            if detected.http.is_none() && detected.https.is_none() {
+                if let Some(pac_url) = &detected.pac_url {
+                    tracing::warn!(%pac_url, "PAC proxy configuration is not supported; going direct");
+                }
+            }
+            let parse = |value: &Option<String>| value.as_deref().and_then(proxy_url);
+            Ok(ResolvedProxy {
+                http: parse(&detected.http),
+                https: parse(&detected.https),
+                no_proxy: detected.no_proxy,
+            })
+        }
+    }
+}
+
+// System settings often omit the scheme ("proxy.corp:8080")
+fn proxy_url(value: &str) -> Option<Url> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    if value.contains("://") {
+        Url::parse(value).ok()
+    } else {
+        Url::parse(&format!("http://{value}")).ok()
+    }
+}
+
+/// Read the OS proxy configuration, falling back to environment variables.
+pub fn detect() -> SystemProxy {
+    let system = detect_os();
+    if system.http.is_some() || system.https.is_some() || system.pac_url.is_some() {
+        return SystemProxy {
+            source: Some("system"),
+            ..system
+        };
+    }
+    let env = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| std::env::var(name).ok().filter(|v| !v.is_empty()))
+    };
+    let all = env(&["ALL_PROXY", "all_proxy"]);
+    let http = env(&["HTTP_PROXY", "http_proxy"]).or_else(|| all.clone());
+    let https = env(&["HTTPS_PROXY", "https_proxy"]).or(all);
+    let source = (http.is_some() || https.is_some()).then_some("environment");
+    SystemProxy {
+        http,
+        https,
+        no_proxy: env(&["NO_PROXY", "no_proxy"]),
+        pac_url: None,
+        source,
+    }
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let mut command = Command::new(program);
+    command.args(args);
+    // Keep a console window from flashing up when queried from the GUI process
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let output = command.output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(target_os = "macos")]
+fn detect_os() -> SystemProxy {
+    let Some(output) = command_output("scutil", &["--proxy"]) else {
+        return SystemProxy::default();
+    };
+    let value = |key: &str| {
+        output.lines().find_map(|line| {
+            let (k, v) = line.split_once(" : ")?;
+            (k.trim() == key).then(|| v.trim().to_string())
+        })
+    };
+    let enabled = |key: &str| value(key).as_deref() == Some("1");
+    let server = |prefix: &str| {
+        if !enabled(&format!("{prefix}Enable")) {
+            return None;
+        }
+        let host = value(&format!("{prefix}Proxy"))?;
+        Some(match value(&format!("{prefix}Port")) {
+            Some(port) => format!("{host}:{port}"),
+            None => host,
+        })
+    };
+    // Exceptions are printed as an indented array of "index : host" lines
+    let exceptions: Vec<String> = output
+        .lines()
+        .skip_while(|line| !line.contains("ExceptionsList"))
+        .skip(1)
+        .take_while(|line| !line.trim_start().starts_with('}'))
+        .filter_map(|line| line.split_once(" : ").map(|(_, v)| v.trim().to_string()))
+        .collect();
+    SystemProxy {
+        http: server("HTTP"),
+        https: server("HTTPS"),
+        no_proxy: (!exceptions.is_empty()).then(|| exceptions.join(",")),
+        pac_url: enabled("ProxyAutoConfigEnable")
+            .then(|| value("ProxyAutoConfigURLString"))
+            .flatten(),
+        source: None,
+    }
+}
+
+#[cfg(windows)]
+fn detect_os() -> SystemProxy {
+    let Some(output) = command_output(
+        "reg",
+        &[
+            "query",
+            r"HKCU\Software\Microsoft\Windows\CurrentVersion\Internet Settings",
+        ],
+    ) else {
+        return SystemProxy::default();
+    };
+    let value = |name: &str| {
+        output.lines().find_map(|line| {
+            let mut parts = line.split_whitespace();
+            (parts.next()? == name).then(|| parts.skip(1).collect::<Vec<_>>().join(" "))
+        })
+    };
+    let pac_url = value("AutoConfigURL");
+    if value("ProxyEnable").as_deref() != Some("0x1") {
+        return SystemProxy {
+            pac_url,
+            ..SystemProxy::default()
+        };
+    }
+    // Either one "host:port" for every protocol or "http=host:port;https=host:port"
+    let server = value("ProxyServer").unwrap_or_default();
+    let (http, https) = if server.contains('=') {
+        let scheme = |name: &str| {
+            server.split(';').find_map(|part| {
+                let (k, v) = part.split_once('=')?;
+                (k.eq_ignore_ascii_case(name)).then(|| v.to_string())
+            })
+        };
+        (scheme("http"), scheme("https"))
+    } else {
+        let server = Some(server).filter(|s| !s.is_empty());
+        (server.clone(), server)
+    };
+    SystemProxy {
+        http,
+        https,
+        // "<local>" means bypass for dotless hosts, which NoProxy cannot express
+        no_proxy: value("ProxyOverride").map(|list| {
+            list.split(';')
+                .filter(|entry| *entry != "<local>")
+                .collect::<Vec<_>>()
+                .join(",")
+        }),
+        pac_url,
+        source: None,
+    }
+}
+
+// GNOME settings; other desktops conventionally export the environment variables
+#[cfg(all(unix, not(target_os = "macos")))]
+fn detect_os() -> SystemProxy {
+    let get = |schema: &str, key: &str| {
+        command_output("gsettings", &["get", schema, key])
+            .map(|v| v.trim().trim_matches('\'').to_string())
+    };
+    match get("org.gnome.system.proxy", "mode").as_deref() {
+        Some("manual") => {
+            let server = |scheme: &str| {
+                let schema = format!("org.gnome.system.proxy.{scheme}");
+                let host = get(&schema, "host").filter(|h| !h.is_empty())?;
+                let port = get(&schema, "port").filter(|p| p != "0");
+                Some(match port {
+                    Some(port) => format!("{host}:{port}"),
+                    None => host,
+                })
+            };
+            // Printed as a GVariant string array: ['localhost', '127.0.0.0/8']
+            let ignore = get("org.gnome.system.proxy", "ignore-hosts").map(|list| {
+                list.trim_matches(|c| c == '[' || c == ']')
+                    .split(',')
+                    .map(|host| host.trim().trim_matches('\''))
+                    .filter(|host| !host.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            });
+            SystemProxy {
+                http: server("http"),
+                https: server("https"),
+                no_proxy: ignore,
+                pac_url: None,
+                source: None,
+            }
+        }
+        Some("auto") => SystemProxy {
+            pac_url: get("org.gnome.system.proxy", "autoconfig-url").filter(|u| !u.is_empty()),
+            ..SystemProxy::default()
+        },
+        _ => SystemProxy::default(),
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn detect_os() -> SystemProxy {
+    SystemProxy::default()
+}
+
+/// Saved settings plus whether a password is stored, which is never returned itself.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyConfig {
+    pub settings: ProxySettings,
+    pub has_password: bool,
+}
+
+#[tauri::command]
+pub async fn get_proxy_settings(state: State<'_, AppState>) -> AppResult<ProxyConfig> {
+    let settings = state.config().proxy;
+    let has_password = tauri::async_runtime::spawn_blocking(|| secrets::get(PASSWORD_SECRET_KEY))
+        .await??
+        .is_some();
+    Ok(ProxyConfig {
+        settings,
+        has_password,
+    })
+}
+
+#[tauri::command]
+pub async fn detect_system_proxy() -> AppResult<SystemProxy> {
+    Ok(tauri::async_runtime::spawn_blocking(detect).await?)
+}
+
+/// Save settings and rebuild outbound clients so they apply immediately.
+///
+/// `password` replaces the stored one when given; an empty string removes it.
+#[tauri::command]
+pub async fn set_proxy_settings(
+    app: AppHandle,
+    proxy: State<'_, ProxyState>,
+    http: State<'_, HttpClient>,
+    settings: ProxySettings,
+    password: Option<String>,
+) -> AppResult<()> {
+    // Resolve before saving so invalid settings never persist
+    let candidate = settings.clone();
+    let resolved = tauri::async_runtime::spawn_blocking(move || {
+        match password.as_deref() {
+            Some("") => secrets::delete(PASSWORD_SECRET_KEY)?,
+            Some(password) => secrets::set(PASSWORD_SECRET_KEY, password)?,
+            None => {}
+        }
+        resolve(&candidate)
+    })
+    .await??;
+
+    let config = config::update(&app, |c| c.proxy = settings)?;
+    http.rebuild(&config, &resolved)?;
+    *proxy.resolved.write().unwrap_or_else(|e| e.into_inner()) = resolved;
+    tracing::info!(mode = ?config.proxy.mode, "proxy settings applied");
+    Ok(())
+}
@@ -0,0 +1,303 @@
+//! Remote push notifications through APNs on iOS and FCM on Android.
+//!
+//! The native plugin reports the device token and every notification through one channel
+//! opened at startup. The token is registered with the backend through the outbox, so it
+//! reaches the server once the device is online and is sent again after each sign-in. A
+//! notification may carry a `link` deep link: tapping it navigates through the same router
+//! as desktop links and notification clicks, which queues the route if the app was launched
+//! by the tap and the frontend has not mounted yet.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::auth;
+use crate::deeplink::Route;
+use crate::error::AppResult;
+use crate::outbox::{self, OutboxRequest};
+
+/// Emitted with a [`PushMessage`] when a notification arrives while the app is open.
+#[cfg_attr(desktop, allow(dead_code))]
+pub const RECEIVED_EVENT: &str = "push:received";
+/// Emitted with a [`PushMessage`] when the user taps a notification.
+#[cfg_attr(desktop, allow(dead_code))]
+pub const OPENED_EVENT: &str = "push:opened";
+
+const DEVICES_PATH: &str = "/v1/devices/push";
+// Payload key holding the deep link to open on tap
+#[cfg_attr(desktop, allow(dead_code))]
+const LINK_KEY: &str = "link";
+
+/// Payload for `push:received` and `push:opened`.
+#[cfg_attr(desktop, allow(dead_code))]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PushMessage {
+    pub title: Option<String>,
+    pub body: Option<String>,
+    /// Where a tap leads, parsed from the payload's `link`.
+    pub route: Option<Route>,
+    /// The rest of the payload as the server sent it.
+    pub data: BTreeMap<String, Value>,
+}
+
+/// Whether this device is registered for push.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PushStatus {
+    /// `ios` or `android`; `None` on desktop.
+    pub platform: Option<&'static str>,
+    pub token: Option<String>,
+}
+
+/// The current device token, registered in app state.
+#[derive(Default)]
+pub struct Push {
+    token: Mutex<Option<String>>,
+}
+
+impl Push {
+    fn token(&self) -> Option<String> {
+        self.token.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    // Returns whether the token changed
+    fn set_token(&self, token: Option<String>) -> bool {
+        let mut current = self.token.lock().unwrap_or_else(|e| e.into_inner());
+        let changed = *current != token;
+        *current = token;
+        changed
+    }
+}
+
+/// Start listening for tokens and notifications; the native plugin must already be
+/// registered.
+pub fn init(app: &AppHandle) {
+    app.manage(Push::default());
+    if let Err(err) = platform::listen(app) {
+        tracing::warn!(%err, "cannot receive push notifications");
+    }
+}
+
+/// Send the device token to the backend again, now that someone is signed in.
+pub fn on_signed_in(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(err) = register_device(&app).await {
+            tracing::warn!(%err, "cannot register device for push");
+        }
+    });
+}
+
+async fn register_device(app: &AppHandle) -> AppResult<()> {
+    let Some(token) = app.state::<Push>().token() else {
+        return Ok(());
+    };
+    // Signed-out devices are registered on the next sign-in instead
+    if tauri::async_runtime::spawn_blocking(auth::session)
+        .await??
+        .is_none()
+    {
+        return Ok(());
+    }
+    outbox::enqueue(
+        app,
+        OutboxRequest {
+            method: "PUT".into(),
+            path: DEVICES_PATH.into(),
+            body: Some(json!({
+                "token": token,
+                "platform": platform::NAME,
+                "appVersion": app.package_info().version.to_string(),
+            })),
+            idempotency_key: Some(format!("push-device:{token}")),
+        },
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(mobile)]
+fn token_received(app: &AppHandle, token: String) {
+    if app.state::<Push>().set_token(Some(token)) {
+        on_signed_in(app);
+    }
+}
+
+#[cfg(mobile)]
+fn message_received(app: &AppHandle, message: platform::NativeMessage) {
+    use tauri::Emitter;
+
+    let route = message
+        .data
+        .get(LINK_KEY)
+        .and_then(Value::as_str)
+        .and_then(|link| url::Url::parse(link).ok())
+        .and_then(|url| Route::parse(&url));
+    let tapped = message.tapped;
+    let message = PushMessage {
+        title: message.title,
+        body: message.body,
+        route,
+        data: message.data,
+    };
+    tracing::debug!(tapped, route = ?message.route, "push notification");
+    if tapped {
+        if let Some(route) = &message.route {
+            crate::deeplink::navigate(app, route.clone());
+        }
+        let _ = app.emit(OPENED_EVENT, &message);
+    } else {
+        let _ = app.emit(RECEIVED_EVENT, &message);
+    }
+}
+
+#[cfg(mobile)]
+mod platform {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+    use tauri::ipc::{Channel, InvokeResponseBody};
+    use tauri::AppHandle;
+
+    use crate::error::{AppError, AppResult};
+    use crate::mobile;
+
+    #[cfg(target_os = "ios")]
+    pub const NAME: Option<&str> = Some("ios");
+    #[cfg(target_os = "android")]
+    pub const NAME: Option<&str> = Some("android");
+
+    #[derive(Deserialize)]
+    pub struct NativeMessage {
+        pub title: Option<String>,
+        pub body: Option<String>,
+        #[serde(default)]
+        pub data: BTreeMap<String, Value>,
+        /// Delivered by a tap rather than while the app was open.
+        #[serde(default)]
+        pub tapped: bool,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(tag = "kind", rename_all = "camelCase")]
+    enum NativeEvent {
+        Token { token: String },
+        Message(NativeMessage),
+    }
+
+    #[derive(Serialize)]
+    struct ListenArgs {
+        handler: Channel<Value>,
+    }
+
+    #[derive(Deserialize)]
+    struct Registered {
+        token: String,
+    }
+
+    pub fn listen(app: &AppHandle) -> AppResult<()> {
+        let handle = app.clone();
+        let handler = Channel::new(move |body: InvokeResponseBody| {
+            match body.deserialize()? {
+                NativeEvent::Token { token } => super::token_received(&handle, token),
+                NativeEvent::Message(message) => super::message_received(&handle, message),
+            }
+            Ok(())
+        });
+        mobile::run(app, "listenForPush", ListenArgs { handler })
+    }
+
+    // The plugin rejects as denied while notifications are not allowed, so the prompt is
+    // only shown when registering needs it
+    pub fn register(app: &AppHandle) -> AppResult<String> {
+        let registered: Registered = match mobile::run(app, "registerForPush", ()) {
+            Err(AppError::DevicePermissionDenied { .. }) => {
+                mobile::ensure_permission(app, "notifications")?;
+                mobile::run(app, "registerForPush", ())?
+            }
+            result => result?,
+        };
+        Ok(registered.token)
+    }
+
+    pub fn unregister(app: &AppHandle) -> AppResult<()> {
+        mobile::run(app, "unregisterForPush", ())
+    }
+}
+
+#[cfg(desktop)]
+mod platform {
+    use tauri::AppHandle;
+
+    use crate::error::{AppError, AppResult};
+
+    pub const NAME: Option<&str> = None;
+
+    pub fn listen(_app: &AppHandle) -> AppResult<()> {
+        Ok(())
+    }
+
+    pub fn register(_app: &AppHandle) -> AppResult<String> {
+        Err(AppError::InvalidInput(
+            "push notifications are only available on mobile".into(),
+        ))
+    }
+
+    pub fn unregister(_app: &AppHandle) -> AppResult<()> {
+        Ok(())
+    }
+}
+
+/// Ask for notification permission if needed and register this device for push.
+///
+/// Fails with `DEVICE_PERMISSION_DENIED` when the user refuses notifications.
+#[tauri::command]
+pub async fn enable_push_notifications(
+    app: AppHandle,
+    push: State<'_, Push>,
+) -> AppResult<PushStatus> {
+    let handle = app.clone();
+    let token = tauri::async_runtime::spawn_blocking(move || platform::register(&handle)).await??;
+    push.set_token(Some(token.clone()));
+    register_device(&app).await?;
+    Ok(PushStatus {
+        platform: platform::NAME,
+        token: Some(token),
+    })
+}
+
+/// Stop push delivery to this device and remove its token from the backend.
+#[tauri::command]
+pub async fn disable_push_notifications(app: AppHandle, push: State<'_, Push>) -> AppResult<()> {
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || platform::unregister(&handle)).await??;
+    let Some(token) = push.token() else {
+        return Ok(());
+    };
+    push.set_token(None);
+    outbox::enqueue(
+        &app,
+        OutboxRequest {
+            method: "DELETE".into(),
+            path: DEVICES_PATH.into(),
+            body: Some(json!({ "token": token })),
+            idempotency_key: Some(format!("push-device-removed:{token}")),
+        },
+    )
+    .await
+    .map(|_| ())
+}
+
+/// The platform and current device token, if push is enabled.
+#[tauri::command]
+pub async fn get_push_status(push: State<'_, Push>) -> AppResult<PushStatus> {
+    Ok(PushStatus {
+        platform: platform::NAME,
+        token: push.token(),
+    })
+}
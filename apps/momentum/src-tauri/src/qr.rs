@@ -0,0 +1,225 @@
+//! QR codes for handing a link from the desktop to a phone.
+//!
+//! The `qrcode` crate picks the segments, version, and mask; this module only renders its
+//! module grid. `generate_qr` renders a code as SVG or PNG for LAN-share links, pairing,
+//! and reports. Reading codes back is `scan`'s job.
+
+use std::fmt::Write;
+use std::io::Cursor;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::{GrayImage, ImageFormat, Luma};
+use qrcode::types::QrError;
+use qrcode::{Color, EcLevel};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, AppResult};
+
+// Light modules around the code, as the standard asks for
+const BORDER: usize = 4;
+const DEFAULT_SIZE: u32 = 256;
+const MIN_SIZE: u32 = 64;
+const MAX_SIZE: u32 = 4096;
+
+/// How much of a code can be damaged or covered and still scan.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum QrEcc {
+    /// About 7%.
+    Low,
+    /// About 15%.
+    #[default]
+    Medium,
+    /// About 25%.
+    Quartile,
+    /// About 30%.
+    High,
+}
+
+impl QrEcc {
+    fn level(self) -> EcLevel {
+        match self {
+            Self::Low => EcLevel::L,
+            Self::Medium => EcLevel::M,
+            Self::Quartile => EcLevel::Q,
+            Self::High => EcLevel::H,
+        }
+    }
+}
+
+/// A square grid of dark and light modules.
+#[derive(Debug, Clone)]
+pub struct QrCode {
+    size: usize,
+    modules: Vec<bool>,
+}
+
+impl QrCode {
+    /// Encode `data` at the smallest version that holds it.
+    pub fn encode(data: &[u8], ecc: QrEcc) -> AppResult<Self> {
+        let code = qrcode::QrCode::with_error_correction_level(data, ecc.level()).map_err(|e| {
+            AppError::InvalidInput(match e {
+                QrError::DataTooLong => format!(
+                    "{} bytes is too much for a QR code at this error correction level",
+                    data.len()
+                ),
+                e => format!("cannot encode a QR code: {e}"),
+            })
+        })?;
+        Ok(Self {
+            size: code.width(),
+            modules: code
+                .into_colors()
+                .into_iter()
+                .map(|color| color == Color::Dark)
+                .collect(),
+        })
+    }
+
+    /// Whether the module at column `x`, row `y` is dark; outside the grid is light.
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < self.size && y < self.size && self.modules[y * self.size + x]
+    }
+
+    /// An SVG with one unit per module and `border` light modules of quiet zone around it.
+    pub fn to_svg(&self, border: usize) -> String {
+        let side = self.size + border * 2;
+        let mut path = String::new();
+        for y in 0..self.size {
+            for x in 0..self.size {
+                if self.get(x, y) {
+                    let _ = write!(path, "M{},{}h1v1h-1z", x + border, y + border);
+                }
+            }
+        }
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {side} {side}\" \
+             shape-rendering=\"crispEdges\"><rect width=\"100%\" height=\"100%\" fill=\"#fff\"/>\
+             <path d=\"{path}\" fill=\"#000\"/></svg>"
+        )
+    }
+
+    /// A PNG `size` pixels square, with whole-pixel modules centred in the quiet zone.
+    pub fn to_png(&self, size: u32, border: usize) -> AppResult<Vec<u8>> {
+        let side = (self.size + border * 2) as u32;
+        let scale = (size / side).max(1);
+        let size = size.max(side * scale);
+        let offset = (size - side * scale) / 2 + border as u32 * scale;
+        let image = GrayImage::from_fn(size, size, |px, py| {
+            let dark = px >= offset
+                && py >= offset
+                && self.get(
+                    ((px - offset) / scale) as usize,
+                    ((py - offset) / scale) as usize,
+                );
+            Luma([if dark { 0 } else { 255 }])
+        });
+        let mut bytes = Cursor::new(Vec::new());
+        image
+            .write_to(&mut bytes, ImageFormat::Png)
+            .map_err(|e| AppError::Internal(format!("cannot encode QR code: {e}")))?;
+        Ok(bytes.into_inner())
+    }
+}
+
+/// Image format for [`generate_qr`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum QrFormat {
+    #[default]
+    Svg,
+    Png,
+}
+
+/// A rendered QR code.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QrImage {
+    pub format: QrFormat,
+    /// Pixel width and height; SVGs are scalable and carry it as their display size.
+    pub size: u32,
+    /// Modules per side, not counting the quiet zone.
+    pub modules: usize,
+    /// A `data:` URL usable directly as an `<img>` source or in report HTML.
+    pub data_url: String,
+}
+
+/// Render `data` as a QR code `size` pixels square, 256 by default.
+#[tauri::command]
+pub async fn generate_qr(
+    data: String,
+    size: Option<u32>,
+    format: Option<QrFormat>,
+    ecc: Option<QrEcc>,
+) -> AppResult<QrImage> {
+    if data.is_empty() {
+        return Err(AppError::InvalidInput("nothing to encode".into()));
+    }
+    let size = size.unwrap_or(DEFAULT_SIZE).clamp(MIN_SIZE, MAX_SIZE);
+    let format = format.unwrap_or_default();
+    tauri::async_runtime::spawn_blocking(move || {
+        let code = QrCode::encode(data.as_bytes(), ecc.unwrap_or_default())?;
+        // Never fewer pixels than modules, so large codes stay scannable at small sizes
+        let size = size.max((code.size + BORDER * 2) as u32);
+        let data_url = match format {
+            QrFormat::Svg => {
+                let svg = code.to_svg(BORDER).replacen(
+                    "<svg ",
+                    &format!("<svg width=\"{size}\" height=\"{size}\" "),
+                    1,
+                );
+                format!("data:image/svg+xml;base64,{}", STANDARD.encode(svg))
+            }
+            QrFormat::Png => {
+                let png = code.to_png(size, BORDER)?;
+                format!("data:image/png;base64,{}", STANDARD.encode(png))
+            }
+        };
+        Ok(QrImage {
+            format,
+            size,
+            modules: code.size,
+            data_url,
+        })
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Read back by an independent decoder, so the grid is checked against the standard
+    // rather than against this module
+    fn decode(code: &QrCode) -> Vec<u8> {
+        let grid = rqrr::SimpleGrid::from_func(code.size, |x, y| code.get(x, y));
+        let mut data = Vec::new();
+        rqrr::Grid::new(grid).decode_to(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn every_level_decodes_what_it_encodes() {
+        let long = "https://example.com/materials/1234567890?lot=ABCDEF&qty=44".repeat(8);
+        let binary: Vec<u8> = (0..=255).collect();
+        let payloads: [&[u8]; 4] = [
+            b"HELLO WORLD",
+            "Béton C25/30 ✓".as_bytes(),
+            &binary,
+            long.as_bytes(),
+        ];
+        for ecc in [QrEcc::Low, QrEcc::Medium, QrEcc::Quartile, QrEcc::High] {
+            for data in payloads {
+                let code = QrCode::encode(data, ecc).unwrap();
+                assert_eq!(decode(&code), data, "{ecc:?}, {} bytes", data.len());
+            }
+        }
+    }
+
+    #[test]
+    fn too_much_data_is_refused() {
+        let err = QrCode::encode(&[b'x'; 3000], QrEcc::High).err().unwrap();
+        assert!(err.to_string().contains("too much"));
+    }
+}
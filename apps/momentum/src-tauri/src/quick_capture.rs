@@ -0,0 +1,130 @@
+//! Small always-on-top "quick add" window for capturing a task or note without switching to
+//! the main window.
+//!
+//! The window is opened from the `quickCapture` global shortcut or the tray, next to the
+//! cursor on whichever monitor it is on, and hides again once it loses focus or the capture
+//! is saved. It is built once and reused so it appears instantly after the first time. The
+//! page saves through the same commands the main window uses, then calls
+//! `quick_capture_done` so the main window can refresh.
+
+use serde_json::Value;
+use tauri::{
+    AppHandle, Emitter, Manager, PhysicalPosition, WebviewUrl, WebviewWindow, WebviewWindowBuilder,
+    WindowEvent,
+};
+
+use crate::error::AppResult;
+use crate::windows::MAIN_WINDOW;
+
+/// Label of the quick-capture window.
+pub const QUICK_CAPTURE_WINDOW: &str = "quick-capture";
+/// Shortcut action that toggles the window.
+pub const SHORTCUT_ACTION: &str = "quickCapture";
+/// Emitted to the main window with whatever the capture saved.
+pub const CAPTURED_EVENT: &str = "quick-capture:captured";
+/// Emitted to the quick-capture window each time it is shown, so it can clear and focus.
+pub const SHOWN_EVENT: &str = "quick-capture:shown";
+
+const ROUTE: &str = "/quick-capture";
+const WIDTH: f64 = 440.0;
+const HEIGHT: f64 = 180.0;
+// Distance from the cursor, so the window does not open under the pointer
+const CURSOR_OFFSET: f64 = 12.0;
+
+fn build(app: &AppHandle) -> AppResult<WebviewWindow> {
+    let builder =
+        WebviewWindowBuilder::new(app, QUICK_CAPTURE_WINDOW, WebviewUrl::App(ROUTE.into()));
+    let window = crate::portable::webview(builder)
+        .title("Quick Add")
+        .inner_size(WIDTH, HEIGHT)
+        .resizable(false)
+        .decorations(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .visible(false)
+        .build()?;
+    let handle = window.clone();
+    window.on_window_event(move |event| {
+        // Clicking anywhere else dismisses it, like a popover
+        if let WindowEvent::Focused(false) = event {
+            let _ = handle.hide();
+        }
+    });
+    Ok(window)
+}
+
+// Below and right of the cursor, flipped or clamped so the whole window stays on its monitor
+fn place(app: &AppHandle, window: &WebviewWindow) -> AppResult<()> {
+    let cursor = app.cursor_position()?;
+    let Some(monitor) = app.monitor_from_point(cursor.x, cursor.y)? else {
+        return Ok(window.center()?);
+    };
+    let scale = monitor.scale_factor();
+    let area = monitor.work_area();
+    let (width, height) = (WIDTH * scale, HEIGHT * scale);
+    let offset = CURSOR_OFFSET * scale;
+    let (left, top) = (f64::from(area.position.x), f64::from(area.position.y));
+    let right = left + f64::from(area.size.width);
+    let bottom = top + f64::from(area.size.height);
+
+    let mut x = cursor.x + offset;
+    if x + width > right {
+        x = cursor.x - offset - width;
+    }
+    let mut y = cursor.y + offset;
+    // Trays sit at the bottom on Windows and most Linux panels, so open upwards there
+    if y + height > bottom {
+        y = cursor.y - offset - height;
+    }
+    let x = x.clamp(left, (right - width).max(left));
+    let y = y.clamp(top, (bottom - height).max(top));
+    window.set_position(PhysicalPosition::new(x.round() as i32, y.round() as i32))?;
+    Ok(())
+}
+
+/// Show the window by the cursor.
+pub fn show(app: &AppHandle) -> AppResult<()> {
+    let window = match app.get_webview_window(QUICK_CAPTURE_WINDOW) {
+        Some(window) => window,
+        None => build(app)?,
+    };
+    place(app, &window)?;
+    window.show()?;
+    window.set_focus()?;
+    let _ = window.emit(SHOWN_EVENT, ());
+    Ok(())
+}
+
+/// Show the window, or hide it if it is already up, as the shortcut does.
+pub fn toggle(app: &AppHandle) {
+    let visible = app
+        .get_webview_window(QUICK_CAPTURE_WINDOW)
+        .is_some_and(|window| window.is_visible().unwrap_or(false));
+    let result = if visible { hide(app) } else { show(app) };
+    if let Err(err) = result {
+        tracing::warn!(%err, "cannot toggle quick capture");
+    }
+}
+
+fn hide(app: &AppHandle) -> AppResult<()> {
+    if let Some(window) = app.get_webview_window(QUICK_CAPTURE_WINDOW) {
+        window.hide()?;
+    }
+    Ok(())
+}
+
+/// Hide the window and pass what was saved on to the main window.
+#[tauri::command]
+pub async fn quick_capture_done(app: AppHandle, captured: Option<Value>) -> AppResult<()> {
+    hide(&app)?;
+    if let Some(captured) = captured {
+        let _ = app.emit_to(MAIN_WINDOW, CAPTURED_EVENT, captured);
+    }
+    Ok(())
+}
+
+/// Hide the window without saving.
+#[tauri::command]
+pub async fn quick_capture_dismiss(app: AppHandle) -> AppResult<()> {
+    hide(&app)
+}
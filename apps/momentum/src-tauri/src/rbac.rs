@@ -0,0 +1,690 @@
+//! Role-based authorization for IPC commands, enforced before a command runs.
+//!
+//! The role comes from the `role` claim of the signed-in user's access token and is kept in
+//! [`AppState`]. It stays in effect after signing out or an expired session, so signing out
+//! never widens access. Only a machine that has never signed in runs without a role, with
+//! full access, as a standalone install. Every command is classified, and one that is not is
+//! refused for every role.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tauri::ipc::Invoke;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::error::{AppError, AppResult};
+use crate::secrets;
+use crate::state::AppState;
+use crate::windows::PRESENTATION_WINDOW;
+
+const ROLE_SECRET_KEY: &str = "auth.role";
+const USER_SECRET_KEY: &str = "auth.user";
+
+/// Emitted with a [`Permissions`] when the signed-in user's role changes.
+pub const ROLE_CHANGED_EVENT: &str = "auth:role-changed";
+
+/// What a user may do, as assigned by their organization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Role {
+    Viewer,
+    Estimator,
+    Admin,
+}
+
+/// A capability a command can require.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Permission {
+    /// Create and change projects, items, and attachments.
+    EditProjects,
+    /// Write project data out of the app: archives, spreadsheets, reports, and prints.
+    ExportData,
+    /// Change settings, security, updates, and background jobs for the whole install.
+    Administer,
+}
+
+const ALL_PERMISSIONS: [Permission; 3] = [
+    Permission::EditProjects,
+    Permission::ExportData,
+    Permission::Administer,
+];
+
+impl Role {
+    pub fn grants(self, permission: Permission) -> bool {
+        match self {
+            Self::Viewer => false,
+            Self::Estimator => permission != Permission::Administer,
+            Self::Admin => true,
+        }
+    }
+
+    // Unknown or missing claims get the least access rather than failing the sign-in
+    fn from_claim(claim: Option<&str>) -> Self {
+        match claim {
+            Some("admin") => Self::Admin,
+            Some("estimator") => Self::Estimator,
+            _ => Self::Viewer,
+        }
+    }
+}
+
+/// Commands that need more than read access.
+const COMMAND_PERMISSIONS: &[(&str, Permission)] = &[
+    ("import_project_archive", Permission::EditProjects),
+    ("attachment_add", Permission::EditProjects),
+    ("attachment_remove", Permission::EditProjects),
+    ("redownload_attachment", Permission::EditProjects),
+    ("edit_externally", Permission::EditProjects),
+    ("autosave_snapshot", Permission::EditProjects),
+    ("autosave_flush", Permission::EditProjects),
+    ("db_execute", Permission::EditProjects),
+    ("create_project", Permission::EditProjects),
+    ("update_project", Permission::EditProjects),
+    ("delete_project", Permission::EditProjects),
+    ("save_items", Permission::EditProjects),
+    ("delete_items", Permission::EditProjects),
+    ("set_cost_structure", Permission::EditProjects),
+    ("set_cost_adjustments", Permission::EditProjects),
+    ("undo", Permission::EditProjects),
+    ("redo", Permission::EditProjects),
+    ("set_drop_target_project", Permission::EditProjects),
+    ("import_csv", Permission::EditProjects),
+    ("import_xlsx", Permission::EditProjects),
+    ("import_estimate", Permission::EditProjects),
+    ("import_with_extension", Permission::EditProjects),
+    ("import_ifc", Permission::EditProjects),
+    ("delete_ifc_import", Permission::EditProjects),
+    ("import_shared_files", Permission::EditProjects),
+    ("capture_photo", Permission::EditProjects),
+    ("tag_location", Permission::EditProjects),
+    ("remove_location_tag", Permission::EditProjects),
+    ("calibrate_plan_page", Permission::EditProjects),
+    ("save_takeoff_measurement", Permission::EditProjects),
+    ("delete_takeoff_measurement", Permission::EditProjects),
+    ("create_estimate_version", Permission::EditProjects),
+    ("delete_estimate_version", Permission::EditProjects),
+    ("outbox_enqueue", Permission::EditProjects),
+    ("retry_outbox_operation", Permission::EditProjects),
+    ("cancel_outbox_operation", Permission::EditProjects),
+    ("project_save", Permission::EditProjects),
+    ("project_save_as", Permission::EditProjects),
+    ("sync_now", Permission::EditProjects),
+    ("upload_start", Permission::EditProjects),
+    ("upload_resume", Permission::EditProjects),
+    ("set_watch_target_project", Permission::EditProjects),
+    ("save_document_template", Permission::EditProjects),
+    ("delete_document_template", Permission::EditProjects),
+    ("start_lan_sync", Permission::EditProjects),
+    ("export_project_archive", Permission::ExportData),
+    ("start_native_drag", Permission::ExportData),
+    ("export_xlsx", Permission::ExportData),
+    ("export_dataset", Permission::ExportData),
+    ("stream_dataset", Permission::ExportData),
+    ("export_ics", Permission::ExportData),
+    ("add_to_calendar", Permission::ExportData),
+    ("pdf_merge", Permission::ExportData),
+    ("pdf_extract_pages", Permission::ExportData),
+    ("pdf_split", Permission::ExportData),
+    ("print", Permission::ExportData),
+    ("print_to_pdf", Permission::ExportData),
+    ("generate_report_pdf", Permission::ExportData),
+    ("generate_daily_log_pdf", Permission::ExportData),
+    ("generate_document_pdf", Permission::ExportData),
+    ("capture_view", Permission::ExportData),
+    ("run_external_tool", Permission::ExportData),
+    ("start_sharing", Permission::ExportData),
+    ("share_file", Permission::ExportData),
+    ("email_report", Permission::ExportData),
+    ("sign_document", Permission::ExportData),
+    ("send_project_to_peer", Permission::ExportData),
+    ("backup_now", Permission::ExportData),
+    ("download_choose_destination", Permission::ExportData),
+    ("download_enqueue", Permission::ExportData),
+    ("query_audit_log", Permission::Administer),
+    ("export_audit_log", Permission::Administer),
+    ("subscribe_logs", Permission::Administer),
+    ("set_config", Permission::Administer),
+    ("activate_license", Permission::Administer),
+    ("deactivate_license", Permission::Administer),
+    ("export_diagnostics", Permission::Administer),
+    ("set_encryption_passphrase", Permission::Administer),
+    ("schedule_job", Permission::Administer),
+    ("run_job_now", Permission::Administer),
+    ("pause_job", Permission::Administer),
+    ("resume_job", Permission::Administer),
+    ("ocr_install_pack", Permission::Administer),
+    ("ocr_remove_pack", Permission::Administer),
+    ("set_proxy_settings", Permission::Administer),
+    ("search_rebuild", Permission::Administer),
+    ("purge_http_cache", Permission::Administer),
+    ("set_global_shortcut", Permission::Administer),
+    ("clear_global_shortcut", Permission::Administer),
+    ("set_update_channel", Permission::Administer),
+    ("install_update", Permission::Administer),
+    ("rollback_update", Permission::Administer),
+    ("set_watched_folders", Permission::Administer),
+    ("install_extension", Permission::Administer),
+    ("set_extension_enabled", Permission::Administer),
+    ("remove_extension", Permission::Administer),
+    ("set_backup_settings", Permission::Administer),
+    ("set_email_settings", Permission::Administer),
+    ("set_background_policy", Permission::Administer),
+    ("restore_backup", Permission::Administer),
+    ("relocate_data_dir", Permission::Administer),
+    ("create_workspace", Permission::Administer),
+];
+
+/// Commands open to every role. A command in neither list is refused, so one added to the
+/// invoke handler cannot run until it is classified here.
+const OPEN_COMMANDS: &[&str] = &[
+    "greet",
+    "analyze_truss",
+    "list_attachments",
+    "attachment_path",
+    "attachment_open",
+    "autosave_discard",
+    "recover_unsaved_work",
+    "get_backup_settings",
+    "list_cloud_backups",
+    "list_local_backups",
+    "set_badge_count",
+    "get_badge",
+    "start_oauth_login",
+    "get_auth_status",
+    "sign_out",
+    "get_biometric_status",
+    "biometric_unlock",
+    "set_unlock_pin",
+    "unlock_with_pin",
+    "recalculate_estimate",
+    "get_cost_adjustments",
+    "sync_catalog",
+    "get_catalog_status",
+    "search_catalog",
+    "get_catalog_item",
+    "paste_from_clipboard",
+    "set_clipboard_watch",
+    "get_clipboard_watch",
+    "get_config",
+    "get_pending_crash_reports",
+    "upload_crash_reports",
+    "dismiss_crash_reports",
+    "db_query",
+    "debug_sql",
+    "debug_dump_state",
+    "get_schema_version",
+    "query_page",
+    "list_projects",
+    "get_project",
+    "list_items",
+    "deeplink_ready",
+    "frontend_ready",
+    "stream_ack",
+    "stream_cancel",
+    "get_system_info",
+    "download_pause",
+    "download_resume",
+    "download_cancel",
+    "list_downloads",
+    "dxf_import",
+    "get_email_settings",
+    "get_encryption_status",
+    "unlock_database",
+    "unlock_with_recovery_key",
+    "get_environment",
+    "cancel_dataset_export",
+    "take_pending_project_files",
+    "get_flags",
+    "refresh_flags",
+    "inspect_extension",
+    "list_extensions",
+    "run_extension_menu_item",
+    "list_external_edits",
+    "stop_editing_externally",
+    "list_external_tools",
+    "list_system_fonts",
+    "validate_font",
+    "validate_formula",
+    "evaluate_formula",
+    "evaluate_item_formula",
+    "cached_fetch",
+    "list_http_cache",
+    "api_request",
+    "list_ifc_imports",
+    "get_ifc_import",
+    "lock_session",
+    "get_session_locked",
+    "hash_file",
+    "verify_attachment",
+    "scan_attachment_integrity",
+    "preview_xlsx",
+    "detect_estimate_format",
+    "preview_estimate_import",
+    "list_jobs",
+    "stop_lan_sync",
+    "get_lan_sync_status",
+    "get_license_status",
+    "get_locale_info",
+    "get_current_location",
+    "get_location_tags",
+    "prefetch_project_map",
+    "get_weather",
+    "unsubscribe_logs",
+    "get_recent_logs",
+    "get_command_metrics",
+    "set_metrics_overlay",
+    "get_resource_usage",
+    "set_menu_item_enabled",
+    "get_connectivity",
+    "send_notification",
+    "ocr_list_packs",
+    "ocr_extract_text",
+    "list_outbox",
+    "pdf_page_count",
+    "pdf_metadata",
+    "pdf_render_page",
+    "pdf_stream_page",
+    "get_app_paths",
+    "get_power_status",
+    "get_background_status",
+    "list_printers",
+    "set_progress",
+    "project_open",
+    "project_close",
+    "get_proxy_settings",
+    "detect_system_proxy",
+    "enable_push_notifications",
+    "disable_push_notifications",
+    "get_push_status",
+    "generate_qr",
+    "quick_capture_done",
+    "quick_capture_dismiss",
+    "get_permissions",
+    "realtime_connect",
+    "realtime_disconnect",
+    "realtime_send",
+    "realtime_subscribe",
+    "realtime_unsubscribe",
+    "get_realtime_status",
+    "get_recent_projects",
+    "clear_recent_projects",
+    "reminder_create",
+    "reminder_update",
+    "reminder_delete",
+    "list_reminders",
+    "scan_code",
+    "search_index_document",
+    "search_query",
+    "secret_set",
+    "secret_get",
+    "secret_delete",
+    "respond_close_request",
+    "close_anyway",
+    "quit_app",
+    "stop_sharing",
+    "get_share_status",
+    "take_shared_files",
+    "discard_shared_files",
+    "verify_document",
+    "get_signing_identity",
+    "get_dictionary",
+    "add_dictionary_words",
+    "remove_dictionary_words",
+    "get_plan_calibration",
+    "measure_plan",
+    "list_takeoff_measurements",
+    "list_tasks",
+    "cancel_task",
+    "track_event",
+    "get_telemetry_status",
+    "set_telemetry_enabled",
+    "list_document_templates",
+    "validate_document_template",
+    "render_document_template",
+    "get_system_theme",
+    "get_thumbnail",
+    "timer_start",
+    "timer_pause",
+    "timer_resume",
+    "timer_stop",
+    "list_timers",
+    "take_timer_gaps",
+    "get_titlebar_info",
+    "window_minimize",
+    "window_maximize",
+    "window_unmaximize",
+    "window_toggle_maximize",
+    "window_close",
+    "get_launch_at_login",
+    "set_launch_at_login",
+    "set_compact_mode",
+    "get_compact_mode",
+    "parse_quantity",
+    "convert_quantity",
+    "list_units",
+    "board_feet",
+    "get_update_channel",
+    "check_for_updates",
+    "upload_pause",
+    "upload_cancel",
+    "list_uploads",
+    "get_sync_status",
+    "list_estimate_versions",
+    "get_estimate_version",
+    "diff_estimate_versions",
+    "get_undo_stack",
+    "acquire_wake_lock",
+    "release_wake_lock",
+    "list_wake_locks",
+    "get_watched_folders",
+    "open_secondary_window",
+    "close_window",
+    "list_monitors",
+    "move_window_to_monitor",
+    "enter_presentation_mode",
+    "exit_presentation_mode",
+    "get_presentation",
+    "get_window_effects_support",
+    "set_window_effect",
+    "set_window_corners",
+    "list_workspaces",
+    "switch_workspace",
+    "set_zoom",
+    "get_zoom",
+];
+
+/// All the presentation window may run: reads of the project on screen, and ending the
+/// presentation. Presenting is read-only for everyone, including the admin who started it.
+const PRESENTATION_COMMANDS: &[&str] = &[
+    "get_presentation",
+    "exit_presentation_mode",
+    "get_permissions",
+    "get_config",
+    "get_environment",
+    "get_flags",
+    "get_locale_info",
+    "get_system_theme",
+    "get_zoom",
+    "get_connectivity",
+    "get_session_locked",
+    "list_system_fonts",
+    "frontend_ready",
+    "deeplink_ready",
+    "stream_ack",
+    "stream_cancel",
+    "get_project",
+    "list_items",
+    "query_page",
+    "get_cost_adjustments",
+    "list_attachments",
+    "attachment_path",
+    "list_estimate_versions",
+    "get_estimate_version",
+    "diff_estimate_versions",
+    "get_plan_calibration",
+    "list_takeoff_measurements",
+    "get_location_tags",
+    "get_weather",
+    "pdf_page_count",
+    "pdf_metadata",
+    "pdf_render_page",
+    "pdf_stream_page",
+    "get_catalog_item",
+    "list_units",
+    "convert_quantity",
+];
+
+/// The permission `command` requires: `None` when it is open to every role, and an error when
+/// it has not been classified.
+pub fn required(command: &str) -> AppResult<Option<Permission>> {
+    if let Some(&(_, permission)) = COMMAND_PERMISSIONS
+        .iter()
+        .find(|(name, _)| *name == command)
+    {
+        return Ok(Some(permission));
+    }
+    if OPEN_COMMANDS.contains(&command) {
+        return Ok(None);
+    }
+    Err(AppError::Internal(format!("{command} has no access rule")))
+}
+
+/// Check `permission` against the current role.
+pub fn ensure(app: &AppHandle, permission: Permission) -> AppResult<()> {
+    match app.state::<AppState>().role() {
+        Some(role) if !role.grants(permission) => {
+            Err(AppError::PermissionDenied { permission, role })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Gate an invoke on its command's permission.
+///
+/// Returns the invoke to dispatch, or `None` after rejecting it with `PERMISSION_DENIED`,
+/// `READ_ONLY_WINDOW` for one the presentation window may not run, or `INTERNAL` for a command
+/// with no access rule.
+pub fn authorize(invoke: Invoke) -> Option<Invoke> {
+    let webview = invoke.message.webview();
+    let command = invoke.message.command();
+    let allowed = match required(command) {
+        Ok(_) if webview.label() == PRESENTATION_WINDOW => {
+            if PRESENTATION_COMMANDS.contains(&command) {
+                Ok(())
+            } else {
+                Err(AppError::ReadOnlyWindow {
+                    command: command.to_string(),
+                })
+            }
+        }
+        Ok(None) => return Some(invoke),
+        Ok(Some(permission)) => ensure(webview.app_handle(), permission),
+        Err(err) => Err(err),
+    };
+    match allowed {
+        Ok(()) => Some(invoke),
+        Err(err) => {
+            tracing::warn!(command = invoke.message.command(), %err, "command denied");
+            invoke.resolver.reject(err);
+            None
+        }
+    }
+}
+
+/// The role remembered from the last sign-in, if this machine has ever signed in.
+pub fn load_role() -> Option<Role> {
+    match secrets::get(ROLE_SECRET_KEY) {
+        Ok(raw) => raw.and_then(|raw| serde_json::from_str(&raw).ok()),
+        // Unreadable is not the same as never signed in
+        Err(err) => {
+            tracing::warn!(%err, "cannot read the stored role; restricting to viewer");
+            Some(Role::Viewer)
+        }
+    }
+}
+
+/// Who last signed in on this machine, for attributing changes.
+pub fn load_user() -> Option<String> {
+    secrets::get(USER_SECRET_KEY).unwrap_or_else(|err| {
+        tracing::warn!(%err, "cannot read the stored user");
+        None
+    })
+}
+
+#[derive(Default, Deserialize)]
+struct Claims {
+    role: Option<String>,
+    email: Option<String>,
+    sub: Option<String>,
+}
+
+// The server checks the signature on every request; here the claims only decide which
+// commands run and whose name goes on the audit log, so the payload is read unverified
+fn claims(access_token: &str) -> Claims {
+    access_token
+        .split('.')
+        .nth(1)
+        .and_then(|payload| URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok())
+        .and_then(|payload| serde_json::from_slice(&payload).ok())
+        .unwrap_or_default()
+}
+
+/// Take the user and role from a newly issued access token and remember them.
+pub fn update_identity(app: &AppHandle, access_token: &str) -> AppResult<()> {
+    let claims = claims(access_token);
+    let state = app.state::<AppState>();
+    if let Some(user) = claims.email.or(claims.sub) {
+        if state.user().as_deref() != Some(user.as_str()) {
+            secrets::set(USER_SECRET_KEY, &user)?;
+            state.set_user(Some(user));
+        }
+    }
+    let role = Role::from_claim(claims.role.as_deref());
+    if state.role() == Some(role) {
+        return Ok(());
+    }
+    secrets::set(ROLE_SECRET_KEY, &serde_json::to_string(&role)?)?;
+    state.set_role(Some(role));
+    tracing::info!(?role, "role changed");
+    let _ = app.emit(ROLE_CHANGED_EVENT, permissions(Some(role)));
+    Ok(())
+}
+
+/// The current role and what it grants, so the UI can match what commands will allow.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Permissions {
+    /// `None` on a machine that has never signed in.
+    pub role: Option<Role>,
+    pub granted: Vec<Permission>,
+}
+
+fn permissions(role: Option<Role>) -> Permissions {
+    let granted = ALL_PERMISSIONS
+        .into_iter()
+        .filter(|&permission| role.is_none_or(|role| role.grants(permission)))
+        .collect();
+    Permissions { role, granted }
+}
+
+#[tauri::command]
+pub async fn get_permissions(state: State<'_, AppState>) -> AppResult<Permissions> {
+    Ok(permissions(state.role()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The commands the invoke handler registers, read from its `generate_handler!` list
+    fn registered() -> Vec<&'static str> {
+        const OPENING: &str = "generate_handler![";
+        let lib = include_str!("lib.rs");
+        let start = lib.find(OPENING).expect("no invoke handler in lib.rs") + OPENING.len();
+        let end = start + lib[start..].find("];").expect("unterminated handler list");
+        lib[start..end]
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                line.trim_end_matches(',')
+                    .rsplit("::")
+                    .next()
+                    .unwrap_or(line)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_registered_command_has_an_access_rule() {
+        let commands = registered();
+        assert!(
+            commands.len() > 100,
+            "read only {} commands",
+            commands.len()
+        );
+        let unclassified: Vec<_> = commands
+            .into_iter()
+            .filter(|command| required(command).is_err())
+            .collect();
+        assert!(
+            unclassified.is_empty(),
+            "no access rule for {unclassified:?}"
+        );
+    }
+
+    #[test]
+    fn every_access_rule_names_a_registered_command() {
+        let commands = registered();
+        let stale: Vec<_> = COMMAND_PERMISSIONS
+            .iter()
+            .map(|&(name, _)| name)
+            .chain(OPEN_COMMANDS.iter().copied())
+            .filter(|name| !commands.contains(name))
+            .collect();
+        assert!(
+            stale.is_empty(),
+            "access rules for unknown commands {stale:?}"
+        );
+    }
+
+    #[test]
+    fn no_command_is_both_open_and_restricted() {
+        let both: Vec<_> = COMMAND_PERMISSIONS
+            .iter()
+            .map(|&(name, _)| name)
+            .filter(|name| OPEN_COMMANDS.contains(name))
+            .collect();
+        assert!(both.is_empty(), "open and restricted: {both:?}");
+    }
+
+    #[test]
+    fn the_presentation_window_only_reads() {
+        for command in PRESENTATION_COMMANDS {
+            assert_eq!(required(command).unwrap(), None, "{command} is not a read");
+        }
+        for command in [
+            "reminder_create",
+            "secret_set",
+            "switch_workspace",
+            "timer_start",
+        ] {
+            assert!(!PRESENTATION_COMMANDS.contains(&command), "{command}");
+        }
+    }
+
+    #[test]
+    fn the_lock_screen_commands_are_registered() {
+        let registered = registered();
+        for command in crate::idle_lock::WHILE_LOCKED {
+            assert!(registered.contains(command), "{command} is not registered");
+        }
+    }
+
+    #[test]
+    fn unclassified_commands_are_refused() {
+        assert!(required("no_such_command").is_err());
+        assert_eq!(required("get_permissions").unwrap(), None);
+        assert_eq!(
+            required("set_config").unwrap(),
+            Some(Permission::Administer)
+        );
+    }
+
+    #[test]
+    fn only_reads_reach_raw_sql_and_disk_writes_need_export() {
+        // `db_query` refuses anything but reads, so viewers may run it
+        assert_eq!(required("db_query").unwrap(), None);
+        assert_eq!(
+            required("db_execute").unwrap(),
+            Some(Permission::EditProjects)
+        );
+        assert_eq!(
+            required("download_enqueue").unwrap(),
+            Some(Permission::ExportData)
+        );
+        assert!(!Role::Viewer.grants(Permission::ExportData));
+        assert!(!PRESENTATION_COMMANDS.contains(&"download_enqueue"));
+    }
+}
@@ -0,0 +1,636 @@
+//! The realtime collaboration socket, owned by the backend so it outlives sleep, wake, and
+//! flaky networks instead of dying with a webview connection.
+//!
+//! `realtime_connect` opens a WebSocket (see [`socket`]) to an API path and keeps it open until
+//! `realtime_disconnect`. A dropped connection comes back with jittered exponential backoff,
+//! idle links are pinged so a silently dead one is noticed, and wall-clock time jumping ahead
+//! of the monotonic clock (the machine slept) reconnects at once. Messages sent while
+//! disconnected are buffered and flushed on reconnect, and topic subscriptions (see
+//! [`topics`]) replayed.
+//!
+//! Messages on the wire are JSON envelopes `{ "type": ..., "topic": ..., "data": ... }`.
+//! Incoming ones for a followed topic go to its windows; the rest reach the frontend as
+//! `realtime:message`. Every change of connection state is emitted as `realtime:status`.
+
+mod socket;
+pub mod topics;
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::{mpsc, watch, Notify};
+use url::Url;
+
+use crate::auth;
+use crate::config;
+use crate::db::now_ms;
+use crate::error::{AppError, AppResult};
+use crate::http_client::RetryPolicy;
+use crate::pinning::{self, PinSet};
+use crate::proxy::ProxyState;
+use crate::state::AppState;
+use socket::{ConnectError, Message, Reader, Target, Writer};
+use topics::Topics;
+
+/// Emitted with [`RealtimeStatus`] whenever the connection changes state.
+pub const STATUS_EVENT: &str = "realtime:status";
+/// Emitted with a [`RealtimeMessage`] for every server message not routed to a topic.
+pub const MESSAGE_EVENT: &str = "realtime:message";
+
+const SUBSCRIBE: &str = "subscribe";
+const UNSUBSCRIBE: &str = "unsubscribe";
+const HEARTBEAT_CHECK: Duration = Duration::from_secs(5);
+// Pings go out only after this long without hearing anything
+const PING_INTERVAL: Duration = Duration::from_secs(20);
+const PONG_TIMEOUT: Duration = Duration::from_secs(10);
+// Wall-clock time running this far ahead of the monotonic clock means the machine slept
+const SLEEP_GAP: Duration = Duration::from_secs(10);
+// A connection that stays up this long resets the backoff
+const STABLE_AFTER: Duration = Duration::from_secs(30);
+const BACKOFF: RetryPolicy = RetryPolicy {
+    max_attempts: u32::MAX,
+    base_delay: Duration::from_millis(500),
+    max_delay: Duration::from_secs(30),
+};
+const MAX_BUFFERED: usize = 1000;
+const MAX_BUFFERED_BYTES: usize = 4 * 1024 * 1024;
+const INCOMING_QUEUE: usize = 64;
+
+/// Where the connection stands.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConnectionState {
+    /// Not asked to connect, or disconnected on request.
+    #[default]
+    Idle,
+    Connecting,
+    Connected,
+    /// Waiting out the backoff after a dropped connection or failed attempt.
+    Reconnecting,
+    /// Stopped trying, e.g. on a pin mismatch or a path the server does not know.
+    Failed,
+}
+
+/// A snapshot of the realtime connection.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RealtimeStatus {
+    pub state: ConnectionState,
+    /// The API path connected to; `None` while idle.
+    pub path: Option<String>,
+    /// Topics any window follows, subscribed whenever connected.
+    pub topics: Vec<String>,
+    /// Failed attempts since the last stable connection.
+    pub attempt: u32,
+    pub connected_since: Option<i64>,
+    /// When the next attempt is due while reconnecting, Unix ms.
+    pub next_retry_at: Option<i64>,
+    /// Messages waiting for the connection to come back.
+    pub buffered: usize,
+    pub last_error: Option<String>,
+}
+
+/// A message from the server.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RealtimeMessage {
+    /// The envelope's `type`, when it had one.
+    pub kind: Option<String>,
+    pub topic: Option<String>,
+    /// The envelope's `data`; the whole message when it was not an envelope, or base64 for
+    /// a binary message.
+    pub data: Value,
+    pub binary: bool,
+    pub received_at: i64,
+}
+
+struct Session {
+    path: String,
+    outbox: VecDeque<String>,
+    outbox_bytes: usize,
+    stop: watch::Sender<bool>,
+}
+
+#[derive(Default)]
+struct Inner {
+    status: RealtimeStatus,
+    session: Option<Session>,
+    // Outlives sessions, so windows can subscribe before connecting and across reconnects
+    topics: Topics,
+    // Bumped per session so a replaced connection's task cannot touch the new one's status
+    generation: u64,
+}
+
+/// The realtime connection, registered in app state.
+#[derive(Default)]
+pub struct Realtime {
+    inner: Mutex<Inner>,
+    queued: Notify,
+    wake: Notify,
+}
+
+impl Realtime {
+    pub fn status(&self) -> RealtimeStatus {
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .status
+            .clone()
+    }
+
+    /// Skip any backoff wait and reconnect now, or check a live connection with a ping.
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Close the connection and stop reconnecting.
+    pub fn disconnect(&self, app: &AppHandle) {
+        let status = {
+            let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+            let Some(session) = inner.session.take() else {
+                return;
+            };
+            let _ = session.stop.send(true);
+            inner.generation += 1;
+            inner.status = RealtimeStatus {
+                topics: inner.topics.names(),
+                ..Default::default()
+            };
+            inner.status.clone()
+        };
+        let _ = app.emit(STATUS_EVENT, &status);
+    }
+
+    // Applies `change` if `generation` is still the current session, then emits the status
+    fn update(
+        &self,
+        app: &AppHandle,
+        generation: u64,
+        change: impl FnOnce(&mut RealtimeStatus),
+    ) -> bool {
+        let status = {
+            let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+            if inner.generation != generation {
+                return false;
+            }
+            change(&mut inner.status);
+            inner.status.topics = inner.topics.names();
+            inner.status.buffered = inner.session.as_ref().map_or(0, |s| s.outbox.len());
+            inner.status.clone()
+        };
+        let _ = app.emit(STATUS_EVENT, &status);
+        true
+    }
+
+    fn next_outgoing(&self, generation: u64) -> Option<String> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if inner.generation != generation {
+            return None;
+        }
+        inner.session.as_ref()?.outbox.front().cloned()
+    }
+
+    fn sent(&self, generation: u64) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if inner.generation != generation {
+            return;
+        }
+        if let Some(session) = inner.session.as_mut() {
+            if let Some(text) = session.outbox.pop_front() {
+                session.outbox_bytes -= text.len();
+            }
+        }
+    }
+
+    // Queues `text` for the current session; returns whether it is waiting on a connection
+    fn enqueue(&self, text: String) -> AppResult<bool> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let connected = inner.status.state == ConnectionState::Connected;
+        let session = inner.session.as_mut().ok_or_else(|| {
+            AppError::Realtime("not connected; call realtime_connect first".into())
+        })?;
+        if session.outbox.len() >= MAX_BUFFERED
+            || session.outbox_bytes + text.len() > MAX_BUFFERED_BYTES
+        {
+            return Err(AppError::Realtime(
+                "too many messages are waiting for the connection".into(),
+            ));
+        }
+        session.outbox_bytes += text.len();
+        session.outbox.push_back(text);
+        drop(inner);
+        self.queued.notify_one();
+        Ok(!connected)
+    }
+}
+
+fn envelope(kind: &str, topic: Option<&str>, data: Value) -> String {
+    let mut envelope = json!({ "type": kind, "data": data });
+    if let Some(topic) = topic {
+        envelope["topic"] = topic.into();
+    }
+    envelope.to_string()
+}
+
+fn websocket_url(app: &AppHandle, path: &str) -> AppResult<Url> {
+    let mut url = config::api_url(app, path)?;
+    let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+    url.set_scheme(scheme)
+        .map_err(|_| AppError::InvalidInput(format!("cannot open a WebSocket to {url}")))?;
+    Ok(url)
+}
+
+async fn open(
+    app: &AppHandle,
+    url: &Url,
+    token: Option<&str>,
+) -> Result<(Reader, Writer), ConnectError> {
+    let config = app.state::<AppState>().config();
+    let tls = PinSet::load(&config.tls_pins)
+        .and_then(pinning::tls_config)
+        .map_err(ConnectError::Permanent)?;
+    let proxy = app.state::<ProxyState>().current();
+    socket::connect(Target {
+        url,
+        token,
+        tls: tls.into(),
+        proxy: proxy.tunnel(url.host_str().unwrap_or_default(), url.scheme() == "wss"),
+    })
+    .await
+}
+
+enum Ended {
+    Stopped,
+    Slept,
+    Dropped(String),
+}
+
+// Keeps one session connected until it is stopped or replaced
+async fn run(app: AppHandle, generation: u64, url: Url, mut stop: watch::Receiver<bool>) {
+    let realtime = app.state::<Realtime>();
+    let path = url.path().to_string();
+    let mut attempt = 0u32;
+    let mut refreshed = false;
+    loop {
+        if *stop.borrow() {
+            return;
+        }
+        let token = auth::access_token(&app).await.ok().flatten();
+        let error = match open(&app, &url, token.as_deref()).await {
+            Ok((reader, writer)) => {
+                refreshed = false;
+                let connected_at = Instant::now();
+                if !realtime.update(&app, generation, |status| {
+                    status.state = ConnectionState::Connected;
+                    status.connected_since = Some(now_ms());
+                    status.next_retry_at = None;
+                    status.last_error = None;
+                }) {
+                    return;
+                }
+                tracing::info!(%path, "realtime connected");
+                let ended = serve(&app, generation, reader, writer, &mut stop).await;
+                if connected_at.elapsed() >= STABLE_AFTER {
+                    attempt = 0;
+                }
+                match ended {
+                    Ended::Stopped => return,
+                    Ended::Slept => {
+                        tracing::info!(%path, "resumed from sleep; reconnecting realtime");
+                        attempt = 0;
+                        realtime.update(&app, generation, |status| {
+                            status.state = ConnectionState::Connecting;
+                            status.connected_since = None;
+                        });
+                        continue;
+                    }
+                    Ended::Dropped(reason) => reason,
+                }
+            }
+            Err(ConnectError::Unauthorized) => match token {
+                // Revoked or expired early; one prompt retry with a fresh token
+                Some(token) if !refreshed => {
+                    refreshed = true;
+                    match auth::refresh_rejected(&app, &token).await {
+                        Ok(_) => continue,
+                        Err(err) => err.to_string(),
+                    }
+                }
+                _ => "the server rejected the session".into(),
+            },
+            Err(ConnectError::Permanent(err)) => {
+                tracing::warn!(%path, %err, "realtime connection failed; not retrying");
+                realtime.update(&app, generation, |status| {
+                    status.state = ConnectionState::Failed;
+                    status.connected_since = None;
+                    status.next_retry_at = None;
+                    status.last_error = Some(err.to_string());
+                });
+                return;
+            }
+            Err(ConnectError::Transient(err)) => err.to_string(),
+        };
+
+        attempt = attempt.saturating_add(1);
+        let delay = BACKOFF.delay(attempt);
+        tracing::debug!(%path, attempt, ?delay, %error, "realtime reconnecting");
+        if !realtime.update(&app, generation, |status| {
+            status.state = ConnectionState::Reconnecting;
+            status.attempt = attempt;
+            status.connected_since = None;
+            status.next_retry_at = Some(now_ms() + delay.as_millis() as i64);
+            status.last_error = Some(error);
+        }) {
+            return;
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = realtime.wake.notified() => {}
+            _ = stop.changed() => return,
+        }
+        realtime.update(&app, generation, |status| {
+            status.state = ConnectionState::Connecting;
+            status.next_retry_at = None;
+        });
+    }
+}
+
+// Frames are read on their own task because a half-read frame cannot be abandoned
+async fn serve(
+    app: &AppHandle,
+    generation: u64,
+    mut reader: Reader,
+    writer: Writer,
+    stop: &mut watch::Receiver<bool>,
+) -> Ended {
+    let (tx, incoming) = mpsc::channel(INCOMING_QUEUE);
+    let reading = tauri::async_runtime::spawn(async move {
+        loop {
+            let message = reader.next().await;
+            let failed = message.is_err();
+            if tx.send(message).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+    let ended = exchange(app, generation, incoming, writer, stop).await;
+    reading.abort();
+    ended
+}
+
+async fn exchange(
+    app: &AppHandle,
+    generation: u64,
+    mut incoming: mpsc::Receiver<AppResult<Message>>,
+    mut writer: Writer,
+    stop: &mut watch::Receiver<bool>,
+) -> Ended {
+    let realtime = app.state::<Realtime>();
+    // Subscriptions first, so buffered messages land on a server that knows what we follow
+    let topics = realtime
+        .inner
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .topics
+        .names();
+    for topic in topics {
+        let subscribe = topics::subscribe_message(&topic);
+        if let Err(err) = writer.send(Message::Text(subscribe)).await {
+            return Ended::Dropped(err.to_string());
+        }
+    }
+    if let Err(err) = flush(app, generation, &mut writer).await {
+        return Ended::Dropped(err.to_string());
+    }
+
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_CHECK);
+    heartbeat.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut clock = (Instant::now(), SystemTime::now());
+    let mut last_heard = Instant::now();
+    let mut ping_sent: Option<Instant> = None;
+    loop {
+        tokio::select! {
+            message = incoming.recv() => {
+                last_heard = Instant::now();
+                ping_sent = None;
+                let sent: AppResult<()> = match message {
+                    // tungstenite answers pings on its own
+                    Some(Ok(Message::Ping(_) | Message::Pong(_))) => Ok(()),
+                    Some(Ok(Message::Text(text))) => {
+                        deliver(app, text);
+                        Ok(())
+                    }
+                    Some(Ok(Message::Binary(data))) => {
+                        let message = RealtimeMessage {
+                            kind: None,
+                            topic: None,
+                            data: Value::String(STANDARD.encode(data)),
+                            binary: true,
+                            received_at: now_ms(),
+                        };
+                        let _ = app.emit(MESSAGE_EVENT, &message);
+                        Ok(())
+                    }
+                    Some(Ok(Message::Close(code, reason))) => {
+                        let _ = writer.send(Message::Close(code.map(|_| socket::CLOSE_NORMAL), String::new())).await;
+                        return Ended::Dropped(match code {
+                            Some(code) if reason.is_empty() => format!("closed by the server ({code})"),
+                            Some(code) => format!("closed by the server ({code}): {reason}"),
+                            None => "closed by the server".into(),
+                        });
+                    }
+                    Some(Err(err)) => {
+                        if matches!(err, AppError::Realtime(_)) {
+                            let _ = writer.send(Message::Close(Some(socket::CLOSE_PROTOCOL_ERROR), String::new())).await;
+                        }
+                        return Ended::Dropped(err.to_string());
+                    }
+                    None => return Ended::Dropped("connection lost".into()),
+                };
+                if let Err(err) = sent {
+                    return Ended::Dropped(err.to_string());
+                }
+            }
+            _ = realtime.queued.notified() => {
+                if let Err(err) = flush(app, generation, &mut writer).await {
+                    return Ended::Dropped(err.to_string());
+                }
+            }
+            _ = realtime.wake.notified() => {
+                if ping_sent.is_none() {
+                    if let Err(err) = writer.send(Message::Ping(Vec::new())).await {
+                        return Ended::Dropped(err.to_string());
+                    }
+                    ping_sent = Some(Instant::now());
+                }
+            }
+            _ = heartbeat.tick() => {
+                let now = (Instant::now(), SystemTime::now());
+                let wall = now.1.duration_since(clock.1).unwrap_or_default();
+                let slept = wall.saturating_sub(now.0 - clock.0) > SLEEP_GAP;
+                clock = now;
+                // The monotonic clock stops while asleep, and the link rarely survives it
+                if slept {
+                    return Ended::Slept;
+                }
+                if ping_sent.is_some_and(|at| at.elapsed() >= PONG_TIMEOUT) {
+                    return Ended::Dropped("heartbeat timed out".into());
+                }
+                if ping_sent.is_none() && last_heard.elapsed() >= PING_INTERVAL {
+                    if let Err(err) = writer.send(Message::Ping(Vec::new())).await {
+                        return Ended::Dropped(err.to_string());
+                    }
+                    ping_sent = Some(Instant::now());
+                }
+            }
+            _ = stop.changed() => {
+                let _ = writer.send(Message::Close(Some(socket::CLOSE_NORMAL), String::new())).await;
+                return Ended::Stopped;
+            }
+        }
+    }
+}
+
+// Sends buffered messages oldest first; each leaves the buffer only once written
+async fn flush(app: &AppHandle, generation: u64, writer: &mut Writer) -> AppResult<()> {
+    let realtime = app.state::<Realtime>();
+    let mut flushed = false;
+    while let Some(text) = realtime.next_outgoing(generation) {
+        writer.send(Message::Text(text)).await?;
+        realtime.sent(generation);
+        flushed = true;
+    }
+    if flushed {
+        realtime.update(app, generation, |_| {});
+    }
+    Ok(())
+}
+
+fn deliver(app: &AppHandle, text: String) {
+    let mut message = RealtimeMessage {
+        kind: None,
+        topic: None,
+        data: Value::Null,
+        binary: false,
+        received_at: now_ms(),
+    };
+    let field = |object: &serde_json::Map<String, Value>, key: &str| {
+        object.get(key).and_then(Value::as_str).map(str::to_string)
+    };
+    message.data = match serde_json::from_str::<Value>(&text) {
+        Ok(Value::Object(mut object)) => {
+            message.kind = field(&object, "type");
+            message.topic = field(&object, "topic");
+            match object.remove("data") {
+                Some(data) if message.kind.is_some() => data,
+                Some(data) => {
+                    object.insert("data".into(), data);
+                    Value::Object(object)
+                }
+                None => Value::Object(object),
+            }
+        }
+        Ok(value) => value,
+        Err(_) => Value::String(text),
+    };
+    let followers = message.topic.as_deref().map(|topic| {
+        app.state::<Realtime>()
+            .inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .topics
+            .followers(topic)
+    });
+    match (&message.topic, followers) {
+        (Some(topic), Some(windows)) if !windows.is_empty() => {
+            let event = topics::event_name(topic);
+            for window in windows {
+                let _ = app.emit_to(window.as_str(), &event, &message);
+            }
+        }
+        _ => {
+            let _ = app.emit(MESSAGE_EVENT, &message);
+        }
+    }
+}
+
+/// Open the realtime socket to an API path such as `/v1/realtime` and keep it open.
+///
+/// Calling it again for the same path is a no-op; another path replaces the connection,
+/// dropping anything still buffered for the old one.
+#[tauri::command]
+pub async fn realtime_connect(
+    app: AppHandle,
+    realtime: State<'_, Realtime>,
+    path: String,
+) -> AppResult<RealtimeStatus> {
+    let url = websocket_url(&app, &path)?;
+    let (stop_tx, stop_rx) = watch::channel(false);
+    let (generation, status) = {
+        let mut inner = realtime.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let same = inner.session.as_ref().is_some_and(|s| s.path == path)
+            && inner.status.state != ConnectionState::Failed;
+        if same {
+            return Ok(inner.status.clone());
+        }
+        if let Some(old) = inner.session.take() {
+            let _ = old.stop.send(true);
+        }
+        inner.generation += 1;
+        inner.status = RealtimeStatus {
+            state: ConnectionState::Connecting,
+            path: Some(path.clone()),
+            topics: inner.topics.names(),
+            ..Default::default()
+        };
+        inner.session = Some(Session {
+            path,
+            outbox: VecDeque::new(),
+            outbox_bytes: 0,
+            stop: stop_tx,
+        });
+        (inner.generation, inner.status.clone())
+    };
+    let _ = app.emit(STATUS_EVENT, &status);
+    tauri::async_runtime::spawn(run(app.clone(), generation, url, stop_rx));
+    Ok(status)
+}
+
+#[tauri::command]
+pub async fn realtime_disconnect(app: AppHandle, realtime: State<'_, Realtime>) -> AppResult<()> {
+    realtime.disconnect(&app);
+    Ok(())
+}
+
+/// Send `{ "type": kind, "topic": topic, "data": data }`, buffered while the link is down.
+#[tauri::command]
+pub async fn realtime_send(
+    app: AppHandle,
+    realtime: State<'_, Realtime>,
+    kind: String,
+    topic: Option<String>,
+    data: Option<Value>,
+) -> AppResult<()> {
+    let text = envelope(&kind, topic.as_deref(), data.unwrap_or(Value::Null));
+    if realtime.enqueue(text)? {
+        // The buffered count only changes visibly while disconnected
+        let generation = realtime
+            .inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .generation;
+        realtime.update(&app, generation, |_| {});
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_realtime_status(realtime: State<'_, Realtime>) -> AppResult<RealtimeStatus> {
+    Ok(realtime.status())
+}
@@ -0,0 +1,176 @@
+//! Topic subscriptions multiplexed over the one realtime connection.
+//!
+//! Any number of windows can follow a topic such as `project:123` or `pricing-updates`. The
+//! server hears one `subscribe` when the first window follows it and one `unsubscribe` when
+//! the last leaves or closes, and every followed topic is subscribed again after a reconnect.
+//! Server messages naming a `topic` go only to the windows following it, as
+//! `realtime:topic:<topic>` events.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_json::Value;
+use tauri::{AppHandle, Manager, State, Window, WindowEvent};
+
+use super::{envelope, ConnectionState, Realtime, SUBSCRIBE, UNSUBSCRIBE};
+use crate::error::{AppError, AppResult};
+
+/// Prefix of the per-topic event; the topic itself follows it.
+pub const TOPIC_EVENT_PREFIX: &str = "realtime:topic:";
+
+const MAX_TOPIC_LEN: usize = 128;
+
+/// The event a topic's messages arrive on.
+pub fn event_name(topic: &str) -> String {
+    format!("{TOPIC_EVENT_PREFIX}{topic}")
+}
+
+// A topic becomes part of an event name, which allows only these characters
+fn validate(topic: &str) -> AppResult<()> {
+    let valid = !topic.is_empty()
+        && topic.len() <= MAX_TOPIC_LEN
+        && topic
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '/'));
+    if !valid {
+        return Err(AppError::InvalidInput(format!(
+            "invalid topic {topic:?}; use letters, digits, and - _ : /"
+        )));
+    }
+    Ok(())
+}
+
+/// Which windows follow which topics.
+#[derive(Debug, Default)]
+pub(super) struct Topics {
+    followers: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Topics {
+    // True when nobody followed `topic` before
+    fn follow(&mut self, topic: &str, window: &str) -> bool {
+        let followers = self.followers.entry(topic.to_string()).or_default();
+        let first = followers.is_empty();
+        followers.insert(window.to_string());
+        first
+    }
+
+    // True when nobody follows `topic` any more
+    fn leave(&mut self, topic: &str, window: &str) -> bool {
+        let Some(followers) = self.followers.get_mut(topic) else {
+            return false;
+        };
+        if !followers.remove(window) || !followers.is_empty() {
+            return false;
+        }
+        self.followers.remove(topic);
+        true
+    }
+
+    // Topics nobody follows once `window` is gone
+    fn leave_all(&mut self, window: &str) -> Vec<String> {
+        let mut abandoned = Vec::new();
+        self.followers.retain(|topic, followers| {
+            followers.remove(window);
+            if followers.is_empty() {
+                abandoned.push(topic.clone());
+            }
+            !followers.is_empty()
+        });
+        abandoned
+    }
+
+    pub(super) fn names(&self) -> Vec<String> {
+        self.followers.keys().cloned().collect()
+    }
+
+    pub(super) fn followers(&self, topic: &str) -> Vec<String> {
+        self.followers
+            .get(topic)
+            .map(|windows| windows.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// The subscribe envelope for `topic`, sent on every connect.
+pub(super) fn subscribe_message(topic: &str) -> String {
+    envelope(SUBSCRIBE, Some(topic), Value::Null)
+}
+
+// Only a live connection is told; anything else learns on connect from the replay
+fn tell_server(realtime: &Realtime, app: &AppHandle, kind: &str, topics: &[String]) {
+    let (generation, connected) = {
+        let inner = realtime.inner.lock().unwrap_or_else(|e| e.into_inner());
+        (
+            inner.generation,
+            inner.status.state == ConnectionState::Connected,
+        )
+    };
+    if connected {
+        for topic in topics {
+            if let Err(err) = realtime.enqueue(envelope(kind, Some(topic), Value::Null)) {
+                // The connection has dropped or is backed up; the next connect replays anyway
+                tracing::debug!(%err, topic, kind, "could not queue subscription change");
+            }
+        }
+    }
+    realtime.update(app, generation, |_| {});
+}
+
+/// Follow `topic` from the calling window; its messages arrive as `realtime:topic:<topic>`.
+///
+/// Works before `realtime_connect`, in which case the topic is subscribed once connected.
+#[tauri::command]
+pub async fn realtime_subscribe(
+    app: AppHandle,
+    window: Window,
+    realtime: State<'_, Realtime>,
+    topic: String,
+) -> AppResult<()> {
+    validate(&topic)?;
+    let first = realtime
+        .inner
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .topics
+        .follow(&topic, window.label());
+    if first {
+        tell_server(&realtime, &app, SUBSCRIBE, &[topic]);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn realtime_unsubscribe(
+    app: AppHandle,
+    window: Window,
+    realtime: State<'_, Realtime>,
+    topic: String,
+) -> AppResult<()> {
+    let last = realtime
+        .inner
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .topics
+        .leave(&topic, window.label());
+    if last {
+        tell_server(&realtime, &app, UNSUBSCRIBE, &[topic]);
+    }
+    Ok(())
+}
+
+/// Drop a window's topics when it closes.
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    if !matches!(event, WindowEvent::Destroyed) {
+        return;
+    }
+    let realtime = window.state::<Realtime>();
+    let abandoned = realtime
+        .inner
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .topics
+        .leave_all(window.label());
+    if !abandoned.is_empty() {
+        tell_server(&realtime, window.app_handle(), UNSUBSCRIBE, &abandoned);
+    }
+}
@@ -0,0 +1,194 @@
+//! Recently opened project files, mirrored into the Windows jump list and macOS dock menu.
+//!
+//! Entries picked from the OS arrive like any double-clicked file: as launch arguments on
+//! Windows and as an `Opened` event on macOS, both handled by `file_open`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::now_ms;
+use crate::db::projects::Project;
+use crate::error::AppResult;
+
+const RECENT_FILE_NAME: &str = "recent-projects.json";
+const MAX_RECENT: usize = 10;
+
+/// A project file opened or saved recently.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentProject {
+    pub path: PathBuf,
+    pub project_id: String,
+    pub name: String,
+    pub opened_at: i64,
+    /// The file has since been moved or deleted.
+    #[serde(skip_deserializing)]
+    pub missing: bool,
+}
+
+/// Recent project list registered in app state.
+pub struct RecentProjects {
+    path: PathBuf,
+    entries: Mutex<Vec<RecentProject>>,
+}
+
+impl RecentProjects {
+    /// Load the list from `data_dir`; an unreadable list starts empty.
+    pub fn load(data_dir: &Path) -> Self {
+        let path = data_dir.join(RECENT_FILE_NAME);
+        let entries = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    fn save(&self, entries: &[RecentProject]) -> AppResult<()> {
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(entries)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Move a project file to the top of the recent list and tell the OS about it.
+pub fn record(app: &AppHandle, path: &Path, project: &Project) {
+    let recent = app.state::<RecentProjects>();
+    let mut entries = recent.entries.lock().unwrap_or_else(|e| e.into_inner());
+    entries.retain(|entry| entry.path != path);
+    entries.insert(
+        0,
+        RecentProject {
+            path: path.to_path_buf(),
+            project_id: project.id.clone(),
+            name: project.name.clone(),
+            opened_at: now_ms(),
+            missing: false,
+        },
+    );
+    entries.truncate(MAX_RECENT);
+    if let Err(err) = recent.save(&entries) {
+        tracing::warn!(%err, "cannot save recent projects");
+    }
+    os::add(app, path);
+}
+
+#[cfg(windows)]
+mod os {
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+
+    use tauri::AppHandle;
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED,
+    };
+    use windows::Win32::UI::Shell::{
+        ApplicationDestinations, IApplicationDestinations, SHAddToRecentDocs, SHARD_PATHW,
+    };
+
+    // The shell files it under the jump list's Recent category through the registered
+    // `.momentum` association
+    pub fn add(_app: &AppHandle, path: &Path) {
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        unsafe { SHAddToRecentDocs(SHARD_PATHW.0 as u32, Some(wide.as_ptr().cast())) };
+    }
+
+    pub fn clear(_app: &AppHandle) {
+        let result = unsafe {
+            // Fails harmlessly when the thread already joined an apartment
+            let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+            CoCreateInstance::<_, IApplicationDestinations>(
+                &ApplicationDestinations,
+                None,
+                CLSCTX_INPROC_SERVER,
+            )
+            .and_then(|destinations| destinations.RemoveAllDestinations())
+        };
+        if let Err(err) = result {
+            tracing::warn!(%err, "cannot clear the jump list");
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use std::path::Path;
+
+    use objc2::MainThreadMarker;
+    use objc2_app_kit::NSDocumentController;
+    use objc2_foundation::{NSString, NSURL};
+    use tauri::AppHandle;
+
+    // AppKit objects may only be touched on the main thread
+    fn on_main(app: &AppHandle, f: impl FnOnce(&NSDocumentController) + Send + 'static) {
+        let _ = app.run_on_main_thread(move || {
+            if let Some(mtm) = MainThreadMarker::new() {
+                f(&NSDocumentController::sharedDocumentController(mtm));
+            }
+        });
+    }
+
+    pub fn add(app: &AppHandle, path: &Path) {
+        let path = path.to_string_lossy().into_owned();
+        on_main(app, move |controller| {
+            let url = NSURL::fileURLWithPath(&NSString::from_str(&path));
+            controller.noteNewRecentDocumentURL(&url);
+        });
+    }
+
+    pub fn clear(app: &AppHandle) {
+        on_main(app, |controller| unsafe {
+            controller.clearRecentDocuments(None)
+        });
+    }
+}
+
+// Linux desktops have no per-app recent list to feed
+#[cfg(not(any(windows, target_os = "macos")))]
+mod os {
+    use std::path::Path;
+
+    use tauri::AppHandle;
+
+    pub fn add(_app: &AppHandle, _path: &Path) {}
+
+    pub fn clear(_app: &AppHandle) {}
+}
+
+/// Recently opened project files, newest first.
+#[tauri::command]
+pub async fn get_recent_projects(
+    recent: State<'_, RecentProjects>,
+) -> AppResult<Vec<RecentProject>> {
+    let mut entries = recent
+        .entries
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    for entry in &mut entries {
+        entry.missing = !entry.path.is_file();
+    }
+    Ok(entries)
+}
+
+/// Empty the recent list here and in the OS.
+#[tauri::command]
+pub async fn clear_recent_projects(
+    app: AppHandle,
+    recent: State<'_, RecentProjects>,
+) -> AppResult<()> {
+    {
+        let mut entries = recent.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.clear();
+        recent.save(&entries)?;
+    }
+    tauri::async_runtime::spawn_blocking(move || os::clear(&app)).await?;
+    Ok(())
+}
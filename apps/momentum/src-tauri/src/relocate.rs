@@ -0,0 +1,352 @@
+//! Moving the data dir to another folder, e.g. onto a larger secondary drive.
+//!
+//! Every file under the data dir is copied and read back against the SHA-256 of what was
+//! read from the original. The bulk goes across while the app keeps working; the database
+//! is then closed, files that changed meanwhile are copied again, and the database file is
+//! copied last. Only then does the workspace point at the new folder, and the app relaunches
+//! into it. The old folder is emptied on that next launch, once nothing holds its files
+//! open, and only of the files that were copied; when that fails, as on read-only media, it
+//! is left as it is with a note saying where the data went.
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::archive;
+use crate::autosave::Autosave;
+use crate::db::Db;
+use crate::encryption::Encryption;
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+use crate::workspaces::{self, Workspaces};
+use crate::{portable, progress, shutdown, store_migrations, wake_lock};
+
+/// Emitted with a [`RelocateProgress`] while files are copied.
+pub const PROGRESS_EVENT: &str = "data-dir:progress";
+
+const DB_FILE_NAME: &str = "momentum.db";
+// Written into the new folder; read and removed on the launch after the move
+const PENDING_CLEANUP_FILE_NAME: &str = "relocated-from.json";
+const LEFT_BEHIND_NOTE_FILE_NAME: &str = "MOVED.txt";
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelocateProgress {
+    /// `copy` for the first pass, `sync` for the one made with the database closed.
+    pub phase: &'static str,
+    pub done: u64,
+    pub total: u64,
+}
+
+/// What moved; the app relaunches from the new folder right after this is returned.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelocateReport {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingCleanup {
+    from: PathBuf,
+    /// Paths relative to `from`, exactly the files that were copied.
+    files: Vec<PathBuf>,
+}
+
+// Size and modification time, enough to tell a file changed between passes
+type Stamp = (u64, Option<SystemTime>);
+
+struct Running;
+
+impl Running {
+    fn start() -> AppResult<Self> {
+        if RUNNING.swap(true, Ordering::SeqCst) {
+            return Err(AppError::InvalidInput(
+                "the data folder is already being moved".into(),
+            ));
+        }
+        Ok(Self)
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+fn is_database(relative: &Path) -> bool {
+    relative
+        .to_str()
+        .and_then(|name| name.strip_prefix(DB_FILE_NAME))
+        .is_some_and(|rest| matches!(rest, "" | "-wal" | "-shm" | "-journal"))
+}
+
+// Regular files under `dir`; links are skipped so a move never follows one out of the folder
+fn walk(dir: &Path, relative: &Path, files: &mut HashMap<PathBuf, Stamp>) -> AppResult<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let kind = entry.file_type()?;
+        let relative = relative.join(entry.file_name());
+        if kind.is_dir() {
+            walk(&entry.path(), &relative, files)?;
+        } else if kind.is_file() {
+            let meta = entry.metadata()?;
+            files.insert(relative, (meta.len(), meta.modified().ok()));
+        }
+    }
+    Ok(())
+}
+
+// At the platform app data dir the default workspace shares its folder with files kept
+// for the whole install, which stay put
+fn stamps(dir: &Path, install_root: bool) -> AppResult<HashMap<PathBuf, Stamp>> {
+    let mut files = HashMap::new();
+    walk(dir, Path::new(""), &mut files)?;
+    if install_root {
+        files.retain(|relative, _| {
+            let top = relative.components().next();
+            !top.and_then(|top| top.as_os_str().to_str())
+                .is_some_and(|name| {
+                    workspaces::is_install_wide(name) || store_migrations::is_store_file(name)
+                })
+        });
+    }
+    Ok(files)
+}
+
+fn hash_file(path: &Path) -> AppResult<String> {
+    let (_, hash) = archive::copy_hashed(&mut BufReader::new(File::open(path)?), &mut io::sink())?;
+    Ok(hash)
+}
+
+// Copy one file, flush it to disk, and check what landed against what was read
+fn copy_verified(source: &Path, target: &Path) -> AppResult<u64> {
+    if let Some(dir) = target.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let input = File::open(source)?;
+    let modified = input.metadata()?.modified().ok();
+    let mut output = BufWriter::new(File::create(target)?);
+    let (size, hash) = archive::copy_hashed(&mut BufReader::new(input), &mut output)?;
+    let output = output.into_inner().map_err(|err| err.into_error())?;
+    if let Some(modified) = modified {
+        let _ = output.set_modified(modified);
+    }
+    output.sync_all()?;
+    drop(output);
+    if hash_file(target)? != hash {
+        return Err(AppError::Internal(format!(
+            "{} did not copy intact",
+            source.display()
+        )));
+    }
+    Ok(size)
+}
+
+// Copies every non-database file whose stamp differs from `copied`, records it there, and
+// drops copies of files deleted since; returns the bytes written
+fn sync(
+    app: &AppHandle,
+    phase: &'static str,
+    (from, install_root): (&Path, bool),
+    to: &Path,
+    copied: &mut HashMap<PathBuf, Stamp>,
+) -> AppResult<u64> {
+    let current = stamps(from, install_root)?;
+    let changed: Vec<_> = current
+        .iter()
+        .filter(|(relative, stamp)| !is_database(relative) && copied.get(*relative) != Some(stamp))
+        .collect();
+    let total = changed.iter().map(|(_, (size, _))| size).sum();
+    let taskbar = progress::track(app, "relocate".into());
+    let mut done = 0;
+    for (relative, stamp) in changed {
+        done += copy_verified(&from.join(relative), &to.join(relative))?;
+        copied.insert(relative.clone(), *stamp);
+        taskbar.set(done, Some(total));
+        let _ = app.emit(PROGRESS_EVENT, RelocateProgress { phase, done, total });
+    }
+    copied.retain(|relative, _| {
+        let keep = current.contains_key(relative);
+        if !keep {
+            let _ = fs::remove_file(to.join(relative));
+        }
+        keep
+    });
+    Ok(done)
+}
+
+// The target must be a new or empty folder that neither holds nor sits inside the data dir
+fn check_target(from: &Path, to: &Path) -> AppResult<PathBuf> {
+    if !to.is_absolute() {
+        return Err(AppError::InvalidInput(
+            "the new data folder must be an absolute path".into(),
+        ));
+    }
+    fs::create_dir_all(to)?;
+    let (from, to) = (fs::canonicalize(from)?, fs::canonicalize(to)?);
+    if to.starts_with(&from) || from.starts_with(&to) {
+        return Err(AppError::InvalidInput(
+            "the new data folder cannot contain or be inside the current one".into(),
+        ));
+    }
+    if fs::read_dir(&to)?.next().is_some() {
+        return Err(AppError::InvalidInput(
+            "the new data folder must be empty".into(),
+        ));
+    }
+    Ok(to)
+}
+
+/// Move the data dir of the running workspace to `new_path` and relaunch from there.
+///
+/// `new_path` must be empty or not exist yet. Unsaved edits are autosaved and carried over,
+/// so the relaunch does not wait on windows with unsaved changes.
+#[tauri::command]
+pub async fn relocate_data_dir(app: AppHandle, new_path: PathBuf) -> AppResult<RelocateReport> {
+    let _running = Running::start()?;
+    let encryption = app.state::<Encryption>();
+    let key = encryption.key_handle().get();
+    if encryption.is_enabled() && key.is_none() {
+        return Err(AppError::DatabaseLocked);
+    }
+    let from = app.state::<AppState>().data_dir().to_path_buf();
+    let to = check_target(&from, &new_path)?;
+    let _awake = wake_lock::hold(&app, "Moving the data folder");
+    tracing::info!(from = %from.display(), to = %to.display(), "moving the data folder");
+
+    let install_root = from == portable::app_data_dir(&app)?;
+    let handle = app.clone();
+    let (source, target) = (from.clone(), to.clone());
+    let moved = tauri::async_runtime::spawn_blocking(move || {
+        let mut copied = HashMap::new();
+        let mut bytes = sync(
+            &handle,
+            "copy",
+            (&source, install_root),
+            &target,
+            &mut copied,
+        )?;
+        // Edits still buffered in memory would otherwise be written after the last pass
+        handle.state::<Autosave>().flush_all();
+        let db = handle.state::<Db>().inner().clone();
+        db.close_with(key.as_deref(), |database| {
+            bytes += sync(
+                &handle,
+                "sync",
+                (&source, install_root),
+                &target,
+                &mut copied,
+            )?;
+            bytes += copy_verified(database, &target.join(DB_FILE_NAME))?;
+            let mut files: Vec<PathBuf> = copied.into_keys().collect();
+            // Closing the last connection at exit normally takes the sidecars with it
+            files.extend(
+                ["", "-wal", "-shm"].map(|suffix| PathBuf::from(format!("{DB_FILE_NAME}{suffix}"))),
+            );
+            let pending = PendingCleanup {
+                from: source.clone(),
+                files,
+            };
+            fs::write(
+                target.join(PENDING_CLEANUP_FILE_NAME),
+                serde_json::to_vec_pretty(&pending)?,
+            )?;
+            handle
+                .state::<Workspaces>()
+                .relocate(&handle, target.clone())?;
+            Ok(RelocateReport {
+                from: source.clone(),
+                to: target.clone(),
+                files: pending.files.len() as u64,
+                bytes,
+            })
+        })
+    })
+    .await?;
+
+    let report = match moved {
+        Ok(report) => report,
+        Err(err) => {
+            tracing::error!(%err, "moving the data folder failed; staying where it was");
+            // The folder was empty before, so all of it is this attempt's partial copy
+            let _ = fs::remove_dir_all(&to);
+            return Err(err);
+        }
+    };
+    tracing::info!(
+        files = report.files,
+        bytes = report.bytes,
+        "data folder moved; relaunching"
+    );
+    shutdown::restart(&app, true);
+    Ok(report)
+}
+
+// Remove each copied file, then any folder left empty, deepest first
+fn remove_copied(from: &Path, files: &[PathBuf]) -> io::Result<()> {
+    let mut first_error = None;
+    let mut dirs = HashSet::new();
+    for relative in files {
+        match fs::remove_file(from.join(relative)) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => {
+                first_error.get_or_insert(err);
+            }
+        }
+        dirs.extend(relative.ancestors().skip(1).map(Path::to_path_buf));
+    }
+    let mut dirs: Vec<_> = dirs.into_iter().collect();
+    dirs.sort_by_key(|dir| Reverse(dir.components().count()));
+    for dir in dirs {
+        // Fails harmlessly for folders still holding files the move did not copy
+        let _ = fs::remove_dir(from.join(dir));
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+/// Empty the folder a move left behind; run at startup once logging is up.
+///
+/// A folder that cannot be emptied is left as it is, with a note where the data went.
+pub fn finish(data_dir: &Path) {
+    let marker = data_dir.join(PENDING_CLEANUP_FILE_NAME);
+    let pending: PendingCleanup = match fs::read(&marker) {
+        Ok(bytes) => match serde_json::from_slice(&bytes) {
+            Ok(pending) => pending,
+            Err(err) => {
+                tracing::warn!(%err, "unreadable note from a data folder move; ignoring it");
+                let _ = fs::remove_file(&marker);
+                return;
+            }
+        },
+        Err(_) => return,
+    };
+    if pending.from != data_dir {
+        match remove_copied(&pending.from, &pending.files) {
+            Ok(()) => tracing::info!(from = %pending.from.display(), "old data folder removed"),
+            Err(err) => {
+                tracing::warn!(%err, from = %pending.from.display(), "old data folder left in place");
+                let note = format!(
+                    "Momentum moved its data to {}.\nThis folder is no longer used and can be deleted.\n",
+                    data_dir.display()
+                );
+                let _ = File::create(pending.from.join(LEFT_BEHIND_NOTE_FILE_NAME))
+                    .and_then(|mut file| file.write_all(note.as_bytes()));
+            }
+        }
+    }
+    let _ = fs::remove_file(marker);
+}
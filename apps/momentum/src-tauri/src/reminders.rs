@@ -0,0 +1,430 @@
+//! Reminders for bid due dates and follow-ups, kept in the database and fired as notifications.
+//!
+//! A reminder stores the wall-clock time the user picked and, optionally, the zone they
+//! picked it in; without one it follows the device, so "9:00" stays 9:00 after travelling.
+//! The instant it fires is derived from those two and rechecked on every pass, which keeps
+//! reminders right across DST transitions, device zone changes, and tz database updates.
+//! A time that falls in a spring-forward gap fires just after the gap; one that occurs twice
+//! fires the first time. Reminders that came due while the app was closed fire on the next
+//! launch, and each fires at most once: it is marked before its notification is shown.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use jiff::civil::DateTime;
+use jiff::tz::TimeZone;
+use rusqlite::{params, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::jobs::Scheduler;
+use crate::notifications::{self, NotificationAction, NotificationRequest, SNOOZE_ACTION};
+
+/// Job kind that fires due reminders.
+pub const CHECK_JOB: &str = "reminders.check";
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Emitted with the [`Reminder`] when it fires, whether or not a notification could be shown.
+pub const FIRED_EVENT: &str = "reminders:fired";
+
+const KINDS: &[&str] = &["bidDue", "followUp", "custom"];
+// A little slack so a reminder set for "now" is not rejected as past
+const PAST_TOLERANCE_MS: i64 = 60 * 1000;
+const MAX_TITLE_LEN: usize = 200;
+
+/// A reminder as shown to the frontend.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reminder {
+    pub id: String,
+    pub project_id: Option<String>,
+    /// `bidDue`, `followUp`, or `custom`.
+    pub kind: String,
+    pub title: String,
+    pub body: String,
+    /// Wall-clock time without an offset, e.g. `2026-11-03T09:00`.
+    pub local_time: String,
+    /// IANA zone the time is in; `None` follows the device.
+    pub time_zone: Option<String>,
+    /// Unix milliseconds it fires at under current zone rules.
+    pub fire_at: i64,
+    pub snoozed_until: Option<i64>,
+    pub fired_at: Option<i64>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Reminder {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            project_id: row.get("project_id")?,
+            kind: row.get("kind")?,
+            title: row.get("title")?,
+            body: row.get("body")?,
+            local_time: row.get("local_time")?,
+            time_zone: row.get("time_zone")?,
+            fire_at: row.get("fire_at")?,
+            snoozed_until: row.get("snoozed_until")?,
+            fired_at: row.get("fired_at")?,
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+
+    fn due_at(&self) -> i64 {
+        self.snoozed_until.unwrap_or(self.fire_at)
+    }
+}
+
+/// Fields for `reminder_create` and `reminder_update`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReminderInput {
+    pub project_id: Option<String>,
+    pub kind: String,
+    pub title: String,
+    #[serde(default)]
+    pub body: String,
+    pub local_time: String,
+    pub time_zone: Option<String>,
+}
+
+fn zone(time_zone: Option<&str>) -> AppResult<TimeZone> {
+    match time_zone {
+        Some(name) => TimeZone::get(name)
+            .map_err(|_| AppError::InvalidInput(format!("unknown time zone {name}"))),
+        None => Ok(TimeZone::system()),
+    }
+}
+
+// The instant a wall-clock time lands on in `tz`, resolving gaps and folds as the module says
+fn fire_at(local_time: &str, tz: TimeZone) -> AppResult<i64> {
+    let civil: DateTime = local_time
+        .parse()
+        .map_err(|_| AppError::InvalidInput(format!("{local_time} is not a date and time")))?;
+    let zoned = civil
+        .to_zoned(tz)
+        .map_err(|e| AppError::InvalidInput(format!("{local_time}: {e}")))?;
+    Ok(zoned.timestamp().as_millisecond())
+}
+
+struct Validated {
+    input: ReminderInput,
+    local_time: String,
+    fire_at: i64,
+}
+
+fn validate(input: ReminderInput) -> AppResult<Validated> {
+    if !KINDS.contains(&input.kind.as_str()) {
+        return Err(AppError::InvalidInput(format!(
+            "unknown reminder kind {}",
+            input.kind
+        )));
+    }
+    let title = input.title.trim();
+    if title.is_empty() || title.chars().count() > MAX_TITLE_LEN {
+        return Err(AppError::InvalidInput(format!(
+            "a reminder title must be 1 to {MAX_TITLE_LEN} characters"
+        )));
+    }
+    let fire_at = fire_at(&input.local_time, zone(input.time_zone.as_deref())?)?;
+    if fire_at < now_ms() - PAST_TOLERANCE_MS {
+        return Err(AppError::InvalidInput(
+            "that time has already passed".into(),
+        ));
+    }
+    // Stored normalized so every reader parses the same form
+    let local_time = input
+        .local_time
+        .parse::<DateTime>()
+        .map(|civil| civil.to_string())
+        .unwrap_or_else(|_| input.local_time.clone());
+    Ok(Validated {
+        input,
+        local_time,
+        fire_at,
+    })
+}
+
+/// Reminder store registered in app state.
+pub struct Reminders {
+    db: Db,
+}
+
+impl Reminders {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    async fn get(&self, id: String) -> AppResult<Reminder> {
+        let lookup = id.clone();
+        self.db
+            .run(move |conn| {
+                Ok(conn
+                    .query_row(
+                        "SELECT * FROM reminders WHERE id = ?1",
+                        [lookup],
+                        Reminder::from_row,
+                    )
+                    .optional()?)
+            })
+            .await?
+            .ok_or_else(|| AppError::not_found("reminder", id))
+    }
+
+    /// Reminders soonest first, all of them or one project's.
+    pub async fn list(
+        &self,
+        project_id: Option<String>,
+        include_fired: bool,
+    ) -> AppResult<Vec<Reminder>> {
+        self.db
+            .run(move |conn| {
+                Ok(conn
+                    .prepare(
+                        "SELECT * FROM reminders
+                         WHERE (?1 IS NULL OR project_id = ?1) AND (?2 OR fired_at IS NULL)
+                         ORDER BY COALESCE(snoozed_until, fire_at)",
+                    )?
+                    .query_map(params![project_id, include_fired], Reminder::from_row)?
+                    .collect::<rusqlite::Result<_>>()?)
+            })
+            .await
+    }
+
+    /// Re-derive due times, then mark and return every reminder now due.
+    async fn take_due(&self) -> AppResult<Vec<Reminder>> {
+        // Read once per pass; a zone change between passes is picked up on the next one
+        let system = TimeZone::system();
+        self.db
+            .run(move |conn| {
+                let tx = conn.transaction()?;
+                let pending: Vec<Reminder> = tx
+                    .prepare("SELECT * FROM reminders WHERE fired_at IS NULL")?
+                    .query_map([], Reminder::from_row)?
+                    .collect::<rusqlite::Result<_>>()?;
+                let now = now_ms();
+                let mut due = Vec::new();
+                for mut reminder in pending {
+                    let tz = match reminder.time_zone.as_deref() {
+                        None => Ok(system.clone()),
+                        Some(name) => TimeZone::get(name),
+                    };
+                    let derived = tz
+                        .map_err(|e| AppError::Internal(format!("time zone: {e}")))
+                        .and_then(|tz| fire_at(&reminder.local_time, tz));
+                    match derived {
+                        Ok(fire_at) if fire_at != reminder.fire_at => {
+                            tx.execute(
+                                "UPDATE reminders SET fire_at = ?2 WHERE id = ?1",
+                                params![reminder.id, fire_at],
+                            )?;
+                            reminder.fire_at = fire_at;
+                        }
+                        Ok(_) => {}
+                        // Keep the last good instant if a zone vanished from the tz database
+                        Err(err) => {
+                            tracing::warn!(id = %reminder.id, %err, "cannot re-derive reminder time")
+                        }
+                    }
+                    if reminder.due_at() <= now {
+                        tx.execute(
+                            "UPDATE reminders SET fired_at = ?2 WHERE id = ?1",
+                            params![reminder.id, now],
+                        )?;
+                        reminder.fired_at = Some(now);
+                        due.push(reminder);
+                    }
+                }
+                tx.commit()?;
+                Ok(due)
+            })
+            .await
+    }
+
+    async fn snooze(&self, id: String, until: i64) -> AppResult<()> {
+        self.db
+            .run(move |conn| {
+                conn.execute(
+                    "UPDATE reminders SET fired_at = NULL, snoozed_until = ?2, updated_at = ?3
+                     WHERE id = ?1",
+                    params![id, until, now_ms()],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+}
+
+fn notify(app: &AppHandle, reminder: &Reminder) -> AppResult<u64> {
+    let mut actions = Vec::new();
+    let target = reminder
+        .project_id
+        .as_ref()
+        .map(|project_id| format!("momentum://project/{project_id}"));
+    if let Some(target) = &target {
+        actions.push(NotificationAction {
+            id: "open".into(),
+            title: "Open project".into(),
+            target: Some(target.clone()),
+        });
+    }
+    actions.push(NotificationAction {
+        id: SNOOZE_ACTION.into(),
+        title: "Snooze".into(),
+        target: None,
+    });
+    let request = NotificationRequest {
+        title: reminder.title.clone(),
+        body: reminder.body.clone(),
+        target,
+        actions,
+        snooze_minutes: None,
+    };
+    // Snoozing goes back to the database so it survives a restart too
+    let id = reminder.id.clone();
+    let on_snooze = Arc::new(move |app: &AppHandle, delay: Duration| {
+        let (app, id) = (app.clone(), id.clone());
+        let until = now_ms() + i64::try_from(delay.as_millis()).unwrap_or(i64::MAX);
+        tauri::async_runtime::spawn(async move {
+            if let Err(err) = app.state::<Reminders>().snooze(id, until).await {
+                tracing::warn!(%err, "failed to snooze reminder");
+            }
+        });
+    });
+    notifications::send_snoozable(app, request, on_snooze)
+}
+
+/// Fire every reminder that is due.
+pub async fn check(app: &AppHandle) -> AppResult<()> {
+    let due = app.state::<Reminders>().take_due().await?;
+    for reminder in due {
+        tracing::info!(id = %reminder.id, kind = %reminder.kind, "reminder fired");
+        if let Err(err) = notify(app, &reminder) {
+            tracing::warn!(id = %reminder.id, %err, "reminder notification not shown");
+        }
+        let _ = app.emit(FIRED_EVENT, &reminder);
+    }
+    Ok(())
+}
+
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(CHECK_JOB, Some(CHECK_INTERVAL), |app| async move {
+        check(&app).await
+    });
+}
+
+#[tauri::command]
+pub async fn reminder_create(
+    reminders: State<'_, Reminders>,
+    input: ReminderInput,
+) -> AppResult<Reminder> {
+    let Validated {
+        input,
+        local_time,
+        fire_at,
+    } = validate(input)?;
+    let id = uuid::Uuid::new_v4().to_string();
+    let row = id.clone();
+    reminders
+        .db
+        .run(move |conn| {
+            let now = now_ms();
+            conn.execute(
+                "INSERT INTO reminders (id, project_id, kind, title, body, local_time, time_zone,
+                                        fire_at, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9)",
+                params![
+                    row,
+                    input.project_id,
+                    input.kind,
+                    input.title.trim(),
+                    input.body,
+                    local_time,
+                    input.time_zone,
+                    fire_at,
+                    now
+                ],
+            )
+            .map_err(|err| match err {
+                rusqlite::Error::SqliteFailure(e, _)
+                    if e.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_FOREIGNKEY =>
+                {
+                    AppError::not_found("project", input.project_id.unwrap_or_default())
+                }
+                err => err.into(),
+            })?;
+            Ok(())
+        })
+        .await?;
+    reminders.get(id).await
+}
+
+/// Replace a reminder's fields; a fired or snoozed reminder is rescheduled for the new time.
+#[tauri::command]
+pub async fn reminder_update(
+    reminders: State<'_, Reminders>,
+    id: String,
+    input: ReminderInput,
+) -> AppResult<Reminder> {
+    let Validated {
+        input,
+        local_time,
+        fire_at,
+    } = validate(input)?;
+    let row = id.clone();
+    let updated = reminders
+        .db
+        .run(move |conn| {
+            Ok(conn.execute(
+                "UPDATE reminders SET project_id = ?2, kind = ?3, title = ?4, body = ?5,
+                     local_time = ?6, time_zone = ?7, fire_at = ?8, snoozed_until = NULL,
+                     fired_at = NULL, updated_at = ?9
+                 WHERE id = ?1",
+                params![
+                    row,
+                    input.project_id,
+                    input.kind,
+                    input.title.trim(),
+                    input.body,
+                    local_time,
+                    input.time_zone,
+                    fire_at,
+                    now_ms()
+                ],
+            )?)
+        })
+        .await?;
+    if updated == 0 {
+        return Err(AppError::not_found("reminder", id));
+    }
+    reminders.get(id).await
+}
+
+#[tauri::command]
+pub async fn reminder_delete(reminders: State<'_, Reminders>, id: String) -> AppResult<()> {
+    let row = id.clone();
+    let deleted = reminders
+        .db
+        .run(move |conn| Ok(conn.execute("DELETE FROM reminders WHERE id = ?1", [row])?))
+        .await?;
+    if deleted == 0 {
+        return Err(AppError::not_found("reminder", id));
+    }
+    Ok(())
+}
+
+/// Reminders soonest first, optionally for one project; fired ones only when asked for.
+#[tauri::command]
+pub async fn list_reminders(
+    reminders: State<'_, Reminders>,
+    project_id: Option<String>,
+    include_fired: Option<bool>,
+) -> AppResult<Vec<Reminder>> {
+    reminders
+        .list(project_id, include_fired.unwrap_or(false))
+        .await
+}
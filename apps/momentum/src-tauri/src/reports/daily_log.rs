@@ -0,0 +1,316 @@
+//! The daily log: one day on one project printed as a field report.
+//!
+//! The day runs midnight to midnight in the machine's time zone. Time entries are the
+//! project's timers started that day, with their notes; photos are the image attachments
+//! added that day, downscaled through the thumbnail cache first so a morning of full-size
+//! camera shots still makes a file small enough to email. The superintendent's own notes
+//! are passed in by the caller, and so may the weather; otherwise it is the jobsite's, from
+//! the weather recorded for that day.
+
+use std::path::PathBuf;
+
+use jiff::civil::Date;
+use jiff::tz::TimeZone;
+use jiff::Timestamp;
+use rusqlite::{params, OptionalExtension};
+use serde_json::{Map, Value};
+use tauri::{AppHandle, Manager, State};
+
+use super::{ReportColumn, ReportData, ReportField, ReportPhoto, ReportSection, ReportTemplate};
+use crate::attachments::{self, AttachmentStore};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::location::Address;
+use crate::reports::{self, ColumnFormat};
+use crate::state::AppState;
+use crate::thumbnails::{self, ThumbnailCache, ThumbnailFormat};
+use crate::timer::Timer;
+use crate::weather;
+
+// Sharp at the two-across print size without carrying the camera's full resolution
+const PHOTO_MAX_DIM: u32 = 1200;
+// Formats the PDF renderer decodes
+const PHOTO_TYPES: &[&str] = &[
+    "image/jpeg",
+    "image/png",
+    "image/webp",
+    "image/gif",
+    "image/tiff",
+];
+const MS_PER_HOUR: f64 = 60.0 * 60.0 * 1000.0;
+
+struct DayRecords {
+    project: String,
+    timers: Vec<Timer>,
+    // Attachment hash, file name, and when it was added
+    photos: Vec<(String, String, i64)>,
+    address: Option<String>,
+}
+
+fn columns() -> Vec<ReportColumn> {
+    let column = |key: &str, header: &str, weight, format| ReportColumn {
+        key: key.into(),
+        header: header.into(),
+        weight,
+        format,
+        decimals: 2,
+        total: false,
+    };
+    vec![
+        column("start", "Start", 1.0, ColumnFormat::Text),
+        column("end", "End", 1.0, ColumnFormat::Text),
+        column("hours", "Hours", 1.0, ColumnFormat::Number),
+        column("note", "Note", 5.0, ColumnFormat::Text),
+    ]
+}
+
+fn clock(ms: i64, tz: &TimeZone) -> String {
+    Timestamp::from_millisecond(ms)
+        .map(|ts| ts.to_zoned(tz.clone()).strftime("%H:%M").to_string())
+        .unwrap_or_default()
+}
+
+// The address a daily log was tagged with, on one line
+fn one_line(address: &str) -> Option<String> {
+    let address: Address = serde_json::from_str(address).ok()?;
+    let parts: Vec<String> = [
+        address.street,
+        address.locality,
+        address.region,
+        address.postal_code,
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|part| !part.trim().is_empty())
+    .collect();
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+async fn day_records(
+    db: &Db,
+    project_id: String,
+    date: Date,
+    from: i64,
+    to: i64,
+) -> AppResult<DayRecords> {
+    db.run(move |conn| {
+        let project: String = conn
+            .query_row(
+                "SELECT name FROM projects WHERE id = ?1",
+                [&project_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or_else(|| AppError::not_found("project", project_id.clone()))?;
+        let timers = conn
+            .prepare(
+                "SELECT * FROM timers
+                 WHERE project_id = ?1 AND started_at >= ?2 AND started_at < ?3
+                 ORDER BY started_at",
+            )?
+            .query_map(params![project_id, from, to], Timer::from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        let photos = conn
+            .prepare(
+                "SELECT hash, file_name, content_type, created_at FROM attachments
+                 WHERE project_id = ?1 AND created_at >= ?2 AND created_at < ?3
+                 ORDER BY created_at",
+            )?
+            .query_map(params![project_id, from, to], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|(_, file_name, content_type, _)| {
+                content_type
+                    .as_deref()
+                    .or_else(|| attachments::content_type_for(file_name))
+                    .is_some_and(|t| PHOTO_TYPES.contains(&t))
+            })
+            .map(|(hash, file_name, _, created_at)| (hash, file_name, created_at))
+            .collect();
+        let address: Option<String> = conn
+            .query_row(
+                "SELECT address FROM location_tags WHERE entity = 'dailyLog' AND entity_id = ?1",
+                [format!("{project_id}/{date}")],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        Ok(DayRecords {
+            project,
+            timers,
+            photos,
+            address: address.as_deref().and_then(one_line),
+        })
+    })
+    .await
+}
+
+/// Print the field report for `date`, as `YYYY-MM-DD`, on one project, to `path` or the
+/// reports folder.
+///
+/// `template` sets the page, font, logo, header, and footer; the columns are the daily
+/// log's own. Photos that cannot be decoded are left out rather than failing the report.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn generate_daily_log_pdf(
+    app: AppHandle,
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    date: String,
+    project_id: String,
+    notes: Option<String>,
+    weather: Option<String>,
+    template: Option<ReportTemplate>,
+    path: Option<PathBuf>,
+) -> AppResult<PathBuf> {
+    let day: Date = date
+        .parse()
+        .map_err(|_| AppError::InvalidInput(format!("{date} is not a YYYY-MM-DD date")))?;
+    let tz = TimeZone::system();
+    let invalid = |e: jiff::Error| AppError::InvalidInput(format!("{date}: {e}"));
+    let start = day.to_zoned(tz.clone()).map_err(invalid)?;
+    let end = day
+        .tomorrow()
+        .map_err(invalid)?
+        .to_zoned(tz.clone())
+        .map_err(invalid)?;
+    let DayRecords {
+        project,
+        timers,
+        photos,
+        address,
+    } = day_records(
+        &db,
+        project_id.clone(),
+        day,
+        start.timestamp().as_millisecond(),
+        end.timestamp().as_millisecond(),
+    )
+    .await?;
+
+    let mut fields = vec![
+        ReportField {
+            label: "Project".into(),
+            value: project.clone(),
+        },
+        ReportField {
+            label: "Date".into(),
+            value: start.strftime("%A %-d %B %Y").to_string(),
+        },
+    ];
+    if let Some(address) = address {
+        fields.push(ReportField {
+            label: "Location".into(),
+            value: address,
+        });
+    }
+    let weather = match weather.filter(|w| !w.trim().is_empty()) {
+        Some(weather) => Some(weather),
+        None => weather::describe_day(&app, &project_id, day).await,
+    };
+    if let Some(weather) = weather {
+        fields.push(ReportField {
+            label: "Weather".into(),
+            value: weather,
+        });
+    }
+    if let Some(user) = state.user() {
+        fields.push(ReportField {
+            label: "Prepared by".into(),
+            value: user,
+        });
+    }
+
+    let total_ms: i64 = timers.iter().map(|t| t.elapsed_ms).sum();
+    let rows = timers
+        .iter()
+        .map(|timer| {
+            let mut row = Map::new();
+            row.insert("start".into(), clock(timer.started_at, &tz).into());
+            let end = match timer.stopped_at {
+                Some(stopped_at) => clock(stopped_at, &tz),
+                None => "running".into(),
+            };
+            row.insert("end".into(), end.into());
+            row.insert(
+                "hours".into(),
+                Value::from(timer.elapsed_ms as f64 / MS_PER_HOUR),
+            );
+            row.insert("note".into(), timer.note.clone().into());
+            row
+        })
+        .collect::<Vec<_>>();
+    let (sections, summary, body) = if rows.is_empty() {
+        (
+            Vec::new(),
+            Vec::new(),
+            Some("No time was recorded on this day.".to_string()),
+        )
+    } else {
+        (
+            vec![ReportSection {
+                title: Some("Time".into()),
+                rows,
+            }],
+            vec![ReportField {
+                label: "Total hours".into(),
+                value: format!("{:.2}", total_ms as f64 / MS_PER_HOUR),
+            }],
+            None,
+        )
+    };
+
+    let handle = app.clone();
+    let photos = tauri::async_runtime::spawn_blocking(move || {
+        let store = handle.state::<AttachmentStore>();
+        let cache = handle.state::<ThumbnailCache>();
+        photos
+            .into_iter()
+            .filter_map(|(hash, file_name, created_at)| {
+                let thumbnail = store.plaintext_path(&hash).and_then(|source| {
+                    thumbnails::render(cache.dir(), &source, PHOTO_MAX_DIM, ThumbnailFormat::Jpeg)
+                });
+                match thumbnail {
+                    Ok(thumbnail) => Some(ReportPhoto {
+                        path: thumbnail.path,
+                        caption: Some(format!("{} · {file_name}", clock(created_at, &tz))),
+                    }),
+                    Err(err) => {
+                        tracing::warn!(%err, file_name, "leaving a photo out of the daily log");
+                        None
+                    }
+                }
+            })
+            .collect::<Vec<_>>()
+    })
+    .await?;
+
+    let title = "Daily log".to_string();
+    let data = ReportData {
+        title: title.clone(),
+        subtitle: Some(project.clone()),
+        fields,
+        sections,
+        summary,
+        notes: notes.filter(|n| !n.trim().is_empty()),
+        body,
+        photos,
+    };
+    let template = ReportTemplate {
+        columns: columns(),
+        ..template.unwrap_or_else(|| ReportTemplate {
+            cover_page: false,
+            ..Default::default()
+        })
+    };
+    let path =
+        path.unwrap_or_else(|| reports::default_path(&state, &format!("{title} {project} {date}")));
+    reports::write(data, template, None, path).await
+}
@@ -0,0 +1,254 @@
+//! Bid and estimate PDFs rendered natively from report data and a layout template.
+//!
+//! The frontend sends what to print; the template decides how: columns, cover page,
+//! header and footer text, and logo. Layout runs before rendering so footers can say
+//! "page 3 of 7".
+
+pub(crate) mod daily_log;
+pub(crate) mod pdf;
+pub(crate) mod protect;
+
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tauri::State;
+
+use crate::db::now_ms;
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+use protect::PdfProtection;
+
+const REPORTS_DIR_NAME: &str = "reports";
+
+/// What a report says.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportData {
+    pub title: String,
+    pub subtitle: Option<String>,
+    /// Labelled values shown on the cover, such as client, project, and date.
+    #[serde(default)]
+    pub fields: Vec<ReportField>,
+    #[serde(default)]
+    pub sections: Vec<ReportSection>,
+    /// Lines printed under the grand total, already formatted, e.g. tax or markup.
+    #[serde(default)]
+    pub summary: Vec<ReportField>,
+    pub notes: Option<String>,
+    /// Running text before the tables, such as a proposal letter; blank lines separate
+    /// paragraphs and lines starting `# ` or `## ` are headings.
+    pub body: Option<String>,
+    /// PNG or JPEG images printed two across after the notes.
+    #[serde(default)]
+    pub photos: Vec<ReportPhoto>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportField {
+    pub label: String,
+    pub value: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportPhoto {
+    pub path: PathBuf,
+    /// Printed under the photo.
+    pub caption: Option<String>,
+}
+
+/// A titled table of rows, subtotalled on its own.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportSection {
+    pub title: Option<String>,
+    /// Values keyed by [`ReportColumn::key`].
+    #[serde(default)]
+    pub rows: Vec<Map<String, Value>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PageSize {
+    #[default]
+    Letter,
+    A4,
+}
+
+impl PageSize {
+    // Millimetres, portrait
+    fn dimensions(self) -> (f32, f32) {
+        match self {
+            Self::Letter => (215.9, 279.4),
+            Self::A4 => (210.0, 297.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ColumnFormat {
+    #[default]
+    Text,
+    Number,
+    Currency,
+}
+
+/// A table column.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportColumn {
+    /// Row key the column reads.
+    pub key: String,
+    pub header: String,
+    /// Share of the table width relative to the other columns.
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    #[serde(default)]
+    pub format: ColumnFormat,
+    /// Decimal places for numbers; currency always uses two.
+    #[serde(default = "default_decimals")]
+    pub decimals: usize,
+    /// Sum the column into section subtotals and the grand total.
+    #[serde(default)]
+    pub total: bool,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+fn default_decimals() -> usize {
+    2
+}
+
+/// How a report looks; every field has a default matching a standard estimate.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReportTemplate {
+    pub page_size: PageSize,
+    pub margin_mm: f32,
+    pub cover_page: bool,
+    /// PNG or JPEG drawn on the cover, or atop the first page without one.
+    pub logo: Option<PathBuf>,
+    /// Top of every page after the cover; `{title}` is replaced.
+    pub header: Option<String>,
+    /// Bottom of every page after the cover; `{page}`, `{pages}`, and `{title}` are replaced.
+    pub footer: String,
+    pub columns: Vec<ReportColumn>,
+    /// Installed family to embed, as listed by `list_system_fonts`; Helvetica without one.
+    pub font_family: Option<String>,
+    pub currency_symbol: String,
+    pub subtotal_label: String,
+    pub total_label: String,
+}
+
+impl Default for ReportTemplate {
+    fn default() -> Self {
+        let column = |key: &str, header: &str, weight, format, total| ReportColumn {
+            key: key.into(),
+            header: header.into(),
+            weight,
+            format,
+            decimals: 2,
+            total,
+        };
+        Self {
+            page_size: PageSize::default(),
+            margin_mm: 18.0,
+            cover_page: true,
+            logo: None,
+            header: Some("{title}".into()),
+            footer: "Page {page} of {pages}".into(),
+            columns: vec![
+                column("name", "Description", 5.0, ColumnFormat::Text, false),
+                column("quantity", "Qty", 1.2, ColumnFormat::Number, false),
+                column("unit", "Unit", 1.0, ColumnFormat::Text, false),
+                column("unitCost", "Unit cost", 1.6, ColumnFormat::Currency, false),
+                column("total", "Total", 1.8, ColumnFormat::Currency, true),
+            ],
+            font_family: None,
+            currency_symbol: "$".into(),
+            subtotal_label: "Subtotal".into(),
+            total_label: "Total".into(),
+        }
+    }
+}
+
+// Titles become file names, so keep them to characters every filesystem accepts
+pub(crate) fn file_stem(title: &str) -> String {
+    let stem: String = title
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let stem = stem
+        .split('-')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if stem.is_empty() {
+        "report".into()
+    } else {
+        stem
+    }
+}
+
+/// Where a report goes when the caller does not say: the app's reports folder.
+pub(crate) fn default_path(state: &AppState, title: &str) -> PathBuf {
+    state
+        .data_dir()
+        .join(REPORTS_DIR_NAME)
+        .join(format!("{}-{}.pdf", file_stem(title), now_ms()))
+}
+
+/// Render on a blocking thread and return where the PDF was written.
+pub(crate) async fn write(
+    data: ReportData,
+    template: ReportTemplate,
+    protection: Option<PdfProtection>,
+    path: PathBuf,
+) -> AppResult<PathBuf> {
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let pages = pdf::render(&data, &template, protection.as_ref(), &path)?;
+        tracing::info!(
+            pages,
+            protected = protection.is_some(),
+            path = %path.display(),
+            "report generated"
+        );
+        Ok(path)
+    })
+    .await?
+}
+
+/// Render a report to `path`, or to the app's reports folder, and return where it was written.
+///
+/// With `protection` the PDF is encrypted under its passwords and restrictions.
+#[tauri::command]
+pub async fn generate_report_pdf(
+    state: State<'_, AppState>,
+    data: ReportData,
+    template: Option<ReportTemplate>,
+    protection: Option<PdfProtection>,
+    path: Option<PathBuf>,
+) -> AppResult<PathBuf> {
+    let template = template.unwrap_or_default();
+    if template.columns.is_empty() {
+        return Err(AppError::InvalidInput(
+            "a report needs at least one column".into(),
+        ));
+    }
+    let path = path.unwrap_or_else(|| default_path(&state, &data.title));
+    write(data, template, protection, path).await
+}
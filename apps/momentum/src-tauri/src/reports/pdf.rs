@@ -0,0 +1,901 @@
+//! Layout and rendering in the template's font, or printpdf's built-in Helvetica without one.
+//!
+//! Built-in fonts need no embedding but printpdf cannot measure them, so widths come from
+//! the standard Helvetica metrics below, and text outside Windows-1252 is dropped by the
+//! encoder. A chosen system font is embedded and measured from its own advance widths.
+
+use std::path::Path;
+
+use printpdf::image_crate::{self, DynamicImage, GenericImageView, Rgb, RgbImage};
+use printpdf::path::PaintMode;
+use printpdf::{
+    BuiltinFont, Color, Greyscale, Image, ImageTransform, IndirectFontRef, Line, Mm, PdfDocument,
+    PdfLayerReference, Point, Rect,
+};
+use serde_json::Value;
+use ttf_parser::Face;
+
+use super::protect::{self, PdfProtection};
+use super::{ColumnFormat, ReportColumn, ReportData, ReportPhoto, ReportTemplate};
+use crate::error::{AppError, AppResult};
+use crate::fonts;
+
+const PT_TO_MM: f32 = 0.352_778;
+const LINE_SPACING: f32 = 1.25;
+const CELL_PADDING: f32 = 1.5;
+const BODY_SIZE: f32 = 9.0;
+// Running text reads better a little larger than table cells
+const PROSE_SIZE: f32 = 10.0;
+const LOGO_DPI: f32 = 300.0;
+const LOGO_MAX: (f32, f32) = (50.0, 22.0);
+const PHOTO_DPI: f32 = 300.0;
+const PHOTO_GAP: f32 = 6.0;
+const PHOTO_MAX_HEIGHT: f32 = 80.0;
+
+// Helvetica advance widths for ASCII 32..=126 in 1/1000 em, from the standard AFM
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+// Where text widths come from
+enum Metrics<'f> {
+    Helvetica,
+    Embedded {
+        regular: Box<Face<'f>>,
+        bold: Option<Box<Face<'f>>>,
+    },
+}
+
+impl Metrics<'_> {
+    fn text_width(&self, text: &str, size: f32, bold: bool) -> f32 {
+        let (regular, bold_face) = match self {
+            Self::Helvetica => {
+                let units: u32 = text
+                    .chars()
+                    .map(|c| match c as u32 {
+                        code @ 32..=126 => HELVETICA_WIDTHS[(code - 32) as usize] as u32,
+                        _ => 556,
+                    })
+                    .sum();
+                // Bold glyphs run about five percent wider; close enough for alignment and wrapping
+                let scale = if bold { 1.05 } else { 1.0 };
+                return units as f32 / 1000.0 * size * PT_TO_MM * scale;
+            }
+            Self::Embedded { regular, bold } => (regular.as_ref(), bold.as_deref()),
+        };
+        // Families without a bold face set bold text in the regular one
+        let face = if bold {
+            bold_face.unwrap_or(regular)
+        } else {
+            regular
+        };
+        let missing = face.units_per_em() / 2;
+        let units: u32 = text
+            .chars()
+            .map(|c| {
+                face.glyph_index(c)
+                    .and_then(|glyph| face.glyph_hor_advance(glyph))
+                    .unwrap_or(missing) as u32
+            })
+            .sum();
+        units as f32 / face.units_per_em() as f32 * size * PT_TO_MM
+    }
+}
+
+fn line_height(size: f32) -> f32 {
+    size * PT_TO_MM * LINE_SPACING
+}
+
+// Greedy word wrap; words wider than the column are split by character
+fn wrap(metrics: &Metrics, text: &str, width: f32, size: f32, bold: bool) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{line} {word}")
+            };
+            if metrics.text_width(&candidate, size, bold) <= width {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            for c in word.chars() {
+                line.push(c);
+                if metrics.text_width(&line, size, bold) > width && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(std::mem::replace(&mut line, c.to_string()));
+                }
+            }
+        }
+        lines.push(line);
+    }
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    lines
+}
+
+fn group_thousands(digits: &str) -> String {
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+fn format_number(value: f64, decimals: usize, symbol: Option<&str>) -> String {
+    let fixed = format!("{:.*}", decimals, value.abs());
+    let (int, frac) = fixed.split_once('.').unwrap_or((&fixed, ""));
+    let sign = if value < 0.0 && fixed.chars().any(|c| c.is_ascii_digit() && c != '0') {
+        "-"
+    } else {
+        ""
+    };
+    let mut text = format!("{sign}{}{}", symbol.unwrap_or(""), group_thousands(int));
+    if !frac.is_empty() {
+        text.push('.');
+        text.push_str(frac);
+    }
+    text
+}
+
+fn numeric(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn format_cell(value: Option<&Value>, column: &ReportColumn, symbol: &str) -> String {
+    let Some(value) = value else {
+        return String::new();
+    };
+    match (column.format, numeric(value)) {
+        (ColumnFormat::Currency, Some(n)) => format_number(n, 2, Some(symbol)),
+        (ColumnFormat::Number, Some(n)) => format_number(n, column.decimals, None),
+        _ => match value {
+            Value::Null => String::new(),
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        },
+    }
+}
+
+enum Op {
+    Text {
+        x: f32,
+        y: f32,
+        size: f32,
+        bold: bool,
+        text: String,
+    },
+    Rule {
+        x1: f32,
+        x2: f32,
+        y: f32,
+        thickness: f32,
+    },
+    Fill {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        gray: f32,
+    },
+    Logo {
+        x: f32,
+        y: f32,
+        scale: f32,
+    },
+    Photo {
+        index: usize,
+        x: f32,
+        y: f32,
+        scale: f32,
+    },
+}
+
+struct Page {
+    ops: Vec<Op>,
+    cover: bool,
+}
+
+struct Logo {
+    image: DynamicImage,
+    // Rendered height in mm and the scale that produces it at LOGO_DPI
+    height: f32,
+    scale: f32,
+}
+
+struct Photo {
+    image: DynamicImage,
+    // Size in mm at PHOTO_DPI, before scaling to the column
+    width: f32,
+    height: f32,
+    caption: Option<String>,
+}
+
+// printpdf draws alpha channels as black, so transparent images are flattened onto white
+fn flatten(image: &DynamicImage) -> DynamicImage {
+    let (px_w, px_h) = image.dimensions();
+    let rgba = image.to_rgba8();
+    let flat = RgbImage::from_fn(px_w, px_h, |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend = |c: u8| ((c as u16 * a as u16 + 255 * (255 - a as u16)) / 255) as u8;
+        Rgb([blend(r), blend(g), blend(b)])
+    });
+    DynamicImage::ImageRgb8(flat)
+}
+
+fn load_logo(path: &Path) -> AppResult<Logo> {
+    let image = image_crate::open(path)
+        .map_err(|e| AppError::InvalidInput(format!("cannot read logo {}: {e}", path.display())))?;
+    let (px_w, px_h) = image.dimensions();
+    let natural = |px: u32| px as f32 / LOGO_DPI * 25.4;
+    let scale = (LOGO_MAX.0 / natural(px_w)).min(LOGO_MAX.1 / natural(px_h));
+    Ok(Logo {
+        image: flatten(&image),
+        height: natural(px_h) * scale,
+        scale,
+    })
+}
+
+fn load_photo(photo: &ReportPhoto) -> AppResult<Photo> {
+    let path = &photo.path;
+    let image = image_crate::open(path).map_err(|e| {
+        AppError::InvalidInput(format!("cannot read photo {}: {e}", path.display()))
+    })?;
+    let (px_w, px_h) = image.dimensions();
+    let natural = |px: u32| px as f32 / PHOTO_DPI * 25.4;
+    Ok(Photo {
+        image: flatten(&image),
+        width: natural(px_w),
+        height: natural(px_h),
+        caption: photo.caption.clone(),
+    })
+}
+
+struct Composer<'a> {
+    data: &'a ReportData,
+    template: &'a ReportTemplate,
+    metrics: &'a Metrics<'a>,
+    page_w: f32,
+    page_h: f32,
+    // Column left edges and widths across the content area
+    columns: Vec<(f32, f32)>,
+    pages: Vec<Page>,
+    y: f32,
+}
+
+impl<'a> Composer<'a> {
+    fn new(data: &'a ReportData, template: &'a ReportTemplate, metrics: &'a Metrics<'a>) -> Self {
+        let (page_w, page_h) = template.page_size.dimensions();
+        let left = template.margin_mm;
+        let content = page_w - 2.0 * template.margin_mm;
+        let total: f32 = template.columns.iter().map(|c| c.weight.max(0.1)).sum();
+        let mut x = left;
+        let columns = template
+            .columns
+            .iter()
+            .map(|c| {
+                let w = c.weight.max(0.1) / total * content;
+                let column = (x, w);
+                x += w;
+                column
+            })
+            .collect();
+        Self {
+            data,
+            template,
+            metrics,
+            page_w,
+            page_h,
+            columns,
+            pages: Vec::new(),
+            y: 0.0,
+        }
+    }
+
+    fn left(&self) -> f32 {
+        self.template.margin_mm
+    }
+
+    fn right(&self) -> f32 {
+        self.page_w - self.template.margin_mm
+    }
+
+    // Lowest baseline content may use, leaving room for the footer
+    fn bottom(&self) -> f32 {
+        self.template.margin_mm + line_height(BODY_SIZE) * 2.0
+    }
+
+    fn push(&mut self, op: Op) {
+        if let Some(page) = self.pages.last_mut() {
+            page.ops.push(op);
+        }
+    }
+
+    fn text(&mut self, x: f32, size: f32, bold: bool, text: impl Into<String>) {
+        let y = self.y;
+        self.push(Op::Text {
+            x,
+            y,
+            size,
+            bold,
+            text: text.into(),
+        });
+    }
+
+    fn text_right(&mut self, right: f32, size: f32, bold: bool, text: String) {
+        let x = right - self.metrics.text_width(&text, size, bold);
+        self.text(x, size, bold, text);
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(Page {
+            ops: Vec::new(),
+            cover: false,
+        });
+        self.y = self.page_h - self.template.margin_mm;
+        if let Some(header) = &self.template.header {
+            let header = header.replace("{title}", &self.data.title);
+            self.y -= line_height(BODY_SIZE);
+            self.text(self.left(), BODY_SIZE, false, header);
+            self.y -= 2.0;
+            let (x1, x2, y) = (self.left(), self.right(), self.y);
+            self.push(Op::Rule {
+                x1,
+                x2,
+                y,
+                thickness: 0.5,
+            });
+            self.y -= line_height(BODY_SIZE);
+        }
+    }
+
+    fn ensure_space(&mut self, height: f32) {
+        if self.y - height < self.bottom() {
+            self.new_page();
+        }
+    }
+
+    fn logo(&mut self, logo: &Logo) {
+        self.y -= logo.height;
+        let (x, y, scale) = (self.left(), self.y, logo.scale);
+        self.push(Op::Logo { x, y, scale });
+        self.y -= 8.0;
+    }
+
+    fn cover(&mut self, logo: Option<&Logo>) {
+        self.pages.push(Page {
+            ops: Vec::new(),
+            cover: true,
+        });
+        self.y = self.page_h - self.template.margin_mm;
+        if let Some(logo) = logo {
+            self.logo(logo);
+        }
+        self.y = self.y.min(self.page_h * 0.62);
+        let width = self.right() - self.left();
+        for line in wrap(self.metrics, &self.data.title, width, 24.0, true) {
+            self.y -= line_height(24.0);
+            self.text(self.left(), 24.0, true, line);
+        }
+        if let Some(subtitle) = &self.data.subtitle {
+            for line in wrap(self.metrics, subtitle, width, 14.0, false) {
+                self.y -= line_height(14.0);
+                self.text(self.left(), 14.0, false, line);
+            }
+        }
+        self.y -= 10.0;
+        self.fields(11.0);
+    }
+
+    // Label and value pairs with the values aligned in a second column
+    fn fields(&mut self, size: f32) {
+        let label_width = self
+            .data
+            .fields
+            .iter()
+            .map(|f| self.metrics.text_width(&f.label, size, true))
+            .fold(0.0, f32::max)
+            + 6.0;
+        let value_x = self.left() + label_width;
+        let value_width = self.right() - value_x;
+        for field in &self.data.fields {
+            let lines = wrap(self.metrics, &field.value, value_width, size, false);
+            self.ensure_space(line_height(size) * lines.len() as f32);
+            self.y -= line_height(size);
+            self.text(self.left(), size, true, field.label.clone());
+            for (i, line) in lines.into_iter().enumerate() {
+                if i > 0 {
+                    self.y -= line_height(size);
+                }
+                self.text(value_x, size, false, line);
+            }
+        }
+    }
+
+    fn title_block(&mut self, logo: Option<&Logo>) {
+        if let Some(logo) = logo {
+            self.logo(logo);
+        }
+        let width = self.right() - self.left();
+        for line in wrap(self.metrics, &self.data.title, width, 18.0, true) {
+            self.y -= line_height(18.0);
+            self.text(self.left(), 18.0, true, line);
+        }
+        if let Some(subtitle) = &self.data.subtitle {
+            self.y -= line_height(12.0);
+            self.text(self.left(), 12.0, false, subtitle.clone());
+        }
+        self.y -= 4.0;
+        self.fields(BODY_SIZE);
+        self.y -= line_height(BODY_SIZE);
+    }
+
+    // Paragraphs of running text, with `# ` and `## ` lines as headings
+    fn body(&mut self) {
+        let Some(body) = &self.data.body else {
+            return;
+        };
+        let width = self.right() - self.left();
+        let mut paragraph = String::new();
+        for line in body.lines().chain(std::iter::once("")) {
+            let heading = line
+                .strip_prefix("# ")
+                .map(|text| (text, 14.0))
+                .or_else(|| line.strip_prefix("## ").map(|text| (text, 11.0)));
+            if line.trim().is_empty() || heading.is_some() {
+                let text = std::mem::take(&mut paragraph);
+                self.paragraph(&text, width);
+            }
+            if let Some((text, size)) = heading {
+                self.ensure_space(line_height(size) + 2.0 + line_height(PROSE_SIZE));
+                self.y -= line_height(size);
+                self.text(self.left(), size, true, text.trim().to_string());
+                self.y -= 2.0;
+            } else if !line.trim().is_empty() {
+                if !paragraph.is_empty() {
+                    paragraph.push('\n');
+                }
+                paragraph.push_str(line);
+            }
+        }
+        self.y -= line_height(BODY_SIZE);
+    }
+
+    fn paragraph(&mut self, text: &str, width: f32) {
+        if text.is_empty() {
+            return;
+        }
+        for line in wrap(self.metrics, text, width, PROSE_SIZE, false) {
+            self.ensure_space(line_height(PROSE_SIZE));
+            self.y -= line_height(PROSE_SIZE);
+            self.text(self.left(), PROSE_SIZE, false, line);
+        }
+        self.y -= line_height(PROSE_SIZE) * 0.5;
+    }
+
+    fn header_row(&mut self) {
+        let height = line_height(BODY_SIZE) + CELL_PADDING * 2.0;
+        self.ensure_space(height);
+        let (x, w) = (self.left(), self.right() - self.left());
+        self.push(Op::Fill {
+            x,
+            y: self.y - height,
+            w,
+            h: height,
+            gray: 0.9,
+        });
+        self.y -= CELL_PADDING + line_height(BODY_SIZE) * 0.8;
+        for (index, column) in self.template.columns.iter().enumerate() {
+            let (x, w) = self.columns[index];
+            if column.format == ColumnFormat::Text {
+                self.text(x + CELL_PADDING, BODY_SIZE, true, column.header.clone());
+            } else {
+                self.text_right(x + w - CELL_PADDING, BODY_SIZE, true, column.header.clone());
+            }
+        }
+        self.y -= CELL_PADDING + line_height(BODY_SIZE) * 0.2;
+    }
+
+    // Returns the wrapped lines of each cell and the row height
+    fn measure_row(&self, cells: &[String]) -> (Vec<Vec<String>>, f32) {
+        let lines: Vec<Vec<String>> = cells
+            .iter()
+            .zip(&self.template.columns)
+            .zip(&self.columns)
+            .map(|((cell, column), (_, w))| match column.format {
+                ColumnFormat::Text => {
+                    wrap(self.metrics, cell, w - CELL_PADDING * 2.0, BODY_SIZE, false)
+                }
+                _ => vec![cell.clone()],
+            })
+            .collect();
+        let count = lines.iter().map(Vec::len).max().unwrap_or(1);
+        (
+            lines,
+            line_height(BODY_SIZE) * count as f32 + CELL_PADDING * 2.0,
+        )
+    }
+
+    fn draw_row(&mut self, lines: Vec<Vec<String>>, height: f32, bold: bool) {
+        let top = self.y;
+        for (index, cell_lines) in lines.into_iter().enumerate() {
+            let (x, w) = self.columns[index];
+            let text_column = self.template.columns[index].format == ColumnFormat::Text;
+            self.y = top - CELL_PADDING;
+            for line in cell_lines {
+                self.y -= line_height(BODY_SIZE) * 0.8;
+                if text_column {
+                    self.text(x + CELL_PADDING, BODY_SIZE, bold, line);
+                } else {
+                    self.text_right(x + w - CELL_PADDING, BODY_SIZE, bold, line);
+                }
+                self.y -= line_height(BODY_SIZE) * 0.2;
+            }
+        }
+        self.y = top - height;
+        let (x1, x2, y) = (self.left(), self.right(), self.y);
+        self.push(Op::Rule {
+            x1,
+            x2,
+            y,
+            thickness: 0.25,
+        });
+    }
+
+    // Label in the first column, sums under their columns
+    fn totals_row(&mut self, label: &str, sums: &[Option<f64>]) {
+        let symbol = &self.template.currency_symbol;
+        let cells: Vec<String> = self
+            .template
+            .columns
+            .iter()
+            .zip(sums)
+            .enumerate()
+            .map(|(index, (column, sum))| match sum {
+                Some(sum) => format_cell(Some(&Value::from(*sum)), column, symbol),
+                None if index == 0 => label.to_string(),
+                None => String::new(),
+            })
+            .collect();
+        let (lines, height) = self.measure_row(&cells);
+        self.ensure_space(height);
+        self.draw_row(lines, height, true);
+    }
+
+    fn sections(&mut self) {
+        let columns = &self.template.columns;
+        let mut grand: Vec<Option<f64>> = columns.iter().map(|c| c.total.then_some(0.0)).collect();
+        for section in &self.data.sections {
+            let mut sums: Vec<Option<f64>> =
+                columns.iter().map(|c| c.total.then_some(0.0)).collect();
+            // Keep a section title with its header and first row
+            let first_row = line_height(BODY_SIZE) * 2.0 + CELL_PADDING * 4.0;
+            if let Some(title) = &section.title {
+                self.ensure_space(line_height(12.0) + 2.0 + first_row);
+                self.y -= line_height(12.0);
+                self.text(self.left(), 12.0, true, title.clone());
+                self.y -= 2.0;
+            } else {
+                self.ensure_space(first_row);
+            }
+            self.header_row();
+
+            for row in &section.rows {
+                let cells: Vec<String> = columns
+                    .iter()
+                    .map(|c| format_cell(row.get(&c.key), c, &self.template.currency_symbol))
+                    .collect();
+                for (sum, column) in sums.iter_mut().zip(columns) {
+                    if let (Some(sum), Some(value)) =
+                        (sum.as_mut(), row.get(&column.key).and_then(numeric))
+                    {
+                        *sum += value;
+                    }
+                }
+                let (lines, height) = self.measure_row(&cells);
+                if self.y - height < self.bottom() {
+                    self.new_page();
+                    self.header_row();
+                }
+                self.draw_row(lines, height, false);
+            }
+
+            if sums.iter().any(Option::is_some) {
+                let label = self.template.subtotal_label.clone();
+                self.totals_row(&label, &sums);
+                for (total, sum) in grand.iter_mut().zip(&sums) {
+                    if let (Some(total), Some(sum)) = (total.as_mut(), sum) {
+                        *total += sum;
+                    }
+                }
+            }
+            self.y -= line_height(BODY_SIZE);
+        }
+
+        if grand.iter().any(Option::is_some) && !self.data.sections.is_empty() {
+            let label = self.template.total_label.clone();
+            self.totals_row(&label, &grand);
+        }
+    }
+
+    fn summary_and_notes(&mut self) {
+        for line in &self.data.summary {
+            self.ensure_space(line_height(10.0));
+            self.y -= line_height(10.0);
+            let value_x = self.right() - CELL_PADDING;
+            let value = line.value.clone();
+            let label_right = value_x - self.metrics.text_width(&value, 10.0, true) - 6.0;
+            self.text_right(label_right, 10.0, false, line.label.clone());
+            self.text_right(value_x, 10.0, true, value);
+        }
+        if let Some(notes) = &self.data.notes {
+            self.y -= line_height(BODY_SIZE);
+            self.ensure_space(line_height(11.0) + line_height(BODY_SIZE));
+            self.y -= line_height(11.0);
+            self.text(self.left(), 11.0, true, "Notes");
+            let width = self.right() - self.left();
+            for line in wrap(self.metrics, notes, width, BODY_SIZE, false) {
+                self.ensure_space(line_height(BODY_SIZE));
+                self.y -= line_height(BODY_SIZE);
+                self.text(self.left(), BODY_SIZE, false, line);
+            }
+        }
+    }
+
+    // Two across, each scaled to its column and no taller than PHOTO_MAX_HEIGHT
+    fn photos(&mut self, photos: &[Photo]) {
+        if photos.is_empty() {
+            return;
+        }
+        let column = (self.right() - self.left() - PHOTO_GAP) / 2.0;
+        let metrics = self.metrics;
+        let scale = |photo: &Photo| (column / photo.width).min(PHOTO_MAX_HEIGHT / photo.height);
+        let captions = |photo: &Photo| match &photo.caption {
+            Some(caption) => wrap(metrics, caption, column, 8.0, false),
+            None => Vec::new(),
+        };
+        self.y -= line_height(BODY_SIZE);
+        let first = &photos[0];
+        self.ensure_space(
+            line_height(11.0)
+                + 3.0
+                + first.height * scale(first)
+                + line_height(8.0) * captions(first).len() as f32,
+        );
+        self.y -= line_height(11.0);
+        self.text(self.left(), 11.0, true, "Photos");
+        self.y -= 3.0;
+        for (row, pair) in photos.chunks(2).enumerate() {
+            let image_height = pair
+                .iter()
+                .map(|photo| photo.height * scale(photo))
+                .fold(0.0, f32::max);
+            let caption_lines: Vec<Vec<String>> = pair.iter().map(captions).collect();
+            let caption_height =
+                line_height(8.0) * caption_lines.iter().map(Vec::len).max().unwrap_or(0) as f32;
+            self.ensure_space(image_height + caption_height);
+            let top = self.y;
+            for (offset, (photo, lines)) in pair.iter().zip(caption_lines).enumerate() {
+                let x = self.left() + offset as f32 * (column + PHOTO_GAP);
+                self.y = top - photo.height * scale(photo);
+                let (y, scale) = (self.y, scale(photo));
+                self.push(Op::Photo {
+                    index: row * 2 + offset,
+                    x,
+                    y,
+                    scale,
+                });
+                self.y = top - image_height;
+                for line in lines {
+                    self.y -= line_height(8.0);
+                    self.text(x, 8.0, false, line);
+                }
+            }
+            self.y = top - image_height - caption_height - PHOTO_GAP;
+        }
+    }
+
+    // Footers go in last, when the page count is known
+    fn footers(&mut self) {
+        let pages = self.pages.len();
+        let y = self.template.margin_mm;
+        let center = self.page_w / 2.0;
+        for (index, page) in self.pages.iter_mut().enumerate() {
+            if page.cover {
+                continue;
+            }
+            let text = self
+                .template
+                .footer
+                .replace("{page}", &(index + 1).to_string())
+                .replace("{pages}", &pages.to_string())
+                .replace("{title}", &self.data.title);
+            let x = center - self.metrics.text_width(&text, 8.0, false) / 2.0;
+            page.ops.push(Op::Text {
+                x,
+                y,
+                size: 8.0,
+                bold: false,
+                text,
+            });
+        }
+    }
+}
+
+fn draw(
+    layer: &PdfLayerReference,
+    op: Op,
+    regular: &IndirectFontRef,
+    bold: &IndirectFontRef,
+    logo: Option<&Logo>,
+    photos: &[Photo],
+) {
+    match op {
+        Op::Text {
+            x,
+            y,
+            size,
+            bold: is_bold,
+            text,
+        } => {
+            let font = if is_bold { bold } else { regular };
+            layer.use_text(text, size, Mm(x), Mm(y), font);
+        }
+        Op::Rule {
+            x1,
+            x2,
+            y,
+            thickness,
+        } => {
+            layer.set_outline_thickness(thickness);
+            layer.add_line(Line {
+                points: vec![
+                    (Point::new(Mm(x1), Mm(y)), false),
+                    (Point::new(Mm(x2), Mm(y)), false),
+                ],
+                is_closed: false,
+            });
+        }
+        Op::Fill { x, y, w, h, gray } => {
+            layer.set_fill_color(Color::Greyscale(Greyscale::new(gray, None)));
+            layer
+                .add_rect(Rect::new(Mm(x), Mm(y), Mm(x + w), Mm(y + h)).with_mode(PaintMode::Fill));
+            // Text is drawn with the fill colour, so put it back
+            layer.set_fill_color(Color::Greyscale(Greyscale::new(0.0, None)));
+        }
+        Op::Logo { x, y, scale } => {
+            if let Some(logo) = logo {
+                Image::from_dynamic_image(&logo.image).add_to_layer(
+                    layer.clone(),
+                    ImageTransform {
+                        translate_x: Some(Mm(x)),
+                        translate_y: Some(Mm(y)),
+                        scale_x: Some(scale),
+                        scale_y: Some(scale),
+                        dpi: Some(LOGO_DPI),
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+        Op::Photo { index, x, y, scale } => {
+            if let Some(photo) = photos.get(index) {
+                Image::from_dynamic_image(&photo.image).add_to_layer(
+                    layer.clone(),
+                    ImageTransform {
+                        translate_x: Some(Mm(x)),
+                        translate_y: Some(Mm(y)),
+                        scale_x: Some(scale),
+                        scale_y: Some(scale),
+                        dpi: Some(PHOTO_DPI),
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+    }
+}
+
+fn unreadable_font(err: ttf_parser::FaceParsingError) -> AppError {
+    AppError::InvalidInput(format!("cannot read the report font: {err}"))
+}
+
+/// Lay out and write the report, encrypted when `protection` is given; returns the page count.
+pub(crate) fn render(
+    data: &ReportData,
+    template: &ReportTemplate,
+    protection: Option<&PdfProtection>,
+    path: &Path,
+) -> AppResult<usize> {
+    let logo = template.logo.as_deref().map(load_logo).transpose()?;
+    let photos = data
+        .photos
+        .iter()
+        .map(load_photo)
+        .collect::<AppResult<Vec<_>>>()?;
+    let font = template
+        .font_family
+        .as_deref()
+        .map(fonts::load)
+        .transpose()?;
+    let metrics = match &font {
+        Some(font) => Metrics::Embedded {
+            regular: Box::new(Face::parse(&font.regular, 0).map_err(unreadable_font)?),
+            bold: font
+                .bold
+                .as_deref()
+                .map(|bold| Face::parse(bold, 0).map(Box::new).map_err(unreadable_font))
+                .transpose()?,
+        },
+        None => Metrics::Helvetica,
+    };
+
+    let mut composer = Composer::new(data, template, &metrics);
+    if template.cover_page {
+        composer.cover(logo.as_ref());
+        composer.new_page();
+    } else {
+        composer.new_page();
+        composer.title_block(logo.as_ref());
+    }
+    composer.body();
+    composer.sections();
+    composer.summary_and_notes();
+    composer.photos(&photos);
+    composer.footers();
+
+    let (page_w, page_h) = (composer.page_w, composer.page_h);
+    let (doc, first_page, first_layer) =
+        PdfDocument::new(&data.title, Mm(page_w), Mm(page_h), "Content");
+    let doc = doc.with_creator(concat!("Momentum ", env!("CARGO_PKG_VERSION")));
+    let (regular, bold) = match &font {
+        Some(font) => {
+            let regular = doc.add_external_font(font.regular.as_slice())?;
+            let bold = match &font.bold {
+                Some(bold) => doc.add_external_font(bold.as_slice())?,
+                None => regular.clone(),
+            };
+            (regular, bold)
+        }
+        None => (
+            doc.add_builtin_font(BuiltinFont::Helvetica)?,
+            doc.add_builtin_font(BuiltinFont::HelveticaBold)?,
+        ),
+    };
+
+    let count = composer.pages.len();
+    for (index, page) in composer.pages.into_iter().enumerate() {
+        let (page_index, layer_index) = if index == 0 {
+            (first_page, first_layer)
+        } else {
+            doc.add_page(Mm(page_w), Mm(page_h), "Content")
+        };
+        let layer = doc.get_page(page_index).get_layer(layer_index);
+        for op in page.ops {
+            draw(&layer, op, &regular, &bold, logo.as_ref(), &photos);
+        }
+    }
+    let mut bytes = doc.save_to_bytes()?;
+    if let Some(protection) = protection {
+        bytes = protect::protect(&bytes, protection)?;
+    }
+    std::fs::write(path, bytes)?;
+    Ok(count)
+}
@@ -0,0 +1,248 @@
+//! Password protection for generated PDFs with the standard security handler, revision 6.
+//!
+//! Strings and streams are sealed with AES-256 as Acrobat X and every current viewer expect.
+//! The user password opens the file; the owner password lifts the restrictions, which
+//! viewers enforce on their honour. Passwords are used as UTF-8 without SASLprep.
+
+use aes::cipher::block_padding::{NoPadding, Pkcs7};
+use aes::cipher::{BlockEncrypt, BlockEncryptMut, KeyInit, KeyIvInit};
+use aes::{Aes128, Aes256};
+use printpdf::lopdf::{Dictionary, Document, Object, StringFormat};
+use rand::Rng;
+use serde::Deserialize;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::error::{AppError, AppResult};
+
+// Longest password revision 6 uses, in bytes
+const MAX_PASSWORD_LEN: usize = 127;
+
+// Bits 7-8 and 13-32 are reserved and must be set; bit 10, accessibility extraction, is
+// always granted since PDF 2.0 deprecates restricting it
+const BASE_PERMISSIONS: u32 = 0xFFFF_F0C0 | 0x200;
+const PRINT: u32 = 0x4 | 0x800;
+const COPY: u32 = 0x10;
+const EDIT: u32 = 0x8 | 0x20 | 0x100 | 0x400;
+
+/// Passwords and restrictions for a generated PDF.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PdfProtection {
+    /// Required to open the file; without one it opens freely but stays restricted.
+    pub user_password: Option<String>,
+    /// Lifts the restrictions; a random one is used when unset, so they cannot be lifted.
+    pub owner_password: Option<String>,
+    pub allow_printing: bool,
+    pub allow_copying: bool,
+    pub allow_editing: bool,
+}
+
+impl Default for PdfProtection {
+    fn default() -> Self {
+        Self {
+            user_password: None,
+            owner_password: None,
+            allow_printing: true,
+            allow_copying: false,
+            allow_editing: false,
+        }
+    }
+}
+
+impl PdfProtection {
+    fn permissions(&self) -> u32 {
+        let mut permissions = BASE_PERMISSIONS;
+        if self.allow_printing {
+            permissions |= PRINT;
+        }
+        if self.allow_copying {
+            permissions |= COPY;
+        }
+        if self.allow_editing {
+            permissions |= EDIT;
+        }
+        permissions
+    }
+}
+
+fn random<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    rand::rng().fill(&mut bytes[..]);
+    bytes
+}
+
+fn password_bytes(password: &str) -> &[u8] {
+    let mut end = password.len().min(MAX_PASSWORD_LEN);
+    while !password.is_char_boundary(end) {
+        end -= 1;
+    }
+    &password.as_bytes()[..end]
+}
+
+// Algorithm 2.B of ISO 32000-2: a SHA-2 chain whose length depends on its own output
+fn hash(password: &[u8], salt: &[u8], user_key: &[u8]) -> [u8; 32] {
+    let mut k = Sha256::new()
+        .chain_update(password)
+        .chain_update(salt)
+        .chain_update(user_key)
+        .finalize()
+        .to_vec();
+    let mut round = 0;
+    loop {
+        let mut k1 = Vec::with_capacity(64 * (password.len() + k.len() + user_key.len()));
+        for _ in 0..64 {
+            k1.extend_from_slice(password);
+            k1.extend_from_slice(&k);
+            k1.extend_from_slice(user_key);
+        }
+        let e = cbc::Encryptor::<Aes128>::new(k[..16].into(), k[16..32].into())
+            .encrypt_padded_vec_mut::<NoPadding>(&k1);
+        // The first 16 bytes as a big-endian number mod 3, since 256 ≡ 1 (mod 3)
+        k = match e[..16].iter().map(|&b| b as u32).sum::<u32>() % 3 {
+            0 => Sha256::digest(&e).to_vec(),
+            1 => Sha384::digest(&e).to_vec(),
+            _ => Sha512::digest(&e).to_vec(),
+        };
+        round += 1;
+        if round >= 64 && e.last().is_some_and(|&last| last as usize <= round - 32) {
+            break;
+        }
+    }
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&k[..32]);
+    out
+}
+
+fn wrap_key(kek: &[u8; 32], file_key: &[u8; 32]) -> Vec<u8> {
+    cbc::Encryptor::<Aes256>::new(kek.into(), &[0u8; 16].into())
+        .encrypt_padded_vec_mut::<NoPadding>(file_key)
+}
+
+// Every string and stream gets its own IV in front of the ciphertext
+fn seal(file_key: &[u8; 32], plaintext: &[u8]) -> Vec<u8> {
+    let iv = random::<16>();
+    let mut sealed = iv.to_vec();
+    sealed.extend(
+        cbc::Encryptor::<Aes256>::new(file_key.into(), &iv.into())
+            .encrypt_padded_vec_mut::<Pkcs7>(plaintext),
+    );
+    sealed
+}
+
+fn seal_object(file_key: &[u8; 32], object: &mut Object) {
+    match object {
+        Object::String(bytes, format) => {
+            *bytes = seal(file_key, bytes);
+            *format = StringFormat::Hexadecimal;
+        }
+        Object::Array(items) => items
+            .iter_mut()
+            .for_each(|item| seal_object(file_key, item)),
+        Object::Dictionary(dict) => dict
+            .iter_mut()
+            .for_each(|(_, value)| seal_object(file_key, value)),
+        Object::Stream(stream) => {
+            stream
+                .dict
+                .iter_mut()
+                .for_each(|(_, value)| seal_object(file_key, value));
+            let content = seal(file_key, &stream.content);
+            stream.set_content(content);
+        }
+        _ => {}
+    }
+}
+
+/// Encrypt a finished PDF; returns the protected file.
+pub(crate) fn protect(pdf: &[u8], protection: &PdfProtection) -> AppResult<Vec<u8>> {
+    let fail =
+        |e: printpdf::lopdf::Error| AppError::Internal(format!("cannot protect the PDF: {e}"));
+    let mut doc = Document::load_mem(pdf).map_err(fail)?;
+    if !doc.trailer.has(b"ID") {
+        let id = random::<16>().to_vec();
+        doc.trailer.set(
+            "ID",
+            Object::Array(vec![
+                Object::String(id.clone(), StringFormat::Hexadecimal),
+                Object::String(id, StringFormat::Hexadecimal),
+            ]),
+        );
+    }
+
+    let file_key = random::<32>();
+    let user_password = password_bytes(protection.user_password.as_deref().unwrap_or(""));
+    let random_owner;
+    let owner_password = match protection.owner_password.as_deref() {
+        Some(password) => password_bytes(password),
+        None => {
+            random_owner = random::<32>();
+            &random_owner[..]
+        }
+    };
+
+    // U and O are a validation hash followed by its salt and the key-wrapping salt
+    let (user_validation, user_key_salt) = (random::<8>(), random::<8>());
+    let mut u = hash(user_password, &user_validation, &[]).to_vec();
+    u.extend_from_slice(&user_validation);
+    u.extend_from_slice(&user_key_salt);
+    let ue = wrap_key(&hash(user_password, &user_key_salt, &[]), &file_key);
+
+    let (owner_validation, owner_key_salt) = (random::<8>(), random::<8>());
+    let mut o = hash(owner_password, &owner_validation, &u).to_vec();
+    o.extend_from_slice(&owner_validation);
+    o.extend_from_slice(&owner_key_salt);
+    let oe = wrap_key(&hash(owner_password, &owner_key_salt, &u), &file_key);
+
+    let permissions = protection.permissions();
+    let mut perms = [0u8; 16];
+    perms[..4].copy_from_slice(&permissions.to_le_bytes());
+    perms[4..8].copy_from_slice(&[0xFF; 4]);
+    perms[8..12].copy_from_slice(b"Tadb");
+    perms[12..].copy_from_slice(&random::<4>());
+    Aes256::new(&file_key.into()).encrypt_block((&mut perms).into());
+
+    for object in doc.objects.values_mut() {
+        seal_object(&file_key, object);
+    }
+
+    let mut filter = Dictionary::new();
+    filter.set("AuthEvent", Object::Name(b"DocOpen".to_vec()));
+    filter.set("CFM", Object::Name(b"AESV3".to_vec()));
+    filter.set("Length", 32);
+    let mut filters = Dictionary::new();
+    filters.set("StdCF", filter);
+    let mut encrypt = Dictionary::new();
+    encrypt.set("Filter", Object::Name(b"Standard".to_vec()));
+    encrypt.set("V", 5);
+    encrypt.set("R", 6);
+    encrypt.set("Length", 256);
+    encrypt.set("CF", filters);
+    encrypt.set("StmF", Object::Name(b"StdCF".to_vec()));
+    encrypt.set("StrF", Object::Name(b"StdCF".to_vec()));
+    encrypt.set("U", Object::String(u, StringFormat::Hexadecimal));
+    encrypt.set("O", Object::String(o, StringFormat::Hexadecimal));
+    encrypt.set("UE", Object::String(ue, StringFormat::Hexadecimal));
+    encrypt.set("OE", Object::String(oe, StringFormat::Hexadecimal));
+    encrypt.set("P", permissions as i32 as i64);
+    encrypt.set(
+        "Perms",
+        Object::String(perms.to_vec(), StringFormat::Hexadecimal),
+    );
+    let encrypt_id = doc.add_object(encrypt);
+    doc.trailer.set("Encrypt", Object::Reference(encrypt_id));
+
+    // Revision 6 arrived as Adobe extension level 8 on top of PDF 1.7
+    doc.version = "1.7".into();
+    let mut adbe = Dictionary::new();
+    adbe.set("BaseVersion", Object::Name(b"1.7".to_vec()));
+    adbe.set("ExtensionLevel", 8);
+    let mut extensions = Dictionary::new();
+    extensions.set("ADBE", adbe);
+    doc.catalog_mut()
+        .map_err(fail)?
+        .set("Extensions", extensions);
+
+    let mut out = Vec::new();
+    doc.save_to(&mut out)?;
+    Ok(out)
+}
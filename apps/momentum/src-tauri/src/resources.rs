@@ -0,0 +1,488 @@
+//! Periodic samples of the app's own memory and CPU use, webview processes included.
+//!
+//! A background task samples every few seconds and records peaks with [`metrics`]. When
+//! the total crosses the configured warning or critical level, `resources:memory-pressure`
+//! tells the frontend to drop caches such as decoded plan pages and thumbnails; it fires
+//! again once use falls back below a level, with some slack so a total hovering at the
+//! line does not flap.
+//!
+//! Webview processes are found among the app's children on Linux and Windows. macOS and
+//! iOS run WebKit's processes as system services, and Android's renderer is forked from the
+//! zygote, so only the app process is counted there; iOS reports nothing at all.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::now_ms;
+use crate::error::AppResult;
+use crate::metrics;
+use crate::state::AppState;
+
+/// Emitted with a [`MemoryPressureChange`] when memory use moves between levels.
+pub const MEMORY_PRESSURE_EVENT: &str = "resources:memory-pressure";
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+const MIB: u64 = 1024 * 1024;
+// A level is left only once use drops this far below its threshold
+const RELEASE_RATIO: f64 = 0.9;
+
+/// Memory thresholds for pressure warnings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ResourceSettings {
+    /// App and webview memory in MiB above which the frontend should trim caches.
+    pub memory_warning_mb: u64,
+    /// Memory in MiB above which it should release everything it can rebuild.
+    pub memory_critical_mb: u64,
+}
+
+impl Default for ResourceSettings {
+    fn default() -> Self {
+        Self {
+            memory_warning_mb: 1536,
+            memory_critical_mb: 3072,
+        }
+    }
+}
+
+/// How close memory use is to the configured limits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MemoryPressure {
+    #[default]
+    Normal,
+    Warning,
+    Critical,
+}
+
+/// Memory and CPU of one process or a group of them.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessUsage {
+    /// Resident memory.
+    pub memory_bytes: u64,
+    /// Percent of one core since the previous sample.
+    pub cpu_percent: f64,
+}
+
+/// One sample.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceUsage {
+    pub at: i64,
+    pub app: ProcessUsage,
+    /// Summed over the webview's processes; `None` where they cannot be seen.
+    pub webview: Option<ProcessUsage>,
+    pub webview_processes: usize,
+    pub total_memory_bytes: u64,
+    pub pressure: MemoryPressure,
+}
+
+/// Payload for `resources:memory-pressure`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryPressureChange {
+    pub level: MemoryPressure,
+    pub previous: MemoryPressure,
+    pub total_memory_bytes: u64,
+    /// The threshold of `level`, or of the level just left when back to normal.
+    pub threshold_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ProcessStats {
+    memory_bytes: u64,
+    cpu_time: Duration,
+}
+
+#[derive(Default)]
+struct Sampler {
+    // CPU time per process at the previous sample, to turn totals into a rate
+    previous: HashMap<u32, Duration>,
+    sampled_at: Option<Instant>,
+    pressure: MemoryPressure,
+    latest: Option<ResourceUsage>,
+}
+
+/// The monitor's latest sample, registered in app state.
+#[derive(Default)]
+pub struct ResourceMonitor {
+    inner: Mutex<Sampler>,
+}
+
+impl Sampler {
+    fn usage(&mut self, pids: &[u32], elapsed: Option<Duration>) -> ProcessUsage {
+        let mut usage = ProcessUsage::default();
+        for &pid in pids {
+            let Some(stats) = os::process_stats(pid) else {
+                continue;
+            };
+            usage.memory_bytes += stats.memory_bytes;
+            let before = self.previous.insert(pid, stats.cpu_time);
+            if let (Some(before), Some(elapsed)) = (before, elapsed) {
+                let busy = stats.cpu_time.saturating_sub(before);
+                usage.cpu_percent += 100.0 * busy.as_secs_f64() / elapsed.as_secs_f64();
+            }
+        }
+        usage
+    }
+
+    fn sample(
+        &mut self,
+        settings: &ResourceSettings,
+    ) -> (ResourceUsage, Option<MemoryPressureChange>) {
+        let now = Instant::now();
+        let elapsed = self
+            .sampled_at
+            .replace(now)
+            .map(|at| now - at)
+            .filter(|elapsed| !elapsed.is_zero());
+        let pid = std::process::id();
+        let webview_pids = os::webview_pids(pid);
+        let mut seen: Vec<u32> = webview_pids.iter().flatten().copied().collect();
+        let app = self.usage(&[pid], elapsed);
+        let webview = webview_pids.map(|pids| self.usage(&pids, elapsed));
+        // Exited processes would otherwise pile up in the map
+        seen.push(pid);
+        self.previous.retain(|pid, _| seen.contains(pid));
+
+        let total = app.memory_bytes + webview.map_or(0, |w| w.memory_bytes);
+        let previous = self.pressure;
+        let level = pressure(total, previous, settings);
+        self.pressure = level;
+        let usage = ResourceUsage {
+            at: now_ms(),
+            app,
+            webview,
+            webview_processes: seen.len() - 1,
+            total_memory_bytes: total,
+            pressure: level,
+        };
+        self.latest = Some(usage.clone());
+        let change = (level != previous).then(|| MemoryPressureChange {
+            level,
+            previous,
+            total_memory_bytes: total,
+            threshold_bytes: threshold(level.max(previous), settings),
+        });
+        (usage, change)
+    }
+}
+
+fn threshold(level: MemoryPressure, settings: &ResourceSettings) -> u64 {
+    match level {
+        MemoryPressure::Normal => 0,
+        MemoryPressure::Warning => settings.memory_warning_mb * MIB,
+        MemoryPressure::Critical => settings.memory_critical_mb * MIB,
+    }
+}
+
+fn pressure(total: u64, current: MemoryPressure, settings: &ResourceSettings) -> MemoryPressure {
+    let reached = |level| total >= threshold(level, settings);
+    let held = |level| {
+        level <= current && total as f64 >= threshold(level, settings) as f64 * RELEASE_RATIO
+    };
+    [MemoryPressure::Critical, MemoryPressure::Warning]
+        .into_iter()
+        .find(|&level| reached(level) || held(level))
+        .unwrap_or(MemoryPressure::Normal)
+}
+
+/// Start sampling; the first CPU figures arrive with the second sample.
+pub fn start(app: &AppHandle) {
+    app.manage(ResourceMonitor::default());
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let handle = app.clone();
+            let sampled = tauri::async_runtime::spawn_blocking(move || {
+                let settings = handle.state::<AppState>().config().resources;
+                let monitor = handle.state::<ResourceMonitor>();
+                let mut sampler = monitor.inner.lock().unwrap_or_else(|e| e.into_inner());
+                sampler.sample(&settings)
+            })
+            .await;
+            match sampled {
+                Ok((usage, change)) => {
+                    let cpu = usage.app.cpu_percent + usage.webview.map_or(0.0, |w| w.cpu_percent);
+                    metrics::record_resources(usage.at, usage.total_memory_bytes, cpu);
+                    if let Some(change) = change {
+                        tracing::info!(
+                            level = ?change.level,
+                            total_mb = change.total_memory_bytes / MIB,
+                            "memory pressure changed"
+                        );
+                        let _ = app.emit(MEMORY_PRESSURE_EVENT, change);
+                    }
+                }
+                Err(err) => tracing::warn!(%err, "resource sample failed"),
+            }
+            tokio::time::sleep(SAMPLE_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod os {
+    use std::fs;
+    use std::time::Duration;
+
+    use super::ProcessStats;
+
+    // USER_HZ, fixed at 100 by the kernel ABI on every architecture we ship
+    const TICKS_PER_SECOND: u64 = 100;
+    const PAGE_SIZE: u64 = 4096;
+
+    // Fields after the parenthesized command name, which may itself contain spaces
+    fn stat_fields(pid: u32) -> Option<Vec<String>> {
+        let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+        let (_, rest) = stat.rsplit_once(')')?;
+        Some(rest.split_whitespace().map(str::to_string).collect())
+    }
+
+    pub fn process_stats(pid: u32) -> Option<ProcessStats> {
+        let fields = stat_fields(pid)?;
+        // utime and stime are fields 14 and 15 of stat, 12 and 13 after the name
+        let ticks: u64 =
+            fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?;
+        let statm = fs::read_to_string(format!("/proc/{pid}/statm")).ok()?;
+        let resident: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+        Some(ProcessStats {
+            memory_bytes: resident * PAGE_SIZE,
+            cpu_time: Duration::from_millis(ticks * 1000 / TICKS_PER_SECOND),
+        })
+    }
+
+    // WebKitGTK's web and network processes are children of the app; Android's renderer is
+    // forked from the zygote instead
+    #[cfg(target_os = "linux")]
+    pub fn webview_pids(root: u32) -> Option<Vec<u32>> {
+        let mut processes = Vec::new();
+        for entry in fs::read_dir("/proc").ok()?.flatten() {
+            let Some(pid) = entry
+                .file_name()
+                .to_str()
+                .and_then(|n| n.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            let Ok(stat) = fs::read_to_string(format!("/proc/{pid}/stat")) else {
+                continue;
+            };
+            let name = stat
+                .split_once('(')
+                .and_then(|(_, rest)| rest.rsplit_once(')'))
+                .map_or("", |(name, _)| name);
+            if let Some(ppid) = stat_fields(pid).and_then(|f| f.get(1)?.parse::<u32>().ok()) {
+                processes.push((pid, ppid, name.to_string()));
+            }
+        }
+        Some(super::webview_tree(root, &processes, |name| {
+            name.starts_with("WebKit")
+        }))
+    }
+
+    #[cfg(target_os = "android")]
+    pub fn webview_pids(_root: u32) -> Option<Vec<u32>> {
+        None
+    }
+}
+
+#[cfg(windows)]
+mod os {
+    use std::time::Duration;
+
+    use windows::Win32::Foundation::{CloseHandle, FILETIME};
+    use windows::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+        TH32CS_SNAPPROCESS,
+    };
+    use windows::Win32::System::ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS};
+    use windows::Win32::System::Threading::{
+        GetProcessTimes, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    use super::ProcessStats;
+
+    // FILETIME counts 100 ns intervals
+    fn duration(time: FILETIME) -> Duration {
+        let ticks = (u64::from(time.dwHighDateTime) << 32) | u64::from(time.dwLowDateTime);
+        Duration::from_nanos(ticks * 100)
+    }
+
+    pub fn process_stats(pid: u32) -> Option<ProcessStats> {
+        // SAFETY: the handle is checked and closed below; the out-params are valid locals
+        unsafe {
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+            let mut counters = PROCESS_MEMORY_COUNTERS {
+                cb: std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32,
+                ..Default::default()
+            };
+            let memory = GetProcessMemoryInfo(process, &mut counters, counters.cb);
+            let (mut created, mut exited, mut kernel, mut user) = Default::default();
+            let times = GetProcessTimes(process, &mut created, &mut exited, &mut kernel, &mut user);
+            let _ = CloseHandle(process);
+            memory.ok()?;
+            times.ok()?;
+            Some(ProcessStats {
+                memory_bytes: counters.WorkingSetSize as u64,
+                cpu_time: duration(kernel) + duration(user),
+            })
+        }
+    }
+
+    // WebView2's browser process is a child of the app and spawns the renderers
+    pub fn webview_pids(root: u32) -> Option<Vec<u32>> {
+        let mut processes = Vec::new();
+        // SAFETY: the snapshot handle is closed below; `entry` has its size set
+        unsafe {
+            let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0).ok()?;
+            let mut entry = PROCESSENTRY32W {
+                dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
+                ..Default::default()
+            };
+            let mut more = Process32FirstW(snapshot, &mut entry).is_ok();
+            while more {
+                let len = entry.szExeFile.iter().position(|&c| c == 0).unwrap_or(0);
+                let name = String::from_utf16_lossy(&entry.szExeFile[..len]);
+                processes.push((entry.th32ProcessID, entry.th32ParentProcessID, name));
+                more = Process32NextW(snapshot, &mut entry).is_ok();
+            }
+            let _ = CloseHandle(snapshot);
+        }
+        Some(super::webview_tree(root, &processes, |name| {
+            name.eq_ignore_ascii_case("msedgewebview2.exe")
+        }))
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use std::ffi::{c_int, c_void};
+    use std::time::Duration;
+
+    use super::ProcessStats;
+
+    const PROC_PIDTASKINFO: c_int = 4;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct ProcTaskInfo {
+        virtual_size: u64,
+        resident_size: u64,
+        total_user: u64,
+        total_system: u64,
+        threads_user: u64,
+        threads_system: u64,
+        policy: i32,
+        faults: i32,
+        pageins: i32,
+        cow_faults: i32,
+        messages_sent: i32,
+        messages_received: i32,
+        syscalls_mach: i32,
+        syscalls_unix: i32,
+        csw: i32,
+        threadnum: i32,
+        numrunning: i32,
+        priority: i32,
+    }
+
+    extern "C" {
+        fn proc_pidinfo(
+            pid: c_int,
+            flavor: c_int,
+            arg: u64,
+            buffer: *mut c_void,
+            buffersize: c_int,
+        ) -> c_int;
+    }
+
+    pub fn process_stats(pid: u32) -> Option<ProcessStats> {
+        let mut info = ProcTaskInfo::default();
+        let size = std::mem::size_of::<ProcTaskInfo>() as c_int;
+        // SAFETY: `info` is a writable PROC_PIDTASKINFO buffer of `size` bytes
+        let written = unsafe {
+            proc_pidinfo(
+                pid as c_int,
+                PROC_PIDTASKINFO,
+                0,
+                (&mut info as *mut ProcTaskInfo).cast(),
+                size,
+            )
+        };
+        // Times are in Mach absolute units, which are nanoseconds on Intel; Apple silicon
+        // counts 125/3 ns per unit
+        let scale = if cfg!(target_arch = "aarch64") {
+            125.0 / 3.0
+        } else {
+            1.0
+        };
+        (written == size).then(|| ProcessStats {
+            memory_bytes: info.resident_size,
+            cpu_time: Duration::from_nanos(
+                ((info.total_user + info.total_system) as f64 * scale) as u64,
+            ),
+        })
+    }
+
+    pub fn webview_pids(_root: u32) -> Option<Vec<u32>> {
+        None
+    }
+}
+
+#[cfg(target_os = "ios")]
+mod os {
+    use super::ProcessStats;
+
+    pub fn process_stats(_pid: u32) -> Option<ProcessStats> {
+        None
+    }
+
+    pub fn webview_pids(_root: u32) -> Option<Vec<u32>> {
+        None
+    }
+}
+
+// Children of `root` that `is_webview` accepts by executable name, and everything below
+// them, from a list of (pid, parent, name)
+#[cfg(any(target_os = "linux", windows))]
+fn webview_tree(
+    root: u32,
+    processes: &[(u32, u32, String)],
+    is_webview: fn(&str) -> bool,
+) -> Vec<u32> {
+    let mut found: Vec<u32> = processes
+        .iter()
+        .filter(|(pid, parent, name)| *parent == root && *pid != root && is_webview(name))
+        .map(|(pid, _, _)| *pid)
+        .collect();
+    let mut index = 0;
+    while index < found.len() {
+        let parent = found[index];
+        for (pid, ppid, _) in processes {
+            if *ppid == parent && !found.contains(pid) && *pid != root {
+                found.push(*pid);
+            }
+        }
+        index += 1;
+    }
+    found
+}
+
+/// The latest sample, or `None` before the first one.
+#[tauri::command]
+pub async fn get_resource_usage(
+    monitor: State<'_, ResourceMonitor>,
+) -> AppResult<Option<ResourceUsage>> {
+    Ok(monitor
+        .inner
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .latest
+        .clone())
+}
@@ -0,0 +1,278 @@
+//! Reading QR and Code 128 labels from a photo.
+//!
+//! `scan_code` takes a shot from the device camera, a frame the webview already captured, or
+//! an image file, and returns every code it can read in it. QR codes are found and decoded by
+//! `rqrr`, Code 128 bars by `rxing`, which also reads them across rows and columns and upside
+//! down; this module only loads the image and merges what the two report.
+//!
+//! Nothing found is an empty list rather than an error, so the caller can just ask again.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::imageops::FilterType;
+use image::GrayImage;
+use rxing::{BarcodeFormat, DecodeHints};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::error::{AppError, AppResult};
+use crate::photos;
+
+// Plenty for a label at arm's length; larger photos are downscaled first
+const MAX_DIM: u32 = 2000;
+
+/// Where the image to scan comes from.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ScanSource {
+    /// Open the device camera; mobile only.
+    Camera,
+    /// An image file on disk.
+    File { path: PathBuf },
+    /// An encoded image as base64, with or without a `data:` URL prefix.
+    Frame { data: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CodeFormat {
+    Qr,
+    Code128,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScannedCode {
+    pub format: CodeFormat,
+    pub text: String,
+}
+
+/// Read the QR and Code 128 codes in an image, QR codes first.
+///
+/// Fails with `CANCELLED` when the user closes the camera without a shot.
+#[tauri::command]
+pub async fn scan_code(app: AppHandle, source: ScanSource) -> AppResult<Vec<ScannedCode>> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let bytes = match source {
+            ScanSource::Camera => photos::take_picture(&app)?,
+            ScanSource::File { path } => fs::read(&path)?,
+            ScanSource::Frame { data } => {
+                let data = match data.strip_prefix("data:") {
+                    Some(url) => url.split_once(',').map_or(url, |(_, data)| data),
+                    None => &data,
+                };
+                STANDARD
+                    .decode(data.trim())
+                    .map_err(|e| AppError::InvalidInput(format!("the frame is not base64: {e}")))?
+            }
+        };
+        let image = image::load_from_memory(&bytes)
+            .map_err(|e| AppError::InvalidInput(format!("cannot read the image: {e}")))?;
+        let image = if image.width().max(image.height()) > MAX_DIM {
+            image.resize(MAX_DIM, MAX_DIM, FilterType::Triangle)
+        } else {
+            image
+        };
+        Ok(scan(&image.to_luma8()))
+    })
+    .await?
+}
+
+fn scan(image: &GrayImage) -> Vec<ScannedCode> {
+    let mut codes: Vec<ScannedCode> = Vec::new();
+    let found = qr_codes(image)
+        .into_iter()
+        .map(|text| (CodeFormat::Qr, text))
+        .chain(
+            code128(image)
+                .into_iter()
+                .map(|text| (CodeFormat::Code128, text)),
+        );
+    for (format, text) in found {
+        let code = ScannedCode { format, text };
+        if !codes.contains(&code) {
+            codes.push(code);
+        }
+    }
+    codes
+}
+
+// Labels are nearly always UTF-8 or plain ASCII; anything else is read as Latin-1
+fn text(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes)
+        .unwrap_or_else(|e| e.into_bytes().into_iter().map(char::from).collect())
+}
+
+fn qr_codes(image: &GrayImage) -> Vec<String> {
+    let mut prepared = rqrr::PreparedImage::prepare(image.clone());
+    prepared
+        .detect_grids()
+        .into_iter()
+        .filter_map(|grid| {
+            let mut data = Vec::new();
+            // A grid that fails to decode was a false finder match or too damaged to read
+            grid.decode_to(&mut data).ok().map(|_| text(data))
+        })
+        .collect()
+}
+
+fn code128(image: &GrayImage) -> Vec<String> {
+    let mut hints = DecodeHints {
+        PossibleFormats: Some(HashSet::from([BarcodeFormat::CODE_128])),
+        TryHarder: Some(true),
+        ..Default::default()
+    };
+    let (width, height) = image.dimensions();
+    // Finding nothing comes back as an error too
+    rxing::helpers::detect_multiple_in_luma_with_hints(
+        image.as_raw().clone(),
+        width,
+        height,
+        &mut hints,
+    )
+    .map(|results| {
+        results
+            .iter()
+            .map(|result| result.getText().to_string())
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use barcoders::sym::code128::Code128;
+    use image::Luma;
+
+    use super::*;
+    use crate::qr::{QrCode, QrEcc};
+
+    const QUIET: usize = 10;
+
+    // Modules from an independent encoder; `data` starts with its code set, À, Ɓ, or Ć
+    fn code128_modules(data: &str) -> Vec<u8> {
+        Code128::new(data).unwrap().encode()
+    }
+
+    // Draws `modules` between quiet zones, `unit` pixels per module
+    fn barcode_image(modules: &[u8], unit: u32) -> GrayImage {
+        let width = (modules.len() + QUIET * 2) as u32 * unit;
+        GrayImage::from_fn(width, 80, |x, _| {
+            let module = (x / unit) as usize;
+            let dark = module
+                .checked_sub(QUIET)
+                .and_then(|i| modules.get(i))
+                .is_some_and(|&m| m == 1);
+            Luma([if dark { 0 } else { 255 }])
+        })
+    }
+
+    fn qr_image(text: &str, ecc: QrEcc) -> GrayImage {
+        let png = QrCode::encode(text.as_bytes(), ecc)
+            .unwrap()
+            .to_png(400, 4)
+            .unwrap();
+        image::load_from_memory(&png).unwrap().to_luma8()
+    }
+
+    fn found(format: CodeFormat, text: &str) -> Vec<ScannedCode> {
+        vec![ScannedCode {
+            format,
+            text: text.to_string(),
+        }]
+    }
+
+    #[test]
+    fn a_rendered_code128_label_scans() {
+        let image = barcode_image(&code128_modules("ƁMAT-0042/b"), 3);
+        assert_eq!(scan(&image), found(CodeFormat::Code128, "MAT-0042/b"));
+    }
+
+    #[test]
+    fn an_upside_down_code128_label_scans() {
+        let mut modules = code128_modules("ƁBin 17");
+        modules.reverse();
+        let image = barcode_image(&modules, 3);
+        assert_eq!(scan(&image), found(CodeFormat::Code128, "Bin 17"));
+    }
+
+    #[test]
+    fn code_sets_decode() {
+        // Pairs of digits in C, then letters in B, and a tab only A holds
+        let image = barcode_image(&code128_modules("Ć1234ƁxÀ\tX"), 3);
+        assert_eq!(scan(&image), found(CodeFormat::Code128, "1234x\tX"));
+    }
+
+    #[test]
+    fn a_scratched_code128_label_still_scans() {
+        let mut image = barcode_image(&code128_modules("ƁLOT 5591-C"), 3);
+        let (width, height) = image.dimensions();
+        // A light scratch across the upper rows and dark specks below it
+        for y in 0..height / 3 {
+            for x in (0..width).filter(|x| (x + y) % 9 < 4) {
+                image.put_pixel(x, y, Luma([255]));
+            }
+        }
+        for y in (height / 2..height).step_by(7) {
+            for x in (0..width).step_by(23) {
+                image.put_pixel(x, y, Luma([0]));
+            }
+        }
+        assert_eq!(scan(&image), found(CodeFormat::Code128, "LOT 5591-C"));
+    }
+
+    #[test]
+    fn a_rendered_qr_code_scans() {
+        let text = "https://example.com/materials/1234567890?lot=ABCDEF";
+        assert_eq!(
+            scan(&qr_image(text, QrEcc::Medium)),
+            found(CodeFormat::Qr, text)
+        );
+    }
+
+    #[test]
+    fn a_partly_covered_qr_code_scans() {
+        let text = "pairing code 418-227";
+        let mut image = qr_image(text, QrEcc::High);
+        // A smudge over the middle, clear of the finder patterns
+        let (width, height) = image.dimensions();
+        for y in height * 9 / 20..height * 11 / 20 {
+            for x in width * 2 / 5..width * 3 / 5 {
+                image.put_pixel(x, y, Luma([0]));
+            }
+        }
+        assert_eq!(scan(&image), found(CodeFormat::Qr, text));
+    }
+
+    #[test]
+    fn both_kinds_in_one_photo_are_listed_qr_first() {
+        let qr = qr_image("site 12", QrEcc::Medium);
+        let bars = barcode_image(&code128_modules("ƁPO 88213"), 3);
+        let mut image = GrayImage::from_pixel(qr.width() + bars.width(), qr.height(), Luma([255]));
+        image::imageops::replace(&mut image, &bars, 0, 0);
+        image::imageops::replace(&mut image, &qr, i64::from(bars.width()), 0);
+        let codes = scan(&image);
+        assert_eq!(
+            codes,
+            [
+                ScannedCode {
+                    format: CodeFormat::Qr,
+                    text: "site 12".into(),
+                },
+                ScannedCode {
+                    format: CodeFormat::Code128,
+                    text: "PO 88213".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn a_blank_image_has_no_codes() {
+        assert!(scan(&GrayImage::from_pixel(200, 200, Luma([255]))).is_empty());
+    }
+}
@@ -0,0 +1,291 @@
+//! Full-text search over project content backed by a tantivy index on disk.
+
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tantivy::collector::TopDocs;
+use tantivy::query::{BooleanQuery, Occur, Query, QueryParser, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, Schema, Value, STORED, STRING, TEXT};
+use tantivy::{doc, Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::background::{self, Work};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::jobs::Scheduler;
+
+const INDEX_DIR_NAME: &str = "search_index";
+const WRITER_HEAP_BYTES: usize = 50_000_000;
+const PROGRESS_INTERVAL: usize = 500;
+
+/// Job kind for the nightly full reindex.
+pub const REBUILD_JOB: &str = "search.rebuild";
+const REBUILD_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Emitted while `search_rebuild` reindexes the database.
+pub const PROGRESS_EVENT: &str = "search:progress";
+
+#[derive(Clone, Copy)]
+struct Fields {
+    id: Field,
+    kind: Field,
+    project_id: Field,
+    title: Field,
+    body: Field,
+}
+
+/// Search index handle registered in app state.
+pub struct SearchIndex {
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    fields: Fields,
+}
+
+/// A document submitted for indexing.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchDocument {
+    pub id: String,
+    pub kind: String,
+    pub project_id: Option<String>,
+    pub title: String,
+    #[serde(default)]
+    pub body: String,
+}
+
+/// A ranked search result.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub id: String,
+    pub kind: String,
+    pub project_id: Option<String>,
+    pub title: String,
+    pub score: f32,
+}
+
+/// Payload for `search:progress`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexProgress {
+    pub indexed: usize,
+    pub total: usize,
+}
+
+impl SearchIndex {
+    fn schema() -> (Schema, Fields) {
+        let mut builder = Schema::builder();
+        let fields = Fields {
+            id: builder.add_text_field("id", STRING | STORED),
+            kind: builder.add_text_field("kind", STRING | STORED),
+            project_id: builder.add_text_field("project_id", STRING | STORED),
+            title: builder.add_text_field("title", TEXT | STORED),
+            body: builder.add_text_field("body", TEXT),
+        };
+        (builder.build(), fields)
+    }
+
+    fn add(&self, writer: &IndexWriter, document: SearchDocument) -> tantivy::Result<()> {
+        let f = self.fields;
+        // Replace semantics: a document id maps to exactly one indexed entry
+        writer.delete_term(Term::from_field_text(f.id, &document.id));
+        writer.add_document(doc!(
+            f.id => document.id,
+            f.kind => document.kind,
+            f.project_id => document.project_id.unwrap_or_default(),
+            f.title => document.title,
+            f.body => document.body,
+        ))?;
+        Ok(())
+    }
+
+    fn lock_writer(&self) -> AppResult<std::sync::MutexGuard<'_, IndexWriter>> {
+        self.writer
+            .lock()
+            .map_err(|_| AppError::Internal("search index writer poisoned".into()))
+    }
+}
+
+/// Open (or create) the search index under `data_dir`.
+pub fn init(data_dir: &Path) -> AppResult<SearchIndex> {
+    let dir = data_dir.join(INDEX_DIR_NAME);
+    std::fs::create_dir_all(&dir)?;
+
+    let (schema, fields) = SearchIndex::schema();
+    let directory = tantivy::directory::MmapDirectory::open(&dir)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let index = Index::open_or_create(directory, schema)?;
+    let reader = index
+        .reader_builder()
+        .reload_policy(ReloadPolicy::OnCommitWithDelay)
+        .try_into()?;
+    let writer = index.writer(WRITER_HEAP_BYTES)?;
+
+    Ok(SearchIndex {
+        index,
+        reader,
+        writer: Mutex::new(writer),
+        fields,
+    })
+}
+
+#[tauri::command]
+pub async fn search_index_document(
+    search: State<'_, SearchIndex>,
+    document: SearchDocument,
+) -> AppResult<()> {
+    let mut writer = search.lock_writer()?;
+    search.add(&writer, document)?;
+    writer.commit()?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn search_query(
+    search: State<'_, SearchIndex>,
+    query: String,
+    project_id: Option<String>,
+    limit: Option<usize>,
+) -> AppResult<Vec<SearchHit>> {
+    let f = search.fields;
+    let mut parser = QueryParser::for_index(&search.index, vec![f.title, f.body]);
+    parser.set_field_boost(f.title, 2.0);
+    // Lenient parsing so half-typed input like `"conc` still returns results
+    let (text_query, _) = parser.parse_query_lenient(&query);
+
+    let query: Box<dyn Query> = match project_id {
+        Some(project_id) => Box::new(BooleanQuery::new(vec![
+            (Occur::Must, text_query),
+            (
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(f.project_id, &project_id),
+                    IndexRecordOption::Basic,
+                )),
+            ),
+        ])),
+        None => text_query,
+    };
+
+    let searcher = search.reader.searcher();
+    let top = searcher.search(
+        &query,
+        &TopDocs::with_limit(limit.unwrap_or(50)).order_by_score(),
+    )?;
+
+    let text = |doc: &TantivyDocument, field: Field| {
+        doc.get_first(field)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    top.into_iter()
+        .map(|(score, address)| {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            let project_id = text(&doc, f.project_id);
+            Ok(SearchHit {
+                id: text(&doc, f.id),
+                kind: text(&doc, f.kind),
+                project_id: (!project_id.is_empty()).then_some(project_id),
+                title: text(&doc, f.title),
+                score,
+            })
+        })
+        .collect()
+}
+
+/// Drop the index and rebuild it from every project and item in the database.
+pub async fn rebuild(app: &AppHandle) -> AppResult<usize> {
+    let db = app.state::<Db>().inner().clone();
+    let documents = db
+        .run(|conn| {
+            let mut documents = Vec::new();
+            let mut stmt = conn.prepare("SELECT id, name, description FROM projects")?;
+            let projects = stmt.query_map([], |row| {
+                Ok(SearchDocument {
+                    id: row.get(0)?,
+                    kind: "project".into(),
+                    project_id: Some(row.get(0)?),
+                    title: row.get(1)?,
+                    body: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                })
+            })?;
+            for project in projects {
+                documents.push(project?);
+            }
+
+            let mut stmt =
+                conn.prepare("SELECT id, project_id, name, description, unit FROM items")?;
+            let items = stmt.query_map([], |row| {
+                let description: Option<String> = row.get(3)?;
+                let unit: Option<String> = row.get(4)?;
+                Ok(SearchDocument {
+                    id: row.get(0)?,
+                    kind: "item".into(),
+                    project_id: Some(row.get(1)?),
+                    title: row.get(2)?,
+                    body: [description, unit]
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                })
+            })?;
+            for item in items {
+                documents.push(item?);
+            }
+            Ok(documents)
+        })
+        .await?;
+
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let search = handle.state::<SearchIndex>();
+        let total = documents.len();
+        let mut writer = search.lock_writer()?;
+        writer.delete_all_documents()?;
+
+        for (n, document) in documents.into_iter().enumerate() {
+            search.add(&writer, document)?;
+            if (n + 1) % PROGRESS_INTERVAL == 0 {
+                let _ = handle.emit(
+                    PROGRESS_EVENT,
+                    IndexProgress {
+                        indexed: n + 1,
+                        total,
+                    },
+                );
+            }
+        }
+
+        writer.commit()?;
+        let _ = handle.emit(
+            PROGRESS_EVENT,
+            IndexProgress {
+                indexed: total,
+                total,
+            },
+        );
+        Ok(total)
+    })
+    .await?
+}
+
+#[tauri::command]
+pub async fn search_rebuild(app: AppHandle) -> AppResult<usize> {
+    rebuild(&app).await
+}
+
+/// Keep the index from drifting when writes bypass `search_index_document`.
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(REBUILD_JOB, Some(REBUILD_INTERVAL), |app| async move {
+        if background::defer(&app, Work::Indexing) {
+            return Ok(());
+        }
+        rebuild(&app).await.map(|_| ())
+    });
+}
@@ -0,0 +1,102 @@
+//! Credential storage in the OS keychain so tokens and API keys never hit plaintext files.
+
+use std::sync::OnceLock;
+
+use keyring::Entry;
+use tauri::AppHandle;
+
+use crate::error::{AppError, AppResult};
+use crate::{biometric, idle_lock};
+
+// Matches the bundle identifier so entries are grouped under the app in keychain UIs.
+const SERVICE: &str = "dev.truss.momentum";
+
+// Workspaces other than the default keep their entries under their own service name
+static WORKSPACE_SERVICE: OnceLock<String> = OnceLock::new();
+
+/// Scope every secret to `workspace`, `None` being the default; set once at startup before
+/// any secret is read.
+pub(crate) fn use_workspace(workspace: Option<&str>) {
+    if let Some(workspace) = workspace {
+        let _ = WORKSPACE_SERVICE.set(format!("{SERVICE}.{workspace}"));
+    }
+}
+
+fn entry(key: &str) -> AppResult<Entry> {
+    let service = WORKSPACE_SERVICE.get().map_or(SERVICE, String::as_str);
+    Ok(Entry::new(service, key)?)
+}
+
+/// Store `value` under `key`, replacing any existing secret.
+pub fn set(key: &str, value: &str) -> AppResult<()> {
+    Ok(entry(key)?.set_password(value)?)
+}
+
+/// Read the secret stored under `key`, if any.
+pub fn get(key: &str) -> AppResult<Option<String>> {
+    match entry(key)?.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Remove the secret stored under `key`; missing entries are not an error.
+pub fn delete(key: &str) -> AppResult<()> {
+    match entry(key)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+// The session, role, and unlock PIN decide what this device may do, the signing key vouches
+// for documents, and the proxy, backup, and SMTP credentials are an administrator's, so the
+// frontend can neither read nor replace them
+const RESERVED_PREFIXES: [&str; 6] = [
+    "auth.", "session.", "signing.", "proxy.", "backup.", "email.",
+];
+
+fn ensure_not_reserved(key: &str) -> AppResult<()> {
+    if RESERVED_PREFIXES
+        .iter()
+        .any(|prefix| key.starts_with(prefix))
+    {
+        return Err(AppError::InvalidInput(format!(
+            "{key} is reserved for the app"
+        )));
+    }
+    Ok(())
+}
+
+// Keychain backends block on IPC with the OS credential service.
+async fn blocking<T, F>(f: F) -> AppResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> AppResult<T> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(f).await?
+}
+
+#[tauri::command]
+pub async fn secret_set(app: AppHandle, key: String, value: String) -> AppResult<()> {
+    idle_lock::ensure_unlocked(&app)?;
+    biometric::ensure_unlocked()?;
+    ensure_not_reserved(&key)?;
+    blocking(move || set(&key, &value)).await
+}
+
+#[tauri::command]
+pub async fn secret_get(app: AppHandle, key: String) -> AppResult<Option<String>> {
+    idle_lock::ensure_unlocked(&app)?;
+    biometric::ensure_unlocked()?;
+    ensure_not_reserved(&key)?;
+    blocking(move || get(&key)).await
+}
+
+#[tauri::command]
+pub async fn secret_delete(app: AppHandle, key: String) -> AppResult<()> {
+    idle_lock::ensure_unlocked(&app)?;
+    biometric::ensure_unlocked()?;
+    ensure_not_reserved(&key)?;
+    blocking(move || delete(&key)).await
+}
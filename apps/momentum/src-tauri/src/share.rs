@@ -0,0 +1,428 @@
+//! Read-only sharing of an estimate or report with phones and tablets on the local network.
+//!
+//! Nothing listens until `start_sharing` is called. A small HTTP server then binds a random
+//! port and answers only at `/s/{token}`, where the token is 32 random bytes; every other
+//! path is a 404. An estimate is rendered from the database on each request, so reloading
+//! the page shows the latest edits; a report is the PDF as written. One share runs at a
+//! time: starting another replaces it, and it stops on `stop_sharing`, after
+//! `expiresInMinutes`, or when the app quits. The server listens on every interface, so
+//! sharing on a public network exposes the link to that network too.
+
+use std::fmt::Write;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::{Path as UrlPath, State as ServerState};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::RngCore;
+use rusqlite::OptionalExtension;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::oneshot;
+
+use crate::db::projects::{Item, Project};
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::network;
+use crate::qr::{QrCode, QrEcc};
+use crate::templates::{self, engine::NumberFormat};
+
+/// Emitted with [`ShareStopped`] when a share ends on its own, i.e. when it expires.
+pub const STOPPED_EVENT: &str = "share:stopped";
+
+const DEFAULT_EXPIRY_MINUTES: u64 = 60;
+const MAX_EXPIRY_MINUTES: u64 = 24 * 60;
+const QR_BORDER: usize = 4;
+// Headers on every answer: the token is in the URL, so it must not leak through caches or referrers
+const SECURITY_HEADERS: &[(header::HeaderName, &str)] = &[
+    (header::CACHE_CONTROL, "no-store"),
+    (header::REFERRER_POLICY, "no-referrer"),
+    (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+    (
+        header::CONTENT_SECURITY_POLICY,
+        "default-src 'none'; style-src 'unsafe-inline'",
+    ),
+];
+
+/// What to share.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ShareTarget {
+    /// A project's estimate, rendered live as a web page.
+    #[serde(rename_all = "camelCase")]
+    Estimate { project_id: String },
+    /// A report PDF already written, such as by `generate_report_pdf`.
+    Report { path: PathBuf },
+}
+
+/// The running share as shown to the frontend.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareInfo {
+    /// Link on the machine's primary network address; the QR code encodes this one.
+    pub url: String,
+    /// The same link on the machine's other addresses, for devices on another interface.
+    pub other_urls: Vec<String>,
+    /// The link as an SVG QR code, ready to show for a phone camera.
+    pub qr_svg: String,
+    pub title: String,
+    pub started_at: i64,
+    pub expires_at: i64,
+}
+
+/// Payload of [`STOPPED_EVENT`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareStopped {
+    pub title: String,
+}
+
+// What the server needs to answer a request
+struct Shared {
+    token: String,
+    target: ShareTarget,
+    title: String,
+    db: Db,
+    format: NumberFormat,
+}
+
+// The server's view of app state: whichever share is running, and the one it serves
+type RouteState = (Arc<Mutex<Option<Running>>>, Arc<Shared>);
+
+struct Running {
+    info: ShareInfo,
+    shared: Arc<Shared>,
+    shutdown: oneshot::Sender<()>,
+}
+
+/// The share registered in app state; empty while nothing is shared.
+#[derive(Default)]
+pub struct Shares {
+    running: Arc<Mutex<Option<Running>>>,
+}
+
+impl Shares {
+    fn stop(&self) -> Option<ShareInfo> {
+        let running = self
+            .running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()?;
+        let _ = running.shutdown.send(());
+        tracing::info!(title = %running.info.title, "stopped sharing");
+        Some(running.info)
+    }
+
+    // Stops the share only if it is still `shared`, not one started since
+    fn stop_if_current(&self, shared: &Arc<Shared>) -> Option<ShareInfo> {
+        let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        if !running
+            .as_ref()
+            .is_some_and(|running| Arc::ptr_eq(&running.shared, shared))
+        {
+            return None;
+        }
+        let running = running.take()?;
+        let _ = running.shutdown.send(());
+        tracing::info!(title = %running.info.title, "share expired");
+        Some(running.info)
+    }
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+// Compares in constant time so response timing says nothing about how much of a guess matched
+fn token_matches(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected
+            .bytes()
+            .zip(given.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn estimate_page(project: &Project, items: &[Item], format: &NumberFormat) -> String {
+    let symbol = &format.currency_symbol;
+    let mut rows = String::new();
+    let mut subtotal = 0.0;
+    for item in items {
+        let total = item.quantity * item.unit_cost;
+        subtotal += total;
+        let _ = write!(
+            rows,
+            "<tr><td>{}{}</td><td class=\"n\">{}</td><td>{}</td><td class=\"n\">{}</td>\
+             <td class=\"n\">{}</td></tr>",
+            escape(&item.name),
+            item.description
+                .as_deref()
+                .map(|text| format!("<div class=\"d\">{}</div>", escape(text)))
+                .unwrap_or_default(),
+            escape(&format.fixed(item.quantity, 2, true)),
+            escape(item.unit.as_deref().unwrap_or("")),
+            escape(&format.currency(item.unit_cost, symbol)),
+            escape(&format.currency(total, symbol)),
+        );
+    }
+    let description = project
+        .description
+        .as_deref()
+        .map(|text| format!("<p>{}</p>", escape(text)))
+        .unwrap_or_default();
+    format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\">\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\
+         <meta name=\"robots\" content=\"noindex\"><title>{name}</title><style>\
+         body{{font:15px/1.4 -apple-system,system-ui,sans-serif;margin:1.5em auto;max-width:60em;padding:0 1em;color:#222}}\
+         table{{width:100%;border-collapse:collapse}}th,td{{padding:.45em .4em;border-bottom:1px solid #ddd;text-align:left;vertical-align:top}}\
+         .n{{text-align:right;white-space:nowrap}}.d{{color:#666;font-size:.9em}}tfoot td{{font-weight:600;border:0}}\
+         footer{{color:#888;font-size:.85em;margin-top:2em}}</style></head><body>\
+         <h1>{name}</h1>{description}<table><thead><tr><th>Description</th><th class=\"n\">Qty</th>\
+         <th>Unit</th><th class=\"n\">Unit cost</th><th class=\"n\">Total</th></tr></thead>\
+         <tbody>{rows}</tbody><tfoot><tr><td colspan=\"4\" class=\"n\">Subtotal</td>\
+         <td class=\"n\">{subtotal}</td></tr></tfoot></table>\
+         <footer>Read-only view shared from Momentum. Reload for the latest changes.</footer>\
+         </body></html>",
+        name = escape(&project.name),
+        subtotal = escape(&format.currency(subtotal, symbol)),
+    )
+}
+
+fn with_headers(mut response: Response) -> Response {
+    let headers = response.headers_mut();
+    for (name, value) in SECURITY_HEADERS {
+        headers.insert(name.clone(), HeaderValue::from_static(value));
+    }
+    response
+}
+
+fn not_found() -> Response {
+    with_headers((StatusCode::NOT_FOUND, "Not found").into_response())
+}
+
+async fn serve_share(
+    ServerState((running, shared)): ServerState<RouteState>,
+    UrlPath(token): UrlPath<String>,
+) -> Response {
+    // Connections kept alive past a stop get nothing
+    let current = running
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .is_some_and(|running| Arc::ptr_eq(&running.shared, &shared));
+    if !current || !token_matches(&shared.token, &token) {
+        return not_found();
+    }
+    let response = match &shared.target {
+        ShareTarget::Estimate { project_id } => {
+            let project_id = project_id.clone();
+            let loaded = shared
+                .db
+                .run(move |conn| {
+                    let Some(project) = conn
+                        .query_row(
+                            "SELECT * FROM projects WHERE id = ?1",
+                            [&project_id],
+                            Project::from_row,
+                        )
+                        .optional()?
+                    else {
+                        return Ok(None);
+                    };
+                    let mut stmt = conn.prepare(
+                        "SELECT * FROM items WHERE project_id = ?1 ORDER BY sort_order, name",
+                    )?;
+                    let items = stmt
+                        .query_map([&project_id], Item::from_row)?
+                        .collect::<rusqlite::Result<Vec<_>>>()?;
+                    Ok(Some((project, items)))
+                })
+                .await;
+            match loaded {
+                Ok(Some((project, items))) => (
+                    [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+                    estimate_page(&project, &items, &shared.format),
+                )
+                    .into_response(),
+                // Deleted since sharing started
+                Ok(None) => return not_found(),
+                Err(err) => {
+                    tracing::warn!(%err, "cannot load the shared estimate");
+                    (StatusCode::INTERNAL_SERVER_ERROR, "Something went wrong").into_response()
+                }
+            }
+        }
+        ShareTarget::Report { path } => match tokio::fs::read(path).await {
+            Ok(bytes) => {
+                let disposition = format!(
+                    "inline; filename=\"{}.pdf\"",
+                    crate::reports::file_stem(&shared.title)
+                );
+                let mut response =
+                    ([(header::CONTENT_TYPE, "application/pdf")], bytes).into_response();
+                if let Ok(value) = HeaderValue::from_str(&disposition) {
+                    response
+                        .headers_mut()
+                        .insert(header::CONTENT_DISPOSITION, value);
+                }
+                response
+            }
+            Err(err) => {
+                tracing::warn!(%err, path = %path.display(), "cannot read the shared report");
+                return not_found();
+            }
+        },
+    };
+    with_headers(response)
+}
+
+// The title shown in the app and the page, checked up front so a bad target fails here
+async fn title(db: &Db, target: &ShareTarget) -> AppResult<String> {
+    match target {
+        ShareTarget::Estimate { project_id } => {
+            let project_id = project_id.clone();
+            db.run(move |conn| {
+                conn.query_row(
+                    "SELECT name FROM projects WHERE id = ?1",
+                    [&project_id],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .ok_or_else(|| AppError::not_found("project", &project_id))
+            })
+            .await
+        }
+        ShareTarget::Report { path } => {
+            let is_pdf = path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"));
+            if !is_pdf || !tokio::fs::metadata(path).await?.is_file() {
+                return Err(AppError::InvalidInput(format!(
+                    "{} is not a PDF report",
+                    path.display()
+                )));
+            }
+            Ok(path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "Report".into()))
+        }
+    }
+}
+
+/// Start sharing `target` on the local network, replacing any share already running.
+#[tauri::command]
+pub async fn start_sharing(
+    app: AppHandle,
+    db: State<'_, Db>,
+    shares: State<'_, Shares>,
+    target: ShareTarget,
+    expires_in_minutes: Option<u64>,
+) -> AppResult<ShareInfo> {
+    let minutes = expires_in_minutes.unwrap_or(DEFAULT_EXPIRY_MINUTES);
+    if !(1..=MAX_EXPIRY_MINUTES).contains(&minutes) {
+        return Err(AppError::InvalidInput(format!(
+            "a share can last 1 to {MAX_EXPIRY_MINUTES} minutes"
+        )));
+    }
+    let title = title(&db, &target).await?;
+    let addresses = network::lan_addresses()?;
+
+    shares.stop();
+    let listener =
+        tokio::net::TcpListener::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))).await?;
+    let port = listener.local_addr()?.port();
+    let token = random_token();
+    let mut urls = addresses
+        .iter()
+        .map(|ip| format!("http://{ip}:{port}/s/{token}"));
+    let url = urls.next().unwrap_or_default();
+    let other_urls = urls.collect();
+    let qr_svg = QrCode::encode(url.as_bytes(), QrEcc::Medium)?.to_svg(QR_BORDER);
+
+    let started_at = now_ms();
+    let info = ShareInfo {
+        url,
+        other_urls,
+        qr_svg,
+        title: title.clone(),
+        started_at,
+        expires_at: started_at + (minutes * 60_000) as i64,
+    };
+    let shared = Arc::new(Shared {
+        token,
+        target,
+        title,
+        db: db.inner().clone(),
+        format: templates::number_format(None),
+    });
+    let router = Router::new()
+        .route("/s/{token}", get(serve_share))
+        .fallback(|| async { not_found() })
+        .with_state((shares.running.clone(), shared.clone()));
+    let (shutdown, stopped) = oneshot::channel();
+    tauri::async_runtime::spawn(async move {
+        let server = axum::serve(listener, router).with_graceful_shutdown(async {
+            let _ = stopped.await;
+        });
+        if let Err(err) = server.await {
+            tracing::warn!(%err, "share server failed");
+        }
+    });
+    *shares.running.lock().unwrap_or_else(|e| e.into_inner()) = Some(Running {
+        info: info.clone(),
+        shared: shared.clone(),
+        shutdown,
+    });
+    tracing::info!(title = %info.title, port, minutes, "started sharing");
+
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(minutes * 60)).await;
+        if let Some(info) = app.state::<Shares>().stop_if_current(&shared) {
+            let _ = app.emit(STOPPED_EVENT, ShareStopped { title: info.title });
+        }
+    });
+    Ok(info)
+}
+
+/// Stop sharing; returns what was shared, or `None` when nothing was.
+#[tauri::command]
+pub async fn stop_sharing(shares: State<'_, Shares>) -> AppResult<Option<ShareInfo>> {
+    Ok(shares.stop())
+}
+
+/// The share currently running, if any.
+#[tauri::command]
+pub async fn get_share_status(shares: State<'_, Shares>) -> AppResult<Option<ShareInfo>> {
+    Ok(shares
+        .running
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|running| running.info.clone()))
+}
@@ -0,0 +1,302 @@
+//! The iOS and Android share sheet: sending generated PDFs and photos to other apps, and
+//! receiving photos other apps share to Momentum.
+//!
+//! Received files are copied into `data_dir/shared-inbox`, by the Android plugin from the
+//! content URIs it is handed and here from the copies iOS leaves in `Documents/Inbox`. Each
+//! batch is announced with `share-sheet:received`, queued like `file_open` until the frontend
+//! calls `take_shared_files`, and `import_shared_files` attaches them to a project.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::attachments::{self, Attachment, AttachmentStore};
+use crate::audit::{self, Change};
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+
+/// Emitted with a [`SharedFiles`] when another app shares files to Momentum.
+#[cfg_attr(desktop, allow(dead_code))]
+pub const SHARED_FILES_EVENT: &str = "share-sheet:received";
+
+const INBOX_DIR_NAME: &str = "shared-inbox";
+
+/// Payload for `share-sheet:received`.
+#[cfg_attr(desktop, allow(dead_code))]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedFiles {
+    pub paths: Vec<PathBuf>,
+}
+
+/// What the user did with the sheet.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareOutcome {
+    /// None on Android, which hands the file to a chooser and never hears back.
+    pub completed: Option<bool>,
+    /// The app or action the file went to, where the platform reports it.
+    pub target: Option<String>,
+}
+
+/// Shared files received before the frontend subscribed to `share-sheet:received`.
+#[derive(Default)]
+pub struct ShareInbox {
+    inner: Mutex<Pending>,
+}
+
+#[derive(Default)]
+struct Pending {
+    ready: bool,
+    queue: Vec<PathBuf>,
+}
+
+fn inbox_dir(app: &AppHandle) -> PathBuf {
+    app.state::<AppState>().data_dir().join(INBOX_DIR_NAME)
+}
+
+/// Start receiving shared files; the native plugin must already be registered.
+pub fn init(app: &AppHandle) -> ShareInbox {
+    if let Err(err) = platform::register(app, inbox_dir(app)) {
+        tracing::warn!(%err, "cannot receive shared files");
+    }
+    ShareInbox::default()
+}
+
+#[cfg(mobile)]
+fn received(app: &AppHandle, paths: Vec<PathBuf>) {
+    use tauri::Emitter;
+
+    if paths.is_empty() {
+        return;
+    }
+    tracing::info!(count = paths.len(), "received shared files");
+    let inbox = app.state::<ShareInbox>();
+    let mut pending = inbox.inner.lock().unwrap_or_else(|e| e.into_inner());
+    if pending.ready {
+        let _ = app.emit(SHARED_FILES_EVENT, SharedFiles { paths });
+    } else {
+        pending.queue.extend(paths);
+    }
+}
+
+/// Move the images among files iOS opened in the app into the inbox.
+///
+/// Sharing a photo to Momentum arrives the same way as opening a document in it, so this
+/// picks out what `file_open` ignores.
+#[cfg(target_os = "ios")]
+pub fn receive_opened(app: &AppHandle, paths: &[PathBuf]) {
+    let inbox = inbox_dir(app);
+    let images = paths
+        .iter()
+        .filter(|path| {
+            attachments::content_type_for(&path.to_string_lossy())
+                .is_some_and(|mime| mime.starts_with("image/"))
+        })
+        .filter_map(|path| match move_into(&inbox, path) {
+            Ok(moved) => Some(moved),
+            Err(err) => {
+                tracing::warn!(%err, path = %path.display(), "cannot take shared file");
+                None
+            }
+        })
+        .collect();
+    received(app, images);
+}
+
+#[cfg(target_os = "ios")]
+fn move_into(inbox: &Path, path: &Path) -> AppResult<PathBuf> {
+    fs::create_dir_all(inbox)?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| AppError::InvalidInput(format!("{} is not a file", path.display())))?;
+    let target = inbox.join(format!(
+        "{}-{}",
+        crate::db::now_ms(),
+        name.to_string_lossy()
+    ));
+    // Documents/Inbox is ours to empty, and a rename is free on the same volume
+    if fs::rename(path, &target).is_err() {
+        fs::copy(path, &target)?;
+        let _ = fs::remove_file(path);
+    }
+    Ok(target)
+}
+
+// Only files in the inbox may be imported or discarded, so the webview cannot delete others
+fn in_inbox(inbox: &Path, path: &Path) -> AppResult<PathBuf> {
+    let canonical = path
+        .canonicalize()
+        .map_err(|_| AppError::InvalidInput(format!("{} is not a shared file", path.display())))?;
+    if !inbox
+        .canonicalize()
+        .is_ok_and(|inbox| canonical.starts_with(inbox))
+    {
+        return Err(AppError::InvalidInput(format!(
+            "{} is not a shared file",
+            path.display()
+        )));
+    }
+    Ok(canonical)
+}
+
+#[cfg(mobile)]
+mod platform {
+    use std::path::PathBuf;
+
+    use serde::Serialize;
+    use tauri::AppHandle;
+
+    use super::ShareOutcome;
+    use crate::error::AppResult;
+    use crate::mobile;
+
+    #[derive(Serialize)]
+    struct ShareArgs {
+        path: PathBuf,
+        mime: String,
+        title: Option<String>,
+    }
+
+    pub fn share(
+        app: &AppHandle,
+        path: PathBuf,
+        mime: String,
+        title: Option<String>,
+    ) -> AppResult<ShareOutcome> {
+        mobile::run(app, "shareFile", ShareArgs { path, mime, title })
+    }
+
+    // Android holds share intents until this hands it somewhere to put the files
+    #[cfg(target_os = "android")]
+    pub fn register(app: &AppHandle, inbox: PathBuf) -> AppResult<()> {
+        use tauri::ipc::{Channel, InvokeResponseBody};
+
+        use super::SharedFiles;
+
+        #[derive(Serialize)]
+        struct RegisterArgs {
+            inbox: PathBuf,
+            handler: Channel<serde_json::Value>,
+        }
+
+        let handle = app.clone();
+        let handler = Channel::new(move |body: InvokeResponseBody| {
+            let files: SharedFiles = body.deserialize()?;
+            super::received(&handle, files.paths);
+            Ok(())
+        });
+        mobile::run(app, "registerShareTarget", RegisterArgs { inbox, handler })
+    }
+
+    // iOS delivers shared photos as opened files instead; see `receive_opened`
+    #[cfg(target_os = "ios")]
+    pub fn register(_app: &AppHandle, _inbox: PathBuf) -> AppResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(desktop)]
+mod platform {
+    use std::path::PathBuf;
+
+    use tauri::AppHandle;
+
+    use super::ShareOutcome;
+    use crate::error::{AppError, AppResult};
+
+    pub fn share(
+        _app: &AppHandle,
+        _path: PathBuf,
+        _mime: String,
+        _title: Option<String>,
+    ) -> AppResult<ShareOutcome> {
+        Err(AppError::InvalidInput(
+            "the share sheet is only available on mobile".into(),
+        ))
+    }
+
+    pub fn register(_app: &AppHandle, _inbox: PathBuf) -> AppResult<()> {
+        Ok(())
+    }
+}
+
+/// Open the native share sheet for the file at `path`.
+///
+/// `mime` defaults to one guessed from the extension, which decides the apps offered.
+#[tauri::command]
+pub async fn share_file(
+    app: AppHandle,
+    path: PathBuf,
+    mime: Option<String>,
+    title: Option<String>,
+) -> AppResult<ShareOutcome> {
+    if !path.is_file() {
+        return Err(AppError::InvalidInput(format!(
+            "{} is not a file",
+            path.display()
+        )));
+    }
+    let mime = mime.unwrap_or_else(|| {
+        attachments::content_type_for(&path.to_string_lossy())
+            .unwrap_or("application/octet-stream")
+            .to_string()
+    });
+    tauri::async_runtime::spawn_blocking(move || platform::share(&app, path, mime, title)).await?
+}
+
+/// Mark the frontend ready and return files shared to the app during startup.
+#[tauri::command]
+pub async fn take_shared_files(inbox: State<'_, ShareInbox>) -> AppResult<Vec<PathBuf>> {
+    let mut pending = inbox.inner.lock().unwrap_or_else(|e| e.into_inner());
+    pending.ready = true;
+    Ok(std::mem::take(&mut pending.queue))
+}
+
+/// Attach shared files to a project and remove them from the inbox.
+#[tauri::command]
+pub async fn import_shared_files(
+    app: AppHandle,
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    project_id: String,
+    paths: Vec<PathBuf>,
+) -> AppResult<Vec<Attachment>> {
+    let actor = audit::actor(&state);
+    let inbox = inbox_dir(&app);
+    let paths = paths
+        .iter()
+        .map(|path| in_inbox(&inbox, path))
+        .collect::<AppResult<Vec<_>>>()?;
+    db.run(move |conn| {
+        let store = app.state::<AttachmentStore>();
+        let mut added = Vec::with_capacity(paths.len());
+        for path in paths {
+            let attachment = attachments::add_file(conn, &store, project_id.clone(), &path)?;
+            audit::record_now(
+                conn,
+                &actor,
+                Change::new("import_shared_files", "attachment", &attachment.id)
+                    .after(&attachment)?,
+            )?;
+            let _ = fs::remove_file(&path);
+            added.push(attachment);
+        }
+        Ok(added)
+    })
+    .await
+}
+
+/// Delete shared files the user chose not to keep.
+#[tauri::command]
+pub async fn discard_shared_files(app: AppHandle, paths: Vec<PathBuf>) -> AppResult<()> {
+    let inbox = inbox_dir(&app);
+    for path in paths {
+        fs::remove_file(in_inbox(&inbox, &path)?)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,159 @@
+//! Configurable system-wide hotkeys, persisted in config and forwarded to the frontend as events.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::plugin::TauriPlugin;
+use tauri::{AppHandle, Emitter, Manager, State, Wry};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+use crate::config;
+use crate::error::{AppError, AppResult};
+use crate::quick_capture;
+use crate::state::AppState;
+
+/// Emitted with a [`ShortcutTriggered`] when a registered hotkey is pressed.
+pub const SHORTCUT_TRIGGERED_EVENT: &str = "shortcut:triggered";
+
+/// Payload for `shortcut:triggered`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShortcutTriggered {
+    pub action: String,
+}
+
+/// Registered shortcuts keyed by action, registered in app state.
+#[derive(Default)]
+pub struct ShortcutRegistry {
+    bindings: Mutex<BTreeMap<String, Shortcut>>,
+}
+
+impl ShortcutRegistry {
+    fn action_for(&self, shortcut: &Shortcut) -> Option<String> {
+        self.bindings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .find(|(_, bound)| bound.id() == shortcut.id())
+            .map(|(action, _)| action.clone())
+    }
+}
+
+/// Build the global-shortcut plugin with a handler that dispatches presses by action.
+pub fn plugin() -> TauriPlugin<Wry> {
+    tauri_plugin_global_shortcut::Builder::new()
+        .with_handler(|app, shortcut, event| {
+            if event.state() != ShortcutState::Pressed {
+                return;
+            }
+            let Some(action) = app
+                .try_state::<ShortcutRegistry>()
+                .and_then(|registry| registry.action_for(shortcut))
+            else {
+                return;
+            };
+            tracing::debug!(%action, "global shortcut triggered");
+            if action == quick_capture::SHORTCUT_ACTION {
+                quick_capture::toggle(app);
+            }
+            let _ = app.emit(SHORTCUT_TRIGGERED_EVENT, ShortcutTriggered { action });
+        })
+        .build()
+}
+
+fn parse(accelerator: &str) -> AppResult<Shortcut> {
+    Shortcut::from_str(accelerator)
+        .map_err(|e| AppError::InvalidInput(format!("invalid shortcut {accelerator:?}: {e}")))
+}
+
+/// Register the shortcuts saved in config.
+///
+/// Accelerators held by another application are logged and skipped so startup never fails on them.
+pub fn init(app: &AppHandle) -> ShortcutRegistry {
+    let registry = ShortcutRegistry::default();
+    let saved = app.state::<AppState>().config().shortcuts;
+    let mut bindings = registry.bindings.lock().unwrap_or_else(|e| e.into_inner());
+    for (action, accelerator) in saved {
+        let result = parse(&accelerator)
+            .and_then(|shortcut| Ok(app.global_shortcut().register(shortcut).map(|_| shortcut)?));
+        match result {
+            Ok(shortcut) => {
+                bindings.insert(action, shortcut);
+            }
+            Err(err) => tracing::warn!(%action, %accelerator, %err, "skipping global shortcut"),
+        }
+    }
+    drop(bindings);
+    registry
+}
+
+/// Bind `accelerator` (e.g. `CommandOrControl+Shift+Space`) to `action`, replacing its previous binding.
+#[tauri::command]
+pub async fn set_global_shortcut(
+    app: AppHandle,
+    registry: State<'_, ShortcutRegistry>,
+    action: String,
+    accelerator: String,
+) -> AppResult<()> {
+    if action.trim().is_empty() {
+        return Err(AppError::InvalidInput("shortcut action is empty".into()));
+    }
+    let shortcut = parse(&accelerator)?;
+    let manager = app.global_shortcut();
+
+    {
+        let mut bindings = registry.bindings.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((holder, _)) = bindings
+            .iter()
+            .find(|(holder, bound)| bound.id() == shortcut.id() && **holder != action)
+        {
+            return Err(AppError::ShortcutConflict {
+                accelerator,
+                action: holder.clone(),
+            });
+        }
+
+        let previous = bindings.remove(&action);
+        if let Some(previous) = previous.filter(|p| p.id() != shortcut.id()) {
+            manager.unregister(previous)?;
+            // Another app may own the new combination; keep the old binding working if so
+            if let Err(err) = manager.register(shortcut) {
+                let _ = manager.register(previous);
+                bindings.insert(action, previous);
+                return Err(err.into());
+            }
+        } else if previous.is_none() {
+            manager.register(shortcut)?;
+        }
+        bindings.insert(action.clone(), shortcut);
+    }
+
+    config::update(&app, |config| {
+        config.shortcuts.insert(action, accelerator);
+    })?;
+    Ok(())
+}
+
+/// Remove the binding for `action`; unbound actions are a no-op.
+#[tauri::command]
+pub async fn clear_global_shortcut(
+    app: AppHandle,
+    registry: State<'_, ShortcutRegistry>,
+    action: String,
+) -> AppResult<()> {
+    let removed = registry
+        .bindings
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&action);
+    if let Some(shortcut) = removed {
+        app.global_shortcut().unregister(shortcut)?;
+    }
+
+    config::update(&app, |config| {
+        config.shortcuts.remove(&action);
+    })?;
+    Ok(())
+}
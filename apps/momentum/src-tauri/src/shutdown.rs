@@ -0,0 +1,327 @@
+//! Graceful window close and app exit.
+//!
+//! Before a window closes, or the app quits, each affected webview is asked whether it holds
+//! unsaved changes. A window that says yes keeps itself open and shows its own prompt, then
+//! calls `close_anyway` or `quit_app` with `force` if the user discards. A webview that does
+//! not answer in time is treated as having nothing to save, so a hung page cannot trap the
+//! user. Once quitting is settled, autosaves are written, the outbox gets a last chance to
+//! drain, background jobs stop, and the database log is checkpointed, and only then does the
+//! process exit.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, ExitRequestApi, Manager, Window, WindowEvent};
+use tokio::sync::oneshot;
+
+use crate::autosave::Autosave;
+use crate::db::Db;
+use crate::error::{AppError, AppResult};
+use crate::jobs::Scheduler;
+use crate::outbox::Outbox;
+use crate::realtime::Realtime;
+use crate::state::AppState;
+use crate::timer::Timers;
+use crate::windows::{MAIN_WINDOW, PRESENTATION_WINDOW};
+
+/// Emitted to a window with a [`CloseRequest`]; answer with `respond_close_request`.
+pub const CLOSE_REQUESTED_EVENT: &str = "shutdown:close-requested";
+/// Emitted when a quit was called off because a window has unsaved changes.
+pub const QUIT_CANCELLED_EVENT: &str = "shutdown:quit-cancelled";
+
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(5);
+const OUTBOX_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+const JOB_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Payload for `shutdown:close-requested`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloseRequest {
+    pub request_id: u64,
+    /// `close` for this window alone, `quit` for the whole app.
+    pub reason: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Running,
+    Quitting,
+    // Cleanup finished; the next exit request goes through
+    Done,
+}
+
+/// Close and quit coordination registered in app state.
+pub struct Shutdown {
+    phase: Mutex<Phase>,
+    next_request: AtomicU64,
+    // Outstanding questions, answered with whether the window has unsaved changes
+    pending: Mutex<HashMap<u64, oneshot::Sender<bool>>>,
+    // Windows currently being asked, so repeated clicks on close prompt once
+    asking: Mutex<HashSet<String>>,
+    // Windows cleared to close on their next close request
+    approved: Mutex<HashSet<String>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self {
+            phase: Mutex::new(Phase::Running),
+            next_request: AtomicU64::new(1),
+            pending: Mutex::new(HashMap::new()),
+            asking: Mutex::new(HashSet::new()),
+            approved: Mutex::new(HashSet::new()),
+        }
+    }
+}
+
+impl Shutdown {
+    fn phase(&self) -> Phase {
+        *self.phase.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn set_phase(&self, phase: Phase) {
+        *self.phase.lock().unwrap_or_else(|e| e.into_inner()) = phase;
+    }
+
+    // Whether `label` has unsaved changes, by its own account
+    async fn has_unsaved_changes(
+        &self,
+        app: &AppHandle,
+        label: &str,
+        reason: &'static str,
+    ) -> bool {
+        let request_id = self.next_request.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(request_id, tx);
+        let asked = app.emit_to(
+            label,
+            CLOSE_REQUESTED_EVENT,
+            CloseRequest { request_id, reason },
+        );
+        let answer = match asked {
+            Ok(()) => tokio::time::timeout(ANSWER_TIMEOUT, rx).await.ok(),
+            Err(_) => None,
+        };
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&request_id);
+        match answer {
+            Some(Ok(unsaved)) => unsaved,
+            _ => {
+                tracing::warn!(window = label, "no answer about unsaved changes; closing");
+                false
+            }
+        }
+    }
+}
+
+fn hides_on_close(window: &Window) -> bool {
+    cfg!(desktop)
+        && window.label() == MAIN_WINDOW
+        && window
+            .try_state::<AppState>()
+            .is_some_and(|state| state.config().close_to_tray)
+}
+
+/// Hold a window's close until its webview confirms there is nothing unsaved.
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    let WindowEvent::CloseRequested { api, .. } = event else {
+        return;
+    };
+    let Some(shutdown) = window.try_state::<Shutdown>() else {
+        return;
+    };
+    // Quitting has already asked every window, and a presentation has nothing to save
+    if hides_on_close(window)
+        || window.label() == PRESENTATION_WINDOW
+        || shutdown.phase() != Phase::Running
+    {
+        return;
+    }
+    let label = window.label().to_string();
+    if shutdown
+        .approved
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&label)
+    {
+        return;
+    }
+    api.prevent_close();
+    if !shutdown
+        .asking
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(label.clone())
+    {
+        return;
+    }
+    let app = window.app_handle().clone();
+    tauri::async_runtime::spawn(async move {
+        let shutdown = app.state::<Shutdown>();
+        let unsaved = shutdown.has_unsaved_changes(&app, &label, "close").await;
+        shutdown
+            .asking
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&label);
+        if unsaved {
+            tracing::info!(window = %label, "close held for unsaved changes");
+            return;
+        }
+        close_approved(&app, &label);
+    });
+}
+
+fn close_approved(app: &AppHandle, label: &str) {
+    let Some(window) = app.get_webview_window(label) else {
+        return;
+    };
+    app.state::<Shutdown>()
+        .approved
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(label.to_string());
+    if let Err(err) = window.close() {
+        tracing::warn!(window = label, %err, "failed to close window");
+    }
+}
+
+/// Hold the exit until cleanup has run; `code` is `None` when the last window closed.
+pub fn on_exit_requested(app: &AppHandle, api: &ExitRequestApi, code: Option<i32>) {
+    // Restarts cannot be held; the exit hook still writes autosaves
+    if code == Some(tauri::RESTART_EXIT_CODE) {
+        return;
+    }
+    let Some(shutdown) = app.try_state::<Shutdown>() else {
+        return;
+    };
+    match shutdown.phase() {
+        Phase::Done => return,
+        Phase::Quitting => {
+            api.prevent_exit();
+            return;
+        }
+        Phase::Running => {}
+    }
+    api.prevent_exit();
+    // Windows closed one by one already answered for themselves
+    quit(app, code.unwrap_or(0), code.is_none());
+}
+
+fn quit(app: &AppHandle, code: i32, force: bool) {
+    let shutdown = app.state::<Shutdown>();
+    {
+        let mut phase = shutdown.phase.lock().unwrap_or_else(|e| e.into_inner());
+        if *phase != Phase::Running {
+            return;
+        }
+        *phase = Phase::Quitting;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let shutdown = app.state::<Shutdown>();
+        if !force {
+            let labels: Vec<String> = app.webview_windows().into_keys().collect();
+            let mut answers = Vec::with_capacity(labels.len());
+            // Asked together so a slow window costs one timeout, not one each
+            for label in labels {
+                let app = app.clone();
+                answers.push(tauri::async_runtime::spawn(async move {
+                    let shutdown = app.state::<Shutdown>();
+                    let unsaved = shutdown.has_unsaved_changes(&app, &label, "quit").await;
+                    (label, unsaved)
+                }));
+            }
+            let mut held_by = None;
+            for answer in answers {
+                if let Ok((label, true)) = answer.await {
+                    held_by.get_or_insert(label);
+                }
+            }
+            if let Some(label) = held_by {
+                tracing::info!(window = %label, "quit held for unsaved changes");
+                shutdown.set_phase(Phase::Running);
+                let _ = app.emit(QUIT_CANCELLED_EVENT, &label);
+                return;
+            }
+        }
+        drain(&app).await;
+        shutdown.set_phase(Phase::Done);
+        if code == tauri::RESTART_EXIT_CODE {
+            app.restart()
+        } else {
+            app.exit(code)
+        }
+    });
+}
+
+/// Relaunch the app through the same pipeline as quitting, so a window with unsaved changes
+/// can still call it off.
+pub(crate) fn restart(app: &AppHandle, force: bool) {
+    quit(app, tauri::RESTART_EXIT_CODE, force);
+}
+
+async fn drain(app: &AppHandle) {
+    // Says goodbye so collaborators see this client leave now rather than at a timeout
+    app.state::<Realtime>().disconnect(app);
+    let written = app.state::<Autosave>().flush_all();
+    if written > 0 {
+        tracing::info!(written, "autosaved before exit");
+    }
+    // Running timers keep running; this marks where the offline stretch starts
+    if let Err(err) = app.state::<Timers>().heartbeat().await {
+        tracing::warn!(%err, "failed to record running timers at exit");
+    }
+    match tokio::time::timeout(OUTBOX_DRAIN_TIMEOUT, app.state::<Outbox>().flush(app)).await {
+        Ok(Err(err)) => tracing::warn!(%err, "outbox not drained before exit"),
+        Err(_) => tracing::warn!("outbox still draining at exit; the rest is sent next launch"),
+        Ok(Ok(())) => {}
+    }
+    let still_running = app.state::<Scheduler>().stop(JOB_STOP_TIMEOUT).await;
+    if still_running > 0 {
+        tracing::warn!(still_running, "jobs abandoned at exit");
+    }
+    if let Err(err) = app.state::<Db>().checkpoint().await {
+        tracing::warn!(%err, "failed to checkpoint the database at exit");
+    }
+    tracing::info!("shutdown complete");
+}
+
+/// Answer a `shutdown:close-requested` event.
+#[tauri::command]
+pub async fn respond_close_request(
+    shutdown: tauri::State<'_, Shutdown>,
+    request_id: u64,
+    has_unsaved_changes: bool,
+) -> AppResult<()> {
+    let sender = shutdown
+        .pending
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&request_id)
+        .ok_or_else(|| AppError::not_found("close request", request_id.to_string()))?;
+    let _ = sender.send(has_unsaved_changes);
+    Ok(())
+}
+
+/// Close the calling window without asking again, after the user chose to discard changes.
+#[tauri::command]
+pub async fn close_anyway(app: AppHandle, window: Window) -> AppResult<()> {
+    close_approved(&app, window.label());
+    Ok(())
+}
+
+/// Quit the app through the shutdown pipeline; `force` skips asking about unsaved changes.
+#[tauri::command]
+pub async fn quit_app(app: AppHandle, force: Option<bool>) -> AppResult<()> {
+    quit(&app, 0, force.unwrap_or(false));
+    Ok(())
+}
@@ -0,0 +1,401 @@
+//! Ed25519 signatures on exported bid PDFs and project archives.
+//!
+//! Each workspace has its own key pair, created on first use and kept in the keychain. The
+//! signature and who signed when are embedded in the file itself: as a comment line after
+//! the final `%%EOF` of a PDF, which readers ignore, or as the comment of a zip archive.
+//! It covers every byte before it plus the signer details, so any later change, including
+//! an incremental PDF update, fails verification.
+//!
+//! Verification only needs the file: the public key travels with the signature, and its
+//! [`SignatureInfo::key_id`] is what a recipient compares with the one the sender gave them.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use crate::audit;
+use crate::db::now_ms;
+use crate::error::{AppError, AppResult};
+use crate::secrets;
+use crate::state::AppState;
+
+const KEY_SECRET_KEY: &str = "signing.ed25519";
+// Bound into every signature so one can never be replayed as some other kind
+const CONTEXT: &[u8] = b"momentum-document-signature-v1";
+const PDF_MARKER: &[u8] = b"%MomentumSignature ";
+const ZIP_MARKER: &[u8] = b"MomentumSignature ";
+// PDF readers look for `%%EOF` in the last kilobyte, so the line has to stay well short
+const MAX_SIGNER_CHARS: usize = 120;
+const PDF_TAIL: u64 = 4096;
+const EOCD_SIGNATURE: &[u8] = b"PK\x05\x06";
+const EOCD_LEN: usize = 22;
+
+/// What kind of file a signature is embedded in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DocumentKind {
+    Pdf,
+    Archive,
+}
+
+/// Who signed a document and when; covered by the signature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureInfo {
+    pub signer: String,
+    pub signed_at: i64,
+    /// Short fingerprint of the public key.
+    pub key_id: String,
+    /// Base64 of the raw Ed25519 public key.
+    pub public_key: String,
+    pub app_version: String,
+}
+
+/// A document just signed.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentSignature {
+    pub path: PathBuf,
+    pub kind: DocumentKind,
+    pub signature: SignatureInfo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VerificationStatus {
+    /// Unchanged since it was signed.
+    Valid,
+    /// Changed after signing, or the signature is damaged.
+    Altered,
+    Unsigned,
+}
+
+/// What verifying a document found.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Verification {
+    pub status: VerificationStatus,
+    pub kind: DocumentKind,
+    /// The embedded signer details; not to be trusted unless `status` is `valid`.
+    pub signature: Option<SignatureInfo>,
+    /// Signed with this workspace's own key.
+    pub own_key: bool,
+}
+
+/// This workspace's signing key, for recipients to check signatures against.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SigningIdentity {
+    pub key_id: String,
+    pub public_key: String,
+}
+
+fn key_id(public_key: &[u8]) -> String {
+    let digest = Sha256::digest(public_key);
+    digest[..8]
+        .chunks(2)
+        .map(|pair| format!("{:02x}{:02x}", pair[0], pair[1]))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn load_key() -> AppResult<Option<Ed25519KeyPair>> {
+    let Some(stored) = secrets::get(KEY_SECRET_KEY)? else {
+        return Ok(None);
+    };
+    let pkcs8 = STANDARD
+        .decode(stored)
+        .map_err(|e| AppError::Internal(format!("stored signing key is damaged: {e}")))?;
+    Ed25519KeyPair::from_pkcs8(&pkcs8)
+        .map(Some)
+        .map_err(|e| AppError::Internal(format!("stored signing key is damaged: {e}")))
+}
+
+fn key_pair() -> AppResult<Ed25519KeyPair> {
+    if let Some(key) = load_key()? {
+        return Ok(key);
+    }
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+        .map_err(|e| AppError::Internal(format!("cannot create a signing key: {e}")))?;
+    secrets::set(KEY_SECRET_KEY, &STANDARD.encode(pkcs8.as_ref()))?;
+    tracing::info!("created workspace signing key");
+    Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+        .map_err(|e| AppError::Internal(format!("cannot create a signing key: {e}")))
+}
+
+fn message(info_json: &[u8], digest: &[u8]) -> Vec<u8> {
+    [CONTEXT, b"\0", info_json, b"\0", digest].concat()
+}
+
+/// Where the signed bytes end and what follows them, as found in one file.
+struct Layout {
+    kind: DocumentKind,
+    /// Length of the signed prefix.
+    content_len: u64,
+    /// The embedded `<info>.<signature>` block, if signed.
+    block: Option<String>,
+    /// Something was written after the signature, such as an incremental PDF update.
+    appended: bool,
+}
+
+fn read_tail(file: &mut File, len: u64) -> AppResult<(u64, Vec<u8>)> {
+    let size = file.metadata()?.len();
+    let start = size.saturating_sub(len);
+    file.seek(SeekFrom::Start(start))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+    Ok((start, tail))
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+fn block_text(bytes: &[u8]) -> Option<String> {
+    let text = std::str::from_utf8(bytes).ok()?.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn pdf_layout(file: &mut File) -> AppResult<Layout> {
+    let (start, tail) = read_tail(file, PDF_TAIL)?;
+    if rfind(&tail, b"%%EOF").is_none() {
+        return Err(AppError::InvalidInput(
+            "the PDF has no end-of-file marker".into(),
+        ));
+    }
+    // Our marker always starts a line; one inside a content stream would not
+    let marker =
+        rfind(&tail, PDF_MARKER).filter(|&at| at == 0 || matches!(tail[at - 1], b'\n' | b'\r'));
+    let Some(marker) = marker else {
+        return Ok(Layout {
+            kind: DocumentKind::Pdf,
+            content_len: start + tail.len() as u64,
+            block: None,
+            appended: false,
+        });
+    };
+    let rest = &tail[marker + PDF_MARKER.len()..];
+    let line_end = rest
+        .iter()
+        .position(|&b| matches!(b, b'\n' | b'\r'))
+        .unwrap_or(rest.len());
+    Ok(Layout {
+        kind: DocumentKind::Pdf,
+        content_len: start + marker as u64,
+        block: block_text(&rest[..line_end]),
+        appended: !rest[line_end..].iter().all(u8::is_ascii_whitespace),
+    })
+}
+
+fn zip_layout(file: &mut File) -> AppResult<Layout> {
+    let (start, tail) = read_tail(file, (EOCD_LEN + u16::MAX as usize) as u64)?;
+    // The real end record is the last one whose comment length reaches the end of the file
+    let eocd = (0..=tail.len().saturating_sub(EOCD_LEN))
+        .rev()
+        .find(|&at| {
+            tail[at..].starts_with(EOCD_SIGNATURE) && {
+                let len = u16::from_le_bytes([tail[at + 20], tail[at + 21]]) as usize;
+                at + EOCD_LEN + len == tail.len()
+            }
+        })
+        .ok_or_else(|| AppError::InvalidInput("the archive has no end record".into()))?;
+    let comment = &tail[eocd + EOCD_LEN..];
+    Ok(Layout {
+        kind: DocumentKind::Archive,
+        // The comment length field changes with the signature, so it is not signed
+        content_len: start + eocd as u64 + 20,
+        block: comment.strip_prefix(ZIP_MARKER).and_then(block_text),
+        appended: false,
+    })
+}
+
+fn layout(path: &Path) -> AppResult<(File, Layout)> {
+    let mut file = File::open(path)?;
+    let mut magic = [0u8; 5];
+    let read = file.read(&mut magic)?;
+    let layout = match &magic[..read] {
+        b"%PDF-" => pdf_layout(&mut file)?,
+        [b'P', b'K', 3, 4, _] => zip_layout(&mut file)?,
+        _ => {
+            return Err(AppError::InvalidInput(format!(
+                "{} is neither a PDF nor an archive",
+                path.display()
+            )))
+        }
+    };
+    Ok((file, layout))
+}
+
+// SHA-256 of the first `len` bytes followed by `suffix`
+fn digest(file: &mut File, len: u64, suffix: &[u8]) -> AppResult<Vec<u8>> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut hasher = Sha256::new();
+    let copied = std::io::copy(&mut file.take(len), &mut hasher)?;
+    if copied != len {
+        return Err(AppError::Internal(
+            "the file changed while it was read".into(),
+        ));
+    }
+    hasher.update(suffix);
+    Ok(hasher.finalize().to_vec())
+}
+
+fn sign(path: &Path, signer: String) -> AppResult<DocumentSignature> {
+    let (mut file, layout) = layout(path)?;
+    if layout.appended {
+        return Err(AppError::InvalidInput(
+            "the PDF was changed after it was signed; export it again to sign it".into(),
+        ));
+    }
+    let key = key_pair()?;
+    let public_key = key.public_key().as_ref();
+    let info = SignatureInfo {
+        signer: signer.chars().take(MAX_SIGNER_CHARS).collect(),
+        signed_at: now_ms(),
+        key_id: key_id(public_key),
+        public_key: STANDARD.encode(public_key),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+    };
+    let info_json = serde_json::to_vec(&info)?;
+
+    // The signature goes on a line of its own, and the newline before it is signed too
+    let mut separator: &[u8] = b"";
+    if layout.kind == DocumentKind::Pdf && layout.content_len > 0 {
+        file.seek(SeekFrom::Start(layout.content_len - 1))?;
+        let mut last = [0u8];
+        file.read_exact(&mut last)?;
+        if !matches!(last[0], b'\n' | b'\r') {
+            separator = b"\n";
+        }
+    }
+    let digest = digest(&mut file, layout.content_len, separator)?;
+    drop(file);
+    let signature = key.sign(&message(&info_json, &digest));
+    let block = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(&info_json),
+        URL_SAFE_NO_PAD.encode(signature.as_ref())
+    );
+
+    let tmp = path.with_extension("signing.tmp");
+    let written = (|| -> AppResult<()> {
+        fs::copy(path, &tmp)?;
+        let mut out = OpenOptions::new().write(true).open(&tmp)?;
+        out.set_len(layout.content_len)?;
+        out.seek(SeekFrom::End(0))?;
+        match layout.kind {
+            DocumentKind::Pdf => {
+                out.write_all(separator)?;
+                out.write_all(PDF_MARKER)?;
+                out.write_all(block.as_bytes())?;
+                out.write_all(b"\n")?;
+            }
+            DocumentKind::Archive => {
+                let comment = [ZIP_MARKER, block.as_bytes()].concat();
+                out.write_all(&(comment.len() as u16).to_le_bytes())?;
+                out.write_all(&comment)?;
+            }
+        }
+        out.sync_all()?;
+        drop(out);
+        fs::rename(&tmp, path)?;
+        Ok(())
+    })();
+    if let Err(err) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+    Ok(DocumentSignature {
+        path: path.to_path_buf(),
+        kind: layout.kind,
+        signature: info,
+    })
+}
+
+fn verify(path: &Path) -> AppResult<Verification> {
+    let (mut file, layout) = layout(path)?;
+    let result = |status, signature| Verification {
+        status,
+        kind: layout.kind,
+        signature,
+        own_key: false,
+    };
+    let Some(block) = &layout.block else {
+        return Ok(result(VerificationStatus::Unsigned, None));
+    };
+    let decoded = block.split_once('.').and_then(|(info, signature)| {
+        let info_json = URL_SAFE_NO_PAD.decode(info).ok()?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        let info = serde_json::from_slice::<SignatureInfo>(&info_json).ok()?;
+        Some((info_json, signature, info))
+    });
+    let Some((info_json, signature, info)) = decoded else {
+        return Ok(result(VerificationStatus::Altered, None));
+    };
+    let Ok(public_key) = STANDARD.decode(&info.public_key) else {
+        return Ok(result(VerificationStatus::Altered, Some(info)));
+    };
+    let digest = digest(&mut file, layout.content_len, b"")?;
+    let valid = !layout.appended
+        && key_id(&public_key) == info.key_id
+        && UnparsedPublicKey::new(&ED25519, &public_key)
+            .verify(&message(&info_json, &digest), &signature)
+            .is_ok();
+    if !valid {
+        return Ok(result(VerificationStatus::Altered, Some(info)));
+    }
+    let own_key = load_key()?.is_some_and(|key| key.public_key().as_ref() == public_key);
+    Ok(Verification {
+        own_key,
+        ..result(VerificationStatus::Valid, Some(info))
+    })
+}
+
+/// Sign an exported PDF or project archive in place with this workspace's key.
+///
+/// Signing again replaces the earlier signature; so does signing an archive that already
+/// has a zip comment.
+#[tauri::command]
+pub async fn sign_document(
+    state: State<'_, AppState>,
+    path: PathBuf,
+) -> AppResult<DocumentSignature> {
+    let signer = audit::actor(&state);
+    let signed = tauri::async_runtime::spawn_blocking(move || sign(&path, signer)).await??;
+    tracing::info!(
+        path = %signed.path.display(),
+        key = %signed.signature.key_id,
+        "document signed"
+    );
+    Ok(signed)
+}
+
+/// Check whether a PDF or archive is unchanged since it was signed.
+#[tauri::command]
+pub async fn verify_document(path: PathBuf) -> AppResult<Verification> {
+    tauri::async_runtime::spawn_blocking(move || verify(&path)).await?
+}
+
+/// This workspace's signing key fingerprint, creating the key if there is none yet.
+#[tauri::command]
+pub async fn get_signing_identity() -> AppResult<SigningIdentity> {
+    tauri::async_runtime::spawn_blocking(|| {
+        let key = key_pair()?;
+        let public_key = key.public_key().as_ref();
+        Ok(SigningIdentity {
+            key_id: key_id(public_key),
+            public_key: STANDARD.encode(public_key),
+        })
+    })
+    .await?
+}
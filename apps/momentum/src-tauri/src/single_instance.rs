@@ -0,0 +1,36 @@
+//! Single-instance enforcement so a second launch reuses the running app.
+
+use std::path::Path;
+
+use serde::Serialize;
+use tauri::plugin::TauriPlugin;
+use tauri::{AppHandle, Emitter, Wry};
+
+use crate::{file_open, windows};
+
+/// Emitted with the forwarded launch arguments of a second instance.
+pub const SECOND_INSTANCE_EVENT: &str = "second-instance";
+
+/// Payload for `second-instance`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecondInstance {
+    pub args: Vec<String>,
+    pub cwd: String,
+}
+
+/// Build the plugin; it must be registered before any other plugin.
+///
+/// Deep links passed to the second process are re-dispatched through the
+/// deep-link plugin, so they arrive via the normal `on_open_url` path.
+pub fn plugin() -> TauriPlugin<Wry> {
+    tauri_plugin_single_instance::init(|app: &AppHandle, args, cwd| {
+        tracing::info!(?args, "second instance launched");
+        windows::show_main_window(app);
+        file_open::open_paths(
+            app,
+            file_open::project_paths_from_args(args.clone(), Path::new(&cwd)),
+        );
+        let _ = app.emit(SECOND_INSTANCE_EVENT, SecondInstance { args, cwd });
+    })
+}
@@ -0,0 +1,450 @@
+//! The webview spell-checker's custom dictionary.
+//!
+//! Proposal and estimate text is full of trade terms that OS dictionaries flag. The terms in
+//! `dictionary.txt` ship with the app, and users add their own words or drop bundled ones;
+//! both lists belong to the workspace and live in its data dir. The OS dictionary the
+//! webview checks against is shared by every workspace, so at startup it is brought in line
+//! with the current one, taking back out the words this app taught it for another.
+//!
+//! macOS teaches `NSSpellChecker`, Windows the user dictionary of the Windows spell checker
+//! that WebView2 uses, and Linux the Enchant personal word list that WebKitGTK reads, after
+//! turning on spell-checking, which WebKitGTK leaves off.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::error::{AppError, AppResult};
+
+/// Emitted with the new [`DictionaryWords`] whenever the dictionary changes.
+pub const DICTIONARY_CHANGED_EVENT: &str = "spellcheck:dictionary";
+
+const BUNDLED_TERMS: &str = include_str!("../dictionary.txt");
+const DICTIONARY_FILE_NAME: &str = "dictionary.json";
+// Beside the config file, since the OS dictionary outlives a workspace switch
+const APPLIED_FILE_NAME: &str = "spellcheck-applied.json";
+const MAX_WORD_LEN: usize = 64;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct StoredDictionary {
+    added: BTreeSet<String>,
+    /// Bundled terms the workspace wants flagged after all.
+    removed: BTreeSet<String>,
+}
+
+/// The workspace's dictionary.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DictionaryWords {
+    /// Every word accepted on top of the OS dictionary, sorted.
+    pub words: Vec<String>,
+    /// Words added in this workspace.
+    pub custom: Vec<String>,
+    /// Bundled terms removed in this workspace.
+    pub removed: Vec<String>,
+}
+
+/// Dictionary state registered in app state.
+pub struct Dictionary {
+    bundled: BTreeSet<String>,
+    path: PathBuf,
+    applied_path: PathBuf,
+    stored: Mutex<StoredDictionary>,
+}
+
+fn parse_terms(text: &str) -> BTreeSet<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+fn read_json<T: Default + for<'de> Deserialize<'de>>(path: &Path) -> T {
+    fs::read(path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> AppResult<()> {
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(value)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+// Spell-checkers look words up one at a time, so a phrase could never match
+fn normalize_word(word: &str) -> AppResult<String> {
+    let word = word.trim();
+    if word.is_empty() || word.chars().count() > MAX_WORD_LEN {
+        return Err(AppError::InvalidInput(format!(
+            "dictionary words must be 1 to {MAX_WORD_LEN} characters"
+        )));
+    }
+    if word.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(AppError::InvalidInput(format!(
+            "\"{word}\" is not a single word"
+        )));
+    }
+    Ok(word.to_string())
+}
+
+/// Load the bundled terms and the workspace's changes to them.
+pub fn init(data_dir: &Path, config_path: &Path) -> AppResult<Dictionary> {
+    let path = data_dir.join(DICTIONARY_FILE_NAME);
+    Ok(Dictionary {
+        bundled: parse_terms(BUNDLED_TERMS),
+        stored: Mutex::new(read_json(&path)),
+        path,
+        applied_path: config_path.with_file_name(APPLIED_FILE_NAME),
+    })
+}
+
+impl Dictionary {
+    fn words(&self, stored: &StoredDictionary) -> BTreeSet<String> {
+        self.bundled
+            .difference(&stored.removed)
+            .chain(&stored.added)
+            .cloned()
+            .collect()
+    }
+
+    fn describe(&self, stored: &StoredDictionary) -> DictionaryWords {
+        DictionaryWords {
+            words: self.words(stored).into_iter().collect(),
+            custom: stored.added.iter().cloned().collect(),
+            removed: stored.removed.iter().cloned().collect(),
+        }
+    }
+
+    pub fn snapshot(&self) -> DictionaryWords {
+        self.describe(&self.stored.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    // Teaches the OS dictionary what it is missing and takes back what it no longer needs
+    fn apply(&self, words: &BTreeSet<String>) -> AppResult<()> {
+        let applied: BTreeSet<String> = read_json(&self.applied_path);
+        let stale: BTreeSet<String> = applied.difference(words).cloned().collect();
+        let missing: BTreeSet<String> = words.difference(&applied).cloned().collect();
+        if stale.is_empty() && missing.is_empty() {
+            return Ok(());
+        }
+        platform::unlearn(&stale)?;
+        // Words the user had taught the OS themselves are left theirs
+        let learned = platform::learn(&missing)?;
+        let applied: BTreeSet<String> =
+            applied.difference(&stale).cloned().chain(learned).collect();
+        write_json(&self.applied_path, &applied)?;
+        tracing::debug!(
+            learned = missing.len(),
+            unlearned = stale.len(),
+            "spell-check dictionary applied"
+        );
+        Ok(())
+    }
+
+    fn update(
+        &self,
+        app: &AppHandle,
+        change: impl FnOnce(&BTreeSet<String>, &mut StoredDictionary),
+    ) -> AppResult<DictionaryWords> {
+        let mut stored = self.stored.lock().unwrap_or_else(|e| e.into_inner());
+        change(&self.bundled, &mut stored);
+        write_json(&self.path, &*stored)?;
+        let words = self.describe(&stored);
+        // Saved either way; the next launch tries the OS dictionary again
+        if let Err(err) = self.apply(&self.words(&stored)) {
+            tracing::warn!(%err, "cannot update the OS spell-check dictionary");
+        }
+        let _ = app.emit(DICTIONARY_CHANGED_EVENT, &words);
+        Ok(words)
+    }
+}
+
+/// Bring the OS dictionary in line with this workspace, off the main thread.
+pub fn start(app: &AppHandle) {
+    platform::enable(app);
+    let handle = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let dictionary = handle.state::<Dictionary>();
+        let words = dictionary.words(&dictionary.stored.lock().unwrap_or_else(|e| e.into_inner()));
+        if let Err(err) = dictionary.apply(&words) {
+            tracing::warn!(%err, "cannot update the OS spell-check dictionary");
+        }
+    });
+}
+
+// As Windows names languages; Enchant and WebKitGTK want `en_US` for `en-US`
+#[cfg(any(windows, target_os = "linux"))]
+fn language_tag() -> String {
+    crate::locale::current().locale
+}
+
+#[tauri::command]
+pub async fn get_dictionary(dictionary: State<'_, Dictionary>) -> AppResult<DictionaryWords> {
+    Ok(dictionary.snapshot())
+}
+
+/// Accept `words` from now on; a removed bundled term is restored instead.
+#[tauri::command]
+pub async fn add_dictionary_words(
+    app: AppHandle,
+    words: Vec<String>,
+) -> AppResult<DictionaryWords> {
+    let words = words
+        .iter()
+        .map(|word| normalize_word(word))
+        .collect::<AppResult<Vec<_>>>()?;
+    tauri::async_runtime::spawn_blocking(move || {
+        app.state::<Dictionary>().update(&app, |bundled, stored| {
+            for word in words {
+                if bundled.contains(&word) {
+                    stored.removed.remove(&word);
+                } else {
+                    stored.added.insert(word);
+                }
+            }
+        })
+    })
+    .await?
+}
+
+/// Flag `words` again, whether they were added here or ship with the app.
+#[tauri::command]
+pub async fn remove_dictionary_words(
+    app: AppHandle,
+    words: Vec<String>,
+) -> AppResult<DictionaryWords> {
+    tauri::async_runtime::spawn_blocking(move || {
+        app.state::<Dictionary>().update(&app, |bundled, stored| {
+            for word in words.iter().map(|word| word.trim()) {
+                stored.added.remove(word);
+                if bundled.contains(word) {
+                    stored.removed.insert(word.to_string());
+                }
+            }
+        })
+    })
+    .await?
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::collections::BTreeSet;
+
+    use objc2_app_kit::NSSpellChecker;
+    use objc2_foundation::NSString;
+    use tauri::AppHandle;
+
+    use crate::error::AppResult;
+
+    // WKWebView follows the system spell checker, so there is nothing to turn on
+    pub fn enable(_app: &AppHandle) {}
+
+    pub fn learn(words: &BTreeSet<String>) -> AppResult<Vec<String>> {
+        let checker = NSSpellChecker::sharedSpellChecker();
+        let mut learned = Vec::new();
+        for word in words {
+            let word_ns = NSString::from_str(word);
+            if !checker.hasLearnedWord(&word_ns) {
+                checker.learnWord(&word_ns);
+                learned.push(word.clone());
+            }
+        }
+        Ok(learned)
+    }
+
+    pub fn unlearn(words: &BTreeSet<String>) -> AppResult<()> {
+        let checker = NSSpellChecker::sharedSpellChecker();
+        for word in words {
+            checker.unlearnWord(&NSString::from_str(word));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::collections::BTreeSet;
+
+    use tauri::AppHandle;
+    use windows::core::{Interface, HSTRING};
+    use windows::Win32::Globalization::{
+        ISpellChecker2, ISpellCheckerFactory, SpellCheckerFactory,
+    };
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_MULTITHREADED,
+    };
+
+    use crate::error::{AppError, AppResult};
+
+    // WebView2 checks with the Windows spell checker, which is on by default
+    pub fn enable(_app: &AppHandle) {}
+
+    fn checker() -> AppResult<ISpellChecker2> {
+        let tag = HSTRING::from(super::language_tag());
+        let unavailable = |err: windows::core::Error| {
+            AppError::Internal(format!("no spell checker for {tag}: {err}"))
+        };
+        unsafe {
+            // Fails harmlessly when the thread already joined an apartment
+            let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+            let factory: ISpellCheckerFactory =
+                CoCreateInstance(&SpellCheckerFactory, None, CLSCTX_INPROC_SERVER)
+                    .map_err(unavailable)?;
+            if !factory.IsSupported(&tag).map_err(unavailable)?.as_bool() {
+                return Err(AppError::Internal(format!("no spell checker for {tag}")));
+            }
+            factory
+                .CreateSpellChecker(&tag)
+                .and_then(|checker| checker.cast())
+                .map_err(unavailable)
+        }
+    }
+
+    pub fn learn(words: &BTreeSet<String>) -> AppResult<Vec<String>> {
+        if words.is_empty() {
+            return Ok(Vec::new());
+        }
+        let checker = checker()?;
+        for word in words {
+            unsafe { checker.Add(&HSTRING::from(word.as_str())) }
+                .map_err(|err| AppError::Internal(format!("cannot add {word}: {err}")))?;
+        }
+        Ok(words.iter().cloned().collect())
+    }
+
+    pub fn unlearn(words: &BTreeSet<String>) -> AppResult<()> {
+        if words.is_empty() {
+            return Ok(());
+        }
+        let checker = checker()?;
+        for word in words {
+            unsafe { checker.Remove(&HSTRING::from(word.as_str())) }
+                .map_err(|err| AppError::Internal(format!("cannot remove {word}: {err}")))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::collections::BTreeSet;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use tauri::{AppHandle, Manager};
+    use webkit2gtk::{WebContextExt, WebViewExt};
+
+    use crate::error::{AppError, AppResult};
+    use crate::windows::MAIN_WINDOW;
+
+    fn language() -> String {
+        super::language_tag().replace('-', "_")
+    }
+
+    // Enchant's personal word list for the language, which it rereads when it changes
+    fn word_list() -> AppResult<PathBuf> {
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .ok_or_else(|| AppError::Internal("no home directory for Enchant".into()))?;
+        Ok(config.join("enchant").join(format!("{}.dic", language())))
+    }
+
+    fn read_words() -> AppResult<(PathBuf, Vec<String>)> {
+        let path = word_list()?;
+        let words = match fs::read_to_string(&path) {
+            Ok(text) => text.lines().map(str::to_string).collect(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok((path, words))
+    }
+
+    fn write_words(path: &PathBuf, words: &[String]) -> AppResult<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut text = words.join("\n");
+        text.push('\n');
+        let tmp = path.with_extension("dic.tmp");
+        fs::write(&tmp, text)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    // The context is shared by every window, so the main one is enough
+    pub fn enable(app: &AppHandle) {
+        let Some(window) = app.get_webview_window(MAIN_WINDOW) else {
+            return;
+        };
+        let language = language();
+        let enabled = window.with_webview(move |webview| {
+            if let Some(context) = webview.inner().context() {
+                context.set_spell_checking_languages(&[&language]);
+                context.set_spell_checking_enabled(true);
+            }
+        });
+        if let Err(err) = enabled {
+            tracing::warn!(%err, "cannot turn on spell-checking");
+        }
+    }
+
+    pub fn learn(words: &BTreeSet<String>) -> AppResult<Vec<String>> {
+        if words.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (path, mut existing) = read_words()?;
+        let learned: Vec<String> = words
+            .iter()
+            .filter(|word| !existing.contains(word))
+            .cloned()
+            .collect();
+        if !learned.is_empty() {
+            existing.extend(learned.iter().cloned());
+            write_words(&path, &existing)?;
+        }
+        Ok(learned)
+    }
+
+    pub fn unlearn(words: &BTreeSet<String>) -> AppResult<()> {
+        if words.is_empty() {
+            return Ok(());
+        }
+        let (path, mut existing) = read_words()?;
+        let before = existing.len();
+        existing.retain(|word| !words.contains(word));
+        if existing.len() != before {
+            write_words(&path, &existing)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(mobile)]
+mod platform {
+    use std::collections::BTreeSet;
+
+    use tauri::AppHandle;
+
+    use crate::error::AppResult;
+
+    // The system keyboards keep their own dictionaries, out of an app's reach
+    pub fn enable(_app: &AppHandle) {}
+
+    pub fn learn(_words: &BTreeSet<String>) -> AppResult<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    pub fn unlearn(_words: &BTreeSet<String>) -> AppResult<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+//! Splash window shown while the app starts.
+//!
+//! The splash is a static page that paints at once, long before the frontend bundle has
+//! loaded. The main window is built hidden behind it and swapped in when the frontend calls
+//! `frontend_ready`, or after [`READY_TIMEOUT`] so a frontend that never reports in still
+//! gets shown.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tauri::{AppHandle, Manager, WebviewWindow};
+
+use crate::error::AppResult;
+use crate::windows::{self, MAIN_WINDOW};
+
+/// Label of the splash window.
+pub const SPLASH_WINDOW: &str = "splash";
+
+/// How long to wait for the frontend before showing the main window anyway.
+pub const READY_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[cfg(desktop)]
+const PAGE: &str = "splash.html";
+#[cfg(desktop)]
+const WIDTH: f64 = 420.0;
+#[cfg(desktop)]
+const HEIGHT: f64 = 260.0;
+
+/// Whether the main window has been swapped in, registered in app state.
+pub struct Splash {
+    finished: AtomicBool,
+    show_main: bool,
+}
+
+/// Open the splash window; call first thing in setup.
+#[cfg(desktop)]
+pub fn show(app: &AppHandle) {
+    let builder =
+        tauri::WebviewWindowBuilder::new(app, SPLASH_WINDOW, tauri::WebviewUrl::App(PAGE.into()));
+    let built = crate::portable::webview(builder)
+        .title(&app.package_info().name)
+        .inner_size(WIDTH, HEIGHT)
+        .resizable(false)
+        .decorations(false)
+        .skip_taskbar(true)
+        .center()
+        .focused(false)
+        .build();
+    // The main window still appears once ready; only the placeholder is lost
+    if let Err(err) = built {
+        tracing::warn!(%err, "cannot open splash window");
+    }
+}
+
+/// Manage the splash state and start the readiness timeout; the main window must exist.
+///
+/// Without `show_main`, as when launched hidden at login, readiness only closes the splash.
+pub fn await_frontend(app: &AppHandle, show_main: bool) {
+    app.manage(Splash {
+        finished: AtomicBool::new(false),
+        show_main,
+    });
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(READY_TIMEOUT).await;
+        if finish(&app) {
+            tracing::warn!(
+                timeout = ?READY_TIMEOUT,
+                "frontend did not report ready; showing the main window"
+            );
+        }
+    });
+}
+
+// Returns whether this call did the swap
+fn finish(app: &AppHandle) -> bool {
+    let Some(splash) = app.try_state::<Splash>() else {
+        return false;
+    };
+    if splash.finished.swap(true, Ordering::SeqCst) {
+        return false;
+    }
+    if splash.show_main {
+        windows::show_main_window(app);
+    }
+    if let Some(window) = app.get_webview_window(SPLASH_WINDOW) {
+        let _ = window.destroy();
+    }
+    true
+}
+
+/// Show the main window in place of the splash once the frontend has rendered.
+///
+/// Only the main window's call counts; secondary windows load the same frontend.
+#[tauri::command]
+pub async fn frontend_ready(window: WebviewWindow) -> AppResult<()> {
+    if window.label() == MAIN_WINDOW && finish(window.app_handle()) {
+        tracing::debug!("frontend ready");
+    }
+    Ok(())
+}
@@ -0,0 +1,103 @@
+//! Central application state shared by every subsystem.
+
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use tauri::AppHandle;
+
+use crate::config::Config;
+use crate::error::AppResult;
+use crate::rbac::{self, Role};
+use crate::tasks::TaskRegistry;
+use crate::workspaces::Workspaces;
+
+const PROJECTS_DIR_NAME: &str = "projects";
+
+/// Process-wide state registered with `app.manage()` during setup.
+pub struct AppState {
+    config: RwLock<Config>,
+    config_path: PathBuf,
+    data_dir: PathBuf,
+    role: RwLock<Option<Role>>,
+    user: RwLock<Option<String>>,
+    tasks: TaskRegistry,
+}
+
+impl AppState {
+    /// Load config and resolve the data dir every other subsystem writes under.
+    pub fn load(app: &AppHandle, workspaces: &Workspaces) -> AppResult<Self> {
+        let config_path = Config::path(app)?;
+        let config = Config::load(&config_path)?;
+        let data_dir = workspaces.data_dir(&config);
+        std::fs::create_dir_all(&data_dir)?;
+
+        Ok(Self {
+            config: RwLock::new(config),
+            config_path,
+            data_dir,
+            role: RwLock::new(rbac::load_role()),
+            user: RwLock::new(rbac::load_user()),
+            tasks: TaskRegistry::default(),
+        })
+    }
+
+    /// Snapshot of the current config.
+    pub fn config(&self) -> Config {
+        self.config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Swap in a new config; returns whether anything changed.
+    pub fn replace_config(&self, config: Config) -> bool {
+        let mut current = self.config.write().unwrap_or_else(|e| e.into_inner());
+        if *current == config {
+            return false;
+        }
+        *current = config;
+        true
+    }
+
+    /// Role of the signed-in user; `None` if this machine has never signed in.
+    pub fn role(&self) -> Option<Role> {
+        *self.role.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_role(&self, role: Option<Role>) {
+        *self.role.write().unwrap_or_else(|e| e.into_inner()) = role;
+    }
+
+    /// Email or subject of the last signed-in user, kept after signing out.
+    pub fn user(&self) -> Option<String> {
+        self.user.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn set_user(&self, user: Option<String>) {
+        *self.user.write().unwrap_or_else(|e| e.into_inner()) = user;
+    }
+
+    /// Long-running commands that can be followed and cancelled.
+    pub fn tasks(&self) -> &TaskRegistry {
+        &self.tasks
+    }
+
+    pub fn config_path(&self) -> &Path {
+        &self.config_path
+    }
+
+    /// Data dir resolved at startup; config changes to it apply on next launch.
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    /// Parent of every project's folder.
+    pub fn projects_dir(&self) -> PathBuf {
+        self.data_dir.join(PROJECTS_DIR_NAME)
+    }
+
+    /// Folder holding a project's files, such as attachments; created on first use.
+    pub fn project_dir(&self, project_id: &str) -> PathBuf {
+        self.projects_dir().join(project_id)
+    }
+}
@@ -0,0 +1,112 @@
+//! Versioned upgrades for the frontend's `tauri-plugin-store` files, run at startup before
+//! any window can load them.
+//!
+//! Each known file records its shape under [`VERSION_KEY`]; a file without one is version 0,
+//! the shape it had before versioning. Transforms run in order from the file's version to
+//! the latest, the original is kept beside it as `<name>.v<version>.bak`, and the result is
+//! written through a temp file and a rename. A file from a newer build, or one that no
+//! longer parses, is left untouched for the frontend to handle. Append a transform to a
+//! file's list whenever its shape changes; never edit one that has shipped.
+
+use std::fs;
+use std::path::Path;
+
+use serde_json::{Map, Value};
+use tauri::AppHandle;
+
+use crate::error::{AppError, AppResult};
+use crate::portable;
+
+/// Key holding a store file's shape version.
+pub const VERSION_KEY: &str = "__version";
+
+/// Turns a file's contents from one version into the next.
+type Transform = fn(Map<String, Value>) -> AppResult<Map<String, Value>>;
+
+struct StoreFile {
+    /// Path relative to the app data dir, as given to the store plugin.
+    name: &'static str,
+    /// `transforms[n]` upgrades version `n` to `n + 1`.
+    transforms: &'static [Transform],
+}
+
+const STORE_FILES: &[StoreFile] = &[StoreFile {
+    name: "settings.json",
+    transforms: &[settings_v1],
+}];
+
+/// Whether `name` is a known store file or a backup kept by upgrading one.
+pub(crate) fn is_store_file(name: &str) -> bool {
+    STORE_FILES.iter().any(|file| {
+        name.strip_prefix(file.name)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(".v"))
+    })
+}
+
+// Version 1 is the unversioned shape, now stamped
+fn settings_v1(store: Map<String, Value>) -> AppResult<Map<String, Value>> {
+    Ok(store)
+}
+
+fn version(store: &Map<String, Value>) -> u64 {
+    store.get(VERSION_KEY).and_then(Value::as_u64).unwrap_or(0)
+}
+
+// Returns the version the file was upgraded from, if it needed upgrading
+fn migrate(path: &Path, transforms: &[Transform]) -> AppResult<Option<u64>> {
+    let original = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let Ok(Value::Object(mut store)) = serde_json::from_slice::<Value>(&original) else {
+        tracing::warn!(path = %path.display(), "store file is not a JSON object; left as is");
+        return Ok(None);
+    };
+    let from = version(&store);
+    let latest = transforms.len() as u64;
+    if from >= latest {
+        if from > latest {
+            tracing::warn!(path = %path.display(), from, latest, "store file is from a newer build");
+        }
+        return Ok(None);
+    }
+
+    for (version, transform) in transforms.iter().enumerate().skip(from as usize) {
+        store = transform(store).map_err(|err| {
+            AppError::Internal(format!(
+                "upgrading {} to version {}: {err}",
+                path.display(),
+                version + 1
+            ))
+        })?;
+    }
+    store.insert(VERSION_KEY.into(), Value::from(latest));
+
+    let mut backup = path.as_os_str().to_os_string();
+    backup.push(format!(".v{from}.bak"));
+    fs::write(&backup, &original)?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(&store)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(Some(from))
+}
+
+/// Upgrade every known store file; one that fails is logged and left for the next launch.
+pub fn run(app: &AppHandle) -> AppResult<()> {
+    let dir = portable::app_data_dir(app)?;
+    for file in STORE_FILES {
+        let path = dir.join(file.name);
+        match migrate(&path, file.transforms) {
+            Ok(Some(from)) => tracing::info!(
+                store = file.name,
+                from,
+                to = file.transforms.len(),
+                "store file upgraded"
+            ),
+            Ok(None) => {}
+            Err(err) => tracing::error!(%err, store = file.name, "store file upgrade failed"),
+        }
+    }
+    Ok(())
+}
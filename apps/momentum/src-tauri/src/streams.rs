@@ -0,0 +1,240 @@
+//! Binary streams to the webview for payloads too large for a JSON `invoke` response.
+//!
+//! A streaming command takes a `streamId` chosen by the frontend and a `Channel`, and sends
+//! the payload down the channel as raw chunks of up to 512 KiB, which arrive in the webview
+//! as `ArrayBuffer`s. The frontend calls `stream_ack` with how many chunks it has taken in;
+//! the sender never gets more than a few chunks ahead of it, so a slow consumer holds the
+//! producer back instead of piling data up in the IPC queue.
+//!
+//! The command returns a [`StreamSummary`] once the last chunk is sent. Chunks can still be
+//! arriving then, so the frontend reads until it has received `chunks` of them.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tauri::{AppHandle, Manager, State};
+
+use crate::error::{AppError, AppResult};
+
+const CHUNK_SIZE: usize = 512 * 1024;
+/// Chunks sent but not yet acknowledged before the sender waits.
+const WINDOW: u64 = 8;
+// A webview that reloaded mid-stream never acknowledges again
+const ACK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How much a finished stream carried.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamSummary {
+    pub stream_id: String,
+    pub chunks: u64,
+    pub bytes: u64,
+}
+
+struct Flow {
+    acked: Mutex<u64>,
+    changed: Condvar,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Open streams by id, registered in app state.
+#[derive(Default)]
+pub struct Streams {
+    active: Mutex<HashMap<String, Arc<Flow>>>,
+}
+
+impl Streams {
+    fn get(&self, id: &str) -> AppResult<Arc<Flow>> {
+        self.active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+            .cloned()
+            .ok_or_else(|| AppError::not_found("stream", id))
+    }
+}
+
+/// The sending end of one stream; a [`Write`] that chunks what it is given.
+///
+/// Writes block while the frontend is behind, so use it from blocking code. Dropping it
+/// closes the stream.
+pub(crate) struct BinaryStream {
+    app: AppHandle,
+    id: String,
+    channel: Channel<InvokeResponseBody>,
+    flow: Arc<Flow>,
+    buffer: Vec<u8>,
+    chunks: u64,
+    bytes: u64,
+}
+
+impl BinaryStream {
+    pub(crate) fn open(
+        app: &AppHandle,
+        id: String,
+        channel: Channel<InvokeResponseBody>,
+    ) -> AppResult<Self> {
+        let flow = Arc::new(Flow {
+            acked: Mutex::new(0),
+            changed: Condvar::new(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        });
+        {
+            let streams = app.state::<Streams>();
+            let mut active = streams.active.lock().unwrap_or_else(|e| e.into_inner());
+            if active.contains_key(&id) {
+                return Err(AppError::InvalidInput(format!(
+                    "stream {id} is already open"
+                )));
+            }
+            active.insert(id.clone(), flow.clone());
+        }
+        Ok(Self {
+            app: app.clone(),
+            id,
+            channel,
+            flow,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+            chunks: 0,
+            bytes: 0,
+        })
+    }
+
+    /// Set once the frontend cancels the stream; writes fail from then on.
+    pub(crate) fn cancelled(&self) -> Arc<AtomicBool> {
+        self.flow.cancelled.clone()
+    }
+
+    fn wait_for_room(&self) -> io::Result<()> {
+        let deadline = Instant::now() + ACK_TIMEOUT;
+        let mut acked = self.flow.acked.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            if self.flow.cancelled.load(Ordering::Relaxed) {
+                return Err(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    "the stream was cancelled",
+                ));
+            }
+            if self.chunks < *acked + WINDOW {
+                return Ok(());
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "the webview stopped reading the stream",
+                ));
+            }
+            acked = self
+                .flow
+                .changed
+                .wait_timeout(acked, left)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    fn send_buffer(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.wait_for_room()?;
+        let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_SIZE));
+        self.bytes += chunk.len() as u64;
+        self.channel
+            .send(InvokeResponseBody::Raw(chunk))
+            .map_err(io::Error::other)?;
+        self.chunks += 1;
+        Ok(())
+    }
+
+    /// Send whatever is still buffered; `err` is what went wrong on the way, if anything.
+    ///
+    /// A stream the frontend cancelled finishes with [`AppError::Cancelled`].
+    pub(crate) fn finish(mut self, err: Option<AppError>) -> AppResult<StreamSummary> {
+        let result = match err {
+            Some(err) => Err(err),
+            None => self.send_buffer().map_err(AppError::from),
+        };
+        if self.flow.cancelled.load(Ordering::Relaxed) {
+            return Err(AppError::Cancelled);
+        }
+        result?;
+        Ok(StreamSummary {
+            stream_id: self.id.clone(),
+            chunks: self.chunks,
+            bytes: self.bytes,
+        })
+    }
+
+    /// Stream the file at `path` in full.
+    pub(crate) fn send_file(mut self, path: &Path) -> AppResult<StreamSummary> {
+        let copied = File::open(path)
+            .and_then(|mut file| io::copy(&mut file, &mut self))
+            .err()
+            .map(AppError::from);
+        self.finish(copied)
+    }
+}
+
+impl Write for BinaryStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(CHUNK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        if self.buffer.len() == CHUNK_SIZE {
+            self.send_buffer()?;
+        }
+        Ok(len)
+    }
+
+    // Chunks go out when full; a flush alone would only make them smaller
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for BinaryStream {
+    fn drop(&mut self) {
+        self.app
+            .state::<Streams>()
+            .active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.id);
+    }
+}
+
+/// Report that the frontend has taken in the first `received` chunks of a stream.
+#[tauri::command]
+pub async fn stream_ack(
+    streams: State<'_, Streams>,
+    stream_id: String,
+    received: u64,
+) -> AppResult<()> {
+    // A late ack for a stream that just finished is harmless
+    let Ok(flow) = streams.get(&stream_id) else {
+        return Ok(());
+    };
+    let mut acked = flow.acked.lock().unwrap_or_else(|e| e.into_inner());
+    *acked = (*acked).max(received);
+    flow.changed.notify_all();
+    Ok(())
+}
+
+/// Stop a stream; its command fails with `CANCELLED`.
+#[tauri::command]
+pub async fn stream_cancel(streams: State<'_, Streams>, stream_id: String) -> AppResult<()> {
+    let flow = streams.get(&stream_id)?;
+    flow.cancelled.store(true, Ordering::Relaxed);
+    // Held so a sender between its check and its wait cannot miss the wake-up
+    let _acked = flow.acked.lock().unwrap_or_else(|e| e.into_inner());
+    flow.changed.notify_all();
+    Ok(())
+}
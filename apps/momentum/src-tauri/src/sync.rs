@@ -0,0 +1,770 @@
+//! Offline-first sync of projects and items against the Truss API.
+//!
+//! Triggers record every local write in `sync_changes`. A sync pulls remote changes since the
+//! stored cursor, resolves any that collide with pending local edits, then pushes what remains.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{params, OptionalExtension, Transaction};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_http::reqwest::RequestBuilder;
+
+use crate::auth;
+use crate::background::{self, Work};
+use crate::badge;
+use crate::config;
+use crate::db::projects::{Item, Project};
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::http_client::{HttpClient, RetryPolicy};
+use crate::jobs::Scheduler;
+use crate::network::NetworkMonitor;
+use crate::power;
+use crate::state::AppState;
+use crate::tasks::TaskKind;
+use crate::wake_lock;
+
+const PULL_PATH: &str = "/v1/sync/pull";
+const PUSH_PATH: &str = "/v1/sync/push";
+const PUSH_BATCH_SIZE: usize = 200;
+// Bounds re-pushing after conflicts so a misbehaving server cannot loop us forever
+const MAX_PUSH_ROUNDS: usize = 10;
+
+const CURSOR_KEY: &str = "cursor";
+const LAST_SYNCED_KEY: &str = "last_synced_at";
+// Presence of this key suppresses the change-capture triggers
+const APPLYING_KEY: &str = "applying";
+
+/// Job kind for periodic background sync.
+pub const SYNC_JOB: &str = "sync";
+const SYNC_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Emitted with an [`EntityStatus`] as each row moves through a sync.
+pub const ENTITY_STATUS_EVENT: &str = "sync:entity-status";
+
+/// Synced tables, named as the API names them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EntityKind {
+    Project,
+    Item,
+}
+
+impl EntityKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Project => "project",
+            Self::Item => "item",
+        }
+    }
+
+    fn table(self) -> &'static str {
+        match self {
+            Self::Project => "projects",
+            Self::Item => "items",
+        }
+    }
+}
+
+impl ToSql for EntityKind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl FromSql for EntityKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "project" => Ok(Self::Project),
+            "item" => Ok(Self::Item),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+/// A row change as exchanged with the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Change {
+    pub entity: EntityKind,
+    pub id: String,
+    /// Server version a local change was based on, or the version a remote change produced.
+    pub version: i64,
+    pub deleted: bool,
+    pub changed_at: i64,
+    /// The row as serialized by [`Project`] or [`Item`]; `None` for deletions.
+    pub data: Option<Value>,
+}
+
+/// A remote change that collides with a pending local one.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub local: Change,
+    pub remote: Change,
+}
+
+/// Which side of a [`Conflict`] survives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Resolution {
+    KeepLocal,
+    KeepRemote,
+}
+
+/// Decides conflicts; implement this for strategies beyond the built-in [`ConflictStrategy`].
+pub trait ConflictResolver: Send + Sync {
+    fn resolve(&self, conflict: &Conflict) -> Resolution;
+}
+
+/// Built-in conflict strategies selectable in settings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictStrategy {
+    /// The most recent edit wins; ties go to the server.
+    #[default]
+    LastWriteWins,
+    PreferServer,
+    PreferClient,
+}
+
+impl ConflictResolver for ConflictStrategy {
+    fn resolve(&self, conflict: &Conflict) -> Resolution {
+        match self {
+            Self::LastWriteWins if conflict.local.changed_at > conflict.remote.changed_at => {
+                Resolution::KeepLocal
+            }
+            Self::LastWriteWins | Self::PreferServer => Resolution::KeepRemote,
+            Self::PreferClient => Resolution::KeepLocal,
+        }
+    }
+}
+
+/// Where a row is in the sync cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EntitySyncState {
+    Syncing,
+    Synced,
+    Conflict,
+    Failed,
+}
+
+/// Payload for `sync:entity-status`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityStatus {
+    pub entity: EntityKind,
+    pub id: String,
+    pub state: EntitySyncState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<Resolution>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Counts from one completed sync.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncSummary {
+    pub pulled: usize,
+    pub pushed: usize,
+    pub conflicts: usize,
+}
+
+/// Overall sync state for the status indicator.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncStatus {
+    pub pending: i64,
+    pub last_synced_at: Option<i64>,
+    pub syncing: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PullResponse {
+    changes: Vec<Change>,
+    cursor: String,
+    #[serde(default)]
+    has_more: bool,
+}
+
+#[derive(Serialize)]
+struct PushRequest<'a> {
+    changes: &'a [Change],
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+enum PushResult {
+    Applied {
+        entity: EntityKind,
+        id: String,
+        version: i64,
+    },
+    Conflict {
+        remote: Change,
+    },
+}
+
+#[derive(Deserialize)]
+struct PushResponse {
+    results: Vec<PushResult>,
+}
+
+enum PushOutcome {
+    Applied {
+        entity: EntityKind,
+        id: String,
+        version: i64,
+        changed_at: Option<i64>,
+    },
+    Conflict {
+        remote: Change,
+        resolution: Resolution,
+    },
+}
+
+/// Sync runner registered in app state.
+pub struct SyncEngine {
+    db: Db,
+    // Serializes manual and scheduled runs so they never interleave pushes
+    running: tokio::sync::Mutex<()>,
+}
+
+impl SyncEngine {
+    pub fn new(db: Db) -> Self {
+        Self {
+            db,
+            running: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Pull, resolve, and push using the conflict strategy from settings.
+    pub async fn sync(&self, app: &AppHandle) -> AppResult<SyncSummary> {
+        let _running = self.running.lock().await;
+        let _awake = wake_lock::hold(app, "Syncing estimates");
+        let token = auth::access_token(app)
+            .await?
+            .ok_or_else(|| AppError::Auth("sign in to sync".into()))?;
+        let strategy = app.state::<AppState>().config().sync_conflict_strategy;
+        let http = app.state::<HttpClient>();
+
+        let mut summary = SyncSummary::default();
+        self.pull(app, &http, &token, &strategy, &mut summary)
+            .await?;
+        self.push(app, &http, &token, &strategy, &mut summary)
+            .await?;
+
+        let now = now_ms().to_string();
+        self.db
+            .run(move |conn| {
+                set_state(conn, LAST_SYNCED_KEY, &now)?;
+                Ok(())
+            })
+            .await?;
+        tracing::info!(
+            pulled = summary.pulled,
+            pushed = summary.pushed,
+            conflicts = summary.conflicts,
+            "sync finished"
+        );
+        badge::spawn_refresh(app);
+        Ok(summary)
+    }
+
+    pub async fn status(&self) -> AppResult<SyncStatus> {
+        let syncing = self.running.try_lock().is_err();
+        let (pending, last_synced_at) = self
+            .db
+            .run(|conn| {
+                let pending =
+                    conn.query_row("SELECT COUNT(*) FROM sync_changes", [], |row| row.get(0))?;
+                let last_synced_at = get_state(conn, LAST_SYNCED_KEY)?.and_then(|v| v.parse().ok());
+                Ok((pending, last_synced_at))
+            })
+            .await?;
+        Ok(SyncStatus {
+            pending,
+            last_synced_at,
+            syncing,
+        })
+    }
+
+    async fn pull(
+        &self,
+        app: &AppHandle,
+        http: &HttpClient,
+        token: &str,
+        resolver: &dyn ConflictResolver,
+        summary: &mut SyncSummary,
+    ) -> AppResult<()> {
+        loop {
+            let cursor = self.db.run(|conn| get_state(conn, CURSOR_KEY)).await?;
+            let mut url = config::api_url(app, PULL_PATH)?;
+            if let Some(cursor) = &cursor {
+                url.query_pairs_mut().append_pair("since", cursor);
+            }
+            let page: PullResponse = send(http, http.client().get(url), token).await?;
+            let pending = self.pending(None).await?;
+
+            let mut resolved = Vec::with_capacity(page.changes.len());
+            for remote in page.changes {
+                let local = pending
+                    .iter()
+                    .find(|c| c.entity == remote.entity && c.id == remote.id);
+                let resolution = match local {
+                    Some(local) => {
+                        let conflict = Conflict {
+                            local: local.clone(),
+                            remote: remote.clone(),
+                        };
+                        Some(resolver.resolve(&conflict))
+                    }
+                    None => None,
+                };
+                resolved.push((remote, resolution));
+            }
+
+            let has_more = page.has_more;
+            let next_cursor = page.cursor;
+            let applied = self
+                .db
+                .run(move |conn| {
+                    let tx = begin_apply(conn)?;
+                    for (remote, resolution) in &resolved {
+                        match resolution {
+                            None | Some(Resolution::KeepRemote) => apply_remote(&tx, remote)?,
+                            Some(Resolution::KeepLocal) => rebase_local(&tx, remote)?,
+                        }
+                    }
+                    set_state(&tx, CURSOR_KEY, &next_cursor)?;
+                    finish_apply(tx)?;
+                    Ok(resolved)
+                })
+                .await?;
+
+            for (remote, resolution) in applied {
+                summary.pulled += 1;
+                let state = match resolution {
+                    Some(_) => {
+                        summary.conflicts += 1;
+                        EntitySyncState::Conflict
+                    }
+                    None => EntitySyncState::Synced,
+                };
+                emit_status(app, remote.entity, remote.id, state, resolution, None);
+            }
+            if !has_more {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn push(
+        &self,
+        app: &AppHandle,
+        http: &HttpClient,
+        token: &str,
+        resolver: &dyn ConflictResolver,
+        summary: &mut SyncSummary,
+    ) -> AppResult<()> {
+        let url = config::api_url(app, PUSH_PATH)?;
+        for _ in 0..MAX_PUSH_ROUNDS {
+            let changes = self.pending(Some(PUSH_BATCH_SIZE)).await?;
+            if changes.is_empty() {
+                return Ok(());
+            }
+            for change in &changes {
+                emit_status(
+                    app,
+                    change.entity,
+                    change.id.clone(),
+                    EntitySyncState::Syncing,
+                    None,
+                    None,
+                );
+            }
+
+            let body = serde_json::to_vec(&PushRequest { changes: &changes })?;
+            let request = http
+                .client()
+                .post(url.clone())
+                .header("Content-Type", "application/json")
+                .body(body);
+            let response: PushResponse = match send(http, request, token).await {
+                Ok(response) => response,
+                Err(err) => {
+                    for change in &changes {
+                        emit_status(
+                            app,
+                            change.entity,
+                            change.id.clone(),
+                            EntitySyncState::Failed,
+                            None,
+                            Some(err.to_string()),
+                        );
+                    }
+                    return Err(err);
+                }
+            };
+
+            let pushed: HashMap<(EntityKind, String), Change> = changes
+                .into_iter()
+                .map(|c| ((c.entity, c.id.clone()), c))
+                .collect();
+            let mut outcomes = Vec::with_capacity(response.results.len());
+            for result in response.results {
+                match result {
+                    PushResult::Applied {
+                        entity,
+                        id,
+                        version,
+                    } => {
+                        let changed_at = pushed.get(&(entity, id.clone())).map(|c| c.changed_at);
+                        outcomes.push(PushOutcome::Applied {
+                            entity,
+                            id,
+                            version,
+                            changed_at,
+                        });
+                    }
+                    PushResult::Conflict { remote } => {
+                        let Some(local) = pushed.get(&(remote.entity, remote.id.clone())) else {
+                            continue;
+                        };
+                        let resolution = resolver.resolve(&Conflict {
+                            local: local.clone(),
+                            remote: remote.clone(),
+                        });
+                        outcomes.push(PushOutcome::Conflict { remote, resolution });
+                    }
+                }
+            }
+
+            let outcomes = self
+                .db
+                .run(move |conn| {
+                    let tx = begin_apply(conn)?;
+                    for outcome in &outcomes {
+                        match outcome {
+                            PushOutcome::Applied {
+                                entity,
+                                id,
+                                version,
+                                changed_at,
+                            } => mark_pushed(&tx, *entity, id, *version, *changed_at)?,
+                            PushOutcome::Conflict {
+                                remote,
+                                resolution: Resolution::KeepRemote,
+                            } => apply_remote(&tx, remote)?,
+                            PushOutcome::Conflict {
+                                remote,
+                                resolution: Resolution::KeepLocal,
+                            } => rebase_local(&tx, remote)?,
+                        }
+                    }
+                    finish_apply(tx)?;
+                    Ok(outcomes)
+                })
+                .await?;
+
+            let mut rebased = false;
+            for outcome in outcomes {
+                match outcome {
+                    PushOutcome::Applied { entity, id, .. } => {
+                        summary.pushed += 1;
+                        emit_status(app, entity, id, EntitySyncState::Synced, None, None);
+                    }
+                    PushOutcome::Conflict { remote, resolution } => {
+                        summary.conflicts += 1;
+                        rebased |= resolution == Resolution::KeepLocal;
+                        emit_status(
+                            app,
+                            remote.entity,
+                            remote.id,
+                            EntitySyncState::Conflict,
+                            Some(resolution),
+                            None,
+                        );
+                    }
+                }
+            }
+            // A full batch may have more behind it; rebased edits need another push
+            if pushed.len() < PUSH_BATCH_SIZE && !rebased {
+                return Ok(());
+            }
+        }
+        tracing::warn!("sync left changes pending after {MAX_PUSH_ROUNDS} push rounds");
+        Ok(())
+    }
+
+    /// Pending local changes, oldest first, with current row data attached.
+    async fn pending(&self, limit: Option<usize>) -> AppResult<Vec<Change>> {
+        self.db
+            .run(move |conn| {
+                let limit = limit.map_or(-1, |l| l as i64);
+                let mut changes = conn
+                    .prepare(
+                        "SELECT entity, entity_id, base_version, deleted, changed_at
+                         FROM sync_changes ORDER BY changed_at LIMIT ?1",
+                    )?
+                    .query_map([limit], |row| {
+                        Ok(Change {
+                            entity: row.get(0)?,
+                            id: row.get(1)?,
+                            version: row.get(2)?,
+                            deleted: row.get(3)?,
+                            changed_at: row.get(4)?,
+                            data: None,
+                        })
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                for change in changes.iter_mut().filter(|c| !c.deleted) {
+                    change.data = load_row(conn, change.entity, &change.id)?;
+                    // Removed by a cascade after the change was recorded
+                    change.deleted = change.data.is_none();
+                }
+                Ok(changes)
+            })
+            .await
+    }
+}
+
+async fn send<T: serde::de::DeserializeOwned>(
+    http: &HttpClient,
+    request: RequestBuilder,
+    token: &str,
+) -> AppResult<T> {
+    let response = http
+        .send(request.bearer_auth(token), RetryPolicy::default())
+        .await?
+        .error_for_status()?;
+    Ok(serde_json::from_slice(&response.bytes().await?)?)
+}
+
+fn emit_status(
+    app: &AppHandle,
+    entity: EntityKind,
+    id: String,
+    state: EntitySyncState,
+    resolution: Option<Resolution>,
+    error: Option<String>,
+) {
+    let _ = app.emit(
+        ENTITY_STATUS_EVENT,
+        EntityStatus {
+            entity,
+            id,
+            state,
+            resolution,
+            error,
+        },
+    );
+}
+
+fn get_state(conn: &rusqlite::Connection, key: &str) -> AppResult<Option<String>> {
+    Ok(conn
+        .query_row(
+            "SELECT value FROM sync_state WHERE key = ?1",
+            [key],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+fn set_state(conn: &rusqlite::Connection, key: &str, value: &str) -> AppResult<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO sync_state (key, value) VALUES (?1, ?2)",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+// Writes made inside this transaction are not captured as local changes
+fn begin_apply(conn: &mut rusqlite::Connection) -> AppResult<Transaction<'_>> {
+    let tx = conn.transaction()?;
+    // Pulled items may arrive before the project they belong to
+    tx.execute_batch("PRAGMA defer_foreign_keys = ON")?;
+    set_state(&tx, APPLYING_KEY, "1")?;
+    Ok(tx)
+}
+
+fn finish_apply(tx: Transaction<'_>) -> AppResult<()> {
+    tx.execute("DELETE FROM sync_state WHERE key = ?1", [APPLYING_KEY])?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn load_row(conn: &rusqlite::Connection, entity: EntityKind, id: &str) -> AppResult<Option<Value>> {
+    let sql = format!("SELECT * FROM {} WHERE id = ?1", entity.table());
+    let value = match entity {
+        EntityKind::Project => conn
+            .query_row(&sql, [id], Project::from_row)
+            .optional()?
+            .map(serde_json::to_value),
+        EntityKind::Item => conn
+            .query_row(&sql, [id], Item::from_row)
+            .optional()?
+            .map(serde_json::to_value),
+    };
+    Ok(value.transpose()?)
+}
+
+fn apply_remote(tx: &Transaction<'_>, change: &Change) -> AppResult<()> {
+    if change.deleted {
+        tx.execute(
+            &format!("DELETE FROM {} WHERE id = ?1", change.entity.table()),
+            [&change.id],
+        )?;
+    } else {
+        let data = change.data.clone().ok_or_else(|| {
+            AppError::Internal(format!(
+                "remote {} {} has no data",
+                change.entity.as_str(),
+                change.id
+            ))
+        })?;
+        match change.entity {
+            EntityKind::Project => {
+                let p: Project = serde_json::from_value(data)?;
+                tx.execute(
+                    "INSERT INTO projects (id, name, description, created_at, updated_at, version)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                     ON CONFLICT(id) DO UPDATE SET
+                        name = excluded.name,
+                        description = excluded.description,
+                        updated_at = excluded.updated_at,
+                        version = excluded.version",
+                    params![
+                        p.id,
+                        p.name,
+                        p.description,
+                        p.created_at,
+                        p.updated_at,
+                        change.version
+                    ],
+                )?;
+            }
+            EntityKind::Item => {
+                let i: Item = serde_json::from_value(data)?;
+                tx.execute(
+                    "INSERT INTO items (id, project_id, name, description, quantity, unit,
+                                        unit_cost, sort_order, created_at, updated_at, version)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+                     ON CONFLICT(id) DO UPDATE SET
+                        project_id = excluded.project_id,
+                        name = excluded.name,
+                        description = excluded.description,
+                        quantity = excluded.quantity,
+                        unit = excluded.unit,
+                        unit_cost = excluded.unit_cost,
+                        sort_order = excluded.sort_order,
+                        updated_at = excluded.updated_at,
+                        version = excluded.version",
+                    params![
+                        i.id,
+                        i.project_id,
+                        i.name,
+                        i.description,
+                        i.quantity,
+                        i.unit,
+                        i.unit_cost,
+                        i.sort_order,
+                        i.created_at,
+                        i.updated_at,
+                        change.version
+                    ],
+                )?;
+            }
+        }
+    }
+    tx.execute(
+        "DELETE FROM sync_changes WHERE entity = ?1 AND entity_id = ?2",
+        params![change.entity, change.id],
+    )?;
+    Ok(())
+}
+
+// Keep the local edit but base it on the remote version so the next push supersedes it
+fn rebase_local(tx: &Transaction<'_>, remote: &Change) -> AppResult<()> {
+    tx.execute(
+        &format!(
+            "UPDATE {} SET version = ?2 WHERE id = ?1",
+            remote.entity.table()
+        ),
+        params![remote.id, remote.version],
+    )?;
+    tx.execute(
+        "UPDATE sync_changes SET base_version = ?3 WHERE entity = ?1 AND entity_id = ?2",
+        params![remote.entity, remote.id, remote.version],
+    )?;
+    Ok(())
+}
+
+// Edits made while the push was in flight have a newer changed_at and stay pending
+fn mark_pushed(
+    tx: &Transaction<'_>,
+    entity: EntityKind,
+    id: &str,
+    version: i64,
+    changed_at: Option<i64>,
+) -> AppResult<()> {
+    tx.execute(
+        &format!("UPDATE {} SET version = ?2 WHERE id = ?1", entity.table()),
+        params![id, version],
+    )?;
+    tx.execute(
+        "UPDATE sync_changes SET base_version = ?3 WHERE entity = ?1 AND entity_id = ?2",
+        params![entity, id, version],
+    )?;
+    tx.execute(
+        "DELETE FROM sync_changes WHERE entity = ?1 AND entity_id = ?2 AND changed_at = ?3",
+        params![entity, id, changed_at],
+    )?;
+    Ok(())
+}
+
+/// Signed-out users stay local-only, which is not a job failure.
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(SYNC_JOB, Some(SYNC_INTERVAL), |app| async move {
+        if !app.state::<NetworkMonitor>().is_online()
+            || power::battery_critical(&app)
+            || background::defer(&app, Work::Sync)
+        {
+            return Ok(());
+        }
+        match app.state::<SyncEngine>().sync(&app).await {
+            Ok(_) | Err(AppError::Auth(_)) => Ok(()),
+            Err(err) => Err(err),
+        }
+    });
+}
+
+/// Sync now instead of waiting for the next scheduled run.
+///
+/// Cancelling stops between requests; pages already pulled or pushed stay applied.
+#[tauri::command]
+pub async fn sync_now(
+    app: AppHandle,
+    engine: State<'_, SyncEngine>,
+    state: State<'_, AppState>,
+    task_id: Option<String>,
+) -> AppResult<SyncSummary> {
+    let app = &app;
+    state
+        .tasks()
+        .run(app, TaskKind::Sync, task_id, |task| async move {
+            task.abortable(engine.sync(app)).await
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_sync_status(engine: State<'_, SyncEngine>) -> AppResult<SyncStatus> {
+    engine.status().await
+}
@@ -0,0 +1,369 @@
+//! The machine the app runs on: OS and webview versions, CPU, memory, GPUs, free disk space,
+//! and display scaling, for the support screen and the diagnostics bundle.
+//!
+//! Hardware details come from each OS's own sources and are left empty where one does not
+//! expose them: procfs and sysfs on Linux and Android, sysctl and `system_profiler` on
+//! Apple platforms, and Win32 on Windows. Mobile devices report no GPU.
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::db;
+use crate::error::AppResult;
+use crate::state::AppState;
+
+/// A snapshot of the machine.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemInfo {
+    pub app_version: String,
+    pub tauri_version: &'static str,
+    /// WebView2, WebKitGTK, or WKWebView version; `None` when it cannot be determined.
+    pub webview_version: Option<String>,
+    pub os: &'static str,
+    pub os_version: String,
+    pub arch: &'static str,
+    pub locale: Option<String>,
+    pub cpu: CpuInfo,
+    pub memory: MemoryInfo,
+    /// Graphics adapter names.
+    pub gpus: Vec<String>,
+    /// Space on the volume holding the app's data.
+    pub disk: Option<DiskInfo>,
+    pub displays: Vec<DisplayInfo>,
+    pub generated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CpuInfo {
+    pub model: Option<String>,
+    /// Logical cores available to the app.
+    pub cores: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryInfo {
+    pub total_bytes: Option<u64>,
+    pub available_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskInfo {
+    pub path: PathBuf,
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DisplayInfo {
+    pub name: Option<String>,
+    /// Physical pixels.
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f64,
+    pub primary: bool,
+}
+
+fn disk(app: &AppHandle) -> Option<DiskInfo> {
+    let path = app.state::<AppState>().data_dir().to_path_buf();
+    let space = fs4::total_space(&path).and_then(|total| Ok((total, fs4::available_space(&path)?)));
+    let (total_bytes, available_bytes) = space
+        .inspect_err(|err| tracing::debug!(%err, "cannot read disk space"))
+        .ok()?;
+    Some(DiskInfo {
+        path,
+        total_bytes,
+        available_bytes,
+    })
+}
+
+fn displays(app: &AppHandle) -> Vec<DisplayInfo> {
+    let primary = app
+        .primary_monitor()
+        .ok()
+        .flatten()
+        .map(|monitor| (monitor.name().cloned(), *monitor.position()));
+    app.available_monitors()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|monitor| DisplayInfo {
+            name: monitor.name().cloned(),
+            width: monitor.size().width,
+            height: monitor.size().height,
+            scale_factor: monitor.scale_factor(),
+            primary: primary.as_ref().is_some_and(|(name, position)| {
+                name.as_ref() == monitor.name() && position == monitor.position()
+            }),
+        })
+        .collect()
+}
+
+/// Gather everything; blocking, since some of it shells out or reads many small files.
+pub fn collect(app: &AppHandle) -> SystemInfo {
+    SystemInfo {
+        app_version: app.package_info().version.to_string(),
+        tauri_version: tauri::VERSION,
+        webview_version: tauri::webview_version().ok(),
+        os: tauri_plugin_os::platform(),
+        os_version: tauri_plugin_os::version().to_string(),
+        arch: tauri_plugin_os::arch(),
+        locale: tauri_plugin_os::locale(),
+        cpu: CpuInfo {
+            model: os::cpu_model(),
+            cores: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        },
+        memory: os::memory(),
+        gpus: os::gpus(),
+        disk: disk(app),
+        displays: displays(app),
+        generated_at: db::now_ms(),
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod os {
+    use std::fs;
+
+    use super::MemoryInfo;
+
+    pub fn cpu_model() -> Option<String> {
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+        // `model name` on x86, `Hardware` or `Processor` on many ARM kernels
+        ["model name", "Hardware", "Processor"]
+            .iter()
+            .find_map(|key| {
+                cpuinfo.lines().find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    (name.trim() == *key && !value.trim().is_empty())
+                        .then(|| value.trim().to_string())
+                })
+            })
+    }
+
+    pub fn memory() -> MemoryInfo {
+        let Ok(meminfo) = fs::read_to_string("/proc/meminfo") else {
+            return MemoryInfo::default();
+        };
+        let kib = |key: &str| {
+            meminfo.lines().find_map(|line| {
+                let value = line.strip_prefix(key)?.strip_prefix(':')?;
+                let kib: u64 = value.trim().trim_end_matches("kB").trim().parse().ok()?;
+                Some(kib * 1024)
+            })
+        };
+        MemoryInfo {
+            total_bytes: kib("MemTotal"),
+            available_bytes: kib("MemAvailable"),
+        }
+    }
+
+    // DRM devices name their vendor and kernel driver, not the marketing name
+    pub fn gpus() -> Vec<String> {
+        let Ok(cards) = fs::read_dir("/sys/class/drm") else {
+            return Vec::new();
+        };
+        let mut gpus: Vec<String> = cards
+            .flatten()
+            .filter(|card| {
+                let name = card.file_name();
+                let name = name.to_string_lossy();
+                name.starts_with("card") && !name.contains('-')
+            })
+            .filter_map(|card| {
+                let device = card.path().join("device");
+                let vendor = fs::read_to_string(device.join("vendor")).ok();
+                let vendor = match vendor.as_deref().map(str::trim) {
+                    Some("0x8086") => "Intel",
+                    Some("0x10de") => "NVIDIA",
+                    Some("0x1002") => "AMD",
+                    Some("0x1af4") => "Virtio",
+                    _ => "Unknown vendor",
+                };
+                let uevent = fs::read_to_string(device.join("uevent")).ok()?;
+                let driver = uevent
+                    .lines()
+                    .find_map(|line| line.strip_prefix("DRIVER="))?;
+                Some(format!("{vendor} ({driver})"))
+            })
+            .collect();
+        gpus.sort();
+        gpus.dedup();
+        gpus
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod os {
+    use std::ffi::{c_char, c_int, c_void, CStr, CString};
+
+    use super::MemoryInfo;
+
+    extern "C" {
+        fn sysctlbyname(
+            name: *const c_char,
+            oldp: *mut c_void,
+            oldlenp: *mut usize,
+            newp: *mut c_void,
+            newlen: usize,
+        ) -> c_int;
+    }
+
+    fn sysctl_bytes(name: &str) -> Option<Vec<u8>> {
+        let name = CString::new(name).ok()?;
+        let mut len = 0usize;
+        // SAFETY: a null buffer asks for the value's size only
+        let rc = unsafe {
+            sysctlbyname(
+                name.as_ptr(),
+                std::ptr::null_mut(),
+                &mut len,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        if rc != 0 || len == 0 {
+            return None;
+        }
+        let mut buf = vec![0u8; len];
+        // SAFETY: `buf` holds `len` bytes, which sysctl may shrink but never exceeds
+        let rc = unsafe {
+            sysctlbyname(
+                name.as_ptr(),
+                buf.as_mut_ptr().cast(),
+                &mut len,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        (rc == 0).then(|| {
+            buf.truncate(len);
+            buf
+        })
+    }
+
+    fn sysctl_string(name: &str) -> Option<String> {
+        let bytes = sysctl_bytes(name)?;
+        let value = CStr::from_bytes_until_nul(&bytes).ok()?;
+        Some(value.to_string_lossy().into_owned())
+    }
+
+    pub fn cpu_model() -> Option<String> {
+        // iOS has no brand string; the hardware model such as `iPhone15,2` stands in
+        sysctl_string("machdep.cpu.brand_string").or_else(|| sysctl_string("hw.machine"))
+    }
+
+    pub fn memory() -> MemoryInfo {
+        let total_bytes = sysctl_bytes("hw.memsize")
+            .and_then(|bytes| Some(u64::from_ne_bytes(bytes.try_into().ok()?)));
+        MemoryInfo {
+            total_bytes,
+            available_bytes: None,
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn gpus() -> Vec<String> {
+        use std::process::Command;
+
+        let output = Command::new("system_profiler")
+            .args(["SPDisplaysDataType", "-json", "-detailLevel", "mini"])
+            .output();
+        let Ok(output) = output.inspect_err(|err| tracing::debug!(%err, "system_profiler")) else {
+            return Vec::new();
+        };
+        let Ok(report) = serde_json::from_slice::<serde_json::Value>(&output.stdout) else {
+            return Vec::new();
+        };
+        report["SPDisplaysDataType"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|gpu| gpu["sppci_model"].as_str().map(str::to_string))
+            .collect()
+    }
+
+    #[cfg(target_os = "ios")]
+    pub fn gpus() -> Vec<String> {
+        Vec::new()
+    }
+}
+
+#[cfg(windows)]
+mod os {
+    use windows::core::{w, PCWSTR};
+    use windows::Win32::Graphics::Gdi::{EnumDisplayDevicesW, DISPLAY_DEVICEW};
+    use windows::Win32::System::Registry::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_SZ};
+    use windows::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+
+    use super::MemoryInfo;
+
+    fn from_wide(wide: &[u16]) -> String {
+        let len = wide.iter().position(|&c| c == 0).unwrap_or(wide.len());
+        String::from_utf16_lossy(&wide[..len]).trim().to_string()
+    }
+
+    pub fn cpu_model() -> Option<String> {
+        let mut buf = [0u16; 256];
+        let mut len = std::mem::size_of_val(&buf) as u32;
+        // SAFETY: `buf` is writable for `len` bytes
+        let status = unsafe {
+            RegGetValueW(
+                HKEY_LOCAL_MACHINE,
+                w!(r"HARDWARE\DESCRIPTION\System\CentralProcessor\0"),
+                w!("ProcessorNameString"),
+                RRF_RT_REG_SZ,
+                None,
+                Some(buf.as_mut_ptr().cast()),
+                Some(&mut len),
+            )
+        };
+        status.is_ok().then(|| from_wide(&buf))
+    }
+
+    pub fn memory() -> MemoryInfo {
+        let mut status = MEMORYSTATUSEX {
+            dwLength: std::mem::size_of::<MEMORYSTATUSEX>() as u32,
+            ..Default::default()
+        };
+        // SAFETY: `status` is a valid MEMORYSTATUSEX with its length set
+        if unsafe { GlobalMemoryStatusEx(&mut status) }.is_err() {
+            return MemoryInfo::default();
+        }
+        MemoryInfo {
+            total_bytes: Some(status.ullTotalPhys),
+            available_bytes: Some(status.ullAvailPhys),
+        }
+    }
+
+    // One entry per output, so an adapter driving two screens appears twice before dedup
+    pub fn gpus() -> Vec<String> {
+        let mut gpus = Vec::new();
+        for index in 0.. {
+            let mut device = DISPLAY_DEVICEW {
+                cb: std::mem::size_of::<DISPLAY_DEVICEW>() as u32,
+                ..Default::default()
+            };
+            // SAFETY: `device` is a valid DISPLAY_DEVICEW with its size set
+            if !unsafe { EnumDisplayDevicesW(PCWSTR::null(), index, &mut device, 0) }.as_bool() {
+                break;
+            }
+            let name = from_wide(&device.DeviceString);
+            if !name.is_empty() && !gpus.contains(&name) {
+                gpus.push(name);
+            }
+        }
+        gpus
+    }
+}
+
+/// OS, webview, and hardware details for the support screen.
+#[tauri::command]
+pub async fn get_system_info(app: AppHandle) -> AppResult<SystemInfo> {
+    Ok(tauri::async_runtime::spawn_blocking(move || collect(&app)).await?)
+}
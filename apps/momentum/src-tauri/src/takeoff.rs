@@ -0,0 +1,498 @@
+//! On-screen takeoff: lengths, areas, and counts measured on plan pages.
+//!
+//! A plan page is one page of an attachment. Its scale is calibrated by tracing a dimension
+//! of known length, which fixes how many pixels of the page image make one unit. Measurements
+//! are stored as the pixel points that were traced and worked out against the page's current
+//! calibration whenever they are read, so correcting a scale corrects everything on the page.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::audit::{self, Change};
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+use crate::units::{self, Dimension, Quantity, Unit};
+
+pub type Point = [f64; 2];
+
+/// Length of the path through `points`, back to the first one when `closed`.
+pub fn path_length(points: &[Point], closed: bool) -> f64 {
+    let closing = (closed && points.len() > 2).then(|| [points[points.len() - 1], points[0]]);
+    points
+        .windows(2)
+        .map(|pair| [pair[0], pair[1]])
+        .chain(closing)
+        .map(|[a, b]| (b[0] - a[0]).hypot(b[1] - a[1]))
+        .sum()
+}
+
+/// Shoelace area of the polygon through `points`, positive when they run counterclockwise.
+pub fn signed_area(points: &[Point]) -> f64 {
+    let sum: f64 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a[0] * b[1] - b[0] * a[1])
+        .sum();
+    sum / 2.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MeasurementKind {
+    /// A run along an open path, such as a wall or pipe.
+    Length,
+    /// The inside of a closed outline, such as a slab or floor.
+    Area,
+    /// Each point is one of something, such as a fixture.
+    Count,
+}
+
+impl MeasurementKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Length => "length",
+            Self::Area => "area",
+            Self::Count => "count",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "length" => Self::Length,
+            "area" => Self::Area,
+            _ => Self::Count,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Calibration {
+    pub attachment_id: String,
+    pub page: u32,
+    pub pixels_per_unit: f64,
+    /// Length unit the scale was calibrated in.
+    pub unit: String,
+    pub calibrated_at: i64,
+}
+
+impl Calibration {
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            attachment_id: row.get("attachment_id")?,
+            page: row.get("page")?,
+            pixels_per_unit: row.get("pixels_per_unit")?,
+            unit: row.get("unit")?,
+            calibrated_at: row.get("calibrated_at")?,
+        })
+    }
+}
+
+/// What a set of points measures on a page.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Measured {
+    /// `None` for a length or area on a page that has not been calibrated.
+    pub quantity: Option<f64>,
+    /// `None` for counts.
+    pub unit: Option<&'static str>,
+    /// Distance around an area, in the matching length unit.
+    pub perimeter: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeasurementInput {
+    /// Omitted to create a measurement; given to replace one.
+    pub id: Option<String>,
+    pub attachment_id: String,
+    pub page: u32,
+    pub kind: MeasurementKind,
+    pub label: Option<String>,
+    pub points: Vec<Point>,
+    /// Estimate item the measurement is the quantity for.
+    pub item_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Measurement {
+    pub id: String,
+    pub project_id: String,
+    pub attachment_id: String,
+    pub page: u32,
+    pub kind: MeasurementKind,
+    pub label: Option<String>,
+    pub points: Vec<Point>,
+    pub item_id: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+    #[serde(flatten)]
+    pub measured: Measured,
+}
+
+fn length_unit(text: &str) -> AppResult<&'static Unit> {
+    units::find(text)
+        .filter(|unit| unit.dimension == Dimension::Length)
+        .ok_or_else(|| AppError::InvalidInput(format!("{text} is not a unit of length")))
+}
+
+// The square of a length unit, as square feet for feet
+fn area_unit(length: &Unit) -> AppResult<&'static Unit> {
+    units::find(&format!("{}2", length.symbol))
+        .filter(|unit| unit.dimension == Dimension::Area)
+        .ok_or_else(|| {
+            AppError::InvalidInput(format!("areas cannot be measured in {}", length.symbol))
+        })
+}
+
+/// Work out `points` against a page's calibration, in `unit` when one is given and
+/// otherwise in the unit the page was calibrated in.
+fn measure(
+    kind: MeasurementKind,
+    points: &[Point],
+    calibration: Option<&Calibration>,
+    unit: Option<&'static Unit>,
+) -> AppResult<Measured> {
+    if kind == MeasurementKind::Count {
+        return Ok(Measured {
+            quantity: Some(points.len() as f64),
+            ..Measured::default()
+        });
+    }
+    let Some(calibration) = calibration else {
+        return Ok(Measured::default());
+    };
+    let scale = length_unit(&calibration.unit)?;
+    let target = unit.unwrap_or(scale);
+    let to_length =
+        |pixels: f64| units::convert(pixels / calibration.pixels_per_unit, scale, target);
+    Ok(match kind {
+        MeasurementKind::Length => Measured {
+            quantity: Some(to_length(path_length(points, false))?),
+            unit: Some(target.symbol),
+            perimeter: None,
+        },
+        _ => {
+            let (from, to) = (area_unit(scale)?, area_unit(target)?);
+            let pixels = signed_area(points).abs();
+            Measured {
+                quantity: Some(units::convert(
+                    pixels / calibration.pixels_per_unit.powi(2),
+                    from,
+                    to,
+                )?),
+                unit: Some(to.symbol),
+                perimeter: Some(to_length(path_length(points, true))?),
+            }
+        }
+    })
+}
+
+fn validate(kind: MeasurementKind, points: &[Point]) -> AppResult<()> {
+    if points.iter().flatten().any(|v| !v.is_finite()) {
+        return Err(AppError::InvalidInput("a point is not a number".into()));
+    }
+    let (needed, words) = match kind {
+        MeasurementKind::Length => (2, "two points"),
+        MeasurementKind::Area => (3, "three points"),
+        MeasurementKind::Count => (1, "a point"),
+    };
+    if points.len() < needed {
+        return Err(AppError::InvalidInput(format!(
+            "a {} needs at least {words}",
+            kind.as_str()
+        )));
+    }
+    Ok(())
+}
+
+fn attachment_project(conn: &Connection, attachment_id: &str) -> AppResult<String> {
+    conn.query_row(
+        "SELECT project_id FROM attachments WHERE id = ?1",
+        [attachment_id],
+        |row| row.get(0),
+    )
+    .optional()?
+    .ok_or_else(|| AppError::not_found("attachment", attachment_id))
+}
+
+fn load_calibration(
+    conn: &Connection,
+    attachment_id: &str,
+    page: u32,
+) -> AppResult<Option<Calibration>> {
+    Ok(conn
+        .query_row(
+            "SELECT * FROM takeoff_calibrations WHERE attachment_id = ?1 AND page = ?2",
+            params![attachment_id, page],
+            Calibration::from_row,
+        )
+        .optional()?)
+}
+
+struct Stored {
+    id: String,
+    attachment_id: String,
+    page: u32,
+    kind: MeasurementKind,
+    label: Option<String>,
+    points: Vec<Point>,
+    item_id: Option<String>,
+    created_at: i64,
+    updated_at: i64,
+}
+
+fn load_measurements(
+    conn: &Connection,
+    attachment_id: &str,
+    page: Option<u32>,
+    unit: Option<&'static Unit>,
+) -> AppResult<Vec<Measurement>> {
+    let project_id = attachment_project(conn, attachment_id)?;
+    let stored = conn
+        .prepare_cached(
+            "SELECT * FROM takeoff_measurements
+             WHERE attachment_id = ?1 AND (?2 IS NULL OR page = ?2)
+             ORDER BY page, created_at",
+        )?
+        .query_map(params![attachment_id, page], |row| {
+            let points: String = row.get("points")?;
+            Ok(Stored {
+                id: row.get("id")?,
+                attachment_id: row.get("attachment_id")?,
+                page: row.get("page")?,
+                kind: MeasurementKind::parse(&row.get::<_, String>("kind")?),
+                label: row.get("label")?,
+                points: serde_json::from_str(&points).unwrap_or_default(),
+                item_id: row.get("item_id")?,
+                created_at: row.get("created_at")?,
+                updated_at: row.get("updated_at")?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut calibrations: Vec<(u32, Option<Calibration>)> = Vec::new();
+    let mut measurements = Vec::with_capacity(stored.len());
+    for s in stored {
+        if calibrations.last().is_none_or(|(page, _)| *page != s.page) {
+            calibrations.push((s.page, load_calibration(conn, attachment_id, s.page)?));
+        }
+        let calibration = calibrations.last().and_then(|(_, c)| c.as_ref());
+        measurements.push(Measurement {
+            measured: measure(s.kind, &s.points, calibration, unit)?,
+            id: s.id,
+            project_id: project_id.clone(),
+            attachment_id: s.attachment_id,
+            page: s.page,
+            kind: s.kind,
+            label: s.label,
+            points: s.points,
+            item_id: s.item_id,
+            created_at: s.created_at,
+            updated_at: s.updated_at,
+        });
+    }
+    Ok(measurements)
+}
+
+/// Set a page's scale from a traced dimension: the pixel points at either end of it and its
+/// real length.
+#[tauri::command]
+pub async fn calibrate_plan_page(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    attachment_id: String,
+    page: u32,
+    from: Point,
+    to: Point,
+    length: Quantity,
+) -> AppResult<Calibration> {
+    let unit = length_unit(&length.unit)?;
+    let pixels = path_length(&[from, to], false);
+    if !(length.value > 0.0 && pixels.is_finite() && pixels > 0.0) {
+        return Err(AppError::InvalidInput(
+            "calibration needs two different points and a length above zero".into(),
+        ));
+    }
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let project_id = attachment_project(conn, &attachment_id)?;
+        let tx = conn.transaction()?;
+        let before = load_calibration(&tx, &attachment_id, page)?;
+        let calibration = Calibration {
+            attachment_id,
+            page,
+            pixels_per_unit: pixels / length.value,
+            unit: unit.symbol.into(),
+            calibrated_at: now_ms(),
+        };
+        tx.execute(
+            "INSERT OR REPLACE INTO takeoff_calibrations
+                (attachment_id, page, pixels_per_unit, unit, calibrated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                calibration.attachment_id,
+                calibration.page,
+                calibration.pixels_per_unit,
+                calibration.unit,
+                calibration.calibrated_at
+            ],
+        )?;
+        audit::record(
+            &tx,
+            &actor,
+            Change::new("calibrate_plan_page", "project", &project_id)
+                .before(&before)?
+                .after(&calibration)?,
+        )?;
+        tx.commit()?;
+        Ok(calibration)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_plan_calibration(
+    db: State<'_, Db>,
+    attachment_id: String,
+    page: u32,
+) -> AppResult<Option<Calibration>> {
+    db.run(move |conn| load_calibration(conn, &attachment_id, page))
+        .await
+}
+
+/// Measure points on a page without saving them, as while a measurement is being drawn.
+#[tauri::command]
+pub async fn measure_plan(
+    db: State<'_, Db>,
+    attachment_id: String,
+    page: u32,
+    kind: MeasurementKind,
+    points: Vec<Point>,
+    unit: Option<String>,
+) -> AppResult<Measured> {
+    let unit = unit.as_deref().map(length_unit).transpose()?;
+    validate(kind, &points)?;
+    db.run(move |conn| {
+        let calibration = load_calibration(conn, &attachment_id, page)?;
+        measure(kind, &points, calibration.as_ref(), unit)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn save_takeoff_measurement(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    measurement: MeasurementInput,
+) -> AppResult<Measurement> {
+    validate(measurement.kind, &measurement.points)?;
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let project_id = attachment_project(conn, &measurement.attachment_id)?;
+        if let Some(item_id) = &measurement.item_id {
+            let in_project: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM items WHERE id = ?1 AND project_id = ?2)",
+                params![item_id, project_id],
+                |row| row.get(0),
+            )?;
+            if !in_project {
+                return Err(AppError::not_found("item", item_id.clone()));
+            }
+        }
+        let tx = conn.transaction()?;
+        let now = now_ms();
+        let id = measurement
+            .id
+            .clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        tx.execute(
+            "INSERT INTO takeoff_measurements
+                (id, attachment_id, page, kind, label, points, item_id, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8)
+             ON CONFLICT(id) DO UPDATE SET
+                attachment_id = excluded.attachment_id,
+                page = excluded.page,
+                kind = excluded.kind,
+                label = excluded.label,
+                points = excluded.points,
+                item_id = excluded.item_id,
+                updated_at = excluded.updated_at",
+            params![
+                id,
+                measurement.attachment_id,
+                measurement.page,
+                measurement.kind.as_str(),
+                measurement.label,
+                serde_json::to_string(&measurement.points)?,
+                measurement.item_id,
+                now
+            ],
+        )?;
+        let saved = load_measurements(
+            &tx,
+            &measurement.attachment_id,
+            Some(measurement.page),
+            None,
+        )?
+        .into_iter()
+        .find(|m| m.id == id)
+        .ok_or_else(|| AppError::not_found("measurement", id.clone()))?;
+        audit::record(
+            &tx,
+            &actor,
+            Change::new("save_takeoff_measurement", "project", &project_id).after(&saved)?,
+        )?;
+        tx.commit()?;
+        Ok(saved)
+    })
+    .await
+}
+
+/// Measurements on an attachment, or on one of its pages, in `unit` when one is given.
+#[tauri::command]
+pub async fn list_takeoff_measurements(
+    db: State<'_, Db>,
+    attachment_id: String,
+    page: Option<u32>,
+    unit: Option<String>,
+) -> AppResult<Vec<Measurement>> {
+    let unit = unit.as_deref().map(length_unit).transpose()?;
+    db.run(move |conn| load_measurements(conn, &attachment_id, page, unit))
+        .await
+}
+
+#[tauri::command]
+pub async fn delete_takeoff_measurement(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    id: String,
+) -> AppResult<()> {
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let tx = conn.transaction()?;
+        let (attachment_id, page): (String, u32) = tx
+            .query_row(
+                "SELECT attachment_id, page FROM takeoff_measurements WHERE id = ?1",
+                [&id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .ok_or_else(|| AppError::not_found("measurement", id.clone()))?;
+        let project_id = attachment_project(&tx, &attachment_id)?;
+        let before = load_measurements(&tx, &attachment_id, Some(page), None)?
+            .into_iter()
+            .find(|m| m.id == id);
+        tx.execute("DELETE FROM takeoff_measurements WHERE id = ?1", [&id])?;
+        audit::record(
+            &tx,
+            &actor,
+            Change::new("delete_takeoff_measurement", "project", &project_id).before(&before)?,
+        )?;
+        tx.commit()?;
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,274 @@
+//! Long-running commands as tasks the caller can follow and cancel.
+//!
+//! Imports, exports, sync, and analysis each run as a [`Task`] in the [`TaskRegistry`] held
+//! by [`AppState`]. The caller may choose the task id up front, through the `importId` or
+//! `exportId` it already passes or a `taskId`, so it can cancel before the command returns;
+//! otherwise one is generated and announced by the first event. Every task reports on
+//! `task:progress` with the same [`TaskProgress`] shape, on top of its own module's event,
+//! and ends with one last event saying how it finished.
+//!
+//! Cancellation is cooperative. Blocking loops call [`Task::check`] between rows and stop
+//! at the next one; async work run through [`Task::abortable`] is dropped at its next await
+//! point. Either way the command fails with `CANCELLED`.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::Notify;
+
+use crate::db::now_ms;
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+
+/// Emitted with a [`TaskProgress`] as a task starts, moves, and finishes.
+pub const TASK_PROGRESS_EVENT: &str = "task:progress";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TaskKind {
+    Import,
+    Export,
+    Sync,
+    Analysis,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TaskStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// Payload of [`TASK_PROGRESS_EVENT`], and what [`list_tasks`] returns for each task.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskProgress {
+    pub task_id: String,
+    pub kind: TaskKind,
+    pub status: TaskStatus,
+    /// Units of work done: rows, members, or records, depending on the kind.
+    pub done: u64,
+    /// `None` while the amount of work is unknown.
+    pub total: Option<u64>,
+    /// The error a failed task stopped with.
+    pub error: Option<String>,
+    pub started_at: i64,
+}
+
+struct Shared {
+    app: AppHandle,
+    cancelled: Arc<AtomicBool>,
+    notify: Notify,
+    progress: Mutex<TaskProgress>,
+}
+
+/// Handle a running command reports through; clones share the same task.
+#[derive(Clone)]
+pub struct Task {
+    id: String,
+    shared: Arc<Shared>,
+}
+
+impl Task {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// `Err(Cancelled)` once the task has been asked to stop.
+    pub fn check(&self) -> AppResult<()> {
+        if self.is_cancelled() {
+            Err(AppError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The flag behind [`Task::check`], for code that already takes one.
+    pub(crate) fn cancelled(&self) -> Arc<AtomicBool> {
+        self.shared.cancelled.clone()
+    }
+
+    /// Report `done` out of `total`; callers throttle, as they do for their own events.
+    pub fn progress(&self, done: u64, total: Option<u64>) {
+        let event = {
+            let mut progress = self.lock();
+            progress.done = done;
+            progress.total = total;
+            progress.clone()
+        };
+        let _ = self.shared.app.emit(TASK_PROGRESS_EVENT, event);
+    }
+
+    /// Run `work` until it finishes or the task is cancelled, whichever comes first.
+    pub async fn abortable<T>(&self, work: impl Future<Output = AppResult<T>>) -> AppResult<T> {
+        tokio::select! {
+            result = work => result,
+            () = self.until_cancelled() => Err(AppError::Cancelled),
+        }
+    }
+
+    async fn until_cancelled(&self) {
+        loop {
+            // Created before the check so a cancel in between still wakes it
+            let notified = self.shared.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    fn cancel(&self) {
+        self.shared.cancelled.store(true, Ordering::Relaxed);
+        self.shared.notify.notify_waiters();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TaskProgress> {
+        self.shared
+            .progress
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn finish<T>(&self, result: &AppResult<T>) {
+        let event = {
+            let mut progress = self.lock();
+            match result {
+                Ok(_) => progress.status = TaskStatus::Completed,
+                Err(AppError::Cancelled) => progress.status = TaskStatus::Cancelled,
+                Err(err) => {
+                    progress.status = TaskStatus::Failed;
+                    progress.error = Some(err.to_string());
+                }
+            }
+            progress.clone()
+        };
+        let _ = self.shared.app.emit(TASK_PROGRESS_EVENT, event);
+    }
+}
+
+/// Tasks currently running, by id.
+#[derive(Default)]
+pub struct TaskRegistry {
+    active: Mutex<HashMap<String, Task>>,
+}
+
+// Takes the task out of the registry however the command ends, including when its own
+// future is dropped
+struct Registered<'a> {
+    registry: &'a TaskRegistry,
+    id: String,
+}
+
+impl Drop for Registered<'_> {
+    fn drop(&mut self) {
+        self.registry.lock().remove(&self.id);
+    }
+}
+
+impl TaskRegistry {
+    /// Run `work` as a task of `kind` under `id`, or a fresh id.
+    ///
+    /// Fails without running anything if a task with `id` is already running.
+    pub async fn run<T, F, Fut>(
+        &self,
+        app: &AppHandle,
+        kind: TaskKind,
+        id: Option<String>,
+        work: F,
+    ) -> AppResult<T>
+    where
+        F: FnOnce(Task) -> Fut,
+        Fut: Future<Output = AppResult<T>>,
+    {
+        let task = self.start(app, kind, id)?;
+        let _registered = Registered {
+            registry: self,
+            id: task.id.clone(),
+        };
+        let result = work(task.clone()).await;
+        task.finish(&result);
+        if let Err(AppError::Cancelled) = result {
+            tracing::info!(task_id = %task.id, ?kind, "task cancelled");
+        }
+        result
+    }
+
+    fn start(&self, app: &AppHandle, kind: TaskKind, id: Option<String>) -> AppResult<Task> {
+        let id = id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let mut active = self.lock();
+        if active.contains_key(&id) {
+            return Err(AppError::InvalidInput(format!(
+                "task {id} is already running"
+            )));
+        }
+        let progress = TaskProgress {
+            task_id: id.clone(),
+            kind,
+            status: TaskStatus::Running,
+            done: 0,
+            total: None,
+            error: None,
+            started_at: now_ms(),
+        };
+        let task = Task {
+            id: id.clone(),
+            shared: Arc::new(Shared {
+                app: app.clone(),
+                cancelled: Arc::new(AtomicBool::new(false)),
+                notify: Notify::new(),
+                progress: Mutex::new(progress.clone()),
+            }),
+        };
+        active.insert(id, task.clone());
+        let _ = app.emit(TASK_PROGRESS_EVENT, progress);
+        Ok(task)
+    }
+
+    /// Ask a running task to stop; it fails with `CANCELLED` at its next check.
+    pub fn cancel(&self, id: &str) -> AppResult<()> {
+        match self.lock().get(id) {
+            Some(task) => {
+                task.cancel();
+                Ok(())
+            }
+            None => Err(AppError::not_found("task", id)),
+        }
+    }
+
+    pub fn list(&self) -> Vec<TaskProgress> {
+        let mut tasks: Vec<TaskProgress> = self
+            .lock()
+            .values()
+            .map(|task| task.lock().clone())
+            .collect();
+        tasks.sort_by_key(|task| task.started_at);
+        tasks
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Task>> {
+        self.active.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Running tasks, oldest first, for a view opened after they started.
+#[tauri::command]
+pub async fn list_tasks(state: State<'_, AppState>) -> AppResult<Vec<TaskProgress>> {
+    Ok(state.tasks().list())
+}
+
+/// Stop a running task; the command that started it fails with `CANCELLED`.
+#[tauri::command]
+pub async fn cancel_task(state: State<'_, AppState>, task_id: String) -> AppResult<()> {
+    state.tasks().cancel(&task_id)
+}
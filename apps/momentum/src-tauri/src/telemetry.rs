@@ -0,0 +1,368 @@
+//! Anonymous usage events, queued on disk and sent in batches unless the user opts out.
+//!
+//! Events carry an install id that is random and unrelated to the account, and are stripped
+//! of anything that could identify a person or a project before they are written. Opting out
+//! deletes everything queued, including the install id, and stops collection entirely.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::{AppHandle, Manager, State};
+use uuid::Uuid;
+
+use crate::config::{self, Config};
+use crate::db::now_ms;
+use crate::error::{AppError, AppResult};
+use crate::http_client::{HttpClient, RetryPolicy};
+use crate::jobs::Scheduler;
+use crate::network::NetworkMonitor;
+use crate::state::AppState;
+
+/// Job kind that sends queued events.
+pub const FLUSH_JOB: &str = "telemetry.flush";
+const FLUSH_INTERVAL: Duration = Duration::from_secs(15 * 60);
+const EXIT_FLUSH_TIMEOUT: Duration = Duration::from_secs(3);
+const UPLOAD_PATH: &str = "/v1/telemetry";
+
+const TELEMETRY_DIR_NAME: &str = "telemetry";
+const PENDING_FILE_NAME: &str = "pending.jsonl";
+// Moved aside while being sent, so events recorded meanwhile start a new file
+const OUTGOING_FILE_NAME: &str = "outgoing.jsonl";
+const INSTALL_ID_FILE_NAME: &str = "install-id";
+
+const MAX_PENDING_BYTES: u64 = 1024 * 1024;
+const MAX_BATCH_EVENTS: usize = 500;
+const MAX_NAME_LEN: usize = 64;
+const MAX_PROPERTIES: usize = 20;
+const MAX_STRING_LEN: usize = 100;
+
+// Property keys containing any of these are dropped whatever their value
+const PII_KEY_PARTS: &[&str] = &[
+    "email",
+    "name",
+    "user",
+    "phone",
+    "address",
+    "token",
+    "password",
+    "secret",
+    "path",
+    "file",
+    "url",
+    "title",
+    "description",
+    "note",
+    "comment",
+    "query",
+    "search",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Event {
+    name: String,
+    at: i64,
+    session_id: String,
+    properties: Map<String, Value>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Batch<'a> {
+    install_id: &'a str,
+    app_version: &'static str,
+    os: &'static str,
+    events: &'a [Event],
+}
+
+/// Telemetry queue registered in app state.
+pub struct Telemetry {
+    dir: PathBuf,
+    // Per launch, so events from one run can be grouped without tracking the user across runs
+    session_id: String,
+    // Guards the pending file against concurrent appends and the move to outgoing
+    files: Mutex<()>,
+    flushing: tokio::sync::Mutex<()>,
+}
+
+/// Open the queue under `data_dir/telemetry`.
+pub fn init(data_dir: &Path) -> AppResult<Telemetry> {
+    let dir = data_dir.join(TELEMETRY_DIR_NAME);
+    fs::create_dir_all(&dir)?;
+    Ok(Telemetry {
+        dir,
+        session_id: Uuid::new_v4().to_string(),
+        files: Mutex::new(()),
+        flushing: tokio::sync::Mutex::new(()),
+    })
+}
+
+fn enabled(config: &Config) -> bool {
+    !config.telemetry_opt_out
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "_.:-".contains(c))
+}
+
+fn looks_identifying(value: &str) -> bool {
+    let email = value
+        .split_once('@')
+        .is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.'));
+    let mut digits = 0;
+    let long_number = value.chars().any(|c| {
+        digits = if c.is_ascii_digit() { digits + 1 } else { 0 };
+        digits >= 7
+    });
+    email || long_number || value.contains("://") || value.contains(['/', '\\'])
+}
+
+// Keeps only short scalar values under keys that cannot name a person, place, or document
+fn scrub(properties: Map<String, Value>) -> Map<String, Value> {
+    properties
+        .into_iter()
+        .filter(|(key, _)| {
+            let key = key.to_ascii_lowercase();
+            valid_name(&key) && !PII_KEY_PARTS.iter().any(|part| key.contains(part))
+        })
+        .filter_map(|(key, value)| {
+            let value = match value {
+                Value::String(s) if looks_identifying(&s) => return None,
+                Value::String(s) => Value::String(s.chars().take(MAX_STRING_LEN).collect()),
+                Value::Null | Value::Bool(_) | Value::Number(_) => value,
+                Value::Array(_) | Value::Object(_) => return None,
+            };
+            Some((key, value))
+        })
+        .take(MAX_PROPERTIES)
+        .collect()
+}
+
+impl Telemetry {
+    fn pending_path(&self) -> PathBuf {
+        self.dir.join(PENDING_FILE_NAME)
+    }
+
+    fn outgoing_path(&self) -> PathBuf {
+        self.dir.join(OUTGOING_FILE_NAME)
+    }
+
+    fn install_id(&self) -> AppResult<String> {
+        let path = self.dir.join(INSTALL_ID_FILE_NAME);
+        match fs::read_to_string(&path) {
+            Ok(id) if Uuid::parse_str(id.trim()).is_ok() => Ok(id.trim().to_string()),
+            Ok(_) => Err(AppError::Internal("telemetry install id is corrupt".into())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                let id = Uuid::new_v4().to_string();
+                fs::write(&path, &id)?;
+                Ok(id)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn append(&self, event: &Event) -> AppResult<()> {
+        let _files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        let path = self.pending_path();
+        let size = fs::metadata(&path).map_or(0, |m| m.len());
+        // Offline for a long time; newer events are dropped rather than growing without bound
+        if size >= MAX_PENDING_BYTES {
+            return Ok(());
+        }
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(&line)?;
+        Ok(())
+    }
+
+    fn pending_count(&self) -> usize {
+        [self.pending_path(), self.outgoing_path()]
+            .iter()
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .map(|content| content.lines().count())
+            .sum()
+    }
+
+    /// Delete every queued event and the install id.
+    fn purge(&self) -> AppResult<()> {
+        let _files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        for name in [PENDING_FILE_NAME, OUTGOING_FILE_NAME, INSTALL_ID_FILE_NAME] {
+            match fs::remove_file(self.dir.join(name)) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // A leftover outgoing file from an earlier failed send goes first
+    fn take_outgoing(&self) -> AppResult<Option<Vec<Event>>> {
+        let _files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        let outgoing = self.outgoing_path();
+        if !outgoing.exists() {
+            match fs::rename(self.pending_path(), &outgoing) {
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                result => result?,
+            }
+        }
+        // A line cut short by a crash is skipped, not allowed to block the rest
+        let events = fs::read_to_string(&outgoing)?
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        Ok(Some(events))
+    }
+
+    /// Send everything queued, if enabled and online.
+    pub async fn flush(&self, app: &AppHandle) -> AppResult<()> {
+        let Ok(_flushing) = self.flushing.try_lock() else {
+            return Ok(());
+        };
+        if !enabled(&app.state::<AppState>().config()) {
+            return self.purge();
+        }
+        if !app.state::<NetworkMonitor>().is_online() {
+            return Ok(());
+        }
+        let Some(events) = self.take_outgoing()? else {
+            return Ok(());
+        };
+        let url = config::api_url(app, UPLOAD_PATH)?;
+        let install_id = self.install_id()?;
+        let http = app.state::<HttpClient>();
+        for chunk in events.chunks(MAX_BATCH_EVENTS) {
+            let batch = Batch {
+                install_id: &install_id,
+                app_version: env!("CARGO_PKG_VERSION"),
+                os: std::env::consts::OS,
+                events: chunk,
+            };
+            // No session token: events must not be linkable to the account. The key makes a
+            // batch resent after a lost response count once.
+            let key = format!("{install_id}:{}:{}", chunk[0].session_id, chunk[0].at);
+            let request = http
+                .client()
+                .post(url.clone())
+                .header("Content-Type", "application/json")
+                .header("Idempotency-Key", key)
+                .body(serde_json::to_vec(&batch)?);
+            let response = http.send(request, RetryPolicy::default()).await?;
+            let status = response.status();
+            if status.is_server_error() || status.as_u16() == 429 {
+                return Err(AppError::Internal(format!(
+                    "telemetry upload failed with {status}"
+                )));
+            }
+            // Anything else the server will never accept, so it is not retried
+            if !status.is_success() {
+                tracing::debug!(%status, events = chunk.len(), "telemetry batch rejected");
+            }
+        }
+        let _files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        fs::remove_file(self.outgoing_path())?;
+        tracing::debug!(events = events.len(), "telemetry sent");
+        Ok(())
+    }
+}
+
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(FLUSH_JOB, Some(FLUSH_INTERVAL), |app| async move {
+        app.state::<Telemetry>().flush(&app).await
+    });
+}
+
+/// Make a last, bounded attempt to send queued events as the app exits.
+pub fn flush_on_exit(app: &AppHandle) {
+    let Some(telemetry) = app.try_state::<Telemetry>() else {
+        return;
+    };
+    let result = tauri::async_runtime::block_on(async {
+        tokio::time::timeout(EXIT_FLUSH_TIMEOUT, telemetry.flush(app)).await
+    });
+    // Whatever did not go out stays queued for the next launch
+    match result {
+        Ok(Err(err)) => tracing::debug!(%err, "telemetry not sent at exit"),
+        Err(_) => tracing::debug!("telemetry flush at exit timed out"),
+        Ok(Ok(())) => {}
+    }
+}
+
+/// Queue a usage event; a no-op once the user has opted out.
+///
+/// `name` is lowercase letters, digits, and `_.:-`. Properties that could identify a person
+/// or a document are dropped: keys such as `email` or `projectName`, values that look like
+/// an email address, URL, path, or long number, and nested values.
+#[tauri::command]
+pub async fn track_event(
+    state: State<'_, AppState>,
+    telemetry: State<'_, Telemetry>,
+    name: String,
+    properties: Option<Map<String, Value>>,
+) -> AppResult<()> {
+    if !enabled(&state.config()) {
+        return Ok(());
+    }
+    if !valid_name(&name) {
+        return Err(AppError::InvalidInput(format!(
+            "invalid telemetry event name {name:?}"
+        )));
+    }
+    telemetry.append(&Event {
+        name,
+        at: now_ms(),
+        session_id: telemetry.session_id.clone(),
+        properties: scrub(properties.unwrap_or_default()),
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelemetryStatus {
+    pub enabled: bool,
+    /// Events queued but not yet sent.
+    pub pending: usize,
+}
+
+#[tauri::command]
+pub async fn get_telemetry_status(
+    state: State<'_, AppState>,
+    telemetry: State<'_, Telemetry>,
+) -> AppResult<TelemetryStatus> {
+    Ok(TelemetryStatus {
+        enabled: enabled(&state.config()),
+        pending: telemetry.pending_count(),
+    })
+}
+
+/// Opt in or out; opting out also deletes everything queued and the install id.
+#[tauri::command]
+pub async fn set_telemetry_enabled(
+    app: AppHandle,
+    telemetry: State<'_, Telemetry>,
+    enabled: bool,
+) -> AppResult<TelemetryStatus> {
+    config::update(&app, |config| config.telemetry_opt_out = !enabled)?;
+    if !enabled {
+        // Waits out a send in progress so it cannot recreate the files afterwards
+        let _flushing = telemetry.flushing.lock().await;
+        telemetry.purge()?;
+        tracing::info!("telemetry disabled");
+    }
+    Ok(TelemetryStatus {
+        enabled,
+        pending: telemetry.pending_count(),
+    })
+}
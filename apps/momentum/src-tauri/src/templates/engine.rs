@@ -0,0 +1,482 @@
+//! Document templates in Handlebars, rendered by the `handlebars` crate.
+//!
+//! `{{project.name}}` prints a value and `{{currency total}}` calls a helper; arguments are
+//! paths, quoted strings, numbers, or `(helper ...)` subexpressions. `{{#if}}`, `{{#unless}}`,
+//! and `{{#each}}` blocks take an optional `{{else}}`, and `{{! ... }}` is a comment. Inside
+//! `each`, names resolve against the current element, `../name` or `@root.name` reach the
+//! outer data, and `this`, `@index`, `@first`, and `@last` describe the element. Only the
+//! helpers in `HELPERS` and those three blocks are accepted; partials are not. Missing values
+//! print as nothing. Output is plain text for the PDF renderer, so nothing is escaped. A block
+//! tag alone on its line takes the line with it, so templates can be laid out readably.
+
+use std::fmt;
+use std::io;
+
+use handlebars::template::{HelperTemplate, Parameter, TemplateElement};
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, RenderContext, RenderError, RenderErrorReason,
+    ScopedJson,
+};
+use jiff::tz::TimeZone;
+use jiff::Timestamp;
+use serde::Serialize;
+use serde_json::Value;
+
+const DEFAULT_DATE_FORMAT: &str = "%B %-d, %Y";
+// Keeps a runaway `each` over a huge list from producing a document nobody can open
+const MAX_OUTPUT_LEN: usize = 4 * 1024 * 1024;
+
+/// Name, minimum and maximum argument count of every helper.
+const HELPERS: &[(&str, usize, usize)] = &[
+    ("currency", 1, 2),
+    ("number", 1, 2),
+    ("unit", 2, 2),
+    ("sum", 1, 2),
+    ("count", 1, 1),
+    ("multiply", 2, 2),
+    ("add", 2, 2),
+    ("upper", 1, 1),
+    ("lower", 1, 1),
+    ("default", 2, 2),
+    ("date", 1, 2),
+];
+
+/// A problem in a template, at the tag it was found in.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateError {
+    pub message: String,
+    /// One-based line of the tag.
+    pub line: usize,
+    /// One-based column of the tag's opening braces, in characters.
+    pub column: usize,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+/// How numbers and money are written.
+#[derive(Debug, Clone)]
+pub struct NumberFormat {
+    pub currency_symbol: String,
+    pub decimal_separator: String,
+    pub grouping_separator: String,
+}
+
+impl NumberFormat {
+    fn group(&self, digits: &str) -> String {
+        let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                grouped.push_str(&self.grouping_separator);
+            }
+            grouped.push(c);
+        }
+        grouped
+    }
+
+    /// `value` to `decimals` places with grouped thousands; `trim` drops trailing zeros.
+    pub fn fixed(&self, value: f64, decimals: usize, trim: bool) -> String {
+        let fixed = format!("{:.*}", decimals, value.abs());
+        let (int, frac) = fixed.split_once('.').unwrap_or((&fixed, ""));
+        let frac = if trim {
+            frac.trim_end_matches('0')
+        } else {
+            frac
+        };
+        let negative = value < 0.0 && fixed.chars().any(|c| c.is_ascii_digit() && c != '0');
+        let mut text = format!("{}{}", if negative { "-" } else { "" }, self.group(int));
+        if !frac.is_empty() {
+            text.push_str(&self.decimal_separator);
+            text.push_str(frac);
+        }
+        text
+    }
+
+    /// Money to two places, with the sign ahead of `symbol`.
+    pub fn currency(&self, value: f64, symbol: &str) -> String {
+        let amount = self.fixed(value.abs(), 2, false);
+        let sign = if value < 0.0 && amount.chars().any(|c| c.is_ascii_digit() && c != '0') {
+            "-"
+        } else {
+            ""
+        };
+        format!("{sign}{symbol}{amount}")
+    }
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self {
+            currency_symbol: "$".into(),
+            decimal_separator: ".".into(),
+            grouping_separator: ",".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Pos {
+    line: usize,
+    column: usize,
+}
+
+impl Pos {
+    fn error(self, message: impl Into<String>) -> TemplateError {
+        TemplateError {
+            message: message.into(),
+            line: self.line,
+            column: self.column,
+        }
+    }
+}
+
+/// A parsed template, ready to render any number of times.
+#[derive(Debug)]
+pub struct Template {
+    compiled: handlebars::Template,
+}
+
+const BLOCKS: &[&str] = &["if", "unless", "each"];
+
+fn name_of(param: &Parameter) -> Option<&str> {
+    match param {
+        Parameter::Name(name) => Some(name),
+        _ => None,
+    }
+}
+
+// A helper call must name one of ours with the right number of arguments
+fn check_call(expr: &HelperTemplate, pos: Pos) -> Result<(), TemplateError> {
+    let first = name_of(&expr.name).unwrap_or_default();
+    let Some(&(helper, min, max)) = HELPERS.iter().find(|(name, _, _)| *name == first) else {
+        return Err(pos.error(format!(
+            "unknown helper `{first}`; available: {}",
+            HELPERS
+                .iter()
+                .map(|(name, _, _)| *name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )));
+    };
+    let args = expr.params.len();
+    if args < min || args > max {
+        let expected = if min == max {
+            min.to_string()
+        } else {
+            format!("{min} or {max}")
+        };
+        return Err(pos.error(format!(
+            "`{helper}` takes {expected} argument{}, got {args}",
+            if max == 1 { "" } else { "s" },
+        )));
+    }
+    check_args(expr, pos)
+}
+
+fn check_args(expr: &HelperTemplate, pos: Pos) -> Result<(), TemplateError> {
+    if !expr.hash.is_empty() {
+        return Err(pos.error("named arguments such as `key=value` are not supported"));
+    }
+    for param in &expr.params {
+        if let Parameter::Subexpression(sub) = param {
+            match sub.as_element() {
+                TemplateElement::Expression(call) if !call.params.is_empty() => {
+                    check_call(call, pos)?
+                }
+                _ => {
+                    return Err(pos.error("`( )` must call a helper, as in `(sum items \"total\")`"))
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Handlebars accepts any helper, partial, or decorator name; only ours are allowed here,
+// so a typo is caught when the template is saved rather than when it is printed
+fn check(template: &handlebars::Template) -> Result<(), TemplateError> {
+    for (index, element) in template.elements.iter().enumerate() {
+        let pos = template
+            .mapping
+            .get(index)
+            .map_or(Pos { line: 1, column: 1 }, |mapping| Pos {
+                line: mapping.0,
+                column: mapping.1,
+            });
+        match element {
+            TemplateElement::RawString(_) | TemplateElement::Comment(_) => {}
+            TemplateElement::Expression(expr) | TemplateElement::HtmlExpression(expr) => {
+                if expr.params.is_empty() {
+                    check_args(expr, pos)?;
+                } else {
+                    check_call(expr, pos)?;
+                }
+            }
+            TemplateElement::HelperBlock(block) => {
+                let name = name_of(&block.name).unwrap_or_default();
+                if !BLOCKS.contains(&name) {
+                    return Err(pos.error(format!(
+                        "unknown block `#{name}`; use `#if`, `#unless`, or `#each`"
+                    )));
+                }
+                if block.params.len() != 1 || block.block_param.is_some() {
+                    return Err(pos.error(format!(
+                        "`#{name}` needs one value, as in `{{{{#{name} items}}}}`"
+                    )));
+                }
+                check_args(block, pos)?;
+                for inner in [&block.template, &block.inverse].into_iter().flatten() {
+                    check(inner)?;
+                }
+            }
+            // Partials, decorators, and anything newer
+            _ => return Err(pos.error("partials and decorators are not supported")),
+        }
+    }
+    Ok(())
+}
+
+/// Parse `source`, reporting the first problem with where it is.
+pub fn parse(source: &str) -> Result<Template, TemplateError> {
+    let compiled = handlebars::Template::compile(source).map_err(|err| {
+        let (line, column) = err.pos().unwrap_or((1, 1));
+        Pos { line, column }.error(err.reason().to_string())
+    })?;
+    check(&compiled)?;
+    Ok(Template { compiled })
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(_) => true,
+    }
+}
+
+fn numeric(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Array(items) => items.iter().map(text).collect::<Vec<_>>().join(", "),
+        Value::Object(_) => value.to_string(),
+    }
+}
+
+// Whole amounts print without a `.0`, as people write them
+fn number_value(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        return Value::from(n as i64);
+    }
+    serde_json::Number::from_f64(n).map_or(Value::Null, Value::Number)
+}
+
+fn tidy(value: &mut Value) {
+    match value {
+        Value::Number(n) if !n.is_i64() && !n.is_u64() => {
+            *value = n.as_f64().map_or(Value::Null, number_value);
+        }
+        Value::Array(items) => items.iter_mut().for_each(tidy),
+        Value::Object(map) => map.values_mut().for_each(tidy),
+        _ => {}
+    }
+}
+
+// One of `HELPERS`, applied to its evaluated arguments
+fn call(helper: &str, args: &[Value], format: &NumberFormat) -> Result<Value, String> {
+    // Missing data prints as nothing rather than failing the whole document
+    let number = |index: usize| -> Result<Option<f64>, String> {
+        match args.get(index) {
+            None | Some(Value::Null) => Ok(None),
+            Some(value) => numeric(value)
+                .map(Some)
+                .ok_or_else(|| format!("`{helper}` needs a number, got {value}")),
+        }
+    };
+    let decimals = |index: usize, default: usize| -> Result<usize, String> {
+        Ok(number(index)?.map_or(default, |n| n.clamp(0.0, 10.0) as usize))
+    };
+    let list = |index: usize| -> Result<&Vec<Value>, String> {
+        match args.get(index) {
+            Some(Value::Array(items)) => Ok(items),
+            Some(other) => Err(format!("`{helper}` needs a list, got {other}")),
+            None => Err(format!("`{helper}` needs a list")),
+        }
+    };
+    let value = match helper {
+        "currency" => match number(0)? {
+            Some(n) => {
+                let symbol = args.get(1).map_or(format.currency_symbol.clone(), text);
+                Value::String(format.currency(n, &symbol))
+            }
+            None => Value::Null,
+        },
+        "number" => match number(0)? {
+            Some(n) => Value::String(format.fixed(n, decimals(1, 2)?, false)),
+            None => Value::Null,
+        },
+        "unit" => match number(0)? {
+            Some(n) => {
+                let unit = text(&args[1]);
+                let amount = format.fixed(n, 2, true);
+                Value::String(if unit.is_empty() {
+                    amount
+                } else {
+                    format!("{amount} {unit}")
+                })
+            }
+            None => Value::Null,
+        },
+        "sum" => {
+            let field = args.get(1).map(text);
+            let total: f64 = list(0)?
+                .iter()
+                .filter_map(|item| match &field {
+                    Some(field) => item.get(field).and_then(numeric),
+                    None => numeric(item),
+                })
+                .sum();
+            number_value(total)
+        }
+        "count" => match args.first() {
+            Some(Value::Null) => Value::from(0),
+            _ => Value::from(list(0)?.len()),
+        },
+        "multiply" | "add" => match (number(0)?, number(1)?) {
+            (Some(a), Some(b)) => number_value(if helper == "add" { a + b } else { a * b }),
+            _ => Value::Null,
+        },
+        "upper" => Value::String(text(&args[0]).to_uppercase()),
+        "lower" => Value::String(text(&args[0]).to_lowercase()),
+        "default" => {
+            if truthy(&args[0]) {
+                args[0].clone()
+            } else {
+                args[1].clone()
+            }
+        }
+        "date" => {
+            let pattern = args.get(1).map_or(DEFAULT_DATE_FORMAT.to_string(), text);
+            match &args[0] {
+                Value::Null => Value::Null,
+                value => {
+                    let timestamp = match value {
+                        Value::String(s) => s.parse::<Timestamp>().ok().or_else(|| {
+                            s.parse::<jiff::civil::Date>()
+                                .ok()
+                                .and_then(|d| d.to_zoned(TimeZone::system()).ok())
+                                .map(|z| z.timestamp())
+                        }),
+                        other => numeric(other)
+                            .and_then(|ms| Timestamp::from_millisecond(ms as i64).ok()),
+                    }
+                    .ok_or_else(|| {
+                        format!("`date` needs Unix milliseconds or an ISO 8601 date, got {value}")
+                    })?;
+                    let zoned = timestamp.to_zoned(TimeZone::system());
+                    Value::String(zoned.strftime(&pattern).to_string())
+                }
+            }
+        }
+        _ => return Err(format!("unknown helper `{helper}`")),
+    };
+    Ok(value)
+}
+
+// Registered under each name in `HELPERS`; returning a value lets it nest in `( )`
+struct Call<'f> {
+    helper: &'static str,
+    format: &'f NumberFormat,
+}
+
+impl HelperDef for Call<'_> {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let args: Vec<Value> = h.params().iter().map(|p| p.value().clone()).collect();
+        call(self.helper, &args, self.format)
+            .map(ScopedJson::Derived)
+            .map_err(|message| RenderErrorReason::Other(message).into())
+    }
+}
+
+// Refuses to grow past `MAX_OUTPUT_LEN`, noting that it did
+struct Capped {
+    out: Vec<u8>,
+    full: bool,
+}
+
+impl io::Write for Capped {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.out.len() + buf.len() > MAX_OUTPUT_LEN {
+            self.full = true;
+            return Err(io::Error::other("document too long"));
+        }
+        self.out.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Template {
+    /// Render against `data`, whose top-level keys are the names the template can use.
+    pub fn render(&self, data: &Value, format: &NumberFormat) -> Result<String, TemplateError> {
+        let mut registry = Handlebars::new();
+        // Output is plain text for the PDF renderer
+        registry.register_escape_fn(handlebars::no_escape);
+        for &(helper, _, _) in HELPERS {
+            registry.register_helper(helper, Box::new(Call { helper, format }));
+        }
+        registry.register_template("document", self.compiled.clone());
+        let mut data = data.clone();
+        tidy(&mut data);
+        let mut out = Capped {
+            out: Vec::new(),
+            full: false,
+        };
+        match registry.render_to_write("document", &data, &mut out) {
+            Ok(()) => Ok(String::from_utf8_lossy(&out.out).into_owned()),
+            Err(_) if out.full => Err(Pos { line: 1, column: 1 }
+                .error("the document is too long; check for a runaway `#each`")),
+            Err(err) => {
+                let pos = Pos {
+                    line: err.line_no.unwrap_or(1),
+                    column: err.column_no.unwrap_or(1),
+                };
+                let message = match err.reason() {
+                    RenderErrorReason::Other(message) => message.clone(),
+                    other => other.to_string(),
+                };
+                Err(pos.error(message))
+            }
+        }
+    }
+}
@@ -0,0 +1,295 @@
+//! User-editable document templates, such as proposals and cover letters, rendered against a
+//! project and printed through the report PDF renderer.
+//!
+//! Templates live in the database and are checked before they are saved, so a broken one
+//! is reported with its line and column instead of failing at print time. A template sees
+//! `project`, `items` (each with its `total`), `subtotal`, `today`, and whatever the caller
+//! passes as `data`, such as the client's name. Numbers follow the user's locale.
+
+pub mod engine;
+
+use std::path::PathBuf;
+
+use rusqlite::{params, Connection, OptionalExtension, Row, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::State;
+
+use crate::audit::{self, Change};
+use crate::db::projects::{Item, Project};
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::locale;
+use crate::reports::{self, ReportData, ReportTemplate};
+use crate::state::AppState;
+use engine::{NumberFormat, TemplateError};
+
+const KINDS: &[&str] = &["proposal", "coverLetter", "custom"];
+const MAX_NAME_LEN: usize = 200;
+const MAX_BODY_LEN: usize = 256 * 1024;
+
+/// A stored document template.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentTemplate {
+    pub id: String,
+    pub name: String,
+    /// `proposal`, `coverLetter`, or `custom`.
+    pub kind: String,
+    pub body: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl DocumentTemplate {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            kind: row.get("kind")?,
+            body: row.get("body")?,
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+}
+
+/// Fields accepted when saving a template; a missing `id` creates one.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentTemplateInput {
+    pub id: Option<String>,
+    pub name: String,
+    pub kind: String,
+    pub body: String,
+}
+
+fn validate_input(input: &DocumentTemplateInput) -> AppResult<()> {
+    let name = input.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+        return Err(AppError::InvalidInput(format!(
+            "template name must be 1 to {MAX_NAME_LEN} characters"
+        )));
+    }
+    if !KINDS.contains(&input.kind.as_str()) {
+        return Err(AppError::InvalidInput(format!(
+            "unknown template kind {}; expected one of {}",
+            input.kind,
+            KINDS.join(", ")
+        )));
+    }
+    if input.body.len() > MAX_BODY_LEN {
+        return Err(AppError::InvalidInput("template is too long".into()));
+    }
+    engine::parse(&input.body).map_err(|err| AppError::InvalidInput(err.to_string()))?;
+    Ok(())
+}
+
+fn get(conn: &Connection, id: &str) -> AppResult<DocumentTemplate> {
+    conn.query_row(
+        "SELECT * FROM document_templates WHERE id = ?1",
+        [id],
+        DocumentTemplate::from_row,
+    )
+    .optional()?
+    .ok_or_else(|| AppError::not_found("document template", id))
+}
+
+// What a template can refer to
+fn context(
+    conn: &Connection,
+    project_id: &str,
+    data: Option<Value>,
+) -> AppResult<(Project, Value)> {
+    let project = conn
+        .query_row(
+            "SELECT * FROM projects WHERE id = ?1",
+            [project_id],
+            Project::from_row,
+        )
+        .optional()?
+        .ok_or_else(|| AppError::not_found("project", project_id))?;
+    let mut stmt =
+        conn.prepare("SELECT * FROM items WHERE project_id = ?1 ORDER BY sort_order, name")?;
+    let items = stmt
+        .query_map([project_id], Item::from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let subtotal: f64 = items
+        .iter()
+        .map(|item| item.quantity * item.unit_cost)
+        .sum();
+    let items = items
+        .iter()
+        .map(|item| {
+            let mut value = serde_json::to_value(item)?;
+            value["total"] = json!(item.quantity * item.unit_cost);
+            Ok(value)
+        })
+        .collect::<AppResult<Vec<_>>>()?;
+    let context = json!({
+        "project": project,
+        "items": items,
+        "subtotal": subtotal,
+        "today": now_ms(),
+        "data": data.unwrap_or(Value::Null),
+    });
+    Ok((project, context))
+}
+
+pub(crate) fn number_format(currency_symbol: Option<String>) -> NumberFormat {
+    let locale = locale::current();
+    NumberFormat {
+        currency_symbol: currency_symbol.unwrap_or_else(|| NumberFormat::default().currency_symbol),
+        decimal_separator: locale.decimal_separator,
+        grouping_separator: locale.grouping_separator,
+    }
+}
+
+fn render(
+    conn: &Connection,
+    id: &str,
+    project_id: &str,
+    data: Option<Value>,
+    format: &NumberFormat,
+) -> AppResult<(DocumentTemplate, Project, String)> {
+    let template = get(conn, id)?;
+    let (project, context) = context(conn, project_id, data)?;
+    let text = engine::parse(&template.body)
+        .and_then(|parsed| parsed.render(&context, format))
+        .map_err(|err| AppError::InvalidInput(format!("{}: {err}", template.name)))?;
+    Ok((template, project, text))
+}
+
+#[tauri::command]
+pub async fn list_document_templates(
+    db: State<'_, Db>,
+    kind: Option<String>,
+) -> AppResult<Vec<DocumentTemplate>> {
+    db.run(move |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT * FROM document_templates WHERE ?1 IS NULL OR kind = ?1 ORDER BY kind, name",
+        )?;
+        let rows = stmt.query_map([&kind], DocumentTemplate::from_row)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    })
+    .await
+}
+
+/// Check a template without saving it; `None` means it is valid.
+#[tauri::command]
+pub async fn validate_document_template(body: String) -> AppResult<Option<TemplateError>> {
+    Ok(engine::parse(&body).err())
+}
+
+/// Create or update a template, refusing one that does not parse.
+#[tauri::command]
+pub async fn save_document_template(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    input: DocumentTemplateInput,
+) -> AppResult<DocumentTemplate> {
+    validate_input(&input)?;
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let id = input.id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let before = get(&tx, &id).ok();
+        let now = now_ms();
+        tx.execute(
+            "INSERT INTO document_templates (id, name, kind, body, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+             ON CONFLICT(id) DO UPDATE SET
+                name = excluded.name,
+                kind = excluded.kind,
+                body = excluded.body,
+                updated_at = excluded.updated_at",
+            params![id, input.name.trim(), input.kind, input.body, now],
+        )?;
+        let template = get(&tx, &id)?;
+        let change = Change::new("save_document_template", "document_template", &id);
+        let change = match &before {
+            Some(before) => change.before(before)?,
+            None => change,
+        };
+        audit::record(&tx, &actor, change.after(&template)?)?;
+        tx.commit()?;
+        Ok(template)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn delete_document_template(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    id: String,
+) -> AppResult<()> {
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        if let Ok(before) = get(&tx, &id) {
+            tx.execute("DELETE FROM document_templates WHERE id = ?1", [&id])?;
+            audit::record(
+                &tx,
+                &actor,
+                Change::new("delete_document_template", "document_template", &id)
+                    .before(&before)?,
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    })
+    .await
+}
+
+/// Render a template against a project as text, for previewing.
+#[tauri::command]
+pub async fn render_document_template(
+    db: State<'_, Db>,
+    id: String,
+    project_id: String,
+    data: Option<Value>,
+    currency_symbol: Option<String>,
+) -> AppResult<String> {
+    let format = number_format(currency_symbol);
+    db.run(move |conn| {
+        let (_, _, text) = render(conn, &id, &project_id, data, &format)?;
+        Ok(text)
+    })
+    .await
+}
+
+/// Render a template against a project and print it to `path`, or to the reports folder.
+///
+/// `report` sets the page, font, header, and footer; its currency symbol is used for money.
+#[tauri::command]
+pub async fn generate_document_pdf(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    id: String,
+    project_id: String,
+    data: Option<Value>,
+    report: Option<ReportTemplate>,
+    path: Option<PathBuf>,
+) -> AppResult<PathBuf> {
+    let report = report.unwrap_or_else(|| ReportTemplate {
+        cover_page: false,
+        ..Default::default()
+    });
+    let format = number_format(Some(report.currency_symbol.clone()));
+    let (template, project, body) = db
+        .run(move |conn| render(conn, &id, &project_id, data, &format))
+        .await?;
+    let data = ReportData {
+        title: project.name,
+        subtitle: Some(template.name),
+        fields: Vec::new(),
+        sections: Vec::new(),
+        summary: Vec::new(),
+        notes: None,
+        body: Some(body),
+        photos: Vec::new(),
+    };
+    let path = path.unwrap_or_else(|| reports::default_path(&state, &data.title));
+    reports::write(data, report, None, path).await
+}
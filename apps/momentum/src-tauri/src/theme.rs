@@ -0,0 +1,305 @@
+//! The system's light or dark preference and accent color, with an event when either changes.
+//!
+//! Light and dark come from the OS where it says so directly (the desktop portal on Linux,
+//! UI settings on Windows) and from the main window's theme otherwise. Windows and Linux
+//! announce changes, which are forwarded as they happen. macOS reports theme changes to the
+//! window but not accent changes, so the accent is rechecked whenever a window regains
+//! focus, which is what happens when the user comes back from System Settings.
+
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State, Window, WindowEvent};
+
+use crate::error::AppResult;
+use crate::windows::MAIN_WINDOW;
+
+/// Emitted with the [`SystemTheme`] when the preference or accent color changes.
+pub const THEME_CHANGED_EVENT: &str = "theme:changed";
+
+/// What the OS prefers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemTheme {
+    /// `light` or `dark`.
+    pub theme: &'static str,
+    /// `#rrggbb`, when the platform has an accent color and the user has one set.
+    pub accent_color: Option<String>,
+}
+
+/// Last theme reported, registered in app state.
+pub struct ThemeWatcher {
+    last: Mutex<Option<SystemTheme>>,
+    // Held so the OS keeps delivering change notifications
+    _backend: Option<os::Backend>,
+}
+
+fn hex(red: u8, green: u8, blue: u8) -> String {
+    format!("#{red:02x}{green:02x}{blue:02x}")
+}
+
+fn current(app: &AppHandle) -> SystemTheme {
+    let dark = os::prefers_dark().unwrap_or_else(|| {
+        app.get_webview_window(MAIN_WINDOW)
+            .and_then(|window| window.theme().ok())
+            .is_some_and(|theme| theme == tauri::Theme::Dark)
+    });
+    SystemTheme {
+        theme: if dark { "dark" } else { "light" },
+        accent_color: os::accent_color(),
+    }
+}
+
+/// Reread the theme and tell the frontend if it changed.
+pub fn refresh(app: &AppHandle) {
+    let Some(watcher) = app.try_state::<ThemeWatcher>() else {
+        return;
+    };
+    let theme = current(app);
+    let mut last = watcher.last.lock().unwrap_or_else(|e| e.into_inner());
+    if last.as_ref() == Some(&theme) {
+        return;
+    }
+    tracing::debug!(theme = theme.theme, accent = ?theme.accent_color, "system theme changed");
+    *last = Some(theme.clone());
+    drop(last);
+    let _ = app.emit(THEME_CHANGED_EVENT, theme);
+}
+
+/// Start listening for theme changes the OS announces.
+pub fn init(app: &AppHandle) -> ThemeWatcher {
+    let backend = os::Backend::new(app)
+        .map_err(|err| tracing::debug!(%err, "theme changes will only be seen through windows"))
+        .ok();
+    ThemeWatcher {
+        last: Mutex::new(Some(current(app))),
+        _backend: backend,
+    }
+}
+
+/// Recheck on theme changes and when a window regains focus.
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    if matches!(
+        event,
+        WindowEvent::ThemeChanged(_) | WindowEvent::Focused(true)
+    ) {
+        // Reading can mean a round trip to a system service; keep it off the UI thread
+        let app = window.app_handle().clone();
+        tauri::async_runtime::spawn_blocking(move || refresh(&app));
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use std::sync::OnceLock;
+
+    use tauri::AppHandle;
+    use zbus::blocking::{Connection, Proxy};
+    use zbus::zvariant::{OwnedValue, Value};
+
+    use crate::error::{AppError, AppResult};
+
+    const DESTINATION: &str = "org.freedesktop.portal.Desktop";
+    const PATH: &str = "/org/freedesktop/portal/desktop";
+    const INTERFACE: &str = "org.freedesktop.portal.Settings";
+    const NAMESPACE: &str = "org.freedesktop.appearance";
+    // color-scheme values: 0 no preference, 1 dark, 2 light
+    const PREFER_DARK: u32 = 1;
+    const PREFER_LIGHT: u32 = 2;
+
+    fn failed(err: zbus::Error) -> AppError {
+        AppError::Internal(format!("desktop portal: {err}"))
+    }
+
+    fn connection() -> Option<&'static Connection> {
+        static CONNECTION: OnceLock<Option<Connection>> = OnceLock::new();
+        CONNECTION
+            .get_or_init(|| Connection::session().ok())
+            .as_ref()
+    }
+
+    // ReadOne is newer; Read wraps the value in one more variant
+    fn read(key: &str) -> Option<OwnedValue> {
+        let connection = connection()?;
+        let call = |method| {
+            connection.call_method(
+                Some(DESTINATION),
+                PATH,
+                Some(INTERFACE),
+                method,
+                &(NAMESPACE, key),
+            )
+        };
+        if let Ok(reply) = call("ReadOne") {
+            return reply.body().deserialize::<OwnedValue>().ok();
+        }
+        let reply = call("Read").ok()?;
+        let outer = reply.body().deserialize::<OwnedValue>().ok()?;
+        match &*outer {
+            Value::Value(inner) => inner.try_to_owned().ok(),
+            _ => Some(outer),
+        }
+    }
+
+    pub fn prefers_dark() -> Option<bool> {
+        match u32::try_from(read("color-scheme")?).ok()? {
+            PREFER_DARK => Some(true),
+            PREFER_LIGHT => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn accent_color() -> Option<String> {
+        let (red, green, blue) = <(f64, f64, f64)>::try_from(read("accent-color")?).ok()?;
+        // Out-of-range components mean the user has not picked one
+        let channel = |c: f64| (0.0..=1.0).contains(&c).then(|| (c * 255.0).round() as u8);
+        Some(super::hex(channel(red)?, channel(green)?, channel(blue)?))
+    }
+
+    pub struct Backend;
+
+    impl Backend {
+        pub fn new(app: &AppHandle) -> AppResult<Self> {
+            let connection = connection()
+                .ok_or_else(|| AppError::Internal("no session bus".into()))?
+                .clone();
+            let proxy =
+                Proxy::new_owned(connection, DESTINATION, PATH, INTERFACE).map_err(failed)?;
+            let signals = proxy.receive_signal("SettingChanged").map_err(failed)?;
+            let app = app.clone();
+            std::thread::Builder::new()
+                .name("theme-changes".into())
+                .spawn(move || {
+                    let _proxy = proxy;
+                    for message in signals {
+                        let Ok((namespace, _key, _value)) =
+                            message.body().deserialize::<(String, String, OwnedValue)>()
+                        else {
+                            continue;
+                        };
+                        if namespace == NAMESPACE {
+                            super::refresh(&app);
+                        }
+                    }
+                })?;
+            Ok(Self)
+        }
+    }
+}
+
+#[cfg(windows)]
+mod os {
+    use tauri::AppHandle;
+    use windows::core::IInspectable;
+    use windows::Foundation::TypedEventHandler;
+    use windows::UI::ViewManagement::{UIColorType, UISettings};
+
+    use crate::error::{AppError, AppResult};
+
+    fn settings() -> Option<UISettings> {
+        UISettings::new().ok()
+    }
+
+    // Windows has no dark flag here; a dark background is the dark theme
+    pub fn prefers_dark() -> Option<bool> {
+        let background = settings()?.GetColorValue(UIColorType::Background).ok()?;
+        let luma = 0.299 * f64::from(background.R)
+            + 0.587 * f64::from(background.G)
+            + 0.114 * f64::from(background.B);
+        Some(luma < 128.0)
+    }
+
+    pub fn accent_color() -> Option<String> {
+        let accent = settings()?.GetColorValue(UIColorType::Accent).ok()?;
+        Some(super::hex(accent.R, accent.G, accent.B))
+    }
+
+    pub struct Backend {
+        // The event stops when the settings object is released
+        _settings: UISettings,
+    }
+
+    impl Backend {
+        pub fn new(app: &AppHandle) -> AppResult<Self> {
+            let failed = |e: windows::core::Error| AppError::Internal(format!("UI settings: {e}"));
+            let settings = UISettings::new().map_err(failed)?;
+            let app = app.clone();
+            settings
+                .ColorValuesChanged(&TypedEventHandler::<UISettings, IInspectable>::new(
+                    move |_, _| {
+                        super::refresh(&app);
+                        Ok(())
+                    },
+                ))
+                .map_err(failed)?;
+            Ok(Self {
+                _settings: settings,
+            })
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use objc2_app_kit::{NSColor, NSColorSpace};
+    use tauri::AppHandle;
+
+    use crate::error::AppResult;
+
+    // The window's theme already follows the appearance
+    pub fn prefers_dark() -> Option<bool> {
+        None
+    }
+
+    pub fn accent_color() -> Option<String> {
+        let accent =
+            NSColor::controlAccentColor().colorUsingColorSpace(&NSColorSpace::sRGBColorSpace())?;
+        let channel = |c: f64| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Some(super::hex(
+            channel(accent.redComponent()),
+            channel(accent.greenComponent()),
+            channel(accent.blueComponent()),
+        ))
+    }
+
+    pub struct Backend;
+
+    impl Backend {
+        pub fn new(_app: &AppHandle) -> AppResult<Self> {
+            Ok(Self)
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows, target_os = "macos")))]
+mod os {
+    use tauri::AppHandle;
+
+    use crate::error::AppResult;
+
+    pub fn prefers_dark() -> Option<bool> {
+        None
+    }
+
+    pub fn accent_color() -> Option<String> {
+        None
+    }
+
+    pub struct Backend;
+
+    impl Backend {
+        pub fn new(_app: &AppHandle) -> AppResult<Self> {
+            Ok(Self)
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn get_system_theme(
+    app: AppHandle,
+    watcher: State<'_, ThemeWatcher>,
+) -> AppResult<SystemTheme> {
+    let theme = current(&app);
+    *watcher.last.lock().unwrap_or_else(|e| e.into_inner()) = Some(theme.clone());
+    Ok(theme)
+}
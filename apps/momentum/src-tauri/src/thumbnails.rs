@@ -0,0 +1,152 @@
+//! Downscaled previews of attached photos, cached on disk.
+//!
+//! Camera photos are often stored sideways with an EXIF orientation tag, so the tag is
+//! applied before scaling and thumbnails come out upright with no tag of their own.
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{DynamicImage, ImageDecoder, ImageReader};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::disk_cache;
+use crate::error::{AppError, AppResult};
+
+const CACHE_DIR_NAME: &str = "thumbnails";
+const CACHE_BUDGET_BYTES: u64 = 256 * 1024 * 1024;
+const JPEG_QUALITY: u8 = 82;
+const MAX_DIM: u32 = 2048;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ThumbnailFormat {
+    #[default]
+    Jpeg,
+    /// Lossless, so larger than JPEG, but keeps transparency.
+    Webp,
+}
+
+impl ThumbnailFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Webp => "webp",
+        }
+    }
+}
+
+/// A thumbnail on disk.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Thumbnail {
+    /// File inside the thumbnail cache; load it with `convertFileSrc`.
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    pub cached: bool,
+}
+
+/// Cache directory handle registered in app state.
+pub struct ThumbnailCache {
+    dir: PathBuf,
+}
+
+/// Open the cache under `data_dir/thumbnails`.
+pub fn init(data_dir: &Path) -> AppResult<ThumbnailCache> {
+    let dir = data_dir.join(CACHE_DIR_NAME);
+    fs::create_dir_all(&dir)?;
+    Ok(ThumbnailCache { dir })
+}
+
+fn image_error(path: &Path, err: image::ImageError) -> AppError {
+    AppError::InvalidInput(format!("cannot read image {}: {err}", path.display()))
+}
+
+pub(crate) fn load_upright(path: &Path) -> AppResult<DynamicImage> {
+    let mut decoder = ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_decoder()
+        .map_err(|e| image_error(path, e))?;
+    let orientation = decoder.orientation().map_err(|e| image_error(path, e))?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|e| image_error(path, e))?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+fn write(image: &DynamicImage, format: ThumbnailFormat, path: &Path) -> AppResult<()> {
+    let tmp = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&tmp)?);
+    let result = match format {
+        // JPEG has no alpha channel
+        ThumbnailFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut writer, JPEG_QUALITY)),
+        ThumbnailFormat::Webp => DynamicImage::ImageRgba8(image.to_rgba8())
+            .write_with_encoder(WebPEncoder::new_lossless(&mut writer)),
+    };
+    result.map_err(|e| AppError::Internal(format!("cannot encode thumbnail: {e}")))?;
+    drop(writer);
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// A copy of the image at `path` no larger than `max_dim` on either side, from the cache in
+/// `dir` when it has one; blocks while decoding and encoding.
+pub(crate) fn render(
+    dir: &Path,
+    path: &Path,
+    max_dim: u32,
+    format: ThumbnailFormat,
+) -> AppResult<Thumbnail> {
+    let max_dim = max_dim.clamp(1, MAX_DIM);
+    let key = disk_cache::source_key(path, &format!("max={max_dim}"))?;
+    let output = dir.join(format!("{key}.{}", format.extension()));
+    if let Ok((width, height)) = image::image_dimensions(&output) {
+        disk_cache::touch(&output);
+        return Ok(Thumbnail {
+            path: output,
+            width,
+            height,
+            cached: true,
+        });
+    }
+
+    let image = load_upright(path)?;
+    let image = if image.width() > max_dim || image.height() > max_dim {
+        image.thumbnail(max_dim, max_dim)
+    } else {
+        image
+    };
+    write(&image, format, &output)?;
+    disk_cache::evict(dir, CACHE_BUDGET_BYTES, &output);
+    Ok(Thumbnail {
+        path: output,
+        width: image.width(),
+        height: image.height(),
+        cached: false,
+    })
+}
+
+impl ThumbnailCache {
+    pub(crate) fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+/// A copy of the image at `path` no larger than `max_dim` on either side.
+///
+/// Images already that small are re-encoded at their own size; `max_dim` is capped at 2048.
+#[tauri::command]
+pub async fn get_thumbnail(
+    cache: State<'_, ThumbnailCache>,
+    path: PathBuf,
+    max_dim: u32,
+    format: Option<ThumbnailFormat>,
+) -> AppResult<Thumbnail> {
+    let format = format.unwrap_or_default();
+    let dir = cache.dir.clone();
+    tauri::async_runtime::spawn_blocking(move || render(&dir, &path, max_dim, format)).await?
+}
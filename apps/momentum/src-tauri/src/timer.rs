@@ -0,0 +1,459 @@
+//! Time tracking that lives in the backend, so a timer keeps counting while the window is
+//! hidden to the tray or the webview reloads.
+//!
+//! Timers are rows in the database and elapsed time is derived from wall-clock timestamps, so
+//! nothing is lost if the app is killed. A running timer keeps running across a restart; the
+//! stretch the app was closed is recorded as offline time and reported once, and the user
+//! can leave it in or drop it when stopping. Only one timer runs at a time: starting or
+//! resuming one pauses the other. While any timer runs, `timer:tick` carries elapsed times
+//! every second.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rusqlite::{params, OptionalExtension, Row};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+
+/// Emitted every second with a [`TimerTick`] per running timer.
+pub const TICK_EVENT: &str = "timer:tick";
+/// Emitted with the [`Timer`] after it starts, pauses, resumes, or stops.
+pub const CHANGED_EVENT: &str = "timer:changed";
+
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+// How stale heartbeat_at can get, and so how much a gap can be overstated after a crash
+const HEARTBEAT_EVERY_TICKS: u32 = 30;
+// Restarts quicker than this are not worth asking about
+const MIN_GAP_MS: i64 = 2 * 60 * 1000;
+const MAX_NOTE_LEN: usize = 500;
+
+const STATE_RUNNING: &str = "running";
+const STATE_STOPPED: &str = "stopped";
+
+/// A timer as shown to the frontend.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Timer {
+    pub id: String,
+    pub project_id: Option<String>,
+    pub note: String,
+    /// `running`, `paused`, or `stopped`.
+    pub state: String,
+    /// Total so far, offline time included.
+    pub elapsed_ms: i64,
+    /// Part of `elapsed_ms` that passed while the app was closed.
+    pub offline_ms: i64,
+    pub started_at: i64,
+    pub stopped_at: Option<i64>,
+    pub updated_at: i64,
+    #[serde(skip)]
+    accumulated_ms: i64,
+    #[serde(skip)]
+    resumed_at: Option<i64>,
+}
+
+impl Timer {
+    pub(crate) fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let mut timer = Self {
+            id: row.get("id")?,
+            project_id: row.get("project_id")?,
+            note: row.get("note")?,
+            state: row.get("state")?,
+            elapsed_ms: 0,
+            offline_ms: row.get("offline_ms")?,
+            started_at: row.get("started_at")?,
+            stopped_at: row.get("stopped_at")?,
+            updated_at: row.get("updated_at")?,
+            accumulated_ms: row.get("accumulated_ms")?,
+            resumed_at: row.get("resumed_at")?,
+        };
+        timer.elapsed_ms = timer.elapsed_at(now_ms());
+        Ok(timer)
+    }
+
+    fn elapsed_at(&self, now: i64) -> i64 {
+        let running = match self.resumed_at {
+            Some(resumed_at) if self.state == STATE_RUNNING => (now - resumed_at).max(0),
+            _ => 0,
+        };
+        self.accumulated_ms + running
+    }
+}
+
+/// Payload for `timer:tick`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimerTick {
+    pub id: String,
+    pub elapsed_ms: i64,
+}
+
+/// A stretch a timer ran while the app was closed, from `take_timer_gaps`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OfflineGap {
+    pub timer_id: String,
+    /// Last moment the app was seen open with the timer running.
+    pub from: i64,
+    pub to: i64,
+    pub gap_ms: i64,
+}
+
+// Folds running time into accumulated_ms, for pausing and stopping
+const SETTLE: &str = "accumulated_ms = accumulated_ms + MAX(?2 - resumed_at, 0), resumed_at = NULL";
+
+/// Timer store registered in app state.
+pub struct Timers {
+    db: Db,
+    // Running timers, so ticks need no query
+    running: Mutex<Vec<Timer>>,
+    // Gaps found at launch, held until the frontend can hear about them
+    gaps: Mutex<Vec<OfflineGap>>,
+}
+
+impl Timers {
+    pub fn new(db: Db) -> Self {
+        Self {
+            db,
+            running: Mutex::new(Vec::new()),
+            gaps: Mutex::new(Vec::new()),
+        }
+    }
+
+    async fn get(&self, id: String) -> AppResult<Timer> {
+        let lookup = id.clone();
+        self.db
+            .run(move |conn| {
+                Ok(conn
+                    .query_row(
+                        "SELECT * FROM timers WHERE id = ?1",
+                        [lookup],
+                        Timer::from_row,
+                    )
+                    .optional()?)
+            })
+            .await?
+            .ok_or_else(|| AppError::not_found("timer", id))
+    }
+
+    async fn reload(&self) -> AppResult<()> {
+        let running: Vec<Timer> = self
+            .db
+            .run(|conn| {
+                Ok(conn
+                    .prepare("SELECT * FROM timers WHERE state = 'running'")?
+                    .query_map([], Timer::from_row)?
+                    .collect::<rusqlite::Result<_>>()?)
+            })
+            .await?;
+        *self.running.lock().unwrap_or_else(|e| e.into_inner()) = running;
+        Ok(())
+    }
+
+    /// Record that the app is open with its timers running.
+    pub async fn heartbeat(&self) -> AppResult<()> {
+        self.db
+            .run(|conn| {
+                conn.execute(
+                    "UPDATE timers SET heartbeat_at = ?1 WHERE state = 'running'",
+                    [now_ms()],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    // Book the time since each running timer's last heartbeat as offline
+    async fn recover(&self) -> AppResult<Vec<OfflineGap>> {
+        let gaps = self
+            .db
+            .run(|conn| {
+                let tx = conn.transaction()?;
+                let now = now_ms();
+                let stale: Vec<(String, i64)> = tx
+                    .prepare(
+                        "SELECT id, COALESCE(heartbeat_at, resumed_at) FROM timers
+                         WHERE state = 'running'",
+                    )?
+                    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<rusqlite::Result<_>>()?;
+                let mut gaps = Vec::new();
+                for (id, from) in stale {
+                    let gap_ms = now - from;
+                    tx.execute(
+                        "UPDATE timers SET heartbeat_at = ?2, offline_ms = offline_ms + ?3
+                         WHERE id = ?1",
+                        params![id, now, gap_ms.max(0)],
+                    )?;
+                    if gap_ms >= MIN_GAP_MS {
+                        gaps.push(OfflineGap {
+                            timer_id: id,
+                            from,
+                            to: now,
+                            gap_ms,
+                        });
+                    }
+                }
+                tx.commit()?;
+                Ok(gaps)
+            })
+            .await?;
+        self.reload().await?;
+        Ok(gaps)
+    }
+
+    fn ticks(&self) -> Vec<TimerTick> {
+        let now = now_ms();
+        self.running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|timer| TimerTick {
+                id: timer.id.clone(),
+                elapsed_ms: timer.elapsed_at(now),
+            })
+            .collect()
+    }
+}
+
+// Reload the tick cache and tell every window what changed
+async fn changed(app: &AppHandle, timers: &Timers, id: String) -> AppResult<Timer> {
+    timers.reload().await?;
+    let timer = timers.get(id).await?;
+    let _ = app.emit(CHANGED_EVENT, &timer);
+    Ok(timer)
+}
+
+fn pause_others(conn: &rusqlite::Connection, except: &str, now: i64) -> rusqlite::Result<()> {
+    conn.execute(
+        &format!(
+            "UPDATE timers SET state = 'paused', {SETTLE}, updated_at = ?2
+             WHERE state = 'running' AND id != ?1"
+        ),
+        params![except, now],
+    )?;
+    Ok(())
+}
+
+/// Pick up timers left running by the last session, then tick until the app exits.
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let timers = app.state::<Timers>();
+        match timers.recover().await {
+            Ok(gaps) => {
+                for gap in &gaps {
+                    tracing::info!(id = %gap.timer_id, gap_ms = gap.gap_ms, "timer ran while closed");
+                }
+                timers
+                    .gaps
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .extend(gaps);
+            }
+            Err(err) => tracing::warn!(%err, "failed to recover running timers"),
+        }
+        let mut interval = tokio::time::interval(TICK_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut since_heartbeat = 0;
+        loop {
+            interval.tick().await;
+            let ticks = timers.ticks();
+            if ticks.is_empty() {
+                continue;
+            }
+            let _ = app.emit(TICK_EVENT, &ticks);
+            since_heartbeat += 1;
+            if since_heartbeat >= HEARTBEAT_EVERY_TICKS {
+                since_heartbeat = 0;
+                if let Err(err) = timers.heartbeat().await {
+                    tracing::debug!(%err, "timer heartbeat failed");
+                }
+            }
+        }
+    });
+}
+
+fn validate_note(note: Option<String>) -> AppResult<String> {
+    let note = note.unwrap_or_default();
+    if note.chars().count() > MAX_NOTE_LEN {
+        return Err(AppError::InvalidInput(format!(
+            "a timer note must be at most {MAX_NOTE_LEN} characters"
+        )));
+    }
+    Ok(note.trim().to_string())
+}
+
+/// Start a new timer, pausing whichever one is running.
+#[tauri::command]
+pub async fn timer_start(
+    app: AppHandle,
+    timers: State<'_, Timers>,
+    project_id: Option<String>,
+    note: Option<String>,
+) -> AppResult<Timer> {
+    let note = validate_note(note)?;
+    let id = uuid::Uuid::new_v4().to_string();
+    let row = id.clone();
+    timers
+        .db
+        .run(move |conn| {
+            let tx = conn.transaction()?;
+            let now = now_ms();
+            pause_others(&tx, &row, now)?;
+            tx.execute(
+                "INSERT INTO timers (id, project_id, note, state, resumed_at, heartbeat_at,
+                                     started_at, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, 'running', ?4, ?4, ?4, ?4, ?4)",
+                params![row, project_id, note, now],
+            )
+            .map_err(|err| match err {
+                rusqlite::Error::SqliteFailure(e, _)
+                    if e.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_FOREIGNKEY =>
+                {
+                    AppError::not_found("project", project_id.clone().unwrap_or_default())
+                }
+                err => err.into(),
+            })?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await?;
+    tracing::info!(%id, "timer started");
+    changed(&app, &timers, id).await
+}
+
+#[tauri::command]
+pub async fn timer_pause(
+    app: AppHandle,
+    timers: State<'_, Timers>,
+    id: String,
+) -> AppResult<Timer> {
+    let row = id.clone();
+    let updated = timers
+        .db
+        .run(move |conn| {
+            Ok(conn.execute(
+                &format!(
+                    "UPDATE timers SET state = 'paused', {SETTLE}, updated_at = ?2
+                     WHERE id = ?1 AND state = 'running'"
+                ),
+                params![row, now_ms()],
+            )?)
+        })
+        .await?;
+    let timer = changed(&app, &timers, id).await?;
+    if updated == 0 && timer.state == STATE_STOPPED {
+        return Err(AppError::InvalidInput(
+            "a stopped timer cannot be paused".into(),
+        ));
+    }
+    Ok(timer)
+}
+
+/// Resume a paused timer, pausing whichever one is running.
+#[tauri::command]
+pub async fn timer_resume(
+    app: AppHandle,
+    timers: State<'_, Timers>,
+    id: String,
+) -> AppResult<Timer> {
+    let row = id.clone();
+    let updated = timers
+        .db
+        .run(move |conn| {
+            let tx = conn.transaction()?;
+            let now = now_ms();
+            let updated = tx.execute(
+                "UPDATE timers SET state = 'running', resumed_at = ?2, heartbeat_at = ?2,
+                     updated_at = ?2
+                 WHERE id = ?1 AND state = 'paused'",
+                params![row, now],
+            )?;
+            if updated > 0 {
+                pause_others(&tx, &row, now)?;
+            }
+            tx.commit()?;
+            Ok(updated)
+        })
+        .await?;
+    let timer = changed(&app, &timers, id).await?;
+    if updated == 0 && timer.state == STATE_STOPPED {
+        return Err(AppError::InvalidInput(
+            "a stopped timer cannot be resumed; start a new one".into(),
+        ));
+    }
+    Ok(timer)
+}
+
+/// Stop a timer for good; `discard_offline` drops the time that passed while the app was closed.
+#[tauri::command]
+pub async fn timer_stop(
+    app: AppHandle,
+    timers: State<'_, Timers>,
+    id: String,
+    discard_offline: Option<bool>,
+) -> AppResult<Timer> {
+    let discard = discard_offline.unwrap_or(false);
+    let row = id.clone();
+    timers
+        .db
+        .run(move |conn| {
+            let tx = conn.transaction()?;
+            let stopped = tx.execute(
+                &format!(
+                    "UPDATE timers SET state = 'stopped', {SETTLE}, stopped_at = ?2,
+                         updated_at = ?2
+                     WHERE id = ?1 AND state != 'stopped'"
+                ),
+                params![row, now_ms()],
+            )?;
+            // Stopping twice must not subtract twice
+            if stopped > 0 && discard {
+                tx.execute(
+                    "UPDATE timers SET accumulated_ms = MAX(accumulated_ms - offline_ms, 0),
+                         offline_ms = 0
+                     WHERE id = ?1",
+                    [&row],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await?;
+    changed(&app, &timers, id).await
+}
+
+/// Timers newest first, optionally for one project; stopped ones only when asked for.
+#[tauri::command]
+pub async fn list_timers(
+    timers: State<'_, Timers>,
+    project_id: Option<String>,
+    include_stopped: Option<bool>,
+) -> AppResult<Vec<Timer>> {
+    let include_stopped = include_stopped.unwrap_or(false);
+    timers
+        .db
+        .run(move |conn| {
+            Ok(conn
+                .prepare(
+                    "SELECT * FROM timers
+                     WHERE (?1 IS NULL OR project_id = ?1) AND (?2 OR state != 'stopped')
+                     ORDER BY started_at DESC",
+                )?
+                .query_map(params![project_id, include_stopped], Timer::from_row)?
+                .collect::<rusqlite::Result<_>>()?)
+        })
+        .await
+}
+
+/// Offline gaps found at launch; each is returned once.
+#[tauri::command]
+pub async fn take_timer_gaps(timers: State<'_, Timers>) -> AppResult<Vec<OfflineGap>> {
+    Ok(std::mem::take(
+        &mut *timers.gaps.lock().unwrap_or_else(|e| e.into_inner()),
+    ))
+}
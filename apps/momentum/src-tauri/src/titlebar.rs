@@ -0,0 +1,154 @@
+//! Frameless window chrome: window-control commands and maximize-state events for the custom titlebar.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{Emitter, Manager, Runtime, WebviewWindow, WebviewWindowBuilder, Window, WindowEvent};
+
+use crate::error::AppResult;
+
+/// Emitted to a window with [`MaximizedChanged`] when it is maximized or restored.
+pub const MAXIMIZED_CHANGED_EVENT: &str = "window:maximized-changed";
+
+// Width reserved for the traffic lights when the titlebar overlays the webview
+#[cfg(target_os = "macos")]
+const TRAFFIC_LIGHT_INSET: f64 = 78.0;
+
+/// Payload for `window:maximized-changed`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaximizedChanged {
+    pub maximized: bool,
+}
+
+/// Last maximize state seen per window label, registered in app state.
+#[derive(Default)]
+pub struct TitlebarState {
+    maximized: Mutex<HashMap<String, bool>>,
+}
+
+/// How the frontend should lay out its titlebar on this platform.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TitlebarInfo {
+    /// `true` when the OS draws the window controls (macOS traffic lights).
+    pub native_controls: bool,
+    /// Side the controls sit on, so the drag region and title avoid them.
+    pub controls_side: &'static str,
+    /// Leading space in logical pixels to leave free for native controls.
+    pub leading_inset: f64,
+    pub maximized: bool,
+}
+
+/// Configure a window for custom chrome.
+///
+/// macOS keeps its native traffic lights over a transparent titlebar; elsewhere decorations
+/// are removed and the frontend draws minimize/maximize/close itself. Edge and Win+Arrow
+/// snapping still work on Windows; only the maximize-button snap-layout flyout is lost.
+pub fn frameless<'a, R: Runtime, M: Manager<R>>(
+    builder: WebviewWindowBuilder<'a, R, M>,
+) -> WebviewWindowBuilder<'a, R, M> {
+    // Transparent so a vibrancy material from `window_effects` can show through the page
+    #[cfg(target_os = "macos")]
+    let builder = builder
+        .title_bar_style(tauri::TitleBarStyle::Overlay)
+        .hidden_title(true)
+        .transparent(true);
+    // Undecorated windows lose their drop shadow and resize border on Windows without this
+    #[cfg(not(target_os = "macos"))]
+    let builder = builder.decorations(false).shadow(true);
+    builder
+}
+
+/// Broadcast maximize-state transitions; resize events fire far more often than state changes.
+pub fn on_window_event<R: Runtime>(window: &Window<R>, event: &WindowEvent) {
+    match event {
+        WindowEvent::Resized(_) => {
+            let (Some(state), Ok(maximized)) =
+                (window.try_state::<TitlebarState>(), window.is_maximized())
+            else {
+                return;
+            };
+            let previous = state
+                .maximized
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(window.label().to_string(), maximized);
+            if previous != Some(maximized) {
+                let _ = window.emit_to(
+                    window.label(),
+                    MAXIMIZED_CHANGED_EVENT,
+                    MaximizedChanged { maximized },
+                );
+            }
+        }
+        WindowEvent::Destroyed => {
+            if let Some(state) = window.try_state::<TitlebarState>() {
+                state
+                    .maximized
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .remove(window.label());
+            }
+        }
+        _ => {}
+    }
+}
+
+#[tauri::command]
+pub async fn get_titlebar_info(window: WebviewWindow) -> AppResult<TitlebarInfo> {
+    let maximized = window.is_maximized()?;
+    #[cfg(target_os = "macos")]
+    let info = TitlebarInfo {
+        native_controls: true,
+        controls_side: "left",
+        // Fullscreen hides the traffic lights entirely
+        leading_inset: if window.is_fullscreen()? {
+            0.0
+        } else {
+            TRAFFIC_LIGHT_INSET
+        },
+        maximized,
+    };
+    #[cfg(not(target_os = "macos"))]
+    let info = TitlebarInfo {
+        native_controls: false,
+        controls_side: "right",
+        leading_inset: 0.0,
+        maximized,
+    };
+    Ok(info)
+}
+
+#[tauri::command]
+pub async fn window_minimize(window: WebviewWindow) -> AppResult<()> {
+    Ok(window.minimize()?)
+}
+
+#[tauri::command]
+pub async fn window_maximize(window: WebviewWindow) -> AppResult<()> {
+    Ok(window.maximize()?)
+}
+
+#[tauri::command]
+pub async fn window_unmaximize(window: WebviewWindow) -> AppResult<()> {
+    Ok(window.unmaximize()?)
+}
+
+/// Maximize or restore, matching a double-click on a native titlebar.
+#[tauri::command]
+pub async fn window_toggle_maximize(window: WebviewWindow) -> AppResult<()> {
+    if window.is_maximized()? {
+        window.unmaximize()?;
+    } else {
+        window.maximize()?;
+    }
+    Ok(())
+}
+
+/// Request a close so close-to-tray and geometry saving still run.
+#[tauri::command]
+pub async fn window_close(window: WebviewWindow) -> AppResult<()> {
+    Ok(window.close()?)
+}
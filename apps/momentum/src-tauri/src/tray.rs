@@ -0,0 +1,102 @@
+//! System tray icon with quick actions, and close-to-tray so background work keeps running.
+
+use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Emitter, Manager, Runtime, Window, WindowEvent};
+
+use crate::error::AppResult;
+use crate::quick_capture;
+use crate::state::AppState;
+use crate::windows::{self, MAIN_WINDOW};
+
+const TRAY_ID: &str = "main";
+const NEW_ITEM_ID: &str = "tray:new-item";
+const QUICK_ADD_ID: &str = "tray:quick-add";
+const SHOW_WINDOW_ID: &str = "tray:show-window";
+const CHECK_UPDATES_ID: &str = "tray:check-for-updates";
+const QUIT_ID: &str = "tray:quit";
+
+/// Emitted after the main window is surfaced so the frontend opens its new-item flow.
+pub const NEW_ITEM_EVENT: &str = "tray:new-item";
+/// Emitted when the user asks for an update check from the tray.
+pub const CHECK_FOR_UPDATES_EVENT: &str = "tray:check-for-updates";
+
+/// Build the tray icon; it lives as long as the app.
+pub fn init(app: &AppHandle) -> AppResult<()> {
+    let menu = Menu::with_items(
+        app,
+        &[
+            &MenuItem::with_id(app, NEW_ITEM_ID, "New Item", true, None::<&str>)?,
+            &MenuItem::with_id(app, QUICK_ADD_ID, "Quick Add…", true, None::<&str>)?,
+            &MenuItem::with_id(app, SHOW_WINDOW_ID, "Show Window", true, None::<&str>)?,
+            &PredefinedMenuItem::separator(app)?,
+            &MenuItem::with_id(
+                app,
+                CHECK_UPDATES_ID,
+                "Check for Updates",
+                true,
+                None::<&str>,
+            )?,
+            &PredefinedMenuItem::separator(app)?,
+            &MenuItem::with_id(app, QUIT_ID, "Quit Momentum", true, None::<&str>)?,
+        ],
+    )?;
+
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("Momentum")
+        .menu(&menu)
+        // Left click surfaces the window; the menu stays on right click
+        .show_menu_on_left_click(false)
+        .on_menu_event(on_menu_event)
+        .on_tray_icon_event(on_tray_icon_event);
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+    Ok(())
+}
+
+fn on_menu_event(app: &AppHandle, event: MenuEvent) {
+    match event.id().as_ref() {
+        NEW_ITEM_ID => {
+            windows::show_main_window(app);
+            let _ = app.emit_to(MAIN_WINDOW, NEW_ITEM_EVENT, ());
+        }
+        // The menu closes where it was clicked, so the cursor is still by the tray
+        QUICK_ADD_ID => quick_capture::toggle(app),
+        SHOW_WINDOW_ID => windows::show_main_window(app),
+        CHECK_UPDATES_ID => {
+            let _ = app.emit(CHECK_FOR_UPDATES_EVENT, ());
+        }
+        QUIT_ID => app.exit(0),
+        _ => {}
+    }
+}
+
+fn on_tray_icon_event(tray: &TrayIcon, event: TrayIconEvent) {
+    if let TrayIconEvent::Click {
+        button: MouseButton::Left,
+        button_state: MouseButtonState::Up,
+        ..
+    } = event
+    {
+        windows::show_main_window(tray.app_handle());
+    }
+}
+
+/// Hide the main window instead of closing it when close-to-tray is enabled.
+pub fn on_window_event<R: Runtime>(window: &Window<R>, event: &WindowEvent) {
+    let WindowEvent::CloseRequested { api, .. } = event else {
+        return;
+    };
+    if window.label() != MAIN_WINDOW {
+        return;
+    }
+    let close_to_tray = window
+        .try_state::<AppState>()
+        .is_some_and(|state| state.config().close_to_tray);
+    if close_to_tray {
+        api.prevent_close();
+        let _ = window.hide();
+    }
+}
@@ -0,0 +1,431 @@
+//! Per-project undo history kept in SQLite, so it survives webview reloads and crashes.
+//!
+//! A journaled command watches the rows it is about to change and, once it has written them,
+//! records each row as it was before and after, in the command's own transaction. Undoing an
+//! entry puts every row's `before` back and redoing puts its `after` back, both as fresh
+//! edits that sync and the audit log see like any other. An entry whose rows have changed
+//! since, say by a sync, is dropped rather than overwriting that change.
+//!
+//! Recording a change clears what could have been redone, and only the latest 100 entries
+//! are kept per project.
+
+use std::collections::HashMap;
+
+use rusqlite::{params, Connection, OptionalExtension, Row, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::State;
+
+use crate::audit::{self, Change};
+use crate::db::{now_ms, row_to_json, to_sql_params, Db};
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+
+const MAX_ENTRIES: i64 = 100;
+// Bumped on every restore, so it says nothing about whether a row was changed since
+const UPDATED_AT: &str = "updated_at";
+
+/// A table the journal can restore rows of.
+///
+/// Declared parents first: rows are restored in this order and deleted in reverse, so
+/// foreign keys always point at a row that exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Table {
+    Projects,
+    Items,
+    CostStructure,
+}
+
+impl Table {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Projects => "projects",
+            Self::Items => "items",
+            Self::CostStructure => "cost_structure",
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Self::Projects | Self::Items => "id",
+            Self::CostStructure => "item_id",
+        }
+    }
+
+    // As the audit log names it
+    fn entity(self) -> &'static str {
+        match self {
+            Self::Projects => "project",
+            Self::Items => "item",
+            Self::CostStructure => "cost_structure",
+        }
+    }
+}
+
+/// A row by column name; `None` when it does not exist.
+type RowState = Option<Map<String, Value>>;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RowChange {
+    table: Table,
+    key: String,
+    before: RowState,
+    after: RowState,
+}
+
+/// A change under way, recorded as one undo step once written.
+pub(crate) struct Journal {
+    project_id: String,
+    command: &'static str,
+    label: String,
+    rows: Vec<RowChange>,
+}
+
+impl Journal {
+    pub fn new(project_id: &str, command: &'static str, label: impl Into<String>) -> Self {
+        Self {
+            project_id: project_id.to_string(),
+            command,
+            label: label.into(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Note a row as it is now, before the command changes it; watching it again is a no-op.
+    pub fn watch(&mut self, conn: &Connection, table: Table, key: &str) -> AppResult<()> {
+        if self.rows.iter().any(|r| r.table == table && r.key == key) {
+            return Ok(());
+        }
+        let before = load_row(conn, table, key)?;
+        self.rows.push(RowChange {
+            table,
+            key: key.to_string(),
+            before,
+            after: None,
+        });
+        Ok(())
+    }
+
+    /// Store the step with every watched row as it is now, unless none of them changed.
+    pub fn record(mut self, conn: &Connection) -> AppResult<()> {
+        for row in &mut self.rows {
+            row.after = load_row(conn, row.table, &row.key)?;
+        }
+        self.rows.retain(|row| !same(&row.after, &row.before));
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        conn.execute(
+            "DELETE FROM undo_journal WHERE project_id = ?1 AND undone = 1",
+            [&self.project_id],
+        )?;
+        conn.execute(
+            "INSERT INTO undo_journal (project_id, command, label, changes, row_count, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                self.project_id,
+                self.command,
+                self.label,
+                serde_json::to_string(&self.rows)?,
+                self.rows.len() as i64,
+                now_ms()
+            ],
+        )?;
+        conn.execute(
+            "DELETE FROM undo_journal WHERE project_id = ?1 AND seq NOT IN (
+                SELECT seq FROM undo_journal WHERE project_id = ?1 ORDER BY seq DESC LIMIT ?2
+             )",
+            params![self.project_id, MAX_ENTRIES],
+        )?;
+        Ok(())
+    }
+}
+
+/// One step in a project's undo history.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UndoEntry {
+    pub seq: i64,
+    pub project_id: String,
+    /// The command that made the change, such as `save_items`.
+    pub command: String,
+    /// What to show in an Undo or Redo menu item, such as "Delete items".
+    pub label: String,
+    pub row_count: u64,
+    pub created_at: i64,
+}
+
+impl UndoEntry {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            seq: row.get("seq")?,
+            project_id: row.get("project_id")?,
+            command: row.get("command")?,
+            label: row.get("label")?,
+            row_count: row.get::<_, i64>("row_count")? as u64,
+            created_at: row.get("created_at")?,
+        })
+    }
+}
+
+/// A project's undo history.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UndoStack {
+    /// Newest first; the first one is what `undo` reverts.
+    pub undo: Vec<UndoEntry>,
+    /// Next first; the first one is what `redo` reapplies.
+    pub redo: Vec<UndoEntry>,
+}
+
+fn load_row(conn: &Connection, table: Table, key: &str) -> AppResult<RowState> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT * FROM {} WHERE {} = ?1",
+        table.name(),
+        table.key()
+    ))?;
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+    Ok(stmt
+        .query_row([key], |row| row_to_json(row, &columns))
+        .optional()?)
+}
+
+// Only the columns the journal knows of count, so a column added by a later migration
+// does not make every older entry look changed
+fn same(current: &RowState, expected: &RowState) -> bool {
+    match (current, expected) {
+        (None, None) => true,
+        (Some(current), Some(expected)) => expected
+            .iter()
+            .filter(|(column, _)| *column != UPDATED_AT)
+            .all(|(column, value)| current.get(column) == Some(value)),
+        _ => false,
+    }
+}
+
+fn restore(
+    conn: &Connection,
+    table: Table,
+    key: &str,
+    state: &RowState,
+    now: i64,
+) -> AppResult<()> {
+    let Some(state) = state else {
+        conn.execute(
+            &format!("DELETE FROM {} WHERE {} = ?1", table.name(), table.key()),
+            [key],
+        )?;
+        return Ok(());
+    };
+    // Columns dropped since the entry was recorded are left out
+    let known: Vec<String> = conn
+        .prepare(&format!(
+            "SELECT name FROM pragma_table_info('{}')",
+            table.name()
+        ))?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    let mut columns = Vec::new();
+    let mut values = Vec::new();
+    for (column, value) in state {
+        if !known.contains(column) {
+            continue;
+        }
+        columns.push(format!("\"{column}\""));
+        values.push(match column.as_str() {
+            UPDATED_AT => Value::from(now),
+            _ => value.clone(),
+        });
+    }
+    let updates: Vec<String> = columns
+        .iter()
+        .filter(|column| column.trim_matches('"') != table.key())
+        .map(|column| format!("{column} = excluded.{column}"))
+        .collect();
+    let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("?{i}")).collect();
+    // An upsert, not a REPLACE: replacing deletes the row first, which cascades to its children
+    conn.execute(
+        &format!(
+            "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT({}) DO UPDATE SET {}",
+            table.name(),
+            columns.join(", "),
+            placeholders.join(", "),
+            table.key(),
+            updates.join(", ")
+        ),
+        rusqlite::params_from_iter(to_sql_params(values)),
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+    Undo,
+    Redo,
+}
+
+impl Direction {
+    fn command(self) -> &'static str {
+        match self {
+            Self::Undo => "undo",
+            Self::Redo => "redo",
+        }
+    }
+
+    // What the rows must still look like
+    fn source(self, change: &RowChange) -> &RowState {
+        match self {
+            Self::Undo => &change.after,
+            Self::Redo => &change.before,
+        }
+    }
+
+    fn target(self, change: &RowChange) -> &RowState {
+        match self {
+            Self::Undo => &change.before,
+            Self::Redo => &change.after,
+        }
+    }
+}
+
+fn step(
+    conn: &mut Connection,
+    actor: &str,
+    project_id: &str,
+    direction: Direction,
+) -> AppResult<Option<UndoEntry>> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let sql = match direction {
+        Direction::Undo => {
+            "SELECT * FROM undo_journal WHERE project_id = ?1 AND undone = 0
+             ORDER BY seq DESC LIMIT 1"
+        }
+        Direction::Redo => {
+            "SELECT * FROM undo_journal WHERE project_id = ?1 AND undone = 1
+             ORDER BY seq LIMIT 1"
+        }
+    };
+    let found = tx
+        .query_row(sql, [project_id], |row| {
+            Ok((UndoEntry::from_row(row)?, row.get::<_, String>("changes")?))
+        })
+        .optional()?;
+    let Some((entry, changes)) = found else {
+        return Ok(None);
+    };
+    let changes: Vec<RowChange> = serde_json::from_str(&changes)?;
+
+    let mut current = HashMap::new();
+    for change in &changes {
+        let row = load_row(&tx, change.table, &change.key)?;
+        if !same(&row, direction.source(change)) {
+            // Left in place it would block every step behind it
+            tx.execute("DELETE FROM undo_journal WHERE seq = ?1", [entry.seq])?;
+            tx.commit()?;
+            return Err(AppError::InvalidInput(format!(
+                "\"{}\" cannot be {} because {} {} has changed since",
+                entry.label,
+                match direction {
+                    Direction::Undo => "undone",
+                    Direction::Redo => "redone",
+                },
+                change.table.entity().replace('_', " "),
+                change.key
+            )));
+        }
+        current.insert((change.table, change.key.as_str()), row);
+    }
+
+    let mut upserts: Vec<&RowChange> = changes
+        .iter()
+        .filter(|c| direction.target(c).is_some())
+        .collect();
+    upserts.sort_by_key(|change| change.table);
+    let mut deletes: Vec<&RowChange> = changes
+        .iter()
+        .filter(|c| direction.target(c).is_none())
+        .collect();
+    deletes.sort_by_key(|change| std::cmp::Reverse(change.table));
+
+    let now = now_ms();
+    for change in upserts.into_iter().chain(deletes) {
+        restore(
+            &tx,
+            change.table,
+            &change.key,
+            direction.target(change),
+            now,
+        )?;
+        let mut audited = Change::new(direction.command(), change.table.entity(), &change.key);
+        if let Some(Some(before)) = current.get(&(change.table, change.key.as_str())) {
+            audited = audited.before(before)?;
+        }
+        if let Some(after) = load_row(&tx, change.table, &change.key)? {
+            audited = audited.after(&after)?;
+        }
+        audit::record(&tx, actor, audited)?;
+    }
+    tx.execute(
+        "UPDATE projects SET updated_at = ?2 WHERE id = ?1",
+        params![project_id, now],
+    )?;
+    tx.execute(
+        "UPDATE undo_journal SET undone = ?2 WHERE seq = ?1",
+        params![entry.seq, matches!(direction, Direction::Undo)],
+    )?;
+    tx.commit()?;
+    tracing::debug!(
+        project = %project_id,
+        seq = entry.seq,
+        rows = entry.row_count,
+        "{} applied",
+        direction.command()
+    );
+    Ok(Some(entry))
+}
+
+/// Revert the latest change to a project; `None` when there is nothing to undo.
+#[tauri::command]
+pub async fn undo(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    project_id: String,
+) -> AppResult<Option<UndoEntry>> {
+    let actor = audit::actor(&state);
+    db.run(move |conn| step(conn, &actor, &project_id, Direction::Undo))
+        .await
+}
+
+/// Reapply the change most recently undone; `None` when there is nothing to redo.
+#[tauri::command]
+pub async fn redo(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    project_id: String,
+) -> AppResult<Option<UndoEntry>> {
+    let actor = audit::actor(&state);
+    db.run(move |conn| step(conn, &actor, &project_id, Direction::Redo))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_undo_stack(db: State<'_, Db>, project_id: String) -> AppResult<UndoStack> {
+    db.run(move |conn| {
+        let list = |sql: &str| -> AppResult<Vec<UndoEntry>> {
+            let mut stmt = conn.prepare(sql)?;
+            let rows = stmt.query_map([&project_id], UndoEntry::from_row)?;
+            Ok(rows.collect::<rusqlite::Result<_>>()?)
+        };
+        Ok(UndoStack {
+            undo: list(
+                "SELECT * FROM undo_journal WHERE project_id = ?1 AND undone = 0
+                 ORDER BY seq DESC",
+            )?,
+            redo: list(
+                "SELECT * FROM undo_journal WHERE project_id = ?1 AND undone = 1 ORDER BY seq",
+            )?,
+        })
+    })
+    .await
+}
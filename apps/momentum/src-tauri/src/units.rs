@@ -0,0 +1,570 @@
+//! Quantities with units, and conversion between the units estimates are written in.
+//!
+//! Units are recognized however a takeoff or supplier sheet spells them (`SF`, `sq. ft.`,
+//! `ft²`), resolved to one entry in [`UNITS`], and converted through SI base units. Board
+//! feet are a volume, so lumber converts to and from cubic feet like anything else.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Dimension {
+    Length,
+    Area,
+    Volume,
+    Weight,
+}
+
+/// A unit and its size in the SI base unit of its dimension: meters, square meters, cubic
+/// meters, or kilograms.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Unit {
+    pub symbol: &'static str,
+    pub name: &'static str,
+    pub dimension: Dimension,
+    #[serde(skip)]
+    factor: f64,
+    /// Spellings that resolve to this unit, lowercased with spaces and periods removed.
+    pub aliases: &'static [&'static str],
+}
+
+const fn unit(
+    symbol: &'static str,
+    name: &'static str,
+    dimension: Dimension,
+    factor: f64,
+    aliases: &'static [&'static str],
+) -> Unit {
+    Unit {
+        symbol,
+        name,
+        dimension,
+        factor,
+        aliases,
+    }
+}
+
+const INCH: f64 = 0.0254;
+const FOOT: f64 = 0.3048;
+const YARD: f64 = 0.9144;
+const POUND: f64 = 0.453_592_37;
+const BOARD_FOOT: f64 = FOOT * FOOT * INCH;
+
+/// Every unit a quantity can be in.
+pub static UNITS: &[Unit] = &[
+    unit(
+        "mm",
+        "millimeter",
+        Dimension::Length,
+        0.001,
+        &["millimeter", "millimeters", "millimetre", "millimetres"],
+    ),
+    unit(
+        "cm",
+        "centimeter",
+        Dimension::Length,
+        0.01,
+        &["centimeter", "centimeters", "centimetre", "centimetres"],
+    ),
+    unit(
+        "m",
+        "meter",
+        Dimension::Length,
+        1.0,
+        &["meter", "meters", "metre", "metres", "lm"],
+    ),
+    unit(
+        "km",
+        "kilometer",
+        Dimension::Length,
+        1000.0,
+        &["kilometer", "kilometers", "kilometre", "kilometres"],
+    ),
+    unit(
+        "in",
+        "inch",
+        Dimension::Length,
+        INCH,
+        &["inch", "inches", "\""],
+    ),
+    unit(
+        "ft",
+        "foot",
+        Dimension::Length,
+        FOOT,
+        &[
+            "foot",
+            "feet",
+            "'",
+            "lf",
+            "lft",
+            "linft",
+            "linearfoot",
+            "linearfeet",
+        ],
+    ),
+    unit(
+        "yd",
+        "yard",
+        Dimension::Length,
+        YARD,
+        &["yard", "yards", "yds"],
+    ),
+    unit(
+        "mi",
+        "mile",
+        Dimension::Length,
+        1609.344,
+        &["mile", "miles"],
+    ),
+    unit(
+        "mm²",
+        "square millimeter",
+        Dimension::Area,
+        1e-6,
+        &["mm2", "sqmm"],
+    ),
+    unit(
+        "cm²",
+        "square centimeter",
+        Dimension::Area,
+        1e-4,
+        &["cm2", "sqcm"],
+    ),
+    unit(
+        "m²",
+        "square meter",
+        Dimension::Area,
+        1.0,
+        &[
+            "m2",
+            "sqm",
+            "squaremeter",
+            "squaremeters",
+            "squaremetre",
+            "squaremetres",
+        ],
+    ),
+    unit(
+        "ha",
+        "hectare",
+        Dimension::Area,
+        10_000.0,
+        &["hectare", "hectares"],
+    ),
+    unit(
+        "in²",
+        "square inch",
+        Dimension::Area,
+        INCH * INCH,
+        &["in2", "sqin", "squareinch", "squareinches"],
+    ),
+    unit(
+        "ft²",
+        "square foot",
+        Dimension::Area,
+        FOOT * FOOT,
+        &["ft2", "sf", "sqft", "squarefoot", "squarefeet"],
+    ),
+    unit(
+        "yd²",
+        "square yard",
+        Dimension::Area,
+        YARD * YARD,
+        &["yd2", "sy", "sqyd", "squareyard", "squareyards"],
+    ),
+    // Roofing and siding are sold by the square of 100 square feet
+    unit(
+        "sq",
+        "square",
+        Dimension::Area,
+        100.0 * FOOT * FOOT,
+        &["square", "squares"],
+    ),
+    unit(
+        "ac",
+        "acre",
+        Dimension::Area,
+        4_046.856_422_4,
+        &["acre", "acres"],
+    ),
+    unit(
+        "mL",
+        "milliliter",
+        Dimension::Volume,
+        1e-6,
+        &[
+            "ml",
+            "cm3",
+            "cc",
+            "milliliter",
+            "milliliters",
+            "millilitre",
+            "millilitres",
+        ],
+    ),
+    unit(
+        "L",
+        "liter",
+        Dimension::Volume,
+        0.001,
+        &["l", "liter", "liters", "litre", "litres"],
+    ),
+    unit(
+        "m³",
+        "cubic meter",
+        Dimension::Volume,
+        1.0,
+        &[
+            "m3",
+            "cum",
+            "cubicmeter",
+            "cubicmeters",
+            "cubicmetre",
+            "cubicmetres",
+        ],
+    ),
+    unit(
+        "in³",
+        "cubic inch",
+        Dimension::Volume,
+        INCH * INCH * INCH,
+        &["in3", "cuin", "cubicinch", "cubicinches"],
+    ),
+    unit(
+        "ft³",
+        "cubic foot",
+        Dimension::Volume,
+        FOOT * FOOT * FOOT,
+        &["ft3", "cf", "cuft", "cubicfoot", "cubicfeet"],
+    ),
+    unit(
+        "yd³",
+        "cubic yard",
+        Dimension::Volume,
+        YARD * YARD * YARD,
+        &["yd3", "cy", "cuyd", "cubicyard", "cubicyards"],
+    ),
+    unit(
+        "gal",
+        "US gallon",
+        Dimension::Volume,
+        0.003_785_411_784,
+        &["gallon", "gallons"],
+    ),
+    unit(
+        "bf",
+        "board foot",
+        Dimension::Volume,
+        BOARD_FOOT,
+        &["bdft", "fbm", "boardfoot", "boardfeet"],
+    ),
+    unit(
+        "mbf",
+        "thousand board feet",
+        Dimension::Volume,
+        1000.0 * BOARD_FOOT,
+        &["mfbm"],
+    ),
+    unit("g", "gram", Dimension::Weight, 0.001, &["gram", "grams"]),
+    unit(
+        "kg",
+        "kilogram",
+        Dimension::Weight,
+        1.0,
+        &["kgs", "kilogram", "kilograms"],
+    ),
+    unit(
+        "t",
+        "tonne",
+        Dimension::Weight,
+        1000.0,
+        &["tonne", "tonnes", "metricton"],
+    ),
+    unit(
+        "oz",
+        "ounce",
+        Dimension::Weight,
+        POUND / 16.0,
+        &["ounce", "ounces"],
+    ),
+    unit(
+        "lb",
+        "pound",
+        Dimension::Weight,
+        POUND,
+        &["lbs", "pound", "pounds", "#"],
+    ),
+    unit(
+        "ton",
+        "short ton",
+        Dimension::Weight,
+        2000.0 * POUND,
+        &["tons", "tn", "shortton"],
+    ),
+];
+
+fn normalize(text: &str) -> String {
+    text.trim()
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '.')
+        .map(|c| match c {
+            '²' => '2',
+            '³' => '3',
+            '’' | '′' => '\'',
+            '”' | '″' => '"',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+/// The unit `text` spells, if it is one this module knows.
+pub fn find(text: &str) -> Option<&'static Unit> {
+    let key = normalize(text);
+    if key.is_empty() {
+        return None;
+    }
+    UNITS.iter().find(|unit| {
+        normalize(unit.symbol) == key || unit.aliases.iter().any(|alias| *alias == key)
+    })
+}
+
+fn resolve(text: &str) -> AppResult<&'static Unit> {
+    find(text).ok_or_else(|| AppError::InvalidInput(format!("{text} is not a known unit")))
+}
+
+/// `value` in `from` expressed in `to`.
+pub fn convert(value: f64, from: &Unit, to: &Unit) -> AppResult<f64> {
+    if from.dimension != to.dimension {
+        return Err(AppError::InvalidInput(format!(
+            "{} cannot be converted to {}",
+            from.symbol, to.symbol
+        )));
+    }
+    Ok(value * from.factor / to.factor)
+}
+
+/// A value and the unit it is in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Quantity {
+    pub value: f64,
+    pub unit: String,
+}
+
+impl Quantity {
+    /// Read text such as `12.5 sf`, `3 cu yd`, or `5' 6"`; parts in several units of one
+    /// dimension are added up in the first one's unit.
+    pub fn parse(text: &str) -> AppResult<Self> {
+        let invalid = || AppError::InvalidInput(format!("{text} is not a quantity"));
+        let mut rest = text.trim();
+        let mut total: Option<(f64, &'static Unit)> = None;
+        while !rest.is_empty() {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | ',' | '-' | '+')))
+                .unwrap_or(rest.len());
+            let value: f64 = rest[..end]
+                .replace(',', "")
+                .parse()
+                .map_err(|_| invalid())?;
+            rest = rest[end..].trim_start();
+            let unit_end = rest
+                .find(|c: char| c.is_ascii_digit() || c == '-' || c == '+')
+                .unwrap_or(rest.len());
+            let name = rest[..unit_end].trim();
+            if name.is_empty() {
+                return Err(AppError::InvalidInput(format!("{text} has no unit")));
+            }
+            let unit = resolve(name)?;
+            rest = rest[unit_end..].trim_start();
+            total = Some(match total {
+                None => (value, unit),
+                Some((sum, first)) => (sum + convert(value, unit, first)?, first),
+            });
+        }
+        let (value, unit) = total.ok_or_else(invalid)?;
+        Ok(Self {
+            value,
+            unit: unit.symbol.into(),
+        })
+    }
+
+    pub fn unit(&self) -> AppResult<&'static Unit> {
+        resolve(&self.unit)
+    }
+
+    /// This quantity in `unit`, written with that unit's symbol.
+    pub fn to(&self, unit: &str) -> AppResult<Self> {
+        let target = resolve(unit)?;
+        Ok(Self {
+            value: convert(self.value, self.unit()?, target)?,
+            unit: target.symbol.into(),
+        })
+    }
+}
+
+/// Parse text such as `12 ft 6 in` into a quantity.
+#[tauri::command]
+pub async fn parse_quantity(text: String) -> AppResult<Quantity> {
+    Quantity::parse(&text)
+}
+
+#[tauri::command]
+pub async fn convert_quantity(quantity: Quantity, to: String) -> AppResult<Quantity> {
+    quantity.to(&to)
+}
+
+/// Known units, optionally of one dimension.
+#[tauri::command]
+pub async fn list_units(dimension: Option<Dimension>) -> AppResult<Vec<&'static Unit>> {
+    Ok(UNITS
+        .iter()
+        .filter(|unit| dimension.is_none_or(|dimension| unit.dimension == dimension))
+        .collect())
+}
+
+/// Board feet in `pieces` boards of the given nominal size.
+#[tauri::command]
+pub async fn board_feet(
+    thickness: Quantity,
+    width: Quantity,
+    length: Quantity,
+    pieces: Option<f64>,
+) -> AppResult<Quantity> {
+    let meters = |quantity: &Quantity| quantity.to("m").map(|q| q.value);
+    let volume = meters(&thickness)? * meters(&width)? * meters(&length)? * pieces.unwrap_or(1.0);
+    Quantity {
+        value: volume,
+        unit: "m³".into(),
+    }
+    .to("bf")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: f64, expected: f64) -> bool {
+        (actual - expected).abs() <= 1e-9 * expected.abs().max(1.0)
+    }
+
+    fn converted(value: f64, from: &str, to: &str) -> f64 {
+        convert(value, resolve(from).unwrap(), resolve(to).unwrap()).unwrap()
+    }
+
+    fn parsed(text: &str) -> (f64, String) {
+        let quantity = Quantity::parse(text).unwrap();
+        (quantity.value, quantity.unit)
+    }
+
+    fn board_feet_of(thickness: &str, width: &str, length: &str, pieces: Option<f64>) -> f64 {
+        let [thickness, width, length] =
+            [thickness, width, length].map(|text| Quantity::parse(text).unwrap());
+        let quantity =
+            tauri::async_runtime::block_on(board_feet(thickness, width, length, pieces)).unwrap();
+        assert_eq!(quantity.unit, "bf");
+        quantity.value
+    }
+
+    #[test]
+    fn every_spelling_names_exactly_one_unit() {
+        for unit in UNITS {
+            assert!(
+                std::ptr::eq(find(unit.symbol).unwrap(), unit),
+                "{}",
+                unit.symbol
+            );
+            for alias in unit.aliases {
+                assert_eq!(normalize(alias), *alias, "{alias} can never match");
+                assert!(std::ptr::eq(find(alias).unwrap(), unit), "{alias}");
+            }
+        }
+        assert!(find("").is_none());
+        assert!(find("furlong").is_none());
+    }
+
+    #[test]
+    fn conversions_match_the_trade_definitions() {
+        let cases = [
+            (1.0, "ft", "in", 12.0),
+            (1.0, "mi", "ft", 5280.0),
+            (1.0, "in", "mm", 25.4),
+            (1.0, "yd³", "ft³", 27.0),
+            (1.0, "sq", "sf", 100.0),
+            (1.0, "ac", "sf", 43_560.0),
+            (1.0, "ha", "m²", 10_000.0),
+            (1.0, "gal", "in³", 231.0),
+            (1.0, "L", "mL", 1000.0),
+            (1.0, "ton", "lb", 2000.0),
+            (1.0, "lb", "oz", 16.0),
+            (1.0, "t", "kg", 1000.0),
+            (1.0, "ft³", "bf", 12.0),
+            (1.0, "mbf", "bf", 1000.0),
+        ];
+        for (value, from, to, expected) in cases {
+            let actual = converted(value, from, to);
+            assert!(close(actual, expected), "{value} {from} is {actual} {to}");
+            assert!(
+                close(converted(actual, to, from), value),
+                "{to} back to {from}"
+            );
+        }
+    }
+
+    #[test]
+    fn units_of_different_dimensions_do_not_convert() {
+        assert!(convert(1.0, resolve("ft").unwrap(), resolve("sf").unwrap()).is_err());
+        assert!(resolve("bf")
+            .and_then(|bf| convert(1.0, bf, resolve("lb")?))
+            .is_err());
+    }
+
+    #[test]
+    fn quantities_parse_as_estimates_write_them() {
+        let (value, unit) = parsed("5' 6\"");
+        assert!(close(value, 5.5) && unit == "ft", "{value} {unit}");
+        let (value, unit) = parsed("12 ft 6 in");
+        assert!(close(value, 12.5) && unit == "ft", "{value} {unit}");
+        assert_eq!(parsed("1,250 sq. ft."), (1250.0, "ft²".to_string()));
+        assert_eq!(parsed("3 CU YD"), (3.0, "yd³".to_string()));
+        assert_eq!(parsed("-2.5kg"), (-2.5, "kg".to_string()));
+        for text in ["", "12", "ft", "5 furlongs", "5 ft 2 lb", "1.2.3 m"] {
+            assert!(Quantity::parse(text).is_err(), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn quantities_convert_to_the_target_symbol() {
+        let quantity = Quantity::parse("3 cy").unwrap().to("cubic feet").unwrap();
+        assert_eq!(quantity.unit, "ft³");
+        assert!(close(quantity.value, 81.0));
+        assert!(Quantity::parse("3 cy").unwrap().to("lf").is_err());
+    }
+
+    #[test]
+    fn board_feet_follow_thickness_times_width_times_length() {
+        // A foot square, an inch thick, is one board foot
+        assert!(close(board_feet_of("1 in", "12 in", "1 ft", None), 1.0));
+        // 2x4s at 8 feet: 2 × 4 × 96 / 144 each
+        assert!(close(
+            board_feet_of("2 in", "4 in", "8 ft", Some(10.0)),
+            160.0 / 3.0
+        ));
+        assert!(close(
+            board_feet_of("25.4 mm", "304.8 mm", "3.048 m", None),
+            10.0
+        ));
+        let weight = Quantity::parse("2 lb").unwrap();
+        let length = Quantity::parse("8 ft").unwrap();
+        let result = tauri::async_runtime::block_on(board_feet(
+            weight.clone(),
+            length.clone(),
+            length,
+            None,
+        ));
+        assert!(result.is_err());
+    }
+}
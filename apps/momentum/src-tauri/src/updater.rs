@@ -0,0 +1,406 @@
+//! Release channels, staged rollout, and manual update checks layered over the updater plugin.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_updater::{Update, UpdaterBuilder, UpdaterExt};
+use url::Url;
+
+use crate::env;
+use crate::error::{AppError, AppResult};
+use crate::portable;
+use crate::progress;
+use crate::proxy::ProxyState;
+
+const SETTINGS_FILE_NAME: &str = "updater.json";
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+// Manifest key holding the share of installs (0-100) offered a release
+const ROLLOUT_KEY: &str = "rolloutPercentage";
+
+/// Emitted with [`UpdateProgress`] while an update downloads.
+pub const UPDATE_PROGRESS_EVENT: &str = "update:progress";
+/// Emitted with [`UpdateInfo`] once a deferred update is downloaded and waiting for quit.
+pub const UPDATE_READY_EVENT: &str = "update:ready";
+
+/// Release stream the updater follows; ordered from most to least stable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UpdateChannel {
+    #[default]
+    Stable,
+    Beta,
+    Nightly,
+}
+
+impl UpdateChannel {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Stable => "stable",
+            Self::Beta => "beta",
+            Self::Nightly => "nightly",
+        }
+    }
+
+    /// Manifest URL for this channel; stable keeps the feed path older builds already poll.
+    pub fn endpoint(self) -> AppResult<Url> {
+        let path = match self {
+            Self::Stable => "latest.json".to_string(),
+            other => format!("{}/latest.json", other.as_str()),
+        };
+        env::current()
+            .update_feed_url()?
+            .join(&path)
+            .map_err(|e| AppError::Internal(format!("invalid update feed URL: {e}")))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct UpdaterSettings {
+    channel: UpdateChannel,
+    /// Set once the user confirms moving to a channel whose latest release may be older.
+    allow_downgrade: bool,
+    /// Stable 0-99 bucket deciding whether this install is inside a staged rollout.
+    rollout_bucket: Option<u8>,
+    /// Version that was running before the last update, for `rollback_update`.
+    previous_version: Option<String>,
+    /// Release rolled back from; never offered again by `check_for_updates`.
+    skipped_version: Option<String>,
+}
+
+/// Updater settings and in-flight updates registered in app state.
+pub struct UpdaterState {
+    path: PathBuf,
+    settings: Mutex<UpdaterSettings>,
+    /// Result of the last successful check, consumed by `install_update`.
+    available: Mutex<Option<Update>>,
+    /// Downloaded package waiting to be installed on quit.
+    staged: Mutex<Option<(Update, Vec<u8>)>>,
+}
+
+impl UpdaterState {
+    /// Load settings from `data_dir`; unreadable files fall back to the stable channel.
+    pub fn load(data_dir: &Path) -> Self {
+        let path = data_dir.join(SETTINGS_FILE_NAME);
+        let mut settings: UpdaterSettings = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        let needs_bucket = settings.rollout_bucket.is_none();
+        if needs_bucket {
+            settings.rollout_bucket = Some(rand::random_range(0..100));
+        }
+        let state = Self {
+            path,
+            settings: Mutex::new(settings.clone()),
+            available: Mutex::new(None),
+            staged: Mutex::new(None),
+        };
+        // Persist immediately so restarting can't re-roll into an active rollout
+        if needs_bucket {
+            if let Err(err) = state.save(&settings) {
+                tracing::warn!(%err, "failed to save updater settings");
+            }
+        }
+        state
+    }
+
+    fn save(&self, settings: &UpdaterSettings) -> AppResult<()> {
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(settings)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn settings(&self) -> UpdaterSettings {
+        self.settings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+/// Channel details for the settings screen.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateChannelInfo {
+    pub channel: UpdateChannel,
+    pub endpoint: String,
+    pub current_version: String,
+    pub allow_downgrade: bool,
+    /// Version `rollback_update` would reinstall, if any.
+    pub rollback_version: Option<String>,
+}
+
+fn channel_info(app: &AppHandle, settings: &UpdaterSettings) -> AppResult<UpdateChannelInfo> {
+    Ok(UpdateChannelInfo {
+        channel: settings.channel,
+        endpoint: settings.channel.endpoint()?.to_string(),
+        current_version: app.package_info().version.to_string(),
+        allow_downgrade: settings.allow_downgrade,
+        rollback_version: settings.previous_version.clone(),
+    })
+}
+
+#[tauri::command]
+pub async fn get_update_channel(
+    app: AppHandle,
+    state: State<'_, UpdaterState>,
+) -> AppResult<UpdateChannelInfo> {
+    channel_info(&app, &state.settings())
+}
+
+/// Switch channels.
+///
+/// Moving from a prerelease build to a more stable channel can mean installing an older
+/// version, so that requires `confirm_downgrade` and fails with a confirmation error otherwise.
+#[tauri::command]
+pub async fn set_update_channel(
+    app: AppHandle,
+    state: State<'_, UpdaterState>,
+    channel: UpdateChannel,
+    confirm_downgrade: Option<bool>,
+) -> AppResult<UpdateChannelInfo> {
+    let mut settings = state.settings.lock().unwrap_or_else(|e| e.into_inner());
+    let current_version = &app.package_info().version;
+    let may_downgrade = channel < settings.channel && !current_version.pre.is_empty();
+
+    if may_downgrade && !confirm_downgrade.unwrap_or(false) {
+        return Err(AppError::DowngradeConfirmationRequired {
+            current_version: current_version.to_string(),
+            channel: channel.as_str(),
+        });
+    }
+
+    let mut next = settings.clone();
+    next.channel = channel;
+    // Moving towards prereleases never downgrades, so any earlier confirmation is dropped
+    next.allow_downgrade =
+        may_downgrade || (settings.allow_downgrade && channel <= settings.channel);
+    state.save(&next)?;
+    *settings = next;
+
+    tracing::info!(channel = channel.as_str(), "update channel changed");
+    channel_info(&app, &settings)
+}
+
+/// An update offered by the current channel.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateInfo {
+    pub version: String,
+    pub current_version: String,
+    pub notes: Option<String>,
+    /// Unix milliseconds from the manifest's `pub_date`.
+    pub date: Option<i64>,
+}
+
+impl From<&Update> for UpdateInfo {
+    fn from(update: &Update) -> Self {
+        Self {
+            version: update.version.clone(),
+            current_version: update.current_version.clone(),
+            notes: update.body.clone(),
+            date: update.date.map(|d| d.unix_timestamp() * 1000),
+        }
+    }
+}
+
+/// Payload for `update:progress`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateProgress {
+    pub downloaded: u64,
+    /// `None` when the server omits `Content-Length`.
+    pub total: Option<u64>,
+}
+
+// Same proxy as the rest of the app's outbound traffic, not the plugin's own env lookup
+fn builder(app: &AppHandle) -> UpdaterBuilder {
+    let builder = app.updater_builder();
+    match app.state::<ProxyState>().current().updater_proxy() {
+        Some(url) => builder.proxy(url.clone()),
+        None => builder.no_proxy(),
+    }
+}
+
+async fn check(app: &AppHandle, settings: &UpdaterSettings) -> AppResult<Option<Update>> {
+    let allow_downgrade = settings.allow_downgrade;
+    let skipped = settings.skipped_version.clone();
+    let updater = builder(app)
+        .endpoints(vec![settings.channel.endpoint()?])?
+        .version_comparator(move |current, release| {
+            if skipped.as_deref() == Some(release.version.to_string().as_str()) {
+                return false;
+            }
+            if allow_downgrade {
+                release.version != current
+            } else {
+                release.version > current
+            }
+        })
+        .build()?;
+    let Some(update) = updater.check().await? else {
+        return Ok(None);
+    };
+
+    // Manifests without a percentage are fully rolled out
+    let rollout = update.raw_json.get(ROLLOUT_KEY).and_then(|v| v.as_f64());
+    let bucket = settings.rollout_bucket.unwrap_or(0);
+    if rollout.is_some_and(|pct| f64::from(bucket) >= pct) {
+        tracing::debug!(version = %update.version, bucket, ?rollout, "update outside rollout");
+        return Ok(None);
+    }
+    Ok(Some(update))
+}
+
+/// Manifest for a specific past release, published next to each channel feed.
+fn release_endpoint(version: &str) -> AppResult<Url> {
+    env::current()
+        .update_feed_url()?
+        .join(&format!("releases/{version}.json"))
+        .map_err(|e| AppError::Internal(format!("invalid release manifest URL: {e}")))
+}
+
+async fn download(app: &AppHandle, update: &Update) -> AppResult<Vec<u8>> {
+    let mut downloaded = 0u64;
+    let mut last_emit = Instant::now() - PROGRESS_INTERVAL;
+    let taskbar = progress::track(app, "update".into());
+    let bytes = update
+        .download(
+            |chunk, total| {
+                downloaded += chunk as u64;
+                // Chunks arrive every few KB; throttle so the IPC channel isn't flooded
+                let finished = total.is_some_and(|t| downloaded >= t);
+                if finished || last_emit.elapsed() >= PROGRESS_INTERVAL {
+                    last_emit = Instant::now();
+                    taskbar.set(downloaded, total);
+                    let _ = app.emit(UPDATE_PROGRESS_EVENT, UpdateProgress { downloaded, total });
+                }
+            },
+            || {},
+        )
+        .await?;
+    Ok(bytes)
+}
+
+/// Query the current channel's feed; the returned update is held for `install_update`.
+#[tauri::command]
+pub async fn check_for_updates(
+    app: AppHandle,
+    state: State<'_, UpdaterState>,
+) -> AppResult<Option<UpdateInfo>> {
+    let update = check(&app, &state.settings()).await?;
+    let info = update.as_ref().map(UpdateInfo::from);
+    match &info {
+        Some(info) => tracing::info!(version = %info.version, "update available"),
+        None => tracing::debug!("no update available"),
+    }
+    *state.available.lock().unwrap_or_else(|e| e.into_inner()) = update;
+    Ok(info)
+}
+
+/// Download the update found by `check_for_updates`, streaming `update:progress`.
+///
+/// With `defer_until_quit` the package is staged and installed when the app exits;
+/// otherwise it installs immediately and relaunches.
+#[tauri::command]
+pub async fn install_update(
+    app: AppHandle,
+    state: State<'_, UpdaterState>,
+    defer_until_quit: Option<bool>,
+) -> AppResult<()> {
+    if portable::is_portable() {
+        return Err(AppError::InvalidInput(
+            "a portable copy is updated by replacing its folder".into(),
+        ));
+    }
+    let update = state
+        .available
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+        .ok_or_else(|| AppError::InvalidInput("no update available; check first".into()))?;
+    let bytes = download(&app, &update).await?;
+
+    if defer_until_quit.unwrap_or(false) {
+        let info = UpdateInfo::from(&update);
+        // The Windows installer would otherwise relaunch an app the user just quit
+        let update = update.restart_after_install(false);
+        *state.staged.lock().unwrap_or_else(|e| e.into_inner()) = Some((update, bytes));
+        tracing::info!(version = %info.version, "update staged for quit");
+        let _ = app.emit(UPDATE_READY_EVENT, info);
+        return Ok(());
+    }
+
+    // Recorded first because the Windows installer exits the process from `install`
+    record_install(&state, &update.current_version)?;
+    update.install(bytes)?;
+    tracing::info!(version = %update.version, "update installed, restarting");
+    app.restart()
+}
+
+fn record_install(state: &UpdaterState, replaced_version: &str) -> AppResult<()> {
+    let mut settings = state.settings.lock().unwrap_or_else(|e| e.into_inner());
+    settings.allow_downgrade = false;
+    settings.previous_version = Some(replaced_version.to_string());
+    state.save(&settings)
+}
+
+/// Reinstall the release that was running before the last update, then relaunch.
+///
+/// The broken version is skipped by future checks until a newer release ships.
+#[tauri::command]
+pub async fn rollback_update(app: AppHandle, state: State<'_, UpdaterState>) -> AppResult<()> {
+    let settings = state.settings();
+    let target = settings
+        .previous_version
+        .clone()
+        .ok_or_else(|| AppError::InvalidInput("no previous version to roll back to".into()))?;
+    let comparator_target = target.clone();
+    let update = builder(&app)
+        .endpoints(vec![release_endpoint(&target)?])?
+        .version_comparator(move |_, release| release.version.to_string() == comparator_target)
+        .build()?
+        .check()
+        .await?
+        .ok_or_else(|| AppError::not_found("release", target.clone()))?;
+    let bytes = download(&app, &update).await?;
+
+    {
+        let mut settings = state.settings.lock().unwrap_or_else(|e| e.into_inner());
+        settings.skipped_version = Some(update.current_version.clone());
+        settings.previous_version = None;
+        settings.allow_downgrade = false;
+        state.save(&settings)?;
+    }
+    tracing::warn!(from = %update.current_version, to = %target, "rolling back update");
+    update.install(bytes)?;
+    app.restart()
+}
+
+/// Install a package staged with `defer_until_quit`; called on app exit.
+pub fn install_staged(app: &AppHandle) {
+    let Some(state) = app.try_state::<UpdaterState>() else {
+        return;
+    };
+    let Some((update, bytes)) = state
+        .staged
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+    else {
+        return;
+    };
+    if let Err(err) = record_install(&state, &update.current_version) {
+        tracing::warn!(%err, "failed to record replaced version");
+    }
+    match update.install(bytes) {
+        Ok(()) => tracing::info!(version = %update.version, "staged update installed"),
+        Err(err) => tracing::error!(%err, "failed to install staged update"),
+    }
+}
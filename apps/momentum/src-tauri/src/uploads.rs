@@ -0,0 +1,578 @@
+//! Resumable chunked uploads for large files such as plan PDFs.
+//!
+//! Files are read in Rust so the webview never holds them in memory. Each chunk is sent with its
+//! SHA-256 and progress is recorded only after the server acknowledges it, so a paused,
+//! crashed, or restarted upload picks up from the last confirmed chunk.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+use rusqlite::{params, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Notify;
+
+use crate::auth;
+use crate::config;
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::http_client::{HttpClient, RetryPolicy};
+use crate::wake_lock;
+
+const CHUNK_SIZE: i64 = 5 * 1024 * 1024;
+const CHUNK_SHA256_HEADER: &str = "X-Chunk-SHA256";
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+pub(crate) const STATUS_UPLOADING: &str = "uploading";
+const STATUS_PAUSED: &str = "paused";
+pub(crate) const STATUS_COMPLETED: &str = "completed";
+pub(crate) const STATUS_FAILED: &str = "failed";
+
+/// Emitted with an [`UploadProgress`] after each acknowledged chunk.
+pub const PROGRESS_EVENT: &str = "upload:progress";
+/// Emitted with the finished [`Upload`], including the server's `result`.
+pub const COMPLETED_EVENT: &str = "upload:completed";
+/// Emitted with the [`Upload`] when it stops on an error; it can be resumed.
+pub const FAILED_EVENT: &str = "upload:failed";
+
+/// An upload as shown to the frontend.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Upload {
+    pub id: String,
+    pub path: PathBuf,
+    pub file_name: String,
+    pub size: i64,
+    pub content_type: String,
+    /// Hex SHA-256 of the whole file, verified by the server on completion.
+    pub sha256: String,
+    pub uploaded: i64,
+    /// `uploading`, `paused`, `completed`, or `failed`.
+    pub status: String,
+    pub last_error: Option<String>,
+    /// The server's response to completion, e.g. the stored file's id.
+    pub result: Option<Value>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Upload {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let path: String = row.get("path")?;
+        let result: Option<String> = row.get("result")?;
+        Ok(Self {
+            id: row.get("id")?,
+            path: PathBuf::from(path),
+            file_name: row.get("file_name")?,
+            size: row.get("size")?,
+            content_type: row.get("content_type")?,
+            sha256: row.get("sha256")?,
+            uploaded: row.get("uploaded")?,
+            status: row.get("status")?,
+            last_error: row.get("last_error")?,
+            result: result.and_then(|r| serde_json::from_str(&r).ok()),
+            created_at: row.get("created_at")?,
+            updated_at: row.get("updated_at")?,
+        })
+    }
+}
+
+/// Payload for `upload:progress`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadProgress {
+    pub id: String,
+    pub uploaded: i64,
+    pub total: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteUpload {
+    id: String,
+    /// Contiguous bytes the server holds; may be ahead of ours if an ack was lost.
+    #[serde(default)]
+    received: i64,
+}
+
+// Fields the upload loop needs beyond what the frontend sees
+struct Job {
+    upload: Upload,
+    modified_at: i64,
+    remote_id: Option<String>,
+    chunk_size: i64,
+}
+
+/// Upload registry held in app state.
+pub struct Uploads {
+    db: Db,
+    // Notified to stop a running upload at the next await point
+    active: Mutex<HashMap<String, Arc<Notify>>>,
+}
+
+impl Uploads {
+    pub fn new(db: Db) -> Self {
+        Self {
+            db,
+            active: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) async fn get(&self, id: &str) -> AppResult<Upload> {
+        let lookup = id.to_string();
+        self.db
+            .run(move |conn| {
+                Ok(conn
+                    .query_row(
+                        "SELECT * FROM uploads WHERE id = ?1",
+                        [lookup],
+                        Upload::from_row,
+                    )
+                    .optional()?)
+            })
+            .await?
+            .ok_or_else(|| AppError::not_found("upload", id))
+    }
+
+    async fn set_status(
+        &self,
+        id: &str,
+        status: &'static str,
+        error: Option<String>,
+    ) -> AppResult<()> {
+        let id = id.to_string();
+        self.db
+            .run(move |conn| {
+                conn.execute(
+                    "UPDATE uploads SET status = ?2, last_error = ?3, updated_at = ?4 WHERE id = ?1",
+                    params![id, status, error, now_ms()],
+                )?;
+                Ok(())
+            })
+            .await
+    }
+
+    fn stop(&self, id: &str) {
+        if let Some(stop) = self
+            .active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(id)
+        {
+            stop.notify_one();
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn modified_ms(path: &Path) -> AppResult<(i64, i64)> {
+    let metadata = std::fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default();
+    Ok((metadata.len() as i64, modified))
+}
+
+fn hash_file(path: &Path) -> AppResult<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
+}
+
+fn read_chunk(path: &Path, offset: i64, len: i64) -> AppResult<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset as u64))?;
+    let mut chunk = Vec::with_capacity(len as usize);
+    file.take(len as u64).read_to_end(&mut chunk)?;
+    Ok(chunk)
+}
+
+async fn bearer(app: &AppHandle) -> AppResult<String> {
+    auth::access_token(app)
+        .await?
+        .ok_or_else(|| AppError::Auth("sign in to upload files".into()))
+}
+
+async fn run(app: &AppHandle, id: &str) -> AppResult<()> {
+    let _awake = wake_lock::hold(app, "Uploading a file");
+    let uploads = app.state::<Uploads>();
+    let http = app.state::<HttpClient>();
+    let lookup = id.to_string();
+    let job = uploads
+        .db
+        .run(move |conn| {
+            Ok(
+                conn.query_row("SELECT * FROM uploads WHERE id = ?1", [lookup], |row| {
+                    Ok(Job {
+                        upload: Upload::from_row(row)?,
+                        modified_at: row.get("modified_at")?,
+                        remote_id: row.get("remote_id")?,
+                        chunk_size: row.get("chunk_size")?,
+                    })
+                })?,
+            )
+        })
+        .await?;
+    let Job {
+        upload,
+        modified_at,
+        remote_id,
+        chunk_size,
+    } = job;
+
+    // Resuming against an edited file would splice two versions together
+    let path = upload.path.clone();
+    if tauri::async_runtime::spawn_blocking(move || modified_ms(&path)).await??
+        != (upload.size, modified_at)
+    {
+        return Err(AppError::InvalidInput(format!(
+            "{} changed since the upload started",
+            upload.file_name
+        )));
+    }
+
+    let token = bearer(app).await?;
+    let (remote_id, mut uploaded) = match remote_id {
+        Some(remote_id) => {
+            let url = config::api_url(app, &format!("/v1/uploads/{remote_id}"))?;
+            let remote: RemoteUpload = send(&http, http.client().get(url), &token).await?;
+            (remote_id, remote.received)
+        }
+        None => {
+            let url = config::api_url(app, "/v1/uploads")?;
+            let body = json!({
+                "fileName": upload.file_name,
+                "size": upload.size,
+                "contentType": upload.content_type,
+                "sha256": upload.sha256,
+                "chunkSize": chunk_size,
+            });
+            // Keyed by our id so a retried create never opens a second upload
+            let request = http
+                .client()
+                .post(url)
+                .header("Idempotency-Key", &upload.id)
+                .header("Content-Type", "application/json")
+                .body(body.to_string());
+            let remote: RemoteUpload = send(&http, request, &token).await?;
+            let (id, remote_id) = (upload.id.clone(), remote.id.clone());
+            uploads
+                .db
+                .run(move |conn| {
+                    conn.execute(
+                        "UPDATE uploads SET remote_id = ?2 WHERE id = ?1",
+                        params![id, remote_id],
+                    )?;
+                    Ok(())
+                })
+                .await?;
+            (remote.id, remote.received)
+        }
+    };
+    // Chunks are numbered, so only whole chunks count as received
+    uploaded = (uploaded.min(upload.size) / chunk_size) * chunk_size;
+
+    while uploaded < upload.size {
+        let len = chunk_size.min(upload.size - uploaded);
+        let path = upload.path.clone();
+        let chunk = tauri::async_runtime::spawn_blocking(move || read_chunk(&path, uploaded, len))
+            .await??;
+        let index = uploaded / chunk_size;
+        let url = config::api_url(app, &format!("/v1/uploads/{remote_id}/chunks/{index}"))?;
+        let request = http
+            .client()
+            .put(url)
+            .header(CHUNK_SHA256_HEADER, hex(&Sha256::digest(&chunk)))
+            .header(
+                "Content-Range",
+                format!("bytes {}-{}/{}", uploaded, uploaded + len - 1, upload.size),
+            )
+            .header("Content-Type", DEFAULT_CONTENT_TYPE)
+            .body(chunk);
+        http.send(request.bearer_auth(&token), RetryPolicy::default())
+            .await?
+            .error_for_status()?;
+
+        uploaded += len;
+        let id = upload.id.clone();
+        uploads
+            .db
+            .run(move |conn| {
+                conn.execute(
+                    "UPDATE uploads SET uploaded = ?2, updated_at = ?3 WHERE id = ?1",
+                    params![id, uploaded, now_ms()],
+                )?;
+                Ok(())
+            })
+            .await?;
+        let _ = app.emit(
+            PROGRESS_EVENT,
+            UploadProgress {
+                id: upload.id.clone(),
+                uploaded,
+                total: upload.size,
+            },
+        );
+    }
+
+    let url = config::api_url(app, &format!("/v1/uploads/{remote_id}/complete"))?;
+    let request = http
+        .client()
+        .post(url)
+        .header("Idempotency-Key", format!("{}:complete", upload.id))
+        .header("Content-Type", "application/json")
+        .body(json!({ "sha256": upload.sha256 }).to_string());
+    let result: Value = send(&http, request, &token).await?;
+    let row = upload.id.clone();
+    uploads
+        .db
+        .run(move |conn| {
+            conn.execute(
+                "UPDATE uploads SET status = ?2, result = ?3, last_error = NULL, updated_at = ?4
+                 WHERE id = ?1",
+                params![row, STATUS_COMPLETED, result.to_string(), now_ms()],
+            )?;
+            Ok(())
+        })
+        .await?;
+    tracing::info!(id, size = upload.size, "upload completed");
+    let _ = app.emit(COMPLETED_EVENT, uploads.get(id).await?);
+    Ok(())
+}
+
+async fn send<T: serde::de::DeserializeOwned>(
+    http: &HttpClient,
+    request: tauri_plugin_http::reqwest::RequestBuilder,
+    token: &str,
+) -> AppResult<T> {
+    let response = http
+        .send(request.bearer_auth(token), RetryPolicy::default())
+        .await?
+        .error_for_status()?;
+    Ok(serde_json::from_slice(&response.bytes().await?)?)
+}
+
+// Pausing or cancelling drops the task's future mid-chunk; the chunk is simply resent later
+fn spawn(app: &AppHandle, id: String) {
+    let stop = Arc::new(Notify::new());
+    {
+        let uploads = app.state::<Uploads>();
+        let mut active = uploads.active.lock().unwrap_or_else(|e| e.into_inner());
+        if active.contains_key(&id) {
+            return;
+        }
+        active.insert(id.clone(), stop.clone());
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let result = tokio::select! {
+            result = run(&app, &id) => Some(result),
+            _ = stop.notified() => None,
+        };
+        let uploads = app.state::<Uploads>();
+        uploads
+            .active
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
+        if let Some(Err(err)) = result {
+            tracing::warn!(%id, %err, "upload failed");
+            if let Err(err) = uploads
+                .set_status(&id, STATUS_FAILED, Some(err.to_string()))
+                .await
+            {
+                tracing::warn!(%id, %err, "failed to record upload failure");
+            }
+            if let Ok(upload) = uploads.get(&id).await {
+                let _ = app.emit(FAILED_EVENT, upload);
+            }
+        }
+    });
+}
+
+/// Restart uploads that were running when the app last quit.
+pub fn resume_interrupted(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        // An encrypted database stays closed until the user unlocks it
+        app.state::<Db>().wait_unlocked().await;
+        let ids = app
+            .state::<Uploads>()
+            .db
+            .run(|conn| {
+                Ok(conn
+                    .prepare("SELECT id FROM uploads WHERE status = ?1")?
+                    .query_map([STATUS_UPLOADING], |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<String>>>()?)
+            })
+            .await;
+        match ids {
+            Ok(ids) => ids.into_iter().for_each(|id| spawn(&app, id)),
+            Err(err) => tracing::warn!(%err, "failed to load interrupted uploads"),
+        }
+    });
+}
+
+/// Start uploading a file in the background.
+pub(crate) async fn start(
+    app: &AppHandle,
+    path: PathBuf,
+    content_type: Option<String>,
+) -> AppResult<Upload> {
+    let uploads = app.state::<Uploads>();
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .ok_or_else(|| AppError::InvalidInput(format!("{} is not a file", path.display())))?;
+    let hashed = path.clone();
+    let ((size, modified_at), sha256) = tauri::async_runtime::spawn_blocking(move || {
+        Ok::<_, AppError>((modified_ms(&hashed)?, hash_file(&hashed)?))
+    })
+    .await??;
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let row = (id.clone(), path.to_string_lossy().into_owned());
+    let content_type = content_type.unwrap_or_else(|| DEFAULT_CONTENT_TYPE.into());
+    uploads
+        .db
+        .run(move |conn| {
+            let now = now_ms();
+            conn.execute(
+                "INSERT INTO uploads (id, path, file_name, size, modified_at, content_type,
+                                      sha256, chunk_size, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9)",
+                params![
+                    row.0,
+                    row.1,
+                    file_name,
+                    size,
+                    modified_at,
+                    content_type,
+                    sha256,
+                    CHUNK_SIZE,
+                    now
+                ],
+            )?;
+            Ok(())
+        })
+        .await?;
+    spawn(app, id.clone());
+    uploads.get(&id).await
+}
+
+/// Start uploading a file; progress and completion arrive as events.
+#[tauri::command]
+pub async fn upload_start(
+    app: AppHandle,
+    path: PathBuf,
+    content_type: Option<String>,
+) -> AppResult<Upload> {
+    start(&app, path, content_type).await
+}
+
+/// Stop after the current chunk is abandoned; progress so far is kept.
+#[tauri::command]
+pub async fn upload_pause(uploads: State<'_, Uploads>, id: String) -> AppResult<Upload> {
+    let upload = uploads.get(&id).await?;
+    if upload.status != STATUS_UPLOADING {
+        return Err(AppError::InvalidInput(format!(
+            "upload is {}, not uploading",
+            upload.status
+        )));
+    }
+    uploads.set_status(&id, STATUS_PAUSED, None).await?;
+    uploads.stop(&id);
+    uploads.get(&id).await
+}
+
+/// Continue a paused or failed upload from its last acknowledged chunk.
+pub(crate) async fn resume(app: &AppHandle, id: &str) -> AppResult<Upload> {
+    let uploads = app.state::<Uploads>();
+    let upload = uploads.get(id).await?;
+    if upload.status == STATUS_COMPLETED {
+        return Err(AppError::InvalidInput("upload already completed".into()));
+    }
+    uploads.set_status(id, STATUS_UPLOADING, None).await?;
+    spawn(app, id.to_string());
+    uploads.get(id).await
+}
+
+/// Continue a paused or failed upload from its last acknowledged chunk.
+#[tauri::command]
+pub async fn upload_resume(app: AppHandle, id: String) -> AppResult<Upload> {
+    resume(&app, &id).await
+}
+
+/// Stop an upload and forget it, telling the server to discard received chunks.
+#[tauri::command]
+pub async fn upload_cancel(
+    app: AppHandle,
+    uploads: State<'_, Uploads>,
+    http: State<'_, HttpClient>,
+    id: String,
+) -> AppResult<()> {
+    uploads.stop(&id);
+    let lookup = id.clone();
+    let remote_id: Option<Option<String>> = uploads
+        .db
+        .run(move |conn| {
+            let remote_id = conn
+                .query_row(
+                    "SELECT remote_id FROM uploads WHERE id = ?1",
+                    [&lookup],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            conn.execute("DELETE FROM uploads WHERE id = ?1", [&lookup])?;
+            Ok(remote_id)
+        })
+        .await?;
+    let Some(remote_id) = remote_id else {
+        return Err(AppError::not_found("upload", id));
+    };
+
+    // The server expires abandoned uploads anyway, so a failed discard is not an error
+    if let Some(remote_id) = remote_id {
+        let discard = async {
+            let url = config::api_url(&app, &format!("/v1/uploads/{remote_id}"))?;
+            let token = bearer(&app).await?;
+            http.send(
+                http.client().delete(url).bearer_auth(token),
+                RetryPolicy::default(),
+            )
+            .await?
+            .error_for_status()?;
+            Ok::<_, AppError>(())
+        };
+        if let Err(err) = discard.await {
+            tracing::debug!(%id, %err, "failed to discard remote upload");
+        }
+    }
+    Ok(())
+}
+
+/// Every known upload, newest first.
+#[tauri::command]
+pub async fn list_uploads(uploads: State<'_, Uploads>) -> AppResult<Vec<Upload>> {
+    uploads
+        .db
+        .run(|conn| {
+            Ok(conn
+                .prepare("SELECT * FROM uploads ORDER BY created_at DESC")?
+                .query_map([], Upload::from_row)?
+                .collect::<rusqlite::Result<_>>()?)
+        })
+        .await
+}
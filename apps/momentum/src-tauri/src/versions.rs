@@ -0,0 +1,477 @@
+//! Estimate versions: copies of an estimate frozen at a point in time, for bid review.
+//!
+//! A version holds the project, every line item with its place in the cost rollup, and the
+//! markups and taxes, serialized to JSON and deflated into one row that cannot be updated.
+//! Versions are taken on request and automatically ahead of imports and bulk deletes; an
+//! automatic one is skipped when the estimate has not changed since the last version, and
+//! only the most recent automatic ones are kept. Any two versions, or a version and the
+//! estimate as it is now, can be compared item by item.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use crate::audit::{self, Change};
+use crate::calc::{self, CostAdjustment, CostBreakdown, CostType, EstimateTotals};
+use crate::db::projects::{Item, Project};
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::state::AppState;
+
+// Automatic versions kept per project; manual ones are kept until deleted
+const MAX_AUTOMATIC: i64 = 50;
+// Quantities and costs closer than this are the same
+const EPSILON: f64 = 1e-9;
+
+/// Why a version was taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Reason {
+    Manual,
+    Import,
+    DeleteItems,
+}
+
+impl Reason {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Manual => "manual",
+            Self::Import => "import",
+            Self::DeleteItems => "deleteItems",
+        }
+    }
+}
+
+/// A line item as it stood in a version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionItem {
+    #[serde(flatten)]
+    pub item: Item,
+    pub parent_id: Option<String>,
+    pub cost_type: CostType,
+}
+
+impl VersionItem {
+    fn cost(&self) -> f64 {
+        self.item.quantity * self.item.unit_cost
+    }
+
+    // What a reviewer compares; ids, ordering, and timestamps are left out
+    fn fields(&self) -> [(&'static str, Value); 7] {
+        let item = &self.item;
+        [
+            ("name", item.name.clone().into()),
+            ("description", item.description.clone().into()),
+            ("quantity", item.quantity.into()),
+            ("unit", item.unit.clone().into()),
+            ("unitCost", item.unit_cost.into()),
+            ("parentId", self.parent_id.clone().into()),
+            (
+                "costType",
+                serde_json::to_value(self.cost_type).unwrap_or_default(),
+            ),
+        ]
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Snapshot {
+    project: Project,
+    items: Vec<VersionItem>,
+    adjustments: Vec<CostAdjustment>,
+}
+
+impl Snapshot {
+    fn totals(&self) -> EstimateTotals {
+        let subtotal = self
+            .items
+            .iter()
+            .fold(CostBreakdown::default(), |sum, item| {
+                sum.sum(CostBreakdown::of(item.cost_type, item.cost()))
+            });
+        calc::apply_adjustments(subtotal, &self.adjustments)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionInfo {
+    pub id: String,
+    pub project_id: String,
+    pub label: Option<String>,
+    /// `manual`, or the operation the version was taken ahead of.
+    pub reason: String,
+    pub item_count: u64,
+    pub total: f64,
+    pub created_by: Option<String>,
+    pub created_at: i64,
+}
+
+impl VersionInfo {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            project_id: row.get("project_id")?,
+            label: row.get("label")?,
+            reason: row.get("reason")?,
+            item_count: row.get::<_, i64>("item_count")? as u64,
+            total: row.get("total")?,
+            created_by: row.get("created_by")?,
+            created_at: row.get("created_at")?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EstimateVersion {
+    #[serde(flatten)]
+    pub info: VersionInfo,
+    pub project: Project,
+    pub items: Vec<VersionItem>,
+    pub adjustments: Vec<CostAdjustment>,
+    pub totals: EstimateTotals,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub before: Value,
+    pub after: Value,
+}
+
+/// An item in both estimates whose compared fields differ.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemChange {
+    pub item_id: String,
+    pub name: String,
+    pub changes: Vec<FieldChange>,
+    pub cost_before: f64,
+    pub cost_after: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EstimateDiff {
+    pub from: VersionInfo,
+    /// `None` when compared with the estimate as it is now.
+    pub to: Option<VersionInfo>,
+    pub added: Vec<VersionItem>,
+    pub removed: Vec<VersionItem>,
+    pub changed: Vec<ItemChange>,
+    pub totals_before: EstimateTotals,
+    pub totals_after: EstimateTotals,
+    pub subtotal_delta: f64,
+    pub total_delta: f64,
+}
+
+fn read_estimate(conn: &Connection, project_id: &str) -> AppResult<Snapshot> {
+    let project = conn
+        .query_row(
+            "SELECT * FROM projects WHERE id = ?1",
+            [project_id],
+            Project::from_row,
+        )
+        .optional()?
+        .ok_or_else(|| AppError::not_found("project", project_id))?;
+    let items = conn
+        .prepare_cached(
+            "SELECT i.*, s.parent_id, s.cost_type FROM items i
+             LEFT JOIN cost_structure s ON s.item_id = i.id
+             WHERE i.project_id = ?1 ORDER BY i.sort_order, i.name",
+        )?
+        .query_map([project_id], |row| {
+            Ok(VersionItem {
+                item: Item::from_row(row)?,
+                parent_id: row.get("parent_id")?,
+                cost_type: CostType::parse(
+                    row.get::<_, Option<String>>("cost_type")?
+                        .as_deref()
+                        .unwrap_or(""),
+                ),
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(Snapshot {
+        project,
+        items,
+        adjustments: calc::load_adjustments(conn, project_id)?,
+    })
+}
+
+/// Take a version of a project's estimate as part of the caller's transaction.
+///
+/// An automatic version of an estimate unchanged since its last version returns that one.
+pub(crate) fn capture(
+    conn: &Connection,
+    project_id: &str,
+    reason: Reason,
+    label: Option<String>,
+    actor: Option<&str>,
+) -> AppResult<VersionInfo> {
+    let snapshot = read_estimate(conn, project_id)?;
+    let json = serde_json::to_vec(&snapshot)?;
+    let hash = Sha256::digest(&json).to_vec();
+    if reason != Reason::Manual {
+        let latest = conn
+            .query_row(
+                "SELECT * FROM estimate_versions WHERE project_id = ?1
+                 ORDER BY created_at DESC, rowid DESC LIMIT 1",
+                [project_id],
+                |row| {
+                    Ok((
+                        VersionInfo::from_row(row)?,
+                        row.get::<_, Vec<u8>>("content_hash")?,
+                    ))
+                },
+            )
+            .optional()?;
+        if let Some((info, _)) = latest.filter(|(_, known)| *known == hash) {
+            return Ok(info);
+        }
+    }
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&json)?;
+    let compressed = encoder.finish()?;
+    let info = VersionInfo {
+        id: uuid::Uuid::new_v4().to_string(),
+        project_id: project_id.to_string(),
+        label,
+        reason: reason.as_str().to_string(),
+        item_count: snapshot.items.len() as u64,
+        total: snapshot.totals().total,
+        created_by: actor.map(str::to_string),
+        created_at: now_ms(),
+    };
+    conn.execute(
+        "INSERT INTO estimate_versions
+            (id, project_id, label, reason, item_count, total, content_hash, snapshot,
+             created_by, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            info.id,
+            info.project_id,
+            info.label,
+            info.reason,
+            info.item_count as i64,
+            info.total,
+            hash,
+            compressed,
+            info.created_by,
+            info.created_at
+        ],
+    )?;
+    conn.execute(
+        "DELETE FROM estimate_versions WHERE project_id = ?1 AND reason != 'manual'
+            AND id NOT IN (SELECT id FROM estimate_versions
+                           WHERE project_id = ?1 AND reason != 'manual'
+                           ORDER BY created_at DESC, rowid DESC LIMIT ?2)",
+        params![project_id, MAX_AUTOMATIC],
+    )?;
+    tracing::debug!(
+        version_id = %info.id,
+        project_id,
+        reason = reason.as_str(),
+        bytes = compressed.len(),
+        "estimate version taken"
+    );
+    Ok(info)
+}
+
+fn load(conn: &Connection, id: &str) -> AppResult<(VersionInfo, Snapshot)> {
+    let (info, compressed) = conn
+        .query_row(
+            "SELECT * FROM estimate_versions WHERE id = ?1",
+            [id],
+            |row| {
+                Ok((
+                    VersionInfo::from_row(row)?,
+                    row.get::<_, Vec<u8>>("snapshot")?,
+                ))
+            },
+        )
+        .optional()?
+        .ok_or_else(|| AppError::not_found("estimate version", id))?;
+    let mut json = Vec::new();
+    DeflateDecoder::new(compressed.as_slice()).read_to_end(&mut json)?;
+    Ok((info, serde_json::from_slice(&json)?))
+}
+
+fn same(before: &Value, after: &Value) -> bool {
+    match (before.as_f64(), after.as_f64()) {
+        (Some(a), Some(b)) => (a - b).abs() <= EPSILON,
+        _ => before == after,
+    }
+}
+
+fn diff(from: VersionInfo, to: Option<VersionInfo>, a: Snapshot, b: Snapshot) -> EstimateDiff {
+    let before: HashMap<&str, &VersionItem> =
+        a.items.iter().map(|i| (i.item.id.as_str(), i)).collect();
+    let after: HashMap<&str, &VersionItem> =
+        b.items.iter().map(|i| (i.item.id.as_str(), i)).collect();
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    for item in &b.items {
+        let Some(old) = before.get(item.item.id.as_str()) else {
+            added.push(item.clone());
+            continue;
+        };
+        let changes: Vec<FieldChange> = old
+            .fields()
+            .into_iter()
+            .zip(item.fields())
+            .filter(|((_, was), (_, is))| !same(was, is))
+            .map(|((field, was), (_, is))| FieldChange {
+                field,
+                before: was,
+                after: is,
+            })
+            .collect();
+        if !changes.is_empty() {
+            changed.push(ItemChange {
+                item_id: item.item.id.clone(),
+                name: item.item.name.clone(),
+                changes,
+                cost_before: old.cost(),
+                cost_after: item.cost(),
+            });
+        }
+    }
+    let removed = a
+        .items
+        .iter()
+        .filter(|item| !after.contains_key(item.item.id.as_str()))
+        .cloned()
+        .collect();
+    let (totals_before, totals_after) = (a.totals(), b.totals());
+    EstimateDiff {
+        from,
+        to,
+        added,
+        removed,
+        changed,
+        subtotal_delta: totals_after.subtotal.total() - totals_before.subtotal.total(),
+        total_delta: totals_after.total - totals_before.total,
+        totals_before,
+        totals_after,
+    }
+}
+
+/// Save a named version of a project's estimate as it is now.
+#[tauri::command]
+pub async fn create_estimate_version(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    project_id: String,
+    label: Option<String>,
+) -> AppResult<VersionInfo> {
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let tx = conn.transaction()?;
+        let info = capture(&tx, &project_id, Reason::Manual, label, Some(&actor))?;
+        audit::record(
+            &tx,
+            &actor,
+            Change::new("create_estimate_version", "project", &project_id).after(&info)?,
+        )?;
+        tx.commit()?;
+        Ok(info)
+    })
+    .await
+}
+
+/// A project's versions, newest first.
+#[tauri::command]
+pub async fn list_estimate_versions(
+    db: State<'_, Db>,
+    project_id: String,
+) -> AppResult<Vec<VersionInfo>> {
+    db.run(move |conn| {
+        let versions = conn
+            .prepare(
+                "SELECT * FROM estimate_versions WHERE project_id = ?1
+                 ORDER BY created_at DESC, rowid DESC",
+            )?
+            .query_map([&project_id], VersionInfo::from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(versions)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_estimate_version(db: State<'_, Db>, id: String) -> AppResult<EstimateVersion> {
+    db.run(move |conn| {
+        let (info, snapshot) = load(conn, &id)?;
+        Ok(EstimateVersion {
+            info,
+            totals: snapshot.totals(),
+            project: snapshot.project,
+            items: snapshot.items,
+            adjustments: snapshot.adjustments,
+        })
+    })
+    .await
+}
+
+/// Items added, removed, and changed from version `a` to version `b`, or to the estimate as
+/// it is now when `b` is omitted, with the change in totals.
+#[tauri::command]
+pub async fn diff_estimate_versions(
+    db: State<'_, Db>,
+    a: String,
+    b: Option<String>,
+) -> AppResult<EstimateDiff> {
+    db.run(move |conn| {
+        let (from, before) = load(conn, &a)?;
+        let (to, after) = match b {
+            Some(b) => {
+                let (info, snapshot) = load(conn, &b)?;
+                (Some(info), snapshot)
+            }
+            None => (None, read_estimate(conn, &from.project_id)?),
+        };
+        Ok(diff(from, to, before, after))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn delete_estimate_version(
+    db: State<'_, Db>,
+    state: State<'_, AppState>,
+    id: String,
+) -> AppResult<()> {
+    let actor = audit::actor(&state);
+    db.run(move |conn| {
+        let tx = conn.transaction()?;
+        let info = tx
+            .query_row(
+                "SELECT * FROM estimate_versions WHERE id = ?1",
+                [&id],
+                VersionInfo::from_row,
+            )
+            .optional()?
+            .ok_or_else(|| AppError::not_found("estimate version", &id))?;
+        tx.execute("DELETE FROM estimate_versions WHERE id = ?1", [&id])?;
+        audit::record(
+            &tx,
+            &actor,
+            Change::new("delete_estimate_version", "project", &info.project_id).before(&info)?,
+        )?;
+        tx.commit()?;
+        Ok(())
+    })
+    .await
+}
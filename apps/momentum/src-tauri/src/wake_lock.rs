@@ -0,0 +1,312 @@
+//! Keeping the machine awake while exports, syncs, and uploads run.
+//!
+//! Rust operations hold a [`WakeLockGuard`] for as long as they run, so the lock ends with
+//! them whether they succeed, fail, or panic. Locks taken by the frontend end with
+//! `release_wake_lock` or when the window that took them closes. Every lock belongs to this
+//! process, so the OS drops them all when it exits. Only idle sleep is held off; closing the
+//! lid or choosing Sleep still works.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State, Window, WindowEvent};
+
+use crate::db::now_ms;
+use crate::error::{AppError, AppResult};
+
+/// A held lock as shown to the frontend.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WakeLock {
+    pub id: u64,
+    pub reason: String,
+    /// Label of the window that took the lock; `None` for the app's own operations.
+    pub window: Option<String>,
+    pub acquired_at: i64,
+}
+
+struct Held {
+    info: WakeLock,
+    _os: os::Assertion,
+}
+
+/// Wake locks registered in app state.
+#[derive(Default)]
+pub struct WakeLocks {
+    next_id: AtomicU64,
+    held: Mutex<BTreeMap<u64, Held>>,
+}
+
+impl WakeLocks {
+    fn acquire(&self, reason: &str, window: Option<String>) -> AppResult<u64> {
+        let assertion = os::Assertion::new(reason)?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        tracing::debug!(id, reason, "wake lock acquired");
+        self.held.lock().unwrap_or_else(|e| e.into_inner()).insert(
+            id,
+            Held {
+                info: WakeLock {
+                    id,
+                    reason: reason.to_string(),
+                    window,
+                    acquired_at: now_ms(),
+                },
+                _os: assertion,
+            },
+        );
+        Ok(id)
+    }
+
+    fn release(&self, id: u64) -> bool {
+        let released = self
+            .held
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
+        if released.is_some() {
+            tracing::debug!(id, "wake lock released");
+        }
+        released.is_some()
+    }
+}
+
+/// Releases its wake lock when dropped.
+pub struct WakeLockGuard {
+    app: AppHandle,
+    id: Option<u64>,
+}
+
+impl Drop for WakeLockGuard {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            self.app.state::<WakeLocks>().release(id);
+        }
+    }
+}
+
+/// Keep the machine awake until the returned guard is dropped.
+///
+/// Failing to take the lock never fails the operation; it just may be interrupted by sleep.
+pub fn hold(app: &AppHandle, reason: &str) -> WakeLockGuard {
+    let id = app
+        .try_state::<WakeLocks>()
+        .and_then(|locks| match locks.acquire(reason, None) {
+            Ok(id) => Some(id),
+            Err(err) => {
+                tracing::warn!(%err, reason, "cannot keep the system awake");
+                None
+            }
+        });
+    WakeLockGuard {
+        app: app.clone(),
+        id,
+    }
+}
+
+/// Release the locks a window took when it closes.
+pub fn on_window_event(window: &Window, event: &WindowEvent) {
+    if !matches!(event, WindowEvent::Destroyed) {
+        return;
+    }
+    let Some(locks) = window.try_state::<WakeLocks>() else {
+        return;
+    };
+    locks
+        .held
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|_, held| held.info.window.as_deref() != Some(window.label()));
+}
+
+#[cfg(windows)]
+mod os {
+    use windows::core::PWSTR;
+    use windows::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows::Win32::System::Power::{
+        PowerClearRequest, PowerCreateRequest, PowerRequestSystemRequired, PowerSetRequest,
+    };
+    use windows::Win32::System::Threading::{
+        POWER_REQUEST_CONTEXT_SIMPLE_STRING, REASON_CONTEXT, REASON_CONTEXT_0,
+    };
+
+    use crate::error::{AppError, AppResult};
+
+    const POWER_REQUEST_CONTEXT_VERSION: u32 = 0;
+
+    // A power request rather than SetThreadExecutionState, which would tie the lock to
+    // whichever runtime thread happened to take it
+    pub struct Assertion {
+        // Raw handles are not Send; the value is only ever passed back to the OS
+        handle: isize,
+    }
+
+    impl Assertion {
+        pub fn new(reason: &str) -> AppResult<Self> {
+            let mut reason: Vec<u16> = reason.encode_utf16().chain(Some(0)).collect();
+            let context = REASON_CONTEXT {
+                Version: POWER_REQUEST_CONTEXT_VERSION,
+                Flags: POWER_REQUEST_CONTEXT_SIMPLE_STRING,
+                Reason: REASON_CONTEXT_0 {
+                    SimpleReasonString: PWSTR(reason.as_mut_ptr()),
+                },
+            };
+            let failed =
+                |e: windows::core::Error| AppError::Internal(format!("power request: {e}"));
+            let handle = unsafe { PowerCreateRequest(&context) }.map_err(failed)?;
+            if let Err(err) = unsafe { PowerSetRequest(handle, PowerRequestSystemRequired) } {
+                let _ = unsafe { CloseHandle(handle) };
+                return Err(failed(err));
+            }
+            Ok(Self {
+                handle: handle.0 as isize,
+            })
+        }
+    }
+
+    impl Drop for Assertion {
+        fn drop(&mut self) {
+            let handle = HANDLE(self.handle as *mut _);
+            unsafe {
+                let _ = PowerClearRequest(handle, PowerRequestSystemRequired);
+                let _ = CloseHandle(handle);
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use std::ffi::c_void;
+
+    use objc2_foundation::NSString;
+
+    use crate::error::{AppError, AppResult};
+
+    const ASSERTION_LEVEL_ON: u32 = 255;
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOPMAssertionCreateWithName(
+            assertion_type: *const c_void,
+            level: u32,
+            name: *const c_void,
+            id: *mut u32,
+        ) -> i32;
+        fn IOPMAssertionRelease(id: u32) -> i32;
+    }
+
+    pub struct Assertion {
+        id: u32,
+    }
+
+    impl Assertion {
+        // NSString is toll-free bridged to the CFString IOKit expects
+        pub fn new(reason: &str) -> AppResult<Self> {
+            let kind = NSString::from_str("PreventUserIdleSystemSleep");
+            let name = NSString::from_str(reason);
+            let mut id = 0;
+            let status = unsafe {
+                IOPMAssertionCreateWithName(
+                    &*kind as *const NSString as *const c_void,
+                    ASSERTION_LEVEL_ON,
+                    &*name as *const NSString as *const c_void,
+                    &mut id,
+                )
+            };
+            if status != 0 {
+                return Err(AppError::Internal(format!(
+                    "power assertion failed with {status:#x}"
+                )));
+            }
+            Ok(Self { id })
+        }
+    }
+
+    impl Drop for Assertion {
+        fn drop(&mut self) {
+            unsafe { IOPMAssertionRelease(self.id) };
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use zbus::zvariant::OwnedFd;
+
+    use crate::error::{AppError, AppResult};
+
+    // logind holds the inhibitor for as long as this descriptor stays open
+    pub struct Assertion {
+        _fd: OwnedFd,
+    }
+
+    impl Assertion {
+        pub fn new(reason: &str) -> AppResult<Self> {
+            let failed = |e: zbus::Error| AppError::Internal(format!("logind inhibit: {e}"));
+            let connection = zbus::blocking::Connection::system().map_err(failed)?;
+            let reply = connection
+                .call_method(
+                    Some("org.freedesktop.login1"),
+                    "/org/freedesktop/login1",
+                    Some("org.freedesktop.login1.Manager"),
+                    "Inhibit",
+                    &("idle", "Momentum", reason, "block"),
+                )
+                .map_err(failed)?;
+            let fd = reply.body().deserialize::<OwnedFd>().map_err(failed)?;
+            Ok(Self { _fd: fd })
+        }
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+mod os {
+    use crate::error::AppResult;
+
+    // Mobile apps are suspended when backgrounded no matter what, so there is nothing to hold
+    pub struct Assertion;
+
+    impl Assertion {
+        pub fn new(_reason: &str) -> AppResult<Self> {
+            Ok(Self)
+        }
+    }
+}
+
+/// Keep the machine awake until `release_wake_lock`, or until the calling window closes.
+///
+/// `reason` is shown by the OS to users asking what keeps the machine awake. Returns the
+/// lock id.
+#[tauri::command]
+pub async fn acquire_wake_lock(window: Window, reason: String) -> AppResult<u64> {
+    if reason.trim().is_empty() {
+        return Err(AppError::InvalidInput("a wake lock needs a reason".into()));
+    }
+    // Asking the OS power service can block briefly
+    tauri::async_runtime::spawn_blocking(move || {
+        let label = window.label().to_string();
+        window.state::<WakeLocks>().acquire(&reason, Some(label))
+    })
+    .await?
+}
+
+#[tauri::command]
+pub async fn release_wake_lock(locks: State<'_, WakeLocks>, id: u64) -> AppResult<()> {
+    if !locks.release(id) {
+        return Err(AppError::not_found("wake lock", id.to_string()));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn list_wake_locks(locks: State<'_, WakeLocks>) -> AppResult<Vec<WakeLock>> {
+    Ok(locks
+        .held
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .values()
+        .map(|held| held.info.clone())
+        .collect())
+}
@@ -0,0 +1,332 @@
+//! Ingestion of files dropped into watched folders, such as a shared "drawings" folder.
+//!
+//! Events are not acted on directly: a file is only reported once its size and modification
+//! time have stopped changing for [`SETTLE_DELAY`], so plotters and sync clients still
+//! writing a large PDF are not picked up half-written.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use notify::event::{CreateKind, EventKind, ModifyKind};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::attachments::{self, Attachment, AttachmentStore};
+use crate::config;
+use crate::db::Db;
+use crate::error::AppResult;
+use crate::state::AppState;
+
+/// Emitted with a [`WatchedFile`] once a new or changed file has finished being written.
+pub const FILE_ADDED_EVENT: &str = "watched-file:added";
+
+const SETTLE_DELAY: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A folder to watch and which of its files to pick up.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchedFolder {
+    pub path: PathBuf,
+    /// Case-insensitive file name globs using `*` and `?`, e.g. `*.pdf`; empty matches all.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    #[serde(default)]
+    pub recursive: bool,
+    /// Attach matching files to the active project as they arrive.
+    #[serde(default)]
+    pub auto_import: bool,
+}
+
+/// A settled file in a watched folder.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchedFile {
+    pub path: PathBuf,
+    /// The [`WatchedFolder::path`] it arrived in.
+    pub folder: PathBuf,
+    pub size: u64,
+    /// False when an existing file was overwritten.
+    pub created: bool,
+    /// Set when the file was auto-imported.
+    pub attachment: Option<Attachment>,
+    /// Why auto-import failed, if it did.
+    pub import_error: Option<String>,
+}
+
+struct Pending {
+    folder: usize,
+    created: bool,
+    last_event: Instant,
+    snapshot: Option<(u64, SystemTime)>,
+}
+
+#[derive(Default)]
+struct Inner {
+    watcher: Option<RecommendedWatcher>,
+    folders: Vec<WatchedFolder>,
+    pending: HashMap<PathBuf, Pending>,
+    active_project: Option<String>,
+}
+
+/// Watcher state registered in app state.
+#[derive(Default)]
+pub struct WatchedFolders {
+    inner: Mutex<Inner>,
+}
+
+// Editors and downloaders write through these before renaming into place
+fn is_temporary(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    name.starts_with('.')
+        || name.starts_with("~$")
+        || [".tmp", ".part", ".crdownload", ".download", ".partial"]
+            .iter()
+            .any(|ext| lower.ends_with(ext))
+}
+
+fn glob_matches(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            glob_matches(&pattern[1..], name)
+                || (!name.is_empty() && glob_matches(pattern, &name[1..]))
+        }
+        (Some('?'), Some(_)) => glob_matches(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) => p == n && glob_matches(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+impl WatchedFolder {
+    fn matches(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            return false;
+        };
+        let within = if self.recursive {
+            path.starts_with(&self.path)
+        } else {
+            path.parent() == Some(self.path.as_path())
+        };
+        if !within || is_temporary(name) {
+            return false;
+        }
+        let name: Vec<char> = name.to_lowercase().chars().collect();
+        self.patterns.is_empty()
+            || self.patterns.iter().any(|pattern| {
+                let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+                glob_matches(&pattern, &name)
+            })
+    }
+}
+
+fn snapshot(path: &Path) -> Option<(u64, SystemTime)> {
+    let meta = fs::metadata(path).ok()?;
+    meta.is_file()
+        .then_some((meta.len(), meta.modified().ok()?))
+}
+
+impl WatchedFolders {
+    fn record(&self, event: notify::Event) {
+        let created = match event.kind {
+            EventKind::Create(CreateKind::Folder) => return,
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_)) => true,
+            EventKind::Modify(ModifyKind::Metadata(_)) => return,
+            EventKind::Modify(_) => false,
+            _ => return,
+        };
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        for path in event.paths {
+            let Some(folder) = inner.folders.iter().position(|f| f.matches(&path)) else {
+                continue;
+            };
+            inner
+                .pending
+                .entry(path)
+                .and_modify(|p| {
+                    p.last_event = now;
+                    p.created |= created;
+                })
+                .or_insert(Pending {
+                    folder,
+                    created,
+                    last_event: now,
+                    snapshot: None,
+                });
+        }
+    }
+
+    // Files quiet for the settle delay whose size and mtime held still since the last poll
+    fn take_settled(&self) -> Vec<(WatchedFolder, WatchedFile)> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let Inner {
+            folders, pending, ..
+        } = &mut *inner;
+        let mut settled = Vec::new();
+        pending.retain(|path, entry| {
+            if entry.last_event.elapsed() < SETTLE_DELAY {
+                return true;
+            }
+            // Deleted or renamed away before it settled
+            let Some(current) = snapshot(path) else {
+                return false;
+            };
+            if entry.snapshot != Some(current) {
+                entry.snapshot = Some(current);
+                return true;
+            }
+            if let Some(folder) = folders.get(entry.folder) {
+                settled.push((
+                    folder.clone(),
+                    WatchedFile {
+                        path: path.clone(),
+                        folder: folder.path.clone(),
+                        size: current.0,
+                        created: entry.created,
+                        attachment: None,
+                        import_error: None,
+                    },
+                ));
+            }
+            false
+        });
+        settled
+    }
+
+    fn active_project(&self) -> Option<String> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .active_project
+            .clone()
+    }
+}
+
+/// Point the watcher at the folders in the current config, replacing any previous watch.
+///
+/// Called at startup and whenever the config changes. Missing folders are skipped with a
+/// warning so one unplugged drive does not stop the others.
+pub fn reconfigure(app: &AppHandle) {
+    let Some(state) = app.try_state::<WatchedFolders>() else {
+        return;
+    };
+    let folders = app.state::<AppState>().config().watched_folders;
+    let previous = {
+        let mut inner = state.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if inner.watcher.is_some() && inner.folders == folders {
+            return;
+        }
+        inner.pending.clear();
+        inner.folders = folders.clone();
+        inner.watcher.take()
+    };
+    // Dropping a watcher joins its thread, which may be waiting on the lock in a callback
+    drop(previous);
+    if folders.is_empty() {
+        return;
+    }
+
+    let handle = app.clone();
+    let watcher =
+        notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+            Ok(event) => handle.state::<WatchedFolders>().record(event),
+            Err(err) => tracing::warn!(%err, "watched folder error"),
+        });
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(err) => {
+            tracing::warn!(%err, "cannot start folder watcher");
+            return;
+        }
+    };
+    for folder in &folders {
+        let mode = if folder.recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        match watcher.watch(&folder.path, mode) {
+            Ok(()) => tracing::info!(path = %folder.path.display(), "watching folder"),
+            Err(err) => {
+                tracing::warn!(%err, path = %folder.path.display(), "cannot watch folder")
+            }
+        }
+    }
+    state
+        .inner
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .watcher = Some(watcher);
+}
+
+async fn ingest(app: &AppHandle, folder: &WatchedFolder, mut file: WatchedFile) {
+    let project_id = app.state::<WatchedFolders>().active_project();
+    if let (true, Some(project_id)) = (folder.auto_import, project_id) {
+        let handle = app.clone();
+        let path = file.path.clone();
+        let result = app
+            .state::<Db>()
+            .run(move |conn| {
+                attachments::add_file(conn, &handle.state::<AttachmentStore>(), project_id, &path)
+            })
+            .await;
+        match result {
+            Ok(attachment) => file.attachment = Some(attachment),
+            Err(err) => {
+                tracing::warn!(%err, path = %file.path.display(), "watched file import failed");
+                file.import_error = Some(err.to_string());
+            }
+        }
+    }
+    tracing::debug!(path = %file.path.display(), "watched file settled");
+    let _ = app.emit(FILE_ADDED_EVENT, &file);
+}
+
+/// Start watching the configured folders and reporting files as they settle.
+pub fn start(app: &AppHandle) {
+    reconfigure(app);
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let settled = app.state::<WatchedFolders>().take_settled();
+            for (folder, file) in settled {
+                ingest(&app, &folder, file).await;
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_watched_folders(state: State<'_, AppState>) -> AppResult<Vec<WatchedFolder>> {
+    Ok(state.config().watched_folders)
+}
+
+/// Replace the watched folders and persist them to the config.
+#[tauri::command]
+pub async fn set_watched_folders(
+    app: AppHandle,
+    folders: Vec<WatchedFolder>,
+) -> AppResult<Vec<WatchedFolder>> {
+    let config = config::update(&app, |config| config.watched_folders = folders)?;
+    Ok(config.watched_folders)
+}
+
+/// Set the project auto-imported files are attached to, or `None` to only emit events.
+#[tauri::command]
+pub async fn set_watch_target_project(
+    state: State<'_, WatchedFolders>,
+    project_id: Option<String>,
+) -> AppResult<()> {
+    state
+        .inner
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .active_project = project_id;
+    Ok(())
+}
@@ -0,0 +1,295 @@
+//! Weather at each jobsite, recorded per day so a daily log has its conditions offline.
+//!
+//! Conditions come from the API's weather endpoint for a project's jobsite, its `project`
+//! location tag, and are kept in `weather_days`, one row per project and day in the machine's
+//! time zone like the daily log itself. An hourly job records today at every jobsite and
+//! finishes yesterday, so a day is already on file before anyone asks for it. `get_weather`
+//! answers from that cache and goes to the network only for a day it lacks, or for today once
+//! the cached conditions are an hour old; a day fetched after it ended is final.
+
+use std::time::Duration;
+
+use jiff::civil::Date;
+use jiff::tz::TimeZone;
+use jiff::Zoned;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::config;
+use crate::db::{now_ms, Db};
+use crate::error::{AppError, AppResult};
+use crate::http_client::{HttpClient, RetryPolicy};
+use crate::jobs::Scheduler;
+use crate::locale::{self, MeasurementSystem};
+use crate::maps;
+use crate::network::NetworkMonitor;
+
+/// Job kind that records the weather at every jobsite.
+pub const RECORD_JOB: &str = "weather.record";
+const RECORD_INTERVAL: Duration = Duration::from_secs(60 * 60);
+// Today's conditions older than this are fetched again when online
+const REFRESH_AFTER_MS: i64 = 60 * 60 * 1000;
+
+/// A day's conditions as the weather endpoint reports them, in metric units.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Conditions {
+    /// Plain words such as `Partly cloudy`.
+    pub summary: Option<String>,
+    pub high_c: Option<f64>,
+    pub low_c: Option<f64>,
+    /// Rain and melted snow over the day so far.
+    pub precipitation_mm: Option<f64>,
+    pub wind_kmh: Option<f64>,
+    pub humidity_percent: Option<f64>,
+}
+
+impl Conditions {
+    /// One line for a report in the user's units, e.g.
+    /// `Light rain, 9–14 °C, 4.2 mm precipitation, wind 18 km/h`.
+    pub fn describe(&self, units: MeasurementSystem) -> Option<String> {
+        let imperial = units == MeasurementSystem::Imperial;
+        let degrees = |c: f64| {
+            if imperial {
+                format!("{:.0}", c * 9.0 / 5.0 + 32.0)
+            } else {
+                format!("{c:.0}")
+            }
+        };
+        let scale = if imperial { "°F" } else { "°C" };
+        let mut parts = Vec::new();
+        if let Some(summary) = self.summary.as_deref().filter(|s| !s.trim().is_empty()) {
+            parts.push(summary.to_string());
+        }
+        match (self.low_c, self.high_c) {
+            (Some(low), Some(high)) => {
+                parts.push(format!("{}–{} {scale}", degrees(low), degrees(high)))
+            }
+            (None, Some(high)) => parts.push(format!("high {} {scale}", degrees(high))),
+            (Some(low), None) => parts.push(format!("low {} {scale}", degrees(low))),
+            (None, None) => {}
+        }
+        if let Some(mm) = self.precipitation_mm.filter(|mm| *mm > 0.0) {
+            parts.push(if imperial {
+                format!("{:.2} in precipitation", mm / 25.4)
+            } else {
+                format!("{mm:.1} mm precipitation")
+            });
+        }
+        if let Some(kmh) = self.wind_kmh {
+            parts.push(if imperial {
+                format!("wind {:.0} mph", kmh / 1.609_344)
+            } else {
+                format!("wind {kmh:.0} km/h")
+            });
+        }
+        if let Some(humidity) = self.humidity_percent {
+            parts.push(format!("{humidity:.0}% humidity"));
+        }
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+}
+
+/// One day of weather at a project's jobsite.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Weather {
+    pub project_id: String,
+    /// `YYYY-MM-DD` in the machine's time zone.
+    pub date: String,
+    #[serde(flatten)]
+    pub conditions: Conditions,
+    pub fetched_at: i64,
+    /// Whether the day was over when this was fetched, so it will not change.
+    pub complete: bool,
+}
+
+fn today() -> Date {
+    Zoned::now().date()
+}
+
+// When `date` ends locally, as Unix ms
+fn end_of(date: Date) -> AppResult<i64> {
+    let end = date
+        .tomorrow()
+        .and_then(|day| day.to_zoned(TimeZone::system()))
+        .map_err(|e| AppError::InvalidInput(format!("{date}: {e}")))?;
+    Ok(end.timestamp().as_millisecond())
+}
+
+async fn cached(db: &Db, project_id: String, date: Date) -> AppResult<Option<Weather>> {
+    db.run(move |conn| {
+        let row: Option<(String, bool, i64)> = conn
+            .query_row(
+                "SELECT conditions, complete, fetched_at FROM weather_days
+                 WHERE project_id = ?1 AND date = ?2",
+                params![project_id, date.to_string()],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        Ok(row.map(|(conditions, complete, fetched_at)| Weather {
+            project_id,
+            date: date.to_string(),
+            // A row written by a later version may not parse; treat it as empty, not fatal
+            conditions: serde_json::from_str(&conditions).unwrap_or_default(),
+            fetched_at,
+            complete,
+        }))
+    })
+    .await
+}
+
+/// Ask the weather endpoint for a day at a position; `None` when it has nothing for it.
+async fn fetch(
+    app: &AppHandle,
+    latitude: f64,
+    longitude: f64,
+    date: Date,
+) -> AppResult<Option<Conditions>> {
+    let mut url = config::api_url(app, "/v1/weather")?;
+    url.query_pairs_mut()
+        .append_pair("latitude", &latitude.to_string())
+        .append_pair("longitude", &longitude.to_string())
+        .append_pair("date", &date.to_string());
+    let http = app.state::<HttpClient>();
+    let response = http
+        .send_authorized(app, http.client().get(url), RetryPolicy::default())
+        .await?;
+    if response.status() == tauri_plugin_http::reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let body = response.error_for_status()?.bytes().await?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+async fn store(
+    db: &Db,
+    project_id: String,
+    date: Date,
+    conditions: Conditions,
+    complete: bool,
+) -> AppResult<Weather> {
+    let json = serde_json::to_string(&conditions)?;
+    let fetched_at = now_ms();
+    let id = project_id.clone();
+    db.run(move |conn| {
+        conn.execute(
+            "INSERT INTO weather_days (project_id, date, conditions, complete, fetched_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(project_id, date) DO UPDATE SET
+                conditions = excluded.conditions,
+                complete = excluded.complete,
+                fetched_at = excluded.fetched_at",
+            params![id, date.to_string(), json, complete, fetched_at],
+        )?;
+        Ok(())
+    })
+    .await?;
+    Ok(Weather {
+        project_id,
+        date: date.to_string(),
+        conditions,
+        fetched_at,
+        complete,
+    })
+}
+
+/// The weather at a project's jobsite on `date`: cached when final or fresh, otherwise
+/// fetched when online, falling back to whatever is cached.
+pub(crate) async fn for_day(
+    app: &AppHandle,
+    project_id: &str,
+    date: Date,
+) -> AppResult<Option<Weather>> {
+    if date > today() {
+        return Err(AppError::InvalidInput(format!(
+            "no weather is recorded for {date} yet"
+        )));
+    }
+    let db = app.state::<Db>();
+    let cached = cached(&db, project_id.to_string(), date).await?;
+    if let Some(weather) = &cached {
+        if weather.complete || now_ms() - weather.fetched_at < REFRESH_AFTER_MS {
+            return Ok(cached);
+        }
+    }
+    if !app.state::<NetworkMonitor>().is_online() {
+        return Ok(cached);
+    }
+    let Some((latitude, longitude)) = maps::jobsite(&db, project_id.to_string()).await? else {
+        return match cached {
+            Some(_) => Ok(cached),
+            None => Err(AppError::not_found("jobsite", project_id)),
+        };
+    };
+    // Decided before the request, so a day that ends while it is in flight stays open
+    let complete = now_ms() >= end_of(date)?;
+    match fetch(app, latitude, longitude, date).await {
+        Ok(Some(conditions)) => Ok(Some(
+            store(&db, project_id.to_string(), date, conditions, complete).await?,
+        )),
+        Ok(None) => Ok(cached),
+        Err(err) if cached.is_some() => {
+            tracing::debug!(%err, project_id, %date, "weather refresh failed; using the cache");
+            Ok(cached)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// The day's weather as one line for a report, or `None` if it is not known.
+pub(crate) async fn describe_day(app: &AppHandle, project_id: &str, date: Date) -> Option<String> {
+    match for_day(app, project_id, date).await {
+        Ok(weather) => weather?
+            .conditions
+            .describe(locale::current().measurement_system),
+        Err(err) => {
+            tracing::warn!(%err, project_id, %date, "no weather for the daily log");
+            None
+        }
+    }
+}
+
+pub fn register_jobs(scheduler: &Scheduler) {
+    scheduler.register(RECORD_JOB, Some(RECORD_INTERVAL), |app| async move {
+        if !app.state::<NetworkMonitor>().is_online() {
+            return Ok(());
+        }
+        let projects: Vec<String> = app
+            .state::<Db>()
+            .run(|conn| {
+                let mut stmt =
+                    conn.prepare("SELECT entity_id FROM location_tags WHERE entity = 'project'")?;
+                let projects = stmt
+                    .query_map([], |row| row.get(0))?
+                    .collect::<rusqlite::Result<_>>()?;
+                Ok(projects)
+            })
+            .await?;
+        let today = today();
+        let days = [today.yesterday().ok(), Some(today)];
+        for project_id in projects {
+            for date in days.into_iter().flatten() {
+                if let Err(err) = for_day(&app, &project_id, date).await {
+                    tracing::warn!(%err, project_id, %date, "could not record the weather");
+                }
+            }
+        }
+        Ok(())
+    });
+}
+
+/// The weather at a project's jobsite on `date`, as `YYYY-MM-DD`; `None` when it was never
+/// recorded and cannot be fetched now.
+#[tauri::command]
+pub async fn get_weather(
+    app: AppHandle,
+    project_id: String,
+    date: String,
+) -> AppResult<Option<Weather>> {
+    let day: Date = date
+        .parse()
+        .map_err(|_| AppError::InvalidInput(format!("{date} is not a YYYY-MM-DD date")))?;
+    for_day(&app, &project_id, day).await
+}
@@ -0,0 +1,317 @@
+//! Native translucent window materials and corner styles.
+//!
+//! macOS offers vibrancy materials (and Liquid Glass from macOS 26); Windows 11 offers Mica
+//! and the tabbed Mica Alt, with acrylic and blur back to Windows 10. The effect shows
+//! through wherever the page leaves its background transparent, so the frontend should ask
+//! `get_window_effects_support` first and keep its opaque background for anything missing.
+//! Linux and mobile support none of these.
+
+use serde::{Deserialize, Serialize};
+use tauri::window::{Color, Effect, EffectState};
+use tauri::WebviewWindow;
+
+use crate::error::{AppError, AppResult};
+
+/// A translucent backdrop; each is available on one platform only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WindowMaterial {
+    // macOS
+    Sidebar,
+    WindowBackground,
+    Hud,
+    Popover,
+    Titlebar,
+    LiquidGlass,
+    LiquidGlassClear,
+    // Windows
+    Mica,
+    MicaLight,
+    MicaDark,
+    Tabbed,
+    Acrylic,
+    Blur,
+}
+
+#[cfg_attr(mobile, allow(dead_code))]
+impl WindowMaterial {
+    fn effect(self) -> Effect {
+        match self {
+            Self::Sidebar => Effect::Sidebar,
+            Self::WindowBackground => Effect::WindowBackground,
+            Self::Hud => Effect::HudWindow,
+            Self::Popover => Effect::Popover,
+            Self::Titlebar => Effect::Titlebar,
+            Self::LiquidGlass => Effect::LiquidGlassRegular,
+            Self::LiquidGlassClear => Effect::LiquidGlassClear,
+            Self::Mica => Effect::Mica,
+            Self::MicaLight => Effect::MicaLight,
+            Self::MicaDark => Effect::MicaDark,
+            Self::Tabbed => Effect::Tabbed,
+            Self::Acrylic => Effect::Acrylic,
+            Self::Blur => Effect::Blur,
+        }
+    }
+
+    // Liquid Glass falls back to this on macOS before 26
+    fn fallback(self) -> Option<Effect> {
+        matches!(self, Self::LiquidGlass | Self::LiquidGlassClear).then_some(Effect::Sidebar)
+    }
+}
+
+/// Whether materials follow the window's focus; macOS only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MaterialState {
+    #[default]
+    FollowsWindow,
+    Active,
+    Inactive,
+}
+
+impl From<MaterialState> for EffectState {
+    fn from(state: MaterialState) -> Self {
+        match state {
+            MaterialState::FollowsWindow => EffectState::FollowsWindowActiveState,
+            MaterialState::Active => EffectState::Active,
+            MaterialState::Inactive => EffectState::Inactive,
+        }
+    }
+}
+
+/// How the window's corners are drawn; Windows 11 only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CornerStyle {
+    Default,
+    Round,
+    RoundSmall,
+    Square,
+}
+
+/// What this system can show, for the frontend to choose a material or fall back.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowEffectsSupport {
+    pub materials: Vec<WindowMaterial>,
+    pub corner_styles: bool,
+}
+
+/// How to draw a material; only `material` is required.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WindowEffectOptions {
+    pub state: MaterialState,
+    /// Corner radius of the material in points; macOS only.
+    pub radius: Option<f64>,
+    /// Tint for acrylic and blur on Windows 10 and for Liquid Glass.
+    pub color: Option<Color>,
+}
+
+fn os_version() -> (u64, u64, u64) {
+    match tauri_plugin_os::version() {
+        tauri_plugin_os::Version::Semantic(major, minor, patch) => (major, minor, patch),
+        _ => (0, 0, 0),
+    }
+}
+
+pub fn support() -> WindowEffectsSupport {
+    platform::support(os_version())
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use tauri::WebviewWindow;
+
+    use super::{CornerStyle, WindowEffectsSupport, WindowMaterial};
+    use crate::error::AppResult;
+
+    pub fn support((major, ..): (u64, u64, u64)) -> WindowEffectsSupport {
+        let mut materials = vec![
+            WindowMaterial::Sidebar,
+            WindowMaterial::WindowBackground,
+            WindowMaterial::Hud,
+            WindowMaterial::Popover,
+            WindowMaterial::Titlebar,
+        ];
+        if major >= 26 {
+            materials.extend([
+                WindowMaterial::LiquidGlass,
+                WindowMaterial::LiquidGlassClear,
+            ]);
+        }
+        WindowEffectsSupport {
+            materials,
+            corner_styles: false,
+        }
+    }
+
+    // macOS draws its own rounded corners; support reports none to change
+    pub fn set_corners(_window: &WebviewWindow, _style: CornerStyle) -> AppResult<()> {
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::ffi::c_void;
+
+    use tauri::WebviewWindow;
+    use windows::Win32::Graphics::Dwm::{
+        DwmSetWindowAttribute, DWMWA_WINDOW_CORNER_PREFERENCE, DWMWCP_DEFAULT, DWMWCP_DONOTROUND,
+        DWMWCP_ROUND, DWMWCP_ROUNDSMALL, DWM_WINDOW_CORNER_PREFERENCE,
+    };
+
+    use super::{CornerStyle, WindowEffectsSupport, WindowMaterial};
+    use crate::error::{AppError, AppResult};
+
+    const WINDOWS_11: u64 = 22000;
+    // First build with the tabbed backdrop
+    const WINDOWS_11_22H2: u64 = 22523;
+
+    pub fn support((major, _, build): (u64, u64, u64)) -> WindowEffectsSupport {
+        let mut materials = Vec::new();
+        if major >= 10 {
+            materials.extend([WindowMaterial::Acrylic, WindowMaterial::Blur]);
+        }
+        if build >= WINDOWS_11 {
+            materials.extend([
+                WindowMaterial::Mica,
+                WindowMaterial::MicaLight,
+                WindowMaterial::MicaDark,
+            ]);
+        }
+        if build >= WINDOWS_11_22H2 {
+            materials.push(WindowMaterial::Tabbed);
+        }
+        WindowEffectsSupport {
+            materials,
+            corner_styles: build >= WINDOWS_11,
+        }
+    }
+
+    pub fn set_corners(window: &WebviewWindow, style: CornerStyle) -> AppResult<()> {
+        let preference: DWM_WINDOW_CORNER_PREFERENCE = match style {
+            CornerStyle::Default => DWMWCP_DEFAULT,
+            CornerStyle::Round => DWMWCP_ROUND,
+            CornerStyle::RoundSmall => DWMWCP_ROUNDSMALL,
+            CornerStyle::Square => DWMWCP_DONOTROUND,
+        };
+        let hwnd = window.hwnd()?;
+        unsafe {
+            DwmSetWindowAttribute(
+                hwnd,
+                DWMWA_WINDOW_CORNER_PREFERENCE,
+                &preference as *const _ as *const c_void,
+                std::mem::size_of::<DWM_WINDOW_CORNER_PREFERENCE>() as u32,
+            )
+        }
+        .map_err(|e| AppError::Internal(format!("corner preference: {e}")))
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+mod platform {
+    use tauri::WebviewWindow;
+
+    use super::{CornerStyle, WindowEffectsSupport};
+    use crate::error::AppResult;
+
+    pub fn support(_version: (u64, u64, u64)) -> WindowEffectsSupport {
+        WindowEffectsSupport {
+            materials: Vec::new(),
+            corner_styles: false,
+        }
+    }
+
+    pub fn set_corners(_window: &WebviewWindow, _style: CornerStyle) -> AppResult<()> {
+        Ok(())
+    }
+}
+
+/// Which materials and corner styles this system supports.
+#[tauri::command]
+pub async fn get_window_effects_support() -> AppResult<WindowEffectsSupport> {
+    Ok(support())
+}
+
+/// Put a translucent material behind the calling window's content, or remove it with `null`.
+///
+/// The page's own background must be transparent for the material to show. Fails with
+/// `INVALID_INPUT` when the material is not in [`get_window_effects_support`].
+#[tauri::command]
+pub async fn set_window_effect(
+    window: WebviewWindow,
+    material: Option<WindowMaterial>,
+    options: Option<WindowEffectOptions>,
+) -> AppResult<()> {
+    let Some(material) = material else {
+        return clear(&window);
+    };
+    if !support().materials.contains(&material) {
+        return Err(AppError::InvalidInput(format!(
+            "{material:?} is not supported on this system"
+        )));
+    }
+    apply(&window, material, options.unwrap_or_default())
+}
+
+#[cfg(desktop)]
+fn apply(
+    window: &WebviewWindow,
+    material: WindowMaterial,
+    options: WindowEffectOptions,
+) -> AppResult<()> {
+    let mut effects = tauri::window::EffectsBuilder::new()
+        .effect(material.effect())
+        .state(options.state.into());
+    if let Some(fallback) = material.fallback() {
+        effects = effects.effect(fallback);
+    }
+    if let Some(radius) = options.radius {
+        effects = effects.radius(radius);
+    }
+    if let Some(color) = options.color {
+        effects = effects.color(color);
+    }
+    // The webview paints over the backdrop unless it is cleared; macOS windows are
+    // created transparent instead
+    #[cfg(windows)]
+    window.set_background_color(Some(Color(0, 0, 0, 0)))?;
+    window.set_effects(effects.build())?;
+    Ok(())
+}
+
+#[cfg(desktop)]
+fn clear(window: &WebviewWindow) -> AppResult<()> {
+    window.set_effects(None)?;
+    #[cfg(windows)]
+    window.set_background_color(None)?;
+    Ok(())
+}
+
+// Support is empty on mobile, so nothing reaches here
+#[cfg(mobile)]
+fn apply(_window: &WebviewWindow, _: WindowMaterial, _: WindowEffectOptions) -> AppResult<()> {
+    Err(AppError::InvalidInput(
+        "window effects are only available on desktop".into(),
+    ))
+}
+
+#[cfg(mobile)]
+fn clear(_window: &WebviewWindow) -> AppResult<()> {
+    Ok(())
+}
+
+/// Set how the calling window's corners are drawn.
+///
+/// Fails with `INVALID_INPUT` unless `cornerStyles` is reported as supported.
+#[tauri::command]
+pub async fn set_window_corners(window: WebviewWindow, style: CornerStyle) -> AppResult<()> {
+    if !support().corner_styles {
+        return Err(AppError::InvalidInput(
+            "corner styles are not supported on this system".into(),
+        ));
+    }
+    platform::set_corners(&window, style)
+}
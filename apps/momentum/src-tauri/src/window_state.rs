@@ -0,0 +1,245 @@
+//! Persist window geometry and zoom across restarts, falling back sensibly when displays
+//! change.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{
+    AppHandle, Manager, Monitor, PhysicalPosition, PhysicalSize, Runtime, WebviewWindow, Window,
+    WindowEvent,
+};
+
+use crate::error::AppResult;
+
+const STATE_FILE_NAME: &str = "window-state.json";
+// Keep at least this much of the title bar on-screen so the window can be grabbed
+const MIN_VISIBLE_PX: i32 = 100;
+
+/// Saved geometry for one window, in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Geometry {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    maximized: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    geometry: Option<Geometry>,
+    monitor: Option<String>,
+    /// Webview zoom factor; 1.0 when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    zoom: Option<f64>,
+}
+
+/// Per-label window geometry registered in app state.
+pub struct WindowStateStore {
+    path: PathBuf,
+    entries: Mutex<BTreeMap<String, Entry>>,
+    // Labels whose current geometry is temporary and must not be recorded
+    held: Mutex<BTreeSet<String>>,
+}
+
+impl WindowStateStore {
+    /// Load saved geometry from `data_dir`; unreadable state starts fresh.
+    pub fn load(data_dir: &Path) -> Self {
+        let path = data_dir.join(STATE_FILE_NAME);
+        let entries = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            entries: Mutex::new(entries),
+            held: Mutex::default(),
+        }
+    }
+
+    /// File the geometry is persisted to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn save(&self) -> AppResult<()> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(&*entries)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// The zoom factor saved for a window label.
+    pub fn zoom(&self, label: &str) -> Option<f64> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.get(label).and_then(|entry| entry.zoom)
+    }
+
+    /// Remember a window's zoom factor and write it out straight away, since zooming
+    /// moves no window and so never triggers a geometry save.
+    pub fn set_zoom(&self, label: &str, zoom: Option<f64>) -> AppResult<()> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(label.to_string())
+            .or_default()
+            .zoom = zoom;
+        self.save()
+    }
+
+    /// Record a window's geometry and then stop tracking it, so a temporary layout is
+    /// never saved; [`Self::restore`] puts the recorded geometry back.
+    pub fn hold<R: Runtime>(&self, window: &Window<R>) {
+        self.record(window);
+        self.held
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(window.label().to_string());
+    }
+
+    /// Resume tracking a held window; returns whether it was held.
+    pub fn release(&self, label: &str) -> bool {
+        self.held
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(label)
+    }
+
+    fn record<R: Runtime>(&self, window: &Window<R>) {
+        if self
+            .held
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains(window.label())
+        {
+            return;
+        }
+        let Ok(maximized) = window.is_maximized() else {
+            return;
+        };
+        // Minimized windows report bogus positions on Windows
+        if window.is_minimized().unwrap_or(false) {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = entries.entry(window.label().to_string()).or_default();
+
+        // While maximized, keep the last normal geometry so un-maximizing restores it
+        match (maximized, entry.geometry.as_mut()) {
+            (true, Some(geometry)) => geometry.maximized = true,
+            (true, None) => {}
+            (false, _) => {
+                let (Ok(position), Ok(size)) = (window.outer_position(), window.inner_size())
+                else {
+                    return;
+                };
+                entry.geometry = Some(Geometry {
+                    x: position.x,
+                    y: position.y,
+                    width: size.width,
+                    height: size.height,
+                    maximized: false,
+                });
+            }
+        }
+        entry.monitor = window
+            .current_monitor()
+            .ok()
+            .flatten()
+            .and_then(|m| m.name().cloned());
+    }
+
+    /// Apply saved geometry and zoom to a window before it is shown.
+    pub fn restore<R: Runtime>(&self, window: &WebviewWindow<R>) {
+        let entry = {
+            let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            entries.get(window.label()).cloned()
+        };
+        if let Some(zoom) = entry.as_ref().and_then(|entry| entry.zoom) {
+            let _ = window.set_zoom(zoom);
+        }
+        let Some(Entry {
+            geometry: Some(geometry),
+            monitor,
+            ..
+        }) = entry
+        else {
+            return;
+        };
+
+        let monitors = window.available_monitors().unwrap_or_default();
+        let target = monitor
+            .as_deref()
+            .and_then(|name| {
+                monitors
+                    .iter()
+                    .find(|m| m.name().map(String::as_str) == Some(name))
+            })
+            .filter(|m| is_reachable(m, &geometry));
+
+        match target {
+            Some(_) => {
+                let _ = window.set_size(PhysicalSize::new(geometry.width, geometry.height));
+                let _ = window.set_position(PhysicalPosition::new(geometry.x, geometry.y));
+            }
+            // The saved display is gone or rearranged: keep the size if it fits and recenter
+            None => {
+                if let Some(primary) = window.primary_monitor().ok().flatten() {
+                    let bounds = primary.size();
+                    let width = geometry.width.min(bounds.width);
+                    let height = geometry.height.min(bounds.height);
+                    let _ = window.set_size(PhysicalSize::new(width, height));
+                }
+                let _ = window.center();
+            }
+        }
+
+        if geometry.maximized {
+            let _ = window.maximize();
+        }
+    }
+}
+
+fn is_reachable(monitor: &Monitor, geometry: &Geometry) -> bool {
+    let origin = monitor.position();
+    let size = monitor.size();
+    let right = origin.x + size.width as i32;
+    let bottom = origin.y + size.height as i32;
+    geometry.x + MIN_VISIBLE_PX <= right
+        && geometry.x + geometry.width as i32 - MIN_VISIBLE_PX >= origin.x
+        && geometry.y >= origin.y
+        && geometry.y + MIN_VISIBLE_PX <= bottom
+}
+
+/// Track geometry changes and flush to disk when a window closes.
+pub fn on_window_event<R: Runtime>(window: &Window<R>, event: &WindowEvent) {
+    let Some(store) = window.try_state::<WindowStateStore>() else {
+        return;
+    };
+    match event {
+        WindowEvent::Moved(_) | WindowEvent::Resized(_) => store.record(window),
+        WindowEvent::CloseRequested { .. } => {
+            store.record(window);
+            if let Err(err) = store.save() {
+                tracing::warn!(%err, "failed to save window state");
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Flush all tracked geometry, used on app exit.
+pub fn save_all<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(store) = app.try_state::<WindowStateStore>() {
+        if let Err(err) = store.save() {
+            tracing::warn!(%err, "failed to save window state");
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! Main-window helpers and detachable secondary windows.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{
+    AppHandle, Emitter, LogicalSize, Manager, State, WebviewUrl, WebviewWindow,
+    WebviewWindowBuilder, WindowEvent,
+};
+
+use crate::error::{AppError, AppResult};
+use crate::window_state::WindowStateStore;
+
+/// Label of the primary window declared in `tauri.conf.json`.
+pub const MAIN_WINDOW: &str = "main";
+/// Label of the read-only window `enter_presentation_mode` opens, matched by
+/// `capabilities/presentation.json`.
+pub const PRESENTATION_WINDOW: &str = "presentation";
+
+// Secondary labels are namespaced so capabilities can match them with `secondary-*`
+pub(crate) const SECONDARY_PREFIX: &str = "secondary-";
+const MAX_LABEL_LEN: usize = 64;
+#[cfg(all(desktop, not(target_os = "macos")))]
+const DOCK_MENU_ID: &str = "window:dock";
+
+const SECONDARY_MIN_SIZE: LogicalSize<f64> = LogicalSize::new(480.0, 360.0);
+
+/// Emitted to the main window with a [`DockWindow`] when a popped-out view is docked back.
+pub const DOCK_EVENT: &str = "window:dock";
+/// Emitted with the caller-facing label after a secondary window is destroyed.
+pub const WINDOW_CLOSED_EVENT: &str = "window:closed";
+
+/// Build the main window from its `tauri.conf.json` entry with custom chrome.
+///
+/// The window starts hidden so callers can restore geometry before showing it.
+pub fn create_main_window(app: &AppHandle) -> AppResult<WebviewWindow> {
+    let config = app
+        .config()
+        .app
+        .windows
+        .iter()
+        .find(|w| w.label == MAIN_WINDOW)
+        .ok_or_else(|| AppError::Internal("main window missing from config".into()))?;
+    let builder = WebviewWindowBuilder::from_config(app, config)?.visible(false);
+    let builder = crate::portable::webview(builder);
+    #[cfg(desktop)]
+    let builder = crate::titlebar::frameless(builder);
+    Ok(builder.build()?)
+}
+
+/// The minimum inner size a window was built with, from its config entry or the
+/// secondary-window default.
+pub fn min_size(app: &AppHandle, label: &str) -> Option<LogicalSize<f64>> {
+    if let Some(config) = app.config().app.windows.iter().find(|w| w.label == label) {
+        return config
+            .min_width
+            .zip(config.min_height)
+            .map(|(width, height)| LogicalSize::new(width, height));
+    }
+    label
+        .starts_with(SECONDARY_PREFIX)
+        .then_some(SECONDARY_MIN_SIZE)
+}
+
+/// Bring the main window to the foreground, restoring it if minimized or hidden.
+pub fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window(MAIN_WINDOW) {
+        #[cfg(desktop)]
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Presentation options for a secondary window; omitted fields use the defaults.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SecondaryWindowOptions {
+    pub title: String,
+    pub width: f64,
+    pub height: f64,
+    pub resizable: bool,
+    // Phones and tablets have no window stacking
+    #[cfg_attr(mobile, allow(dead_code))]
+    pub always_on_top: bool,
+}
+
+impl Default for SecondaryWindowOptions {
+    fn default() -> Self {
+        Self {
+            title: "Momentum".into(),
+            width: 1000.0,
+            height: 700.0,
+            resizable: true,
+            always_on_top: false,
+        }
+    }
+}
+
+/// Payload for `window:dock`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DockWindow {
+    pub label: String,
+    pub route: String,
+}
+
+/// Open secondary windows keyed by caller-facing label, with the route each one shows.
+#[derive(Default)]
+pub struct WindowRegistry {
+    windows: Mutex<BTreeMap<String, String>>,
+}
+
+impl WindowRegistry {
+    fn insert(&self, label: &str, route: &str) {
+        self.lock().insert(label.to_string(), route.to_string());
+    }
+
+    fn remove(&self, label: &str) -> Option<String> {
+        self.lock().remove(label)
+    }
+
+    fn route(&self, label: &str) -> Option<String> {
+        self.lock().get(label).cloned()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, String>> {
+        self.windows.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+pub(crate) fn window_label(label: &str) -> AppResult<String> {
+    let valid = !label.is_empty()
+        && label.len() <= MAX_LABEL_LEN
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(AppError::InvalidInput(format!(
+            "invalid window label: {label:?}"
+        )));
+    }
+    Ok(format!("{SECONDARY_PREFIX}{label}"))
+}
+
+// Only in-app router paths; anything else could point the webview off-origin
+fn validate_route(route: &str) -> AppResult<()> {
+    if !route.starts_with('/') || route.starts_with("//") || route.contains('\\') {
+        return Err(AppError::InvalidInput(format!(
+            "route must be an app path: {route:?}"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(all(desktop, not(target_os = "macos")))]
+fn window_menu(app: &AppHandle) -> AppResult<tauri::menu::Menu<tauri::Wry>> {
+    use tauri::menu::{Menu, MenuItem, PredefinedMenuItem, Submenu};
+
+    let window = Submenu::with_items(
+        app,
+        "Window",
+        true,
+        &[
+            &MenuItem::with_id(app, DOCK_MENU_ID, "Dock to Main Window", true, None::<&str>)?,
+            &PredefinedMenuItem::separator(app)?,
+            &PredefinedMenuItem::minimize(app, None)?,
+            &PredefinedMenuItem::close_window(app, None)?,
+        ],
+    )?;
+    Ok(Menu::with_items(app, &[&window])?)
+}
+
+#[cfg(all(desktop, not(target_os = "macos")))]
+fn dock(app: &AppHandle, label: &str) {
+    let Some(route) = app.state::<WindowRegistry>().route(label) else {
+        return;
+    };
+    let payload = DockWindow {
+        label: label.to_string(),
+        route,
+    };
+    let _ = app.emit_to(MAIN_WINDOW, DOCK_EVENT, payload);
+    show_main_window(app);
+    if let Ok(full) = window_label(label) {
+        if let Some(window) = app.get_webview_window(&full) {
+            let _ = window.close();
+        }
+    }
+}
+
+/// Pop a route out into its own window, or focus the window already using `label`.
+#[tauri::command]
+pub async fn open_secondary_window(
+    app: AppHandle,
+    registry: State<'_, WindowRegistry>,
+    label: String,
+    route: String,
+    options: Option<SecondaryWindowOptions>,
+) -> AppResult<()> {
+    let full_label = window_label(&label)?;
+    validate_route(&route)?;
+
+    if let Some(existing) = app.get_webview_window(&full_label) {
+        #[cfg(desktop)]
+        existing.unminimize()?;
+        existing.set_focus()?;
+        return Ok(());
+    }
+
+    let options = options.unwrap_or_default();
+    let builder =
+        WebviewWindowBuilder::new(&app, &full_label, WebviewUrl::App(route.clone().into()))
+            .title(&options.title)
+            .inner_size(options.width, options.height)
+            .min_inner_size(SECONDARY_MIN_SIZE.width, SECONDARY_MIN_SIZE.height)
+            .resizable(options.resizable)
+            .visible(false);
+    let builder = crate::portable::webview(builder);
+    #[cfg(desktop)]
+    let builder = crate::titlebar::frameless(builder.always_on_top(options.always_on_top));
+
+    // macOS menus are app-wide, so secondary windows share the main menu bar there
+    #[cfg(all(desktop, not(target_os = "macos")))]
+    let builder = {
+        let handle = app.clone();
+        let dock_label = label.clone();
+        builder
+            .menu(window_menu(&app)?)
+            .on_menu_event(move |_, event| {
+                if event.id() == DOCK_MENU_ID {
+                    dock(&handle, &dock_label);
+                }
+            })
+    };
+
+    let window = builder.build()?;
+    if let Some(store) = app.try_state::<WindowStateStore>() {
+        store.restore(&window);
+    }
+    window.show()?;
+    window.set_focus()?;
+
+    registry.insert(&label, &route);
+    let handle = app.clone();
+    window.on_window_event(move |event| {
+        if let WindowEvent::Destroyed = event {
+            handle.state::<WindowRegistry>().remove(&label);
+            let _ = handle.emit(WINDOW_CLOSED_EVENT, &label);
+        }
+    });
+    Ok(())
+}
+
+/// Close a secondary window opened with `open_secondary_window`.
+#[tauri::command]
+pub async fn close_window(
+    app: AppHandle,
+    registry: State<'_, WindowRegistry>,
+    label: String,
+) -> AppResult<()> {
+    let full_label = window_label(&label)?;
+    if registry.route(&label).is_none() {
+        return Err(AppError::not_found("window", label));
+    }
+    match app.get_webview_window(&full_label) {
+        Some(window) => Ok(window.close()?),
+        // Already gone; drop the stale entry
+        None => {
+            registry.remove(&label);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! Named workspaces, each with its own data dir, database, and keychain entries.
+//!
+//! The registry lives in `workspaces.json` under the platform app data dir and is read once
+//! at startup, before anything opens the data dir, so one process only ever sees one
+//! workspace. The `default` workspace is the data an install had before workspaces, kept
+//! where `dataDir` in the config puts it; every other workspace gets
+//! `workspaces/<id>` unless created with its own folder. Switching saves the choice and
+//! relaunches the app through the usual quit pipeline.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, State};
+
+use crate::config::{self, Config};
+use crate::db::now_ms;
+use crate::error::{AppError, AppResult};
+use crate::reports::file_stem;
+use crate::{portable, secrets, shutdown};
+
+const REGISTRY_FILE_NAME: &str = "workspaces.json";
+const WORKSPACES_DIR_NAME: &str = "workspaces";
+/// Id of the workspace every install starts in.
+pub const DEFAULT_WORKSPACE: &str = "default";
+/// Emitted with the target [`Workspace`] just before the app relaunches into it.
+pub const SWITCHING_EVENT: &str = "workspaces:switching";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Workspace {
+    pub id: String,
+    pub name: String,
+    /// Chosen folder; `None` uses the standard location.
+    pub data_dir: Option<PathBuf>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Registry {
+    active: Option<String>,
+    workspaces: Vec<Workspace>,
+}
+
+impl Registry {
+    fn get(&self, id: &str) -> Option<&Workspace> {
+        self.workspaces.iter().find(|w| w.id == id)
+    }
+}
+
+/// Every workspace and which one this process runs.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceList {
+    pub current: String,
+    pub workspaces: Vec<Workspace>,
+}
+
+/// The registry and the workspace this process opened, registered in app state.
+pub struct Workspaces {
+    path: PathBuf,
+    root: PathBuf,
+    current: Workspace,
+    registry: Mutex<Registry>,
+}
+
+/// Read the registry and scope secrets to the active workspace.
+///
+/// Must run before the data dir is resolved or any secret is read.
+pub fn init(app: &AppHandle) -> AppResult<Workspaces> {
+    let root = portable::app_data_dir(app)?;
+    let path = root.join(REGISTRY_FILE_NAME);
+    let mut registry: Registry = match fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|err| {
+            tracing::warn!(%err, "workspace registry unreadable; starting in the default");
+            Registry::default()
+        }),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Registry::default(),
+        Err(err) => return Err(err.into()),
+    };
+    if registry.get(DEFAULT_WORKSPACE).is_none() {
+        registry.workspaces.insert(
+            0,
+            Workspace {
+                id: DEFAULT_WORKSPACE.into(),
+                name: "Default".into(),
+                data_dir: None,
+                created_at: now_ms(),
+            },
+        );
+    }
+    let current = registry
+        .active
+        .as_deref()
+        .and_then(|id| registry.get(id))
+        .or_else(|| registry.get(DEFAULT_WORKSPACE))
+        .cloned()
+        .ok_or_else(|| AppError::Internal("the default workspace is missing".into()))?;
+    secrets::use_workspace((current.id != DEFAULT_WORKSPACE).then_some(current.id.as_str()));
+    Ok(Workspaces {
+        path,
+        root,
+        current,
+        registry: Mutex::new(registry),
+    })
+}
+
+/// Whether `name`, directly under the platform app data dir, belongs to every workspace
+/// rather than to the default one whose data dir it doubles as.
+pub(crate) fn is_install_wide(name: &str) -> bool {
+    name == REGISTRY_FILE_NAME || name == WORKSPACES_DIR_NAME
+}
+
+impl Workspaces {
+    /// Data dir of the running workspace.
+    pub fn data_dir(&self, config: &Config) -> PathBuf {
+        match (&self.current.data_dir, self.current.id.as_str()) {
+            (Some(dir), _) => dir.clone(),
+            (None, DEFAULT_WORKSPACE) => config.data_dir.clone().unwrap_or(self.root.clone()),
+            (None, id) => self.root.join(WORKSPACES_DIR_NAME).join(id),
+        }
+    }
+
+    /// Point the running workspace at `dir` from the next launch.
+    pub(crate) fn relocate(&self, app: &AppHandle, dir: PathBuf) -> AppResult<()> {
+        if self.current.id == DEFAULT_WORKSPACE {
+            config::update(app, |config| config.data_dir = Some(dir))?;
+            return Ok(());
+        }
+        let mut registry = self.registry.lock().unwrap_or_else(|e| e.into_inner());
+        let mut updated = registry.clone();
+        if let Some(workspace) = updated
+            .workspaces
+            .iter_mut()
+            .find(|w| w.id == self.current.id)
+        {
+            workspace.data_dir = Some(dir);
+        }
+        self.save(&updated)?;
+        *registry = updated;
+        Ok(())
+    }
+
+    fn save(&self, registry: &Registry) -> AppResult<()> {
+        fs::create_dir_all(&self.root)?;
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(registry)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+#[tauri::command]
+pub async fn list_workspaces(workspaces: State<'_, Workspaces>) -> AppResult<WorkspaceList> {
+    let registry = workspaces
+        .registry
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    Ok(WorkspaceList {
+        current: workspaces.current.id.clone(),
+        workspaces: registry.workspaces.clone(),
+    })
+}
+
+/// Add a workspace; `data_dir` places its data somewhere other than the standard location.
+#[tauri::command]
+pub async fn create_workspace(
+    workspaces: State<'_, Workspaces>,
+    name: String,
+    data_dir: Option<PathBuf>,
+) -> AppResult<Workspace> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput("the workspace needs a name".into()));
+    }
+    if data_dir.as_ref().is_some_and(|dir| !dir.is_absolute()) {
+        return Err(AppError::InvalidInput(
+            "the workspace folder must be an absolute path".into(),
+        ));
+    }
+    let mut registry = workspaces
+        .registry
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if registry
+        .workspaces
+        .iter()
+        .any(|w| w.name.eq_ignore_ascii_case(&name))
+    {
+        return Err(AppError::InvalidInput(format!(
+            "a workspace named {name} already exists"
+        )));
+    }
+    // The id names a folder and a keychain service, so it stays plain and never reused
+    let stem = file_stem(&name);
+    let mut id = stem.clone();
+    let mut n = 2;
+    while id == DEFAULT_WORKSPACE || registry.get(&id).is_some() {
+        id = format!("{stem}-{n}");
+        n += 1;
+    }
+    let workspace = Workspace {
+        id,
+        name,
+        data_dir,
+        created_at: now_ms(),
+    };
+    let mut updated = registry.clone();
+    updated.workspaces.push(workspace.clone());
+    workspaces.save(&updated)?;
+    *registry = updated;
+    tracing::info!(workspace = %workspace.id, "workspace created");
+    Ok(workspace)
+}
+
+/// Make `id` the active workspace and relaunch into it.
+///
+/// A window with unsaved changes can hold the relaunch; calling again once it is saved
+/// finishes the switch, and the choice applies at the next launch regardless.
+#[tauri::command]
+pub async fn switch_workspace(
+    app: AppHandle,
+    workspaces: State<'_, Workspaces>,
+    id: String,
+    force: Option<bool>,
+) -> AppResult<()> {
+    let target = {
+        let mut registry = workspaces
+            .registry
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let target = registry
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| AppError::not_found("workspace", id.clone()))?;
+        if registry.active.as_deref() != Some(id.as_str()) {
+            let mut updated = registry.clone();
+            updated.active = Some(id.clone());
+            workspaces.save(&updated)?;
+            *registry = updated;
+        }
+        target
+    };
+    if target.id == workspaces.current.id {
+        return Ok(());
+    }
+    tracing::info!(from = %workspaces.current.id, to = %target.id, "switching workspace");
+    let _ = app.emit(SWITCHING_EVENT, &target);
+    shutdown::restart(&app, force.unwrap_or(false));
+    Ok(())
+}
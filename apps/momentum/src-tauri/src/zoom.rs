@@ -0,0 +1,85 @@
+//! Webview zoom per window, kept with the window's saved geometry.
+//!
+//! The View menu's Zoom In, Zoom Out, and Actual Size items carry the Ctrl+= / Ctrl+- /
+//! Ctrl+0 accelerators and step through [`LEVELS`] here; `set_zoom` accepts any factor in
+//! range. Every change is saved at once and announced to the window with `zoom:changed`.
+
+use tauri::{AppHandle, Emitter, Manager, WebviewWindow};
+
+use crate::error::{AppError, AppResult};
+use crate::window_state::WindowStateStore;
+
+/// Emitted to a window with its new zoom factor.
+pub const ZOOM_CHANGED_EVENT: &str = "zoom:changed";
+
+/// Steps taken by the zoom accelerators, as in browsers.
+pub const LEVELS: [f64; 13] = [
+    0.5, 0.67, 0.75, 0.8, 0.9, 1.0, 1.1, 1.25, 1.5, 1.75, 2.0, 2.5, 3.0,
+];
+const DEFAULT: f64 = 1.0;
+
+/// Which way a zoom accelerator goes.
+#[cfg_attr(mobile, allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZoomStep {
+    In,
+    Out,
+    Reset,
+}
+
+fn current(app: &AppHandle, label: &str) -> f64 {
+    app.state::<WindowStateStore>()
+        .zoom(label)
+        .unwrap_or(DEFAULT)
+}
+
+fn apply(window: &WebviewWindow, factor: f64) -> AppResult<f64> {
+    window.set_zoom(factor)?;
+    let saved = (factor != DEFAULT).then_some(factor);
+    window
+        .state::<WindowStateStore>()
+        .set_zoom(window.label(), saved)?;
+    let _ = window.emit_to(window.label(), ZOOM_CHANGED_EVENT, factor);
+    Ok(factor)
+}
+
+/// Move a window one level in or out from its current zoom, or back to 100%.
+#[cfg_attr(mobile, allow(dead_code))]
+pub fn step(window: &WebviewWindow, step: ZoomStep) -> AppResult<f64> {
+    let now = current(window.app_handle(), window.label());
+    // A factor set between levels steps to the nearest one beyond it
+    let next = match step {
+        ZoomStep::In => LEVELS
+            .iter()
+            .copied()
+            .find(|&level| level > now + f64::EPSILON),
+        ZoomStep::Out => LEVELS
+            .iter()
+            .rev()
+            .copied()
+            .find(|&level| level < now - f64::EPSILON),
+        ZoomStep::Reset => Some(DEFAULT),
+    };
+    match next {
+        Some(factor) => apply(window, factor),
+        None => Ok(now),
+    }
+}
+
+/// Set the calling window's zoom factor, between 50% and 300%; returns the factor applied.
+#[tauri::command]
+pub async fn set_zoom(window: WebviewWindow, factor: f64) -> AppResult<f64> {
+    if !factor.is_finite() {
+        return Err(AppError::InvalidInput(format!(
+            "invalid zoom factor {factor}"
+        )));
+    }
+    let factor = factor.clamp(LEVELS[0], LEVELS[LEVELS.len() - 1]);
+    apply(&window, factor)
+}
+
+/// The calling window's zoom factor.
+#[tauri::command]
+pub async fn get_zoom(window: WebviewWindow) -> AppResult<f64> {
+    Ok(current(window.app_handle(), window.label()))
+}
@@ -1,4 +1,5 @@
 import React from "react";
+import { invoke } from "@tauri-apps/api/core";
 import ReactDOM from "react-dom/client";
 import { ConvexReactClient } from "convex/react";
 import { ConvexBetterAuthProvider } from "@convex-dev/better-auth/react";
@@ -32,3 +33,8 @@ ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
     </ConvexBetterAuthProvider>
   </React.StrictMode>
 );
+
+// Swap the main window in for the splash once the first frame has painted
+requestAnimationFrame(() => {
+  invoke("frontend_ready").catch(() => {});
+});